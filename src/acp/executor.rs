@@ -49,7 +49,7 @@ use crate::agent::Agent;
 use crate::commands::build_agent_environment;
 use crate::config::Config;
 use crate::error::Result;
-use crate::providers::{create_provider, Provider};
+use crate::providers::{create_provider, wrap_with_quirks, Provider};
 
 /// ACP executor outcome.
 ///
@@ -440,15 +440,17 @@ impl AcpExecutor {
         // McpToolExecutor instances (registered in tools) can call back to it.
         let _mcp_manager = env.mcp_manager;
 
-        let provider_box =
-            create_provider(&self.config.provider.provider_type, &self.config.provider)?;
+        let provider_box = wrap_with_quirks(
+            create_provider(&self.config.provider.provider_type, &self.config.provider)?,
+            &self.config.provider.quirks,
+        );
         let provider: Arc<dyn Provider> = Arc::from(provider_box);
 
         let subagent_tool = crate::tools::SubagentTool::new_with_config(
             Arc::clone(&provider),
             &self.config.provider,
             self.config.agent.clone(),
-            tools.clone(),
+            tools.fork(),
             0,
         )?;
         tools.register("subagent", Arc::new(subagent_tool));
@@ -41,6 +41,7 @@ use crate::acp::{
 };
 use crate::config::{AcpCompatibilityMode, AcpDefaultRunMode, Config};
 use crate::error::{Result, XzatomaError};
+use crate::paths::Paths;
 use crate::storage::{
     PublicStoredAcpAwaitState, PublicStoredAcpCancellation, PublicStoredAcpRunEvent, SqliteStorage,
 };
@@ -552,7 +553,9 @@ impl AcpRuntime {
     /// let _ = runtime.run_count();
     /// ```
     pub fn new(config: Config) -> Self {
-        let storage = SqliteStorage::new().ok();
+        let storage = Paths::from_config(&config)
+            .and_then(|paths| SqliteStorage::new(&paths))
+            .ok();
         let runtime = Self {
             config,
             state: Arc::new(Mutex::new(AcpRuntimeState::default())),
@@ -56,11 +56,13 @@ use crate::error::{Result, XzatomaError};
 use crate::mcp::manager::McpClientManager;
 use crate::mcp::server::{McpServerConfig, McpServerTransportConfig};
 use crate::mcp::tool_bridge::register_mcp_tools;
+use crate::paths::Paths;
 use crate::prompts;
 use crate::providers::{
-    create_provider_with_override, Message, ModelCapability, ModelInfo as XzatomaModelInfo,
-    MultimodalPromptInput, Provider,
+    create_provider_with_override, wrap_with_quirks, Message, ModelCapability,
+    ModelInfo as XzatomaModelInfo, MultimodalPromptInput, Provider,
 };
+use crate::read_only::ReadOnlyPolicy;
 use crate::storage::{PublicStoredAcpStdioSession, SqliteStorage};
 use crate::tools::ide_tools::register_ide_tools;
 use crate::tools::terminal::{CommandValidator, TerminalTool};
@@ -250,6 +252,8 @@ pub struct ActiveSessionState {
     runtime_state: SessionRuntimeState,
     /// IDE tool bridge, present when the Zed client advertised IDE capabilities.
     ide_bridge: Option<Arc<IdeBridge>>,
+    /// Mode prompt style selected for the session's model.
+    prompt_style: prompts::PromptStyle,
 }
 
 impl ActiveSessionState {
@@ -513,11 +517,14 @@ impl AcpStdioServerState {
             }
         }
 
-        let provider_box = create_provider_with_override(
-            &self.config.provider,
-            self.options.provider.as_deref(),
-            self.options.model.as_deref(),
-        )?;
+        let provider_box = wrap_with_quirks(
+            create_provider_with_override(
+                &self.config.provider,
+                self.options.provider.as_deref(),
+                self.options.model.as_deref(),
+            )?,
+            &self.config.provider.quirks,
+        );
         let provider: Arc<dyn Provider> = Arc::from(provider_box);
 
         // Create the session ID early so it can be used by the IDE bridge and
@@ -559,12 +566,16 @@ impl AcpStdioServerState {
                 Arc::clone(&provider),
                 &self.config.provider,
                 self.config.agent.clone(),
-                tools.clone(),
+                tools.fork(),
                 0,
             )?;
             tools.register("subagent", Arc::new(subagent_tool));
         }
 
+        let prompt_style =
+            prompts::detect_prompt_style(&self.config.provider, &provider_name, provider.as_ref())
+                .await;
+
         let mut agent = if let Some(conversation) = resumed_conversation {
             XzatomaAgent::with_conversation_and_shared_provider(
                 Arc::clone(&provider),
@@ -576,8 +587,11 @@ impl AcpStdioServerState {
             XzatomaAgent::new_from_shared_provider(provider, tools, self.config.agent.clone())?
         };
 
-        let mut transient_system_messages =
-            vec![prompts::build_system_prompt(env.chat_mode, env.safety_mode)];
+        let mut transient_system_messages = vec![prompts::build_system_prompt(
+            env.chat_mode,
+            env.safety_mode,
+            prompt_style,
+        )];
         if let Some(disclosure) = env.skill_disclosure {
             transient_system_messages.push(disclosure);
         }
@@ -639,6 +653,7 @@ impl AcpStdioServerState {
             current_mode_id,
             runtime_state,
             ide_bridge,
+            prompt_style,
         };
 
         self.sessions.insert(active_session).await;
@@ -689,6 +704,9 @@ impl AcpStdioServerState {
 
         let mode_id = request.mode_id.0.as_ref().to_string();
         let effect = mode_runtime_effect(&mode_id)?;
+        if let Ok(chat_mode) = crate::chat_mode::ChatMode::parse_str(&effect.chat_mode_str) {
+            ReadOnlyPolicy::from_config(&self.config).check_mode(chat_mode)?;
+        }
 
         let mut session_lock = session.lock().await;
         session_lock.current_mode_id = mode_id.clone();
@@ -703,7 +721,11 @@ impl AcpStdioServerState {
             let chat_mode = ChatMode::parse_str(&effect.chat_mode_str).unwrap_or(ChatMode::Write);
             let safety_mode =
                 SafetyMode::parse_str(&effect.safety_mode_str).unwrap_or(SafetyMode::AlwaysConfirm);
-            let system_prompt = crate::prompts::build_system_prompt(chat_mode, safety_mode);
+            let system_prompt = crate::prompts::build_system_prompt(
+                chat_mode,
+                safety_mode,
+                session_lock.prompt_style,
+            );
             let agent = session_lock.xzatoma_agent.clone();
             drop(session_lock);
             let mut agent_lock = agent.lock().await;
@@ -1430,6 +1452,7 @@ fn convert_acp_mcp_server(server: &acp::McpServer) -> Result<McpServerConfig> {
                 prompts_enabled: false,
                 sampling_enabled: false,
                 elicitation_enabled: false,
+                read_safe_tools: vec![],
             };
             cfg.validate()?;
             Ok(cfg)
@@ -1462,6 +1485,7 @@ fn convert_acp_mcp_server(server: &acp::McpServer) -> Result<McpServerConfig> {
                 prompts_enabled: false,
                 sampling_enabled: false,
                 elicitation_enabled: false,
+                read_safe_tools: vec![],
             };
             cfg.validate()?;
             Ok(cfg)
@@ -1494,6 +1518,7 @@ fn convert_acp_mcp_server(server: &acp::McpServer) -> Result<McpServerConfig> {
                 prompts_enabled: false,
                 sampling_enabled: false,
                 elicitation_enabled: false,
+                read_safe_tools: vec![],
             };
             cfg.validate()?;
             Ok(cfg)
@@ -1535,7 +1560,7 @@ fn open_stdio_storage(config: &Config) -> Option<SqliteStorage> {
         return None;
     }
 
-    match SqliteStorage::new() {
+    match Paths::from_config(config).and_then(|paths| SqliteStorage::new(&paths)) {
         Ok(storage) => {
             prune_old_stdio_sessions(&storage, config);
             Some(storage)
@@ -2041,7 +2066,8 @@ async fn execute_queued_prompt(
             }
         }
         Err(crate::error::XzatomaError::Cancelled) => acp::StopReason::Cancelled,
-        Err(crate::error::XzatomaError::MaxIterationsExceeded { .. }) => {
+        Err(crate::error::XzatomaError::MaxIterationsExceeded { .. })
+        | Err(crate::error::XzatomaError::ToolLoopDetected { .. }) => {
             acp::StopReason::MaxTurnRequests
         }
         Err(error) => {
@@ -2141,9 +2167,8 @@ fn acp_internal_error(error: impl ToString) -> acp_sdk::Error {
 fn map_error_to_stop_reason(error: &crate::error::XzatomaError) -> acp::StopReason {
     match error {
         crate::error::XzatomaError::Cancelled => acp::StopReason::Cancelled,
-        crate::error::XzatomaError::MaxIterationsExceeded { .. } => {
-            acp::StopReason::MaxTurnRequests
-        }
+        crate::error::XzatomaError::MaxIterationsExceeded { .. }
+        | crate::error::XzatomaError::ToolLoopDetected { .. } => acp::StopReason::MaxTurnRequests,
         _ => acp::StopReason::EndTurn,
     }
 }
@@ -2771,6 +2796,7 @@ mod tests {
                 supports_token_counts: false,
                 supports_streaming: true,
                 supports_vision: true,
+                ..Default::default()
             }
         }
     }
@@ -0,0 +1,330 @@
+//! Review of multi-file change sets
+//!
+//! When one response asks for several file changes in a row, the agent
+//! previews them as a change set (see [`crate::tools::change_set`]) and asks
+//! its [`ChangeReview`] which of them to apply before running any. Approved
+//! calls then run in order through the normal tool path. Rejected calls are
+//! not run; the model receives [`rejected_change_result`] for each of them so
+//! it knows which files were left unchanged.
+//!
+//! Only change sets that touch more than one file are reviewed. A single
+//! file change runs as before.
+
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+
+use crate::tools::change_set::{ChangeKind, FileChange};
+use crate::tools::ToolResult;
+
+/// Metadata key recording how review handled a call
+pub const CHANGE_REVIEW_METADATA: &str = "change_review";
+
+/// Which changes of a change set to apply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReviewDecision {
+    /// Apply every change
+    ApproveAll,
+    /// Apply none of the changes
+    RejectAll,
+    /// Apply the changes at these zero-based indices
+    Apply(BTreeSet<usize>),
+}
+
+impl ReviewDecision {
+    /// Returns true when the change at `index` should be applied
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::agent::change_review::ReviewDecision;
+    ///
+    /// let decision = ReviewDecision::Apply([0, 2].into_iter().collect());
+    /// assert!(decision.approves(2));
+    /// assert!(!decision.approves(1));
+    /// ```
+    pub fn approves(&self, index: usize) -> bool {
+        match self {
+            ReviewDecision::ApproveAll => true,
+            ReviewDecision::RejectAll => false,
+            ReviewDecision::Apply(indices) => indices.contains(&index),
+        }
+    }
+}
+
+/// Decides which changes of a change set to apply
+pub trait ChangeReview: Send + Sync {
+    /// Returns the changes to apply
+    ///
+    /// # Arguments
+    ///
+    /// * `changes` - The previewed changes, in the order they would run
+    fn review(&self, changes: &[FileChange]) -> ReviewDecision;
+}
+
+/// Reviews change sets on the terminal
+///
+/// Prints one line per change with its lines added and removed, then reads
+/// a command from stdin:
+///
+/// - `a` applies every change;
+/// - `r` rejects every change;
+/// - `p 1,3` applies only the listed changes;
+/// - `d 2` shows the diff of one change, and `d` alone shows all of them.
+///
+/// An empty answer or end of input rejects the change set.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TerminalChangeReview;
+
+impl TerminalChangeReview {
+    /// Runs the review against the given input and output
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    /// use xzatoma::agent::change_review::{ReviewDecision, TerminalChangeReview};
+    /// use xzatoma::tools::change_set::{ChangeKind, FileChange};
+    ///
+    /// let changes = vec![
+    ///     FileChange::new("a.txt", ChangeKind::Create, PathBuf::from("/p/a.txt")),
+    ///     FileChange::new("b.txt", ChangeKind::Create, PathBuf::from("/p/b.txt")),
+    /// ];
+    /// let mut input = "p 2\n".as_bytes();
+    /// let mut output = Vec::new();
+    /// let decision = TerminalChangeReview.review_with(&changes, &mut input, &mut output);
+    /// assert_eq!(decision, ReviewDecision::Apply([1].into_iter().collect()));
+    /// ```
+    pub fn review_with(
+        &self,
+        changes: &[FileChange],
+        input: &mut dyn BufRead,
+        output: &mut dyn Write,
+    ) -> ReviewDecision {
+        let _ = output.write_all(format_summary(changes).as_bytes());
+        loop {
+            let _ = write!(
+                output,
+                "[a]pply all, [r]eject all, [p]ick (e.g. p 1,3), [d N] show diff: "
+            );
+            let _ = output.flush();
+
+            let mut line = String::new();
+            match input.read_line(&mut line) {
+                Ok(0) | Err(_) => return ReviewDecision::RejectAll,
+                Ok(_) => {}
+            }
+            let answer = line.trim().to_lowercase();
+            let (command, rest) = answer.split_once(' ').unwrap_or((answer.as_str(), ""));
+            match command {
+                "a" | "apply" | "y" | "yes" => return ReviewDecision::ApproveAll,
+                "" | "r" | "reject" | "n" | "no" => return ReviewDecision::RejectAll,
+                "p" | "pick" => match parse_selection(rest, changes.len()) {
+                    Some(indices) => return ReviewDecision::Apply(indices),
+                    None => {
+                        let _ = writeln!(
+                            output,
+                            "Pick changes by number, between 1 and {}.",
+                            changes.len()
+                        );
+                    }
+                },
+                "d" | "diff" => {
+                    if rest.is_empty() {
+                        for (index, change) in changes.iter().enumerate() {
+                            let _ = write!(output, "{}", format_diff(index, change));
+                        }
+                    } else {
+                        match parse_selection(rest, changes.len()) {
+                            Some(indices) => {
+                                for index in indices {
+                                    let _ =
+                                        write!(output, "{}", format_diff(index, &changes[index]));
+                                }
+                            }
+                            None => {
+                                let _ = writeln!(
+                                    output,
+                                    "No change numbered '{}'. Changes are numbered 1 to {}.",
+                                    rest,
+                                    changes.len()
+                                );
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    let _ = writeln!(output, "Unknown answer '{}'.", answer);
+                }
+            }
+        }
+    }
+}
+
+impl ChangeReview for TerminalChangeReview {
+    fn review(&self, changes: &[FileChange]) -> ReviewDecision {
+        let stdin = std::io::stdin();
+        let mut input = stdin.lock();
+        self.review_with(changes, &mut input, &mut std::io::stderr())
+    }
+}
+
+/// Formats the change set header and one line per change
+///
+/// # Examples
+///
+/// ```
+/// use std::path::PathBuf;
+/// use xzatoma::agent::change_review::format_summary;
+/// use xzatoma::tools::change_set::{ChangeKind, FileChange};
+///
+/// let change = FileChange::new("a.txt", ChangeKind::Create, PathBuf::from("/p/a.txt"))
+///     .with_contents(None, Some("hello\n".to_string()));
+/// let summary = format_summary(&[change]);
+/// assert!(summary.contains("1 change (+1 -0)"));
+/// assert!(summary.contains("1. A a.txt"));
+/// ```
+pub fn format_summary(changes: &[FileChange]) -> String {
+    let counts: Vec<(usize, usize)> = changes.iter().map(FileChange::line_counts).collect();
+    let added: usize = counts.iter().map(|(added, _)| added).sum();
+    let removed: usize = counts.iter().map(|(_, removed)| removed).sum();
+    let width = changes.iter().map(|c| c.path.len()).max().unwrap_or(0);
+
+    let mut summary = format!(
+        "\nThe agent wants to apply {} change{} (+{} -{}):\n",
+        changes.len(),
+        if changes.len() == 1 { "" } else { "s" },
+        added,
+        removed
+    );
+    for (index, (change, (added, removed))) in changes.iter().zip(counts).enumerate() {
+        let mut line = format!(
+            "  {}. {} {:<width$}  +{} -{}",
+            index + 1,
+            kind_symbol(&change.kind),
+            change.path,
+            added,
+            removed,
+            width = width
+        );
+        if let ChangeKind::Copy { .. } | ChangeKind::Move { .. } = change.kind {
+            line.push_str(&format!("  ({})", change.kind));
+        }
+        if let Some(note) = &change.note {
+            line.push_str(&format!("  ({})", note));
+        }
+        summary.push_str(line.trim_end());
+        summary.push('\n');
+    }
+    summary
+}
+
+/// Builds the result the model receives for a rejected change
+pub fn rejected_change_result(change: &FileChange) -> ToolResult {
+    ToolResult::error(format!(
+        "Not applied: the user rejected this change ({} {}) during review. \
+         {} was left unchanged. Do not retry it unless the user asks.",
+        change.kind, change.path, change.path
+    ))
+    .with_metadata(CHANGE_REVIEW_METADATA.to_string(), "rejected".to_string())
+}
+
+/// One-letter marker for a change, in the style of `git status --short`
+fn kind_symbol(kind: &ChangeKind) -> char {
+    match kind {
+        ChangeKind::Create => 'A',
+        ChangeKind::Modify => 'M',
+        ChangeKind::Delete => 'D',
+        ChangeKind::Copy { .. } => 'C',
+        ChangeKind::Move { .. } => 'R',
+        ChangeKind::CreateDirectory => '+',
+    }
+}
+
+fn format_diff(index: usize, change: &FileChange) -> String {
+    let body = match &change.note {
+        Some(note) if change.before.is_none() && change.after.is_none() => {
+            format!("({})\n", note)
+        }
+        _ => change.diff(),
+    };
+    format!(
+        "\n--- {}. {} {}\n{}",
+        index + 1,
+        change.kind,
+        change.path,
+        body
+    )
+}
+
+/// Parses one-based change numbers separated by commas or spaces
+fn parse_selection(text: &str, count: usize) -> Option<BTreeSet<usize>> {
+    let indices = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(|part| match part.parse::<usize>() {
+            Ok(number) if (1..=count).contains(&number) => Some(number - 1),
+            _ => None,
+        })
+        .collect::<Option<BTreeSet<usize>>>()?;
+    if indices.is_empty() {
+        None
+    } else {
+        Some(indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn changes() -> Vec<FileChange> {
+        vec![
+            FileChange::new("src/lib.rs", ChangeKind::Modify, PathBuf::from("/p/lib.rs"))
+                .with_contents(Some("a\nb\n".to_string()), Some("a\nc\n".to_string())),
+            FileChange::new("notes.md", ChangeKind::Create, PathBuf::from("/p/notes.md"))
+                .with_contents(None, Some("new\n".to_string())),
+            FileChange::new("old", ChangeKind::Delete, PathBuf::from("/p/old"))
+                .with_note("directory and all of its contents"),
+        ]
+    }
+
+    fn review(answers: &str) -> (ReviewDecision, String) {
+        let mut input = answers.as_bytes();
+        let mut output = Vec::new();
+        let decision = TerminalChangeReview.review_with(&changes(), &mut input, &mut output);
+        (decision, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_terminal_review_answers() {
+        assert_eq!(review("a\n").0, ReviewDecision::ApproveAll);
+        assert_eq!(review("r\n").0, ReviewDecision::RejectAll);
+        assert_eq!(review("").0, ReviewDecision::RejectAll);
+        assert_eq!(
+            review("p 1, 3\n").0,
+            ReviewDecision::Apply([0, 2].into_iter().collect())
+        );
+    }
+
+    #[test]
+    fn test_terminal_review_shows_diff_and_reprompts() {
+        let (decision, output) = review("d 1\np 4\np\np 2\n");
+
+        assert_eq!(decision, ReviewDecision::Apply([1].into_iter().collect()));
+        assert!(output.contains("3 changes (+2 -1)"));
+        assert!(output.contains("- b"));
+        assert!(output.contains("+ c"));
+        assert_eq!(output.matches("between 1 and 3").count(), 2);
+    }
+
+    #[test]
+    fn test_rejected_change_result_names_the_file() {
+        let result = rejected_change_result(&changes()[1]);
+
+        assert!(!result.success);
+        assert_eq!(result.metadata[CHANGE_REVIEW_METADATA], "rejected");
+        assert!(result.to_message().contains("create notes.md"));
+        assert!(result.to_message().contains("notes.md was left unchanged"));
+    }
+}
@@ -3,9 +3,15 @@
 //! This module implements conversation history management with automatic
 //! token counting and intelligent pruning to stay within context limits.
 
-use crate::error::Result;
+use crate::agent::history_hygiene::{self, HygieneReport, Stub, StubReason};
+use crate::agent::pruning::{
+    PruneAction, PruneReason, PruneReport, PrunedMessage, PRUNING_LOG_LIMIT,
+};
+use crate::error::{Result, XzatomaError};
 use crate::providers::{Message, TokenUsage};
 
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
 /// Information about the current context window status
@@ -125,6 +131,120 @@ impl ContextStatus {
     }
 }
 
+/// Part of the context that a message counts toward in a [`ContextBreakdown`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextCategory {
+    /// System prompt, summaries, and other stored system messages
+    SystemPrompt,
+    /// Pinned messages and content injected into every request, such as
+    /// active skill instructions
+    Pinned,
+    /// Results returned by tools
+    ToolResult,
+    /// User and assistant turns, including tool calls
+    Chat,
+}
+
+impl ContextCategory {
+    /// Classify a message stored in the conversation by its role
+    pub fn of(message: &Message) -> Self {
+        match message.role.as_str() {
+            "system" => ContextCategory::SystemPrompt,
+            "tool" => ContextCategory::ToolResult,
+            _ => ContextCategory::Chat,
+        }
+    }
+}
+
+/// A message as it will be sent to the provider, with its estimated tokens
+#[derive(Debug, Clone)]
+pub struct ContextEntry {
+    /// The message itself
+    pub message: Message,
+    /// Part of the context the message counts toward
+    pub category: ContextCategory,
+    /// Estimated token count, computed the same way as for pruning
+    pub tokens: usize,
+    /// Position in the stored conversation, or `None` for transient messages
+    pub index: Option<usize>,
+}
+
+impl ContextEntry {
+    /// Create an entry, estimating its tokens with [`message_tokens`]
+    pub fn new(message: Message, category: ContextCategory) -> Self {
+        let tokens = message_tokens(&message);
+        Self {
+            message,
+            category,
+            tokens,
+            index: None,
+        }
+    }
+
+    /// One-line preview of the message, at most `max_chars` characters long
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::agent::conversation::{ContextCategory, ContextEntry};
+    /// use xzatoma::providers::Message;
+    ///
+    /// let entry = ContextEntry::new(Message::user("first line\nsecond"), ContextCategory::Chat);
+    /// assert_eq!(entry.preview(40), "first line ...");
+    /// ```
+    pub fn preview(&self, max_chars: usize) -> String {
+        message_preview(&self.message, max_chars)
+    }
+}
+
+/// Estimated token subtotals for each [`ContextCategory`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContextBreakdown {
+    /// Tokens in system prompts and summaries
+    pub system_prompt: usize,
+    /// Tokens in pinned content
+    pub pinned: usize,
+    /// Tokens in tool results
+    pub tool_results: usize,
+    /// Tokens in user and assistant turns
+    pub chat: usize,
+}
+
+impl ContextBreakdown {
+    /// Sum the tokens of `entries` by category
+    pub fn from_entries(entries: &[ContextEntry]) -> Self {
+        let mut breakdown = Self::default();
+        for entry in entries {
+            let subtotal = match entry.category {
+                ContextCategory::SystemPrompt => &mut breakdown.system_prompt,
+                ContextCategory::Pinned => &mut breakdown.pinned,
+                ContextCategory::ToolResult => &mut breakdown.tool_results,
+                ContextCategory::Chat => &mut breakdown.chat,
+            };
+            *subtotal += entry.tokens;
+        }
+        breakdown
+    }
+
+    /// Total tokens across all categories
+    pub fn total(&self) -> usize {
+        self.system_prompt + self.pinned + self.tool_results + self.chat
+    }
+}
+
+/// What [`Conversation::compact_to`] removed to fit a token budget
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Compaction {
+    /// Earlier messages replaced by a summary
+    pub messages_summarized: usize,
+    /// Tool outputs of the current turn replaced by a placeholder
+    pub tool_outputs_dropped: usize,
+    /// Estimated tokens before compaction
+    pub tokens_before: usize,
+    /// Estimated tokens after compaction
+    pub tokens_after: usize,
+}
+
 /// Manages conversation history with token tracking and pruning
 ///
 /// The conversation maintains a list of messages and tracks the total token count.
@@ -142,18 +262,31 @@ impl ContextStatus {
 /// When token count exceeds `prune_threshold * max_tokens`:
 /// 1. Keep system message (if present)
 /// 2. Keep last `min_retain_turns` conversation turns
-/// 3. Summarize and remove older messages
-/// 4. Insert summary as new system message
+/// 3. Keep pinned messages verbatim, in their original order
+/// 4. Summarize and remove older messages
+/// 5. Insert summary as new system message
 #[derive(Debug, Clone)]
 pub struct Conversation {
     id: Uuid,
     title: String,
     messages: Vec<Message>,
+    /// Pin flags, parallel to `messages`
+    pinned: Vec<bool>,
+    /// Working directory each message was added in, parallel to `messages`
+    cwds: Vec<Option<PathBuf>>,
+    /// Working directory recorded with new messages
+    cwd: Option<PathBuf>,
     token_count: usize,
     max_tokens: usize,
     min_retain_turns: usize,
     prune_threshold: f64,
     provider_token_usage: Option<TokenUsage>,
+    /// Tool results stubbed by [`Conversation::apply_history_hygiene`]
+    hygiene: HygieneReport,
+    /// Every pruning decision made in this conversation, oldest first
+    pruning_log: Vec<PruneReport>,
+    /// Pruning decisions not yet taken by [`Conversation::take_prune_reports`]
+    pending_prunes: Vec<PruneReport>,
 }
 
 impl Conversation {
@@ -194,11 +327,17 @@ impl Conversation {
             id: Uuid::new_v4(),
             title: "New Conversation".to_string(),
             messages: Vec::new(),
+            pinned: Vec::new(),
+            cwds: Vec::new(),
+            cwd: None,
             token_count: 0,
             max_tokens,
             min_retain_turns,
             prune_threshold: prune_threshold.clamp(0.0, 1.0),
             provider_token_usage: None,
+            hygiene: HygieneReport::default(),
+            pruning_log: Vec::new(),
+            pending_prunes: Vec::new(),
         }
     }
 
@@ -215,11 +354,17 @@ impl Conversation {
             id,
             title,
             messages: Vec::new(), // Will be populated via update_token_count loop
+            pinned: Vec::new(),
+            cwds: Vec::new(),
+            cwd: None,
             token_count: 0,
             max_tokens,
             min_retain_turns,
             prune_threshold,
             provider_token_usage: None,
+            hygiene: HygieneReport::default(),
+            pruning_log: Vec::new(),
+            pending_prunes: Vec::new(),
         };
 
         // Add messages one by one to calculate tokens
@@ -227,6 +372,8 @@ impl Conversation {
         for msg in messages {
             conv.update_token_count(&msg);
             conv.messages.push(msg);
+            conv.pinned.push(false);
+            conv.cwds.push(None);
         }
 
         conv
@@ -237,6 +384,11 @@ impl Conversation {
         self.id
     }
 
+    /// Set the conversation ID, used to save under a caller-chosen ID
+    pub fn set_id(&mut self, id: Uuid) {
+        self.id = id;
+    }
+
     /// Get the conversation title
     pub fn title(&self) -> &str {
         &self.title
@@ -264,9 +416,7 @@ impl Conversation {
     /// ```
     pub fn add_user_message(&mut self, content: impl Into<String>) {
         let message = Message::user(content);
-        self.update_token_count(&message);
-        self.messages.push(message);
-        self.prune_if_needed();
+        self.push_message(message);
     }
 
     /// Adds an assistant message to the conversation
@@ -276,9 +426,7 @@ impl Conversation {
     /// * `content` - The assistant message content
     pub fn add_assistant_message(&mut self, content: impl Into<String>) {
         let message = Message::assistant(content);
-        self.update_token_count(&message);
-        self.messages.push(message);
-        self.prune_if_needed();
+        self.push_message(message);
     }
 
     /// Adds a tool result message to the conversation
@@ -289,9 +437,7 @@ impl Conversation {
     /// * `content` - The tool execution result content
     pub fn add_tool_result(&mut self, tool_call_id: impl Into<String>, content: impl Into<String>) {
         let message = Message::tool_result(tool_call_id, content);
-        self.update_token_count(&message);
-        self.messages.push(message);
-        self.prune_if_needed();
+        self.push_message(message);
     }
 
     /// Adds a system message to the conversation
@@ -301,9 +447,7 @@ impl Conversation {
     /// * `content` - The system message content
     pub fn add_system_message(&mut self, content: impl Into<String>) {
         let message = Message::system(content);
-        self.update_token_count(&message);
-        self.messages.push(message);
-        self.prune_if_needed();
+        self.push_message(message);
     }
 
     /// Adds a generic message to the conversation
@@ -311,37 +455,245 @@ impl Conversation {
     /// This is a helper for tests and for callers that already have a
     /// `Message` instance. It updates token counting and triggers pruning.
     pub fn add_message(&mut self, message: Message) {
+        self.push_message(message);
+    }
+
+    /// Appends an unpinned message, updates the token count, and prunes
+    fn push_message(&mut self, message: Message) {
         self.update_token_count(&message);
         self.messages.push(message);
+        self.pinned.push(false);
+        self.cwds.push(self.cwd.clone());
         self.prune_if_needed();
     }
 
+    /// Pins the message at `index` so pruning and summarization keep it
+    ///
+    /// Pinned messages are retained verbatim and excluded from summaries, but
+    /// their tokens still count toward the context budget. Tool calls and tool
+    /// results cannot be pinned, because they must be pruned together.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `index` is out of range or refers to a tool call or
+    /// tool result.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::agent::Conversation;
+    ///
+    /// let mut conversation = Conversation::new(8000, 10, 0.8);
+    /// conversation.add_user_message("Task: migrate the database");
+    /// conversation.pin(0).unwrap();
+    /// assert!(conversation.is_pinned(0));
+    /// ```
+    pub fn pin(&mut self, index: usize) -> Result<()> {
+        let message = self.messages.get(index).ok_or_else(|| {
+            XzatomaError::Command(format!(
+                "No message {} in conversation ({} messages)",
+                index + 1,
+                self.messages.len()
+            ))
+        })?;
+        if message.role == "tool" || message.tool_calls.is_some() {
+            return Err(XzatomaError::Command(
+                "Tool calls and tool results cannot be pinned".to_string(),
+            ));
+        }
+        self.pinned[index] = true;
+        Ok(())
+    }
+
+    /// Returns true if the message at `index` is pinned
+    pub fn is_pinned(&self, index: usize) -> bool {
+        self.pinned.get(index).copied().unwrap_or(false)
+    }
+
+    /// Returns the indices of all pinned messages, in order
+    pub fn pinned_indices(&self) -> Vec<usize> {
+        self.pinned
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, pinned)| pinned.then_some(idx))
+            .collect()
+    }
+
+    /// Returns the index of the most recent user message, if any
+    pub fn last_user_message_index(&self) -> Option<usize> {
+        self.messages
+            .iter()
+            .rposition(|message| message.role == "user")
+    }
+
+    /// Sets the working directory recorded with messages added from now on
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use xzatoma::agent::Conversation;
+    ///
+    /// let mut conversation = Conversation::new(8000, 10, 0.8);
+    /// conversation.add_user_message("before");
+    /// conversation.set_cwd(Some("/repo/services/billing".into()));
+    /// conversation.add_user_message("after");
+    ///
+    /// assert_eq!(conversation.message_cwd(0), None);
+    /// assert_eq!(
+    ///     conversation.message_cwd(1),
+    ///     Some(Path::new("/repo/services/billing"))
+    /// );
+    /// ```
+    pub fn set_cwd(&mut self, cwd: Option<PathBuf>) {
+        self.cwd = cwd;
+    }
+
+    /// Returns the working directory recorded with new messages
+    pub fn cwd(&self) -> Option<&Path> {
+        self.cwd.as_deref()
+    }
+
+    /// Returns the working directory the message at `index` was added in
+    pub fn message_cwd(&self, index: usize) -> Option<&Path> {
+        self.cwds.get(index).and_then(|cwd| cwd.as_deref())
+    }
+
+    /// Returns the working directory of every message, in order
+    pub fn message_cwds(&self) -> &[Option<PathBuf>] {
+        &self.cwds
+    }
+
+    /// Restores the working directories of loaded messages
+    ///
+    /// Entries beyond the number of messages are ignored; missing entries
+    /// are recorded as unknown.
+    pub fn set_message_cwds(&mut self, mut cwds: Vec<Option<PathBuf>>) {
+        cwds.resize(self.messages.len(), None);
+        self.cwds = cwds;
+    }
+
+    /// Puts `stored` in place of the first `base_len` messages
+    ///
+    /// Messages after `base_len` are kept after the stored ones, with their
+    /// pins and working directories. The stored messages start unpinned and
+    /// without a working directory. Chat uses this to merge the messages a
+    /// session has not saved yet into a conversation that another session
+    /// saved in the meantime.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::agent::Conversation;
+    /// use xzatoma::providers::Message;
+    ///
+    /// let mut conversation = Conversation::new(8000, 10, 0.8);
+    /// conversation.add_user_message("saved");
+    /// conversation.add_user_message("not saved yet");
+    ///
+    /// conversation.rebase(vec![Message::user("saved"), Message::user("from elsewhere")], 1);
+    ///
+    /// let contents: Vec<_> = conversation
+    ///     .messages()
+    ///     .iter()
+    ///     .map(|m| m.content.as_deref().unwrap())
+    ///     .collect();
+    /// assert_eq!(contents, ["saved", "from elsewhere", "not saved yet"]);
+    /// ```
+    pub fn rebase(&mut self, stored: Vec<Message>, base_len: usize) {
+        let base_len = base_len.min(self.messages.len());
+        let local: Vec<_> = self
+            .messages
+            .drain(base_len..)
+            .zip(self.pinned.drain(base_len..))
+            .zip(self.cwds.drain(base_len..))
+            .collect();
+
+        self.messages.clear();
+        self.pinned.clear();
+        self.cwds.clear();
+        self.token_count = 0;
+        self.provider_token_usage = None;
+        for message in stored {
+            self.update_token_count(&message);
+            self.messages.push(message);
+            self.pinned.push(false);
+            self.cwds.push(None);
+        }
+        for ((message, pinned), cwd) in local {
+            self.update_token_count(&message);
+            self.messages.push(message);
+            self.pinned.push(pinned);
+            self.cwds.push(cwd);
+        }
+    }
+
+    /// Returns the estimated tokens used by pinned messages
+    pub fn pinned_token_count(&self) -> usize {
+        self.messages_with_tokens()
+            .zip(&self.pinned)
+            .filter(|(_, pinned)| **pinned)
+            .map(|((_, tokens), _)| tokens)
+            .sum()
+    }
+
+    /// Check if pinned messages alone reach the warning threshold
+    ///
+    /// Pruning and summarization cannot free pinned tokens, so this condition
+    /// needs the user's attention.
+    ///
+    /// # Arguments
+    ///
+    /// * `warning_threshold` - Fraction (0.0-1.0) that triggers warning
+    pub fn pinned_exceeds_warning(&self, warning_threshold: f64) -> bool {
+        if self.max_tokens == 0 {
+            return false;
+        }
+        let warning_threshold = warning_threshold.clamp(0.0, 1.0);
+        self.pinned_token_count() as f64 / self.max_tokens as f64 >= warning_threshold
+    }
+
+    /// Returns the messages that are not pinned, in order
+    ///
+    /// This is the input for summarization.
+    pub fn unpinned_messages(&self) -> Vec<Message> {
+        self.messages
+            .iter()
+            .zip(&self.pinned)
+            .filter(|(_, pinned)| !**pinned)
+            .map(|(message, _)| message.clone())
+            .collect()
+    }
+
+    /// Replaces the conversation with a summary, keeping pinned messages
+    ///
+    /// The summary becomes the first message, followed by the pinned messages
+    /// in their original order.
+    pub fn replace_with_summary(&mut self, summary: impl Into<String>) {
+        let pinned: Vec<(Message, Option<PathBuf>)> = self
+            .pinned_indices()
+            .into_iter()
+            .map(|idx| (self.messages[idx].clone(), self.cwds[idx].clone()))
+            .collect();
+
+        self.clear();
+        self.messages.push(Message::system(summary));
+        self.pinned.push(false);
+        self.cwds.push(self.cwd.clone());
+        for (message, cwd) in pinned {
+            self.messages.push(message);
+            self.pinned.push(true);
+            self.cwds.push(cwd);
+        }
+        self.recalculate_tokens();
+    }
+
     /// Updates the token count based on a new message
     ///
     /// Uses a simple heuristic: characters / 4
     /// This approximates GPT tokenization for English text.
     fn update_token_count(&mut self, message: &Message) {
-        let content_tokens = message
-            .content
-            .as_ref()
-            .map(|s| estimate_tokens(s))
-            .unwrap_or(0);
-
-        let tool_calls_tokens = message
-            .tool_calls
-            .as_ref()
-            .map(|calls| {
-                calls
-                    .iter()
-                    .map(|call| {
-                        estimate_tokens(&call.function.name)
-                            + estimate_tokens(&call.function.arguments)
-                    })
-                    .sum()
-            })
-            .unwrap_or(0);
-
-        self.token_count += content_tokens + tool_calls_tokens;
+        self.token_count += message_tokens(message);
     }
 
     /// Find indices of tool messages that reference the given tool_call_id
@@ -370,13 +722,16 @@ impl Conversation {
     /// - Tool call pairs (assistant tool_calls + corresponding tool results)
     ///
     /// Removed messages are summarized and added as a new system message.
-    pub fn prune_if_needed(&mut self) {
-        use std::collections::HashSet;
-
+    ///
+    /// # Returns
+    ///
+    /// A [`PruneReport`] when messages were pruned, also recorded in
+    /// [`Conversation::pruning_log`]
+    pub fn prune_if_needed(&mut self) -> Option<PruneReport> {
         let threshold = (self.max_tokens as f64 * self.prune_threshold) as usize;
 
         if self.token_count <= threshold {
-            return;
+            return None;
         }
 
         tracing::info!(
@@ -403,13 +758,13 @@ impl Conversation {
 
         // Don't prune if we can't find enough turns to keep
         if keep_from_index == 0 && !self.messages.is_empty() {
-            return;
+            return None;
         }
 
-        // Build initial prune index set (exclude system messages)
+        // Build initial prune index set (exclude system and pinned messages)
         let mut prune_indices: HashSet<usize> = HashSet::new();
         for (idx, message) in self.messages.iter().enumerate() {
-            if idx < keep_from_index && message.role != "system" {
+            if idx < keep_from_index && message.role != "system" && !self.pinned[idx] {
                 prune_indices.insert(idx);
             }
         }
@@ -470,50 +825,224 @@ impl Conversation {
         let mut indices_vec: Vec<usize> = prune_indices.iter().copied().collect();
         indices_vec.sort();
 
-        if !indices_vec.is_empty() {
-            let mut pruned_messages = Vec::new();
-            for &i in &indices_vec {
-                if i < self.messages.len() {
-                    pruned_messages.push(self.messages[i].clone());
-                }
+        if indices_vec.is_empty() {
+            return None;
+        }
+
+        let tokens_before = self.token_count;
+        let mut pruned_messages = Vec::new();
+        for &i in &indices_vec {
+            if i < self.messages.len() {
+                pruned_messages.push(self.messages[i].clone());
             }
+        }
 
-            // Create summary for pruned content
-            let summary = self.create_summary(&pruned_messages);
-            tracing::debug!(
-                "Pruned {} messages, inserting summary",
-                pruned_messages.len()
-            );
-
-            // Reconstruct messages: system messages + kept messages
-            let mut system_messages = Vec::new();
-            let mut to_keep = Vec::new();
-
-            for (idx, message) in self.messages.iter().enumerate() {
-                if message.role == "system" {
-                    system_messages.push(message.clone());
-                } else if prune_indices.contains(&idx) {
-                    // Skip pruned
-                } else {
-                    to_keep.push(message.clone());
+        // Create summary for pruned content
+        let summary = Message::system(self.create_summary(&pruned_messages));
+        let summary_tokens = message_tokens(&summary);
+        tracing::debug!(
+            "Pruned {} messages, inserting summary",
+            pruned_messages.len()
+        );
+
+        // Reconstruct messages: system messages + kept messages (with pin flags)
+        let mut system_messages = Vec::new();
+        let mut to_keep = Vec::new();
+
+        for (idx, message) in self.messages.iter().enumerate() {
+            let entry = (message.clone(), self.pinned[idx], self.cwds[idx].clone());
+            if message.role == "system" {
+                system_messages.push(entry);
+            } else if prune_indices.contains(&idx) {
+                // Skip pruned
+            } else {
+                to_keep.push(entry);
+            }
+        }
+
+        // Append summary system message
+        system_messages.push((summary, false, self.cwd.clone()));
+
+        self.messages.clear();
+        self.pinned.clear();
+        self.cwds.clear();
+        for (message, pinned, cwd) in system_messages.into_iter().chain(to_keep) {
+            self.messages.push(message);
+            self.pinned.push(pinned);
+            self.cwds.push(cwd);
+        }
+
+        // Recalculate token count
+        self.recalculate_tokens();
+
+        tracing::info!(
+            "Pruning complete: removed {} messages, tokens now {}/{}",
+            pruned_messages.len(),
+            self.token_count,
+            self.max_tokens
+        );
+
+        let report = PruneReport {
+            reason: PruneReason::Threshold,
+            messages: pruned_messages
+                .iter()
+                .map(|message| PrunedMessage::new(message, PruneAction::Summarized))
+                .collect(),
+            tokens_before,
+            tokens_after: self.token_count,
+            summary_tokens: Some(summary_tokens),
+        };
+        self.record_prune(report.clone());
+        Some(report)
+    }
+
+    /// Shrinks the conversation to `target_tokens`, keeping the current turn
+    ///
+    /// Used when a provider rejects a request for exceeding its context
+    /// window. Every unpinned message before the most recent user message is
+    /// replaced by a summary. If that is not enough, the largest tool outputs
+    /// of the current turn are replaced by a short placeholder, so each tool
+    /// call keeps its result. System messages, pinned messages, and the
+    /// current user message are never removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `target_tokens` - Estimated token count to shrink to
+    ///
+    /// # Returns
+    ///
+    /// A [`Compaction`] describing what was summarized or dropped. The
+    /// affected messages are recorded in [`Conversation::pruning_log`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::agent::Conversation;
+    ///
+    /// let mut conversation = Conversation::new(8000, 10, 0.8);
+    /// conversation.add_user_message("x".repeat(4000));
+    /// conversation.add_assistant_message("done");
+    /// conversation.add_user_message("next question");
+    ///
+    /// let compaction = conversation.compact_to(100);
+    /// assert_eq!(compaction.messages_summarized, 2);
+    /// assert!(conversation.token_count() <= 100);
+    /// ```
+    pub fn compact_to(&mut self, target_tokens: usize) -> Compaction {
+        let tokens_before = self.token_count;
+        let mut messages_summarized = 0;
+        let mut pruned = Vec::new();
+        let mut summary_tokens = None;
+
+        let turn_start = self
+            .last_user_message_index()
+            .unwrap_or(self.messages.len());
+        if self.token_count > target_tokens {
+            let earlier: Vec<Message> = self.messages[..turn_start]
+                .iter()
+                .zip(&self.pinned)
+                .filter(|(message, pinned)| message.role != "system" && !**pinned)
+                .map(|(message, _)| message.clone())
+                .collect();
+
+            if !earlier.is_empty() {
+                let summary = Message::system(self.create_summary(&earlier));
+                summary_tokens = Some(message_tokens(&summary));
+                let mut kept = Vec::new();
+                let mut current_turn = Vec::new();
+                for (idx, ((message, pinned), cwd)) in self
+                    .messages
+                    .iter()
+                    .zip(&self.pinned)
+                    .zip(&self.cwds)
+                    .enumerate()
+                {
+                    let entry = (message.clone(), *pinned, cwd.clone());
+                    if idx >= turn_start {
+                        current_turn.push(entry);
+                    } else if message.role == "system" || *pinned {
+                        kept.push(entry);
+                    }
                 }
+                kept.push((summary, false, self.cwd.clone()));
+
+                self.messages.clear();
+                self.pinned.clear();
+                self.cwds.clear();
+                for (message, pinned, cwd) in kept.into_iter().chain(current_turn) {
+                    self.messages.push(message);
+                    self.pinned.push(pinned);
+                    self.cwds.push(cwd);
+                }
+                self.recalculate_tokens();
+                messages_summarized = earlier.len();
+                pruned.extend(
+                    earlier
+                        .iter()
+                        .map(|message| PrunedMessage::new(message, PruneAction::Summarized)),
+                );
             }
+        }
 
-            // Append summary system message
-            system_messages.push(Message::system(summary));
+        let mut tool_outputs_dropped = 0;
+        if self.token_count > target_tokens {
+            let turn_start = self
+                .last_user_message_index()
+                .unwrap_or(self.messages.len());
+            let mut outputs: Vec<(usize, usize)> = self
+                .messages
+                .iter()
+                .enumerate()
+                .skip(turn_start)
+                .filter(|(idx, message)| message.role == "tool" && !self.pinned[*idx])
+                .map(|(idx, message)| (idx, message_tokens(message)))
+                .collect();
+            outputs.sort_by(|a, b| b.1.cmp(&a.1));
+
+            for (idx, tokens) in outputs {
+                if self.token_count <= target_tokens {
+                    break;
+                }
+                let placeholder = format!(
+                    "[Tool output of about {} tokens dropped to fit the context window]",
+                    tokens
+                );
+                if estimate_tokens(&placeholder) >= tokens {
+                    break;
+                }
+                pruned.push(PrunedMessage::new(
+                    &self.messages[idx],
+                    PruneAction::Stubbed,
+                ));
+                self.messages[idx].content = Some(placeholder);
+                self.token_count = self.token_count - tokens + message_tokens(&self.messages[idx]);
+                tool_outputs_dropped += 1;
+            }
+        }
 
-            self.messages = system_messages;
-            self.messages.extend(to_keep);
+        tracing::info!(
+            "Compacted conversation: summarized {} messages, dropped {} tool outputs, tokens {} -> {}",
+            messages_summarized,
+            tool_outputs_dropped,
+            tokens_before,
+            self.token_count
+        );
 
-            // Recalculate token count
-            self.recalculate_tokens();
+        if !pruned.is_empty() {
+            self.record_prune(PruneReport {
+                reason: PruneReason::OverflowRecovery,
+                messages: pruned,
+                tokens_before,
+                tokens_after: self.token_count,
+                summary_tokens,
+            });
+        }
 
-            tracing::info!(
-                "Pruning complete: removed {} messages, tokens now {}/{}",
-                pruned_messages.len(),
-                self.token_count,
-                self.max_tokens
-            );
+        Compaction {
+            messages_summarized,
+            tool_outputs_dropped,
+            tokens_before,
+            tokens_after: self.token_count,
         }
     }
 
@@ -600,6 +1129,27 @@ impl Conversation {
         &self.messages
     }
 
+    /// Iterates over messages together with their estimated token counts
+    ///
+    /// Counts match the ones used for pruning, so they add up to
+    /// [`Conversation::token_count`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::agent::Conversation;
+    ///
+    /// let mut conversation = Conversation::new(8000, 10, 0.8);
+    /// conversation.add_user_message("Hello there");
+    /// let total: usize = conversation.messages_with_tokens().map(|(_, t)| t).sum();
+    /// assert_eq!(total, conversation.token_count());
+    /// ```
+    pub fn messages_with_tokens(&self) -> impl Iterator<Item = (&Message, usize)> + '_ {
+        self.messages
+            .iter()
+            .map(|message| (message, message_tokens(message)))
+    }
+
     /// Returns the current token count
     ///
     /// # Examples
@@ -669,8 +1219,135 @@ impl Conversation {
     /// Clears all messages from the conversation
     pub fn clear(&mut self) {
         self.messages.clear();
+        self.pinned.clear();
+        self.cwds.clear();
         self.token_count = 0;
         self.provider_token_usage = None;
+        self.hygiene = HygieneReport::default();
+    }
+
+    /// Replaces superseded tool results with short stubs
+    ///
+    /// Older results of a call that was repeated later with identical
+    /// output, and older reads of files a mutating tool changed later, are
+    /// replaced. The most recent copy of a result and pinned messages are
+    /// kept verbatim. See [`history_hygiene`] for the rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_mutating` - Whether a tool name changes the workspace
+    ///
+    /// # Returns
+    ///
+    /// What this pass stubbed. The totals for the conversation are
+    /// available from [`Conversation::history_hygiene`].
+    pub fn apply_history_hygiene(&mut self, is_mutating: impl Fn(&str) -> bool) -> HygieneReport {
+        let stubs = history_hygiene::find_stubs(&self.messages, &self.pinned, &is_mutating);
+        self.apply_stubs(stubs)
+    }
+
+    /// Stubs the reads of files changed outside the session
+    ///
+    /// Every unpinned `read_file` result for one of `paths` is replaced,
+    /// so only the fresh read appended afterwards shows the file. Counted
+    /// as stale reads in [`Conversation::history_hygiene`].
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - Changed paths, as passed to `read_file`
+    pub fn stub_external_reads(&mut self, paths: &HashSet<String>) -> HygieneReport {
+        let stubs = history_hygiene::find_external_stale_reads(&self.messages, &self.pinned, paths);
+        self.apply_stubs(stubs)
+    }
+
+    fn apply_stubs(&mut self, stubs: Vec<Stub>) -> HygieneReport {
+        let mut report = HygieneReport::default();
+        let (duplicates, stale_reads): (Vec<Stub>, Vec<Stub>) = stubs
+            .into_iter()
+            .partition(|stub| stub.reason == StubReason::Duplicate);
+
+        // One pruning report per reason, so each says why it happened
+        for (reason, stubs) in [
+            (StubReason::Duplicate, duplicates),
+            (StubReason::StaleRead, stale_reads),
+        ] {
+            if stubs.is_empty() {
+                continue;
+            }
+            let tokens_before = self.token_count;
+            let mut pruned = Vec::new();
+            for stub in stubs {
+                let message = &mut self.messages[stub.index];
+                pruned.push(PrunedMessage::new(message, PruneAction::Stubbed));
+                let before = message_tokens(message);
+                message.content = Some(stub.content);
+                let after = message_tokens(message);
+                self.token_count = self.token_count.saturating_sub(before) + after;
+                report.tokens_reclaimed += before.saturating_sub(after);
+                match reason {
+                    StubReason::Duplicate => report.duplicates_stubbed += 1,
+                    StubReason::StaleRead => report.stale_reads_stubbed += 1,
+                }
+            }
+            self.record_prune(PruneReport {
+                reason: reason.into(),
+                messages: pruned,
+                tokens_before,
+                tokens_after: self.token_count,
+                summary_tokens: None,
+            });
+        }
+        if !report.is_empty() {
+            tracing::debug!("History hygiene: {}", report);
+        }
+        self.hygiene.add(report);
+        report
+    }
+
+    /// Returns what history hygiene has stubbed in this conversation
+    pub fn history_hygiene(&self) -> HygieneReport {
+        self.hygiene
+    }
+
+    /// Returns the pruning decisions made in this conversation, oldest first
+    ///
+    /// Only the most recent [`PRUNING_LOG_LIMIT`] reports are kept.
+    /// [`Conversation::clear`] leaves the log intact.
+    pub fn pruning_log(&self) -> &[PruneReport] {
+        &self.pruning_log
+    }
+
+    /// Takes the pruning reports recorded since the last call
+    ///
+    /// The agent emits each one as a `ConversationPruned` event. The
+    /// reports stay in [`Conversation::pruning_log`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::agent::Conversation;
+    ///
+    /// let mut conversation = Conversation::new(8000, 10, 0.8);
+    /// conversation.add_user_message("x".repeat(4000));
+    /// conversation.add_assistant_message("done");
+    /// conversation.add_user_message("next question");
+    /// conversation.compact_to(100);
+    ///
+    /// assert_eq!(conversation.take_prune_reports().len(), 1);
+    /// assert!(conversation.take_prune_reports().is_empty());
+    /// assert_eq!(conversation.pruning_log().len(), 1);
+    /// ```
+    pub fn take_prune_reports(&mut self) -> Vec<PruneReport> {
+        std::mem::take(&mut self.pending_prunes)
+    }
+
+    fn record_prune(&mut self, report: PruneReport) {
+        for log in [&mut self.pruning_log, &mut self.pending_prunes] {
+            if log.len() == PRUNING_LOG_LIMIT {
+                log.remove(0);
+            }
+            log.push(report.clone());
+        }
     }
 
     /// Updates token count from provider-reported usage
@@ -870,13 +1547,16 @@ impl Conversation {
     /// Summarize the conversation and reset for new turns
     ///
     /// This method:
-    /// 1. Collects all non-system messages
+    /// 1. Collects all non-system, unpinned messages
     /// 2. Creates a comprehensive summary
-    /// 3. Clears all messages except system messages
+    /// 3. Clears all messages except system and pinned messages
     /// 4. Adds the summary as a new system message
     /// 5. Resets the token count
     /// 6. Returns the summary text for display or logging
     ///
+    /// The summarized messages are recorded in [`Conversation::pruning_log`]
+    /// with the summary's token count.
+    ///
     /// # Returns
     ///
     /// Returns the generated summary text, or an error if the operation fails
@@ -896,37 +1576,64 @@ impl Conversation {
     /// // assert!(summary.is_ok());
     /// ```
     pub fn summarize_and_reset(&mut self) -> Result<String> {
-        // Collect all non-system messages for summarization
+        let tokens_before = self.token_count;
+
+        // Collect all non-system, unpinned messages for summarization
         let messages_to_summarize: Vec<_> = self
             .messages
             .iter()
-            .filter(|msg| msg.role != "system")
-            .cloned()
+            .zip(&self.pinned)
+            .filter(|(msg, pinned)| msg.role != "system" && !**pinned)
+            .map(|(msg, _)| msg.clone())
             .collect();
 
         // Create summary from collected messages
         let summary = self.create_summary(&messages_to_summarize);
 
-        // Keep system messages
-        let system_messages: Vec<_> = self
+        // Keep system messages and pinned messages, in order
+        let kept: Vec<(Message, bool, Option<PathBuf>)> = self
             .messages
             .iter()
-            .filter(|msg| msg.role == "system")
-            .cloned()
+            .zip(&self.pinned)
+            .zip(&self.cwds)
+            .filter(|((msg, pinned), _)| msg.role == "system" || **pinned)
+            .map(|((msg, pinned), cwd)| (msg.clone(), *pinned, cwd.clone()))
             .collect();
 
-        // Clear all messages and rebuild with systems only
+        // Clear all messages and rebuild with systems and pinned only
         self.messages.clear();
-        self.messages.extend(system_messages);
+        self.pinned.clear();
+        self.cwds.clear();
+        for (message, pinned, cwd) in kept {
+            self.messages.push(message);
+            self.pinned.push(pinned);
+            self.cwds.push(cwd);
+        }
 
         // Add summary as a new system message
+        let mut summary_tokens = None;
         if !summary.is_empty() {
-            self.add_system_message(format!("Previous conversation summary:\n{}", summary));
+            let message = Message::system(format!("Previous conversation summary:\n{}", summary));
+            summary_tokens = Some(message_tokens(&message));
+            self.add_message(message);
         }
 
         // Reset token count by recalculating from remaining messages
         self.recalculate_tokens();
 
+        if !messages_to_summarize.is_empty() {
+            self.record_prune(PruneReport {
+                reason: PruneReason::AutoSummary,
+                messages: messages_to_summarize
+                    .iter()
+                    .map(|message| PrunedMessage::new(message, PruneAction::Summarized))
+                    .collect(),
+                tokens_before,
+                tokens_after: self.token_count,
+                summary_tokens,
+            });
+        }
+
         Ok(summary)
     }
 }
@@ -935,10 +1642,72 @@ impl Conversation {
 ///
 /// Uses characters / 4, which approximates GPT tokenization for English text.
 /// For production use, replace with an actual tokenizer library (e.g., tiktoken-rs).
-fn estimate_tokens(text: &str) -> usize {
+pub fn estimate_tokens(text: &str) -> usize {
     (text.chars().count() + 3) / 4
 }
 
+/// Estimates the tokens a message contributes to the context
+///
+/// Counts the message content plus the name and arguments of each tool call.
+pub fn message_tokens(message: &Message) -> usize {
+    let content_tokens = message
+        .content
+        .as_ref()
+        .map(|s| estimate_tokens(s))
+        .unwrap_or(0);
+
+    let tool_calls_tokens = message
+        .tool_calls
+        .as_ref()
+        .map(|calls| {
+            calls
+                .iter()
+                .map(|call| {
+                    estimate_tokens(&call.function.name) + estimate_tokens(&call.function.arguments)
+                })
+                .sum()
+        })
+        .unwrap_or(0);
+
+    content_tokens + tool_calls_tokens
+}
+
+/// One-line preview of a message, at most `max_chars` characters long
+///
+/// Shows the first line of the content, or the names of the tool calls
+/// when there is no content.
+pub(crate) fn message_preview(message: &Message, max_chars: usize) -> String {
+    let content = message.content.as_deref().unwrap_or("").trim();
+    let text = if content.is_empty() {
+        match &message.tool_calls {
+            Some(calls) if !calls.is_empty() => {
+                let names: Vec<&str> = calls
+                    .iter()
+                    .map(|call| call.function.name.as_str())
+                    .collect();
+                format!("tool calls: {}", names.join(", "))
+            }
+            _ => String::from("(empty)"),
+        }
+    } else {
+        let mut lines = content.lines();
+        let first = lines.next().unwrap_or("").trim();
+        if lines.next().is_some() {
+            format!("{} ...", first)
+        } else {
+            first.to_string()
+        }
+    };
+
+    if text.chars().count() <= max_chars {
+        text
+    } else {
+        let mut truncated: String = text.chars().take(max_chars.saturating_sub(3)).collect();
+        truncated.push_str("...");
+        truncated
+    }
+}
+
 /// Truncates a string to a maximum length, adding ellipsis if truncated
 fn truncate_string(s: &str, max_len: usize) -> String {
     if s.len() <= max_len {
@@ -1051,6 +1820,169 @@ mod tests {
         assert!(last_user.unwrap().contains("Message 9"));
     }
 
+    #[test]
+    fn test_pinned_messages_survive_pruning_in_order() {
+        let mut conversation = Conversation::new(200, 2, 0.5);
+        conversation.add_system_message("System prompt");
+        conversation.add_user_message("Task: migrate the billing tables");
+        conversation.pin(1).unwrap();
+        conversation.add_assistant_message("Understood");
+        conversation.add_user_message("Schema: accounts(id, balance)");
+        conversation.pin(3).unwrap();
+
+        for i in 0..40 {
+            conversation.add_user_message(format!("Message {}", i));
+            conversation.add_assistant_message(format!("Response {}", i));
+        }
+
+        let messages = conversation.messages();
+        let summaries: Vec<&Message> = messages
+            .iter()
+            .filter(|m| {
+                m.content
+                    .as_deref()
+                    .is_some_and(|c| c.starts_with("Summary of earlier conversation"))
+            })
+            .collect();
+        assert!(
+            !summaries.is_empty(),
+            "conversation should have been pruned"
+        );
+        for summary in summaries {
+            let text = summary.content.as_deref().unwrap();
+            assert!(!text.contains("Task:"));
+            assert!(!text.contains("Schema:"));
+        }
+        assert!(!messages
+            .iter()
+            .any(|m| m.content.as_deref() == Some("Understood")));
+
+        let pinned: Vec<&str> = conversation
+            .pinned_indices()
+            .into_iter()
+            .map(|idx| messages[idx].content.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            pinned,
+            vec![
+                "Task: migrate the billing tables",
+                "Schema: accounts(id, balance)"
+            ]
+        );
+        assert_eq!(
+            conversation.token_count(),
+            conversation
+                .messages_with_tokens()
+                .map(|(_, t)| t)
+                .sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn test_summarize_and_reset_keeps_pinned_messages() {
+        let mut conv = Conversation::new(1000, 5, 0.8);
+        conv.add_system_message("System");
+        conv.add_user_message("Pinned instructions");
+        conv.add_assistant_message("Ack");
+        conv.add_user_message("Question");
+        conv.pin(1).unwrap();
+
+        let summary = conv.summarize_and_reset().unwrap();
+        assert!(!summary.contains("Pinned instructions"));
+
+        let contents: Vec<&str> = conv
+            .messages()
+            .iter()
+            .filter_map(|m| m.content.as_deref())
+            .collect();
+        assert_eq!(contents[0], "System");
+        assert_eq!(contents[1], "Pinned instructions");
+        assert!(contents[2].starts_with("Previous conversation summary:"));
+        assert_eq!(conv.pinned_indices(), vec![1]);
+    }
+
+    #[test]
+    fn test_replace_with_summary_keeps_pinned_messages() {
+        let mut conv = Conversation::new(1000, 5, 0.8);
+        conv.add_user_message("Keep me");
+        conv.add_assistant_message("Drop me");
+        conv.pin(0).unwrap();
+
+        assert_eq!(conv.unpinned_messages().len(), 1);
+        conv.replace_with_summary("Summary");
+
+        assert_eq!(conv.len(), 2);
+        assert_eq!(conv.messages()[0].content.as_deref(), Some("Summary"));
+        assert_eq!(conv.messages()[1].content.as_deref(), Some("Keep me"));
+        assert!(conv.is_pinned(1));
+        assert!(!conv.is_pinned(0));
+    }
+
+    #[test]
+    fn test_message_cwds_follow_messages_through_summary() {
+        let mut conv = Conversation::new(1000, 5, 0.8);
+        conv.set_cwd(Some(PathBuf::from("/repo")));
+        conv.add_user_message("Keep me");
+        conv.set_cwd(Some(PathBuf::from("/repo/services/billing")));
+        conv.add_assistant_message("Drop me");
+        conv.pin(0).unwrap();
+
+        conv.replace_with_summary("Summary");
+
+        assert_eq!(
+            conv.message_cwd(0),
+            Some(Path::new("/repo/services/billing"))
+        );
+        assert_eq!(conv.message_cwd(1), Some(Path::new("/repo")));
+        assert_eq!(conv.message_cwds().len(), conv.len());
+    }
+
+    #[test]
+    fn test_set_message_cwds_matches_message_count() {
+        let mut conv = Conversation::with_history(
+            Uuid::new_v4(),
+            "t".to_string(),
+            vec![Message::user("a"), Message::assistant("b")],
+            1000,
+            5,
+            0.8,
+        );
+        conv.set_message_cwds(vec![Some(PathBuf::from("/repo"))]);
+
+        assert_eq!(conv.message_cwds(), &[Some(PathBuf::from("/repo")), None]);
+    }
+
+    #[test]
+    fn test_pin_rejects_invalid_targets() {
+        let mut conv = Conversation::new(1000, 5, 0.8);
+        conv.add_message(Message::assistant_with_tools(vec![ToolCall {
+            id: "call_1".to_string(),
+            function: FunctionCall {
+                name: "read_file".to_string(),
+                arguments: "{}".to_string(),
+            },
+        }]));
+        conv.add_tool_result("call_1", "contents");
+
+        assert!(conv.pin(0).is_err());
+        assert!(conv.pin(1).is_err());
+        assert!(conv.pin(2).is_err());
+        assert!(conv.pinned_indices().is_empty());
+    }
+
+    #[test]
+    fn test_pinned_exceeds_warning() {
+        let mut conv = Conversation::new(100, 5, 0.99);
+        conv.add_user_message("x".repeat(360));
+        assert!(!conv.pinned_exceeds_warning(0.85));
+
+        conv.pin(0).unwrap();
+        assert_eq!(conv.pinned_token_count(), 90);
+        assert!(conv.pinned_exceeds_warning(0.85));
+        assert!(!conv.pinned_exceeds_warning(0.95));
+        assert_eq!(conv.last_user_message_index(), Some(0));
+    }
+
     #[test]
     fn test_pruning_creates_summary() {
         let mut conversation = Conversation::new(200, 2, 0.5);
@@ -1087,6 +2019,148 @@ mod tests {
         assert!(conversation.is_empty());
     }
 
+    #[test]
+    fn test_messages_with_tokens_sum_to_token_count() {
+        let mut conversation = Conversation::new(8000, 10, 0.8);
+        conversation.add_system_message("You are helpful");
+        conversation.add_user_message("Read the file");
+        conversation.add_tool_result("call_1", "fn main() {}");
+
+        let counts: Vec<usize> = conversation
+            .messages_with_tokens()
+            .map(|(_, t)| t)
+            .collect();
+        assert_eq!(counts, vec![4, 4, 3]);
+        assert_eq!(counts.iter().sum::<usize>(), conversation.token_count());
+    }
+
+    #[test]
+    fn test_history_hygiene_updates_tokens_and_totals() {
+        let mut conversation = Conversation::new(100_000, 10, 0.8);
+        let output = "fn main() {}\n".repeat(50);
+        for id in ["call_1", "call_2"] {
+            conversation.add_message(Message::assistant_with_tools(vec![
+                crate::providers::ToolCall {
+                    id: id.to_string(),
+                    function: crate::providers::FunctionCall {
+                        name: "read_file".to_string(),
+                        arguments: r#"{"path":"src/main.rs"}"#.to_string(),
+                    },
+                },
+            ]));
+            conversation.add_tool_result(id, output.clone());
+        }
+        let before = conversation.token_count();
+
+        let report = conversation.apply_history_hygiene(|_| false);
+        assert_eq!(report.duplicates_stubbed, 1);
+        assert_eq!(
+            conversation.messages()[1].content.as_deref(),
+            Some("[superseded by later identical read of src/main.rs]")
+        );
+        assert_eq!(
+            conversation.messages()[3].content.as_deref(),
+            Some(output.as_str())
+        );
+        assert_eq!(conversation.token_count(), before - report.tokens_reclaimed);
+        let total: usize = conversation.messages_with_tokens().map(|(_, t)| t).sum();
+        assert_eq!(total, conversation.token_count());
+
+        assert!(conversation.apply_history_hygiene(|_| false).is_empty());
+        assert_eq!(conversation.history_hygiene(), report);
+
+        let log = conversation.pruning_log();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].reason, PruneReason::Duplicate);
+        assert_eq!(log[0].messages.len(), 1);
+        assert_eq!(log[0].messages[0].role, "tool");
+        assert_eq!(log[0].messages[0].action, PruneAction::Stubbed);
+        assert_eq!(log[0].tokens_reclaimed(), report.tokens_reclaimed);
+    }
+
+    #[test]
+    fn test_context_breakdown_subtotals() {
+        let assistant_with_call = Message::assistant_with_tools(vec![ToolCall {
+            id: "call_1".to_string(),
+            function: FunctionCall {
+                name: "read_file".to_string(),
+                arguments: r#"{"path":"a.rs"}"#.to_string(),
+            },
+        }]);
+        let entries = vec![
+            // 16 chars -> 4 tokens
+            ContextEntry::new(
+                Message::system("You are helpful!"),
+                ContextCategory::SystemPrompt,
+            ),
+            // 8 chars -> 2 tokens
+            ContextEntry::new(Message::system("Task: do"), ContextCategory::Pinned),
+            // 5 chars -> 2 tokens
+            ContextEntry::new(Message::user("Hello"), ContextCategory::Chat),
+            // name 9 chars -> 3 tokens, arguments 15 chars -> 4 tokens
+            ContextEntry::new(assistant_with_call, ContextCategory::Chat),
+            // 12 chars -> 3 tokens
+            ContextEntry::new(
+                Message::tool_result("call_1", "fn main() {}"),
+                ContextCategory::ToolResult,
+            ),
+        ];
+
+        let breakdown = ContextBreakdown::from_entries(&entries);
+        assert_eq!(
+            breakdown,
+            ContextBreakdown {
+                system_prompt: 4,
+                pinned: 2,
+                tool_results: 3,
+                chat: 9,
+            }
+        );
+        assert_eq!(breakdown.total(), 18);
+        assert_eq!(
+            breakdown.total(),
+            entries.iter().map(|entry| entry.tokens).sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn test_context_category_of_roles() {
+        assert_eq!(
+            ContextCategory::of(&Message::system("s")),
+            ContextCategory::SystemPrompt
+        );
+        assert_eq!(
+            ContextCategory::of(&Message::tool_result("id", "r")),
+            ContextCategory::ToolResult
+        );
+        assert_eq!(
+            ContextCategory::of(&Message::assistant("a")),
+            ContextCategory::Chat
+        );
+    }
+
+    #[test]
+    fn test_context_entry_preview() {
+        let entry = ContextEntry::new(
+            Message::user("  a long first line\nsecond"),
+            ContextCategory::Chat,
+        );
+        assert_eq!(entry.preview(80), "a long first line ...");
+        assert_eq!(entry.preview(10), "a long ...");
+
+        let calls = ContextEntry::new(
+            Message::assistant_with_tools(vec![ToolCall {
+                id: "1".to_string(),
+                function: FunctionCall {
+                    name: "grep".to_string(),
+                    arguments: "{}".to_string(),
+                },
+            }]),
+            ContextCategory::Chat,
+        );
+        assert_eq!(calls.preview(80), "tool calls: grep");
+    }
+
     #[test]
     fn test_estimate_tokens() {
         // Simple heuristic: chars / 4
@@ -1277,6 +2351,168 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_prune_if_needed_reports_pruned_messages() {
+        let mut conv = Conversation::new(100, 1, 0.5);
+        for i in 0..5 {
+            conv.add_message(Message::user(format!("message {}", i)));
+        }
+        conv.token_count = 60; // Force pruning
+
+        let report = conv.prune_if_needed().expect("threshold was crossed");
+
+        assert_eq!(report.reason, PruneReason::Threshold);
+        let previews: Vec<&str> = report.messages.iter().map(|m| m.preview.as_str()).collect();
+        assert_eq!(
+            previews,
+            ["message 0", "message 1", "message 2", "message 3"]
+        );
+        assert!(report
+            .messages
+            .iter()
+            .all(|m| m.role == "user" && m.tokens == 3 && m.action == PruneAction::Summarized));
+        assert_eq!(report.tokens_before, 60);
+        assert_eq!(report.tokens_after, conv.token_count());
+        assert_eq!(
+            report.summary_tokens,
+            Some(message_tokens(&conv.messages()[0]))
+        );
+        assert_eq!(conv.pruning_log(), [report.clone()]);
+        assert_eq!(conv.take_prune_reports(), [report]);
+        assert!(conv.take_prune_reports().is_empty());
+        assert!(conv.prune_if_needed().is_none());
+    }
+
+    #[test]
+    fn test_compact_to_reports_summarized_and_dropped_messages() {
+        let mut conv = Conversation::new(100_000, 10, 0.8);
+        conv.add_user_message("x".repeat(4000));
+        conv.add_assistant_message("done");
+        conv.add_user_message("read the file");
+        conv.add_message(Message::assistant_with_tools(vec![ToolCall {
+            id: "call_a".to_string(),
+            function: FunctionCall {
+                name: "read_file".to_string(),
+                arguments: "{}".to_string(),
+            },
+        }]));
+        conv.add_tool_result("call_a", "a".repeat(8000));
+        let tokens_before = conv.token_count();
+
+        let compaction = conv.compact_to(500);
+        let reports = conv.take_prune_reports();
+
+        assert_eq!(reports.len(), 1);
+        let report = &reports[0];
+        assert_eq!(report.reason, PruneReason::OverflowRecovery);
+        assert_eq!(report.tokens_before, tokens_before);
+        assert_eq!(report.tokens_after, compaction.tokens_after);
+        assert!(report.summary_tokens.is_some_and(|tokens| tokens > 0));
+
+        let messages: Vec<(&str, usize, PruneAction)> = report
+            .messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.tokens, m.action))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                ("user", 1000, PruneAction::Summarized),
+                ("assistant", 1, PruneAction::Summarized),
+                ("tool", 2000, PruneAction::Stubbed),
+            ]
+        );
+        assert_eq!(report.messages[0].preview.chars().count(), 72);
+        assert!(report.messages[2].preview.starts_with("aaa"));
+    }
+
+    #[test]
+    fn test_summarize_and_reset_reports_summary_tokens() {
+        let mut conv = Conversation::new(100_000, 10, 0.8);
+        conv.add_user_message("first question");
+        conv.add_assistant_message("first answer");
+
+        conv.summarize_and_reset().unwrap();
+
+        let report = conv.take_prune_reports().remove(0);
+        assert_eq!(report.reason, PruneReason::AutoSummary);
+        assert_eq!(report.messages.len(), 2);
+        assert_eq!(report.tokens_after, conv.token_count());
+        assert_eq!(
+            report.summary_tokens,
+            Some(message_tokens(conv.messages().last().unwrap()))
+        );
+    }
+
+    #[test]
+    fn test_compact_to_summarizes_earlier_turns_and_keeps_current_turn() {
+        let mut conv = Conversation::new(100_000, 10, 0.8);
+        conv.add_system_message("You are helpful");
+        conv.add_user_message("x".repeat(4000));
+        conv.add_assistant_message("y".repeat(4000));
+        conv.add_user_message("keep me");
+        conv.pin(3).unwrap();
+        conv.add_user_message("current question");
+
+        let compaction = conv.compact_to(200);
+
+        assert_eq!(compaction.messages_summarized, 2);
+        assert_eq!(compaction.tool_outputs_dropped, 0);
+        assert!(compaction.tokens_after <= 200);
+        assert_eq!(compaction.tokens_after, conv.token_count());
+        let contents: Vec<_> = conv
+            .messages()
+            .iter()
+            .filter_map(|m| m.content.as_deref())
+            .collect();
+        assert_eq!(contents[0], "You are helpful");
+        assert_eq!(contents[1], "keep me");
+        assert!(contents[2].contains("Summary"));
+        assert_eq!(contents[3], "current question");
+        assert!(conv.is_pinned(1));
+    }
+
+    #[test]
+    fn test_compact_to_drops_largest_tool_outputs_of_current_turn() {
+        let mut conv = Conversation::new(100_000, 10, 0.8);
+        conv.add_user_message("read both files");
+        conv.add_message(Message::assistant_with_tools(vec![
+            ToolCall {
+                id: "call_a".to_string(),
+                function: FunctionCall {
+                    name: "read_file".to_string(),
+                    arguments: "{}".to_string(),
+                },
+            },
+            ToolCall {
+                id: "call_b".to_string(),
+                function: FunctionCall {
+                    name: "read_file".to_string(),
+                    arguments: "{}".to_string(),
+                },
+            },
+        ]));
+        conv.add_tool_result("call_a", "a".repeat(8000));
+        conv.add_tool_result("call_b", "b".repeat(400));
+
+        let compaction = conv.compact_to(1000);
+
+        assert_eq!(compaction.messages_summarized, 0);
+        assert_eq!(compaction.tool_outputs_dropped, 1);
+        assert!(conv.token_count() <= 1000);
+        assert_eq!(conv.len(), 4);
+        assert!(conv.messages()[2]
+            .content
+            .as_deref()
+            .unwrap()
+            .contains("dropped to fit the context window"));
+        assert_eq!(
+            conv.messages()[3].content.as_deref(),
+            Some("b".repeat(400).as_str())
+        );
+        assert_eq!(conv.messages()[2].tool_call_id.as_deref(), Some("call_a"));
+    }
+
     #[test]
     fn test_context_status_normal_when_below_warning_threshold() {
         let conv = Conversation::new(1000, 5, 0.8);
@@ -5,27 +5,64 @@
 //! - Executes tool calls requested by the provider
 //! - Enforces iteration limits and timeouts
 //! - Handles errors and stops conditions gracefully
+//! - Ends the run when the model calls the `finish` tool and records the
+//!   structured [`ExecutionOutcome`]
+//!
+//! A prompt runs as an [`AgentSession`](super::session::AgentSession): each
+//! turn is one model call plus its tool calls, and `execute` loops over the
+//! turns until the session ends.
 
 use crate::agent::events::{AgentExecutionEvent, AgentObserver, NoOpObserver};
 use crate::chat_mode::{ChatMode, SafetyMode};
 use crate::config::AgentConfig;
 use crate::error::{Result, XzatomaError};
+use crate::mention_parser::MentionCache;
 use crate::prompts;
-use crate::providers::{Message, Provider, TokenUsage, ToolCall};
-use crate::tools::{ToolRegistry, ToolResult};
+use crate::providers::timeouts;
+use crate::providers::tool_arguments::parse_tool_arguments;
+use crate::providers::{CompletionResponse, FunctionCall, Message, Provider, TokenUsage, ToolCall};
+use crate::telemetry::{TelemetryObserver, TelemetrySink};
+use crate::tools::call_policy::{deferred_call_message, ToolCallPolicy};
+use crate::tools::change_set::{FileChange, StagedFiles};
+use crate::tools::definition_limits::{fit_tool_definitions, ToolDefinitionLimits, ToolFitReport};
+use crate::tools::finish::{parse_outcome, FINISH_TOOL_NAME};
+use crate::tools::output_pages::{OutputPager, ReadToolOutputTool, READ_TOOL_OUTPUT_TOOL_NAME};
+use crate::tools::overflow_summary::OverflowSummarizer;
+use crate::tools::{
+    validate_tool_arguments, ArgumentValidationOutcome, ToolOutputLine, ToolOutputSink,
+    ToolRegistry, ToolResult,
+};
+use crate::trace_context;
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::Instant;
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, info, warn};
-
+use tracing::{debug, info, warn, Instrument};
+
+use super::change_review::{
+    rejected_change_result, ChangeReview, ReviewDecision, CHANGE_REVIEW_METADATA,
+};
+use super::conversation::message_tokens;
+use super::file_tracker::{external_change_note, ExternalChange, FileChangeKind, FileTracker};
+use super::history_hygiene::{path_argument, MUTATED_PATH_ARGUMENTS};
+use super::loop_guard::{LoopCheck, LoopGuard};
+use super::mode_gate::{EscalationRequest, GateDecision, ModeGate};
+use super::outcome::ExecutionOutcome;
+use super::session::TurnBudget;
+use super::step_policy::StepPolicy;
 use super::thinking::extract_thinking;
-use super::{ContextInfo, Conversation};
+use super::{
+    Compaction, ContextCategory, ContextEntry, ContextInfo, Conversation, ToolCallStatus,
+    ToolHealth, ToolMetrics,
+};
 
 /// The main agent that executes autonomous tasks
 ///
 /// The agent maintains a conversation with an AI provider and executes
 /// tool calls requested by the provider. It enforces safety limits:
 /// - Maximum iterations to prevent infinite loops
+/// - Repeated identical tool calls are cached, then stopped
 /// - Timeout to prevent runaway execution
 /// - Tool execution validation
 /// - Token usage tracking across multiple completions
@@ -57,8 +94,26 @@ pub struct Agent {
     config: AgentConfig,
     accumulated_usage: Arc<Mutex<Option<TokenUsage>>>,
     transient_system_messages: Vec<String>,
+    tool_metrics: ToolMetrics,
+    tool_health: ToolHealth,
+    telemetry: Option<Arc<TelemetrySink>>,
+    mode_gate: Option<ModeGate>,
+    step_policy: Option<StepPolicy>,
+    finished: Option<ExecutionOutcome>,
+    last_outcome: Option<ExecutionOutcome>,
+    change_review: Option<Arc<dyn ChangeReview>>,
+    loop_guard: LoopGuard,
+    tool_fit_report: Option<ToolFitReport>,
+    overflow_summarizer: Option<Arc<OverflowSummarizer>>,
+    output_pager: Option<Arc<OutputPager>>,
+    file_tracker: FileTracker,
+    mention_cache: Option<MentionCache>,
 }
 
+/// Result recorded for tool calls that follow a `finish` call in the same response
+const SKIPPED_AFTER_FINISH_MESSAGE: &str =
+    "Not run: the task was already finished by an earlier finish call.";
+
 /// Combines reasoning text from two independent sources.
 ///
 /// `raw` is the structured `CompletionResponse.reasoning` field populated by the
@@ -77,6 +132,87 @@ fn combine_reasoning(raw: Option<String>, tags: Option<String>) -> Option<String
     }
 }
 
+/// Sent once when a model that needs nudging names a tool instead of calling it
+fn tool_call_nudge(tool: &str) -> String {
+    format!(
+        "You mentioned `{}` but did not call it. If the task needs a tool, call it now \
+         with a tool call instead of describing it. Otherwise, give your final answer again.",
+        tool
+    )
+}
+
+/// Returns the first offered tool, other than `finish`, named in `text`
+fn mentioned_tool<'a>(text: &str, definitions: &'a [serde_json::Value]) -> Option<&'a str> {
+    definitions
+        .iter()
+        .filter_map(|definition| {
+            definition
+                .get("function")
+                .unwrap_or(definition)
+                .get("name")?
+                .as_str()
+        })
+        .filter(|name| *name != FINISH_TOOL_NAME)
+        .find(|name| {
+            text.match_indices(name).any(|(start, _)| {
+                let is_word = |c: char| c.is_alphanumeric() || c == '_';
+                !text[..start].ends_with(is_word)
+                    && !text[start + name.len()..].starts_with(is_word)
+            })
+        })
+}
+
+/// Splits a response's tool calls into those to run now and those beyond
+/// the policy's per-turn limit.
+fn split_tool_calls<'a>(
+    tool_calls: &'a [ToolCall],
+    policy: &ToolCallPolicy,
+) -> (&'a [ToolCall], &'a [ToolCall]) {
+    let limit = policy
+        .calls_per_turn()
+        .unwrap_or(tool_calls.len())
+        .min(tool_calls.len());
+    tool_calls.split_at(limit)
+}
+
+/// Replaces tool-call arguments that are not valid JSON with their repair
+///
+/// Each repair is logged and counted in `tool_call_argument_repairs_total`.
+/// Arguments that cannot be repaired are left as received and counted with
+/// the `failed` label; running the call returns a corrective tool result.
+fn repair_tool_call_arguments(tool_calls: &mut [ToolCall]) {
+    for tool_call in tool_calls {
+        let name = &tool_call.function.name;
+        match parse_tool_arguments(&tool_call.function.arguments) {
+            Ok(parsed) if parsed.was_repaired() => {
+                let repairs: Vec<&str> = parsed
+                    .repairs
+                    .iter()
+                    .map(|repair| repair.as_str())
+                    .collect();
+                warn!(tool = %name, repairs = %repairs.join(","), "Repaired malformed tool call arguments");
+                for repair in repairs {
+                    metrics::increment_counter!(
+                        "tool_call_argument_repairs_total",
+                        "tool" => name.to_string(),
+                        "repair" => repair
+                    );
+                }
+                tool_call.function.arguments = parsed.value.to_string();
+            }
+            Ok(_) => {}
+            Err(malformed) => {
+                warn!(tool = %name, error = %malformed.error, "Could not repair tool call arguments");
+                metrics::increment_counter!(
+                    "tool_call_argument_repairs_total",
+                    "tool" => name.to_string(),
+                    "repair" => "failed"
+                );
+            }
+        }
+    }
+}
+
 impl Agent {
     /// Creates a new agent instance
     ///
@@ -115,9 +251,23 @@ impl Agent {
             provider: Arc::new(provider),
             conversation,
             tools,
+            loop_guard: LoopGuard::new(config.tools.loop_detection.clone()),
+            tool_health: ToolHealth::new(config.tools.health.clone()),
             config,
             accumulated_usage: Arc::new(Mutex::new(None)),
             transient_system_messages: Vec::new(),
+            tool_metrics: ToolMetrics::new(),
+            telemetry: None,
+            mode_gate: None,
+            step_policy: None,
+            finished: None,
+            last_outcome: None,
+            change_review: None,
+            tool_fit_report: None,
+            overflow_summarizer: None,
+            output_pager: None,
+            file_tracker: FileTracker::new(),
+            mention_cache: None,
         })
     }
 
@@ -161,9 +311,23 @@ impl Agent {
             provider: Arc::from(provider),
             conversation,
             tools,
+            loop_guard: LoopGuard::new(config.tools.loop_detection.clone()),
+            tool_health: ToolHealth::new(config.tools.health.clone()),
             config,
             accumulated_usage: Arc::new(Mutex::new(None)),
             transient_system_messages: Vec::new(),
+            tool_metrics: ToolMetrics::new(),
+            telemetry: None,
+            mode_gate: None,
+            step_policy: None,
+            finished: None,
+            last_outcome: None,
+            change_review: None,
+            tool_fit_report: None,
+            overflow_summarizer: None,
+            output_pager: None,
+            file_tracker: FileTracker::new(),
+            mention_cache: None,
         })
     }
 
@@ -237,9 +401,23 @@ impl Agent {
             provider, // Use provided Arc directly (no wrapping)
             conversation,
             tools,
+            loop_guard: LoopGuard::new(config.tools.loop_detection.clone()),
+            tool_health: ToolHealth::new(config.tools.health.clone()),
             config,
             accumulated_usage: Arc::new(Mutex::new(None)),
             transient_system_messages: Vec::new(),
+            tool_metrics: ToolMetrics::new(),
+            telemetry: None,
+            mode_gate: None,
+            step_policy: None,
+            finished: None,
+            last_outcome: None,
+            change_review: None,
+            tool_fit_report: None,
+            overflow_summarizer: None,
+            output_pager: None,
+            file_tracker: FileTracker::new(),
+            mention_cache: None,
         })
     }
 
@@ -310,9 +488,23 @@ impl Agent {
             provider: Arc::from(provider),
             conversation,
             tools,
+            loop_guard: LoopGuard::new(config.tools.loop_detection.clone()),
+            tool_health: ToolHealth::new(config.tools.health.clone()),
             config,
             accumulated_usage: Arc::new(Mutex::new(None)),
             transient_system_messages: Vec::new(),
+            tool_metrics: ToolMetrics::new(),
+            telemetry: None,
+            mode_gate: None,
+            step_policy: None,
+            finished: None,
+            last_outcome: None,
+            change_review: None,
+            tool_fit_report: None,
+            overflow_summarizer: None,
+            output_pager: None,
+            file_tracker: FileTracker::new(),
+            mention_cache: None,
         })
     }
 
@@ -375,9 +567,23 @@ impl Agent {
             provider,
             conversation,
             tools,
+            loop_guard: LoopGuard::new(config.tools.loop_detection.clone()),
+            tool_health: ToolHealth::new(config.tools.health.clone()),
             config,
             accumulated_usage: Arc::new(Mutex::new(None)),
             transient_system_messages: Vec::new(),
+            tool_metrics: ToolMetrics::new(),
+            telemetry: None,
+            mode_gate: None,
+            step_policy: None,
+            finished: None,
+            last_outcome: None,
+            change_review: None,
+            tool_fit_report: None,
+            overflow_summarizer: None,
+            output_pager: None,
+            file_tracker: FileTracker::new(),
+            mention_cache: None,
         })
     }
 
@@ -394,6 +600,7 @@ impl Agent {
     /// * `config` - Agent configuration (limits, timeouts, etc.)
     /// * `mode` - The ChatMode (Planning or Write)
     /// * `safety` - The SafetyMode (AlwaysConfirm or NeverConfirm)
+    /// * `style` - Prompt style selected for the active model
     ///
     /// # Returns
     ///
@@ -409,6 +616,7 @@ impl Agent {
     /// use xzatoma::agent::Agent;
     /// use xzatoma::chat_mode::{ChatMode, SafetyMode};
     /// use xzatoma::config::AgentConfig;
+    /// use xzatoma::prompts::PromptStyle;
     /// use xzatoma::tools::ToolRegistry;
     ///
     /// # async fn example() -> xzatoma::error::Result<()> {
@@ -421,6 +629,7 @@ impl Agent {
     ///     config,
     ///     ChatMode::Planning,
     ///     SafetyMode::AlwaysConfirm,
+    ///     PromptStyle::Full,
     /// )?;
     /// # Ok(())
     /// # }
@@ -431,6 +640,7 @@ impl Agent {
         config: AgentConfig,
         mode: ChatMode,
         safety: SafetyMode,
+        style: prompts::PromptStyle,
     ) -> Result<Self> {
         // Validate configuration
         if config.max_turns == 0 {
@@ -446,18 +656,35 @@ impl Agent {
         );
 
         // Build and add mode-specific system prompt
-        let system_prompt = prompts::build_system_prompt(mode, safety);
+        let system_prompt = prompts::build_system_prompt(mode, safety, style);
         conversation.add_system_message(system_prompt);
 
-        debug!("Created agent with mode={:?} safety={:?}", mode, safety);
+        debug!(
+            "Created agent with mode={:?} safety={:?} style={:?}",
+            mode, safety, style
+        );
 
         Ok(Self {
             provider: Arc::from(provider),
             conversation,
             tools,
+            loop_guard: LoopGuard::new(config.tools.loop_detection.clone()),
+            tool_health: ToolHealth::new(config.tools.health.clone()),
             config,
             accumulated_usage: Arc::new(Mutex::new(None)),
             transient_system_messages: Vec::new(),
+            tool_metrics: ToolMetrics::new(),
+            telemetry: None,
+            mode_gate: None,
+            step_policy: None,
+            finished: None,
+            last_outcome: None,
+            change_review: None,
+            tool_fit_report: None,
+            overflow_summarizer: None,
+            output_pager: None,
+            file_tracker: FileTracker::new(),
+            mention_cache: None,
         })
     }
 
@@ -559,243 +786,95 @@ impl Agent {
         cancellation_token: &CancellationToken,
         observer: &mut dyn AgentObserver,
     ) -> Result<String> {
-        if cancellation_token.is_cancelled() {
-            observer.on_event(AgentExecutionEvent::CancellationRequested);
-            return Err(XzatomaError::Cancelled);
-        }
-
-        observer.on_event(AgentExecutionEvent::PromptStarted);
-
-        let start_time = Instant::now();
-        let timeout = Duration::from_secs(self.config.timeout_seconds);
-
-        self.conversation.add_user_message(user_prompt.into());
-
-        let mut iteration = 0;
-
-        loop {
-            if cancellation_token.is_cancelled() {
-                observer.on_event(AgentExecutionEvent::CancellationRequested);
-                return Err(XzatomaError::Cancelled);
-            }
-
-            iteration += 1;
-
-            if iteration > self.config.max_turns {
-                warn!("Maximum iterations ({}) exceeded", self.config.max_turns);
-                let error = XzatomaError::MaxIterationsExceeded {
-                    limit: self.config.max_turns,
-                    message: format!(
-                        "Agent exceeded maximum iteration limit of {}",
-                        self.config.max_turns
-                    ),
-                };
-                observer.on_event(AgentExecutionEvent::ExecutionFailed {
-                    error: error.to_string(),
-                });
-                return Err(error);
-            }
-
-            if start_time.elapsed() > timeout {
-                warn!("Agent execution timeout after {:?}", start_time.elapsed());
-                let error = XzatomaError::Config(format!(
-                    "Agent execution timeout after {} seconds",
-                    self.config.timeout_seconds
-                ));
-                observer.on_event(AgentExecutionEvent::ExecutionFailed {
-                    error: error.to_string(),
-                });
-                return Err(error);
-            }
-
-            debug!(
-                "Iteration {}/{}, tokens: {}/{}",
-                iteration,
-                self.config.max_turns,
-                self.conversation.token_count(),
-                self.conversation.max_tokens()
-            );
-
-            let tool_definitions = self.tools.all_definitions();
-            let prompt_messages = self.messages_with_transient_system_messages();
-
-            observer.on_event(AgentExecutionEvent::ProviderRequestStarted);
-
-            let completion_response = tokio::select! {
-                result = self.provider.complete(&prompt_messages, &tool_definitions) => result?,
-                _ = cancellation_token.cancelled() => {
-                    observer.on_event(AgentExecutionEvent::CancellationRequested);
-                    return Err(XzatomaError::Cancelled);
-                }
-            };
-
-            let raw_reasoning = completion_response.reasoning;
-            let mut message = completion_response.message;
-            debug!("Provider response: {:?}", message);
-
-            // Strip inline thinking tags from the assistant text. Any text enclosed in
-            // thinking tag blocks is removed from the message content before it enters
-            // conversation history, and the extracted content is collected as tag_reasoning.
-            let tag_reasoning = if let Some(text) = message.content.take() {
-                let (clean, tag_r) = extract_thinking(&text);
-                message.content = Some(clean);
-                tag_r
-            } else {
-                None
-            };
-
-            // Emit a ReasoningEmitted event when reasoning is available from either the
-            // structured CompletionResponse.reasoning field or extracted thinking tags.
-            if let Some(combined) = combine_reasoning(raw_reasoning, tag_reasoning) {
-                observer.on_event(AgentExecutionEvent::ReasoningEmitted { text: combined });
-            }
-
-            if let Some(usage) = completion_response.usage {
-                self.conversation.update_from_provider_usage(&usage);
-                let mut accumulated = self.accumulated_usage.lock().unwrap();
-                if let Some(existing) = *accumulated {
-                    *accumulated = Some(TokenUsage::new(
-                        existing.prompt_tokens + usage.prompt_tokens,
-                        existing.completion_tokens + usage.completion_tokens,
-                    ));
-                } else {
-                    *accumulated = Some(usage);
-                }
-                drop(accumulated);
-            }
-
-            // Emit context window state regardless of whether provider usage was returned.
-            // get_context_info() prefers provider usage over the heuristic when available.
-            let ctx = self.get_context_info(self.conversation.max_tokens());
-            observer.on_event(AgentExecutionEvent::ContextWindowUpdated {
-                used_tokens: ctx.used_tokens as u64,
-                max_tokens: ctx.max_tokens as u64,
-            });
-
-            let has_tool_calls = message.tool_calls.as_ref().is_some_and(|tc| !tc.is_empty());
-
-            observer.on_event(AgentExecutionEvent::ProviderResponseReceived {
-                text: message.content.clone(),
-                has_tool_calls,
-            });
-
-            if let Some(text) = &message.content {
-                if !text.is_empty() {
-                    observer
-                        .on_event(AgentExecutionEvent::AssistantTextEmitted { text: text.clone() });
-                }
-            }
-
-            self.conversation.add_message(message.clone());
-
-            if let Some(tool_calls) = &message.tool_calls {
-                if tool_calls.is_empty() {
-                    debug!("Provider returned empty tool calls, stopping");
-                    break;
-                }
-
-                debug!("Executing {} tool calls", tool_calls.len());
-
-                for tool_call in tool_calls {
-                    if cancellation_token.is_cancelled() {
-                        observer.on_event(AgentExecutionEvent::CancellationRequested);
-                        return Err(XzatomaError::Cancelled);
-                    }
-
-                    observer.on_event(AgentExecutionEvent::ToolCallStarted {
-                        id: tool_call.id.clone(),
-                        name: tool_call.function.name.clone(),
-                        arguments: tool_call.function.arguments.clone(),
-                    });
-
-                    let result = tokio::select! {
-                        result = self.execute_tool_call(tool_call) => result,
-                        _ = cancellation_token.cancelled() => {
-                            observer.on_event(AgentExecutionEvent::CancellationRequested);
-                            return Err(XzatomaError::Cancelled);
-                        }
-                    };
-
-                    match result {
-                        Ok(tool_result) => {
-                            observer.on_event(AgentExecutionEvent::ToolCallCompleted {
-                                id: tool_call.id.clone(),
-                                name: tool_call.function.name.clone(),
-                                output: tool_result.output.clone(),
-                            });
-                            self.conversation
-                                .add_tool_result(&tool_call.id, tool_result.to_message());
-                        }
-                        Err(error) => {
-                            observer.on_event(AgentExecutionEvent::ToolCallFailed {
-                                id: tool_call.id.clone(),
-                                name: tool_call.function.name.clone(),
-                                error: error.to_string(),
-                            });
-                            return Err(error);
-                        }
-                    }
-                }
+        self.execute_prompt(user_prompt.into(), None, cancellation_token, observer)
+            .await
+    }
 
-                let auto_threshold = self.config.conversation.auto_summary_threshold as f64;
-                if self.conversation.should_auto_summarize(auto_threshold) {
-                    warn!(
-                        "Context window critical (>{}%), triggering automatic summarization",
-                        (auto_threshold * 100.0) as u8
-                    );
-                    match self.perform_auto_summarization().await {
-                        Ok(_) => {
-                            info!("Automatic summarization complete, conversation pruned");
-                        }
-                        Err(error) => {
-                            warn!(
-                                "Automatic summarization failed: {}. Continuing with pruning.",
-                                error
-                            );
-                            self.conversation.prune_if_needed();
-                        }
-                    }
-                }
+    /// Executes a prompt with what is left of a turn and time budget
+    ///
+    /// Behaves like [`Agent::execute_with_observer`], except that the prompt
+    /// continues `budget` instead of starting with a fresh one, and the
+    /// turns and time it used are added to `budget` when it returns. Plan
+    /// steps use this so that retrying a step never extends its budget.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_prompt` - The user's input prompt.
+    /// * `budget` - Budget to continue; updated with what the prompt used.
+    /// * `cancellation_token` - Token checked at safe boundaries.
+    /// * `observer` - Receives [`AgentExecutionEvent`] values as execution proceeds.
+    ///
+    /// # Errors
+    ///
+    /// Returns the budget's error once it runs out, and the same errors as
+    /// [`Agent::execute_with_observer`] otherwise.
+    pub async fn execute_with_budget(
+        &mut self,
+        user_prompt: impl Into<String>,
+        budget: &mut TurnBudget,
+        cancellation_token: &CancellationToken,
+        observer: &mut dyn AgentObserver,
+    ) -> Result<String> {
+        self.execute_prompt(
+            user_prompt.into(),
+            Some(budget),
+            cancellation_token,
+            observer,
+        )
+        .await
+    }
 
-                continue;
-            }
+    /// Runs a prompt inside the agent span, reporting it to telemetry
+    async fn execute_prompt(
+        &mut self,
+        user_prompt: String,
+        budget: Option<&mut TurnBudget>,
+        cancellation_token: &CancellationToken,
+        observer: &mut dyn AgentObserver,
+    ) -> Result<String> {
+        let span = trace_context::agent_span(&self.conversation.id().to_string());
+        let Some(telemetry) = self.telemetry.clone() else {
+            let result = self
+                .run_prompt(user_prompt, budget, cancellation_token, observer)
+                .instrument(span.clone())
+                .await;
+            trace_context::record_success(&span, result.is_ok());
+            return result;
+        };
 
-            if message.content.is_some() {
-                debug!("Provider returned final response, stopping");
-                break;
-            }
+        let mut observer = TelemetryObserver::new(telemetry, observer);
+        let result = self
+            .run_prompt(user_prompt, budget, cancellation_token, &mut observer)
+            .instrument(span.clone())
+            .await;
+        trace_context::record_success(&span, result.is_ok());
+        observer.finish(&result);
+        result
+    }
 
-            warn!("Provider returned neither content nor tool calls");
-            let error = XzatomaError::Provider(
-                "Provider returned invalid response (no content or tool calls)".to_string(),
-            );
-            observer.on_event(AgentExecutionEvent::ExecutionFailed {
-                error: error.to_string(),
-            });
-            return Err(error);
+    /// Runs the execution loop for a user prompt
+    async fn run_prompt(
+        &mut self,
+        user_prompt: String,
+        budget: Option<&mut TurnBudget>,
+        cancellation_token: &CancellationToken,
+        observer: &mut dyn AgentObserver,
+    ) -> Result<String> {
+        if cancellation_token.is_cancelled() {
+            observer.on_event(AgentExecutionEvent::CancellationRequested);
+            return Err(XzatomaError::Cancelled);
         }
 
-        let final_message = self
-            .conversation
-            .messages()
-            .iter()
-            .rev()
-            .find(|m| m.role == "assistant")
-            .and_then(|m| m.content.as_ref())
-            .cloned()
-            .unwrap_or_else(|| "No response from assistant".to_string());
-
-        observer.on_event(AgentExecutionEvent::ExecutionCompleted {
-            response: final_message.clone(),
-        });
-
-        info!(
-            "Agent execution completed in {} iterations, {} seconds",
-            iteration,
-            start_time.elapsed().as_secs()
-        );
+        observer.on_event(AgentExecutionEvent::PromptStarted);
 
-        Ok(final_message)
+        let Some(budget) = budget else {
+            let mut session = self.start_session(user_prompt);
+            return session.run_to_end(cancellation_token, observer).await;
+        };
+        let mut session = self.start_session_with_budget(user_prompt, *budget);
+        let result = session.run_to_end(cancellation_token, observer).await;
+        *budget = session.budget();
+        result
     }
 
     /// Executes the agent with already-constructed provider messages.
@@ -898,6 +977,27 @@ impl Agent {
         messages: Vec<Message>,
         cancellation_token: &CancellationToken,
         observer: &mut dyn AgentObserver,
+    ) -> Result<String> {
+        let Some(telemetry) = self.telemetry.clone() else {
+            return self
+                .run_provider_messages(messages, cancellation_token, observer)
+                .await;
+        };
+
+        let mut observer = TelemetryObserver::new(telemetry, observer);
+        let result = self
+            .run_provider_messages(messages, cancellation_token, &mut observer)
+            .await;
+        observer.finish(&result);
+        result
+    }
+
+    /// Runs the execution loop for provider messages
+    async fn run_provider_messages(
+        &mut self,
+        messages: Vec<Message>,
+        cancellation_token: &CancellationToken,
+        observer: &mut dyn AgentObserver,
     ) -> Result<String> {
         if messages.is_empty() {
             return Err(XzatomaError::Provider(
@@ -919,247 +1019,553 @@ impl Agent {
             });
         }
 
-        let start_time = Instant::now();
-        let timeout = Duration::from_secs(self.config.timeout_seconds);
-
         info!("Starting agent execution from provider messages");
 
-        for message in messages {
-            self.conversation.add_message(message);
-        }
+        let mut session = self.start_session_with_messages(messages)?;
+        session.run_to_end(cancellation_token, observer).await
+    }
+
+    /// Clears the per-prompt state before a new prompt runs
+    pub(super) fn begin_prompt(&mut self) {
+        self.loop_guard.reset();
+        self.finished = None;
+        self.last_outcome = None;
+    }
+
+    /// Returns the agent configuration
+    pub(super) fn config(&self) -> &AgentConfig {
+        &self.config
+    }
 
-        let mut iteration = 0;
+    /// Sets the outcome of a restored session that had already ended
+    pub(super) fn restore_outcome(&mut self, outcome: Option<ExecutionOutcome>) {
+        self.last_outcome = outcome;
+    }
+
+    /// Performs one model call and runs the tool calls it asks for
+    ///
+    /// Budgets are checked by the caller; see
+    /// [`AgentSession::next_turn`](super::session::AgentSession::next_turn).
+    ///
+    /// # Arguments
+    ///
+    /// * `first_turn` - Whether this is the first model call of the prompt
+    /// * `deadline` - When the prompt's time budget runs out
+    /// * `cancellation_token` - Token checked at safe boundaries
+    /// * `observer` - Receives execution events
+    ///
+    /// # Returns
+    ///
+    /// Returns true when tool calls ran or the model was reminded to use
+    /// its tools, and the model should be asked again, and false when the
+    /// model answered in text or called `finish`.
+    pub(super) async fn run_turn(
+        &mut self,
+        first_turn: bool,
+        deadline: Option<tokio::time::Instant>,
+        cancellation_token: &CancellationToken,
+        observer: &mut dyn AgentObserver,
+    ) -> Result<bool> {
+        let tool_definitions = self.request_tool_definitions()?;
+        let policy = self.negotiate_tool_calls(first_turn, !tool_definitions.is_empty())?;
 
-        loop {
-            if cancellation_token.is_cancelled() {
+        observer.on_event(AgentExecutionEvent::ProviderRequestStarted);
+
+        let (completion_response, compaction) = tokio::select! {
+            result = self.complete_with_overflow_recovery(&tool_definitions, deadline) => result?,
+            _ = cancellation_token.cancelled() => {
                 observer.on_event(AgentExecutionEvent::CancellationRequested);
                 return Err(XzatomaError::Cancelled);
             }
+        };
 
-            iteration += 1;
+        if let Some((limit, compaction)) = compaction {
+            observer.on_event(AgentExecutionEvent::ContextCompacted {
+                limit,
+                messages_summarized: compaction.messages_summarized,
+                tool_outputs_dropped: compaction.tool_outputs_dropped,
+                tokens_before: compaction.tokens_before,
+                tokens_after: compaction.tokens_after,
+            });
+        }
+        self.report_pruning(observer);
+
+        let raw_reasoning = completion_response.reasoning;
+        let mut message = completion_response.message;
+        debug!("Provider response: {:?}", message);
+
+        // Strip inline thinking tags from the assistant text. Any text enclosed in
+        // thinking tag blocks is removed from the message content before it enters
+        // conversation history, and the extracted content is collected as tag_reasoning.
+        let tag_reasoning = if let Some(text) = message.content.take() {
+            let (clean, tag_r) = extract_thinking(&text);
+            message.content = Some(clean);
+            tag_r
+        } else {
+            None
+        };
 
-            if iteration > self.config.max_turns {
-                warn!("Maximum iterations ({}) exceeded", self.config.max_turns);
-                let error = XzatomaError::MaxIterationsExceeded {
-                    limit: self.config.max_turns,
-                    message: format!(
-                        "Agent exceeded maximum iteration limit of {}",
-                        self.config.max_turns
-                    ),
-                };
-                observer.on_event(AgentExecutionEvent::ExecutionFailed {
-                    error: error.to_string(),
-                });
-                return Err(error);
-            }
+        // Emit a ReasoningEmitted event when reasoning is available from either the
+        // structured CompletionResponse.reasoning field or extracted thinking tags.
+        if let Some(combined) = combine_reasoning(raw_reasoning, tag_reasoning) {
+            observer.on_event(AgentExecutionEvent::ReasoningEmitted { text: combined });
+        }
 
-            if start_time.elapsed() > timeout {
-                warn!("Agent execution timeout after {:?}", start_time.elapsed());
-                let error = XzatomaError::Config(format!(
-                    "Agent execution timeout after {} seconds",
-                    self.config.timeout_seconds
-                ));
-                observer.on_event(AgentExecutionEvent::ExecutionFailed {
-                    error: error.to_string(),
-                });
-                return Err(error);
-            }
+        // Repair malformed tool-call arguments before they enter history, so
+        // review, loop detection, and execution all see the repaired call
+        if let Some(tool_calls) = &mut message.tool_calls {
+            repair_tool_call_arguments(tool_calls);
+        }
 
-            debug!(
-                "Iteration {}/{}, tokens: {}/{}",
-                iteration,
-                self.config.max_turns,
-                self.conversation.token_count(),
-                self.conversation.max_tokens()
-            );
+        if completion_response.cached {
+            let usage = completion_response.usage.unwrap_or_default();
+            observer.on_event(AgentExecutionEvent::ProviderCacheHit {
+                prompt_tokens: usage.prompt_tokens as u64,
+                completion_tokens: usage.completion_tokens as u64,
+            });
+        }
 
-            let tool_definitions = self.tools.all_definitions();
-            let prompt_messages = self.messages_with_transient_system_messages();
+        if let Some(usage) = completion_response.usage {
+            self.conversation.update_from_provider_usage(&usage);
 
-            observer.on_event(AgentExecutionEvent::ProviderRequestStarted);
+            // Cached responses cost nothing, so they are left out of the
+            // billable totals.
+            if !completion_response.cached {
+                observer.on_event(AgentExecutionEvent::TokenUsageReported {
+                    prompt_tokens: usage.prompt_tokens as u64,
+                    completion_tokens: usage.completion_tokens as u64,
+                });
+                self.add_usage(usage);
+            }
+        }
 
-            let completion_response = tokio::select! {
-                result = self.provider.complete(&prompt_messages, &tool_definitions) => result?,
-                _ = cancellation_token.cancelled() => {
-                    observer.on_event(AgentExecutionEvent::CancellationRequested);
-                    return Err(XzatomaError::Cancelled);
-                }
-            };
+        // Emit context window state regardless of whether provider usage was returned.
+        // get_context_info() prefers provider usage over the heuristic when available.
+        let ctx = self.get_context_info(self.conversation.max_tokens());
+        observer.on_event(AgentExecutionEvent::ContextWindowUpdated {
+            used_tokens: ctx.used_tokens as u64,
+            max_tokens: ctx.max_tokens as u64,
+        });
 
-            let raw_reasoning = completion_response.reasoning;
-            let mut message = completion_response.message;
-            debug!("Provider response: {:?}", message);
+        let has_tool_calls = message.tool_calls.as_ref().is_some_and(|tc| !tc.is_empty());
 
-            // Strip inline thinking tags from the assistant text.
-            let tag_reasoning = if let Some(text) = message.content.take() {
-                let (clean, tag_r) = extract_thinking(&text);
-                message.content = Some(clean);
-                tag_r
-            } else {
-                None
-            };
+        observer.on_event(AgentExecutionEvent::ProviderResponseReceived {
+            text: message.content.clone(),
+            has_tool_calls,
+        });
 
-            // Emit a ReasoningEmitted event when reasoning is available.
-            if let Some(combined) = combine_reasoning(raw_reasoning, tag_reasoning) {
-                observer.on_event(AgentExecutionEvent::ReasoningEmitted { text: combined });
+        if let Some(text) = &message.content {
+            if !text.is_empty() {
+                observer.on_event(AgentExecutionEvent::AssistantTextEmitted { text: text.clone() });
             }
+        }
 
-            if let Some(usage) = completion_response.usage {
-                self.conversation.update_from_provider_usage(&usage);
+        self.conversation.add_message(message.clone());
 
-                let mut accumulated_usage = self.accumulated_usage.lock().unwrap();
-                if let Some(existing) = *accumulated_usage {
-                    *accumulated_usage = Some(TokenUsage::new(
-                        existing.prompt_tokens + usage.prompt_tokens,
-                        existing.completion_tokens + usage.completion_tokens,
-                    ));
-                } else {
-                    *accumulated_usage = Some(usage);
-                }
-                drop(accumulated_usage);
+        if let Some(tool_calls) = &message.tool_calls {
+            if tool_calls.is_empty() {
+                debug!("Provider returned empty tool calls, stopping");
+                return Ok(false);
             }
 
-            // Emit context window state regardless of whether provider usage was returned.
-            // get_context_info() prefers provider usage over the heuristic when available.
-            let ctx = self.get_context_info(self.conversation.max_tokens());
-            observer.on_event(AgentExecutionEvent::ContextWindowUpdated {
-                used_tokens: ctx.used_tokens as u64,
-                max_tokens: ctx.max_tokens as u64,
-            });
+            let (tool_calls, deferred) = split_tool_calls(tool_calls, &policy);
+            debug!(
+                "Executing {} tool calls, deferring {}",
+                tool_calls.len(),
+                deferred.len()
+            );
 
-            let has_tool_calls = message.tool_calls.as_ref().is_some_and(|tc| !tc.is_empty());
+            self.run_tool_calls(tool_calls, cancellation_token, observer)
+                .await?;
 
-            observer.on_event(AgentExecutionEvent::ProviderResponseReceived {
-                text: message.content.clone(),
-                has_tool_calls,
-            });
+            self.defer_tool_calls(deferred, tool_calls.len());
 
-            if let Some(text) = &message.content {
-                if !text.is_empty() {
-                    observer
-                        .on_event(AgentExecutionEvent::AssistantTextEmitted { text: text.clone() });
-                }
+            if self.finished.is_some() {
+                debug!("Model called finish, stopping");
+                return Ok(false);
             }
 
-            self.conversation.add_message(message.clone());
-
-            if let Some(tool_calls) = &message.tool_calls {
-                if tool_calls.is_empty() {
-                    debug!("Provider returned empty tool calls, stopping");
-                    break;
-                }
-
-                debug!("Executing {} tool calls", tool_calls.len());
-
-                for tool_call in tool_calls {
-                    if cancellation_token.is_cancelled() {
-                        observer.on_event(AgentExecutionEvent::CancellationRequested);
-                        return Err(XzatomaError::Cancelled);
+            let auto_threshold = self.config.conversation.auto_summary_threshold as f64;
+            if self.conversation.should_auto_summarize(auto_threshold) {
+                warn!(
+                    "Context window critical (>{}%), triggering automatic summarization",
+                    (auto_threshold * 100.0) as u8
+                );
+                let messages_before = self.conversation.messages().len();
+                match self.perform_auto_summarization().await {
+                    Ok(_) => {
+                        info!("Automatic summarization complete, conversation pruned");
+                        observer.on_event(AgentExecutionEvent::ConversationSummarized {
+                            messages_before,
+                            messages_after: self.conversation.messages().len(),
+                            tokens_after: self.conversation.token_count(),
+                        });
                     }
-
-                    observer.on_event(AgentExecutionEvent::ToolCallStarted {
-                        id: tool_call.id.clone(),
-                        name: tool_call.function.name.clone(),
-                        arguments: tool_call.function.arguments.clone(),
-                    });
-
-                    let result = tokio::select! {
-                        result = self.execute_tool_call(tool_call) => result,
-                        _ = cancellation_token.cancelled() => {
-                            observer.on_event(AgentExecutionEvent::CancellationRequested);
-                            return Err(XzatomaError::Cancelled);
-                        }
-                    };
-
-                    match result {
-                        Ok(tool_result) => {
-                            observer.on_event(AgentExecutionEvent::ToolCallCompleted {
-                                id: tool_call.id.clone(),
-                                name: tool_call.function.name.clone(),
-                                output: tool_result.output.clone(),
-                            });
-                            self.conversation
-                                .add_tool_result(&tool_call.id, tool_result.to_message());
-                        }
-                        Err(error) => {
-                            observer.on_event(AgentExecutionEvent::ToolCallFailed {
-                                id: tool_call.id.clone(),
-                                name: tool_call.function.name.clone(),
-                                error: error.to_string(),
-                            });
-                            return Err(error);
-                        }
+                    Err(error) => {
+                        warn!(
+                            "Automatic summarization failed: {}. Continuing with pruning.",
+                            error
+                        );
+                        self.conversation.prune_if_needed();
                     }
                 }
+            }
+
+            return Ok(true);
+        }
 
-                let auto_threshold = self.config.conversation.auto_summary_threshold as f64;
-                if self.conversation.should_auto_summarize(auto_threshold) {
-                    warn!(
-                        "Context window critical (>{}%), triggering automatic summarization",
-                        (auto_threshold * 100.0) as u8
+        if let Some(text) = &message.content {
+            // Models flagged by their quirks sometimes describe the first
+            // tool call in prose; they get one reminder per prompt
+            if first_turn && self.provider.needs_tool_call_nudge() {
+                if let Some(tool) = mentioned_tool(text, &tool_definitions) {
+                    info!(
+                        tool,
+                        "Model described a tool call instead of making it, nudging"
                     );
-                    match self.perform_auto_summarization().await {
-                        Ok(_) => {
-                            info!("Automatic summarization complete, conversation pruned");
-                        }
-                        Err(error) => {
-                            warn!(
-                                "Automatic summarization failed: {}. Continuing with pruning.",
-                                error
-                            );
-                            self.conversation.prune_if_needed();
-                        }
-                    }
+                    self.conversation
+                        .add_message(Message::user(tool_call_nudge(tool)));
+                    return Ok(true);
                 }
-
-                continue;
-            }
-
-            if message.content.is_some() {
-                debug!("Provider returned final response, stopping");
-                break;
             }
-
-            warn!("Provider returned neither content nor tool calls");
-            let error = XzatomaError::Provider(
-                "Provider returned invalid response (no content or tool calls)".to_string(),
-            );
-            observer.on_event(AgentExecutionEvent::ExecutionFailed {
-                error: error.to_string(),
-            });
-            return Err(error);
+            debug!("Provider returned final response, stopping");
+            return Ok(false);
         }
 
-        let final_message = self
-            .conversation
-            .messages()
-            .iter()
-            .rev()
-            .find(|message| message.role == "assistant")
-            .and_then(|message| message.content.as_ref())
-            .cloned()
-            .unwrap_or_else(|| "No response from assistant".to_string());
-
-        observer.on_event(AgentExecutionEvent::ExecutionCompleted {
-            response: final_message.clone(),
-        });
-
-        info!(
-            "Agent execution completed in {} iterations, {} seconds",
-            iteration,
-            start_time.elapsed().as_secs()
+        warn!("Provider returned neither content nor tool calls");
+        let error = XzatomaError::Provider(
+            "Provider returned invalid response (no content or tool calls)".to_string(),
         );
+        observer.on_event(AgentExecutionEvent::ExecutionFailed {
+            error: error.to_string(),
+        });
+        Err(error)
+    }
 
-        Ok(final_message)
+    /// Emits a `ConversationPruned` event for each pruning decision
+    /// recorded since the last call
+    pub(super) fn report_pruning(&mut self, observer: &mut dyn AgentObserver) {
+        for report in self.conversation.take_prune_reports() {
+            observer.on_event(AgentExecutionEvent::ConversationPruned { report });
+        }
     }
 
-    /// Executes a single tool call
-    ///
-    /// # Arguments
-    ///
-    /// * `tool_call` - The tool call to execute
+    /// Runs the tool calls of one response in order
     ///
-    /// # Returns
+    /// Consecutive calls that change files are previewed together. When
+    /// they touch more than one file and a change reviewer is installed,
+    /// the user reviews the whole change set before any of it runs. Approved
+    /// calls then run as usual; rejected calls are answered with
+    /// [`rejected_change_result`] instead. Calls after a successful `finish`
+    /// call are not run.
+    async fn run_tool_calls(
+        &mut self,
+        tool_calls: &[ToolCall],
+        cancellation_token: &CancellationToken,
+        observer: &mut dyn AgentObserver,
+    ) -> Result<()> {
+        let mut next = 0;
+        while next < tool_calls.len() {
+            let (changes, decision) = self.review_change_set(&tool_calls[next..]);
+            let batch_len = changes.len().max(1);
+            for (index, tool_call) in tool_calls[next..next + batch_len].iter().enumerate() {
+                if self.finished.is_some() {
+                    self.conversation
+                        .add_tool_result(&tool_call.id, SKIPPED_AFTER_FINISH_MESSAGE);
+                    continue;
+                }
+                let review = decision
+                    .as_ref()
+                    .map(|decision| (decision.approves(index), &changes[index]));
+                self.run_tool_call(tool_call, review, cancellation_token, observer)
+                    .await?;
+            }
+            next += batch_len;
+        }
+        Ok(())
+    }
+
+    /// Previews the file changes at the start of `tool_calls` and asks the
+    /// change reviewer about them
+    ///
+    /// # Returns
+    ///
+    /// Returns the previewed changes, one per leading call, and the review
+    /// decision. The decision is `None` when no reviewer is installed, the
+    /// agent is in Planning mode, or the changes touch fewer than two files.
+    fn review_change_set(
+        &self,
+        tool_calls: &[ToolCall],
+    ) -> (Vec<FileChange>, Option<ReviewDecision>) {
+        let Some(review) = &self.change_review else {
+            return (Vec::new(), None);
+        };
+        if self.chat_mode() == Some(ChatMode::Planning) {
+            return (Vec::new(), None);
+        }
+
+        let mut staged = StagedFiles::new();
+        let mut changes = Vec::new();
+        for tool_call in tool_calls {
+            let Some(tool) = self.tools.get(&tool_call.function.name) else {
+                break;
+            };
+            let Ok(args) = serde_json::from_str::<serde_json::Value>(&tool_call.function.arguments)
+            else {
+                break;
+            };
+            let Some(change) = tool.preview_change(&args, &staged) else {
+                break;
+            };
+            staged.stage(&change);
+            changes.push(change);
+        }
+
+        let files: std::collections::HashSet<&std::path::Path> =
+            changes.iter().flat_map(FileChange::touched_paths).collect();
+        if files.len() < 2 {
+            return (changes, None);
+        }
+        let decision = review.review(&changes);
+        info!(
+            changes = changes.len(),
+            decision = ?decision,
+            "Reviewed change set"
+        );
+        (changes, Some(decision))
+    }
+
+    /// Runs one tool call and records its result in the conversation
+    ///
+    /// `review` carries the review outcome and the previewed change when
+    /// the call was part of a reviewed change set.
+    async fn run_tool_call(
+        &mut self,
+        tool_call: &ToolCall,
+        review: Option<(bool, &FileChange)>,
+        cancellation_token: &CancellationToken,
+        observer: &mut dyn AgentObserver,
+    ) -> Result<()> {
+        if cancellation_token.is_cancelled() {
+            observer.on_event(AgentExecutionEvent::CancellationRequested);
+            return Err(XzatomaError::Cancelled);
+        }
+
+        observer.on_event(AgentExecutionEvent::ToolCallStarted {
+            id: tool_call.id.clone(),
+            name: tool_call.function.name.clone(),
+            arguments: tool_call.function.arguments.clone(),
+        });
+
+        let result = if let Some((false, change)) = review {
+            Ok(rejected_change_result(change))
+        } else if let Some(refusal) = self
+            .step_policy
+            .as_ref()
+            .and_then(|policy| policy.refusal(&tool_call.function.name))
+        {
+            info!(tool = %tool_call.function.name, "Refused tool call outside the plan step's tools");
+            Ok(refusal)
+        } else if let Some(refusal) = self.gate_tool_call(tool_call) {
+            Ok(refusal)
+        } else {
+            let (read_only, mutates) = self
+                .tools
+                .get(&tool_call.function.name)
+                .map_or((false, false), |tool| (tool.read_only(), tool.mutates()));
+            let key = match self.loop_guard.before_call(
+                &tool_call.function.name,
+                &tool_call.function.arguments,
+                read_only,
+            ) {
+                LoopCheck::Run(key) => key,
+                LoopCheck::Cached(tool_result) => {
+                    debug!(tool = %tool_call.function.name, "Answered repeated tool call from cache");
+                    return self.record_tool_result(tool_call, tool_result, observer);
+                }
+                LoopCheck::Stop(error) => {
+                    warn!(tool = %tool_call.function.name, "Stopping repeated tool call loop");
+                    self.conversation
+                        .add_tool_result(&tool_call.id, format!("Error: {}", error));
+                    observer.on_event(AgentExecutionEvent::ToolCallFailed {
+                        id: tool_call.id.clone(),
+                        name: tool_call.function.name.clone(),
+                        error: error.to_string(),
+                    });
+                    return Err(error);
+                }
+            };
+            let Some(result) = self
+                .execute_tool_call_streaming(tool_call, cancellation_token, observer)
+                .await
+            else {
+                return Err(XzatomaError::Cancelled);
+            };
+            let result =
+                result.map(|tool_result| self.loop_guard.after_call(key, mutates, tool_result));
+            match review {
+                Some(_) => result.map(|tool_result| {
+                    tool_result
+                        .with_metadata(CHANGE_REVIEW_METADATA.to_string(), "approved".to_string())
+                }),
+                None => result,
+            }
+        };
+
+        match result {
+            Ok(tool_result) => {
+                if tool_call.function.name == FINISH_TOOL_NAME && tool_result.success {
+                    self.finished = serde_json::from_str(&tool_call.function.arguments)
+                        .ok()
+                        .and_then(|args| parse_outcome(args).ok());
+                }
+                self.record_tool_result(tool_call, tool_result, observer)
+            }
+            Err(error) => {
+                observer.on_event(AgentExecutionEvent::ToolCallFailed {
+                    id: tool_call.id.clone(),
+                    name: tool_call.function.name.clone(),
+                    error: error.to_string(),
+                });
+                Err(error)
+            }
+        }
+    }
+
+    /// Records how the run ended and returns the final response
+    ///
+    /// A successful `finish` call decides the outcome. Otherwise the last
+    /// assistant text is taken as a successful outcome with the text as its
+    /// summary.
+    pub(super) fn settle_outcome(&mut self) -> String {
+        let outcome = self.finished.take().unwrap_or_else(|| {
+            let text = self
+                .conversation
+                .messages()
+                .iter()
+                .rev()
+                .find(|message| message.role == "assistant")
+                .and_then(|message| message.content.as_ref())
+                .cloned()
+                .unwrap_or_else(|| "No response from assistant".to_string());
+            ExecutionOutcome::from_text(text)
+        });
+        let response = outcome.summary.clone();
+        self.last_outcome = Some(outcome);
+        response
+    }
+
+    /// Reports a completed tool call and adds its result to the conversation
+    fn record_tool_result(
+        &mut self,
+        tool_call: &ToolCall,
+        tool_result: ToolResult,
+        observer: &mut dyn AgentObserver,
+    ) -> Result<()> {
+        observer.on_event(AgentExecutionEvent::ToolCallCompleted {
+            id: tool_call.id.clone(),
+            name: tool_call.function.name.clone(),
+            output: tool_result.output.clone(),
+            status: ToolCallStatus::classify(tool_result.success, tool_result.error.as_deref()),
+        });
+        self.conversation
+            .add_tool_result(&tool_call.id, tool_result.to_message());
+        Ok(())
+    }
+
+    /// Checks a tool call against the mode gate before it is dispatched
+    ///
+    /// Only tools that report
+    /// [`ToolExecutor::mutates`](crate::tools::ToolExecutor::mutates) are gated. When the
+    /// user approves switching to Write mode, the Write mode tools the agent
+    /// does not have yet are registered so the call can run.
+    ///
+    /// # Returns
+    ///
+    /// Returns the refusal to record instead of running the tool, or `None`
+    /// when the call should be dispatched.
+    fn gate_tool_call(&mut self, tool_call: &ToolCall) -> Option<ToolResult> {
+        let gate = self.mode_gate.as_mut()?;
+        let name = &tool_call.function.name;
+        let tool = self.tools.get(name).or_else(|| gate.write_tool(name))?;
+        if !tool.mutates() {
+            return None;
+        }
+
+        let request = EscalationRequest::from_call(name, &tool_call.function.arguments);
+        match gate.check(request) {
+            GateDecision::Allow => None,
+            GateDecision::Escalate(write_tools) => {
+                if let Some(write_tools) = write_tools {
+                    for tool_name in write_tools.tool_names() {
+                        if self.tools.get(&tool_name).is_none() {
+                            if let Some(tool) = write_tools.get(&tool_name) {
+                                self.tools.register(tool_name, tool);
+                            }
+                        }
+                    }
+                }
+                None
+            }
+            GateDecision::Refuse(refusal) => {
+                info!(tool = %name, "Refused mutating tool call in Planning mode");
+                Some(refusal)
+            }
+        }
+    }
+
+    /// Executes a tool call, forwarding its streamed output to the observer
+    ///
+    /// Every streamed line is delivered before this returns, so observers see
+    /// a tool's output lines before its completion event.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` when the cancellation token fires before the tool
+    /// finishes; `CancellationRequested` has then already been emitted.
+    async fn execute_tool_call_streaming(
+        &self,
+        tool_call: &ToolCall,
+        cancellation_token: &CancellationToken,
+        observer: &mut dyn AgentObserver,
+    ) -> Option<Result<ToolResult>> {
+        let (sink, mut lines) = ToolOutputSink::channel();
+        let output_event = |line: ToolOutputLine| AgentExecutionEvent::ToolOutputChunk {
+            id: tool_call.id.clone(),
+            name: tool_call.function.name.clone(),
+            stream: line.stream,
+            line: line.line,
+        };
+
+        let call = self.execute_tool_call(tool_call, sink);
+        tokio::pin!(call);
+        let result = loop {
+            tokio::select! {
+                biased;
+                _ = cancellation_token.cancelled() => {
+                    observer.on_event(AgentExecutionEvent::CancellationRequested);
+                    return None;
+                }
+                Some(line) = lines.recv() => observer.on_event(output_event(line)),
+                result = &mut call => break result,
+            }
+        };
+
+        // Lines pushed just before the tool returned may still be buffered
+        while let Ok(line) = lines.try_recv() {
+            observer.on_event(output_event(line));
+        }
+
+        Some(result)
+    }
+
+    /// Executes a single tool call
+    ///
+    /// # Arguments
+    ///
+    /// * `tool_call` - The tool call to execute
+    ///
+    /// # Returns
     ///
     /// Returns the tool execution result or an error
     ///
@@ -1168,7 +1574,76 @@ impl Agent {
     /// Returns `XzatomaError::Tool` if:
     /// - Tool is not found in registry
     /// - Tool execution fails
-    async fn execute_tool_call(&self, tool_call: &ToolCall) -> Result<ToolResult> {
+    ///
+    /// Arguments that violate the tool's schema do not fail the run; they
+    /// produce an error `ToolResult` that lists the violations.
+    async fn execute_tool_call(
+        &self,
+        tool_call: &ToolCall,
+        sink: ToolOutputSink,
+    ) -> Result<ToolResult> {
+        let started = Instant::now();
+        let span = trace_context::tool_span(
+            &self.conversation.id().to_string(),
+            &tool_call.function.name,
+        );
+        let result = self
+            .dispatch_tool_call(tool_call, sink)
+            .instrument(span.clone())
+            .await;
+        trace_context::record_success(&span, result.as_ref().map(|r| r.success).unwrap_or(false));
+
+        let (status, output_bytes) = match &result {
+            Ok(tool_result) => (
+                ToolCallStatus::classify(tool_result.success, tool_result.error.as_deref()),
+                tool_result.output.len(),
+            ),
+            Err(e) => (ToolCallStatus::classify(false, Some(&e.to_string())), 0),
+        };
+        self.tool_metrics.record(
+            &tool_call.function.name,
+            status,
+            started.elapsed(),
+            output_bytes,
+        );
+        self.record_tool_health(&tool_call.function.name, &result);
+
+        result
+    }
+
+    /// Records a call's outcome in the tool health tracker
+    ///
+    /// Only calls that reached the tool count: unknown tools and arguments
+    /// rejected by schema validation say nothing about the tool's health.
+    fn record_tool_health(&self, tool_name: &str, result: &Result<ToolResult>) {
+        match result {
+            Ok(tool_result) => {
+                let rejected = tool_result
+                    .metadata
+                    .get("argument_validation")
+                    .map(String::as_str)
+                    == Some(ArgumentValidationOutcome::Invalid.as_str());
+                if !rejected {
+                    self.tool_health.record(
+                        tool_name,
+                        tool_result.success,
+                        tool_result.error.as_deref(),
+                    );
+                }
+            }
+            Err(XzatomaError::ToolFailed { reason, .. }) => {
+                self.tool_health.record(tool_name, false, Some(reason))
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// Validates arguments and runs a tool call without recording metrics
+    async fn dispatch_tool_call(
+        &self,
+        tool_call: &ToolCall,
+        sink: ToolOutputSink,
+    ) -> Result<ToolResult> {
         let tool_name = &tool_call.function.name;
         debug!("Executing tool: {}", tool_name);
 
@@ -1178,33 +1653,140 @@ impl Agent {
             .get(tool_name)
             .ok_or_else(|| XzatomaError::Tool(format!("Tool not found: {}", tool_name)))?;
 
-        // Parse arguments
-        let args: serde_json::Value =
-            serde_json::from_str(&tool_call.function.arguments).map_err(|e| {
-                XzatomaError::Tool(format!(
-                    "Failed to parse tool arguments for '{}': {}",
-                    tool_name, e
-                ))
-            })?;
+        // Parse arguments. Arguments that could not be repaired are answered
+        // with a request to resend them instead of failing the turn.
+        let args = match parse_tool_arguments(&tool_call.function.arguments) {
+            Ok(parsed) => parsed.value,
+            Err(malformed) => {
+                return Ok(ToolResult::error(malformed.corrective_message(tool_name))
+                    .with_metadata(
+                        "argument_validation".to_string(),
+                        ArgumentValidationOutcome::Invalid.to_string(),
+                    ));
+            }
+        };
+
+        // Validate arguments against the declared schema. Invalid arguments
+        // are reported back to the model so it can correct the call.
+        let validation = validate_tool_arguments(&tool_executor.tool_definition(), args);
+        metrics::increment_counter!(
+            "tool_argument_validations_total",
+            "tool" => tool_name.to_string(),
+            "outcome" => validation.outcome.as_str()
+        );
+        if !validation.is_valid() {
+            warn!(
+                "Rejected arguments for tool '{}': {}",
+                tool_name,
+                validation.violations.join("; ")
+            );
+            return Ok(
+                ToolResult::error(validation.error_message(tool_name)).with_metadata(
+                    "argument_validation".to_string(),
+                    validation.outcome.to_string(),
+                ),
+            );
+        }
+        if !validation.adjustments.is_empty() {
+            debug!(
+                "Adjusted arguments for tool '{}': {}",
+                tool_name,
+                validation.adjustments.join("; ")
+            );
+        }
+
+        // Paths the call reads or changes, for the file tracker
+        let arguments = &tool_call.function.arguments;
+        let tracked_paths: Vec<String> = if tool_name == "read_file" {
+            path_argument(arguments, "path").into_iter().collect()
+        } else if tool_executor.mutates() {
+            MUTATED_PATH_ARGUMENTS
+                .iter()
+                .filter_map(|name| path_argument(arguments, name))
+                .collect()
+        } else {
+            Vec::new()
+        };
 
         // Execute tool
-        let result = tool_executor.execute(args).await.map_err(|e| {
-            XzatomaError::Tool(format!("Tool '{}' execution failed: {}", tool_name, e))
-        })?;
+        let mut result = tool_executor
+            .execute_streaming(validation.args, sink)
+            .await
+            .map_err(|e| XzatomaError::ToolFailed {
+                tool: tool_name.to_string(),
+                reason: e.to_string(),
+            })?;
+        result.metadata.insert(
+            "argument_validation".to_string(),
+            validation.outcome.to_string(),
+        );
+        if !validation.adjustments.is_empty() {
+            result.metadata.insert(
+                "argument_adjustments".to_string(),
+                validation.adjustments.join("; "),
+            );
+        }
+        if result.success {
+            for path in &tracked_paths {
+                let absolute = self.resolve_tool_path(path);
+                if tool_name == "read_file" {
+                    self.file_tracker.record(&absolute, path);
+                } else {
+                    self.file_tracker.refresh(&absolute);
+                }
+            }
+        }
 
-        // Truncate output if needed
+        // Summarize, page, or truncate output over the size limit
         let max_output_size = self.config.tools.max_output_size;
         let original_len = result.output.len();
-        let truncated_result = result.truncate_if_needed(max_output_size);
+        let fitted_result = match &self.overflow_summarizer {
+            Some(summarizer) => {
+                let shrunk = summarizer.shrink(tool_name, result, max_output_size).await;
+                if let Some(usage) = shrunk.usage {
+                    debug!(
+                        "Tool output summarized from {} bytes using {} tokens",
+                        original_len, usage.total_tokens
+                    );
+                    self.add_usage(usage);
+                }
+                shrunk.result
+            }
+            None => match &self.output_pager {
+                Some(pager) => pager.page_result(tool_name, result, max_output_size),
+                None => result.truncate_if_needed(max_output_size),
+            },
+        };
 
-        if truncated_result.truncated {
+        if fitted_result.truncated {
             debug!(
                 "Tool output truncated from {} to {} bytes",
                 original_len, max_output_size
             );
         }
 
-        Ok(truncated_result)
+        Ok(fitted_result)
+    }
+
+    /// Resolves a path argument against the conversation working directory
+    fn resolve_tool_path(&self, path: &str) -> PathBuf {
+        let base = match self.conversation.cwd() {
+            Some(cwd) => cwd.to_path_buf(),
+            None => std::env::current_dir().unwrap_or_default(),
+        };
+        base.join(path)
+    }
+
+    /// Adds billable token usage to the session total
+    fn add_usage(&self, usage: TokenUsage) {
+        let mut accumulated = self.accumulated_usage.lock().unwrap();
+        *accumulated = Some(match *accumulated {
+            Some(existing) => TokenUsage::new(
+                existing.prompt_tokens + usage.prompt_tokens,
+                existing.completion_tokens + usage.completion_tokens,
+            ),
+            None => usage,
+        });
     }
 
     /// Performs automatic summarization of the conversation
@@ -1259,107 +1841,525 @@ impl Agent {
         &mut self.conversation
     }
 
-    /// Sets transient system messages used only during prompt assembly.
+    /// Returns the per-tool execution metrics collected by this agent
     ///
-    /// These messages are appended to the provider input immediately before each
-    /// completion request and are not stored in `Conversation.messages`.
-    pub fn set_transient_system_messages(&mut self, messages: Vec<String>) {
-        self.transient_system_messages = messages;
+    /// The collector is shared, so a clone keeps observing later calls.
+    pub fn tool_metrics(&self) -> &ToolMetrics {
+        &self.tool_metrics
     }
 
-    /// Clears all transient system messages.
+    /// Returns how the tool definitions were trimmed for the last request
     ///
-    /// This removes any runtime-only prompt injections without modifying
-    /// `Conversation.messages`.
-    pub fn clear_transient_system_messages(&mut self) {
-        self.transient_system_messages.clear();
-    }
-
-    /// Returns the current transient system messages.
-    pub fn transient_system_messages(&self) -> &[String] {
-        &self.transient_system_messages
+    /// `None` when the definitions fit the provider's limits unchanged.
+    pub fn tool_fit_report(&self) -> Option<&ToolFitReport> {
+        self.tool_fit_report.as_ref()
     }
 
-    fn messages_with_transient_system_messages(&self) -> Vec<Message> {
-        if self.transient_system_messages.is_empty() {
-            return self.conversation.messages().to_vec();
+    /// Tool definitions for the next request, fitted to the provider's limits
+    ///
+    /// Trimming is logged when it first happens and whenever it changes.
+    fn request_tool_definitions(&mut self) -> Result<Vec<serde_json::Value>> {
+        let config = &self.config.tools.definition_limits;
+        let limits =
+            ToolDefinitionLimits::resolve(&self.provider.get_provider_capabilities(), config);
+        let definitions = self.tool_health.apply(self.tools.all_definitions());
+        let (definitions, report) = fit_tool_definitions(definitions, &limits, config)?;
+
+        if !report.is_trimmed() {
+            self.tool_fit_report = None;
+        } else if self.tool_fit_report.as_ref() != Some(&report) {
+            warn!("Tool definitions exceed provider limits; {}", report);
+            self.tool_fit_report = Some(report);
         }
+        Ok(definitions)
+    }
 
-        let mut messages = Vec::with_capacity(
-            self.conversation.messages().len() + self.transient_system_messages.len(),
+    /// Negotiates the tool-call parameters for the next request
+    ///
+    /// Resolves the [`ToolCallPolicy`] from the provider's capabilities and
+    /// the `agent.tools.tool_calls` overrides, and passes the resulting
+    /// `tool_choice` and `parallel_tool_calls` values to the provider.
+    fn negotiate_tool_calls(&self, first_request: bool, has_tools: bool) -> Result<ToolCallPolicy> {
+        let policy = ToolCallPolicy::resolve(
+            &self.provider.get_provider_capabilities(),
+            &self.config.tools.tool_calls,
         );
+        self.provider
+            .set_tool_call_options(policy.options(first_request, has_tools))?;
+        Ok(policy)
+    }
 
-        let mut inserted = false;
-        for message in self.conversation.messages() {
-            if !inserted && message.role != "system" {
-                for transient in &self.transient_system_messages {
-                    messages.push(Message::system(transient.clone()));
-                }
-                inserted = true;
-            }
-
-            messages.push(message.clone());
+    /// Answers tool calls beyond the per-turn limit without running them
+    ///
+    /// Every call in the assistant message needs a result, so each deferred
+    /// call gets a short note asking the model to request it again.
+    fn defer_tool_calls(&mut self, deferred: &[ToolCall], limit: usize) {
+        for tool_call in deferred {
+            debug!(
+                "Deferring tool call {} ({}) beyond the per-turn limit of {}",
+                tool_call.id, tool_call.function.name, limit
+            );
+            self.conversation.add_tool_result(
+                &tool_call.id,
+                deferred_call_message(limit, &tool_call.function.name),
+            );
         }
+    }
 
-        if !inserted {
-            for transient in &self.transient_system_messages {
-                messages.push(Message::system(transient.clone()));
-            }
-        }
+    /// Returns the health tracker that flags and withholds failing tools
+    ///
+    /// The tracker is shared, so a clone keeps observing later calls.
+    pub fn tool_health(&self) -> &ToolHealth {
+        &self.tool_health
+    }
 
-        messages
+    /// Replaces the tool health tracker
+    ///
+    /// Used to keep tool health when the agent is rebuilt, for example after
+    /// a model or mode switch.
+    pub fn set_tool_health(&mut self, tool_health: ToolHealth) {
+        self.tool_health = tool_health;
     }
 
-    /// Returns a reference to the provider
+    /// Replaces the tool metrics collector
     ///
-    /// Useful for accessing provider-specific methods like model listing and switching
-    pub fn provider(&self) -> &dyn Provider {
-        &*self.provider
+    /// Used to keep session statistics when the agent is rebuilt, for example
+    /// after a model or mode switch.
+    pub fn set_tool_metrics(&mut self, tool_metrics: ToolMetrics) {
+        self.tool_metrics = tool_metrics;
     }
 
-    /// Returns a reference to the tool registry
+    /// Attaches a telemetry sink that records structured events for every
+    /// execution
     ///
-    /// Useful for accessing and managing available tools
-    pub fn tools(&self) -> &ToolRegistry {
-        &self.tools
+    /// The sink observes the same [`AgentExecutionEvent`] stream as the
+    /// caller's observer. Pass the previous agent's sink when the agent is
+    /// rebuilt, for example after a model or mode switch.
+    pub fn set_telemetry(&mut self, telemetry: Option<Arc<TelemetrySink>>) {
+        self.telemetry = telemetry;
     }
 
-    /// Returns a mutable reference to the agent's tool registry.
+    /// Installs the gate that keeps Planning mode read-only
     ///
-    /// This accessor is used by the ACP stdio layer to replace individual tools
-    /// (such as the terminal tool) when the session mode changes at runtime.
+    /// Without a gate every tool call is dispatched. Install a new gate
+    /// whenever the agent is rebuilt for a different chat mode.
+    pub fn set_mode_gate(&mut self, gate: Option<ModeGate>) {
+        self.mode_gate = gate;
+    }
+
+    /// Installs the tool restriction of the plan step being run
     ///
-    /// # Returns
+    /// Calls to tools the step does not list are answered with a refusal
+    /// instead of running. Without a policy every registered tool may run.
+    pub fn set_step_policy(&mut self, policy: Option<StepPolicy>) {
+        self.step_policy = policy;
+    }
+
+    /// Installs the summarizer for tool output over `tools.max_output_size`
     ///
-    /// Returns a mutable reference to the agent's [`ToolRegistry`].
+    /// Without one, oversized output is truncated. Tokens spent on summaries
+    /// are added to [`Agent::get_token_usage`]. Pass the previous agent's
+    /// summarizer when the agent is rebuilt.
+    pub fn set_overflow_summarizer(&mut self, summarizer: Option<Arc<OverflowSummarizer>>) {
+        self.overflow_summarizer = summarizer;
+    }
+
+    /// Returns the installed overflow summarizer, if any
+    pub fn overflow_summarizer(&self) -> Option<&Arc<OverflowSummarizer>> {
+        self.overflow_summarizer.as_ref()
+    }
+
+    /// Installs the store that pages tool output over `tools.max_output_size`
+    ///
+    /// Registers the `read_tool_output` tool reading from it, or removes the
+    /// tool when `pager` is `None`. An installed overflow summarizer takes
+    /// precedence. Pass the previous agent's pager when the agent is rebuilt
+    /// so pages stay readable.
+    pub fn set_output_pager(&mut self, pager: Option<Arc<OutputPager>>) {
+        match &pager {
+            Some(pager) => self.tools.register(
+                READ_TOOL_OUTPUT_TOOL_NAME,
+                Arc::new(ReadToolOutputTool::new(Arc::clone(pager))),
+            ),
+            None => {
+                self.tools.remove(READ_TOOL_OUTPUT_TOOL_NAME);
+            }
+        }
+        self.output_pager = pager;
+    }
+
+    /// Returns the installed output pager, if any
+    pub fn output_pager(&self) -> Option<&Arc<OutputPager>> {
+        self.output_pager.as_ref()
+    }
+
+    /// Replaces the tracker of files read during the session
     ///
-    /// # Examples
+    /// Files read through `read_file` are recorded on it, and before each
+    /// provider request the model is told about tracked files changed
+    /// outside the session. Pass the previous agent's tracker when the
+    /// agent is rebuilt.
+    pub fn set_file_tracker(&mut self, tracker: FileTracker) {
+        self.file_tracker = tracker;
+    }
+
+    /// Returns the tracker of files read during the session
     ///
-    /// ```
-    /// use xzatoma::agent::Agent;
-    /// use xzatoma::tools::ToolRegistry;
+    /// Record files loaded outside tool calls, such as mentions, on it.
+    pub fn file_tracker(&self) -> &FileTracker {
+        &self.file_tracker
+    }
+
+    /// Installs the mention cache to invalidate when a tracked file changes
+    pub fn set_mention_cache(&mut self, cache: Option<MentionCache>) {
+        self.mention_cache = cache;
+    }
+
+    /// Returns the installed mention cache, if any
+    pub fn mention_cache(&self) -> Option<&MentionCache> {
+        self.mention_cache.as_ref()
+    }
+
+    /// Returns how the last run ended
     ///
-    /// // The registry can be updated to reflect runtime mode changes.
-    /// // let mut agent = ...;
-    /// // let registry = agent.tools_mut();
-    /// ```
-    pub fn tools_mut(&mut self) -> &mut ToolRegistry {
-        &mut self.tools
+    /// Set when a prompt completes, from the `finish` call or, failing
+    /// that, from the final assistant text. `None` before the first prompt
+    /// completes and after a prompt that ended in an error.
+    pub fn last_outcome(&self) -> Option<&ExecutionOutcome> {
+        self.last_outcome.as_ref()
     }
 
-    /// Returns the number of registered tools
+    /// Installs the reviewer asked before a multi-file change set is applied
     ///
-    /// Useful for testing and debugging
-    pub fn num_tools(&self) -> usize {
-        self.tools.len()
+    /// Without a reviewer every file change runs as soon as it is called.
+    /// Chat mode installs one unless the safety mode is NeverConfirm.
+    pub fn set_change_review(&mut self, review: Option<Arc<dyn ChangeReview>>) {
+        self.change_review = review;
     }
 
-    /// Returns the accumulated token usage from all completions
+    /// Returns the chat mode tracked by the mode gate, if one is installed
     ///
-    /// This tracks the total prompt tokens, completion tokens, and cumulative usage
-    /// from all provider completions across the entire agent session.
+    /// The mode changes to Write when the user approves an escalation
+    /// during execution.
+    pub fn chat_mode(&self) -> Option<ChatMode> {
+        self.mode_gate.as_ref().map(ModeGate::mode)
+    }
+
+    /// Returns the attached telemetry sink, if any
+    pub fn telemetry(&self) -> Option<&Arc<TelemetrySink>> {
+        self.telemetry.as_ref()
+    }
+
+    /// Sets transient system messages used only during prompt assembly.
     ///
-    /// # Returns
+    /// These messages are appended to the provider input immediately before each
+    /// completion request and are not stored in `Conversation.messages`.
+    pub fn set_transient_system_messages(&mut self, messages: Vec<String>) {
+        self.transient_system_messages = messages;
+    }
+
+    /// Clears all transient system messages.
+    ///
+    /// This removes any runtime-only prompt injections without modifying
+    /// `Conversation.messages`.
+    pub fn clear_transient_system_messages(&mut self) {
+        self.transient_system_messages.clear();
+    }
+
+    /// Returns the current transient system messages.
+    pub fn transient_system_messages(&self) -> &[String] {
+        &self.transient_system_messages
+    }
+
+    /// Request a completion, capping provider request timeouts at `deadline`.
+    async fn complete_within_deadline(
+        &self,
+        messages: &[Message],
+        tools: &[serde_json::Value],
+        deadline: Option<tokio::time::Instant>,
+    ) -> Result<CompletionResponse> {
+        let span = trace_context::provider_span(
+            &self.conversation.id().to_string(),
+            &self.provider.get_current_model(),
+        );
+        let completion = self
+            .provider
+            .complete(messages, tools)
+            .instrument(span.clone());
+        let result = match deadline {
+            Some(deadline) => timeouts::with_deadline(deadline, completion).await,
+            None => completion.await,
+        };
+        trace_context::record_completion(&span, &result);
+        result
+    }
+
+    /// Tells the model about tracked files changed outside the session
+    ///
+    /// Changed files are dropped from the mention cache and from the loop
+    /// guard's cached results, and a system note lists them. With
+    /// `auto_refresh_context`, earlier reads of each modified file up to
+    /// `auto_refresh_max_bytes` are stubbed and the file is read again.
+    async fn refresh_external_changes(&mut self) {
+        let changes = self.file_tracker.check();
+        if changes.is_empty() {
+            return;
+        }
+        if let Some(cache) = &self.mention_cache {
+            for change in &changes {
+                cache.invalidate(&change.path).await;
+            }
+        }
+        self.loop_guard.start_generation();
+
+        let max_bytes = self.config.auto_refresh_max_bytes;
+        let refreshable: Vec<&ExternalChange> = changes
+            .iter()
+            .filter(|change| {
+                self.config.auto_refresh_context
+                    && change.kind == FileChangeKind::Modified
+                    && std::fs::metadata(&change.path).is_ok_and(|m| m.len() <= max_bytes)
+            })
+            .collect();
+        let refreshed: Vec<String> = refreshable
+            .iter()
+            .map(|change| change.display.clone())
+            .collect();
+        info!(
+            changed = changes.len(),
+            refreshed = refreshed.len(),
+            "Files changed outside the session"
+        );
+
+        self.conversation
+            .add_system_message(external_change_note(&changes, &refreshed));
+        if refreshed.is_empty() {
+            return;
+        }
+        let paths: HashSet<String> = refreshed.iter().cloned().collect();
+        self.conversation.stub_external_reads(&paths);
+        for (index, change) in refreshable.into_iter().enumerate() {
+            self.append_fresh_read(change, index).await;
+        }
+    }
+
+    /// Appends a `read_file` call and its result for a file changed externally
+    ///
+    /// The registered `read_file` tool produces the result so it looks like
+    /// any other read; without one the raw file content is used.
+    async fn append_fresh_read(&mut self, change: &ExternalChange, index: usize) {
+        let tool_call = ToolCall {
+            id: format!("external_refresh_{}_{}", self.conversation.len(), index),
+            function: FunctionCall {
+                name: "read_file".to_string(),
+                arguments: serde_json::json!({ "path": change.display }).to_string(),
+            },
+        };
+        let output = match self.tools.get("read_file") {
+            Some(tool) => match tool
+                .execute(serde_json::json!({ "path": change.display }))
+                .await
+            {
+                Ok(result) => result
+                    .truncate_if_needed(self.config.tools.max_output_size)
+                    .to_message(),
+                Err(error) => format!("Error: {}", error),
+            },
+            None => match tokio::fs::read_to_string(&change.path).await {
+                Ok(content) => content,
+                Err(error) => format!("Error: {}", error),
+            },
+        };
+        self.file_tracker.record(&change.path, &change.display);
+        self.conversation
+            .add_message(Message::assistant_with_tools(vec![tool_call.clone()]));
+        self.conversation.add_tool_result(&tool_call.id, output);
+    }
+
+    /// Sends the conversation to the provider, recovering once from context overflow
+    ///
+    /// Tracked files changed outside the session are reported first, see
+    /// [`FileTracker`]. Superseded tool results are then stubbed when
+    /// `conversation.history_hygiene` is enabled. When the provider rejects
+    /// the request as too large for its context window, the conversation is
+    /// compacted to `overflow_target` of the window and the request is
+    /// retried once. A second overflow is returned to the caller.
+    ///
+    /// # Returns
+    ///
+    /// The completion, plus the context window and what was compacted when
+    /// the request had to be retried
+    async fn complete_with_overflow_recovery(
+        &mut self,
+        tools: &[serde_json::Value],
+        deadline: Option<tokio::time::Instant>,
+    ) -> Result<(CompletionResponse, Option<(usize, Compaction)>)> {
+        self.refresh_external_changes().await;
+        if self.config.conversation.history_hygiene {
+            let registry = &self.tools;
+            self.conversation.apply_history_hygiene(|name| {
+                registry.get(name).is_some_and(|tool| tool.mutates())
+            });
+        }
+        let prompt_messages = self.messages_with_transient_system_messages();
+        let (limit, attempted) = match self
+            .complete_within_deadline(&prompt_messages, tools, deadline)
+            .await
+        {
+            Err(XzatomaError::ContextOverflow { limit, attempted }) => (limit, attempted),
+            result => return result.map(|response| (response, None)),
+        };
+
+        let limit = limit.unwrap_or_else(|| self.conversation.max_tokens());
+        let compaction = self.compact_after_overflow(limit, attempted)?;
+        let prompt_messages = self.messages_with_transient_system_messages();
+        let response = self
+            .complete_within_deadline(&prompt_messages, tools, deadline)
+            .await?;
+        Ok((response, Some((limit, compaction))))
+    }
+
+    /// Compacts the conversation after the provider reported a context overflow
+    ///
+    /// Conversation token counts are estimates, so when the provider reported
+    /// the size of the rejected request, the window is scaled by the ratio
+    /// between the estimate and the provider's count.
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::InputTooLarge` if the current user message
+    /// alone does not fit the window.
+    fn compact_after_overflow(
+        &mut self,
+        limit: usize,
+        attempted: Option<usize>,
+    ) -> Result<Compaction> {
+        let estimated = self.conversation.token_count().max(1);
+        let scale = attempted
+            .filter(|&attempted| attempted > estimated)
+            .map_or(1.0, |attempted| estimated as f64 / attempted as f64);
+        let window = (limit as f64 * scale) as usize;
+
+        if let Some(index) = self.conversation.last_user_message_index() {
+            let tokens = message_tokens(&self.conversation.messages()[index]);
+            if tokens >= window {
+                return Err(XzatomaError::InputTooLarge {
+                    tokens: (tokens as f64 / scale) as usize,
+                    limit,
+                });
+            }
+        }
+
+        let target = (window as f64 * self.config.conversation.overflow_target as f64) as usize;
+        warn!(
+            "Request exceeded the {}-token context window, compacting to about {} tokens and retrying",
+            limit, target
+        );
+        Ok(self.conversation.compact_to(target))
+    }
+
+    fn messages_with_transient_system_messages(&self) -> Vec<Message> {
+        if self.transient_system_messages.is_empty() {
+            return self.conversation.messages().to_vec();
+        }
+
+        self.context_entries()
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect()
+    }
+
+    /// Returns the messages as they will be sent to the provider
+    ///
+    /// Each entry carries its estimated token count and category. Pinned
+    /// messages are reported as [`ContextCategory::Pinned`], as are transient
+    /// system messages, which are placed after the stored system messages.
+    pub fn context_entries(&self) -> Vec<ContextEntry> {
+        let pinned = || {
+            self.transient_system_messages.iter().map(|transient| {
+                ContextEntry::new(Message::system(transient.clone()), ContextCategory::Pinned)
+            })
+        };
+
+        let mut entries = Vec::with_capacity(
+            self.conversation.messages().len() + self.transient_system_messages.len(),
+        );
+
+        let mut inserted = false;
+        for (index, message) in self.conversation.messages().iter().enumerate() {
+            if !inserted && message.role != "system" {
+                entries.extend(pinned());
+                inserted = true;
+            }
+
+            let category = if self.conversation.is_pinned(index) {
+                ContextCategory::Pinned
+            } else {
+                ContextCategory::of(message)
+            };
+            entries.push(ContextEntry {
+                index: Some(index),
+                ..ContextEntry::new(message.clone(), category)
+            });
+        }
+
+        if !inserted {
+            entries.extend(pinned());
+        }
+
+        entries
+    }
+
+    /// Returns a reference to the provider
+    ///
+    /// Useful for accessing provider-specific methods like model listing and switching
+    pub fn provider(&self) -> &dyn Provider {
+        &*self.provider
+    }
+
+    /// Returns a reference to the tool registry
+    ///
+    /// Useful for accessing and managing available tools
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    /// Returns a mutable reference to the agent's tool registry.
+    ///
+    /// This accessor is used by the ACP stdio layer to replace individual tools
+    /// (such as the terminal tool) when the session mode changes at runtime.
+    ///
+    /// # Returns
+    ///
+    /// Returns a mutable reference to the agent's [`ToolRegistry`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::agent::Agent;
+    /// use xzatoma::tools::ToolRegistry;
+    ///
+    /// // The registry can be updated to reflect runtime mode changes.
+    /// // let mut agent = ...;
+    /// // let registry = agent.tools_mut();
+    /// ```
+    pub fn tools_mut(&mut self) -> &mut ToolRegistry {
+        &mut self.tools
+    }
+
+    /// Returns the number of registered tools
+    ///
+    /// Useful for testing and debugging
+    pub fn num_tools(&self) -> usize {
+        self.tools.len()
+    }
+
+    /// Returns the accumulated token usage from all completions
+    ///
+    /// This tracks the total prompt tokens, completion tokens, and cumulative usage
+    /// from all provider completions across the entire agent session.
+    ///
+    /// # Returns
     ///
     /// Returns a TokenUsage struct if the provider has reported token counts,
     /// otherwise returns None
@@ -1435,6 +2435,7 @@ impl Agent {
 mod tests {
     use super::*;
     use crate::providers::{CompletionResponse, FunctionCall, Message};
+    use crate::testing::MockProvider as ScriptedProvider;
     use async_trait::async_trait;
 
     /// Mock provider for testing
@@ -1540,6 +2541,29 @@ mod tests {
         assert!(agent.is_err());
     }
 
+    #[test]
+    fn test_agent_new_with_mode_uses_given_prompt_style() {
+        let agent = Agent::new_with_mode(
+            Box::new(MockProvider::new(vec![])),
+            ToolRegistry::new(),
+            AgentConfig::default(),
+            ChatMode::Write,
+            SafetyMode::AlwaysConfirm,
+            prompts::PromptStyle::Concise,
+        )
+        .unwrap();
+
+        let expected = prompts::build_system_prompt(
+            ChatMode::Write,
+            SafetyMode::AlwaysConfirm,
+            prompts::PromptStyle::Concise,
+        );
+        assert_eq!(
+            agent.conversation().messages()[0].content.as_deref(),
+            Some(expected.as_str())
+        );
+    }
+
     #[tokio::test]
     async fn test_agent_execute_simple_response() {
         let provider = MockProvider::new(vec![Message::assistant("Hello, world!")]);
@@ -1753,6 +2777,38 @@ mod tests {
         assert!((context.percentage_used - 36.6).abs() < 0.1); // ~36.6%
     }
 
+    #[test]
+    fn test_context_entries_place_transient_messages_as_pinned() {
+        let provider = MockProvider::new(vec![]);
+        let mut agent = Agent::new(provider, ToolRegistry::new(), AgentConfig::default()).unwrap();
+        agent.conversation_mut().add_user_message("Hello");
+        agent.set_transient_system_messages(vec!["Skill instructions".to_string()]);
+
+        let entries = agent.context_entries();
+        let indices: Vec<Option<usize>> = entries.iter().map(|e| e.index).collect();
+        assert_eq!(indices, vec![Some(0), None, Some(1)]);
+        let categories: Vec<ContextCategory> = entries.iter().map(|e| e.category).collect();
+        assert_eq!(
+            categories,
+            vec![
+                ContextCategory::SystemPrompt,
+                ContextCategory::Pinned,
+                ContextCategory::Chat
+            ]
+        );
+
+        assert_eq!(
+            entries[1].message.content.as_deref(),
+            Some("Skill instructions")
+        );
+        let sent = agent.messages_with_transient_system_messages();
+        assert_eq!(sent.len(), entries.len());
+        for (message, entry) in sent.iter().zip(&entries) {
+            assert_eq!(message.role, entry.message.role);
+            assert_eq!(message.content, entry.message.content);
+        }
+    }
+
     #[tokio::test]
     async fn test_conversation_update_from_provider_usage() {
         let mut conversation = Conversation::new(8000, 10, 0.8);
@@ -1869,94 +2925,1021 @@ mod tests {
             }
         }
 
-        let token = tokio_util::sync::CancellationToken::new();
-        let mut collector = EventCollector { events: Vec::new() };
-        let result = agent
-            .execute_with_observer("Hello", &token, &mut collector)
+        let token = tokio_util::sync::CancellationToken::new();
+        let mut collector = EventCollector { events: Vec::new() };
+        let result = agent
+            .execute_with_observer("Hello", &token, &mut collector)
+            .await;
+        assert!(result.is_ok());
+        assert!(collector.events.iter().any(|e| e.contains("PromptStarted")));
+        assert!(collector
+            .events
+            .iter()
+            .any(|e| e.contains("ExecutionCompleted")));
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_observer_respects_cancellation() {
+        let provider = MockProvider::new(vec![Message::assistant("Hello!")]);
+        let tools = ToolRegistry::new();
+        let config = AgentConfig::default();
+        let mut agent = Agent::new(provider, tools, config).unwrap();
+
+        let token = tokio_util::sync::CancellationToken::new();
+        token.cancel();
+
+        let mut observer = crate::agent::events::NoOpObserver;
+        let result = agent
+            .execute_with_observer("Hello", &token, &mut observer)
+            .await;
+        assert!(matches!(result, Err(crate::error::XzatomaError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_execute_provider_messages_with_observer_emits_prompt_started() {
+        let provider = MockProvider::new(vec![Message::assistant("Hello!")]);
+        let tools = ToolRegistry::new();
+        let config = AgentConfig::default();
+        let mut agent = Agent::new(provider, tools, config).unwrap();
+
+        struct EventCollector {
+            events: Vec<String>,
+        }
+        impl crate::agent::events::AgentObserver for EventCollector {
+            fn on_event(&mut self, event: crate::agent::events::AgentExecutionEvent) {
+                self.events.push(format!("{:?}", event));
+            }
+        }
+
+        let token = tokio_util::sync::CancellationToken::new();
+        let mut collector = EventCollector { events: Vec::new() };
+        let messages = vec![crate::providers::Message::user("Hello")];
+        let result = agent
+            .execute_provider_messages_with_observer(messages, &token, &mut collector)
+            .await;
+        assert!(result.is_ok());
+        assert!(collector.events.iter().any(|e| e.contains("PromptStarted")));
+    }
+
+    #[tokio::test]
+    async fn test_agent_tools_mut_returns_mutable_registry() {
+        struct MockTool;
+
+        #[async_trait]
+        impl crate::tools::ToolExecutor for MockTool {
+            fn tool_definition(&self) -> serde_json::Value {
+                serde_json::json!({
+                    "name": "mock_extra",
+                    "description": "extra mock tool for tools_mut test",
+                    "parameters": {"type": "object"}
+                })
+            }
+
+            async fn execute(&self, _args: serde_json::Value) -> Result<ToolResult> {
+                Ok(ToolResult::success("mock output".to_string()))
+            }
+        }
+
+        let provider = MockProvider::new(vec![]);
+        let tools = ToolRegistry::new();
+        let config = AgentConfig::default();
+        let mut agent = Agent::new(provider, tools, config).unwrap();
+
+        assert_eq!(agent.num_tools(), 0, "registry should start empty");
+
+        agent.tools_mut().register("mock_extra", Arc::new(MockTool));
+
+        assert_eq!(
+            agent.num_tools(),
+            1,
+            "registry should reflect the new tool after tools_mut registration"
+        );
+    }
+
+    /// Tool that records the arguments it receives
+    struct RecordingTool {
+        received: Arc<std::sync::Mutex<Vec<serde_json::Value>>>,
+    }
+
+    #[async_trait]
+    impl crate::tools::ToolExecutor for RecordingTool {
+        fn tool_definition(&self) -> serde_json::Value {
+            serde_json::json!({
+                "name": "recording_tool",
+                "description": "records arguments",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {"type": "string"},
+                        "limit": {"type": "integer"}
+                    },
+                    "required": ["path"]
+                }
+            })
+        }
+
+        async fn execute(&self, args: serde_json::Value) -> Result<ToolResult> {
+            self.received.lock().unwrap().push(args);
+            Ok(ToolResult::success("recorded".to_string()))
+        }
+    }
+
+    fn agent_with_recording_tool(
+        arguments: &str,
+    ) -> (Agent, Arc<std::sync::Mutex<Vec<serde_json::Value>>>) {
+        let provider = MockProvider::new(vec![
+            Message::assistant_with_tools(vec![ToolCall {
+                id: "call_1".to_string(),
+                function: FunctionCall {
+                    name: "recording_tool".to_string(),
+                    arguments: arguments.to_string(),
+                },
+            }]),
+            Message::assistant("Done"),
+        ]);
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut tools = ToolRegistry::new();
+        tools.register(
+            "recording_tool",
+            Arc::new(RecordingTool {
+                received: Arc::clone(&received),
+            }),
+        );
+        let agent = Agent::new(provider, tools, AgentConfig::default()).unwrap();
+        (agent, received)
+    }
+
+    fn tool_result_content(agent: &Agent) -> String {
+        agent
+            .conversation()
+            .messages()
+            .iter()
+            .find(|m| m.role == "tool")
+            .and_then(|m| m.content.clone())
+            .unwrap()
+    }
+
+    /// Agent whose model calls `finish` and then another tool in one response
+    fn finishing_agent(arguments: &str) -> (Agent, Arc<std::sync::Mutex<Vec<serde_json::Value>>>) {
+        let call = |id: &str, name: &str, arguments: &str| ToolCall {
+            id: id.to_string(),
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        };
+        let provider = MockProvider::new(vec![
+            Message::assistant_with_tools(vec![
+                call("call_1", FINISH_TOOL_NAME, arguments),
+                call("call_2", "recording_tool", r#"{"path": "a.txt"}"#),
+            ]),
+            Message::assistant("Should not be reached"),
+        ]);
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut tools = ToolRegistry::new();
+        tools.register(FINISH_TOOL_NAME, Arc::new(crate::tools::finish::FinishTool));
+        tools.register(
+            "recording_tool",
+            Arc::new(RecordingTool {
+                received: Arc::clone(&received),
+            }),
+        );
+        let agent = Agent::new(provider, tools, AgentConfig::default()).unwrap();
+        (agent, received)
+    }
+
+    #[tokio::test]
+    async fn test_finish_call_ends_the_run_with_each_status() {
+        use crate::agent::outcome::FinishStatus;
+
+        for status in [
+            FinishStatus::Success,
+            FinishStatus::Failure,
+            FinishStatus::NeedsInput,
+        ] {
+            let arguments = serde_json::json!({
+                "status": status,
+                "summary": format!("ended with {}", status),
+                "details": {"tests_run": 3}
+            })
+            .to_string();
+            let (mut agent, received) = finishing_agent(&arguments);
+
+            let response = agent.execute("Do the task").await.unwrap();
+
+            assert_eq!(response, format!("ended with {}", status));
+            let outcome = agent.last_outcome().unwrap();
+            assert_eq!(outcome.status, status);
+            assert!(outcome.explicit);
+            assert_eq!(outcome.details.as_ref().unwrap()["tests_run"], 3);
+            assert!(
+                received.lock().unwrap().is_empty(),
+                "calls after finish must not run"
+            );
+            let messages = agent.conversation().messages();
+            assert!(messages
+                .iter()
+                .any(|m| m.content.as_deref() == Some(SKIPPED_AFTER_FINISH_MESSAGE)));
+            assert!(!messages
+                .iter()
+                .any(|m| m.content.as_deref() == Some("Should not be reached")));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_finish_call_does_not_end_the_run() {
+        let (mut agent, _) = finishing_agent(r#"{"status": "done", "summary": "x"}"#);
+
+        let response = agent.execute("Do the task").await.unwrap();
+
+        assert_eq!(response, "Should not be reached");
+        assert!(!agent.last_outcome().unwrap().explicit);
+    }
+
+    #[tokio::test]
+    async fn test_plain_text_ending_is_a_successful_outcome() {
+        let provider = MockProvider::new(vec![Message::assistant("All tests pass")]);
+        let mut agent = Agent::new(provider, ToolRegistry::new(), AgentConfig::default()).unwrap();
+        assert!(agent.last_outcome().is_none());
+
+        let response = agent.execute("Run the tests").await.unwrap();
+
+        let outcome = agent.last_outcome().unwrap();
+        assert_eq!(response, "All tests pass");
+        assert!(outcome.is_success());
+        assert_eq!(outcome.summary, "All tests pass");
+        assert!(!outcome.explicit);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_tool_arguments_are_reported_to_the_model() {
+        for arguments in [
+            r#"{"limit": 5}"#,
+            r#"{"path": "a.txt", "limit": "five"}"#,
+            r#"["a.txt"]"#,
+        ] {
+            let (mut agent, received) = agent_with_recording_tool(arguments);
+
+            let result = agent.execute("Use tool").await;
+
+            assert!(result.is_ok(), "run should continue for {}", arguments);
+            assert!(received.lock().unwrap().is_empty());
+            let content = tool_result_content(&agent);
+            assert!(
+                content.contains("Invalid arguments for tool 'recording_tool'"),
+                "unexpected tool result for {}: {}",
+                arguments,
+                content
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_arguments_are_coerced_before_dispatch() {
+        let arguments = r#"{"path": "a.txt", "limit": "5", "mode": "fast"}"#;
+        let (agent, received) = agent_with_recording_tool(arguments);
+
+        let result = agent
+            .execute_tool_call(
+                &ToolCall {
+                    id: "call_1".to_string(),
+                    function: FunctionCall {
+                        name: "recording_tool".to_string(),
+                        arguments: arguments.to_string(),
+                    },
+                },
+                ToolOutputSink::disabled(),
+            )
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(
+            received.lock().unwrap()[0],
+            serde_json::json!({"path": "a.txt", "limit": 5})
+        );
+        assert_eq!(result.metadata["argument_validation"], "coerced");
+        assert!(result.metadata["argument_adjustments"].contains("mode"));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_tool_arguments_are_repaired_or_sent_back() {
+        let (mut agent, received) =
+            agent_with_recording_tool(r#"{"path": "a.txt"} Reading the file now."#);
+
+        agent.execute("Use tool").await.unwrap();
+
+        assert_eq!(
+            received.lock().unwrap()[0],
+            serde_json::json!({"path": "a.txt"})
+        );
+        let calls = agent
+            .conversation()
+            .messages()
+            .iter()
+            .find_map(|m| m.tool_calls.as_ref())
+            .unwrap();
+        assert_eq!(calls[0].function.arguments, r#"{"path":"a.txt"}"#);
+
+        let (mut agent, received) =
+            agent_with_recording_tool(r#"{"path": "a.txt", "content": "fn main() {"#);
+
+        let result = agent.execute("Use tool").await;
+
+        assert!(result.is_ok());
+        assert!(received.lock().unwrap().is_empty());
+        let content = tool_result_content(&agent);
+        assert!(content.contains("are not valid JSON"));
+        assert!(content.contains(r#""content": "fn main() {"#));
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_are_recorded_in_tool_metrics() {
+        let (mut agent, _received) = agent_with_recording_tool(r#"{"path": "a.txt"}"#);
+
+        agent.execute("Use tool").await.unwrap();
+        let _ = agent
+            .execute_tool_call(
+                &ToolCall {
+                    id: "call_2".to_string(),
+                    function: FunctionCall {
+                        name: "recording_tool".to_string(),
+                        arguments: r#"{"limit": 1}"#.to_string(),
+                    },
+                },
+                ToolOutputSink::disabled(),
+            )
+            .await;
+
+        let summary = agent.tool_metrics().summary();
+        assert_eq!(summary.tools.len(), 1);
+        assert_eq!(summary.tools[0].name, "recording_tool");
+        assert_eq!(summary.tools[0].stats.calls, 2);
+        assert_eq!(summary.tools[0].stats.successes, 1);
+        assert_eq!(summary.tools[0].stats.failures, 1);
+        assert!(summary.totals.total_output_bytes >= "recorded".len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_failing_tool_is_flagged_withheld_and_recovers() {
+        use crate::testing::MockToolExecutor;
+
+        let status_api = MockToolExecutor::builder("status_api")
+            .description("Checks the status API")
+            .parameters(serde_json::json!({
+                "type": "object",
+                "properties": {"attempt": {"type": "integer"}}
+            }))
+            .then(ToolResult::error("connection refused"))
+            .then(ToolResult::error("connection refused"))
+            .then(ToolResult::error("connection refused"))
+            .output("up")
+            .build();
+        let mut tools = ToolRegistry::new();
+        tools.register("status_api", Arc::new(status_api.clone()));
+        tools.register(
+            "backup",
+            Arc::new(MockToolExecutor::builder("backup").build()),
+        );
+
+        // The fourth call names the withheld tool anyway, and it still runs
+        let mut provider = ScriptedProvider::new();
+        for attempt in 1..=4 {
+            provider =
+                provider.then_tool_call("status_api", serde_json::json!({"attempt": attempt}));
+        }
+        let provider = provider.then_text("Done");
+        let mut config = AgentConfig::default();
+        config.tools.health.min_calls = 2;
+        config.tools.health.withhold_min_calls = 3;
+        let mut agent = Agent::new(provider.clone(), tools, config).unwrap();
+
+        agent.execute("Check the status").await.unwrap();
+
+        assert_eq!(status_api.call_count(), 4);
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 5);
+        let description = |request: &crate::testing::RecordedRequest| {
+            request
+                .tools
+                .iter()
+                .find(|tool| tool["name"] == "status_api")
+                .map(|tool| tool["description"].as_str().unwrap().to_string())
+        };
+        assert_eq!(
+            description(&requests[1]).as_deref(),
+            Some("Checks the status API")
+        );
+        assert_eq!(
+            description(&requests[2]).as_deref(),
+            Some("Checks the status API (currently failing: connection refused — prefer alternatives)")
+        );
+        assert_eq!(description(&requests[3]), None);
+        assert!(requests[3].tool_names().contains(&"backup".to_string()));
+        assert_eq!(
+            description(&requests[4]).as_deref(),
+            Some("Checks the status API")
+        );
+        assert!(agent.tool_health().report().is_empty());
+    }
+
+    /// Tool that counts how often it actually runs
+    struct LoopingTool {
+        read_only: bool,
+        runs: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl crate::tools::ToolExecutor for LoopingTool {
+        fn tool_definition(&self) -> serde_json::Value {
+            serde_json::json!({
+                "name": "lookup",
+                "description": "looks something up",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "pattern": {"type": "string"},
+                        "path": {"type": "string"}
+                    }
+                }
+            })
+        }
+
+        fn read_only(&self) -> bool {
+            self.read_only
+        }
+
+        async fn execute(&self, _args: serde_json::Value) -> Result<ToolResult> {
+            let run = self.runs.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Ok(ToolResult::success(format!("run {}", run)))
+        }
+    }
+
+    /// The same `lookup` call, with key order and spacing varying by `index`
+    fn lookup_call(index: usize) -> Message {
+        let arguments = if index % 2 == 0 {
+            r#"{"pattern": "todo", "path": "src"}"#
+        } else {
+            r#"{"path":"src","pattern":"todo"}"#
+        };
+        Message::assistant_with_tools(vec![ToolCall {
+            id: format!("call_{}", index + 1),
+            function: FunctionCall {
+                name: "lookup".to_string(),
+                arguments: arguments.to_string(),
+            },
+        }])
+    }
+
+    fn looping_agent(
+        read_only: bool,
+        responses: Vec<Message>,
+    ) -> (Agent, Arc<std::sync::atomic::AtomicUsize>) {
+        let runs = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut tools = ToolRegistry::new();
+        tools.register(
+            "lookup",
+            Arc::new(LoopingTool {
+                read_only,
+                runs: Arc::clone(&runs),
+            }),
+        );
+        let agent =
+            Agent::new(MockProvider::new(responses), tools, AgentConfig::default()).unwrap();
+        (agent, runs)
+    }
+
+    fn tool_results(agent: &Agent) -> Vec<String> {
+        agent
+            .conversation()
+            .messages()
+            .iter()
+            .filter(|m| m.role == "tool")
+            .filter_map(|m| m.content.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_repeated_read_only_calls_are_cached_then_stopped() {
+        let (mut agent, runs) = looping_agent(true, (0..10).map(lookup_call).collect());
+
+        let error = agent.execute("Find the todos").await.unwrap_err();
+
+        assert!(matches!(
+            error,
+            XzatomaError::ToolLoopDetected { ref tool, repeats: 5 } if tool == "lookup"
+        ));
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 2);
+        let results = tool_results(&agent);
+        assert_eq!(results.len(), 5);
+        assert!(!results[1].contains("repeated call"));
+        for cached in &results[2..4] {
+            assert!(cached.contains(crate::agent::loop_guard::REPEATED_CALL_NOTE));
+            assert!(cached.contains("run 1"));
+        }
+        assert!(results[4].contains("Tool call loop detected"));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_calls_to_other_tools_run_and_are_flagged() {
+        let (mut agent, runs) = looping_agent(false, (0..3).map(lookup_call).collect());
+
+        agent.execute("Find the todos").await.unwrap();
+
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 3);
+        let results = tool_results(&agent);
+        assert!(results[2].contains("already made"));
+        assert!(results[2].contains("run 3"));
+    }
+
+    #[tokio::test]
+    async fn test_loop_detection_resets_per_prompt() {
+        let responses = vec![
+            lookup_call(0),
+            lookup_call(1),
+            Message::assistant("Found them"),
+            lookup_call(2),
+            lookup_call(3),
+        ];
+        let (mut agent, runs) = looping_agent(true, responses);
+
+        agent.execute("Find the todos").await.unwrap();
+        agent.execute("Look again").await.unwrap();
+
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 4);
+        assert!(tool_results(&agent)
+            .iter()
+            .all(|result| !result.contains("repeated call")));
+    }
+
+    /// Tool whose definition has the given name and description length
+    struct SizedTool {
+        name: &'static str,
+        description_chars: usize,
+    }
+
+    #[async_trait]
+    impl crate::tools::ToolExecutor for SizedTool {
+        fn tool_definition(&self) -> serde_json::Value {
+            serde_json::json!({
+                "name": self.name,
+                "description": "d".repeat(self.description_chars),
+                "parameters": {"type": "object"}
+            })
+        }
+
+        async fn execute(&self, _args: serde_json::Value) -> Result<ToolResult> {
+            Ok(ToolResult::success("ok".to_string()))
+        }
+    }
+
+    fn agent_with_sized_tools(strategy: crate::config::ToolLimitStrategy) -> Agent {
+        let provider = MockProvider::new(vec![Message::assistant("Done")]);
+        let mut tools = ToolRegistry::new();
+        for (name, description_chars) in [("read_file", 10), ("scratch__echo", 2_000)] {
+            tools.register(
+                name,
+                Arc::new(SizedTool {
+                    name,
+                    description_chars,
+                }),
+            );
+        }
+        let mut config = AgentConfig::default();
+        config.tools.definition_limits.strategy = strategy;
+        config.tools.definition_limits.max_tools = Some(1);
+        Agent::new(provider, tools, config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tool_definitions_are_fitted_to_limits() {
+        let mut agent = agent_with_sized_tools(crate::config::ToolLimitStrategy::Drop);
+        assert!(agent.tool_fit_report().is_none());
+
+        agent.execute("hello").await.unwrap();
+
+        let report = agent.tool_fit_report().unwrap();
+        assert_eq!(report.original_count, 2);
+        assert_eq!(report.final_count, 1);
+        assert_eq!(report.dropped[0].name, "scratch__echo");
+    }
+
+    #[tokio::test]
+    async fn test_tool_definitions_over_limits_fail_fast() {
+        let mut agent = agent_with_sized_tools(crate::config::ToolLimitStrategy::Fail);
+
+        let err = agent.execute("hello").await.unwrap_err();
+
+        assert!(matches!(err, XzatomaError::ToolDefinitionsExceedLimits(_)));
+        assert!(err.to_string().contains("2 tools (limit 1)"));
+    }
+
+    /// Tool that prints a long build log ending in an error
+    struct BuildLogTool;
+
+    #[async_trait]
+    impl crate::tools::ToolExecutor for BuildLogTool {
+        fn tool_definition(&self) -> serde_json::Value {
+            serde_json::json!({"name": "build", "description": "Build", "parameters": {"type": "object"}})
+        }
+
+        async fn execute(&self, _args: serde_json::Value) -> Result<ToolResult> {
+            Ok(ToolResult::success(format!(
+                "{}error: boom at src/lib.rs:1:1\n",
+                "   Compiling dep v0.1.0\n".repeat(500)
+            )))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_oversized_tool_output_is_summarized_and_counted_in_usage() {
+        use crate::tools::overflow_summary::ToolOutputStore;
+
+        let dir = tempfile::tempdir().unwrap();
+        let provider = MockProvider::with_token_usage(
+            vec![
+                Message::assistant_with_tools(vec![ToolCall {
+                    id: "call_1".to_string(),
+                    function: FunctionCall {
+                        name: "build".to_string(),
+                        arguments: "{}".to_string(),
+                    },
+                }]),
+                Message::assistant("Fixed"),
+            ],
+            TokenUsage::new(10, 5),
+        );
+        let summary_model = MockProvider::with_token_usage(
+            vec![Message::assistant("error: boom at src/lib.rs:1:1")],
+            TokenUsage::new(200, 20),
+        );
+        let store = ToolOutputStore::new(dir.path());
+        let mut tools = ToolRegistry::new();
+        tools.register("build", Arc::new(BuildLogTool));
+        let mut config = AgentConfig::default();
+        config.tools.max_output_size = 1024;
+        let mut agent = Agent::new(provider, tools, config).unwrap();
+        agent.set_overflow_summarizer(Some(Arc::new(OverflowSummarizer::new(
+            Arc::new(summary_model),
+            store.clone(),
+        ))));
+
+        agent.execute("Build it").await.unwrap();
+
+        let result = tool_result_content(&agent);
+        assert!(result.contains("error: boom at src/lib.rs:1:1"));
+        assert!(!result.contains("Compiling dep"));
+        let id = result
+            .split("xzatoma tool-output show ")
+            .nth(1)
+            .and_then(|rest| rest.split('`').next())
+            .unwrap();
+        assert!(store.load(id).unwrap().starts_with("   Compiling dep"));
+
+        let usage = agent.get_token_usage().unwrap();
+        assert_eq!(usage.prompt_tokens, 10 + 10 + 200);
+        assert_eq!(usage.completion_tokens, 5 + 5 + 20);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_tool_output_is_paged_and_readable() {
+        let dir = tempfile::tempdir().unwrap();
+        let provider = MockProvider::new(vec![
+            Message::assistant_with_tools(vec![ToolCall {
+                id: "call_1".to_string(),
+                function: FunctionCall {
+                    name: "build".to_string(),
+                    arguments: "{}".to_string(),
+                },
+            }]),
+            Message::assistant("Fixed"),
+        ]);
+        let mut tools = ToolRegistry::new();
+        tools.register("build", Arc::new(BuildLogTool));
+        let mut config = AgentConfig::default();
+        config.tools.max_output_size = 1024;
+        let mut agent = Agent::new(provider, tools, config).unwrap();
+        let pager =
+            Arc::new(OutputPager::new(4, 1024 * 1024).with_spill_dir(dir.path().join("pages")));
+        agent.set_output_pager(Some(Arc::clone(&pager)));
+        assert!(agent.tools().get(READ_TOOL_OUTPUT_TOOL_NAME).is_some());
+
+        agent.execute("Build it").await.unwrap();
+
+        let result = tool_result_content(&agent);
+        assert!(result.starts_with("   Compiling dep"));
+        let id = result
+            .split("with id=")
+            .nth(1)
+            .and_then(|rest| rest.split(' ').next())
+            .unwrap();
+        let first = pager.read(id, 1).unwrap();
+        let last = pager.read(id, first.total).unwrap();
+        assert!(last.text.ends_with("error: boom at src/lib.rs:1:1\n"));
+
+        agent.set_output_pager(None);
+        assert!(agent.tools().get(READ_TOOL_OUTPUT_TOOL_NAME).is_none());
+    }
+
+    /// Agent that reads `notes.md` in a temporary directory, then answers
+    fn agent_reading_notes(dir: &std::path::Path, auto_refresh: bool) -> Agent {
+        let provider = MockProvider::new(vec![
+            Message::assistant_with_tools(vec![ToolCall {
+                id: "call_1".to_string(),
+                function: FunctionCall {
+                    name: "read_file".to_string(),
+                    arguments: r#"{"path":"notes.md"}"#.to_string(),
+                },
+            }]),
+            Message::assistant("Read it"),
+        ]);
+        let mut tools = ToolRegistry::new();
+        tools.register(
+            "read_file",
+            Arc::new(crate::tools::read_file::ReadFileTool::new(
+                dir.to_path_buf(),
+                1024 * 1024,
+                1000,
+            )),
+        );
+        let mut config = AgentConfig::default();
+        config.auto_refresh_context = auto_refresh;
+        let mut agent = Agent::new(provider, tools, config).unwrap();
+        agent.conversation_mut().set_cwd(Some(dir.to_path_buf()));
+        agent
+    }
+
+    #[tokio::test]
+    async fn test_external_change_is_reported_and_invalidates_mention_cache() {
+        use crate::mention_parser::MentionContent;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(&path, "first draft").unwrap();
+        let mut agent = agent_reading_notes(dir.path(), false);
+        let cache = MentionCache::new();
+        agent.set_mention_cache(Some(cache.clone()));
+
+        agent.execute("Read the notes").await.unwrap();
+        assert_eq!(agent.file_tracker().len(), 1);
+
+        std::fs::write(&path, "second draft, edited in the editor").unwrap();
+        let canonical = path.canonicalize().unwrap();
+        cache
+            .insert(
+                canonical.clone(),
+                MentionContent::new(
+                    canonical,
+                    "notes.md".to_string(),
+                    "second draft, edited in the editor".to_string(),
+                    Some(std::time::SystemTime::now() + std::time::Duration::from_secs(3600)),
+                ),
+            )
             .await;
-        assert!(result.is_ok());
-        assert!(collector.events.iter().any(|e| e.contains("PromptStarted")));
-        assert!(collector
-            .events
+        agent.execute("Summarize them").await.unwrap();
+
+        let notes: Vec<&str> = agent
+            .conversation()
+            .messages()
             .iter()
-            .any(|e| e.contains("ExecutionCompleted")));
+            .filter(|m| m.role == "system")
+            .filter_map(|m| m.content.as_deref())
+            .filter(|content| content.starts_with("Files changed outside this session:"))
+            .collect();
+        assert_eq!(notes.len(), 1);
+        assert!(notes[0].contains("notes.md was modified externally since you last read it."));
+        assert_eq!(cache.len().await, 0);
+        assert!(tool_result_content(&agent).contains("first draft"));
     }
 
     #[tokio::test]
-    async fn test_execute_with_observer_respects_cancellation() {
-        let provider = MockProvider::new(vec![Message::assistant("Hello!")]);
-        let tools = ToolRegistry::new();
-        let config = AgentConfig::default();
-        let mut agent = Agent::new(provider, tools, config).unwrap();
+    async fn test_auto_refresh_replaces_stale_read_with_current_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(&path, "first draft").unwrap();
+        let mut agent = agent_reading_notes(dir.path(), true);
+
+        agent.execute("Read the notes").await.unwrap();
+        std::fs::write(&path, "second draft, edited in the editor").unwrap();
+        agent.execute("Summarize them").await.unwrap();
+
+        let results: Vec<String> = agent
+            .conversation()
+            .messages()
+            .iter()
+            .filter(|m| m.role == "tool")
+            .filter_map(|m| m.content.clone())
+            .collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].starts_with("[earlier read of notes.md omitted"));
+        assert!(results[1].contains("second draft, edited in the editor"));
+        assert!(agent.file_tracker().check().is_empty());
+    }
 
-        let token = tokio_util::sync::CancellationToken::new();
-        token.cancel();
+    #[tokio::test]
+    async fn test_telemetry_records_session_event_sequence() {
+        use crate::telemetry::{TelemetryEvent, TelemetryEventKind, TelemetryStatus};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("telemetry.jsonl");
+        let config = crate::config::TelemetryConfig {
+            file: Some(path.display().to_string()),
+            stdout: false,
+            otlp: None,
+        };
+        let sink = TelemetrySink::from_config(&config, "session-telemetry")
+            .unwrap()
+            .unwrap();
 
-        let mut observer = crate::agent::events::NoOpObserver;
-        let result = agent
-            .execute_with_observer("Hello", &token, &mut observer)
-            .await;
-        assert!(matches!(result, Err(crate::error::XzatomaError::Cancelled)));
+        let provider = MockProvider::with_token_usage(
+            vec![
+                Message::assistant_with_tools(vec![ToolCall {
+                    id: "call_1".to_string(),
+                    function: FunctionCall {
+                        name: "recording_tool".to_string(),
+                        arguments: r#"{"path": "a.txt"}"#.to_string(),
+                    },
+                }]),
+                Message::assistant("Done"),
+            ],
+            TokenUsage::new(10, 5),
+        );
+        let mut tools = ToolRegistry::new();
+        tools.register(
+            "recording_tool",
+            Arc::new(RecordingTool {
+                received: Arc::new(std::sync::Mutex::new(Vec::new())),
+            }),
+        );
+        let mut agent = Agent::new(provider, tools, AgentConfig::default()).unwrap();
+        agent.set_telemetry(Some(Arc::clone(&sink)));
+
+        sink.start_session("run", "mock", None);
+        agent.execute("Use tool").await.unwrap();
+        agent.execute("Again").await.unwrap();
+        sink.end_session(TelemetryStatus::Success);
+
+        let events: Vec<TelemetryEvent> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let tags: Vec<String> = events
+            .iter()
+            .map(|event| serde_json::to_value(event).unwrap()["event"].to_string())
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                "\"session_start\"",
+                "\"turn_start\"",
+                "\"tool_call\"",
+                "\"turn_end\"",
+                "\"turn_start\"",
+                "\"turn_end\"",
+                "\"session_end\"",
+            ]
+        );
+        assert!(events
+            .iter()
+            .all(|event| event.session_id == "session-telemetry" && event.schema_version == 1));
+        assert!(matches!(
+            &events[2].kind,
+            TelemetryEventKind::ToolCall { turn: 1, name, status: ToolCallStatus::Success, .. }
+                if name == "recording_tool"
+        ));
+        assert!(matches!(
+            events[3].kind,
+            TelemetryEventKind::TurnEnd {
+                turn: 1,
+                status: TelemetryStatus::Success,
+                provider_requests: 2,
+                prompt_tokens: 20,
+                completion_tokens: 10,
+                total_tokens: 30,
+                ..
+            }
+        ));
+        assert!(matches!(
+            events[5].kind,
+            TelemetryEventKind::TurnEnd {
+                turn: 2,
+                provider_requests: 1,
+                ..
+            }
+        ));
+        assert!(matches!(
+            events[6].kind,
+            TelemetryEventKind::SessionEnd {
+                turns: 2,
+                status: TelemetryStatus::Success,
+                ..
+            }
+        ));
     }
 
-    #[tokio::test]
-    async fn test_execute_provider_messages_with_observer_emits_prompt_started() {
-        let provider = MockProvider::new(vec![Message::assistant("Hello!")]);
-        let tools = ToolRegistry::new();
-        let config = AgentConfig::default();
-        let mut agent = Agent::new(provider, tools, config).unwrap();
+    /// Tool that streams a fixed script of lines before returning
+    struct ScriptedStreamingTool {
+        script: Vec<(crate::tools::ToolOutputStream, &'static str)>,
+    }
 
-        struct EventCollector {
-            events: Vec<String>,
+    #[async_trait]
+    impl crate::tools::ToolExecutor for ScriptedStreamingTool {
+        fn tool_definition(&self) -> serde_json::Value {
+            serde_json::json!({
+                "name": "scripted",
+                "description": "streams scripted lines",
+                "parameters": {"type": "object", "properties": {}}
+            })
         }
-        impl crate::agent::events::AgentObserver for EventCollector {
-            fn on_event(&mut self, event: crate::agent::events::AgentExecutionEvent) {
-                self.events.push(format!("{:?}", event));
-            }
+
+        async fn execute(&self, _args: serde_json::Value) -> Result<ToolResult> {
+            Ok(ToolResult::success("not streamed".to_string()))
         }
 
-        let token = tokio_util::sync::CancellationToken::new();
-        let mut collector = EventCollector { events: Vec::new() };
-        let messages = vec![crate::providers::Message::user("Hello")];
-        let result = agent
-            .execute_provider_messages_with_observer(messages, &token, &mut collector)
-            .await;
-        assert!(result.is_ok());
-        assert!(collector.events.iter().any(|e| e.contains("PromptStarted")));
+        async fn execute_streaming(
+            &self,
+            _args: serde_json::Value,
+            sink: ToolOutputSink,
+        ) -> Result<ToolResult> {
+            let mut output = Vec::new();
+            for (stream, line) in &self.script {
+                sink.push(*stream, *line);
+                output.push(*line);
+                tokio::task::yield_now().await;
+            }
+            Ok(ToolResult::success(output.join("\n")))
+        }
     }
 
     #[tokio::test]
-    async fn test_agent_tools_mut_returns_mutable_registry() {
-        struct MockTool;
-
-        #[async_trait]
-        impl crate::tools::ToolExecutor for MockTool {
-            fn tool_definition(&self) -> serde_json::Value {
-                serde_json::json!({
-                    "name": "mock_extra",
-                    "description": "extra mock tool for tools_mut test",
-                    "parameters": {"type": "object"}
-                })
-            }
+    async fn test_streamed_tool_output_precedes_completion() {
+        use crate::tools::ToolOutputStream;
 
-            async fn execute(&self, _args: serde_json::Value) -> Result<ToolResult> {
-                Ok(ToolResult::success("mock output".to_string()))
+        struct EventCollector {
+            events: Vec<AgentExecutionEvent>,
+        }
+        impl crate::agent::events::AgentObserver for EventCollector {
+            fn on_event(&mut self, event: AgentExecutionEvent) {
+                self.events.push(event);
             }
         }
 
-        let provider = MockProvider::new(vec![]);
-        let tools = ToolRegistry::new();
-        let config = AgentConfig::default();
-        let mut agent = Agent::new(provider, tools, config).unwrap();
-
-        assert_eq!(agent.num_tools(), 0, "registry should start empty");
+        let provider = MockProvider::new(vec![
+            Message::assistant_with_tools(vec![ToolCall {
+                id: "call_1".to_string(),
+                function: FunctionCall {
+                    name: "scripted".to_string(),
+                    arguments: "{}".to_string(),
+                },
+            }]),
+            Message::assistant("Done"),
+        ]);
+        let mut tools = ToolRegistry::new();
+        tools.register(
+            "scripted",
+            Arc::new(ScriptedStreamingTool {
+                script: vec![
+                    (ToolOutputStream::Stdout, "step 1"),
+                    (ToolOutputStream::Stderr, "warning"),
+                    (ToolOutputStream::Stdout, "step 2"),
+                ],
+            }),
+        );
+        let mut agent = Agent::new(provider, tools, AgentConfig::default()).unwrap();
 
-        agent.tools_mut().register("mock_extra", Arc::new(MockTool));
+        let token = CancellationToken::new();
+        let mut collector = EventCollector { events: Vec::new() };
+        agent
+            .execute_with_observer("Run it", &token, &mut collector)
+            .await
+            .unwrap();
 
+        let tool_events: Vec<String> = collector
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                AgentExecutionEvent::ToolCallStarted { name, .. } => {
+                    Some(format!("started {}", name))
+                }
+                AgentExecutionEvent::ToolOutputChunk {
+                    id, stream, line, ..
+                } => Some(format!("{} {:?} {}", id, stream, line)),
+                AgentExecutionEvent::ToolCallCompleted { output, .. } => {
+                    Some(format!("completed {}", output.replace('\n', "|")))
+                }
+                _ => None,
+            })
+            .collect();
         assert_eq!(
-            agent.num_tools(),
-            1,
-            "registry should reflect the new tool after tools_mut registration"
+            tool_events,
+            vec![
+                "started scripted",
+                "call_1 Stdout step 1",
+                "call_1 Stderr warning",
+                "call_1 Stdout step 2",
+                "completed step 1|warning|step 2",
+            ]
         );
     }
 
@@ -2421,4 +4404,512 @@ mod tests {
             panic!("expected ContextWindowUpdated variant");
         }
     }
+
+    /// Mutating tool that counts how often it runs
+    struct WritingTool {
+        runs: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl crate::tools::ToolExecutor for WritingTool {
+        fn tool_definition(&self) -> serde_json::Value {
+            serde_json::json!({
+                "name": "write_file",
+                "description": "writes a file",
+                "parameters": {
+                    "type": "object",
+                    "properties": {"path": {"type": "string"}}
+                }
+            })
+        }
+
+        async fn execute(&self, _args: serde_json::Value) -> Result<ToolResult> {
+            self.runs.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(ToolResult::success("written".to_string()))
+        }
+
+        fn mutates(&self) -> bool {
+            true
+        }
+    }
+
+    struct Approve(bool);
+
+    impl crate::agent::mode_gate::ModeEscalation for Approve {
+        fn approve(&self, _request: &EscalationRequest) -> bool {
+            self.0
+        }
+    }
+
+    /// Builds an agent whose model calls `write_file` once, and the tools the
+    /// gate adds on escalation
+    fn planning_agent(
+        planning_tools: bool,
+    ) -> (Agent, ToolRegistry, Arc<std::sync::atomic::AtomicUsize>) {
+        let provider = MockProvider::new(vec![
+            Message::assistant_with_tools(vec![ToolCall {
+                id: "call_1".to_string(),
+                function: FunctionCall {
+                    name: "write_file".to_string(),
+                    arguments: r#"{"path":"notes.md"}"#.to_string(),
+                },
+            }]),
+            Message::assistant("Done"),
+        ]);
+        let runs = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut write_tools = ToolRegistry::new();
+        write_tools.register("write_file", Arc::new(WritingTool { runs: runs.clone() }));
+        let mut tools = ToolRegistry::new();
+        if planning_tools {
+            tools.register("write_file", Arc::new(WritingTool { runs: runs.clone() }));
+        }
+        let agent = Agent::new(provider, tools, AgentConfig::default()).unwrap();
+        (agent, write_tools, runs)
+    }
+
+    fn last_tool_message(agent: &Agent) -> String {
+        agent
+            .conversation()
+            .messages()
+            .iter()
+            .rev()
+            .find(|m| m.role == "tool")
+            .and_then(|m| m.content.clone())
+            .unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn test_mode_gate_refuses_mutating_tool_in_planning_mode() {
+        let (mut agent, _write_tools, runs) = planning_agent(true);
+        agent.set_mode_gate(Some(ModeGate::new(ChatMode::Planning)));
+
+        agent.execute("Plan the change").await.unwrap();
+
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert!(last_tool_message(&agent).contains("not available in PLANNING mode"));
+        assert_eq!(agent.chat_mode(), Some(ChatMode::Planning));
+    }
+
+    #[tokio::test]
+    async fn test_mode_gate_runs_tool_after_approved_escalation() {
+        let (mut agent, write_tools, runs) = planning_agent(false);
+        agent.set_mode_gate(Some(
+            ModeGate::new(ChatMode::Planning).with_escalation(Arc::new(Approve(true)), write_tools),
+        ));
+
+        agent.execute("Write the notes").await.unwrap();
+
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(agent.chat_mode(), Some(ChatMode::Write));
+        assert_eq!(agent.num_tools(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_mode_gate_refuses_when_mode_switching_disabled() {
+        let (mut agent, write_tools, runs) = planning_agent(true);
+        agent.set_mode_gate(Some(
+            ModeGate::new(ChatMode::Planning)
+                .with_mode_switching(false)
+                .with_escalation(Arc::new(Approve(true)), write_tools),
+        ));
+
+        agent.execute("Write the notes").await.unwrap();
+
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert!(last_tool_message(&agent).contains("Mode switching is disabled"));
+        assert_eq!(agent.chat_mode(), Some(ChatMode::Planning));
+    }
+
+    /// Reviewer that applies a fixed selection and records what it saw
+    struct PickReview {
+        decision: crate::agent::ReviewDecision,
+        reviewed: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl crate::agent::ChangeReview for PickReview {
+        fn review(
+            &self,
+            changes: &[crate::tools::change_set::FileChange],
+        ) -> crate::agent::ReviewDecision {
+            let mut reviewed = self.reviewed.lock().unwrap();
+            reviewed.extend(changes.iter().map(|change| change.path.clone()));
+            self.decision.clone()
+        }
+    }
+
+    fn agent_writing_files(
+        dir: &std::path::Path,
+        paths: &[&str],
+        decision: crate::agent::ReviewDecision,
+    ) -> (Agent, Arc<std::sync::Mutex<Vec<String>>>) {
+        let calls = paths
+            .iter()
+            .enumerate()
+            .map(|(index, path)| ToolCall {
+                id: format!("call_{}", index + 1),
+                function: FunctionCall {
+                    name: "write_file".to_string(),
+                    arguments: serde_json::json!({"path": path, "content": "new\n"}).to_string(),
+                },
+            })
+            .collect();
+        let provider = MockProvider::new(vec![
+            Message::assistant_with_tools(calls),
+            Message::assistant("Done"),
+        ]);
+        let mut tools = ToolRegistry::new();
+        tools.register(
+            "write_file",
+            Arc::new(crate::tools::write_file::WriteFileTool::new(
+                dir.to_path_buf(),
+                1024,
+            )),
+        );
+        let reviewed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut agent = Agent::new(provider, tools, AgentConfig::default()).unwrap();
+        agent.set_change_review(Some(Arc::new(PickReview {
+            decision,
+            reviewed: Arc::clone(&reviewed),
+        })));
+        (agent, reviewed)
+    }
+
+    fn tool_message_for(agent: &Agent, id: &str) -> String {
+        agent
+            .conversation()
+            .messages()
+            .iter()
+            .find(|m| m.role == "tool" && m.tool_call_id.as_deref() == Some(id))
+            .and_then(|m| m.content.clone())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_change_review_applies_only_picked_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("b.txt"), "old\n").unwrap();
+        let (mut agent, reviewed) = agent_writing_files(
+            dir.path(),
+            &["a.txt", "b.txt", "c.txt"],
+            crate::agent::ReviewDecision::Apply([0, 2].into_iter().collect()),
+        );
+
+        agent.execute("Write the files").await.unwrap();
+
+        assert_eq!(*reviewed.lock().unwrap(), vec!["a.txt", "b.txt", "c.txt"]);
+        assert!(dir.path().join("a.txt").exists());
+        assert!(dir.path().join("c.txt").exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("b.txt")).unwrap(),
+            "old\n"
+        );
+        assert!(tool_message_for(&agent, "call_1").contains("File written successfully"));
+        let skipped = tool_message_for(&agent, "call_2");
+        assert!(skipped.starts_with("Error: Not applied"));
+        assert!(skipped.contains("b.txt was left unchanged"));
+        assert!(tool_message_for(&agent, "call_3").contains("File written successfully"));
+    }
+
+    #[tokio::test]
+    async fn test_change_review_skips_single_file_changes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (mut agent, reviewed) = agent_writing_files(
+            dir.path(),
+            &["a.txt"],
+            crate::agent::ReviewDecision::RejectAll,
+        );
+
+        agent.execute("Write the file").await.unwrap();
+
+        assert!(reviewed.lock().unwrap().is_empty());
+        assert!(dir.path().join("a.txt").exists());
+    }
+
+    /// Agent whose provider rejects requests larger than `limit` tokens
+    fn overflow_agent(limit: usize) -> (Agent, ScriptedProvider) {
+        let provider = ScriptedProvider::new()
+            .with_context_window(limit)
+            .with_fallback(CompletionResponse::new(Message::assistant("Done")));
+        let agent = Agent::new(
+            provider.clone(),
+            ToolRegistry::new(),
+            AgentConfig::default(),
+        )
+        .unwrap();
+        (agent, provider)
+    }
+
+    /// Estimated size of each request the provider received
+    fn request_tokens(provider: &ScriptedProvider) -> Vec<usize> {
+        provider
+            .requests()
+            .iter()
+            .map(|request| request.messages.iter().map(message_tokens).sum())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_context_overflow_compacts_and_retries_once() {
+        let (mut agent, provider) = overflow_agent(1000);
+        for i in 0..4 {
+            let conversation = agent.conversation_mut();
+            conversation.add_user_message(format!("question {} {}", i, "x".repeat(2000)));
+            conversation.add_assistant_message("y".repeat(2000));
+        }
+
+        struct EventCollector {
+            events: Vec<AgentExecutionEvent>,
+        }
+        impl AgentObserver for EventCollector {
+            fn on_event(&mut self, event: AgentExecutionEvent) {
+                self.events.push(event);
+            }
+        }
+
+        let token = CancellationToken::new();
+        let mut collector = EventCollector { events: Vec::new() };
+        let result = agent
+            .execute_with_observer("What changed?", &token, &mut collector)
+            .await;
+
+        assert_eq!(result.unwrap(), "Done");
+        let request_tokens = request_tokens(&provider);
+        assert_eq!(request_tokens.len(), 2);
+        assert!(request_tokens[0] > 1000);
+        assert!(request_tokens[1] <= 600);
+        assert!(collector.events.iter().any(|event| matches!(
+            event,
+            AgentExecutionEvent::ContextCompacted {
+                limit: 1000,
+                messages_summarized: 8,
+                ..
+            }
+        )));
+        let messages = agent.conversation().messages();
+        assert!(messages
+            .iter()
+            .any(|m| m.role == "user" && m.content.as_deref() == Some("What changed?")));
+        assert!(messages
+            .iter()
+            .any(|m| m.role == "system"
+                && m.content.as_deref().is_some_and(|c| c.contains("Summary"))));
+    }
+
+    #[tokio::test]
+    async fn test_context_overflow_fails_when_user_message_alone_is_too_large() {
+        let (mut agent, provider) = overflow_agent(1000);
+
+        let result = agent.execute("z".repeat(8000)).await;
+
+        match result {
+            Err(XzatomaError::InputTooLarge { tokens, limit }) => {
+                assert_eq!(tokens, 2000);
+                assert_eq!(limit, 1000);
+            }
+            other => panic!("expected InputTooLarge, got {:?}", other),
+        }
+        provider.assert_request_count(1);
+    }
+
+    #[tokio::test]
+    async fn test_nudges_model_that_describes_a_tool_call_once() {
+        let provider = ScriptedProvider::new()
+            .with_tool_call_nudge(true)
+            .then_text("I would use write_file to create a.md.")
+            .then_tool_call("write_file", serde_json::json!({"path": "a.md"}))
+            .then_text("Wrote a.md");
+        let runs = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut tools = ToolRegistry::new();
+        tools.register("write_file", Arc::new(WritingTool { runs: runs.clone() }));
+        let mut agent = Agent::new(provider, tools, AgentConfig::default()).unwrap();
+
+        assert_eq!(agent.execute("Create a.md").await.unwrap(), "Wrote a.md");
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 1);
+        let nudge = tool_call_nudge("write_file");
+        let nudges = agent
+            .conversation()
+            .messages()
+            .iter()
+            .filter(|m| m.role == "user" && m.content.as_deref() == Some(nudge.as_str()))
+            .count();
+        assert_eq!(nudges, 1);
+
+        // Later answers are accepted as they are, as is prose naming no tool
+        let provider = ScriptedProvider::new()
+            .with_tool_call_nudge(true)
+            .then_text("Nothing to rewrite here.");
+        let mut tools = ToolRegistry::new();
+        tools.register("write_file", Arc::new(WritingTool { runs }));
+        let mut agent = Agent::new(provider, tools, AgentConfig::default()).unwrap();
+        assert_eq!(
+            agent.execute("Anything to do?").await.unwrap(),
+            "Nothing to rewrite here."
+        );
+    }
+
+    #[test]
+    fn test_mentioned_tool_matches_whole_names() {
+        let definitions = vec![
+            serde_json::json!({"name": "finish"}),
+            serde_json::json!({"type": "function", "function": {"name": "read_file"}}),
+        ];
+        let cases = [
+            ("Call read_file on a.txt", Some("read_file")),
+            ("`read_file`(path)", Some("read_file")),
+            ("I read_files all day", None),
+            ("I will finish now", None),
+            ("Nothing here", None),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(mentioned_tool(text, &definitions), expected, "{text:?}");
+        }
+    }
+
+    /// Runs one prompt against a provider that calls `write_file` three
+    /// times at once, and returns the agent, the number of tool runs, and
+    /// the options negotiated for each request
+    async fn run_tool_calls(
+        capabilities: crate::providers::ProviderCapabilities,
+        tool_calls: crate::config::ToolCallsConfig,
+    ) -> (Agent, usize, Vec<crate::providers::ToolCallOptions>) {
+        let calls = (1..=3)
+            .map(|i| {
+                (
+                    "write_file".to_string(),
+                    serde_json::json!({ "path": format!("{}.md", i) }),
+                )
+            })
+            .collect();
+        let provider = ScriptedProvider::new()
+            .with_capabilities(capabilities)
+            .then_tool_calls(calls)
+            .with_fallback(CompletionResponse::new(Message::assistant("Done")));
+        let runs = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut tools = ToolRegistry::new();
+        tools.register("write_file", Arc::new(WritingTool { runs: runs.clone() }));
+        let mut config = AgentConfig::default();
+        config.tools.tool_calls = tool_calls;
+        let mut agent = Agent::new(provider.clone(), tools, config).unwrap();
+
+        assert_eq!(agent.execute("Write three files").await.unwrap(), "Done");
+
+        (
+            agent,
+            runs.load(std::sync::atomic::Ordering::SeqCst),
+            provider.tool_call_options(),
+        )
+    }
+
+    fn tool_results(agent: &Agent) -> Vec<(String, String)> {
+        agent
+            .conversation()
+            .messages()
+            .iter()
+            .filter(|m| m.role == "tool")
+            .map(|m| {
+                (
+                    m.tool_call_id.clone().unwrap_or_default(),
+                    m.content.clone().unwrap_or_default(),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_per_turn_follow_capabilities() {
+        use crate::config::ToolCallsConfig;
+        use crate::providers::ProviderCapabilities;
+
+        let parallel = ProviderCapabilities {
+            supports_parallel_tool_calls: true,
+            ..Default::default()
+        };
+        let cases = [
+            (parallel, ToolCallsConfig::default(), 3),
+            (
+                ProviderCapabilities::default(),
+                ToolCallsConfig::default(),
+                1,
+            ),
+            (
+                ProviderCapabilities {
+                    max_tools_per_turn: Some(2),
+                    ..parallel
+                },
+                ToolCallsConfig::default(),
+                2,
+            ),
+            (
+                ProviderCapabilities::default(),
+                ToolCallsConfig {
+                    supports_parallel_tool_calls: Some(true),
+                    ..Default::default()
+                },
+                3,
+            ),
+            (
+                parallel,
+                ToolCallsConfig {
+                    max_tools_per_turn: Some(1),
+                    ..Default::default()
+                },
+                1,
+            ),
+        ];
+
+        for (capabilities, config, expected_runs) in cases {
+            let (agent, runs, _) = run_tool_calls(capabilities, config).await;
+            assert_eq!(runs, expected_runs);
+
+            // Every call still gets a result, in order, and the skipped ones
+            // ask the model to try again.
+            let results = tool_results(&agent);
+            let ids: Vec<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+            assert_eq!(ids, vec!["call_1", "call_2", "call_3"]);
+            for (index, (_, content)) in results.iter().enumerate() {
+                assert_eq!(content.starts_with("Not run"), index >= expected_runs);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_options_sent_only_when_supported() {
+        use crate::config::{ToolCallsConfig, ToolChoiceMode};
+        use crate::providers::{ProviderCapabilities, ToolCallOptions};
+
+        let required = ToolCallsConfig {
+            tool_choice: ToolChoiceMode::Required,
+            ..Default::default()
+        };
+
+        let capabilities = ProviderCapabilities {
+            supports_tool_choice: true,
+            ..Default::default()
+        };
+        let (_, _, options) = run_tool_calls(capabilities, required.clone()).await;
+        assert_eq!(
+            options,
+            vec![
+                ToolCallOptions {
+                    tool_choice: Some(ToolChoiceMode::Required),
+                    parallel_tool_calls: Some(false),
+                },
+                ToolCallOptions {
+                    tool_choice: None,
+                    parallel_tool_calls: Some(false),
+                },
+            ]
+        );
+
+        let capabilities = ProviderCapabilities {
+            supports_tool_choice: true,
+            supports_parallel_tool_calls: true,
+            ..Default::default()
+        };
+        let (_, _, options) = run_tool_calls(capabilities, ToolCallsConfig::default()).await;
+        assert!(options.iter().all(|o| *o == ToolCallOptions::default()));
+
+        let (_, _, options) = run_tool_calls(ProviderCapabilities::default(), required).await;
+        assert!(options.iter().all(|o| *o == ToolCallOptions::default()));
+    }
 }
@@ -13,6 +13,10 @@
 //! observer.on_event(AgentExecutionEvent::PromptStarted);
 //! ```
 
+use crate::agent::pruning::PruneReport;
+use crate::agent::ToolCallStatus;
+use crate::tools::ToolOutputStream;
+
 /// Events emitted by the agent execution loop.
 ///
 /// Observers receive these events in the order they are emitted during a single
@@ -44,6 +48,28 @@ pub enum AgentExecutionEvent {
         has_tool_calls: bool,
     },
 
+    /// The provider reported token usage for a completion.
+    ///
+    /// Emitted once per provider response that carries usage, before
+    /// `ContextWindowUpdated`.
+    TokenUsageReported {
+        /// Prompt tokens consumed by this completion.
+        prompt_tokens: u64,
+        /// Completion tokens generated by this completion.
+        completion_tokens: u64,
+    },
+
+    /// The provider response was served from the provider response cache.
+    ///
+    /// Emitted instead of `TokenUsageReported` for cached responses. The token
+    /// counts are those of the original request and were not billed again.
+    ProviderCacheHit {
+        /// Prompt tokens recorded with the cached response.
+        prompt_tokens: u64,
+        /// Completion tokens recorded with the cached response.
+        completion_tokens: u64,
+    },
+
     /// The provider returned non-empty assistant text content.
     AssistantTextEmitted {
         /// The assistant text returned by the provider.
@@ -71,7 +97,25 @@ pub enum AgentExecutionEvent {
         arguments: String,
     },
 
-    /// A tool call completed successfully.
+    /// A running tool emitted a line of incremental output.
+    ///
+    /// Lines for a tool call always arrive before its `ToolCallCompleted` or
+    /// `ToolCallFailed` event, which still carries the complete output.
+    ToolOutputChunk {
+        /// Unique tool call identifier.
+        id: String,
+        /// Name of the tool that is running.
+        name: String,
+        /// Stream the line was written to.
+        stream: ToolOutputStream,
+        /// Line content without the trailing newline.
+        line: String,
+    },
+
+    /// A tool call returned a result.
+    ///
+    /// The tool may still have reported an error result; `status` tells them
+    /// apart.
     ToolCallCompleted {
         /// Unique tool call identifier.
         id: String,
@@ -79,6 +123,8 @@ pub enum AgentExecutionEvent {
         name: String,
         /// Output string returned by the tool executor.
         output: String,
+        /// Outcome classified from the tool result.
+        status: ToolCallStatus,
     },
 
     /// A tool call failed with an error.
@@ -110,6 +156,41 @@ pub enum AgentExecutionEvent {
         max_tokens: u64,
     },
 
+    /// The conversation was automatically summarized to free context.
+    ConversationSummarized {
+        /// Messages in the conversation before summarization.
+        messages_before: usize,
+        /// Messages in the conversation after summarization.
+        messages_after: usize,
+        /// Estimated tokens in use after summarization.
+        tokens_after: usize,
+    },
+
+    /// The provider rejected a request as too large for its context window,
+    /// and the conversation was compacted before the request was retried.
+    ContextCompacted {
+        /// Context window of the model, in tokens.
+        limit: usize,
+        /// Earlier messages replaced by a summary.
+        messages_summarized: usize,
+        /// Tool outputs of the current turn replaced by a placeholder.
+        tool_outputs_dropped: usize,
+        /// Estimated tokens in use before compaction.
+        tokens_before: usize,
+        /// Estimated tokens in use after compaction.
+        tokens_after: usize,
+    },
+
+    /// Messages were summarized or stubbed to save context.
+    ///
+    /// Emitted once per pruning decision, including threshold pruning and
+    /// history hygiene. For compaction and auto-summarization it follows the
+    /// `ContextCompacted` or `ConversationSummarized` event.
+    ConversationPruned {
+        /// Affected messages, the reason, and the token totals.
+        report: PruneReport,
+    },
+
     /// Cancellation was detected at a safe execution boundary.
     CancellationRequested,
 
@@ -182,6 +263,7 @@ impl AgentObserver for NoOpObserver {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::agent::pruning::PruneReason;
 
     #[test]
     fn test_no_op_observer_accepts_all_events() {
@@ -203,16 +285,48 @@ mod tests {
             name: "read_file".to_string(),
             arguments: r#"{"path":"foo"}"#.to_string(),
         });
+        observer.on_event(AgentExecutionEvent::ToolOutputChunk {
+            id: "tc-1".to_string(),
+            name: "read_file".to_string(),
+            stream: ToolOutputStream::Stdout,
+            line: "partial".to_string(),
+        });
         observer.on_event(AgentExecutionEvent::ToolCallCompleted {
             id: "tc-1".to_string(),
             name: "read_file".to_string(),
             output: "contents".to_string(),
+            status: ToolCallStatus::Success,
         });
         observer.on_event(AgentExecutionEvent::ToolCallFailed {
             id: "tc-1".to_string(),
             name: "read_file".to_string(),
             error: "not found".to_string(),
         });
+        observer.on_event(AgentExecutionEvent::TokenUsageReported {
+            prompt_tokens: 100,
+            completion_tokens: 20,
+        });
+        observer.on_event(AgentExecutionEvent::ConversationSummarized {
+            messages_before: 40,
+            messages_after: 6,
+            tokens_after: 900,
+        });
+        observer.on_event(AgentExecutionEvent::ContextCompacted {
+            limit: 8192,
+            messages_summarized: 12,
+            tool_outputs_dropped: 1,
+            tokens_before: 9100,
+            tokens_after: 4800,
+        });
+        observer.on_event(AgentExecutionEvent::ConversationPruned {
+            report: PruneReport {
+                reason: PruneReason::Threshold,
+                messages: Vec::new(),
+                tokens_before: 9100,
+                tokens_after: 6000,
+                summary_tokens: Some(80),
+            },
+        });
         observer.on_event(AgentExecutionEvent::VisionInputAttached { count: 2 });
         observer.on_event(AgentExecutionEvent::ContextWindowUpdated {
             used_tokens: 1024,
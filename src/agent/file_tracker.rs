@@ -0,0 +1,337 @@
+//! Detection of files changed on disk after the agent read them
+//!
+//! The user may edit files in an editor while the agent works. The agent
+//! then reasons about the copy it read earlier and writes patches that no
+//! longer apply. [`FileTracker`] records each file read through `read_file`
+//! or loaded by a mention, and [`FileTracker::check`] reports which of them
+//! changed since.
+//!
+//! The check stats each file and compares the modification time and size.
+//! File systems store modification times at a coarse granularity, so a
+//! write soon after a read can keep the same time. For a file modified
+//! shortly before it was recorded, a content hash is kept as well and
+//! compared when the time and size are unchanged.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Modification times this close to the recording time are not trusted alone
+const SUSPICIOUS_MTIME_WINDOW: Duration = Duration::from_secs(2);
+
+/// How a tracked file changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChangeKind {
+    /// The file content changed
+    Modified,
+    /// The file no longer exists
+    Deleted,
+}
+
+/// A tracked file that changed since it was last recorded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalChange {
+    /// Absolute path, canonicalized when the file existed when recorded
+    pub path: PathBuf,
+    /// The path as the agent or the user referred to it
+    pub display: String,
+    /// How the file changed
+    pub kind: FileChangeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    /// Content hash, kept only while the modification time is suspicious
+    hash: Option<u64>,
+}
+
+impl FileStamp {
+    /// Stats `path`, hashing it when its modification time is recent
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok().filter(|m| m.is_file())?;
+        let modified = metadata.modified().ok();
+        let hash = is_suspicious(modified).then(|| hash_file(path)).flatten();
+        Some(Self {
+            modified,
+            len: metadata.len(),
+            hash,
+        })
+    }
+}
+
+fn is_suspicious(modified: Option<SystemTime>) -> bool {
+    match modified {
+        Some(modified) => SystemTime::now()
+            .duration_since(modified)
+            .map_or(true, |age| age < SUSPICIOUS_MTIME_WINDOW),
+        None => true,
+    }
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    let content = std::fs::read(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+#[derive(Debug, Clone)]
+struct TrackedFile {
+    display: String,
+    stamp: FileStamp,
+}
+
+/// Files read during a session, with their state when last read
+///
+/// Clones share the same records, so the tracker survives agent rebuilds
+/// in chat.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::agent::file_tracker::{FileChangeKind, FileTracker};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("notes.md");
+/// std::fs::write(&path, "draft").unwrap();
+///
+/// let tracker = FileTracker::new();
+/// tracker.record(&path, "notes.md");
+/// assert!(tracker.check().is_empty());
+///
+/// std::fs::write(&path, "final draft").unwrap();
+/// let changes = tracker.check();
+/// assert_eq!(changes[0].display, "notes.md");
+/// assert_eq!(changes[0].kind, FileChangeKind::Modified);
+/// assert!(tracker.check().is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FileTracker {
+    files: Arc<Mutex<HashMap<PathBuf, TrackedFile>>>,
+}
+
+impl FileTracker {
+    /// Creates an empty tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the current state of a file the session read
+    ///
+    /// Paths that are not regular files are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Absolute path of the file
+    /// * `display` - The path as shown to the model, such as `src/config.rs`
+    pub fn record(&self, path: &Path, display: &str) {
+        let path = canonical(path);
+        let Some(stamp) = FileStamp::read(&path) else {
+            return;
+        };
+        self.lock().insert(
+            path,
+            TrackedFile {
+                display: display.to_string(),
+                stamp,
+            },
+        );
+    }
+
+    /// Records the new state of a tracked file the session changed itself
+    ///
+    /// Untracked paths are ignored, and a tracked file that no longer exists
+    /// is dropped.
+    pub fn refresh(&self, path: &Path) {
+        let path = canonical(path);
+        let mut files = self.lock();
+        if !files.contains_key(&path) {
+            return;
+        }
+        match FileStamp::read(&path) {
+            Some(stamp) => {
+                if let Some(file) = files.get_mut(&path) {
+                    file.stamp = stamp;
+                }
+            }
+            None => {
+                files.remove(&path);
+            }
+        }
+    }
+
+    /// Returns the tracked files that changed since they were recorded
+    ///
+    /// Each change is reported once: modified files are recorded again and
+    /// deleted files are no longer tracked.
+    pub fn check(&self) -> Vec<ExternalChange> {
+        let mut files = self.lock();
+        let mut changes = Vec::new();
+        files.retain(|path, file| {
+            let Some(current) = FileStamp::read(path) else {
+                changes.push(ExternalChange {
+                    path: path.clone(),
+                    display: file.display.clone(),
+                    kind: FileChangeKind::Deleted,
+                });
+                return false;
+            };
+            let changed = current.modified != file.stamp.modified
+                || current.len != file.stamp.len
+                || match file.stamp.hash {
+                    Some(recorded) => hash_file(path) != Some(recorded),
+                    None => false,
+                };
+            if changed {
+                changes.push(ExternalChange {
+                    path: path.clone(),
+                    display: file.display.clone(),
+                    kind: FileChangeKind::Modified,
+                });
+            }
+            // Replace the stamp so a suspicious time is re-evaluated, and
+            // the hash dropped once the time is old enough to trust
+            if changed || file.stamp.hash.is_some() {
+                file.stamp = current;
+            }
+            true
+        });
+        changes.sort_by(|a, b| a.display.cmp(&b.display));
+        changes
+    }
+
+    /// Returns the number of tracked files
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true when no file is tracked
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, TrackedFile>> {
+        self.files.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Formats the system note listing files changed outside the session
+///
+/// # Arguments
+///
+/// * `changes` - The changed files
+/// * `refreshed` - Display paths whose current content follows the note
+pub fn external_change_note(changes: &[ExternalChange], refreshed: &[String]) -> String {
+    let mut note = String::from("Files changed outside this session:");
+    for change in changes {
+        let line = match change.kind {
+            FileChangeKind::Deleted => format!(
+                "{} was deleted externally since you last read it.",
+                change.display
+            ),
+            FileChangeKind::Modified if refreshed.contains(&change.display) => format!(
+                "{} was modified externally since you last read it. Its current content follows.",
+                change.display
+            ),
+            FileChangeKind::Modified => format!(
+                "{} was modified externally since you last read it. Read it again before editing it.",
+                change.display
+            ),
+        };
+        note.push_str("\n- ");
+        note.push_str(&line);
+    }
+    note
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn set_mtime(path: &Path, modified: SystemTime) {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_same_mtime_and_size_is_caught_by_hash_only_when_recent() {
+        let dir = TempDir::new().unwrap();
+        let recent = dir.path().join("recent.rs");
+        let old = dir.path().join("old.rs");
+        std::fs::write(&recent, "fn a() {}").unwrap();
+        std::fs::write(&old, "fn a() {}").unwrap();
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        set_mtime(&old, an_hour_ago);
+
+        let tracker = FileTracker::new();
+        tracker.record(&recent, "recent.rs");
+        tracker.record(&old, "old.rs");
+
+        // Same size, and the modification time is put back
+        let recent_mtime = std::fs::metadata(&recent).unwrap().modified().unwrap();
+        std::fs::write(&recent, "fn b() {}").unwrap();
+        set_mtime(&recent, recent_mtime);
+        std::fs::write(&old, "fn b() {}").unwrap();
+        set_mtime(&old, an_hour_ago);
+
+        let changes = tracker.check();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].display, "recent.rs");
+    }
+
+    #[test]
+    fn test_deleted_files_are_reported_once_and_refresh_hides_own_edits() {
+        let dir = TempDir::new().unwrap();
+        let edited = dir.path().join("edited.rs");
+        let deleted = dir.path().join("deleted.rs");
+        std::fs::write(&edited, "a").unwrap();
+        std::fs::write(&deleted, "a").unwrap();
+        let tracker = FileTracker::new();
+        tracker.record(&edited, "edited.rs");
+        tracker.record(&deleted, "deleted.rs");
+        tracker.record(&dir.path().join("missing.rs"), "missing.rs");
+        assert_eq!(tracker.len(), 2);
+
+        std::fs::write(&edited, "changed by the agent").unwrap();
+        tracker.refresh(&edited);
+        std::fs::remove_file(&deleted).unwrap();
+
+        let changes = tracker.check();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, FileChangeKind::Deleted);
+        assert!(tracker.check().is_empty());
+        assert_eq!(tracker.len(), 1);
+    }
+
+    #[test]
+    fn test_external_change_note_lists_each_file() {
+        let change = |display: &str, kind| ExternalChange {
+            path: PathBuf::from(display),
+            display: display.to_string(),
+            kind,
+        };
+        let note = external_change_note(
+            &[
+                change("src/config.rs", FileChangeKind::Modified),
+                change("src/old.rs", FileChangeKind::Deleted),
+            ],
+            &[],
+        );
+        assert!(note.contains("- src/config.rs was modified externally since you last read it."));
+        assert!(note.contains("- src/old.rs was deleted externally since you last read it."));
+    }
+}
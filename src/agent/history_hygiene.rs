@@ -0,0 +1,430 @@
+//! Stubbing of superseded tool results in the conversation history
+//!
+//! Long conversations collect near-duplicate tool results: the same file read
+//! three times, the same grep output twice. Before each provider request,
+//! [`find_stubs`] picks the older copies that no longer carry information:
+//!
+//! - a tool result whose call (tool name and canonicalized arguments) was
+//!   repeated later with identical output becomes
+//!   `[superseded by later identical read of src/config.rs]`;
+//! - a `read_file` result for a file that a mutating tool later changed
+//!   becomes a stub noting the file has changed since.
+//!
+//! Reads of files changed outside the session are stubbed separately by
+//! [`find_external_stale_reads`] when the agent reads them again.
+//!
+//! The most recent copy of a result is always kept verbatim, and pinned
+//! messages are never stubbed. Calls and outputs are compared by hash, so
+//! the pass is deterministic and costs one hash per tool result.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use serde_json::Value;
+
+use crate::agent::conversation::{estimate_tokens, message_tokens};
+use crate::agent::loop_guard::call_hash;
+use crate::providers::{FunctionCall, Message};
+
+/// Start of the stub that replaces a result repeated later
+pub const SUPERSEDED_STUB_PREFIX: &str = "[superseded by later identical ";
+
+/// Start of the stub that replaces a read of a file changed later
+pub const STALE_READ_STUB_PREFIX: &str = "[earlier read of ";
+
+/// Argument names that hold a path a mutating tool changes
+pub(crate) const MUTATED_PATH_ARGUMENTS: [&str; 3] = ["path", "source_path", "destination_path"];
+
+/// Why a tool result was stubbed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StubReason {
+    /// The same call was made later with identical output
+    Duplicate,
+    /// The file read was modified later, by the agent or externally
+    StaleRead,
+}
+
+/// A tool result to replace with a stub
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stub {
+    /// Index of the tool result message in the conversation
+    pub index: usize,
+    /// Why the result is stubbed
+    pub reason: StubReason,
+    /// Replacement content
+    pub content: String,
+}
+
+/// What the history hygiene pass stubbed, accumulated over a conversation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HygieneReport {
+    /// Results replaced because an identical result came later
+    pub duplicates_stubbed: usize,
+    /// File reads replaced because the file changed later
+    pub stale_reads_stubbed: usize,
+    /// Estimated tokens removed from the context
+    pub tokens_reclaimed: usize,
+}
+
+impl HygieneReport {
+    /// Returns true when nothing was stubbed
+    pub fn is_empty(&self) -> bool {
+        self.duplicates_stubbed == 0 && self.stale_reads_stubbed == 0
+    }
+
+    /// Adds the counts of `other` to this report
+    pub fn add(&mut self, other: HygieneReport) {
+        self.duplicates_stubbed += other.duplicates_stubbed;
+        self.stale_reads_stubbed += other.stale_reads_stubbed;
+        self.tokens_reclaimed += other.tokens_reclaimed;
+    }
+}
+
+impl fmt::Display for HygieneReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} duplicate tool result(s) and {} stale file read(s) stubbed, about {} tokens reclaimed",
+            self.duplicates_stubbed, self.stale_reads_stubbed, self.tokens_reclaimed
+        )
+    }
+}
+
+/// Finds the tool results that can be replaced with stubs
+///
+/// # Arguments
+///
+/// * `messages` - Conversation messages, oldest first
+/// * `pinned` - Pin flags, parallel to `messages`
+/// * `is_mutating` - Whether a tool name changes the workspace
+///
+/// # Returns
+///
+/// Returns the stubs in message order. A stub is only returned when it is
+/// smaller than the result it replaces.
+pub fn find_stubs(
+    messages: &[Message],
+    pinned: &[bool],
+    is_mutating: &dyn Fn(&str) -> bool,
+) -> Vec<Stub> {
+    let calls: HashMap<&str, &FunctionCall> = messages
+        .iter()
+        .filter_map(|message| message.tool_calls.as_ref())
+        .flatten()
+        .map(|call| (call.id.as_str(), &call.function))
+        .collect();
+
+    // Tool results with their call, skipping results already stubbed
+    let results: Vec<(usize, &FunctionCall, &str)> = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| message.role == "tool")
+        .filter_map(|(index, message)| {
+            let call = calls.get(message.tool_call_id.as_deref()?)?;
+            let content = message.content.as_deref()?;
+            Some((index, *call, content))
+        })
+        .filter(|(_, _, content)| !is_stub(content))
+        .collect();
+
+    // Newest first, so the first copy of each result seen is the one kept
+    let mut seen: HashSet<(u64, u64)> = HashSet::new();
+    let mut modified: HashSet<String> = HashSet::new();
+    let mut stubs = Vec::new();
+    for &(index, call, content) in results.iter().rev() {
+        let read_path = (call.name == "read_file")
+            .then(|| path_argument(&call.arguments, "path"))
+            .flatten();
+        let is_new = seen.insert((call_hash(&call.name, &call.arguments), text_hash(content)));
+
+        if is_mutating(&call.name) {
+            if !content.starts_with("Error: ") {
+                for name in MUTATED_PATH_ARGUMENTS {
+                    if let Some(path) = path_argument(&call.arguments, name) {
+                        modified.insert(path);
+                    }
+                }
+            }
+            continue;
+        }
+        if pinned.get(index).copied().unwrap_or(false) {
+            continue;
+        }
+
+        let stub = if !is_new {
+            let subject = match &read_path {
+                Some(path) => format!("read of {}", path),
+                None => format!("{} call", call.name),
+            };
+            Some((
+                StubReason::Duplicate,
+                format!("{}{}]", SUPERSEDED_STUB_PREFIX, subject),
+            ))
+        } else {
+            read_path
+                .filter(|path| modified.contains(path))
+                .map(|path| {
+                    (
+                        StubReason::StaleRead,
+                        format!(
+                            "{}{} omitted: the file has changed since]",
+                            STALE_READ_STUB_PREFIX, path
+                        ),
+                    )
+                })
+        };
+        if let Some((reason, content)) = stub {
+            if estimate_tokens(&content) < message_tokens(&messages[index]) {
+                stubs.push(Stub {
+                    index,
+                    reason,
+                    content,
+                });
+            }
+        }
+    }
+    stubs.reverse();
+    stubs
+}
+
+/// Finds the `read_file` results for files changed outside the session
+///
+/// Every unpinned read of the given paths is stubbed, including the most
+/// recent one: the caller appends a fresh read after applying the stubs.
+///
+/// # Arguments
+///
+/// * `messages` - Conversation messages, oldest first
+/// * `pinned` - Pin flags, parallel to `messages`
+/// * `paths` - Changed paths, as passed to `read_file`
+///
+/// # Returns
+///
+/// Returns the stubs in message order
+pub fn find_external_stale_reads(
+    messages: &[Message],
+    pinned: &[bool],
+    paths: &HashSet<String>,
+) -> Vec<Stub> {
+    let calls: HashMap<&str, &FunctionCall> = messages
+        .iter()
+        .filter_map(|message| message.tool_calls.as_ref())
+        .flatten()
+        .filter(|call| call.function.name == "read_file")
+        .map(|call| (call.id.as_str(), &call.function))
+        .collect();
+
+    messages
+        .iter()
+        .enumerate()
+        .filter(|(index, message)| {
+            message.role == "tool" && !pinned.get(*index).copied().unwrap_or(false)
+        })
+        .filter_map(|(index, message)| {
+            let call = calls.get(message.tool_call_id.as_deref()?)?;
+            let content = message.content.as_deref()?;
+            let path = path_argument(&call.arguments, "path").filter(|p| paths.contains(p))?;
+            (!is_stub(content)).then(|| Stub {
+                index,
+                reason: StubReason::StaleRead,
+                content: format!(
+                    "{}{} omitted: the file was modified outside this session since]",
+                    STALE_READ_STUB_PREFIX, path
+                ),
+            })
+        })
+        .collect()
+}
+
+fn is_stub(content: &str) -> bool {
+    content.starts_with(SUPERSEDED_STUB_PREFIX) || content.starts_with(STALE_READ_STUB_PREFIX)
+}
+
+fn text_hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Reads a path argument, normalized so `./src/a.rs` and `src/a.rs` match
+pub(crate) fn path_argument(arguments: &str, name: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(arguments).ok()?;
+    let path = value.get(name)?.as_str()?;
+    let path = path.trim_start_matches("./").trim_end_matches('/');
+    (!path.is_empty()).then(|| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::ToolCall;
+
+    /// Builds an assistant tool call followed by its result
+    fn call(id: &str, name: &str, arguments: &str, output: &str) -> [Message; 2] {
+        [
+            Message::assistant_with_tools(vec![ToolCall {
+                id: id.to_string(),
+                function: FunctionCall {
+                    name: name.to_string(),
+                    arguments: arguments.to_string(),
+                },
+            }]),
+            Message::tool_result(id, output),
+        ]
+    }
+
+    fn is_mutating(name: &str) -> bool {
+        matches!(name, "write_file" | "edit_file" | "move_path")
+    }
+
+    fn stubbed(messages: &[Message], pinned: &[bool]) -> Vec<(usize, StubReason)> {
+        find_stubs(messages, pinned, &is_mutating)
+            .into_iter()
+            .map(|stub| (stub.index, stub.reason))
+            .collect()
+    }
+
+    fn long(text: &str) -> String {
+        text.repeat(40)
+    }
+
+    #[test]
+    fn test_older_identical_results_are_stubbed_and_latest_kept() {
+        let config = long("retries: 3\n");
+        let grep = long("src/a.rs:1: todo\n");
+        let mut messages = vec![Message::user("look at the config")];
+        messages.extend(call(
+            "1",
+            "read_file",
+            r#"{"path":"src/config.rs"}"#,
+            &config,
+        )); // 1, 2
+        messages.extend(call("2", "grep", r#"{"regex":"todo"}"#, &grep)); // 3, 4
+        messages.extend(call(
+            "3",
+            "read_file",
+            r#"{ "path": "src/config.rs" }"#,
+            &config,
+        )); // 5, 6
+        messages.extend(call("4", "grep", r#"{"regex":"todo"}"#, &long("other\n"))); // 7, 8
+        messages.extend(call(
+            "5",
+            "read_file",
+            r#"{"path":"src/config.rs"}"#,
+            &config,
+        )); // 9, 10
+        let pinned = vec![false; messages.len()];
+
+        assert_eq!(
+            stubbed(&messages, &pinned),
+            [(2, StubReason::Duplicate), (6, StubReason::Duplicate)]
+        );
+        let stubs = find_stubs(&messages, &pinned, &is_mutating);
+        assert_eq!(
+            stubs[0].content,
+            "[superseded by later identical read of src/config.rs]"
+        );
+    }
+
+    #[test]
+    fn test_reads_before_a_modification_are_stubbed() {
+        let original = long("fn a() {}\n");
+        let mut messages = Vec::new();
+        messages.extend(call(
+            "1",
+            "read_file",
+            r#"{"path":"./src/a.rs"}"#,
+            &original,
+        )); // 0, 1
+        messages.extend(call("2", "read_file", r#"{"path":"src/b.rs"}"#, &original)); // 2, 3
+        messages.extend(call(
+            "3",
+            "edit_file",
+            r#"{"path":"src/a.rs","content":"x"}"#,
+            "Edited src/a.rs",
+        )); // 4, 5
+        messages.extend(call(
+            "4",
+            "write_file",
+            r#"{"path":"src/b.rs","content":"x"}"#,
+            "Error: permission denied",
+        )); // 6, 7
+        messages.extend(call(
+            "5",
+            "read_file",
+            r#"{"path":"src/a.rs"}"#,
+            &long("fn b() {}\n"),
+        )); // 8, 9
+        let pinned = vec![false; messages.len()];
+
+        assert_eq!(stubbed(&messages, &pinned), [(1, StubReason::StaleRead)]);
+        assert_eq!(
+            find_stubs(&messages, &pinned, &is_mutating)[0].content,
+            "[earlier read of src/a.rs omitted: the file has changed since]"
+        );
+    }
+
+    #[test]
+    fn test_pinned_short_and_already_stubbed_results_are_left_alone() {
+        let output = long("line\n");
+        let mut messages = Vec::new();
+        messages.extend(call("1", "grep", r#"{"regex":"x"}"#, &output)); // 0, 1
+        messages.extend(call("2", "grep", r#"{"regex":"x"}"#, &output)); // 2, 3
+        messages.extend(call("3", "list_directory", "{}", "a")); // 4, 5
+        messages.extend(call("4", "list_directory", "{}", "a")); // 6, 7
+        messages.extend(call("5", "grep", r#"{"regex":"x"}"#, &output)); // 8, 9
+
+        let mut pinned = vec![false; messages.len()];
+        pinned[1] = true;
+        assert_eq!(stubbed(&messages, &pinned), [(3, StubReason::Duplicate)]);
+
+        messages[3].content = Some("[superseded by later identical grep call]".to_string());
+        assert!(stubbed(&messages, &pinned).is_empty());
+    }
+
+    #[test]
+    fn test_move_marks_source_and_destination_as_modified() {
+        let output = long("text\n");
+        let mut messages = Vec::new();
+        messages.extend(call("1", "read_file", r#"{"path":"a.md"}"#, &output));
+        messages.extend(call("2", "read_file", r#"{"path":"b.md"}"#, &output));
+        messages.extend(call(
+            "3",
+            "move_path",
+            r#"{"source_path":"a.md","destination_path":"b.md"}"#,
+            "Moved",
+        ));
+        let pinned = vec![false; messages.len()];
+
+        assert_eq!(
+            stubbed(&messages, &pinned),
+            [(1, StubReason::StaleRead), (3, StubReason::StaleRead)]
+        );
+    }
+
+    #[test]
+    fn test_external_stale_reads_stub_every_unpinned_read_of_the_path() {
+        let output = long("fn a() {}\n");
+        let mut messages = Vec::new();
+        messages.extend(call("1", "read_file", r#"{"path":"./src/a.rs"}"#, &output)); // 0, 1
+        messages.extend(call("2", "read_file", r#"{"path":"src/b.rs"}"#, &output)); // 2, 3
+        messages.extend(call("3", "grep", r#"{"path":"src/a.rs"}"#, &output)); // 4, 5
+        messages.extend(call("4", "read_file", r#"{"path":"src/a.rs"}"#, "short")); // 6, 7
+        messages.extend(call("5", "read_file", r#"{"path":"src/a.rs"}"#, &output)); // 8, 9
+        let mut pinned = vec![false; messages.len()];
+        pinned[9] = true;
+
+        let paths = HashSet::from(["src/a.rs".to_string()]);
+        let stubs = find_external_stale_reads(&messages, &pinned, &paths);
+        assert_eq!(
+            stubs.iter().map(|stub| stub.index).collect::<Vec<_>>(),
+            [1, 7]
+        );
+        assert_eq!(
+            stubs[0].content,
+            "[earlier read of src/a.rs omitted: the file was modified outside this session since]"
+        );
+    }
+}
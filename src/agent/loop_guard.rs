@@ -0,0 +1,329 @@
+//! Detection of repeated tool calls
+//!
+//! Models sometimes get stuck calling the same tool with the same arguments
+//! over and over, for example grepping for one pattern or re-reading a file
+//! that has not changed. [`LoopGuard`] counts identical calls within one
+//! prompt. A call is identified by a hash of the tool name and its
+//! canonicalized arguments, so key order and whitespace do not matter.
+//!
+//! From `repeat_threshold` identical calls on, a read-only tool is not run
+//! again: the previous result is returned with [`REPEATED_CALL_NOTE`]. Other
+//! tools still run, since their effects cannot be cached, and their result
+//! carries a note that the call was already made. At `stop_threshold` identical calls the prompt is
+//! stopped with [`XzatomaError::ToolLoopDetected`].
+//!
+//! A read-only call only counts as a repeat while the workspace is
+//! unchanged. Any successful call to a mutating tool starts a new
+//! generation, after which read-only calls are counted afresh.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use serde_json::Value;
+
+use crate::config::LoopDetectionConfig;
+use crate::error::XzatomaError;
+use crate::tools::ToolResult;
+
+/// Metadata key recording that a result was affected by loop detection
+pub const LOOP_GUARD_METADATA: &str = "loop_guard";
+
+/// Note added to the results of repeated calls
+pub const REPEATED_CALL_NOTE: &str =
+    "repeated call — returning cached result; consider a different approach";
+
+/// Note added to repeated calls that had to run again
+const REPEATED_UNCACHED_NOTE: &str =
+    "repeated call — this exact call was already made; consider a different approach";
+
+/// What to do with a tool call before it runs
+#[derive(Debug)]
+pub enum LoopCheck {
+    /// Run the call, then pass its result to [`LoopGuard::after_call`]
+    Run(CallKey),
+    /// Answer the call with this earlier result instead of running it
+    Cached(ToolResult),
+    /// Stop the prompt with this error
+    Stop(XzatomaError),
+}
+
+/// Identifies one tool call across [`LoopGuard::before_call`] and
+/// [`LoopGuard::after_call`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallKey(u64);
+
+#[derive(Debug)]
+struct CallRecord {
+    read_only: bool,
+    repeats: usize,
+    generation: u64,
+    result: Option<ToolResult>,
+}
+
+/// Counts identical tool calls within one prompt
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::agent::loop_guard::{LoopCheck, LoopGuard};
+/// use xzatoma::config::LoopDetectionConfig;
+/// use xzatoma::tools::ToolResult;
+///
+/// let mut guard = LoopGuard::new(LoopDetectionConfig::default());
+/// for _ in 0..2 {
+///     let LoopCheck::Run(key) = guard.before_call("grep", r#"{"regex":"todo"}"#, true) else {
+///         panic!("expected the call to run");
+///     };
+///     guard.after_call(key, false, ToolResult::success("3 matches".to_string()));
+/// }
+///
+/// let LoopCheck::Cached(result) = guard.before_call("grep", r#"{ "regex": "todo" }"#, true) else {
+///     panic!("expected a cached result");
+/// };
+/// assert!(result.output.contains("3 matches"));
+/// ```
+#[derive(Debug)]
+pub struct LoopGuard {
+    config: LoopDetectionConfig,
+    calls: HashMap<u64, CallRecord>,
+    generation: u64,
+}
+
+impl LoopGuard {
+    /// Creates a guard with no recorded calls
+    pub fn new(config: LoopDetectionConfig) -> Self {
+        Self {
+            config,
+            calls: HashMap::new(),
+            generation: 0,
+        }
+    }
+
+    /// Forgets every recorded call; called at the start of each prompt
+    pub fn reset(&mut self) {
+        self.calls.clear();
+        self.generation = 0;
+    }
+
+    /// Starts a new workspace generation, as a successful mutating call does
+    ///
+    /// Called when files changed outside the session, so cached read-only
+    /// results are not served again.
+    pub fn start_generation(&mut self) {
+        self.generation += 1;
+    }
+
+    /// Records a call and decides whether it should run
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Tool name
+    /// * `arguments` - Arguments as sent by the model
+    /// * `read_only` - Whether the tool reports
+    ///   [`ToolExecutor::read_only`](crate::tools::ToolExecutor::read_only)
+    pub fn before_call(&mut self, name: &str, arguments: &str, read_only: bool) -> LoopCheck {
+        let key = call_hash(name, arguments);
+        if !self.config.enabled {
+            return LoopCheck::Run(CallKey(key));
+        }
+
+        let generation = self.generation;
+        let record = self.calls.entry(key).or_insert(CallRecord {
+            read_only,
+            repeats: 0,
+            generation,
+            result: None,
+        });
+        if read_only && record.generation != generation {
+            record.repeats = 0;
+            record.generation = generation;
+            record.result = None;
+        }
+        record.repeats += 1;
+
+        if record.repeats >= self.config.stop_threshold {
+            return LoopCheck::Stop(XzatomaError::ToolLoopDetected {
+                tool: name.to_string(),
+                repeats: record.repeats,
+            });
+        }
+        if record.repeats >= self.config.repeat_threshold {
+            if let Some(result) = &record.result {
+                return LoopCheck::Cached(annotate(result.clone(), REPEATED_CALL_NOTE, "cached"));
+            }
+        }
+        LoopCheck::Run(CallKey(key))
+    }
+
+    /// Records the result of a call that ran
+    ///
+    /// Results of read-only calls are kept for later repeats. A successful
+    /// call to a mutating tool starts a new workspace generation.
+    ///
+    /// # Returns
+    ///
+    /// Returns the result, annotated when the call was a repeat.
+    pub fn after_call(&mut self, key: CallKey, mutates: bool, result: ToolResult) -> ToolResult {
+        if !self.config.enabled {
+            return result;
+        }
+        if mutates && result.success {
+            self.generation += 1;
+        }
+
+        let Some(record) = self.calls.get_mut(&key.0) else {
+            return result;
+        };
+        if record.read_only && record.result.is_none() {
+            record.result = Some(result.clone());
+        }
+        if record.repeats >= self.config.repeat_threshold {
+            annotate(result, REPEATED_UNCACHED_NOTE, "repeated")
+        } else {
+            result
+        }
+    }
+}
+
+fn annotate(result: ToolResult, note: &str, marker: &str) -> ToolResult {
+    let mut annotated = result.with_metadata(LOOP_GUARD_METADATA.to_string(), marker.to_string());
+    annotated.output = format!("[{}]\n{}", note, annotated.output);
+    annotated
+}
+
+/// Hashes a tool name and its canonicalized arguments
+///
+/// Arguments that are not valid JSON are hashed as given.
+pub(crate) fn call_hash(name: &str, arguments: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    match serde_json::from_str::<Value>(arguments) {
+        Ok(value) => hash_value(&value, &mut hasher),
+        Err(_) => arguments.hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+fn hash_value(value: &Value, hasher: &mut DefaultHasher) {
+    match value {
+        Value::Null => 0u8.hash(hasher),
+        Value::Bool(b) => (1u8, b).hash(hasher),
+        Value::Number(n) => (2u8, n.to_string()).hash(hasher),
+        Value::String(s) => (3u8, s).hash(hasher),
+        Value::Array(items) => {
+            (4u8, items.len()).hash(hasher);
+            for item in items {
+                hash_value(item, hasher);
+            }
+        }
+        Value::Object(map) => {
+            (5u8, map.len()).hash(hasher);
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            for (key, item) in entries {
+                key.hash(hasher);
+                hash_value(item, hasher);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(guard: &mut LoopGuard, name: &str, args: &str, read_only: bool) -> LoopCheck {
+        guard.before_call(name, args, read_only)
+    }
+
+    #[test]
+    fn test_read_only_repeats_are_cached_then_stopped() {
+        let mut guard = LoopGuard::new(LoopDetectionConfig::default());
+        for _ in 0..2 {
+            let LoopCheck::Run(key) = run(&mut guard, "read_file", r#"{"path":"a"}"#, true) else {
+                panic!("expected run");
+            };
+            let result = guard.after_call(key, false, ToolResult::success("abc".to_string()));
+            assert!(!result.output.contains("repeated call"));
+        }
+
+        let LoopCheck::Cached(cached) = run(&mut guard, "read_file", r#"{"path":"a"}"#, true)
+        else {
+            panic!("expected cached result");
+        };
+        assert!(cached
+            .output
+            .starts_with(&format!("[{}]", REPEATED_CALL_NOTE)));
+        assert_eq!(cached.metadata[LOOP_GUARD_METADATA], "cached");
+
+        assert!(matches!(
+            run(&mut guard, "read_file", r#"{"path":"a"}"#, true),
+            LoopCheck::Cached(_)
+        ));
+        let LoopCheck::Stop(XzatomaError::ToolLoopDetected { tool, repeats }) =
+            run(&mut guard, "read_file", r#"{"path":"a"}"#, true)
+        else {
+            panic!("expected stop");
+        };
+        assert_eq!((tool.as_str(), repeats), ("read_file", 5));
+    }
+
+    #[test]
+    fn test_mutation_resets_read_only_repeats() {
+        let mut guard = LoopGuard::new(LoopDetectionConfig::default());
+        for _ in 0..2 {
+            let LoopCheck::Run(key) = run(&mut guard, "grep", r#"{"regex":"x"}"#, true) else {
+                panic!("expected run");
+            };
+            guard.after_call(key, false, ToolResult::success("none".to_string()));
+        }
+        let LoopCheck::Run(key) = run(&mut guard, "edit_file", r#"{"path":"a"}"#, false) else {
+            panic!("expected run");
+        };
+        guard.after_call(key, true, ToolResult::success("edited".to_string()));
+
+        assert!(matches!(
+            run(&mut guard, "grep", r#"{"regex":"x"}"#, true),
+            LoopCheck::Run(_)
+        ));
+    }
+
+    #[test]
+    fn test_mutating_repeats_run_and_are_flagged() {
+        let mut guard = LoopGuard::new(LoopDetectionConfig::default());
+        let args = r#"{"path":"a","content":"x"}"#;
+        let mut last = None;
+        for _ in 0..3 {
+            let LoopCheck::Run(key) = run(&mut guard, "write_file", args, false) else {
+                panic!("mutating calls are never cached");
+            };
+            last = Some(guard.after_call(key, true, ToolResult::success("written".to_string())));
+        }
+        let last = last.unwrap();
+        assert!(last.output.contains("already made"));
+        assert_eq!(last.metadata[LOOP_GUARD_METADATA], "repeated");
+    }
+
+    #[test]
+    fn test_argument_order_does_not_matter_and_reset_clears() {
+        let mut guard = LoopGuard::new(LoopDetectionConfig::default());
+        assert_eq!(
+            call_hash("grep", r#"{"a":1,"b":[true,null]}"#),
+            call_hash("grep", r#"{ "b": [true, null], "a": 1 }"#)
+        );
+        assert_ne!(
+            call_hash("grep", r#"{"a":1}"#),
+            call_hash("find_path", r#"{"a":1}"#)
+        );
+
+        for _ in 0..4 {
+            run(&mut guard, "terminal", "{}", false);
+        }
+        guard.reset();
+        assert!(matches!(
+            run(&mut guard, "terminal", "{}", false),
+            LoopCheck::Run(_)
+        ));
+    }
+}
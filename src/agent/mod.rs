@@ -3,20 +3,47 @@
 //! This module contains the core agent logic, including conversation management,
 //! tool execution, and the main agent execution loop.
 
+pub mod change_review;
 pub mod conversation;
 pub mod core;
 pub mod events;
+pub mod file_tracker;
+pub mod history_hygiene;
+pub mod loop_guard;
 pub mod metrics;
+pub mod mode_gate;
+pub mod outcome;
 pub mod persistence;
+pub mod preflight;
+pub mod pruning;
 pub mod quota;
+pub mod session;
+pub mod step_policy;
 pub(crate) mod thinking;
+pub mod tool_health;
+pub mod tool_metrics;
 pub use thinking::extract_thinking;
 
-pub use conversation::{ContextInfo, ContextStatus, Conversation};
+pub use change_review::{ChangeReview, ReviewDecision, TerminalChangeReview};
+pub use conversation::{
+    Compaction, ContextBreakdown, ContextCategory, ContextEntry, ContextInfo, ContextStatus,
+    Conversation,
+};
 pub use core::Agent;
 pub use events::{AgentExecutionEvent, AgentObserver, NoOpObserver};
+pub use file_tracker::FileTracker;
+pub use loop_guard::{LoopCheck, LoopGuard};
 pub use metrics::{init_metrics_exporter, SubagentMetrics};
+pub use mode_gate::{EscalationRequest, ModeEscalation, ModeGate, TerminalModeEscalation};
+pub use outcome::{ExecutionOutcome, FinishStatus};
 pub use persistence::{
     new_conversation_id, now_rfc3339, ConversationMetadata, ConversationRecord, ConversationStore,
 };
+pub use pruning::{PruneAction, PruneReason, PruneReport, PrunedMessage};
 pub use quota::{QuotaLimits, QuotaTracker, QuotaUsage};
+pub use session::{AgentSession, BudgetReason, SessionState, TurnBudget, TurnOutcome};
+pub use step_policy::{PlanStepExecutor, StepPolicy};
+pub use tool_health::{ToolHealth, ToolHealthEntry, ToolHealthStatus};
+pub use tool_metrics::{
+    ToolCallStatus, ToolMetrics, ToolMetricsSummary, ToolStats, ToolStatsEntry,
+};
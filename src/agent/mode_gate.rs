@@ -0,0 +1,297 @@
+//! Mode-aware tool gating
+//!
+//! In Planning mode the agent must not change the workspace, but models still
+//! sometimes call mutating tools. Tools declare whether they mutate state
+//! through [`ToolExecutor::mutates`](crate::tools::ToolExecutor::mutates), and
+//! the agent consults its [`ModeGate`] before dispatching each call.
+//!
+//! A mutating call in Planning mode is answered with a refusal
+//! [`ToolResult`] that tells the model it is in Planning mode. When an
+//! escalation handler is installed and mode switching is allowed, the user is
+//! asked first; on approval the gate switches to Write mode, the Write mode
+//! tools are added to the agent, and the call runs as normal.
+
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+
+use crate::chat_mode::ChatMode;
+use crate::tools::{ToolRegistry, ToolResult};
+
+/// Metadata key recording how the gate handled a call
+pub const MODE_GATE_METADATA: &str = "mode_gate";
+
+/// Asks whether the agent may leave Planning mode
+pub trait ModeEscalation: Send + Sync {
+    /// Returns true when the user allows switching to Write mode
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The tool call that needs Write mode
+    fn approve(&self, request: &EscalationRequest) -> bool;
+}
+
+/// A mutating tool call made in Planning mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscalationRequest {
+    /// Name of the tool the model called
+    pub tool: String,
+    /// Short description of what the call would do
+    pub action: String,
+}
+
+impl EscalationRequest {
+    /// Describes a tool call from its name and arguments
+    ///
+    /// Uses the `command` argument for terminal calls and the `path`
+    /// argument for file tools.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::agent::mode_gate::EscalationRequest;
+    ///
+    /// let request = EscalationRequest::from_call("write_file", r#"{"path":"src/lib.rs"}"#);
+    /// assert_eq!(request.action, "use write_file on src/lib.rs");
+    /// ```
+    pub fn from_call(tool: &str, arguments: &str) -> Self {
+        let args: serde_json::Value = serde_json::from_str(arguments).unwrap_or_default();
+        let argument = |key: &str| args.get(key).and_then(|v| v.as_str());
+        let action = if let Some(command) = argument("command") {
+            format!("run `{}`", command)
+        } else if let Some(path) = argument("path").or_else(|| argument("source")) {
+            format!("use {} on {}", tool, path)
+        } else {
+            format!("use {}", tool)
+        };
+        Self {
+            tool: tool.to_string(),
+            action,
+        }
+    }
+}
+
+impl fmt::Display for EscalationRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The agent wants to switch to Write mode to {}.",
+            self.action
+        )
+    }
+}
+
+/// Asks on the terminal: "The agent wants to switch to Write mode to X. Allow? [y/N]"
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TerminalModeEscalation;
+
+impl ModeEscalation for TerminalModeEscalation {
+    fn approve(&self, request: &EscalationRequest) -> bool {
+        eprint!("{} Allow? [y/N] ", request);
+        let _ = std::io::stderr().flush();
+
+        let mut line = String::new();
+        if std::io::stdin().lock().read_line(&mut line).is_err() {
+            return false;
+        }
+        let answer = line.trim().to_lowercase();
+        answer == "y" || answer == "yes"
+    }
+}
+
+/// Outcome of checking a tool call against the gate
+pub(crate) enum GateDecision {
+    /// Dispatch the call
+    Allow,
+    /// Dispatch the call after switching to Write mode; carries the tools to add
+    Escalate(Option<ToolRegistry>),
+    /// Answer the call with this result instead of running it
+    Refuse(ToolResult),
+}
+
+/// Chat mode state consulted before each tool call
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::agent::mode_gate::ModeGate;
+/// use xzatoma::chat_mode::ChatMode;
+///
+/// let gate = ModeGate::new(ChatMode::Planning).with_mode_switching(false);
+/// assert_eq!(gate.mode(), ChatMode::Planning);
+/// ```
+pub struct ModeGate {
+    mode: ChatMode,
+    allow_mode_switching: bool,
+    escalation: Option<Arc<dyn ModeEscalation>>,
+    write_tools: Option<ToolRegistry>,
+}
+
+impl ModeGate {
+    /// Creates a gate for `mode` without an escalation handler
+    pub fn new(mode: ChatMode) -> Self {
+        Self {
+            mode,
+            allow_mode_switching: true,
+            escalation: None,
+            write_tools: None,
+        }
+    }
+
+    /// Asks `escalation` before refusing a mutating call in Planning mode
+    ///
+    /// # Arguments
+    ///
+    /// * `escalation` - Handler that asks the user
+    /// * `write_tools` - Tools added to the agent when it switches to Write
+    ///   mode; tools it already has are kept
+    pub fn with_escalation(
+        mut self,
+        escalation: Arc<dyn ModeEscalation>,
+        write_tools: ToolRegistry,
+    ) -> Self {
+        self.escalation = Some(escalation);
+        self.write_tools = Some(write_tools);
+        self
+    }
+
+    /// Sets whether the gate may switch modes (`chat.allow_mode_switching`)
+    pub fn with_mode_switching(mut self, allowed: bool) -> Self {
+        self.allow_mode_switching = allowed;
+        self
+    }
+
+    /// Returns the current mode
+    pub fn mode(&self) -> ChatMode {
+        self.mode
+    }
+
+    /// Returns a tool that only becomes available in Write mode
+    pub(crate) fn write_tool(&self, name: &str) -> Option<Arc<dyn crate::tools::ToolExecutor>> {
+        self.write_tools.as_ref().and_then(|tools| tools.get(name))
+    }
+
+    /// Decides how to handle a call to a mutating tool
+    pub(crate) fn check(&mut self, request: EscalationRequest) -> GateDecision {
+        if self.mode != ChatMode::Planning {
+            return GateDecision::Allow;
+        }
+
+        if !self.allow_mode_switching {
+            return GateDecision::Refuse(refusal(
+                &request,
+                "Mode switching is disabled (chat.allow_mode_switching: false).",
+                "refused",
+            ));
+        }
+        let Some(escalation) = &self.escalation else {
+            return GateDecision::Refuse(refusal(
+                &request,
+                "Describe the change in your plan instead; the user can switch to Write mode with /mode write.",
+                "refused",
+            ));
+        };
+
+        if escalation.approve(&request) {
+            tracing::info!(tool = %request.tool, "User approved switching to Write mode");
+            self.mode = ChatMode::Write;
+            GateDecision::Escalate(self.write_tools.take())
+        } else {
+            GateDecision::Refuse(refusal(
+                &request,
+                "The user declined to switch to Write mode. Continue planning without modifying anything.",
+                "declined",
+            ))
+        }
+    }
+}
+
+impl fmt::Debug for ModeGate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModeGate")
+            .field("mode", &self.mode)
+            .field("allow_mode_switching", &self.allow_mode_switching)
+            .field("escalation", &self.escalation.is_some())
+            .finish()
+    }
+}
+
+/// Builds the structured refusal returned to the model
+fn refusal(request: &EscalationRequest, guidance: &str, outcome: &str) -> ToolResult {
+    ToolResult::error(format!(
+        "Tool '{}' modifies the workspace and is not available in PLANNING mode. {}",
+        request.tool, guidance
+    ))
+    .with_metadata(MODE_GATE_METADATA.to_string(), outcome.to_string())
+    .with_metadata("chat_mode".to_string(), ChatMode::Planning.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Answer(bool);
+
+    impl ModeEscalation for Answer {
+        fn approve(&self, _request: &EscalationRequest) -> bool {
+            self.0
+        }
+    }
+
+    fn request() -> EscalationRequest {
+        EscalationRequest::from_call("terminal", r#"{"command":"cargo fmt"}"#)
+    }
+
+    #[test]
+    fn test_request_describes_call() {
+        assert_eq!(request().action, "run `cargo fmt`");
+        assert_eq!(
+            request().to_string(),
+            "The agent wants to switch to Write mode to run `cargo fmt`."
+        );
+        assert_eq!(
+            EscalationRequest::from_call("delete_path", "not json").action,
+            "use delete_path"
+        );
+    }
+
+    #[test]
+    fn test_write_mode_allows_everything() {
+        let mut gate = ModeGate::new(ChatMode::Write).with_mode_switching(false);
+        assert!(matches!(gate.check(request()), GateDecision::Allow));
+    }
+
+    #[test]
+    fn test_planning_without_handler_refuses() {
+        let mut gate = ModeGate::new(ChatMode::Planning);
+        let GateDecision::Refuse(result) = gate.check(request()) else {
+            panic!("expected refusal");
+        };
+        assert!(!result.success);
+        assert_eq!(result.metadata[MODE_GATE_METADATA], "refused");
+        assert_eq!(gate.mode(), ChatMode::Planning);
+    }
+
+    #[test]
+    fn test_declined_escalation_keeps_planning_mode() {
+        let mut gate = ModeGate::new(ChatMode::Planning)
+            .with_escalation(Arc::new(Answer(false)), ToolRegistry::new());
+        let GateDecision::Refuse(result) = gate.check(request()) else {
+            panic!("expected refusal");
+        };
+        assert_eq!(result.metadata[MODE_GATE_METADATA], "declined");
+        assert_eq!(gate.mode(), ChatMode::Planning);
+    }
+
+    #[test]
+    fn test_approved_escalation_switches_once() {
+        let mut gate = ModeGate::new(ChatMode::Planning)
+            .with_escalation(Arc::new(Answer(true)), ToolRegistry::new());
+        assert!(matches!(
+            gate.check(request()),
+            GateDecision::Escalate(Some(_))
+        ));
+        assert_eq!(gate.mode(), ChatMode::Write);
+        assert!(matches!(gate.check(request()), GateDecision::Allow));
+    }
+}
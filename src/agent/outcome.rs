@@ -0,0 +1,122 @@
+//! Structured result of an agent run
+//!
+//! A run ends in one of two ways. Either the model calls the `finish` tool
+//! with a status and a summary, or it stops calling tools and answers in
+//! plain text. Both are surfaced as an [`ExecutionOutcome`], so run mode,
+//! the subagent tool, and the watcher can tell a completed task from one
+//! that failed or needs more input. A plain-text ending is treated as
+//! success, with the text as the summary.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Status reported by the `finish` tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishStatus {
+    /// The task was completed
+    Success,
+    /// The task could not be completed
+    Failure,
+    /// The task cannot continue without more input from the user
+    NeedsInput,
+}
+
+impl fmt::Display for FinishStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FinishStatus::Success => "success",
+            FinishStatus::Failure => "failure",
+            FinishStatus::NeedsInput => "needs_input",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How an agent run ended
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::agent::outcome::{ExecutionOutcome, FinishStatus};
+///
+/// let outcome = ExecutionOutcome::from_text("All tests pass.");
+/// assert_eq!(outcome.status, FinishStatus::Success);
+/// assert_eq!(outcome.summary, "All tests pass.");
+/// assert!(!outcome.explicit);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionOutcome {
+    /// Reported status
+    pub status: FinishStatus,
+    /// Short description of what was done, or why it was not
+    pub summary: String,
+    /// Optional structured details supplied by the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+    /// Whether the model called `finish`, rather than ending in plain text
+    #[serde(default)]
+    pub explicit: bool,
+}
+
+impl ExecutionOutcome {
+    /// Creates the outcome of a `finish` call
+    pub fn finished(status: FinishStatus, summary: impl Into<String>) -> Self {
+        Self {
+            status,
+            summary: summary.into(),
+            details: None,
+            explicit: true,
+        }
+    }
+
+    /// Creates the fallback outcome of a run that ended in plain text
+    pub fn from_text(text: impl Into<String>) -> Self {
+        Self {
+            status: FinishStatus::Success,
+            summary: text.into(),
+            details: None,
+            explicit: false,
+        }
+    }
+
+    /// Sets the structured details
+    pub fn with_details(mut self, details: Option<Value>) -> Self {
+        self.details = details;
+        self
+    }
+
+    /// Returns true when the task was completed
+    pub fn is_success(&self) -> bool {
+        self.status == FinishStatus::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_serializes_in_snake_case() {
+        for (status, name) in [
+            (FinishStatus::Success, "success"),
+            (FinishStatus::Failure, "failure"),
+            (FinishStatus::NeedsInput, "needs_input"),
+        ] {
+            assert_eq!(status.to_string(), name);
+            assert_eq!(serde_json::to_value(status).unwrap(), name);
+        }
+    }
+
+    #[test]
+    fn test_text_fallback_is_an_implicit_success() {
+        let outcome = ExecutionOutcome::from_text("done");
+        assert!(outcome.is_success());
+        assert!(!outcome.explicit);
+        assert!(
+            !ExecutionOutcome::finished(FinishStatus::NeedsInput, "which branch?").is_success()
+        );
+    }
+}
@@ -1,13 +1,13 @@
 //! Conversation persistence for debugging and auditing
 //!
-//! Stores subagent conversation history in an embedded database
-//! with support for replay and historical analysis.
+//! Stores subagent conversation history in the SQLite conversation history
+//! database with support for replay and historical analysis.
 
-use crate::error::{Result, XzatomaError};
+use crate::error::Result;
 use crate::providers::Message;
+use crate::storage::SqliteStorage;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use sled::Db;
 use std::path::Path;
 use ulid::Ulid;
 
@@ -88,10 +88,12 @@ pub struct ConversationMetadata {
 
 /// Conversation persistence manager
 ///
-/// Manages persistent storage and retrieval of subagent conversations
-/// using an embedded `sled` key-value database.
+/// Stores subagent conversations in the `subagent_conversations` table of
+/// a conversation history database, which is the main history database
+/// unless `agent.subagent.persistence.database` names another one.
+#[derive(Clone)]
 pub struct ConversationStore {
-    db: Db,
+    storage: SqliteStorage,
 }
 
 impl ConversationStore {
@@ -99,7 +101,7 @@ impl ConversationStore {
     ///
     /// # Arguments
     ///
-    /// * `path` - Path to the database directory
+    /// * `path` - Path to the SQLite history database
     ///
     /// # Returns
     ///
@@ -115,18 +117,30 @@ impl ConversationStore {
     /// use xzatoma::agent::ConversationStore;
     ///
     /// # fn main() -> xzatoma::error::Result<()> {
-    /// let store = ConversationStore::new("/tmp/conversations.db")?;
+    /// # let dir = tempfile::tempdir().unwrap();
+    /// let store = ConversationStore::new(dir.path().join("history.db"))?;
     /// # Ok(())
     /// # }
     /// ```
     pub fn new(path: impl AsRef<Path>) -> Result<Self> {
-        let db = sled::open(path)
-            .map_err(|e| XzatomaError::Storage(format!("Failed to open database: {}", e)))?;
-        Ok(Self { db })
+        Ok(Self::from_storage(SqliteStorage::new_with_path(
+            path.as_ref(),
+        )?))
+    }
+
+    /// Use an already open history database
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - History database to store conversations in
+    pub fn from_storage(storage: SqliteStorage) -> Self {
+        Self { storage }
     }
 
     /// Save a conversation record to the store
     ///
+    /// A record with the same ID is replaced.
+    ///
     /// # Arguments
     ///
     /// * `record` - The conversation record to persist
@@ -139,19 +153,7 @@ impl ConversationStore {
     ///
     /// Returns `XzatomaError::Storage` if serialization or insertion fails
     pub fn save(&self, record: &ConversationRecord) -> Result<()> {
-        let key = record.id.as_bytes();
-        let value = serde_json::to_vec(record)
-            .map_err(|e| XzatomaError::Storage(format!("Serialization failed: {}", e)))?;
-
-        self.db
-            .insert(key, value)
-            .map_err(|e| XzatomaError::Storage(format!("Insert failed: {}", e)))?;
-
-        self.db
-            .flush()
-            .map_err(|e| XzatomaError::Storage(format!("Flush failed: {}", e)))?;
-
-        Ok(())
+        self.storage.save_subagent_conversation(record)
     }
 
     /// Retrieve a conversation record by ID
@@ -168,23 +170,13 @@ impl ConversationStore {
     ///
     /// Returns `XzatomaError::Storage` if retrieval or deserialization fails
     pub fn get(&self, id: &str) -> Result<Option<ConversationRecord>> {
-        let key = id.as_bytes();
-        match self
-            .db
-            .get(key)
-            .map_err(|e| XzatomaError::Storage(format!("Get failed: {}", e)))?
-        {
-            Some(bytes) => {
-                let record = serde_json::from_slice(&bytes)
-                    .map_err(|e| XzatomaError::Storage(format!("Deserialization failed: {}", e)))?;
-                Ok(Some(record))
-            }
-            None => Ok(None),
-        }
+        self.storage.load_subagent_conversation(id)
     }
 
     /// List all conversations with pagination support
     ///
+    /// Records are ordered by ID, which is the order they started in.
+    ///
     /// # Arguments
     ///
     /// * `limit` - Maximum number of records to return
@@ -196,27 +188,9 @@ impl ConversationStore {
     ///
     /// # Errors
     ///
-    /// Returns `XzatomaError::Storage` if iteration or deserialization fails
+    /// Returns `XzatomaError::Storage` if the query or deserialization fails
     pub fn list(&self, limit: usize, offset: usize) -> Result<Vec<ConversationRecord>> {
-        let mut records = Vec::new();
-        for (i, result) in self.db.iter().enumerate() {
-            if i < offset {
-                continue;
-            }
-            if records.len() >= limit {
-                break;
-            }
-
-            let (_, value) =
-                result.map_err(|e| XzatomaError::Storage(format!("Iteration failed: {}", e)))?;
-
-            let record: ConversationRecord = serde_json::from_slice(&value)
-                .map_err(|e| XzatomaError::Storage(format!("Deserialization failed: {}", e)))?;
-
-            records.push(record);
-        }
-
-        Ok(records)
+        self.storage.list_subagent_conversations(limit, offset)
     }
 
     /// Find all conversations with a specific parent ID
@@ -234,22 +208,10 @@ impl ConversationStore {
     ///
     /// # Errors
     ///
-    /// Returns `XzatomaError::Storage` if iteration or deserialization fails
+    /// Returns `XzatomaError::Storage` if the query or deserialization fails
     pub fn find_by_parent(&self, parent_id: &str) -> Result<Vec<ConversationRecord>> {
-        let mut records = Vec::new();
-        for result in self.db.iter() {
-            let (_, value) =
-                result.map_err(|e| XzatomaError::Storage(format!("Iteration failed: {}", e)))?;
-
-            let record: ConversationRecord = serde_json::from_slice(&value)
-                .map_err(|e| XzatomaError::Storage(format!("Deserialization failed: {}", e)))?;
-
-            if record.parent_id.as_deref() == Some(parent_id) {
-                records.push(record);
-            }
-        }
-
-        Ok(records)
+        self.storage
+            .find_subagent_conversations_by_parent(parent_id)
     }
 }
 
@@ -516,6 +478,57 @@ mod tests {
         assert_eq!(children.len(), 0);
     }
 
+    #[test]
+    fn test_conversation_store_save_replaces_record_with_same_id() {
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+        let store = ConversationStore::new(temp_dir.path().join("test.db"))
+            .expect("Failed to create store");
+
+        let mut record = ConversationRecord {
+            id: new_conversation_id(),
+            parent_id: None,
+            label: "task".to_string(),
+            depth: 1,
+            messages: vec![],
+            started_at: now_rfc3339(),
+            completed_at: None,
+            metadata: ConversationMetadata::default(),
+        };
+        store.save(&record).expect("Failed to save record");
+        record.completed_at = Some(now_rfc3339());
+        store.save(&record).expect("Failed to update record");
+
+        let records = store.list(10, 0).expect("Failed to list records");
+        assert_eq!(records.len(), 1);
+        assert!(records[0].completed_at.is_some());
+    }
+
+    #[test]
+    fn test_conversation_store_shares_history_database() {
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+        let storage = SqliteStorage::new_with_path(temp_dir.path().join("history.db"))
+            .expect("Failed to open history");
+        storage
+            .save_conversation("chat", "Chat", None, &[])
+            .expect("Failed to save chat");
+        let store = ConversationStore::from_storage(storage.clone());
+
+        let record = ConversationRecord {
+            id: new_conversation_id(),
+            parent_id: Some("chat".to_string()),
+            label: "task".to_string(),
+            depth: 1,
+            messages: vec![],
+            started_at: now_rfc3339(),
+            completed_at: None,
+            metadata: ConversationMetadata::default(),
+        };
+        store.save(&record).expect("Failed to save record");
+
+        assert!(storage.load_conversation("chat").unwrap().is_some());
+        assert_eq!(store.find_by_parent("chat").unwrap().len(), 1);
+    }
+
     #[test]
     fn test_conversation_record_serialization() {
         let record = ConversationRecord {
@@ -0,0 +1,378 @@
+//! Size check before sending a large prompt
+//!
+//! Mentions can pull several large files into one message, and the first
+//! sign of trouble used to be a slow, expensive request or a context
+//! overflow. [`check`] estimates the tokens of the prompt, each mention,
+//! and the conversation before anything is sent. When the total exceeds
+//! [`PreflightConfig::context_fraction`] of the context window, or
+//! [`PreflightConfig::max_tokens`], it returns a [`PreflightReport`] with a
+//! breakdown, an estimated cost, and suggestions for the largest mentions.
+//!
+//! Token counts use the same estimate as the conversation, so the numbers
+//! match `/context`.
+
+use std::fmt::Write as _;
+
+use crate::agent::conversation::estimate_tokens;
+use crate::config::PreflightConfig;
+use crate::format::{format_cost, format_count};
+use crate::mention_parser::{Mention, MentionPart};
+
+/// Most suggestions shown in a report
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Tokens contributed by one mention
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MentionCost {
+    /// The mention as the user would write it, e.g. `@src/main.rs#L1-20`
+    pub label: String,
+    /// Estimated tokens of the loaded content
+    pub tokens: usize,
+    /// How to make the mention smaller, when there is a way
+    pub suggestion: Option<String>,
+}
+
+/// Token breakdown of a prompt that exceeds the preflight threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreflightReport {
+    /// Model the prompt would be sent to
+    pub model: String,
+    /// Estimated tokens of the text the user typed
+    pub prompt_tokens: usize,
+    /// Tokens per mention, largest first
+    pub mentions: Vec<MentionCost>,
+    /// Estimated tokens already in the conversation
+    pub conversation_tokens: usize,
+    /// Context window the threshold was computed from
+    pub context_window: usize,
+    /// Token count above which the prompt is reported
+    pub threshold: usize,
+    /// USD per million input tokens for `model`, when configured
+    pub price_per_million: Option<f64>,
+}
+
+impl PreflightReport {
+    /// Estimated tokens of the whole request
+    pub fn total_tokens(&self) -> usize {
+        self.prompt_tokens
+            + self.conversation_tokens
+            + self.mentions.iter().map(|m| m.tokens).sum::<usize>()
+    }
+
+    /// Estimated input cost of the request in USD, when the model has a price
+    pub fn estimated_cost(&self) -> Option<f64> {
+        self.price_per_million
+            .map(|price| self.total_tokens() as f64 * price / 1_000_000.0)
+    }
+
+    /// Suggestions for the largest mentions, largest first
+    pub fn suggestions(&self) -> Vec<String> {
+        self.mentions
+            .iter()
+            .filter_map(|mention| mention.suggestion.clone())
+            .take(MAX_SUGGESTIONS)
+            .collect()
+    }
+
+    /// One-line summary of the request size against the threshold
+    pub fn headline(&self) -> String {
+        let percent = if self.context_window == 0 {
+            0
+        } else {
+            self.total_tokens() * 100 / self.context_window
+        };
+        format!(
+            "Large prompt: ~{} tokens, {}% of the {}-token context window (preflight limit {})",
+            format_count(self.total_tokens() as u64),
+            percent,
+            format_count(self.context_window as u64),
+            format_count(self.threshold as u64)
+        )
+    }
+
+    /// Multi-line breakdown with cost and suggestions, without the headline
+    pub fn breakdown(&self) -> String {
+        let mut rows: Vec<(String, usize)> = self
+            .mentions
+            .iter()
+            .map(|mention| (mention.label.clone(), mention.tokens))
+            .collect();
+        rows.push(("prompt".to_string(), self.prompt_tokens));
+        rows.push(("conversation".to_string(), self.conversation_tokens));
+        let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+
+        let mut out = String::new();
+        for (label, tokens) in &rows {
+            let _ = writeln!(
+                out,
+                "  {:<width$}  {:>8} tokens",
+                label,
+                format_count(*tokens as u64),
+                width = width
+            );
+        }
+        match (self.estimated_cost(), self.price_per_million) {
+            (Some(cost), Some(price)) => {
+                let _ = writeln!(
+                    out,
+                    "  Estimated input cost: {} ({} at ${} per million tokens)",
+                    format_cost(cost),
+                    self.model,
+                    price
+                );
+            }
+            _ => {
+                let _ = writeln!(
+                    out,
+                    "  Estimated input cost: unknown (no agent.preflight.pricing entry for {})",
+                    self.model
+                );
+            }
+        }
+        let suggestions = self.suggestions();
+        if !suggestions.is_empty() {
+            out.push_str("Suggestions:\n");
+            for suggestion in suggestions {
+                let _ = writeln!(out, "  - {}", suggestion);
+            }
+        }
+        out.trim_end().to_string()
+    }
+}
+
+/// Token count above which a prompt is reported
+///
+/// The smaller of `context_fraction` of the context window and
+/// `max_tokens`, when set.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::agent::preflight::threshold;
+/// use xzatoma::config::PreflightConfig;
+///
+/// let mut config = PreflightConfig::default();
+/// assert_eq!(threshold(&config, 128_000), 64_000);
+///
+/// config.max_tokens = Some(20_000);
+/// assert_eq!(threshold(&config, 128_000), 20_000);
+/// ```
+pub fn threshold(config: &PreflightConfig, context_window: usize) -> usize {
+    let from_window = (context_window as f64 * f64::from(config.context_fraction)) as usize;
+    match config.max_tokens {
+        Some(max_tokens) => from_window.min(max_tokens),
+        None => from_window,
+    }
+}
+
+/// Checks whether a prompt is large enough to report before sending
+///
+/// # Arguments
+///
+/// * `config` - Preflight settings
+/// * `model` - Model the prompt would be sent to
+/// * `context_window` - Effective context window of the model
+/// * `conversation_tokens` - Estimated tokens already in the conversation
+/// * `prompt` - The text the user typed, without mention contents
+/// * `parts` - Contents loaded for the prompt's mentions
+///
+/// # Returns
+///
+/// Returns a report when the check is enabled and the request exceeds the
+/// threshold, and `None` otherwise.
+pub fn check(
+    config: &PreflightConfig,
+    model: &str,
+    context_window: usize,
+    conversation_tokens: usize,
+    prompt: &str,
+    parts: &[MentionPart],
+) -> Option<PreflightReport> {
+    if !config.enabled {
+        return None;
+    }
+
+    let mut mentions: Vec<MentionCost> = parts
+        .iter()
+        .map(|part| MentionCost {
+            label: mention_label(&part.mention),
+            tokens: estimate_tokens(&part.content),
+            suggestion: suggestion_for(&part.mention),
+        })
+        .collect();
+    mentions.sort_by(|a, b| b.tokens.cmp(&a.tokens));
+
+    let report = PreflightReport {
+        model: model.to_string(),
+        prompt_tokens: estimate_tokens(prompt),
+        mentions,
+        conversation_tokens,
+        context_window,
+        threshold: threshold(config, context_window),
+        price_per_million: config.pricing.get(model).copied(),
+    };
+    (report.total_tokens() > report.threshold).then_some(report)
+}
+
+/// Writes a mention the way the user would type it
+fn mention_label(mention: &Mention) -> String {
+    match mention {
+        Mention::File(file) => match (file.start_line, file.end_line) {
+            (Some(start), Some(end)) => format!("@{}#L{}-{}", file.path, start, end),
+            (Some(line), None) => format!("@{}#L{}", file.path, line),
+            _ => format!("@{}", file.path),
+        },
+        Mention::Search(search) => format!("@search:\"{}\"", search.pattern),
+        Mention::Grep(search) => format!("@grep:\"{}\"", search.pattern),
+        Mention::Semantic(search) => format!("@semantic:\"{}\"", search.pattern),
+        Mention::Url(url) => format!("@url:{}", url.url),
+        Mention::Image(image) => format!("@image:{}", image.path),
+        Mention::Diff(diff) => diff.to_string(),
+    }
+}
+
+fn suggestion_for(mention: &Mention) -> Option<String> {
+    let label = mention_label(mention);
+    match mention {
+        Mention::File(file) if file.start_line.is_none() => Some(format!(
+            "use a line range on {}, e.g. {}#L1-200",
+            label, label
+        )),
+        Mention::File(_) => Some(format!("narrow the line range on {}", label)),
+        Mention::Search(_) | Mention::Grep(_) => {
+            Some(format!("use a more specific pattern than {}", label))
+        }
+        Mention::Semantic(_) => Some(format!("ask a narrower question than {}", label)),
+        Mention::Url(_) => Some(format!(
+            "paste only the relevant part of {} instead of the whole page",
+            label
+        )),
+        Mention::Diff(_) => Some(format!(
+            "mention the changed files you need instead of {}",
+            label
+        )),
+        Mention::Image(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mention_parser::{FileMention, SearchMention, UrlMention};
+
+    fn file(path: &str, range: Option<(usize, usize)>, tokens: usize) -> MentionPart {
+        MentionPart {
+            mention: Mention::File(FileMention {
+                path: path.to_string(),
+                start_line: range.map(|r| r.0),
+                end_line: range.map(|r| r.1),
+            }),
+            content: "x".repeat(tokens * 4),
+        }
+    }
+
+    #[test]
+    fn test_threshold_uses_smaller_of_fraction_and_absolute_limit() {
+        let mut config = PreflightConfig::default();
+        assert_eq!(threshold(&config, 8_000), 4_000);
+
+        config.context_fraction = 0.25;
+        assert_eq!(threshold(&config, 8_000), 2_000);
+
+        config.max_tokens = Some(1_000);
+        assert_eq!(threshold(&config, 8_000), 1_000);
+
+        config.max_tokens = Some(50_000);
+        assert_eq!(threshold(&config, 8_000), 2_000);
+    }
+
+    #[test]
+    fn test_check_reports_only_above_threshold() {
+        let config = PreflightConfig::default();
+        let parts = [file("src/big.rs", None, 3_000)];
+
+        // 3,000 mention + 1 prompt + 999 conversation = 4,000, not above 4,000
+        assert!(check(&config, "m", 8_000, 999, "why", &parts).is_none());
+
+        let report = check(&config, "m", 8_000, 1_000, "why", &parts).unwrap();
+        assert_eq!(report.total_tokens(), 4_001);
+        assert_eq!(report.threshold, 4_000);
+        assert!(report.headline().contains("50% of the 8,000-token"));
+
+        let disabled = PreflightConfig {
+            enabled: false,
+            ..PreflightConfig::default()
+        };
+        assert!(check(&disabled, "m", 8_000, 1_000_000, "why", &parts).is_none());
+    }
+
+    #[test]
+    fn test_suggestions_name_largest_mentions_first() {
+        let config = PreflightConfig {
+            max_tokens: Some(100),
+            ..PreflightConfig::default()
+        };
+        let parts = [
+            file("README.md", None, 10),
+            file("src/big_file.rs", None, 5_000),
+            MentionPart {
+                mention: Mention::Url(UrlMention {
+                    url: "https://example.com/spec".to_string(),
+                }),
+                content: "x".repeat(8_000),
+            },
+            file("src/lib.rs", Some((1, 900)), 1_200),
+            MentionPart {
+                mention: Mention::Grep(SearchMention {
+                    pattern: "fn main".to_string(),
+                }),
+                content: "x".repeat(400),
+            },
+        ];
+
+        let report = check(&config, "m", 128_000, 0, "review", &parts).unwrap();
+        let labels: Vec<&str> = report.mentions.iter().map(|m| m.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            [
+                "@src/big_file.rs",
+                "@url:https://example.com/spec",
+                "@src/lib.rs#L1-900",
+                "@grep:\"fn main\"",
+                "@README.md"
+            ]
+        );
+
+        let suggestions = report.suggestions();
+        assert_eq!(suggestions.len(), 3);
+        assert_eq!(
+            suggestions[0],
+            "use a line range on @src/big_file.rs, e.g. @src/big_file.rs#L1-200"
+        );
+        assert!(suggestions[1].contains("@url:https://example.com/spec"));
+        assert_eq!(
+            suggestions[2],
+            "narrow the line range on @src/lib.rs#L1-900"
+        );
+        assert!(report.breakdown().contains("Suggestions:"));
+    }
+
+    #[test]
+    fn test_estimated_cost_uses_pricing_table() {
+        let mut config = PreflightConfig {
+            max_tokens: Some(10),
+            ..PreflightConfig::default()
+        };
+        let parts = [file("a.rs", None, 1_999_999)];
+
+        let report = check(&config, "gpt-5-mini", 4_000_000, 0, "go", &parts).unwrap();
+        assert_eq!(report.estimated_cost(), None);
+        assert!(report.breakdown().contains("cost: unknown"));
+
+        config.pricing.insert("gpt-5-mini".to_string(), 0.25);
+        let report = check(&config, "gpt-5-mini", 4_000_000, 0, "go", &parts).unwrap();
+        assert_eq!(report.total_tokens(), 2_000_000);
+        assert_eq!(report.estimated_cost(), Some(0.5));
+        assert!(report.breakdown().contains("$0.5000"));
+    }
+}
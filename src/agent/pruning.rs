@@ -0,0 +1,197 @@
+//! Reports of what context management removed from the conversation
+//!
+//! The conversation drops, summarizes, or stubs messages on its own: when
+//! the token count crosses the prune threshold, when a provider rejects a
+//! request as too large, when auto-summarization runs, and when history
+//! hygiene stubs superseded tool results. Each of those decisions produces
+//! a [`PruneReport`] naming the affected messages, the reason, and the
+//! token totals before and after.
+//!
+//! The conversation keeps the reports as its pruning log, and the agent
+//! emits each one as an
+//! [`AgentExecutionEvent::ConversationPruned`](crate::agent::AgentExecutionEvent::ConversationPruned)
+//! event, which chat prints as a dim notice and telemetry records as a
+//! `pruning` event.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::conversation::{message_preview, message_tokens};
+use crate::agent::history_hygiene::StubReason;
+use crate::format::format_tokens;
+use crate::providers::Message;
+
+/// Most reports a conversation keeps in its pruning log
+pub const PRUNING_LOG_LIMIT: usize = 200;
+
+/// Characters kept in the preview of a pruned message
+const PREVIEW_CHARS: usize = 72;
+
+/// Why messages were pruned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneReason {
+    /// The token count crossed `prune_threshold * max_tokens`
+    Threshold,
+    /// The provider rejected a request for exceeding its context window
+    OverflowRecovery,
+    /// The context window reached `auto_summary_threshold`
+    AutoSummary,
+    /// A tool result was repeated later with identical output
+    Duplicate,
+    /// A file read was superseded by a later change to the file
+    StaleRead,
+}
+
+impl From<StubReason> for PruneReason {
+    fn from(reason: StubReason) -> Self {
+        match reason {
+            StubReason::Duplicate => PruneReason::Duplicate,
+            StubReason::StaleRead => PruneReason::StaleRead,
+        }
+    }
+}
+
+impl fmt::Display for PruneReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            PruneReason::Threshold => "threshold crossed",
+            PruneReason::OverflowRecovery => "overflow recovery",
+            PruneReason::AutoSummary => "auto-summarization",
+            PruneReason::Duplicate => "duplicate tool result",
+            PruneReason::StaleRead => "stale file read",
+        };
+        f.write_str(text)
+    }
+}
+
+/// What happened to a pruned message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneAction {
+    /// Removed from the conversation and folded into a summary message
+    Summarized,
+    /// Kept in place with its content replaced by a short stub
+    Stubbed,
+}
+
+/// One message affected by a pruning decision
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrunedMessage {
+    /// Role of the message, such as `user` or `tool`
+    pub role: String,
+    /// One-line preview of the original content
+    pub preview: String,
+    /// Estimated tokens of the original message
+    pub tokens: usize,
+    /// Whether the message was summarized or stubbed
+    pub action: PruneAction,
+}
+
+impl PrunedMessage {
+    /// Describes `message` before it is pruned
+    pub fn new(message: &Message, action: PruneAction) -> Self {
+        Self {
+            role: message.role.clone(),
+            preview: message_preview(message, PREVIEW_CHARS),
+            tokens: message_tokens(message),
+            action,
+        }
+    }
+}
+
+/// One pruning decision: which messages, why, and what it saved
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::agent::pruning::{PruneReason, PruneReport};
+///
+/// let report = PruneReport {
+///     reason: PruneReason::Threshold,
+///     messages: Vec::new(),
+///     tokens_before: 7_400,
+///     tokens_after: 4_300,
+///     summary_tokens: Some(60),
+/// };
+/// assert_eq!(report.tokens_reclaimed(), 3_100);
+/// assert_eq!(report.to_string(), "pruned 0 messages / 3.1k tokens (threshold crossed)");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneReport {
+    /// Why the messages were pruned
+    pub reason: PruneReason,
+    /// Affected messages, oldest first
+    pub messages: Vec<PrunedMessage>,
+    /// Estimated conversation tokens before pruning
+    pub tokens_before: usize,
+    /// Estimated conversation tokens after pruning
+    pub tokens_after: usize,
+    /// Estimated tokens of the summary message inserted, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary_tokens: Option<usize>,
+}
+
+impl PruneReport {
+    /// Estimated tokens the decision removed from the context
+    pub fn tokens_reclaimed(&self) -> usize {
+        self.tokens_before.saturating_sub(self.tokens_after)
+    }
+}
+
+impl fmt::Display for PruneReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = self.messages.len();
+        write!(
+            f,
+            "pruned {} message{} / {} tokens ({})",
+            count,
+            if count == 1 { "" } else { "s" },
+            format_tokens(self.tokens_reclaimed() as u64),
+            self.reason
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_display_counts_messages() {
+        let message = PrunedMessage::new(
+            &Message::tool_result("call_1", "fn main() {}"),
+            PruneAction::Stubbed,
+        );
+        let report = PruneReport {
+            reason: PruneReason::Duplicate,
+            messages: vec![message],
+            tokens_before: 500,
+            tokens_after: 380,
+            summary_tokens: None,
+        };
+
+        assert_eq!(
+            report.to_string(),
+            "pruned 1 message / 120 tokens (duplicate tool result)"
+        );
+        assert_eq!(report.messages[0].role, "tool");
+        assert_eq!(report.messages[0].preview, "fn main() {}");
+    }
+
+    #[test]
+    fn test_report_serializes_reason_in_snake_case() {
+        let report = PruneReport {
+            reason: PruneReason::OverflowRecovery,
+            messages: Vec::new(),
+            tokens_before: 10,
+            tokens_after: 5,
+            summary_tokens: None,
+        };
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["reason"], "overflow_recovery");
+        assert!(json.get("summary_tokens").is_none());
+    }
+}
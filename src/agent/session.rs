@@ -0,0 +1,707 @@
+//! Turn-by-turn execution of an agent prompt
+//!
+//! [`Agent::execute`] runs a prompt to the end in one call. An
+//! [`AgentSession`] exposes the same loop one turn at a time: each call to
+//! [`AgentSession::next_turn`] makes exactly one model request, runs the tool
+//! calls it asks for, and reports a [`TurnOutcome`]. Callers that need to
+//! stop between turns, grant more turns, or save progress drive the session
+//! themselves; `Agent::execute` is a loop over `next_turn`.
+//!
+//! [`AgentSession::state`] captures the conversation, the budget spent so
+//! far, and the names of the registered tools as a serializable
+//! [`SessionState`]. [`Agent::resume_session`] restores it into an agent
+//! with the same tools, so a session can continue in another process.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::agent::events::{AgentExecutionEvent, AgentObserver};
+use crate::agent::outcome::{ExecutionOutcome, FinishStatus};
+use crate::agent::{Agent, Conversation};
+use crate::error::{Result, XzatomaError};
+use crate::providers::Message;
+
+/// Budget that stopped a session before its next turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum BudgetReason {
+    /// All turns allowed by `max_turns` were used
+    MaxTurns {
+        /// The turn limit
+        limit: usize,
+    },
+    /// The prompt ran longer than `timeout_seconds`
+    Timeout {
+        /// The time limit in seconds
+        seconds: u64,
+    },
+}
+
+impl BudgetReason {
+    /// Returns the error `Agent::execute` reports for this budget
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::agent::session::BudgetReason;
+    /// use xzatoma::error::XzatomaError;
+    ///
+    /// let error = BudgetReason::MaxTurns { limit: 3 }.into_error();
+    /// assert!(matches!(error, XzatomaError::MaxIterationsExceeded { limit: 3, .. }));
+    /// ```
+    pub fn into_error(self) -> XzatomaError {
+        match self {
+            BudgetReason::MaxTurns { limit } => XzatomaError::MaxIterationsExceeded {
+                limit,
+                message: format!("Agent exceeded maximum iteration limit of {}", limit),
+            },
+            BudgetReason::Timeout { seconds } => {
+                XzatomaError::Config(format!("Agent execution timeout after {} seconds", seconds))
+            }
+        }
+    }
+}
+
+/// What a turn led to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TurnOutcome {
+    /// Tool calls ran; the model should be asked again
+    Continue,
+    /// The run ended; holds the final response
+    Finished(String),
+    /// The model called `finish` with `needs_input`; holds its question
+    NeedsInput(String),
+    /// A budget ran out before the turn could start
+    Budget {
+        /// The budget that ran out
+        reason: BudgetReason,
+    },
+}
+
+impl TurnOutcome {
+    /// Returns the ending reported for a settled outcome
+    fn ended(outcome: &ExecutionOutcome) -> Self {
+        match outcome.status {
+            FinishStatus::NeedsInput => TurnOutcome::NeedsInput(outcome.summary.clone()),
+            _ => TurnOutcome::Finished(outcome.summary.clone()),
+        }
+    }
+}
+
+/// Turn and time budget of a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnBudget {
+    /// Turns the session may use
+    pub max_turns: usize,
+    /// Turns used so far
+    pub turns_used: usize,
+    /// Time the session may run, in seconds
+    pub timeout_seconds: u64,
+    /// Time used so far, in milliseconds
+    pub elapsed_ms: u64,
+}
+
+/// Serializable snapshot of a session
+///
+/// The tool registry cannot be serialized, so only the tool names are
+/// kept; [`Agent::resume_session`] checks that the resuming agent has them.
+/// Loop detection counts are not kept and start over on resume.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionState {
+    /// Conversation identifier
+    pub conversation_id: Uuid,
+    /// Conversation title
+    pub title: String,
+    /// Conversation messages, including the prompt
+    pub messages: Vec<Message>,
+    /// Indices of pinned messages
+    #[serde(default)]
+    pub pinned: Vec<usize>,
+    /// Working directory of each message
+    #[serde(default)]
+    pub message_cwds: Vec<Option<PathBuf>>,
+    /// Budget spent so far
+    pub budget: TurnBudget,
+    /// Names of the registered tools, sorted
+    pub tools: Vec<String>,
+    /// How the session ended, when it has
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<ExecutionOutcome>,
+}
+
+/// A prompt being executed one turn at a time
+///
+/// # Examples
+///
+/// ```no_run
+/// # use xzatoma::agent::Agent;
+/// # use xzatoma::agent::events::NoOpObserver;
+/// # use xzatoma::agent::session::TurnOutcome;
+/// # use xzatoma::config::AgentConfig;
+/// # use xzatoma::tools::ToolRegistry;
+/// # use tokio_util::sync::CancellationToken;
+/// # async fn example() -> xzatoma::error::Result<()> {
+/// # use xzatoma::config::CopilotConfig;
+/// # use xzatoma::providers::CopilotProvider;
+/// # let provider = CopilotProvider::new(CopilotConfig::default())?;
+/// # let mut agent = Agent::new(provider, ToolRegistry::new(), AgentConfig::default())?;
+/// let token = CancellationToken::new();
+/// let mut observer = NoOpObserver;
+/// let mut session = agent.start_session("Fix the failing test");
+/// loop {
+///     match session.next_turn(&token, &mut observer).await? {
+///         TurnOutcome::Continue => continue,
+///         TurnOutcome::Budget { .. } => session.grant_turns(10),
+///         TurnOutcome::Finished(response) | TurnOutcome::NeedsInput(response) => {
+///             println!("{}", response);
+///             break;
+///         }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct AgentSession<'a> {
+    agent: &'a mut Agent,
+    budget: TurnBudget,
+    started: Instant,
+    ended: Option<TurnOutcome>,
+}
+
+impl<'a> AgentSession<'a> {
+    fn new(agent: &'a mut Agent, budget: TurnBudget, ended: Option<TurnOutcome>) -> Self {
+        Self {
+            agent,
+            budget,
+            started: Instant::now(),
+            ended,
+        }
+    }
+
+    /// Returns the agent running the session
+    pub fn agent(&self) -> &Agent {
+        self.agent
+    }
+
+    /// Returns the budget, with the time used so far
+    pub fn budget(&self) -> TurnBudget {
+        TurnBudget {
+            elapsed_ms: self.elapsed().as_millis() as u64,
+            ..self.budget
+        }
+    }
+
+    /// Allows `extra` more turns, for example after a `Budget` outcome
+    pub fn grant_turns(&mut self, extra: usize) {
+        self.budget.max_turns = self.budget.max_turns.saturating_add(extra);
+    }
+
+    /// Allows the session to run `extra` longer
+    pub fn grant_time(&mut self, extra: Duration) {
+        self.budget.timeout_seconds = self.budget.timeout_seconds.saturating_add(extra.as_secs());
+    }
+
+    /// Returns true once the session has finished or needs input
+    pub fn is_ended(&self) -> bool {
+        self.ended.is_some()
+    }
+
+    fn elapsed(&self) -> Duration {
+        Duration::from_millis(self.budget.elapsed_ms) + self.started.elapsed()
+    }
+
+    /// Performs one turn: one model call and the tool calls it asks for
+    ///
+    /// Budgets are checked before the model is called. Once the session
+    /// has ended, further calls return the same ending without calling the
+    /// model.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`XzatomaError::Cancelled`] if the cancellation token fires,
+    /// and the provider and tool errors [`Agent::execute`] reports.
+    pub async fn next_turn(
+        &mut self,
+        cancellation_token: &CancellationToken,
+        observer: &mut dyn AgentObserver,
+    ) -> Result<TurnOutcome> {
+        if let Some(ended) = &self.ended {
+            return Ok(ended.clone());
+        }
+
+        if cancellation_token.is_cancelled() {
+            observer.on_event(AgentExecutionEvent::CancellationRequested);
+            return Err(XzatomaError::Cancelled);
+        }
+
+        if self.budget.turns_used >= self.budget.max_turns {
+            warn!("Maximum iterations ({}) exceeded", self.budget.max_turns);
+            return Ok(TurnOutcome::Budget {
+                reason: BudgetReason::MaxTurns {
+                    limit: self.budget.max_turns,
+                },
+            });
+        }
+
+        let elapsed = self.elapsed();
+        let timeout = Duration::from_secs(self.budget.timeout_seconds);
+        if elapsed > timeout {
+            warn!("Agent execution timeout after {:?}", elapsed);
+            return Ok(TurnOutcome::Budget {
+                reason: BudgetReason::Timeout {
+                    seconds: self.budget.timeout_seconds,
+                },
+            });
+        }
+
+        self.budget.turns_used += 1;
+        debug!(
+            "Iteration {}/{}, tokens: {}/{}",
+            self.budget.turns_used,
+            self.budget.max_turns,
+            self.agent.conversation().token_count(),
+            self.agent.conversation().max_tokens()
+        );
+
+        let deadline = tokio::time::Instant::now().checked_add(timeout - elapsed);
+        let first_turn = self.budget.turns_used == 1;
+        let more = self
+            .agent
+            .run_turn(first_turn, deadline, cancellation_token, observer)
+            .await;
+        // Pruning from tool results and auto-summarization during the turn
+        self.agent.report_pruning(observer);
+        if more? {
+            return Ok(TurnOutcome::Continue);
+        }
+
+        let response = self.agent.settle_outcome();
+        observer.on_event(AgentExecutionEvent::ExecutionCompleted {
+            response: response.clone(),
+        });
+        info!(
+            "Agent execution completed in {} iterations, {} seconds",
+            self.budget.turns_used,
+            self.elapsed().as_secs()
+        );
+
+        let ended = match self.agent.last_outcome() {
+            Some(outcome) => TurnOutcome::ended(outcome),
+            None => TurnOutcome::Finished(response),
+        };
+        self.ended = Some(ended.clone());
+        Ok(ended)
+    }
+
+    /// Runs turns until the session ends, as [`Agent::execute`] does
+    ///
+    /// # Returns
+    ///
+    /// Returns the final response, including when the model needs input.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error for the budget that ran out, as well as the errors
+    /// of [`AgentSession::next_turn`].
+    pub async fn run_to_end(
+        &mut self,
+        cancellation_token: &CancellationToken,
+        observer: &mut dyn AgentObserver,
+    ) -> Result<String> {
+        loop {
+            match self.next_turn(cancellation_token, observer).await? {
+                TurnOutcome::Continue => continue,
+                TurnOutcome::Finished(response) | TurnOutcome::NeedsInput(response) => {
+                    return Ok(response)
+                }
+                TurnOutcome::Budget { reason } => {
+                    let error = reason.into_error();
+                    observer.on_event(AgentExecutionEvent::ExecutionFailed {
+                        error: error.to_string(),
+                    });
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    /// Captures the session as serializable state
+    pub fn state(&self) -> SessionState {
+        let conversation = self.agent.conversation();
+        let mut tools = self.agent.tools().tool_names();
+        tools.sort();
+        SessionState {
+            conversation_id: conversation.id(),
+            title: conversation.title().to_string(),
+            messages: conversation.messages().to_vec(),
+            pinned: conversation.pinned_indices(),
+            message_cwds: conversation.message_cwds().to_vec(),
+            budget: self.budget(),
+            tools,
+            outcome: self
+                .ended
+                .as_ref()
+                .and_then(|_| self.agent.last_outcome().cloned()),
+        }
+    }
+}
+
+impl Agent {
+    /// Returns the budget a new prompt starts with: the agent's
+    /// `max_turns` and `timeout_seconds`, none of it used
+    pub fn fresh_budget(&self) -> TurnBudget {
+        TurnBudget {
+            max_turns: self.config().max_turns,
+            turns_used: 0,
+            timeout_seconds: self.config().timeout_seconds,
+            elapsed_ms: 0,
+        }
+    }
+
+    /// Adds a user prompt and returns a session that runs it turn by turn
+    ///
+    /// The session uses the agent's `max_turns` and `timeout_seconds`.
+    pub fn start_session(&mut self, user_prompt: impl Into<String>) -> AgentSession<'_> {
+        self.conversation_mut().add_user_message(user_prompt);
+        self.begin_prompt();
+        let budget = self.fresh_budget();
+        AgentSession::new(self, budget, None)
+    }
+
+    /// Adds a user prompt and returns a session that continues `budget`
+    ///
+    /// Turns and time already recorded in `budget` count against its
+    /// limits.
+    pub fn start_session_with_budget(
+        &mut self,
+        user_prompt: impl Into<String>,
+        budget: TurnBudget,
+    ) -> AgentSession<'_> {
+        self.conversation_mut().add_user_message(user_prompt);
+        self.begin_prompt();
+        AgentSession::new(self, budget, None)
+    }
+
+    /// Adds provider messages and returns a session that runs them
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Provider` if `messages` is empty.
+    pub fn start_session_with_messages(
+        &mut self,
+        messages: Vec<Message>,
+    ) -> Result<AgentSession<'_>> {
+        if messages.is_empty() {
+            return Err(XzatomaError::Provider(
+                "provider message execution requires at least one message".to_string(),
+            ));
+        }
+        for message in messages {
+            self.conversation_mut().add_message(message);
+        }
+        self.begin_prompt();
+        let budget = self.fresh_budget();
+        Ok(AgentSession::new(self, budget, None))
+    }
+
+    /// Restores a session captured with [`AgentSession::state`]
+    ///
+    /// The agent's conversation is replaced by the saved one, and the
+    /// session continues with the saved budget.
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Tool` if a tool the session used is not
+    /// registered in this agent.
+    pub fn resume_session(&mut self, state: SessionState) -> Result<AgentSession<'_>> {
+        if let Some(missing) = state
+            .tools
+            .iter()
+            .find(|name| self.tools().get(name).is_none())
+        {
+            return Err(XzatomaError::Tool(format!(
+                "Cannot resume session: tool '{}' is not registered",
+                missing
+            )));
+        }
+
+        let settings = &self.config().conversation;
+        let mut conversation = Conversation::with_history(
+            state.conversation_id,
+            state.title,
+            state.messages,
+            self.conversation().max_tokens(),
+            settings.min_retain_turns,
+            settings.prune_threshold as f64,
+        );
+        for index in state.pinned {
+            if let Err(e) = conversation.pin(index) {
+                debug!("Skipping saved pin {}: {}", index, e);
+            }
+        }
+        conversation.set_message_cwds(state.message_cwds);
+        conversation.set_cwd(self.conversation().cwd().map(Path::to_path_buf));
+        *self.conversation_mut() = conversation;
+
+        self.begin_prompt();
+        let ended = state.outcome.as_ref().map(TurnOutcome::ended);
+        self.restore_outcome(state.outcome);
+        Ok(AgentSession::new(self, state.budget, ended))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::NoOpObserver;
+    use crate::config::AgentConfig;
+    use crate::providers::{CompletionResponse, FunctionCall, ToolCall};
+    use crate::testing::MockProvider;
+    use crate::tools::{ToolExecutor, ToolRegistry, ToolResult};
+    use async_trait::async_trait;
+    use std::sync::Arc;
+
+    struct EchoTool;
+
+    #[async_trait]
+    impl ToolExecutor for EchoTool {
+        fn tool_definition(&self) -> serde_json::Value {
+            serde_json::json!({
+                "name": "echo",
+                "description": "test tool",
+                "parameters": {"type": "object", "properties": {"text": {"type": "string"}}}
+            })
+        }
+
+        async fn execute(&self, args: serde_json::Value) -> Result<ToolResult> {
+            Ok(ToolResult::success(format!("echo: {}", args["text"])))
+        }
+    }
+
+    fn call(id: &str, name: &str, arguments: &str) -> Message {
+        Message::assistant_with_tools(vec![ToolCall {
+            id: id.to_string(),
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        }])
+    }
+
+    fn script() -> Vec<Message> {
+        vec![
+            call("call_1", "echo", r#"{"text":"one"}"#),
+            call("call_2", "echo", r#"{"text":"two"}"#),
+            Message::assistant("All done"),
+        ]
+    }
+
+    fn agent(responses: Vec<Message>, max_turns: usize) -> Agent {
+        let mut tools = ToolRegistry::new();
+        tools.register("echo", Arc::new(EchoTool));
+        let config = AgentConfig {
+            max_turns,
+            ..AgentConfig::default()
+        };
+        // Replays the script, then answers "Done"
+        let provider = responses.into_iter().fold(
+            MockProvider::new().with_fallback(CompletionResponse::new(Message::assistant("Done"))),
+            |provider, message| provider.then_response(CompletionResponse::new(message)),
+        );
+        Agent::new(provider, tools, config).unwrap()
+    }
+
+    fn transcript(agent: &Agent) -> serde_json::Value {
+        serde_json::to_value(agent.conversation().messages()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_turns_produce_the_same_conversation_as_execute() {
+        let token = CancellationToken::new();
+        for responses in [
+            script(),
+            vec![Message::assistant("Only text")],
+            vec![call(
+                "call_f",
+                "finish",
+                r#"{"status":"needs_input","summary":"Which branch?"}"#,
+            )],
+        ] {
+            let mut executed = agent(responses.clone(), 10);
+            executed
+                .tools_mut()
+                .register("finish", Arc::new(crate::tools::finish::FinishTool));
+            let expected = executed.execute("Go").await.unwrap();
+
+            let mut stepped = agent(responses, 10);
+            stepped
+                .tools_mut()
+                .register("finish", Arc::new(crate::tools::finish::FinishTool));
+            let mut session = stepped.start_session("Go");
+            let mut turns = Vec::new();
+            let response = loop {
+                let outcome = session.next_turn(&token, &mut NoOpObserver).await.unwrap();
+                turns.push(outcome.clone());
+                match outcome {
+                    TurnOutcome::Continue => continue,
+                    TurnOutcome::Finished(response) | TurnOutcome::NeedsInput(response) => {
+                        break response
+                    }
+                    TurnOutcome::Budget { reason } => panic!("budget ran out: {:?}", reason),
+                }
+            };
+
+            assert_eq!(response, expected);
+            assert_eq!(transcript(&stepped), transcript(&executed));
+            assert_eq!(stepped.last_outcome(), executed.last_outcome());
+            assert!(turns[..turns.len() - 1]
+                .iter()
+                .all(|turn| *turn == TurnOutcome::Continue));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_needs_input_and_repeated_calls_after_the_end() {
+        let mut agent = agent(
+            vec![call(
+                "call_f",
+                "finish",
+                r#"{"status":"needs_input","summary":"Which branch?"}"#,
+            )],
+            5,
+        );
+        agent
+            .tools_mut()
+            .register("finish", Arc::new(crate::tools::finish::FinishTool));
+        let token = CancellationToken::new();
+        let mut session = agent.start_session("Deploy");
+        let outcome = session.next_turn(&token, &mut NoOpObserver).await.unwrap();
+        assert_eq!(
+            outcome,
+            TurnOutcome::NeedsInput("Which branch?".to_string())
+        );
+        assert!(session.is_ended());
+        let messages = session.agent().conversation().len();
+        assert_eq!(
+            session.next_turn(&token, &mut NoOpObserver).await.unwrap(),
+            outcome
+        );
+        assert_eq!(session.agent().conversation().len(), messages);
+    }
+
+    #[tokio::test]
+    async fn test_budget_outcome_can_be_extended_while_execute_fails() {
+        let mut executed = agent(script(), 2);
+        let error = executed.execute("Go").await.unwrap_err();
+        assert!(matches!(
+            error,
+            XzatomaError::MaxIterationsExceeded { limit: 2, .. }
+        ));
+
+        let mut stepped = agent(script(), 2);
+        let token = CancellationToken::new();
+        let mut session = stepped.start_session("Go");
+        for _ in 0..2 {
+            assert_eq!(
+                session.next_turn(&token, &mut NoOpObserver).await.unwrap(),
+                TurnOutcome::Continue
+            );
+        }
+        assert_eq!(
+            session.next_turn(&token, &mut NoOpObserver).await.unwrap(),
+            TurnOutcome::Budget {
+                reason: BudgetReason::MaxTurns { limit: 2 }
+            }
+        );
+        session.grant_turns(1);
+        assert_eq!(
+            session.next_turn(&token, &mut NoOpObserver).await.unwrap(),
+            TurnOutcome::Finished("All done".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_state_round_trips_and_resumes() {
+        let token = CancellationToken::new();
+        let mut expected = agent(script(), 10);
+        expected.execute("Go").await.unwrap();
+
+        let mut first = agent(script(), 10);
+        let saved = {
+            let mut session = first.start_session("Go");
+            assert_eq!(
+                session.next_turn(&token, &mut NoOpObserver).await.unwrap(),
+                TurnOutcome::Continue
+            );
+            session.state()
+        };
+        assert_eq!(saved.budget.turns_used, 1);
+        assert_eq!(saved.tools, vec!["echo".to_string()]);
+
+        let json = serde_json::to_string(&saved).unwrap();
+        let restored: SessionState = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            serde_json::to_value(&restored).unwrap(),
+            serde_json::to_value(&saved).unwrap()
+        );
+
+        // The second agent's provider continues the script where the first stopped
+        let mut second = agent(script().split_off(1), 10);
+        let mut session = second.resume_session(restored).unwrap();
+        assert_eq!(session.budget().turns_used, 1);
+        let response = session.run_to_end(&token, &mut NoOpObserver).await.unwrap();
+        assert_eq!(response, "All done");
+        assert_eq!(session.budget().turns_used, 3);
+        assert_eq!(second.conversation().id(), first.conversation().id());
+        assert_eq!(transcript(&second), transcript(&expected));
+    }
+
+    #[tokio::test]
+    async fn test_ended_session_state_round_trips_its_outcome() {
+        let token = CancellationToken::new();
+        let mut finished = agent(vec![Message::assistant("Done already")], 10);
+        let saved = {
+            let mut session = finished.start_session("Go");
+            session.run_to_end(&token, &mut NoOpObserver).await.unwrap();
+            session.state()
+        };
+        let restored: SessionState =
+            serde_json::from_str(&serde_json::to_string(&saved).unwrap()).unwrap();
+        assert_eq!(restored.outcome.as_ref().unwrap().summary, "Done already");
+
+        let mut other = agent(vec![Message::assistant("Should not be asked")], 10);
+        let mut session = other.resume_session(restored).unwrap();
+        assert_eq!(
+            session.next_turn(&token, &mut NoOpObserver).await.unwrap(),
+            TurnOutcome::Finished("Done already".to_string())
+        );
+        assert_eq!(other.last_outcome().unwrap().summary, "Done already");
+    }
+
+    #[test]
+    fn test_resume_requires_the_saved_tools() {
+        let mut agent = agent(Vec::new(), 10);
+        let state = SessionState {
+            conversation_id: Uuid::new_v4(),
+            title: "t".to_string(),
+            messages: vec![Message::user("Go")],
+            pinned: Vec::new(),
+            message_cwds: Vec::new(),
+            budget: TurnBudget {
+                max_turns: 10,
+                turns_used: 0,
+                timeout_seconds: 60,
+                elapsed_ms: 0,
+            },
+            tools: vec!["echo".to_string(), "terminal".to_string()],
+            outcome: None,
+        };
+        let error = agent.resume_session(state).err().unwrap();
+        assert!(error.to_string().contains("terminal"));
+    }
+}
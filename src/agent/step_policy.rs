@@ -0,0 +1,941 @@
+//! Per-step tool restrictions for plan execution
+//!
+//! A plan step may list the tools it is allowed to call and override the
+//! terminal execution mode:
+//!
+//! ```yaml
+//! steps:
+//!   - name: inspect
+//!     action: Find the failing tests
+//!     tools: [read_file, grep]
+//!   - name: fix
+//!     action: Fix them
+//!     tools: [read_file, edit_file, terminal]
+//!     execution_mode: restricted_autonomous
+//! ```
+//!
+//! Plans with such steps are run one step at a time by [`PlanStepExecutor`].
+//! Each step gets a [`StepPolicy`]: the agent only sees the step's tools, the
+//! terminal tool validates commands in the step's mode, and a call to any
+//! other tool is answered with a refusal naming the restriction. The
+//! `finish` tool is always available, so a step can end the run early with
+//! a failure or a question for the user; the remaining steps are skipped.
+//!
+//! The run's mode is a ceiling. A step can lower its mode but never raise it
+//! above the ceiling, so `full_autonomous` needs `--allow-dangerous`.
+//!
+//! A step may also be retried after it fails:
+//!
+//! ```yaml
+//! steps:
+//!   - name: test
+//!     action: Make the test suite pass
+//!     retries: 2
+//! ```
+//!
+//! A failed attempt's turns are dropped from the conversation, and the next
+//! attempt starts from where the step began, with a system note that lists
+//! each failed attempt's error and tool calls and asks for a different
+//! approach. All attempts at a step share the step's turn and time budget.
+//! A step that asks the user for input, or is cancelled, is not retried.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::agent::{
+    Agent, AgentExecutionEvent, AgentObserver, FinishStatus, ToolCallStatus, TurnBudget,
+};
+use crate::config::ExecutionMode;
+use crate::error::{Result, XzatomaError};
+use crate::tools::finish::FINISH_TOOL_NAME;
+use crate::tools::output_pages::READ_TOOL_OUTPUT_TOOL_NAME;
+use crate::tools::plan::{Plan, PlanStep};
+use crate::tools::{ToolExecutor, ToolRegistry, ToolResult};
+
+/// Metadata key recording that a call was refused by a step policy
+pub const STEP_POLICY_METADATA: &str = "step_policy";
+
+/// Builds the terminal tool for an execution mode
+pub type TerminalFactory = Arc<dyn Fn(ExecutionMode) -> Arc<dyn ToolExecutor> + Send + Sync>;
+
+/// Caps `requested` at `ceiling`
+///
+/// Modes are ordered from most to least restrictive: `interactive`,
+/// `restricted_autonomous`, `full_autonomous`.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::agent::step_policy::clamp_mode;
+/// use xzatoma::config::ExecutionMode;
+///
+/// assert_eq!(
+///     clamp_mode(ExecutionMode::FullAutonomous, ExecutionMode::RestrictedAutonomous),
+///     ExecutionMode::RestrictedAutonomous
+/// );
+/// assert_eq!(
+///     clamp_mode(ExecutionMode::Interactive, ExecutionMode::FullAutonomous),
+///     ExecutionMode::Interactive
+/// );
+/// ```
+pub fn clamp_mode(requested: ExecutionMode, ceiling: ExecutionMode) -> ExecutionMode {
+    if mode_rank(requested) > mode_rank(ceiling) {
+        ceiling
+    } else {
+        requested
+    }
+}
+
+fn mode_rank(mode: ExecutionMode) -> u8 {
+    match mode {
+        ExecutionMode::Interactive => 0,
+        ExecutionMode::RestrictedAutonomous => 1,
+        ExecutionMode::FullAutonomous => 2,
+    }
+}
+
+/// The tools and terminal mode in effect for one plan step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepPolicy {
+    step: String,
+    tools: Option<Vec<String>>,
+    mode: ExecutionMode,
+}
+
+impl StepPolicy {
+    /// Resolves the policy for a step
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - The plan step
+    /// * `default_mode` - Mode for steps without an `execution_mode`
+    /// * `ceiling` - Highest mode any step may use
+    pub fn for_step(step: &PlanStep, default_mode: ExecutionMode, ceiling: ExecutionMode) -> Self {
+        let requested = step.execution_mode.unwrap_or(default_mode);
+        Self {
+            step: step.name.clone(),
+            tools: step.tools.clone(),
+            mode: clamp_mode(requested, ceiling),
+        }
+    }
+
+    /// Name of the step
+    pub fn step(&self) -> &str {
+        &self.step
+    }
+
+    /// Tools the step may call, or `None` when it may call every tool
+    pub fn tools(&self) -> Option<&[String]> {
+        self.tools.as_deref()
+    }
+
+    /// Terminal execution mode for the step
+    pub fn mode(&self) -> ExecutionMode {
+        self.mode
+    }
+
+    /// Returns true when the step may call `tool`
+    ///
+    /// `finish` and `read_tool_output`, which reads the rest of output the
+    /// step already received, are always allowed.
+    pub fn allows(&self, tool: &str) -> bool {
+        tool == FINISH_TOOL_NAME
+            || tool == READ_TOOL_OUTPUT_TOOL_NAME
+            || self
+                .tools
+                .as_ref()
+                .map_or(true, |tools| tools.iter().any(|t| t == tool))
+    }
+
+    /// Returns the refusal for a call the step does not allow
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::agent::step_policy::StepPolicy;
+    /// use xzatoma::config::ExecutionMode;
+    /// use xzatoma::tools::plan::PlanStep;
+    ///
+    /// let step = PlanStep::new("inspect".to_string())
+    ///     .with_action("Look around".to_string())
+    ///     .with_tools(vec!["read_file".to_string()]);
+    /// let policy = StepPolicy::for_step(
+    ///     &step,
+    ///     ExecutionMode::RestrictedAutonomous,
+    ///     ExecutionMode::RestrictedAutonomous,
+    /// );
+    ///
+    /// assert!(policy.refusal("read_file").is_none());
+    /// let refusal = policy.refusal("write_file").unwrap();
+    /// assert!(refusal.error.unwrap().contains("not allowed in plan step 'inspect'"));
+    /// ```
+    pub fn refusal(&self, tool: &str) -> Option<ToolResult> {
+        if self.allows(tool) {
+            return None;
+        }
+        Some(
+            ToolResult::error(format!(
+                "Tool '{}' is not allowed in plan step '{}'. This step may only use: {}.",
+                tool,
+                self.step,
+                self.describe_tools()
+            ))
+            .with_metadata(STEP_POLICY_METADATA.to_string(), "refused".to_string())
+            .with_metadata("plan_step".to_string(), self.step.clone()),
+        )
+    }
+
+    /// Builds the step's registry from the run's full registry
+    ///
+    /// Listed tools the run does not provide are skipped. The terminal tool,
+    /// when kept, is rebuilt by `terminal` for the step's mode.
+    pub fn registry(
+        &self,
+        tools: &ToolRegistry,
+        terminal: Option<&TerminalFactory>,
+    ) -> ToolRegistry {
+        let mut registry = match &self.tools {
+            Some(allowed) => {
+                let mut registry = tools.clone_with_filter(allowed);
+                for always in [FINISH_TOOL_NAME, READ_TOOL_OUTPUT_TOOL_NAME] {
+                    if let Some(tool) = tools.get(always) {
+                        registry.register(always, tool);
+                    }
+                }
+                registry
+            }
+            None => tools.fork(),
+        };
+        if let Some(terminal) = terminal {
+            if registry.get("terminal").is_some() {
+                registry.register("terminal", terminal(self.mode));
+            }
+        }
+        registry
+    }
+
+    /// Lists the step's tools for messages and dry-run output
+    pub fn describe_tools(&self) -> String {
+        match &self.tools {
+            Some(tools) if tools.is_empty() => "no tools".to_string(),
+            Some(tools) => {
+                let mut tools = tools.clone();
+                tools.sort();
+                tools.join(", ")
+            }
+            None => "all tools".to_string(),
+        }
+    }
+}
+
+/// Runs a plan one step at a time, each step under its own [`StepPolicy`]
+///
+/// All steps share the agent's conversation, so later steps see what earlier
+/// steps found. Each step gets a subset of the agent's registry.
+pub struct PlanStepExecutor {
+    default_mode: ExecutionMode,
+    ceiling: ExecutionMode,
+    terminal: Option<TerminalFactory>,
+}
+
+impl PlanStepExecutor {
+    /// Creates an executor
+    ///
+    /// # Arguments
+    ///
+    /// * `default_mode` - Mode for steps without an `execution_mode`
+    /// * `ceiling` - Highest mode any step may use
+    pub fn new(default_mode: ExecutionMode, ceiling: ExecutionMode) -> Self {
+        Self {
+            default_mode: clamp_mode(default_mode, ceiling),
+            ceiling,
+            terminal: None,
+        }
+    }
+
+    /// Sets the factory that builds the terminal tool for each step's mode
+    ///
+    /// Without a factory the run's terminal tool is used unchanged.
+    pub fn with_terminal(mut self, terminal: TerminalFactory) -> Self {
+        self.terminal = Some(terminal);
+        self
+    }
+
+    /// Resolves the policy of every step, in order
+    pub fn policies(&self, plan: &Plan) -> Vec<StepPolicy> {
+        plan.steps
+            .iter()
+            .map(|step| StepPolicy::for_step(step, self.default_mode, self.ceiling))
+            .collect()
+    }
+
+    /// Runs every step of `plan` with `agent`
+    ///
+    /// The agent's tools are replaced for each step and restored afterwards.
+    /// A failed step is retried as often as its `retries` allow.
+    ///
+    /// # Returns
+    ///
+    /// Returns the agent's responses, one section per step, along with the
+    /// attempts made at each step that ran
+    ///
+    /// # Errors
+    ///
+    /// Stops at the first step that still fails after its retries and
+    /// returns its error. A step that calls `finish` with a status other than
+    /// success also stops the run; its outcome is left in
+    /// [`Agent::last_outcome`].
+    pub async fn execute(
+        &self,
+        agent: &mut Agent,
+        plan: &Plan,
+        cancellation_token: &CancellationToken,
+        observer: &mut dyn AgentObserver,
+    ) -> PlanExecution {
+        let run_tools = std::mem::take(agent.tools_mut());
+        let mut outcome = Ok(Vec::new());
+        let mut steps = Vec::new();
+        for (index, (step, policy)) in plan.steps.iter().zip(self.policies(plan)).enumerate() {
+            tracing::info!(
+                step = %policy.step(),
+                tools = %policy.describe_tools(),
+                mode = %policy.mode(),
+                retries = step.retries.unwrap_or(0),
+                "Running plan step"
+            );
+            *agent.tools_mut() = policy.registry(&run_tools, self.terminal.as_ref());
+            agent.set_step_policy(Some(policy));
+
+            let prompt = step_prompt(plan, index, step);
+            let (result, summary) =
+                run_step(agent, step, &prompt, cancellation_token, observer).await;
+            steps.push(summary);
+            match result {
+                Ok(response) => {
+                    if let Ok(responses) = &mut outcome {
+                        responses.push(format!("## {}\n\n{}", step.name, response));
+                    }
+                    if agent
+                        .last_outcome()
+                        .is_some_and(|finished| !finished.is_success())
+                    {
+                        tracing::info!(step = %step.name, "Plan step did not succeed, stopping");
+                        break;
+                    }
+                }
+                Err(error) => {
+                    outcome = Err(error);
+                    break;
+                }
+            }
+        }
+        agent.set_step_policy(None);
+        *agent.tools_mut() = run_tools;
+        PlanExecution {
+            result: outcome.map(|responses| responses.join("\n\n")),
+            steps,
+        }
+    }
+}
+
+/// What [`PlanStepExecutor::execute`] produced
+#[derive(Debug)]
+pub struct PlanExecution {
+    /// The agent's responses, one section per step, or the error that
+    /// stopped the run
+    pub result: Result<String>,
+    /// The attempts made at each step that ran, in order
+    pub steps: Vec<StepSummary>,
+}
+
+/// The attempts made at one plan step
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StepSummary {
+    /// Name of the step
+    pub step: String,
+    /// Attempts in the order they ran; only the last one can have succeeded
+    pub attempts: Vec<StepAttempt>,
+}
+
+/// One attempt at a plan step
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StepAttempt {
+    /// Attempt number, starting at 1
+    pub attempt: u32,
+    /// How the attempt ended
+    pub status: AttemptStatus,
+    /// Error or failure summary of an attempt that failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Number of tool calls the attempt made
+    pub tool_calls: usize,
+    /// Wall-clock duration of the attempt in milliseconds
+    pub duration_ms: u64,
+}
+
+/// How an attempt at a plan step ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttemptStatus {
+    /// The step completed
+    Success,
+    /// The agent called `finish` with a failure or the attempt returned an
+    /// error
+    Failure,
+    /// The agent asked the user for input
+    NeedsInput,
+    /// The run was cancelled
+    Cancelled,
+}
+
+impl fmt::Display for AttemptStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+            Self::NeedsInput => "needs_input",
+            Self::Cancelled => "cancelled",
+        })
+    }
+}
+
+/// Most tool calls of one failed attempt listed in a retry note
+const NOTE_MAX_TOOL_CALLS: usize = 20;
+
+/// Longest argument or error text quoted in a retry note, in characters
+const NOTE_MAX_TEXT_CHARS: usize = 200;
+
+/// A tool call made during an attempt, as quoted in a retry note
+struct AttemptToolCall {
+    name: String,
+    arguments: String,
+    result: String,
+}
+
+/// A failed attempt, as described to the next one
+struct FailedAttempt {
+    attempt: u32,
+    error: String,
+    tool_calls: Vec<AttemptToolCall>,
+}
+
+/// Observer that forwards events and records the tool calls of an attempt
+///
+/// `finish` calls are not recorded; the attempt's error already carries
+/// their summary.
+struct AttemptRecorder<'a> {
+    inner: &'a mut dyn AgentObserver,
+    calls: Vec<AttemptToolCall>,
+}
+
+impl AgentObserver for AttemptRecorder<'_> {
+    fn on_event(&mut self, event: AgentExecutionEvent) {
+        match &event {
+            AgentExecutionEvent::ToolCallStarted {
+                name, arguments, ..
+            } if name != FINISH_TOOL_NAME => self.calls.push(AttemptToolCall {
+                name: name.clone(),
+                arguments: shorten(arguments),
+                result: "no result".to_string(),
+            }),
+            AgentExecutionEvent::ToolCallCompleted {
+                name,
+                output,
+                status,
+                ..
+            } => {
+                let result = match status {
+                    ToolCallStatus::Success => "ok".to_string(),
+                    ToolCallStatus::Failure => format!("failed: {}", shorten(output)),
+                    ToolCallStatus::Timeout => "timed out".to_string(),
+                };
+                self.set_result(name, result);
+            }
+            AgentExecutionEvent::ToolCallFailed { name, error, .. } => {
+                self.set_result(name, format!("failed: {}", shorten(error)));
+            }
+            _ => {}
+        }
+        self.inner.on_event(event);
+    }
+}
+
+impl AttemptRecorder<'_> {
+    /// Records the result of the latest pending call to `name`
+    fn set_result(&mut self, name: &str, result: String) {
+        if let Some(call) = self
+            .calls
+            .iter_mut()
+            .rev()
+            .find(|call| call.name == name && call.result == "no result")
+        {
+            call.result = result;
+        }
+    }
+}
+
+/// Runs one step, retrying it while it fails and retries and budget remain
+///
+/// Every attempt continues the step's [`TurnBudget`], so retries never
+/// extend the step's limits.
+async fn run_step(
+    agent: &mut Agent,
+    step: &PlanStep,
+    prompt: &str,
+    cancellation_token: &CancellationToken,
+    observer: &mut dyn AgentObserver,
+) -> (Result<String>, StepSummary) {
+    let max_attempts = step.retries.unwrap_or(0).saturating_add(1);
+    let mut budget = agent.fresh_budget();
+    // Where the step began; a retry drops the failed attempt's turns
+    let checkpoint = (max_attempts > 1).then(|| agent.conversation().clone());
+    let mut failures = Vec::new();
+    let mut summary = StepSummary {
+        step: step.name.clone(),
+        attempts: Vec::new(),
+    };
+    loop {
+        let attempt = failures.len() as u32 + 1;
+        let started = Instant::now();
+        let mut recorder = AttemptRecorder {
+            inner: &mut *observer,
+            calls: Vec::new(),
+        };
+        let result = agent
+            .execute_with_budget(prompt, &mut budget, cancellation_token, &mut recorder)
+            .await;
+        let tool_calls = recorder.calls;
+        let (status, error) = match &result {
+            Ok(_) => match agent.last_outcome() {
+                Some(finished) if finished.status == FinishStatus::Failure => {
+                    (AttemptStatus::Failure, Some(finished.summary.clone()))
+                }
+                Some(finished) if finished.status == FinishStatus::NeedsInput => {
+                    (AttemptStatus::NeedsInput, None)
+                }
+                _ => (AttemptStatus::Success, None),
+            },
+            Err(XzatomaError::Cancelled) => (AttemptStatus::Cancelled, None),
+            Err(error) => (AttemptStatus::Failure, Some(error.to_string())),
+        };
+        summary.attempts.push(StepAttempt {
+            attempt,
+            status,
+            error: error.clone(),
+            tool_calls: tool_calls.len(),
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+
+        let retry = status == AttemptStatus::Failure
+            && attempt < max_attempts
+            && budget_remains(&budget)
+            && !cancellation_token.is_cancelled();
+        let Some(checkpoint) = checkpoint.as_ref().filter(|_| retry) else {
+            return (result, summary);
+        };
+        let error = error.unwrap_or_default();
+        tracing::info!(step = %step.name, attempt, error = %error, "Plan step failed, retrying");
+        failures.push(FailedAttempt {
+            attempt,
+            error,
+            tool_calls,
+        });
+        *agent.conversation_mut() = checkpoint.clone();
+        agent
+            .conversation_mut()
+            .add_system_message(retry_note(step, max_attempts, &failures));
+    }
+}
+
+/// Whether `budget` has turns and time left
+fn budget_remains(budget: &TurnBudget) -> bool {
+    budget.turns_used < budget.max_turns
+        && budget.elapsed_ms < budget.timeout_seconds.saturating_mul(1000)
+}
+
+/// Composes the note that starts a retry of `step`
+fn retry_note(step: &PlanStep, max_attempts: u32, failures: &[FailedAttempt]) -> String {
+    let mut note = format!(
+        "Plan step '{}' failed {} time{}. This is attempt {} of {}. The failed attempts were removed from the conversation; this note is all that remains of them.\n",
+        step.name,
+        failures.len(),
+        if failures.len() == 1 { "" } else { "s" },
+        failures.len() + 1,
+        max_attempts
+    );
+    for failure in failures {
+        note.push_str(&format!(
+            "\nAttempt {} failed: {}\n",
+            failure.attempt,
+            shorten(&failure.error)
+        ));
+        if failure.tool_calls.is_empty() {
+            note.push_str("It made no tool calls.\n");
+            continue;
+        }
+        note.push_str("Tool calls:\n");
+        let skipped = failure.tool_calls.len().saturating_sub(NOTE_MAX_TOOL_CALLS);
+        if skipped > 0 {
+            note.push_str(&format!("- ({} earlier calls omitted)\n", skipped));
+        }
+        for call in &failure.tool_calls[skipped..] {
+            note.push_str(&format!(
+                "- {} {} -> {}\n",
+                call.name, call.arguments, call.result
+            ));
+        }
+    }
+    note.push_str(
+        "\nTry a different approach. Do not repeat a command or change that already failed unless you first deal with why it failed.",
+    );
+    note
+}
+
+/// Cuts `text` to its first line and at most [`NOTE_MAX_TEXT_CHARS`]
+/// characters
+fn shorten(text: &str) -> String {
+    let line = text.trim().lines().next().unwrap_or_default();
+    if line.chars().count() > NOTE_MAX_TEXT_CHARS {
+        let cut: String = line.chars().take(NOTE_MAX_TEXT_CHARS).collect();
+        format!("{}...", cut)
+    } else {
+        line.to_string()
+    }
+}
+
+/// Composes the prompt for one step
+fn step_prompt(plan: &Plan, index: usize, step: &PlanStep) -> String {
+    let mut prompt = format!(
+        "{}\n\nStep: {}\nAction: {}\n",
+        step_prompt_heading(plan, index),
+        step.name,
+        step.action
+    );
+    if let Some(context) = &step.context {
+        prompt.push_str(&format!("\nContext:\n{}\n", context));
+    }
+    prompt
+}
+
+/// First line of the prompt for step `index`, which marks where the step
+/// starts in the conversation
+pub(crate) fn step_prompt_heading(plan: &Plan, index: usize) -> String {
+    format!(
+        "Execute step {} of {} of the plan '{}'.",
+        index + 1,
+        plan.step_count(),
+        plan.name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::NoOpObserver;
+    use crate::config::AgentConfig;
+    use crate::providers::{CompletionResponse, FunctionCall, Message, ToolCall};
+    use crate::testing::MockProvider;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    /// Provider that answers with scripted messages, then "Done"
+    fn scripted(responses: Vec<Message>) -> MockProvider {
+        responses.into_iter().fold(
+            MockProvider::new().with_fallback(CompletionResponse::new(Message::assistant("Done"))),
+            |provider, message| provider.then_response(CompletionResponse::new(message)),
+        )
+    }
+
+    struct CountingTool {
+        name: &'static str,
+        runs: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl ToolExecutor for CountingTool {
+        fn tool_definition(&self) -> serde_json::Value {
+            serde_json::json!({
+                "name": self.name,
+                "description": "test tool",
+                "parameters": {"type": "object", "properties": {}}
+            })
+        }
+
+        async fn execute(&self, _args: serde_json::Value) -> Result<ToolResult> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            Ok(ToolResult::success(format!("{} ran", self.name)))
+        }
+    }
+
+    fn call(id: &str, name: &str, arguments: &str) -> Message {
+        Message::assistant_with_tools(vec![ToolCall {
+            id: id.to_string(),
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        }])
+    }
+
+    fn write_call(id: &str) -> Message {
+        call(id, "write_file", "{}")
+    }
+
+    fn step(name: &str, tools: &[&str]) -> PlanStep {
+        PlanStep::new(name.to_string())
+            .with_action(format!("do {}", name))
+            .with_tools(tools.iter().map(|t| t.to_string()).collect())
+    }
+
+    #[test]
+    fn test_step_mode_is_capped_by_the_ceiling() {
+        let full =
+            PlanStep::new("s".to_string()).with_execution_mode(ExecutionMode::FullAutonomous);
+        let policy = StepPolicy::for_step(
+            &full,
+            ExecutionMode::RestrictedAutonomous,
+            ExecutionMode::RestrictedAutonomous,
+        );
+        assert_eq!(policy.mode(), ExecutionMode::RestrictedAutonomous);
+
+        let policy = StepPolicy::for_step(
+            &full,
+            ExecutionMode::RestrictedAutonomous,
+            ExecutionMode::FullAutonomous,
+        );
+        assert_eq!(policy.mode(), ExecutionMode::FullAutonomous);
+
+        let plain = PlanStep::new("s".to_string());
+        let policy = StepPolicy::for_step(
+            &plain,
+            ExecutionMode::Interactive,
+            ExecutionMode::FullAutonomous,
+        );
+        assert_eq!(policy.mode(), ExecutionMode::Interactive);
+        assert_eq!(policy.describe_tools(), "all tools");
+    }
+
+    #[test]
+    fn test_registry_keeps_listed_tools_and_rebuilds_terminal() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut tools = ToolRegistry::new();
+        for name in ["read_file", "write_file", "terminal"] {
+            tools.register(
+                name,
+                Arc::new(CountingTool {
+                    name,
+                    runs: runs.clone(),
+                }),
+            );
+        }
+        let modes = Arc::new(Mutex::new(Vec::new()));
+        let seen = modes.clone();
+        let terminal: TerminalFactory = Arc::new(move |mode| -> Arc<dyn ToolExecutor> {
+            seen.lock().unwrap().push(mode);
+            Arc::new(CountingTool {
+                name: "terminal",
+                runs: Arc::new(AtomicUsize::new(0)),
+            })
+        });
+
+        let policy = StepPolicy::for_step(
+            &step("check", &["read_file", "terminal", "fetch"])
+                .with_execution_mode(ExecutionMode::Interactive),
+            ExecutionMode::RestrictedAutonomous,
+            ExecutionMode::RestrictedAutonomous,
+        );
+        let registry = policy.registry(&tools, Some(&terminal));
+
+        let mut names = registry.tool_names();
+        names.sort();
+        assert_eq!(names, ["read_file", "terminal"]);
+        assert_eq!(*modes.lock().unwrap(), [ExecutionMode::Interactive]);
+        assert_eq!(policy.describe_tools(), "fetch, read_file, terminal");
+    }
+
+    #[tokio::test]
+    async fn test_read_only_step_refuses_write_and_next_step_writes() {
+        let writes = Arc::new(AtomicUsize::new(0));
+        let mut tools = ToolRegistry::new();
+        tools.register(
+            "read_file",
+            Arc::new(CountingTool {
+                name: "read_file",
+                runs: Arc::new(AtomicUsize::new(0)),
+            }),
+        );
+        tools.register(
+            "write_file",
+            Arc::new(CountingTool {
+                name: "write_file",
+                runs: writes.clone(),
+            }),
+        );
+        let provider = scripted(vec![
+            write_call("call_1"),
+            Message::assistant("Inspected"),
+            write_call("call_2"),
+            Message::assistant("Written"),
+        ]);
+        let mut agent = Agent::new(provider, tools, AgentConfig::default()).unwrap();
+        let plan = Plan::new(
+            "Fix".to_string(),
+            vec![
+                step("inspect", &["read_file"]),
+                step("write", &["read_file", "write_file"]),
+            ],
+        );
+
+        let executor = PlanStepExecutor::new(
+            ExecutionMode::RestrictedAutonomous,
+            ExecutionMode::RestrictedAutonomous,
+        );
+        let result = executor
+            .execute(
+                &mut agent,
+                &plan,
+                &CancellationToken::new(),
+                &mut NoOpObserver,
+            )
+            .await
+            .result
+            .unwrap();
+
+        assert_eq!(writes.load(Ordering::SeqCst), 1);
+        assert!(result.contains("## inspect\n\nInspected"));
+        assert!(result.contains("## write\n\nWritten"));
+        let tool_results: Vec<String> = agent
+            .conversation()
+            .messages()
+            .iter()
+            .filter(|m| m.role == "tool")
+            .filter_map(|m| m.content.clone())
+            .collect();
+        assert_eq!(tool_results.len(), 2);
+        assert!(tool_results[0].contains("Tool 'write_file' is not allowed in plan step 'inspect'"));
+        assert!(tool_results[0].contains("This step may only use: read_file."));
+        assert!(tool_results[1].contains("write_file ran"));
+        assert_eq!(agent.num_tools(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_step_is_retried_with_a_note_about_the_failure() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut tools = ToolRegistry::new();
+        tools.register(
+            "terminal",
+            Arc::new(CountingTool {
+                name: "terminal",
+                runs: runs.clone(),
+            }),
+        );
+        tools.register(FINISH_TOOL_NAME, Arc::new(crate::tools::finish::FinishTool));
+        let provider = scripted(vec![
+            call("call_1", "terminal", r#"{"command":"cargo test"}"#),
+            call(
+                "call_2",
+                FINISH_TOOL_NAME,
+                r#"{"status":"failure","summary":"2 tests still fail"}"#,
+            ),
+            Message::assistant("Tests pass"),
+        ]);
+        let requests = provider.clone();
+        let mut agent = Agent::new(provider, tools, AgentConfig::default()).unwrap();
+        let plan = Plan::new(
+            "Fix".to_string(),
+            vec![PlanStep::new("test".to_string())
+                .with_action("make the tests pass".to_string())
+                .with_retries(2)],
+        );
+        assert!(plan.runs_stepwise());
+
+        let executor = PlanStepExecutor::new(
+            ExecutionMode::RestrictedAutonomous,
+            ExecutionMode::RestrictedAutonomous,
+        );
+        let execution = executor
+            .execute(
+                &mut agent,
+                &plan,
+                &CancellationToken::new(),
+                &mut NoOpObserver,
+            )
+            .await;
+
+        assert_eq!(execution.result.unwrap(), "## test\n\nTests pass");
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(execution.steps.len(), 1);
+        let attempts = &execution.steps[0].attempts;
+        assert_eq!(attempts.len(), 2);
+        assert_eq!(attempts[0].attempt, 1);
+        assert_eq!(attempts[0].status, AttemptStatus::Failure);
+        assert_eq!(attempts[0].error.as_deref(), Some("2 tests still fail"));
+        assert_eq!(attempts[0].tool_calls, 1);
+        assert_eq!(attempts[1].attempt, 2);
+        assert_eq!(attempts[1].status, AttemptStatus::Success);
+        assert_eq!(attempts[1].error, None);
+        assert_eq!(attempts[1].tool_calls, 0);
+
+        let summary = serde_json::to_value(&execution.steps[0]).unwrap();
+        assert_eq!(summary["step"], "test");
+        assert_eq!(summary["attempts"][0]["status"], "failure");
+        assert!(summary["attempts"][1].get("error").is_none());
+
+        // The retry sees the note but none of the failed attempt's turns
+        let retry_request = requests.last_request().unwrap().messages;
+        let note = retry_request
+            .iter()
+            .filter(|m| m.role == "system")
+            .filter_map(|m| m.content.as_deref())
+            .find(|content| content.contains("Plan step 'test' failed"))
+            .expect("the retry should carry a note about the failure");
+        assert!(note.contains("This is attempt 2 of 3."));
+        assert!(note.contains("Attempt 1 failed: 2 tests still fail"));
+        assert!(note.contains(r#"- terminal {"command":"cargo test"} -> ok"#));
+        assert!(note.contains("Try a different approach."));
+        assert!(!retry_request.iter().any(|m| m.role == "tool"));
+        assert_eq!(retry_request.iter().filter(|m| m.role == "user").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_step_without_retries_stops_at_the_first_failure() {
+        let mut tools = ToolRegistry::new();
+        tools.register(FINISH_TOOL_NAME, Arc::new(crate::tools::finish::FinishTool));
+        let provider = scripted(vec![call(
+            "call_1",
+            FINISH_TOOL_NAME,
+            r#"{"status":"failure","summary":"cannot build"}"#,
+        )]);
+        let mut agent = Agent::new(provider, tools, AgentConfig::default()).unwrap();
+        let plan = Plan::new(
+            "Fix".to_string(),
+            vec![step("build", &[]).with_retries(0), step("deploy", &[])],
+        );
+
+        let execution = PlanStepExecutor::new(
+            ExecutionMode::RestrictedAutonomous,
+            ExecutionMode::RestrictedAutonomous,
+        )
+        .execute(
+            &mut agent,
+            &plan,
+            &CancellationToken::new(),
+            &mut NoOpObserver,
+        )
+        .await;
+
+        assert!(execution.result.is_ok());
+        assert_eq!(execution.steps.len(), 1);
+        assert_eq!(execution.steps[0].attempts.len(), 1);
+        assert_eq!(
+            execution.steps[0].attempts[0].status,
+            AttemptStatus::Failure
+        );
+        assert!(!agent.last_outcome().unwrap().is_success());
+    }
+}
@@ -0,0 +1,486 @@
+//! Health of tools whose recent calls keep failing
+//!
+//! When an MCP server is flapping or a service behind a tool is down, the
+//! model tends to call the broken tool again and again. [`ToolHealth`]
+//! keeps the outcomes of each tool's recent calls and feeds them back into
+//! the tool definitions sent to the provider:
+//!
+//! - a degraded tool keeps its definition, with the last error and a hint
+//!   to prefer alternatives appended to the description;
+//! - a withheld tool is left out of the definitions for a cooldown period.
+//!   It stays registered, so a call the model makes by name still runs.
+//!
+//! Outcomes older than `decay_seconds` are forgotten, and a successful call
+//! returns a degraded or withheld tool to healthy. The thresholds come from
+//! [`ToolHealthConfig`]. The tracker is shared behind an `Arc<Mutex<_>>`,
+//! so clones record into the same state.
+//!
+//! # Examples
+//!
+//! ```
+//! use serde_json::json;
+//! use xzatoma::agent::ToolHealth;
+//! use xzatoma::config::ToolHealthConfig;
+//!
+//! let health = ToolHealth::new(ToolHealthConfig::default());
+//! for _ in 0..3 {
+//!     health.record("search", false, Some("connection refused"));
+//! }
+//!
+//! let definitions = health.apply(vec![json!({"name": "search", "description": "Searches"})]);
+//! assert_eq!(
+//!     definitions[0]["description"],
+//!     "Searches (currently failing: connection refused — prefer alternatives)"
+//! );
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use crate::config::ToolHealthConfig;
+
+/// Longest error summary kept per tool, in characters
+const MAX_ERROR_CHARS: usize = 80;
+
+/// Health of one tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolHealthStatus {
+    /// Recent calls mostly succeed
+    Healthy,
+    /// Recent calls mostly fail; the definition carries a warning
+    Degraded,
+    /// Recent calls keep failing; the definition is left out of requests
+    Withheld,
+}
+
+impl fmt::Display for ToolHealthStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Healthy => write!(f, "healthy"),
+            Self::Degraded => write!(f, "degraded"),
+            Self::Withheld => write!(f, "withheld"),
+        }
+    }
+}
+
+/// Current health of a tool that is not healthy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolHealthEntry {
+    /// Tool name as called by the model
+    pub name: String,
+    /// Degraded or withheld
+    pub status: ToolHealthStatus,
+    /// Calls that still count towards the failure rate
+    pub recent_calls: usize,
+    /// Failed calls among them
+    pub recent_failures: usize,
+    /// First line of the last error
+    pub last_error: Option<String>,
+    /// Time until a withheld tool is offered again
+    pub withheld_for: Option<Duration>,
+}
+
+/// Recent outcomes of one tool
+#[derive(Debug, Default)]
+struct ToolRecord {
+    /// When each recent call finished and whether it succeeded, oldest first
+    outcomes: VecDeque<(Instant, bool)>,
+    last_error: Option<String>,
+    withheld_until: Option<Instant>,
+}
+
+impl ToolRecord {
+    /// Forgets outcomes older than the decay period
+    fn decay(&mut self, config: &ToolHealthConfig, now: Instant) {
+        let decay = Duration::from_secs(config.decay_seconds);
+        while self
+            .outcomes
+            .front()
+            .is_some_and(|(at, _)| now.saturating_duration_since(*at) > decay)
+        {
+            self.outcomes.pop_front();
+        }
+    }
+
+    fn failures(&self) -> usize {
+        self.outcomes.iter().filter(|(_, success)| !success).count()
+    }
+
+    /// Whether at least `min_calls` outcomes are recorded and `rate` of
+    /// them failed
+    fn failing(&self, min_calls: usize, rate: f64) -> bool {
+        let calls = self.outcomes.len();
+        calls >= min_calls && self.failures() as f64 >= rate * calls as f64
+    }
+
+    fn status(&self, config: &ToolHealthConfig, now: Instant) -> ToolHealthStatus {
+        if self.withheld_until.is_some_and(|until| now < until) {
+            ToolHealthStatus::Withheld
+        } else if self.failing(config.min_calls, config.degraded_failure_rate) {
+            ToolHealthStatus::Degraded
+        } else {
+            ToolHealthStatus::Healthy
+        }
+    }
+}
+
+/// Thread-safe tracker of tool health
+///
+/// Cloning a `ToolHealth` shares the underlying state.
+#[derive(Debug, Clone)]
+pub struct ToolHealth {
+    config: ToolHealthConfig,
+    tools: Arc<Mutex<BTreeMap<String, ToolRecord>>>,
+}
+
+impl ToolHealth {
+    /// Creates a tracker with no recorded calls
+    pub fn new(config: ToolHealthConfig) -> Self {
+        Self {
+            config,
+            tools: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// Records the outcome of one tool call
+    ///
+    /// # Arguments
+    ///
+    /// * `tool` - Tool name as called by the model
+    /// * `success` - Whether the call succeeded
+    /// * `error` - Error text of a failed call
+    pub fn record(&self, tool: &str, success: bool, error: Option<&str>) {
+        self.record_at(tool, success, error, Instant::now());
+    }
+
+    fn record_at(&self, tool: &str, success: bool, error: Option<&str>, now: Instant) {
+        if !self.config.enabled {
+            return;
+        }
+        let mut tools = self.lock();
+        let record = tools.entry(tool.to_string()).or_default();
+        record.decay(&self.config, now);
+
+        if success {
+            if record.status(&self.config, now) != ToolHealthStatus::Healthy {
+                tracing::info!(tool, "Tool recovered");
+                *record = ToolRecord::default();
+            }
+            record.outcomes.push_back((now, true));
+        } else {
+            record.outcomes.push_back((now, false));
+            record.last_error = error.map(summarize_error).filter(|e| !e.is_empty());
+        }
+        while record.outcomes.len() > self.config.window {
+            record.outcomes.pop_front();
+        }
+
+        let withheld = record.withheld_until.is_some_and(|until| now < until);
+        if !success
+            && !withheld
+            && record.failing(
+                self.config.withhold_min_calls,
+                self.config.withhold_failure_rate,
+            )
+        {
+            tracing::warn!(
+                tool,
+                failures = record.failures(),
+                calls = record.outcomes.len(),
+                cooldown_seconds = self.config.cooldown_seconds,
+                "Withholding failing tool from requests"
+            );
+            record.withheld_until = Some(now + Duration::from_secs(self.config.cooldown_seconds));
+        }
+    }
+
+    /// Returns the current health of a tool
+    pub fn status(&self, tool: &str) -> ToolHealthStatus {
+        let now = Instant::now();
+        let mut tools = self.lock();
+        match tools.get_mut(tool) {
+            Some(record) => {
+                record.decay(&self.config, now);
+                record.status(&self.config, now)
+            }
+            None => ToolHealthStatus::Healthy,
+        }
+    }
+
+    /// Adjusts tool definitions to the health of their tools
+    ///
+    /// Definitions of withheld tools are dropped, and the descriptions of
+    /// degraded tools get a status suffix. Definitions are matched by their
+    /// `name`, or `function.name` in the OpenAI format.
+    ///
+    /// # Arguments
+    ///
+    /// * `definitions` - Tool definitions about to be sent to the provider
+    ///
+    /// # Returns
+    ///
+    /// Returns the definitions to send
+    pub fn apply(&self, definitions: Vec<Value>) -> Vec<Value> {
+        self.apply_at(definitions, Instant::now())
+    }
+
+    fn apply_at(&self, definitions: Vec<Value>, now: Instant) -> Vec<Value> {
+        if !self.config.enabled {
+            return definitions;
+        }
+        let mut tools = self.lock();
+        if tools.is_empty() {
+            return definitions;
+        }
+
+        definitions
+            .into_iter()
+            .filter_map(|mut definition| {
+                let function = if definition.get("function").is_some() {
+                    &mut definition["function"]
+                } else {
+                    &mut definition
+                };
+                let Some(record) = function
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .and_then(|name| tools.get_mut(name))
+                else {
+                    return Some(definition);
+                };
+                record.decay(&self.config, now);
+                match record.status(&self.config, now) {
+                    ToolHealthStatus::Healthy => {}
+                    ToolHealthStatus::Withheld => return None,
+                    ToolHealthStatus::Degraded => {
+                        let error = record
+                            .last_error
+                            .as_deref()
+                            .unwrap_or("recent calls failed");
+                        let description = function
+                            .get("description")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default();
+                        let described = format!(
+                            "{} (currently failing: {} — prefer alternatives)",
+                            description, error
+                        );
+                        if let Some(object) = function.as_object_mut() {
+                            object.insert(
+                                "description".to_string(),
+                                Value::String(described.trim_start().to_string()),
+                            );
+                        }
+                    }
+                }
+                Some(definition)
+            })
+            .collect()
+    }
+
+    /// Returns the tools that are degraded or withheld, sorted by name
+    pub fn report(&self) -> Vec<ToolHealthEntry> {
+        self.report_at(Instant::now())
+    }
+
+    fn report_at(&self, now: Instant) -> Vec<ToolHealthEntry> {
+        let mut tools = self.lock();
+        tools
+            .iter_mut()
+            .filter_map(|(name, record)| {
+                record.decay(&self.config, now);
+                let status = record.status(&self.config, now);
+                (status != ToolHealthStatus::Healthy).then(|| ToolHealthEntry {
+                    name: name.clone(),
+                    status,
+                    recent_calls: record.outcomes.len(),
+                    recent_failures: record.failures(),
+                    last_error: record.last_error.clone(),
+                    withheld_for: record
+                        .withheld_until
+                        .filter(|_| status == ToolHealthStatus::Withheld)
+                        .map(|until| until.saturating_duration_since(now)),
+                })
+            })
+            .collect()
+    }
+
+    /// Locks the records, recovering from a poisoned lock
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, ToolRecord>> {
+        self.tools
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for ToolHealth {
+    fn default() -> Self {
+        Self::new(ToolHealthConfig::default())
+    }
+}
+
+/// Returns the first line of an error, cut to [`MAX_ERROR_CHARS`]
+fn summarize_error(error: &str) -> String {
+    let line = error.lines().next().unwrap_or_default().trim();
+    match line.char_indices().nth(MAX_ERROR_CHARS) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn definitions() -> Vec<Value> {
+        vec![
+            json!({"name": "mcp_search", "description": "Searches the web"}),
+            json!({"name": "read_file", "description": "Reads a file"}),
+        ]
+    }
+
+    fn names(definitions: &[Value]) -> Vec<&str> {
+        definitions
+            .iter()
+            .filter_map(|definition| definition["name"].as_str())
+            .collect()
+    }
+
+    fn fail(health: &ToolHealth, times: usize, now: Instant) {
+        for _ in 0..times {
+            health.record_at(
+                "mcp_search",
+                false,
+                Some("connection refused\nat transport.rs:12"),
+                now,
+            );
+        }
+    }
+
+    #[test]
+    fn test_failures_below_the_thresholds_leave_definitions_alone() {
+        let health = ToolHealth::default();
+        let now = Instant::now();
+        for success in [true, true, false, true, false] {
+            health.record_at("mcp_search", success, Some("connection refused"), now);
+        }
+
+        // 2 failures in 5 calls is below the degraded rate of 0.5
+        assert_eq!(health.apply_at(definitions(), now), definitions());
+        assert!(health.report_at(now).is_empty());
+    }
+
+    #[test]
+    fn test_degraded_tool_gets_a_status_suffix() {
+        let health = ToolHealth::default();
+        let now = Instant::now();
+        fail(&health, 3, now);
+
+        let applied = health.apply_at(definitions(), now);
+        assert_eq!(names(&applied), ["mcp_search", "read_file"]);
+        assert_eq!(
+            applied[0]["description"],
+            "Searches the web (currently failing: connection refused — prefer alternatives)"
+        );
+        assert_eq!(applied[1], definitions()[1]);
+
+        let report = health.report_at(now);
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].status, ToolHealthStatus::Degraded);
+        assert_eq!(report[0].recent_failures, 3);
+        assert_eq!(report[0].withheld_for, None);
+    }
+
+    #[test]
+    fn test_openai_format_definitions_are_matched_by_function_name() {
+        let health = ToolHealth::default();
+        let now = Instant::now();
+        fail(&health, 3, now);
+
+        let applied = health.apply_at(
+            vec![json!({
+                "type": "function",
+                "function": {"name": "mcp_search", "description": "Searches the web"}
+            })],
+            now,
+        );
+        assert!(applied[0]["function"]["description"]
+            .as_str()
+            .unwrap()
+            .ends_with("— prefer alternatives)"));
+    }
+
+    #[test]
+    fn test_withheld_tool_is_dropped_until_the_cooldown_ends() {
+        let health = ToolHealth::default();
+        let now = Instant::now();
+        fail(&health, 5, now);
+
+        assert_eq!(names(&health.apply_at(definitions(), now)), ["read_file"]);
+        let report = health.report_at(now);
+        assert_eq!(report[0].status, ToolHealthStatus::Withheld);
+        assert_eq!(report[0].withheld_for, Some(Duration::from_secs(120)));
+
+        // After the cooldown the tool is offered again, still flagged, and
+        // the next failure withholds it for another cooldown
+        let later = now + Duration::from_secs(121);
+        let applied = health.apply_at(definitions(), later);
+        assert_eq!(names(&applied), ["mcp_search", "read_file"]);
+        assert!(applied[0]["description"]
+            .as_str()
+            .unwrap()
+            .contains("currently failing"));
+        fail(&health, 1, later);
+        assert_eq!(names(&health.apply_at(definitions(), later)), ["read_file"]);
+    }
+
+    #[test]
+    fn test_success_recovers_a_withheld_tool() {
+        let health = ToolHealth::default();
+        let now = Instant::now();
+        fail(&health, 5, now);
+
+        health.record_at("mcp_search", true, None, now);
+
+        assert_eq!(health.apply_at(definitions(), now), definitions());
+        assert!(health.report_at(now).is_empty());
+        fail(&health, 1, now);
+        assert_eq!(health.apply_at(definitions(), now), definitions());
+    }
+
+    #[test]
+    fn test_failures_decay_over_time() {
+        let health = ToolHealth::default();
+        let now = Instant::now();
+        fail(&health, 3, now);
+
+        let later = now + Duration::from_secs(601);
+        assert_eq!(health.apply_at(definitions(), later), definitions());
+        fail(&health, 2, later);
+        assert!(health.report_at(later).is_empty());
+    }
+
+    #[test]
+    fn test_disabled_tracker_changes_nothing() {
+        let health = ToolHealth::new(ToolHealthConfig {
+            enabled: false,
+            ..ToolHealthConfig::default()
+        });
+        let now = Instant::now();
+        fail(&health, 10, now);
+        assert_eq!(health.apply_at(definitions(), now), definitions());
+        assert!(health.report_at(now).is_empty());
+    }
+
+    #[test]
+    fn test_summarize_error_keeps_the_first_line() {
+        assert_eq!(summarize_error("refused\nstack"), "refused");
+        let long = "x".repeat(100);
+        assert_eq!(summarize_error(&long), format!("{}...", "x".repeat(80)));
+    }
+}
@@ -0,0 +1,354 @@
+//! Per-tool execution metrics for a session
+//!
+//! This module collects lightweight statistics for every tool call the agent
+//! makes: call counts by outcome, time spent, and output size. The collector
+//! is shared behind an `Arc<Mutex<_>>`, so clones record into the same
+//! aggregate and recording from concurrent tool calls is safe.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use xzatoma::agent::{ToolCallStatus, ToolMetrics};
+//!
+//! let metrics = ToolMetrics::new();
+//! metrics.record("read_file", ToolCallStatus::Success, Duration::from_millis(12), 420);
+//!
+//! let summary = metrics.summary();
+//! assert_eq!(summary.totals.calls, 1);
+//! assert_eq!(summary.tools[0].name, "read_file");
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Outcome of a single tool call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallStatus {
+    /// The tool ran and reported success
+    Success,
+    /// The tool failed or reported an error result
+    Failure,
+    /// The tool, or a server it called, timed out
+    Timeout,
+}
+
+impl ToolCallStatus {
+    /// Classifies a tool call from its success flag and error text
+    ///
+    /// Errors that mention a timeout are reported as `Timeout`, which covers
+    /// terminal command timeouts and MCP request timeouts.
+    ///
+    /// # Arguments
+    ///
+    /// * `success` - Whether the tool reported success
+    /// * `error` - Error text, if any
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::agent::ToolCallStatus;
+    ///
+    /// assert_eq!(ToolCallStatus::classify(true, None), ToolCallStatus::Success);
+    /// assert_eq!(
+    ///     ToolCallStatus::classify(false, Some("MCP timeout: server=git, method=tools/call")),
+    ///     ToolCallStatus::Timeout
+    /// );
+    /// ```
+    pub fn classify(success: bool, error: Option<&str>) -> Self {
+        if success {
+            return Self::Success;
+        }
+        let timed_out = error.is_some_and(|e| {
+            let e = e.to_lowercase();
+            e.contains("timed out") || e.contains("timeout")
+        });
+        if timed_out {
+            Self::Timeout
+        } else {
+            Self::Failure
+        }
+    }
+}
+
+/// Aggregated statistics for one tool, or for all tools combined
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ToolStats {
+    /// Total number of calls
+    pub calls: u64,
+    /// Calls that succeeded
+    pub successes: u64,
+    /// Calls that failed for reasons other than a timeout
+    pub failures: u64,
+    /// Calls that timed out
+    pub timeouts: u64,
+    /// Total wall-clock time spent in the tool, in milliseconds
+    pub total_duration_ms: u64,
+    /// Longest single call, in milliseconds
+    pub max_duration_ms: u64,
+    /// Total bytes of output returned to the model
+    pub total_output_bytes: u64,
+}
+
+impl ToolStats {
+    /// Adds a single call to the statistics
+    fn record(&mut self, status: ToolCallStatus, duration: Duration, output_bytes: usize) {
+        let duration_ms = duration.as_millis().min(u64::MAX as u128) as u64;
+        self.calls += 1;
+        match status {
+            ToolCallStatus::Success => self.successes += 1,
+            ToolCallStatus::Failure => self.failures += 1,
+            ToolCallStatus::Timeout => self.timeouts += 1,
+        }
+        self.total_duration_ms = self.total_duration_ms.saturating_add(duration_ms);
+        self.max_duration_ms = self.max_duration_ms.max(duration_ms);
+        self.total_output_bytes = self.total_output_bytes.saturating_add(output_bytes as u64);
+    }
+
+    /// Adds another set of statistics to this one
+    fn merge(&mut self, other: &ToolStats) {
+        self.calls += other.calls;
+        self.successes += other.successes;
+        self.failures += other.failures;
+        self.timeouts += other.timeouts;
+        self.total_duration_ms = self
+            .total_duration_ms
+            .saturating_add(other.total_duration_ms);
+        self.max_duration_ms = self.max_duration_ms.max(other.max_duration_ms);
+        self.total_output_bytes = self
+            .total_output_bytes
+            .saturating_add(other.total_output_bytes);
+    }
+
+    /// Returns the mean call duration in milliseconds, or 0 with no calls
+    pub fn average_duration_ms(&self) -> u64 {
+        if self.calls == 0 {
+            0
+        } else {
+            self.total_duration_ms / self.calls
+        }
+    }
+}
+
+/// Statistics for a single named tool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ToolStatsEntry {
+    /// Tool name
+    pub name: String,
+    /// Statistics for the tool
+    #[serde(flatten)]
+    pub stats: ToolStats,
+}
+
+/// Snapshot of all tool metrics, sorted by tool name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ToolMetricsSummary {
+    /// Per-tool statistics
+    pub tools: Vec<ToolStatsEntry>,
+    /// Statistics across all tools
+    pub totals: ToolStats,
+}
+
+impl ToolMetricsSummary {
+    /// Returns true when no tool calls were recorded
+    pub fn is_empty(&self) -> bool {
+        self.totals.calls == 0
+    }
+}
+
+/// Thread-safe collector of per-tool execution metrics
+///
+/// Cloning a `ToolMetrics` shares the underlying counters.
+#[derive(Debug, Clone, Default)]
+pub struct ToolMetrics {
+    stats: Arc<Mutex<BTreeMap<String, ToolStats>>>,
+}
+
+impl ToolMetrics {
+    /// Creates an empty collector
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a single tool call
+    ///
+    /// # Arguments
+    ///
+    /// * `tool` - Tool name
+    /// * `status` - Outcome of the call
+    /// * `duration` - Wall-clock time spent in the call
+    /// * `output_bytes` - Size of the output returned to the model
+    pub fn record(
+        &self,
+        tool: &str,
+        status: ToolCallStatus,
+        duration: Duration,
+        output_bytes: usize,
+    ) {
+        self.lock()
+            .entry(tool.to_string())
+            .or_default()
+            .record(status, duration, output_bytes);
+    }
+
+    /// Returns a snapshot of the per-tool and aggregate statistics
+    pub fn summary(&self) -> ToolMetricsSummary {
+        let stats = self.lock();
+        let mut totals = ToolStats::default();
+        let tools = stats
+            .iter()
+            .map(|(name, stats)| {
+                totals.merge(stats);
+                ToolStatsEntry {
+                    name: name.clone(),
+                    stats: stats.clone(),
+                }
+            })
+            .collect();
+        ToolMetricsSummary { tools, totals }
+    }
+
+    /// Clears all recorded statistics
+    pub fn reset(&self) {
+        self.lock().clear();
+    }
+
+    /// Returns true when no tool calls were recorded
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Locks the statistics, recovering from a poisoned lock
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, ToolStats>> {
+        self.stats
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_status() {
+        assert_eq!(
+            ToolCallStatus::classify(true, None),
+            ToolCallStatus::Success
+        );
+        assert_eq!(
+            ToolCallStatus::classify(false, Some("File not found: a.txt")),
+            ToolCallStatus::Failure
+        );
+        assert_eq!(
+            ToolCallStatus::classify(false, Some("Command timed out after 30s")),
+            ToolCallStatus::Timeout
+        );
+        assert_eq!(
+            ToolCallStatus::classify(false, None),
+            ToolCallStatus::Failure
+        );
+    }
+
+    #[test]
+    fn test_summary_aggregates_per_tool_and_totals() {
+        let metrics = ToolMetrics::new();
+        metrics.record(
+            "read_file",
+            ToolCallStatus::Success,
+            Duration::from_millis(10),
+            100,
+        );
+        metrics.record(
+            "read_file",
+            ToolCallStatus::Failure,
+            Duration::from_millis(30),
+            20,
+        );
+        metrics.record(
+            "terminal",
+            ToolCallStatus::Timeout,
+            Duration::from_millis(500),
+            0,
+        );
+
+        let summary = metrics.summary();
+        assert_eq!(summary.tools.len(), 2);
+
+        let read_file = &summary.tools[0];
+        assert_eq!(read_file.name, "read_file");
+        assert_eq!(read_file.stats.calls, 2);
+        assert_eq!(read_file.stats.successes, 1);
+        assert_eq!(read_file.stats.failures, 1);
+        assert_eq!(read_file.stats.total_duration_ms, 40);
+        assert_eq!(read_file.stats.max_duration_ms, 30);
+        assert_eq!(read_file.stats.average_duration_ms(), 20);
+        assert_eq!(read_file.stats.total_output_bytes, 120);
+
+        assert_eq!(summary.totals.calls, 3);
+        assert_eq!(summary.totals.successes, 1);
+        assert_eq!(summary.totals.failures, 1);
+        assert_eq!(summary.totals.timeouts, 1);
+        assert_eq!(summary.totals.total_duration_ms, 540);
+        assert_eq!(summary.totals.max_duration_ms, 500);
+        assert_eq!(summary.totals.total_output_bytes, 120);
+    }
+
+    #[test]
+    fn test_reset_clears_statistics() {
+        let metrics = ToolMetrics::new();
+        metrics.record("grep", ToolCallStatus::Success, Duration::ZERO, 1);
+        assert!(!metrics.is_empty());
+
+        metrics.reset();
+
+        assert!(metrics.is_empty());
+        assert!(metrics.summary().is_empty());
+        assert_eq!(metrics.summary().totals.average_duration_ms(), 0);
+    }
+
+    #[test]
+    fn test_concurrent_recording_from_parallel_calls() {
+        let metrics = ToolMetrics::new();
+        let handles: Vec<_> = (0..8)
+            .map(|worker| {
+                let metrics = metrics.clone();
+                std::thread::spawn(move || {
+                    let tool = if worker % 2 == 0 { "read_file" } else { "grep" };
+                    for _ in 0..100 {
+                        metrics.record(tool, ToolCallStatus::Success, Duration::from_millis(1), 3);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let summary = metrics.summary();
+        assert_eq!(summary.totals.calls, 800);
+        assert_eq!(summary.totals.successes, 800);
+        assert_eq!(summary.totals.total_duration_ms, 800);
+        assert_eq!(summary.totals.total_output_bytes, 2400);
+        assert!(summary.tools.iter().all(|entry| entry.stats.calls == 400));
+    }
+
+    #[test]
+    fn test_summary_serializes_flattened_entries() {
+        let metrics = ToolMetrics::new();
+        metrics.record(
+            "fetch",
+            ToolCallStatus::Success,
+            Duration::from_millis(5),
+            7,
+        );
+
+        let value = serde_json::to_value(metrics.summary()).unwrap();
+        assert_eq!(value["tools"][0]["name"], "fetch");
+        assert_eq!(value["tools"][0]["calls"], 1);
+        assert_eq!(value["totals"]["total_output_bytes"], 7);
+    }
+}
@@ -9,6 +9,8 @@
 use colored::Colorize;
 use std::fmt;
 
+use crate::tools::confirmation::ConfirmationGrants;
+
 /// Chat mode for interactive sessions
 ///
 /// Determines which tools are available and how the agent behaves.
@@ -114,6 +116,13 @@ pub enum SafetyMode {
     /// Operations proceed without confirmation. Use with caution
     /// as this can lead to unintended side effects.
     NeverConfirm,
+
+    /// Confirm the first action of each category once per session
+    ///
+    /// The first file write, delete, terminal command, or fetch from a new
+    /// domain needs confirmation; the grant then covers the rest of the
+    /// session. See [`crate::tools::confirmation`].
+    ConfirmOnce,
 }
 
 impl fmt::Display for SafetyMode {
@@ -121,6 +130,7 @@ impl fmt::Display for SafetyMode {
         match self {
             Self::AlwaysConfirm => write!(f, "SAFE"),
             Self::NeverConfirm => write!(f, "YOLO"),
+            Self::ConfirmOnce => write!(f, "ONCE"),
         }
     }
 }
@@ -130,7 +140,8 @@ impl SafetyMode {
     ///
     /// # Arguments
     ///
-    /// * `s` - String representation ("confirm", "always", "safe", "yolo", "never", or "off")
+    /// * `s` - String representation ("confirm", "always", "safe", "yolo", "never",
+    ///   "off", or "confirm_once")
     ///
     /// # Returns
     ///
@@ -148,6 +159,7 @@ impl SafetyMode {
         match s.to_lowercase().as_str() {
             "confirm" | "always" | "safe" | "on" => Ok(Self::AlwaysConfirm),
             "yolo" | "never" | "off" => Ok(Self::NeverConfirm),
+            "confirm_once" | "once" => Ok(Self::ConfirmOnce),
             other => Err(format!("Unknown safety mode: {}", other)),
         }
     }
@@ -161,6 +173,7 @@ impl SafetyMode {
         match self {
             Self::AlwaysConfirm => "Confirm dangerous operations",
             Self::NeverConfirm => "Never confirm operations (YOLO)",
+            Self::ConfirmOnce => "Confirm each kind of operation once per session",
         }
     }
 
@@ -183,6 +196,7 @@ impl SafetyMode {
         match self {
             Self::AlwaysConfirm => format!("[{}]", "SAFE".cyan()),
             Self::NeverConfirm => format!("[{}]", "YOLO".yellow()),
+            Self::ConfirmOnce => format!("[{}]", "ONCE".blue()),
         }
     }
 }
@@ -198,6 +212,10 @@ pub struct ChatModeState {
     pub safety_mode: SafetyMode,
     /// Whether subagent delegation is enabled in chat mode
     pub subagents_enabled: bool,
+    /// Confirmations granted in ConfirmOnce mode, shared by rebuilt tools
+    pub confirmation_grants: ConfirmationGrants,
+    /// Whether the session is read-only; see [`crate::read_only`]
+    pub read_only: bool,
 }
 
 impl ChatModeState {
@@ -225,6 +243,8 @@ impl ChatModeState {
             chat_mode,
             safety_mode,
             subagents_enabled: false,
+            confirmation_grants: ConfirmationGrants::new(),
+            read_only: false,
         }
     }
 
@@ -477,6 +497,19 @@ mod tests {
     fn test_safety_mode_display() {
         assert_eq!(SafetyMode::AlwaysConfirm.to_string(), "SAFE");
         assert_eq!(SafetyMode::NeverConfirm.to_string(), "YOLO");
+        assert_eq!(SafetyMode::ConfirmOnce.to_string(), "ONCE");
+    }
+
+    #[test]
+    fn test_safety_mode_from_str_confirm_once() {
+        assert_eq!(
+            SafetyMode::parse_str("confirm_once").unwrap(),
+            SafetyMode::ConfirmOnce
+        );
+        assert_eq!(
+            SafetyMode::parse_str("ONCE").unwrap(),
+            SafetyMode::ConfirmOnce
+        );
     }
 
     #[test]
@@ -3,6 +3,7 @@
 //! This module defines the CLI structure using clap's derive API,
 //! providing commands for chat, plan execution, and authentication.
 
+use crate::storage::types::IfExists;
 use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
 
@@ -15,7 +16,7 @@ use std::path::PathBuf;
 #[command(version, about, long_about = None)]
 pub struct Cli {
     /// Path to configuration file
-    #[arg(short, long, default_value = "config/config.yaml")]
+    #[arg(short, long, default_value = crate::config::DEFAULT_CONFIG_PATH)]
     pub config: Option<String>,
 
     /// Enable verbose logging
@@ -26,6 +27,47 @@ pub struct Cli {
     #[arg(long, env = "XZATOMA_HISTORY_DB")]
     pub storage_path: Option<String>,
 
+    /// Disable all network features except the local Ollama provider
+    #[arg(long, global = true)]
+    pub offline: bool,
+
+    /// Refuse every change to the workspace and every command
+    #[arg(long, global = true)]
+    pub read_only: bool,
+
+    /// Allow `@url:` mentions and the fetch tool with --read-only
+    #[arg(long, global = true, requires = "read_only")]
+    pub allow_fetch: bool,
+
+    /// Bypass the provider response cache for this invocation
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+
+    /// Keep sending provider requests past `budget.monthly_cost_limit`
+    #[arg(long, global = true)]
+    pub ignore_budget: bool,
+
+    /// Ignore MCP servers defined in `.mcp.json` or `.vscode/mcp.json`
+    #[arg(long, global = true)]
+    pub no_project_mcp: bool,
+
+    /// Never offer the guided setup when no configuration file exists
+    #[arg(long, global = true)]
+    pub no_setup: bool,
+
+    /// Print token counts, costs, durations, and sizes as plain numbers
+    ///
+    /// For scripts that read the text output; `--json` output is never
+    /// formatted.
+    #[arg(long, global = true)]
+    pub raw_numbers: bool,
+
+    /// When to color output: auto, always, or never
+    ///
+    /// `auto` colors a terminal unless `NO_COLOR` is set.
+    #[arg(long, global = true, value_name = "WHEN", default_value = "auto")]
+    pub color: String,
+
     /// Command to execute
     #[command(subcommand)]
     pub command: Commands,
@@ -41,7 +83,10 @@ pub enum Commands {
         provider: Option<String>,
 
         /// Chat mode: planning (read-only) or write (read/write)
-        #[arg(short, long, default_value = "planning")]
+        ///
+        /// Defaults to `agent.chat.default_mode`, which is `planning` unless
+        /// configured otherwise.
+        #[arg(short, long)]
         mode: Option<String>,
 
         /// Enable safety mode (always confirm dangerous operations)
@@ -60,9 +105,49 @@ pub enum Commands {
         /// configuration file specifies a level.
         #[arg(long)]
         thinking_effort: Option<String>,
+
+        /// Start the session in this directory instead of the current one.
+        ///
+        /// File tools, the terminal, and mentions operate relative to the
+        /// session directory. Use `/cd` to change it during the chat.
+        #[arg(long, value_name = "PATH")]
+        cwd: Option<PathBuf>,
+
+        /// Allow `--cwd` and `/cd` to leave the launch directory
+        #[arg(long)]
+        allow_cwd_escape: bool,
+
+        /// Mirror the conversation to this markdown file as it happens
+        ///
+        /// Overrides `agent.chat.transcript_path`. The file is appended to,
+        /// so a resumed session continues in the same transcript.
+        #[arg(long, value_name = "PATH")]
+        transcript: Option<PathBuf>,
+
+        /// Save the conversation under this stable ID instead of a random one
+        ///
+        /// A key that is not a UUID is hashed into one, so the same key
+        /// always names the same stored conversation.
+        #[arg(long, value_name = "KEY", conflicts_with = "resume")]
+        session_id: Option<String>,
+
+        /// What to do when the session ID is already stored: replace, append, or fail
+        #[arg(long, value_name = "POLICY", requires = "session_id")]
+        if_exists: Option<IfExists>,
+
+        /// Start without the startup banner
+        ///
+        /// Overrides `agent.chat.banner` with `off`.
+        #[arg(short, long)]
+        quiet: bool,
     },
 
     /// Execute a plan or prompt
+    ///
+    /// Examples:
+    ///   xzatoma run --plan plans/release.yaml --snapshot
+    ///   xzatoma run rollback 3f2a9c1e-04b7-4c1d-9a8e-5b6f7d8e9f01
+    #[command(args_conflicts_with_subcommands = true)]
     Run {
         /// Path to plan file (YAML format)
         #[arg(short, long)]
@@ -82,6 +167,57 @@ pub enum Commands {
         /// When omitted, the value from the configuration file is used.
         #[arg(long)]
         thinking_effort: Option<String>,
+
+        /// Print the result and tool metrics as JSON instead of text
+        #[arg(long)]
+        json: bool,
+
+        /// Validate the plan file and report every problem without running it
+        #[arg(long, requires = "plan", conflicts_with = "prompt")]
+        validate_only: bool,
+
+        /// Print each step's effective tools and execution mode without running the plan
+        #[arg(long, requires = "plan", conflicts_with_all = ["prompt", "validate_only"])]
+        dry_run: bool,
+
+        /// Run the agent's tools in this directory instead of the current one
+        #[arg(long, value_name = "PATH")]
+        cwd: Option<PathBuf>,
+
+        /// Approve a full_autonomous plan run without a prompt, using the hash printed by --dry-run
+        #[arg(long, value_name = "PLAN_HASH", requires = "plan")]
+        confirm_dangerous: Option<String>,
+
+        /// Fail instead of warning when the prompt exceeds the preflight size limit
+        #[arg(long)]
+        strict_budget: bool,
+
+        /// Record every provider request of the run for `xzatoma debug`
+        #[arg(long, conflicts_with_all = ["validate_only", "dry_run"])]
+        record: bool,
+
+        /// Generate a post-mortem report with the provider if the run fails
+        #[arg(long, conflicts_with_all = ["validate_only", "dry_run"])]
+        postmortem: bool,
+
+        /// Save the run's conversation under this stable ID
+        ///
+        /// A key that is not a UUID is hashed into one, so re-running the
+        /// same job updates one stored conversation.
+        #[arg(long, value_name = "KEY", conflicts_with_all = ["validate_only", "dry_run"])]
+        session_id: Option<String>,
+
+        /// What to do when the session ID is already stored: replace, append, or fail
+        #[arg(long, value_name = "POLICY", requires = "session_id")]
+        if_exists: Option<IfExists>,
+
+        /// Snapshot the workspace first so `xzatoma run rollback` can undo the run
+        #[arg(long, conflicts_with_all = ["validate_only", "dry_run"])]
+        snapshot: bool,
+
+        /// Run subcommand to execute instead of a plan or prompt
+        #[command(subcommand)]
+        command: Option<RunCommand>,
     },
 
     /// Run as an ACP stdio agent subprocess for Zed or another ACP-compatible client
@@ -103,6 +239,25 @@ pub enum Commands {
         working_dir: Option<PathBuf>,
     },
 
+    /// Serve agent sessions over JSON-RPC for editor integrations
+    ///
+    /// Reads one JSON-RPC 2.0 message per line from stdin and writes
+    /// responses and `session/update` notifications to stdout. Logs go to
+    /// stderr.
+    ///
+    /// Examples:
+    ///   xzatoma serve --stdio
+    ///   xzatoma serve --stdio --working-dir /path/to/project
+    Serve {
+        /// Communicate over stdin and stdout (the only transport)
+        #[arg(long, required = true)]
+        stdio: bool,
+
+        /// Workspace for sessions that do not name one (defaults to the current directory)
+        #[arg(long)]
+        working_dir: Option<PathBuf>,
+    },
+
     /// Watch Kafka topic for events and execute plans
     Watch {
         /// Kafka topic to watch (overrides config)
@@ -195,7 +350,7 @@ pub enum Commands {
         command: HistoryCommand,
     },
 
-    /// Replay subagent conversations for debugging
+    /// Replay subagent conversations, or re-run a stored chat conversation
     Replay {
         /// Conversation ID to replay
         #[arg(long, short = 'i')]
@@ -205,9 +360,10 @@ pub enum Commands {
         #[arg(long, short = 'l')]
         list: bool,
 
-        /// Path to conversation database
-        #[arg(long, default_value = "~/.xzatoma/conversations.db")]
-        db_path: std::path::PathBuf,
+        /// Path to conversation database; defaults to
+        /// `agent.subagent.persistence.database`
+        #[arg(long)]
+        db_path: Option<std::path::PathBuf>,
 
         /// Limit for list results
         #[arg(long, default_value = "10")]
@@ -220,6 +376,40 @@ pub enum Commands {
         /// Show conversation tree (with nested subagents)
         #[arg(long, short = 't')]
         tree: bool,
+
+        /// Stored chat conversation to re-run against the current configuration
+        #[arg(value_name = "CONVERSATION_ID")]
+        conversation_id: Option<String>,
+
+        /// Provider to use for the re-run instead of the configured one
+        #[arg(long)]
+        provider: Option<String>,
+
+        /// Model to use for the re-run instead of the configured one
+        #[arg(long)]
+        model: Option<String>,
+
+        /// Show a unified diff of each original and new assistant response
+        #[arg(long)]
+        diff: bool,
+
+        /// Execute tools during the re-run instead of returning recorded results
+        #[arg(long)]
+        allow_tools: bool,
+    },
+
+    /// Step through the recorded turns of a `run --record` execution
+    ///
+    /// Opens an interactive inspector. Commands: `list`, `show <turn>`,
+    /// `diff <from> <to>`, `dump <turn>`, `help`, and `quit`.
+    ///
+    /// Examples:
+    ///   xzatoma run --prompt "Fix the failing test" --record
+    ///   xzatoma debug 3f2a9c1e
+    Debug {
+        /// Recorded run ID, or a unique prefix of it
+        #[arg(value_name = "RUN_ID")]
+        run_id: String,
     },
 
     /// MCP server management commands
@@ -242,6 +432,245 @@ pub enum Commands {
         #[command(subcommand)]
         command: SkillsCommand,
     },
+
+    /// Inspect the file mutation audit log
+    Audit {
+        /// Audit subcommand to execute
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+
+    /// Draft and refine plan files with the agent
+    ///
+    /// Examples:
+    ///   xzatoma plan new "Add a release workflow" --output plans/release.yaml
+    ///   xzatoma plan refine plans/release.yaml "Run the tests before tagging"
+    Plan {
+        /// Plan subcommand to execute
+        #[command(subcommand)]
+        command: PlanCommand,
+    },
+
+    /// Inspect or clear the provider response cache
+    ///
+    /// Examples:
+    ///   xzatoma cache stats
+    ///   xzatoma cache clear
+    Cache {
+        /// Cache subcommand to execute
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+
+    /// Build the semantic index used by `@semantic` mentions
+    ///
+    /// Examples:
+    ///   xzatoma index build
+    ///   xzatoma index build ../other-project
+    Index {
+        /// Index subcommand to execute
+        #[command(subcommand)]
+        command: IndexCommand,
+    },
+
+    /// Print tool output that was summarized because it was too large
+    ///
+    /// Examples:
+    ///   xzatoma tool-output show 3f2a9c1e04b7
+    ToolOutput {
+        /// Tool output subcommand to execute
+        #[command(subcommand)]
+        command: ToolOutputCommand,
+    },
+
+    /// Report provider usage and estimated cost for a month
+    ///
+    /// Examples:
+    ///   xzatoma usage
+    ///   xzatoma usage --month 2025-01 --by day
+    ///   xzatoma usage --by session --json
+    Usage {
+        /// Month to report, as YYYY-MM (default: the current month, UTC)
+        #[arg(long, value_name = "YYYY-MM")]
+        month: Option<String>,
+
+        /// Group rows by model, day, or session
+        #[arg(long, value_name = "GROUP", default_value = "model")]
+        by: String,
+
+        /// Output the report as pretty-printed JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show where data, cache, and state files are kept
+    ///
+    /// Examples:
+    ///   xzatoma paths
+    ///   XZATOMA_DATA_DIR=/tmp/xz xzatoma paths
+    Paths,
+
+    /// Manage trusted workspace directories
+    ///
+    /// Examples:
+    ///   xzatoma trust add .
+    ///   xzatoma trust list
+    Trust {
+        /// Trust subcommand to execute
+        #[command(subcommand)]
+        command: TrustCommand,
+    },
+
+    /// Check the local setup and report problems
+    ///
+    /// Exits with a non-zero status when any check fails.
+    ///
+    /// Examples:
+    ///   xzatoma doctor
+    ///   xzatoma doctor --json > doctor.json
+    Doctor {
+        /// Print the report as JSON (useful for bug reports)
+        #[arg(long)]
+        json: bool,
+
+        /// Seconds each check may take before it is reported as failed
+        #[arg(long, default_value_t = 10)]
+        timeout: u64,
+    },
+}
+
+/// Run subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum RunCommand {
+    /// Restore the workspace snapshot taken by `run --snapshot`
+    ///
+    /// Lists the files changed since the snapshot with their line counts,
+    /// then asks before restoring them.
+    Rollback {
+        /// Run ID printed by `run --snapshot`
+        #[arg(value_name = "RUN_ID")]
+        run_id: String,
+
+        /// Restore without asking for confirmation
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+}
+
+/// Provider response cache subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum CacheCommand {
+    /// Show the number, size, and age of cached responses
+    Stats,
+
+    /// Remove every cached response
+    Clear,
+}
+
+/// Stored tool output subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum ToolOutputCommand {
+    /// Print the full original output of a summarized tool result
+    Show {
+        /// Output ID from the summary header
+        #[arg(value_name = "ID")]
+        id: String,
+    },
+}
+
+/// Semantic index subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum IndexCommand {
+    /// Embed new and changed files and drop deleted ones
+    Build {
+        /// Workspace directory to index
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
+}
+
+/// Workspace trust subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum TrustCommand {
+    /// Trust a directory and its current project-local files
+    Add {
+        /// Directory to trust
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
+
+    /// Remove trust for a directory
+    Remove {
+        /// Directory to stop trusting
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
+
+    /// List trusted directories
+    List,
+}
+
+/// Plan drafting subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum PlanCommand {
+    /// Draft a new plan for a goal
+    New {
+        /// Goal the plan should achieve; asked interactively when omitted
+        goal: Option<String>,
+
+        /// Path to write the plan to; asked interactively when omitted
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+
+        /// Directory the agent explores while drafting (default: current directory)
+        #[arg(long)]
+        working_dir: Option<PathBuf>,
+
+        /// Drafts to request before giving up when the plan fails validation
+        #[arg(long, default_value_t = crate::commands::plan::DEFAULT_MAX_ATTEMPTS)]
+        max_attempts: usize,
+
+        /// Write the plan without asking for confirmation
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+
+    /// Apply an edit instruction to an existing plan
+    Refine {
+        /// Plan file to refine (yaml/json/md)
+        file: PathBuf,
+
+        /// Change to make to the plan
+        instruction: String,
+
+        /// Path to write the refined plan to (default: the input file, or a
+        /// `.yaml` file next to it for JSON and Markdown plans)
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+
+        /// Directory the agent explores while refining (default: current directory)
+        #[arg(long)]
+        working_dir: Option<PathBuf>,
+
+        /// Drafts to request before giving up when the plan fails validation
+        #[arg(long, default_value_t = crate::commands::plan::DEFAULT_MAX_ATTEMPTS)]
+        max_attempts: usize,
+
+        /// Write the plan without asking for confirmation
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+}
+
+/// Audit log subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum AuditCommand {
+    /// List recorded file mutations, oldest first
+    List {
+        /// Only show entries from this session id
+        #[arg(long)]
+        session: Option<String>,
+    },
 }
 
 /// Skills management subcommands
@@ -413,7 +842,58 @@ pub enum AcpCommand {
 #[derive(Subcommand, Debug, Clone)]
 pub enum HistoryCommand {
     /// List saved conversations
-    List,
+    List {
+        /// Only list conversations carrying this tag (repeat to require several)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Show exact timestamps in UTC instead of relative local times
+        #[arg(long)]
+        utc: bool,
+
+        /// Show exact RFC 3339 timestamps (local time unless --utc is set)
+        #[arg(long)]
+        iso: bool,
+
+        /// Maximum number of conversations to list
+        #[arg(long, default_value = "25")]
+        limit: usize,
+
+        /// Number of most recent conversations to skip
+        #[arg(long, default_value = "0")]
+        offset: usize,
+    },
+
+    /// Interactively pick a saved conversation and print its ID
+    ///
+    /// Type to filter by title or ID and use the arrow keys to move the
+    /// selection. When stdin is not a terminal, a numbered list is printed
+    /// to stderr and the choice is read from stdin. Only the chosen ID is
+    /// written to stdout, so the command composes with others, for example
+    /// `xzatoma chat --resume $(xzatoma history pick)`.
+    Pick {
+        /// Only offer conversations carrying this tag (repeat to require several)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+
+    /// Search saved conversations by title and message content
+    Search {
+        /// Text to search for (case-insensitive)
+        query: String,
+
+        /// Only include conversations carrying this tag (repeat to require several)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Show exact timestamps in UTC instead of relative local times
+        #[arg(long)]
+        utc: bool,
+
+        /// Show exact RFC 3339 timestamps (local time unless --utc is set)
+        #[arg(long)]
+        iso: bool,
+    },
 
     /// Show detailed message-level history for a conversation
     Show {
@@ -430,12 +910,135 @@ pub enum HistoryCommand {
         limit: Option<usize>,
     },
 
-    /// Delete a saved conversation
+    /// Delete a saved conversation, or every conversation matching filters
+    ///
+    /// Filters combine: a conversation is deleted only when it matches all
+    /// of them. The matching conversations are listed before anything is
+    /// deleted.
+    ///
+    /// Examples:
+    ///   xzatoma history delete --id 3f2a9c1e
+    ///   xzatoma history delete --older-than 90d --tag scratch
+    ///   xzatoma history delete --untitled --model gpt-4o --yes
+    #[command(group(
+        clap::ArgGroup::new("selection")
+            .required(true)
+            .multiple(true)
+            .args(["id", "older_than", "tags", "untitled", "model"])
+    ))]
     Delete {
         /// ID of the conversation to delete
+        #[arg(short, long, conflicts_with_all = ["older_than", "tags", "untitled", "model"])]
+        id: Option<String>,
+
+        /// Only delete conversations last updated longer ago than this (e.g. 12h, 90d, 4w)
+        #[arg(long, value_name = "AGE")]
+        older_than: Option<String>,
+
+        /// Only delete conversations carrying this tag (repeat to require several)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Only delete conversations that were never given a title
+        #[arg(long)]
+        untitled: bool,
+
+        /// Only delete conversations that used this model
+        #[arg(long, value_name = "NAME")]
+        model: Option<String>,
+
+        /// Delete filtered conversations without asking for confirmation
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+
+    /// Attach a tag to a conversation
+    Tag {
+        /// ID of the conversation to tag
+        #[arg(short, long)]
+        id: String,
+
+        /// Tag to attach (normalized to lowercase)
+        tag: String,
+    },
+
+    /// Remove a tag from a conversation
+    Untag {
+        /// ID of the conversation to untag
+        #[arg(short, long)]
+        id: String,
+
+        /// Tag to remove
+        tag: String,
+    },
+
+    /// Remove conversations that exceed the configured retention limits
+    Prune {
+        /// Show what would be removed without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Pin a conversation so retention pruning never removes it
+    Pin {
+        /// ID of the conversation to pin
+        #[arg(short, long)]
+        id: String,
+    },
+
+    /// Unpin a previously pinned conversation
+    Unpin {
+        /// ID of the conversation to unpin
         #[arg(short, long)]
         id: String,
     },
+
+    /// Export every conversation to a directory as JSON files plus a manifest
+    Backup {
+        /// Directory to write the backup to (created if missing)
+        dir: PathBuf,
+    },
+
+    /// Import conversations from a backup or from another tool's export
+    ///
+    /// Conversations that already exist are skipped. Conversations from other
+    /// tools are tagged `imported:<tool>`.
+    ///
+    /// Examples:
+    ///   xzatoma history import ./backup
+    ///   xzatoma history import --format claude-json conversations.json
+    ///   xzatoma history import --format aider-md .aider.chat.history.md --dry-run
+    Import {
+        /// Backup directory, or an export file or directory of export files
+        path: PathBuf,
+
+        /// Format of the input: native, claude-json, openai-json, or aider-md
+        #[arg(long, default_value = "native")]
+        format: String,
+
+        /// Report what would be imported without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show usage statistics: sessions over time, per model, and per tool
+    ///
+    /// Examples:
+    ///   xzatoma history stats
+    ///   xzatoma history stats --since 30d --json
+    Stats {
+        /// Only include sessions updated within this age (e.g. 12h, 30d, 4w)
+        #[arg(long, value_name = "AGE")]
+        since: Option<String>,
+
+        /// Maximum rows per ranking table
+        #[arg(short = 'n', long, default_value_t = 10)]
+        limit: usize,
+
+        /// Output the statistics as pretty-printed JSON
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 impl Cli {
@@ -449,12 +1052,56 @@ impl Cli {
     }
 }
 
+impl Commands {
+    /// Returns true when the command prints JSON to stdout
+    ///
+    /// Status messages are silenced for these commands so stdout holds
+    /// nothing but the JSON document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use clap::Parser;
+    /// use xzatoma::cli::Cli;
+    ///
+    /// let cli = Cli::try_parse_from(["xzatoma", "doctor", "--json"]).unwrap();
+    /// assert!(cli.command.json_output());
+    /// let cli = Cli::try_parse_from(["xzatoma", "history", "list"]).unwrap();
+    /// assert!(!cli.command.json_output());
+    /// ```
+    pub fn json_output(&self) -> bool {
+        match self {
+            Commands::Run { json, .. }
+            | Commands::Doctor { json, .. }
+            | Commands::Usage { json, .. } => *json,
+            Commands::Models {
+                command: ModelCommand::List { json, .. } | ModelCommand::Info { json, .. },
+            } => *json,
+            Commands::History {
+                command: HistoryCommand::Stats { json, .. },
+            } => *json,
+            // stdout carries protocol messages
+            Commands::Serve { .. } => true,
+            _ => false,
+        }
+    }
+}
+
 impl Default for Cli {
     fn default() -> Self {
         Self {
             config: Some("config/config.yaml".to_string()),
             verbose: false,
             storage_path: None,
+            offline: false,
+            read_only: false,
+            allow_fetch: false,
+            no_cache: false,
+            ignore_budget: false,
+            no_project_mcp: false,
+            no_setup: false,
+            raw_numbers: false,
+            color: "auto".to_string(),
             command: Commands::Auth {
                 provider: Some("copilot".to_string()),
             },
@@ -482,11 +1129,78 @@ mod tests {
     }
 
     #[test]
-    fn test_cli_parses_agent_defaults() {
-        let cli = Cli::try_parse_from(["xzatoma", "agent"]);
-        assert!(cli.is_ok());
+    fn test_cli_parses_global_offline_flag() {
+        let cli = Cli::try_parse_from(["xzatoma", "--offline", "chat"]).unwrap();
+        assert!(cli.offline);
 
-        let cli = cli.unwrap();
+        let cli = Cli::try_parse_from(["xzatoma", "run", "--prompt", "hi", "--offline"]).unwrap();
+        assert!(cli.offline);
+
+        let cli = Cli::try_parse_from(["xzatoma", "chat"]).unwrap();
+        assert!(!cli.offline);
+    }
+
+    #[test]
+    fn test_cli_parses_global_read_only_flags() {
+        let cli = Cli::try_parse_from(["xzatoma", "chat", "--read-only", "--allow-fetch"]).unwrap();
+        assert!(cli.read_only);
+        assert!(cli.allow_fetch);
+
+        let cli = Cli::try_parse_from(["xzatoma", "--read-only", "run", "--prompt", "hi"]).unwrap();
+        assert!(cli.read_only);
+        assert!(!cli.allow_fetch);
+
+        assert!(Cli::try_parse_from(["xzatoma", "chat", "--allow-fetch"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_parse_usage_and_ignore_budget() {
+        let cli = Cli::try_parse_from(["xzatoma", "usage"]).unwrap();
+        match cli.command {
+            Commands::Usage { month, by, json } => {
+                assert_eq!(month, None);
+                assert_eq!(by, "model");
+                assert!(!json);
+            }
+            _ => panic!("Expected Usage command"),
+        }
+
+        let cli =
+            Cli::try_parse_from(["xzatoma", "usage", "--month", "2025-01", "--by", "day"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Usage { month: Some(ref m), ref by, .. } if m == "2025-01" && by == "day"
+        ));
+
+        let cli = Cli::try_parse_from(["xzatoma", "chat", "--ignore-budget"]).unwrap();
+        assert!(cli.ignore_budget);
+    }
+
+    #[test]
+    fn test_cli_parse_no_project_mcp() {
+        let cli = Cli::try_parse_from(["xzatoma", "chat"]).unwrap();
+        assert!(!cli.no_project_mcp);
+
+        let cli =
+            Cli::try_parse_from(["xzatoma", "run", "--prompt", "hi", "--no-project-mcp"]).unwrap();
+        assert!(cli.no_project_mcp);
+    }
+
+    #[test]
+    fn test_cli_parse_no_setup() {
+        let cli = Cli::try_parse_from(["xzatoma", "chat"]).unwrap();
+        assert!(!cli.no_setup);
+
+        let cli = Cli::try_parse_from(["xzatoma", "chat", "--no-setup"]).unwrap();
+        assert!(cli.no_setup);
+    }
+
+    #[test]
+    fn test_cli_parses_agent_defaults() {
+        let cli = Cli::try_parse_from(["xzatoma", "agent"]);
+        assert!(cli.is_ok());
+
+        let cli = cli.unwrap();
         match cli.command {
             Commands::Agent {
                 provider,
@@ -596,6 +1310,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cli_parses_serve_stdio() {
+        let cli = Cli::parse_from([
+            "xzatoma",
+            "serve",
+            "--stdio",
+            "--working-dir",
+            "/tmp/xzatoma-editor-workspace",
+        ]);
+        assert!(cli.command.json_output());
+        match cli.command {
+            Commands::Serve { stdio, working_dir } => {
+                assert!(stdio);
+                assert_eq!(
+                    working_dir,
+                    Some(PathBuf::from("/tmp/xzatoma-editor-workspace"))
+                );
+            }
+            other => panic!("expected serve command, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cli_serve_requires_a_transport() {
+        assert!(Cli::try_parse_from(["xzatoma", "serve"]).is_err());
+    }
+
     #[test]
     fn test_cli_parses_acp_serve_defaults() {
         let cli = Cli::parse_from(["xzatoma", "acp", "serve"]);
@@ -654,130 +1395,660 @@ mod tests {
     }
 
     #[test]
-    fn test_cli_parses_acp_config_subcommand() {
-        let cli = Cli::parse_from(["xzatoma", "acp", "config"]);
+    fn test_cli_parses_acp_config_subcommand() {
+        let cli = Cli::parse_from(["xzatoma", "acp", "config"]);
+
+        match cli.command {
+            Commands::Acp { command } => match command {
+                AcpCommand::Config => {}
+                other => panic!("expected ACP config command, got {:?}", other),
+            },
+            other => panic!("expected ACP command, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cli_parses_acp_runs_subcommand_with_filters() {
+        let cli = Cli::parse_from([
+            "xzatoma",
+            "acp",
+            "runs",
+            "--session-id",
+            "session_123",
+            "--limit",
+            "5",
+        ]);
+
+        match cli.command {
+            Commands::Acp { command } => match command {
+                AcpCommand::Runs { session_id, limit } => {
+                    assert_eq!(session_id.as_deref(), Some("session_123"));
+                    assert_eq!(limit, 5);
+                }
+                other => panic!("expected ACP runs command, got {:?}", other),
+            },
+            other => panic!("expected ACP command, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cli_parses_acp_validate_subcommand() {
+        let cli = Cli::parse_from([
+            "xzatoma",
+            "acp",
+            "validate",
+            "--manifest",
+            "docs/reference/acp_manifest.json",
+        ]);
+
+        match cli.command {
+            Commands::Acp { command } => match command {
+                AcpCommand::Validate { manifest } => {
+                    assert_eq!(
+                        manifest,
+                        Some(PathBuf::from("docs/reference/acp_manifest.json"))
+                    );
+                }
+                other => panic!("expected ACP validate command, got {:?}", other),
+            },
+            other => panic!("expected ACP command, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_chat_command() {
+        let cli = Cli::try_parse_from(["xzatoma", "chat"]);
+        assert!(cli.is_ok());
+        let cli = cli.unwrap();
+        assert!(matches!(cli.command, Commands::Chat { .. }));
+    }
+
+    #[test]
+    fn test_cli_parse_chat_with_provider() {
+        let cli = Cli::try_parse_from(["xzatoma", "chat", "--provider", "ollama"]);
+        assert!(cli.is_ok());
+        let cli = cli.unwrap();
+        if let Commands::Chat {
+            provider,
+            mode: _,
+            safe: _,
+            resume: _,
+            thinking_effort: _,
+            cwd: _,
+            allow_cwd_escape: _,
+            transcript: _,
+            session_id: _,
+            if_exists: _,
+            quiet: _,
+        } = cli.command
+        {
+            assert_eq!(provider, Some("ollama".to_string()));
+        } else {
+            panic!("Expected Chat command");
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_chat_with_resume() {
+        let cli = Cli::try_parse_from(["xzatoma", "chat", "--resume", "abc123"]);
+        assert!(cli.is_ok());
+        let cli = cli.unwrap();
+        if let Commands::Chat { resume, .. } = cli.command {
+            assert_eq!(resume, Some("abc123".to_string()));
+        } else {
+            panic!("Expected Chat command");
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_chat_with_cwd() {
+        let cli = Cli::try_parse_from([
+            "xzatoma",
+            "chat",
+            "--cwd",
+            "services/billing",
+            "--allow-cwd-escape",
+        ])
+        .unwrap();
+        if let Commands::Chat {
+            cwd,
+            allow_cwd_escape,
+            ..
+        } = cli.command
+        {
+            assert_eq!(cwd, Some(PathBuf::from("services/billing")));
+            assert!(allow_cwd_escape);
+        } else {
+            panic!("Expected Chat command");
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_run_with_cwd() {
+        let cli = Cli::try_parse_from([
+            "xzatoma",
+            "run",
+            "--prompt",
+            "hi",
+            "--cwd",
+            "services/billing",
+        ])
+        .unwrap();
+        if let Commands::Run { cwd, .. } = cli.command {
+            assert_eq!(cwd, Some(PathBuf::from("services/billing")));
+        } else {
+            panic!("Expected Run command");
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_replay_rerun_with_overrides() {
+        let cli = Cli::try_parse_from([
+            "xzatoma",
+            "replay",
+            "abc123",
+            "--provider",
+            "ollama",
+            "--model",
+            "llama3.2:latest",
+            "--diff",
+        ])
+        .unwrap();
+        if let Commands::Replay {
+            conversation_id,
+            provider,
+            model,
+            diff,
+            allow_tools,
+            id,
+            ..
+        } = cli.command
+        {
+            assert_eq!(conversation_id, Some("abc123".to_string()));
+            assert_eq!(provider, Some("ollama".to_string()));
+            assert_eq!(model, Some("llama3.2:latest".to_string()));
+            assert!(diff);
+            assert!(!allow_tools);
+            assert!(id.is_none());
+        } else {
+            panic!("Expected Replay command");
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_history_list() {
+        let cli = Cli::try_parse_from(["xzatoma", "history", "list"]);
+        assert!(cli.is_ok());
+        let cli = cli.unwrap();
+        if let Commands::History { command } = cli.command {
+            assert!(matches!(
+                command,
+                HistoryCommand::List {
+                    tags,
+                    utc: false,
+                    iso: false,
+                    limit: 25,
+                    offset: 0,
+                } if tags.is_empty()
+            ));
+        } else {
+            panic!("Expected History command");
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_history_delete() {
+        let cli = Cli::try_parse_from(["xzatoma", "history", "delete", "--id", "session123"]);
+        assert!(cli.is_ok());
+        let cli = cli.unwrap();
+        if let Commands::History { command } = cli.command {
+            if let HistoryCommand::Delete { id, yes, .. } = command {
+                assert_eq!(id, Some("session123".to_string()));
+                assert!(!yes);
+            } else {
+                panic!("Expected Delete command");
+            }
+        } else {
+            panic!("Expected History command");
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_history_list_with_multiple_tags() {
+        let cli = Cli::try_parse_from([
+            "xzatoma", "history", "list", "--tag", "billing", "--tag", "infra",
+        ])
+        .unwrap();
+
+        match cli.command {
+            Commands::History {
+                command: HistoryCommand::List { tags, .. },
+            } => assert_eq!(tags, vec!["billing".to_string(), "infra".to_string()]),
+            _ => panic!("Expected History List command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_history_list_timestamp_flags() {
+        let cli = Cli::try_parse_from(["xzatoma", "history", "list", "--utc", "--iso"]).unwrap();
+        match cli.command {
+            Commands::History {
+                command: HistoryCommand::List { utc, iso, .. },
+            } => assert!(utc && iso),
+            _ => panic!("Expected History List command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_history_list_pagination_and_pick() {
+        let cli = Cli::try_parse_from([
+            "xzatoma", "history", "list", "--limit", "10", "--offset", "20",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::History {
+                command: HistoryCommand::List { limit, offset, .. },
+            } => assert_eq!((limit, offset), (10, 20)),
+            _ => panic!("Expected History List command"),
+        }
+
+        let cli = Cli::try_parse_from(["xzatoma", "history", "pick", "--tag", "infra"]).unwrap();
+        match cli.command {
+            Commands::History {
+                command: HistoryCommand::Pick { tags },
+            } => assert_eq!(tags, vec!["infra".to_string()]),
+            _ => panic!("Expected History Pick command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_history_search_and_tag() {
+        let cli = Cli::try_parse_from(["xzatoma", "history", "search", "deploy", "--tag", "infra"])
+            .unwrap();
+        match cli.command {
+            Commands::History {
+                command: HistoryCommand::Search { query, tags, .. },
+            } => {
+                assert_eq!(query, "deploy");
+                assert_eq!(tags, vec!["infra".to_string()]);
+            }
+            _ => panic!("Expected History Search command"),
+        }
+
+        let cli =
+            Cli::try_parse_from(["xzatoma", "history", "tag", "--id", "abc123", "infra"]).unwrap();
+        match cli.command {
+            Commands::History {
+                command: HistoryCommand::Tag { id, tag },
+            } => {
+                assert_eq!(id, "abc123");
+                assert_eq!(tag, "infra");
+            }
+            _ => panic!("Expected History Tag command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_audit_list_with_session() {
+        let cli = Cli::try_parse_from(["xzatoma", "audit", "list"]).unwrap();
+        match cli.command {
+            Commands::Audit {
+                command: AuditCommand::List { session },
+            } => assert!(session.is_none()),
+            _ => panic!("Expected Audit List command"),
+        }
+
+        let cli = Cli::try_parse_from(["xzatoma", "audit", "list", "--session", "01JABC"]).unwrap();
+        match cli.command {
+            Commands::Audit {
+                command: AuditCommand::List { session },
+            } => assert_eq!(session.as_deref(), Some("01JABC")),
+            _ => panic!("Expected Audit List command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_tool_output_show() {
+        let cli = Cli::try_parse_from(["xzatoma", "tool-output", "show", "3f2a9c1e04b7"]).unwrap();
+        match cli.command {
+            Commands::ToolOutput {
+                command: ToolOutputCommand::Show { id },
+            } => assert_eq!(id, "3f2a9c1e04b7"),
+            other => panic!("Expected ToolOutput Show command, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_cache_commands_and_no_cache_flag() {
+        let cli = Cli::try_parse_from(["xzatoma", "cache", "stats"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Cache {
+                command: CacheCommand::Stats
+            }
+        ));
+        assert!(!cli.no_cache);
+
+        let cli = Cli::try_parse_from(["xzatoma", "cache", "clear"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Cache {
+                command: CacheCommand::Clear
+            }
+        ));
+
+        let cli = Cli::try_parse_from(["xzatoma", "run", "--prompt", "hi", "--no-cache"]).unwrap();
+        assert!(cli.no_cache);
+    }
+
+    #[test]
+    fn test_cli_parse_index_build() {
+        let cli = Cli::try_parse_from(["xzatoma", "index", "build"]).unwrap();
+        match cli.command {
+            Commands::Index {
+                command: IndexCommand::Build { path },
+            } => assert_eq!(path, PathBuf::from(".")),
+            _ => panic!("Expected Index Build command"),
+        }
+
+        let cli = Cli::try_parse_from(["xzatoma", "index", "build", "../other"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Index {
+                command: IndexCommand::Build { path },
+            } if path == PathBuf::from("../other")
+        ));
+    }
+
+    #[test]
+    fn test_cli_parse_paths() {
+        let cli = Cli::try_parse_from(["xzatoma", "paths"]).unwrap();
+        assert!(matches!(cli.command, Commands::Paths));
+    }
+
+    #[test]
+    fn test_cli_parse_doctor() {
+        let cli = Cli::try_parse_from(["xzatoma", "doctor"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Doctor {
+                json: false,
+                timeout: 10
+            }
+        ));
+
+        let cli = Cli::try_parse_from(["xzatoma", "doctor", "--json", "--timeout", "3"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Doctor {
+                json: true,
+                timeout: 3
+            }
+        ));
+    }
+
+    #[test]
+    fn test_cli_parse_trust_commands() {
+        let cli = Cli::try_parse_from(["xzatoma", "trust", "add"]).unwrap();
+        match cli.command {
+            Commands::Trust {
+                command: TrustCommand::Add { path },
+            } => assert_eq!(path, PathBuf::from(".")),
+            _ => panic!("Expected Trust Add command"),
+        }
+
+        let cli = Cli::try_parse_from(["xzatoma", "trust", "remove", "/tmp/project"]).unwrap();
+        match cli.command {
+            Commands::Trust {
+                command: TrustCommand::Remove { path },
+            } => assert_eq!(path, PathBuf::from("/tmp/project")),
+            _ => panic!("Expected Trust Remove command"),
+        }
+
+        let cli = Cli::try_parse_from(["xzatoma", "trust", "list"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Trust {
+                command: TrustCommand::List
+            }
+        ));
+    }
+
+    #[test]
+    fn test_cli_parse_plan_new_and_refine() {
+        let cli = Cli::try_parse_from(["xzatoma", "plan", "new"]).unwrap();
+        match cli.command {
+            Commands::Plan {
+                command:
+                    PlanCommand::New {
+                        goal,
+                        output,
+                        max_attempts,
+                        yes,
+                        ..
+                    },
+            } => {
+                assert!(goal.is_none());
+                assert!(output.is_none());
+                assert_eq!(max_attempts, 3);
+                assert!(!yes);
+            }
+            _ => panic!("Expected Plan New command"),
+        }
 
+        let cli = Cli::try_parse_from([
+            "xzatoma",
+            "plan",
+            "refine",
+            "plan.yaml",
+            "Add a test step",
+            "--max-attempts",
+            "5",
+            "-y",
+        ])
+        .unwrap();
         match cli.command {
-            Commands::Acp { command } => match command {
-                AcpCommand::Config => {}
-                other => panic!("expected ACP config command, got {:?}", other),
-            },
-            other => panic!("expected ACP command, got {:?}", other),
+            Commands::Plan {
+                command:
+                    PlanCommand::Refine {
+                        file,
+                        instruction,
+                        max_attempts,
+                        yes,
+                        ..
+                    },
+            } => {
+                assert_eq!(file, PathBuf::from("plan.yaml"));
+                assert_eq!(instruction, "Add a test step");
+                assert_eq!(max_attempts, 5);
+                assert!(yes);
+            }
+            _ => panic!("Expected Plan Refine command"),
         }
     }
 
     #[test]
-    fn test_cli_parses_acp_runs_subcommand_with_filters() {
-        let cli = Cli::parse_from([
+    fn test_cli_parse_history_delete_filters() {
+        let cli = Cli::try_parse_from([
             "xzatoma",
-            "acp",
-            "runs",
-            "--session-id",
-            "session_123",
-            "--limit",
-            "5",
-        ]);
-
+            "history",
+            "delete",
+            "--older-than",
+            "90d",
+            "--tag",
+            "scratch",
+            "--tag",
+            "tmp",
+            "--untitled",
+            "--model",
+            "gpt-4o",
+            "--yes",
+        ])
+        .unwrap();
         match cli.command {
-            Commands::Acp { command } => match command {
-                AcpCommand::Runs { session_id, limit } => {
-                    assert_eq!(session_id.as_deref(), Some("session_123"));
-                    assert_eq!(limit, 5);
-                }
-                other => panic!("expected ACP runs command, got {:?}", other),
-            },
-            other => panic!("expected ACP command, got {:?}", other),
+            Commands::History {
+                command:
+                    HistoryCommand::Delete {
+                        id,
+                        older_than,
+                        tags,
+                        untitled,
+                        model,
+                        yes,
+                    },
+            } => {
+                assert!(id.is_none());
+                assert_eq!(older_than.as_deref(), Some("90d"));
+                assert_eq!(tags, vec!["scratch".to_string(), "tmp".to_string()]);
+                assert!(untitled);
+                assert_eq!(model.as_deref(), Some("gpt-4o"));
+                assert!(yes);
+            }
+            _ => panic!("Expected History Delete command"),
         }
+
+        // Something must select what to delete, and an ID excludes filters
+        assert!(Cli::try_parse_from(["xzatoma", "history", "delete"]).is_err());
+        assert!(Cli::try_parse_from(["xzatoma", "history", "delete", "--yes"]).is_err());
+        assert!(
+            Cli::try_parse_from(["xzatoma", "history", "delete", "--id", "abc", "--untitled"])
+                .is_err()
+        );
     }
 
     #[test]
-    fn test_cli_parses_acp_validate_subcommand() {
-        let cli = Cli::parse_from([
-            "xzatoma",
-            "acp",
-            "validate",
-            "--manifest",
-            "docs/reference/acp_manifest.json",
-        ]);
+    fn test_cli_parse_history_backup_and_import() {
+        let cli = Cli::try_parse_from(["xzatoma", "history", "backup", "/tmp/backup"]).unwrap();
+        match cli.command {
+            Commands::History {
+                command: HistoryCommand::Backup { dir },
+            } => assert_eq!(dir, PathBuf::from("/tmp/backup")),
+            _ => panic!("Expected History Backup command"),
+        }
 
+        let cli = Cli::try_parse_from(["xzatoma", "history", "import", "/tmp/backup"]).unwrap();
         match cli.command {
-            Commands::Acp { command } => match command {
-                AcpCommand::Validate { manifest } => {
-                    assert_eq!(
-                        manifest,
-                        Some(PathBuf::from("docs/reference/acp_manifest.json"))
-                    );
-                }
-                other => panic!("expected ACP validate command, got {:?}", other),
-            },
-            other => panic!("expected ACP command, got {:?}", other),
+            Commands::History {
+                command:
+                    HistoryCommand::Import {
+                        path,
+                        format,
+                        dry_run,
+                    },
+            } => {
+                assert_eq!(path, PathBuf::from("/tmp/backup"));
+                assert_eq!(format, "native");
+                assert!(!dry_run);
+            }
+            _ => panic!("Expected History Import command"),
+        }
+
+        let cli = Cli::try_parse_from([
+            "xzatoma",
+            "history",
+            "import",
+            "--format",
+            "claude-json",
+            "--dry-run",
+            "conversations.json",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::History {
+                command:
+                    HistoryCommand::Import {
+                        path,
+                        format,
+                        dry_run,
+                    },
+            } => {
+                assert_eq!(path, PathBuf::from("conversations.json"));
+                assert_eq!(format, "claude-json");
+                assert!(dry_run);
+            }
+            _ => panic!("Expected History Import command"),
         }
     }
 
     #[test]
-    fn test_cli_parse_chat_command() {
-        let cli = Cli::try_parse_from(["xzatoma", "chat"]);
-        assert!(cli.is_ok());
-        let cli = cli.unwrap();
-        assert!(matches!(cli.command, Commands::Chat { .. }));
+    fn test_cli_parse_color() {
+        let cli = Cli::try_parse_from(["xzatoma", "history", "list"]).unwrap();
+        assert_eq!(cli.color, "auto");
+
+        let cli =
+            Cli::try_parse_from(["xzatoma", "history", "stats", "--json", "--color", "never"])
+                .unwrap();
+        assert_eq!(cli.color, "never");
+        assert!(cli.command.json_output());
     }
 
     #[test]
-    fn test_cli_parse_chat_with_provider() {
-        let cli = Cli::try_parse_from(["xzatoma", "chat", "--provider", "ollama"]);
-        assert!(cli.is_ok());
-        let cli = cli.unwrap();
-        if let Commands::Chat {
-            provider,
-            mode: _,
-            safe: _,
-            resume: _,
-            thinking_effort: _,
-        } = cli.command
-        {
-            assert_eq!(provider, Some("ollama".to_string()));
-        } else {
-            panic!("Expected Chat command");
-        }
+    fn test_cli_parse_raw_numbers() {
+        let cli = Cli::try_parse_from(["xzatoma", "history", "stats"]).unwrap();
+        assert!(!cli.raw_numbers);
+
+        let cli = Cli::try_parse_from(["xzatoma", "usage", "--raw-numbers"]).unwrap();
+        assert!(cli.raw_numbers);
     }
 
     #[test]
-    fn test_cli_parse_chat_with_resume() {
-        let cli = Cli::try_parse_from(["xzatoma", "chat", "--resume", "abc123"]);
-        assert!(cli.is_ok());
-        let cli = cli.unwrap();
-        if let Commands::Chat { resume, .. } = cli.command {
-            assert_eq!(resume, Some("abc123".to_string()));
-        } else {
-            panic!("Expected Chat command");
+    fn test_cli_parse_history_prune_dry_run() {
+        let cli = Cli::try_parse_from(["xzatoma", "history", "prune", "--dry-run"]).unwrap();
+
+        match cli.command {
+            Commands::History {
+                command: HistoryCommand::Prune { dry_run },
+            } => assert!(dry_run),
+            _ => panic!("Expected History Prune command"),
         }
     }
 
     #[test]
-    fn test_cli_parse_history_list() {
-        let cli = Cli::try_parse_from(["xzatoma", "history", "list"]);
-        assert!(cli.is_ok());
-        let cli = cli.unwrap();
-        if let Commands::History { command } = cli.command {
-            assert!(matches!(command, HistoryCommand::List));
-        } else {
-            panic!("Expected History command");
+    fn test_cli_parse_history_pin_and_unpin() {
+        let cli = Cli::try_parse_from(["xzatoma", "history", "pin", "--id", "abc123"]).unwrap();
+        match cli.command {
+            Commands::History {
+                command: HistoryCommand::Pin { id },
+            } => assert_eq!(id, "abc123"),
+            _ => panic!("Expected History Pin command"),
+        }
+
+        let cli = Cli::try_parse_from(["xzatoma", "history", "unpin", "--id", "abc123"]).unwrap();
+        match cli.command {
+            Commands::History {
+                command: HistoryCommand::Unpin { id },
+            } => assert_eq!(id, "abc123"),
+            _ => panic!("Expected History Unpin command"),
         }
     }
 
     #[test]
-    fn test_cli_parse_history_delete() {
-        let cli = Cli::try_parse_from(["xzatoma", "history", "delete", "--id", "session123"]);
-        assert!(cli.is_ok());
-        let cli = cli.unwrap();
-        if let Commands::History { command } = cli.command {
-            if let HistoryCommand::Delete { id } = command {
-                assert_eq!(id, "session123".to_string());
-            } else {
-                panic!("Expected Delete command");
+    fn test_cli_parse_history_stats() {
+        let cli = Cli::try_parse_from(["xzatoma", "history", "stats"]).unwrap();
+        match cli.command {
+            Commands::History {
+                command: HistoryCommand::Stats { since, limit, json },
+            } => {
+                assert!(since.is_none());
+                assert_eq!(limit, 10);
+                assert!(!json);
             }
-        } else {
-            panic!("Expected History command");
+            _ => panic!("Expected History Stats command"),
+        }
+
+        let cli = Cli::try_parse_from([
+            "xzatoma", "history", "stats", "--since", "30d", "-n", "5", "--json",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::History {
+                command: HistoryCommand::Stats { since, limit, json },
+            } => {
+                assert_eq!(since.as_deref(), Some("30d"));
+                assert_eq!(limit, 5);
+                assert!(json);
+            }
+            _ => panic!("Expected History Stats command"),
         }
     }
 
@@ -851,6 +2122,18 @@ mod tests {
             prompt,
             allow_dangerous,
             thinking_effort: _,
+            json: _,
+            validate_only: _,
+            dry_run: _,
+            cwd: _,
+            confirm_dangerous: _,
+            strict_budget: _,
+            record: _,
+            postmortem: _,
+            session_id: _,
+            if_exists: _,
+            snapshot: _,
+            command: _,
         } = cli.command
         {
             assert_eq!(plan, Some(PathBuf::from("test.yaml")));
@@ -871,6 +2154,18 @@ mod tests {
             prompt,
             allow_dangerous,
             thinking_effort: _,
+            json: _,
+            validate_only: _,
+            dry_run: _,
+            cwd: _,
+            confirm_dangerous: _,
+            strict_budget: _,
+            record: _,
+            postmortem: _,
+            session_id: _,
+            if_exists: _,
+            snapshot: _,
+            command: _,
         } = cli.command
         {
             assert_eq!(plan, None);
@@ -891,6 +2186,18 @@ mod tests {
             prompt,
             allow_dangerous,
             thinking_effort: _,
+            json: _,
+            validate_only: _,
+            dry_run: _,
+            cwd: _,
+            confirm_dangerous: _,
+            strict_budget: _,
+            record: _,
+            postmortem: _,
+            session_id: _,
+            if_exists: _,
+            snapshot: _,
+            command: _,
         } = cli.command
         {
             assert_eq!(plan, None);
@@ -1072,6 +2379,12 @@ mod tests {
             safe,
             resume: _,
             thinking_effort: _,
+            cwd: _,
+            allow_cwd_escape: _,
+            transcript: _,
+            session_id: _,
+            if_exists: _,
+            quiet: _,
         } = cli.command
         {
             assert_eq!(provider, None);
@@ -1093,6 +2406,12 @@ mod tests {
             safe: _,
             resume: _,
             thinking_effort: _,
+            cwd: _,
+            allow_cwd_escape: _,
+            transcript: _,
+            session_id: _,
+            if_exists: _,
+            quiet: _,
         } = cli.command
         {
             assert_eq!(mode, Some("write".to_string()));
@@ -1112,10 +2431,16 @@ mod tests {
             safe,
             resume: _,
             thinking_effort: _,
+            cwd: _,
+            allow_cwd_escape: _,
+            transcript: _,
+            session_id: _,
+            if_exists: _,
+            quiet: _,
         } = cli.command
         {
             assert!(safe);
-            assert_eq!(mode, Some("planning".to_string())); // default mode
+            assert_eq!(mode, None); // agent.chat.default_mode applies
         } else {
             panic!("Expected Chat command");
         }
@@ -1139,7 +2464,7 @@ mod tests {
         assert!(cli.is_ok());
         let cli = cli.unwrap();
         if let Commands::Chat { mode, safe, .. } = cli.command {
-            assert_eq!(mode, Some("planning".to_string())); // default is planning
+            assert_eq!(mode, None); // agent.chat.default_mode applies
             assert!(!safe); // default is no safety flag
         } else {
             panic!("Expected Chat command");
@@ -1165,6 +2490,12 @@ mod tests {
             safe,
             resume: _,
             thinking_effort: _,
+            cwd: _,
+            allow_cwd_escape: _,
+            transcript: _,
+            session_id: _,
+            if_exists: _,
+            quiet: _,
         } = cli.command
         {
             assert_eq!(provider, Some("ollama".to_string()));
@@ -1638,6 +2969,197 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cli_parse_run_json() {
+        let cli = Cli::try_parse_from(["xzatoma", "run", "--prompt", "hello", "--json"]).unwrap();
+        if let Commands::Run { json, .. } = cli.command {
+            assert!(json);
+        } else {
+            panic!("Expected Run command");
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_run_strict_budget() {
+        let cli = Cli::try_parse_from(["xzatoma", "run", "--prompt", "hello"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Run {
+                strict_budget: false,
+                ..
+            }
+        ));
+
+        let cli = Cli::try_parse_from(["xzatoma", "run", "--prompt", "hello", "--strict-budget"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Run {
+                strict_budget: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_cli_parse_run_validate_only_requires_plan() {
+        let cli =
+            Cli::try_parse_from(["xzatoma", "run", "--plan", "p.yaml", "--validate-only"]).unwrap();
+        if let Commands::Run { validate_only, .. } = cli.command {
+            assert!(validate_only);
+        } else {
+            panic!("Expected Run command");
+        }
+
+        assert!(Cli::try_parse_from(["xzatoma", "run", "--validate-only"]).is_err());
+        assert!(
+            Cli::try_parse_from(["xzatoma", "run", "--prompt", "hi", "--validate-only"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_cli_parse_run_dry_run_requires_plan() {
+        let cli = Cli::try_parse_from(["xzatoma", "run", "--plan", "p.yaml", "--dry-run"]).unwrap();
+        assert!(matches!(cli.command, Commands::Run { dry_run: true, .. }));
+
+        assert!(Cli::try_parse_from(["xzatoma", "run", "--dry-run"]).is_err());
+        assert!(Cli::try_parse_from([
+            "xzatoma",
+            "run",
+            "--plan",
+            "p.yaml",
+            "--dry-run",
+            "--validate-only"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_cli_parse_run_confirm_dangerous_requires_plan() {
+        let cli = Cli::try_parse_from([
+            "xzatoma",
+            "run",
+            "--plan",
+            "p.yaml",
+            "--allow-dangerous",
+            "--confirm-dangerous",
+            "0123456789abcdef",
+        ])
+        .unwrap();
+        if let Commands::Run {
+            confirm_dangerous, ..
+        } = cli.command
+        {
+            assert_eq!(confirm_dangerous, Some("0123456789abcdef".to_string()));
+        } else {
+            panic!("Expected Run command");
+        }
+
+        assert!(Cli::try_parse_from([
+            "xzatoma",
+            "run",
+            "--prompt",
+            "hi",
+            "--confirm-dangerous",
+            "0123456789abcdef"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_cli_parse_run_record_and_debug() {
+        let cli = Cli::try_parse_from(["xzatoma", "run", "--prompt", "hi"]).unwrap();
+        assert!(matches!(cli.command, Commands::Run { record: false, .. }));
+        let cli = Cli::try_parse_from(["xzatoma", "run", "--prompt", "hi", "--record"]).unwrap();
+        assert!(matches!(cli.command, Commands::Run { record: true, .. }));
+        assert!(Cli::try_parse_from([
+            "xzatoma",
+            "run",
+            "--plan",
+            "p.yaml",
+            "--validate-only",
+            "--record"
+        ])
+        .is_err());
+
+        let cli = Cli::try_parse_from(["xzatoma", "debug", "3f2a9c1e"]).unwrap();
+        match cli.command {
+            Commands::Debug { run_id } => assert_eq!(run_id, "3f2a9c1e"),
+            other => panic!("Expected Debug command, got {:?}", other),
+        }
+        assert!(Cli::try_parse_from(["xzatoma", "debug"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_parse_run_snapshot_and_rollback() {
+        let cli = Cli::try_parse_from(["xzatoma", "run", "--prompt", "hi", "--snapshot"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Run {
+                snapshot: true,
+                command: None,
+                ..
+            }
+        ));
+
+        let cli = Cli::try_parse_from(["xzatoma", "run", "rollback", "3f2a9c1e", "--yes"]).unwrap();
+        match cli.command {
+            Commands::Run {
+                command: Some(RunCommand::Rollback { run_id, yes }),
+                ..
+            } => {
+                assert_eq!(run_id, "3f2a9c1e");
+                assert!(yes);
+            }
+            other => panic!("Expected run rollback, got {:?}", other),
+        }
+        assert!(
+            Cli::try_parse_from(["xzatoma", "run", "--prompt", "hi", "rollback", "x"]).is_err()
+        );
+        assert!(Cli::try_parse_from(["xzatoma", "run", "rollback"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_parse_session_id_and_if_exists() {
+        let cli = Cli::try_parse_from([
+            "xzatoma",
+            "run",
+            "--prompt",
+            "hi",
+            "--session-id",
+            "nightly-lint",
+            "--if-exists",
+            "append",
+        ])
+        .unwrap();
+        if let Commands::Run {
+            session_id,
+            if_exists,
+            ..
+        } = cli.command
+        {
+            assert_eq!(session_id.as_deref(), Some("nightly-lint"));
+            assert_eq!(if_exists, Some(IfExists::Append));
+        } else {
+            panic!("Expected Run command");
+        }
+
+        assert!(Cli::try_parse_from([
+            "xzatoma",
+            "chat",
+            "--session-id",
+            "k",
+            "--if-exists",
+            "merge"
+        ])
+        .is_err());
+        assert!(Cli::try_parse_from(["xzatoma", "chat", "--if-exists", "fail"]).is_err());
+        assert!(
+            Cli::try_parse_from(["xzatoma", "chat", "--session-id", "k", "--resume", "abc"])
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_cli_parse_run_thinking_effort_defaults_none() {
         let cli = Cli::try_parse_from(["xzatoma", "run", "--prompt", "hello"]).unwrap();
@@ -32,7 +32,9 @@ use crate::acp::server::run_server;
 use crate::cli::AcpCommand;
 use crate::config::{AcpCompatibilityMode, Config};
 use crate::error::{Result, XzatomaError};
+use crate::paths::Paths;
 use crate::storage::SqliteStorage;
+use crate::{ui, ui_println};
 
 /// Handles ACP subcommands.
 ///
@@ -83,7 +85,7 @@ pub async fn handle_acp(command: AcpCommand, mut config: Config) -> Result<()> {
         }
         AcpCommand::Runs { session_id, limit } => {
             config.validate()?;
-            list_recent_runs(&session_id, limit)?;
+            list_recent_runs(&Paths::from_config(&config)?, &session_id, limit)?;
             Ok(())
         }
         AcpCommand::Validate { manifest } => {
@@ -170,8 +172,7 @@ pub fn apply_serve_overrides(
 /// # Ok::<(), anyhow::Error>(())
 /// ```
 pub fn print_effective_config(config: &Config) -> Result<()> {
-    let rendered = serde_json::to_string_pretty(&config.acp)?;
-    println!("{rendered}");
+    ui::json(&config.acp)?;
     Ok(())
 }
 
@@ -179,6 +180,7 @@ pub fn print_effective_config(config: &Config) -> Result<()> {
 ///
 /// # Arguments
 ///
+/// * `paths` - Resolved data directories holding the history database
 /// * `session_id` - Optional session filter
 /// * `limit` - Maximum number of rows to print
 ///
@@ -190,28 +192,33 @@ pub fn print_effective_config(config: &Config) -> Result<()> {
 ///
 /// ```
 /// use xzatoma::commands::acp::list_recent_runs;
+/// use xzatoma::paths::Paths;
 ///
-/// let _ = list_recent_runs(&None, 10);
+/// let _ = Paths::from_env().and_then(|paths| list_recent_runs(&paths, &None, 10));
 /// ```
-pub fn list_recent_runs(session_id: &Option<String>, limit: usize) -> Result<()> {
+pub fn list_recent_runs(paths: &Paths, session_id: &Option<String>, limit: usize) -> Result<()> {
     if limit == 0 {
         return Err(XzatomaError::Config(
             "ACP run listing limit must be greater than 0".to_string(),
         ));
     }
 
-    let storage = SqliteStorage::new()?;
+    let storage = SqliteStorage::new(paths)?;
     let runs = match session_id {
         Some(session) => storage.list_acp_runs_for_session(session)?,
         None => load_all_runs(&storage)?,
     };
 
-    println!("run_id\tsession_id\tstate\tcreated_at\tupdated_at");
+    ui_println!("run_id\tsession_id\tstate\tcreated_at\tupdated_at");
 
     for run in runs.into_iter().rev().take(limit) {
-        println!(
+        ui_println!(
             "{}\t{}\t{}\t{}\t{}",
-            run.run_id, run.session_id, run.state, run.created_at, run.updated_at
+            run.run_id,
+            run.session_id,
+            run.state,
+            run.created_at,
+            run.updated_at
         );
     }
 
@@ -248,12 +255,12 @@ pub fn validate_acp_manifest_and_config(
     if let Some(path) = manifest_path {
         let manifest = load_manifest(path)?;
         manifest.validate()?;
-        println!("ACP manifest validation succeeded: {}", path.display());
+        ui_println!("ACP manifest validation succeeded: {}", path.display());
     } else {
-        println!("ACP configuration validation succeeded");
+        ui_println!("ACP configuration validation succeeded");
     }
 
-    println!(
+    ui_println!(
         "ACP compatibility mode: {}",
         match config.acp.compatibility_mode {
             AcpCompatibilityMode::Versioned => "versioned",
@@ -328,9 +335,10 @@ pub fn load_manifest(path: &Path) -> Result<AcpAgentManifest> {
 ///
 /// ```
 /// use xzatoma::commands::acp::load_all_runs;
+/// use xzatoma::paths::Paths;
 /// use xzatoma::storage::SqliteStorage;
 ///
-/// let storage = SqliteStorage::new()?;
+/// let storage = SqliteStorage::new(&Paths::from_env()?)?;
 /// let _runs = load_all_runs(&storage)?;
 /// # Ok::<(), anyhow::Error>(())
 /// ```
@@ -0,0 +1,155 @@
+//! File mutation audit log commands
+//!
+//! Reads back the audit log written by the file-mutating tools and prints it
+//! as a table, optionally limited to one session.
+
+use std::path::Path;
+
+use colored::Colorize;
+use prettytable::{format, Table};
+
+use crate::cli::AuditCommand;
+use crate::config::Config;
+use crate::error::Result;
+use crate::paths::Paths;
+use crate::tools::audit_log::{AuditEntry, AuditLog};
+use crate::ui_println;
+
+/// Handle audit commands
+pub fn handle_audit(config: &Config, command: AuditCommand) -> Result<()> {
+    let path = AuditLog::resolve_path(&config.agent.tools, &Paths::from_config(config)?);
+    match command {
+        AuditCommand::List { session } => list_entries(&path, session.as_deref()),
+    }
+}
+
+/// Print the entries of the audit log at `path`
+fn list_entries(path: &Path, session: Option<&str>) -> Result<()> {
+    let entries = AuditLog::read_entries(path, session)?;
+
+    if entries.is_empty() {
+        let message = match session {
+            Some(session) => format!("No audit entries for session {}.", session),
+            None => format!("No audit entries in {}.", path.display()),
+        };
+        ui_println!("{}", message.yellow());
+        return Ok(());
+    }
+
+    ui_println!("\nAudit log: {}", path.display());
+    print_entries_table(&entries);
+    ui_println!("{} entr{}", entries.len(), plural_suffix(entries.len()));
+    ui_println!();
+    Ok(())
+}
+
+/// Print audit entries as a table
+fn print_entries_table(entries: &[AuditEntry]) {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+
+    table.add_row(prettytable::row![
+        "Time".bold(),
+        "Session".bold(),
+        "Tool".bold(),
+        "Operation".bold(),
+        "Path".bold(),
+        "Bytes".bold(),
+        "Hash (before -> after)".bold()
+    ]);
+
+    for entry in entries {
+        table.add_row(prettytable::row![
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+            entry.session_id.chars().take(8).collect::<String>().cyan(),
+            entry.tool,
+            entry.operation.as_str(),
+            format_paths(entry),
+            format_byte_delta(entry.byte_delta),
+            format!(
+                "{} -> {}",
+                short_hash(entry.hash_before.as_deref()),
+                short_hash(entry.hash_after.as_deref())
+            )
+        ]);
+    }
+
+    table.printstd();
+}
+
+/// Show the related path of moves and copies next to the mutated path
+fn format_paths(entry: &AuditEntry) -> String {
+    match (&entry.source, &entry.destination) {
+        (Some(source), _) => format!("{} (from {})", entry.path.display(), source.display()),
+        (_, Some(destination)) => format!("{} -> {}", entry.path.display(), destination.display()),
+        _ => entry.path.display().to_string(),
+    }
+}
+
+/// Format a byte delta with an explicit sign
+fn format_byte_delta(delta: i64) -> String {
+    if delta > 0 {
+        format!("+{}", delta)
+    } else {
+        delta.to_string()
+    }
+}
+
+/// Abbreviate a content hash, or `-` when there is none
+fn short_hash(hash: Option<&str>) -> String {
+    hash.map_or_else(|| "-".to_string(), |hash| hash.chars().take(12).collect())
+}
+
+fn plural_suffix(count: usize) -> &'static str {
+    if count == 1 {
+        "y"
+    } else {
+        "ies"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::audit_log::AuditOperation;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_list_entries_with_missing_log_succeeds() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("audit.jsonl");
+        assert!(list_entries(&path, None).is_ok());
+        assert!(list_entries(&path, Some("session")).is_ok());
+    }
+
+    #[test]
+    fn test_handle_audit_reads_configured_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("audit.jsonl");
+        let log = AuditLog::new(&path, "session-1");
+        log.append(
+            &log.entry("move_path", AuditOperation::Move, "/w/a.txt")
+                .with_destination("/w/b.txt"),
+        )
+        .unwrap();
+
+        let mut config = Config::default();
+        config.agent.tools.audit_log_path = Some(path.display().to_string());
+        let result = handle_audit(
+            &config,
+            AuditCommand::List {
+                session: Some("session-1".to_string()),
+            },
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_formatting_helpers() {
+        assert_eq!(format_byte_delta(12), "+12");
+        assert_eq!(format_byte_delta(-4), "-4");
+        assert_eq!(format_byte_delta(0), "0");
+        assert_eq!(short_hash(None), "-");
+        assert_eq!(short_hash(Some("0123456789abcdef")), "0123456789ab");
+    }
+}
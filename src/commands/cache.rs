@@ -0,0 +1,102 @@
+//! Provider response cache commands
+//!
+//! Shows the size of the provider response cache and clears it. See
+//! [`crate::providers::cache`] for how responses are cached.
+
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+
+use crate::cli::CacheCommand;
+use crate::config::Config;
+use crate::error::Result;
+use crate::format::format_bytes;
+use crate::paths::Paths;
+use crate::providers::cache::{CacheStats, ResponseCache};
+use crate::ui_println;
+
+/// Handle provider cache commands
+///
+/// # Arguments
+///
+/// * `config` - The loaded configuration; `provider.cache` selects the cache
+/// * `command` - The cache subcommand
+pub fn handle_cache(config: &Config, command: CacheCommand) -> Result<()> {
+    let cache = ResponseCache::from_config(&config.provider.cache, &Paths::from_config(config)?);
+    match command {
+        CacheCommand::Stats => {
+            let stats = cache.stats()?;
+            print_stats(&cache, &stats, config.provider.cache.enabled);
+            Ok(())
+        }
+        CacheCommand::Clear => {
+            let removed = cache.clear()?;
+            ui_println!(
+                "Removed {} cached response{} from {}",
+                removed,
+                if removed == 1 { "" } else { "s" },
+                cache.dir().display()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Print a cache summary
+fn print_stats(cache: &ResponseCache, stats: &CacheStats, enabled: bool) {
+    ui_println!("\nProvider cache: {}", cache.dir().display());
+    if !enabled {
+        ui_println!(
+            "{}",
+            "Caching is disabled. Set provider.cache.enabled to use it.".yellow()
+        );
+    }
+    ui_println!("  Entries: {} ({} expired)", stats.entries, stats.expired);
+    ui_println!(
+        "  Size:    {} of {}",
+        format_bytes(stats.size_bytes),
+        format_bytes(cache.max_size_bytes())
+    );
+    ui_println!("  Oldest:  {}", format_time(stats.oldest));
+    ui_println!("  Newest:  {}", format_time(stats.newest));
+    ui_println!();
+}
+
+fn format_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::cache::CacheEntry;
+    use crate::providers::{FinishReason, Message};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_stats_and_clear_use_configured_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.provider.cache.dir = Some(temp_dir.path().display().to_string());
+
+        let cache = ResponseCache::from_config(
+            &config.provider.cache,
+            &Paths::from_config(&config).unwrap(),
+        );
+        let entry = CacheEntry {
+            created_at: Utc::now(),
+            provider: "ollama".to_string(),
+            model: "llama3.2:latest".to_string(),
+            message: Message::assistant("cached"),
+            usage: None,
+            response_model: None,
+            reasoning: None,
+            finish_reason: FinishReason::Stop,
+        };
+        cache.put("entry", &entry).unwrap();
+
+        assert!(handle_cache(&config, CacheCommand::Stats).is_ok());
+        assert!(handle_cache(&config, CacheCommand::Clear).is_ok());
+        assert_eq!(cache.stats().unwrap().entries, 0);
+    }
+}
@@ -0,0 +1,388 @@
+//! Startup banner for chat sessions
+//!
+//! The banner says which environment a session talks to before the first
+//! prompt: the provider and model, the mode and safety setting, the working
+//! directory with its git branch and changed files, how many MCP tools are
+//! registered, and the last stored conversation to resume.
+//! `agent.chat.banner` selects the `full` banner, a `minimal` line, or `off`,
+//! and `xzatoma chat --quiet` turns it off.
+//!
+//! The git status and the stored conversation are looked up concurrently by
+//! [`gather_startup_facts`]. Each lookup gets [`STARTUP_BUDGET`]; one that
+//! takes longer is left out of the banner instead of delaying the prompt.
+//! Rendering is separate, in [`render_banner`], so every level can be tested
+//! without a terminal, a repository, or storage.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use colored::Colorize;
+
+use crate::chat_mode::{ChatMode, ChatModeState, SafetyMode};
+use crate::config::ChatBanner;
+use crate::storage::SqliteStorage;
+use crate::ui;
+
+/// Longest time a startup lookup may take before it is left out
+pub const STARTUP_BUDGET: Duration = Duration::from_millis(300);
+
+/// Git state of the working directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitSummary {
+    /// Checked-out branch, or `None` for a detached HEAD
+    pub branch: Option<String>,
+    /// Number of modified, staged, or untracked files
+    pub changed_files: usize,
+}
+
+/// Stored conversation that `xzatoma chat --resume` would continue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumableSession {
+    /// Conversation ID
+    pub id: String,
+    /// Conversation title
+    pub title: String,
+    /// Number of stored messages
+    pub message_count: usize,
+}
+
+/// Results of the startup lookups; a lookup that failed or ran late is `None`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StartupFacts {
+    /// Git state of the working directory
+    pub git: Option<GitSummary>,
+    /// Most recently updated stored conversation
+    pub last_session: Option<ResumableSession>,
+}
+
+/// Everything the banner shows
+#[derive(Debug, Clone)]
+pub struct BannerContext {
+    /// Provider type, such as `copilot`
+    pub provider: String,
+    /// Active model
+    pub model: String,
+    /// Chat mode the session starts in
+    pub chat_mode: ChatMode,
+    /// Safety mode the session starts in
+    pub safety_mode: SafetyMode,
+    /// Whether the session is read-only
+    pub read_only: bool,
+    /// Session working directory
+    pub working_dir: PathBuf,
+    /// Number of registered MCP tools
+    pub mcp_tools: usize,
+    /// Results of the startup lookups
+    pub facts: StartupFacts,
+}
+
+impl BannerContext {
+    /// Creates a context without MCP tools or startup facts
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::chat_mode::{ChatMode, ChatModeState, SafetyMode};
+    /// use xzatoma::commands::chat_banner::{render_banner, BannerContext};
+    /// use xzatoma::config::ChatBanner;
+    ///
+    /// let state = ChatModeState::new(ChatMode::Planning, SafetyMode::AlwaysConfirm);
+    /// let context = BannerContext::new("ollama", "llama3.2", &state, "/work/app");
+    /// assert!(render_banner(ChatBanner::Minimal, &context).contains("ollama/llama3.2"));
+    /// assert!(render_banner(ChatBanner::Off, &context).is_empty());
+    /// ```
+    pub fn new(
+        provider: impl Into<String>,
+        model: impl Into<String>,
+        mode_state: &ChatModeState,
+        working_dir: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            provider: provider.into(),
+            model: model.into(),
+            chat_mode: mode_state.chat_mode,
+            safety_mode: mode_state.safety_mode,
+            read_only: mode_state.read_only,
+            working_dir: working_dir.into(),
+            mcp_tools: 0,
+            facts: StartupFacts::default(),
+        }
+    }
+
+    /// Returns the working directory with its git branch and changes
+    fn directory(&self) -> String {
+        let dir = self.working_dir.display().to_string();
+        match &self.facts.git {
+            Some(git) => {
+                let branch = git.branch.as_deref().unwrap_or("detached HEAD");
+                let changes = match git.changed_files {
+                    0 => "clean".to_string(),
+                    1 => "1 changed file".to_string(),
+                    n => format!("{} changed files", n),
+                };
+                format!("{} ({}, {})", dir, branch.cyan(), changes)
+            }
+            None => dir,
+        }
+    }
+}
+
+/// Renders the startup banner at `level`
+///
+/// `Off` renders an empty string. `Minimal` is one line with the model,
+/// mode, safety, and directory. `Full` adds MCP tools and the conversation
+/// to resume, one fact per line. Facts that were not found are left out.
+pub fn render_banner(level: ChatBanner, context: &BannerContext) -> String {
+    let read_only = if context.read_only {
+        format!(
+            "{} No tool can change files or run commands in this session.\n\n",
+            "[READ-ONLY]".red().bold()
+        )
+    } else {
+        String::new()
+    };
+
+    match level {
+        ChatBanner::Off => String::new(),
+        ChatBanner::Minimal => format!(
+            "{}/{} {}{} {}\n{}Type '/help' for available commands, 'exit' to quit\n\n",
+            context.provider,
+            context.model,
+            context.chat_mode.colored_tag(),
+            context.safety_mode.colored_tag(),
+            context.directory(),
+            read_only
+        ),
+        ChatBanner::Full => {
+            let mut lines = vec![
+                format!(
+                    "\n{}\n",
+                    ui::banner("XZatoma Interactive Chat Mode - Welcome!", 64)
+                ),
+                format!("Model:     {}/{}", context.provider, context.model),
+                format!(
+                    "Mode:      {} ({})",
+                    context.chat_mode.colored_tag(),
+                    context.chat_mode.description()
+                ),
+                format!(
+                    "Safety:    {} ({})",
+                    context.safety_mode.colored_tag(),
+                    context.safety_mode.description()
+                ),
+                format!("Directory: {}", context.directory()),
+                format!("MCP tools: {}", context.mcp_tools),
+            ];
+            if let Some(session) = &context.facts.last_session {
+                let short_id: String = session.id.chars().take(8).collect();
+                lines.push(format!(
+                    "Resume:    \"{}\" ({} messages) with {}",
+                    session.title,
+                    session.message_count,
+                    format!("xzatoma chat --resume {}", short_id).dimmed()
+                ));
+            }
+            format!(
+                "{}\n\n{}Type '/help' for available commands, 'exit' to quit\n\n",
+                lines.join("\n"),
+                read_only
+            )
+        }
+    }
+}
+
+/// Looks up the git state and the last stored conversation concurrently
+///
+/// Each lookup is abandoned after `budget`, so this returns within `budget`
+/// however slow git or storage are.
+///
+/// # Arguments
+///
+/// * `working_dir` - Directory whose git state is shown
+/// * `storage` - Conversation storage, or `None` to skip the lookup
+/// * `budget` - Longest time each lookup may take
+pub async fn gather_startup_facts(
+    working_dir: PathBuf,
+    storage: Option<SqliteStorage>,
+    budget: Duration,
+) -> StartupFacts {
+    let git = tokio::time::timeout(budget, git_summary(&working_dir));
+    let last_session = tokio::time::timeout(budget, last_session(storage));
+    let (git, last_session) = tokio::join!(git, last_session);
+    StartupFacts {
+        git: git.ok().flatten(),
+        last_session: last_session.ok().flatten(),
+    }
+}
+
+/// Runs `git status` in `dir`, returning `None` outside a repository
+///
+/// The fsmonitor hook is disabled, so a repository's configuration cannot
+/// run a command when the banner is shown, and no optional locks are taken.
+async fn git_summary(dir: &Path) -> Option<GitSummary> {
+    let output = tokio::process::Command::new("git")
+        .args([
+            "--no-optional-locks",
+            "-c",
+            "core.fsmonitor=false",
+            "status",
+            "--porcelain=v1",
+            "--branch",
+        ])
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_git_status(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses `git status --porcelain=v1 --branch` output
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::commands::chat_banner::parse_git_status;
+///
+/// let git = parse_git_status("## main...origin/main [ahead 1]\n M src/lib.rs\n?? notes.md\n");
+/// assert_eq!(git.branch.as_deref(), Some("main"));
+/// assert_eq!(git.changed_files, 2);
+/// ```
+pub fn parse_git_status(output: &str) -> GitSummary {
+    let mut lines = output.lines();
+    let branch = lines
+        .next()
+        .and_then(|header| header.strip_prefix("## "))
+        .and_then(|header| {
+            let header = header
+                .strip_prefix("No commits yet on ")
+                .or_else(|| header.strip_prefix("Initial commit on "))
+                .unwrap_or(header);
+            let name = header.split("...").next()?.split(' ').next()?;
+            (name != "HEAD").then(|| name.to_string())
+        });
+    GitSummary {
+        branch,
+        changed_files: lines.filter(|line| !line.is_empty()).count(),
+    }
+}
+
+/// Returns the most recently updated stored conversation
+async fn last_session(storage: Option<SqliteStorage>) -> Option<ResumableSession> {
+    let storage = storage?;
+    let page = tokio::task::spawn_blocking(move || storage.list_sessions_page(&[], 1, 0))
+        .await
+        .ok()?
+        .map_err(|e| tracing::debug!("Could not look up the last conversation: {}", e))
+        .ok()?;
+    page.sessions
+        .into_iter()
+        .next()
+        .map(|session| ResumableSession {
+            id: session.id,
+            title: session.title,
+            message_count: session.message_count,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> BannerContext {
+        let state = ChatModeState::new(ChatMode::Write, SafetyMode::AlwaysConfirm);
+        let mut context = BannerContext::new("copilot", "gpt-5-mini", &state, "/work/billing");
+        context.mcp_tools = 12;
+        context.facts = StartupFacts {
+            git: Some(GitSummary {
+                branch: Some("main".to_string()),
+                changed_files: 3,
+            }),
+            last_session: Some(ResumableSession {
+                id: "1a2b3c4d-0000-4000-8000-000000000000".to_string(),
+                title: "Fix flaky test".to_string(),
+                message_count: 24,
+            }),
+        };
+        context
+    }
+
+    #[test]
+    fn test_full_banner_shows_every_fact() {
+        let banner = render_banner(ChatBanner::Full, &context());
+
+        assert!(banner.contains("XZatoma Interactive Chat Mode"));
+        assert!(banner.contains("Model:     copilot/gpt-5-mini"));
+        assert!(banner.contains("WRITE"));
+        assert!(banner.contains("SAFE"));
+        assert!(banner.contains("Directory: /work/billing ("));
+        assert!(banner.contains("main"));
+        assert!(banner.contains(", 3 changed files)"));
+        assert!(banner.contains("MCP tools: 12"));
+        assert!(banner.contains("\"Fix flaky test\" (24 messages)"));
+        assert!(banner.contains("xzatoma chat --resume 1a2b3c4d"));
+        assert!(!banner.contains("READ-ONLY"));
+    }
+
+    #[test]
+    fn test_minimal_banner_is_one_line_of_facts() {
+        let mut context = context();
+        context.read_only = true;
+
+        let banner = render_banner(ChatBanner::Minimal, &context);
+
+        let first = banner.lines().next().unwrap();
+        assert!(first.starts_with("copilot/gpt-5-mini "));
+        assert!(first.contains("/work/billing ("));
+        assert!(!banner.contains("MCP tools"));
+        assert!(!banner.contains("--resume"));
+        assert!(banner.contains("READ-ONLY"));
+        assert!(banner.contains("Type '/help'"));
+    }
+
+    #[test]
+    fn test_off_renders_nothing() {
+        assert_eq!(render_banner(ChatBanner::Off, &context()), "");
+    }
+
+    #[test]
+    fn test_missing_git_and_storage_are_left_out() {
+        let mut context = context();
+        context.facts = StartupFacts::default();
+
+        let banner = render_banner(ChatBanner::Full, &context);
+
+        assert!(banner.contains("Directory: /work/billing\n"));
+        assert!(!banner.contains("changed file"));
+        assert!(!banner.contains("Resume:"));
+    }
+
+    #[test]
+    fn test_parse_git_status_headers() {
+        let clean = parse_git_status("## feature/banner\n");
+        assert_eq!(clean.branch.as_deref(), Some("feature/banner"));
+        assert_eq!(clean.changed_files, 0);
+
+        let detached = parse_git_status("## HEAD (no branch)\nM  src/main.rs\n");
+        assert_eq!(detached.branch, None);
+        assert_eq!(detached.changed_files, 1);
+
+        let empty = parse_git_status("## No commits yet on trunk\n?? README.md\n");
+        assert_eq!(empty.branch.as_deref(), Some("trunk"));
+    }
+
+    #[tokio::test]
+    async fn test_gather_outside_a_repository_finds_no_git() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let facts = gather_startup_facts(dir.path().to_path_buf(), None, STARTUP_BUDGET).await;
+
+        assert_eq!(facts.git, None);
+        assert_eq!(facts.last_session, None);
+    }
+}
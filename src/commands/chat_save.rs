@@ -0,0 +1,468 @@
+//! Saving chat conversations that another session may also save
+//!
+//! Two terminals can resume the same conversation. Chat saves after every
+//! turn, so with plain saves the session that saves last silently drops the
+//! other session's messages. Chat therefore saves with the revision it last
+//! loaded or saved ([`ExpectedRevision::Exactly`]). When another session
+//! saved in between, the save fails with a [`ConversationConflict`] and the
+//! user chooses how to resolve it:
+//!
+//! - merge: reload the stored conversation and append the messages this
+//!   session added since its last save;
+//! - fork: save this session's conversation under a new ID, leaving the
+//!   stored one alone;
+//! - skip: save nothing now; the next turn's save hits the conflict again.
+//!
+//! The resolution logic lives in [`save_chat_conversation`] and takes the
+//! choice as a callback, so it can be tested without a terminal.
+
+use std::io::{BufRead, Write};
+
+use uuid::Uuid;
+
+use crate::agent::Conversation;
+use crate::error::{Result, XzatomaError};
+use crate::storage::types::{ConversationConflict, ExpectedRevision};
+use crate::storage::SqliteStorage;
+
+/// What a chat session last stored for its conversation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChatSaveState {
+    /// Revision the session last loaded or saved; `0` when it never did
+    pub revision: u64,
+    /// Number of messages that revision holds
+    pub saved_messages: usize,
+}
+
+impl ChatSaveState {
+    /// Creates the state of a session whose first `saved_messages` messages
+    /// are stored at `revision`
+    pub fn new(revision: u64, saved_messages: usize) -> Self {
+        Self {
+            revision,
+            saved_messages,
+        }
+    }
+
+    /// Number of messages the session added since its last save
+    pub fn unsaved(&self, conversation: &Conversation) -> usize {
+        conversation.len().saturating_sub(self.saved_messages)
+    }
+}
+
+/// How to resolve a save that another session got to first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// Append this session's unsaved messages to the stored conversation
+    Merge,
+    /// Save this session's conversation under a new ID
+    Fork,
+    /// Leave the stored conversation alone for now
+    Skip,
+}
+
+/// What [`save_chat_conversation`] did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatSaveOutcome {
+    /// The conversation was saved
+    Saved,
+    /// The stored conversation was reloaded and saved with this session's
+    /// unsaved messages appended
+    Merged {
+        /// Number of messages appended
+        appended: usize,
+    },
+    /// The conversation was saved under a new ID
+    Forked {
+        /// ID the conversation had before the fork
+        from: String,
+    },
+    /// Nothing was saved
+    Skipped,
+}
+
+/// Saves `conversation` unless another session saved it since `state`
+///
+/// On success `state` records the new revision. On a conflict `resolve` is
+/// called with the conflict and the number of unsaved messages, and the
+/// save is retried after a merge or a fork. Pinned messages and message
+/// directories are saved along with the messages.
+///
+/// # Arguments
+///
+/// * `storage` - Conversation storage
+/// * `conversation` - The session's conversation; merged or re-identified
+///   in place when a conflict is resolved
+/// * `model` - Model name recorded with the conversation
+/// * `state` - Revision and length of the session's last save
+/// * `resolve` - Chooses how to resolve a conflict
+///
+/// # Errors
+///
+/// Returns storage errors. Conflicts are resolved through `resolve`, never
+/// returned.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::agent::Conversation;
+/// use xzatoma::commands::chat_save::{
+///     save_chat_conversation, ChatSaveOutcome, ChatSaveState, ConflictResolution,
+/// };
+/// use xzatoma::storage::SqliteStorage;
+///
+/// let storage = SqliteStorage::new_in_memory()?;
+/// let mut conversation = Conversation::new(8000, 10, 0.8);
+/// conversation.add_user_message("hello");
+/// let mut state = ChatSaveState::default();
+///
+/// let outcome = save_chat_conversation(
+///     &storage,
+///     &mut conversation,
+///     None,
+///     &mut state,
+///     &mut |_, _| ConflictResolution::Skip,
+/// )?;
+/// assert_eq!(outcome, ChatSaveOutcome::Saved);
+/// assert_eq!(state, ChatSaveState::new(1, 1));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn save_chat_conversation(
+    storage: &SqliteStorage,
+    conversation: &mut Conversation,
+    model: Option<&str>,
+    state: &mut ChatSaveState,
+    resolve: &mut dyn FnMut(&ConversationConflict, usize) -> ConflictResolution,
+) -> Result<ChatSaveOutcome> {
+    let mut outcome = ChatSaveOutcome::Saved;
+    loop {
+        let id = conversation.id().to_string();
+        let saved = storage.save_conversation_checked(
+            &id,
+            conversation.title(),
+            model,
+            conversation.messages(),
+            ExpectedRevision::Exactly(state.revision),
+        );
+        let conflict = match saved {
+            Ok(revision) => {
+                *state = ChatSaveState::new(revision, conversation.len());
+                storage.set_pinned_messages(&id, &conversation.pinned_indices())?;
+                storage.set_message_cwds(&id, conversation.message_cwds())?;
+                return Ok(outcome);
+            }
+            Err(XzatomaError::ConversationConflict(conflict)) => conflict,
+            Err(e) => return Err(e),
+        };
+
+        tracing::warn!(
+            id = %conflict.id,
+            expected = conflict.expected_revision,
+            stored = conflict.stored_revision,
+            "Conversation was saved by another session"
+        );
+        match resolve(&conflict, state.unsaved(conversation)) {
+            ConflictResolution::Merge => {
+                let appended = state.unsaved(conversation);
+                merge_stored(storage, conversation, state)?;
+                outcome = ChatSaveOutcome::Merged { appended };
+            }
+            ConflictResolution::Fork => {
+                conversation.set_id(Uuid::new_v4());
+                *state = ChatSaveState::default();
+                outcome = ChatSaveOutcome::Forked { from: id };
+            }
+            ConflictResolution::Skip => return Ok(ChatSaveOutcome::Skipped),
+        }
+    }
+}
+
+/// Replaces the saved part of `conversation` with what is stored now
+fn merge_stored(
+    storage: &SqliteStorage,
+    conversation: &mut Conversation,
+    state: &mut ChatSaveState,
+) -> Result<()> {
+    let id = conversation.id().to_string();
+    // Read the revision before the messages: a save that lands in between
+    // then shows up as another conflict instead of being overwritten
+    let revision = storage.conversation_revision(&id)?.unwrap_or(0);
+    let (stored, pins, mut cwds) = match storage.load_conversation(&id)? {
+        Some((_, _, messages)) => (
+            messages,
+            storage.load_pinned_messages(&id)?,
+            storage.load_message_cwds(&id)?,
+        ),
+        None => (Vec::new(), Vec::new(), Vec::new()),
+    };
+
+    let stored_len = stored.len();
+    cwds.resize(stored_len, None);
+    cwds.extend(
+        conversation
+            .message_cwds()
+            .iter()
+            .skip(state.saved_messages)
+            .cloned(),
+    );
+    conversation.rebase(stored, state.saved_messages);
+    conversation.set_message_cwds(cwds);
+    for index in pins {
+        if let Err(e) = conversation.pin(index) {
+            tracing::debug!("Skipping stored pin {}: {}", index, e);
+        }
+    }
+    *state = ChatSaveState::new(revision, stored_len);
+    Ok(())
+}
+
+/// Returns the stored revision of the conversation `id` names
+///
+/// `id` may be a full ID, a prefix, or a session key. Returns `0` when the
+/// conversation is not stored or the lookup fails; a session that starts
+/// from `0` meets a conflict on its first save if the conversation does
+/// exist, so a failed lookup never leads to an overwrite.
+pub fn stored_revision(storage: &SqliteStorage, id: &str) -> u64 {
+    let revision = storage
+        .resolve_conversation_id(id)
+        .and_then(|resolved| match resolved {
+            Some(resolved) => storage.conversation_revision(&resolved),
+            None => Ok(None),
+        });
+    match revision {
+        Ok(revision) => revision.unwrap_or(0),
+        Err(e) => {
+            tracing::warn!("Failed to read conversation revision: {}", e);
+            0
+        }
+    }
+}
+
+/// Asks how to resolve a conflict, reading the answer from `input`
+///
+/// An empty answer or end of input skips the save.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::commands::chat_save::{ask_resolution, ConflictResolution};
+/// use xzatoma::storage::types::ConversationConflict;
+///
+/// let conflict = ConversationConflict {
+///     id: "c1".to_string(),
+///     expected_revision: 1,
+///     stored_revision: 2,
+///     stored_updated_at: None,
+/// };
+/// let mut output = Vec::new();
+/// let choice = ask_resolution(&conflict, 3, &mut "m\n".as_bytes(), &mut output);
+/// assert_eq!(choice, ConflictResolution::Merge);
+/// ```
+pub fn ask_resolution(
+    conflict: &ConversationConflict,
+    unsaved: usize,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> ConflictResolution {
+    let _ = writeln!(
+        output,
+        "Another session saved this conversation: {}.",
+        conflict
+    );
+    loop {
+        let _ = write!(
+            output,
+            "[m]erge (reload it and append this session's {} unsaved message{}), [f]ork (save under a new ID), [s]kip: ",
+            unsaved,
+            if unsaved == 1 { "" } else { "s" }
+        );
+        let _ = output.flush();
+
+        let mut line = String::new();
+        match input.read_line(&mut line) {
+            Ok(0) | Err(_) => return ConflictResolution::Skip,
+            Ok(_) => {}
+        }
+        match line.trim().to_lowercase().as_str() {
+            "m" | "merge" => return ConflictResolution::Merge,
+            "f" | "fork" => return ConflictResolution::Fork,
+            "" | "s" | "skip" => return ConflictResolution::Skip,
+            other => {
+                let _ = writeln!(output, "Unknown answer '{}'.", other);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::Message;
+
+    fn contents(messages: &[Message]) -> Vec<&str> {
+        messages
+            .iter()
+            .filter_map(|message| message.content.as_deref())
+            .collect()
+    }
+
+    /// Two sessions that resumed the same stored conversation
+    fn two_sessions(
+        storage: &SqliteStorage,
+    ) -> (Conversation, ChatSaveState, Conversation, ChatSaveState) {
+        let mut first = Conversation::new(8000, 10, 0.8);
+        first.add_user_message("shared start");
+        let mut state = ChatSaveState::default();
+        save_chat_conversation(storage, &mut first, None, &mut state, &mut |_, _| {
+            panic!("no conflict expected")
+        })
+        .unwrap();
+        let second = first.clone();
+        (first, state, second, state)
+    }
+
+    #[test]
+    fn test_merge_appends_unsaved_messages_after_the_other_session() {
+        let storage = SqliteStorage::new_in_memory().unwrap();
+        let (mut first, mut first_state, mut second, mut second_state) = two_sessions(&storage);
+
+        first.add_user_message("first session");
+        second.add_user_message("second session");
+        second.add_assistant_message("second reply");
+        second.pin(2).unwrap();
+
+        save_chat_conversation(&storage, &mut first, None, &mut first_state, &mut |_, _| {
+            panic!("the first save should not conflict")
+        })
+        .unwrap();
+        assert_eq!(first_state, ChatSaveState::new(2, 2));
+
+        let mut asked = Vec::new();
+        let outcome = save_chat_conversation(
+            &storage,
+            &mut second,
+            None,
+            &mut second_state,
+            &mut |conflict, unsaved| {
+                asked.push((
+                    conflict.expected_revision,
+                    conflict.stored_revision,
+                    unsaved,
+                ));
+                ConflictResolution::Merge
+            },
+        )
+        .unwrap();
+
+        assert_eq!(asked, [(1, 2, 2)]);
+        assert_eq!(outcome, ChatSaveOutcome::Merged { appended: 2 });
+        assert_eq!(second_state, ChatSaveState::new(3, 4));
+        let (_, _, stored) = storage
+            .load_conversation(&second.id().to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            contents(&stored),
+            [
+                "shared start",
+                "first session",
+                "second session",
+                "second reply"
+            ]
+        );
+        assert_eq!(contents(second.messages()), contents(&stored));
+        assert_eq!(second.pinned_indices(), [3]);
+        assert_eq!(
+            storage
+                .load_pinned_messages(&second.id().to_string())
+                .unwrap(),
+            [3]
+        );
+    }
+
+    #[test]
+    fn test_fork_saves_under_a_new_id_and_keeps_the_stored_conversation() {
+        let storage = SqliteStorage::new_in_memory().unwrap();
+        let (mut first, mut first_state, mut second, mut second_state) = two_sessions(&storage);
+        let original_id = first.id().to_string();
+
+        first.add_user_message("first session");
+        save_chat_conversation(&storage, &mut first, None, &mut first_state, &mut |_, _| {
+            panic!("the first save should not conflict")
+        })
+        .unwrap();
+        second.add_user_message("second session");
+        let outcome = save_chat_conversation(
+            &storage,
+            &mut second,
+            None,
+            &mut second_state,
+            &mut |_, _| ConflictResolution::Fork,
+        )
+        .unwrap();
+
+        assert_eq!(
+            outcome,
+            ChatSaveOutcome::Forked {
+                from: original_id.clone()
+            }
+        );
+        assert_ne!(second.id().to_string(), original_id);
+        assert_eq!(second_state, ChatSaveState::new(1, 2));
+        let (_, _, stored) = storage.load_conversation(&original_id).unwrap().unwrap();
+        assert_eq!(contents(&stored), ["shared start", "first session"]);
+        let (_, _, forked) = storage
+            .load_conversation(&second.id().to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(contents(&forked), ["shared start", "second session"]);
+    }
+
+    #[test]
+    fn test_skip_leaves_the_conflict_for_the_next_save() {
+        let storage = SqliteStorage::new_in_memory().unwrap();
+        let (mut first, mut first_state, mut second, mut second_state) = two_sessions(&storage);
+
+        first.add_user_message("first session");
+        save_chat_conversation(&storage, &mut first, None, &mut first_state, &mut |_, _| {
+            panic!("the first save should not conflict")
+        })
+        .unwrap();
+        second.add_user_message("second session");
+        let outcome = save_chat_conversation(
+            &storage,
+            &mut second,
+            None,
+            &mut second_state,
+            &mut |_, _| ConflictResolution::Skip,
+        )
+        .unwrap();
+
+        assert_eq!(outcome, ChatSaveOutcome::Skipped);
+        assert_eq!(second_state, ChatSaveState::new(1, 1));
+        let (_, _, stored) = storage
+            .load_conversation(&first.id().to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(contents(&stored), ["shared start", "first session"]);
+    }
+
+    #[test]
+    fn test_ask_resolution_reprompts_and_skips_at_end_of_input() {
+        let conflict = ConversationConflict {
+            id: "c1".to_string(),
+            expected_revision: 1,
+            stored_revision: 2,
+            stored_updated_at: None,
+        };
+        let mut output = Vec::new();
+        let choice = ask_resolution(&conflict, 1, &mut "x\nfork\n".as_bytes(), &mut output);
+        assert_eq!(choice, ConflictResolution::Fork);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Unknown answer 'x'."));
+        assert!(output.contains("append this session's 1 unsaved message)"));
+
+        let choice = ask_resolution(&conflict, 1, &mut "".as_bytes(), &mut Vec::new());
+        assert_eq!(choice, ConflictResolution::Skip);
+    }
+}
@@ -0,0 +1,346 @@
+//! Quick actions for code blocks in chat responses
+//!
+//! After a response that contains code, chat lists its code blocks and
+//! offers three commands:
+//!
+//! - `/blocks` lists the blocks of the last response with their language
+//!   and line count;
+//! - `/copy N` copies block N to the clipboard, or prints it plainly when no
+//!   clipboard is available ([`SessionClipboard`]);
+//! - `/apply N <path>` writes block N to a file through the session's
+//!   `write_file` tool, after the same change review the agent's own writes
+//!   get ([`apply_block`]).
+//!
+//! Blocks are found by [`extract_code_blocks`]. When the sentence before a
+//! block names its file, as in "Create `src/foo.rs`:", `/blocks` shows that
+//! path so it can be passed to `/apply`.
+
+use crate::agent::change_review::{rejected_change_result, ChangeReview};
+use crate::error::{Result, XzatomaError};
+use crate::markdown::{extract_code_blocks, suggested_filename, CodeBlock};
+use crate::tools::change_set::StagedFiles;
+use crate::tools::{ToolRegistry, ToolResult};
+
+/// Registry name of the tool that `/apply` writes through
+const WRITE_FILE_TOOL: &str = "write_file";
+
+/// Code blocks of the last response, numbered from 1
+///
+/// Chat replaces them after every response and empties them when a new
+/// prompt is sent, so the numbers always refer to the response on screen.
+#[derive(Debug, Clone, Default)]
+pub struct ResponseBlocks {
+    blocks: Vec<CodeBlock>,
+    /// File named before each block, if any
+    suggested_paths: Vec<Option<String>>,
+}
+
+impl ResponseBlocks {
+    /// Collects the code blocks of `response`, skipping empty ones
+    pub fn from_response(response: &str) -> Self {
+        let blocks: Vec<CodeBlock> = extract_code_blocks(response)
+            .into_iter()
+            .filter(|block| !block.is_empty())
+            .collect();
+        let suggested_paths = blocks
+            .iter()
+            .map(|block| suggested_filename(response, block))
+            .collect();
+        Self {
+            blocks,
+            suggested_paths,
+        }
+    }
+
+    /// Forgets the blocks of the previous response
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+        self.suggested_paths.clear();
+    }
+
+    /// Returns the number of blocks
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns true when the last response had no code
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Returns block `number`, counting from 1
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Command` when the last response has no block
+    /// with that number.
+    pub fn get(&self, number: usize) -> Result<&CodeBlock> {
+        if self.blocks.is_empty() {
+            return Err(XzatomaError::Command(
+                "The last response has no code blocks".to_string(),
+            ));
+        }
+        number
+            .checked_sub(1)
+            .and_then(|index| self.blocks.get(index))
+            .ok_or_else(|| {
+                XzatomaError::Command(format!(
+                    "No code block {}; the last response has blocks 1 to {}",
+                    number,
+                    self.blocks.len()
+                ))
+            })
+    }
+
+    /// One line offering the quick actions, shown after a response
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::commands::code_blocks::ResponseBlocks;
+    ///
+    /// let blocks = ResponseBlocks::from_response("```sh\ncargo test\n```\n");
+    /// assert_eq!(
+    ///     blocks.quick_actions(),
+    ///     "Code blocks: [1] sh, 1 line. /copy N, /apply N <path>, /blocks"
+    /// );
+    /// ```
+    pub fn quick_actions(&self) -> String {
+        let labels: Vec<String> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| format!("[{}] {}", index + 1, block.label()))
+            .collect();
+        format!(
+            "Code blocks: {}. /copy N, /apply N <path>, /blocks",
+            labels.join("; ")
+        )
+    }
+
+    /// File the response named for block `number`, counting from 1
+    pub fn suggested_path(&self, number: usize) -> Option<&str> {
+        number
+            .checked_sub(1)
+            .and_then(|index| self.suggested_paths.get(index))
+            .and_then(Option::as_deref)
+    }
+
+    /// The `/blocks` listing: number, language, line count, and first line,
+    /// followed by the `/apply` command for blocks whose file was named
+    pub fn listing(&self) -> String {
+        if self.blocks.is_empty() {
+            return "The last response has no code blocks.".to_string();
+        }
+        let mut listing = String::from("Code blocks in the last response:\n");
+        for (index, block) in self.blocks.iter().enumerate() {
+            let first_line = block.content.lines().next().unwrap_or_default().trim();
+            let mut preview: String = first_line.chars().take(60).collect();
+            if preview.len() < first_line.len() {
+                preview.push_str("...");
+            }
+            listing.push_str(&format!(
+                "  {:>2}  {:<12} {:>5} lines  {}\n",
+                index + 1,
+                block.language.as_deref().unwrap_or("text"),
+                block.line_count(),
+                preview
+            ));
+            if let Some(path) = self.suggested_path(index + 1) {
+                listing.push_str(&format!("      /apply {} {}\n", index + 1, path));
+            }
+        }
+        listing
+    }
+}
+
+/// The system clipboard, opened on first use and kept for the session
+///
+/// Some platforms only serve copied text while the clipboard handle is
+/// alive, so chat keeps one handle for the whole session.
+#[derive(Default)]
+pub struct SessionClipboard {
+    clipboard: Option<arboard::Clipboard>,
+}
+
+impl SessionClipboard {
+    /// Copies `text` to the clipboard
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Command` when no clipboard is available, for
+    /// example over SSH or without a display server. Chat then prints the
+    /// block plainly instead.
+    pub fn copy(&mut self, text: &str) -> Result<()> {
+        let clipboard = match &mut self.clipboard {
+            Some(clipboard) => clipboard,
+            None => {
+                self.clipboard
+                    .insert(arboard::Clipboard::new().map_err(|e| {
+                        XzatomaError::Command(format!("No clipboard is available: {}", e))
+                    })?)
+            }
+        };
+        clipboard
+            .set_text(text.to_string())
+            .map_err(|e| XzatomaError::Command(format!("Could not copy to the clipboard: {}", e)))
+    }
+}
+
+/// Writes `block` to `path` with the session's `write_file` tool
+///
+/// With a reviewer, the write is previewed and shown for review first, as
+/// the agent's own file changes are. An approved write runs with
+/// `"confirm": true`, since the user has just confirmed it; a rejected one
+/// returns the usual rejection result and changes nothing. The tool's path
+/// checks, size limit, audit log, and read-only refusal all apply.
+///
+/// # Arguments
+///
+/// * `tools` - The session's tool registry
+/// * `review` - Reviewer for the write, `None` when writes are not confirmed
+/// * `block` - Code block to write
+/// * `path` - Target file, relative to the session working directory
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Command` when no `write_file` tool is registered,
+/// as in Planning mode, and the tool's error when its arguments are invalid.
+pub async fn apply_block(
+    tools: &ToolRegistry,
+    review: Option<&dyn ChangeReview>,
+    block: &CodeBlock,
+    path: &str,
+) -> Result<ToolResult> {
+    let tool = tools.get(WRITE_FILE_TOOL).ok_or_else(|| {
+        XzatomaError::Command(
+            "Code blocks can only be applied in Write mode. Switch with /write".to_string(),
+        )
+    })?;
+
+    let mut args = serde_json::json!({"path": path, "content": block.content});
+    if let Some(review) = review {
+        if let Some(change) = tool.preview_change(&args, &StagedFiles::new()) {
+            if !review.review(std::slice::from_ref(&change)).approves(0) {
+                return Ok(rejected_change_result(&change));
+            }
+            args["confirm"] = serde_json::Value::Bool(true);
+        }
+    }
+    tool.execute(args).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::change_review::ReviewDecision;
+    use crate::chat_mode::SafetyMode;
+    use crate::tools::change_set::{ChangeKind, FileChange};
+    use crate::tools::confirmation::ConfirmationPolicy;
+    use crate::tools::write_file::WriteFileTool;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_response_blocks_numbering_and_listing() {
+        let blocks = ResponseBlocks::from_response("```rust\na\nb\n```\n```\nc\n```\n");
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks.get(2).unwrap().content, "c\n");
+        assert!(blocks.get(0).is_err());
+        assert!(blocks.get(3).unwrap_err().to_string().contains("1 to 2"));
+        assert!(blocks.listing().contains("rust"));
+        assert!(blocks
+            .quick_actions()
+            .starts_with("Code blocks: [1] rust, 2 lines; [2] text, 1 line."));
+
+        let response =
+            "Create `src/lib.rs`:\n\n```rust\npub fn f() {}\n```\n\n```\n\n```\n```\nls\n```\n";
+        let named = ResponseBlocks::from_response(response);
+        assert_eq!(named.len(), 2);
+        assert_eq!(named.suggested_path(1), Some("src/lib.rs"));
+        assert_eq!(named.suggested_path(2), None);
+        assert!(named.listing().contains("/apply 1 src/lib.rs\n"));
+
+        let mut blocks = blocks;
+        blocks.clear();
+        assert!(blocks.get(1).unwrap_err().to_string().contains("no code"));
+    }
+
+    /// Reviewer that records what it was shown and answers with `approve`
+    struct RecordingReview {
+        approve: bool,
+        seen: Mutex<Vec<FileChange>>,
+    }
+
+    impl ChangeReview for RecordingReview {
+        fn review(&self, changes: &[FileChange]) -> ReviewDecision {
+            self.seen.lock().unwrap().extend_from_slice(changes);
+            if self.approve {
+                ReviewDecision::ApproveAll
+            } else {
+                ReviewDecision::RejectAll
+            }
+        }
+    }
+
+    fn write_registry(dir: &std::path::Path) -> ToolRegistry {
+        let mut tools = ToolRegistry::new();
+        tools.register(
+            "write_file",
+            Arc::new(
+                WriteFileTool::new(dir.to_path_buf(), 1024 * 1024)
+                    .with_confirmation(Some(ConfirmationPolicy::new(SafetyMode::ConfirmOnce))),
+            ),
+        );
+        tools
+    }
+
+    #[tokio::test]
+    async fn test_apply_goes_through_review_and_confirmation() {
+        let dir = tempfile::tempdir().unwrap();
+        let tools = write_registry(dir.path());
+        let block = extract_code_blocks("```rust\nfn main() {}\n```\n").remove(0);
+
+        let rejecting = RecordingReview {
+            approve: false,
+            seen: Mutex::new(Vec::new()),
+        };
+        let result = apply_block(&tools, Some(&rejecting), &block, "src/main.rs")
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("rejected"));
+        assert!(!dir.path().join("src/main.rs").exists());
+        let seen = rejecting.seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].kind, ChangeKind::Create);
+        assert_eq!(seen[0].path, "src/main.rs");
+
+        // Without review, the tool's own confirmation still applies
+        let result = apply_block(&tools, None, &block, "src/main.rs")
+            .await
+            .unwrap();
+        assert!(result.error.unwrap().contains("Confirmation required"));
+
+        let approving = RecordingReview {
+            approve: true,
+            seen: Mutex::new(Vec::new()),
+        };
+        let result = apply_block(&tools, Some(&approving), &block, "src/main.rs")
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("src/main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_needs_a_write_tool() {
+        let block = extract_code_blocks("```\nx\n```\n").remove(0);
+        let err = apply_block(&ToolRegistry::new(), None, &block, "x.txt")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Write mode"));
+    }
+}
@@ -0,0 +1,276 @@
+//! Multi-line message composer for chat mode
+//!
+//! Readline returns one line per Enter. The composer turns those reads into
+//! whole messages:
+//!
+//! - a line ending in `\` continues on the next line ([`Draft`]);
+//! - text pasted through bracketed paste arrives in one read and stays one
+//!   message ([`split_pasted`]);
+//! - `/edit`, or a multi-line paste with `agent.chat.editor_on_multiline`,
+//!   opens the draft in an editor ([`DraftEditor`], [`edit_message`]).
+//!
+//! The composed text then goes through special command parsing, mention
+//! parsing, and the preflight check like any typed message.
+
+use crate::error::{Result, XzatomaError};
+use std::process::Command;
+
+/// Prompt shown while a message continues over several lines
+pub const CONTINUATION_PROMPT: &str = "... ";
+
+/// Bracketed paste start and end sequences, with and without the escape
+/// byte a terminal may have consumed
+const PASTE_MARKERS: [&str; 4] = ["\x1b[200~", "\x1b[201~", "[200~", "[201~"];
+
+/// Editor used when neither `VISUAL` nor `EDITOR` is set
+const FALLBACK_EDITOR: &str = "vi";
+
+/// Splits input from one read into lines
+///
+/// Bracketed paste delivers a whole paste in one read, with its newlines.
+/// Paste markers left at either end are removed, `\r\n` and `\r` become
+/// `\n`, and trailing line breaks are dropped. More than one line means the
+/// input was pasted.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::commands::composer::split_pasted;
+///
+/// assert_eq!(split_pasted("fn main() {\r\n}\r\n"), vec!["fn main() {", "}"]);
+/// assert_eq!(split_pasted("hello"), vec!["hello"]);
+/// ```
+pub fn split_pasted(input: &str) -> Vec<String> {
+    let mut text = input;
+    while let Some(rest) = PASTE_MARKERS
+        .iter()
+        .find_map(|marker| text.strip_prefix(marker))
+    {
+        text = rest;
+    }
+    while let Some(rest) = PASTE_MARKERS
+        .iter()
+        .find_map(|marker| text.strip_suffix(marker))
+    {
+        text = rest;
+    }
+
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .trim_end_matches('\n')
+        .split('\n')
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns `line` without its continuation backslash, or `None` when the
+/// line does not continue
+///
+/// A line continues when it ends in an odd number of backslashes, so `\\`
+/// at the end of a line is kept as written.
+pub fn continued(line: &str) -> Option<&str> {
+    let body = line.trim_end();
+    let backslashes = body.len() - body.trim_end_matches('\\').len();
+    (backslashes % 2 == 1).then(|| &body[..body.len() - 1])
+}
+
+/// A message assembled from lines that end in a backslash
+#[derive(Debug, Default)]
+pub struct Draft {
+    lines: Vec<String>,
+}
+
+impl Draft {
+    /// Adds a typed line
+    ///
+    /// Returns the whole message, one line per typed line, once a line does
+    /// not continue; the draft is then empty again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::commands::composer::Draft;
+    ///
+    /// let mut draft = Draft::default();
+    /// assert_eq!(draft.push("Refactor the parser \\"), None);
+    /// assert_eq!(
+    ///     draft.push("and keep the tests"),
+    ///     Some("Refactor the parser \nand keep the tests".to_string())
+    /// );
+    /// assert!(draft.is_empty());
+    /// ```
+    pub fn push(&mut self, line: &str) -> Option<String> {
+        match continued(line) {
+            Some(head) => {
+                self.lines.push(head.to_string());
+                None
+            }
+            None => {
+                self.lines.push(line.to_string());
+                Some(self.take())
+            }
+        }
+    }
+
+    /// Whether no line is pending
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Empties the draft and returns the lines so far
+    pub fn take(&mut self) -> String {
+        std::mem::take(&mut self.lines).join("\n")
+    }
+}
+
+/// Opens a draft in an editor and returns the saved text
+pub trait DraftEditor {
+    /// Edits `draft` and returns the content saved on exit
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the editor cannot be started or exits with a
+    /// failure status.
+    fn edit(&self, draft: &str) -> Result<String>;
+}
+
+/// Edits drafts in an external editor such as `$EDITOR`
+///
+/// The draft is written to a temporary file, the editor runs with the file
+/// as its last argument, and the file is read back and removed.
+#[derive(Debug, Clone)]
+pub struct ExternalEditor {
+    command: String,
+}
+
+impl ExternalEditor {
+    /// Uses `VISUAL`, then `EDITOR`, then `vi`
+    pub fn from_env() -> Self {
+        let command = ["VISUAL", "EDITOR"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.trim().is_empty())
+            .unwrap_or_else(|| FALLBACK_EDITOR.to_string());
+        Self::new(command)
+    }
+
+    /// Uses `command`, split on whitespace, such as `code --wait`
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+        }
+    }
+}
+
+impl DraftEditor for ExternalEditor {
+    fn edit(&self, draft: &str) -> Result<String> {
+        let mut words = self.command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| XzatomaError::Command("The editor command is empty".to_string()))?;
+
+        let path =
+            std::env::temp_dir().join(format!("xzatoma-message-{}.md", uuid::Uuid::new_v4()));
+        std::fs::write(&path, draft)?;
+
+        let status = Command::new(program).args(words).arg(&path).status();
+        let saved = match status {
+            Ok(status) if status.success() => std::fs::read_to_string(&path).map_err(Into::into),
+            Ok(status) => Err(XzatomaError::Command(format!(
+                "Editor '{}' exited with {}; the message was not sent",
+                self.command, status
+            ))),
+            Err(e) => Err(XzatomaError::Command(format!(
+                "Failed to start editor '{}': {}. Set VISUAL or EDITOR",
+                self.command, e
+            ))),
+        };
+        let _ = std::fs::remove_file(&path);
+        saved
+    }
+}
+
+/// Opens `draft` in `editor` and returns the message to send
+///
+/// Returns `None` when the saved content is empty or only whitespace, which
+/// aborts the message.
+///
+/// # Errors
+///
+/// Returns the editor's error.
+pub fn edit_message(editor: &dyn DraftEditor, draft: &str) -> Result<Option<String>> {
+    let saved = editor.edit(draft)?;
+    let message = saved.trim_end();
+    if message.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(message.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Replaces the draft with a fixed text and remembers what it was given
+    struct ScriptedEditor {
+        saved: String,
+        seen: RefCell<Option<String>>,
+    }
+
+    impl ScriptedEditor {
+        fn new(saved: &str) -> Self {
+            Self {
+                saved: saved.to_string(),
+                seen: RefCell::new(None),
+            }
+        }
+    }
+
+    impl DraftEditor for ScriptedEditor {
+        fn edit(&self, draft: &str) -> Result<String> {
+            *self.seen.borrow_mut() = Some(draft.to_string());
+            Ok(self.saved.clone())
+        }
+    }
+
+    #[test]
+    fn test_backslash_continuation_assembles_lines() {
+        let mut draft = Draft::default();
+        assert_eq!(draft.push("first \\"), None);
+        assert_eq!(draft.push("  second\\  "), None);
+        assert!(!draft.is_empty());
+        assert_eq!(
+            draft.push("third"),
+            Some("first \n  second\nthird".to_string())
+        );
+        assert!(draft.is_empty());
+
+        // An escaped backslash ends the message and is kept
+        assert_eq!(draft.push(r"C:\temp\\"), Some(r"C:\temp\\".to_string()));
+        assert_eq!(continued(r"a \\\"), Some(r"a \\"));
+    }
+
+    #[test]
+    fn test_paste_detection_splits_lines_and_strips_markers() {
+        assert_eq!(
+            split_pasted("\x1b[200~let a = 1;\r\nlet b = 2;\rlet c = 3;\n\x1b[201~"),
+            vec!["let a = 1;", "let b = 2;", "let c = 3;"]
+        );
+        assert_eq!(split_pasted("[200~one\n\ntwo[201~"), vec!["one", "", "two"]);
+        assert_eq!(split_pasted("typed line"), vec!["typed line"]);
+    }
+
+    #[test]
+    fn test_edit_message_sends_saved_text_and_aborts_on_empty_save() {
+        let editor = ScriptedEditor::new("Review @src/main.rs\nand list risks\n\n");
+        assert_eq!(
+            edit_message(&editor, "draft").unwrap(),
+            Some("Review @src/main.rs\nand list risks".to_string())
+        );
+        assert_eq!(editor.seen.borrow().as_deref(), Some("draft"));
+
+        let editor = ScriptedEditor::new("  \n");
+        assert_eq!(edit_message(&editor, "draft").unwrap(), None);
+    }
+}
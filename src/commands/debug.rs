@@ -0,0 +1,583 @@
+//! Turn-by-turn inspector for recorded runs
+//!
+//! `xzatoma debug <run-id>` loads the turns stored by `xzatoma run --record`
+//! and reads commands from the terminal, or one per line from standard input
+//! when it is not a terminal. Only command output is printed in the second
+//! case, so it can be redirected to a file:
+//!
+//! - `list` shows one line per turn
+//! - `show <turn>` prints a turn's request, response, and tool calls
+//! - `diff <from> <to>` shows how the conversation changed between turns
+//! - `dump <turn>` prints the turn's provider request as JSON, ready to send
+//!   to the provider by hand
+//!
+//! The conversation at a turn is the request's messages followed by the
+//! response, so `diff 1 2` shows the response of turn 1 and the tool results
+//! that were sent with turn 2.
+
+use std::io::{BufRead, IsTerminal};
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use crate::config::Config;
+use crate::error::{Result, XzatomaError};
+use crate::paths::Paths;
+use crate::providers::Message;
+use crate::storage::types::StoredRunTurn;
+use crate::storage::SqliteStorage;
+use crate::{ui_eprintln, ui_println};
+
+/// Longest response excerpt shown in the turn list, in characters
+const SUMMARY_CHARS: usize = 60;
+
+/// Help text printed by the `help` command
+const HELP: &str = "\
+Commands:
+  list               List the recorded turns
+  show <turn>        Show a turn's request, response, and tool calls
+  diff <from> <to>   Diff the conversation between two turns
+  dump <turn>        Print a turn's provider request as JSON
+  help               Show this help
+  quit               Leave the inspector";
+
+/// A command typed into the inspector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InspectorCommand {
+    /// List the recorded turns
+    List,
+    /// Show one turn
+    Show(u32),
+    /// Diff the conversation between two turns
+    Diff(u32, u32),
+    /// Print one turn's provider request as JSON
+    Dump(u32),
+    /// Show the available commands
+    Help,
+    /// Leave the inspector
+    Quit,
+}
+
+impl InspectorCommand {
+    /// Parses one line of inspector input
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Config` describing the expected usage when the
+    /// command is unknown or its turn numbers are missing or invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::commands::debug::InspectorCommand;
+    ///
+    /// assert_eq!(InspectorCommand::parse("diff 1 3")?, InspectorCommand::Diff(1, 3));
+    /// assert!(InspectorCommand::parse("show").is_err());
+    /// # Ok::<(), xzatoma::error::XzatomaError>(())
+    /// ```
+    pub fn parse(line: &str) -> Result<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let turn = |word: Option<&&str>, usage: &str| -> Result<u32> {
+            word.and_then(|word| word.parse().ok())
+                .ok_or_else(|| XzatomaError::Config(format!("usage: {}", usage)))
+        };
+        match words.first().copied() {
+            Some("list" | "ls" | "l") => Ok(Self::List),
+            Some("show" | "s") => Ok(Self::Show(turn(words.get(1), "show <turn>")?)),
+            Some("diff" | "d") => Ok(Self::Diff(
+                turn(words.get(1), "diff <from> <to>")?,
+                turn(words.get(2), "diff <from> <to>")?,
+            )),
+            Some("dump") => Ok(Self::Dump(turn(words.get(1), "dump <turn>")?)),
+            Some("help" | "h" | "?") | None => Ok(Self::Help),
+            Some("quit" | "exit" | "q") => Ok(Self::Quit),
+            Some(other) => Err(XzatomaError::Config(format!(
+                "unknown command '{}'; type 'help' for the list of commands",
+                other
+            ))),
+        }
+    }
+}
+
+/// The recorded turns of one run and the views the inspector prints
+pub struct TurnInspector {
+    run_id: String,
+    turns: Vec<StoredRunTurn>,
+}
+
+impl TurnInspector {
+    /// Creates an inspector over the turns of `run_id`
+    pub fn new(run_id: impl Into<String>, turns: Vec<StoredRunTurn>) -> Self {
+        Self {
+            run_id: run_id.into(),
+            turns,
+        }
+    }
+
+    fn turn(&self, number: u32) -> Result<&StoredRunTurn> {
+        self.turns
+            .iter()
+            .find(|turn| turn.turn == number)
+            .ok_or_else(|| {
+                XzatomaError::Config(format!(
+                    "run {} has no turn {} (turns 1-{})",
+                    self.run_id,
+                    number,
+                    self.turns.len()
+                ))
+            })
+    }
+
+    /// Runs one command and returns the text to print
+    ///
+    /// Returns `None` for [`InspectorCommand::Quit`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the command names a turn the run does not have.
+    pub fn execute(&self, command: InspectorCommand) -> Result<Option<String>> {
+        let output = match command {
+            InspectorCommand::List => self.turn_list(),
+            InspectorCommand::Show(turn) => self.show_turn(turn)?,
+            InspectorCommand::Diff(from, to) => self.diff(from, to)?,
+            InspectorCommand::Dump(turn) => {
+                serde_json::to_string_pretty(&self.provider_request(turn)?)?
+            }
+            InspectorCommand::Help => HELP.to_string(),
+            InspectorCommand::Quit => return Ok(None),
+        };
+        Ok(Some(output))
+    }
+
+    /// Lists the turns with their timing and a summary of each response
+    pub fn turn_list(&self) -> String {
+        let mut lines = vec![format!(
+            "Run {}: {} turn{}",
+            self.run_id,
+            self.turns.len(),
+            if self.turns.len() == 1 { "" } else { "s" }
+        )];
+        for turn in &self.turns {
+            lines.push(format!(
+                "{:>4}  {}  {:>7} ms  {}  {} message{}  {}{}",
+                turn.turn,
+                turn.started_at.format("%H:%M:%S"),
+                turn.duration_ms,
+                turn.model,
+                turn.messages.len(),
+                if turn.messages.len() == 1 { "" } else { "s" },
+                response_summary(turn),
+                if turn.truncated { " (truncated)" } else { "" }
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// Shows a turn's request, response, and tool calls
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the run has no such turn.
+    pub fn show_turn(&self, number: u32) -> Result<String> {
+        let turn = self.turn(number)?;
+        let mut lines = vec![
+            format!("Turn {} of run {}", turn.turn, self.run_id),
+            format!(
+                "Started {}, took {} ms, model {}",
+                turn.started_at.to_rfc3339(),
+                turn.duration_ms,
+                turn.model
+            ),
+        ];
+        if turn.truncated {
+            lines.push("Some payloads were truncated when recorded.".to_string());
+        }
+
+        lines.push(String::new());
+        lines.push(format!(
+            "Request: {} message{}, {} tool definition{}",
+            turn.messages.len(),
+            if turn.messages.len() == 1 { "" } else { "s" },
+            turn.tools.len(),
+            if turn.tools.len() == 1 { "" } else { "s" }
+        ));
+        for (index, message) in turn.messages.iter().enumerate() {
+            lines.push(format!("[{}] {}", index, render_message(message)));
+        }
+
+        lines.push(String::new());
+        match (&turn.response, &turn.error) {
+            (Some(response), _) => {
+                lines.push(format!("Response ({:?}):", response.finish_reason));
+                lines.push(render_message(&response.message));
+                if let Some(reasoning) = &response.reasoning {
+                    lines.push(format!("Reasoning: {}", reasoning));
+                }
+                if let Some(usage) = &response.usage {
+                    lines.push(format!(
+                        "Usage: {} prompt + {} completion = {} tokens{}",
+                        usage.prompt_tokens,
+                        usage.completion_tokens,
+                        usage.total_tokens,
+                        if response.cached { " (cached)" } else { "" }
+                    ));
+                }
+            }
+            (None, Some(error)) => lines.push(format!("Error: {}", error)),
+            (None, None) => lines.push("No response recorded.".to_string()),
+        }
+
+        if !turn.tool_calls.is_empty() {
+            lines.push(String::new());
+            lines.push("Tool calls:".to_string());
+            for call in &turn.tool_calls {
+                lines.push(format!("- {} {}", call.name, call.arguments));
+                match &call.result {
+                    Some(result) => lines.push(format!("  -> {}", result)),
+                    None => lines.push("  -> (no result recorded)".to_string()),
+                }
+            }
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Unified diff of the conversation at two turns
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the run lacks either turn.
+    pub fn diff(&self, from: u32, to: u32) -> Result<String> {
+        let before = conversation_text(self.turn(from)?);
+        let after = conversation_text(self.turn(to)?);
+        let diff = similar::TextDiff::from_lines(&before, &after)
+            .unified_diff()
+            .header(&format!("turn {}", from), &format!("turn {}", to))
+            .to_string();
+        if diff.is_empty() {
+            Ok(format!(
+                "No differences between turn {} and turn {}.",
+                from, to
+            ))
+        } else {
+            Ok(diff)
+        }
+    }
+
+    /// The request a turn sent, as a JSON body
+    ///
+    /// The object has `model`, `messages`, and, when tools were offered,
+    /// `tools` in the OpenAI function format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the run has no such turn.
+    pub fn provider_request(&self, number: u32) -> Result<serde_json::Value> {
+        let turn = self.turn(number)?;
+        let mut request = serde_json::json!({
+            "model": turn.model,
+            "messages": turn.messages,
+        });
+        if !turn.tools.is_empty() {
+            request["tools"] = turn
+                .tools
+                .iter()
+                .map(|tool| serde_json::json!({"type": "function", "function": tool}))
+                .collect();
+        }
+        Ok(request)
+    }
+
+    /// Whether the given turn was truncated when recorded
+    fn is_truncated(&self, number: u32) -> bool {
+        self.turn(number).is_ok_and(|turn| turn.truncated)
+    }
+}
+
+/// One line describing how a turn's request ended
+fn response_summary(turn: &StoredRunTurn) -> String {
+    if let Some(error) = &turn.error {
+        return format!("error: {}", excerpt(error));
+    }
+    if !turn.tool_calls.is_empty() {
+        let names: Vec<&str> = turn
+            .tool_calls
+            .iter()
+            .map(|call| call.name.as_str())
+            .collect();
+        return format!("tool calls: {}", names.join(", "));
+    }
+    let text = turn
+        .response
+        .as_ref()
+        .and_then(|response| response.message.content.as_deref())
+        .unwrap_or_default();
+    format!("text: {}", excerpt(text))
+}
+
+/// First line of `text`, cut to the summary length
+fn excerpt(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    if line.chars().count() > SUMMARY_CHARS {
+        format!(
+            "{}...",
+            line.chars().take(SUMMARY_CHARS).collect::<String>()
+        )
+    } else {
+        line.to_string()
+    }
+}
+
+/// Renders a message as `role: content`, followed by any tool calls
+fn render_message(message: &Message) -> String {
+    let mut text = match &message.tool_call_id {
+        Some(id) => format!("{} ({}): ", message.role, id),
+        None => format!("{}: ", message.role),
+    };
+    text.push_str(message.content.as_deref().unwrap_or_default());
+    for call in message.tool_calls.iter().flatten() {
+        text.push_str(&format!(
+            "\n    call {} {} {}",
+            call.id, call.function.name, call.function.arguments
+        ));
+    }
+    text
+}
+
+/// The conversation after a turn: its request messages and its response
+fn conversation_text(turn: &StoredRunTurn) -> String {
+    let mut lines: Vec<String> = turn.messages.iter().map(render_message).collect();
+    if let Some(response) = &turn.response {
+        lines.push(render_message(&response.message));
+    }
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+/// Open the inspector for a recorded run
+///
+/// # Arguments
+///
+/// * `config` - Loaded configuration, used to locate the history database
+/// * `run_id` - Full run ID or a prefix of it
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be opened, no recorded run
+/// matches `run_id`, or reading input fails.
+pub fn run_debug(config: &Config, run_id: &str) -> Result<()> {
+    let storage = SqliteStorage::new(&Paths::from_config(config)?)?;
+    let run_id = storage.resolve_run_id(run_id)?.ok_or_else(|| {
+        XzatomaError::Config(format!(
+            "No recorded run matches '{}'. Record one with `xzatoma run --record`.",
+            run_id
+        ))
+    })?;
+    let inspector = TurnInspector::new(run_id.clone(), storage.load_run_turns(&run_id)?);
+
+    if std::io::stdin().is_terminal() {
+        ui_println!("{}", inspector.turn_list());
+        ui_println!("Type 'help' for commands.");
+        let mut editor = DefaultEditor::new()?;
+        loop {
+            let line = match editor.readline("debug> ") {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+                Err(error) => return Err(error.into()),
+            };
+            let _ = editor.add_history_entry(line.as_str());
+            if !run_line(&inspector, &line) {
+                break;
+            }
+        }
+    } else {
+        for line in std::io::stdin().lock().lines() {
+            let line = line?;
+            if !line.trim().is_empty() && !run_line(&inspector, &line) {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs one line of input, returning false when the user quits
+fn run_line(inspector: &TurnInspector, line: &str) -> bool {
+    let command = match InspectorCommand::parse(line) {
+        Ok(command) => command,
+        Err(error) => {
+            ui_eprintln!("{}", error);
+            return true;
+        }
+    };
+    match inspector.execute(command) {
+        Ok(Some(output)) => {
+            if let InspectorCommand::Dump(turn) = command {
+                if inspector.is_truncated(turn) {
+                    ui_eprintln!("Note: turn {} was truncated when recorded.", turn);
+                }
+            }
+            ui_println!("{}", output);
+            true
+        }
+        Ok(None) => false,
+        Err(error) => {
+            ui_eprintln!("{}", error);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::Agent;
+    use crate::config::AgentConfig;
+    use crate::providers::recording::{RecordingProvider, RunRecorder};
+    use crate::providers::{CompletionResponse, FunctionCall, ToolCall};
+    use crate::testing::MockProvider;
+    use crate::tools::{ToolExecutor, ToolRegistry, ToolResult};
+    use async_trait::async_trait;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    struct EchoTool;
+
+    #[async_trait]
+    impl ToolExecutor for EchoTool {
+        fn tool_definition(&self) -> serde_json::Value {
+            serde_json::json!({
+                "name": "echo",
+                "description": "Echo the text argument",
+                "parameters": {"type": "object", "properties": {"text": {"type": "string"}}}
+            })
+        }
+
+        async fn execute(&self, args: serde_json::Value) -> Result<ToolResult> {
+            Ok(ToolResult::success(format!("echoed {}", args["text"])))
+        }
+    }
+
+    /// Records a two-turn run: one echo tool call, then a text answer
+    async fn recorded_run(dir: &TempDir) -> TurnInspector {
+        let storage = SqliteStorage::new_with_path(dir.path().join("history.db")).unwrap();
+        let recorder = Arc::new(RunRecorder::new(storage.clone(), 64 * 1024));
+        let provider = RecordingProvider::new(
+            MockProvider::new()
+                .with_model("fake-model")
+                .then_response(CompletionResponse::new(Message::assistant_with_tools(
+                    vec![ToolCall {
+                        id: "call-1".to_string(),
+                        function: FunctionCall {
+                            name: "echo".to_string(),
+                            arguments: r#"{"text":"hi"}"#.to_string(),
+                        },
+                    }],
+                )))
+                .then_text("All done"),
+            Arc::clone(&recorder),
+        );
+        let mut tools = ToolRegistry::new();
+        tools.register("echo", Arc::new(EchoTool));
+
+        let mut agent = Agent::new(provider, tools, AgentConfig::default()).unwrap();
+        let response = agent.execute("Say hi").await.unwrap();
+        assert_eq!(response, "All done");
+        recorder.finish(agent.conversation().messages());
+
+        let turns = storage.load_run_turns(recorder.run_id()).unwrap();
+        TurnInspector::new(recorder.run_id(), turns)
+    }
+
+    #[tokio::test]
+    async fn test_inspector_lists_recorded_turns() {
+        let dir = TempDir::new().unwrap();
+        let inspector = recorded_run(&dir).await;
+
+        let list = inspector.turn_list();
+        let lines: Vec<&str> = list.lines().collect();
+        assert_eq!(lines.len(), 3, "{}", list);
+        assert!(lines[0].ends_with(": 2 turns"));
+        assert!(lines[1].trim_start().starts_with("1  "));
+        assert!(lines[1].contains("fake-model"));
+        assert!(lines[1].ends_with("tool calls: echo"));
+        assert!(lines[2].trim_start().starts_with("2  "));
+        assert!(lines[2].ends_with("text: All done"));
+
+        let shown = inspector.show_turn(1).unwrap();
+        assert!(shown.contains("- echo {\"text\":\"hi\"}"));
+        assert!(shown.contains("  -> echoed"), "{}", shown);
+        assert!(inspector.show_turn(3).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_inspector_diffs_conversation_between_turns() {
+        let dir = TempDir::new().unwrap();
+        let inspector = recorded_run(&dir).await;
+
+        let diff = inspector.diff(1, 2).unwrap();
+        assert!(diff.starts_with("--- turn 1\n+++ turn 2\n"), "{}", diff);
+        let added: Vec<&str> = diff
+            .lines()
+            .filter(|line| line.starts_with('+') && !line.starts_with("+++"))
+            .collect();
+        assert_eq!(added.len(), 2, "{}", diff);
+        assert!(added[0].starts_with("+tool (call-1): "));
+        assert!(added[0].contains("echoed"));
+        assert_eq!(added[1], "+assistant: All done");
+        assert!(!diff
+            .lines()
+            .any(|line| line.starts_with('-') && !line.starts_with("---")));
+
+        assert_eq!(
+            inspector.diff(2, 2).unwrap(),
+            "No differences between turn 2 and turn 2."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dump_produces_provider_request_json() {
+        let dir = TempDir::new().unwrap();
+        let inspector = recorded_run(&dir).await;
+
+        let request = inspector.provider_request(2).unwrap();
+        assert_eq!(request["model"], "fake-model");
+        let messages = request["messages"].as_array().unwrap();
+        assert_eq!(messages.last().unwrap()["role"], "tool");
+        assert_eq!(messages.last().unwrap()["tool_call_id"], "call-1");
+        assert_eq!(request["tools"][0]["type"], "function");
+        assert_eq!(request["tools"][0]["function"]["name"], "echo");
+
+        let dumped = inspector
+            .execute(InspectorCommand::Dump(2))
+            .unwrap()
+            .unwrap();
+        let reparsed: serde_json::Value = serde_json::from_str(&dumped).unwrap();
+        assert_eq!(reparsed, request);
+        assert_eq!(inspector.execute(InspectorCommand::Quit).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_inspector_commands() {
+        assert_eq!(
+            InspectorCommand::parse("list").unwrap(),
+            InspectorCommand::List
+        );
+        assert_eq!(
+            InspectorCommand::parse(" show 2 ").unwrap(),
+            InspectorCommand::Show(2)
+        );
+        assert_eq!(
+            InspectorCommand::parse("dump 1").unwrap(),
+            InspectorCommand::Dump(1)
+        );
+        assert_eq!(
+            InspectorCommand::parse("q").unwrap(),
+            InspectorCommand::Quit
+        );
+        assert_eq!(InspectorCommand::parse("").unwrap(), InspectorCommand::Help);
+        assert!(InspectorCommand::parse("diff 1").is_err());
+        assert!(InspectorCommand::parse("show x").is_err());
+        assert!(InspectorCommand::parse("rewind").is_err());
+    }
+}
@@ -0,0 +1,987 @@
+//! Local setup diagnostics
+//!
+//! `xzatoma doctor` checks the configuration, the provider, local storage,
+//! MCP servers, and required programs, and reports pass, warn, or fail for
+//! each. Checks run concurrently, each with its own timeout, so one hung
+//! server does not stall the report.
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::cli::Cli;
+use crate::config::{Config, CredentialBackend, PathsConfig};
+use crate::credentials::{CredentialStore, GuardedKeyring, CREDENTIALS_KEY_ENV};
+use crate::error::Result;
+use crate::mcp::auth::token_store::TokenStore;
+use crate::mcp::manager::McpClientManager;
+use crate::mcp::server::McpServerConfig;
+use crate::network_policy::NetworkPolicy;
+use crate::paths::Paths;
+use crate::providers::factory::{KEYRING_COPILOT_USER, KEYRING_SERVICE};
+use crate::providers::{create_provider, OllamaProvider, Provider};
+use crate::storage::SqliteStorage;
+use crate::{ui, ui_println};
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    /// The check found no problem
+    Pass,
+    /// Something is missing or odd, but XZatoma can still run
+    Warn,
+    /// XZatoma will not work until this is fixed
+    Fail,
+}
+
+/// Result of a single check
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    /// Short name of what was checked, such as `config file`
+    pub name: String,
+    /// Outcome of the check
+    pub status: CheckStatus,
+    /// What the check found
+    pub message: String,
+    /// One-line suggestion for fixing a warning or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl CheckResult {
+    fn pass(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Pass,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: impl Into<String>, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Warn,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: impl Into<String>, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Fail,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// All check results of one doctor run
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    /// Check results in a stable order
+    pub checks: Vec<CheckResult>,
+}
+
+impl DoctorReport {
+    /// Number of checks with the given status
+    pub fn count(&self, status: CheckStatus) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.status == status)
+            .count()
+    }
+
+    /// Returns `true` when any check failed; warnings do not count
+    pub fn has_failures(&self) -> bool {
+        self.count(CheckStatus::Fail) > 0
+    }
+}
+
+/// A check that is still running
+type PendingCheck<'a> = Pin<Box<dyn Future<Output = Vec<CheckResult>> + 'a>>;
+
+/// Run every check and print the report
+///
+/// # Arguments
+///
+/// * `config_path` - Path of the configuration file to check
+/// * `cli` - Parsed command line, for the overrides applied when loading
+/// * `json` - Print the report as JSON instead of text
+/// * `timeout` - Time each check may take before it is reported as failed
+///
+/// # Returns
+///
+/// Returns the report. The caller exits with a non-zero status when
+/// [`DoctorReport::has_failures`] is `true`.
+///
+/// # Errors
+///
+/// Returns an error only if the JSON report cannot be serialized.
+pub async fn run_doctor(
+    config_path: &str,
+    cli: &Cli,
+    json: bool,
+    timeout: Duration,
+) -> Result<DoctorReport> {
+    let report = diagnose(config_path, cli, timeout).await;
+    if json {
+        ui::json(&report)?;
+    } else {
+        print_report(&report);
+    }
+    Ok(report)
+}
+
+/// Run every check and collect the results
+///
+/// The configuration is checked first because the other checks depend on
+/// it. When it cannot be loaded, the remaining checks use the defaults.
+pub async fn diagnose(config_path: &str, cli: &Cli, timeout: Duration) -> DoctorReport {
+    let (mut checks, config) = check_config(Path::new(config_path), cli);
+
+    let pending: Vec<PendingCheck<'_>> = vec![
+        Box::pin(with_timeout(
+            "provider",
+            timeout,
+            check_configured_provider(&config),
+        )),
+        Box::pin(with_timeout("keyring", timeout, check_keyring(&config))),
+        Box::pin(with_timeout(
+            "storage",
+            timeout,
+            check_storage(config.paths.clone()),
+        )),
+        Box::pin(check_mcp_servers(&config, timeout)),
+        Box::pin(with_timeout("git", timeout, check_git())),
+        Box::pin(with_timeout("terminal", timeout, check_terminal())),
+    ];
+    for results in futures::future::join_all(pending).await {
+        checks.extend(results);
+    }
+
+    DoctorReport { checks }
+}
+
+/// Run a check, reporting a failure when it does not finish in time
+async fn with_timeout(
+    name: &str,
+    timeout: Duration,
+    check: impl Future<Output = Vec<CheckResult>>,
+) -> Vec<CheckResult> {
+    match tokio::time::timeout(timeout, check).await {
+        Ok(results) => results,
+        Err(_) => vec![CheckResult::fail(
+            name,
+            format!("did not finish within {}s", timeout.as_secs_f64()),
+            "Check that the service is responding, or re-run with a larger --timeout",
+        )],
+    }
+}
+
+/// Run a blocking check on the blocking thread pool
+///
+/// Keyring and SQLite calls block, so they must not run on the async
+/// workers where a timeout could not interrupt them.
+async fn blocking<F>(name: &'static str, check: F) -> Vec<CheckResult>
+where
+    F: FnOnce() -> Vec<CheckResult> + Send + 'static,
+{
+    tokio::task::spawn_blocking(check)
+        .await
+        .unwrap_or_else(|e| {
+            vec![CheckResult::fail(
+                name,
+                format!("check panicked: {}", e),
+                "Re-run with --verbose and report the output",
+            )]
+        })
+}
+
+// ---------------------------------------------------------------------------
+// Configuration
+// ---------------------------------------------------------------------------
+
+/// Check that the configuration file exists, parses, and validates
+///
+/// # Returns
+///
+/// Returns the check results and the loaded configuration. When the file
+/// cannot be loaded, the configuration is built without it.
+pub fn check_config(config_path: &Path, cli: &Cli) -> (Vec<CheckResult>, Config) {
+    let mut checks = Vec::new();
+    let display = config_path.display();
+
+    if config_path.exists() {
+        let contents = match std::fs::read_to_string(config_path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                checks.push(CheckResult::fail(
+                    "config file",
+                    format!("cannot read {}: {}", display, e),
+                    format!("Check the permissions of {}", display),
+                ));
+                return (checks, Config::load_without_file(cli));
+            }
+        };
+        match Config::unknown_keys(&contents) {
+            Ok(keys) if keys.is_empty() => {
+                checks.push(CheckResult::pass(
+                    "config file",
+                    format!("{} found", display),
+                ));
+            }
+            Ok(keys) => checks.push(CheckResult::warn(
+                "config file",
+                format!("{} has unknown keys: {}", display, keys.join(", ")),
+                "Fix the spelling or remove the keys; see docs/reference/configuration.md",
+            )),
+            Err(e) => {
+                checks.push(CheckResult::fail(
+                    "config file",
+                    e.to_string(),
+                    format!("Fix the YAML in {}", display),
+                ));
+                return (checks, Config::load_without_file(cli));
+            }
+        }
+    } else {
+        checks.push(CheckResult::warn(
+            "config file",
+            format!("{} not found; using built-in defaults", display),
+            format!("Create {} or pass --config <path>", display),
+        ));
+    }
+
+    let config = match Config::load(&config_path.to_string_lossy(), cli) {
+        Ok(config) => config,
+        Err(e) => {
+            checks.push(CheckResult::fail(
+                "config validation",
+                e.user_message(),
+                format!("Fix the reported setting in {}", display),
+            ));
+            return (checks, Config::load_without_file(cli));
+        }
+    };
+
+    match config.validate() {
+        Ok(()) => checks.push(CheckResult::pass(
+            "config validation",
+            "configuration is valid",
+        )),
+        Err(e) => checks.push(CheckResult::fail(
+            "config validation",
+            e.user_message(),
+            format!("Fix the reported setting in {}", display),
+        )),
+    }
+
+    (checks, config)
+}
+
+// ---------------------------------------------------------------------------
+// Provider
+// ---------------------------------------------------------------------------
+
+/// Model configured for the active provider
+fn configured_model(config: &Config) -> String {
+    match config.provider.provider_type.as_str() {
+        "copilot" => config.provider.copilot.model.clone(),
+        "ollama" => config.provider.ollama.model.clone(),
+        "openai" => config.provider.openai.model.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Create the configured provider and check it
+async fn check_configured_provider(config: &Config) -> Vec<CheckResult> {
+    let provider_type = config.provider.provider_type.as_str();
+    let provider = match create_provider(provider_type, &config.provider) {
+        Ok(provider) => provider,
+        Err(e) => {
+            return vec![CheckResult::fail(
+                "provider",
+                e.user_message(),
+                "Set provider.type to copilot, ollama, or openai",
+            )]
+        }
+    };
+
+    let mut checks = Vec::new();
+    if provider_type == "ollama" {
+        checks.push(check_ollama_server(config).await);
+    }
+    checks
+        .extend(check_provider(provider.as_ref(), provider_type, &configured_model(config)).await);
+    checks
+}
+
+/// Check the Ollama server version
+async fn check_ollama_server(config: &Config) -> CheckResult {
+    let provider = match OllamaProvider::new(config.provider.ollama.clone()) {
+        Ok(provider) => provider,
+        Err(e) => {
+            return CheckResult::fail(
+                "ollama server",
+                e.user_message(),
+                "Check provider.ollama.host",
+            )
+        }
+    };
+    match provider.health_check().await {
+        Ok(version) => CheckResult::pass(
+            "ollama server",
+            format!("Ollama {} at {}", version, provider.host()),
+        ),
+        Err(e) => CheckResult::fail(
+            "ollama server",
+            e.user_message(),
+            "Start Ollama with `ollama serve` or set provider.ollama.host",
+        ),
+    }
+}
+
+/// Check provider credentials, reachability, and the configured model
+///
+/// Providers that need credentials are not contacted without them, so an
+/// expired Copilot token never starts the interactive device flow.
+///
+/// # Arguments
+///
+/// * `provider` - The provider to check
+/// * `provider_type` - Provider name, used for fix suggestions
+/// * `model` - The configured model, which must be in the model list
+pub async fn check_provider(
+    provider: &dyn Provider,
+    provider_type: &str,
+    model: &str,
+) -> Vec<CheckResult> {
+    let mut checks = Vec::new();
+
+    if provider_type != "ollama" {
+        if !provider.is_authenticated() {
+            checks.push(CheckResult::fail(
+                "provider auth",
+                format!("{} has no valid credentials", provider_type),
+                auth_fix(provider_type),
+            ));
+            return checks;
+        }
+        checks.push(CheckResult::pass(
+            "provider auth",
+            format!("{} credentials found", provider_type),
+        ));
+    }
+
+    let models = match provider.list_models().await {
+        Ok(models) => models,
+        Err(e) => {
+            checks.push(CheckResult::fail(
+                "provider reachability",
+                e.user_message(),
+                reachability_fix(provider_type),
+            ));
+            return checks;
+        }
+    };
+    checks.push(CheckResult::pass(
+        "provider reachability",
+        format!("{} is reachable ({} models)", provider_type, models.len()),
+    ));
+
+    if models.iter().any(|info| info.name == model) {
+        checks.push(CheckResult::pass(
+            "configured model",
+            format!("{} is available", model),
+        ));
+    } else {
+        checks.push(CheckResult::fail(
+            "configured model",
+            format!("{} is not available from {}", model, provider_type),
+            model_fix(provider_type, model),
+        ));
+    }
+
+    checks
+}
+
+fn auth_fix(provider_type: &str) -> String {
+    match provider_type {
+        "copilot" => "Run `xzatoma auth --provider copilot`".to_string(),
+        "openai" => "Set provider.openai.api_key or XZATOMA_OPENAI_API_KEY".to_string(),
+        other => format!("Check the provider.{} settings", other),
+    }
+}
+
+fn reachability_fix(provider_type: &str) -> String {
+    match provider_type {
+        "ollama" => "Start Ollama with `ollama serve` or set provider.ollama.host".to_string(),
+        "openai" => "Check provider.openai.base_url and your network connection".to_string(),
+        "copilot" => {
+            "Check your network connection, then run `xzatoma auth --provider copilot`".to_string()
+        }
+        other => format!("Check the provider.{} settings", other),
+    }
+}
+
+fn model_fix(provider_type: &str, model: &str) -> String {
+    match provider_type {
+        "ollama" => format!("Run `ollama pull {}`", model),
+        other => format!(
+            "Pick a model from `xzatoma models list` and set provider.{}.model",
+            other
+        ),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Keyring and storage
+// ---------------------------------------------------------------------------
+
+/// Check that the system keyring can be read
+///
+/// A failure only matters for Copilot, which stores its token there,
+/// unless the encrypted credentials file can take its place. A keyring that
+/// hangs on an unlock prompt fails after `credentials.keyring_timeout_seconds`.
+async fn check_keyring(config: &Config) -> Vec<CheckResult> {
+    let required = config.provider.provider_type == "copilot";
+    let credentials = config.credentials.clone();
+    blocking("keyring", move || {
+        let file_available = credentials.allow_plaintext
+            || std::env::var(CREDENTIALS_KEY_ENV).is_ok_and(|key| !key.is_empty());
+        if credentials.backend == CredentialBackend::File {
+            let check = if file_available {
+                CheckResult::pass("credentials", "credentials are stored in the credentials file")
+            } else {
+                CheckResult::fail(
+                    "credentials",
+                    format!(
+                        "credentials.backend is 'file' but {} is not set",
+                        CREDENTIALS_KEY_ENV
+                    ),
+                    format!(
+                        "Export {} or set credentials.allow_plaintext: true",
+                        CREDENTIALS_KEY_ENV
+                    ),
+                )
+            };
+            return vec![check];
+        }
+
+        let result =
+            GuardedKeyring::system(&credentials).get(KEYRING_SERVICE, KEYRING_COPILOT_USER);
+        let check = match result {
+            Ok(_) => {
+                CheckResult::pass("keyring", "system keyring is accessible")
+            }
+            Err(e) if file_available && credentials.backend == CredentialBackend::Auto => {
+                CheckResult::pass(
+                    "keyring",
+                    format!(
+                        "system keyring is not accessible ({}); using the credentials file",
+                        e
+                    ),
+                )
+            }
+            Err(e) if required => CheckResult::fail(
+                "keyring",
+                format!("system keyring is not accessible: {}", e),
+                format!(
+                    "Unlock or install a keyring service, or export {} to store the Copilot token in an encrypted file",
+                    CREDENTIALS_KEY_ENV
+                ),
+            ),
+            Err(e) => CheckResult::warn(
+                "keyring",
+                format!("system keyring is not accessible: {}", e),
+                "Unlock or install a keyring service before using Copilot",
+            ),
+        };
+        vec![check]
+    })
+    .await
+}
+
+/// Check the data directory and the history database
+async fn check_storage(paths_config: PathsConfig) -> Vec<CheckResult> {
+    blocking("storage", move || {
+        let db_path = match Paths::resolve(&paths_config) {
+            Ok(paths) => SqliteStorage::default_database_path(&paths),
+            Err(e) => {
+                return vec![CheckResult::fail(
+                    "data directory",
+                    e.to_string(),
+                    "Set XZATOMA_DATA_DIR to a writable directory",
+                )]
+            }
+        };
+        let data_dir = db_path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        vec![check_data_dir(&data_dir), check_history_db(&db_path)]
+    })
+    .await
+}
+
+/// Check that a data directory can be created and written to
+pub fn check_data_dir(dir: &Path) -> CheckResult {
+    let probe = dir.join(".xzatoma-doctor-probe");
+    let result = std::fs::create_dir_all(dir)
+        .and_then(|()| std::fs::write(&probe, b"ok"))
+        .and_then(|()| std::fs::remove_file(&probe));
+
+    match result {
+        Ok(()) => CheckResult::pass("data directory", format!("{} is writable", dir.display())),
+        Err(e) => CheckResult::fail(
+            "data directory",
+            format!("{} is not writable: {}", dir.display(), e),
+            format!(
+                "Make {} writable or set XZATOMA_HISTORY_DB to a writable path",
+                dir.display()
+            ),
+        ),
+    }
+}
+
+/// Run SQLite's integrity check on the history database
+pub fn check_history_db(db_path: &Path) -> CheckResult {
+    if !db_path.exists() {
+        return CheckResult::pass(
+            "history database",
+            format!("{} not created yet", db_path.display()),
+        );
+    }
+
+    let fix = format!(
+        "Move {} aside; a new history database is created on the next run",
+        db_path.display()
+    );
+    let storage = match SqliteStorage::new_with_path(db_path) {
+        Ok(storage) => storage,
+        Err(e) => {
+            return CheckResult::fail(
+                "history database",
+                format!("cannot open {}: {}", db_path.display(), e),
+                fix,
+            )
+        }
+    };
+
+    match storage.integrity_check() {
+        Ok(problems) if problems.is_empty() => CheckResult::pass(
+            "history database",
+            format!("{} passed the integrity check", db_path.display()),
+        ),
+        Ok(problems) => CheckResult::fail(
+            "history database",
+            format!(
+                "{} failed the integrity check: {}",
+                db_path.display(),
+                problems.join("; ")
+            ),
+            fix,
+        ),
+        Err(e) => CheckResult::fail(
+            "history database",
+            format!("cannot check {}: {}", db_path.display(), e),
+            fix,
+        ),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// MCP servers
+// ---------------------------------------------------------------------------
+
+/// Connect to every enabled MCP server concurrently
+async fn check_mcp_servers(config: &Config, timeout: Duration) -> Vec<CheckResult> {
+    let servers: Vec<&McpServerConfig> = config
+        .mcp
+        .servers
+        .iter()
+        .filter(|server| server.enabled)
+        .collect();
+    if servers.is_empty() {
+        return vec![CheckResult::pass("mcp servers", "no MCP servers enabled")];
+    }
+
+    let policy = NetworkPolicy::from_config(config);
+    futures::future::join_all(
+        servers
+            .into_iter()
+            .map(|server| check_mcp_server(server.clone(), policy, timeout)),
+    )
+    .await
+}
+
+/// Connect to one MCP server and list its tools
+async fn check_mcp_server(
+    server: McpServerConfig,
+    policy: NetworkPolicy,
+    timeout: Duration,
+) -> CheckResult {
+    let id = server.id.clone();
+    let name = format!("mcp: {}", id);
+    let fix = format!(
+        "Check mcp.servers entry '{}', or set `enabled: false` to skip it",
+        id
+    );
+
+    let mut manager = McpClientManager::new(
+        Arc::new(reqwest::Client::new()),
+        Arc::new(TokenStore::new()),
+    )
+    .with_network_policy(policy);
+    let result = tokio::time::timeout(timeout, manager.connect(server)).await;
+
+    let check = match result {
+        Ok(Ok(())) => {
+            let tools = manager
+                .connected_servers()
+                .iter()
+                .find(|entry| entry.config.id == id)
+                .map(|entry| entry.tools.len())
+                .unwrap_or(0);
+            CheckResult::pass(name, format!("connected, {} tools", tools))
+        }
+        Ok(Err(e)) => CheckResult::fail(name, e.user_message(), fix),
+        Err(_) => CheckResult::fail(
+            name,
+            format!("did not connect within {}s", timeout.as_secs_f64()),
+            fix,
+        ),
+    };
+
+    if let Err(e) = manager.disconnect(&id).await {
+        tracing::debug!("Failed to disconnect MCP server {}: {}", id, e);
+    }
+    check
+}
+
+// ---------------------------------------------------------------------------
+// Programs
+// ---------------------------------------------------------------------------
+
+/// Check that git is installed
+async fn check_git() -> Vec<CheckResult> {
+    let output = tokio::process::Command::new("git")
+        .arg("--version")
+        .kill_on_drop(true)
+        .output()
+        .await;
+
+    let check = match output {
+        Ok(output) if output.status.success() => CheckResult::pass(
+            "git",
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        ),
+        Ok(output) => CheckResult::warn(
+            "git",
+            format!("`git --version` exited with {}", output.status),
+            "Reinstall git; the agent uses it to inspect repositories",
+        ),
+        Err(e) => CheckResult::warn(
+            "git",
+            format!("git not found: {}", e),
+            "Install git; the agent uses it to inspect repositories",
+        ),
+    };
+    vec![check]
+}
+
+/// Check what the terminal tool needs to run commands
+///
+/// The terminal tool runs commands in the current directory and stops timed
+/// out commands with `kill` on Unix and `taskkill` on Windows.
+async fn check_terminal() -> Vec<CheckResult> {
+    let mut checks = Vec::new();
+
+    match std::env::current_dir() {
+        Ok(dir) => checks.push(CheckResult::pass(
+            "working directory",
+            format!("{} is accessible", dir.display()),
+        )),
+        Err(e) => checks.push(CheckResult::fail(
+            "working directory",
+            format!("current directory is not accessible: {}", e),
+            "Run xzatoma from an existing directory you can read",
+        )),
+    }
+
+    let killer = if cfg!(windows) { "taskkill" } else { "kill" };
+    match find_in_path(killer) {
+        Some(path) => checks.push(CheckResult::pass(
+            "terminal tool",
+            format!("{} found at {}", killer, path.display()),
+        )),
+        None => checks.push(CheckResult::warn(
+            "terminal tool",
+            format!("{} not found in PATH", killer),
+            format!(
+                "Install {} so timed out terminal commands can be stopped",
+                killer
+            ),
+        )),
+    }
+
+    checks
+}
+
+/// Find an executable in the directories listed in `PATH`
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path).find_map(|dir| {
+        let candidate = dir.join(program);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        let candidate = dir.join(format!("{}.exe", program));
+        candidate.is_file().then_some(candidate)
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Output
+// ---------------------------------------------------------------------------
+
+fn print_report(report: &DoctorReport) {
+    ui_println!("\nXZatoma doctor\n");
+
+    let width = report
+        .checks
+        .iter()
+        .map(|check| check.name.len())
+        .max()
+        .unwrap_or(0);
+    for check in &report.checks {
+        let label = match check.status {
+            CheckStatus::Pass => "PASS".green(),
+            CheckStatus::Warn => "WARN".yellow(),
+            CheckStatus::Fail => "FAIL".red(),
+        };
+        ui_println!(
+            "  {}  {:width$}  {}",
+            label,
+            check.name,
+            check.message,
+            width = width
+        );
+        if let Some(fix) = &check.fix {
+            ui_println!(
+                "        {:width$}  {} {}",
+                "",
+                "fix:".dimmed(),
+                fix,
+                width = width
+            );
+        }
+    }
+
+    ui_println!(
+        "\n{} passed, {} warnings, {} failed\n",
+        report.count(CheckStatus::Pass),
+        report.count(CheckStatus::Warn),
+        report.count(CheckStatus::Fail)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::ModelInfo;
+    use crate::testing::MockProvider;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    /// Provider that lists `names` as its models
+    fn listing(names: &[&str]) -> MockProvider {
+        MockProvider::new().with_models(
+            names
+                .iter()
+                .map(|name| ModelInfo::new(*name, *name, 8192))
+                .collect(),
+        )
+    }
+
+    fn statuses(checks: &[CheckResult]) -> Vec<(&str, CheckStatus)> {
+        checks
+            .iter()
+            .map(|check| (check.name.as_str(), check.status))
+            .collect()
+    }
+
+    fn doctor_cli() -> Cli {
+        Cli::try_parse_from(["xzatoma", "doctor"]).unwrap()
+    }
+
+    #[test]
+    fn test_check_config_valid_file_passes() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(
+            &path,
+            "provider:\n  type: ollama\nagent:\n  max_turns: 10\n",
+        )
+        .unwrap();
+
+        let (checks, config) = check_config(&path, &doctor_cli());
+        assert_eq!(
+            statuses(&checks),
+            vec![
+                ("config file", CheckStatus::Pass),
+                ("config validation", CheckStatus::Pass)
+            ]
+        );
+        assert_eq!(config.agent.max_turns, 10);
+    }
+
+    #[test]
+    fn test_check_config_unknown_keys_warn() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(&path, "provider:\n  type: ollama\nagent:\n  max_turn: 10\n").unwrap();
+
+        let (checks, _) = check_config(&path, &doctor_cli());
+        assert_eq!(checks[0].status, CheckStatus::Warn);
+        assert!(checks[0].message.contains("agent.max_turn"));
+        assert!(checks[0].fix.is_some());
+    }
+
+    #[test]
+    fn test_check_config_invalid_yaml_fails() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(&path, "provider: [unclosed\n").unwrap();
+
+        let (checks, config) = check_config(&path, &doctor_cli());
+        assert_eq!(statuses(&checks), vec![("config file", CheckStatus::Fail)]);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_check_config_missing_file_warns() {
+        let dir = TempDir::new().unwrap();
+        let (checks, _) = check_config(&dir.path().join("missing.yaml"), &doctor_cli());
+        assert_eq!(checks[0].status, CheckStatus::Warn);
+        assert!(checks[0].message.contains("not found"));
+    }
+
+    #[test]
+    fn test_check_history_db_passes_for_missing_and_healthy_database() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("history.db");
+        assert_eq!(check_history_db(&db_path).status, CheckStatus::Pass);
+
+        SqliteStorage::new_with_path(&db_path).unwrap();
+        let check = check_history_db(&db_path);
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert!(check.message.contains("integrity check"));
+    }
+
+    #[test]
+    fn test_check_history_db_fails_for_corrupt_database() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("history.db");
+        std::fs::write(&db_path, b"this is not a sqlite database at all").unwrap();
+
+        let check = check_history_db(&db_path);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.fix.unwrap().contains("Move"));
+    }
+
+    #[test]
+    fn test_check_data_dir_creates_writable_directory() {
+        let dir = TempDir::new().unwrap();
+        let data_dir = dir.path().join("data");
+        assert_eq!(check_data_dir(&data_dir).status, CheckStatus::Pass);
+        assert!(data_dir.is_dir());
+        assert!(!data_dir.join(".xzatoma-doctor-probe").exists());
+    }
+
+    #[tokio::test]
+    async fn test_check_provider_reports_available_model() {
+        let provider = listing(&["gpt-4o", "gpt-4o-mini"]);
+        let checks = check_provider(&provider, "openai", "gpt-4o-mini").await;
+        assert_eq!(
+            statuses(&checks),
+            vec![
+                ("provider auth", CheckStatus::Pass),
+                ("provider reachability", CheckStatus::Pass),
+                ("configured model", CheckStatus::Pass)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_provider_missing_model_suggests_pull_for_ollama() {
+        let provider = listing(&["llama3.2:latest"]);
+        let checks = check_provider(&provider, "ollama", "qwen3:8b").await;
+        assert_eq!(
+            statuses(&checks),
+            vec![
+                ("provider reachability", CheckStatus::Pass),
+                ("configured model", CheckStatus::Fail)
+            ]
+        );
+        assert_eq!(checks[1].fix.as_deref(), Some("Run `ollama pull qwen3:8b`"));
+    }
+
+    #[tokio::test]
+    async fn test_check_provider_unauthenticated_skips_network() {
+        let provider = MockProvider::new()
+            .with_authenticated(false)
+            .with_model_list_error("must not be called");
+        let checks = check_provider(&provider, "copilot", "gpt-5-mini").await;
+        assert_eq!(
+            statuses(&checks),
+            vec![("provider auth", CheckStatus::Fail)]
+        );
+        assert!(checks[0].fix.as_deref().unwrap().contains("xzatoma auth"));
+    }
+
+    #[tokio::test]
+    async fn test_check_provider_unreachable_fails() {
+        let provider = MockProvider::new().with_model_list_error("connection refused");
+        let checks = check_provider(&provider, "openai", "gpt-4o").await;
+        assert_eq!(checks[1].name, "provider reachability");
+        assert_eq!(checks[1].status, CheckStatus::Fail);
+    }
+
+    #[tokio::test]
+    async fn test_with_timeout_reports_hung_check() {
+        let checks = with_timeout("mcp: slow", Duration::from_millis(20), async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            vec![CheckResult::pass("mcp: slow", "unreachable")]
+        })
+        .await;
+        assert_eq!(statuses(&checks), vec![("mcp: slow", CheckStatus::Fail)]);
+    }
+
+    #[test]
+    fn test_report_failures_ignore_warnings() {
+        let mut report = DoctorReport {
+            checks: vec![
+                CheckResult::pass("a", "ok"),
+                CheckResult::warn("b", "odd", "fix b"),
+            ],
+        };
+        assert!(!report.has_failures());
+
+        report
+            .checks
+            .push(CheckResult::fail("c", "broken", "fix c"));
+        assert!(report.has_failures());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["checks"][0]["status"], "pass");
+        assert!(json["checks"][0].get("fix").is_none());
+        assert_eq!(json["checks"][2]["fix"], "fix c");
+    }
+}
@@ -19,7 +19,12 @@ use crate::config::Config;
 use crate::error::{Result, XzatomaError};
 use crate::mcp::manager::{build_mcp_manager_from_config, McpClientManager};
 use crate::mcp::tool_bridge::register_mcp_tools;
+use crate::network_policy::NetworkPolicy;
+use crate::paths::Paths;
+use crate::read_only::ReadOnlyPolicy;
 use crate::skills::ActiveSkillRegistry;
+use crate::tools::audit_log::AuditLog;
+use crate::tools::confirmation::ConfirmationPolicy;
 use crate::tools::registry_builder::ToolRegistryBuilder;
 use crate::tools::ToolRegistry;
 
@@ -53,7 +58,8 @@ pub struct AgentEnvironment {
     /// Live MCP client manager shared across async tasks, or `None` when MCP
     /// auto-connect is disabled or no servers are configured.
     pub mcp_manager: Option<Arc<RwLock<McpClientManager>>>,
-    /// Chat mode derived from `config.agent.chat.default_mode`.
+    /// Chat mode derived from `config.agent.chat.default_mode`; always
+    /// Planning in read-only mode.
     pub chat_mode: ChatMode,
     /// Safety mode derived from `config.agent.chat.default_safety`.
     pub safety_mode: SafetyMode,
@@ -117,12 +123,12 @@ pub async fn build_agent_environment(
     headless: bool,
 ) -> Result<AgentEnvironment> {
     // 1. Parse chat mode and safety mode from config.
-    let chat_mode =
-        ChatMode::parse_str(&config.agent.chat.default_mode).unwrap_or(ChatMode::Planning);
-    let safety_mode = match config.agent.chat.default_safety.to_lowercase().as_str() {
-        "yolo" => SafetyMode::NeverConfirm,
-        _ => SafetyMode::AlwaysConfirm,
-    };
+    let read_only = ReadOnlyPolicy::from_config(config);
+    let chat_mode = read_only.effective_mode(
+        ChatMode::parse_str(&config.agent.chat.default_mode).unwrap_or(ChatMode::Planning),
+    );
+    let safety_mode = SafetyMode::parse_str(&config.agent.chat.default_safety)
+        .unwrap_or(SafetyMode::AlwaysConfirm);
 
     // 2. Build startup skill disclosure text.
     let skill_disclosure = super::build_startup_skill_disclosure(config, working_dir)?;
@@ -136,6 +142,15 @@ pub async fn build_agent_environment(
         ToolRegistryBuilder::new(chat_mode, safety_mode, working_dir.to_path_buf())
             .with_tools_config(config.agent.tools.clone())
             .with_terminal_config(config.agent.terminal.clone())
+            .with_network_policy(NetworkPolicy::from_config(config))
+            .with_read_only_policy(read_only)
+            .with_audit_log(AuditLog::from_config(
+                &config.agent.tools,
+                &Paths::from_config(config)?,
+            ))
+            .with_confirmation(
+                ConfirmationPolicy::new(safety_mode).with_chat_config(&config.agent.chat),
+            )
             .build()?;
 
     // 5. Register activate_skill tool.
@@ -1,84 +1,997 @@
 use crate::cli::HistoryCommand;
+use crate::config::Config;
 use crate::error::{Result, XzatomaError};
+use crate::format::{format_bytes, format_count, format_duration, raw_numbers};
+use crate::paths::Paths;
 use crate::providers::Message;
+use crate::storage::import::{import_exports, FileImportSummary, ImportFormat};
+use crate::storage::types::{HistoryFilter, HistoryStats, PeriodUsage, PruneReport, StoredSession};
 use crate::storage::SqliteStorage;
+use crate::{ui, ui_eprint, ui_eprintln, ui_println};
+use chrono::{DateTime, Local, SecondsFormat, TimeZone, Utc};
 use colored::Colorize;
 use prettytable::{format, Table};
+use rustyline::completion::Completer;
+use rustyline::config::Behavior;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::{Hint, Hinter};
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{
+    Cmd, ConditionalEventHandler, Editor, Event, EventContext, EventHandler, Helper, KeyCode,
+    KeyEvent, Modifiers, RepeatCount,
+};
+use std::io::{BufRead, IsTerminal, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// Handle history commands
-pub fn handle_history(command: HistoryCommand) -> Result<()> {
+pub fn handle_history(config: &Config, command: HistoryCommand) -> Result<()> {
     // Initialize storage
-    // Note: We use the default location. If we need custom paths, we'd need to thread config here.
-    let storage = SqliteStorage::new()?;
-    handle_history_with_storage(&storage, command)
+    let storage = SqliteStorage::new(&Paths::from_config(config)?)?;
+    handle_history_with_storage(&storage, config, command)
 }
 
 /// Helper that performs history operations using a provided storage instance.
 ///
 /// This is intentionally separate from `handle_history(...)` so the behavior
 /// can be tested by passing a test-local `SqliteStorage` (e.g., via `new_with_path`).
-fn handle_history_with_storage(storage: &SqliteStorage, command: HistoryCommand) -> Result<()> {
+fn handle_history_with_storage(
+    storage: &SqliteStorage,
+    config: &Config,
+    command: HistoryCommand,
+) -> Result<()> {
     match command {
-        HistoryCommand::List => {
-            let sessions = storage.list_sessions()?;
-
-            if sessions.is_empty() {
-                println!("{}", "No conversation history found.".yellow());
-                return Ok(());
+        HistoryCommand::List {
+            tags,
+            utc,
+            iso,
+            limit,
+            offset,
+        } => {
+            if limit == 0 {
+                return Err(XzatomaError::Config(
+                    "History list limit must be greater than 0".to_string(),
+                ));
             }
+            let page = storage.list_sessions_page(&tags, limit, offset)?;
 
-            let mut table = Table::new();
-            table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
-
-            table.add_row(prettytable::row![
-                "ID".bold(),
-                "Title".bold(),
-                "Model".bold(),
-                "Messages".bold(),
-                "Last Updated".bold()
-            ]);
-
-            for session in sessions {
-                let id_short = &session.id[..8];
-                let title = if session.title.len() > 40 {
-                    format!("{}...", &session.title[..37])
+            if page.sessions.is_empty() {
+                if page.total > 0 {
+                    ui_println!(
+                        "{}",
+                        format!(
+                            "No conversations past offset {} ({} in total).",
+                            offset, page.total
+                        )
+                        .yellow()
+                    );
+                } else if tags.is_empty() {
+                    ui_println!("{}", "No conversation history found.".yellow());
                 } else {
-                    session.title
-                };
-                let model = session.model.unwrap_or_else(|| "-".to_string());
-                let updated = session.updated_at.format("%Y-%m-%d %H:%M").to_string();
-
-                table.add_row(prettytable::row![
-                    id_short.cyan(),
-                    title,
-                    model,
-                    session.message_count,
-                    updated
-                ]);
+                    ui_println!(
+                        "{}",
+                        format!("No conversations tagged {}.", tags.join(", ")).yellow()
+                    );
+                }
+                return Ok(());
             }
 
-            println!("\nConversation History:");
-            table.printstd();
-            println!();
-            println!(
+            let remaining = page.remaining();
+            ui_println!("\nConversation History:");
+            print_sessions_table(page.sessions, TimestampStyle::from_flags(utc, iso));
+            if remaining > 0 {
+                ui_println!(
+                    "{}",
+                    format!(
+                        "... and {} more (use --limit/--offset or history search)",
+                        remaining
+                    )
+                    .dimmed()
+                );
+            }
+            ui_println!();
+            ui_println!(
                 "Use {} to resume a session.",
                 "xzatoma chat --resume <ID>".cyan()
             );
-            println!();
+            ui_println!();
+        }
+        HistoryCommand::Pick { tags } => {
+            let sessions = storage.list_sessions_with_tags(&tags)?;
+            if sessions.is_empty() {
+                return Err(XzatomaError::Command(
+                    "No conversations to pick from".to_string(),
+                ));
+            }
+
+            let id = if std::io::stdin().is_terminal() {
+                pick_interactive(&sessions)?
+            } else {
+                pick_from_list(
+                    &sessions,
+                    &mut std::io::stdin().lock(),
+                    &mut std::io::stderr(),
+                )?
+            };
+            ui::data(id);
+        }
+        HistoryCommand::Search {
+            query,
+            tags,
+            utc,
+            iso,
+        } => {
+            let sessions = storage.search_sessions(&query, &tags)?;
+
+            if sessions.is_empty() {
+                ui_println!(
+                    "{}",
+                    format!("No conversations match '{}'.", query).yellow()
+                );
+                return Ok(());
+            }
+
+            ui_println!("\nConversations matching '{}':", query);
+            print_sessions_table(sessions, TimestampStyle::from_flags(utc, iso));
+            ui_println!();
         }
         HistoryCommand::Show { id, raw, limit } => {
             show_conversation(storage, &id, raw, limit)?;
         }
-        HistoryCommand::Delete { id } => {
+        HistoryCommand::Delete { id: Some(id), .. } => {
             // Delete is idempotent; report to user for feedback.
             storage.delete_conversation(&id)?;
-            println!("{}", format!("Deleted conversation {}", id).green());
+            ui_println!("{}", format!("Deleted conversation {}", id).green());
+        }
+        HistoryCommand::Delete {
+            id: None,
+            older_than,
+            tags,
+            untitled,
+            model,
+            yes,
+        } => {
+            let filter = HistoryFilter {
+                older_than: older_than
+                    .as_deref()
+                    .map(parse_age)
+                    .transpose()?
+                    .map(|age| Utc::now() - age),
+                tags,
+                untitled,
+                model,
+            };
+            delete_matching(storage, &filter, yes)?;
+        }
+        HistoryCommand::Backup { dir } => {
+            let manifest = storage.backup_conversations(&dir)?;
+            ui_println!(
+                "{}",
+                format!(
+                    "Backed up {} conversation(s) to {}",
+                    manifest.conversations.len(),
+                    dir.display()
+                )
+                .green()
+            );
+        }
+        HistoryCommand::Import {
+            path,
+            format,
+            dry_run,
+        } => {
+            let format = ImportFormat::parse(&format)?;
+            if format == ImportFormat::Native {
+                let report = storage.import_conversations(&path, dry_run)?;
+                let verb = if dry_run { "Would import" } else { "Imported" };
+                ui_println!(
+                    "{}",
+                    format!(
+                        "{} {} conversation(s) from {}",
+                        verb,
+                        report.imported.len(),
+                        path.display()
+                    )
+                    .green()
+                );
+                if !report.skipped.is_empty() {
+                    ui_println!(
+                        "{}",
+                        format!(
+                            "Skipped {} conversation(s) that already exist: {}",
+                            report.skipped.len(),
+                            report.skipped.join(", ")
+                        )
+                        .yellow()
+                    );
+                }
+            } else {
+                let summaries = import_exports(storage, format, &path, dry_run)?;
+                print_import_summaries(format, &summaries, dry_run);
+            }
+        }
+        HistoryCommand::Tag { id, tag } => {
+            let tag = storage.add_conversation_tag(&id, &tag)?;
+            ui_println!(
+                "{}",
+                format!("Tagged conversation {} with '{}'", id, tag).green()
+            );
+        }
+        HistoryCommand::Untag { id, tag } => {
+            if storage.remove_conversation_tag(&id, &tag)? {
+                ui_println!(
+                    "{}",
+                    format!("Removed tag '{}' from conversation {}", tag.trim(), id).green()
+                );
+            } else {
+                ui_println!(
+                    "{}",
+                    format!("Conversation {} is not tagged '{}'", id, tag.trim()).yellow()
+                );
+            }
+        }
+        HistoryCommand::Prune { dry_run } => {
+            let retention = &config.storage.retention;
+            if !retention.is_enabled() {
+                ui_println!(
+                    "{}",
+                    "No retention limits configured under storage.retention; nothing to prune."
+                        .yellow()
+                );
+                return Ok(());
+            }
+
+            let report = storage.prune_old_sessions(retention, dry_run)?;
+            print_prune_report(&report);
+        }
+        HistoryCommand::Pin { id } => {
+            set_pinned(storage, &id, true)?;
+            ui_println!("{}", format!("Pinned conversation {}", id).green());
+        }
+        HistoryCommand::Unpin { id } => {
+            set_pinned(storage, &id, false)?;
+            ui_println!("{}", format!("Unpinned conversation {}", id).green());
+        }
+        HistoryCommand::Stats { since, limit, json } => {
+            let since = since
+                .as_deref()
+                .map(parse_age)
+                .transpose()?
+                .map(|age| Utc::now() - age);
+            let stats = storage.history_stats(since, limit)?;
+
+            if json {
+                ui::json(&stats)?;
+            } else {
+                print_stats(&stats);
+            }
         }
     }
 
     Ok(())
 }
 
+/// How session timestamps are rendered in listings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimestampStyle {
+    /// Compact relative form in the local timezone, chosen by age
+    Relative,
+    /// Exact time in UTC
+    Utc,
+    /// RFC 3339 in the local timezone
+    IsoLocal,
+    /// RFC 3339 in UTC
+    IsoUtc,
+}
+
+impl TimestampStyle {
+    fn from_flags(utc: bool, iso: bool) -> Self {
+        match (utc, iso) {
+            (false, false) => Self::Relative,
+            (true, false) => Self::Utc,
+            (false, true) => Self::IsoLocal,
+            (true, true) => Self::IsoUtc,
+        }
+    }
+
+    /// Format a stored UTC timestamp for display
+    fn format(self, timestamp: &DateTime<Utc>) -> String {
+        match self {
+            Self::Relative => {
+                format_relative_timestamp(&timestamp.with_timezone(&Local), &Local::now())
+            }
+            Self::Utc => timestamp.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            Self::IsoLocal => timestamp
+                .with_timezone(&Local)
+                .to_rfc3339_opts(SecondsFormat::Secs, false),
+            Self::IsoUtc => timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+/// Format `timestamp` relative to `now`, picking the form by age
+///
+/// - under a minute: "just now"
+/// - under an hour: "8 minutes ago"
+/// - earlier the same day: "3 hours ago"
+/// - the previous day: "yesterday 14:32"
+/// - within the last week: "Mon 14:32"
+/// - older, or in the future: "2024-12-03"
+///
+/// The storage sentinel for unparseable dates (the Unix epoch) is shown as
+/// "unknown".
+fn format_relative_timestamp<Tz: TimeZone>(timestamp: &DateTime<Tz>, now: &DateTime<Tz>) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    if timestamp.timestamp() == 0 {
+        return "unknown".to_string();
+    }
+
+    let age = now.clone().signed_duration_since(timestamp.clone());
+    if age < chrono::Duration::zero() {
+        // Clock skew of a few seconds still reads as "just now"
+        if age > -chrono::Duration::minutes(1) {
+            return "just now".to_string();
+        }
+        return timestamp.format("%Y-%m-%d").to_string();
+    }
+
+    let days_apart = (now.date_naive() - timestamp.date_naive()).num_days();
+    if age < chrono::Duration::minutes(1) {
+        "just now".to_string()
+    } else if age < chrono::Duration::hours(1) {
+        let minutes = age.num_minutes();
+        format!(
+            "{} minute{} ago",
+            minutes,
+            if minutes == 1 { "" } else { "s" }
+        )
+    } else if days_apart == 0 {
+        let hours = age.num_hours();
+        format!("{} hour{} ago", hours, if hours == 1 { "" } else { "s" })
+    } else if days_apart == 1 {
+        timestamp.format("yesterday %H:%M").to_string()
+    } else if days_apart < 7 {
+        timestamp.format("%a %H:%M").to_string()
+    } else {
+        timestamp.format("%Y-%m-%d").to_string()
+    }
+}
+
+/// Print conversation summaries as a table
+fn print_sessions_table(sessions: Vec<StoredSession>, style: TimestampStyle) {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+
+    table.add_row(prettytable::row![
+        "ID".bold(),
+        "Title".bold(),
+        "Model".bold(),
+        "Messages".bold(),
+        "Created".bold(),
+        "Last Updated".bold(),
+        "Pinned".bold(),
+        "Tags".bold()
+    ]);
+
+    for session in sessions {
+        let id_short: String = session.id.chars().take(8).collect();
+        let title = if session.title.len() > 40 {
+            format!("{}...", &session.title[..37])
+        } else {
+            session.title
+        };
+        let model = session.model.unwrap_or_else(|| "-".to_string());
+        let created = style.format(&session.created_at);
+        let updated = style.format(&session.updated_at);
+        let pinned = if session.pinned { "yes" } else { "" };
+
+        table.add_row(prettytable::row![
+            id_short.cyan(),
+            title,
+            model,
+            session.message_count,
+            created,
+            updated,
+            pinned,
+            session.tags.join(", ")
+        ]);
+    }
+
+    table.printstd();
+}
+
+/// Minimum Jaro-Winkler similarity for a title to match a pick filter
+const FUZZY_PICK_THRESHOLD: f64 = 0.8;
+
+/// Sessions matching a pick filter, best match first
+///
+/// Titles and IDs containing the filter (case-insensitively) come first, in
+/// listing order. When nothing contains it, titles are ranked by
+/// Jaro-Winkler similarity so a small typo still finds the conversation.
+fn match_sessions<'a>(sessions: &'a [StoredSession], query: &str) -> Vec<&'a StoredSession> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return sessions.iter().collect();
+    }
+
+    let contained: Vec<&StoredSession> = sessions
+        .iter()
+        .filter(|session| {
+            session.title.to_lowercase().contains(&query)
+                || session.id.to_lowercase().contains(&query)
+        })
+        .collect();
+    if !contained.is_empty() {
+        return contained;
+    }
+
+    let mut scored: Vec<(f64, &StoredSession)> = sessions
+        .iter()
+        .map(|session| {
+            let score = strsim::jaro_winkler(&query, &session.title.to_lowercase());
+            (score, session)
+        })
+        .filter(|(score, _)| *score >= FUZZY_PICK_THRESHOLD)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().map(|(_, session)| session).collect()
+}
+
+/// One-line description of a session in the picker
+fn pick_label(session: &StoredSession) -> String {
+    let id_short: String = session.id.chars().take(8).collect();
+    format!(
+        "{} ({}, {})",
+        session.title,
+        id_short,
+        TimestampStyle::Relative.format(&session.updated_at)
+    )
+}
+
+/// Pick a session from a numbered list when stdin is not a terminal
+///
+/// The list is written to `output`, then one line is read from `input`. A
+/// list number picks that session; any other answer is used as a filter
+/// that must match exactly one session.
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Cancelled` when the answer is empty, and
+/// `XzatomaError::Command` when it matches no session or several.
+fn pick_from_list<R: BufRead, W: Write>(
+    sessions: &[StoredSession],
+    input: &mut R,
+    output: &mut W,
+) -> Result<String> {
+    for (index, session) in sessions.iter().enumerate() {
+        writeln!(output, "{:>3}) {}", index + 1, pick_label(session))?;
+    }
+    write!(output, "Select a conversation [1-{}]: ", sessions.len())?;
+    output.flush()?;
+
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    let answer = line.trim();
+    if answer.is_empty() {
+        return Err(XzatomaError::Cancelled);
+    }
+
+    if let Ok(number) = answer.parse::<usize>() {
+        return number
+            .checked_sub(1)
+            .and_then(|index| sessions.get(index))
+            .map(|session| session.id.clone())
+            .ok_or_else(|| {
+                XzatomaError::Command(format!("Choose a number between 1 and {}", sessions.len()))
+            });
+    }
+
+    match match_sessions(sessions, answer).as_slice() {
+        [session] => Ok(session.id.clone()),
+        [] => Err(XzatomaError::Command(format!(
+            "No conversation matches '{}'",
+            answer
+        ))),
+        matches => Err(XzatomaError::Command(format!(
+            "'{}' matches {} conversations; be more specific",
+            answer,
+            matches.len()
+        ))),
+    }
+}
+
+/// Hint shown after the picker's filter; never inserted into the line
+struct PickHint(String);
+
+impl Hint for PickHint {
+    fn display(&self) -> &str {
+        &self.0
+    }
+
+    fn completion(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Line-editor helper that shows the selected match as a hint
+///
+/// Up and Down move `selected`; editing the filter resets it to the best
+/// match.
+struct PickHelper {
+    sessions: Vec<StoredSession>,
+    selected: Arc<AtomicUsize>,
+    last_query: Mutex<String>,
+}
+
+impl PickHelper {
+    /// Index of the selected match for `query`, clamped to the match count
+    fn selection(&self, query: &str, matches: usize) -> usize {
+        let mut last_query = self
+            .last_query
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if *last_query != query {
+            *last_query = query.to_string();
+            self.selected.store(0, Ordering::Relaxed);
+        }
+        let index = self
+            .selected
+            .load(Ordering::Relaxed)
+            .min(matches.saturating_sub(1));
+        self.selected.store(index, Ordering::Relaxed);
+        index
+    }
+}
+
+impl Hinter for PickHelper {
+    type Hint = PickHint;
+
+    fn hint(&self, line: &str, _pos: usize, _ctx: &rustyline::Context<'_>) -> Option<PickHint> {
+        let matches = match_sessions(&self.sessions, line);
+        if matches.is_empty() {
+            return Some(PickHint("  (no matches)".to_string()));
+        }
+        let index = self.selection(line, matches.len());
+        Some(PickHint(format!(
+            "  [{}/{}] {}",
+            index + 1,
+            matches.len(),
+            pick_label(matches[index])
+        )))
+    }
+}
+
+impl Completer for PickHelper {
+    type Candidate = String;
+}
+
+impl Highlighter for PickHelper {}
+
+impl Validator for PickHelper {}
+
+impl Helper for PickHelper {}
+
+/// Key handler that moves the picker selection and repaints the hint
+struct MoveSelection {
+    selected: Arc<AtomicUsize>,
+    down: bool,
+}
+
+impl ConditionalEventHandler for MoveSelection {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        _ctx: &EventContext,
+    ) -> Option<Cmd> {
+        if self.down {
+            self.selected.fetch_add(1, Ordering::Relaxed);
+        } else {
+            let _ = self
+                .selected
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |index| {
+                    index.checked_sub(1)
+                });
+        }
+        Some(Cmd::Repaint)
+    }
+}
+
+/// Pick a session interactively on the terminal
+///
+/// The editor talks to the terminal directly rather than stdout, so only
+/// the chosen ID reaches stdout even when it is captured by `$(...)`.
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Cancelled` on Ctrl-C or Ctrl-D, or an error if the
+/// terminal cannot be used.
+fn pick_interactive(sessions: &[StoredSession]) -> Result<String> {
+    let config = rustyline::config::Config::builder()
+        .behavior(Behavior::PreferTerm)
+        .auto_add_history(true)
+        .build();
+    let selected = Arc::new(AtomicUsize::new(0));
+    let mut editor: Editor<PickHelper, DefaultHistory> = Editor::with_config(config)?;
+    editor.set_helper(Some(PickHelper {
+        sessions: sessions.to_vec(),
+        selected: Arc::clone(&selected),
+        last_query: Mutex::new(String::new()),
+    }));
+    for (code, down) in [(KeyCode::Up, false), (KeyCode::Down, true)] {
+        editor.bind_sequence(
+            KeyEvent(code, Modifiers::NONE),
+            EventHandler::Conditional(Box::new(MoveSelection {
+                selected: Arc::clone(&selected),
+                down,
+            })),
+        );
+    }
+
+    ui_eprintln!(
+        "Type to filter {} conversation(s); Up/Down to choose, Enter to pick.",
+        sessions.len()
+    );
+    loop {
+        let query = match editor.readline("pick> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => {
+                return Err(XzatomaError::Cancelled)
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let matches = match_sessions(sessions, &query);
+        if matches.is_empty() {
+            ui_eprintln!(
+                "{}",
+                format!("No conversation matches '{}'", query).yellow()
+            );
+            continue;
+        }
+        let index = editor
+            .helper()
+            .map_or(0, |helper| helper.selection(&query, matches.len()));
+        return Ok(matches[index].id.clone());
+    }
+}
+
+/// Delete every conversation matching `filter` after listing them
+///
+/// Without `yes` the user confirms on the terminal; when stdin is not a
+/// terminal nothing is deleted and `--yes` is required instead.
+fn delete_matching(storage: &SqliteStorage, filter: &HistoryFilter, yes: bool) -> Result<()> {
+    if filter.is_empty() {
+        return Err(XzatomaError::Command(
+            "Refusing to delete every conversation; pass --id or at least one filter".to_string(),
+        ));
+    }
+
+    let sessions = storage.find_sessions(filter)?;
+    if sessions.is_empty() {
+        ui_println!("{}", "No conversations match the filters.".yellow());
+        return Ok(());
+    }
+
+    ui_println!("\n{} conversation(s) match:", sessions.len());
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+    table.add_row(prettytable::row![
+        "ID".bold(),
+        "Title".bold(),
+        "Model".bold(),
+        "Last Updated".bold()
+    ]);
+    for session in &sessions {
+        let id_short: String = session.id.chars().take(8).collect();
+        table.add_row(prettytable::row![
+            id_short.cyan(),
+            session.title,
+            session.model.as_deref().unwrap_or("-"),
+            session.updated_at.format("%Y-%m-%d %H:%M").to_string()
+        ]);
+    }
+    table.printstd();
+
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            return Err(XzatomaError::Command(format!(
+                "Refusing to delete {} conversation(s) without confirmation; pass --yes",
+                sessions.len()
+            )));
+        }
+        ui_eprint!("Delete {} conversation(s)? [y/N] ", sessions.len());
+        let _ = std::io::stderr().flush();
+
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        let answer = line.trim().to_lowercase();
+        if answer != "y" && answer != "yes" {
+            ui_println!("{}", "Nothing deleted.".yellow());
+            return Ok(());
+        }
+    }
+
+    let ids: Vec<String> = sessions.into_iter().map(|session| session.id).collect();
+    let deleted = storage.delete_conversations(&ids)?;
+    ui_println!("{}", format!("Deleted {} conversation(s)", deleted).green());
+
+    Ok(())
+}
+
+/// Update the pin state of a conversation, failing when it does not exist
+fn set_pinned(storage: &SqliteStorage, id: &str, pinned: bool) -> Result<()> {
+    if storage.set_conversation_pinned(id, pinned)? {
+        Ok(())
+    } else {
+        Err(XzatomaError::Storage(format!(
+            "Conversation not found: {}",
+            id
+        )))
+    }
+}
+
+/// Print the sessions selected by a prune pass and the resulting size change
+fn print_prune_report(report: &PruneReport) {
+    if report.removed.is_empty() {
+        ui_println!(
+            "{}",
+            "No conversations exceed the retention limits.".green()
+        );
+        return;
+    }
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+    table.add_row(prettytable::row![
+        "ID".bold(),
+        "Title".bold(),
+        "Last Updated".bold(),
+        "Reason".bold()
+    ]);
+
+    for session in &report.removed {
+        let id_short: String = session.id.chars().take(8).collect();
+        table.add_row(prettytable::row![
+            id_short.cyan(),
+            session.title,
+            session.updated_at.format("%Y-%m-%d %H:%M").to_string(),
+            session.reason.to_string()
+        ]);
+    }
+
+    let heading = if report.dry_run {
+        format!("Would remove {} conversation(s):", report.removed.len())
+    } else {
+        format!("Removed {} conversation(s):", report.removed.len())
+    };
+
+    ui_println!("\n{}", heading);
+    table.printstd();
+    ui_println!(
+        "Database size: {} -> {}{}{}",
+        format_bytes(report.size_before_bytes),
+        format_bytes(report.size_after_bytes),
+        if report.dry_run { " (estimated)" } else { "" },
+        if report.vacuumed { ", vacuumed" } else { "" }
+    );
+    ui_println!();
+}
+
+/// Print one summary per export file imported by `history import --format`
+fn print_import_summaries(format: ImportFormat, summaries: &[FileImportSummary], dry_run: bool) {
+    if summaries.is_empty() {
+        ui_println!("{}", format!("No {} export files found.", format).yellow());
+        return;
+    }
+
+    let verb = if dry_run { "Would import" } else { "Imported" };
+    for summary in summaries {
+        ui_println!("\n{}", summary.path.display().to_string().bold());
+        if let Some(error) = &summary.error {
+            ui_println!("  {}", format!("Could not read file: {}", error).red());
+            continue;
+        }
+        ui_println!(
+            "  {}",
+            format!(
+                "{} {} conversation(s) with {} message(s), tagged '{}'",
+                verb,
+                summary.imported,
+                summary.messages,
+                format.tag()
+            )
+            .green()
+        );
+        if summary.existing > 0 {
+            ui_println!(
+                "  {}",
+                format!(
+                    "Skipped {} conversation(s) already imported",
+                    summary.existing
+                )
+                .yellow()
+            );
+        }
+        for reason in &summary.skipped {
+            ui_println!("  {}", format!("Skipped {}", reason).yellow());
+        }
+        if summary.skipped_messages > 0 {
+            ui_println!(
+                "  {}",
+                format!("Skipped {} malformed message(s)", summary.skipped_messages).yellow()
+            );
+        }
+    }
+    ui_println!();
+}
+
+/// Parse an age such as `90m`, `12h`, `30d`, or `4w` into a duration
+fn parse_age(value: &str) -> Result<chrono::Duration> {
+    let invalid = || {
+        XzatomaError::Config(format!(
+            "Invalid age '{}': expected a number followed by m, h, d, or w (e.g. 30d)",
+            value
+        ))
+    };
+
+    let value = value.trim();
+    let unit = value.chars().last().ok_or_else(invalid)?;
+    let amount: i64 = value[..value.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    if amount < 0 {
+        return Err(invalid());
+    }
+
+    match unit {
+        'm' => Ok(chrono::Duration::minutes(amount)),
+        'h' => Ok(chrono::Duration::hours(amount)),
+        'd' => Ok(chrono::Duration::days(amount)),
+        'w' => Ok(chrono::Duration::weeks(amount)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Print usage statistics as a summary followed by one table per ranking
+fn print_stats(stats: &HistoryStats) {
+    if stats.total_sessions == 0 {
+        ui_println!("{}", "No conversation history found.".yellow());
+        return;
+    }
+
+    match stats.since {
+        Some(since) => ui_println!(
+            "\nUsage since {}:",
+            since.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        ),
+        None => ui_println!("\nUsage:"),
+    }
+    ui_println!(
+        "  Sessions:               {}",
+        format_count(stats.total_sessions)
+    );
+    ui_println!(
+        "  Messages:               {}",
+        format_count(stats.total_messages)
+    );
+    ui_println!("  Messages per session:   {:.1}", stats.average_messages);
+    ui_println!(
+        "  Average session length: {}",
+        format_minutes(stats.average_duration_minutes)
+    );
+
+    ui_println!("\n{}", "Per day".bold());
+    print_period_table(&stats.per_day, "Day");
+
+    ui_println!("\n{}", "Per week".bold());
+    print_period_table(&stats.per_week, "Week");
+
+    ui_println!("\n{}", "Per model".bold());
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+    table.add_row(prettytable::row![
+        "Model".bold(),
+        "Sessions".bold(),
+        "Messages".bold(),
+        "Est. Tokens".bold()
+    ]);
+    for usage in &stats.per_model {
+        table.add_row(prettytable::row![
+            usage.model,
+            r->format_count(usage.sessions),
+            r->format_count(usage.messages),
+            r->format_count(usage.estimated_tokens)
+        ]);
+    }
+    table.printstd();
+
+    ui_println!("\n{}", "Most-used tools".bold());
+    if stats.top_tools.is_empty() {
+        ui_println!("{}", "No tool calls recorded.".dimmed());
+    } else {
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+        table.add_row(prettytable::row!["Tool".bold(), "Calls".bold()]);
+        for usage in &stats.top_tools {
+            table.add_row(prettytable::row![usage.tool, r->format_count(usage.calls)]);
+        }
+        table.printstd();
+    }
+
+    ui_println!("\n{}", "Longest sessions".bold());
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+    table.add_row(prettytable::row![
+        "ID".bold(),
+        "Title".bold(),
+        "Messages".bold(),
+        "Duration".bold()
+    ]);
+    for session in &stats.longest_sessions {
+        let id_short: String = session.id.chars().take(8).collect();
+        let title: String = if session.title.chars().count() > 40 {
+            format!("{}...", session.title.chars().take(37).collect::<String>())
+        } else {
+            session.title.clone()
+        };
+        table.add_row(prettytable::row![
+            id_short.cyan(),
+            title,
+            r->format_count(session.messages),
+            r->format_minutes(session.duration_minutes)
+        ]);
+    }
+    table.printstd();
+    ui_println!();
+}
+
+/// Print session and message counts for a list of days or weeks
+fn print_period_table(usage: &[PeriodUsage], label: &str) {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+    table.add_row(prettytable::row![
+        label.bold(),
+        "Sessions".bold(),
+        "Messages".bold()
+    ]);
+    for period in usage {
+        table.add_row(prettytable::row![
+            period.period,
+            r->format_count(period.sessions),
+            r->format_count(period.messages)
+        ]);
+    }
+    table.printstd();
+}
+
+/// Format a session length given in minutes, e.g. `44m 36s` or `2h 05m`
+///
+/// Empty sessions show as `0s` and sessions shorter than a minute as `<1m`,
+/// rather than as milliseconds.
+fn format_minutes(minutes: f64) -> String {
+    let seconds = if minutes.is_finite() {
+        minutes.max(0.0) * 60.0
+    } else {
+        0.0
+    };
+    if !raw_numbers() {
+        if seconds == 0.0 {
+            return "0s".to_string();
+        }
+        if seconds < 60.0 {
+            return "<1m".to_string();
+        }
+    }
+    format_duration(Duration::from_secs_f64(seconds))
+}
+
 /// Show detailed conversation history
 fn show_conversation(
     storage: &SqliteStorage,
@@ -91,6 +1004,7 @@ fn show_conversation(
 
     let (title, model, messages) = maybe_conv
         .ok_or_else(|| XzatomaError::Config(format!("Conversation not found: {}", id)))?;
+    let replayed_from = storage.load_replayed_from(id)?;
 
     // Apply limit if specified
     let messages_to_display = if let Some(n) = limit {
@@ -106,24 +1020,29 @@ fn show_conversation(
             "id": id,
             "title": title,
             "model": model,
+            "replayed_from": replayed_from,
             "message_count": messages.len(),
             "messages": messages_to_display,
         });
-        println!("{}", serde_json::to_string_pretty(&output)?);
+        ui::json(&output)?;
     } else {
         // Formatted display
-        println!("\n{}", "Conversation: ".bold());
-        println!("{}", title.cyan());
-        println!("{}", "ID: ".bold());
-        println!("{}", id.cyan());
-        println!("{}", "Model: ".bold());
-        println!("{}", model.unwrap_or_else(|| "unknown".to_string()).cyan());
-        println!("{}", "Messages: ".bold());
-        println!("{} total", messages.len());
+        ui_println!("\n{}", "Conversation: ".bold());
+        ui_println!("{}", title.cyan());
+        ui_println!("{}", "ID: ".bold());
+        ui_println!("{}", id.cyan());
+        ui_println!("{}", "Model: ".bold());
+        ui_println!("{}", model.unwrap_or_else(|| "unknown".to_string()).cyan());
+        if let Some(original_id) = &replayed_from {
+            ui_println!("{}", "Replay of: ".bold());
+            ui_println!("{}", original_id.cyan());
+        }
+        ui_println!("{}", "Messages: ".bold());
+        ui_println!("{} total", messages.len());
         if limit.is_some() {
-            println!("Showing: last {} messages", messages_to_display.len());
+            ui_println!("Showing: last {} messages", messages_to_display.len());
         }
-        println!("{}", "=".repeat(80));
+        ui_println!("{}", "=".repeat(80));
 
         for (idx, msg) in messages_to_display.iter().enumerate() {
             let global_idx = if limit.is_some() {
@@ -134,7 +1053,7 @@ fn show_conversation(
 
             print_message(global_idx, msg);
         }
-        println!();
+        ui_println!();
     }
 
     tracing::info!(
@@ -148,19 +1067,19 @@ fn show_conversation(
 
 /// Print a single message in formatted mode
 fn print_message(idx: usize, msg: &Message) {
-    println!("\n{} [{}]", "[MESSAGE]".bold(), idx.to_string().cyan());
-    println!("  {}: {}", "Role".bold(), msg.role.yellow());
+    ui_println!("\n{} [{}]", "[MESSAGE]".bold(), idx.to_string().cyan());
+    ui_println!("  {}: {}", "Role".bold(), msg.role.yellow());
 
     // Show tool_call_id if present (for tool messages)
     if let Some(tool_call_id) = &msg.tool_call_id {
-        println!("  {}: {}", "Tool Call ID".bold(), tool_call_id.magenta());
+        ui_println!("  {}: {}", "Tool Call ID".bold(), tool_call_id.magenta());
     }
 
     // Show tool_calls summary if present (for assistant messages)
     if let Some(tool_calls) = &msg.tool_calls {
-        println!("  {}: {} total", "Tool Calls".bold(), tool_calls.len());
+        ui_println!("  {}: {} total", "Tool Calls".bold(), tool_calls.len());
         for tc in tool_calls {
-            println!("    - {} (id: {})", tc.function.name, tc.id);
+            ui_println!("    - {} (id: {})", tc.function.name, tc.id);
         }
     }
 
@@ -172,9 +1091,9 @@ fn print_message(idx: usize, msg: &Message) {
             content.clone()
         };
 
-        println!("  {}: {}", "Content".bold(), content_preview);
+        ui_println!("  {}: {}", "Content".bold(), content_preview);
     } else {
-        println!("  {}: {}", "Content".bold(), "(no content)".dimmed());
+        ui_println!("  {}: {}", "Content".bold(), "(no content)".dimmed());
     }
 }
 
@@ -261,6 +1180,120 @@ mod tests {
             .is_none());
     }
 
+    #[test]
+    fn test_history_delete_with_filters_removes_only_matching_sessions() {
+        let tmp = tempdir().expect("failed to create tempdir");
+        let storage = SqliteStorage::new_with_path(tmp.path().join("history.db"))
+            .expect("failed to create storage");
+        for (id, title, model) in [
+            ("scratch-gpt", "New Conversation", Some("gpt-4o")),
+            ("scratch-llama", "New Conversation", Some("llama3")),
+            ("scratch-titled", "Release notes", Some("gpt-4o")),
+            ("keep", "New Conversation", Some("gpt-4o")),
+        ] {
+            storage
+                .save_conversation(id, title, model, &[Message::user(id)])
+                .expect("save failed");
+            if id.starts_with("scratch") {
+                storage.add_conversation_tag(id, "scratch").unwrap();
+            }
+        }
+
+        handle_history_with_storage(
+            &storage,
+            &Config::default(),
+            HistoryCommand::Delete {
+                id: None,
+                older_than: None,
+                tags: vec!["scratch".to_string()],
+                untitled: true,
+                model: Some("gpt-4o".to_string()),
+                yes: true,
+            },
+        )
+        .expect("delete failed");
+
+        let mut remaining: Vec<String> = storage
+            .list_sessions()
+            .unwrap()
+            .into_iter()
+            .map(|session| session.id)
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["keep", "scratch-llama", "scratch-titled"]);
+    }
+
+    #[test]
+    fn test_history_delete_with_empty_filter_is_refused() {
+        let tmp = tempdir().expect("failed to create tempdir");
+        let storage = SqliteStorage::new_with_path(tmp.path().join("history.db"))
+            .expect("failed to create storage");
+        storage
+            .save_conversation("keep", "Keep", None, &[Message::user("x")])
+            .expect("save failed");
+
+        let result = delete_matching(&storage, &HistoryFilter::default(), true);
+
+        assert!(result.is_err());
+        assert_eq!(storage.list_sessions().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_history_backup_and_import_restore_sessions() {
+        let tmp = tempdir().expect("failed to create tempdir");
+        let storage = SqliteStorage::new_with_path(tmp.path().join("history.db"))
+            .expect("failed to create storage");
+        let messages = vec![Message::user("Hello"), Message::assistant("Hi there")];
+        storage
+            .save_conversation("session-1", "Greeting", Some("gpt-4o"), &messages)
+            .expect("save failed");
+        let backup_dir = tmp.path().join("backup");
+        let config = Config::default();
+
+        handle_history_with_storage(
+            &storage,
+            &config,
+            HistoryCommand::Backup {
+                dir: backup_dir.clone(),
+            },
+        )
+        .expect("backup failed");
+
+        let restored = SqliteStorage::new_with_path(tmp.path().join("restored.db"))
+            .expect("failed to create storage");
+        handle_history_with_storage(
+            &restored,
+            &config,
+            HistoryCommand::Import {
+                path: backup_dir.clone(),
+                format: "native".to_string(),
+                dry_run: true,
+            },
+        )
+        .expect("dry run failed");
+        assert!(restored.list_sessions().unwrap().is_empty());
+
+        handle_history_with_storage(
+            &restored,
+            &config,
+            HistoryCommand::Import {
+                path: backup_dir,
+                format: "native".to_string(),
+                dry_run: false,
+            },
+        )
+        .expect("import failed");
+
+        let (title, model, restored_messages) =
+            restored.load_conversation("session-1").unwrap().unwrap();
+        assert_eq!(title, "Greeting");
+        assert_eq!(model.as_deref(), Some("gpt-4o"));
+        assert_eq!(
+            serde_json::to_string(&restored_messages).unwrap(),
+            serde_json::to_string(&messages).unwrap()
+        );
+    }
+
     #[test]
     fn test_show_conversation_formatted() {
         let tmp = tempdir().expect("failed to create tempdir");
@@ -312,6 +1345,93 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_history_prune_dry_run_keeps_sessions() {
+        let tmp = tempdir().expect("failed to create tempdir");
+        let storage = SqliteStorage::new_with_path(tmp.path().join("history.db"))
+            .expect("failed to create storage");
+        for id in ["a", "b", "c"] {
+            storage
+                .save_conversation(id, id, None, &[Message::user(id)])
+                .expect("save failed");
+        }
+
+        let mut config = Config::default();
+        config.storage.retention.max_sessions = Some(1);
+
+        handle_history_with_storage(&storage, &config, HistoryCommand::Prune { dry_run: true })
+            .expect("dry run failed");
+        assert_eq!(storage.list_sessions().expect("list failed").len(), 3);
+
+        handle_history_with_storage(&storage, &config, HistoryCommand::Prune { dry_run: false })
+            .expect("prune failed");
+        assert_eq!(storage.list_sessions().expect("list failed").len(), 1);
+    }
+
+    #[test]
+    fn test_history_tag_list_and_untag() {
+        let tmp = tempdir().expect("failed to create tempdir");
+        let storage = SqliteStorage::new_with_path(tmp.path().join("history.db"))
+            .expect("failed to create storage");
+        storage
+            .save_conversation("abc", "Short id", None, &[Message::user("x")])
+            .expect("save failed");
+        let config = Config::default();
+
+        handle_history_with_storage(
+            &storage,
+            &config,
+            HistoryCommand::Tag {
+                id: "abc".to_string(),
+                tag: "Infra".to_string(),
+            },
+        )
+        .expect("tag failed");
+        handle_history_with_storage(
+            &storage,
+            &config,
+            HistoryCommand::List {
+                tags: vec!["infra".to_string()],
+                utc: false,
+                iso: false,
+                limit: 25,
+                offset: 0,
+            },
+        )
+        .expect("list failed");
+        assert_eq!(
+            storage.list_conversation_tags("abc").unwrap(),
+            vec!["infra".to_string()]
+        );
+
+        handle_history_with_storage(
+            &storage,
+            &config,
+            HistoryCommand::Untag {
+                id: "abc".to_string(),
+                tag: "infra".to_string(),
+            },
+        )
+        .expect("untag failed");
+        assert!(storage.list_conversation_tags("abc").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_history_pin_unknown_conversation_fails() {
+        let tmp = tempdir().expect("failed to create tempdir");
+        let storage = SqliteStorage::new_with_path(tmp.path().join("history.db"))
+            .expect("failed to create storage");
+
+        let result = handle_history_with_storage(
+            &storage,
+            &Config::default(),
+            HistoryCommand::Pin {
+                id: "missing".to_string(),
+            },
+        );
+        assert!(matches!(result, Err(XzatomaError::Storage(_))));
+    }
+
     #[test]
     fn test_show_conversation_not_found() {
         let tmp = tempdir().expect("failed to create tempdir");
@@ -321,4 +1441,260 @@ mod tests {
         let result = show_conversation(&storage, "nonexistent", false, None);
         assert!(result.is_err());
     }
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_format_relative_timestamp_boundaries() {
+        let now = at("2024-12-10T15:00:00Z");
+        let cases = [
+            ("2024-12-10T14:59:30Z", "just now"),
+            ("2024-12-10T14:59:00Z", "1 minute ago"),
+            ("2024-12-10T14:52:00Z", "8 minutes ago"),
+            ("2024-12-10T14:00:01Z", "59 minutes ago"),
+            ("2024-12-10T14:00:00Z", "1 hour ago"),
+            ("2024-12-10T00:00:00Z", "15 hours ago"),
+            ("2024-12-09T23:59:00Z", "yesterday 23:59"),
+            ("2024-12-09T14:32:00Z", "yesterday 14:32"),
+            ("2024-12-08T14:32:00Z", "Sun 14:32"),
+            ("2024-12-04T00:00:00Z", "Wed 00:00"),
+            ("2024-12-03T23:59:00Z", "2024-12-03"),
+            ("2024-12-10T15:00:20Z", "just now"),
+            ("2024-12-11T09:00:00Z", "2024-12-11"),
+        ];
+        for (timestamp, expected) in cases {
+            assert_eq!(
+                format_relative_timestamp(&at(timestamp), &now),
+                expected,
+                "timestamp {}",
+                timestamp
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_relative_timestamp_uses_timezone_calendar_days() {
+        // 01:30 local time in UTC+2 is still "yesterday" for a 23:00 local
+        // timestamp even though both fall on the same UTC day
+        let offset = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+        let now = at("2024-12-09T23:30:00Z").with_timezone(&offset);
+        let timestamp = at("2024-12-09T21:00:00Z").with_timezone(&offset);
+        assert_eq!(
+            format_relative_timestamp(&timestamp, &now),
+            "yesterday 23:00"
+        );
+    }
+
+    #[test]
+    fn test_format_relative_timestamp_epoch_sentinel_is_unknown() {
+        let now = at("2024-12-10T15:00:00Z");
+        assert_eq!(
+            format_relative_timestamp(&DateTime::<Utc>::UNIX_EPOCH, &now),
+            "unknown"
+        );
+    }
+
+    #[test]
+    fn test_timestamp_style_exact_formats() {
+        let timestamp = at("2024-12-03T14:32:05Z");
+        assert_eq!(
+            TimestampStyle::from_flags(true, false).format(&timestamp),
+            "2024-12-03 14:32:05 UTC"
+        );
+        assert_eq!(
+            TimestampStyle::from_flags(true, true).format(&timestamp),
+            "2024-12-03T14:32:05Z"
+        );
+        let local = TimestampStyle::from_flags(false, true).format(&timestamp);
+        assert_eq!(
+            DateTime::parse_from_rfc3339(&local)
+                .unwrap()
+                .with_timezone(&Utc),
+            timestamp
+        );
+    }
+
+    #[test]
+    fn test_parse_age_units() {
+        assert_eq!(parse_age("90m").unwrap(), chrono::Duration::minutes(90));
+        assert_eq!(parse_age("12h").unwrap(), chrono::Duration::hours(12));
+        assert_eq!(parse_age("30d").unwrap(), chrono::Duration::days(30));
+        assert_eq!(parse_age("4w").unwrap(), chrono::Duration::weeks(4));
+    }
+
+    #[test]
+    fn test_parse_age_rejects_invalid_values() {
+        for value in ["", "d", "30", "30y", "-1d", "abcd"] {
+            let error = parse_age(value).unwrap_err();
+            assert!(error.to_string().contains("Invalid age"), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_format_minutes() {
+        assert_eq!(format_minutes(44.6), "44m 36s");
+        assert_eq!(format_minutes(125.0), "2h 05m");
+    }
+
+    #[test]
+    fn test_format_minutes_zero_and_sub_minute() {
+        assert_eq!(format_minutes(0.0), "0s");
+        assert_eq!(format_minutes(-3.0), "0s");
+        assert_eq!(format_minutes(f64::NAN), "0s");
+        assert_eq!(format_minutes(0.005), "<1m");
+        assert_eq!(format_minutes(0.5), "<1m");
+        assert_eq!(format_minutes(1.0), "1m 00s");
+    }
+
+    #[test]
+    fn test_history_stats_json_output() {
+        let tmp = tempdir().expect("failed to create tempdir");
+        let db_path = tmp.path().join("history.db");
+        let storage = SqliteStorage::new_with_path(&db_path).expect("failed to create storage");
+        storage
+            .save_conversation(
+                "session-1",
+                "First",
+                Some("gpt-5-mini"),
+                &[Message::user("one")],
+            )
+            .expect("save failed");
+
+        #[allow(deprecated)]
+        let mut cmd = Command::cargo_bin("xzatoma").expect("failed to find binary");
+        cmd.arg("--storage-path")
+            .arg(db_path.to_string_lossy().to_string())
+            .args(["history", "stats", "--since", "7d", "--json"]);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("\"total_sessions\": 1"))
+            .stdout(predicate::str::contains("gpt-5-mini"));
+    }
+
+    #[test]
+    fn test_json_output_writes_only_json_to_stdout() {
+        let tmp = tempdir().expect("failed to create tempdir");
+        let db_path = tmp.path().join("history.db");
+        let storage = SqliteStorage::new_with_path(&db_path).expect("failed to create storage");
+        storage
+            .save_conversation("session-1", "First", None, &[Message::user("one")])
+            .expect("save failed");
+
+        #[allow(deprecated)]
+        let mut cmd = Command::cargo_bin("xzatoma").expect("failed to find binary");
+        cmd.env_remove("NO_COLOR")
+            .arg("--storage-path")
+            .arg(db_path.to_string_lossy().to_string())
+            .args(["--color", "always", "history", "stats", "--json"]);
+
+        let output = cmd.assert().success().get_output().stdout.clone();
+        assert!(!output.contains(&0x1b), "stdout contains escape codes");
+        serde_json::from_slice::<serde_json::Value>(&output)
+            .expect("stdout should be a single JSON document");
+    }
+
+    #[test]
+    fn test_history_list_pages_with_footer() {
+        let tmp = tempdir().expect("failed to create tempdir");
+        let db_path = tmp.path().join("history.db");
+        let storage = SqliteStorage::new_with_path(&db_path).expect("failed to create storage");
+        for id in ["session-1", "session-2", "session-3"] {
+            storage
+                .save_conversation(id, id, None, &[Message::user("one")])
+                .expect("save failed");
+        }
+
+        #[allow(deprecated)]
+        let mut cmd = Command::cargo_bin("xzatoma").expect("failed to find binary");
+        cmd.arg("--storage-path")
+            .arg(db_path.to_string_lossy().to_string())
+            .args(["history", "list", "--limit", "1", "--offset", "1"]);
+
+        cmd.assert().success().stdout(predicate::str::contains(
+            "... and 1 more (use --limit/--offset or history search)",
+        ));
+    }
+
+    fn pick_session(id: &str, title: &str) -> StoredSession {
+        StoredSession {
+            id: id.to_string(),
+            title: title.to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            model: None,
+            message_count: 1,
+            pinned: false,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_match_sessions_filters_by_substring_then_fuzzy_title() {
+        let sessions = vec![
+            pick_session("3f2a1b9c-0000", "Fix login redirect"),
+            pick_session("7d41e0aa-0000", "Deploy pipeline"),
+        ];
+        let ids = |query: &str| -> Vec<String> {
+            match_sessions(&sessions, query)
+                .into_iter()
+                .map(|session| session.id.clone())
+                .collect()
+        };
+
+        assert_eq!(ids("").len(), 2);
+        assert_eq!(ids("LOGIN"), vec!["3f2a1b9c-0000".to_string()]);
+        assert_eq!(ids("7d41"), vec!["7d41e0aa-0000".to_string()]);
+        assert_eq!(ids("deploy pipelnie"), vec!["7d41e0aa-0000".to_string()]);
+        assert!(ids("kubernetes").is_empty());
+    }
+
+    #[test]
+    fn test_pick_from_list_reads_number_or_filter() {
+        let sessions = vec![
+            pick_session("session-a", "Fix login redirect"),
+            pick_session("session-b", "Deploy pipeline"),
+        ];
+        let pick = |answer: &str| {
+            let mut output = Vec::new();
+            let result = pick_from_list(&sessions, &mut answer.as_bytes(), &mut output);
+            (result, String::from_utf8(output).unwrap())
+        };
+
+        let (result, output) = pick("2\n");
+        assert_eq!(result.unwrap(), "session-b");
+        assert!(output.contains("  1) Fix login redirect (session-"));
+        assert!(output.contains("Select a conversation [1-2]"));
+
+        assert_eq!(pick("deploy\n").0.unwrap(), "session-b");
+        assert!(matches!(pick("").0, Err(XzatomaError::Cancelled)));
+        assert!(matches!(pick("3\n").0, Err(XzatomaError::Command(_))));
+        assert!(matches!(pick("session\n").0, Err(XzatomaError::Command(_))));
+    }
+
+    #[test]
+    fn test_history_pick_without_tty_prints_only_the_id() {
+        let tmp = tempdir().expect("failed to create tempdir");
+        let db_path = tmp.path().join("history.db");
+        let storage = SqliteStorage::new_with_path(&db_path).expect("failed to create storage");
+        storage
+            .save_conversation("session-1", "First", None, &[Message::user("one")])
+            .expect("save failed");
+
+        #[allow(deprecated)]
+        let mut cmd = Command::cargo_bin("xzatoma").expect("failed to find binary");
+        cmd.arg("--storage-path")
+            .arg(db_path.to_string_lossy().to_string())
+            .args(["history", "pick"])
+            .write_stdin("1\n");
+
+        cmd.assert()
+            .success()
+            .stdout("session-1\n")
+            .stderr(predicate::str::contains("1) First"));
+    }
 }
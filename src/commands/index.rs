@@ -0,0 +1,118 @@
+//! Semantic index commands
+//!
+//! Builds the embedding index that `@semantic` mentions search. See
+//! [`crate::semantic_index`] for how files are chunked and stored.
+
+use std::path::Path;
+
+use colored::Colorize;
+
+use crate::cli::IndexCommand;
+use crate::config::Config;
+use crate::error::{Result, XzatomaError};
+use crate::providers::embeddings::{EmbeddingProvider, OllamaEmbeddingProvider};
+use crate::semantic_index::{BuildReport, IndexOptions, SemanticIndex};
+use crate::ui_println;
+
+/// Handle semantic index commands
+///
+/// # Arguments
+///
+/// * `config` - The loaded configuration; `semantic` selects the model,
+///   chunking, and index directory
+/// * `command` - The index subcommand
+pub async fn handle_index(config: &Config, command: IndexCommand) -> Result<()> {
+    match command {
+        IndexCommand::Build { path } => {
+            let root = path.canonicalize().map_err(|e| {
+                XzatomaError::Config(format!("Cannot index {}: {}", path.display(), e))
+            })?;
+            let index_path = SemanticIndex::path_for(&SemanticIndex::dir_for(config)?, &root);
+            let embedder = OllamaEmbeddingProvider::from_config(config)?;
+
+            ui_println!(
+                "{}",
+                format!(
+                    "Indexing {} with {}",
+                    root.display(),
+                    config.semantic.embedding_model
+                )
+                .cyan()
+            );
+            let report = build_index(
+                &root,
+                &index_path,
+                &embedder,
+                IndexOptions::from(&config.semantic),
+            )
+            .await?;
+            ui_println!(
+                "Indexed {} file{}, {} unchanged, {} removed; {} chunks in {}",
+                report.indexed,
+                if report.indexed == 1 { "" } else { "s" },
+                report.unchanged,
+                report.removed,
+                report.chunks,
+                index_path.display()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Updates the index at `index_path` for `root` and saves it
+///
+/// An existing index is updated incrementally; otherwise a new one is
+/// created.
+async fn build_index(
+    root: &Path,
+    index_path: &Path,
+    embedder: &dyn EmbeddingProvider,
+    options: IndexOptions,
+) -> Result<BuildReport> {
+    let mut index = SemanticIndex::load(index_path)?
+        .unwrap_or_else(|| SemanticIndex::new(root, embedder.model()));
+    let report = index.update(root, embedder, options).await?;
+    index.save(index_path)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use tempfile::TempDir;
+
+    struct LengthEmbedder;
+
+    #[async_trait]
+    impl EmbeddingProvider for LengthEmbedder {
+        fn model(&self) -> &str {
+            "length"
+        }
+
+        async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            Ok(vec![text.len() as f32, 1.0])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_build_index_saves_and_updates_incrementally() {
+        let workspace = TempDir::new().unwrap();
+        let index_dir = TempDir::new().unwrap();
+        std::fs::write(workspace.path().join("main.rs"), "fn main() {}\n").unwrap();
+        let index_path = SemanticIndex::path_for(index_dir.path(), workspace.path());
+        let options = IndexOptions::from(&Config::default().semantic);
+
+        let report = build_index(workspace.path(), &index_path, &LengthEmbedder, options)
+            .await
+            .unwrap();
+        assert_eq!((report.indexed, report.chunks), (1, 1));
+        assert!(index_path.exists());
+
+        let report = build_index(workspace.path(), &index_path, &LengthEmbedder, options)
+            .await
+            .unwrap();
+        assert_eq!((report.indexed, report.unchanged), (0, 1));
+    }
+}
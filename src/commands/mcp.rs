@@ -4,10 +4,19 @@
 //! operations for MCP (Model Context Protocol) server connections including
 //! listing configured servers and their connection status.
 
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::RwLock;
+
 use crate::config::Config;
 use crate::error::Result;
-use crate::mcp::manager::{build_mcp_manager_from_config, McpServerState};
+use crate::mcp::config::McpConfig;
+use crate::mcp::manager::{
+    build_mcp_manager_from_config, McpClientManager, McpServerEntry, McpServerState,
+};
 use crate::mcp::server::McpServerTransportConfig;
+use crate::ui_println;
 
 /// MCP subcommand variants
 ///
@@ -56,6 +65,7 @@ pub async fn handle_mcp(command: McpCommands, config: Config) -> Result<()> {
 /// enabled servers and reports their live state (Connected, Disconnected, or
 /// Failed) along with the number of advertised tools. When `auto_connect` is
 /// disabled, lists the configured servers without attempting to connect.
+/// Every line ends with the server's origin.
 ///
 /// # Arguments
 ///
@@ -67,92 +77,97 @@ pub async fn handle_mcp(command: McpCommands, config: Config) -> Result<()> {
 /// individual server connection failures (which are reported inline).
 async fn handle_list(config: Config) -> Result<()> {
     if config.mcp.servers.is_empty() {
-        println!("No MCP servers configured.");
+        ui_println!("No MCP servers configured.");
         return Ok(());
     }
 
-    println!("Configured MCP servers ({}):\n", config.mcp.servers.len());
+    ui_println!("Configured MCP servers ({}):\n", config.mcp.servers.len());
 
     if config.mcp.auto_connect {
         // Build the manager, which connects to all enabled servers.
-        let manager_opt = build_mcp_manager_from_config(&config).await?;
-
-        match manager_opt {
-            Some(manager_arc) => {
-                let manager = manager_arc.read().await;
-                let connected = manager.connected_servers();
-
-                // Build a lookup set of connected server IDs for quick access.
-                let connected_map: std::collections::HashMap<
-                    &str,
-                    &crate::mcp::manager::McpServerEntry,
-                > = connected
-                    .iter()
-                    .map(|entry| (entry.config.id.as_str(), *entry))
-                    .collect();
-
-                for server_cfg in &config.mcp.servers {
-                    let transport_label = transport_type_label(&server_cfg.transport);
-                    let enabled_label = if server_cfg.enabled {
-                        "enabled"
-                    } else {
-                        "disabled"
-                    };
-
-                    if let Some(entry) = connected_map.get(server_cfg.id.as_str()) {
-                        let state_label = format_server_state(&entry.state);
-                        let tool_count = entry.tools.len();
-                        println!(
-                            "  - {} ({}, {}, {}, {} tools)",
-                            server_cfg.id, transport_label, enabled_label, state_label, tool_count
-                        );
-                    } else {
-                        // Server exists in config but was not connected (e.g. disabled or failed
-                        // before being registered in the manager).
-                        let state_label = if server_cfg.enabled {
-                            "Disconnected".to_string()
-                        } else {
-                            "Skipped (disabled)".to_string()
-                        };
-                        println!(
-                            "  - {} ({}, {}, {}, 0 tools)",
-                            server_cfg.id, transport_label, enabled_label, state_label
-                        );
-                    }
-                }
-            }
-            None => {
-                // build_mcp_manager_from_config returned None even though we checked
-                // auto_connect -- this can happen if all servers are disabled.
-                print_servers_without_status(&config);
-            }
-        }
+        let manager = build_mcp_manager_from_config(&config).await?;
+        print_server_lines(&config.mcp, manager.as_ref()).await;
     } else {
-        println!("  auto_connect is disabled; showing configuration only.\n");
-        print_servers_without_status(&config);
+        ui_println!("  auto_connect is disabled; showing configuration only.\n");
+        print_server_lines(&config.mcp, None).await;
     }
 
     Ok(())
 }
 
-/// Print configured servers without attempting any connections.
+/// Print the MCP servers of a chat session for `/mcp status`.
 ///
-/// Used when `auto_connect` is disabled or when no manager could be built.
-fn print_servers_without_status(config: &Config) {
-    for server_cfg in &config.mcp.servers {
-        let transport_label = transport_type_label(&server_cfg.transport);
-        let enabled_label = if server_cfg.enabled {
-            "enabled"
-        } else {
-            "disabled"
+/// Lists every configured server with its transport, origin (xzatoma config
+/// or a project MCP file), connection state, and tool count.
+///
+/// # Arguments
+///
+/// * `mcp` - The session's MCP configuration, including merged project servers
+/// * `manager` - The session's manager, or `None` when nothing was connected
+pub async fn print_mcp_status(mcp: &McpConfig, manager: Option<&Arc<RwLock<McpClientManager>>>) {
+    if mcp.servers.is_empty() {
+        ui_println!("No MCP servers configured.\n");
+        return;
+    }
+    ui_println!("MCP servers ({}):\n", mcp.servers.len());
+    print_server_lines(mcp, manager).await;
+    ui_println!();
+}
+
+/// Print one line per configured server.
+///
+/// With a manager, each line shows the live state and tool count of the
+/// server; without one, only its configuration.
+async fn print_server_lines(mcp: &McpConfig, manager: Option<&Arc<RwLock<McpClientManager>>>) {
+    let Some(manager) = manager else {
+        for server_cfg in &mcp.servers {
+            ui_println!(
+                "  - {} ({}, {}, {})",
+                server_cfg.id,
+                transport_type_label(&server_cfg.transport),
+                enabled_label(server_cfg.enabled),
+                mcp.server_origin(&server_cfg.id)
+            );
+        }
+        return;
+    };
+
+    let manager = manager.read().await;
+    let connected: HashMap<&str, &McpServerEntry> = manager
+        .connected_servers()
+        .into_iter()
+        .map(|entry| (entry.config.id.as_str(), entry))
+        .collect();
+
+    for server_cfg in &mcp.servers {
+        let (state_label, tool_count) = match connected.get(server_cfg.id.as_str()) {
+            Some(entry) => (format_server_state(&entry.state), entry.tools.len()),
+            // Server exists in config but was not connected (e.g. disabled or
+            // failed before being registered in the manager).
+            None if server_cfg.enabled => ("Disconnected".to_string(), 0),
+            None => ("Skipped (disabled)".to_string(), 0),
         };
-        println!(
-            "  - {} ({}, {})",
-            server_cfg.id, transport_label, enabled_label
+        ui_println!(
+            "  - {} ({}, {}, {}, {} tools, {})",
+            server_cfg.id,
+            transport_type_label(&server_cfg.transport),
+            enabled_label(server_cfg.enabled),
+            state_label,
+            tool_count,
+            mcp.server_origin(&server_cfg.id)
         );
     }
 }
 
+/// Return `"enabled"` or `"disabled"`.
+fn enabled_label(enabled: bool) -> &'static str {
+    if enabled {
+        "enabled"
+    } else {
+        "disabled"
+    }
+}
+
 /// Return a human-readable label for the transport type.
 fn transport_type_label(transport: &McpServerTransportConfig) -> &'static str {
     match transport {
@@ -272,14 +287,15 @@ mod tests {
                 prompts_enabled: false,
                 sampling_enabled: false,
                 elicitation_enabled: true,
+                read_safe_tools: vec![],
             });
         // Should not attempt any connections, just list config.
         let result = handle_mcp(McpCommands::List, config).await;
         assert!(result.is_ok());
     }
 
-    #[test]
-    fn test_print_servers_without_status_shows_disabled() {
+    #[tokio::test]
+    async fn test_print_server_lines_without_manager_shows_disabled() {
         let mut config = Config::default();
         config
             .mcp
@@ -299,8 +315,13 @@ mod tests {
                 prompts_enabled: false,
                 sampling_enabled: false,
                 elicitation_enabled: true,
+                read_safe_tools: vec![],
             });
         // This should not panic; it only prints to stdout.
-        print_servers_without_status(&config);
+        config
+            .mcp
+            .project_servers
+            .insert("disabled-srv".to_string(), ".mcp.json".into());
+        print_server_lines(&config.mcp, None).await;
     }
 }
@@ -13,27 +13,51 @@ These handlers are intentionally small and use the library components:
 providers, tools, and the agent.
 */
 
-use crate::agent::Agent;
+use crate::agent::preflight::PreflightReport;
+use crate::agent::step_policy::StepSummary;
+use crate::agent::{
+    Agent, ChangeReview, ModeGate, NoOpObserver, TerminalChangeReview, TerminalModeEscalation,
+    ToolMetricsSummary,
+};
 use crate::chat_mode::{ChatMode, ChatModeState, SafetyMode};
 use crate::commands::special_commands::{
     parse_special_command, print_help, print_models_help, SpecialCommand,
 };
 use crate::config::Config;
 use crate::error::{Result, XzatomaError};
+use crate::format::{format_bytes, format_duration, format_tokens};
 use crate::mcp::manager::build_mcp_manager_from_config;
 use crate::mcp::tool_bridge::register_mcp_tools;
 use crate::mention_parser;
-use crate::providers::{create_provider, CopilotProvider, OllamaProvider};
+use crate::network_policy::NetworkPolicy;
+use crate::paths::Paths;
+use crate::prompts::PromptStyle;
+use crate::providers::budget::SystemClock;
+use crate::providers::{
+    create_provider, wrap_with_budget, wrap_with_cache, wrap_with_quirks, wrap_with_recorder,
+    CacheCounters, CopilotProvider, ImagePromptPart, OllamaProvider, TokenUsage, UsageLedger,
+};
+use crate::session_cwd::SessionCwd;
+use crate::shutdown::{ChildProcessRegistry, ShutdownCoordinator};
 use crate::skills::{
     build_skill_disclosure_section, discover_skills, render_skill_catalog, ActiveSkillRegistry,
     SkillCatalog, SkillRecord,
 };
+use crate::telemetry::{TelemetrySink, TelemetryStatus};
 use crate::tools::activate_skill::ActivateSkillTool;
+use crate::tools::audit_log::{process_session_id, AuditLog};
+use crate::tools::confirmation::ConfirmationPolicy;
+use crate::tools::definition_limits::ToolFitReport;
+use crate::tools::output_pages::OutputPager;
+use crate::tools::overflow_summary::{OverflowSummarizer, ToolOutputStore};
 use crate::tools::plan::PlanParser;
 use crate::tools::registry_builder::ToolRegistryBuilder;
 use crate::tools::{SubagentTool, ToolRegistry};
+use crate::ui::{self, Glyph};
+use crate::{ui_eprintln, ui_print, ui_println};
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 // Chat mode types and utilities
 pub mod chat_mode;
@@ -41,6 +65,24 @@ pub mod chat_mode;
 // Special commands parser for mode switching
 pub mod special_commands;
 
+// Multi-line message composer for chat mode
+pub mod composer;
+
+// Startup banner for chat sessions
+pub mod chat_banner;
+
+// Quick actions for code blocks in chat responses
+pub mod code_blocks;
+
+// Conflict-aware saving of chat conversations
+pub mod chat_save;
+
+// Post-mortem reports for failed runs
+pub mod postmortem;
+
+// Workspace snapshots and rollback around runs
+pub mod snapshot;
+
 // Model management commands
 pub mod models;
 
@@ -50,6 +92,9 @@ pub mod history;
 // Replay command for conversation debugging
 pub mod replay;
 
+// Turn-by-turn inspector for recorded runs
+pub mod debug;
+
 // MCP server management commands
 pub mod mcp;
 
@@ -59,9 +104,42 @@ pub mod acp;
 // ACP stdio agent command for Zed and other ACP-compatible clients
 pub mod agent;
 
+// JSON-RPC serve mode for editor integrations
+pub mod serve;
+
 // Skills management commands
 pub mod skills;
 
+// File mutation audit log commands
+pub mod audit;
+
+// Plan drafting commands
+pub mod plan;
+
+// Workspace trust commands
+pub mod trust;
+
+// Provider response cache commands
+pub mod cache;
+
+// Semantic index commands
+pub mod index;
+
+// Data location commands
+pub mod paths;
+
+// Stored tool output commands
+pub mod tool_output;
+
+// Provider usage report
+pub mod usage;
+
+// Setup diagnostics
+pub mod doctor;
+
+// Guided setup when no configuration file exists
+pub mod setup;
+
 // Agent environment builder (shared tool/skill/MCP initialization)
 pub mod environment;
 pub use environment::{build_agent_environment, AgentEnvironment};
@@ -369,6 +447,283 @@ pub fn build_active_skill_prompt_injection(
     Ok(registry.render_for_prompt_injection())
 }
 
+/// Print how the tool definitions were trimmed to fit the provider's limits
+///
+/// # Arguments
+///
+/// * `report` - Report from the last provider request
+pub fn print_tool_fit_report(report: &ToolFitReport) {
+    use colored::Colorize;
+
+    ui_println!(
+        "{} {} of {} tools sent ({} of {} bytes), strategy {}",
+        "Tool definitions trimmed:".yellow().bold(),
+        report.final_count,
+        report.original_count,
+        report.final_bytes,
+        report.original_bytes,
+        report.strategy
+    );
+    for truncated in &report.truncated {
+        ui_println!(
+            "  truncated {} ({} -> {} chars)",
+            truncated.name.cyan(),
+            truncated.original_chars,
+            truncated.truncated_chars
+        );
+    }
+    for dropped in &report.dropped {
+        ui_println!(
+            "  dropped   {} ({} bytes)",
+            dropped.name.cyan(),
+            dropped.bytes
+        );
+    }
+}
+
+/// Print the tools that are degraded or withheld
+///
+/// # Arguments
+///
+/// * `entries` - Snapshot from `ToolHealth::report`
+pub fn print_tool_health(entries: &[ToolHealthEntry]) {
+    use colored::Colorize;
+
+    ui_println!("{}", "Tool health:".yellow().bold());
+    for entry in entries {
+        let status = match entry.withheld_for {
+            Some(remaining) => format!("withheld for {}s", remaining.as_secs()),
+            None => entry.status.to_string(),
+        };
+        ui_println!(
+            "  {} {} ({} of {} recent calls failed){}",
+            entry.name.cyan(),
+            status,
+            entry.recent_failures,
+            entry.recent_calls,
+            entry
+                .last_error
+                .as_deref()
+                .map(|error| format!(": {}", error))
+                .unwrap_or_default()
+        );
+    }
+}
+
+/// Print a per-tool metrics table followed by the aggregate totals
+///
+/// # Arguments
+///
+/// * `summary` - Snapshot from `ToolMetrics::summary`
+pub fn print_tool_metrics(summary: &ToolMetricsSummary) {
+    use colored::Colorize;
+    use prettytable::{format, Table};
+
+    if summary.is_empty() {
+        ui_println!("No tool calls recorded.");
+        return;
+    }
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+    table.add_row(prettytable::row![
+        "Tool".bold(),
+        "Calls".bold(),
+        "OK".bold(),
+        "Failed".bold(),
+        "Timeout".bold(),
+        "Total Time".bold(),
+        "Avg Time".bold(),
+        "Output".bold()
+    ]);
+
+    let rows = summary
+        .tools
+        .iter()
+        .map(|entry| (entry.name.cyan().to_string(), &entry.stats))
+        .chain(std::iter::once((
+            "Total".bold().to_string(),
+            &summary.totals,
+        )));
+    for (name, stats) in rows {
+        table.add_row(prettytable::row![
+            name,
+            stats.calls,
+            stats.successes,
+            stats.failures,
+            stats.timeouts,
+            format_duration(Duration::from_millis(stats.total_duration_ms)),
+            format_duration(Duration::from_millis(stats.average_duration_ms())),
+            format_bytes(stats.total_output_bytes)
+        ]);
+    }
+
+    table.printstd();
+}
+
+/// Print the attempts of each plan step, one line per attempt
+///
+/// # Arguments
+///
+/// * `steps` - Step summaries from `PlanStepExecutor::execute`
+fn print_step_attempts(steps: &[StepSummary]) {
+    for step in steps {
+        ui_println!("  {}", step.step);
+        for attempt in &step.attempts {
+            let mut line = format!(
+                "    {}. {} in {}, {} tool call{}",
+                attempt.attempt,
+                attempt.status,
+                format_duration(Duration::from_millis(attempt.duration_ms)),
+                attempt.tool_calls,
+                if attempt.tool_calls == 1 { "" } else { "s" }
+            );
+            if let Some(error) = &attempt.error {
+                line.push_str(&format!(": {}", error));
+            }
+            ui_println!("{}", line);
+        }
+    }
+}
+
+/// Format the token usage line printed after a run
+///
+/// Billable tokens exclude responses served from the provider cache. With
+/// the cache enabled the line also counts cache hits.
+///
+/// # Arguments
+///
+/// * `usage` - Billable token usage accumulated by the agent
+/// * `cache` - Provider cache counters, when the cache is enabled
+fn format_usage_line(usage: &TokenUsage, cache: Option<&CacheCounters>) -> String {
+    let mut line = format!(
+        "Usage: {} prompt + {} completion = {} tokens",
+        format_tokens(usage.prompt_tokens as u64),
+        format_tokens(usage.completion_tokens as u64),
+        format_tokens(usage.total_tokens as u64)
+    );
+    if let Some(cache) = cache {
+        let responses = cache.hits() + cache.misses() + cache.bypassed();
+        line.push_str(&format!(
+            " ({} of {} responses served from cache)",
+            cache.hits(),
+            responses
+        ));
+    }
+    line
+}
+
+/// The smaller of the conversation budget and the model's context window
+///
+/// Falls back to the conversation budget when the provider cannot report
+/// the model's context window.
+async fn effective_context_limit(agent: &Agent) -> usize {
+    let conversation_limit = agent.conversation().max_tokens();
+    let model_name = agent.provider().get_current_model();
+    match agent.provider().get_model_info(&model_name).await {
+        Ok(model_info) => conversation_limit.min(model_info.context_window),
+        Err(_) => conversation_limit,
+    }
+}
+
+/// Builds the summarizer for tool output over `tools.max_output_size`
+///
+/// Returns `None` when `tools.summarize_overflow` is off. Uses
+/// `conversation.summary_model` when set and the session provider otherwise.
+/// A summary provider that cannot be created leaves the agent truncating.
+pub(crate) async fn build_overflow_summarizer(
+    config: &Config,
+    provider: &Arc<dyn crate::providers::Provider>,
+) -> Option<Arc<OverflowSummarizer>> {
+    let tools = &config.agent.tools;
+    if !tools.summarize_overflow {
+        return None;
+    }
+    let summary_provider = match &config.agent.conversation.summary_model {
+        Some(model) => {
+            match r#run::create_summary_provider_if_needed(config, provider, model).await {
+                Ok(summary_provider) => summary_provider,
+                Err(e) => {
+                    tracing::warn!(
+                        error = %e,
+                        "Cannot create the summary provider; oversized tool output will be truncated"
+                    );
+                    return None;
+                }
+            }
+        }
+        None => Arc::clone(provider),
+    };
+    let paths = match Paths::from_config(config) {
+        Ok(paths) => paths,
+        Err(e) => {
+            tracing::warn!(
+                error = %e,
+                "Cannot resolve the data directory; oversized tool output will be truncated"
+            );
+            return None;
+        }
+    };
+    Some(Arc::new(
+        OverflowSummarizer::new(summary_provider, ToolOutputStore::from_paths(&paths))
+            .with_chunking(tools.summary_chunk_bytes, tools.summary_max_chunks),
+    ))
+}
+
+/// Builds the session store that pages tool output over `tools.max_output_size`
+///
+/// Returns `None` when `tools.paging.enabled` is off, which leaves oversized
+/// output truncated. The store and its spill files are removed when the
+/// session ends.
+pub(crate) fn build_output_pager(config: &Config) -> Option<Arc<OutputPager>> {
+    let paging = &config.agent.tools.paging;
+    paging
+        .enabled
+        .then(|| Arc::new(OutputPager::from_config(paging)))
+}
+
+/// Starts usage logging for a command that sends provider requests
+///
+/// When the history database cannot be opened, usage is not logged. That
+/// only fails the command when `budget.monthly_cost_limit` is set, since the
+/// limit could not be enforced.
+pub(crate) fn start_usage_ledger(
+    config: &Config,
+    provider_type: &str,
+) -> Result<Option<Arc<UsageLedger>>> {
+    let ledger = Paths::from_config(config)
+        .and_then(|paths| crate::storage::SqliteStorage::new(&paths))
+        .and_then(|storage| {
+            UsageLedger::start(
+                storage,
+                config,
+                provider_type,
+                process_session_id(),
+                Arc::new(SystemClock),
+            )
+        });
+    match ledger {
+        Ok(ledger) => Ok(Some(ledger)),
+        Err(e) if config.budget.monthly_cost_limit.is_none() => {
+            tracing::warn!("Failed to start usage logging: {}", e);
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Print the breakdown of a prompt that exceeds the preflight limit
+///
+/// # Arguments
+///
+/// * `report` - Report from `preflight::check`
+pub fn print_preflight_report(report: &PreflightReport) {
+    use colored::Colorize;
+
+    ui_eprintln!("{}", report.headline().yellow().bold());
+    ui_eprintln!("{}", report.breakdown());
+}
+
 // Chat command handler
 pub mod chat {
     //! Interactive chat mode handler.
@@ -378,10 +733,20 @@ pub mod chat {
     //!
     //! The agent will use the registered tools (file_ops, etc.) as required.
 
+    use super::chat_banner::{self, BannerContext};
+    use super::chat_save::{self, ChatSaveOutcome, ChatSaveState};
+    use super::code_blocks::{ResponseBlocks, SessionClipboard};
+    use super::composer::{self, Draft, DraftEditor, ExternalEditor};
     use super::*;
+    use crate::config::ChatBanner;
+    use crate::read_only::{ReadOnlyPolicy, READ_ONLY_PROMPT};
+    use crate::storage::types::IfExists;
+    use crate::transcript::{Transcript, TranscriptObserver};
+    use crate::workspace_trust::{self, WorkspaceTrustStore};
     use colored::Colorize;
     use rustyline::error::ReadlineError;
     use rustyline::DefaultEditor;
+    use std::io::IsTerminal;
 
     /// Start interactive chat mode
     ///
@@ -396,23 +761,34 @@ pub mod chat {
     ///   extended reasoning. Accepted values: `none`, `low`, `medium`, `high`,
     ///   `extra_high`. When `Some("none")`, reasoning parameters are cleared.
     ///   When `None`, the provider default is used.
+    /// * `session_cwd` - Session working directory for tools and mentions;
+    ///   `/cd` moves it during the chat
+    /// * `config_path` - Absolute path of the configuration file, used to
+    ///   evaluate workspace trust for the directories `/cd` moves into
     ///
     /// # Examples
     ///
     /// ```
     /// use xzatoma::commands::chat;
     /// use xzatoma::config::Config;
+    /// use xzatoma::session_cwd::SessionCwd;
     ///
     /// // In application code:
-    /// // chat::run_chat(Config::default(), None, None, false, None, None).await?;
+    /// // let cwd = SessionCwd::from_launch_dir(None, false)?;
+    /// // let config_path = std::env::current_dir()?.join("config/config.yaml");
+    /// // chat::run_chat(Config::default(), None, None, false, None, None, cwd, &config_path)
+    /// //     .await?;
     /// ```
+    #[allow(clippy::too_many_arguments)]
     pub async fn run_chat(
-        config: Config,
+        mut config: Config,
         provider_name: Option<String>,
         mode: Option<String>,
         _safe: bool,
         resume: Option<String>,
         thinking_effort: Option<String>,
+        mut session_cwd: SessionCwd,
+        config_path: &Path,
     ) -> Result<()> {
         use crate::storage::SqliteStorage;
 
@@ -421,21 +797,31 @@ pub mod chat {
         let provider_type = provider_name
             .as_deref()
             .unwrap_or(&config.provider.provider_type);
+        NetworkPolicy::from_config(&config).check_provider(provider_type)?;
 
-        let working_dir = std::env::current_dir()?;
+        let working_dir = session_cwd.current().to_path_buf();
         let skill_disclosure = build_startup_skill_disclosure(&config, &working_dir)?;
         let visible_skill_catalog = build_visible_skill_catalog(&config, &working_dir)?;
         let active_skill_registry = Arc::new(std::sync::Mutex::new(ActiveSkillRegistry::new()));
 
-        // Initialize mode state from command-line arguments
-        // Defaults: Planning mode, AlwaysConfirm (safe) safety mode
-        let initial_mode = mode
-            .as_deref()
-            .and_then(|m| ChatMode::parse_str(m).ok())
-            .unwrap_or(ChatMode::Planning);
+        // Initialize mode state from the command line, then `chat.default_mode`
+        // Defaults: Planning mode; read-only sessions always plan
+        let read_only = ReadOnlyPolicy::from_config(&config);
+        let requested_mode = mode.as_deref().unwrap_or(&config.agent.chat.default_mode);
+        let requested_mode = ChatMode::parse_str(requested_mode).unwrap_or(ChatMode::Planning);
+        let initial_mode = read_only.effective_mode(requested_mode);
+        if initial_mode != requested_mode {
+            ui_eprintln!(
+                "{}",
+                "Read-only mode: starting in PLANNING mode instead of WRITE".yellow()
+            );
+        }
 
-        // Default to safe mode (AlwaysConfirm)
-        let mut mode_state = ChatModeState::new(initial_mode, SafetyMode::AlwaysConfirm);
+        // Safety mode comes from `chat.default_safety`, falling back to AlwaysConfirm
+        let initial_safety = SafetyMode::parse_str(&config.agent.chat.default_safety)
+            .unwrap_or(SafetyMode::AlwaysConfirm);
+        let mut mode_state = ChatModeState::new(initial_mode, initial_safety);
+        mode_state.read_only = read_only.is_enabled();
 
         // Build initial tool registry based on mode
         let mut tools = build_tools_for_mode(&mode_state, &config, &working_dir)?;
@@ -453,11 +839,13 @@ pub mod chat {
         let mcp_manager = build_mcp_manager_from_config(&config).await?;
 
         // Register MCP tools into the registry.
+        let mut mcp_tool_count = 0;
         if let Some(ref manager) = mcp_manager {
             let execution_mode = config.agent.terminal.default_mode;
             match register_mcp_tools(&mut tools, Arc::clone(manager), execution_mode, false).await {
                 Ok(count) if count > 0 => {
                     tracing::info!(count = %count, "Registered MCP tools for chat command");
+                    mcp_tool_count = count;
                 }
                 Ok(_) => {}
                 Err(e) => {
@@ -467,7 +855,8 @@ pub mod chat {
         }
 
         // Initialize storage
-        let storage = match SqliteStorage::new() {
+        let storage = match Paths::from_config(&config).and_then(|paths| SqliteStorage::new(&paths))
+        {
             Ok(s) => Some(s),
             Err(e) => {
                 tracing::warn!("Failed to initialize persistence storage: {}", e);
@@ -475,8 +864,83 @@ pub mod chat {
             }
         };
 
-        // Create provider
-        let provider_box = create_provider(provider_type, &config.provider)?;
+        // A session key names the stored conversation; an existing one is
+        // replaced, continued after a separator, or refused
+        let session_id = config
+            .storage
+            .session_id
+            .as_deref()
+            .map(crate::storage::session_conversation_id);
+        let mut resume = resume;
+        let mut append_to_session = false;
+        if let (Some(id), Some(storage)) = (session_id, &storage) {
+            if storage.conversation_exists(&id.to_string())? {
+                match config.storage.if_exists {
+                    IfExists::Replace => {}
+                    IfExists::Append => {
+                        resume = Some(id.to_string());
+                        append_to_session = true;
+                    }
+                    IfExists::Fail => {
+                        return Err(XzatomaError::Storage(format!(
+                            "Session '{}' is already stored as conversation {}; use --if-exists replace or append",
+                            config.storage.session_id.as_deref().unwrap_or_default(),
+                            id
+                        )));
+                    }
+                }
+            }
+        }
+
+        // Apply the retention policy in the background so startup is not delayed
+        if let Some(storage) = &storage {
+            if config.storage.retention.is_enabled() {
+                let storage = storage.clone();
+                let retention = config.storage.retention.clone();
+                tokio::task::spawn_blocking(move || {
+                    match storage.prune_old_sessions(&retention, false) {
+                        Ok(report) if !report.removed.is_empty() => tracing::info!(
+                            removed = report.removed.len(),
+                            vacuumed = report.vacuumed,
+                            "Pruned conversation history"
+                        ),
+                        Ok(_) => {}
+                        Err(e) => tracing::warn!("Failed to prune conversation history: {}", e),
+                    }
+                });
+            }
+        }
+
+        // Look up the banner's git state and last conversation while the
+        // provider starts; lookups that run late are left out of the banner
+        let banner_level = config.agent.chat.banner;
+        let startup_facts = (banner_level != ChatBanner::Off).then(|| {
+            let storage = storage
+                .clone()
+                .filter(|_| banner_level == ChatBanner::Full && resume.is_none());
+            tokio::spawn(chat_banner::gather_startup_facts(
+                working_dir.clone(),
+                storage,
+                chat_banner::STARTUP_BUDGET,
+            ))
+        });
+
+        // Create provider, answering repeated requests from the response cache
+        // when it is enabled. Requests that reach the provider are logged and
+        // checked against the monthly budget.
+        let usage_ledger = start_usage_ledger(&config, provider_type)?;
+        let (provider_box, _) = wrap_with_cache(
+            wrap_with_budget(
+                wrap_with_quirks(
+                    create_provider(provider_type, &config.provider)?,
+                    &config.provider.quirks,
+                ),
+                usage_ledger.as_ref(),
+            ),
+            provider_type,
+            &config.provider.cache,
+            &Paths::from_config(&config)?,
+        );
 
         // Convert provider to Arc for sharing with subagent and main agent
         let provider: Arc<dyn crate::providers::Provider> = Arc::from(provider_box);
@@ -506,16 +970,35 @@ pub mod chat {
             Arc::clone(&provider), // Parent provider (used if no override)
             &config.provider,      // Provider config for override instantiation
             config.agent.clone(),  // Agent config with subagent settings
-            tools.clone(),         // Parent registry for filtering
+            tools.fork(),          // Parent registry for filtering
             0,                     // Root depth (main agent is depth 0)
         )?;
         tools.register("subagent", Arc::new(subagent_tool));
 
+        // Select full or concise mode prompts for the active model
+        let mut prompt_style =
+            crate::prompts::detect_prompt_style(&config.provider, provider_type, provider.as_ref())
+                .await;
+
         // Initialize agent with conversation
+        let telemetry = TelemetrySink::from_config(&config.telemetry, process_session_id())?;
+        if let Some(telemetry) = &telemetry {
+            telemetry.start_session("chat", provider_type, Some(provider.get_current_model()));
+        }
+        // Ends the session as an error if chat returns before the goodbye
+        let _telemetry_guard = telemetry.as_ref().map(|telemetry| telemetry.end_on_drop());
+
+        // Revision and length of the stored conversation this session
+        // continues; saves fail instead of overwriting another session's
+        let mut chat_save_state = ChatSaveState::default();
         let mut agent = if let Some(ref resume_id) = resume {
             if let Some(storage) = &storage {
+                // Read the revision before the messages so that a save landing
+                // in between shows up as a conflict on the first save
+                let resumed_revision = chat_save::stored_revision(storage, resume_id);
                 match storage.load_conversation(resume_id) {
                     Ok(Some((title, _model, messages))) => {
+                        chat_save_state = ChatSaveState::new(resumed_revision, messages.len());
                         // Diagnostic logging to help track resume issues (message counts, sample content)
                         let user_count = messages.iter().filter(|m| m.role == "user").count();
                         tracing::debug!(
@@ -532,9 +1015,25 @@ pub mod chat {
                             }
                         }
 
-                        println!("Resuming conversation: {}", title.cyan());
-                        let conversation = crate::agent::Conversation::with_history(
-                            uuid::Uuid::parse_str(resume_id)
+                        ui_println!("Resuming conversation: {}", title.cyan());
+                        let pinned_indices =
+                            storage.load_pinned_messages(resume_id).unwrap_or_else(|e| {
+                                tracing::warn!("Failed to load pinned messages: {}", e);
+                                Vec::new()
+                            });
+                        let message_cwds =
+                            storage.load_message_cwds(resume_id).unwrap_or_else(|e| {
+                                tracing::warn!("Failed to load message directories: {}", e);
+                                Vec::new()
+                            });
+                        // Keep the stored ID when resuming by prefix or session key
+                        let stored_id = storage
+                            .resolve_conversation_id(resume_id)
+                            .ok()
+                            .flatten()
+                            .unwrap_or_else(|| resume_id.clone());
+                        let mut conversation = crate::agent::Conversation::with_history(
+                            uuid::Uuid::parse_str(&stored_id)
                                 .unwrap_or_else(|_| uuid::Uuid::new_v4()),
                             title,
                             messages,
@@ -542,6 +1041,12 @@ pub mod chat {
                             config.agent.conversation.min_retain_turns,
                             config.agent.conversation.prune_threshold as f64,
                         );
+                        for index in pinned_indices {
+                            if let Err(e) = conversation.pin(index) {
+                                tracing::debug!("Skipping stored pin {}: {}", index, e);
+                            }
+                        }
+                        conversation.set_message_cwds(message_cwds);
                         let mut agent = Agent::with_conversation_and_shared_provider(
                             Arc::clone(&provider),
                             tools,
@@ -563,18 +1068,16 @@ pub mod chat {
                             }
                         }
 
-                        let mut transient_system_messages = Vec::new();
-                        if let Some(active_skill_prompt) =
-                            build_active_skill_prompt_injection(&active_skill_registry)?
-                        {
-                            transient_system_messages.push(active_skill_prompt);
-                        }
-                        agent.set_transient_system_messages(transient_system_messages);
+                        agent.set_transient_system_messages(build_chat_system_messages(
+                            &mode_state,
+                            prompt_style,
+                            &active_skill_registry,
+                        )?);
 
                         agent
                     }
                     Ok(None) => {
-                        println!(
+                        ui_println!(
                             "{}",
                             format!("Conversation {} not found, starting new one.", resume_id)
                                 .yellow()
@@ -590,19 +1093,17 @@ pub mod chat {
                                 .add_system_message(disclosure.clone());
                         }
 
-                        let mut transient_system_messages = Vec::new();
-                        if let Some(active_skill_prompt) =
-                            build_active_skill_prompt_injection(&active_skill_registry)?
-                        {
-                            transient_system_messages.push(active_skill_prompt);
-                        }
-                        agent.set_transient_system_messages(transient_system_messages);
+                        agent.set_transient_system_messages(build_chat_system_messages(
+                            &mode_state,
+                            prompt_style,
+                            &active_skill_registry,
+                        )?);
 
                         agent
                     }
                     Err(e) => {
                         tracing::error!("Failed to load conversation: {}", e);
-                        println!("{}", "Failed to load conversation, starting new one.".red());
+                        ui_println!("{}", "Failed to load conversation, starting new one.".red());
                         let mut agent = Agent::new_from_shared_provider(
                             Arc::clone(&provider),
                             tools,
@@ -614,19 +1115,17 @@ pub mod chat {
                                 .add_system_message(disclosure.clone());
                         }
 
-                        let mut transient_system_messages = Vec::new();
-                        if let Some(active_skill_prompt) =
-                            build_active_skill_prompt_injection(&active_skill_registry)?
-                        {
-                            transient_system_messages.push(active_skill_prompt);
-                        }
-                        agent.set_transient_system_messages(transient_system_messages);
+                        agent.set_transient_system_messages(build_chat_system_messages(
+                            &mode_state,
+                            prompt_style,
+                            &active_skill_registry,
+                        )?);
 
                         agent
                     }
                 }
             } else {
-                println!(
+                ui_println!(
                     "{}",
                     "Storage not available, starting new conversation.".yellow()
                 );
@@ -641,13 +1140,11 @@ pub mod chat {
                         .add_system_message(disclosure.clone());
                 }
 
-                let mut transient_system_messages = Vec::new();
-                if let Some(active_skill_prompt) =
-                    build_active_skill_prompt_injection(&active_skill_registry)?
-                {
-                    transient_system_messages.push(active_skill_prompt);
-                }
-                agent.set_transient_system_messages(transient_system_messages);
+                agent.set_transient_system_messages(build_chat_system_messages(
+                    &mode_state,
+                    prompt_style,
+                    &active_skill_registry,
+                )?);
 
                 agent
             }
@@ -663,19 +1160,52 @@ pub mod chat {
                     .add_system_message(disclosure.clone());
             }
 
-            let mut transient_system_messages = Vec::new();
-            if let Some(active_skill_prompt) =
-                build_active_skill_prompt_injection(&active_skill_registry)?
-            {
-                transient_system_messages.push(active_skill_prompt);
-            }
-            agent.set_transient_system_messages(transient_system_messages);
+            agent.set_transient_system_messages(build_chat_system_messages(
+                &mode_state,
+                prompt_style,
+                &active_skill_registry,
+            )?);
 
             agent
         };
+        if let Some(id) = session_id {
+            if agent.conversation().id() != id {
+                // Replacing the stored session: the first save expects the
+                // revision stored now, so a concurrent save still conflicts
+                chat_save_state = ChatSaveState::new(
+                    storage.as_ref().map_or(0, |storage| {
+                        chat_save::stored_revision(storage, &id.to_string())
+                    }),
+                    0,
+                );
+            }
+            agent.conversation_mut().set_id(id);
+            if append_to_session {
+                agent
+                    .conversation_mut()
+                    .add_system_message(crate::storage::SESSION_SEPARATOR);
+            }
+        }
+        agent.set_telemetry(telemetry.clone());
+        agent.set_overflow_summarizer(build_overflow_summarizer(&config, &provider).await);
+        agent.set_output_pager(build_output_pager(&config));
+        agent.set_mode_gate(Some(build_mode_gate(&mode_state, &config, &working_dir)?));
+        agent.set_change_review(build_change_review(&mode_state));
+        agent.conversation_mut().set_cwd(Some(working_dir));
+
+        // Mirror the conversation to a markdown transcript when requested
+        let mut transcript = match config.agent.chat.transcript_path.as_deref() {
+            Some(path) => {
+                let transcript = Transcript::open(Path::new(path))?;
+                ui_println!("{}", format!("Writing transcript to {}", path).dimmed());
+                Some(transcript)
+            }
+            None => None,
+        };
 
-        // Create readline instance
+        // Create readline instance; bracketed paste keeps a paste in one read
         let mut rl = DefaultEditor::new()?;
+        let editor = ExternalEditor::from_env();
 
         // Populate readline history with previous user inputs when resuming
         if resume.is_some() {
@@ -702,13 +1232,36 @@ pub mod chat {
         }
 
         // Initialize mention cache for file content injection
-        let mut mention_cache = crate::mention_parser::MentionCache::new();
+        let mention_cache = crate::mention_parser::MentionCache::new();
+        agent.set_mention_cache(Some(mention_cache.clone()));
         let max_file_size = config.agent.tools.max_file_read_size as u64;
+        let max_image_bytes = config.agent.chat.max_image_bytes;
+
+        // Images queued with /attach, sent with the next user message
+        let mut pending_images: Vec<ImagePromptPart> = Vec::new();
 
-        // Display welcome banner with current mode and safety
-        print_welcome_banner(&mode_state.chat_mode, &mode_state.safety_mode);
+        // Code blocks of the last response, for /blocks, /copy, and /apply
+        let mut response_blocks = ResponseBlocks::default();
+        let mut clipboard = SessionClipboard::default();
+
+        // Display the startup banner at the configured level
+        let mut banner = BannerContext::new(
+            provider_type,
+            provider.get_current_model(),
+            &mode_state,
+            session_cwd.current(),
+        );
+        banner.mcp_tools = mcp_tool_count;
+        if let Some(facts) = startup_facts {
+            banner.facts = facts.await.unwrap_or_default();
+        }
+        print_welcome_banner(banner_level, &banner);
+        print_budget_warning(usage_ledger.as_deref());
 
         loop {
+            // Write what the last turn or command added to the transcript
+            flush_transcript(&mut transcript);
+
             // Build a prompt that includes provider/model when available.
             let current_model: Option<String> = {
                 let m = agent.provider().get_current_model();
@@ -724,30 +1277,82 @@ pub mod chat {
             } else {
                 mode_state.format_colored_prompt()
             };
+            let prompt = with_cwd_indicator(prompt, &session_cwd);
 
             match rl.readline(&prompt) {
                 Ok(line) => {
+                    // Join continuation lines and pastes into one message
+                    let line = match compose_message(
+                        line,
+                        &mut rl,
+                        &editor,
+                        config.agent.chat.editor_on_multiline,
+                    ) {
+                        Ok(Some(line)) => line,
+                        Ok(None) => continue,
+                        Err(e) => {
+                            ui_eprintln!("{}", e.to_string().red());
+                            ui_println!();
+                            continue;
+                        }
+                    };
                     let trimmed = line.trim();
                     if trimmed.is_empty() {
                         continue;
                     }
 
                     // Check for special commands first
-                    match parse_special_command(trimmed) {
+                    let command = parse_special_command(trimmed);
+                    let mut edited: Option<String> = None;
+                    if matches!(&command, Ok(parsed) if *parsed != SpecialCommand::None) {
+                        record_transcript_metadata(
+                            &mut transcript,
+                            &format!("Command: {}", trimmed),
+                        );
+                    }
+                    match command {
                         Ok(SpecialCommand::SwitchMode(new_mode)) => {
+                            if let Err(e) = read_only.check_mode(new_mode) {
+                                ui_eprintln!("{}\n", e.to_string().red());
+                                record_transcript_metadata(&mut transcript, &e.to_string());
+                                continue;
+                            }
+                            let old_mode = mode_state.chat_mode;
                             handle_mode_switch(
                                 &mut agent,
                                 &mut mode_state,
                                 new_mode,
                                 &config,
-                                &working_dir,
+                                session_cwd.current(),
                                 provider_type,
+                                prompt_style,
+                                &active_skill_registry,
+                                usage_ledger.as_ref(),
                             )?;
+                            if mode_state.chat_mode != old_mode {
+                                record_transcript_metadata(
+                                    &mut transcript,
+                                    &format!(
+                                        "Switched from {} to {} mode",
+                                        old_mode, mode_state.chat_mode
+                                    ),
+                                );
+                            }
                             continue;
                         }
                         Ok(SpecialCommand::SwitchSafety(new_safety)) => {
                             let old_safety = mode_state.switch_safety(new_safety);
-                            println!("Switched from {} to {} mode\n", old_safety, new_safety);
+                            agent.set_change_review(build_change_review(&mode_state));
+                            agent.set_transient_system_messages(build_chat_system_messages(
+                                &mode_state,
+                                prompt_style,
+                                &active_skill_registry,
+                            )?);
+                            ui_println!("Switched from {} to {} mode\n", old_safety, new_safety);
+                            record_transcript_metadata(
+                                &mut transcript,
+                                &format!("Switched from {} to {} mode", old_safety, new_safety),
+                            );
                             continue;
                         }
                         Ok(SpecialCommand::ShowStatus) => {
@@ -756,6 +1361,36 @@ pub mod chat {
                             print_status_display(&mode_state, tool_count, conversation_len);
                             continue;
                         }
+                        Ok(SpecialCommand::ShowToolStats) => {
+                            ui_println!();
+                            print_tool_metrics(&agent.tool_metrics().summary());
+                            let health = agent.tool_health().report();
+                            if !health.is_empty() {
+                                ui_println!();
+                                print_tool_health(&health);
+                            }
+                            let hygiene = agent.conversation().history_hygiene();
+                            if !hygiene.is_empty() {
+                                ui_println!();
+                                ui_println!("{} {}", "History hygiene:".cyan().bold(), hygiene);
+                            }
+                            if let Some(report) = agent.tool_fit_report() {
+                                ui_println!();
+                                print_tool_fit_report(report);
+                            }
+                            ui_println!();
+                            continue;
+                        }
+                        Ok(SpecialCommand::ResetToolStats) => {
+                            agent.tool_metrics().reset();
+                            ui_println!("Tool statistics reset.\n");
+                            continue;
+                        }
+                        Ok(SpecialCommand::McpStatus) => {
+                            ui_println!();
+                            mcp::print_mcp_status(&config.mcp, mcp_manager.as_ref()).await;
+                            continue;
+                        }
                         Ok(SpecialCommand::Help) => {
                             print_help();
                             continue;
@@ -784,7 +1419,7 @@ pub mod chat {
                             {
                                 Ok(_) => {}
                                 Err(e) => {
-                                    eprintln!("Failed to show model info: {}", e);
+                                    ui_eprintln!("Failed to show model info: {}", e);
                                 }
                             }
                             continue;
@@ -792,14 +1427,17 @@ pub mod chat {
                         Ok(SpecialCommand::Auth(provider_opt)) => {
                             let provider_to_auth =
                                 provider_opt.unwrap_or_else(|| provider_type.to_string());
-                            println!("Starting authentication for provider: {}", provider_to_auth);
+                            ui_println!(
+                                "Starting authentication for provider: {}",
+                                provider_to_auth
+                            );
 
                             match auth::authenticate(config.clone(), provider_to_auth).await {
                                 Ok(_) => {
-                                    println!("Authentication completed.");
+                                    ui_println!("Authentication completed.");
                                 }
                                 Err(e) => {
-                                    eprintln!("Authentication failed: {}", e);
+                                    ui_eprintln!("Authentication failed: {}", e);
                                 }
                             }
                             continue;
@@ -810,8 +1448,12 @@ pub mod chat {
                                 &model_name,
                                 &mut rl,
                                 &config,
-                                &working_dir,
+                                session_cwd.current(),
                                 provider_type,
+                                &mode_state,
+                                &mut prompt_style,
+                                &active_skill_registry,
+                                usage_ledger.as_ref(),
                             )
                             .await?;
                             continue;
@@ -820,6 +1462,22 @@ pub mod chat {
                             handle_show_context_info(&agent).await;
                             continue;
                         }
+                        Ok(SpecialCommand::ContextView) => {
+                            handle_show_context_view(&agent).await;
+                            continue;
+                        }
+                        Ok(SpecialCommand::ContextFull(number)) => {
+                            handle_show_context_message(&agent, number);
+                            continue;
+                        }
+                        Ok(SpecialCommand::ContextCache) => {
+                            handle_show_mention_cache(&mention_cache).await;
+                            continue;
+                        }
+                        Ok(SpecialCommand::PruningLog) => {
+                            handle_show_pruning_log(&agent);
+                            continue;
+                        }
                         Ok(SpecialCommand::ContextSummary { model }) => {
                             // Determine which model to use for summarization
                             let summary_model = model
@@ -831,7 +1489,10 @@ pub mod chat {
                                         .unwrap_or_else(|| provider_type.to_string())
                                 });
 
-                            println!("Summarizing conversation using model: {}...", summary_model);
+                            ui_println!(
+                                "Summarizing conversation using model: {}...",
+                                summary_model
+                            );
 
                             // Perform summarization
                             match perform_context_summary(
@@ -842,12 +1503,12 @@ pub mod chat {
                             .await
                             {
                                 Ok(_summary_text) => {
-                                    println!(
+                                    ui_println!(
                                         "\nContext summarized. New conversation started with summary in context.\n"
                                     );
                                 }
                                 Err(e) => {
-                                    eprintln!("Failed to summarize context: {}\n", e);
+                                    ui_eprintln!("Failed to summarize context: {}\n", e);
                                 }
                             }
                             continue;
@@ -855,12 +1516,106 @@ pub mod chat {
                         Ok(SpecialCommand::ToggleSubagents(enable)) => {
                             if enable {
                                 mode_state.enable_subagents();
-                                println!("Subagent delegation enabled for subsequent requests");
+                                ui_println!("Subagent delegation enabled for subsequent requests");
                             } else {
                                 mode_state.disable_subagents();
-                                println!("Subagent delegation disabled");
+                                ui_println!("Subagent delegation disabled");
+                            }
+                            ui_println!();
+                            continue;
+                        }
+                        Ok(SpecialCommand::Tag(tag)) => {
+                            handle_tag_session(
+                                storage.as_ref(),
+                                &mut agent,
+                                &mut chat_save_state,
+                                &tag,
+                                current_model.as_deref(),
+                            );
+                            continue;
+                        }
+                        Ok(SpecialCommand::Pin(number)) => {
+                            handle_pin_message(
+                                storage.as_ref(),
+                                &mut agent,
+                                number,
+                                config.agent.conversation.warning_threshold as f64,
+                            );
+                            continue;
+                        }
+                        Ok(SpecialCommand::Attach(path)) => {
+                            use colored::Colorize;
+                            match mention_parser::load_image_attachment(
+                                &path,
+                                session_cwd.current(),
+                                max_image_bytes,
+                            )
+                            .await
+                            {
+                                Ok(image) => {
+                                    pending_images.push(image);
+                                    ui_println!(
+                                        "{}",
+                                        format!(
+                                            "Attached {} ({} pending); it will be sent with your next message",
+                                            path,
+                                            pending_images.len()
+                                        )
+                                        .green()
+                                    );
+                                }
+                                Err(e) => {
+                                    ui_eprintln!(
+                                        "{}",
+                                        format!("Failed to attach {}: {}", path, e).red()
+                                    );
+                                }
+                            }
+                            ui_println!();
+                            continue;
+                        }
+                        Ok(SpecialCommand::ChangeDirectory(path)) => {
+                            handle_change_directory(
+                                &mut agent,
+                                &mut session_cwd,
+                                path.as_deref(),
+                                &mode_state,
+                                &mut config,
+                                config_path,
+                                &mention_cache,
+                            )
+                            .await;
+                            continue;
+                        }
+                        Ok(SpecialCommand::Edit(draft)) => {
+                            match open_in_editor(&editor, draft.as_deref().unwrap_or("")) {
+                                Ok(Some(text)) => edited = Some(text),
+                                Ok(None) => continue,
+                                Err(e) => {
+                                    ui_eprintln!("{}", e.to_string().red());
+                                    ui_println!();
+                                    continue;
+                                }
                             }
-                            println!();
+                        }
+                        Ok(SpecialCommand::ListBlocks) => {
+                            ui_println!("{}", response_blocks.listing());
+                            continue;
+                        }
+                        Ok(SpecialCommand::CopyBlock(number)) => {
+                            handle_copy_block(&response_blocks, &mut clipboard, number);
+                            continue;
+                        }
+                        Ok(SpecialCommand::ApplyBlock { number, path }) => {
+                            handle_apply_block(
+                                &agent,
+                                &response_blocks,
+                                &mode_state,
+                                number,
+                                &path,
+                                &mut transcript,
+                            )
+                            .await;
                             continue;
                         }
                         Ok(SpecialCommand::Exit) => break,
@@ -870,28 +1625,39 @@ pub mod chat {
                         Err(e) => {
                             // Display command error
                             use colored::Colorize;
-                            eprintln!("{}", e.to_string().red());
-                            println!();
+                            ui_eprintln!("{}", e.to_string().red());
+                            ui_println!();
                             continue;
                         }
                     }
 
+                    // A message saved from /edit replaces the command line
+                    let trimmed = edited.as_deref().map(str::trim).unwrap_or(trimmed);
+
                     // Parse mentions from input
-                    let (mentions, cleaned_text) = match mention_parser::parse_mentions(trimmed) {
-                        Ok((m, c)) => {
-                            if !m.is_empty() {
-                                tracing::info!("Detected {} mentions in input", m.len());
-                                for mention in &m {
-                                    tracing::debug!("Mention: {:?}", mention);
+                    let parse_options = mention_parser::MentionParseOptions {
+                        strip: if config.agent.chat.strip_mentions {
+                            mention_parser::StripMode::ReplaceWithPlaceholder
+                        } else {
+                            mention_parser::StripMode::Keep
+                        },
+                    };
+                    let (mentions, cleaned_text) =
+                        match mention_parser::parse_mentions_with_options(trimmed, parse_options) {
+                            Ok((m, c)) => {
+                                if !m.is_empty() {
+                                    tracing::info!("Detected {} mentions in input", m.len());
+                                    for mention in &m {
+                                        tracing::debug!("Mention: {:?}", mention);
+                                    }
                                 }
+                                (m, c)
                             }
-                            (m, c)
-                        }
-                        Err(e) => {
-                            tracing::warn!("Failed to parse mentions: {}", e);
-                            (Vec::new(), trimmed.to_string())
-                        }
-                    };
+                            Err(e) => {
+                                tracing::warn!("Failed to parse mentions: {}", e);
+                                (Vec::new(), trimmed.to_string())
+                            }
+                        };
 
                     rl.add_history_entry(trimmed)?;
 
@@ -904,23 +1670,23 @@ pub mod chat {
                                 crate::mention_parser::Mention::File(fm) => {
                                     match crate::mention_parser::resolve_mention_path(
                                         &fm.path,
-                                        &working_dir,
+                                        session_cwd.current(),
                                     ) {
                                         Ok(path) => {
-                                            if mention_cache.get(&path).is_some() {
-                                                println!(
+                                            if mention_cache.contains(&path).await {
+                                                ui_println!(
                                                     "{}",
                                                     format!("Using cached @{}", fm.path).green()
                                                 );
                                             } else {
-                                                println!(
+                                                ui_println!(
                                                     "{}",
                                                     format!("Loading @{}", fm.path).cyan()
                                                 );
                                             }
                                         }
                                         Err(e) => {
-                                            println!(
+                                            ui_println!(
                                                 "{}",
                                                 format!("Loading @{} (path error: {})", fm.path, e)
                                                     .yellow()
@@ -929,34 +1695,99 @@ pub mod chat {
                                     }
                                 }
                                 crate::mention_parser::Mention::Url(um) => {
-                                    println!("{}", format!("Fetching @url:{}", um.url).cyan());
+                                    ui_println!("{}", format!("Fetching @url:{}", um.url).cyan());
                                 }
                                 crate::mention_parser::Mention::Search(sm) => {
-                                    println!(
+                                    ui_println!(
                                         "{}",
                                         format!("Searching @search:\"{}\"", sm.pattern).cyan()
                                     );
                                 }
                                 crate::mention_parser::Mention::Grep(gm) => {
-                                    println!(
+                                    ui_println!(
                                         "{}",
                                         format!("Searching @grep:\"{}\"", gm.pattern).cyan()
                                     );
                                 }
+                                crate::mention_parser::Mention::Semantic(sm) => {
+                                    ui_println!(
+                                        "{}",
+                                        format!("Searching @semantic:\"{}\"", sm.pattern).cyan()
+                                    );
+                                }
+                                crate::mention_parser::Mention::Image(im) => {
+                                    ui_println!(
+                                        "{}",
+                                        format!("Attaching @image:{}", im.path).cyan()
+                                    );
+                                }
+                                crate::mention_parser::Mention::Diff(dm) => {
+                                    ui_println!(
+                                        "{}",
+                                        format!("Running git diff for {}", dm).cyan()
+                                    );
+                                }
                             }
                         }
                     }
 
-                    // Augment prompt with file contents from mentions
-                    let (augmented_prompt, load_errors, successes) =
-                        crate::mention_parser::augment_prompt_with_mentions(
+                    // Semantic mentions query the index for the session directory
+                    let semantic_search = if mentions
+                        .iter()
+                        .any(|m| matches!(m, crate::mention_parser::Mention::Semantic(_)))
+                    {
+                        match crate::semantic_index::SemanticSearch::from_config(
+                            &config,
+                            session_cwd.current(),
+                        ) {
+                            Ok(search) => Some(search),
+                            Err(e) => {
+                                tracing::warn!("Semantic search unavailable: {}", e);
+                                None
+                            }
+                        }
+                    } else {
+                        None
+                    };
+
+                    // Load mention contents; they are prepended to the prompt below
+                    let (mut mention_parts, mut load_errors, mut successes) =
+                        crate::mention_parser::load_mention_parts(
                             &mentions,
-                            &cleaned_text,
-                            &working_dir,
+                            session_cwd.current(),
                             max_file_size,
-                            &mut mention_cache,
+                            &mention_cache,
+                            NetworkPolicy::from_config(&config),
+                            semantic_search.as_ref(),
+                        )
+                        .await;
+
+                    // Track mentioned files so later external edits are reported
+                    for mention in &mentions {
+                        let crate::mention_parser::Mention::File(file_mention) = mention else {
+                            continue;
+                        };
+                        if load_errors.iter().any(|e| e.source == file_mention.path) {
+                            continue;
+                        }
+                        if let Ok(path) = crate::mention_parser::resolve_mention_path(
+                            &file_mention.path,
+                            session_cwd.current(),
+                        ) {
+                            agent.file_tracker().record(&path, &file_mention.path);
+                        }
+                    }
+
+                    // Load @image: mentions as image parts for the next message
+                    let (mention_images, image_errors, image_successes) =
+                        crate::mention_parser::load_image_mentions(
+                            &mentions,
+                            session_cwd.current(),
+                            max_image_bytes,
                         )
                         .await;
+                    load_errors.extend(image_errors);
+                    successes.extend(image_successes);
 
                     // Summarize mention load results...
                     use colored::Colorize;
@@ -986,21 +1817,30 @@ pub mod chat {
                                     m,
                                     crate::mention_parser::Mention::Search(_)
                                         | crate::mention_parser::Mention::Grep(_)
+                                        | crate::mention_parser::Mention::Semantic(_)
                                 )
                             })
                             .count();
+                        let total_images = mentions
+                            .iter()
+                            .filter(|m| matches!(m, crate::mention_parser::Mention::Image(_)))
+                            .count();
+                        let total_diffs = mentions
+                            .iter()
+                            .filter(|m| matches!(m, crate::mention_parser::Mention::Diff(_)))
+                            .count();
 
                         if !successes.is_empty() {
                             for msg in &successes {
-                                println!("{}", msg.green());
+                                ui_println!("{}", msg.green());
                             }
                         }
 
                         let failed = load_errors.len();
                         if failed == 0 {
-                            println!("{}", format!("Loaded {} mentions ({} files, {} urls, {} searches) — all succeeded", total_mentions, total_files, total_urls, total_searches).green());
+                            ui_println!("{}", format!("Loaded {} mentions ({} files, {} urls, {} searches, {} images, {} diffs) {} all succeeded", total_mentions, total_files, total_urls, total_searches, total_images, total_diffs, Glyph::Dash).green());
                         } else {
-                            println!(
+                            ui_println!(
                                 "{}",
                                 format!(
                                     "Loaded {} mentions: {} succeeded, {} failed",
@@ -1011,20 +1851,162 @@ pub mod chat {
                                 .yellow()
                             );
                             for error in &load_errors {
-                                eprintln!("{}", format!("Error: {}", error).red());
+                                ui_eprintln!("{}", format!("Error: {}", error).red());
                             }
                         }
                     }
 
-                    // Execute the prompt via the agent
-                    match agent.execute(augmented_prompt).await {
-                        Ok(response) => {
-                            println!("\n{}\n", response);
-
-                            // Check context status and display warnings if needed
-                            let warning_threshold =
-                                config.agent.conversation.warning_threshold as f64;
-                            let auto_summary_threshold =
+                    // Fetched content the injection scanner flagged is only
+                    // included once the user agrees; without a terminal it
+                    // stays in, carrying the warning written by the guard
+                    for part in mention_parts
+                        .iter_mut()
+                        .filter(|part| !part.findings.is_empty())
+                    {
+                        let source = match &part.mention {
+                            crate::mention_parser::Mention::Url(url_mention) => {
+                                url_mention.url.clone()
+                            }
+                            _ => "a mention".to_string(),
+                        };
+                        ui_eprintln!(
+                            "{}",
+                            format!(
+                                "Content from {} looks like a prompt injection attempt:",
+                                source
+                            )
+                            .yellow()
+                            .bold()
+                        );
+                        for finding in &part.findings {
+                            ui_eprintln!("  {} {}", Glyph::Dash, finding);
+                        }
+                        if std::io::stdin().is_terminal() {
+                            let include = matches!(
+                                rl.readline("Include it anyway? [y/N] "),
+                                Ok(answer) if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+                            );
+                            if !include {
+                                part.exclude();
+                                ui_println!("{}", format!("Left out {}", source).yellow());
+                            }
+                        }
+                    }
+
+                    // Ask before sending a prompt that takes up much of the context
+                    if let Some(report) = crate::agent::preflight::check(
+                        &config.agent.preflight,
+                        &agent.provider().get_current_model(),
+                        effective_context_limit(&agent).await,
+                        agent.conversation().token_count(),
+                        &cleaned_text,
+                        &mention_parts,
+                    ) {
+                        print_preflight_report(&report);
+                        if std::io::stdin().is_terminal() {
+                            let send = matches!(
+                                rl.readline("Send anyway? [y/N] "),
+                                Ok(answer) if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+                            );
+                            if !send {
+                                ui_println!("{}", "Prompt not sent. Press Up to edit it.".yellow());
+                                ui_println!();
+                                continue;
+                            }
+                        }
+                    }
+                    let augmented_prompt =
+                        crate::mention_parser::join_mention_parts(&mention_parts, &cleaned_text);
+
+                    // Images from /attach and @image: mentions travel in the same message
+                    let mut images = std::mem::take(&mut pending_images);
+                    images.extend(mention_images);
+
+                    // The transcript shows the message as typed, without mention contents
+                    if let Some(transcript) = transcript.as_mut() {
+                        transcript.user_message(trimmed);
+                    }
+
+                    // Block numbers refer to the response this prompt produces
+                    response_blocks.clear();
+
+                    // Execute the prompt via the agent, rendering tool output live
+                    let cancellation_token = tokio_util::sync::CancellationToken::new();
+                    let mut chat_observer = ChatToolOutputObserver;
+                    let mut transcript_observer;
+                    let observer: &mut dyn crate::agent::AgentObserver = match transcript.as_mut() {
+                        Some(transcript) => {
+                            transcript_observer =
+                                TranscriptObserver::new(transcript, &mut chat_observer);
+                            &mut transcript_observer
+                        }
+                        None => &mut chat_observer,
+                    };
+                    let result = if images.is_empty() {
+                        agent
+                            .execute_with_observer(augmented_prompt, &cancellation_token, observer)
+                            .await
+                    } else {
+                        let message = build_image_user_message(
+                            agent.provider(),
+                            provider_type,
+                            augmented_prompt,
+                            images,
+                        )
+                        .await;
+                        match message {
+                            Ok(message) => {
+                                agent
+                                    .execute_provider_messages_with_observer(
+                                        vec![message],
+                                        &cancellation_token,
+                                        observer,
+                                    )
+                                    .await
+                            }
+                            Err(e) => Err(e),
+                        }
+                    };
+                    match result {
+                        Ok(response) => {
+                            ui_println!("\n{}\n", response);
+
+                            response_blocks = ResponseBlocks::from_response(&response);
+                            if !response_blocks.is_empty() {
+                                ui_println!("{}\n", response_blocks.quick_actions().dimmed());
+                            }
+
+                            // The user may have approved leaving Planning mode mid-turn
+                            if let Some(chat_mode) = agent.chat_mode() {
+                                if chat_mode != mode_state.chat_mode {
+                                    let old_mode = mode_state.chat_mode;
+                                    mode_state.chat_mode = chat_mode;
+                                    agent.set_transient_system_messages(
+                                        build_chat_system_messages(
+                                            &mode_state,
+                                            prompt_style,
+                                            &active_skill_registry,
+                                        )?,
+                                    );
+                                    ui_println!(
+                                        "Switched from {} to {} mode\n",
+                                        old_mode,
+                                        mode_state.chat_mode
+                                    );
+                                    record_transcript_metadata(
+                                        &mut transcript,
+                                        &format!(
+                                            "Switched from {} to {} mode",
+                                            old_mode, mode_state.chat_mode
+                                        ),
+                                    );
+                                }
+                            }
+
+                            // Check context status and display warnings if needed
+                            let warning_threshold =
+                                config.agent.conversation.warning_threshold as f64;
+                            let auto_summary_threshold =
                                 config.agent.conversation.auto_summary_threshold as f64;
 
                             {
@@ -1043,7 +2025,7 @@ pub mod chat {
                                             percentage,
                                             tokens_remaining,
                                         } => {
-                                            println!(
+                                            ui_println!(
                                                 "{}",
                                                 format!(
                                                     "WARNING: Context window is {:.0}% full",
@@ -1051,17 +2033,17 @@ pub mod chat {
                                                 )
                                                 .yellow()
                                             );
-                                            println!(
+                                            ui_println!(
                                                 "   {} tokens remaining. Consider running '/context summary' to free up space.",
-                                                tokens_remaining
+                                                format_tokens(tokens_remaining as u64)
                                             );
-                                            println!();
+                                            ui_println!();
                                         }
                                         crate::agent::ContextStatus::Critical {
                                             percentage,
                                             tokens_remaining,
                                         } => {
-                                            println!(
+                                            ui_println!(
                                                 "{}",
                                                 format!(
                                                     "CRITICAL: Context window is {:.0}% full!",
@@ -1069,14 +2051,14 @@ pub mod chat {
                                                 )
                                                 .red()
                                             );
-                                            println!(
+                                            ui_println!(
                                                 "   Only {} tokens remaining!",
-                                                tokens_remaining
+                                                format_tokens(tokens_remaining as u64)
                                             );
-                                            println!(
+                                            ui_println!(
                                                 "   Run '/context summary' to free up space or risk losing context."
                                             );
-                                            println!();
+                                            ui_println!();
                                         }
                                         crate::agent::ContextStatus::Normal => {
                                             // No warning needed
@@ -1103,15 +2085,14 @@ pub mod chat {
                                 };
 
                                 if should_update {
-                                    agent.conversation_mut().set_title(title.clone());
+                                    agent.conversation_mut().set_title(title);
                                 }
 
-                                let conv = agent.conversation();
-                                if let Err(e) = storage.save_conversation(
-                                    &conv.id().to_string(),
-                                    &title,
+                                if let Err(e) = save_chat_turn(
+                                    storage,
+                                    &mut agent,
                                     current_model.as_deref(),
-                                    conv.messages(),
+                                    &mut chat_save_state,
                                 ) {
                                     tracing::error!("Failed to save conversation: {}", e);
                                     // Optional: notify user?
@@ -1119,16 +2100,18 @@ pub mod chat {
                             }
                         }
                         Err(e) => {
-                            eprintln!("Error: {}\n", e);
+                            ui_eprintln!("Error: {}\n", e);
+                            record_transcript_metadata(&mut transcript, &format!("Error: {}", e));
                         }
                     }
+                    print_budget_warning(usage_ledger.as_deref());
                 }
                 Err(ReadlineError::Interrupted) => {
-                    println!("CTRL-C");
+                    ui_println!("CTRL-C");
                     break;
                 }
                 Err(ReadlineError::Eof) => {
-                    println!("CTRL-D");
+                    ui_println!("CTRL-D");
                     break;
                 }
                 Err(err) => {
@@ -1138,7 +2121,21 @@ pub mod chat {
             }
         }
 
-        println!("Goodbye!");
+        flush_transcript(&mut transcript);
+
+        let tool_summary = agent.tool_metrics().summary();
+        if !tool_summary.is_empty() {
+            ui_println!("\nTool usage this session:");
+            print_tool_metrics(&tool_summary);
+        }
+        if let Some(telemetry) = &telemetry {
+            telemetry.end_session(TelemetryStatus::Success);
+        }
+        if let Some(ledger) = &usage_ledger {
+            ledger.flush().await;
+        }
+
+        ui_println!("Goodbye!");
         Ok(())
     }
 
@@ -1153,38 +2150,17 @@ pub mod chat {
     /// # Returns
     ///
     /// Returns a configured ToolRegistry or an error
-    /// Display welcome banner at the start of interactive chat mode
+    /// Display the startup banner at the start of interactive chat mode
     ///
-    /// Shows a formatted banner with the application name, current mode,
-    /// safety mode, and basic instructions.
+    /// Prints nothing when `level` is `Off`. See
+    /// [`render_banner`](super::chat_banner::render_banner).
     ///
     /// # Arguments
     ///
-    /// * `mode` - The initial chat mode
-    /// * `safety` - The initial safety mode
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// use xzatoma::chat_mode::{ChatMode, SafetyMode};
-    ///
-    /// let mode = ChatMode::Planning;
-    /// let safety = SafetyMode::AlwaysConfirm;
-    /// // Use public helpers for verification instead of internal display functions
-    /// assert!(mode.description().len() > 0);
-    /// assert!(safety.description().len() > 0);
-    /// ```
-    fn print_welcome_banner(mode: &ChatMode, safety: &SafetyMode) {
-        println!("\n╔══════════════════════════════════════════════════════════════╗");
-        println!("║         XZatoma Interactive Chat Mode - Welcome!             ║");
-        println!("╚══════════════════════════════════════════════════════════════╝\n");
-        println!("Mode:   {} ({})", mode.colored_tag(), mode.description());
-        println!(
-            "Safety: {} ({})\n",
-            safety.colored_tag(),
-            safety.description()
-        );
-        println!("Type '/help' for available commands, 'exit' to quit\n");
+    /// * `level` - How much the banner shows, from `agent.chat.banner`
+    /// * `context` - Provider, mode, directory, and startup facts to show
+    fn print_welcome_banner(level: ChatBanner, context: &BannerContext) {
+        ui_print!("{}", chat_banner::render_banner(level, context));
     }
 
     /// Display detailed status information about the current session
@@ -1209,6 +2185,375 @@ pub mod chat {
     /// assert_eq!(state.safety_mode, SafetyMode::AlwaysConfirm);
     /// assert!(state.format_colored_prompt().len() > 0);
     /// ```
+    /// Renders tool activity in the chat terminal
+    ///
+    /// Prints a banner when a tool starts, then its streamed output lines,
+    /// indented and dimmed, while it runs.
+    struct ChatToolOutputObserver;
+
+    impl crate::agent::AgentObserver for ChatToolOutputObserver {
+        fn on_event(&mut self, event: crate::agent::AgentExecutionEvent) {
+            use crate::agent::AgentExecutionEvent;
+            use crate::tools::ToolOutputStream;
+
+            match event {
+                AgentExecutionEvent::ToolCallStarted { name, .. } => {
+                    ui_println!("{}", format!("> {}", name).cyan());
+                }
+                AgentExecutionEvent::ToolOutputChunk { stream, line, .. } => match stream {
+                    ToolOutputStream::Stdout => ui_println!("    {}", line.dimmed()),
+                    ToolOutputStream::Stderr => ui_eprintln!("    {}", line.yellow().dimmed()),
+                },
+                AgentExecutionEvent::ProviderCacheHit { .. } => {
+                    ui_println!("{}", "(response served from provider cache)".dimmed());
+                }
+                AgentExecutionEvent::ContextCompacted {
+                    limit,
+                    messages_summarized,
+                    tool_outputs_dropped,
+                    ..
+                } => {
+                    let notice = format!(
+                        "Request exceeded the {}-token context window; summarized {} earlier messages and dropped {} tool outputs, then retried",
+                        limit, messages_summarized, tool_outputs_dropped
+                    );
+                    ui_println!("{}", notice.yellow());
+                }
+                AgentExecutionEvent::ConversationPruned { report } => {
+                    ui_println!("{}", report.to_string().dimmed());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Print the budget warning once spending crosses a threshold
+    fn print_budget_warning(ledger: Option<&UsageLedger>) {
+        if let Some(warning) = ledger.and_then(UsageLedger::take_warning) {
+            ui_println!("{}\n", warning.yellow().bold());
+        }
+    }
+
+    /// Turns the line just read into a whole message
+    ///
+    /// A multi-line paste is sent as one message, or opened in the editor
+    /// when `editor_on_multiline` is set. A line ending in `\` keeps reading
+    /// at the continuation prompt, where `/edit` moves the draft to the
+    /// editor and Ctrl-C or Ctrl-D discards it.
+    ///
+    /// Returns `None` when there is nothing to send.
+    fn compose_message(
+        line: String,
+        rl: &mut DefaultEditor,
+        editor: &dyn DraftEditor,
+        editor_on_multiline: bool,
+    ) -> Result<Option<String>> {
+        let lines = composer::split_pasted(&line);
+        if lines.len() > 1 {
+            let pasted = lines.join("\n");
+            if editor_on_multiline {
+                return open_in_editor(editor, &pasted);
+            }
+            ui_println!(
+                "{}",
+                format!("Pasted {} lines; sending them as one message", lines.len()).dimmed()
+            );
+            return Ok(Some(pasted));
+        }
+
+        let mut draft = Draft::default();
+        let mut next = line;
+        loop {
+            if let Some(message) = draft.push(&next) {
+                return Ok(Some(message));
+            }
+            match rl.readline(composer::CONTINUATION_PROMPT) {
+                Ok(more) if more.trim() == "/edit" => {
+                    return open_in_editor(editor, &draft.take());
+                }
+                Ok(more) => next = more,
+                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
+                    ui_println!("{}", "Draft discarded".dimmed());
+                    return Ok(None);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Opens `draft` in the editor and echoes the message that will be sent
+    ///
+    /// Returns `None`, after a note, when the editor saved an empty file.
+    fn open_in_editor(editor: &dyn DraftEditor, draft: &str) -> Result<Option<String>> {
+        let message = composer::edit_message(editor, draft)?;
+        match &message {
+            Some(text) => ui_println!("{}\n", text),
+            None => ui_println!("{}\n", "Empty message; nothing sent".dimmed()),
+        }
+        Ok(message)
+    }
+
+    /// Records a metadata line in the transcript, if one is being written
+    fn record_transcript_metadata(transcript: &mut Option<Transcript>, text: &str) {
+        if let Some(transcript) = transcript {
+            transcript.metadata(text);
+        }
+    }
+
+    /// Writes pending transcript entries
+    ///
+    /// The first failed write is reported; the transcript is disabled after
+    /// it and the chat goes on.
+    fn flush_transcript(transcript: &mut Option<Transcript>) {
+        if let Some(transcript) = transcript {
+            if let Err(e) = transcript.flush() {
+                ui_eprintln!(
+                    "{}",
+                    format!(
+                        "Warning: the transcript can no longer be written ({}); continuing without it",
+                        e
+                    )
+                    .yellow()
+                );
+            }
+        }
+    }
+
+    /// Handle `/copy N`, copying a code block of the last response
+    ///
+    /// Without a clipboard, as over SSH, the block is printed without
+    /// decoration so it can be selected in the terminal.
+    fn handle_copy_block(blocks: &ResponseBlocks, clipboard: &mut SessionClipboard, number: usize) {
+        let block = match blocks.get(number) {
+            Ok(block) => block,
+            Err(e) => {
+                ui_eprintln!("{}", e.to_string().red());
+                ui_println!();
+                return;
+            }
+        };
+        match clipboard.copy(&block.content) {
+            Ok(()) => ui_println!(
+                "{}\n",
+                format!("Copied code block {} ({})", number, block.label()).green()
+            ),
+            Err(e) => {
+                ui_eprintln!(
+                    "{}",
+                    format!("{}; printing code block {} instead", e, number).yellow()
+                );
+                ui_println!();
+                ui_print!("{}", block.content);
+                ui_println!();
+            }
+        }
+    }
+
+    /// Handle `/apply N <path>`, writing a code block of the last response
+    ///
+    /// The write goes through the session's `write_file` tool and, unless
+    /// the safety mode never confirms, the same change review as the
+    /// agent's own writes.
+    async fn handle_apply_block(
+        agent: &Agent,
+        blocks: &ResponseBlocks,
+        mode_state: &ChatModeState,
+        number: usize,
+        path: &str,
+        transcript: &mut Option<Transcript>,
+    ) {
+        let result = match blocks.get(number) {
+            Ok(_) if mode_state.read_only => Err(XzatomaError::ReadOnly(
+                "code blocks cannot be applied".to_string(),
+            )),
+            Ok(block) => {
+                let review = build_change_review(mode_state);
+                super::code_blocks::apply_block(agent.tools(), review.as_deref(), block, path).await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(result) if result.success => {
+                ui_println!(
+                    "{}\n",
+                    format!("Applied code block {} to {}", number, path).green()
+                );
+                record_transcript_metadata(
+                    transcript,
+                    &format!("Applied code block {} to {}", number, path),
+                );
+            }
+            Ok(result) => {
+                ui_eprintln!("{}", result.to_message().red());
+                ui_println!();
+            }
+            Err(e) => {
+                ui_eprintln!("{}", e.to_string().red());
+                ui_println!();
+            }
+        }
+    }
+
+    /// Pin a message so pruning and summarization keep it verbatim
+    ///
+    /// Without a number the most recent user message is pinned; otherwise the
+    /// message with that number in the `/context` listing. Warns when pinned
+    /// messages alone reach the context warning threshold.
+    fn handle_pin_message(
+        storage: Option<&SqliteStorage>,
+        agent: &mut Agent,
+        number: Option<usize>,
+        warning_threshold: f64,
+    ) {
+        let index = match number {
+            None => agent
+                .conversation()
+                .last_user_message_index()
+                .ok_or_else(|| {
+                    XzatomaError::Command("There is no user message to pin yet".to_string())
+                }),
+            Some(number) => agent
+                .context_entries()
+                .get(number - 1)
+                .ok_or_else(|| {
+                    XzatomaError::Command(format!(
+                        "No message {} in context. Run '/context' to list messages.",
+                        number
+                    ))
+                })
+                .and_then(|entry| {
+                    entry.index.ok_or_else(|| {
+                        XzatomaError::Command(format!(
+                            "Message {} is injected for every request and is always kept",
+                            number
+                        ))
+                    })
+                }),
+        };
+
+        if let Err(e) = index.and_then(|index| agent.conversation_mut().pin(index)) {
+            ui_eprintln!("{}", format!("Failed to pin message: {}", e).red());
+            ui_println!();
+            return;
+        }
+
+        let conversation = agent.conversation();
+        ui_println!(
+            "Pinned message ({} pinned, {} tokens). Pinned messages are never pruned or summarized.",
+            conversation.pinned_indices().len(),
+            format_tokens(conversation.pinned_token_count() as u64)
+        );
+
+        // Persist right away when the conversation is already stored; new
+        // conversations record their pins on the next save
+        if let Some(storage) = storage {
+            if let Err(e) = storage.set_pinned_messages(
+                &conversation.id().to_string(),
+                &conversation.pinned_indices(),
+            ) {
+                tracing::error!("Failed to save pinned messages: {}", e);
+            }
+        }
+
+        if conversation.pinned_exceeds_warning(warning_threshold) {
+            ui_println!(
+                "{}",
+                format!(
+                    "Warning: pinned messages alone use {} of {} tokens. They cannot be pruned or summarized, so consider pinning less.",
+                    format_tokens(conversation.pinned_token_count() as u64),
+                    format_tokens(conversation.max_tokens() as u64)
+                )
+                .yellow()
+            );
+        }
+        ui_println!();
+    }
+
+    /// Save the conversation, asking on the terminal how to resolve a save
+    /// that another session got to first
+    fn save_chat_turn(
+        storage: &SqliteStorage,
+        agent: &mut Agent,
+        model: Option<&str>,
+        state: &mut ChatSaveState,
+    ) -> Result<()> {
+        let outcome = chat_save::save_chat_conversation(
+            storage,
+            agent.conversation_mut(),
+            model,
+            state,
+            &mut |conflict, unsaved| {
+                chat_save::ask_resolution(
+                    conflict,
+                    unsaved,
+                    &mut std::io::stdin().lock(),
+                    &mut std::io::stderr(),
+                )
+            },
+        )?;
+
+        match outcome {
+            ChatSaveOutcome::Saved => {}
+            ChatSaveOutcome::Merged { appended } => ui_println!(
+                "{}\n",
+                format!(
+                    "Reloaded the conversation and appended this session's {} unsaved message(s).",
+                    appended
+                )
+                .yellow()
+            ),
+            ChatSaveOutcome::Forked { from } => ui_println!(
+                "{}\n",
+                format!(
+                    "Saved as new conversation {} (forked from {}).",
+                    agent.conversation().id(),
+                    from
+                )
+                .yellow()
+            ),
+            ChatSaveOutcome::Skipped => {
+                ui_println!("{}\n", "Not saved; the next save will ask again.".yellow())
+            }
+        }
+        Ok(())
+    }
+
+    /// Tag the current conversation, saving it first if nothing was persisted yet
+    fn handle_tag_session(
+        storage: Option<&SqliteStorage>,
+        agent: &mut Agent,
+        state: &mut ChatSaveState,
+        tag: &str,
+        model: Option<&str>,
+    ) {
+        let Some(storage) = storage else {
+            ui_eprintln!(
+                "{}",
+                "Conversation storage is unavailable; cannot tag".red()
+            );
+            ui_println!();
+            return;
+        };
+
+        let result = storage
+            .conversation_exists(&agent.conversation().id().to_string())
+            .and_then(|exists| {
+                if !exists {
+                    save_chat_turn(storage, agent, model, state)?;
+                }
+                // A fork during the save gives the conversation a new ID
+                storage.add_conversation_tag(&agent.conversation().id().to_string(), tag)
+            });
+
+        match result {
+            Ok(tag) => ui_println!("Tagged this conversation with '{}'\n", tag),
+            Err(e) => {
+                ui_eprintln!("{}", format!("Failed to tag conversation: {}", e).red());
+                ui_println!();
+            }
+        }
+    }
+
     fn print_status_display(
         mode_state: &ChatModeState,
         tool_count: usize,
@@ -1216,15 +2561,13 @@ pub mod chat {
     ) {
         use colored::Colorize;
 
-        println!("\n╔══════════════════════════════════════════════════════════════╗");
-        println!("║                     XZatoma Session Status                   ║");
-        println!("╚══════════════════════════════════════════════════════════════╝\n");
-        println!(
+        ui_println!("\n{}\n", ui::banner("XZatoma Session Status", 64));
+        ui_println!(
             "Chat Mode:         {} ({})",
             mode_state.chat_mode.colored_tag(),
             mode_state.chat_mode.description()
         );
-        println!(
+        ui_println!(
             "Safety Mode:       {} ({})",
             mode_state.safety_mode.colored_tag(),
             mode_state.safety_mode.description()
@@ -1236,12 +2579,12 @@ pub mod chat {
         } else {
             "disabled".normal().to_string()
         };
-        println!("Subagents:        {}", subagent_status);
+        ui_println!("Subagents:        {}", subagent_status);
 
-        println!("Available Tools:   {}", tool_count);
-        println!("Conversation Size: {} messages", conversation_len);
-        println!("Prompt Format:     {}", mode_state.format_colored_prompt());
-        println!();
+        ui_println!("Available Tools:   {}", tool_count);
+        ui_println!("Conversation Size: {} messages", conversation_len);
+        ui_println!("Prompt Format:     {}", mode_state.format_colored_prompt());
+        ui_println!();
     }
 
     fn build_tools_for_mode(
@@ -1255,11 +2598,173 @@ pub mod chat {
             working_dir.to_path_buf(),
         )
         .with_tools_config(config.agent.tools.clone())
-        .with_terminal_config(config.agent.terminal.clone());
+        .with_terminal_config(config.agent.terminal.clone())
+        .with_network_policy(NetworkPolicy::from_config(config))
+        .with_read_only_policy(ReadOnlyPolicy::from_config(config))
+        .with_audit_log(AuditLog::from_config(
+            &config.agent.tools,
+            &Paths::from_config(config)?,
+        ))
+        .with_confirmation(
+            ConfirmationPolicy::new(mode_state.safety_mode)
+                .with_grants(mode_state.confirmation_grants.clone())
+                .with_chat_config(&config.agent.chat),
+        );
 
         builder.build()
     }
 
+    /// Builds the gate that keeps Planning mode read-only
+    ///
+    /// In Planning mode a mutating tool call asks the user on the terminal
+    /// before switching to Write mode, unless `chat.allow_mode_switching` is
+    /// false or the session is read-only. The Write mode tools are built up
+    /// front so an approved call can run in the same turn.
+    fn build_mode_gate(
+        mode_state: &ChatModeState,
+        config: &Config,
+        working_dir: &std::path::Path,
+    ) -> Result<ModeGate> {
+        let gate = ModeGate::new(mode_state.chat_mode)
+            .with_mode_switching(config.agent.chat.allow_mode_switching && !mode_state.read_only);
+        if mode_state.chat_mode != ChatMode::Planning || mode_state.read_only {
+            return Ok(gate);
+        }
+
+        let mut write_state = mode_state.clone();
+        write_state.chat_mode = ChatMode::Write;
+        let write_tools = build_tools_for_mode(&write_state, config, working_dir)?;
+        Ok(gate.with_escalation(Arc::new(TerminalModeEscalation), write_tools))
+    }
+
+    /// Builds the reviewer asked before a multi-file change set is applied
+    ///
+    /// NeverConfirm applies every change without asking, so it gets none.
+    fn build_change_review(mode_state: &ChatModeState) -> Option<Arc<dyn ChangeReview>> {
+        if mode_state.safety_mode == SafetyMode::NeverConfirm {
+            None
+        } else {
+            Some(Arc::new(TerminalChangeReview))
+        }
+    }
+
+    /// Adds the session working directory to the chat prompt indicator
+    fn with_cwd_indicator(prompt: String, session_cwd: &SessionCwd) -> String {
+        match prompt.strip_suffix(" >>> ") {
+            Some(tags) => format!("{}[{}] >>> ", tags, session_cwd.prompt_label().blue()),
+            None => prompt,
+        }
+    }
+
+    /// Handle `/cd`, moving the session to another directory
+    ///
+    /// Without a path the current directory is shown. Mention cache entries
+    /// whose mention path resolves to a different file from the new
+    /// directory are dropped.
+    async fn handle_change_directory(
+        agent: &mut Agent,
+        session_cwd: &mut SessionCwd,
+        path: Option<&str>,
+        mode_state: &ChatModeState,
+        config: &mut Config,
+        config_path: &Path,
+        mention_cache: &mention_parser::MentionCache,
+    ) {
+        let Some(path) = path else {
+            ui_println!(
+                "Working directory: {} ({})\n",
+                session_cwd.display_label(),
+                session_cwd.current().display()
+            );
+            return;
+        };
+
+        let changed = WorkspaceTrustStore::load_default().and_then(|mut store| {
+            change_session_directory(
+                agent,
+                session_cwd,
+                path,
+                mode_state,
+                config,
+                &mut store,
+                config_path,
+            )
+        });
+        if let Err(e) = changed {
+            ui_eprintln!("{}", e.to_string().red());
+            ui_println!();
+            return;
+        }
+
+        let invalidated = mention_cache
+            .invalidate_for_working_dir(session_cwd.current())
+            .await;
+        tracing::debug!(invalidated, "Invalidated mention cache entries after /cd");
+        ui_println!(
+            "Working directory: {}\n",
+            session_cwd.display_label().cyan()
+        );
+    }
+
+    /// Moves the session and the agent's tools to `path`
+    ///
+    /// The new directory goes through the same workspace trust check as the
+    /// launch directory. When the user declines to trust it, the session
+    /// keeps running under [`workspace_trust::restrict_untrusted`] from then
+    /// on. The built-in file and terminal tools and the Planning mode gate
+    /// are rebuilt in the new directory. Other registered tools, such as MCP
+    /// tools, the subagent tool, and `activate_skill`, are kept as they are.
+    /// Messages added from now on record the new directory. Nothing changes
+    /// when the directory is rejected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory does not exist, escapes the launch
+    /// directory without `--allow-cwd-escape`, is untrusted while stdin is
+    /// not a terminal, or the tools cannot be built.
+    fn change_session_directory(
+        agent: &mut Agent,
+        session_cwd: &mut SessionCwd,
+        path: &str,
+        mode_state: &ChatModeState,
+        config: &mut Config,
+        trust_store: &mut WorkspaceTrustStore,
+        config_path: &Path,
+    ) -> Result<()> {
+        let mut next = session_cwd.clone();
+        next.change(path)?;
+
+        let trust = workspace_trust::ensure_trusted(
+            trust_store,
+            next.current(),
+            config_path,
+            std::io::stdin().is_terminal(),
+        )?;
+        if !trust.is_trusted() {
+            workspace_trust::restrict_untrusted(config);
+        }
+
+        let mut tools = build_tools_for_mode(mode_state, config, next.current())?;
+        let built_in = tools.tool_names();
+        for name in agent.tools().tool_names() {
+            if built_in.contains(&name) {
+                continue;
+            }
+            if let Some(tool) = agent.tools().get(&name) {
+                tools.register(name, tool);
+            }
+        }
+        let gate = build_mode_gate(mode_state, config, next.current())?;
+
+        *agent.tools_mut() = tools;
+        agent.set_mode_gate(Some(gate));
+        agent
+            .conversation_mut()
+            .set_cwd(Some(next.current().to_path_buf()));
+        *session_cwd = next;
+        Ok(())
+    }
+
     /// Handle listing available models
     ///
     /// # Arguments
@@ -1273,7 +2778,7 @@ pub mod chat {
         match agent.provider().list_models().await {
             Ok(models) => {
                 if models.is_empty() {
-                    println!("{}", "No models available from this provider".yellow());
+                    ui_println!("{}", "No models available from this provider".yellow());
                     return;
                 }
 
@@ -1332,16 +2837,70 @@ pub mod chat {
                     }
                 }
 
-                println!();
+                ui_println!();
                 table.printstd();
-                println!();
-                println!("{}", "Note: Current model is highlighted in green".cyan());
-                println!();
+                ui_println!();
+                ui_println!("{}", "Note: Current model is highlighted in green".cyan());
+                ui_println!();
+            }
+            Err(e) => {
+                ui_eprintln!("{}", format!("Error listing models: {}", e).red());
             }
+        }
+    }
+
+    /// Builds a multimodal user message from prompt text and attached images
+    ///
+    /// The active model is checked for vision support first so that a
+    /// text-only model produces a clear error instead of a failed request.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The active provider
+    /// * `provider_type` - The provider name used for the capability fallback
+    /// * `text` - The (mention-augmented) prompt text
+    /// * `images` - Images from `/attach` and `@image:` mentions
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the model cannot accept images or the message
+    /// cannot be built from the supplied parts.
+    async fn build_image_user_message(
+        provider: &dyn crate::providers::Provider,
+        provider_type: &str,
+        text: String,
+        images: Vec<ImagePromptPart>,
+    ) -> Result<crate::providers::Message> {
+        use crate::providers::{ModelCapability, MultimodalPromptInput, PromptInputPart};
+
+        let model = provider.get_current_model();
+        let supports_vision = match provider.get_model_info(&model).await {
+            Ok(info) => info.supports_capability(ModelCapability::Vision),
             Err(e) => {
-                eprintln!("{}", format!("Error listing models: {}", e).red());
+                tracing::debug!(
+                    "Could not look up model info for '{}', using name heuristics: {}",
+                    model,
+                    e
+                );
+                crate::acp::prompt_input::provider_model_supports_vision(provider_type, &model)
             }
+        };
+
+        if !supports_vision {
+            return Err(XzatomaError::Provider(format!(
+                "Model '{}' does not support image input; switch to a vision-capable model with /model or remove the attached images",
+                model
+            )));
+        }
+
+        let mut parts = Vec::with_capacity(images.len() + 1);
+        if !text.trim().is_empty() {
+            parts.push(PromptInputPart::text(text));
         }
+        parts.extend(images.into_iter().map(PromptInputPart::image));
+
+        crate::providers::Message::try_user_from_multimodal_input(MultimodalPromptInput::new(parts))
+            .map_err(XzatomaError::Provider)
     }
 
     /// Handle switching to a different model
@@ -1354,17 +2913,26 @@ pub mod chat {
     /// * `config` - Global configuration
     /// * `working_dir` - Working directory for tool operations
     /// * `provider_type` - Type of provider ("copilot" or "ollama")
+    /// * `mode_state` - Current chat and safety mode
+    /// * `prompt_style` - Prompt style, re-detected for the new model
+    /// * `active_skill_registry` - Active skills re-injected after the switch
+    /// * `usage_ledger` - Usage log and budget the new provider reports to
     ///
     /// # Returns
     ///
     /// Returns Ok if the switch succeeded, or an error if it failed
+    #[allow(clippy::too_many_arguments)]
     async fn handle_switch_model(
         agent: &mut Agent,
         model_name: &str,
         _rl: &mut rustyline::DefaultEditor,
         config: &Config,
-        _working_dir: &std::path::Path,
+        working_dir: &std::path::Path,
         provider_type: &str,
+        mode_state: &ChatModeState,
+        prompt_style: &mut PromptStyle,
+        active_skill_registry: &Arc<std::sync::Mutex<ActiveSkillRegistry>>,
+        usage_ledger: Option<&Arc<UsageLedger>>,
     ) -> Result<()> {
         use colored::Colorize;
 
@@ -1382,20 +2950,21 @@ pub mod chat {
 
                 // Check if current conversation exceeds new context window
                 if current_tokens > new_context_window {
-                    println!(
+                    ui_println!(
                         "{}",
                         format!(
                             "WARNING: Current conversation ({} tokens) exceeds new model context ({} tokens)",
-                            current_tokens, new_context_window
+                            format_tokens(current_tokens as u64),
+                            format_tokens(new_context_window as u64)
                         )
                         .yellow()
                     );
-                    println!(
+                    ui_println!(
                         "{}",
                         "Messages will be pruned to fit the new context window.".yellow()
                     );
-                    println!();
-                    println!("{}Continue with model switch? [y/N]: ", ">>> ".cyan());
+                    ui_println!();
+                    ui_println!("{}Continue with model switch? [y/N]: ", ">>> ".cyan());
 
                     // For now, we don't prompt (would need interactive input from readline)
                     // In a full implementation, this would wait for user confirmation
@@ -1403,7 +2972,18 @@ pub mod chat {
                 }
 
                 // Create new provider
-                let mut new_provider = create_provider(provider_type, &config.provider)?;
+                let (mut new_provider, _) = wrap_with_cache(
+                    wrap_with_budget(
+                        wrap_with_quirks(
+                            create_provider(provider_type, &config.provider)?,
+                            &config.provider.quirks,
+                        ),
+                        usage_ledger,
+                    ),
+                    provider_type,
+                    &config.provider.cache,
+                    &Paths::from_config(&config)?,
+                );
 
                 // Switch model
                 new_provider.set_model(&model_info.name);
@@ -1422,17 +3002,43 @@ pub mod chat {
 
                 // Create new agent with updated provider and conversation
                 let tools = agent.tools().clone();
-                let new_agent = Agent::with_conversation(
-                    new_provider,
+                let new_provider: Arc<dyn crate::providers::Provider> = Arc::from(new_provider);
+                let mut new_agent = Agent::with_conversation_and_shared_provider(
+                    Arc::clone(&new_provider),
                     tools,
                     config.agent.clone(),
                     conversation,
                 )?;
+                new_agent.set_tool_metrics(agent.tool_metrics().clone());
+                new_agent.set_tool_health(agent.tool_health().clone());
+                new_agent.set_telemetry(agent.telemetry().cloned());
+                new_agent.set_file_tracker(agent.file_tracker().clone());
+                new_agent.set_mention_cache(agent.mention_cache().cloned());
+                // Without a summary model, tool output is summarized by the new model
+                new_agent.set_overflow_summarizer(
+                    build_overflow_summarizer(config, &new_provider).await,
+                );
+                new_agent.set_output_pager(agent.output_pager().cloned());
+                new_agent.set_mode_gate(Some(build_mode_gate(mode_state, config, working_dir)?));
+                new_agent.set_change_review(build_change_review(mode_state));
+
+                // The new model may need a different prompt style
+                *prompt_style = crate::prompts::detect_prompt_style(
+                    &config.provider,
+                    provider_type,
+                    new_agent.provider(),
+                )
+                .await;
+                new_agent.set_transient_system_messages(build_chat_system_messages(
+                    mode_state,
+                    *prompt_style,
+                    active_skill_registry,
+                )?);
 
                 // Replace agent
                 *agent = new_agent;
 
-                println!(
+                ui_println!(
                     "{}",
                     format!(
                         "Switched to model: {} ({} token context)",
@@ -1440,10 +3046,10 @@ pub mod chat {
                     )
                     .green()
                 );
-                println!();
+                ui_println!();
             }
             None => {
-                eprintln!(
+                ui_eprintln!(
                     "{}",
                     format!(
                         "Model '{}' not found. Use '/models list' to see available models.",
@@ -1478,8 +3084,8 @@ pub mod chat {
     ) -> Result<String> {
         use crate::providers::Message;
 
-        // Get current messages to summarize
-        let messages = agent.conversation().messages().to_vec();
+        // Pinned messages are kept verbatim, so only the rest is summarized
+        let messages = agent.conversation().unpinned_messages();
 
         if messages.is_empty() {
             return Err(XzatomaError::Internal(
@@ -1500,10 +3106,8 @@ pub mod chat {
             .content
             .unwrap_or_else(|| "Unable to generate summary".to_string());
 
-        // Reset conversation while preserving summary
-        let conv = agent.conversation_mut();
-        conv.clear();
-        conv.add_system_message(format!(
+        // Reset conversation while preserving summary and pinned messages
+        agent.conversation_mut().replace_with_summary(format!(
             "Previous conversation summary:\n\n{}",
             summary_text
         ));
@@ -1554,26 +3158,24 @@ pub mod chat {
             Ok(model_info) => {
                 let context = agent.get_context_info(model_info.context_window);
 
-                println!();
-                println!("{}", "╔════════════════════════════════════╗".cyan());
-                println!("{}", "║     Context Window Information      ║".cyan());
-                println!("{}", "╚════════════════════════════════════╝".cyan());
-                println!();
+                ui_println!();
+                ui_println!("{}", ui::banner("Context Window Information", 38).cyan());
+                ui_println!();
 
-                println!("Current Model:     {}", model_name.bold());
-                println!(
+                ui_println!("Current Model:     {}", model_name.bold());
+                ui_println!(
                     "Context Window:    {} tokens",
-                    model_info.context_window.to_string().bold()
+                    format_tokens(model_info.context_window as u64).bold()
                 );
-                println!(
+                ui_println!(
                     "Tokens Used:       {} tokens",
-                    context.used_tokens.to_string().bold()
+                    format_tokens(context.used_tokens as u64).bold()
                 );
-                println!(
+                ui_println!(
                     "Remaining:         {} tokens",
-                    context.remaining_tokens.to_string().bold()
+                    format_tokens(context.remaining_tokens as u64).bold()
                 );
-                println!("Usage:             {:.1}%", context.percentage_used);
+                ui_println!("Usage:             {:.1}%", context.percentage_used);
 
                 // Color code the usage percentage
                 let usage_color = if context.percentage_used < 60.0 {
@@ -1584,14 +3186,221 @@ pub mod chat {
                     context.percentage_used.to_string().red()
                 };
 
-                println!();
-                println!("Usage Level:       {}", usage_color);
-                println!();
+                ui_println!();
+                ui_println!("Usage Level:       {}", usage_color);
+                ui_println!();
             }
             Err(e) => {
-                eprintln!("{}", format!("Error getting model info: {}", e).red());
+                ui_eprintln!("{}", format!("Error getting model info: {}", e).red());
+            }
+        }
+    }
+
+    /// Handle listing the messages currently in the model's context
+    ///
+    /// Prints each message as it would be sent to the provider with its role,
+    /// a one-line preview, and its estimated token count, followed by
+    /// per-category subtotals and the total against the effective limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent` - The current agent
+    async fn handle_show_context_view(agent: &Agent) {
+        use colored::Colorize;
+
+        let entries = agent.context_entries();
+        let breakdown = crate::agent::ContextBreakdown::from_entries(&entries);
+
+        let limit = effective_context_limit(agent).await;
+
+        ui_println!();
+        ui_println!("{}", "Context Contents".cyan().bold());
+        ui_println!();
+        if entries.is_empty() {
+            ui_println!("No messages in context.");
+        }
+        for (index, entry) in entries.iter().enumerate() {
+            let role = match entry.category {
+                crate::agent::ContextCategory::Pinned => "pinned".to_string(),
+                _ => entry.message.role.clone(),
+            };
+            ui_println!(
+                "{:>4}  {:<9} {:>7}  {}",
+                index + 1,
+                role,
+                entry.tokens,
+                entry.preview(72)
+            );
+        }
+
+        let total = breakdown.total();
+        let percentage = if limit == 0 {
+            0.0
+        } else {
+            total as f64 / limit as f64 * 100.0
+        };
+
+        ui_println!();
+        ui_println!(
+            "System Prompt:     {} tokens",
+            format_tokens(breakdown.system_prompt as u64)
+        );
+        ui_println!(
+            "Pinned:            {} tokens",
+            format_tokens(breakdown.pinned as u64)
+        );
+        ui_println!(
+            "Tool Results:      {} tokens",
+            format_tokens(breakdown.tool_results as u64)
+        );
+        ui_println!(
+            "Chat Turns:        {} tokens",
+            format_tokens(breakdown.chat as u64)
+        );
+        ui_println!(
+            "Total:             {} / {} tokens ({:.1}%)",
+            format_tokens(total as u64).bold(),
+            format_tokens(limit as u64),
+            percentage
+        );
+        ui_println!();
+        ui_println!("Use '/context full N' to print message N verbatim.");
+        ui_println!();
+    }
+
+    /// Handle printing a single context message verbatim
+    ///
+    /// # Arguments
+    ///
+    /// * `agent` - The current agent
+    /// * `number` - The 1-based message number from the `/context` listing
+    fn handle_show_context_message(agent: &Agent, number: usize) {
+        use colored::Colorize;
+
+        let entries = agent.context_entries();
+        let Some(entry) = entries.get(number - 1) else {
+            ui_eprintln!(
+                "{}",
+                format!(
+                    "No message {} in context ({} messages). Run '/context' to list them.",
+                    number,
+                    entries.len()
+                )
+                .red()
+            );
+            ui_println!();
+            return;
+        };
+
+        ui_println!();
+        ui_println!(
+            "{}",
+            format!(
+                "Message {} ({}, {} tokens)",
+                number, entry.message.role, entry.tokens
+            )
+            .cyan()
+            .bold()
+        );
+        ui_println!();
+        if let Some(content) = &entry.message.content {
+            ui_println!("{}", content);
+        }
+        if let Some(tool_calls) = &entry.message.tool_calls {
+            for call in tool_calls {
+                ui_println!(
+                    "tool call {}: {}({})",
+                    call.id,
+                    call.function.name,
+                    call.function.arguments
+                );
+            }
+        }
+        if let Some(tool_call_id) = &entry.message.tool_call_id {
+            ui_println!("(result for tool call {})", tool_call_id);
+        }
+        ui_println!();
+    }
+
+    /// Handle listing the session's pruning decisions
+    ///
+    /// Prints each decision with its reason and token totals, followed by
+    /// the messages it summarized or stubbed.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent` - The current agent
+    fn handle_show_pruning_log(agent: &Agent) {
+        use crate::agent::PruneAction;
+        use colored::Colorize;
+
+        let log = agent.conversation().pruning_log();
+
+        ui_println!();
+        ui_println!("{}", "Pruning Log".cyan().bold());
+        ui_println!();
+        if log.is_empty() {
+            ui_println!("Nothing has been pruned in this session.");
+        }
+        for (index, report) in log.iter().enumerate() {
+            let summary = match report.summary_tokens {
+                Some(tokens) => format!(", summary {} tokens", format_tokens(tokens as u64)),
+                None => String::new(),
+            };
+            ui_println!(
+                "{:>4}  {}: {} -> {} tokens{}",
+                index + 1,
+                report.reason.to_string().bold(),
+                format_tokens(report.tokens_before as u64),
+                format_tokens(report.tokens_after as u64),
+                summary
+            );
+            for message in &report.messages {
+                let action = match message.action {
+                    PruneAction::Summarized => "summarized",
+                    PruneAction::Stubbed => "stubbed",
+                };
+                ui_println!(
+                    "      {:<10} {:<9} {:>7}  {}",
+                    action,
+                    message.role,
+                    message.tokens,
+                    message.preview.dimmed()
+                );
             }
         }
+        ui_println!();
+    }
+
+    /// Handle displaying mention cache statistics
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The mention cache shared with prompt augmentation
+    async fn handle_show_mention_cache(cache: &crate::mention_parser::MentionCache) {
+        use colored::Colorize;
+
+        let stats = cache.stats().await;
+        let lookups = stats.hits + stats.misses;
+        let hit_rate = if lookups == 0 {
+            0.0
+        } else {
+            stats.hits as f64 / lookups as f64 * 100.0
+        };
+
+        ui_println!();
+        ui_println!("{}", "Mention Cache".cyan().bold());
+        ui_println!();
+        ui_println!("Cached Files:      {}", stats.entries.to_string().bold());
+        ui_println!(
+            "Cached Size:       {}",
+            format_bytes(stats.total_bytes).bold()
+        );
+        ui_println!("Hits:              {}", stats.hits);
+        ui_println!("Misses:            {}", stats.misses);
+        ui_println!("Hit Rate:          {:.1}%", hit_rate);
+        ui_println!("Evictions:         {}", stats.evictions);
+        ui_println!();
     }
 
     /// Handle switching to a new chat mode while preserving conversation
@@ -1604,10 +3413,14 @@ pub mod chat {
     /// * `config` - Global configuration
     /// * `working_dir` - Working directory for tool operations
     /// * `provider_type` - Type of provider ("copilot" or "ollama")
+    /// * `prompt_style` - Prompt style selected for the active model
+    /// * `active_skill_registry` - Active skills re-injected after the switch
+    /// * `usage_ledger` - Usage log and budget the new provider reports to
     ///
     /// # Returns
     ///
     /// Returns Ok if the switch succeeded, or an error if it failed
+    #[allow(clippy::too_many_arguments)]
     fn handle_mode_switch(
         agent: &mut Agent,
         mode_state: &mut ChatModeState,
@@ -1615,11 +3428,14 @@ pub mod chat {
         config: &Config,
         working_dir: &std::path::Path,
         provider_type: &str,
+        prompt_style: PromptStyle,
+        active_skill_registry: &Arc<std::sync::Mutex<ActiveSkillRegistry>>,
+        usage_ledger: Option<&Arc<UsageLedger>>,
     ) -> Result<()> {
         // Show warning when switching to Write mode
         if matches!(new_mode, ChatMode::Write) {
-            println!("\nWarning: Switching to WRITE mode - agent can now modify files and execute commands!");
-            println!("Type '/safe' to enable confirmations, or '/yolo' to disable.\n");
+            ui_println!("\nWarning: Switching to WRITE mode - agent can now modify files and execute commands!");
+            ui_println!("Type '/safe' to enable confirmations, or '/yolo' to disable.\n");
         }
 
         // Update mode state
@@ -1633,22 +3449,83 @@ pub mod chat {
         let conversation = agent.conversation().clone();
 
         // Create new provider
-        let new_provider = create_provider(provider_type, &config.provider)?;
+        let (new_provider, _) = wrap_with_cache(
+            wrap_with_budget(
+                wrap_with_quirks(
+                    create_provider(provider_type, &config.provider)?,
+                    &config.provider.quirks,
+                ),
+                usage_ledger,
+            ),
+            provider_type,
+            &config.provider.cache,
+            &Paths::from_config(&config)?,
+        );
 
         // Create new agent with same conversation but new tools
-        let new_agent =
+        let mut new_agent =
             Agent::with_conversation(new_provider, new_tools, config.agent.clone(), conversation)?;
+        new_agent.set_tool_metrics(agent.tool_metrics().clone());
+        new_agent.set_tool_health(agent.tool_health().clone());
+        new_agent.set_telemetry(agent.telemetry().cloned());
+        new_agent.set_overflow_summarizer(agent.overflow_summarizer().cloned());
+        new_agent.set_output_pager(agent.output_pager().cloned());
+        new_agent.set_file_tracker(agent.file_tracker().clone());
+        new_agent.set_mention_cache(agent.mention_cache().cloned());
+        new_agent.set_mode_gate(Some(build_mode_gate(mode_state, config, working_dir)?));
+        new_agent.set_change_review(build_change_review(mode_state));
+        new_agent.set_transient_system_messages(build_chat_system_messages(
+            mode_state,
+            prompt_style,
+            active_skill_registry,
+        )?);
 
         // Replace agent
         *agent = new_agent;
 
-        println!(
+        ui_println!(
             "Switched from {} to {} mode\n",
-            old_mode, mode_state.chat_mode
+            old_mode,
+            mode_state.chat_mode
         );
         Ok(())
     }
 
+    /// Builds the transient system messages for the current chat state
+    ///
+    /// The mode prompt comes first so it follows `/mode`, `/safe`, and `/yolo`
+    /// switches, followed by any active skill content.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode_state` - Current chat and safety mode
+    /// * `prompt_style` - Prompt style selected for the active model
+    /// * `active_skill_registry` - Active skills to inject after the mode prompt
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the active skill registry lock cannot be acquired.
+    fn build_chat_system_messages(
+        mode_state: &ChatModeState,
+        prompt_style: PromptStyle,
+        active_skill_registry: &Arc<std::sync::Mutex<ActiveSkillRegistry>>,
+    ) -> Result<Vec<String>> {
+        let mut messages = vec![crate::prompts::build_system_prompt(
+            mode_state.chat_mode,
+            mode_state.safety_mode,
+            prompt_style,
+        )];
+        if mode_state.read_only {
+            messages.push(READ_ONLY_PROMPT.to_string());
+        }
+        if let Some(active_skill_prompt) =
+            build_active_skill_prompt_injection(active_skill_registry)?
+        {
+            messages.push(active_skill_prompt);
+        }
+        Ok(messages)
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
@@ -1660,7 +3537,9 @@ pub mod chat {
             let mut cfg = Config::default();
             cfg.provider.provider_type = "invalid_provider".to_string();
 
-            let res = run_chat(cfg, None, None, false, None, None).await;
+            let cwd = SessionCwd::from_launch_dir(None, false).unwrap();
+            let config_path = cwd.current().join("config/config.yaml");
+            let res = run_chat(cfg, None, None, false, None, None, cwd, &config_path).await;
             assert!(res.is_err());
         }
 
@@ -1727,45 +3606,9 @@ pub mod chat {
 
         #[test]
         fn test_handle_mode_switch_planning_to_write() {
-            use crate::providers::Message;
-            use async_trait::async_trait;
-
-            // Create a simple mock provider
-            #[derive(Clone)]
-            struct TestProvider;
-
-            #[async_trait]
-            impl crate::providers::Provider for TestProvider {
-                fn is_authenticated(&self) -> bool {
-                    false
-                }
-
-                fn current_model(&self) -> Option<&str> {
-                    None
-                }
-
-                fn set_model(&mut self, _model: &str) {}
-
-                async fn fetch_models(&self) -> Result<Vec<crate::providers::ModelInfo>> {
-                    Err(crate::error::XzatomaError::Provider(
-                        "not supported".to_string(),
-                    ))
-                }
-
-                async fn complete(
-                    &self,
-                    _messages: &[Message],
-                    _tools: &[serde_json::Value],
-                ) -> Result<crate::providers::CompletionResponse> {
-                    Ok(crate::providers::CompletionResponse::new(
-                        Message::assistant("test"),
-                    ))
-                }
-            }
-
             let config = Config::default();
             let working_dir = std::path::PathBuf::from(".");
-            let provider = TestProvider;
+            let provider = crate::testing::MockProvider::new();
             let tools = build_tools_for_mode(
                 &ChatModeState::new(ChatMode::Planning, SafetyMode::AlwaysConfirm),
                 &config,
@@ -1776,6 +3619,7 @@ pub mod chat {
             let mut agent = Agent::new(provider, tools, config.agent.clone()).unwrap();
             let mut mode_state = ChatModeState::new(ChatMode::Planning, SafetyMode::AlwaysConfirm);
 
+            let registry = Arc::new(std::sync::Mutex::new(ActiveSkillRegistry::new()));
             let result = handle_mode_switch(
                 &mut agent,
                 &mut mode_state,
@@ -1783,10 +3627,158 @@ pub mod chat {
                 &config,
                 &working_dir,
                 "ollama",
+                PromptStyle::Concise,
+                &registry,
+                None,
             );
 
             assert!(result.is_ok());
             assert_eq!(mode_state.chat_mode, ChatMode::Write);
+            let messages = agent.transient_system_messages();
+            assert_eq!(messages.len(), 1);
+            assert_eq!(
+                messages[0],
+                crate::prompts::build_system_prompt(
+                    ChatMode::Write,
+                    SafetyMode::AlwaysConfirm,
+                    PromptStyle::Concise
+                )
+            );
+        }
+
+        #[tokio::test]
+        async fn test_change_directory_moves_tools_and_keeps_extra_tools() {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::create_dir_all(dir.path().join("services/billing")).unwrap();
+            std::fs::write(dir.path().join("notes.txt"), "root notes").unwrap();
+            std::fs::write(
+                dir.path().join("services/billing/notes.txt"),
+                "billing notes",
+            )
+            .unwrap();
+
+            let mut config = Config::default();
+            let mode_state = ChatModeState::new(ChatMode::Planning, SafetyMode::AlwaysConfirm);
+            let mut session_cwd = SessionCwd::new(dir.path(), false).unwrap();
+            let store_dir = tempfile::tempdir().unwrap();
+            let mut store = WorkspaceTrustStore::new(store_dir.path().join("trust.yaml"));
+            let config_path = store_dir.path().join("config.yaml");
+            let mut tools =
+                build_tools_for_mode(&mode_state, &config, session_cwd.current()).unwrap();
+            let extra = tools.get("read_file").unwrap();
+            tools.register("mcp_server__read", extra);
+            let mut agent = Agent::new(
+                crate::testing::MockProvider::new(),
+                tools,
+                config.agent.clone(),
+            )
+            .unwrap();
+
+            change_session_directory(
+                &mut agent,
+                &mut session_cwd,
+                "services/billing",
+                &mode_state,
+                &mut config,
+                &mut store,
+                &config_path,
+            )
+            .unwrap();
+
+            assert_eq!(session_cwd.display_label(), "services/billing");
+            assert_eq!(agent.conversation().cwd(), Some(session_cwd.current()));
+            let result = agent
+                .tools()
+                .get("read_file")
+                .unwrap()
+                .execute(serde_json::json!({ "path": "notes.txt" }))
+                .await
+                .unwrap();
+            assert!(result.success);
+            assert!(result.output.contains("billing notes"));
+
+            // Tools that are not built per directory are carried over as is
+            let result = agent
+                .tools()
+                .get("mcp_server__read")
+                .unwrap()
+                .execute(serde_json::json!({ "path": "notes.txt" }))
+                .await
+                .unwrap();
+            assert!(result.output.contains("root notes"));
+
+            // A rejected directory leaves the session and tools untouched
+            assert!(change_session_directory(
+                &mut agent,
+                &mut session_cwd,
+                "../../..",
+                &mode_state,
+                &mut config,
+                &mut store,
+                &config_path,
+            )
+            .is_err());
+            assert_eq!(session_cwd.display_label(), "services/billing");
+        }
+
+        #[test]
+        fn test_change_directory_checks_trust_of_new_directory() {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::create_dir_all(dir.path().join("vendor/.xzatoma/skills")).unwrap();
+
+            let mut config = Config::default();
+            let mode_state = ChatModeState::new(ChatMode::Planning, SafetyMode::AlwaysConfirm);
+            let mut session_cwd = SessionCwd::new(dir.path(), false).unwrap();
+            let tools = build_tools_for_mode(&mode_state, &config, session_cwd.current()).unwrap();
+            let provider = crate::testing::MockProvider::new();
+            let mut agent = Agent::new(provider, tools, config.agent.clone()).unwrap();
+            let store_dir = tempfile::tempdir().unwrap();
+            let mut store = WorkspaceTrustStore::new(store_dir.path().join("trust.yaml"));
+            let config_path = store_dir.path().join("config.yaml");
+
+            // Project skills in an untrusted directory cannot be loaded
+            // without asking, and tests never run on a terminal
+            let error = change_session_directory(
+                &mut agent,
+                &mut session_cwd,
+                "vendor",
+                &mode_state,
+                &mut config,
+                &mut store,
+                &config_path,
+            )
+            .unwrap_err();
+            assert!(matches!(error, XzatomaError::UntrustedWorkspace(_)));
+            assert_eq!(session_cwd.display_label(), ".");
+
+            let vendor = dir.path().join("vendor");
+            let trust =
+                workspace_trust::WorkspaceTrust::evaluate(&store, &vendor, &config_path).unwrap();
+            store.add(&trust).unwrap();
+            change_session_directory(
+                &mut agent,
+                &mut session_cwd,
+                "vendor",
+                &mode_state,
+                &mut config,
+                &mut store,
+                &config_path,
+            )
+            .unwrap();
+            assert_eq!(session_cwd.display_label(), "vendor");
+        }
+
+        #[test]
+        fn test_with_cwd_indicator_adds_directory_tag() {
+            let dir = tempfile::tempdir().unwrap();
+            let session_cwd = SessionCwd::new(dir.path(), false).unwrap();
+            let state = ChatModeState::new(ChatMode::Planning, SafetyMode::AlwaysConfirm);
+
+            let prompt = with_cwd_indicator(state.format_prompt(), &session_cwd);
+
+            assert!(prompt.starts_with("[PLANNING][SAFE]["));
+            assert!(prompt.contains(&session_cwd.prompt_label()));
+            assert!(prompt.ends_with("] >>> "));
         }
 
         #[test]
@@ -1825,7 +3817,11 @@ pub mod chat {
             let safety = SafetyMode::AlwaysConfirm;
 
             // Note: In actual tests, we'd capture stdout, but this is a smoke test
-            print_welcome_banner(&mode, &safety);
+            let state = ChatModeState::new(mode, safety);
+            print_welcome_banner(
+                ChatBanner::Full,
+                &BannerContext::new("ollama", "llama3.2", &state, "/work"),
+            );
             // If this doesn't panic, the function works
         }
 
@@ -1835,7 +3831,12 @@ pub mod chat {
             let mode = ChatMode::Write;
             let safety = SafetyMode::NeverConfirm;
 
-            print_welcome_banner(&mode, &safety);
+            let mut mode_state = ChatModeState::new(mode, safety);
+            mode_state.read_only = true;
+            print_welcome_banner(
+                ChatBanner::Minimal,
+                &BannerContext::new("copilot", "gpt-5-mini", &mode_state, "/work"),
+            );
             // Smoke test - verifies function executes without panic
         }
 
@@ -1925,42 +3926,396 @@ pub mod chat {
             );
         }
 
-        #[test]
-        fn test_mode_display_formatting() {
-            // Test that mode displays correctly as uppercase strings
-            assert_eq!(ChatMode::Planning.to_string(), "PLANNING");
-            assert_eq!(ChatMode::Write.to_string(), "WRITE");
-            assert_eq!(SafetyMode::AlwaysConfirm.to_string(), "SAFE");
-            assert_eq!(SafetyMode::NeverConfirm.to_string(), "YOLO");
+        #[test]
+        fn test_mode_display_formatting() {
+            // Test that mode displays correctly as uppercase strings
+            assert_eq!(ChatMode::Planning.to_string(), "PLANNING");
+            assert_eq!(ChatMode::Write.to_string(), "WRITE");
+            assert_eq!(SafetyMode::AlwaysConfirm.to_string(), "SAFE");
+            assert_eq!(SafetyMode::NeverConfirm.to_string(), "YOLO");
+        }
+    }
+}
+
+/// Run command handler(s)
+///
+/// This module provides `run_plan` which runs a plan or a single prompt.
+/// We provide a `run_plan_with_options` helper to support the `allow_dangerous` flag.
+pub mod r#run {
+    use super::*;
+    use crate::agent::outcome::{ExecutionOutcome, FinishStatus};
+    use crate::agent::step_policy::{PlanStepExecutor, TerminalFactory};
+    use crate::commands::postmortem::{self, FailureContext, PostmortemGenerator};
+    use crate::commands::snapshot::{self, Snapshot};
+    use crate::config::ExecutionMode;
+    use crate::read_only::READ_ONLY_PROMPT;
+    use crate::storage::types::IfExists;
+    use crate::storage::{session_conversation_id, SqliteStorage};
+    use crate::tools::plan::Plan;
+    use crate::tools::plan_risk::PlanRiskReport;
+    use crate::tools::{CommandValidator, TerminalTool, ToolExecutor};
+    use std::io::{BufRead, Write};
+
+    /// Run a plan or a prompt via the agent
+    ///
+    /// Wrapper that uses `allow_dangerous = false` and no explicit `thinking_effort`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Global configuration (consumed)
+    /// * `plan_path` - Optional path to plan file (yaml/json/md)
+    /// * `prompt` - Optional prompt text (used if plan_path is None)
+    pub async fn run_plan(
+        config: Config,
+        plan_path: Option<String>,
+        prompt: Option<String>,
+    ) -> Result<()> {
+        run_plan_with_options(config, plan_path, prompt, false, None).await
+    }
+
+    /// Check a plan file without running it
+    ///
+    /// Parses the plan and reports every schema and semantic problem found,
+    /// with YAML line and column locations. Intended for CI linting via
+    /// `xzatoma run --plan <file> --validate-only`.
+    ///
+    /// # Arguments
+    ///
+    /// * `plan_path` - Path to the plan file (yaml/json/md)
+    /// * `json` - Print a JSON report with `valid` and `issues` fields
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::InvalidPlan` listing the problems when the plan
+    /// is invalid, or a read error when the file cannot be loaded
+    pub fn validate_plan_file(plan_path: &Path, json: bool) -> Result<()> {
+        let result = PlanParser::from_file(plan_path);
+
+        if json {
+            let report = match &result {
+                Ok(plan) => serde_json::json!({
+                    "valid": true,
+                    "plan": plan.name,
+                    "steps": plan.step_count(),
+                    "issues": [],
+                }),
+                Err(XzatomaError::InvalidPlan(error)) => serde_json::json!({
+                    "valid": false,
+                    "issues": error.issues,
+                }),
+                Err(e) => serde_json::json!({
+                    "valid": false,
+                    "error": e.to_string(),
+                }),
+            };
+            ui::json(&report)?;
+        } else if let Ok(plan) = &result {
+            ui_println!(
+                "{}: plan '{}' is valid ({} steps)",
+                plan_path.display(),
+                plan.name,
+                plan.step_count()
+            );
+        }
+
+        result.map(|_| ())
+    }
+
+    /// Show how a plan would run without running it
+    ///
+    /// Validates the plan and prints each step's effective tool set and
+    /// terminal execution mode, after the run's ceiling is applied. When a
+    /// step would run in `full_autonomous`, the plan's risk review and the
+    /// hash for `--confirm-dangerous` follow. No provider is contacted and
+    /// no tool runs.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Global configuration, for the terminal default mode
+    /// * `plan_path` - Path to the plan file (yaml/json/md)
+    /// * `allow_dangerous` - Whether the run would allow `full_autonomous`
+    /// * `working_dir` - Directory the run's tools would be rooted in
+    /// * `json` - Print a JSON report with one entry per step
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::InvalidPlan` when the plan is invalid, or a
+    /// read error when the file cannot be loaded
+    pub fn dry_run_plan(
+        config: &Config,
+        plan_path: &Path,
+        allow_dangerous: bool,
+        working_dir: &Path,
+        json: bool,
+    ) -> Result<()> {
+        let plan = PlanParser::from_file(plan_path)?;
+        let (default_mode, ceiling) = step_modes(config, allow_dangerous);
+        let policies = PlanStepExecutor::new(default_mode, ceiling).policies(&plan);
+        let risk = runs_full_autonomous(config, &plan, allow_dangerous)
+            .then(|| PlanRiskReport::analyze(&plan, working_dir));
+
+        if json {
+            let steps: Vec<serde_json::Value> = policies
+                .iter()
+                .zip(&plan.steps)
+                .map(|(policy, step)| {
+                    serde_json::json!({
+                        "name": policy.step(),
+                        "tools": policy.tools(),
+                        "execution_mode": policy.mode().to_string(),
+                        "retries": step.retries.unwrap_or(0),
+                    })
+                })
+                .collect();
+            return ui::json(&serde_json::json!({
+                "plan": plan.name,
+                "step_by_step": plan.runs_stepwise(),
+                "ceiling": ceiling.to_string(),
+                "steps": steps,
+                "risk": risk,
+            }));
+        }
+
+        ui_println!(
+            "Plan '{}' ({} steps), terminal mode ceiling {}",
+            plan.name,
+            plan.step_count(),
+            ceiling
+        );
+        for (index, (policy, step)) in policies.iter().zip(&plan.steps).enumerate() {
+            ui_println!("  {}. {}", index + 1, policy.step());
+            ui_println!("     tools: {}", policy.describe_tools());
+            ui_println!("     mode:  {}", policy.mode());
+            if let Some(retries) = step.retries.filter(|retries| *retries > 0) {
+                ui_println!("     retries: {}", retries);
+            }
+        }
+        if !plan.runs_stepwise() {
+            ui_println!("No step restricts its tools or retries; the plan runs as a single task.");
+        }
+        if let Some(risk) = risk {
+            ui_println!();
+            ui_println!("{}", risk);
+            ui_println!(
+                "Run it non-interactively with --confirm-dangerous {}",
+                risk.plan_hash
+            );
+        }
+        Ok(())
+    }
+
+    /// Number of risk items echoed by the interactive confirmation
+    const CONFIRM_RISK_ITEMS: usize = 5;
+
+    /// Require confirmation before a plan runs any step in `full_autonomous`
+    ///
+    /// Analyzes the plan with [`PlanRiskReport`]. A plan with no step in
+    /// `full_autonomous` needs no confirmation. Otherwise `confirm` must
+    /// carry the report's plan hash; without it, an interactive run shows
+    /// the riskiest items and asks, and a non-interactive run fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Global configuration, for the terminal default mode
+    /// * `plan_path` - Path to the plan file (yaml/json/md)
+    /// * `allow_dangerous` - Whether the run allows `full_autonomous`
+    /// * `confirm` - Plan hash given with `--confirm-dangerous`
+    /// * `working_dir` - Directory the run's tools are rooted in
+    /// * `interactive` - Whether stdin is a terminal that can answer a prompt
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::DangerousPlanNotConfirmed` when the hash does
+    /// not match, the prompt is declined, or a non-interactive run has no
+    /// hash, and a plan error when the file cannot be loaded
+    pub fn confirm_dangerous_plan(
+        config: &Config,
+        plan_path: &Path,
+        allow_dangerous: bool,
+        confirm: Option<&str>,
+        working_dir: &Path,
+        interactive: bool,
+    ) -> Result<()> {
+        let plan = PlanParser::from_file(plan_path)?;
+        if !runs_full_autonomous(config, &plan, allow_dangerous) {
+            return Ok(());
+        }
+
+        let report = PlanRiskReport::analyze(&plan, working_dir);
+        if let Some(confirm) = confirm {
+            report.verify(confirm)?;
+            tracing::warn!(
+                "Running plan '{}' in full_autonomous, confirmed with hash {}",
+                report.plan,
+                report.plan_hash
+            );
+            return Ok(());
+        }
+
+        if !interactive {
+            return Err(XzatomaError::DangerousPlanNotConfirmed(format!(
+                "plan '{}' runs in full_autonomous; review it and pass --confirm-dangerous {}",
+                report.plan, report.plan_hash
+            )));
+        }
+
+        eprintln!(
+            "Plan '{}' runs terminal commands in full_autonomous, without confirmation.",
+            report.plan
+        );
+        if report.items.is_empty() {
+            eprintln!(
+                "The plan text names no dangerous commands, outside paths, or network operations."
+            );
+        } else {
+            eprintln!("Riskiest items:");
+            for item in report.riskiest(CONFIRM_RISK_ITEMS) {
+                eprintln!("  {}", item);
+            }
+            let hidden = report.items.len().saturating_sub(CONFIRM_RISK_ITEMS);
+            if hidden > 0 {
+                eprintln!("  ... and {} more (see --dry-run)", hidden);
+            }
+        }
+        eprint!("Run it? [y/N] ");
+        let _ = std::io::stderr().flush();
+
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        let answer = line.trim().to_lowercase();
+        if answer == "y" || answer == "yes" {
+            Ok(())
+        } else {
+            Err(XzatomaError::DangerousPlanNotConfirmed(format!(
+                "declined to run plan '{}' (hash {})",
+                report.plan, report.plan_hash
+            )))
+        }
+    }
+
+    /// Whether any step of `plan` would run its terminal in `full_autonomous`
+    fn runs_full_autonomous(config: &Config, plan: &Plan, allow_dangerous: bool) -> bool {
+        let (default_mode, ceiling) = step_modes(config, allow_dangerous);
+        if !plan.runs_stepwise() {
+            // The plan runs as a single task in the configured default mode
+            return default_mode == ExecutionMode::FullAutonomous;
         }
+        PlanStepExecutor::new(default_mode, ceiling)
+            .policies(plan)
+            .iter()
+            .any(|policy| policy.mode() == ExecutionMode::FullAutonomous)
     }
-}
 
-/// Run command handler(s)
-///
-/// This module provides `run_plan` which runs a plan or a single prompt.
-/// We provide a `run_plan_with_options` helper to support the `allow_dangerous` flag.
-pub mod r#run {
-    use super::*;
+    /// Terminal modes for a plan run: the default for steps without an
+    /// `execution_mode`, and the ceiling no step may exceed
+    ///
+    /// The ceiling is `full_autonomous` with `--allow-dangerous` and the
+    /// configured default mode otherwise.
+    fn step_modes(config: &Config, allow_dangerous: bool) -> (ExecutionMode, ExecutionMode) {
+        let default_mode = config.agent.terminal.default_mode;
+        let ceiling = if allow_dangerous {
+            ExecutionMode::FullAutonomous
+        } else {
+            default_mode
+        };
+        (default_mode, ceiling)
+    }
 
-    /// Run a plan or a prompt via the agent
+    /// Builds terminal tools like the registry builder, in a given mode
+    fn terminal_factory(
+        config: &Config,
+        working_dir: &Path,
+        safety_mode: SafetyMode,
+    ) -> TerminalFactory {
+        let working_dir = working_dir.to_path_buf();
+        let terminal_config = config.agent.terminal.clone();
+        let confirmation =
+            ConfirmationPolicy::new(safety_mode).with_chat_config(&config.agent.chat);
+        Arc::new(move |mode| -> Arc<dyn ToolExecutor> {
+            let validator = CommandValidator::new(mode, working_dir.clone());
+            Arc::new(
+                TerminalTool::new(validator, terminal_config.clone())
+                    .with_safety_mode(safety_mode)
+                    .with_confirmation(Some(confirmation.clone())),
+            )
+        })
+    }
+
+    /// Run a plan or a prompt via the agent with extra options.
     ///
-    /// Wrapper that uses `allow_dangerous = false` and no explicit `thinking_effort`.
+    /// # Arguments
+    ///
+    /// * `config` - Global configuration (consumed)
+    /// * `plan_path` - Optional path to a plan file
+    /// * `prompt` - Optional direct prompt
+    /// * `allow_dangerous` - If true, the execution mode is escalated to FullAutonomous
+    /// * `thinking_effort` - Optional thinking effort level for models that support
+    ///   extended reasoning. Accepted values: `none`, `low`, `medium`, `high`,
+    ///   `extra_high`. When `Some("none")`, reasoning parameters are cleared.
+    ///   When `None`, the provider default is used.
+    pub async fn run_plan_with_options(
+        config: Config,
+        plan_path: Option<String>,
+        prompt: Option<String>,
+        allow_dangerous: bool,
+        thinking_effort: Option<String>,
+    ) -> Result<()> {
+        run_plan_with_output(
+            config,
+            plan_path,
+            prompt,
+            allow_dangerous,
+            thinking_effort,
+            false,
+            &std::env::current_dir()?,
+        )
+        .await
+    }
+
+    /// Run a plan or a prompt via the agent, choosing the output format.
+    ///
+    /// Text output prints the result followed by a tool metrics table. JSON
+    /// output prints a single object with `success`, `result` or `error`, and
+    /// `tool_metrics`.
     ///
     /// # Arguments
     ///
     /// * `config` - Global configuration (consumed)
-    /// * `plan_path` - Optional path to plan file (yaml/json/md)
-    /// * `prompt` - Optional prompt text (used if plan_path is None)
-    pub async fn run_plan(
+    /// * `plan_path` - Optional path to a plan file
+    /// * `prompt` - Optional direct prompt
+    /// * `allow_dangerous` - If true, the execution mode is escalated to FullAutonomous
+    /// * `thinking_effort` - Optional thinking effort level (see `run_plan_with_options`)
+    /// * `json` - If true, print the outcome as JSON
+    /// * `working_dir` - Directory the agent's tools are rooted in (`--cwd`)
+    pub async fn run_plan_with_output(
         config: Config,
         plan_path: Option<String>,
         prompt: Option<String>,
+        allow_dangerous: bool,
+        thinking_effort: Option<String>,
+        json: bool,
+        working_dir: &Path,
     ) -> Result<()> {
-        run_plan_with_options(config, plan_path, prompt, false, None).await
+        // Ctrl-C or SIGTERM cancels the agent turn and stops child processes
+        let shutdown = ShutdownCoordinator::install();
+        run_plan_with_shutdown(
+            config,
+            plan_path,
+            prompt,
+            allow_dangerous,
+            thinking_effort,
+            json,
+            working_dir,
+            &shutdown,
+        )
+        .await
     }
 
-    /// Run a plan or a prompt via the agent with extra options.
+    /// Run a plan or a prompt with tools rooted at an explicit directory.
+    ///
+    /// File and terminal tools, and skill discovery, operate in `working_dir`
+    /// instead of the process working directory. Watcher executions use this
+    /// to run each plan in its own workspace.
     ///
     /// # Arguments
     ///
@@ -1968,18 +4323,52 @@ pub mod r#run {
     /// * `plan_path` - Optional path to a plan file
     /// * `prompt` - Optional direct prompt
     /// * `allow_dangerous` - If true, the execution mode is escalated to FullAutonomous
-    /// * `thinking_effort` - Optional thinking effort level for models that support
-    ///   extended reasoning. Accepted values: `none`, `low`, `medium`, `high`,
-    ///   `extra_high`. When `Some("none")`, reasoning parameters are cleared.
-    ///   When `None`, the provider default is used.
-    pub async fn run_plan_with_options(
+    /// * `thinking_effort` - Optional thinking effort level (see `run_plan_with_options`)
+    /// * `json` - If true, print the outcome as JSON
+    /// * `working_dir` - Directory the agent's tools are rooted in
+    pub async fn run_plan_in_working_dir(
+        config: Config,
+        plan_path: Option<String>,
+        prompt: Option<String>,
+        allow_dangerous: bool,
+        thinking_effort: Option<String>,
+        json: bool,
+        working_dir: &Path,
+    ) -> Result<()> {
+        // Signals are handled by the caller; a watcher stops the child
+        // processes of its running plans itself
+        let shutdown = ShutdownCoordinator::new(ChildProcessRegistry::global());
+        run_plan_with_shutdown(
+            config,
+            plan_path,
+            prompt,
+            allow_dangerous,
+            thinking_effort,
+            json,
+            working_dir,
+            &shutdown,
+        )
+        .await
+    }
+
+    /// Run a plan or a prompt, stopping cleanly when `shutdown` is requested
+    ///
+    /// When the shutdown token cancels the agent turn, registered child
+    /// processes are stopped, the telemetry session ends as cancelled, and a
+    /// summary is printed to stderr before `XzatomaError::Cancelled` is
+    /// returned.
+    #[allow(clippy::too_many_arguments)]
+    async fn run_plan_with_shutdown(
         config: Config,
         plan_path: Option<String>,
         prompt: Option<String>,
         allow_dangerous: bool,
         thinking_effort: Option<String>,
+        json: bool,
+        working_dir: &Path,
+        shutdown: &ShutdownCoordinator,
     ) -> Result<()> {
-        tracing::info!("Starting plan execution mode");
+        tracing::info!(working_dir = %working_dir.display(), "Starting plan execution mode");
 
         if plan_path.is_none() && prompt.is_none() {
             return Err(XzatomaError::Config(
@@ -1987,26 +4376,70 @@ pub mod r#run {
             ));
         }
 
-        // Build tools & agent
-        let working_dir = std::env::current_dir()?;
+        if json && config.telemetry.stdout {
+            return Err(XzatomaError::Config(
+                "telemetry.stdout cannot be combined with --json; set telemetry.file instead"
+                    .to_string(),
+            ));
+        }
 
         if allow_dangerous {
             tracing::warn!("Dangerous commands are allowed for this run: allow_dangerous=true");
         }
 
+        // A session ID that must be new is checked before any provider work
+        let session = open_session_storage(&config)?;
+
         // Build tools, skills, and MCP stack via the shared environment builder.
         // The run command is always headless (non-interactive).
-        let env = build_agent_environment(&config, &working_dir, true).await?;
+        let env = build_agent_environment(&config, working_dir, true).await?;
         let tools = env.tool_registry;
+        let safety_mode = env.safety_mode;
         let active_skill_registry = env.active_skill_registry;
         let skill_disclosure = env.skill_disclosure;
         // Keep the MCP manager Arc alive for the entire function so that
         // McpToolExecutor instances (registered in tools) can call back to it.
         let _mcp_manager = env.mcp_manager;
 
-        // Create agent using the shared provider factory.
-        let provider_box = create_provider(&config.provider.provider_type, &config.provider)?;
+        // Create agent using the shared provider factory. Requests that reach
+        // the provider are logged and checked against the monthly budget.
+        let usage_ledger = start_usage_ledger(&config, &config.provider.provider_type)?;
+        let (provider_box, cache_counters) = wrap_with_cache(
+            wrap_with_budget(
+                wrap_with_quirks(
+                    create_provider(&config.provider.provider_type, &config.provider)?,
+                    &config.provider.quirks,
+                ),
+                usage_ledger.as_ref(),
+            ),
+            &config.provider.provider_type,
+            &config.provider.cache,
+            &Paths::from_config(&config)?,
+        );
+        // `--record` stores every provider request for `xzatoma debug`
+        let (provider_box, recorder) = wrap_with_recorder(
+            provider_box,
+            &config.agent.recording,
+            &Paths::from_config(&config)?,
+        )?;
         let provider: Arc<dyn crate::providers::Provider> = Arc::from(provider_box);
+        // Names the run's artifacts: post-mortem, snapshot record, recording
+        let run_id = recorder.as_ref().map_or_else(
+            || uuid::Uuid::new_v4().to_string(),
+            |recorder| recorder.run_id().to_string(),
+        );
+
+        // The workspace is captured before the agent can change it; a
+        // snapshot that cannot be taken stops the run
+        let workspace_snapshot: Option<Snapshot> = if config.run.snapshot.enabled {
+            let taken = snapshot::snapshot_run(&config, working_dir, &run_id)?;
+            if !json {
+                ui_println!("{}", snapshot::describe_snapshot(&taken));
+            }
+            Some(taken)
+        } else {
+            None
+        };
 
         // Apply thinking effort from CLI flag if provided.
         // "none" is the sentinel string meaning "clear any explicit effort" (maps to
@@ -2027,13 +4460,37 @@ pub mod r#run {
             }
         }
 
+        let telemetry = TelemetrySink::from_config(&config.telemetry, process_session_id())?;
+        let _telemetry_guard = telemetry.as_ref().map(|telemetry| telemetry.end_on_drop());
+        if let Some(telemetry) = &telemetry {
+            telemetry.start_session(
+                "run",
+                &config.provider.provider_type,
+                Some(provider.get_current_model()),
+            );
+        }
+
+        let overflow_summarizer = build_overflow_summarizer(&config, &provider).await;
+        // Kept for the post-mortem, which is requested after the agent stops
+        let report_provider = Arc::clone(&provider);
         let mut agent = Agent::new_from_shared_provider(provider, tools, config.agent.clone())?;
+        agent.set_telemetry(telemetry.clone());
+        agent.set_overflow_summarizer(overflow_summarizer);
+        agent.set_output_pager(build_output_pager(&config));
+        agent
+            .conversation_mut()
+            .set_cwd(Some(working_dir.to_path_buf()));
 
         if let Some(disclosure) = &skill_disclosure {
             agent
                 .conversation_mut()
                 .add_system_message(disclosure.clone());
         }
+        if config.agent.read_only {
+            agent
+                .conversation_mut()
+                .add_system_message(READ_ONLY_PROMPT.to_string());
+        }
 
         let mut transient_system_messages = Vec::new();
         if let Some(active_skill_prompt) =
@@ -2044,39 +4501,312 @@ pub mod r#run {
         agent.set_transient_system_messages(transient_system_messages);
 
         // Compose a textual task to send to the agent
-        let task = if let Some(path) = plan_path {
-            let plan = PlanParser::from_file(Path::new(&path))?;
-            PlanParser::validate(&plan)?;
+        let plan = match plan_path {
+            Some(path) => {
+                let plan = PlanParser::from_file(Path::new(&path))?;
+                PlanParser::validate(&plan)?;
+                Some(plan)
+            }
+            None => None,
+        };
+        let task = match &plan {
+            Some(plan) => plan.to_instruction(),
+            // `prompt` is guaranteed to be Some when here because of the earlier check
+            None => prompt.unwrap(),
+        };
+        let session_title = match &plan {
+            Some(plan) => plan.name.clone(),
+            None => prompt_title(&task),
+        };
 
-            let steps_s = plan
-                .steps
-                .iter()
-                .map(|s| format!("- {}: {}", s.name, s.action))
-                .collect::<Vec<_>>()
-                .join("\n");
+        // Large prompts are reported before anything is sent
+        if let Some(report) = crate::agent::preflight::check(
+            &config.agent.preflight,
+            &agent.provider().get_current_model(),
+            effective_context_limit(&agent).await,
+            agent.conversation().token_count(),
+            &task,
+            &[],
+        ) {
+            if config.agent.preflight.strict {
+                if let Some(telemetry) = &telemetry {
+                    telemetry.end_session(TelemetryStatus::Error);
+                }
+                return Err(XzatomaError::QuotaExceeded(format!(
+                    "the prompt is about {} tokens, over the preflight limit of {} (--strict-budget)",
+                    report.total_tokens(),
+                    report.threshold
+                )));
+            }
+            print_preflight_report(&report);
+        }
 
-            format!(
-                "Execute this plan:\n\nName: {}\n\nSteps:\n{}\n",
-                plan.name, steps_s
-            )
+        if !json {
+            ui_println!("Executing task...\n");
+        }
+        // Plans that restrict or retry steps run one step at a time, each
+        // with its own tools and terminal mode
+        let stepwise = plan.as_ref().is_some_and(Plan::runs_stepwise);
+        let mut step_summaries = Vec::new();
+        let outcome = match plan.as_ref().filter(|_| stepwise) {
+            Some(plan) => {
+                let (default_mode, ceiling) = step_modes(&config, allow_dangerous);
+                let execution = PlanStepExecutor::new(default_mode, ceiling)
+                    .with_terminal(terminal_factory(&config, working_dir, safety_mode))
+                    .execute(&mut agent, plan, shutdown.token(), &mut NoOpObserver)
+                    .await;
+                step_summaries = execution.steps;
+                execution.result
+            }
+            None => {
+                agent
+                    .execute_with_observer(task.clone(), shutdown.token(), &mut NoOpObserver)
+                    .await
+            }
+        };
+        // A `finish` call with a status other than success fails the run
+        let finished = agent.last_outcome().filter(|_| outcome.is_ok()).cloned();
+        let outcome = match &finished {
+            Some(finished) => {
+                outcome.and_then(|response| outcome_result(finished).map(|()| response))
+            }
+            None => outcome,
+        };
+        let interrupted = if shutdown.is_requested() {
+            Some(shutdown.shutdown().await)
         } else {
-            // `prompt` is guaranteed to be Some when here because of the earlier check
-            prompt.unwrap()
+            None
+        };
+        let postmortem = match &outcome {
+            Err(error) if config.run.postmortem => {
+                let context = FailureContext::from_run(
+                    &task,
+                    plan.as_ref(),
+                    stepwise,
+                    agent.conversation().messages(),
+                    error,
+                );
+                write_postmortem(&config, &report_provider, &context, error, &run_id).await
+            }
+            _ => None,
         };
+        if let Some(recorder) = &recorder {
+            recorder.finish(agent.conversation().messages());
+        }
+        let saved_session = session.as_ref().and_then(|(storage, id)| {
+            match storage.upsert_conversation(
+                id,
+                &session_title,
+                Some(agent.provider().get_current_model().as_str()),
+                agent.conversation().messages(),
+                config.storage.if_exists,
+            ) {
+                Ok(()) => Some(id.clone()),
+                Err(e) => {
+                    ui_eprintln!("Failed to save conversation: {}", e);
+                    None
+                }
+            }
+        });
+        if let Some(ledger) = &usage_ledger {
+            ledger.flush().await;
+        }
+        let tool_summary = agent.tool_metrics().summary();
+        let usage = agent.get_token_usage().unwrap_or_default();
+        let cache_summary = cache_counters.as_ref().map(|counters| {
+            serde_json::json!({
+                "hits": counters.hits(),
+                "misses": counters.misses(),
+                "bypassed": counters.bypassed(),
+            })
+        });
+        if let Some(telemetry) = &telemetry {
+            telemetry.end_session(match &outcome {
+                Ok(_) => TelemetryStatus::Success,
+                Err(XzatomaError::Cancelled) => TelemetryStatus::Cancelled,
+                Err(_) => TelemetryStatus::Error,
+            });
+        }
+        if let Some(summary) = &interrupted {
+            ui_eprintln!("\n{}", summary);
+        }
 
-        println!("Executing task...\n");
-        match agent.execute(task).await {
-            Ok(response) => {
-                println!("Result:\n{}", response);
-                Ok(())
+        if json {
+            let mut report = match &outcome {
+                Ok(response) => serde_json::json!({
+                    "success": true,
+                    "result": response,
+                    "outcome": finished,
+                    "tool_metrics": tool_summary,
+                    "usage": usage,
+                    "provider_cache": cache_summary,
+                }),
+                Err(e) => serde_json::json!({
+                    "success": false,
+                    "error": e.to_string(),
+                    "outcome": finished,
+                    "tool_metrics": tool_summary,
+                    "usage": usage,
+                    "provider_cache": cache_summary,
+                }),
+            };
+            if let Some(recorder) = &recorder {
+                report["recorded_run"] = serde_json::json!(recorder.run_id());
+            }
+            if let Some(id) = &saved_session {
+                report["conversation_id"] = serde_json::json!(id);
+            }
+            if let Some((_, path)) = &postmortem {
+                report["postmortem"] = serde_json::json!(path);
+            }
+            if let Some(taken) = &workspace_snapshot {
+                report["snapshot"] = serde_json::json!({
+                    "id": taken.id,
+                    "kind": taken.kind,
+                    "run_id": run_id,
+                });
+            }
+            if stepwise {
+                report["steps"] = serde_json::json!(step_summaries);
+            }
+            ui::json(&report)?;
+            return outcome.map(|_| ());
+        }
+
+        match &outcome {
+            Ok(response) => ui_println!("Result:\n{}", response),
+            Err(e) => ui_eprintln!("Execution failed: {}", e),
+        }
+        if let Some((report, path)) = &postmortem {
+            ui_eprintln!("\nPost-mortem (saved to {}):\n\n{}", path.display(), report);
+        }
+        if step_summaries.iter().any(|step| step.attempts.len() > 1) {
+            ui_println!("\nStep attempts:");
+            print_step_attempts(&step_summaries);
+        }
+        if !tool_summary.is_empty() {
+            ui_println!("\nTool usage:");
+            print_tool_metrics(&tool_summary);
+        }
+        ui_println!("\n{}", format_usage_line(&usage, cache_counters.as_deref()));
+        if let Some(warning) = usage_ledger.as_deref().and_then(UsageLedger::take_warning) {
+            ui_eprintln!("{}", warning);
+        }
+        if let Some(recorder) = &recorder {
+            ui_println!(
+                "Recorded {} turn{} as run {} (inspect with: xzatoma debug {})",
+                recorder.turns_recorded(),
+                if recorder.turns_recorded() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                recorder.run_id(),
+                recorder.run_id()
+            );
+        }
+        if let Some(id) = &saved_session {
+            ui_println!("Saved conversation {}", id);
+        }
+        if workspace_snapshot.is_some() {
+            ui_println!(
+                "Undo this run's changes with: xzatoma run rollback {}",
+                run_id
+            );
+        }
+        outcome.map(|_| ())
+    }
+
+    /// Generates the post-mortem of a failed run and saves it with the run's
+    /// artifacts
+    ///
+    /// Returns the report and the file it was written to, or `None` when the
+    /// failure came from the provider or the report could not be made; the
+    /// reason is logged, and the run's own error is what gets reported.
+    async fn write_postmortem(
+        config: &Config,
+        provider: &Arc<dyn crate::providers::Provider>,
+        context: &FailureContext,
+        error: &XzatomaError,
+        run_id: &str,
+    ) -> Option<(String, std::path::PathBuf)> {
+        if let Some(reason) = postmortem::skip_reason(error) {
+            tracing::info!("No post-mortem written: {}", reason);
+            return None;
+        }
+        let paths = match Paths::from_config(config) {
+            Ok(paths) => paths,
+            Err(e) => {
+                tracing::warn!(error = %e, "Cannot resolve the data directory; no post-mortem written");
+                return None;
             }
+        };
+        let generator =
+            PostmortemGenerator::new(Arc::clone(provider), ToolOutputStore::from_paths(&paths));
+        let written = generator.generate(context).await.and_then(|report| {
+            postmortem::write_report(&paths.run_artifacts_dir(run_id), &report)
+                .map(|path| (report, path))
+        });
+        match written {
+            Ok(written) => Some(written),
             Err(e) => {
-                eprintln!("Execution failed: {}", e);
-                Err(e)
+                ui_eprintln!("Could not write the post-mortem: {}", e);
+                None
             }
         }
     }
 
+    /// Opens history storage when `storage.session_id` is set
+    ///
+    /// Returns the storage and the conversation ID derived from the session
+    /// key, or `None` when the run is not saved.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage cannot be opened, or if the
+    /// conversation already exists and `storage.if_exists` is `fail`.
+    fn open_session_storage(config: &Config) -> Result<Option<(SqliteStorage, String)>> {
+        let Some(key) = config.storage.session_id.as_deref() else {
+            return Ok(None);
+        };
+        let storage = SqliteStorage::new(&Paths::from_config(config)?)?;
+        let id = session_conversation_id(key).to_string();
+        if config.storage.if_exists == IfExists::Fail && storage.conversation_exists(&id)? {
+            return Err(XzatomaError::Storage(format!(
+                "Session '{}' is already stored as conversation {}; use --if-exists replace or append",
+                key, id
+            )));
+        }
+        Ok(Some((storage, id)))
+    }
+
+    /// Title for a saved run: the first line of the prompt, shortened to 50
+    /// characters
+    fn prompt_title(prompt: &str) -> String {
+        let line = prompt.lines().next().unwrap_or_default().trim();
+        if line.chars().count() <= 50 {
+            return line.to_string();
+        }
+        let mut title: String = line.chars().take(47).collect();
+        title.push_str("...");
+        title
+    }
+
+    /// Maps how the agent finished to the result of the run
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::TaskFailed` for status `failure` and
+    /// `XzatomaError::TaskNeedsInput` for status `needs_input`, so the
+    /// process exits non-zero.
+    pub(crate) fn outcome_result(outcome: &ExecutionOutcome) -> Result<()> {
+        match outcome.status {
+            FinishStatus::Success => Ok(()),
+            FinishStatus::Failure => Err(XzatomaError::TaskFailed(outcome.summary.clone())),
+            FinishStatus::NeedsInput => Err(XzatomaError::TaskNeedsInput(outcome.summary.clone())),
+        }
+    }
+
     /// Creates a provider instance for a specific model
     ///
     /// This helper function creates a new provider configured to use the specified model.
@@ -2171,6 +4901,28 @@ pub mod r#run {
             assert!(res.is_err());
         }
 
+        #[tokio::test]
+        async fn test_run_json_rejects_stdout_telemetry() {
+            let dir = tempdir().unwrap();
+            let mut cfg = Config::default();
+            cfg.provider.provider_type = "invalid_provider".to_string();
+            cfg.telemetry.stdout = true;
+
+            let res = run_plan_in_working_dir(
+                cfg,
+                None,
+                Some("hello".to_string()),
+                false,
+                None,
+                true,
+                dir.path(),
+            )
+            .await;
+            assert!(
+                matches!(res, Err(XzatomaError::Config(msg)) if msg.contains("telemetry.stdout"))
+            );
+        }
+
         // Prepare a simple plan file and validate parsing does not panic
         #[tokio::test]
         async fn test_run_plan_parses_file_and_validates() {
@@ -2192,6 +4944,108 @@ steps:
             let res = run_plan(cfg, Some(p.to_string_lossy().to_string()), None).await;
             assert!(res.is_err());
         }
+
+        #[test]
+        fn test_validate_plan_file_accepts_valid_plan_and_rejects_invalid() {
+            let dir = tempdir().unwrap();
+            let valid = dir.path().join("valid.yaml");
+            stdfs::write(&valid, "name: Ok\nsteps:\n  - name: s\n    action: a\n").unwrap();
+            assert!(validate_plan_file(&valid, false).is_ok());
+
+            let invalid = dir.path().join("invalid.yaml");
+            stdfs::write(&invalid, "name: \"\"\nsteps: []\n").unwrap();
+            match validate_plan_file(&invalid, true) {
+                Err(XzatomaError::InvalidPlan(error)) => assert_eq!(error.issues.len(), 2),
+                other => panic!("expected InvalidPlan, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_dry_run_plan_caps_step_modes_without_allow_dangerous() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("plan.yaml");
+            stdfs::write(
+                &path,
+                "name: Fix\nsteps:\n  - name: inspect\n    action: look\n    tools: [read_file]\n  - name: fix\n    action: edit\n    execution_mode: full_autonomous\n",
+            )
+            .unwrap();
+            let config = Config::default();
+            assert!(dry_run_plan(&config, &path, false, dir.path(), true).is_ok());
+
+            let plan = PlanParser::from_file(&path).unwrap();
+            let (default_mode, ceiling) = step_modes(&config, false);
+            let policies = PlanStepExecutor::new(default_mode, ceiling).policies(&plan);
+            assert_eq!(policies[0].describe_tools(), "read_file");
+            assert_eq!(policies[1].mode(), ExecutionMode::RestrictedAutonomous);
+
+            let (default_mode, ceiling) = step_modes(&config, true);
+            let policies = PlanStepExecutor::new(default_mode, ceiling).policies(&plan);
+            assert_eq!(policies[0].mode(), ExecutionMode::RestrictedAutonomous);
+            assert_eq!(policies[1].mode(), ExecutionMode::FullAutonomous);
+        }
+
+        #[test]
+        fn test_full_autonomous_plan_requires_matching_hash_when_not_interactive() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("plan.yaml");
+            stdfs::write(
+                &path,
+                "name: Wipe\nsteps:\n  - name: wipe\n    action: Run `rm -rf /`\n    execution_mode: full_autonomous\n",
+            )
+            .unwrap();
+            let config = Config::default();
+
+            // Capped below full_autonomous, so no confirmation is needed
+            assert!(confirm_dangerous_plan(&config, &path, false, None, dir.path(), false).is_ok());
+
+            let missing =
+                confirm_dangerous_plan(&config, &path, true, None, dir.path(), false).unwrap_err();
+            assert!(matches!(
+                missing,
+                XzatomaError::DangerousPlanNotConfirmed(_)
+            ));
+            assert_eq!(missing.exit_code(), crate::error::exit_codes::NOPERM);
+
+            let hash = PlanRiskReport::analyze(&PlanParser::from_file(&path).unwrap(), dir.path())
+                .plan_hash;
+            assert!(
+                confirm_dangerous_plan(&config, &path, true, Some(&hash), dir.path(), false)
+                    .is_ok()
+            );
+            assert!(matches!(
+                confirm_dangerous_plan(
+                    &config,
+                    &path,
+                    true,
+                    Some("0123456789abcdef"),
+                    dir.path(),
+                    false
+                ),
+                Err(XzatomaError::DangerousPlanNotConfirmed(_))
+            ));
+        }
+
+        #[test]
+        fn test_outcome_result_maps_status_to_exit_code() {
+            assert!(outcome_result(&ExecutionOutcome::from_text("done")).is_ok());
+
+            let failed = outcome_result(&ExecutionOutcome::finished(
+                FinishStatus::Failure,
+                "tests still fail",
+            ))
+            .unwrap_err();
+            assert!(
+                matches!(&failed, XzatomaError::TaskFailed(summary) if summary == "tests still fail")
+            );
+            assert_eq!(failed.exit_code(), crate::error::exit_codes::GENERAL);
+
+            let blocked = outcome_result(&ExecutionOutcome::finished(
+                FinishStatus::NeedsInput,
+                "which branch?",
+            ))
+            .unwrap_err();
+            assert_eq!(blocked.exit_code(), crate::error::exit_codes::TEMPFAIL);
+        }
     }
 }
 
@@ -2218,22 +5072,25 @@ pub mod auth {
                 // poll until the user authorizes the device (or an error/timeout occurs).
                 let provider = CopilotProvider::new(config.provider.copilot.clone())?;
 
-                println!("Copilot: initiating device flow (you will be prompted to visit a URL and enter a code)...");
+                ui_println!("Copilot: initiating device flow (you will be prompted to visit a URL and enter a code)...");
                 // Run the provider's authenticate flow and surface any errors to the user.
                 match provider.authenticate().await {
                     Ok(_) => {
-                        println!("Copilot: authentication successful — token cached in the system keyring.");
+                        ui_println!(
+                            "Copilot: authentication successful {} token cached in the system keyring.",
+                            Glyph::Dash
+                        );
                         Ok(())
                     }
                     Err(e) => {
                         // Provide a clear, immediate message and propagate the error.
-                        eprintln!("Copilot: authentication failed: {}", e);
+                        ui_eprintln!("Copilot: authentication failed: {}", e);
                         Err(e)
                     }
                 }
             }
             "ollama" => {
-                println!("Ollama: typically uses a local host with no OAuth; ensure `provider.ollama` config is set.");
+                ui_println!("Ollama: typically uses a local host with no OAuth; ensure `provider.ollama` config is set.");
                 Ok(())
             }
             other => Err(XzatomaError::Provider(format!(
@@ -2295,6 +5152,8 @@ pub mod watch {
         /// Optional generic matcher version regex override.
         pub match_version: Option<String>,
     }
+    use crate::tracing_setup::TracingHandle;
+    use crate::watcher::reload::ReloadSource;
     use std::path::PathBuf;
 
     /// Run the watch command
@@ -2302,11 +5161,16 @@ pub mod watch {
     /// This function is the entry point for the `xzatoma watch` command.
     /// It configures the watcher based on CLI arguments and configuration file,
     /// sets up logging, and starts the event consumption loop with signal handling.
+    /// With a `reload` source, config file changes and `SIGHUP` hot-reload
+    /// the watcher settings that are safe to change at runtime.
     ///
     /// # Arguments
     ///
     /// * `config` - Global configuration (will be modified by CLI overrides)
     /// * `overrides` - Optional CLI overrides for watcher behavior
+    /// * `reload` - Where to reload the configuration from, if anywhere
+    /// * `tracing_handle` - Handle to the global subscriber, which switches to the
+    ///   watcher's log format and level
     ///
     /// # Returns
     ///
@@ -2319,12 +5183,19 @@ pub mod watch {
     /// - Log initialization fails
     /// - Watcher creation fails
     /// - Message consumption fails
-    pub async fn run_watch(mut config: Config, overrides: WatchCliOverrides) -> Result<()> {
+    pub async fn run_watch(
+        mut config: Config,
+        overrides: WatchCliOverrides,
+        reload: Option<ReloadSource>,
+        tracing_handle: &TracingHandle,
+    ) -> Result<()> {
         // Apply CLI argument overrides to configuration
         apply_cli_overrides(&mut config, &overrides)?;
 
         // Initialize logging system
-        crate::watcher::logging::init_watcher_logging(&config.watcher.logging)?;
+        let log_level =
+            crate::watcher::logging::init_watcher_logging(&config.watcher.logging, tracing_handle)?;
+        let reload_config = config.watcher.reload.clone();
 
         tracing::info!("Watch command started");
         tracing::info!(
@@ -2361,74 +5232,106 @@ pub mod watch {
             crate::config::WatcherType::XZepr => {
                 let mut watcher = crate::watcher::XzeprWatcher::new(config, overrides.dry_run)
                     .map_err(|error| XzatomaError::Watcher(error.to_string()))?;
-
-                // Set up signal handling for graceful shutdown
-                let (shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::channel(1);
-
-                // Spawn signal handler task
-                tokio::spawn(async move {
-                    match tokio::signal::ctrl_c().await {
-                        Ok(()) => {
-                            tracing::info!("Received CTRL+C signal, initiating graceful shutdown");
-                            if shutdown_tx.send(()).await.is_err() {
-                                tracing::debug!(
-                                    "Shutdown signal receiver already dropped for xzepr watcher"
-                                );
-                            }
-                        }
-                        Err(err) => {
-                            tracing::error!(error = %err, "Failed to set up signal handler");
-                        }
-                    }
+                let reload_task = reload.map(|source| {
+                    spawn_reload(
+                        source,
+                        watcher.live_settings(),
+                        log_level,
+                        &reload_config,
+                        &overrides,
+                    )
                 });
 
-                tokio::select! {
+                // Ctrl-C or SIGTERM stops the watcher and the child processes
+                // of any plan it is running
+                let shutdown = ShutdownCoordinator::install();
+
+                let result = tokio::select! {
                     result = watcher.start() => {
                         result.map_err(|error| XzatomaError::Watcher(error.to_string()))
                     }
-                    _ = shutdown_rx.recv() => {
+                    _ = shutdown.requested() => {
+                        let summary = shutdown.shutdown().await;
+                        ui_eprintln!("\n{}", summary);
                         tracing::info!("Graceful shutdown completed");
-                        Ok(())
+                        Err(XzatomaError::Cancelled)
                     }
+                };
+                if let Some(task) = reload_task {
+                    task.abort();
                 }
+                result
             }
             crate::config::WatcherType::Generic => {
                 let mut watcher =
                     crate::watcher::generic::GenericWatcher::new(config, overrides.dry_run)?;
-
-                // Set up signal handling for graceful shutdown
-                let (shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::channel(1);
-
-                // Spawn signal handler task
-                tokio::spawn(async move {
-                    match tokio::signal::ctrl_c().await {
-                        Ok(()) => {
-                            tracing::info!("Received CTRL+C signal, initiating graceful shutdown");
-                            if shutdown_tx.send(()).await.is_err() {
-                                tracing::debug!(
-                                    "Shutdown signal receiver already dropped for generic watcher"
-                                );
-                            }
-                        }
-                        Err(err) => {
-                            tracing::error!(error = %err, "Failed to set up signal handler");
-                        }
-                    }
+                let reload_task = reload.map(|source| {
+                    spawn_reload(
+                        source,
+                        watcher.live_settings(),
+                        log_level,
+                        &reload_config,
+                        &overrides,
+                    )
                 });
 
-                tokio::select! {
+                // Ctrl-C or SIGTERM stops the watcher and the child processes
+                // of any plan it is running
+                let shutdown = ShutdownCoordinator::install();
+
+                let result = tokio::select! {
                     result = watcher.start(None) => {
                         result.map_err(|error| XzatomaError::Watcher(error.to_string()))
                     }
-                    _ = shutdown_rx.recv() => {
+                    _ = shutdown.requested() => {
+                        let summary = shutdown.shutdown().await;
+                        ui_eprintln!("\n{}", summary);
                         tracing::info!("Graceful shutdown completed");
-                        Ok(())
+                        Err(XzatomaError::Cancelled)
                     }
+                };
+                if let Some(task) = reload_task {
+                    task.abort();
                 }
+                result
             }
         }
     }
 
+    /// Starts hot-reloading the watcher settings from `source`
+    ///
+    /// The watch command's CLI overrides are applied to every reloaded
+    /// configuration, and a `--filter-config` file is watched alongside the
+    /// config file.
+    fn spawn_reload(
+        source: ReloadSource,
+        settings: std::sync::Arc<crate::watcher::reload::LiveSettings>,
+        log_level: crate::watcher::logging::LogLevelHandle,
+        reload: &crate::config::WatcherReloadConfig,
+        overrides: &WatchCliOverrides,
+    ) -> tokio::task::JoinHandle<()> {
+        let mut source = source;
+        if let Some(path) = &overrides.filter_config {
+            source = source.also_watch(path.clone());
+        }
+        let cli_overrides = overrides.clone();
+        let source =
+            source.with_overrides(move |config| apply_cli_overrides(config, &cli_overrides));
+        let reloader =
+            crate::watcher::reload::ConfigReloader::new(settings).with_log_level(log_level);
+
+        tracing::info!(
+            watch_files = reload.enabled,
+            "Config hot-reload enabled; send SIGHUP to reload"
+        );
+        crate::watcher::reload::spawn(
+            reloader,
+            source,
+            reload.enabled,
+            std::time::Duration::from_millis(reload.debounce_ms),
+        )
+    }
+
     /// Apply CLI argument overrides to the configuration
     ///
     /// Updates the configuration object with values provided via CLI arguments.
@@ -3059,7 +5962,9 @@ pub mod watch {
             config.watcher.watcher_type = crate::config::WatcherType::XZepr;
             config.watcher.kafka = None;
 
-            let result = run_watch(config, WatchCliOverrides::default()).await;
+            let (_subscriber, tracing_handle) = crate::tracing_setup::cli_subscriber();
+            let result =
+                run_watch(config, WatchCliOverrides::default(), None, &tracing_handle).await;
 
             assert!(result.is_err());
             assert!(result
@@ -3074,7 +5979,9 @@ pub mod watch {
             config.watcher.watcher_type = crate::config::WatcherType::Generic;
             config.watcher.kafka = None;
 
-            let result = run_watch(config, WatchCliOverrides::default()).await;
+            let (_subscriber, tracing_handle) = crate::tracing_setup::cli_subscriber();
+            let result =
+                run_watch(config, WatchCliOverrides::default(), None, &tracing_handle).await;
 
             assert!(result.is_err());
             assert!(result
@@ -3096,6 +6003,30 @@ mod tests {
         let _ = Config::default();
     }
 
+    #[test]
+    fn test_format_usage_line_reports_cache_hits_only_when_enabled() {
+        let usage = TokenUsage::new(120, 30);
+        assert_eq!(
+            format_usage_line(&usage, None),
+            "Usage: 120 prompt + 30 completion = 150 tokens"
+        );
+
+        let counters = CacheCounters::default();
+        assert_eq!(
+            format_usage_line(&usage, Some(&counters)),
+            "Usage: 120 prompt + 30 completion = 150 tokens (0 of 0 responses served from cache)"
+        );
+    }
+
+    #[test]
+    fn test_format_usage_line_compacts_large_counts() {
+        let usage = TokenUsage::new(123_456, 999);
+        assert_eq!(
+            format_usage_line(&usage, None),
+            "Usage: 123.5k prompt + 999 completion = 124.5k tokens"
+        );
+    }
+
     #[test]
     fn test_should_enable_subagents_with_subagent_keyword() {
         assert!(should_enable_subagents("use subagents to organize files"));
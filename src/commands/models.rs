@@ -8,6 +8,7 @@ use crate::config::Config;
 use crate::error::{Result, XzatomaError};
 use crate::providers;
 use crate::providers::{ModelInfo, ModelInfoSummary};
+use crate::{ui, ui_print, ui_println};
 use prettytable::{row, Table};
 use serde_json;
 
@@ -61,9 +62,9 @@ pub async fn list_models(
 
         if models_summary.is_empty() {
             if json {
-                println!("[]");
+                ui::data("[]");
             } else {
-                println!("No models available from provider: {}", provider_type);
+                ui_println!("No models available from provider: {}", provider_type);
             }
             return Ok(());
         }
@@ -81,9 +82,9 @@ pub async fn list_models(
 
         if models.is_empty() {
             if json {
-                println!("[]");
+                ui::data("[]");
             } else {
-                println!("No models available from provider: {}", provider_type);
+                ui_println!("No models available from provider: {}", provider_type);
             }
             return Ok(());
         }
@@ -208,10 +209,10 @@ pub async fn show_current_model(config: &Config, provider_name: Option<&str>) ->
 
     let current_model = provider.get_current_model();
 
-    println!("\nCurrent Model Information\n");
-    println!("Provider:       {}", provider_type);
-    println!("Active Model:   {}", current_model);
-    println!();
+    ui_println!("\nCurrent Model Information\n");
+    ui_println!("Provider:       {}", provider_type);
+    ui_println!("Active Model:   {}", current_model);
+    ui_println!();
 
     Ok(())
 }
@@ -238,13 +239,13 @@ fn output_models_json(models: &[ModelInfo], provider_type: &str) -> Result<()> {
 
         if !raw_models.is_empty() {
             let json = serialize_pretty(&raw_models).map_err(XzatomaError::Serialization)?;
-            println!("{}", json);
+            ui::data(json);
             return Ok(());
         }
     }
 
     let json = serialize_pretty(models).map_err(XzatomaError::Serialization)?;
-    println!("{}", json);
+    ui::data(json);
     Ok(())
 }
 
@@ -287,12 +288,12 @@ fn output_models_summary_json(models: &[ModelInfoSummary], provider_type: &str)
             .collect();
 
         let json = serialize_pretty(&ollama_summaries).map_err(XzatomaError::Serialization)?;
-        println!("{}", json);
+        ui::data(json);
         return Ok(());
     }
 
     let json = serialize_pretty(models).map_err(XzatomaError::Serialization)?;
-    println!("{}", json);
+    ui::data(json);
     Ok(())
 }
 
@@ -326,9 +327,9 @@ fn output_models_table(models: &[ModelInfo], provider_type: &str) {
         ]);
     }
 
-    println!("\nAvailable models from {}:\n", provider_type);
+    ui_println!("\nAvailable models from {}:\n", provider_type);
     table.printstd();
-    println!();
+    ui_println!();
 }
 
 /// Output models summary in table format (full data)
@@ -336,7 +337,7 @@ fn output_models_summary_table(models: &[ModelInfoSummary], provider_type: &str)
     // Print the rendered table string. Rendering is done by a helper so unit tests
     // can capture and assert on the string output without redirecting stdout.
     let output = render_models_summary_table(models, provider_type);
-    print!("{}", output);
+    ui_print!("{}", output);
 }
 
 /// Format optional boolean for display
@@ -568,12 +569,12 @@ fn output_model_info_json(model: &ModelInfo, provider_type: &str) -> Result<()>
     if provider_type == "ollama" {
         if let Some(raw) = &model.raw_data {
             let json = serialize_pretty(raw).map_err(XzatomaError::Serialization)?;
-            println!("{}", json);
+            ui::data(json);
             return Ok(());
         }
     }
     let json = serialize_pretty(model).map_err(XzatomaError::Serialization)?;
-    println!("{}", json);
+    ui::data(json);
     Ok(())
 }
 
@@ -605,21 +606,21 @@ fn output_model_summary_json(model: &ModelInfoSummary, provider_type: &str) -> R
         });
 
         let json = serialize_pretty(&ollama_summary).map_err(XzatomaError::Serialization)?;
-        println!("{}", json);
+        ui::data(json);
         return Ok(());
     }
     let json = serialize_pretty(model).map_err(XzatomaError::Serialization)?;
-    println!("{}", json);
+    ui::data(json);
     Ok(())
 }
 
 /// Output model info in detailed format (basic data)
 fn output_model_info_detailed(model: &ModelInfo) {
-    println!("\nModel Information ({})\n", model.display_name);
-    println!("Name:            {}", model.name);
-    println!("Display Name:    {}", model.display_name);
-    println!("Context Window:  {} tokens", model.context_window);
-    println!(
+    ui_println!("\nModel Information ({})\n", model.display_name);
+    ui_println!("Name:            {}", model.name);
+    ui_println!("Display Name:    {}", model.display_name);
+    ui_println!("Context Window:  {} tokens", model.context_window);
+    ui_println!(
         "Capabilities:    {}",
         if model.capabilities.is_empty() {
             "None".to_string()
@@ -634,19 +635,19 @@ fn output_model_info_detailed(model: &ModelInfo) {
     );
 
     if !model.provider_specific.is_empty() {
-        println!("\nProvider-Specific Metadata:");
+        ui_println!("\nProvider-Specific Metadata:");
         for (key, value) in &model.provider_specific {
-            println!("  {}: {}", key, value);
+            ui_println!("  {}: {}", key, value);
         }
     }
 
-    println!();
+    ui_println!();
 }
 
 /// Output model summary in detailed format (full data)
 fn output_model_summary_detailed(model: &ModelInfoSummary) {
     let output = render_model_summary_detailed(model);
-    print!("{}", output);
+    ui_print!("{}", output);
 }
 
 #[cfg(test)]
@@ -0,0 +1,213 @@
+//! Data location commands
+//!
+//! Prints where xzatoma keeps its data, cache, and state, where each of
+//! those locations came from, and whether it exists and can be written. See
+//! [`crate::paths`] for how the directories are resolved.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::paths::Paths;
+use crate::providers::cache::ResponseCache;
+use crate::semantic_index::SemanticIndex;
+use crate::skills::trust::resolve_trust_store_path;
+use crate::storage::SqliteStorage;
+use crate::tools::audit_log::AuditLog;
+use crate::ui_println;
+use crate::workspace_trust;
+
+/// Whether a location exists and can be written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocationStatus {
+    /// Exists and can be written
+    Writable,
+    /// Exists but cannot be written
+    ReadOnly,
+    /// Does not exist yet; its nearest existing parent can be written
+    Creatable,
+    /// Does not exist and cannot be created
+    Unavailable,
+}
+
+impl LocationStatus {
+    /// Checks `path` without leaving anything behind
+    pub fn check(path: &Path) -> Self {
+        if path.exists() {
+            if is_writable(path) {
+                LocationStatus::Writable
+            } else {
+                LocationStatus::ReadOnly
+            }
+        } else if path
+            .ancestors()
+            .skip(1)
+            .find(|ancestor| ancestor.exists())
+            .is_some_and(is_writable)
+        {
+            LocationStatus::Creatable
+        } else {
+            LocationStatus::Unavailable
+        }
+    }
+
+    fn label(self) -> colored::ColoredString {
+        match self {
+            LocationStatus::Writable => "exists, writable".green(),
+            LocationStatus::ReadOnly => "exists, not writable".red(),
+            LocationStatus::Creatable => "missing, will be created".yellow(),
+            LocationStatus::Unavailable => "missing, cannot be created".red(),
+        }
+    }
+}
+
+/// Handle the paths command
+///
+/// # Arguments
+///
+/// * `config` - The loaded configuration; `paths` and the per-component
+///   location settings select what is printed
+pub fn handle_paths(config: &Config) -> Result<()> {
+    let paths = Paths::from_config(config)?;
+
+    ui_println!("\n{}", "Directories:".bold());
+    for (name, dir) in [
+        ("data", paths.data()),
+        ("cache", paths.cache()),
+        ("state", paths.state()),
+    ] {
+        ui_println!(
+            "  {:<6} {} ({}; {})",
+            name,
+            dir.path.display(),
+            dir.source,
+            LocationStatus::check(&dir.path).label()
+        );
+    }
+
+    ui_println!("\n{}", "Files:".bold());
+    for (name, path) in component_paths(config, &paths)? {
+        ui_println!(
+            "  {:<23} {} ({})",
+            name,
+            path.display(),
+            LocationStatus::check(&path).label()
+        );
+    }
+    ui_println!();
+    Ok(())
+}
+
+/// Location of every file, with per-component settings applied
+fn component_paths(config: &Config, paths: &Paths) -> Result<Vec<(&'static str, PathBuf)>> {
+    Ok(vec![
+        (
+            "history database",
+            SqliteStorage::default_database_path(paths),
+        ),
+        (
+            "subagent conversations",
+            config
+                .agent
+                .subagent
+                .conversation_database()
+                .unwrap_or_else(|| SqliteStorage::default_database_path(paths)),
+        ),
+        (
+            "credentials file",
+            config
+                .credentials
+                .path
+                .as_ref()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| paths.credentials_file()),
+        ),
+        ("semantic index", SemanticIndex::dir_for(config)?),
+        (
+            "workspace trust store",
+            workspace_trust::default_trust_store_path(&Paths::from_env()?),
+        ),
+        (
+            "skills trust store",
+            resolve_trust_store_path(config.skills.trust_store_path.as_deref())?,
+        ),
+        (
+            "provider cache",
+            ResponseCache::from_config(&config.provider.cache, paths)
+                .dir()
+                .to_path_buf(),
+        ),
+        (
+            "audit log",
+            AuditLog::resolve_path(&config.agent.tools, paths),
+        ),
+    ])
+}
+
+/// Tries to write to `path` without changing it
+fn is_writable(path: &Path) -> bool {
+    if path.is_dir() {
+        let probe = path.join(format!(".xzatoma-write-test-{}", std::process::id()));
+        let writable = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&probe)
+            .is_ok();
+        if writable {
+            let _ = fs::remove_file(&probe);
+        }
+        writable
+    } else {
+        fs::OpenOptions::new().append(true).open(path).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PathsConfig;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_location_status_distinguishes_existing_and_missing() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("history.db");
+        fs::write(&file, "").unwrap();
+
+        assert_eq!(LocationStatus::check(dir.path()), LocationStatus::Writable);
+        assert_eq!(LocationStatus::check(&file), LocationStatus::Writable);
+        assert_eq!(
+            LocationStatus::check(&dir.path().join("new/nested")),
+            LocationStatus::Creatable
+        );
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_component_paths_apply_overrides() {
+        let mut config = Config::default();
+        config.provider.cache.dir = Some("/custom/cache".to_string());
+        config.agent.tools.audit_log_path = Some("/custom/audit.jsonl".to_string());
+        let paths = Paths::resolve_with(
+            &PathsConfig {
+                data_dir: Some("/d".to_string()),
+                cache_dir: Some("/c".to_string()),
+                state_dir: Some("/s".to_string()),
+            },
+            |_| None,
+        )
+        .unwrap();
+
+        let files = component_paths(&config, &paths).unwrap();
+        let find = |name: &str| files.iter().find(|(n, _)| *n == name).unwrap().1.clone();
+        assert_eq!(find("provider cache"), PathBuf::from("/custom/cache"));
+        assert_eq!(find("audit log"), PathBuf::from("/custom/audit.jsonl"));
+        assert_eq!(
+            find("credentials file"),
+            PathBuf::from("/d/credentials.json")
+        );
+    }
+}
@@ -0,0 +1,520 @@
+//! Plan drafting commands
+//!
+//! `xzatoma plan new` asks the agent to draft a plan for a goal, and
+//! `xzatoma plan refine` asks it to edit an existing plan. The agent runs in
+//! Planning mode with read-only tools, so it can explore the workspace but not
+//! change it.
+//!
+//! Every draft is checked with [`PlanParser::validate_yaml`]. When a draft is
+//! invalid, the validation errors are sent back to the model and it is asked
+//! for a corrected plan, up to `--max-attempts` drafts in total. The final
+//! plan is previewed and written as YAML once the user confirms.
+
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::agent::Agent;
+use crate::chat_mode::{ChatMode, SafetyMode};
+use crate::cli::PlanCommand;
+use crate::config::Config;
+use crate::error::{Result, XzatomaError};
+use crate::markdown::{extract_code_blocks_with, ExtractOptions};
+use crate::network_policy::NetworkPolicy;
+use crate::paths::Paths;
+use crate::prompts::planning_prompt::generate_planning_prompt;
+use crate::providers::{
+    create_provider, wrap_with_budget, wrap_with_cache, wrap_with_quirks, UsageLedger,
+};
+use crate::read_only::ReadOnlyPolicy;
+use crate::tools::plan::{Plan, PlanParser};
+use crate::tools::plan_validation::PLAN_SCHEMA;
+use crate::tools::registry_builder::ToolRegistryBuilder;
+use crate::{ui_eprint, ui_print, ui_println};
+
+/// Default number of drafts requested before giving up
+pub const DEFAULT_MAX_ATTEMPTS: usize = 3;
+
+/// Default output path for `plan new`
+const DEFAULT_OUTPUT: &str = "plan.yaml";
+
+/// A validated plan returned by the agent
+#[derive(Debug, Clone)]
+pub struct DraftedPlan {
+    /// The parsed plan
+    pub plan: Plan,
+    /// The YAML text the model returned, written to disk as-is
+    pub yaml: String,
+    /// Number of drafts requested, including the valid one
+    pub attempts: usize,
+}
+
+/// Handle plan commands
+///
+/// # Arguments
+///
+/// * `config` - Loaded configuration
+/// * `command` - Plan subcommand to execute
+///
+/// # Errors
+///
+/// Returns an error if the provider cannot be created, the agent fails, the
+/// plan is still invalid after the last attempt, or the file cannot be
+/// written
+pub async fn handle_plan(config: Config, command: PlanCommand) -> Result<()> {
+    match command {
+        PlanCommand::New {
+            goal,
+            output,
+            working_dir,
+            max_attempts,
+            yes,
+        } => {
+            let (request, working_dir) = match goal {
+                Some(goal) => (new_plan_request(&goal, None), working_dir),
+                None => {
+                    let goal = ask("Goal", None)?;
+                    if goal.is_empty() {
+                        return Err(XzatomaError::Config(
+                            "A goal is required to draft a plan".to_string(),
+                        ));
+                    }
+                    let constraints = ask("Constraints (optional)", None)?;
+                    let working_dir = match working_dir {
+                        Some(dir) => Some(dir),
+                        None => Some(PathBuf::from(ask("Target directory", Some("."))?)),
+                    };
+                    let constraints = (!constraints.is_empty()).then_some(constraints.as_str());
+                    (new_plan_request(&goal, constraints), working_dir)
+                }
+            };
+            let output = match output {
+                Some(output) => output,
+                None if yes => PathBuf::from(DEFAULT_OUTPUT),
+                None => PathBuf::from(ask("Write plan to", Some(DEFAULT_OUTPUT))?),
+            };
+
+            let usage_ledger = super::start_usage_ledger(&config, &config.provider.provider_type)?;
+            let mut agent = build_planning_agent(&config, working_dir, usage_ledger.as_ref())?;
+            let drafted = draft_plan(&mut agent, request, max_attempts).await;
+            if let Some(ledger) = &usage_ledger {
+                ledger.flush().await;
+            }
+            save_plan(&drafted?, &output, yes)
+        }
+        PlanCommand::Refine {
+            file,
+            instruction,
+            output,
+            working_dir,
+            max_attempts,
+            yes,
+        } => {
+            let current = current_plan_yaml(&file)?;
+            let output = output.unwrap_or_else(|| default_refine_output(&file));
+
+            let usage_ledger = super::start_usage_ledger(&config, &config.provider.provider_type)?;
+            let mut agent = build_planning_agent(&config, working_dir, usage_ledger.as_ref())?;
+            let request = refine_plan_request(&current, &instruction);
+            let drafted = draft_plan(&mut agent, request, max_attempts).await;
+            if let Some(ledger) = &usage_ledger {
+                ledger.flush().await;
+            }
+            save_plan(&drafted?, &output, yes)
+        }
+    }
+}
+
+/// Asks the agent for a plan until it returns a valid one
+///
+/// Each response is validated with [`PlanParser::validate_yaml`]. An invalid
+/// draft is answered with its validation errors and a request for a
+/// corrected plan, in the same conversation.
+///
+/// # Arguments
+///
+/// * `agent` - Agent to draft with
+/// * `request` - Initial request, from [`new_plan_request`] or
+///   [`refine_plan_request`]
+/// * `max_attempts` - Maximum number of drafts to request
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Config` if `max_attempts` is zero, the agent's
+/// error if execution fails, or `XzatomaError::InvalidPlan` with the last
+/// draft's problems when no draft is valid
+pub async fn draft_plan(
+    agent: &mut Agent,
+    request: String,
+    max_attempts: usize,
+) -> Result<DraftedPlan> {
+    if max_attempts == 0 {
+        return Err(XzatomaError::Config(
+            "--max-attempts must be at least 1".to_string(),
+        ));
+    }
+
+    let mut prompt = request;
+    let mut attempt = 1;
+    loop {
+        let response = agent.execute(prompt).await?;
+        let yaml = extract_plan_yaml(&response);
+        let error = match PlanParser::validate_yaml(&yaml) {
+            Ok(plan) => {
+                return Ok(DraftedPlan {
+                    plan,
+                    yaml: format!("{}\n", yaml.trim_end()),
+                    attempts: attempt,
+                })
+            }
+            Err(error) => error,
+        };
+
+        tracing::warn!(attempt, max_attempts, error = %error, "Drafted plan failed validation");
+        if attempt == max_attempts {
+            return Err(error.into());
+        }
+        prompt = format!(
+            "The plan you returned is not valid:\n{}\n\nFix these problems and reply with the complete corrected plan in a single ```yaml block.",
+            error
+        );
+        attempt += 1;
+    }
+}
+
+/// Builds the request for a new plan
+///
+/// # Arguments
+///
+/// * `goal` - What the plan should achieve
+/// * `constraints` - Optional constraints the plan must respect
+pub fn new_plan_request(goal: &str, constraints: Option<&str>) -> String {
+    let mut request = format!("Draft an XZatoma plan for this goal:\n{}\n", goal.trim());
+    if let Some(constraints) = constraints {
+        request.push_str(&format!("\nConstraints:\n{}\n", constraints.trim()));
+    }
+    request.push_str(
+        "\nExplore the workspace as needed, then reply with the plan in a single ```yaml block.",
+    );
+    request
+}
+
+/// Builds the request to edit an existing plan
+///
+/// # Arguments
+///
+/// * `current_yaml` - The plan to edit, as YAML
+/// * `instruction` - The change to make
+pub fn refine_plan_request(current_yaml: &str, instruction: &str) -> String {
+    format!(
+        "Here is the current XZatoma plan:\n```yaml\n{}\n```\n\nApply this change:\n{}\n\nReply with the complete updated plan in a single ```yaml block.",
+        current_yaml.trim_end(),
+        instruction.trim()
+    )
+}
+
+/// Returns the plan YAML from a model response
+///
+/// Uses the first fenced block tagged `yaml` or `yml`, then the first fenced
+/// block of any kind, and falls back to the whole response.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::commands::plan::extract_plan_yaml;
+///
+/// let response = "Here it is:\n```yaml\nname: Deploy\n```\nDone.";
+/// assert_eq!(extract_plan_yaml(response), "name: Deploy\n");
+/// assert_eq!(extract_plan_yaml("name: Deploy"), "name: Deploy");
+/// ```
+pub fn extract_plan_yaml(response: &str) -> String {
+    let blocks = extract_code_blocks_with(response, ExtractOptions::fenced_only());
+    blocks
+        .iter()
+        .find(|block| matches!(block.language.as_deref(), Some("yaml" | "yml")))
+        .or_else(|| blocks.first())
+        .map_or_else(
+            || response.trim().to_string(),
+            |block| block.content.clone(),
+        )
+}
+
+/// Creates an agent in Planning mode with read-only tools
+fn build_planning_agent(
+    config: &Config,
+    working_dir: Option<PathBuf>,
+    usage_ledger: Option<&Arc<UsageLedger>>,
+) -> Result<Agent> {
+    let working_dir = match working_dir {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    };
+    let tools =
+        ToolRegistryBuilder::new(ChatMode::Planning, SafetyMode::AlwaysConfirm, working_dir)
+            .with_tools_config(config.agent.tools.clone())
+            .with_network_policy(NetworkPolicy::from_config(config))
+            .with_read_only_policy(ReadOnlyPolicy::from_config(config))
+            .build_for_planning()?;
+
+    let (provider, _) = wrap_with_cache(
+        wrap_with_budget(
+            wrap_with_quirks(
+                create_provider(&config.provider.provider_type, &config.provider)?,
+                &config.provider.quirks,
+            ),
+            usage_ledger,
+        ),
+        &config.provider.provider_type,
+        &config.provider.cache,
+        &Paths::from_config(&config)?,
+    );
+    let mut agent =
+        Agent::new_from_shared_provider(Arc::from(provider), tools, config.agent.clone())?;
+    agent
+        .conversation_mut()
+        .add_system_message(generate_planning_prompt(SafetyMode::AlwaysConfirm));
+    agent.conversation_mut().add_system_message(format!(
+        "Plans you produce are XZatoma plan files. Reply with one ```yaml block containing a document that matches this JSON Schema. Step names must be unique and every step needs a concrete action.\n\n{}",
+        PLAN_SCHEMA
+    ));
+    Ok(agent)
+}
+
+/// Returns the plan at `path` as YAML, keeping the original text for YAML files
+fn current_plan_yaml(path: &Path) -> Result<String> {
+    let plan = PlanParser::from_file(path)?;
+    if is_yaml(path) {
+        Ok(std::fs::read_to_string(path)?)
+    } else {
+        Ok(serde_yaml::to_string(&plan)?)
+    }
+}
+
+/// Refined JSON and Markdown plans are written to a `.yaml` file next to them
+fn default_refine_output(path: &Path) -> PathBuf {
+    if is_yaml(path) {
+        path.to_path_buf()
+    } else {
+        path.with_extension("yaml")
+    }
+}
+
+fn is_yaml(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("yaml" | "yml")
+    )
+}
+
+/// Prints the preview and writes the plan once confirmed
+fn save_plan(drafted: &DraftedPlan, output: &Path, yes: bool) -> Result<()> {
+    ui_print!("{}", render_preview(drafted, output));
+    if !yes && !confirm(&format!("Write plan to {}?", output.display()))? {
+        ui_println!("Plan not written.");
+        return Ok(());
+    }
+
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(output, &drafted.yaml)?;
+    ui_println!("Wrote plan '{}' to {}", drafted.plan.name, output.display());
+    Ok(())
+}
+
+/// Renders the dry-run preview shown before the plan is written
+fn render_preview(drafted: &DraftedPlan, output: &Path) -> String {
+    let plan = &drafted.plan;
+    let mut preview = format!(
+        "Plan preview (nothing written yet)\n\nName: {}\n",
+        plan.name
+    );
+    if let Some(description) = &plan.description {
+        preview.push_str(&format!("Description: {}\n", description));
+    }
+    preview.push_str(&format!("Steps ({}):\n", plan.step_count()));
+    for (i, step) in plan.steps.iter().enumerate() {
+        preview.push_str(&format!("  {}. {}: {}\n", i + 1, step.name, step.action));
+    }
+    let note = if output.exists() {
+        " (overwrites existing file)"
+    } else {
+        ""
+    };
+    preview.push_str(&format!(
+        "\nValid after {} draft{}. Target: {}{}\n\n",
+        drafted.attempts,
+        if drafted.attempts == 1 { "" } else { "s" },
+        output.display(),
+        note
+    ));
+    preview
+}
+
+/// Asks a question on stderr and reads one line from stdin
+fn ask(question: &str, default: Option<&str>) -> Result<String> {
+    match default {
+        Some(default) => ui_eprint!("{} [{}]: ", question, default),
+        None => ui_eprint!("{}: ", question),
+    }
+    let _ = std::io::stderr().flush();
+
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    let answer = line.trim();
+    Ok(match default {
+        Some(default) if answer.is_empty() => default.to_string(),
+        _ => answer.to_string(),
+    })
+}
+
+/// Asks a yes/no question; anything but `y` or `yes` declines
+fn confirm(question: &str) -> Result<bool> {
+    let answer = ask(&format!("{} [y/N]", question), None)?.to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AgentConfig;
+    use crate::testing::MockProvider;
+    use crate::tools::ToolRegistry;
+    use tempfile::TempDir;
+
+    const INVALID_PLAN: &str = "Here is the plan:\n```yaml\nname: Release\nsteps:\n  - name: build\n  - name: build\n    action: cargo build\n```\n";
+    const VALID_PLAN: &str = "```yaml\nname: Release\nsteps:\n  - name: build\n    action: cargo build --release\n  - name: test\n    action: cargo test\n```";
+
+    fn canned_agent(responses: Vec<&'static str>) -> (Agent, MockProvider) {
+        let provider = responses
+            .into_iter()
+            .fold(MockProvider::new(), |provider, response| {
+                provider.then_text(response)
+            });
+        let agent = Agent::new(
+            provider.clone(),
+            ToolRegistry::new(),
+            AgentConfig::default(),
+        )
+        .unwrap();
+        (agent, provider)
+    }
+
+    /// The last user message of each request the provider received
+    fn user_prompts(provider: &MockProvider) -> Vec<String> {
+        provider
+            .requests()
+            .iter()
+            .map(|request| {
+                request
+                    .messages
+                    .iter()
+                    .rev()
+                    .find(|m| m.role == "user")
+                    .and_then(|m| m.content.clone())
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_draft_plan_retries_with_validation_errors() {
+        let (mut agent, provider) = canned_agent(vec![INVALID_PLAN, VALID_PLAN]);
+
+        let drafted = draft_plan(&mut agent, new_plan_request("Ship a release", None), 3)
+            .await
+            .unwrap();
+
+        assert_eq!(drafted.attempts, 2);
+        assert_eq!(drafted.plan.name, "Release");
+        assert_eq!(drafted.plan.steps.len(), 2);
+        assert!(drafted.yaml.starts_with("name: Release\n"));
+
+        let prompts = user_prompts(&provider);
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[0].contains("Ship a release"));
+        assert!(prompts[1].contains("missing field `action`"));
+        assert!(prompts[1].contains("Step name 'build' is already used by step 1"));
+    }
+
+    #[tokio::test]
+    async fn test_draft_plan_gives_up_after_max_attempts() {
+        let (mut agent, provider) = canned_agent(vec![INVALID_PLAN, INVALID_PLAN]);
+
+        let error = draft_plan(&mut agent, new_plan_request("Ship a release", None), 2)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, XzatomaError::InvalidPlan(_)));
+        assert!(error.to_string().contains("2 problems found"));
+        provider.assert_request_count(2);
+    }
+
+    #[tokio::test]
+    async fn test_draft_plan_rejects_zero_attempts() {
+        let (mut agent, provider) = canned_agent(vec![VALID_PLAN]);
+        let error = draft_plan(&mut agent, "draft".to_string(), 0)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, XzatomaError::Config(_)));
+        provider.assert_request_count(0);
+    }
+
+    #[test]
+    fn test_extract_plan_yaml_prefers_yaml_fence() {
+        let response = "```bash\nls\n```\n```yaml\nname: A\n```";
+        assert_eq!(extract_plan_yaml(response), "name: A\n");
+        assert_eq!(extract_plan_yaml("```\nname: B\n```"), "name: B\n");
+        assert_eq!(extract_plan_yaml("  name: C\n"), "name: C");
+    }
+
+    #[test]
+    fn test_refine_request_includes_plan_and_instruction() {
+        let request = refine_plan_request("name: A\n", "Add a lint step");
+        assert!(request.contains("```yaml\nname: A\n```"));
+        assert!(request.contains("Add a lint step"));
+    }
+
+    #[test]
+    fn test_current_plan_yaml_converts_json_and_keeps_yaml_text() {
+        let temp_dir = TempDir::new().unwrap();
+        let yaml_path = temp_dir.path().join("plan.yaml");
+        let yaml = "# release plan\nname: A\nsteps:\n  - name: s\n    action: run\n";
+        std::fs::write(&yaml_path, yaml).unwrap();
+        assert_eq!(current_plan_yaml(&yaml_path).unwrap(), yaml);
+        assert_eq!(default_refine_output(&yaml_path), yaml_path);
+
+        let json_path = temp_dir.path().join("plan.json");
+        std::fs::write(
+            &json_path,
+            r#"{"name":"A","steps":[{"name":"s","action":"run"}]}"#,
+        )
+        .unwrap();
+        let converted = current_plan_yaml(&json_path).unwrap();
+        assert!(PlanParser::validate_yaml(&converted).is_ok());
+        assert_eq!(
+            default_refine_output(&json_path),
+            temp_dir.path().join("plan.yaml")
+        );
+    }
+
+    #[test]
+    fn test_save_plan_with_yes_writes_yaml() {
+        let temp_dir = TempDir::new().unwrap();
+        let output = temp_dir.path().join("plans/release.yaml");
+        let yaml = "name: A\nsteps:\n  - name: s\n    action: run\n".to_string();
+        let drafted = DraftedPlan {
+            plan: PlanParser::validate_yaml(&yaml).unwrap(),
+            yaml: yaml.clone(),
+            attempts: 1,
+        };
+
+        let preview = render_preview(&drafted, &output);
+        assert!(preview.contains("1. s: run"));
+        assert!(!preview.contains("overwrites"));
+
+        save_plan(&drafted, &output, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), yaml);
+        assert!(render_preview(&drafted, &output).contains("overwrites existing file"));
+    }
+}
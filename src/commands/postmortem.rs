@@ -0,0 +1,606 @@
+//! Post-mortem reports for failed runs
+//!
+//! With `run.postmortem` set, or `xzatoma run --postmortem`, a failed run
+//! asks the provider for a report that can be pasted into an incident
+//! ticket. The request carries the task, how far each plan step got, the
+//! tool output of the failing step, and the final error; the reply has four
+//! sections: what was attempted, what failed, the likely cause, and
+//! suggested next steps. It is written to [`REPORT_FILE_NAME`] in the run's
+//! artifacts directory and printed after the failure summary.
+//!
+//! Failures of the provider itself, such as an expired token or an
+//! unreachable endpoint, get no report: the request for it would fail the
+//! same way. See [`skip_reason`].
+//!
+//! The request is kept under a byte limit. Tool output that does not fit is
+//! summarized by the [`OverflowSummarizer`], and cut to its last lines when
+//! the summary fails or is still too long.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::agent::step_policy::step_prompt_heading;
+use crate::error::{Result, XzatomaError};
+use crate::providers::{Message, Provider};
+use crate::tools::overflow_summary::{OverflowSummarizer, ToolOutputStore, DEFAULT_CHUNK_BYTES};
+use crate::tools::plan::Plan;
+
+/// Name of the report file in the run's artifacts directory
+pub const REPORT_FILE_NAME: &str = "postmortem.md";
+
+/// Default size limit of the post-mortem request, in bytes
+pub const DEFAULT_MAX_INPUT_BYTES: usize = 48 * 1024;
+
+/// Smallest share of the limit given to one tool output
+const MIN_TOOL_OUTPUT_BYTES: usize = 1024;
+
+/// Bytes reserved per tool output for the markers added around it
+const OUTPUT_OVERHEAD_BYTES: usize = 64;
+
+const POSTMORTEM_PROMPT: &str = "You write post-mortem reports for failed runs of a coding agent. \
+The reader was not watching the run and will paste the report into an incident ticket. \
+Use exactly these Markdown sections: '## What was attempted', '## What failed', '## Likely cause', and '## Suggested next steps'. \
+Base every statement on the run details given. Quote error messages and file paths verbatim. \
+When a detail is missing, say so instead of guessing. Keep next steps concrete and short. Reply with the report only.";
+
+/// How far a plan step got before the run failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepStatus {
+    /// The step finished and the next one started
+    Completed,
+    /// The run stopped during this step
+    Failed,
+    /// The run stopped before this step started
+    NotReached,
+    /// The plan ran as a single task, so steps were not tracked
+    Unknown,
+}
+
+impl StepStatus {
+    /// Returns the label used in the post-mortem request
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StepStatus::Completed => "completed",
+            StepStatus::Failed => "failed",
+            StepStatus::NotReached => "not reached",
+            StepStatus::Unknown => "not tracked",
+        }
+    }
+}
+
+/// One plan step and how far it got
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepOutcome {
+    /// Step name from the plan
+    pub name: String,
+    /// How far the step got
+    pub status: StepStatus,
+}
+
+/// Output of one tool call made by the failing step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolOutput {
+    /// Name of the tool, or `unknown` when the call was not found
+    pub tool: String,
+    /// Output the tool returned to the agent
+    pub output: String,
+}
+
+/// What a post-mortem is written from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FailureContext {
+    /// The prompt or plan instruction the run was given
+    pub task: String,
+    /// Plan steps in order, empty for a prompt run
+    pub steps: Vec<StepOutcome>,
+    /// Tool output of the failing step, oldest first
+    pub tool_outputs: Vec<ToolOutput>,
+    /// The error the run ended with
+    pub error: String,
+}
+
+impl FailureContext {
+    /// Collects the failure details of a run from its conversation
+    ///
+    /// Steps run one at a time are matched to their prompts in `messages`;
+    /// the last step that started is the failing one. Tool output is taken
+    /// from the messages after the last user message, which is the failing
+    /// step's prompt, or the task itself for a run without steps.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - The prompt or plan instruction of the run
+    /// * `plan` - The plan, `None` for a prompt run
+    /// * `stepwise` - Whether the plan's steps ran one at a time
+    /// * `messages` - The run's conversation
+    /// * `error` - The error the run ended with
+    pub fn from_run(
+        task: &str,
+        plan: Option<&Plan>,
+        stepwise: bool,
+        messages: &[Message],
+        error: &XzatomaError,
+    ) -> Self {
+        Self {
+            task: task.to_string(),
+            steps: plan
+                .map(|plan| step_outcomes(plan, stepwise, messages))
+                .unwrap_or_default(),
+            tool_outputs: failing_tool_outputs(messages),
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Returns why no post-mortem is written for `error`, or `None` when one is
+///
+/// Provider, authentication, and network failures are skipped, since the
+/// report would be requested from the same provider, and so are cancelled
+/// runs.
+pub fn skip_reason(error: &XzatomaError) -> Option<&'static str> {
+    match error {
+        XzatomaError::Cancelled => Some("the run was cancelled"),
+        XzatomaError::Auth { .. }
+        | XzatomaError::MissingCredentials(_)
+        | XzatomaError::Credentials(_)
+        | XzatomaError::CredentialBackendUnavailable { .. }
+        | XzatomaError::Keyring(_) => Some("the provider rejected the credentials"),
+        XzatomaError::Provider(_)
+        | XzatomaError::Http(_)
+        | XzatomaError::NetworkUnreachable { .. }
+        | XzatomaError::NetworkDisabled(_)
+        | XzatomaError::OllamaUnavailable { .. }
+        | XzatomaError::RateLimited { .. }
+        | XzatomaError::RequestTimeout { .. }
+        | XzatomaError::RequestQueueTimeout { .. }
+        | XzatomaError::RateLimitQueueTimeout { .. }
+        | XzatomaError::QuotaExceeded(_)
+        | XzatomaError::BudgetExceeded { .. }
+        | XzatomaError::StreamInterrupted(_)
+        | XzatomaError::StreamFailed { .. }
+        | XzatomaError::StreamingNotSupported
+        | XzatomaError::SseParseError(_)
+        | XzatomaError::InvalidResponseFormat(_)
+        | XzatomaError::MessageConversionError(_)
+        | XzatomaError::EndpointFallbackFailed
+        | XzatomaError::UnsupportedEndpoint(_, _)
+        | XzatomaError::ModelNotFound { .. }
+        | XzatomaError::ModelNotPulled { .. }
+        | XzatomaError::ContextOverflow { .. } => Some("the provider request failed"),
+        _ => None,
+    }
+}
+
+/// Asks the provider for post-mortem reports
+pub struct PostmortemGenerator {
+    provider: Arc<dyn Provider>,
+    summarizer: OverflowSummarizer,
+    max_input_bytes: usize,
+}
+
+impl PostmortemGenerator {
+    /// Creates a generator with [`DEFAULT_MAX_INPUT_BYTES`]
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - Provider that writes the report and the summaries
+    /// * `store` - Tool output store required by the summarizer
+    pub fn new(provider: Arc<dyn Provider>, store: ToolOutputStore) -> Self {
+        // The first and last chunks are enough: errors sit at the end of a log
+        let summarizer = OverflowSummarizer::new(Arc::clone(&provider), store)
+            .with_chunking(DEFAULT_CHUNK_BYTES, 2);
+        Self {
+            provider,
+            summarizer,
+            max_input_bytes: DEFAULT_MAX_INPUT_BYTES,
+        }
+    }
+
+    /// Sets the size limit of the post-mortem request, in bytes
+    pub fn with_max_input_bytes(mut self, max_input_bytes: usize) -> Self {
+        self.max_input_bytes = max_input_bytes.max(4 * MIN_TOOL_OUTPUT_BYTES);
+        self
+    }
+
+    /// Writes a post-mortem of the failure described by `context`
+    ///
+    /// # Errors
+    ///
+    /// Returns the provider error, or `XzatomaError::Provider` when the
+    /// model returns no text.
+    pub async fn generate(&self, context: &FailureContext) -> Result<String> {
+        let request = self.compose_request(context).await;
+        let response = self
+            .provider
+            .complete(
+                &[Message::system(POSTMORTEM_PROMPT), Message::user(request)],
+                &[],
+            )
+            .await?;
+        response
+            .message
+            .content
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty())
+            .ok_or_else(|| XzatomaError::Provider("The model returned no post-mortem".to_string()))
+    }
+
+    /// Builds the request text, fitting the tool output to the size limit
+    async fn compose_request(&self, context: &FailureContext) -> String {
+        let quarter = self.max_input_bytes / 4;
+        let mut request = format!(
+            "A run failed. Write its post-mortem.\n\n# Task\n\n{}\n\n# Final error\n\n{}\n\n# Steps\n\n",
+            keep_tail(context.task.trim(), quarter),
+            keep_tail(context.error.trim(), quarter)
+        );
+        if context.steps.is_empty() {
+            request.push_str("The run was given a prompt, not a plan.\n");
+        }
+        for (index, step) in context.steps.iter().enumerate() {
+            request.push_str(&format!(
+                "{}. {} ({})\n",
+                index + 1,
+                step.name,
+                step.status.as_str()
+            ));
+        }
+        request.push_str("\n# Tool output of the failing step\n\n");
+
+        let fitting =
+            (self.max_input_bytes.saturating_sub(request.len()) / MIN_TOOL_OUTPUT_BYTES).max(1);
+        let skipped = context.tool_outputs.len().saturating_sub(fitting);
+        let outputs = &context.tool_outputs[skipped..];
+        if outputs.is_empty() {
+            request.push_str("No tool output was captured for the failing step.\n");
+            return request;
+        }
+        if skipped > 0 {
+            request.push_str(&format!(
+                "({} earlier tool output{} left out.)\n\n",
+                skipped,
+                if skipped == 1 { " was" } else { "s were" }
+            ));
+        }
+        // Each output's share leaves room for its heading and cut marker
+        let share = (self.max_input_bytes.saturating_sub(request.len()) / outputs.len())
+            .saturating_sub(OUTPUT_OVERHEAD_BYTES);
+        for output in outputs {
+            let heading = format!("## {}\n\n", output.tool);
+            let text = self
+                .fit_output(output, share.saturating_sub(heading.len()))
+                .await;
+            request.push_str(&format!("{}{}\n\n", heading, text.trim_end()));
+        }
+        request
+    }
+
+    /// Returns `output` within `max_bytes`, summarized or cut when longer
+    async fn fit_output(&self, output: &ToolOutput, max_bytes: usize) -> String {
+        if output.output.len() <= max_bytes {
+            return output.output.clone();
+        }
+        match self
+            .summarizer
+            .summarize(&output.tool, &output.output)
+            .await
+        {
+            Ok((summary, _)) if summary.len() <= max_bytes => format!(
+                "[Summary of {} bytes of output]\n{}",
+                output.output.len(),
+                summary
+            ),
+            Ok(_) => keep_tail(&output.output, max_bytes),
+            Err(e) => {
+                tracing::warn!(
+                    "Could not summarize {} output for the post-mortem ({}); cutting it instead",
+                    output.tool,
+                    e
+                );
+                keep_tail(&output.output, max_bytes)
+            }
+        }
+    }
+}
+
+/// Writes `report` to [`REPORT_FILE_NAME`] in `dir`, creating the directory
+///
+/// # Errors
+///
+/// Returns an I/O error if the directory or the file cannot be written.
+pub fn write_report(dir: &Path, report: &str) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(REPORT_FILE_NAME);
+    std::fs::write(&path, format!("{}\n", report.trim_end()))?;
+    Ok(path)
+}
+
+/// Matches each plan step to its prompt in the conversation
+fn step_outcomes(plan: &Plan, stepwise: bool, messages: &[Message]) -> Vec<StepOutcome> {
+    if !stepwise {
+        return plan
+            .steps
+            .iter()
+            .map(|step| StepOutcome {
+                name: step.name.clone(),
+                status: StepStatus::Unknown,
+            })
+            .collect();
+    }
+    let started: Vec<bool> = (0..plan.steps.len())
+        .map(|index| {
+            let heading = step_prompt_heading(plan, index);
+            messages.iter().any(|message| {
+                message.role == "user"
+                    && message
+                        .content
+                        .as_deref()
+                        .is_some_and(|content| content.starts_with(&heading))
+            })
+        })
+        .collect();
+    let failing = started.iter().rposition(|started| *started);
+    plan.steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| StepOutcome {
+            name: step.name.clone(),
+            status: match failing {
+                Some(failing) if index < failing => StepStatus::Completed,
+                Some(failing) if index == failing => StepStatus::Failed,
+                _ => StepStatus::NotReached,
+            },
+        })
+        .collect()
+}
+
+/// Tool results after the last user message, named after their calls
+fn failing_tool_outputs(messages: &[Message]) -> Vec<ToolOutput> {
+    let start = messages
+        .iter()
+        .rposition(|message| message.role == "user")
+        .map_or(0, |index| index + 1);
+    let messages = &messages[start..];
+    let names: HashMap<&str, &str> = messages
+        .iter()
+        .flat_map(|message| message.tool_calls.iter().flatten())
+        .map(|call| (call.id.as_str(), call.function.name.as_str()))
+        .collect();
+    messages
+        .iter()
+        .filter(|message| message.role == "tool")
+        .map(|message| ToolOutput {
+            tool: message
+                .tool_call_id
+                .as_deref()
+                .and_then(|id| names.get(id))
+                .unwrap_or(&"unknown")
+                .to_string(),
+            output: message.content.clone().unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Keeps the last `max_bytes` of `text`, starting at a line when possible
+fn keep_tail(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let mut start = text.len() - max_bytes;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    if let Some(newline) = text[start..].find('\n') {
+        if newline + 1 < text.len() - start {
+            start += newline + 1;
+        }
+    }
+    format!("[... {} bytes left out]\n{}", start, &text[start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{CompletionResponse, FunctionCall, ToolCall};
+    use crate::testing::MockProvider;
+    use crate::tools::plan::PlanStep;
+    use tempfile::TempDir;
+
+    const CANNED_REPORT: &str = "## What was attempted\nDeploy.\n\n## What failed\nThe build.\n\n## Likely cause\nA missing import.\n\n## Suggested next steps\nAdd the import.";
+
+    const BUILD_SUMMARY: &str = "error[E0432]: unresolved import `crate::deploy` at src/main.rs:1";
+
+    /// Provider that answers the post-mortem request with a canned report
+    fn canned_report() -> MockProvider {
+        MockProvider::new().then_text(CANNED_REPORT)
+    }
+
+    /// User messages of the post-mortem requests the provider received
+    fn postmortem_requests(provider: &MockProvider) -> Vec<String> {
+        provider
+            .requests()
+            .iter()
+            .filter(|request| request.messages[0].content.as_deref() == Some(POSTMORTEM_PROMPT))
+            .map(|request| request.messages[1].content.clone().unwrap_or_default())
+            .collect()
+    }
+
+    fn generator(provider: &MockProvider, dir: &TempDir) -> PostmortemGenerator {
+        PostmortemGenerator::new(Arc::new(provider.clone()), ToolOutputStore::new(dir.path()))
+    }
+
+    fn plan() -> Plan {
+        let steps = ["build", "test", "ship"]
+            .iter()
+            .map(|name| PlanStep::new(name.to_string()).with_action(format!("run {}", name)))
+            .collect();
+        Plan::new("deploy".to_string(), steps)
+    }
+
+    fn terminal_call(id: &str) -> Message {
+        Message::assistant_with_tools(vec![ToolCall {
+            id: id.to_string(),
+            function: FunctionCall {
+                name: "terminal".to_string(),
+                arguments: "{}".to_string(),
+            },
+        }])
+    }
+
+    /// Conversation of a stepwise run that failed in its second step
+    fn failed_run(plan: &Plan, build_log: &str) -> Vec<Message> {
+        vec![
+            Message::user(format!("{}\n\nStep: build", step_prompt_heading(plan, 0))),
+            terminal_call("call-1"),
+            Message::tool_result("call-1", "built in 3s"),
+            Message::assistant("Built"),
+            Message::user(format!("{}\n\nStep: test", step_prompt_heading(plan, 1))),
+            terminal_call("call-2"),
+            Message::tool_result("call-2", build_log),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_report_is_written_from_the_failing_step() {
+        let dir = TempDir::new().unwrap();
+        let provider = canned_report();
+        let plan = plan();
+        let error = XzatomaError::TaskFailed("tests do not compile".to_string());
+        let context = FailureContext::from_run(
+            "Deploy the service",
+            Some(&plan),
+            true,
+            &failed_run(&plan, "error[E0432]: unresolved import"),
+            &error,
+        );
+
+        let statuses: Vec<StepStatus> = context.steps.iter().map(|step| step.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                StepStatus::Completed,
+                StepStatus::Failed,
+                StepStatus::NotReached
+            ]
+        );
+        assert_eq!(
+            context.tool_outputs,
+            [ToolOutput {
+                tool: "terminal".to_string(),
+                output: "error[E0432]: unresolved import".to_string(),
+            }]
+        );
+
+        let report = generator(&provider, &dir).generate(&context).await.unwrap();
+        let path = write_report(&dir.path().join("runs").join("run-1"), &report).unwrap();
+        assert_eq!(path.file_name().unwrap(), REPORT_FILE_NAME);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{}\n", CANNED_REPORT)
+        );
+
+        let requests = postmortem_requests(&provider);
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("Deploy the service"));
+        assert!(requests[0].contains("tests do not compile"));
+        assert!(
+            requests[0].contains("1. build (completed)\n2. test (failed)\n3. ship (not reached)")
+        );
+        assert!(requests[0].contains("## terminal\n\nerror[E0432]: unresolved import"));
+        assert!(!requests[0].contains("built in 3s"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_tool_output_and_steps_are_tolerated() {
+        let dir = TempDir::new().unwrap();
+        let provider = canned_report();
+        let error = XzatomaError::MaxIterationsExceeded {
+            limit: 5,
+            message: "stopped".to_string(),
+        };
+        let context = FailureContext::from_run("Fix the build", None, false, &[], &error);
+        assert!(context.steps.is_empty());
+        assert!(context.tool_outputs.is_empty());
+
+        let report = generator(&provider, &dir).generate(&context).await.unwrap();
+        assert_eq!(report, CANNED_REPORT);
+        let request = &postmortem_requests(&provider)[0];
+        assert!(request.contains("The run was given a prompt, not a plan."));
+        assert!(request.contains("No tool output was captured for the failing step."));
+
+        // A plan run as one task lists its steps without tracking them
+        let plan = plan();
+        let context = FailureContext::from_run("Deploy", Some(&plan), false, &[], &error);
+        assert!(context
+            .steps
+            .iter()
+            .all(|step| step.status == StepStatus::Unknown));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_tool_output_is_summarized_within_the_limit() {
+        let dir = TempDir::new().unwrap();
+        // Chunks of the oversized log are summarized before the report is
+        // requested
+        let provider = MockProvider::new()
+            .with_fallback(CompletionResponse::new(Message::assistant(BUILD_SUMMARY)));
+        let plan = plan();
+        let log = "   Compiling crate v0.1.0\n".repeat(2000);
+        let context = FailureContext::from_run(
+            "Deploy",
+            Some(&plan),
+            true,
+            &failed_run(&plan, &log),
+            &XzatomaError::TaskFailed("build failed".to_string()),
+        );
+
+        generator(&provider, &dir)
+            .with_max_input_bytes(8 * 1024)
+            .generate(&context)
+            .await
+            .unwrap();
+        let request = &postmortem_requests(&provider)[0];
+        assert!(request.len() <= 8 * 1024);
+        assert!(request.contains(&format!("[Summary of {} bytes of output]", log.len())));
+        assert!(request.contains("unresolved import `crate::deploy`"));
+    }
+
+    #[test]
+    fn test_provider_and_auth_failures_are_skipped() {
+        let skipped = [
+            XzatomaError::Auth {
+                provider: "copilot".to_string(),
+                reason: "token expired".to_string(),
+            },
+            XzatomaError::MissingCredentials("openai".to_string()),
+            XzatomaError::Provider("HTTP 500".to_string()),
+            XzatomaError::NetworkUnreachable {
+                endpoint: "http://localhost:11434".to_string(),
+                reason: "connection refused".to_string(),
+            },
+            XzatomaError::Cancelled,
+        ];
+        for error in &skipped {
+            assert!(skip_reason(error).is_some(), "{} was not skipped", error);
+        }
+
+        let reported = [
+            XzatomaError::TaskFailed("tests fail".to_string()),
+            XzatomaError::Tool("no such file".to_string()),
+            XzatomaError::ToolLoopDetected {
+                tool: "grep".to_string(),
+                repeats: 4,
+            },
+        ];
+        for error in &reported {
+            assert_eq!(skip_reason(error), None, "{} was skipped", error);
+        }
+    }
+
+    #[test]
+    fn test_keep_tail_keeps_the_last_lines() {
+        assert_eq!(keep_tail("short", 10), "short");
+        let cut = keep_tail("first line\nsecond line\nlast line", 15);
+        assert_eq!(cut, "[... 23 bytes left out]\nlast line");
+    }
+}
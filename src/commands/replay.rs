@@ -1,12 +1,24 @@
 //! Replay subagent conversations for debugging and analysis
 //!
 //! This module provides commands to list, replay, and visualize
-//! conversation history stored by the persistence system.
+//! conversation history stored by the persistence system. It can also re-run
+//! the user turns of a stored chat conversation against the current
+//! configuration and compare the new responses with the recorded ones.
 
-use crate::agent::ConversationStore;
-use crate::error::Result;
+use crate::agent::{Agent, ConversationStore};
+use crate::config::Config;
+use crate::error::{Result, XzatomaError};
+use crate::paths::Paths;
+use crate::providers::{Message, TokenUsage};
+use crate::storage::SqliteStorage;
+use crate::tools::{ToolExecutor, ToolRegistry, ToolResult};
+use crate::ui::Glyph;
+use crate::{ui_eprintln, ui_print, ui_println};
+use async_trait::async_trait;
 use clap::Args;
+use std::collections::{HashMap, VecDeque};
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 /// Arguments for the replay command
 ///
@@ -22,9 +34,10 @@ pub struct ReplayArgs {
     #[arg(long, short = 'l')]
     pub list: bool,
 
-    /// Path to conversation database
-    #[arg(long, default_value = "~/.xzatoma/conversations.db")]
-    pub db_path: PathBuf,
+    /// Path to conversation database; defaults to
+    /// `agent.subagent.persistence.database`
+    #[arg(long)]
+    pub db_path: Option<PathBuf>,
 
     /// Limit for list results
     #[arg(long, default_value = "10")]
@@ -37,6 +50,26 @@ pub struct ReplayArgs {
     /// Show conversation tree (with nested subagents)
     #[arg(long, short = 't')]
     pub tree: bool,
+
+    /// Stored chat conversation to re-run against the current configuration
+    #[arg(value_name = "CONVERSATION_ID")]
+    pub conversation_id: Option<String>,
+
+    /// Provider to use for the re-run instead of the configured one
+    #[arg(long)]
+    pub provider: Option<String>,
+
+    /// Model to use for the re-run instead of the configured one
+    #[arg(long)]
+    pub model: Option<String>,
+
+    /// Show a unified diff of each original and new assistant response
+    #[arg(long)]
+    pub diff: bool,
+
+    /// Execute tools during the re-run instead of returning recorded results
+    #[arg(long)]
+    pub allow_tools: bool,
 }
 
 /// Run the replay command
@@ -59,23 +92,40 @@ pub struct ReplayArgs {
 /// let args = replay::ReplayArgs {
 ///     id: None,
 ///     list: true,
-///     db_path: PathBuf::from("~/.xzatoma/conversations.db"),
+///     db_path: Some(PathBuf::from("~/.xzatoma/conversations.db")),
 ///     limit: 10,
 ///     offset: 0,
 ///     tree: false,
+///     conversation_id: None,
+///     provider: None,
+///     model: None,
+///     diff: false,
+///     allow_tools: false,
 /// };
 /// assert!(args.list);
 /// ```
-pub async fn run_replay(args: ReplayArgs) -> Result<()> {
+pub async fn run_replay(args: ReplayArgs, config: &Config) -> Result<()> {
+    if let Some(conversation_id) = &args.conversation_id {
+        return rerun_conversation(config, conversation_id, &args).await;
+    }
+
+    let db_path = match args.db_path {
+        Some(path) => path,
+        None => match config.agent.subagent.conversation_database() {
+            Some(path) => path,
+            None => SqliteStorage::default_database_path(&Paths::from_config(config)?),
+        },
+    };
+
     // Expand tilde in path
-    let db_path = if args.db_path.to_string_lossy().starts_with('~') {
+    let db_path = if db_path.to_string_lossy().starts_with('~') {
         let home = std::env::var("HOME")
             .ok()
             .map(PathBuf::from)
             .unwrap_or_else(|| PathBuf::from("."));
-        home.join(args.db_path.to_string_lossy().trim_start_matches("~/"))
+        home.join(db_path.to_string_lossy().trim_start_matches("~/"))
     } else {
-        args.db_path
+        db_path
     };
 
     let store = ConversationStore::new(&db_path)?;
@@ -89,7 +139,7 @@ pub async fn run_replay(args: ReplayArgs) -> Result<()> {
             replay_conversation(&store, &id)?;
         }
     } else {
-        eprintln!("Error: Must specify --list or --id");
+        ui_eprintln!("Error: Must specify --list, --id, or a conversation ID to re-run");
         std::process::exit(1);
     }
 
@@ -110,20 +160,20 @@ pub async fn run_replay(args: ReplayArgs) -> Result<()> {
 fn list_conversations(store: &ConversationStore, limit: usize, offset: usize) -> Result<()> {
     let records = store.list(limit, offset)?;
 
-    println!("Conversations (showing {} starting at {}):", limit, offset);
-    println!();
+    ui_println!("Conversations (showing {} starting at {}):", limit, offset);
+    ui_println!();
 
     for record in records {
-        println!("ID:     {}", record.id);
-        println!("Label:  {}", record.label);
-        println!("Depth:  {}", record.depth);
-        println!("Status: {}", record.metadata.completion_status);
-        println!("Turns:  {}", record.metadata.turns_used);
-        println!("Start:  {}", record.started_at);
+        ui_println!("ID:     {}", record.id);
+        ui_println!("Label:  {}", record.label);
+        ui_println!("Depth:  {}", record.depth);
+        ui_println!("Status: {}", record.metadata.completion_status);
+        ui_println!("Turns:  {}", record.metadata.turns_used);
+        ui_println!("Start:  {}", record.started_at);
         if let Some(parent_id) = &record.parent_id {
-            println!("Parent: {}", parent_id);
+            ui_println!("Parent: {}", parent_id);
         }
-        println!();
+        ui_println!();
     }
 
     Ok(())
@@ -142,41 +192,41 @@ fn list_conversations(store: &ConversationStore, limit: usize, offset: usize) ->
 fn replay_conversation(store: &ConversationStore, id: &str) -> Result<()> {
     match store.get(id)? {
         Some(record) => {
-            println!("=== Conversation {} ===", record.id);
-            println!("Label: {}", record.label);
-            println!("Depth: {}", record.depth);
-            println!("Started: {}", record.started_at);
+            ui_println!("=== Conversation {} ===", record.id);
+            ui_println!("Label: {}", record.label);
+            ui_println!("Depth: {}", record.depth);
+            ui_println!("Started: {}", record.started_at);
             if let Some(completed_at) = &record.completed_at {
-                println!("Completed: {}", completed_at);
+                ui_println!("Completed: {}", completed_at);
             }
-            println!();
-            println!("Task: {}", record.metadata.task_prompt);
-            println!();
-            println!("=== Messages ===");
-            println!();
+            ui_println!();
+            ui_println!("Task: {}", record.metadata.task_prompt);
+            ui_println!();
+            ui_println!("=== Messages ===");
+            ui_println!();
 
             for (i, message) in record.messages.iter().enumerate() {
-                println!("--- Message {} ({}) ---", i + 1, message.role);
+                ui_println!("--- Message {} ({}) ---", i + 1, message.role);
                 if let Some(content) = &message.content {
-                    println!("{}", content);
+                    ui_println!("{}", content);
                 } else {
-                    println!("(no content)");
+                    ui_println!("(no content)");
                 }
                 if let Some(tool_calls) = &message.tool_calls {
-                    println!("Tool calls: {:?}", tool_calls);
+                    ui_println!("Tool calls: {:?}", tool_calls);
                 }
-                println!();
+                ui_println!();
             }
 
-            println!("=== Metadata ===");
-            println!("Turns Used: {}", record.metadata.turns_used);
-            println!("Tokens Consumed: {}", record.metadata.tokens_consumed);
-            println!("Status: {}", record.metadata.completion_status);
-            println!("Max Turns Reached: {}", record.metadata.max_turns_reached);
-            println!("Allowed Tools: {:?}", record.metadata.allowed_tools);
+            ui_println!("=== Metadata ===");
+            ui_println!("Turns Used: {}", record.metadata.turns_used);
+            ui_println!("Tokens Consumed: {}", record.metadata.tokens_consumed);
+            ui_println!("Status: {}", record.metadata.completion_status);
+            ui_println!("Max Turns Reached: {}", record.metadata.max_turns_reached);
+            ui_println!("Allowed Tools: {:?}", record.metadata.allowed_tools);
         }
         None => {
-            eprintln!("Error: Conversation {} not found", id);
+            ui_eprintln!("Error: Conversation {} not found", id);
             std::process::exit(1);
         }
     }
@@ -195,7 +245,7 @@ fn replay_conversation(store: &ConversationStore, id: &str) -> Result<()> {
 ///
 /// Returns Ok(()) on success
 fn show_conversation_tree(store: &ConversationStore, id: &str) -> Result<()> {
-    println!("Conversation tree:");
+    ui_println!("Conversation tree:");
     print_tree(store, id, 0)?;
     Ok(())
 }
@@ -217,9 +267,14 @@ fn print_tree(store: &ConversationStore, id: &str, indent: usize) -> Result<()>
     })?;
 
     let prefix = "  ".repeat(indent);
-    println!(
-        "{}├─ {} [{}] (depth={}, turns={})",
-        prefix, record.id, record.label, record.depth, record.metadata.turns_used
+    ui_println!(
+        "{}{} {} [{}] (depth={}, turns={})",
+        prefix,
+        Glyph::Branch,
+        record.id,
+        record.label,
+        record.depth,
+        record.metadata.turns_used
     );
 
     let children = store.find_by_parent(id)?;
@@ -230,19 +285,620 @@ fn print_tree(store: &ConversationStore, id: &str, indent: usize) -> Result<()>
     Ok(())
 }
 
+/// One user turn of a stored conversation and what the original run produced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedTurn {
+    /// The user prompt that started the turn
+    pub prompt: String,
+    /// The last non-empty assistant text of the turn
+    pub response: String,
+    /// Number of tool calls the assistant made during the turn
+    pub tool_calls: usize,
+    /// Session working directory the prompt was sent from, when recorded
+    pub cwd: Option<PathBuf>,
+}
+
+/// Split a stored conversation into its user turns
+///
+/// Messages before the first user message, such as system prompts, are
+/// skipped.
+///
+/// # Arguments
+///
+/// * `messages` - Messages of the stored conversation
+///
+/// # Returns
+///
+/// Returns one turn per user message, in order
+pub fn extract_recorded_turns(messages: &[Message]) -> Vec<RecordedTurn> {
+    extract_recorded_turns_with_cwds(messages, &[])
+}
+
+/// Split a stored conversation into its user turns with their directories
+///
+/// Same as [`extract_recorded_turns`], with each turn carrying the session
+/// working directory recorded for its user message.
+///
+/// # Arguments
+///
+/// * `messages` - Messages of the stored conversation
+/// * `cwds` - Recorded working directory of each message, in order
+///
+/// # Returns
+///
+/// Returns one turn per user message, in order
+pub fn extract_recorded_turns_with_cwds(
+    messages: &[Message],
+    cwds: &[Option<PathBuf>],
+) -> Vec<RecordedTurn> {
+    let mut turns: Vec<RecordedTurn> = Vec::new();
+
+    for (index, message) in messages.iter().enumerate() {
+        match message.role.as_str() {
+            "user" => turns.push(RecordedTurn {
+                prompt: message.content.clone().unwrap_or_default(),
+                response: String::new(),
+                tool_calls: 0,
+                cwd: cwds.get(index).cloned().flatten(),
+            }),
+            "assistant" => {
+                if let Some(turn) = turns.last_mut() {
+                    turn.tool_calls += message.tool_calls.as_ref().map_or(0, Vec::len);
+                    if let Some(content) = message.content.as_deref().filter(|c| !c.is_empty()) {
+                        turn.response = content.to_string();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    turns
+}
+
+/// A tool result recorded in a stored conversation
+#[derive(Debug)]
+struct RecordedToolResult {
+    arguments: Option<serde_json::Value>,
+    output: String,
+}
+
+/// Tool results recorded in a stored conversation, keyed by tool name
+///
+/// Replayed tool calls get new IDs, so results are matched by tool name and
+/// arguments. A call with arguments that were never recorded takes the
+/// oldest unused result for that tool.
+#[derive(Debug, Default)]
+pub struct RecordedToolResults {
+    by_tool: HashMap<String, VecDeque<RecordedToolResult>>,
+}
+
+impl RecordedToolResults {
+    /// Collect the tool results of a stored conversation
+    ///
+    /// Each tool message is paired with the assistant tool call that has the
+    /// same ID. Tool messages without a matching call are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - Messages of the stored conversation
+    ///
+    /// # Returns
+    ///
+    /// Returns the recorded results in conversation order
+    pub fn from_messages(messages: &[Message]) -> Self {
+        let mut calls: HashMap<&str, (&str, &str)> = HashMap::new();
+        let mut by_tool: HashMap<String, VecDeque<RecordedToolResult>> = HashMap::new();
+
+        for message in messages {
+            if let Some(tool_calls) = &message.tool_calls {
+                for call in tool_calls {
+                    calls.insert(
+                        call.id.as_str(),
+                        (
+                            call.function.name.as_str(),
+                            call.function.arguments.as_str(),
+                        ),
+                    );
+                }
+            }
+
+            if message.role != "tool" {
+                continue;
+            }
+            if let Some((name, arguments)) =
+                message.tool_call_id.as_deref().and_then(|id| calls.get(id))
+            {
+                by_tool
+                    .entry(name.to_string())
+                    .or_default()
+                    .push_back(RecordedToolResult {
+                        arguments: serde_json::from_str(arguments).ok(),
+                        output: message.content.clone().unwrap_or_default(),
+                    });
+            }
+        }
+
+        Self { by_tool }
+    }
+
+    /// Names of the tools that have recorded results
+    pub fn tool_names(&self) -> Vec<String> {
+        self.by_tool.keys().cloned().collect()
+    }
+
+    /// Number of unused recorded results for a tool
+    pub fn remaining(&self, name: &str) -> usize {
+        self.by_tool.get(name).map_or(0, VecDeque::len)
+    }
+
+    /// Take the recorded result for a tool call
+    ///
+    /// Only a recorded call with the same arguments matches. A result
+    /// recorded for other arguments is never handed out, since the model
+    /// would be given the output of a different call.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Tool name
+    /// * `arguments` - Arguments of the replayed call
+    ///
+    /// # Returns
+    ///
+    /// Returns the recorded output, or `None` when no unused result with
+    /// these arguments is left
+    pub fn take(&mut self, name: &str, arguments: &serde_json::Value) -> Option<String> {
+        let results = self.by_tool.get_mut(name)?;
+        let index = results
+            .iter()
+            .position(|r| r.arguments.as_ref() == Some(arguments))?;
+        results.remove(index).map(|r| r.output)
+    }
+}
+
+/// Tool stand-in that answers calls with results from the original conversation
+struct RecordedToolStub {
+    name: String,
+    definition: serde_json::Value,
+    results: Arc<Mutex<RecordedToolResults>>,
+}
+
+#[async_trait]
+impl ToolExecutor for RecordedToolStub {
+    fn tool_definition(&self) -> serde_json::Value {
+        self.definition.clone()
+    }
+
+    async fn execute(&self, args: serde_json::Value) -> Result<ToolResult> {
+        let mut results = self.results.lock().unwrap_or_else(|e| e.into_inner());
+        let recorded = results.take(&self.name, &args);
+
+        Ok(match recorded {
+            Some(output) => ToolResult::success(output),
+            None if results.remaining(&self.name) > 0 => ToolResult::error(format!(
+                "Replay diverged: the original conversation never called '{}' with these arguments; re-run with --allow-tools to execute tools",
+                self.name
+            )),
+            None => ToolResult::error(format!(
+                "No recorded result for tool '{}' in the original conversation; re-run with --allow-tools to execute tools",
+                self.name
+            )),
+        })
+    }
+}
+
+/// Build a registry that answers every tool call from recorded results
+///
+/// Each tool in `live` is replaced by a stub with the same definition, so the
+/// model sees the same tools as a normal run. Tools that only appear in the
+/// recording get a stub with an open parameter schema.
+///
+/// # Arguments
+///
+/// * `live` - Registry built from the current configuration
+/// * `recorded` - Tool results from the original conversation
+///
+/// # Returns
+///
+/// Returns a registry whose tools never touch the workspace
+pub fn build_recorded_tool_registry(
+    live: &ToolRegistry,
+    recorded: RecordedToolResults,
+) -> ToolRegistry {
+    let mut names = live.tool_names();
+    for name in recorded.tool_names() {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    let results = Arc::new(Mutex::new(recorded));
+    let mut registry = ToolRegistry::new();
+    for name in names {
+        let definition = live
+            .get(&name)
+            .map(|tool| tool.tool_definition())
+            .unwrap_or_else(|| {
+                serde_json::json!({
+                    "name": name,
+                    "description": "Tool recorded in the original conversation",
+                    "parameters": {"type": "object", "properties": {}}
+                })
+            });
+        registry.register(
+            name.clone(),
+            Arc::new(RecordedToolStub {
+                name,
+                definition,
+                results: Arc::clone(&results),
+            }),
+        );
+    }
+
+    registry
+}
+
+/// Comparison of one recorded turn with its re-run
+#[derive(Debug, Clone)]
+pub struct TurnComparison {
+    /// The turn as recorded in the original conversation
+    pub original: RecordedTurn,
+    /// The final response of the re-run
+    pub response: String,
+    /// Number of tool calls the re-run made
+    pub tool_calls: usize,
+    /// Tokens used by the re-run of this turn
+    pub usage: TokenUsage,
+    /// Error message when the re-run of this turn failed
+    pub error: Option<String>,
+}
+
+/// Result of re-running a stored conversation
+#[derive(Debug, Clone)]
+pub struct ReplayReport {
+    /// ID of the conversation that was replayed
+    pub original_id: String,
+    /// ID of the new conversation recorded by the replay
+    pub replay_id: String,
+    /// Per-turn comparisons, in order
+    pub turns: Vec<TurnComparison>,
+}
+
+/// Re-run recorded user turns through an agent
+///
+/// Turns run in order on the same agent, so later turns see the new
+/// responses rather than the recorded ones. A failed turn is reported and the
+/// next turn still runs.
+///
+/// # Arguments
+///
+/// * `agent` - Fresh agent built from the current configuration
+/// * `turns` - Turns extracted from the original conversation
+///
+/// # Returns
+///
+/// Returns one comparison per turn
+pub async fn replay_turns(agent: &mut Agent, turns: &[RecordedTurn]) -> Vec<TurnComparison> {
+    let mut comparisons = Vec::with_capacity(turns.len());
+
+    for turn in turns {
+        // Record the turn under the directory the original prompt was sent from
+        if turn.cwd.is_some() {
+            agent.conversation_mut().set_cwd(turn.cwd.clone());
+        }
+        let start = agent.conversation().messages().len();
+        let usage_before = agent.get_token_usage().unwrap_or_default();
+
+        let outcome = agent.execute(turn.prompt.clone()).await;
+
+        let messages = agent.conversation().messages();
+        let tool_calls = messages
+            .get(start..)
+            .unwrap_or(messages)
+            .iter()
+            .filter_map(|m| m.tool_calls.as_ref())
+            .map(Vec::len)
+            .sum();
+        let usage_after = agent.get_token_usage().unwrap_or_default();
+        let usage = TokenUsage::new(
+            usage_after
+                .prompt_tokens
+                .saturating_sub(usage_before.prompt_tokens),
+            usage_after
+                .completion_tokens
+                .saturating_sub(usage_before.completion_tokens),
+        );
+
+        let (response, error) = match outcome {
+            Ok(response) => (response, None),
+            Err(e) => (String::new(), Some(e.to_string())),
+        };
+
+        comparisons.push(TurnComparison {
+            original: turn.clone(),
+            response,
+            tool_calls,
+            usage,
+            error,
+        });
+    }
+
+    comparisons
+}
+
+/// Re-run recorded turns and save the result as a linked conversation
+///
+/// # Arguments
+///
+/// * `storage` - Conversation history storage
+/// * `original_id` - Full ID of the replayed conversation
+/// * `original_title` - Title of the replayed conversation
+/// * `turns` - Turns extracted from the original conversation
+/// * `agent` - Fresh agent built from the current configuration
+///
+/// # Returns
+///
+/// Returns the per-turn comparison and the ID of the new conversation
+///
+/// # Errors
+///
+/// Returns an error if the new conversation cannot be saved
+pub async fn replay_and_record(
+    storage: &SqliteStorage,
+    original_id: &str,
+    original_title: &str,
+    turns: &[RecordedTurn],
+    agent: &mut Agent,
+) -> Result<ReplayReport> {
+    let comparisons = replay_turns(agent, turns).await;
+
+    let conversation = agent.conversation();
+    let replay_id = conversation.id().to_string();
+    let model = agent.provider().get_current_model();
+    let model = (model != "none").then_some(model);
+    storage.save_conversation(
+        &replay_id,
+        &format!("Replay of {}", original_title),
+        model.as_deref(),
+        conversation.messages(),
+    )?;
+    storage.set_message_cwds(&replay_id, conversation.message_cwds())?;
+    storage.set_replayed_from(&replay_id, original_id)?;
+
+    Ok(ReplayReport {
+        original_id: original_id.to_string(),
+        replay_id,
+        turns: comparisons,
+    })
+}
+
+/// Unified diff of an original and a replayed assistant response
+///
+/// # Arguments
+///
+/// * `original` - Response recorded in the original conversation
+/// * `replay` - Response produced by the re-run
+///
+/// # Returns
+///
+/// Returns the diff text, which is empty when the responses are identical
+pub fn response_diff(original: &str, replay: &str) -> String {
+    similar::TextDiff::from_lines(original, replay)
+        .unified_diff()
+        .header("original", "replay")
+        .to_string()
+}
+
+/// Re-run a stored chat conversation against the current configuration
+///
+/// # Arguments
+///
+/// * `config` - Loaded configuration
+/// * `conversation_id` - Full ID or prefix of the stored conversation
+/// * `args` - Parsed replay arguments with overrides and output options
+///
+/// # Errors
+///
+/// Returns an error if the conversation cannot be found, has no user turns,
+/// or the agent cannot be built
+async fn rerun_conversation(
+    config: &Config,
+    conversation_id: &str,
+    args: &ReplayArgs,
+) -> Result<()> {
+    let storage = SqliteStorage::new(&Paths::from_config(config)?)?;
+    let original_id = storage
+        .resolve_conversation_id(conversation_id)?
+        .ok_or_else(|| {
+            XzatomaError::Storage(format!("Conversation {} not found", conversation_id))
+        })?;
+    let (title, _model, messages) = storage.load_conversation(&original_id)?.ok_or_else(|| {
+        XzatomaError::Storage(format!("Conversation {} not found", conversation_id))
+    })?;
+
+    let cwds = storage.load_message_cwds(&original_id)?;
+    let turns = extract_recorded_turns_with_cwds(&messages, &cwds);
+    if turns.is_empty() {
+        return Err(XzatomaError::Storage(format!(
+            "Conversation {} has no user messages to replay",
+            original_id
+        )));
+    }
+
+    let provider_type = args
+        .provider
+        .clone()
+        .unwrap_or_else(|| config.provider.provider_type.clone());
+    let provider: Arc<dyn crate::providers::Provider> =
+        Arc::from(crate::providers::create_provider_with_override(
+            &config.provider,
+            args.provider.as_deref(),
+            args.model.as_deref(),
+        )?);
+
+    // Tools run where the first turn was sent from, when that directory
+    // was recorded and still exists
+    let working_dir = match turns[0].cwd.as_deref() {
+        Some(cwd) if cwd.is_dir() => cwd.to_path_buf(),
+        _ => std::env::current_dir()?,
+    };
+    let env = super::build_agent_environment(config, &working_dir, true).await?;
+    // Keep MCP connections alive while tools may call back into them
+    let _mcp_manager = env.mcp_manager;
+
+    let tools = if args.allow_tools {
+        env.tool_registry
+    } else {
+        build_recorded_tool_registry(
+            &env.tool_registry,
+            RecordedToolResults::from_messages(&messages),
+        )
+    };
+
+    let mut agent =
+        Agent::new_from_shared_provider(Arc::clone(&provider), tools, config.agent.clone())?;
+    agent.conversation_mut().set_cwd(Some(working_dir));
+    if let Some(disclosure) = &env.skill_disclosure {
+        agent
+            .conversation_mut()
+            .add_system_message(disclosure.clone());
+    }
+    let prompt_style =
+        crate::prompts::detect_prompt_style(&config.provider, &provider_type, provider.as_ref())
+            .await;
+    let mut system_messages = vec![crate::prompts::build_system_prompt(
+        env.chat_mode,
+        env.safety_mode,
+        prompt_style,
+    )];
+    if let Some(active_skill_prompt) =
+        super::build_active_skill_prompt_injection(&env.active_skill_registry)?
+    {
+        system_messages.push(active_skill_prompt);
+    }
+    agent.set_transient_system_messages(system_messages);
+
+    ui_println!(
+        "Replaying {} user turn(s) from {} with {} ({})",
+        turns.len(),
+        original_id,
+        provider.get_current_model(),
+        if args.allow_tools {
+            "tools enabled"
+        } else {
+            "recorded tool results"
+        }
+    );
+    ui_println!();
+
+    let report = replay_and_record(&storage, &original_id, &title, &turns, &mut agent).await?;
+    print_replay_report(&report, args.diff);
+
+    Ok(())
+}
+
+/// Print the per-turn comparison of a replay
+///
+/// # Arguments
+///
+/// * `report` - Replay result
+/// * `show_diff` - Whether to print a unified diff of each response
+fn print_replay_report(report: &ReplayReport, show_diff: bool) {
+    use colored::Colorize;
+    use prettytable::{format, Table};
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+    table.add_row(prettytable::row![
+        "Turn".bold(),
+        "Prompt".bold(),
+        format!("Response chars (old {} new)", Glyph::Arrow).bold(),
+        format!("Tool calls (old {} new)", Glyph::Arrow).bold(),
+        "Tokens (prompt/completion)".bold(),
+        "Status".bold()
+    ]);
+
+    for (index, turn) in report.turns.iter().enumerate() {
+        let prompt: String = turn.original.prompt.chars().take(40).collect();
+        let prompt = if turn.original.prompt.chars().count() > 40 {
+            format!("{}...", prompt)
+        } else {
+            prompt
+        };
+        let status = match &turn.error {
+            Some(_) => "failed".red(),
+            None => "ok".green(),
+        };
+
+        table.add_row(prettytable::row![
+            index + 1,
+            prompt,
+            format!(
+                "{} {} {}",
+                turn.original.response.chars().count(),
+                Glyph::Arrow,
+                turn.response.chars().count()
+            ),
+            format!(
+                "{} {} {}",
+                turn.original.tool_calls,
+                Glyph::Arrow,
+                turn.tool_calls
+            ),
+            format!(
+                "{}/{}",
+                turn.usage.prompt_tokens, turn.usage.completion_tokens
+            ),
+            status
+        ]);
+    }
+
+    table.printstd();
+
+    for (index, turn) in report.turns.iter().enumerate() {
+        if let Some(error) = &turn.error {
+            ui_eprintln!("{}", format!("Turn {} failed: {}", index + 1, error).red());
+        }
+        if show_diff {
+            ui_println!();
+            ui_println!("=== Turn {} ===", index + 1);
+            let diff = response_diff(&turn.original.response, &turn.response);
+            if diff.is_empty() {
+                ui_println!("(responses are identical)");
+            } else {
+                ui_print!("{}", diff);
+            }
+        }
+    }
+
+    ui_println!();
+    ui_println!(
+        "Recorded replay as {} (replay of {})",
+        report.replay_id.cyan(),
+        report.original_id
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::providers::CompletionResponse;
+    use crate::testing::MockProvider;
 
     #[test]
     fn test_replay_args_list_defaults() {
         let args = ReplayArgs {
             id: None,
             list: true,
-            db_path: PathBuf::from("test.db"),
+            db_path: Some(PathBuf::from("test.db")),
             limit: 10,
             offset: 0,
             tree: false,
+            conversation_id: None,
+            provider: None,
+            model: None,
+            diff: false,
+            allow_tools: false,
         };
         assert!(args.list);
         assert!(args.id.is_none());
@@ -254,10 +910,15 @@ mod tests {
         let args = ReplayArgs {
             id: Some("test_id".to_string()),
             list: false,
-            db_path: PathBuf::from("test.db"),
+            db_path: Some(PathBuf::from("test.db")),
             limit: 10,
             offset: 0,
             tree: false,
+            conversation_id: None,
+            provider: None,
+            model: None,
+            diff: false,
+            allow_tools: false,
         };
         assert!(!args.list);
         assert_eq!(args.id, Some("test_id".to_string()));
@@ -269,10 +930,15 @@ mod tests {
         let args = ReplayArgs {
             id: Some("test_id".to_string()),
             list: false,
-            db_path: PathBuf::from("test.db"),
+            db_path: Some(PathBuf::from("test.db")),
             limit: 10,
             offset: 0,
             tree: true,
+            conversation_id: None,
+            provider: None,
+            model: None,
+            diff: false,
+            allow_tools: false,
         };
         assert!(args.tree);
         assert_eq!(args.id, Some("test_id".to_string()));
@@ -283,12 +949,227 @@ mod tests {
         let args = ReplayArgs {
             id: None,
             list: true,
-            db_path: PathBuf::from("test.db"),
+            db_path: Some(PathBuf::from("test.db")),
             limit: 20,
             offset: 5,
             tree: false,
+            conversation_id: None,
+            provider: None,
+            model: None,
+            diff: false,
+            allow_tools: false,
         };
         assert_eq!(args.limit, 20);
         assert_eq!(args.offset, 5);
     }
+
+    fn read_file_call(id: &str, path: &str) -> Message {
+        Message::assistant_with_tools(vec![crate::providers::ToolCall {
+            id: id.to_string(),
+            function: crate::providers::FunctionCall {
+                name: "read_file".to_string(),
+                arguments: serde_json::json!({ "path": path }).to_string(),
+            },
+        }])
+    }
+
+    fn recorded_conversation() -> Vec<Message> {
+        vec![
+            Message::system("system prompt"),
+            Message::user("Summarize a.txt"),
+            read_file_call("call_old", "a.txt"),
+            Message::tool_result("call_old", "recorded contents of a.txt"),
+            Message::assistant("a.txt says hello"),
+            Message::user("Thanks"),
+            Message::assistant("You're welcome"),
+        ]
+    }
+
+    #[test]
+    fn test_extract_recorded_turns_groups_by_user_message() {
+        let turns = extract_recorded_turns(&recorded_conversation());
+
+        assert_eq!(
+            turns,
+            vec![
+                RecordedTurn {
+                    prompt: "Summarize a.txt".to_string(),
+                    response: "a.txt says hello".to_string(),
+                    tool_calls: 1,
+                    cwd: None,
+                },
+                RecordedTurn {
+                    prompt: "Thanks".to_string(),
+                    response: "You're welcome".to_string(),
+                    tool_calls: 0,
+                    cwd: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_recorded_turns_carries_message_cwds() {
+        let billing = PathBuf::from("/repo/services/billing");
+        let mut cwds = vec![None; 5];
+        cwds[1] = Some(PathBuf::from("/repo"));
+        cwds.push(Some(billing.clone()));
+
+        let turns = extract_recorded_turns_with_cwds(&recorded_conversation(), &cwds);
+
+        assert_eq!(turns[0].cwd, Some(PathBuf::from("/repo")));
+        assert_eq!(turns[1].cwd, Some(billing));
+    }
+
+    #[test]
+    fn test_recorded_tool_results_match_arguments_only() {
+        let messages = vec![
+            read_file_call("call_1", "a.txt"),
+            Message::tool_result("call_1", "contents of a"),
+            read_file_call("call_2", "b.txt"),
+            Message::tool_result("call_2", "contents of b"),
+            Message::tool_result("orphan", "ignored"),
+        ];
+        let mut recorded = RecordedToolResults::from_messages(&messages);
+
+        assert_eq!(
+            recorded.take("read_file", &serde_json::json!({ "path": "b.txt" })),
+            Some("contents of b".to_string())
+        );
+        assert_eq!(
+            recorded.take("read_file", &serde_json::json!({ "path": "other.txt" })),
+            None
+        );
+        assert_eq!(recorded.remaining("read_file"), 1);
+        assert_eq!(
+            recorded.take("read_file", &serde_json::json!({ "path": "a.txt" })),
+            Some("contents of a".to_string())
+        );
+        assert_eq!(
+            recorded.take("read_file", &serde_json::json!({ "path": "a.txt" })),
+            None
+        );
+        assert_eq!(recorded.take("terminal", &serde_json::json!({})), None);
+    }
+
+    #[tokio::test]
+    async fn test_replay_uses_recorded_tool_results_and_links_conversations() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SqliteStorage::new_with_path(dir.path().join("history.db")).unwrap();
+        let original = recorded_conversation();
+        storage
+            .save_conversation("original-id", "Original", Some("old-model"), &original)
+            .unwrap();
+
+        let provider = [
+            read_file_call("call_new", "a.txt"),
+            Message::assistant("a.txt says hello again"),
+            Message::assistant("Any time"),
+        ]
+        .into_iter()
+        .fold(
+            MockProvider::new().with_model("scripted"),
+            |provider, message| {
+                provider.then_response(CompletionResponse::with_usage(
+                    message,
+                    TokenUsage::new(10, 5),
+                ))
+            },
+        );
+        let tools = build_recorded_tool_registry(
+            &ToolRegistry::new(),
+            RecordedToolResults::from_messages(&original),
+        );
+        let mut agent = Agent::new(
+            provider.clone(),
+            tools,
+            crate::config::AgentConfig::default(),
+        )
+        .unwrap();
+
+        let turns = extract_recorded_turns(&original);
+        let report = replay_and_record(&storage, "original-id", "Original", &turns, &mut agent)
+            .await
+            .unwrap();
+
+        assert_eq!(report.turns.len(), 2);
+        assert_eq!(report.turns[0].response, "a.txt says hello again");
+        assert_eq!(report.turns[0].tool_calls, 1);
+        assert_eq!(report.turns[0].usage.prompt_tokens, 20);
+        assert_eq!(report.turns[1].response, "Any time");
+        assert_eq!(report.turns[1].tool_calls, 0);
+        assert!(report.turns.iter().all(|t| t.error.is_none()));
+
+        // The second request carries the recorded tool output, not a live read
+        let requests = provider.requests();
+        let tool_message = requests[1]
+            .messages
+            .iter()
+            .find(|m| m.role == "tool")
+            .expect("tool result sent to provider");
+        assert_eq!(tool_message.tool_call_id.as_deref(), Some("call_new"));
+        assert_eq!(
+            tool_message.content.as_deref(),
+            Some("recorded contents of a.txt")
+        );
+
+        assert_ne!(report.replay_id, "original-id");
+        assert_eq!(
+            storage.load_replayed_from(&report.replay_id).unwrap(),
+            Some("original-id".to_string())
+        );
+        let (title, model, messages) = storage
+            .load_conversation(&report.replay_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(title, "Replay of Original");
+        assert_eq!(model.as_deref(), Some("scripted"));
+        assert!(messages
+            .iter()
+            .any(|m| m.content.as_deref() == Some("Any time")));
+    }
+
+    #[tokio::test]
+    async fn test_recorded_tool_stub_reports_missing_result() {
+        let tools =
+            build_recorded_tool_registry(&ToolRegistry::new(), RecordedToolResults::default());
+        assert!(tools.is_empty());
+
+        let tools = build_recorded_tool_registry(
+            &ToolRegistry::new(),
+            RecordedToolResults::from_messages(&recorded_conversation()),
+        );
+        let stub = tools.get("read_file").expect("stub registered");
+        assert_eq!(stub.tool_definition()["name"], "read_file");
+
+        // A call the original conversation never made is not answered with
+        // another call's output
+        let result = stub
+            .execute(serde_json::json!({ "path": "other.txt" }))
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.to_message().contains("Replay diverged"));
+
+        stub.execute(serde_json::json!({ "path": "a.txt" }))
+            .await
+            .unwrap();
+        let result = stub
+            .execute(serde_json::json!({ "path": "a.txt" }))
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.to_message().contains("--allow-tools"));
+    }
+
+    #[test]
+    fn test_response_diff_is_unified_and_empty_when_identical() {
+        assert!(response_diff("same\n", "same\n").is_empty());
+
+        let diff = response_diff("old line\n", "new line\n");
+        assert!(diff.contains("--- original"));
+        assert!(diff.contains("+++ replay"));
+        assert!(diff.contains("-old line"));
+        assert!(diff.contains("+new line"));
+    }
 }
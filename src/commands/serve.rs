@@ -0,0 +1,63 @@
+//! Handler for `xzatoma serve`
+//!
+//! Resolves the default workspace and hands stdin and stdout to
+//! [`crate::serve::run_stdio_server`]. Nothing else may write to stdout
+//! while the server runs, since it carries the protocol stream.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use xzatoma::commands::serve::handle_serve;
+//! use xzatoma::Config;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! handle_serve(true, None, Config::default()).await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::error::{Result, XzatomaError};
+use crate::serve::run_stdio_server;
+
+/// Handles the `xzatoma serve` command
+///
+/// # Arguments
+///
+/// * `stdio` - Whether to serve over stdin and stdout; the only transport
+/// * `working_dir` - Workspace for sessions that do not name one; defaults
+///   to the current directory
+/// * `config` - Loaded XZatoma configuration
+///
+/// # Errors
+///
+/// Returns an error if no transport was selected, the working directory is
+/// not a directory, or stdin or stdout fails.
+pub async fn handle_serve(stdio: bool, working_dir: Option<PathBuf>, config: Config) -> Result<()> {
+    if !stdio {
+        return Err(XzatomaError::Config(
+            "xzatoma serve needs a transport; pass --stdio".to_string(),
+        ));
+    }
+    let working_dir = match working_dir {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    };
+    let working_dir = working_dir.canonicalize().map_err(|e| {
+        XzatomaError::Config(format!(
+            "Cannot use {} as the working directory: {}",
+            working_dir.display(),
+            e
+        ))
+    })?;
+    if !working_dir.is_dir() {
+        return Err(XzatomaError::Config(format!(
+            "{} is not a directory",
+            working_dir.display()
+        )));
+    }
+
+    tracing::info!(working_dir = %working_dir.display(), "Starting JSON-RPC serve mode on stdio");
+    run_stdio_server(config, working_dir).await
+}
@@ -0,0 +1,650 @@
+//! First-run setup
+//!
+//! When no configuration file exists anywhere on the search path, `xzatoma
+//! chat` and `xzatoma run` on a terminal offer a short guided setup instead
+//! of falling back to the defaults. It picks a provider, preferring a local
+//! Ollama server when one answers, signs in to Copilot through the usual
+//! device flow, picks a model, and sets the default chat and safety modes.
+//! The answers are written to the user configuration file (see
+//! [`crate::paths::user_config_file`]) and the requested command continues
+//! with it.
+//!
+//! The setup never runs with `--no-setup`, with `--config`, with JSON
+//! output, or when stdin is not a terminal.
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use async_trait::async_trait;
+use serde_yaml::{Mapping, Value};
+
+use crate::chat_mode::{ChatMode, SafetyMode};
+use crate::cli::{Cli, Commands};
+use crate::config::{Config, OllamaConfig, DEFAULT_CONFIG_PATH};
+use crate::error::{Result, XzatomaError};
+use crate::providers::{CopilotProvider, OllamaProvider, Provider};
+
+/// Header written above the generated configuration
+const CONFIG_HEADER: &str = "# Written by xzatoma's first-run setup.\n\
+# See docs/reference/configuration.md for every setting.\n";
+
+/// Answers collected by the setup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetupChoices {
+    /// Provider type: `ollama` or `copilot`
+    pub provider: String,
+    /// Model for the chosen provider
+    pub model: String,
+    /// Ollama host, written only for the Ollama provider
+    pub ollama_host: Option<String>,
+    /// Default chat mode, written as `agent.chat.default_mode`
+    pub chat_mode: ChatMode,
+    /// Default safety mode, written as `agent.chat.default_safety`
+    pub safety: SafetyMode,
+}
+
+impl SetupChoices {
+    /// Renders the choices as a configuration file
+    ///
+    /// Only the chosen settings are written; everything else keeps its
+    /// default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::chat_mode::{ChatMode, SafetyMode};
+    /// use xzatoma::commands::setup::SetupChoices;
+    ///
+    /// let choices = SetupChoices {
+    ///     provider: "copilot".to_string(),
+    ///     model: "gpt-5-mini".to_string(),
+    ///     ollama_host: None,
+    ///     chat_mode: ChatMode::Planning,
+    ///     safety: SafetyMode::AlwaysConfirm,
+    /// };
+    /// let yaml = choices.to_yaml().unwrap();
+    /// assert!(yaml.contains("type: copilot"));
+    /// assert!(yaml.contains("default_safety: confirm"));
+    /// ```
+    pub fn to_yaml(&self) -> Result<String> {
+        let mut provider_settings = vec![("model", Value::from(self.model.as_str()))];
+        if let Some(host) = &self.ollama_host {
+            provider_settings.insert(0, ("host", Value::from(host.as_str())));
+        }
+
+        let document = mapping(vec![
+            (
+                "provider",
+                mapping(vec![
+                    ("type", Value::from(self.provider.as_str())),
+                    (self.provider.as_str(), mapping(provider_settings)),
+                ]),
+            ),
+            (
+                "agent",
+                mapping(vec![(
+                    "chat",
+                    mapping(vec![
+                        ("default_mode", Value::from(chat_mode_name(self.chat_mode))),
+                        ("default_safety", Value::from(safety_name(self.safety))),
+                    ]),
+                )]),
+            ),
+        ]);
+        let body = serde_yaml::to_string(&document)
+            .map_err(|e| XzatomaError::Config(format!("Failed to write config: {}", e)))?;
+        Ok(format!("{}{}", CONFIG_HEADER, body))
+    }
+}
+
+/// Probes and sign-ins the setup needs, replaceable in tests
+#[async_trait]
+pub trait SetupBackend {
+    /// Models installed on the Ollama server at `host`
+    ///
+    /// Returns `None` when no Ollama server answers there.
+    async fn ollama_models(&self, host: &str) -> Option<Vec<String>>;
+
+    /// Runs the Copilot device flow and lists the models the account can use
+    async fn copilot_login(&self) -> Result<Vec<String>>;
+}
+
+/// Backend that talks to the real Ollama server and Copilot
+pub struct LiveSetupBackend {
+    config: Config,
+}
+
+impl LiveSetupBackend {
+    /// Creates a backend using the provider settings in `config`
+    pub fn new(config: Config) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl SetupBackend for LiveSetupBackend {
+    async fn ollama_models(&self, host: &str) -> Option<Vec<String>> {
+        let provider = OllamaProvider::new(OllamaConfig {
+            host: host.to_string(),
+            ..self.config.provider.ollama.clone()
+        })
+        .ok()?;
+        provider.health_check().await.ok()?;
+        let models = provider.list_models().await.unwrap_or_default();
+        Some(models.into_iter().map(|model| model.name).collect())
+    }
+
+    async fn copilot_login(&self) -> Result<Vec<String>> {
+        super::auth::authenticate(self.config.clone(), "copilot".to_string()).await?;
+        let provider = CopilotProvider::new(self.config.provider.copilot.clone())?;
+        let models = provider.list_models().await?;
+        Ok(models.into_iter().map(|model| model.name).collect())
+    }
+}
+
+/// Returns true when the setup may be offered for this invocation
+///
+/// The caller still has to check that no configuration file was found.
+///
+/// # Arguments
+///
+/// * `cli` - Parsed command line
+/// * `interactive` - Whether stdin is a terminal
+pub fn offers_setup(cli: &Cli, interactive: bool) -> bool {
+    let runs_agent = matches!(
+        cli.command,
+        Commands::Chat { .. }
+            | Commands::Run {
+                validate_only: false,
+                dry_run: false,
+                json: false,
+                command: None,
+                ..
+            }
+    );
+    interactive
+        && runs_agent
+        && !cli.no_setup
+        && cli.config.as_deref().unwrap_or(DEFAULT_CONFIG_PATH) == DEFAULT_CONFIG_PATH
+}
+
+/// Runs the setup on the terminal and writes the user configuration file
+///
+/// # Arguments
+///
+/// * `cli` - Parsed command line; its overrides apply to the probes
+/// * `path` - The user configuration file to write
+///
+/// # Returns
+///
+/// Returns true when the file was written, false when the user declined
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written.
+pub async fn first_run_setup(cli: &Cli, path: &Path) -> Result<bool> {
+    let base = Config::load_without_file(cli);
+    let backend = LiveSetupBackend::new(base.clone());
+    let mut input = BufReader::new(std::io::stdin());
+    let mut output = std::io::stderr();
+
+    let choices = match run_setup(&backend, &base, &mut input, &mut output).await {
+        Ok(Some(choices)) => choices,
+        Ok(None) | Err(XzatomaError::Cancelled) => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    write_config(path, &choices)?;
+    let _ = writeln!(
+        output,
+        "Wrote {}. Edit it or run `xzatoma doctor` to check it.\n",
+        path.display()
+    );
+    Ok(true)
+}
+
+/// Asks the setup questions
+///
+/// # Arguments
+///
+/// * `backend` - Ollama probe and Copilot sign-in
+/// * `base` - Defaults, environment, and CLI overrides; supplies the Ollama
+///   host and the default models
+/// * `input` - Where answers are read from
+/// * `output` - Where questions are written
+///
+/// # Returns
+///
+/// Returns the choices, or `None` when the user declined the setup
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Cancelled` when input ends before the last answer.
+pub async fn run_setup(
+    backend: &dyn SetupBackend,
+    base: &Config,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<Option<SetupChoices>> {
+    writeln!(output, "No XZatoma configuration file was found.")?;
+    let start = choose(
+        input,
+        output,
+        "Guided setup",
+        &[
+            ("yes", "Answer a few questions and save them".to_string()),
+            (
+                "no",
+                "Use the built-in defaults (pass --no-setup to stop asking)".to_string(),
+            ),
+        ],
+        0,
+    )?;
+    if start == 1 {
+        return Ok(None);
+    }
+
+    let host = base.provider.ollama.host.clone();
+    writeln!(output, "Looking for Ollama at {}...", host)?;
+    let ollama_models = backend.ollama_models(&host).await;
+    let ollama_label = match &ollama_models {
+        Some(models) => format!(
+            "Local models, running at {} ({} installed)",
+            host,
+            models.len()
+        ),
+        None => format!("Local models; not running at {}", host),
+    };
+    let copilot_label = "GitHub Copilot; signs in with your GitHub account".to_string();
+    let providers = if ollama_models.is_some() {
+        [("ollama", ollama_label), ("copilot", copilot_label)]
+    } else {
+        [("copilot", copilot_label), ("ollama", ollama_label)]
+    };
+    let provider = providers[choose(input, output, "Provider", &providers, 0)?].0;
+
+    let (model, ollama_host) = if provider == "ollama" {
+        let installed = ollama_models.unwrap_or_default();
+        if installed.is_empty() {
+            writeln!(
+                output,
+                "No models found. Pull one with `ollama pull <model>` before chatting."
+            )?;
+        }
+        let model = choose_model(input, output, &installed, &base.provider.ollama.model)?;
+        (model, Some(host))
+    } else {
+        writeln!(output, "Signing in to GitHub Copilot...")?;
+        let available = match backend.copilot_login().await {
+            Ok(models) => models,
+            Err(e) => {
+                writeln!(
+                    output,
+                    "Copilot sign-in failed: {}. Run `xzatoma auth --provider copilot` later.",
+                    e
+                )?;
+                Vec::new()
+            }
+        };
+        let model = choose_model(input, output, &available, &base.provider.copilot.model)?;
+        (model, None)
+    };
+
+    let chat_modes = [ChatMode::Planning, ChatMode::Write];
+    let chat_mode = chat_modes[choose(
+        input,
+        output,
+        "Default chat mode",
+        &chat_modes.map(|mode| (chat_mode_name(mode), mode.description().to_string())),
+        0,
+    )?];
+
+    let safety_modes = [
+        SafetyMode::AlwaysConfirm,
+        SafetyMode::ConfirmOnce,
+        SafetyMode::NeverConfirm,
+    ];
+    let safety = safety_modes[choose(
+        input,
+        output,
+        "Dangerous operations",
+        &safety_modes.map(|mode| (safety_name(mode), mode.description().to_string())),
+        0,
+    )?];
+
+    Ok(Some(SetupChoices {
+        provider: provider.to_string(),
+        model,
+        ollama_host,
+        chat_mode,
+        safety,
+    }))
+}
+
+/// Writes the choices to `path`, creating its directory
+///
+/// # Errors
+///
+/// Returns an error if `path` already exists or cannot be written.
+pub fn write_config(path: &Path, choices: &SetupChoices) -> Result<()> {
+    if path.exists() {
+        return Err(XzatomaError::Config(format!(
+            "{} already exists; not overwriting it",
+            path.display()
+        )));
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, choices.to_yaml()?)?;
+    Ok(())
+}
+
+/// Asks for a model, offering `available` by number when there are any
+fn choose_model(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    available: &[String],
+    default: &str,
+) -> Result<String> {
+    writeln!(output, "\nModel:")?;
+    if available.is_empty() {
+        let answer = ask(input, output, &format!("Name [{}]: ", default))?;
+        return Ok(if answer.is_empty() {
+            default.to_string()
+        } else {
+            answer
+        });
+    }
+
+    let default_index = available
+        .iter()
+        .position(|model| model == default)
+        .unwrap_or(0);
+    for (index, model) in available.iter().enumerate() {
+        writeln!(output, "{:>3}) {}", index + 1, model)?;
+    }
+    loop {
+        let answer = ask(
+            input,
+            output,
+            &format!("Choice [{}]: ", available[default_index]),
+        )?;
+        if answer.is_empty() {
+            return Ok(available[default_index].clone());
+        }
+        match answer.parse::<usize>() {
+            Ok(number) => match number.checked_sub(1).and_then(|i| available.get(i)) {
+                Some(model) => return Ok(model.clone()),
+                None => writeln!(output, "Choose a number between 1 and {}.", available.len())?,
+            },
+            // Models that are not installed yet can be named directly
+            Err(_) => return Ok(answer),
+        }
+    }
+}
+
+/// Asks one multiple-choice question and returns the chosen index
+///
+/// Accepts the option number, its name, or an unambiguous abbreviation
+/// such as `y`; an empty answer picks `default`.
+fn choose(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    question: &str,
+    options: &[(&str, String)],
+    default: usize,
+) -> Result<usize> {
+    writeln!(output, "\n{}:", question)?;
+    for (index, (name, description)) in options.iter().enumerate() {
+        writeln!(output, "{:>3}) {:<13} {}", index + 1, name, description)?;
+    }
+    loop {
+        let answer = ask(input, output, &format!("Choice [{}]: ", options[default].0))?;
+        if answer.is_empty() {
+            return Ok(default);
+        }
+        let by_number = answer
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| number.checked_sub(1))
+            .filter(|index| *index < options.len());
+        match by_number.or_else(|| option_by_name(options, &answer)) {
+            Some(index) => return Ok(index),
+            None => writeln!(output, "Unknown answer '{}'.", answer)?,
+        }
+    }
+}
+
+/// Finds the option named `answer`, or the only one it abbreviates
+fn option_by_name(options: &[(&str, String)], answer: &str) -> Option<usize> {
+    let answer = answer.to_lowercase();
+    if let Some(index) = options.iter().position(|(name, _)| *name == answer) {
+        return Some(index);
+    }
+    let mut prefixed = options
+        .iter()
+        .enumerate()
+        .filter(|(_, (name, _))| name.starts_with(&answer));
+    match (prefixed.next(), prefixed.next()) {
+        (Some((index, _)), None) => Some(index),
+        _ => None,
+    }
+}
+
+/// Writes `prompt` and reads one trimmed line
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Cancelled` at the end of input.
+fn ask(input: &mut dyn BufRead, output: &mut dyn Write, prompt: &str) -> Result<String> {
+    write!(output, "{}", prompt)?;
+    output.flush()?;
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Err(XzatomaError::Cancelled);
+    }
+    Ok(line.trim().to_string())
+}
+
+fn chat_mode_name(mode: ChatMode) -> &'static str {
+    match mode {
+        ChatMode::Planning => "planning",
+        ChatMode::Write => "write",
+    }
+}
+
+fn safety_name(mode: SafetyMode) -> &'static str {
+    match mode {
+        SafetyMode::AlwaysConfirm => "confirm",
+        SafetyMode::ConfirmOnce => "confirm_once",
+        SafetyMode::NeverConfirm => "yolo",
+    }
+}
+
+fn mapping(entries: Vec<(&str, Value)>) -> Value {
+    Value::Mapping(
+        entries
+            .into_iter()
+            .map(|(key, value)| (Value::from(key), value))
+            .collect::<Mapping>(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    struct FakeBackend {
+        ollama: Option<Vec<String>>,
+        copilot: std::result::Result<Vec<String>, String>,
+    }
+
+    #[async_trait]
+    impl SetupBackend for FakeBackend {
+        async fn ollama_models(&self, _host: &str) -> Option<Vec<String>> {
+            self.ollama.clone()
+        }
+
+        async fn copilot_login(&self) -> Result<Vec<String>> {
+            self.copilot.clone().map_err(XzatomaError::Provider)
+        }
+    }
+
+    fn ollama_running(models: &[&str]) -> FakeBackend {
+        FakeBackend {
+            ollama: Some(models.iter().map(|model| model.to_string()).collect()),
+            copilot: Err("not expected".to_string()),
+        }
+    }
+
+    fn ollama_stopped(copilot: std::result::Result<Vec<String>, String>) -> FakeBackend {
+        FakeBackend {
+            ollama: None,
+            copilot,
+        }
+    }
+
+    /// Runs the setup with `answers`, writes the result, and loads it back
+    async fn setup_with(backend: &FakeBackend, answers: &str) -> (Option<Config>, String) {
+        let mut output = Vec::new();
+        let choices = run_setup(
+            backend,
+            &Config::default(),
+            &mut answers.as_bytes(),
+            &mut output,
+        )
+        .await
+        .unwrap();
+        let transcript = String::from_utf8(output).unwrap();
+        let Some(choices) = choices else {
+            return (None, transcript);
+        };
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("xzatoma").join("config.yaml");
+        write_config(&path, &choices).unwrap();
+        let config = Config::load(path.to_str().unwrap(), &Cli::default()).unwrap();
+        config.validate().unwrap();
+        (Some(config), transcript)
+    }
+
+    #[tokio::test]
+    async fn test_setup_prefers_running_ollama_and_lists_its_models() {
+        let backend = ollama_running(&["qwen3:8b", "llama3.2:latest"]);
+
+        let (config, transcript) = setup_with(&backend, "\n\n1\nwrite\n2\n").await;
+
+        let config = config.unwrap();
+        assert_eq!(config.provider.provider_type, "ollama");
+        assert_eq!(config.provider.ollama.model, "qwen3:8b");
+        assert_eq!(config.provider.ollama.host, "http://localhost:11434");
+        assert_eq!(config.agent.chat.default_mode, "write");
+        assert_eq!(config.agent.chat.default_safety, "confirm_once");
+        assert!(transcript.contains("2 installed"));
+        assert!(transcript.contains("llama3.2:latest"));
+    }
+
+    #[tokio::test]
+    async fn test_setup_offers_copilot_first_without_ollama() {
+        let backend = ollama_stopped(Ok(vec!["gpt-5-mini".to_string(), "gpt-4.1".to_string()]));
+
+        let (config, transcript) = setup_with(&backend, "y\n\ngpt-4.1\n\n\n").await;
+
+        let config = config.unwrap();
+        assert_eq!(config.provider.provider_type, "copilot");
+        assert_eq!(config.provider.copilot.model, "gpt-4.1");
+        assert_eq!(config.agent.chat.default_mode, "planning");
+        assert_eq!(config.agent.chat.default_safety, "confirm");
+        assert!(transcript.contains("not running at http://localhost:11434"));
+        assert!(transcript.contains("Signing in to GitHub Copilot"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_copilot_sign_in_still_asks_for_a_model() {
+        let backend = ollama_stopped(Err("device flow expired".to_string()));
+
+        let (config, transcript) = setup_with(&backend, "\ncopilot\n\n\n3\n").await;
+
+        let config = config.unwrap();
+        assert_eq!(config.provider.copilot.model, "gpt-5-mini");
+        assert_eq!(config.agent.chat.default_safety, "yolo");
+        assert!(transcript.contains("device flow expired"));
+        assert!(transcript.contains("xzatoma auth --provider copilot"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_answers_are_asked_again() {
+        let backend = ollama_running(&["qwen3:8b"]);
+
+        let (config, transcript) = setup_with(&backend, "\nmaybe\n2\n\n\n\n").await;
+
+        assert_eq!(config.unwrap().provider.provider_type, "copilot");
+        assert!(transcript.contains("Unknown answer 'maybe'."));
+    }
+
+    #[tokio::test]
+    async fn test_declining_setup_writes_nothing() {
+        let (config, _) = setup_with(&ollama_running(&[]), "n\n").await;
+        assert!(config.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_end_of_input_cancels_setup() {
+        let mut output = Vec::new();
+        let result = run_setup(
+            &ollama_running(&[]),
+            &Config::default(),
+            &mut "\n".as_bytes(),
+            &mut output,
+        )
+        .await;
+        assert!(matches!(result, Err(XzatomaError::Cancelled)));
+    }
+
+    #[test]
+    fn test_write_config_never_overwrites() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(&path, "provider:\n  type: ollama\n").unwrap();
+        let choices = SetupChoices {
+            provider: "copilot".to_string(),
+            model: "gpt-5-mini".to_string(),
+            ollama_host: None,
+            chat_mode: ChatMode::Planning,
+            safety: SafetyMode::AlwaysConfirm,
+        };
+
+        assert!(write_config(&path, &choices).is_err());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "provider:\n  type: ollama\n"
+        );
+    }
+
+    #[test]
+    fn test_offers_setup_only_for_interactive_agent_commands() {
+        let parse = |args: &[&str]| Cli::try_parse_from(args).unwrap();
+
+        assert!(offers_setup(&parse(&["xzatoma", "chat"]), true));
+        assert!(offers_setup(
+            &parse(&["xzatoma", "run", "--prompt", "hi"]),
+            true
+        ));
+        assert!(!offers_setup(&parse(&["xzatoma", "chat"]), false));
+        assert!(!offers_setup(
+            &parse(&["xzatoma", "chat", "--no-setup"]),
+            true
+        ));
+        assert!(!offers_setup(
+            &parse(&["xzatoma", "--config", "other.yaml", "chat"]),
+            true
+        ));
+        assert!(!offers_setup(
+            &parse(&["xzatoma", "run", "--prompt", "hi", "--json"]),
+            true
+        ));
+        assert!(!offers_setup(&parse(&["xzatoma", "models", "list"]), true));
+    }
+}
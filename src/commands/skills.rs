@@ -9,6 +9,7 @@ use crate::skills::trust::{
     filter_visible_skill_records, load_trust_store, load_trusted_paths, resolve_trust_store_path,
 };
 use crate::skills::{discover_skills, SkillCatalog};
+use crate::ui_println;
 use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
 
@@ -40,13 +41,13 @@ pub fn list_skills(config: Config) -> Result<()> {
     let visible_catalog = build_visible_skill_catalog(&config, &working_dir, &trusted_paths)?;
 
     if visible_catalog.is_empty() {
-        println!("No valid visible skills found.");
+        ui_println!("No valid visible skills found.");
         return Ok(());
     }
 
     for name in visible_catalog.names() {
         if let Some(record) = visible_catalog.get(name) {
-            println!(
+            ui_println!(
                 "{}\t{}\t{}",
                 record.metadata.name,
                 record.metadata.description,
@@ -87,12 +88,12 @@ pub fn validate_skills(config: Config) -> Result<()> {
     let visible_catalog = build_visible_skill_catalog(&config, &working_dir, &trusted_paths)?;
 
     if visible_catalog.is_empty() {
-        println!("No valid visible skills found.");
+        ui_println!("No valid visible skills found.");
     } else {
-        println!("Valid visible skills:");
+        ui_println!("Valid visible skills:");
         for name in visible_catalog.names() {
             if let Some(record) = visible_catalog.get(name) {
-                println!(
+                ui_println!(
                     "- {}\n  description: {}\n  location: {}\n  scope: {}",
                     record.metadata.name,
                     record.metadata.description,
@@ -104,9 +105,9 @@ pub fn validate_skills(config: Config) -> Result<()> {
     }
 
     if !discovery.invalid_diagnostics.is_empty() {
-        println!("\nInvalid skill diagnostics:");
+        ui_println!("\nInvalid skill diagnostics:");
         for diagnostic in &discovery.invalid_diagnostics {
-            println!(
+            ui_println!(
                 "- [{}] {} ({})",
                 diagnostic.code(),
                 diagnostic.message,
@@ -116,7 +117,7 @@ pub fn validate_skills(config: Config) -> Result<()> {
     }
 
     if !discovery.shadowed_diagnostics.is_empty() {
-        println!("\nShadowed skill diagnostics:");
+        ui_println!("\nShadowed skill diagnostics:");
         for diagnostic in &discovery.shadowed_diagnostics {
             let shadowed_by = diagnostic
                 .overshadowed_by
@@ -124,7 +125,7 @@ pub fn validate_skills(config: Config) -> Result<()> {
                 .map(|path| path.display().to_string())
                 .unwrap_or_else(|| "<unknown>".to_string());
 
-            println!(
+            ui_println!(
                 "- [{}] {} ({}) shadowed_by={}",
                 diagnostic.code(),
                 diagnostic.message,
@@ -169,31 +170,31 @@ pub fn show_skill(config: Config, name: &str) -> Result<()> {
         ))
     })?;
 
-    println!("name: {}", record.metadata.name);
-    println!("description: {}", record.metadata.description);
-    println!("scope: {}", record.source_scope.as_str());
-    println!("skill_dir: {}", record.skill_dir.display());
-    println!("skill_file: {}", record.skill_file.display());
+    ui_println!("name: {}", record.metadata.name);
+    ui_println!("description: {}", record.metadata.description);
+    ui_println!("scope: {}", record.source_scope.as_str());
+    ui_println!("skill_dir: {}", record.skill_dir.display());
+    ui_println!("skill_file: {}", record.skill_file.display());
 
     if let Some(license) = &record.metadata.license {
-        println!("license: {}", license);
+        ui_println!("license: {}", license);
     }
 
     if let Some(compatibility) = &record.metadata.compatibility {
-        println!("compatibility: {}", compatibility);
+        ui_println!("compatibility: {}", compatibility);
     }
 
     if !record.metadata.allowed_tools.is_empty() {
-        println!(
+        ui_println!(
             "allowed_tools: {}",
             record.metadata.allowed_tools.join(", ")
         );
     }
 
     if !record.metadata.metadata.is_empty() {
-        println!("metadata:");
+        ui_println!("metadata:");
         for (key, value) in &record.metadata.metadata {
-            println!("  {}: {}", key, value);
+            ui_println!("  {}: {}", key, value);
         }
     }
 
@@ -235,21 +236,21 @@ pub fn show_paths(config: Config) -> Result<()> {
     let user_client_specific = home.join(".xzatoma").join("skills");
     let user_shared_convention = home.join(".agents").join("skills");
 
-    println!("working_dir: {}", working_dir.display());
-    println!("trust_store: {}", trust_store_path.display());
-    println!(
+    ui_println!("working_dir: {}", working_dir.display());
+    ui_println!("trust_store: {}", trust_store_path.display());
+    ui_println!(
         "project_trust_required: {}",
         config.skills.project_trust_required
     );
-    println!(
+    ui_println!(
         "allow_custom_paths_without_trust: {}",
         config.skills.allow_custom_paths_without_trust
     );
 
-    println!("\nconfigured discovery roots:");
+    ui_println!("\nconfigured discovery roots:");
 
     if config.skills.project_enabled {
-        println!(
+        ui_println!(
             "- project_client_specific: {} [trust={}]",
             project_client_specific.display(),
             root_trust_status(
@@ -259,7 +260,7 @@ pub fn show_paths(config: Config) -> Result<()> {
                 config.skills.project_trust_required
             )
         );
-        println!(
+        ui_println!(
             "- project_shared_convention: {} [trust={}]",
             project_shared_convention.display(),
             root_trust_status(
@@ -272,11 +273,11 @@ pub fn show_paths(config: Config) -> Result<()> {
     }
 
     if config.skills.user_enabled {
-        println!(
+        ui_println!(
             "- user_client_specific: {} [trust=not_required]",
             user_client_specific.display()
         );
-        println!(
+        ui_println!(
             "- user_shared_convention: {} [trust=not_required]",
             user_shared_convention.display()
         );
@@ -284,7 +285,7 @@ pub fn show_paths(config: Config) -> Result<()> {
 
     for (index, path) in config.skills.additional_paths.iter().enumerate() {
         let configured_path = PathBuf::from(path);
-        println!(
+        ui_println!(
             "- custom_{}: {} [trust={}]",
             index,
             configured_path.display(),
@@ -296,12 +297,12 @@ pub fn show_paths(config: Config) -> Result<()> {
         );
     }
 
-    println!("\ntrusted paths:");
+    ui_println!("\ntrusted paths:");
     if trusted_paths.is_empty() {
-        println!("- <none>");
+        ui_println!("- <none>");
     } else {
         for path in trusted_paths {
-            println!("- {}", path.display());
+            ui_println!("- {}", path.display());
         }
     }
 
@@ -352,22 +353,22 @@ fn show_trust(config: Config) -> Result<()> {
     let store = load_trust_store(&config.skills, &working_dir)?;
     let trusted = store.trusted_paths();
 
-    println!("trust_store: {}", store.path().display());
-    println!(
+    ui_println!("trust_store: {}", store.path().display());
+    ui_println!(
         "project_trust_required: {}",
         config.skills.project_trust_required
     );
-    println!(
+    ui_println!(
         "allow_custom_paths_without_trust: {}",
         config.skills.allow_custom_paths_without_trust
     );
 
     if trusted.is_empty() {
-        println!("trusted_paths: <none>");
+        ui_println!("trusted_paths: <none>");
     } else {
-        println!("trusted_paths:");
+        ui_println!("trusted_paths:");
         for path in trusted {
-            println!("- {}", path.display());
+            ui_println!("- {}", path.display());
         }
     }
 
@@ -379,8 +380,8 @@ fn trust_add(config: Config, path: &Path) -> Result<()> {
     let mut store = load_trust_store(&config.skills, &working_dir)?;
     let canonical = store.add_path(path)?;
 
-    println!("Trusted path added: {}", canonical.display());
-    println!("trust_store: {}", store.path().display());
+    ui_println!("Trusted path added: {}", canonical.display());
+    ui_println!("trust_store: {}", store.path().display());
 
     Ok(())
 }
@@ -391,12 +392,12 @@ fn trust_remove(config: Config, path: &Path) -> Result<()> {
     let removed = store.remove_path(path)?;
 
     if removed {
-        println!("Trusted path removed: {}", path.display());
+        ui_println!("Trusted path removed: {}", path.display());
     } else {
-        println!("Path was not trusted: {}", path.display());
+        ui_println!("Path was not trusted: {}", path.display());
     }
 
-    println!("trust_store: {}", store.path().display());
+    ui_println!("trust_store: {}", store.path().display());
 
     Ok(())
 }
@@ -0,0 +1,1412 @@
+//! Workspace snapshots around `xzatoma run`
+//!
+//! With `run.snapshot.enabled`, or `xzatoma run --snapshot`, the workspace
+//! is captured before the agent starts, and `xzatoma run rollback <run-id>`
+//! puts it back. There are two ways to capture it:
+//!
+//! - **git**: when the workspace is in a repository that has a commit and no
+//!   merge, rebase, cherry-pick, or revert in progress, HEAD is recorded and
+//!   the working tree, including untracked files, is written to a commit
+//!   under `refs/xzatoma/snapshots/`. The user's index, stash, and branches
+//!   are not touched.
+//! - **copy**: otherwise each file is stored by its SHA-256 in
+//!   `snapshots/objects` under the data directory, and the snapshot's
+//!   manifest maps paths to those objects.
+//!
+//! Both skip ignored files and files over `run.snapshot.max_file_size_bytes`.
+//! A snapshot that would hold nothing, or more than
+//! `run.snapshot.max_total_bytes`, fails the run before the agent starts.
+//!
+//! The snapshot ID is recorded in the run's artifacts directory as
+//! [`RUN_RECORD_FILE_NAME`]. Taking a snapshot removes those beyond the
+//! newest `run.snapshot.keep` and those older than `run.snapshot.max_age_days`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use similar::{ChangeTag, TextDiff};
+
+use crate::config::{Config, RunSnapshotConfig, SnapshotStrategy};
+use crate::error::{Result, XzatomaError};
+use crate::paths::Paths;
+use crate::{ui_eprint, ui_print, ui_println};
+
+/// Name of the file in the run's artifacts directory that names its snapshot
+pub const RUN_RECORD_FILE_NAME: &str = "snapshot.json";
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+const OBJECTS_DIR_NAME: &str = "objects";
+
+const SNAPSHOT_REF_PREFIX: &str = "refs/xzatoma/snapshots/";
+
+/// How a snapshot was captured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotKind {
+    /// A commit of the working tree in the workspace's repository
+    Git,
+    /// File contents copied into the data directory
+    Copy,
+}
+
+impl std::fmt::Display for SnapshotKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotKind::Git => write!(f, "git"),
+            SnapshotKind::Copy => write!(f, "copy"),
+        }
+    }
+}
+
+/// A file stored by a copy snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    /// Hex SHA-256 of the contents, naming the stored object
+    pub hash: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Whether the file had an execute bit set
+    #[serde(default)]
+    pub executable: bool,
+}
+
+/// The manifest of one workspace snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Snapshot ID
+    pub id: String,
+    /// Run the snapshot was taken for
+    pub run_id: String,
+    /// When the snapshot was taken
+    pub created_at: DateTime<Utc>,
+    /// Directory the snapshot restores; the repository root for git
+    pub workspace: PathBuf,
+    /// How the snapshot was captured
+    pub kind: SnapshotKind,
+    /// HEAD when the snapshot was taken (git)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head: Option<String>,
+    /// Commit holding the working tree (git)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Stored files by path relative to the workspace (copy)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, SnapshotFile>,
+    /// Number of files captured
+    pub file_count: usize,
+    /// Bytes of file contents written for the snapshot
+    pub captured_bytes: u64,
+    /// Files left out because they were over the size limit
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded: Vec<String>,
+    /// Size limit the snapshot was taken with; rollback applies the same one
+    pub max_file_size_bytes: u64,
+}
+
+/// How a file changed since the snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Created since the snapshot; rollback removes it
+    Added,
+    /// Changed since the snapshot; rollback restores it
+    Modified,
+    /// Deleted since the snapshot; rollback restores it
+    Deleted,
+}
+
+impl std::fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // `pad` so the preview can align the column
+        f.pad(match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Deleted => "deleted",
+        })
+    }
+}
+
+/// A file that differs from the snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileChange {
+    /// Path relative to the workspace
+    pub path: String,
+    /// How the file changed
+    pub kind: ChangeKind,
+    /// Lines added since the snapshot
+    pub insertions: usize,
+    /// Lines removed since the snapshot
+    pub deletions: usize,
+    /// Whether either side is binary, so no line counts were taken
+    pub binary: bool,
+}
+
+/// What a rollback would revert
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RollbackPreview {
+    /// HEAD now, when it moved away from the snapshot's HEAD (git)
+    pub moved_head: Option<String>,
+    /// Files that differ from the snapshot, sorted by path
+    pub changes: Vec<FileChange>,
+}
+
+impl RollbackPreview {
+    /// Returns true when the workspace matches the snapshot
+    pub fn is_empty(&self) -> bool {
+        self.moved_head.is_none() && self.changes.is_empty()
+    }
+}
+
+/// The snapshot ID stored with a run
+#[derive(Debug, Serialize, Deserialize)]
+struct RunRecord {
+    snapshot_id: String,
+}
+
+/// Snapshots in the data directory
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    root: PathBuf,
+}
+
+impl SnapshotStore {
+    /// Opens the store at `root`; nothing is created until a snapshot is taken
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Opens the store in the data directory
+    pub fn from_paths(paths: &Paths) -> Self {
+        Self::new(paths.snapshots_dir())
+    }
+
+    /// Captures `workspace` for `run_id` and removes snapshots the retention
+    /// policy no longer keeps
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Snapshot` when `strategy` is `git` and the
+    /// workspace is not a usable repository, when there is nothing to
+    /// capture, when the files are over `max_total_bytes`, or when a file or
+    /// git command fails.
+    pub fn create(
+        &self,
+        workspace: &Path,
+        run_id: &str,
+        config: &RunSnapshotConfig,
+    ) -> Result<Snapshot> {
+        fs::create_dir_all(&self.root)?;
+        let id = uuid::Uuid::new_v4().to_string();
+        let checkout = match config.strategy {
+            SnapshotStrategy::Copy => None,
+            SnapshotStrategy::Git => Some(git_checkout(workspace).map_err(|reason| {
+                XzatomaError::Snapshot(format!(
+                    "cannot use git for {}: {}",
+                    workspace.display(),
+                    reason
+                ))
+            })?),
+            SnapshotStrategy::Auto => match git_checkout(workspace) {
+                Ok(checkout) => Some(checkout),
+                Err(reason) => {
+                    tracing::debug!(%reason, "Copying workspace files for the snapshot");
+                    None
+                }
+            },
+        };
+        let snapshot = match checkout {
+            Some((root, head)) => self.capture_git(&id, run_id, &root, &head, config)?,
+            None => self.capture_copy(&id, run_id, workspace, config)?,
+        };
+
+        let dir = self.root.join(&id);
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join(MANIFEST_FILE_NAME),
+            serde_json::to_vec_pretty(&snapshot)?,
+        )?;
+
+        if let Err(e) = self.prune(config, Utc::now()) {
+            tracing::warn!(error = %e, "Failed to remove old workspace snapshots");
+        }
+        Ok(snapshot)
+    }
+
+    /// Loads a snapshot by ID
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Snapshot` when no snapshot has the ID.
+    pub fn load(&self, id: &str) -> Result<Snapshot> {
+        let path = self.root.join(id).join(MANIFEST_FILE_NAME);
+        let bytes = fs::read(&path).map_err(|e| {
+            XzatomaError::Snapshot(format!("snapshot {} is not available: {}", id, e))
+        })?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// Every readable snapshot, newest first
+    pub fn list(&self) -> Result<Vec<Snapshot>> {
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut snapshots = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_name() == OBJECTS_DIR_NAME || !entry.file_type()?.is_dir() {
+                continue;
+            }
+            match self.load(&entry.file_name().to_string_lossy()) {
+                Ok(snapshot) => snapshots.push(snapshot),
+                Err(e) => tracing::warn!(
+                    path = %entry.path().display(),
+                    error = %e,
+                    "Skipping unreadable workspace snapshot"
+                ),
+            }
+        }
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.created_at));
+        Ok(snapshots)
+    }
+
+    /// Removes snapshots beyond the newest `keep` and those older than
+    /// `max_age_days`, and returns their IDs
+    ///
+    /// Git snapshots lose their ref, so git can collect the commit; copy
+    /// snapshots release objects no other snapshot uses.
+    pub fn prune(&self, config: &RunSnapshotConfig, now: DateTime<Utc>) -> Result<Vec<String>> {
+        let mut removed = Vec::new();
+        let mut kept_objects = BTreeSet::new();
+        for (index, snapshot) in self.list()?.into_iter().enumerate() {
+            let age_days = u64::try_from((now - snapshot.created_at).num_days()).unwrap_or(0);
+            let expired = config.max_age_days > 0 && age_days >= config.max_age_days;
+            if index < config.keep && !expired {
+                kept_objects.extend(snapshot.files.values().map(|file| file.hash.clone()));
+                continue;
+            }
+            if snapshot.kind == SnapshotKind::Git {
+                let deleted = Git::new(&snapshot.workspace).run(&[
+                    "update-ref",
+                    "-d",
+                    &snapshot_ref(&snapshot.id),
+                ]);
+                if let Err(e) = deleted {
+                    tracing::debug!(id = %snapshot.id, error = %e, "Snapshot ref not removed");
+                }
+            }
+            fs::remove_dir_all(self.root.join(&snapshot.id))?;
+            removed.push(snapshot.id);
+        }
+        if !removed.is_empty() {
+            self.remove_unused_objects(&kept_objects)?;
+        }
+        Ok(removed)
+    }
+
+    /// Records `snapshot` as the one to roll `run_dir`'s run back to
+    pub fn record_run(&self, run_dir: &Path, snapshot: &Snapshot) -> Result<()> {
+        fs::create_dir_all(run_dir)?;
+        let record = RunRecord {
+            snapshot_id: snapshot.id.clone(),
+        };
+        fs::write(
+            run_dir.join(RUN_RECORD_FILE_NAME),
+            serde_json::to_vec_pretty(&record)?,
+        )?;
+        Ok(())
+    }
+
+    /// Loads the snapshot recorded for the run in `run_dir`
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Snapshot` when the run took no snapshot or its
+    /// snapshot was removed.
+    pub fn for_run(&self, run_dir: &Path, run_id: &str) -> Result<Snapshot> {
+        let bytes = fs::read(run_dir.join(RUN_RECORD_FILE_NAME)).map_err(|_| {
+            XzatomaError::Snapshot(format!(
+                "run {} has no snapshot; it was not started with --snapshot",
+                run_id
+            ))
+        })?;
+        let record: RunRecord = serde_json::from_slice(&bytes)?;
+        self.load(&record.snapshot_id).map_err(|_| {
+            XzatomaError::Snapshot(format!(
+                "snapshot {} of run {} was removed by the retention policy",
+                record.snapshot_id, run_id
+            ))
+        })
+    }
+
+    /// Lists what rolling back to `snapshot` would revert
+    pub fn preview(&self, snapshot: &Snapshot) -> Result<RollbackPreview> {
+        if !snapshot.workspace.is_dir() {
+            return Err(XzatomaError::Snapshot(format!(
+                "workspace {} no longer exists",
+                snapshot.workspace.display()
+            )));
+        }
+        let mut preview = match snapshot.kind {
+            SnapshotKind::Git => self.preview_git(snapshot)?,
+            SnapshotKind::Copy => self.preview_copy(snapshot)?,
+        };
+        // Files that were too large to capture are left alone
+        preview.changes.retain(|change| {
+            change.kind != ChangeKind::Added || !snapshot.excluded.contains(&change.path)
+        });
+        preview.changes.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(preview)
+    }
+
+    /// Puts the workspace back to `snapshot`, reverting the changes in
+    /// `preview`
+    ///
+    /// Added files are removed, and modified and deleted files are written
+    /// from the snapshot. For git, the current branch and the index are
+    /// reset to the snapshot's HEAD first.
+    pub fn restore(&self, snapshot: &Snapshot, preview: &RollbackPreview) -> Result<()> {
+        match snapshot.kind {
+            SnapshotKind::Git => self.restore_git(snapshot, preview),
+            SnapshotKind::Copy => self.restore_copy(snapshot, preview),
+        }
+    }
+
+    fn capture_git(
+        &self,
+        id: &str,
+        run_id: &str,
+        root: &Path,
+        head: &str,
+        config: &RunSnapshotConfig,
+    ) -> Result<Snapshot> {
+        let tree = self.write_worktree_tree(root, config.max_file_size_bytes)?;
+        if tree.file_count == 0 {
+            return Err(XzatomaError::Snapshot(format!(
+                "nothing to snapshot in {}",
+                root.display()
+            )));
+        }
+        if tree.untracked_bytes > config.max_total_bytes {
+            return Err(too_large(root, tree.untracked_bytes, config));
+        }
+        let git = Git::new(root);
+        let message = format!("xzatoma snapshot {} of run {}", id, run_id);
+        let commit = git.text(&["commit-tree", &tree.id, "-p", head, "-m", &message])?;
+        git.run(&["update-ref", &snapshot_ref(id), &commit])?;
+        Ok(Snapshot {
+            id: id.to_string(),
+            run_id: run_id.to_string(),
+            created_at: Utc::now(),
+            workspace: root.to_path_buf(),
+            kind: SnapshotKind::Git,
+            head: Some(head.to_string()),
+            commit: Some(commit),
+            files: BTreeMap::new(),
+            file_count: tree.file_count,
+            captured_bytes: tree.untracked_bytes,
+            excluded: tree.excluded,
+            max_file_size_bytes: config.max_file_size_bytes,
+        })
+    }
+
+    fn capture_copy(
+        &self,
+        id: &str,
+        run_id: &str,
+        workspace: &Path,
+        config: &RunSnapshotConfig,
+    ) -> Result<Snapshot> {
+        let workspace = workspace.canonicalize()?;
+        let scan = scan_workspace(&workspace, config.max_file_size_bytes)?;
+        if scan.files.is_empty() {
+            return Err(XzatomaError::Snapshot(format!(
+                "nothing to snapshot in {}",
+                workspace.display()
+            )));
+        }
+        let total: u64 = scan.files.values().map(|file| file.size).sum();
+        if total > config.max_total_bytes {
+            return Err(too_large(&workspace, total, config));
+        }
+
+        let mut files = BTreeMap::new();
+        for (path, file) in &scan.files {
+            let contents = fs::read(&file.path).map_err(|e| {
+                XzatomaError::Snapshot(format!("failed to read {}: {}", file.path.display(), e))
+            })?;
+            let hash = content_hash(&contents);
+            self.write_object(&hash, &contents)?;
+            files.insert(
+                path.clone(),
+                SnapshotFile {
+                    hash,
+                    size: contents.len() as u64,
+                    executable: file.executable,
+                },
+            );
+        }
+        Ok(Snapshot {
+            id: id.to_string(),
+            run_id: run_id.to_string(),
+            created_at: Utc::now(),
+            workspace,
+            kind: SnapshotKind::Copy,
+            head: None,
+            commit: None,
+            file_count: files.len(),
+            files,
+            captured_bytes: total,
+            excluded: scan.excluded,
+            max_file_size_bytes: config.max_file_size_bytes,
+        })
+    }
+
+    fn preview_git(&self, snapshot: &Snapshot) -> Result<RollbackPreview> {
+        let (Some(head), Some(commit)) = (&snapshot.head, &snapshot.commit) else {
+            return Err(XzatomaError::Snapshot(format!(
+                "git snapshot {} has no commit",
+                snapshot.id
+            )));
+        };
+        let root = &snapshot.workspace;
+        let git = Git::new(root);
+        let current_head = git.text(&["rev-parse", "--verify", "-q", "HEAD^{commit}"])?;
+        let current = self.write_worktree_tree(root, snapshot.max_file_size_bytes)?;
+        let snapshot_tree = format!("{}^{{tree}}", commit);
+        let diff = |format: &str| {
+            git.output(
+                &[
+                    "diff-tree",
+                    "-r",
+                    "-z",
+                    "--no-renames",
+                    format,
+                    &snapshot_tree,
+                    &current.id,
+                ],
+                None,
+            )
+        };
+        let line_counts = parse_numstat(&diff("--numstat")?);
+        let changes = parse_name_status(&diff("--name-status")?)
+            .into_iter()
+            .map(|(status, path)| {
+                let kind = match status {
+                    'A' => ChangeKind::Added,
+                    'D' if !root.join(&path).exists() => ChangeKind::Deleted,
+                    // Includes files that grew past the size limit
+                    _ => ChangeKind::Modified,
+                };
+                let counts = line_counts.get(&path).copied().flatten();
+                FileChange {
+                    kind,
+                    insertions: counts.map_or(0, |(insertions, _)| insertions),
+                    deletions: counts.map_or(0, |(_, deletions)| deletions),
+                    binary: counts.is_none(),
+                    path,
+                }
+            })
+            .collect();
+        Ok(RollbackPreview {
+            moved_head: (&current_head != head).then_some(current_head),
+            changes,
+        })
+    }
+
+    fn preview_copy(&self, snapshot: &Snapshot) -> Result<RollbackPreview> {
+        let root = &snapshot.workspace;
+        let scan = scan_workspace(root, snapshot.max_file_size_bytes)?;
+        let mut changes = Vec::new();
+        for (path, stored) in &snapshot.files {
+            let on_disk = root.join(path);
+            if !on_disk.is_file() {
+                let old = self.read_object(&stored.hash)?;
+                changes.push(file_change(path, ChangeKind::Deleted, &old, &[]));
+                continue;
+            }
+            let contents = fs::read(&on_disk)?;
+            if content_hash(&contents) != stored.hash {
+                let old = self.read_object(&stored.hash)?;
+                changes.push(file_change(path, ChangeKind::Modified, &old, &contents));
+            }
+        }
+        for (path, file) in &scan.files {
+            if !snapshot.files.contains_key(path) {
+                let contents = fs::read(&file.path)?;
+                changes.push(file_change(path, ChangeKind::Added, &[], &contents));
+            }
+        }
+        Ok(RollbackPreview {
+            moved_head: None,
+            changes,
+        })
+    }
+
+    fn restore_git(&self, snapshot: &Snapshot, preview: &RollbackPreview) -> Result<()> {
+        let (Some(head), Some(commit)) = (&snapshot.head, &snapshot.commit) else {
+            return Err(XzatomaError::Snapshot(format!(
+                "git snapshot {} has no commit",
+                snapshot.id
+            )));
+        };
+        let root = &snapshot.workspace;
+        Git::new(root).run(&["reset", "-q", head])?;
+        remove_added(root, preview)?;
+
+        let restored: Vec<&str> = preview
+            .changes
+            .iter()
+            .filter(|change| change.kind != ChangeKind::Added)
+            .map(|change| change.path.as_str())
+            .collect();
+        if restored.is_empty() {
+            return Ok(());
+        }
+        let index = TempIndex::new(&self.root)?;
+        let git = Git::new(root).with_index(index.path());
+        git.run(&["read-tree", commit])?;
+        git.output(
+            &["checkout-index", "-f", "-z", "--stdin"],
+            Some(&nul_separated(&restored)),
+        )?;
+        Ok(())
+    }
+
+    fn restore_copy(&self, snapshot: &Snapshot, preview: &RollbackPreview) -> Result<()> {
+        let root = &snapshot.workspace;
+        let restored: Vec<(&FileChange, &SnapshotFile)> = preview
+            .changes
+            .iter()
+            .filter(|change| change.kind != ChangeKind::Added)
+            .filter_map(|change| snapshot.files.get(&change.path).map(|file| (change, file)))
+            .collect();
+        // Nothing is touched unless every object is still there
+        if let Some((change, _)) = restored
+            .iter()
+            .find(|(_, file)| !self.object_path(&file.hash).is_file())
+        {
+            return Err(XzatomaError::Snapshot(format!(
+                "the stored copy of {} is missing from snapshot {}",
+                change.path, snapshot.id
+            )));
+        }
+
+        remove_added(root, preview)?;
+        for (change, file) in restored {
+            let target = root.join(&change.path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&target, self.read_object(&file.hash)?)?;
+            set_executable(&target, file.executable)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the working tree under `root` to a tree object through a
+    /// temporary index, leaving the user's index alone
+    fn write_worktree_tree(&self, root: &Path, max_file_size_bytes: u64) -> Result<WorktreeTree> {
+        fs::create_dir_all(&self.root)?;
+        let index = TempIndex::new(&self.root)?;
+        let git = Git::new(root).with_index(index.path());
+        git.run(&["read-tree", "HEAD"])?;
+        let tracked = split_nul(&git.output(&["ls-files", "-z", "--cached"], None)?);
+        let untracked =
+            split_nul(&git.output(&["ls-files", "-z", "--others", "--exclude-standard"], None)?);
+
+        let mut paths: Vec<&str> = tracked.iter().map(String::as_str).collect();
+        let mut excluded = Vec::new();
+        let mut untracked_bytes = 0;
+        // Nested repositories are listed as `dir/` and left out
+        for path in untracked.iter().filter(|path| !path.ends_with('/')) {
+            let size = fs::symlink_metadata(root.join(path)).map_or(0, |meta| meta.len());
+            if size > max_file_size_bytes {
+                excluded.push(path.clone());
+                continue;
+            }
+            untracked_bytes += size;
+            paths.push(path);
+        }
+        if !paths.is_empty() {
+            git.output(
+                &["add", "-A", "--pathspec-from-file=-", "--pathspec-file-nul"],
+                Some(&nul_separated(&paths)),
+            )?;
+        }
+        let file_count = split_nul(&git.output(&["ls-files", "-z", "--cached"], None)?).len();
+        Ok(WorktreeTree {
+            id: git.text(&["write-tree"])?,
+            file_count,
+            untracked_bytes,
+            excluded,
+        })
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.root
+            .join(OBJECTS_DIR_NAME)
+            .join(&hash[..2.min(hash.len())])
+            .join(hash)
+    }
+
+    fn write_object(&self, hash: &str, contents: &[u8]) -> Result<()> {
+        let path = self.object_path(hash);
+        if path.is_file() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let partial = path.with_extension("partial");
+        fs::write(&partial, contents)?;
+        fs::rename(&partial, &path)?;
+        Ok(())
+    }
+
+    fn read_object(&self, hash: &str) -> Result<Vec<u8>> {
+        fs::read(self.object_path(hash)).map_err(|e| {
+            XzatomaError::Snapshot(format!("stored object {} is not readable: {}", hash, e))
+        })
+    }
+
+    fn remove_unused_objects(&self, kept: &BTreeSet<String>) -> Result<()> {
+        let objects = self.root.join(OBJECTS_DIR_NAME);
+        if !objects.is_dir() {
+            return Ok(());
+        }
+        for entry in walkdir::WalkDir::new(&objects).min_depth(2).max_depth(2) {
+            let entry = entry.map_err(|e| XzatomaError::Snapshot(e.to_string()))?;
+            if !kept.contains(entry.file_name().to_string_lossy().as_ref()) {
+                fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Takes the snapshot for a run and records it in the run's artifacts
+/// directory
+///
+/// # Errors
+///
+/// See [`SnapshotStore::create`].
+pub fn snapshot_run(config: &Config, workspace: &Path, run_id: &str) -> Result<Snapshot> {
+    let paths = Paths::from_config(config)?;
+    let store = SnapshotStore::from_paths(&paths);
+    let snapshot = store.create(workspace, run_id, &config.run.snapshot)?;
+    store.record_run(&paths.run_artifacts_dir(run_id), &snapshot)?;
+    Ok(snapshot)
+}
+
+/// One-line description of a new snapshot, printed before the run starts
+pub fn describe_snapshot(snapshot: &Snapshot) -> String {
+    let mut line = format!(
+        "Snapshot {} of {} ({}, {} file{})",
+        snapshot.id,
+        snapshot.workspace.display(),
+        snapshot.kind,
+        snapshot.file_count,
+        if snapshot.file_count == 1 { "" } else { "s" }
+    );
+    if !snapshot.excluded.is_empty() {
+        line.push_str(&format!(
+            "; {} large file{} left out",
+            snapshot.excluded.len(),
+            if snapshot.excluded.len() == 1 {
+                ""
+            } else {
+                "s"
+            }
+        ));
+    }
+    line
+}
+
+/// Handles `xzatoma run rollback <run-id>`
+///
+/// Prints what the rollback would revert and restores the snapshot once
+/// confirmed. Without `yes` and without a terminal to ask on, only the
+/// preview is printed.
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Snapshot` when the run has no snapshot or it
+/// cannot be restored.
+pub fn handle_rollback(config: &Config, run_id: &str, yes: bool) -> Result<()> {
+    let paths = Paths::from_config(config)?;
+    let store = SnapshotStore::from_paths(&paths);
+    let snapshot = store.for_run(&paths.run_artifacts_dir(run_id), run_id)?;
+    let preview = store.preview(&snapshot)?;
+    if preview.is_empty() {
+        ui_println!(
+            "Nothing to roll back: {} matches snapshot {}",
+            snapshot.workspace.display(),
+            snapshot.id
+        );
+        return Ok(());
+    }
+
+    ui_print!("{}", render_preview(&snapshot, &preview));
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            ui_println!("Nothing restored; rerun with --yes to restore without a prompt.");
+            return Ok(());
+        }
+        if !confirm("Restore the snapshot?")? {
+            ui_println!("Nothing restored.");
+            return Ok(());
+        }
+    }
+    store.restore(&snapshot, &preview)?;
+    ui_println!(
+        "Restored {} to snapshot {}",
+        snapshot.workspace.display(),
+        snapshot.id
+    );
+    Ok(())
+}
+
+/// Renders the list of changes shown before a rollback
+pub fn render_preview(snapshot: &Snapshot, preview: &RollbackPreview) -> String {
+    let mut text = format!(
+        "Rolling back run {} restores {} to snapshot {} ({}, taken {}):\n\n",
+        snapshot.run_id,
+        snapshot.workspace.display(),
+        snapshot.id,
+        snapshot.kind,
+        snapshot.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    if let (Some(current), Some(head)) = (&preview.moved_head, &snapshot.head) {
+        text.push_str(&format!(
+            "  HEAD moved from {} to {}; the current branch is reset to {}\n",
+            short_id(head),
+            short_id(current),
+            short_id(head)
+        ));
+    }
+    let width = preview
+        .changes
+        .iter()
+        .map(|change| change.path.chars().count())
+        .max()
+        .unwrap_or(0);
+    for change in &preview.changes {
+        let stat = if change.binary {
+            "binary".to_string()
+        } else {
+            format!("+{} -{}", change.insertions, change.deletions)
+        };
+        text.push_str(&format!(
+            "  {:<8}  {:<width$}  {}\n",
+            change.kind,
+            change.path,
+            stat,
+            width = width
+        ));
+    }
+    let insertions: usize = preview.changes.iter().map(|c| c.insertions).sum();
+    let deletions: usize = preview.changes.iter().map(|c| c.deletions).sum();
+    text.push_str(&format!(
+        "\n{} file{} changed since the snapshot, {} insertion{}(+), {} deletion{}(-)\n",
+        preview.changes.len(),
+        if preview.changes.len() == 1 { "" } else { "s" },
+        insertions,
+        if insertions == 1 { "" } else { "s" },
+        deletions,
+        if deletions == 1 { "" } else { "s" }
+    ));
+    text.push_str("Added files are removed; modified and deleted files are restored.\n\n");
+    text
+}
+
+/// Asks a yes/no question on stderr; anything but `y` or `yes` declines
+fn confirm(question: &str) -> Result<bool> {
+    ui_eprint!("{} [y/N]: ", question);
+    let _ = std::io::stderr().flush();
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    let answer = line.trim().to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
+
+/// The repository root and HEAD when `workspace` can be captured with git,
+/// or why it cannot
+fn git_checkout(workspace: &Path) -> std::result::Result<(PathBuf, String), String> {
+    let git = Git::new(workspace);
+    let root = git
+        .text(&["rev-parse", "--show-toplevel"])
+        .map_err(|_| "not inside a git work tree".to_string())?;
+    let head = git
+        .text(&["rev-parse", "--verify", "-q", "HEAD^{commit}"])
+        .map_err(|_| "the repository has no commits".to_string())?;
+    for marker in [
+        "MERGE_HEAD",
+        "CHERRY_PICK_HEAD",
+        "REVERT_HEAD",
+        "REBASE_HEAD",
+    ] {
+        if git.text(&["rev-parse", "--verify", "-q", marker]).is_ok() {
+            return Err(format!(
+                "{} exists; finish or abort the operation in progress",
+                marker
+            ));
+        }
+    }
+    Ok((PathBuf::from(root), head))
+}
+
+fn snapshot_ref(id: &str) -> String {
+    format!("{}{}", SNAPSHOT_REF_PREFIX, id)
+}
+
+fn too_large(workspace: &Path, bytes: u64, config: &RunSnapshotConfig) -> XzatomaError {
+    XzatomaError::Snapshot(format!(
+        "{} holds {} bytes to capture, more than run.snapshot.max_total_bytes ({})",
+        workspace.display(),
+        bytes,
+        config.max_total_bytes
+    ))
+}
+
+fn short_id(id: &str) -> &str {
+    &id[..id.len().min(12)]
+}
+
+/// Hex SHA-256 of file contents
+fn content_hash(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// A change with line counts taken from the old and new contents
+fn file_change(path: &str, kind: ChangeKind, old: &[u8], new: &[u8]) -> FileChange {
+    let counts = match (std::str::from_utf8(old), std::str::from_utf8(new)) {
+        (Ok(old), Ok(new)) if !old.contains('\0') && !new.contains('\0') => {
+            let diff = TextDiff::from_lines(old, new);
+            let count = |tag| {
+                diff.iter_all_changes()
+                    .filter(|change| change.tag() == tag)
+                    .count()
+            };
+            Some((count(ChangeTag::Insert), count(ChangeTag::Delete)))
+        }
+        _ => None,
+    };
+    FileChange {
+        path: path.to_string(),
+        kind,
+        insertions: counts.map_or(0, |(insertions, _)| insertions),
+        deletions: counts.map_or(0, |(_, deletions)| deletions),
+        binary: counts.is_none(),
+    }
+}
+
+/// Removes the files the rollback reverts by deleting them, and the
+/// directories they leave empty
+fn remove_added(root: &Path, preview: &RollbackPreview) -> Result<()> {
+    for change in &preview.changes {
+        if change.kind != ChangeKind::Added {
+            continue;
+        }
+        let path = root.join(&change.path);
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let mut dir = path.parent();
+        while let Some(parent) = dir.filter(|parent| *parent != root) {
+            if fs::remove_dir(parent).is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+    }
+    Ok(())
+}
+
+/// A file found by [`scan_workspace`]
+struct ScannedFile {
+    path: PathBuf,
+    size: u64,
+    executable: bool,
+}
+
+/// Files a copy snapshot captures, by path relative to the root
+struct Scan {
+    files: BTreeMap<String, ScannedFile>,
+    excluded: Vec<String>,
+}
+
+/// Regular files under `root` that are not ignored, with those over the
+/// size limit listed separately
+///
+/// Hidden files are included; `.git` directories, symlinks, and files
+/// matched by `.gitignore` or `.ignore` are not.
+fn scan_workspace(root: &Path, max_file_size_bytes: u64) -> Result<Scan> {
+    let mut scan = Scan {
+        files: BTreeMap::new(),
+        excluded: Vec::new(),
+    };
+    let walker = ignore::WalkBuilder::new(root)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+    for entry in walker {
+        let entry = entry.map_err(|e| {
+            XzatomaError::Snapshot(format!("failed to walk {}: {}", root.display(), e))
+        })?;
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue;
+        }
+        let Ok(relative) = entry.path().strip_prefix(root) else {
+            continue;
+        };
+        let relative = relative
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let meta = entry.metadata().map_err(|e| {
+            XzatomaError::Snapshot(format!("failed to read {}: {}", entry.path().display(), e))
+        })?;
+        if meta.len() > max_file_size_bytes {
+            scan.excluded.push(relative);
+            continue;
+        }
+        scan.files.insert(
+            relative,
+            ScannedFile {
+                path: entry.path().to_path_buf(),
+                size: meta.len(),
+                executable: is_executable(&meta),
+            },
+        );
+    }
+    Ok(scan)
+}
+
+#[cfg(unix)]
+fn is_executable(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_meta: &fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path, executable: bool) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)?.permissions();
+    let mode = permissions.mode();
+    let mode = if executable {
+        mode | ((mode & 0o444) >> 2)
+    } else {
+        mode & !0o111
+    };
+    permissions.set_mode(mode);
+    fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path, _executable: bool) -> Result<()> {
+    Ok(())
+}
+
+/// A working tree written to a tree object
+struct WorktreeTree {
+    id: String,
+    file_count: usize,
+    untracked_bytes: u64,
+    excluded: Vec<String>,
+}
+
+/// A git index file that is removed when dropped
+struct TempIndex {
+    path: PathBuf,
+}
+
+impl TempIndex {
+    fn new(dir: &Path) -> Result<Self> {
+        let dir = if dir.is_absolute() {
+            dir.to_path_buf()
+        } else {
+            std::env::current_dir()?.join(dir)
+        };
+        Ok(Self {
+            path: dir.join(format!("index-{}.tmp", uuid::Uuid::new_v4())),
+        })
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempIndex {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Runs git in a directory, optionally with another index file
+struct Git<'a> {
+    dir: &'a Path,
+    index: Option<&'a Path>,
+}
+
+impl<'a> Git<'a> {
+    fn new(dir: &'a Path) -> Self {
+        Self { dir, index: None }
+    }
+
+    fn with_index(mut self, index: &'a Path) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    fn run(&self, args: &[&str]) -> Result<()> {
+        self.output(args, None).map(|_| ())
+    }
+
+    /// Runs the command and returns its stdout without surrounding whitespace
+    fn text(&self, args: &[&str]) -> Result<String> {
+        let stdout = self.output(args, None)?;
+        Ok(String::from_utf8_lossy(&stdout).trim().to_string())
+    }
+
+    /// Runs the command with `input` on stdin and returns its stdout
+    fn output(&self, args: &[&str], input: Option<&[u8]>) -> Result<Vec<u8>> {
+        let mut command = Command::new("git");
+        command
+            .args(args)
+            .current_dir(self.dir)
+            // Paths are passed as they are, never as glob patterns
+            .env("GIT_LITERAL_PATHSPECS", "1")
+            // Snapshot commits must not depend on the user's identity
+            .env("GIT_AUTHOR_NAME", "xzatoma")
+            .env("GIT_AUTHOR_EMAIL", "xzatoma@localhost")
+            .env("GIT_COMMITTER_NAME", "xzatoma")
+            .env("GIT_COMMITTER_EMAIL", "xzatoma@localhost")
+            .stdin(if input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(index) = self.index {
+            command.env("GIT_INDEX_FILE", index);
+        }
+        let mut child = command
+            .spawn()
+            .map_err(|e| XzatomaError::Snapshot(format!("failed to run git: {}", e)))?;
+        if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+            stdin.write_all(input)?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(XzatomaError::Snapshot(format!(
+                "git {} failed in {}: {}",
+                args.first().copied().unwrap_or_default(),
+                self.dir.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(output.stdout)
+    }
+}
+
+fn split_nul(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .split(|&b| b == 0)
+        .filter(|part| !part.is_empty())
+        .map(|part| String::from_utf8_lossy(part).into_owned())
+        .collect()
+}
+
+fn nul_separated(paths: &[&str]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for path in paths {
+        bytes.extend_from_slice(path.as_bytes());
+        bytes.push(0);
+    }
+    bytes
+}
+
+/// Parses `diff-tree -z --numstat` into line counts by path; binary files
+/// map to `None`
+fn parse_numstat(bytes: &[u8]) -> BTreeMap<String, Option<(usize, usize)>> {
+    split_nul(bytes)
+        .into_iter()
+        .filter_map(|record| {
+            let mut fields = record.splitn(3, '\t');
+            let insertions = fields.next()?.parse().ok();
+            let deletions = fields.next()?.parse().ok();
+            let path = fields.next()?.to_string();
+            Some((path, insertions.zip(deletions)))
+        })
+        .collect()
+}
+
+/// Parses `diff-tree -z --name-status` into status letters and paths
+fn parse_name_status(bytes: &[u8]) -> Vec<(char, String)> {
+    let fields = split_nul(bytes);
+    fields
+        .chunks(2)
+        .filter_map(|pair| match pair {
+            [status, path] => Some((status.chars().next()?, path.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        Git::new(dir).run(args).unwrap();
+    }
+
+    fn write(root: &Path, path: &str, contents: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn read(root: &Path, path: &str) -> String {
+        fs::read_to_string(root.join(path)).unwrap()
+    }
+
+    fn config(strategy: SnapshotStrategy) -> RunSnapshotConfig {
+        RunSnapshotConfig {
+            enabled: true,
+            strategy,
+            max_file_size_bytes: 64,
+            ..RunSnapshotConfig::default()
+        }
+    }
+
+    fn git_workspace() -> TempDir {
+        let workspace = TempDir::new().unwrap();
+        let root = workspace.path();
+        git(root, &["init", "-q"]);
+        write(root, ".gitignore", "target/\n");
+        write(root, "src/lib.rs", "fn a() {}\nfn b() {}\n");
+        write(root, "README.md", "# Demo\n");
+        git(root, &["add", "-A"]);
+        git(root, &["commit", "-q", "-m", "initial"]);
+        workspace
+    }
+
+    #[test]
+    fn test_git_snapshot_rolls_back_edits_deletions_and_new_files() {
+        let workspace = git_workspace();
+        let root = workspace.path();
+        // Dirty state before the run is part of the snapshot
+        write(root, "notes.txt", "draft\n");
+        write(root, "target/out.bin", "ignored");
+        write(root, "big.log", &"x".repeat(100));
+        let data = TempDir::new().unwrap();
+        let store = SnapshotStore::new(data.path().join("snapshots"));
+
+        let snapshot = store
+            .create(root, "run-1", &config(SnapshotStrategy::Auto))
+            .unwrap();
+        assert_eq!(snapshot.kind, SnapshotKind::Git);
+        assert_eq!(snapshot.file_count, 4);
+        assert_eq!(snapshot.excluded, vec!["big.log".to_string()]);
+        let user_index = Git::new(root).text(&["diff", "--cached", "--name-only"]);
+        assert_eq!(user_index.unwrap(), "");
+
+        // What the agent does during the run
+        write(root, "src/lib.rs", "fn a() {}\nfn c() {}\nfn d() {}\n");
+        fs::remove_file(root.join("README.md")).unwrap();
+        fs::remove_file(root.join("notes.txt")).unwrap();
+        write(root, "src/new.rs", "pub fn new() {}\n");
+        git(root, &["add", "-A"]);
+        git(root, &["commit", "-q", "-m", "agent work"]);
+
+        let preview = store.preview(&snapshot).unwrap();
+        assert!(preview.moved_head.is_some());
+        let changes: Vec<(&str, ChangeKind, usize, usize)> = preview
+            .changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.kind, c.insertions, c.deletions))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("README.md", ChangeKind::Deleted, 0, 1),
+                ("notes.txt", ChangeKind::Deleted, 0, 1),
+                ("src/lib.rs", ChangeKind::Modified, 2, 1),
+                ("src/new.rs", ChangeKind::Added, 1, 0),
+            ]
+        );
+        let text = render_preview(&snapshot, &preview);
+        assert!(text.contains("HEAD moved from"));
+        assert!(
+            text.contains("4 files changed since the snapshot, 3 insertions(+), 3 deletions(-)")
+        );
+
+        store.restore(&snapshot, &preview).unwrap();
+        assert_eq!(read(root, "src/lib.rs"), "fn a() {}\nfn b() {}\n");
+        assert_eq!(read(root, "README.md"), "# Demo\n");
+        assert_eq!(read(root, "notes.txt"), "draft\n");
+        assert!(!root.join("src/new.rs").exists());
+        assert!(root.join("target/out.bin").exists());
+        assert!(root.join("big.log").exists());
+        let head = Git::new(root).text(&["rev-parse", "HEAD"]).unwrap();
+        assert_eq!(Some(head), snapshot.head.clone());
+        assert!(store.preview(&snapshot).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_copy_snapshot_rolls_back_edits_deletions_and_new_files() {
+        let workspace = TempDir::new().unwrap();
+        let root = workspace.path();
+        write(root, ".gitignore", "target/\n");
+        write(root, "src/main.rs", "fn main() {}\n");
+        write(root, "docs/guide.md", "one\ntwo\n");
+        write(root, "target/cache", "ignored");
+        write(root, "data.bin", &"x".repeat(100));
+        let data = TempDir::new().unwrap();
+        let store = SnapshotStore::new(data.path().join("snapshots"));
+
+        let snapshot = store
+            .create(root, "run-2", &config(SnapshotStrategy::Auto))
+            .unwrap();
+        assert_eq!(snapshot.kind, SnapshotKind::Copy);
+        let captured: Vec<&str> = snapshot.files.keys().map(String::as_str).collect();
+        assert_eq!(captured, vec![".gitignore", "docs/guide.md", "src/main.rs"]);
+        assert_eq!(snapshot.excluded, vec!["data.bin".to_string()]);
+
+        write(root, "src/main.rs", "fn main() {\n    run();\n}\n");
+        fs::remove_dir_all(root.join("docs")).unwrap();
+        write(root, "scratch/tmp.txt", "temp\n");
+
+        let preview = store.preview(&snapshot).unwrap();
+        let changes: Vec<(&str, ChangeKind)> = preview
+            .changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.kind))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("docs/guide.md", ChangeKind::Deleted),
+                ("scratch/tmp.txt", ChangeKind::Added),
+                ("src/main.rs", ChangeKind::Modified),
+            ]
+        );
+
+        store.restore(&snapshot, &preview).unwrap();
+        assert_eq!(read(root, "src/main.rs"), "fn main() {}\n");
+        assert_eq!(read(root, "docs/guide.md"), "one\ntwo\n");
+        assert!(!root.join("scratch").exists());
+        assert!(root.join("target/cache").exists());
+        assert!(root.join("data.bin").exists());
+        assert!(store.preview(&snapshot).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_fails_loudly_instead_of_capturing_nothing() {
+        let data = TempDir::new().unwrap();
+        let store = SnapshotStore::new(data.path().join("snapshots"));
+
+        let empty = TempDir::new().unwrap();
+        write(empty.path(), ".gitignore", "*\n");
+        let err = store
+            .create(empty.path(), "run", &config(SnapshotStrategy::Copy))
+            .unwrap_err();
+        assert!(err.to_string().contains("nothing to snapshot"), "{}", err);
+
+        let workspace = TempDir::new().unwrap();
+        write(workspace.path(), "a.txt", &"a".repeat(60));
+        write(workspace.path(), "b.txt", &"b".repeat(60));
+        let small = RunSnapshotConfig {
+            max_total_bytes: 100,
+            ..config(SnapshotStrategy::Copy)
+        };
+        let err = store.create(workspace.path(), "run", &small).unwrap_err();
+        assert!(err.to_string().contains("max_total_bytes"), "{}", err);
+
+        let err = store
+            .create(workspace.path(), "run", &config(SnapshotStrategy::Git))
+            .unwrap_err();
+        assert!(err.to_string().contains("cannot use git"), "{}", err);
+        assert!(store.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_retention_removes_old_snapshots_and_their_objects() {
+        let workspace = TempDir::new().unwrap();
+        let data = TempDir::new().unwrap();
+        let store = SnapshotStore::new(data.path().join("snapshots"));
+        let keep_two = RunSnapshotConfig {
+            keep: 2,
+            ..config(SnapshotStrategy::Copy)
+        };
+
+        let mut ids = Vec::new();
+        for version in 0..3 {
+            write(
+                workspace.path(),
+                "file.txt",
+                &format!("version {}\n", version),
+            );
+            ids.push(store.create(workspace.path(), "run", &keep_two).unwrap().id);
+        }
+        let listed: Vec<String> = store.list().unwrap().into_iter().map(|s| s.id).collect();
+        assert_eq!(listed, vec![ids[2].clone(), ids[1].clone()]);
+        assert!(store.load(&ids[0]).is_err());
+        let objects = walkdir::WalkDir::new(data.path().join("snapshots/objects"))
+            .min_depth(2)
+            .into_iter()
+            .count();
+        assert_eq!(objects, 2);
+
+        let pruned = store
+            .prune(&keep_two, Utc::now() + Duration::days(31))
+            .unwrap();
+        assert_eq!(pruned.len(), 2);
+        assert!(store.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_run_record_names_the_snapshot() {
+        let workspace = TempDir::new().unwrap();
+        write(workspace.path(), "a.txt", "a\n");
+        let data = TempDir::new().unwrap();
+        let store = SnapshotStore::new(data.path().join("snapshots"));
+        let run_dir = data.path().join("runs/run-3");
+
+        let err = store.for_run(&run_dir, "run-3").unwrap_err();
+        assert!(err.to_string().contains("--snapshot"));
+
+        let snapshot = store
+            .create(workspace.path(), "run-3", &config(SnapshotStrategy::Copy))
+            .unwrap();
+        store.record_run(&run_dir, &snapshot).unwrap();
+        assert_eq!(store.for_run(&run_dir, "run-3").unwrap(), snapshot);
+    }
+}
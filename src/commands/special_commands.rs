@@ -11,6 +11,7 @@
 //! Commands are prefixed with `/` and are case-insensitive.
 
 use crate::chat_mode::{ChatMode, SafetyMode};
+use crate::ui_println;
 use thiserror::Error;
 
 /// Errors that can occur when parsing special commands
@@ -52,6 +53,20 @@ pub enum SpecialCommand {
     /// Shows the current chat mode, safety mode, and their descriptions.
     ShowStatus,
 
+    /// Display per-tool execution metrics for this session
+    ///
+    /// Shows call counts by outcome, time spent, and output size per tool.
+    ShowToolStats,
+
+    /// Reset the per-tool execution metrics
+    ResetToolStats,
+
+    /// Display the session's MCP servers
+    ///
+    /// Shows each server's transport, connection state, tool count, and
+    /// whether it came from the xzatoma config or a project MCP file.
+    McpStatus,
+
     /// Display help information
     ///
     /// Shows all available special commands and their usage.
@@ -94,6 +109,29 @@ pub enum SpecialCommand {
     /// Shows current token usage, context window size, remaining tokens, and usage percentage.
     ContextInfo,
 
+    /// Display what is currently in the model's context
+    ///
+    /// Lists every message that would be sent to the provider with its role,
+    /// a preview, and its token count, followed by per-category subtotals.
+    ContextView,
+
+    /// Print one message from the `/context` listing verbatim
+    ///
+    /// The number is the 1-based index shown by `/context`.
+    ContextFull(usize),
+
+    /// Display mention cache statistics
+    ///
+    /// Shows cached file count and size, hits, misses, and evictions.
+    ContextCache,
+
+    /// Display the session's pruning log
+    ///
+    /// Lists every time messages were summarized or stubbed to save
+    /// context, with the reason, the affected messages, and the token
+    /// totals before and after.
+    PruningLog,
+
     /// Summarize current context and start fresh conversation
     ///
     /// Summarizes all messages in the conversation and resets the history,
@@ -108,6 +146,60 @@ pub enum SpecialCommand {
     /// Use `/subagents on` to enable, `/subagents off` to disable, or `/subagents` to toggle.
     ToggleSubagents(bool), // true = enable, false = disable
 
+    /// Tag the current chat session
+    ///
+    /// Attaches a tag to the conversation so it can be filtered with
+    /// `xzatoma history list --tag <name>`.
+    Tag(String),
+
+    /// Pin a message so it survives pruning and summarization
+    ///
+    /// `/pin` pins the most recent user message; `/pin N` pins message N
+    /// from the `/context` listing.
+    Pin(Option<usize>),
+
+    /// Attach an image to the next message
+    ///
+    /// `/attach <path>` queues a PNG, JPEG, or WebP image that is sent with
+    /// the next prompt. Same as an `@image:<path>` mention in that prompt.
+    Attach(String),
+
+    /// Change the session working directory
+    ///
+    /// `/cd <path>` moves file tools, the terminal, and mentions to `path`,
+    /// resolved against the current session directory. `/cd` alone shows
+    /// the current directory.
+    ChangeDirectory(Option<String>),
+
+    /// Compose the next message in `$EDITOR`
+    ///
+    /// `/edit` opens the editor on an empty draft; `/edit <text>` starts
+    /// from `text`. The saved content is sent as the message, and an empty
+    /// save sends nothing.
+    Edit(Option<String>),
+
+    /// List the code blocks of the last response
+    ///
+    /// Shows each block's number, language, and line count.
+    ListBlocks,
+
+    /// Copy a code block of the last response to the clipboard
+    ///
+    /// `/copy N` copies block N. Without a clipboard the block is printed
+    /// plainly instead.
+    CopyBlock(usize),
+
+    /// Write a code block of the last response to a file
+    ///
+    /// `/apply N <path>` writes block N to `path` through the `write_file`
+    /// tool, after the usual change review.
+    ApplyBlock {
+        /// Block number from `/blocks`, starting at 1
+        number: usize,
+        /// Target file, relative to the session working directory
+        path: String,
+    },
+
     /// Exit the interactive session
     ///
     /// Gracefully closes the chat session.
@@ -151,6 +243,7 @@ pub enum SpecialCommand {
 ///
 /// Other commands:
 /// - `/status` - Show current mode and safety status
+/// - `/stats` - Show per-tool execution metrics (`/stats reset` clears them)
 /// - `/help` - Show help information
 /// - `exit` or `quit` - Exit the session
 ///
@@ -223,6 +316,9 @@ pub fn parse_special_command(input: &str) -> Result<SpecialCommand, CommandError
 
         // Status and help
         "/status" => Ok(SpecialCommand::ShowStatus),
+        "/stats" => Ok(SpecialCommand::ShowToolStats),
+        "/stats reset" => Ok(SpecialCommand::ResetToolStats),
+        "/mcp" | "/mcp status" => Ok(SpecialCommand::McpStatus),
         "/help" | "/?" => Ok(SpecialCommand::Help),
         "/mentions" => Ok(SpecialCommand::Mentions),
 
@@ -264,7 +360,23 @@ pub fn parse_special_command(input: &str) -> Result<SpecialCommand, CommandError
             }
         }
 
-        "/context" | "/context info" => Ok(SpecialCommand::ContextInfo),
+        "/context" => Ok(SpecialCommand::ContextView),
+        "/context info" => Ok(SpecialCommand::ContextInfo),
+        "/context full" => Err(CommandError::MissingArgument {
+            command: "/context full".to_string(),
+            usage: "/context full <message_number>".to_string(),
+        }),
+        input if input.starts_with("/context full ") => {
+            let rest = input[14..].trim();
+            match rest.parse::<usize>() {
+                Ok(number) if number > 0 => Ok(SpecialCommand::ContextFull(number)),
+                _ => Err(CommandError::UnsupportedArgument {
+                    command: "/context full".to_string(),
+                    arg: rest.to_string(),
+                }),
+            }
+        }
+        "/context cache" => Ok(SpecialCommand::ContextCache),
 
         // Handle /context summary with optional model parameter
         input if input.starts_with("/context summary") => {
@@ -310,6 +422,14 @@ pub fn parse_special_command(input: &str) -> Result<SpecialCommand, CommandError
                 arg: subcommand.to_string(),
             })
         }
+        "/pruning" | "/pruning log" => Ok(SpecialCommand::PruningLog),
+        input if input.starts_with("/pruning ") => {
+            let arg = input[9..].trim();
+            Err(CommandError::UnsupportedArgument {
+                command: "/pruning".to_string(),
+                arg: arg.to_string(),
+            })
+        }
         "/auth" => Ok(SpecialCommand::Auth(None)),
         input if input.starts_with("/auth ") => {
             let rest = input[6..].trim();
@@ -354,6 +474,110 @@ pub fn parse_special_command(input: &str) -> Result<SpecialCommand, CommandError
             })
         }
 
+        // Conversation tagging
+        "/tag" => Err(CommandError::MissingArgument {
+            command: "/tag".to_string(),
+            usage: "/tag <name>".to_string(),
+        }),
+        input if input.starts_with("/tag ") => {
+            let tag = input[5..].trim();
+            if tag.is_empty() {
+                Err(CommandError::MissingArgument {
+                    command: "/tag".to_string(),
+                    usage: "/tag <name>".to_string(),
+                })
+            } else {
+                Ok(SpecialCommand::Tag(tag.to_string()))
+            }
+        }
+        "/pin" => Ok(SpecialCommand::Pin(None)),
+        input if input.starts_with("/pin ") => {
+            let rest = input[5..].trim();
+            match rest.parse::<usize>() {
+                Ok(number) if number > 0 => Ok(SpecialCommand::Pin(Some(number))),
+                _ => Err(CommandError::UnsupportedArgument {
+                    command: "/pin".to_string(),
+                    arg: rest.to_string(),
+                }),
+            }
+        }
+
+        // Image attachments keep the path's original case
+        "/attach" => Err(CommandError::MissingArgument {
+            command: "/attach".to_string(),
+            usage: "/attach <path>".to_string(),
+        }),
+        input if input.starts_with("/attach ") => {
+            let path = trimmed["/attach ".len()..].trim();
+            let path = path
+                .strip_prefix('"')
+                .and_then(|p| p.strip_suffix('"'))
+                .unwrap_or(path);
+            Ok(SpecialCommand::Attach(path.to_string()))
+        }
+
+        // Directory paths keep their original case
+        "/cd" => Ok(SpecialCommand::ChangeDirectory(None)),
+        input if input.starts_with("/cd ") => {
+            let path = trimmed["/cd ".len()..].trim();
+            let path = path
+                .strip_prefix('"')
+                .and_then(|p| p.strip_suffix('"'))
+                .unwrap_or(path);
+            Ok(SpecialCommand::ChangeDirectory(Some(path.to_string())))
+        }
+
+        // The draft keeps its original case
+        "/edit" => Ok(SpecialCommand::Edit(None)),
+        input if input.starts_with("/edit ") => {
+            let draft = trimmed["/edit ".len()..].trim();
+            Ok(SpecialCommand::Edit(Some(draft.to_string())))
+        }
+
+        // Code block quick actions; paths keep their original case
+        "/blocks" => Ok(SpecialCommand::ListBlocks),
+        "/copy" => Err(CommandError::MissingArgument {
+            command: "/copy".to_string(),
+            usage: "/copy <block_number>".to_string(),
+        }),
+        input if input.starts_with("/copy ") => {
+            let rest = input[6..].trim();
+            match rest.parse::<usize>() {
+                Ok(number) if number > 0 => Ok(SpecialCommand::CopyBlock(number)),
+                _ => Err(CommandError::UnsupportedArgument {
+                    command: "/copy".to_string(),
+                    arg: rest.to_string(),
+                }),
+            }
+        }
+        "/apply" => Err(CommandError::MissingArgument {
+            command: "/apply".to_string(),
+            usage: "/apply <block_number> <path>".to_string(),
+        }),
+        input if input.starts_with("/apply ") => {
+            let rest = trimmed["/apply ".len()..].trim();
+            let (number, path) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let path = path.trim();
+            let path = path
+                .strip_prefix('"')
+                .and_then(|p| p.strip_suffix('"'))
+                .unwrap_or(path);
+            match number.parse::<usize>() {
+                Ok(number) if number > 0 && !path.is_empty() => Ok(SpecialCommand::ApplyBlock {
+                    number,
+                    path: path.to_string(),
+                }),
+                Ok(number) if number > 0 => Err(CommandError::MissingArgument {
+                    command: "/apply".to_string(),
+                    usage: "/apply <block_number> <path>".to_string(),
+                }),
+                _ => Err(CommandError::UnsupportedArgument {
+                    command: "/apply".to_string(),
+                    arg: number.to_string(),
+                }),
+            }
+        }
+
         // Exit commands
         "exit" | "quit" | "/exit" | "/quit" => Ok(SpecialCommand::Exit),
 
@@ -381,7 +605,7 @@ pub fn parse_special_command(input: &str) -> Result<SpecialCommand, CommandError
 /// print_help();
 /// ```
 pub fn print_help() {
-    println!(
+    ui_println!(
         r#"
 Special Commands for Interactive Chat Mode
 ===========================================
@@ -412,6 +636,10 @@ CONTEXT MENTIONS (Quick Reference):
   @search:"pattern"     - Search for literal text
   @grep:"regex"         - Search with regex patterns
   @url:https://...      - Include web content
+  @image:shot.png       - Send an image to a vision-capable model
+
+IMAGE ATTACHMENTS:
+  /attach <path>  - Send a PNG, JPEG, or WebP image with the next message
 
 MODEL MANAGEMENT:
   /models         - Show help for models subcommands and flags
@@ -421,16 +649,45 @@ MODEL MANAGEMENT:
   /auth [provider] - Start authentication for the provider; use `/auth` for the configured provider
 
 CONTEXT WINDOW MANAGEMENT:
+  /context                   - List messages in context with token counts and subtotals
+  /context full N            - Print message N from the /context listing verbatim
   /context info              - Show context window usage and token statistics
   /context summary           - Summarize conversation and reset context window
   /context summary -m MODEL  - Summarize using a specific model (for cost optimization)
+  /context cache             - Show mention cache size, hits, misses, and evictions
+  /pruning log               - Show what was pruned or stubbed this session, and why
 
 SESSION INFORMATION:
   /status         - Show current mode and safety status
+  /stats          - Show tool call counts, failures, time per tool, tokens
+                    reclaimed by stubbing superseded tool results, and
+                    any tool definitions trimmed to fit the provider
+  /stats reset    - Reset the tool statistics
+  /mcp status     - Show MCP servers, their state, and where they were defined
   /help           - Show this help message
   /?              - Same as /help
   /mentions       - Show detailed context mention help
 
+CONVERSATION HISTORY:
+  /tag <name>     - Tag this conversation (see `xzatoma history list --tag`)
+  /pin            - Pin the last user message so it is never pruned or summarized
+  /pin N          - Pin message N from the /context listing
+
+WORKING DIRECTORY:
+  /cd <path>      - Move file tools, the terminal, and mentions to <path>
+  /cd             - Show the session working directory
+
+COMPOSING MESSAGES:
+  /edit           - Write the next message in $EDITOR; an empty save sends nothing
+  /edit <text>    - Same, starting from <text>
+  line ending in \ - Continue the message on the next line
+  Pasted text with several lines is sent as one message
+
+CODE BLOCKS (from the last response):
+  /blocks         - List code blocks with their language and line count
+  /copy N         - Copy block N to the clipboard, or print it without one
+  /apply N <path> - Write block N to <path> after the usual change review
+
 SESSION CONTROL:
   exit            - Exit interactive mode
   quit            - Same as exit
@@ -461,7 +718,7 @@ NOTES:
 /// print_models_help();
 /// ```
 pub fn print_models_help() {
-    println!(
+    ui_println!(
         r#"
 Models Command - Usage and Examples
 ===================================
@@ -507,7 +764,7 @@ NOTES:
 /// print_mention_help();
 /// ```
 pub fn print_mention_help() {
-    println!(
+    ui_println!(
         r#"
 Context Mentions for XZatoma
 =============================
@@ -634,6 +891,25 @@ Security:
   - Limits content to 1 MB
   - Rate-limited per domain
 
+IMAGE MENTIONS
+==============
+Send images to vision-capable models.
+
+Syntax:
+  @image:path/to/shot.png   - Attach an image to this message
+  @image:"shots/my shot.png" - Paths with spaces need quotes
+  /attach path/to/shot.png  - Attach an image to the next message
+
+Examples:
+  What does this error mean? @image:screenshots/error.png
+  Compare @image:before.png and @image:after.png
+
+Limits:
+  - PNG, JPEG, and WebP only, detected from the file contents
+  - Up to agent.chat.max_image_bytes per image (default 5 MB)
+  - Paths must be inside the working directory
+  - The model must accept images; otherwise the message is not sent
+
 COMBINING MENTIONS
 ==================
 Use multiple mentions in one prompt:
@@ -743,6 +1019,121 @@ mod tests {
         assert_eq!(cmd, SpecialCommand::Auth(Some("copilot".to_string())));
     }
 
+    #[test]
+    fn test_parse_tag_with_name() {
+        let cmd = parse_special_command("/tag Billing").unwrap();
+        assert_eq!(cmd, SpecialCommand::Tag("billing".to_string()));
+    }
+
+    #[test]
+    fn test_parse_tag_without_name_is_error() {
+        assert!(matches!(
+            parse_special_command("/tag"),
+            Err(CommandError::MissingArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_pin_without_number_pins_last_user_message() {
+        assert_eq!(
+            parse_special_command("/pin").unwrap(),
+            SpecialCommand::Pin(None)
+        );
+    }
+
+    #[test]
+    fn test_parse_pin_with_number() {
+        assert_eq!(
+            parse_special_command("/pin 4").unwrap(),
+            SpecialCommand::Pin(Some(4))
+        );
+    }
+
+    #[test]
+    fn test_parse_pin_rejects_invalid_number() {
+        for input in ["/pin 0", "/pin two"] {
+            assert!(matches!(
+                parse_special_command(input),
+                Err(CommandError::UnsupportedArgument { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_attach_keeps_path_case() {
+        assert_eq!(
+            parse_special_command("/attach Screens/Error.PNG").unwrap(),
+            SpecialCommand::Attach("Screens/Error.PNG".to_string())
+        );
+        assert_eq!(
+            parse_special_command("/attach \"my shots/a.png\"").unwrap(),
+            SpecialCommand::Attach("my shots/a.png".to_string())
+        );
+        assert!(matches!(
+            parse_special_command("/attach"),
+            Err(CommandError::MissingArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_change_directory_keeps_path_case() {
+        assert_eq!(
+            parse_special_command("/cd Services/Billing").unwrap(),
+            SpecialCommand::ChangeDirectory(Some("Services/Billing".to_string()))
+        );
+        assert_eq!(
+            parse_special_command("/cd \"my service\"").unwrap(),
+            SpecialCommand::ChangeDirectory(Some("my service".to_string()))
+        );
+        assert_eq!(
+            parse_special_command("/cd").unwrap(),
+            SpecialCommand::ChangeDirectory(None)
+        );
+    }
+
+    #[test]
+    fn test_parse_edit_keeps_draft_case() {
+        assert_eq!(
+            parse_special_command("/edit").unwrap(),
+            SpecialCommand::Edit(None)
+        );
+        assert_eq!(
+            parse_special_command("/edit Explain  README.md").unwrap(),
+            SpecialCommand::Edit(Some("Explain  README.md".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_code_block_actions() {
+        assert_eq!(
+            parse_special_command("/blocks").unwrap(),
+            SpecialCommand::ListBlocks
+        );
+        assert_eq!(
+            parse_special_command("/copy 2").unwrap(),
+            SpecialCommand::CopyBlock(2)
+        );
+        assert_eq!(
+            parse_special_command("/apply 1 \"src/My File.rs\"").unwrap(),
+            SpecialCommand::ApplyBlock {
+                number: 1,
+                path: "src/My File.rs".to_string()
+            }
+        );
+        assert!(matches!(
+            parse_special_command("/apply 1"),
+            Err(CommandError::MissingArgument { .. })
+        ));
+        assert!(matches!(
+            parse_special_command("/copy 0"),
+            Err(CommandError::UnsupportedArgument { .. })
+        ));
+        assert!(matches!(
+            parse_special_command("/apply src/main.rs 1"),
+            Err(CommandError::UnsupportedArgument { .. })
+        ));
+    }
+
     #[test]
     fn test_parse_switch_safety_always_confirm() {
         let cmd = parse_special_command("/safe").unwrap();
@@ -773,6 +1164,30 @@ mod tests {
         assert_eq!(cmd, SpecialCommand::ShowStatus);
     }
 
+    #[test]
+    fn test_parse_tool_stats() {
+        assert_eq!(
+            parse_special_command("/stats").unwrap(),
+            SpecialCommand::ShowToolStats
+        );
+        assert_eq!(
+            parse_special_command("/stats reset").unwrap(),
+            SpecialCommand::ResetToolStats
+        );
+    }
+
+    #[test]
+    fn test_parse_mcp_status() {
+        assert_eq!(
+            parse_special_command("/mcp status").unwrap(),
+            SpecialCommand::McpStatus
+        );
+        assert_eq!(
+            parse_special_command("/MCP").unwrap(),
+            SpecialCommand::McpStatus
+        );
+    }
+
     #[test]
     fn test_parse_help() {
         let cmd = parse_special_command("/help").unwrap();
@@ -902,9 +1317,31 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_context_info() {
+    fn test_parse_context_view() {
         let cmd = parse_special_command("/context").unwrap();
-        assert_eq!(cmd, SpecialCommand::ContextInfo);
+        assert_eq!(cmd, SpecialCommand::ContextView);
+    }
+
+    #[test]
+    fn test_parse_context_full() {
+        let cmd = parse_special_command("/context full 3").unwrap();
+        assert_eq!(cmd, SpecialCommand::ContextFull(3));
+    }
+
+    #[test]
+    fn test_parse_context_full_requires_valid_number() {
+        assert!(matches!(
+            parse_special_command("/context full"),
+            Err(CommandError::MissingArgument { .. })
+        ));
+        assert!(matches!(
+            parse_special_command("/context full 0"),
+            Err(CommandError::UnsupportedArgument { .. })
+        ));
+        assert!(matches!(
+            parse_special_command("/context full abc"),
+            Err(CommandError::UnsupportedArgument { .. })
+        ));
     }
 
     #[test]
@@ -913,6 +1350,28 @@ mod tests {
         assert_eq!(cmd, SpecialCommand::ContextInfo);
     }
 
+    #[test]
+    fn test_parse_context_cache() {
+        let cmd = parse_special_command("/context cache").unwrap();
+        assert_eq!(cmd, SpecialCommand::ContextCache);
+    }
+
+    #[test]
+    fn test_parse_pruning_log() {
+        assert_eq!(
+            parse_special_command("/pruning log").unwrap(),
+            SpecialCommand::PruningLog
+        );
+        assert_eq!(
+            parse_special_command("/pruning").unwrap(),
+            SpecialCommand::PruningLog
+        );
+        assert!(matches!(
+            parse_special_command("/pruning clear"),
+            Err(CommandError::UnsupportedArgument { arg, .. }) if arg == "clear"
+        ));
+    }
+
     #[test]
     fn test_parse_context_summary_no_model() {
         let cmd = parse_special_command("/context summary").unwrap();
@@ -0,0 +1,36 @@
+//! Stored tool output commands
+//!
+//! Prints tool output that the agent replaced with a summary because it was
+//! over `agent.tools.max_output_size`. See
+//! [`crate::tools::overflow_summary`] for how outputs are stored.
+
+use crate::cli::ToolOutputCommand;
+use crate::config::Config;
+use crate::error::Result;
+use crate::paths::Paths;
+use crate::tools::overflow_summary::ToolOutputStore;
+use crate::{ui_print, ui_println};
+
+/// Handle stored tool output commands
+///
+/// # Arguments
+///
+/// * `config` - The loaded configuration; selects the data directory
+/// * `command` - The tool-output subcommand
+///
+/// # Errors
+///
+/// Returns `XzatomaError::FileLoad` when no output is stored under the ID.
+pub fn handle_tool_output(config: &Config, command: ToolOutputCommand) -> Result<()> {
+    let store = ToolOutputStore::from_paths(&Paths::from_config(config)?);
+    match command {
+        ToolOutputCommand::Show { id } => {
+            let output = store.load(id.trim())?;
+            ui_print!("{}", output);
+            if !output.ends_with('\n') {
+                ui_println!();
+            }
+            Ok(())
+        }
+    }
+}
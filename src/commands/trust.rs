@@ -0,0 +1,69 @@
+//! Workspace trust commands
+//!
+//! Adds, removes, and lists the directories recorded in the workspace trust
+//! store. See [`crate::workspace_trust`] for what trust controls.
+
+use std::path::Path;
+
+use colored::Colorize;
+
+use crate::cli::TrustCommand;
+use crate::error::Result;
+use crate::ui_println;
+use crate::workspace_trust::{WorkspaceTrust, WorkspaceTrustStore};
+
+/// Handle workspace trust commands
+///
+/// # Arguments
+///
+/// * `command` - The trust subcommand
+/// * `config_path` - The configuration file path; it is part of the trusted
+///   fingerprint when it lies inside the directory
+pub fn handle_trust(command: TrustCommand, config_path: &str) -> Result<()> {
+    let mut store = WorkspaceTrustStore::load_default()?;
+    match command {
+        TrustCommand::Add { path } => add_workspace(&mut store, &path, config_path),
+        TrustCommand::Remove { path } => remove_workspace(&mut store, &path),
+        TrustCommand::List => {
+            list_workspaces(&store);
+            Ok(())
+        }
+    }
+}
+
+fn add_workspace(store: &mut WorkspaceTrustStore, path: &Path, config_path: &str) -> Result<()> {
+    let config_path = Path::new(config_path);
+    // A relative config path is resolved against the directory being trusted,
+    // the same way it is resolved when a command starts there.
+    let trust = WorkspaceTrust::evaluate(store, path, config_path)?;
+    store.add(&trust)?;
+
+    ui_println!("{} {}", "Trusted".green(), trust.working_dir().display());
+    for file in trust.project_files() {
+        ui_println!("  {}", file.display());
+    }
+    ui_println!("Trust store: {}", store.path().display());
+    Ok(())
+}
+
+fn remove_workspace(store: &mut WorkspaceTrustStore, path: &Path) -> Result<()> {
+    if store.remove(path)? {
+        ui_println!("{} {}", "Removed trust for".yellow(), path.display());
+    } else {
+        ui_println!("{} is not trusted.", path.display());
+    }
+    Ok(())
+}
+
+fn list_workspaces(store: &WorkspaceTrustStore) {
+    if store.workspaces().is_empty() {
+        ui_println!("{}", "No trusted workspaces.".yellow());
+        return;
+    }
+
+    ui_println!("\nTrusted workspaces ({}):", store.path().display());
+    for dir in store.workspaces().keys() {
+        ui_println!("  {}", dir.display());
+    }
+    ui_println!();
+}
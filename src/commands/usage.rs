@@ -0,0 +1,266 @@
+//! Provider usage report
+//!
+//! `xzatoma usage` summarizes the `usage_log` table for one calendar month
+//! (UTC), grouped by model, day, or session. Every aggregate is computed in
+//! SQL; see [`SqliteStorage::usage_by`].
+
+use crate::config::Config;
+use crate::error::{Result, XzatomaError};
+use crate::format::{format_cost, format_count};
+use crate::paths::Paths;
+use crate::providers::budget::{month_start, next_month};
+use crate::storage::types::{UsageAggregate, UsageGrouping};
+use crate::storage::SqliteStorage;
+use crate::{ui, ui_println};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use colored::Colorize;
+use prettytable::{format, Table};
+use serde::Serialize;
+
+/// Usage of one month, as printed by `xzatoma usage`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UsageReport {
+    /// Reported month, `YYYY-MM`
+    pub month: String,
+    /// Column the rows are grouped by
+    pub grouping: UsageGrouping,
+    /// One row per model, day, or session
+    pub rows: Vec<UsageAggregate>,
+    /// Totals across every row
+    pub total: UsageAggregate,
+    /// `budget.monthly_cost_limit`, if set
+    pub limit: Option<f64>,
+}
+
+/// Handle the usage command
+///
+/// # Arguments
+///
+/// * `config` - Loaded configuration; selects the database and the limit
+/// * `month` - Month to report as `YYYY-MM`, the current month when `None`
+/// * `by` - Grouping: `model`, `day`, or `session`
+/// * `json` - Print the report as JSON
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Config` for an invalid month or grouping, and a
+/// storage error if the usage log cannot be read.
+pub fn handle_usage(config: &Config, month: Option<String>, by: &str, json: bool) -> Result<()> {
+    let storage = SqliteStorage::new(&Paths::from_config(config)?)?;
+    let start = match month.as_deref() {
+        Some(month) => parse_month(month)?,
+        None => month_start(Utc::now()),
+    };
+    let report = usage_report(
+        &storage,
+        start,
+        parse_grouping(by)?,
+        config.budget.monthly_cost_limit,
+    )?;
+
+    if json {
+        ui::json(&report)?;
+    } else {
+        print_report(&report);
+    }
+    Ok(())
+}
+
+/// Builds the report for the month starting at `start`
+fn usage_report(
+    storage: &SqliteStorage,
+    start: DateTime<Utc>,
+    grouping: UsageGrouping,
+    limit: Option<f64>,
+) -> Result<UsageReport> {
+    let end = next_month(start);
+    Ok(UsageReport {
+        month: start.format("%Y-%m").to_string(),
+        grouping,
+        rows: storage.usage_by(start, end, grouping)?,
+        total: storage.usage_total(start, end)?,
+        limit,
+    })
+}
+
+/// Parse a month such as `2025-01` into the start of that month
+fn parse_month(value: &str) -> Result<DateTime<Utc>> {
+    let invalid = || {
+        XzatomaError::Config(format!(
+            "Invalid month '{}': expected YYYY-MM (e.g. 2025-01)",
+            value
+        ))
+    };
+    let date = NaiveDate::parse_from_str(&format!("{}-01", value.trim()), "%Y-%m-%d")
+        .map_err(|_| invalid())?;
+    let start = date.and_hms_opt(0, 0, 0).ok_or_else(invalid)?;
+    Ok(Utc.from_utc_datetime(&start))
+}
+
+/// Parse the `--by` value
+fn parse_grouping(value: &str) -> Result<UsageGrouping> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "model" => Ok(UsageGrouping::Model),
+        "day" => Ok(UsageGrouping::Day),
+        "session" => Ok(UsageGrouping::Session),
+        _ => Err(XzatomaError::Config(format!(
+            "Invalid grouping '{}': expected model, day, or session",
+            value
+        ))),
+    }
+}
+
+/// Print the report as a table followed by the month's total
+fn print_report(report: &UsageReport) {
+    if report.rows.is_empty() {
+        ui_println!(
+            "{}",
+            format!("No provider usage recorded for {}.", report.month).yellow()
+        );
+        return;
+    }
+
+    let label = match report.grouping {
+        UsageGrouping::Model => "Model",
+        UsageGrouping::Day => "Day",
+        UsageGrouping::Session => "Session",
+    };
+    ui_println!("\nProvider usage for {}:", report.month);
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+    table.add_row(prettytable::row![
+        label.bold(),
+        "Requests".bold(),
+        "Prompt Tokens".bold(),
+        "Completion Tokens".bold(),
+        "Est. Cost".bold()
+    ]);
+    for row in &report.rows {
+        let key: String = match report.grouping {
+            UsageGrouping::Session => row.key.chars().take(8).collect(),
+            _ => row.key.clone(),
+        };
+        table.add_row(prettytable::row![
+            key,
+            r->format_count(row.requests),
+            r->format_count(row.prompt_tokens),
+            r->format_count(row.completion_tokens),
+            r->format_cost(row.estimated_cost)
+        ]);
+    }
+    table.printstd();
+
+    ui_println!("  {}", total_line(&report.total));
+    if let Some(limit) = report.limit {
+        ui_println!(
+            "  Budget: {} of {} ({:.0}%)",
+            format_cost(report.total.estimated_cost),
+            format_cost(limit),
+            report.total.estimated_cost / limit * 100.0
+        );
+    }
+    ui_println!();
+}
+
+/// The month's totals, as printed below the table
+fn total_line(total: &UsageAggregate) -> String {
+    format!(
+        "Total: {} requests, {} prompt + {} completion tokens, {}",
+        format_count(total.requests),
+        format_count(total.prompt_tokens),
+        format_count(total.completion_tokens),
+        format_cost(total.estimated_cost)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::types::UsageRecord;
+    use tempfile::tempdir;
+
+    fn record(timestamp: &str, model: &str, cost: f64) -> UsageRecord {
+        UsageRecord {
+            timestamp: DateTime::parse_from_rfc3339(timestamp)
+                .unwrap()
+                .with_timezone(&Utc),
+            provider: "openai".to_string(),
+            model: model.to_string(),
+            session_id: "session-1".to_string(),
+            prompt_tokens: 200,
+            completion_tokens: 50,
+            estimated_cost: cost,
+        }
+    }
+
+    #[test]
+    fn test_usage_report_covers_only_the_requested_month() {
+        let dir = tempdir().unwrap();
+        let storage = SqliteStorage::new_with_path(dir.path().join("history.db")).unwrap();
+        storage
+            .save_usage_records(&[
+                record("2025-01-31T23:30:00Z", "gpt-5", 3.0),
+                record("2025-02-01T00:30:00Z", "gpt-5", 1.0),
+                record("2025-02-14T10:00:00Z", "gpt-5-mini", 0.5),
+            ])
+            .unwrap();
+
+        let report = usage_report(
+            &storage,
+            parse_month("2025-02").unwrap(),
+            parse_grouping("day").unwrap(),
+            Some(10.0),
+        )
+        .unwrap();
+
+        assert_eq!(report.month, "2025-02");
+        let days: Vec<&str> = report.rows.iter().map(|row| row.key.as_str()).collect();
+        assert_eq!(days, vec!["2025-02-01", "2025-02-14"]);
+        assert_eq!(report.total.requests, 2);
+        assert_eq!(report.total.estimated_cost, 1.5);
+    }
+
+    #[test]
+    fn test_total_line_is_formatted_but_json_keeps_numbers() {
+        let total = UsageAggregate {
+            key: String::new(),
+            requests: 1_200,
+            prompt_tokens: 123_456,
+            completion_tokens: 999,
+            estimated_cost: 0.0312,
+        };
+
+        assert_eq!(
+            total_line(&total),
+            "Total: 1,200 requests, 123,456 prompt + 999 completion tokens, $0.03"
+        );
+
+        let report = UsageReport {
+            month: "2025-02".to_string(),
+            grouping: UsageGrouping::Model,
+            rows: Vec::new(),
+            total,
+            limit: None,
+        };
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["total"]["prompt_tokens"], 123_456);
+        assert_eq!(json["total"]["estimated_cost"], 0.0312);
+    }
+
+    #[test]
+    fn test_invalid_month_and_grouping_are_rejected() {
+        assert!(matches!(
+            parse_month("2025-13"),
+            Err(XzatomaError::Config(_))
+        ));
+        assert!(matches!(
+            parse_month("January"),
+            Err(XzatomaError::Config(_))
+        ));
+        assert!(matches!(
+            parse_grouping("week"),
+            Err(XzatomaError::Config(_))
+        ));
+        assert_eq!(parse_grouping("Session").unwrap(), UsageGrouping::Session);
+    }
+}
@@ -5,10 +5,14 @@
 
 use crate::error::{Result, XzatomaError};
 use crate::mcp::config::McpConfig;
+use crate::prompts::PromptStyle;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
+/// Configuration file read when `--config` is not given
+pub const DEFAULT_CONFIG_PATH: &str = "config/config.yaml";
+
 /// Main configuration structure for XZatoma
 ///
 /// This structure holds all configuration needed for the agent,
@@ -31,6 +35,27 @@ pub struct Config {
     /// Skills discovery and parsing configuration
     #[serde(default)]
     pub skills: SkillsConfig,
+    /// Conversation storage configuration
+    #[serde(default)]
+    pub storage: StorageConfig,
+    /// Structured telemetry event output
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    /// Where Copilot and MCP credentials are stored
+    #[serde(default)]
+    pub credentials: CredentialsConfig,
+    /// Embedding index for `@semantic` mentions
+    #[serde(default)]
+    pub semantic: SemanticConfig,
+    /// Data, cache, and state directory locations
+    #[serde(default)]
+    pub paths: PathsConfig,
+    /// Monthly provider spending limit
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    /// Behavior of `xzatoma run`
+    #[serde(default)]
+    pub run: RunConfig,
 }
 
 /// Provider configuration
@@ -59,6 +84,272 @@ pub struct ProviderConfig {
     /// OpenAI (and OpenAI-compatible) provider configuration
     #[serde(default)]
     pub openai: OpenAIConfig,
+
+    /// On-disk cache of completed provider responses
+    #[serde(default)]
+    pub cache: ProviderCacheConfig,
+
+    /// Per-model adjustments of requests and responses
+    #[serde(default)]
+    pub quirks: ProviderQuirksConfig,
+
+    /// Process-wide request pacing shared by every provider instance
+    #[serde(default)]
+    pub rate_limits: ProviderRateLimitsConfig,
+}
+
+/// Provider response cache configuration
+///
+/// When enabled, completed responses are stored on disk keyed by a hash of
+/// the provider, model, messages, tools, and temperature, and identical
+/// requests are answered from the cache without a network call. Streaming
+/// requests and requests with a temperature above zero bypass the cache
+/// unless `force` is set. The global `--no-cache` flag disables the cache for
+/// one invocation.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::ProviderCacheConfig;
+///
+/// let cache = ProviderCacheConfig::default();
+/// assert!(!cache.enabled);
+/// assert_eq!(cache.max_size_mb, 100);
+/// assert_eq!(cache.ttl_seconds, 7 * 24 * 60 * 60);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProviderCacheConfig {
+    /// Serve identical requests from the cache
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Cache directory; defaults to `provider_cache` in the cache directory
+    #[serde(default)]
+    pub dir: Option<String>,
+
+    /// Total size the cache is trimmed to, oldest entries first
+    #[serde(default = "default_provider_cache_max_size_mb")]
+    pub max_size_mb: u64,
+
+    /// Entries older than this many seconds are treated as misses and removed
+    #[serde(default = "default_provider_cache_ttl_seconds")]
+    pub ttl_seconds: u64,
+
+    /// Also cache requests that would normally bypass the cache
+    #[serde(default)]
+    pub force: bool,
+}
+
+fn default_provider_cache_max_size_mb() -> u64 {
+    100
+}
+
+fn default_provider_cache_ttl_seconds() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+impl Default for ProviderCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: None,
+            max_size_mb: default_provider_cache_max_size_mb(),
+            ttl_seconds: default_provider_cache_ttl_seconds(),
+            force: false,
+        }
+    }
+}
+
+/// Model quirks configuration
+///
+/// Some models need small adjustments to work well with the agent, such as
+/// removing a "Sure, here's..." opener from their answers. Each quirk is
+/// switched on for the models whose name matches a glob pattern. The
+/// built-in table covers common Ollama models; the entries in `models` are
+/// applied after it, in order, and every field an entry sets overrides the
+/// value from earlier matches. Patterns match the lowercase model name.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::ProviderQuirksConfig;
+///
+/// let yaml = r#"
+/// models:
+///   - pattern: "llama3*"
+///     strip_preamble: false
+///   - pattern: "my-finetune*"
+///     tool_call_nudge: true
+///     max_temperature: 0.4
+/// "#;
+/// let quirks: ProviderQuirksConfig = serde_yaml::from_str(yaml).unwrap();
+/// assert!(quirks.enabled);
+/// assert!(quirks.builtin);
+/// assert_eq!(quirks.models.len(), 2);
+/// assert_eq!(quirks.models[0].strip_preamble, Some(false));
+/// assert_eq!(quirks.models[1].unwrap_code_fence, None);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProviderQuirksConfig {
+    /// Apply model quirks at all
+    #[serde(default = "default_quirks_enabled")]
+    pub enabled: bool,
+
+    /// Start from the built-in quirks table
+    #[serde(default = "default_quirks_builtin")]
+    pub builtin: bool,
+
+    /// Quirks for model name patterns, applied after the built-in table
+    #[serde(default)]
+    pub models: Vec<ModelQuirksConfig>,
+}
+
+fn default_quirks_enabled() -> bool {
+    true
+}
+
+fn default_quirks_builtin() -> bool {
+    true
+}
+
+impl Default for ProviderQuirksConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_quirks_enabled(),
+            builtin: default_quirks_builtin(),
+            models: Vec::new(),
+        }
+    }
+}
+
+/// Quirks for the models matching one name pattern
+///
+/// Unset fields keep the value from earlier matching entries.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelQuirksConfig {
+    /// Glob pattern matched against the lowercase model name, e.g. `qwen*`
+    pub pattern: String,
+
+    /// Remove leading filler such as "Sure, here's the file:" from answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strip_preamble: Option<bool>,
+
+    /// Unwrap an answer that is one plain code fence when no tools were offered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unwrap_code_fence: Option<bool>,
+
+    /// Repeat the tool-use rules at the end of the last user message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_tool_instructions: Option<bool>,
+
+    /// Highest sampling temperature passed on to the provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_temperature: Option<f32>,
+
+    /// Remind the model once to use its tools when it answers in prose
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_nudge: Option<bool>,
+}
+
+/// Process-wide pacing of provider requests
+///
+/// Every provider instance in the process, including those built for
+/// subagents and parallel plan steps, shares one governor per provider and
+/// host (and, for OpenAI, API key). Its limits come from the entry for the
+/// provider type; unset limits do not apply. A rate-limit response from the
+/// provider pauses the whole governor for the `Retry-After` time, or for
+/// `backoff_seconds` when the provider gave none.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::ProviderRateLimitsConfig;
+///
+/// let yaml = r#"
+/// copilot:
+///   requests_per_minute: 30
+///   max_concurrent: 2
+/// "#;
+/// let limits: ProviderRateLimitsConfig = serde_yaml::from_str(yaml).unwrap();
+/// assert!(limits.enabled);
+/// assert_eq!(limits.for_provider("copilot").requests_per_minute, Some(30));
+/// assert_eq!(limits.for_provider("ollama").max_concurrent, None);
+/// assert_eq!(limits.backoff_seconds, 10);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProviderRateLimitsConfig {
+    /// Route provider requests through the shared governor at all
+    #[serde(default = "default_rate_limits_enabled")]
+    pub enabled: bool,
+
+    /// Limits for GitHub Copilot
+    #[serde(default)]
+    pub copilot: RateLimitConfig,
+
+    /// Limits for Ollama, on top of `provider.ollama.max_concurrent_requests`
+    #[serde(default)]
+    pub ollama: RateLimitConfig,
+
+    /// Limits for OpenAI and OpenAI-compatible servers
+    #[serde(default)]
+    pub openai: RateLimitConfig,
+
+    /// Pause after a rate-limit response that carried no `Retry-After`
+    #[serde(default = "default_rate_limit_backoff_seconds")]
+    pub backoff_seconds: u64,
+
+    /// Longest time a request waits for the governor before failing
+    #[serde(default = "default_rate_limit_max_wait_seconds")]
+    pub max_wait_seconds: u64,
+}
+
+fn default_rate_limits_enabled() -> bool {
+    true
+}
+
+fn default_rate_limit_backoff_seconds() -> u64 {
+    10
+}
+
+fn default_rate_limit_max_wait_seconds() -> u64 {
+    300
+}
+
+impl Default for ProviderRateLimitsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_rate_limits_enabled(),
+            copilot: RateLimitConfig::default(),
+            ollama: RateLimitConfig::default(),
+            openai: RateLimitConfig::default(),
+            backoff_seconds: default_rate_limit_backoff_seconds(),
+            max_wait_seconds: default_rate_limit_max_wait_seconds(),
+        }
+    }
+}
+
+impl ProviderRateLimitsConfig {
+    /// Returns the limits for a provider type; unknown types have none
+    pub fn for_provider(&self, provider_type: &str) -> RateLimitConfig {
+        match provider_type {
+            "copilot" => self.copilot,
+            "ollama" => self.ollama,
+            "openai" => self.openai,
+            _ => RateLimitConfig::default(),
+        }
+    }
+}
+
+/// Request limits for one provider type
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RateLimitConfig {
+    /// Most requests started in any minute, spread evenly across it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+
+    /// Most requests running at once across the process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent: Option<usize>,
 }
 
 /// GitHub Copilot provider configuration
@@ -104,6 +395,25 @@ pub struct CopilotConfig {
     /// their reasoning process in the response. Defaults to false.
     #[serde(default = "default_include_reasoning")]
     pub include_reasoning: bool,
+
+    /// Total timeout in seconds for a single non-streaming completion request.
+    ///
+    /// Defaults to 120 seconds. Never exceeds the time left in
+    /// `agent.timeout_seconds`.
+    ///
+    /// Set via the `XZATOMA_COPILOT_REQUEST_TIMEOUT` environment variable.
+    #[serde(default = "default_copilot_request_timeout")]
+    pub request_timeout_seconds: u64,
+
+    /// Seconds a streaming response may go without sending any data before
+    /// it is abandoned. Defaults to 60 seconds.
+    #[serde(default = "default_stream_idle_timeout")]
+    pub stream_idle_timeout_seconds: u64,
+
+    /// Seconds before the Copilot session token expires at which it is
+    /// refreshed ahead of the next request. Defaults to 300 seconds.
+    #[serde(default = "default_copilot_token_refresh_margin")]
+    pub token_refresh_margin_seconds: u64,
 }
 
 fn default_copilot_model() -> String {
@@ -122,6 +432,25 @@ fn default_include_reasoning() -> bool {
     false
 }
 
+/// Default total timeout in seconds for a single non-streaming completion
+/// request to a remote provider.
+pub const DEFAULT_COMPLETION_REQUEST_TIMEOUT_SECONDS: u64 = 120;
+
+/// Default number of seconds a streaming response may stay silent.
+pub const DEFAULT_STREAM_IDLE_TIMEOUT_SECONDS: u64 = 60;
+
+fn default_copilot_request_timeout() -> u64 {
+    DEFAULT_COMPLETION_REQUEST_TIMEOUT_SECONDS
+}
+
+fn default_stream_idle_timeout() -> u64 {
+    DEFAULT_STREAM_IDLE_TIMEOUT_SECONDS
+}
+
+fn default_copilot_token_refresh_margin() -> u64 {
+    300
+}
+
 impl Default for CopilotConfig {
     fn default() -> Self {
         Self {
@@ -131,6 +460,9 @@ impl Default for CopilotConfig {
             enable_endpoint_fallback: default_enable_endpoint_fallback(),
             reasoning_effort: None,
             include_reasoning: default_include_reasoning(),
+            request_timeout_seconds: default_copilot_request_timeout(),
+            stream_idle_timeout_seconds: default_stream_idle_timeout(),
+            token_refresh_margin_seconds: default_copilot_token_refresh_margin(),
         }
     }
 }
@@ -163,6 +495,27 @@ pub struct OllamaConfig {
     /// Set via the `XZATOMA_OLLAMA_REQUEST_TIMEOUT` environment variable.
     #[serde(default = "default_ollama_request_timeout")]
     pub request_timeout_seconds: u64,
+
+    /// Maximum number of completion requests sent to this host at once.
+    ///
+    /// Requests beyond the limit wait on the client, in order, instead of
+    /// piling up on the server. The limit is shared by every Ollama provider
+    /// in the process that points at the same host, including the ones
+    /// subagents create. Match it to the server's `OLLAMA_NUM_PARALLEL`.
+    ///
+    /// Defaults to 2. Set via the `XZATOMA_OLLAMA_MAX_CONCURRENT_REQUESTS`
+    /// environment variable.
+    #[serde(default = "default_ollama_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+
+    /// Forces the system prompt style regardless of model size detection.
+    ///
+    /// When unset, models of up to 4B parameters (per `/api/show`) get the
+    /// concise prompts and larger models get the full prompts.
+    ///
+    /// Set via the `XZATOMA_OLLAMA_PROMPT_STYLE` environment variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_style: Option<PromptStyle>,
 }
 
 fn default_ollama_host() -> String {
@@ -177,12 +530,18 @@ fn default_ollama_request_timeout() -> u64 {
     600
 }
 
+fn default_ollama_max_concurrent_requests() -> usize {
+    2
+}
+
 impl Default for OllamaConfig {
     fn default() -> Self {
         Self {
             host: default_ollama_host(),
             model: default_ollama_model(),
             request_timeout_seconds: default_ollama_request_timeout(),
+            max_concurrent_requests: default_ollama_max_concurrent_requests(),
+            prompt_style: None,
         }
     }
 }
@@ -206,6 +565,7 @@ impl Default for OllamaConfig {
 ///     enable_streaming: true,
 ///     request_timeout_seconds: 600,
 ///     reasoning_effort: None,
+///     stream_idle_timeout_seconds: 60,
 /// };
 /// assert_eq!(config.model, "gpt-4o-mini");
 /// assert_eq!(config.base_url, "https://api.openai.com/v1");
@@ -263,13 +623,18 @@ pub struct OpenAIConfig {
     /// take several minutes to generate a long response; set this to a value
     /// larger than your expected worst-case generation time.
     ///
-    /// Defaults to 600 seconds (10 minutes). The agent-level
-    /// `agent.timeout_seconds` provides a separate overall budget.
+    /// Defaults to 120 seconds. Never exceeds the time left in
+    /// `agent.timeout_seconds`.
     ///
     /// Set via the `XZATOMA_OPENAI_REQUEST_TIMEOUT` environment variable.
     #[serde(default = "default_openai_request_timeout")]
     pub request_timeout_seconds: u64,
 
+    /// Seconds a streaming response may go without sending any data before
+    /// it is abandoned. Defaults to 60 seconds.
+    #[serde(default = "default_stream_idle_timeout")]
+    pub stream_idle_timeout_seconds: u64,
+
     /// Reasoning effort level for OpenAI o-series reasoning models.
     ///
     /// Accepted values: `"low"`, `"medium"`, `"high"`. Set to `None` to use
@@ -298,7 +663,7 @@ fn default_openai_streaming() -> bool {
 }
 
 fn default_openai_request_timeout() -> u64 {
-    600
+    DEFAULT_COMPLETION_REQUEST_TIMEOUT_SECONDS
 }
 
 impl Default for OpenAIConfig {
@@ -310,6 +675,7 @@ impl Default for OpenAIConfig {
             organization_id: None,
             enable_streaming: default_openai_streaming(),
             request_timeout_seconds: default_openai_request_timeout(),
+            stream_idle_timeout_seconds: default_stream_idle_timeout(),
             reasoning_effort: None,
         }
     }
@@ -345,6 +711,47 @@ pub struct AgentConfig {
     /// Subagent delegation settings
     #[serde(default)]
     pub subagent: SubagentConfig,
+
+    /// Disable every network feature except the local Ollama provider
+    ///
+    /// Also enabled by the global `--offline` flag.
+    #[serde(default)]
+    pub offline: bool,
+
+    /// Guarantee that the agent changes nothing and runs no commands
+    ///
+    /// Mutating tools refuse every call, the terminal is not registered, and
+    /// only MCP tools listed in their server's `read_safe_tools` are
+    /// available. Also enabled by the global `--read-only` flag.
+    #[serde(default)]
+    pub read_only: bool,
+
+    /// Allow `@url:` mentions and the fetch tool in read-only mode
+    ///
+    /// Also enabled by the global `--allow-fetch` flag.
+    #[serde(default)]
+    pub read_only_allow_fetch: bool,
+
+    /// Size check run before sending a large prompt
+    #[serde(default)]
+    pub preflight: PreflightConfig,
+
+    /// Per-turn recording of `run` executions for `xzatoma debug`
+    #[serde(default)]
+    pub recording: RecordingConfig,
+
+    /// Re-read files changed outside the session and replace the stale
+    /// reads in the conversation, instead of only noting the change
+    #[serde(default)]
+    pub auto_refresh_context: bool,
+
+    /// Largest file re-read by `auto_refresh_context`, in bytes
+    #[serde(default = "default_auto_refresh_max_bytes")]
+    pub auto_refresh_max_bytes: u64,
+}
+
+fn default_auto_refresh_max_bytes() -> u64 {
+    64 * 1024
 }
 
 fn default_max_turns() -> usize {
@@ -365,6 +772,123 @@ impl Default for AgentConfig {
             terminal: TerminalConfig::default(),
             chat: ChatConfig::default(),
             subagent: SubagentConfig::default(),
+            offline: false,
+            read_only: false,
+            read_only_allow_fetch: false,
+            preflight: PreflightConfig::default(),
+            recording: RecordingConfig::default(),
+            auto_refresh_context: false,
+            auto_refresh_max_bytes: default_auto_refresh_max_bytes(),
+        }
+    }
+}
+
+/// Size check run before sending a prompt augmented with mentions
+///
+/// A prompt is large when the prompt plus the conversation exceeds
+/// `context_fraction` of the context window, or `max_tokens` when set.
+/// Interactive chat then shows a breakdown and asks before sending; `run`
+/// prints a warning, or fails with `--strict-budget`.
+///
+/// `pricing` maps model names to the USD cost of one million input tokens
+/// and is used to estimate the cost of the request. Models without an
+/// entry show no cost.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::PreflightConfig;
+///
+/// let yaml = "max_tokens: 20000\npricing:\n  gpt-5-mini: 0.25\n";
+/// let preflight: PreflightConfig = serde_yaml::from_str(yaml).unwrap();
+/// assert!(preflight.enabled);
+/// assert_eq!(preflight.context_fraction, 0.5);
+/// assert_eq!(preflight.max_tokens, Some(20000));
+/// assert_eq!(preflight.pricing["gpt-5-mini"], 0.25);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreflightConfig {
+    /// Enable the preflight check (default: true)
+    #[serde(default = "default_preflight_enabled")]
+    pub enabled: bool,
+
+    /// Fraction of the context window above which a prompt is large (0.0-1.0, default: 0.5)
+    #[serde(default = "default_preflight_context_fraction")]
+    pub context_fraction: f32,
+
+    /// Token count above which a prompt is large, regardless of the context window
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+
+    /// USD per million input tokens, keyed by model name
+    #[serde(default)]
+    pub pricing: std::collections::HashMap<String, f64>,
+
+    /// Make `run` fail instead of warning on a large prompt
+    ///
+    /// Also enabled by `run --strict-budget`.
+    #[serde(default)]
+    pub strict: bool,
+}
+
+fn default_preflight_enabled() -> bool {
+    true
+}
+
+fn default_preflight_context_fraction() -> f32 {
+    0.5
+}
+
+impl Default for PreflightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_preflight_enabled(),
+            context_fraction: default_preflight_context_fraction(),
+            max_tokens: None,
+            pricing: std::collections::HashMap::new(),
+            strict: false,
+        }
+    }
+}
+
+/// Per-turn recording of `run` executions
+///
+/// When enabled, every provider request of a run is stored in the history
+/// database with its messages, tool definitions, response, tool calls, and
+/// timing, for inspection with `xzatoma debug <run-id>`. Strings are cut
+/// with a truncation marker until a turn fits in `max_turn_bytes`.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::RecordingConfig;
+///
+/// let recording: RecordingConfig = serde_yaml::from_str("enabled: true").unwrap();
+/// assert!(recording.enabled);
+/// assert_eq!(recording.max_turn_bytes, 262_144);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordingConfig {
+    /// Record run turns (default: false)
+    ///
+    /// Also enabled by `run --record`.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Largest serialized size of one recorded turn, in bytes (default: 262144)
+    #[serde(default = "default_recording_max_turn_bytes")]
+    pub max_turn_bytes: usize,
+}
+
+fn default_recording_max_turn_bytes() -> usize {
+    256 * 1024
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_turn_bytes: default_recording_max_turn_bytes(),
         }
     }
 }
@@ -891,57 +1415,555 @@ impl Default for SkillsConfig {
     }
 }
 
-/// Subagent delegation configuration
+/// Conversation storage configuration
 ///
-/// Settings for spawning and managing recursive agent instances
-/// with task delegation and controlled resource usage.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SubagentConfig {
-    /// Maximum recursion depth for nested subagents
-    ///
-    /// Root agent is depth 0, first subagent spawned is depth 1.
-    /// Prevents infinite recursion and stack overflow.
-    /// - depth < max_depth: Allow spawning
-    /// - depth >= max_depth: Return error
-    #[serde(default = "default_subagent_max_depth")]
-    pub max_depth: usize,
+/// Settings for the SQLite-backed conversation history database.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StorageConfig {
+    /// Retention limits applied when pruning stored conversations
+    #[serde(default)]
+    pub retention: RetentionConfig,
 
-    /// Default maximum turns per subagent execution
+    /// Stable key to save conversations under, usually set by `--session-id`
     ///
-    /// Used when subagent input does not specify max_turns.
-    /// Limits conversation turns to prevent runaway execution.
-    #[serde(default = "default_subagent_max_turns")]
-    pub default_max_turns: usize,
+    /// See [`crate::storage::session_conversation_id`]. `run` saves its
+    /// conversation only when this is set.
+    #[serde(default)]
+    pub session_id: Option<String>,
 
-    /// Maximum output size in bytes before truncation
-    ///
-    /// Prevents subagent output from expanding context window excessively.
-    /// Output exceeding this size will be truncated with notice.
-    #[serde(default = "default_subagent_output_max_size")]
-    pub output_max_size: usize,
+    /// What saving does when `session_id` is already stored
+    #[serde(default)]
+    pub if_exists: crate::storage::types::IfExists,
+}
 
-    /// Enable subagent execution telemetry
-    ///
-    /// When true, logs structured telemetry events (spawn, complete, error, etc).
-    /// Set to false to disable telemetry logging.
-    #[serde(default = "default_subagent_telemetry_enabled")]
-    pub telemetry_enabled: bool,
+/// Semantic workspace search configuration
+///
+/// Embeddings come from the Ollama server in `provider.ollama.host`, whatever
+/// chat provider is selected. The index is built with `xzatoma index build`
+/// and queried by `@semantic:"..."` mentions.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::SemanticConfig;
+///
+/// let semantic: SemanticConfig = serde_yaml::from_str("top_k: 3\n").unwrap();
+/// assert_eq!(semantic.top_k, 3);
+/// assert_eq!(semantic.embedding_model, "nomic-embed-text");
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SemanticConfig {
+    /// Ollama embedding model (default: `nomic-embed-text`)
+    #[serde(default = "default_embedding_model")]
+    pub embedding_model: String,
+
+    /// Directory holding index files (default: `semantic_index` in the data
+    /// directory)
+    #[serde(default)]
+    pub index_dir: Option<String>,
 
-    /// Enable conversation persistence for debugging
-    ///
-    /// When true, saves subagent conversations to persistent storage
-    /// for replay and debugging. Phase 4 feature, currently ignored.
-    #[serde(default = "default_subagent_persistence_enabled")]
-    pub persistence_enabled: bool,
+    /// Lines per indexed chunk
+    #[serde(default = "default_semantic_chunk_lines")]
+    pub chunk_lines: usize,
 
-    /// Path to conversation database for persistence
-    ///
-    /// Used when persistence_enabled is true. Specifies the location
-    /// of the sled database storing conversation history.
-    #[serde(default = "default_persistence_path")]
-    pub persistence_path: String,
+    /// Lines shared by consecutive chunks
+    #[serde(default = "default_semantic_chunk_overlap")]
+    pub chunk_overlap: usize,
 
-    /// Maximum total subagent executions per session
+    /// Files larger than this are not indexed
+    #[serde(default = "default_semantic_max_file_size_bytes")]
+    pub max_file_size_bytes: u64,
+
+    /// Chunks added to the prompt for each `@semantic` mention
+    #[serde(default = "default_semantic_top_k")]
+    pub top_k: usize,
+}
+
+fn default_embedding_model() -> String {
+    "nomic-embed-text".to_string()
+}
+
+fn default_semantic_chunk_lines() -> usize {
+    40
+}
+
+fn default_semantic_chunk_overlap() -> usize {
+    10
+}
+
+fn default_semantic_max_file_size_bytes() -> u64 {
+    1024 * 1024
+}
+
+fn default_semantic_top_k() -> usize {
+    5
+}
+
+impl Default for SemanticConfig {
+    fn default() -> Self {
+        Self {
+            embedding_model: default_embedding_model(),
+            index_dir: None,
+            chunk_lines: default_semantic_chunk_lines(),
+            chunk_overlap: default_semantic_chunk_overlap(),
+            max_file_size_bytes: default_semantic_max_file_size_bytes(),
+            top_k: default_semantic_top_k(),
+        }
+    }
+}
+
+/// Directory locations for everything xzatoma writes
+///
+/// Unset directories use the platform defaults. `XZATOMA_DATA_DIR`,
+/// `XZATOMA_CACHE_DIR`, and `XZATOMA_STATE_DIR` take precedence over these
+/// settings. See [`crate::paths`] for which files live in each directory.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::PathsConfig;
+///
+/// let paths: PathsConfig = serde_yaml::from_str("data_dir: /srv/xzatoma\n").unwrap();
+/// assert_eq!(paths.data_dir.as_deref(), Some("/srv/xzatoma"));
+/// assert!(paths.cache_dir.is_none());
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PathsConfig {
+    /// Directory for the history database, credentials, and trust stores
+    #[serde(default)]
+    pub data_dir: Option<String>,
+
+    /// Directory for the provider response cache
+    #[serde(default)]
+    pub cache_dir: Option<String>,
+
+    /// Directory for the audit log
+    #[serde(default)]
+    pub state_dir: Option<String>,
+}
+
+/// Monthly spending limit on provider requests
+///
+/// Every provider request is recorded in the `usage_log` table of the
+/// history database with an estimated cost. Input tokens are priced with
+/// `agent.preflight.pricing` and output tokens with `completion_pricing`,
+/// which falls back to the input price. Models without a price cost 0.
+///
+/// When the month-to-date cost reaches `warn_at_percent` of
+/// `monthly_cost_limit`, chat shows a warning. Past the limit, provider
+/// requests are refused unless `allow_overrun` is set or `--ignore-budget`
+/// is passed. Months are calendar months in UTC.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::BudgetConfig;
+///
+/// let yaml = "monthly_cost_limit: 50.0\ncompletion_pricing:\n  gpt-5-mini: 2.0\n";
+/// let budget: BudgetConfig = serde_yaml::from_str(yaml).unwrap();
+/// assert_eq!(budget.monthly_cost_limit, Some(50.0));
+/// assert_eq!(budget.warn_at_percent, 80);
+/// assert_eq!(budget.completion_pricing["gpt-5-mini"], 2.0);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetConfig {
+    /// Month-to-date estimated cost in USD past which requests are refused
+    #[serde(default)]
+    pub monthly_cost_limit: Option<f64>,
+
+    /// Percent of the limit at which chat warns (1-100, default: 80)
+    #[serde(default = "default_budget_warn_at_percent")]
+    pub warn_at_percent: u8,
+
+    /// USD per million output tokens, keyed by model name
+    #[serde(default)]
+    pub completion_pricing: std::collections::HashMap<String, f64>,
+
+    /// Keep sending requests past the limit, only warning
+    ///
+    /// Also enabled by `--ignore-budget`.
+    #[serde(default)]
+    pub allow_overrun: bool,
+}
+
+fn default_budget_warn_at_percent() -> u8 {
+    80
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            monthly_cost_limit: None,
+            warn_at_percent: default_budget_warn_at_percent(),
+            completion_pricing: std::collections::HashMap::new(),
+            allow_overrun: false,
+        }
+    }
+}
+
+/// Run command configuration
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::RunConfig;
+///
+/// let run: RunConfig = serde_yaml::from_str("postmortem: true").unwrap();
+/// assert!(run.postmortem);
+/// assert!(!RunConfig::default().postmortem);
+/// assert!(!RunConfig::default().snapshot.enabled);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunConfig {
+    /// Ask the provider for a post-mortem report when a run fails
+    ///
+    /// The report is written to `postmortem.md` in the run's artifacts
+    /// directory and printed after the failure summary. It costs one or
+    /// more extra provider requests. Also enabled by `--postmortem`.
+    #[serde(default)]
+    pub postmortem: bool,
+
+    /// Workspace snapshot taken before the run starts
+    #[serde(default)]
+    pub snapshot: RunSnapshotConfig,
+}
+
+/// How a workspace snapshot is captured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotStrategy {
+    /// Use git when the workspace is in a repository with a commit and no
+    /// merge or rebase in progress, and copy the files otherwise
+    #[default]
+    Auto,
+    /// Always use git; fail when the workspace cannot be captured with it
+    Git,
+    /// Always copy the files into the data directory
+    Copy,
+}
+
+/// Workspace snapshots for `xzatoma run`
+///
+/// A snapshot records the workspace before the agent touches it, so
+/// `xzatoma run rollback <run-id>` can put it back. Ignored files and files
+/// over `max_file_size_bytes` are left out. Snapshots beyond the newest
+/// `keep`, or older than `max_age_days`, are removed when a new one is taken.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::{RunSnapshotConfig, SnapshotStrategy};
+///
+/// let snapshot: RunSnapshotConfig =
+///     serde_yaml::from_str("enabled: true\nstrategy: copy\n").unwrap();
+/// assert!(snapshot.enabled);
+/// assert_eq!(snapshot.strategy, SnapshotStrategy::Copy);
+/// assert_eq!(snapshot.keep, 20);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunSnapshotConfig {
+    /// Take a snapshot before every run (default: false; `--snapshot`)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// How the snapshot is captured (default: auto)
+    #[serde(default)]
+    pub strategy: SnapshotStrategy,
+
+    /// Files larger than this are left out of the snapshot (default: 5 MiB)
+    #[serde(default = "default_snapshot_max_file_size_bytes")]
+    pub max_file_size_bytes: u64,
+
+    /// Largest total size of captured files; a bigger workspace fails the
+    /// snapshot (default: 512 MiB)
+    #[serde(default = "default_snapshot_max_total_bytes")]
+    pub max_total_bytes: u64,
+
+    /// Number of snapshots kept (default: 20)
+    #[serde(default = "default_snapshot_keep")]
+    pub keep: usize,
+
+    /// Days after which a snapshot is removed; 0 keeps them until `keep`
+    /// is reached (default: 30)
+    #[serde(default = "default_snapshot_max_age_days")]
+    pub max_age_days: u64,
+}
+
+fn default_snapshot_max_file_size_bytes() -> u64 {
+    5 * 1024 * 1024
+}
+
+fn default_snapshot_max_total_bytes() -> u64 {
+    512 * 1024 * 1024
+}
+
+fn default_snapshot_keep() -> usize {
+    20
+}
+
+fn default_snapshot_max_age_days() -> u64 {
+    30
+}
+
+impl Default for RunSnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strategy: SnapshotStrategy::default(),
+            max_file_size_bytes: default_snapshot_max_file_size_bytes(),
+            max_total_bytes: default_snapshot_max_total_bytes(),
+            keep: default_snapshot_keep(),
+            max_age_days: default_snapshot_max_age_days(),
+        }
+    }
+}
+
+/// Credential storage backend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialBackend {
+    /// Use the OS keyring and fall back to the credentials file when the
+    /// keyring is unavailable
+    #[default]
+    Auto,
+    /// Use only the OS keyring
+    Keyring,
+    /// Use only the credentials file
+    File,
+}
+
+/// Credential storage configuration
+///
+/// The credentials file is encrypted with a key derived from the passphrase
+/// in `XZATOMA_CREDENTIALS_KEY`. Without a passphrase the file is used only
+/// when `allow_plaintext` is true.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::{CredentialBackend, CredentialsConfig};
+///
+/// let credentials: CredentialsConfig = serde_yaml::from_str("backend: file\n").unwrap();
+/// assert_eq!(credentials.backend, CredentialBackend::File);
+/// assert!(!credentials.allow_plaintext);
+/// assert_eq!(credentials.keyring_timeout_seconds, 5);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CredentialsConfig {
+    /// Storage backend (default: `auto`)
+    #[serde(default)]
+    pub backend: CredentialBackend,
+
+    /// Store credentials unencrypted when no passphrase is set
+    #[serde(default)]
+    pub allow_plaintext: bool,
+
+    /// Credentials file location (default: `credentials.json` in the data
+    /// directory)
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// Seconds to wait for a keyring operation before treating the keyring
+    /// as unavailable (default: 5)
+    ///
+    /// A locked keyring can block while it waits for a graphical unlock
+    /// prompt that nobody will answer, for example over SSH.
+    #[serde(default = "default_keyring_timeout_seconds")]
+    pub keyring_timeout_seconds: u64,
+}
+
+fn default_keyring_timeout_seconds() -> u64 {
+    5
+}
+
+impl Default for CredentialsConfig {
+    fn default() -> Self {
+        Self {
+            backend: CredentialBackend::default(),
+            allow_plaintext: false,
+            path: None,
+            keyring_timeout_seconds: default_keyring_timeout_seconds(),
+        }
+    }
+}
+
+/// Telemetry event sink configuration
+///
+/// Telemetry is off unless `file` is set or `stdout` is true. Events are
+/// written as JSON lines; see [`crate::telemetry`] for the event schema.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::TelemetryConfig;
+///
+/// let telemetry = TelemetryConfig {
+///     file: Some("/tmp/xzatoma-telemetry.jsonl".to_string()),
+///     stdout: false,
+///     otlp: None,
+/// };
+/// assert!(telemetry.is_enabled());
+/// assert!(!TelemetryConfig::default().is_enabled());
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TelemetryConfig {
+    /// Append telemetry events to this file, creating it if needed
+    #[serde(default)]
+    pub file: Option<String>,
+
+    /// Write telemetry events to standard output
+    #[serde(default)]
+    pub stdout: bool,
+
+    /// Export tracing spans to an OpenTelemetry collector
+    ///
+    /// Only takes effect when xzatoma is built with the `otel` feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otlp: Option<OtlpConfig>,
+}
+
+impl TelemetryConfig {
+    /// Returns true when at least one sink is configured
+    pub fn is_enabled(&self) -> bool {
+        self.file.is_some() || self.stdout
+    }
+}
+
+/// OTLP span exporter configuration
+///
+/// Spans are sent over OTLP/HTTP with protobuf encoding. The exporter
+/// appends `/v1/traces` to `endpoint`.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::OtlpConfig;
+///
+/// let otlp: OtlpConfig = serde_yaml::from_str(
+///     "endpoint: http://localhost:4318\nheaders:\n  x-api-key: secret\n",
+/// )
+/// .unwrap();
+/// assert_eq!(otlp.service_name, "xzatoma");
+/// assert_eq!(otlp.headers["x-api-key"], "secret");
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OtlpConfig {
+    /// Collector base URL, e.g. `http://localhost:4318`
+    pub endpoint: String,
+
+    /// Extra HTTP headers sent with every export request
+    #[serde(default)]
+    pub headers: std::collections::HashMap<String, String>,
+
+    /// Value of the `service.name` resource attribute
+    #[serde(default = "default_otlp_service_name")]
+    pub service_name: String,
+}
+
+fn default_otlp_service_name() -> String {
+    "xzatoma".to_string()
+}
+
+/// Conversation retention policy
+///
+/// Each limit is optional; an unset limit is not enforced. Pinned
+/// conversations are never removed by pruning.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::RetentionConfig;
+///
+/// let retention = RetentionConfig {
+///     max_sessions: Some(100),
+///     max_age_days: Some(90),
+///     max_db_size_mb: None,
+/// };
+/// assert!(retention.is_enabled());
+/// assert!(!RetentionConfig::default().is_enabled());
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RetentionConfig {
+    /// Maximum number of conversations to keep
+    #[serde(default)]
+    pub max_sessions: Option<usize>,
+
+    /// Remove conversations not updated within this many days
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+
+    /// Target upper bound for the history database size in megabytes
+    #[serde(default)]
+    pub max_db_size_mb: Option<u64>,
+}
+
+impl RetentionConfig {
+    /// Returns true when at least one retention limit is configured
+    pub fn is_enabled(&self) -> bool {
+        self.max_sessions.is_some() || self.max_age_days.is_some() || self.max_db_size_mb.is_some()
+    }
+}
+
+/// Subagent delegation configuration
+///
+/// Settings for spawning and managing recursive agent instances
+/// with task delegation and controlled resource usage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubagentConfig {
+    /// Maximum recursion depth for nested subagents
+    ///
+    /// Root agent is depth 0, first subagent spawned is depth 1.
+    /// Prevents infinite recursion and stack overflow.
+    /// - depth < max_depth: Allow spawning
+    /// - depth >= max_depth: Return error
+    #[serde(default = "default_subagent_max_depth")]
+    pub max_depth: usize,
+
+    /// Default maximum turns per subagent execution
+    ///
+    /// Used when subagent input does not specify max_turns.
+    /// Limits conversation turns to prevent runaway execution.
+    #[serde(default = "default_subagent_max_turns")]
+    pub default_max_turns: usize,
+
+    /// Maximum output size in bytes before truncation
+    ///
+    /// Prevents subagent output from expanding context window excessively.
+    /// Output exceeding this size will be truncated with notice.
+    #[serde(default = "default_subagent_output_max_size")]
+    pub output_max_size: usize,
+
+    /// Enable subagent execution telemetry
+    ///
+    /// When true, logs structured telemetry events (spawn, complete, error, etc).
+    /// Set to false to disable telemetry logging.
+    #[serde(default = "default_subagent_telemetry_enabled")]
+    pub telemetry_enabled: bool,
+
+    /// Conversation persistence for replay and debugging
+    ///
+    /// `None` when the config has no `persistence` section. Read the
+    /// effective settings with [`SubagentConfig::saves_conversations`] and
+    /// [`SubagentConfig::conversation_database`], which also honor the
+    /// deprecated keys below.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persistence: Option<SubagentPersistenceConfig>,
+
+    /// Deprecated: use `persistence.enabled`
+    ///
+    /// Still read when there is no `persistence` section.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persistence_enabled: Option<bool>,
+
+    /// Deprecated: use `persistence.database`
+    ///
+    /// Still read when `persistence.database` is not set. It used to name
+    /// a sled database; it now names a SQLite file, so an old sled
+    /// directory cannot be opened.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persistence_path: Option<String>,
+
+    /// Maximum total subagent executions per session
     ///
     /// Limits the number of subagents that can be spawned in a single session.
     /// None means unlimited executions.
@@ -1003,21 +2025,6 @@ fn default_subagent_telemetry_enabled() -> bool {
     true
 }
 
-fn default_subagent_persistence_enabled() -> bool {
-    false
-}
-
-fn default_persistence_path() -> String {
-    let home = std::env::var("HOME")
-        .ok()
-        .map(std::path::PathBuf::from)
-        .unwrap_or_else(|| std::path::PathBuf::from("."));
-    home.join(".xzatoma")
-        .join("conversations.db")
-        .to_string_lossy()
-        .to_string()
-}
-
 fn default_max_executions() -> Option<usize> {
     None
 }
@@ -1041,8 +2048,9 @@ impl Default for SubagentConfig {
             default_max_turns: default_subagent_max_turns(),
             output_max_size: default_subagent_output_max_size(),
             telemetry_enabled: default_subagent_telemetry_enabled(),
-            persistence_enabled: default_subagent_persistence_enabled(),
-            persistence_path: default_persistence_path(),
+            persistence: None,
+            persistence_enabled: None,
+            persistence_path: None,
             max_executions: default_max_executions(),
             max_total_tokens: default_max_total_tokens(),
             max_total_time: default_max_total_time(),
@@ -1053,223 +2061,1110 @@ impl Default for SubagentConfig {
     }
 }
 
-/// Chat mode configuration
-///
-/// Settings for interactive chat sessions, including default modes
-/// and safety behaviors.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ChatConfig {
-    /// Default chat mode: "planning" or "write"
-    #[serde(default = "default_chat_mode")]
-    pub default_mode: String,
+impl SubagentConfig {
+    /// Deprecated keys and their replacements
+    const DEPRECATED_KEYS: [(&'static str, &'static str); 2] = [
+        ("persistence_enabled", "persistence.enabled"),
+        ("persistence_path", "persistence.database"),
+    ];
+
+    /// Whether subagent conversations are saved
+    ///
+    /// `persistence.enabled` when the section is present, otherwise the
+    /// deprecated `persistence_enabled`, otherwise false.
+    pub fn saves_conversations(&self) -> bool {
+        match &self.persistence {
+            Some(persistence) => persistence.enabled,
+            None => self.persistence_enabled.unwrap_or(false),
+        }
+    }
+
+    /// Database that subagent conversations are saved to, if configured
+    ///
+    /// `persistence.database`, otherwise the deprecated `persistence_path`.
+    /// `Config::load` fills in the history database when neither is set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::config::SubagentConfig;
+    ///
+    /// let config: SubagentConfig =
+    ///     serde_yaml::from_str("persistence_path: /tmp/subagents.db\n").unwrap();
+    /// assert_eq!(
+    ///     config.conversation_database(),
+    ///     Some(std::path::PathBuf::from("/tmp/subagents.db"))
+    /// );
+    /// ```
+    pub fn conversation_database(&self) -> Option<PathBuf> {
+        self.persistence
+            .as_ref()
+            .and_then(|persistence| persistence.database.clone())
+            .or_else(|| self.persistence_path.as_ref().map(PathBuf::from))
+    }
+
+    /// Lists the deprecated keys that are set, with their replacements
+    ///
+    /// # Returns
+    ///
+    /// `(old, new)` key names relative to `agent.subagent`
+    pub fn deprecated_keys(&self) -> Vec<(&'static str, &'static str)> {
+        let set = [
+            self.persistence_enabled.is_some(),
+            self.persistence_path.is_some(),
+        ];
+        Self::DEPRECATED_KEYS
+            .into_iter()
+            .zip(set)
+            .filter(|(_, set)| *set)
+            .map(|(keys, _)| keys)
+            .collect()
+    }
+
+    /// Checks that deprecated keys agree with their replacements
+    fn validate_persistence(&self) -> Result<()> {
+        let Some(persistence) = &self.persistence else {
+            return Ok(());
+        };
+        if let Some(enabled) = self.persistence_enabled {
+            if enabled != persistence.enabled {
+                return Err(XzatomaError::Config(format!(
+                    "agent.subagent.persistence_enabled ({}) conflicts with \
+                     agent.subagent.persistence.enabled ({}); remove the deprecated key",
+                    enabled, persistence.enabled
+                )));
+            }
+        }
+        if let (Some(path), Some(database)) = (&self.persistence_path, &persistence.database) {
+            if Path::new(path) != database {
+                return Err(XzatomaError::Config(format!(
+                    "agent.subagent.persistence_path ({}) conflicts with \
+                     agent.subagent.persistence.database ({}); remove the deprecated key",
+                    path,
+                    database.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Subagent conversation persistence
+///
+/// Saved conversations can be listed and replayed with `xzatoma replay`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubagentPersistenceConfig {
+    /// Save each subagent conversation
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// SQLite database to save to
+    ///
+    /// Defaults to the history database, so subagent runs are stored
+    /// beside normal chat history.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database: Option<PathBuf>,
+}
+
+/// Chat mode configuration
+///
+/// Settings for interactive chat sessions, including default modes
+/// and safety behaviors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatConfig {
+    /// Default chat mode: "planning" or "write"
+    #[serde(default = "default_chat_mode")]
+    pub default_mode: String,
+
+    /// Default safety mode: "confirm", "yolo", or "confirm_once"
+    #[serde(default = "default_safety_mode")]
+    pub default_safety: String,
+
+    /// In "confirm_once" mode, confirm every delete instead of granting
+    /// deletes once per session
+    #[serde(default)]
+    pub confirm_each_delete: bool,
+
+    /// In "confirm_once" mode, confirm every terminal command outside the
+    /// allowlist instead of granting them once per session
+    #[serde(default)]
+    pub confirm_each_dangerous_command: bool,
+
+    /// Allow switching between modes during a session
+    #[serde(default = "default_allow_mode_switching")]
+    pub allow_mode_switching: bool,
+
+    /// Persist special commands in conversation history
+    #[serde(default = "default_persist_special_commands")]
+    pub persist_special_commands: bool,
+
+    /// Replace `@` mentions in the prompt with short placeholders such as
+    /// `[file: src/main.rs lines 1-50]` once their content has been prepended
+    #[serde(default = "default_strip_mentions")]
+    pub strip_mentions: bool,
+
+    /// Largest image, in bytes, that `@image:` mentions and `/attach` accept
+    #[serde(default = "default_chat_max_image_bytes")]
+    pub max_image_bytes: u64,
+
+    /// Markdown file the conversation is mirrored to as it happens
+    ///
+    /// Overridden by `xzatoma chat --transcript`.
+    #[serde(default)]
+    pub transcript_path: Option<String>,
+
+    /// Open pasted multi-line input in `$EDITOR` before sending it
+    #[serde(default)]
+    pub editor_on_multiline: bool,
+
+    /// How much the startup banner shows
+    ///
+    /// Overridden with `off` by `xzatoma chat --quiet`.
+    #[serde(default)]
+    pub banner: ChatBanner,
+}
+
+/// How much the chat startup banner shows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatBanner {
+    /// Model, mode, safety, directory and git state, MCP tools, and the
+    /// conversation to resume, one per line
+    #[default]
+    Full,
+    /// One line with the model, mode, safety, and directory
+    Minimal,
+    /// No banner
+    Off,
+}
+
+fn default_chat_mode() -> String {
+    "planning".to_string()
+}
+
+fn default_safety_mode() -> String {
+    "confirm".to_string()
+}
+
+fn default_allow_mode_switching() -> bool {
+    true
+}
+
+fn default_persist_special_commands() -> bool {
+    true
+}
+
+fn default_strip_mentions() -> bool {
+    true
+}
+
+fn default_chat_max_image_bytes() -> u64 {
+    5 * 1024 * 1024
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            default_mode: default_chat_mode(),
+            default_safety: default_safety_mode(),
+            confirm_each_delete: false,
+            confirm_each_dangerous_command: false,
+            allow_mode_switching: default_allow_mode_switching(),
+            persist_special_commands: default_persist_special_commands(),
+            strip_mentions: default_strip_mentions(),
+            max_image_bytes: default_chat_max_image_bytes(),
+            transcript_path: None,
+            editor_on_multiline: false,
+            banner: ChatBanner::default(),
+        }
+    }
+}
+
+/// Conversation management configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationConfig {
+    /// Maximum tokens allowed in conversation context
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: usize,
+
+    /// Minimum number of turns to retain when pruning
+    #[serde(default = "default_min_retain")]
+    pub min_retain_turns: usize,
+
+    /// Token threshold to trigger pruning (percentage of max_tokens)
+    #[serde(default = "default_prune_threshold")]
+    pub prune_threshold: f32,
+
+    /// Token percentage threshold for warning status (0.0-1.0)
+    /// Default: 0.85 (warn at 85% context usage)
+    #[serde(default = "default_warning_threshold")]
+    pub warning_threshold: f32,
+
+    /// Token percentage threshold for automatic summarization (0.0-1.0)
+    /// Default: 0.90 (auto-summarize at 90% context usage)
+    #[serde(default = "default_auto_summary_threshold")]
+    pub auto_summary_threshold: f32,
+
+    /// Fraction of the context window to compact to after a provider
+    /// rejects a request as too large (0.0-1.0)
+    /// Default: 0.6
+    #[serde(default = "default_overflow_target")]
+    pub overflow_target: f32,
+
+    /// Model to use for automatic summarization (e.g., "gpt-4", "claude-3")
+    /// If None, uses the default provider model
+    #[serde(default)]
+    pub summary_model: Option<String>,
+
+    /// Replace superseded tool results with short stubs before each request
+    ///
+    /// Older results repeated later with identical output, and older reads
+    /// of files the agent has since modified, are stubbed.
+    /// Default: true
+    #[serde(default = "default_history_hygiene")]
+    pub history_hygiene: bool,
+}
+
+fn default_max_tokens() -> usize {
+    100_000
+}
+
+fn default_min_retain() -> usize {
+    5
+}
+
+fn default_prune_threshold() -> f32 {
+    0.8
+}
+
+fn default_warning_threshold() -> f32 {
+    0.85
+}
+
+fn default_auto_summary_threshold() -> f32 {
+    0.90
+}
+
+fn default_overflow_target() -> f32 {
+    0.6
+}
+
+fn default_history_hygiene() -> bool {
+    true
+}
+
+impl Default for ConversationConfig {
+    fn default() -> Self {
+        Self {
+            max_tokens: default_max_tokens(),
+            min_retain_turns: default_min_retain(),
+            prune_threshold: default_prune_threshold(),
+            warning_threshold: default_warning_threshold(),
+            auto_summary_threshold: default_auto_summary_threshold(),
+            overflow_target: default_overflow_target(),
+            summary_model: None,
+            history_hygiene: default_history_hygiene(),
+        }
+    }
+}
+
+/// Tool execution configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolsConfig {
+    /// Maximum size of tool output (bytes)
+    #[serde(default = "default_max_output")]
+    pub max_output_size: usize,
+
+    /// Maximum size of file to read (bytes)
+    #[serde(default = "default_max_file_read")]
+    pub max_file_read_size: usize,
+
+    /// Maximum results per page for grep tool
+    #[serde(default = "default_grep_max_results_per_page")]
+    pub grep_max_results_per_page: usize,
+
+    /// Number of context lines to show around grep matches
+    #[serde(default = "default_grep_context_lines")]
+    pub grep_context_lines: usize,
+
+    /// Maximum file size for grep searches (bytes)
+    #[serde(default = "default_grep_max_file_size")]
+    pub grep_max_file_size: u64,
+
+    /// File patterns to exclude from grep searches
+    #[serde(default = "default_grep_excluded_patterns")]
+    pub grep_excluded_patterns: Vec<String>,
+
+    /// Fetch tool timeout in seconds (default: 30)
+    #[serde(default = "default_fetch_timeout_seconds")]
+    pub fetch_timeout_seconds: u64,
+
+    /// Maximum size of fetched content (bytes, default: 5 MB)
+    #[serde(default = "default_max_fetch_size_bytes")]
+    pub max_fetch_size_bytes: usize,
+
+    /// Maximum number of fetch requests per minute (default: 10)
+    ///
+    /// Used as the global bucket when `fetch_rate_limits` is not set.
+    #[serde(default = "default_max_fetches_per_minute")]
+    pub max_fetches_per_minute: u32,
+
+    /// Global and per-domain fetch rate limits
+    #[serde(default)]
+    pub fetch_rate_limits: Option<FetchRateLimitsConfig>,
+
+    /// Optional allowlist of domains for fetch tool
+    #[serde(default)]
+    pub fetch_allowed_domains: Option<Vec<String>>,
+
+    /// Optional blocklist of domains for fetch tool
+    #[serde(default)]
+    pub fetch_blocked_domains: Option<Vec<String>>,
+
+    /// Ports the fetch tool may connect to in addition to 80 and 443
+    #[serde(default)]
+    pub fetch_allowed_ports: Vec<u16>,
+
+    /// Maximum number of redirects the fetch tool follows (default: 5)
+    #[serde(default = "default_fetch_max_redirects")]
+    pub fetch_max_redirects: usize,
+
+    /// Record file mutations in the audit log (default: true)
+    #[serde(default = "default_audit_log_enabled")]
+    pub audit_log_enabled: bool,
+
+    /// Audit log location (default: `audit.jsonl` in the data directory)
+    #[serde(default)]
+    pub audit_log_path: Option<String>,
+
+    /// Abort a file mutation when its audit entry cannot be written
+    #[serde(default)]
+    pub audit_required: bool,
+
+    /// What to do when tool definitions exceed the provider's limits
+    #[serde(default)]
+    pub definition_limits: ToolDefinitionLimitsConfig,
+
+    /// How many tool calls run per turn and which `tool_choice` is sent
+    #[serde(default)]
+    pub tool_calls: ToolCallsConfig,
+
+    /// Detection of identical tool calls repeated within one prompt
+    #[serde(default)]
+    pub loop_detection: LoopDetectionConfig,
+
+    /// Flagging and withholding of tools whose recent calls keep failing
+    #[serde(default)]
+    pub health: ToolHealthConfig,
+
+    /// Wrapping and scanning of fetched web content and MCP resource text
+    #[serde(default)]
+    pub untrusted_content: UntrustedContentConfig,
+
+    /// Summarize tool output over `max_output_size` with the summary model
+    /// instead of truncating it
+    #[serde(default)]
+    pub summarize_overflow: bool,
+
+    /// Bytes of output sent to the summary model per request
+    #[serde(default = "default_summary_chunk_bytes")]
+    pub summary_chunk_bytes: usize,
+
+    /// Most summary requests made for one output
+    #[serde(default = "default_summary_max_chunks")]
+    pub summary_max_chunks: usize,
+
+    /// Paging of tool output over `max_output_size`
+    #[serde(default)]
+    pub paging: ToolOutputPagingConfig,
+
+    /// Shell-script tools defined in the config, registered in Write mode
+    #[serde(default)]
+    pub custom: Vec<CustomToolConfig>,
+}
+
+fn default_max_output() -> usize {
+    5_242_880 // 5 MB
+}
+
+fn default_max_file_read() -> usize {
+    10_485_760 // 10 MB
+}
+
+fn default_grep_max_results_per_page() -> usize {
+    20
+}
+
+fn default_grep_context_lines() -> usize {
+    2
+}
+
+fn default_grep_max_file_size() -> u64 {
+    1_048_576 // 1 MB
+}
+
+fn default_grep_excluded_patterns() -> Vec<String> {
+    vec![
+        "*.lock".to_string(),
+        "target/**".to_string(),
+        "node_modules/**".to_string(),
+        ".git/**".to_string(),
+    ]
+}
+
+fn default_fetch_timeout_seconds() -> u64 {
+    30
+}
+
+fn default_max_fetch_size_bytes() -> usize {
+    5 * 1024 * 1024 // 5 MB
+}
+
+fn default_max_fetches_per_minute() -> u32 {
+    10
+}
+
+fn default_fetch_max_redirects() -> usize {
+    5
+}
+
+fn default_audit_log_enabled() -> bool {
+    true
+}
+
+fn default_summary_chunk_bytes() -> usize {
+    crate::tools::overflow_summary::DEFAULT_CHUNK_BYTES
+}
+
+fn default_summary_max_chunks() -> usize {
+    crate::tools::overflow_summary::DEFAULT_MAX_CHUNKS
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self {
+            max_output_size: default_max_output(),
+            max_file_read_size: default_max_file_read(),
+            grep_max_results_per_page: default_grep_max_results_per_page(),
+            grep_context_lines: default_grep_context_lines(),
+            grep_max_file_size: default_grep_max_file_size(),
+            grep_excluded_patterns: default_grep_excluded_patterns(),
+            fetch_timeout_seconds: default_fetch_timeout_seconds(),
+            max_fetch_size_bytes: default_max_fetch_size_bytes(),
+            max_fetches_per_minute: default_max_fetches_per_minute(),
+            fetch_rate_limits: None,
+            fetch_allowed_domains: None,
+            fetch_blocked_domains: None,
+            fetch_allowed_ports: Vec::new(),
+            fetch_max_redirects: default_fetch_max_redirects(),
+            audit_log_enabled: default_audit_log_enabled(),
+            audit_log_path: None,
+            audit_required: false,
+            definition_limits: ToolDefinitionLimitsConfig::default(),
+            tool_calls: ToolCallsConfig::default(),
+            loop_detection: LoopDetectionConfig::default(),
+            health: ToolHealthConfig::default(),
+            untrusted_content: UntrustedContentConfig::default(),
+            summarize_overflow: false,
+            summary_chunk_bytes: default_summary_chunk_bytes(),
+            summary_max_chunks: default_summary_max_chunks(),
+            paging: ToolOutputPagingConfig::default(),
+            custom: Vec::new(),
+        }
+    }
+}
+
+impl ToolsConfig {
+    /// Returns the fetch rate limits in effect
+    ///
+    /// When `fetch_rate_limits` is not set, the defaults apply with the
+    /// global bucket sized by `max_fetches_per_minute`.
+    pub fn effective_fetch_rate_limits(&self) -> FetchRateLimitsConfig {
+        self.fetch_rate_limits
+            .clone()
+            .unwrap_or_else(|| FetchRateLimitsConfig {
+                global: RateLimit::per_minute(self.max_fetches_per_minute),
+                ..FetchRateLimitsConfig::default()
+            })
+    }
+}
+
+/// Strategy applied when tool definitions exceed provider limits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolLimitStrategy {
+    /// Shorten long descriptions, then drop tools if still over the limits
+    #[default]
+    Truncate,
+    /// Drop tools, lowest priority first
+    Drop,
+    /// Refuse to send the request and list the offending tools
+    Fail,
+}
+
+impl std::fmt::Display for ToolLimitStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncate => write!(f, "truncate"),
+            Self::Drop => write!(f, "drop"),
+            Self::Fail => write!(f, "fail"),
+        }
+    }
+}
+
+/// Limits on the tool definitions sent with each provider request
+///
+/// The provider's own limits apply unless overridden here.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::{ToolDefinitionLimitsConfig, ToolLimitStrategy};
+///
+/// let yaml = r#"
+/// strategy: drop
+/// mcp_priorities:
+///   github: 10
+///   scratch: -5
+/// "#;
+/// let limits: ToolDefinitionLimitsConfig = serde_yaml::from_str(yaml).unwrap();
+/// assert_eq!(limits.strategy, ToolLimitStrategy::Drop);
+/// assert_eq!(limits.mcp_priorities["github"], 10);
+/// assert!(limits.max_tools.is_none());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolDefinitionLimitsConfig {
+    /// How to bring oversized definitions within the limits
+    #[serde(default)]
+    pub strategy: ToolLimitStrategy,
+
+    /// Priority of each MCP server's tools, keyed by server id (default 0)
+    ///
+    /// Lower priorities are dropped first. Built-in tools rank above all
+    /// MCP tools.
+    #[serde(default)]
+    pub mcp_priorities: std::collections::HashMap<String, i32>,
+
+    /// Override the provider's maximum number of tools per request
+    #[serde(default)]
+    pub max_tools: Option<usize>,
+
+    /// Override the provider's maximum description length, in characters
+    #[serde(default)]
+    pub max_description_chars: Option<usize>,
+
+    /// Override the provider's maximum combined definition size, in bytes
+    #[serde(default)]
+    pub max_total_bytes: Option<usize>,
+}
+
+/// `tool_choice` sent with requests that carry tool definitions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolChoiceMode {
+    /// The model decides whether to call a tool
+    #[default]
+    Auto,
+    /// The first request of each prompt must call a tool; later requests
+    /// fall back to `auto` so the model can finish
+    Required,
+}
+
+impl std::fmt::Display for ToolChoiceMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Required => write!(f, "required"),
+        }
+    }
+}
+
+/// How the agent negotiates tool calls with the provider
+///
+/// Each capability defaults to what the provider reports for the active
+/// model; set a field to override it.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::{ToolCallsConfig, ToolChoiceMode};
+///
+/// let yaml = r#"
+/// tool_choice: required
+/// supports_parallel_tool_calls: false
+/// "#;
+/// let tool_calls: ToolCallsConfig = serde_yaml::from_str(yaml).unwrap();
+/// assert_eq!(tool_calls.tool_choice, ToolChoiceMode::Required);
+/// assert_eq!(tool_calls.supports_parallel_tool_calls, Some(false));
+/// assert!(tool_calls.max_tools_per_turn.is_none());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolCallsConfig {
+    /// `tool_choice` to request when the provider supports it
+    #[serde(default)]
+    pub tool_choice: ToolChoiceMode,
+
+    /// Override whether the provider runs several tool calls in one turn
+    #[serde(default)]
+    pub supports_parallel_tool_calls: Option<bool>,
+
+    /// Override whether the provider accepts a `tool_choice` parameter
+    #[serde(default)]
+    pub supports_tool_choice: Option<bool>,
+
+    /// Override the most tool calls executed from one response
+    #[serde(default)]
+    pub max_tools_per_turn: Option<usize>,
+}
+
+/// Detection of a model that keeps repeating the same tool call
+///
+/// Calls are identical when the tool name and the arguments match, ignoring
+/// key order and whitespace. Read-only calls only count as repeats while no
+/// tool has changed the workspace in between. From the
+/// `repeat_threshold`-th identical call, read-only tools return their
+/// previous result without running again, and every result is annotated
+/// with a hint to try something else. At `stop_threshold` identical calls
+/// the prompt stops with an error. The counts reset with each prompt.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::LoopDetectionConfig;
+///
+/// let loop_detection: LoopDetectionConfig = serde_yaml::from_str("stop_threshold: 8\n").unwrap();
+/// assert!(loop_detection.enabled);
+/// assert_eq!(loop_detection.repeat_threshold, 3);
+/// assert_eq!(loop_detection.stop_threshold, 8);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoopDetectionConfig {
+    /// Enable loop detection (default: true)
+    #[serde(default = "default_loop_detection_enabled")]
+    pub enabled: bool,
+
+    /// Identical calls after which results are cached and annotated (default: 3)
+    #[serde(default = "default_loop_repeat_threshold")]
+    pub repeat_threshold: usize,
+
+    /// Identical calls after which the prompt is stopped (default: 5)
+    #[serde(default = "default_loop_stop_threshold")]
+    pub stop_threshold: usize,
+}
+
+fn default_loop_detection_enabled() -> bool {
+    true
+}
+
+fn default_loop_repeat_threshold() -> usize {
+    3
+}
+
+fn default_loop_stop_threshold() -> usize {
+    5
+}
+
+impl Default for LoopDetectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_loop_detection_enabled(),
+            repeat_threshold: default_loop_repeat_threshold(),
+            stop_threshold: default_loop_stop_threshold(),
+        }
+    }
+}
+
+/// Tracking of tools whose recent calls keep failing
+///
+/// The agent keeps the outcomes of each tool's last `window` calls, and
+/// outcomes older than `decay_seconds` are forgotten. Once a tool has at
+/// least `min_calls` outcomes and `degraded_failure_rate` of them failed,
+/// its definition tells the model that it is currently failing. With at
+/// least `withhold_min_calls` outcomes and `withhold_failure_rate` failed,
+/// the definition is left out of requests for `cooldown_seconds`; the tool
+/// still runs when the model calls it by name. A successful call returns a
+/// degraded or withheld tool to healthy.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::ToolHealthConfig;
+///
+/// let health: ToolHealthConfig = serde_yaml::from_str("cooldown_seconds: 30\n").unwrap();
+/// assert!(health.enabled);
+/// assert_eq!(health.cooldown_seconds, 30);
+/// assert_eq!(health.min_calls, 3);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolHealthConfig {
+    /// Track tool health (default: true)
+    #[serde(default = "default_tool_health_enabled")]
+    pub enabled: bool,
+
+    /// Recent calls per tool the failure rate is taken over (default: 10)
+    #[serde(default = "default_tool_health_window")]
+    pub window: usize,
+
+    /// Recent calls needed before a tool is marked degraded (default: 3)
+    #[serde(default = "default_tool_health_min_calls")]
+    pub min_calls: usize,
+
+    /// Failure rate at which a tool is marked degraded (default: 0.5)
+    #[serde(default = "default_tool_health_degraded_rate")]
+    pub degraded_failure_rate: f64,
 
-    /// Default safety mode: "confirm" or "yolo"
-    #[serde(default = "default_safety_mode")]
-    pub default_safety: String,
+    /// Recent calls needed before a tool is withheld (default: 5)
+    #[serde(default = "default_tool_health_withhold_min_calls")]
+    pub withhold_min_calls: usize,
 
-    /// Allow switching between modes during a session
-    #[serde(default = "default_allow_mode_switching")]
-    pub allow_mode_switching: bool,
+    /// Failure rate at which a tool is withheld (default: 0.8)
+    #[serde(default = "default_tool_health_withhold_rate")]
+    pub withhold_failure_rate: f64,
 
-    /// Persist special commands in conversation history
-    #[serde(default = "default_persist_special_commands")]
-    pub persist_special_commands: bool,
+    /// Seconds a withheld tool stays out of requests (default: 120)
+    #[serde(default = "default_tool_health_cooldown_seconds")]
+    pub cooldown_seconds: u64,
+
+    /// Seconds after which a call no longer counts (default: 600)
+    #[serde(default = "default_tool_health_decay_seconds")]
+    pub decay_seconds: u64,
 }
 
-fn default_chat_mode() -> String {
-    "planning".to_string()
+fn default_tool_health_enabled() -> bool {
+    true
 }
 
-fn default_safety_mode() -> String {
-    "confirm".to_string()
+fn default_tool_health_window() -> usize {
+    10
 }
 
-fn default_allow_mode_switching() -> bool {
-    true
+fn default_tool_health_min_calls() -> usize {
+    3
 }
 
-fn default_persist_special_commands() -> bool {
-    true
+fn default_tool_health_degraded_rate() -> f64 {
+    0.5
 }
 
-impl Default for ChatConfig {
+fn default_tool_health_withhold_min_calls() -> usize {
+    5
+}
+
+fn default_tool_health_withhold_rate() -> f64 {
+    0.8
+}
+
+fn default_tool_health_cooldown_seconds() -> u64 {
+    120
+}
+
+fn default_tool_health_decay_seconds() -> u64 {
+    600
+}
+
+impl Default for ToolHealthConfig {
     fn default() -> Self {
         Self {
-            default_mode: default_chat_mode(),
-            default_safety: default_safety_mode(),
-            allow_mode_switching: default_allow_mode_switching(),
-            persist_special_commands: default_persist_special_commands(),
+            enabled: default_tool_health_enabled(),
+            window: default_tool_health_window(),
+            min_calls: default_tool_health_min_calls(),
+            degraded_failure_rate: default_tool_health_degraded_rate(),
+            withhold_min_calls: default_tool_health_withhold_min_calls(),
+            withhold_failure_rate: default_tool_health_withhold_rate(),
+            cooldown_seconds: default_tool_health_cooldown_seconds(),
+            decay_seconds: default_tool_health_decay_seconds(),
         }
     }
 }
 
-/// Conversation management configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ConversationConfig {
-    /// Maximum tokens allowed in conversation context
-    #[serde(default = "default_max_tokens")]
-    pub max_tokens: usize,
-
-    /// Minimum number of turns to retain when pruning
-    #[serde(default = "default_min_retain")]
-    pub min_retain_turns: usize,
-
-    /// Token threshold to trigger pruning (percentage of max_tokens)
-    #[serde(default = "default_prune_threshold")]
-    pub prune_threshold: f32,
+/// Paging of tool output over `tools.max_output_size`
+///
+/// Oversized output is kept for the session and the agent receives the
+/// first page with a note naming the `read_tool_output` call that returns
+/// the next one. Up to `max_outputs` outputs are kept, least recently used
+/// first out. Outputs stay in memory up to `memory_bytes` in total and are
+/// written to a private temporary directory beyond that. With paging off,
+/// oversized output is truncated. `summarize_overflow` takes precedence
+/// over paging.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::ToolOutputPagingConfig;
+///
+/// let paging: ToolOutputPagingConfig = serde_yaml::from_str("max_outputs: 8\n").unwrap();
+/// assert!(paging.enabled);
+/// assert_eq!(paging.max_outputs, 8);
+/// assert_eq!(paging.memory_bytes, 16 * 1024 * 1024);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolOutputPagingConfig {
+    /// Page oversized output instead of truncating it (default: true)
+    #[serde(default = "default_paging_enabled")]
+    pub enabled: bool,
 
-    /// Token percentage threshold for warning status (0.0-1.0)
-    /// Default: 0.85 (warn at 85% context usage)
-    #[serde(default = "default_warning_threshold")]
-    pub warning_threshold: f32,
+    /// Outputs kept per session before the least recently used is evicted (default: 32)
+    #[serde(default = "default_paging_max_outputs")]
+    pub max_outputs: usize,
 
-    /// Token percentage threshold for automatic summarization (0.0-1.0)
-    /// Default: 0.90 (auto-summarize at 90% context usage)
-    #[serde(default = "default_auto_summary_threshold")]
-    pub auto_summary_threshold: f32,
+    /// Output bytes kept in memory before spilling to disk (default: 16 MiB)
+    #[serde(default = "default_paging_memory_bytes")]
+    pub memory_bytes: usize,
+}
 
-    /// Model to use for automatic summarization (e.g., "gpt-4", "claude-3")
-    /// If None, uses the default provider model
-    #[serde(default)]
-    pub summary_model: Option<String>,
+fn default_paging_enabled() -> bool {
+    true
 }
 
-fn default_max_tokens() -> usize {
-    100_000
+fn default_paging_max_outputs() -> usize {
+    crate::tools::output_pages::DEFAULT_MAX_OUTPUTS
 }
 
-fn default_min_retain() -> usize {
-    5
+fn default_paging_memory_bytes() -> usize {
+    crate::tools::output_pages::DEFAULT_MEMORY_BYTES
 }
 
-fn default_prune_threshold() -> f32 {
-    0.8
+impl Default for ToolOutputPagingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_paging_enabled(),
+            max_outputs: default_paging_max_outputs(),
+            memory_bytes: default_paging_memory_bytes(),
+        }
+    }
 }
 
-fn default_warning_threshold() -> f32 {
-    0.85
+/// Guard for text fetched from outside the workspace
+///
+/// Content from `@url:` mentions and MCP resources is enclosed in a
+/// delimited block marked as untrusted data and scanned for common
+/// prompt-injection payloads. `patterns` are case-insensitive regular
+/// expressions checked in addition to the built-in list, or instead of it
+/// when `use_default_patterns` is false.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::UntrustedContentConfig;
+///
+/// let untrusted: UntrustedContentConfig =
+///     serde_yaml::from_str("patterns: ['launch\\s+code']\n").unwrap();
+/// assert!(untrusted.enabled);
+/// assert!(untrusted.use_default_patterns);
+/// assert_eq!(untrusted.patterns, vec!["launch\\s+code".to_string()]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UntrustedContentConfig {
+    /// Wrap and scan external content (default: true)
+    #[serde(default = "default_untrusted_content_enabled")]
+    pub enabled: bool,
+
+    /// Scan with the built-in injection patterns (default: true)
+    #[serde(default = "default_untrusted_content_enabled")]
+    pub use_default_patterns: bool,
+
+    /// Additional regular expressions that flag content as suspicious
+    #[serde(default)]
+    pub patterns: Vec<String>,
 }
 
-fn default_auto_summary_threshold() -> f32 {
-    0.90
+fn default_untrusted_content_enabled() -> bool {
+    true
 }
 
-impl Default for ConversationConfig {
+impl Default for UntrustedContentConfig {
     fn default() -> Self {
         Self {
-            max_tokens: default_max_tokens(),
-            min_retain_turns: default_min_retain(),
-            prune_threshold: default_prune_threshold(),
-            warning_threshold: default_warning_threshold(),
-            auto_summary_threshold: default_auto_summary_threshold(),
-            summary_model: None,
+            enabled: default_untrusted_content_enabled(),
+            use_default_patterns: default_untrusted_content_enabled(),
+            patterns: Vec::new(),
         }
     }
 }
 
-/// Tool execution configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ToolsConfig {
-    /// Maximum size of tool output (bytes)
-    #[serde(default = "default_max_output")]
-    pub max_output_size: usize,
-
-    /// Maximum size of file to read (bytes)
-    #[serde(default = "default_max_file_read")]
-    pub max_file_read_size: usize,
-
-    /// Maximum results per page for grep tool
-    #[serde(default = "default_grep_max_results_per_page")]
-    pub grep_max_results_per_page: usize,
-
-    /// Number of context lines to show around grep matches
-    #[serde(default = "default_grep_context_lines")]
-    pub grep_context_lines: usize,
+/// A tool backed by a shell command template
+///
+/// `{{param}}` placeholders in `command` are replaced with the call's
+/// arguments, each quoted for the shell, and the command runs with `sh -c`.
+/// Placeholders must not be quoted in the template.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::CustomToolConfig;
+///
+/// let tool: CustomToolConfig = serde_yaml::from_str(
+///     r#"
+/// name: run_tests
+/// description: Run the test suite for one package
+/// parameters:
+///   type: object
+///   properties:
+///     package: { type: string }
+///   required: [package]
+/// command: ./scripts/test.sh --package {{package}}
+/// "#,
+/// )
+/// .unwrap();
+/// assert_eq!(tool.name, "run_tests");
+/// assert!(tool.env.is_empty());
+/// assert!(!tool.allow_dangerous);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomToolConfig {
+    /// Tool name shown to the model; must not match a built-in tool
+    pub name: String,
 
-    /// Maximum file size for grep searches (bytes)
-    #[serde(default = "default_grep_max_file_size")]
-    pub grep_max_file_size: u64,
+    /// What the tool does, shown to the model
+    pub description: String,
 
-    /// File patterns to exclude from grep searches
-    #[serde(default = "default_grep_excluded_patterns")]
-    pub grep_excluded_patterns: Vec<String>,
+    /// JSON schema for the arguments (default: an object with no properties)
+    #[serde(default = "default_custom_tool_parameters")]
+    pub parameters: serde_json::Value,
 
-    /// Fetch tool timeout in seconds (default: 30)
-    #[serde(default = "default_fetch_timeout_seconds")]
-    pub fetch_timeout_seconds: u64,
+    /// Command template with `{{param}}` placeholders
+    pub command: String,
 
-    /// Maximum size of fetched content (bytes, default: 5 MB)
-    #[serde(default = "default_max_fetch_size_bytes")]
-    pub max_fetch_size_bytes: usize,
+    /// Directory the command runs in, relative to the workspace
+    /// (default: the workspace)
+    #[serde(default)]
+    pub working_dir: Option<String>,
 
-    /// Maximum number of fetch requests per minute (default: 10)
-    #[serde(default = "default_max_fetches_per_minute")]
-    pub max_fetches_per_minute: u32,
+    /// Seconds before the command is killed (default: `terminal.timeout_seconds`)
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
 
-    /// Optional allowlist of domains for fetch tool
+    /// Extra environment variables for the command
     #[serde(default)]
-    pub fetch_allowed_domains: Option<Vec<String>>,
+    pub env: std::collections::HashMap<String, String>,
 
-    /// Optional blocklist of domains for fetch tool
+    /// Run without confirmation even when the template matches a dangerous
+    /// command pattern
     #[serde(default)]
-    pub fetch_blocked_domains: Option<Vec<String>>,
+    pub allow_dangerous: bool,
 }
 
-fn default_max_output() -> usize {
-    5_242_880 // 5 MB
+fn default_custom_tool_parameters() -> serde_json::Value {
+    serde_json::json!({"type": "object", "properties": {}})
 }
 
-fn default_max_file_read() -> usize {
-    10_485_760 // 10 MB
+/// A request budget written as `<requests>/<unit>`
+///
+/// The unit is one of `s`, `sec`, `second`, `min`, `minute`, `h`, `hour`.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::RateLimit;
+///
+/// let limit: RateLimit = "4/min".parse().unwrap();
+/// assert_eq!(limit.requests, 4);
+/// assert_eq!(limit.period.as_secs(), 60);
+/// assert_eq!(limit.to_string(), "4/min");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RateLimit {
+    /// Requests allowed per period; also the burst size
+    pub requests: u32,
+    /// Length of the period
+    pub period: std::time::Duration,
+}
+
+impl RateLimit {
+    /// Creates a limit of `requests` per minute
+    pub fn per_minute(requests: u32) -> Self {
+        Self {
+            requests,
+            period: std::time::Duration::from_secs(60),
+        }
+    }
 }
 
-fn default_grep_max_results_per_page() -> usize {
-    20
+impl std::str::FromStr for RateLimit {
+    type Err = XzatomaError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            XzatomaError::Config(format!(
+                "Invalid rate limit '{}': expected <requests>/<s|min|hour>",
+                s
+            ))
+        };
+        let (count, unit) = s.trim().split_once('/').ok_or_else(invalid)?;
+        let requests = count.trim().parse::<u32>().map_err(|_| invalid())?;
+        let seconds = match unit.trim() {
+            "s" | "sec" | "second" => 1,
+            "m" | "min" | "minute" => 60,
+            "h" | "hour" => 3600,
+            _ => return Err(invalid()),
+        };
+        Ok(Self {
+            requests,
+            period: std::time::Duration::from_secs(seconds),
+        })
+    }
 }
 
-fn default_grep_context_lines() -> usize {
-    2
+impl std::fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.period.as_secs() {
+            1 => write!(f, "{}/s", self.requests),
+            60 => write!(f, "{}/min", self.requests),
+            3600 => write!(f, "{}/hour", self.requests),
+            secs => write!(f, "{}/{}s", self.requests, secs),
+        }
+    }
 }
 
-fn default_grep_max_file_size() -> u64 {
-    1_048_576 // 1 MB
+impl TryFrom<String> for RateLimit {
+    type Error = XzatomaError;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
 }
 
-fn default_grep_excluded_patterns() -> Vec<String> {
-    vec![
-        "*.lock".to_string(),
-        "target/**".to_string(),
-        "node_modules/**".to_string(),
-        ".git/**".to_string(),
-    ]
+impl From<RateLimit> for String {
+    fn from(limit: RateLimit) -> Self {
+        limit.to_string()
+    }
 }
 
-fn default_fetch_timeout_seconds() -> u64 {
-    30
+/// Rate limits shared by the fetch tool and URL mentions
+///
+/// Every request takes a token from the global bucket and from the bucket
+/// of its domain. Domains listed in `overrides` use their own limit instead
+/// of `per_domain`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FetchRateLimitsConfig {
+    /// Budget shared by every domain (default: 10/min)
+    #[serde(default = "default_fetch_global_rate_limit")]
+    pub global: RateLimit,
+
+    /// Budget for each domain (default: 4/min)
+    #[serde(default = "default_fetch_per_domain_rate_limit")]
+    pub per_domain: RateLimit,
+
+    /// Per-domain budgets keyed by host name, e.g. `api.github.com: 2/min`
+    #[serde(default)]
+    pub overrides: std::collections::HashMap<String, RateLimit>,
 }
 
-fn default_max_fetch_size_bytes() -> usize {
-    5 * 1024 * 1024 // 5 MB
+fn default_fetch_global_rate_limit() -> RateLimit {
+    RateLimit::per_minute(10)
 }
 
-fn default_max_fetches_per_minute() -> u32 {
-    10
+fn default_fetch_per_domain_rate_limit() -> RateLimit {
+    RateLimit::per_minute(4)
 }
 
-impl Default for ToolsConfig {
+impl Default for FetchRateLimitsConfig {
     fn default() -> Self {
         Self {
-            max_output_size: default_max_output(),
-            max_file_read_size: default_max_file_read(),
-            grep_max_results_per_page: default_grep_max_results_per_page(),
-            grep_context_lines: default_grep_context_lines(),
-            grep_max_file_size: default_grep_max_file_size(),
-            grep_excluded_patterns: default_grep_excluded_patterns(),
-            fetch_timeout_seconds: default_fetch_timeout_seconds(),
-            max_fetch_size_bytes: default_max_fetch_size_bytes(),
-            max_fetches_per_minute: default_max_fetches_per_minute(),
-            fetch_allowed_domains: None,
-            fetch_blocked_domains: None,
+            global: default_fetch_global_rate_limit(),
+            per_domain: default_fetch_per_domain_rate_limit(),
+            overrides: std::collections::HashMap::new(),
         }
     }
 }
@@ -1332,6 +3227,16 @@ pub enum ExecutionMode {
     FullAutonomous,
 }
 
+impl std::fmt::Display for ExecutionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Interactive => write!(f, "interactive"),
+            Self::RestrictedAutonomous => write!(f, "restricted_autonomous"),
+            Self::FullAutonomous => write!(f, "full_autonomous"),
+        }
+    }
+}
+
 impl Config {
     /// Load configuration from file with environment and CLI overrides
     ///
@@ -1349,7 +3254,16 @@ impl Config {
     /// Returns error if file cannot be read or parsed
     pub fn load(path: &str, cli: &crate::cli::Cli) -> Result<Self> {
         let mut config = if Path::new(path).exists() {
-            Self::from_file(path)?
+            let config = Self::from_file(path)?;
+            for (old, new) in config.agent.subagent.deprecated_keys() {
+                tracing::warn!(
+                    "agent.subagent.{} in {} is deprecated; use agent.subagent.{} instead",
+                    old,
+                    path,
+                    new
+                );
+            }
+            config
         } else {
             tracing::warn!("Config file not found at {}, using defaults", path);
             Self::default_config()
@@ -1357,10 +3271,67 @@ impl Config {
 
         config.apply_env_vars();
         config.apply_cli_overrides(cli);
+        config.apply_path_defaults();
 
         Ok(config)
     }
 
+    /// Finds the configuration file to read
+    ///
+    /// The search path is `config_path`, then the user configuration file
+    /// when `config_path` is [`DEFAULT_CONFIG_PATH`]. A path given with
+    /// `--config` is never replaced by the user file.
+    ///
+    /// # Arguments
+    ///
+    /// * `config_path` - Path from `--config`, or the default
+    /// * `user_config` - The user configuration file, if it can be located
+    ///
+    /// # Returns
+    ///
+    /// Returns the first file that exists, or `None` when none does
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::config::Config;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let user_config = dir.path().join("config.yaml");
+    /// std::fs::write(&user_config, "provider:\n  type: ollama\n").unwrap();
+    ///
+    /// let found = Config::locate_file("config/missing.yaml", Some(&user_config));
+    /// assert_eq!(found, None);
+    /// ```
+    pub fn locate_file(config_path: &str, user_config: Option<&Path>) -> Option<PathBuf> {
+        if Path::new(config_path).exists() {
+            return Some(PathBuf::from(config_path));
+        }
+        if config_path != DEFAULT_CONFIG_PATH {
+            return None;
+        }
+        user_config
+            .filter(|path| path.exists())
+            .map(Path::to_path_buf)
+    }
+
+    /// Builds the configuration from defaults, environment variables, and
+    /// CLI overrides without reading a config file
+    ///
+    /// Used when the config file is project-local and the workspace is not
+    /// trusted.
+    ///
+    /// # Arguments
+    ///
+    /// * `cli` - CLI arguments for overrides
+    pub fn load_without_file(cli: &crate::cli::Cli) -> Self {
+        let mut config = Self::default_config();
+        config.apply_env_vars();
+        config.apply_cli_overrides(cli);
+        config.apply_path_defaults();
+        config
+    }
+
     fn default_config() -> Self {
         Self {
             provider: ProviderConfig {
@@ -1368,22 +3339,121 @@ impl Config {
                 copilot: CopilotConfig::default(),
                 ollama: OllamaConfig::default(),
                 openai: OpenAIConfig::default(),
+                cache: ProviderCacheConfig::default(),
+                quirks: ProviderQuirksConfig::default(),
+                rate_limits: ProviderRateLimitsConfig::default(),
             },
             agent: AgentConfig::default(),
             watcher: WatcherConfig::default(),
             mcp: McpConfig::default(),
             acp: AcpConfig::default(),
             skills: SkillsConfig::default(),
+            storage: StorageConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            credentials: CredentialsConfig::default(),
+            semantic: SemanticConfig::default(),
+            paths: PathsConfig::default(),
+            budget: BudgetConfig::default(),
+            run: RunConfig::default(),
         }
     }
 
+    /// Lists keys in a config document that no config field reads
+    ///
+    /// The document is deserialized and serialized again; keys that do not
+    /// survive the round trip are unknown. Serde ignores them silently, so a
+    /// misspelled key falls back to its default without any error.
+    ///
+    /// # Arguments
+    ///
+    /// * `contents` - YAML text of the configuration file
+    ///
+    /// # Returns
+    ///
+    /// Returns the dotted paths of unknown keys, such as `agent.max_turn`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Config` if the document does not parse.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::config::Config;
+    ///
+    /// let yaml = "provider:\n  type: ollama\nagent:\n  max_turn: 5\n";
+    /// let unknown = Config::unknown_keys(yaml).unwrap();
+    /// assert_eq!(unknown, vec!["agent.max_turn".to_string()]);
+    /// ```
+    pub fn unknown_keys(contents: &str) -> Result<Vec<String>> {
+        let original: serde_yaml::Value = serde_yaml::from_str(contents)
+            .map_err(|e| XzatomaError::Config(format!("Failed to parse config: {}", e)))?;
+        let config: Config = serde_yaml::from_value(original.clone())
+            .map_err(|e| XzatomaError::Config(format!("Failed to parse config: {}", e)))?;
+        let known = serde_yaml::to_value(&config)
+            .map_err(|e| XzatomaError::Config(format!("Failed to serialize config: {}", e)))?;
+
+        let mut unknown = Vec::new();
+        collect_unknown_keys(&original, &known, "", &mut unknown);
+        Ok(unknown)
+    }
+
+    /// Reads a config file, expanding `${VAR}` references in string values
+    ///
+    /// See [`expand_env_vars`] for the syntax.
     fn from_file(path: &str) -> Result<Self> {
         let contents = std::fs::read_to_string(path)
             .map_err(|e| XzatomaError::Config(format!("Failed to read config file: {}", e)))?;
-        serde_yaml::from_str(&contents)
+        let mut value: serde_yaml::Value = serde_yaml::from_str(&contents)
+            .map_err(|e| XzatomaError::Config(format!("Failed to parse config: {}", e)))?;
+
+        let lookup = |name: &str| std::env::var(name).ok();
+        if !expand_env_vars(&mut value, "", &lookup)? {
+            // Parsing the text again keeps line numbers in error messages
+            return serde_yaml::from_str(&contents)
+                .map_err(|e| XzatomaError::Config(format!("Failed to parse config: {}", e)));
+        }
+        serde_yaml::from_value(value)
             .map_err(|e| XzatomaError::Config(format!("Failed to parse config: {}", e)))
     }
 
+    /// Places files without an explicit location under the `paths`
+    /// directories
+    ///
+    /// The subagent conversation database, the credentials file, and the
+    /// skills trust store are opened from their own sections alone, so
+    /// their default locations are resolved here while the `paths` section
+    /// is at hand. Subagent conversations default to the history database.
+    /// The skills trust store finds the environment and platform defaults
+    /// itself and is only filled in for a configured data directory.
+    fn apply_path_defaults(&mut self) {
+        let Ok(paths) = crate::paths::Paths::from_config(self) else {
+            return;
+        };
+        let subagent = &mut self.agent.subagent;
+        if subagent.conversation_database().is_none() {
+            // A section created here keeps the deprecated `enabled` value,
+            // so validation sees no conflict
+            let enabled = subagent.saves_conversations();
+            subagent
+                .persistence
+                .get_or_insert_with(|| SubagentPersistenceConfig {
+                    enabled,
+                    database: None,
+                })
+                .database = Some(crate::storage::SqliteStorage::default_database_path(&paths));
+        }
+        if self.credentials.path.is_none() {
+            self.credentials.path = Some(paths.credentials_file().to_string_lossy().into_owned());
+        }
+        if self.skills.trust_store_path.is_none()
+            && paths.data().source == crate::paths::PathSource::Config
+        {
+            self.skills.trust_store_path =
+                Some(paths.skills_trust_file().to_string_lossy().into_owned());
+        }
+    }
+
     fn apply_env_vars(&mut self) {
         // Provider overrides
         if let Ok(provider_type) = std::env::var("XZATOMA_PROVIDER") {
@@ -1394,6 +3464,14 @@ impl Config {
             self.provider.copilot.model = copilot_model;
         }
 
+        if let Ok(timeout) = std::env::var("XZATOMA_COPILOT_REQUEST_TIMEOUT") {
+            if let Ok(value) = timeout.parse::<u64>() {
+                self.provider.copilot.request_timeout_seconds = value;
+            } else {
+                tracing::warn!("Invalid XZATOMA_COPILOT_REQUEST_TIMEOUT: {}", timeout);
+            }
+        }
+
         if let Ok(ollama_host) = std::env::var("XZATOMA_OLLAMA_HOST") {
             self.provider.ollama.host = ollama_host;
         }
@@ -1410,6 +3488,21 @@ impl Config {
             }
         }
 
+        if let Ok(limit) = std::env::var("XZATOMA_OLLAMA_MAX_CONCURRENT_REQUESTS") {
+            if let Ok(value) = limit.parse::<usize>() {
+                self.provider.ollama.max_concurrent_requests = value;
+            } else {
+                tracing::warn!("Invalid XZATOMA_OLLAMA_MAX_CONCURRENT_REQUESTS: {}", limit);
+            }
+        }
+
+        if let Ok(style) = std::env::var("XZATOMA_OLLAMA_PROMPT_STYLE") {
+            match PromptStyle::parse_str(&style) {
+                Ok(value) => self.provider.ollama.prompt_style = Some(value),
+                Err(e) => tracing::warn!("Invalid XZATOMA_OLLAMA_PROMPT_STYLE: {}", e),
+            }
+        }
+
         if let Ok(openai_api_key) = std::env::var("XZATOMA_OPENAI_API_KEY") {
             self.provider.openai.api_key = openai_api_key;
         }
@@ -2228,6 +4321,97 @@ impl Config {
         if cli.verbose {
             tracing::debug!("Verbose mode enabled");
         }
+
+        if cli.offline {
+            tracing::debug!("Offline mode enabled");
+            self.agent.offline = true;
+        }
+
+        if cli.read_only {
+            tracing::debug!("Read-only mode enabled");
+            self.agent.read_only = true;
+        }
+
+        if cli.allow_fetch {
+            tracing::debug!("Fetching allowed in read-only mode");
+            self.agent.read_only_allow_fetch = true;
+        }
+
+        if cli.no_cache {
+            tracing::debug!("Provider response cache disabled");
+            self.provider.cache.enabled = false;
+        }
+
+        if cli.ignore_budget {
+            tracing::debug!("Monthly budget limit ignored");
+            self.budget.allow_overrun = true;
+        }
+
+        if cli.no_project_mcp {
+            tracing::debug!("Project MCP server discovery disabled");
+            self.mcp.discover_project_config = false;
+        }
+
+        if let crate::cli::Commands::Chat {
+            transcript: Some(path),
+            ..
+        } = &cli.command
+        {
+            tracing::debug!("Chat transcript: {}", path.display());
+            self.agent.chat.transcript_path = Some(path.display().to_string());
+        }
+
+        if let crate::cli::Commands::Chat { quiet: true, .. } = &cli.command {
+            tracing::debug!("Chat startup banner disabled");
+            self.agent.chat.banner = ChatBanner::Off;
+        }
+
+        if let crate::cli::Commands::Run {
+            strict_budget: true,
+            ..
+        } = &cli.command
+        {
+            tracing::debug!("Strict preflight budget enabled");
+            self.agent.preflight.strict = true;
+        }
+
+        if let crate::cli::Commands::Run { record: true, .. } = &cli.command {
+            tracing::debug!("Run recording enabled");
+            self.agent.recording.enabled = true;
+        }
+
+        if let crate::cli::Commands::Run {
+            postmortem: true, ..
+        } = &cli.command
+        {
+            tracing::debug!("Post-mortem reports enabled");
+            self.run.postmortem = true;
+        }
+
+        if let crate::cli::Commands::Run { snapshot: true, .. } = &cli.command {
+            tracing::debug!("Workspace snapshot enabled");
+            self.run.snapshot.enabled = true;
+        }
+
+        if let crate::cli::Commands::Chat {
+            session_id,
+            if_exists,
+            ..
+        }
+        | crate::cli::Commands::Run {
+            session_id,
+            if_exists,
+            ..
+        } = &cli.command
+        {
+            if let Some(session_id) = session_id {
+                tracing::debug!("Session ID: {}", session_id);
+                self.storage.session_id = Some(session_id.clone());
+            }
+            if let Some(if_exists) = if_exists {
+                self.storage.if_exists = *if_exists;
+            }
+        }
     }
 
     /// Validate the configuration
@@ -2258,6 +4442,76 @@ impl Config {
             )));
         }
 
+        let provider_timeouts = [
+            (
+                "provider.copilot.request_timeout_seconds",
+                self.provider.copilot.request_timeout_seconds,
+            ),
+            (
+                "provider.copilot.stream_idle_timeout_seconds",
+                self.provider.copilot.stream_idle_timeout_seconds,
+            ),
+            (
+                "provider.openai.stream_idle_timeout_seconds",
+                self.provider.openai.stream_idle_timeout_seconds,
+            ),
+        ];
+        for (name, value) in provider_timeouts {
+            if value == 0 {
+                return Err(XzatomaError::Config(format!(
+                    "{} must be greater than 0",
+                    name
+                )));
+            }
+        }
+
+        if self.provider.ollama.max_concurrent_requests == 0 {
+            return Err(XzatomaError::Config(
+                "provider.ollama.max_concurrent_requests must be greater than 0".to_string(),
+            ));
+        }
+
+        for entry in &self.provider.quirks.models {
+            if entry.pattern.trim().is_empty() {
+                return Err(XzatomaError::Config(
+                    "provider.quirks.models entries need a non-empty pattern".to_string(),
+                ));
+            }
+            if let Some(max) = entry.max_temperature {
+                if !(0.0..=2.0).contains(&max) {
+                    return Err(XzatomaError::Config(format!(
+                        "provider.quirks.models '{}' max_temperature must be between 0 and 2",
+                        entry.pattern
+                    )));
+                }
+            }
+        }
+
+        let rate_limits = &self.provider.rate_limits;
+        for (name, limits) in [
+            ("copilot", rate_limits.copilot),
+            ("ollama", rate_limits.ollama),
+            ("openai", rate_limits.openai),
+        ] {
+            if limits.requests_per_minute == Some(0) {
+                return Err(XzatomaError::Config(format!(
+                    "provider.rate_limits.{}.requests_per_minute must be greater than 0",
+                    name
+                )));
+            }
+            if limits.max_concurrent == Some(0) {
+                return Err(XzatomaError::Config(format!(
+                    "provider.rate_limits.{}.max_concurrent must be greater than 0",
+                    name
+                )));
+            }
+        }
+        if rate_limits.max_wait_seconds == 0 {
+            return Err(XzatomaError::Config(
+                "provider.rate_limits.max_wait_seconds must be greater than 0".to_string(),
+            ));
+        }
+
         if self.agent.max_turns == 0 {
             return Err(XzatomaError::Config(
                 "max_turns must be greater than 0".to_string(),
@@ -2314,6 +4568,89 @@ impl Config {
             ));
         }
 
+        let preflight = &self.agent.preflight;
+        if preflight.context_fraction <= 0.0 || preflight.context_fraction > 1.0 {
+            return Err(XzatomaError::Config(
+                "preflight.context_fraction must be between 0.0 and 1.0".to_string(),
+            ));
+        }
+        if preflight.max_tokens == Some(0) {
+            return Err(XzatomaError::Config(
+                "preflight.max_tokens must be greater than 0".to_string(),
+            ));
+        }
+        if let Some((model, _)) = preflight
+            .pricing
+            .iter()
+            .find(|(_, price)| !price.is_finite() || **price < 0.0)
+        {
+            return Err(XzatomaError::Config(format!(
+                "preflight.pricing.{} must be a non-negative number",
+                model
+            )));
+        }
+
+        if let Some(limit) = self.budget.monthly_cost_limit {
+            if !limit.is_finite() || limit <= 0.0 {
+                return Err(XzatomaError::Config(
+                    "budget.monthly_cost_limit must be a positive number".to_string(),
+                ));
+            }
+        }
+
+        if !(1..=100).contains(&self.budget.warn_at_percent) {
+            return Err(XzatomaError::Config(
+                "budget.warn_at_percent must be between 1 and 100".to_string(),
+            ));
+        }
+
+        let snapshot = &self.run.snapshot;
+        if snapshot.max_file_size_bytes == 0
+            || snapshot.max_total_bytes < snapshot.max_file_size_bytes
+        {
+            return Err(XzatomaError::Config(
+                "run.snapshot requires 0 < max_file_size_bytes <= max_total_bytes".to_string(),
+            ));
+        }
+        if snapshot.keep == 0 {
+            return Err(XzatomaError::Config(
+                "run.snapshot.keep must be at least 1".to_string(),
+            ));
+        }
+
+        if let Some((model, _)) = self
+            .budget
+            .completion_pricing
+            .iter()
+            .find(|(_, price)| !price.is_finite() || **price < 0.0)
+        {
+            return Err(XzatomaError::Config(format!(
+                "budget.completion_pricing.{} must be a non-negative number",
+                model
+            )));
+        }
+
+        if self.agent.auto_refresh_context && self.agent.auto_refresh_max_bytes == 0 {
+            return Err(XzatomaError::Config(
+                "agent.auto_refresh_max_bytes must be greater than 0 when auto_refresh_context is enabled"
+                    .to_string(),
+            ));
+        }
+
+        if self.agent.recording.max_turn_bytes < 1024 {
+            return Err(XzatomaError::Config(
+                "recording.max_turn_bytes must be at least 1024".to_string(),
+            ));
+        }
+
+        if self.agent.conversation.overflow_target <= 0.0
+            || self.agent.conversation.overflow_target > 1.0
+        {
+            return Err(XzatomaError::Config(
+                "conversation.overflow_target must be between 0.0 and 1.0".to_string(),
+            ));
+        }
+
         if self.agent.tools.max_output_size == 0 {
             return Err(XzatomaError::Config(
                 "tools.max_output_size must be greater than 0".to_string(),
@@ -2326,6 +4663,114 @@ impl Config {
             ));
         }
 
+        let definition_limits = &self.agent.tools.definition_limits;
+        if definition_limits.max_tools == Some(0)
+            || definition_limits.max_description_chars == Some(0)
+            || definition_limits.max_total_bytes == Some(0)
+        {
+            return Err(XzatomaError::Config(
+                "tools.definition_limits limits must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.agent.tools.tool_calls.max_tools_per_turn == Some(0) {
+            return Err(XzatomaError::Config(
+                "tools.tool_calls.max_tools_per_turn must be greater than 0".to_string(),
+            ));
+        }
+
+        let loop_detection = &self.agent.tools.loop_detection;
+        if loop_detection.repeat_threshold < 2 {
+            return Err(XzatomaError::Config(
+                "tools.loop_detection.repeat_threshold must be at least 2".to_string(),
+            ));
+        }
+        if loop_detection.stop_threshold <= loop_detection.repeat_threshold {
+            return Err(XzatomaError::Config(
+                "tools.loop_detection.stop_threshold must be greater than repeat_threshold"
+                    .to_string(),
+            ));
+        }
+
+        let health = &self.agent.tools.health;
+        let valid_rate = |rate: f64| rate > 0.0 && rate <= 1.0;
+        if !valid_rate(health.degraded_failure_rate) || !valid_rate(health.withhold_failure_rate) {
+            return Err(XzatomaError::Config(
+                "tools.health failure rates must be greater than 0 and at most 1".to_string(),
+            ));
+        }
+        if health.withhold_failure_rate < health.degraded_failure_rate {
+            return Err(XzatomaError::Config(
+                "tools.health.withhold_failure_rate must be at least degraded_failure_rate"
+                    .to_string(),
+            ));
+        }
+        if health.min_calls == 0
+            || health.withhold_min_calls < health.min_calls
+            || health.window < health.withhold_min_calls
+        {
+            return Err(XzatomaError::Config(
+                "tools.health requires 0 < min_calls <= withhold_min_calls <= window".to_string(),
+            ));
+        }
+
+        crate::untrusted_content::InjectionScanner::from_config(
+            &self.agent.tools.untrusted_content,
+        )?;
+
+        if self.agent.tools.summary_chunk_bytes < 1024 {
+            return Err(XzatomaError::Config(
+                "tools.summary_chunk_bytes must be at least 1024".to_string(),
+            ));
+        }
+        if self.agent.tools.summary_max_chunks == 0 {
+            return Err(XzatomaError::Config(
+                "tools.summary_max_chunks must be greater than 0".to_string(),
+            ));
+        }
+        if self.agent.tools.paging.max_outputs == 0 {
+            return Err(XzatomaError::Config(
+                "tools.paging.max_outputs must be greater than 0".to_string(),
+            ));
+        }
+
+        let mut custom_names = std::collections::HashSet::new();
+        for tool in &self.agent.tools.custom {
+            crate::tools::script_tool::validate_custom_tool(tool)?;
+            if !custom_names.insert(tool.name.as_str()) {
+                return Err(XzatomaError::Config(format!(
+                    "tools.custom defines the tool '{}' more than once",
+                    tool.name
+                )));
+            }
+        }
+
+        if self.agent.tools.fetch_max_redirects > 20 {
+            return Err(XzatomaError::Config(
+                "tools.fetch_max_redirects cannot exceed 20".to_string(),
+            ));
+        }
+
+        let limits = self.agent.tools.effective_fetch_rate_limits();
+        let mut named = vec![
+            ("global".to_string(), limits.global),
+            ("per_domain".to_string(), limits.per_domain),
+        ];
+        named.extend(
+            limits
+                .overrides
+                .iter()
+                .map(|(domain, limit)| (format!("overrides.{}", domain), *limit)),
+        );
+        for (name, limit) in named {
+            if limit.requests == 0 {
+                return Err(XzatomaError::Config(format!(
+                    "tools.fetch_rate_limits.{} must allow at least 1 request",
+                    name
+                )));
+            }
+        }
+
         // Validate subagent configuration
         if self.agent.subagent.max_depth == 0 {
             return Err(XzatomaError::Config(
@@ -2356,6 +4801,7 @@ impl Config {
                 "agent.subagent.output_max_size must be at least 1024 bytes".to_string(),
             ));
         }
+        self.agent.subagent.validate_persistence()?;
 
         // Validate subagent provider override if specified
         if let Some(ref provider) = self.agent.subagent.provider {
@@ -2369,6 +4815,13 @@ impl Config {
             }
         }
 
+        // Offline mode only allows the local Ollama provider
+        let network_policy = crate::network_policy::NetworkPolicy::from_config(self);
+        network_policy.check_provider(&self.provider.provider_type)?;
+        if let Some(ref provider) = self.agent.subagent.provider {
+            network_policy.check_provider(provider)?;
+        }
+
         if let Some(kafka) = &self.watcher.kafka {
             if kafka.brokers.trim().is_empty() {
                 return Err(XzatomaError::Config(
@@ -2403,6 +4856,12 @@ impl Config {
             }
         }
 
+        if self.watcher.execution.max_concurrent_executions == 0 {
+            return Err(XzatomaError::Config(
+                "watcher.execution.max_concurrent_executions must be at least 1".to_string(),
+            ));
+        }
+
         match self.watcher.watcher_type {
             WatcherType::Generic => {
                 if self.watcher.kafka.is_none() {
@@ -2466,6 +4925,11 @@ impl Config {
         self.mcp.validate()?;
         self.validate_acp_config()?;
         self.validate_skills_config()?;
+        self.validate_storage_config()?;
+        self.validate_telemetry_config()?;
+        self.validate_credentials_config()?;
+        self.validate_semantic_config()?;
+        self.validate_paths_config()?;
 
         Ok(())
     }
@@ -2525,67 +4989,197 @@ impl Config {
             }
         }
 
-        if self.acp.persistence.max_events_per_run == 0 {
+        if self.acp.persistence.max_events_per_run == 0 {
+            return Err(XzatomaError::Config(
+                "acp.persistence.max_events_per_run must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.acp.persistence.max_completed_runs == 0 {
+            return Err(XzatomaError::Config(
+                "acp.persistence.max_completed_runs must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.acp.stdio.max_active_sessions == 0 {
+            return Err(XzatomaError::Config(
+                "acp.stdio.max_active_sessions must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.acp.stdio.session_timeout_seconds == 0 {
+            return Err(XzatomaError::Config(
+                "acp.stdio.session_timeout_seconds must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.acp.stdio.prompt_queue_capacity == 0 {
+            return Err(XzatomaError::Config(
+                "acp.stdio.prompt_queue_capacity must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.acp.stdio.model_list_timeout_seconds == 0 {
+            return Err(XzatomaError::Config(
+                "acp.stdio.model_list_timeout_seconds must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.agent.chat.max_image_bytes == 0 {
+            return Err(XzatomaError::Config(
+                "agent.chat.max_image_bytes must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.acp.stdio.max_image_bytes == 0 {
+            return Err(XzatomaError::Config(
+                "acp.stdio.max_image_bytes must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.acp.stdio.allowed_image_mime_types.is_empty() {
+            return Err(XzatomaError::Config(
+                "acp.stdio.allowed_image_mime_types cannot be empty".to_string(),
+            ));
+        }
+
+        for mime_type in &self.acp.stdio.allowed_image_mime_types {
+            let trimmed = mime_type.trim();
+            if trimmed.is_empty() {
+                return Err(XzatomaError::Config(
+                    "acp.stdio.allowed_image_mime_types cannot contain empty values".to_string(),
+                ));
+            }
+
+            if !trimmed.starts_with("image/") {
+                return Err(XzatomaError::Config(format!(
+                    "acp.stdio.allowed_image_mime_types value '{}' must start with 'image/'",
+                    mime_type
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_storage_config(&self) -> Result<()> {
+        let retention = &self.storage.retention;
+
+        if retention.max_sessions == Some(0) {
             return Err(XzatomaError::Config(
-                "acp.persistence.max_events_per_run must be greater than 0".to_string(),
+                "storage.retention.max_sessions must be greater than 0".to_string(),
             ));
         }
 
-        if self.acp.persistence.max_completed_runs == 0 {
+        if retention.max_age_days == Some(0) {
             return Err(XzatomaError::Config(
-                "acp.persistence.max_completed_runs must be greater than 0".to_string(),
+                "storage.retention.max_age_days must be greater than 0".to_string(),
             ));
         }
 
-        if self.acp.stdio.max_active_sessions == 0 {
+        if retention.max_db_size_mb == Some(0) {
             return Err(XzatomaError::Config(
-                "acp.stdio.max_active_sessions must be greater than 0".to_string(),
+                "storage.retention.max_db_size_mb must be greater than 0".to_string(),
             ));
         }
 
-        if self.acp.stdio.session_timeout_seconds == 0 {
+        if self
+            .storage
+            .session_id
+            .as_deref()
+            .is_some_and(|id| id.trim().is_empty())
+        {
             return Err(XzatomaError::Config(
-                "acp.stdio.session_timeout_seconds must be greater than 0".to_string(),
+                "storage.session_id cannot be empty".to_string(),
             ));
         }
 
-        if self.acp.stdio.prompt_queue_capacity == 0 {
+        Ok(())
+    }
+
+    fn validate_credentials_config(&self) -> Result<()> {
+        if matches!(&self.credentials.path, Some(path) if path.trim().is_empty()) {
             return Err(XzatomaError::Config(
-                "acp.stdio.prompt_queue_capacity must be greater than 0".to_string(),
+                "credentials.path cannot be empty when set".to_string(),
+            ));
+        }
+        if self.credentials.keyring_timeout_seconds == 0 {
+            return Err(XzatomaError::Config(
+                "credentials.keyring_timeout_seconds must be greater than 0".to_string(),
             ));
         }
+        Ok(())
+    }
 
-        if self.acp.stdio.model_list_timeout_seconds == 0 {
+    fn validate_paths_config(&self) -> Result<()> {
+        let dirs = [
+            ("paths.data_dir", &self.paths.data_dir),
+            ("paths.cache_dir", &self.paths.cache_dir),
+            ("paths.state_dir", &self.paths.state_dir),
+        ];
+        for (name, dir) in dirs {
+            if dir.as_deref().is_some_and(|dir| dir.trim().is_empty()) {
+                return Err(XzatomaError::Config(format!(
+                    "{} cannot be empty when set",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_semantic_config(&self) -> Result<()> {
+        let semantic = &self.semantic;
+
+        if semantic.embedding_model.trim().is_empty() {
             return Err(XzatomaError::Config(
-                "acp.stdio.model_list_timeout_seconds must be greater than 0".to_string(),
+                "semantic.embedding_model cannot be empty".to_string(),
             ));
         }
 
-        if self.acp.stdio.max_image_bytes == 0 {
+        if semantic.chunk_lines == 0 {
             return Err(XzatomaError::Config(
-                "acp.stdio.max_image_bytes must be greater than 0".to_string(),
+                "semantic.chunk_lines must be greater than 0".to_string(),
             ));
         }
 
-        if self.acp.stdio.allowed_image_mime_types.is_empty() {
+        if semantic.chunk_overlap >= semantic.chunk_lines {
             return Err(XzatomaError::Config(
-                "acp.stdio.allowed_image_mime_types cannot be empty".to_string(),
+                "semantic.chunk_overlap must be less than semantic.chunk_lines".to_string(),
             ));
         }
 
-        for mime_type in &self.acp.stdio.allowed_image_mime_types {
-            let trimmed = mime_type.trim();
-            if trimmed.is_empty() {
+        if semantic.top_k == 0 {
+            return Err(XzatomaError::Config(
+                "semantic.top_k must be greater than 0".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn validate_telemetry_config(&self) -> Result<()> {
+        if self
+            .telemetry
+            .file
+            .as_deref()
+            .is_some_and(|file| file.trim().is_empty())
+        {
+            return Err(XzatomaError::Config(
+                "telemetry.file cannot be empty".to_string(),
+            ));
+        }
+
+        if let Some(otlp) = &self.telemetry.otlp {
+            if otlp.endpoint.trim().is_empty() {
                 return Err(XzatomaError::Config(
-                    "acp.stdio.allowed_image_mime_types cannot contain empty values".to_string(),
+                    "telemetry.otlp.endpoint cannot be empty".to_string(),
                 ));
             }
-
-            if !trimmed.starts_with("image/") {
-                return Err(XzatomaError::Config(format!(
-                    "acp.stdio.allowed_image_mime_types value '{}' must start with 'image/'",
-                    mime_type
-                )));
+            if otlp.service_name.trim().is_empty() {
+                return Err(XzatomaError::Config(
+                    "telemetry.otlp.service_name cannot be empty".to_string(),
+                ));
             }
         }
 
@@ -2651,6 +5245,192 @@ impl Config {
     }
 }
 
+/// Keys accepted as serde aliases, which never appear after a round trip
+const CONFIG_KEY_ALIASES: &[&str] = &["provider.openai.host"];
+
+/// Records keys of `original` that are missing from the round-tripped `known`
+fn collect_unknown_keys(
+    original: &serde_yaml::Value,
+    known: &serde_yaml::Value,
+    path: &str,
+    unknown: &mut Vec<String>,
+) {
+    match (original, known) {
+        (serde_yaml::Value::Mapping(original), serde_yaml::Value::Mapping(known)) => {
+            for (key, value) in original {
+                let name = match key {
+                    serde_yaml::Value::String(name) => name.clone(),
+                    other => serde_yaml::to_string(other)
+                        .map(|name| name.trim().to_string())
+                        .unwrap_or_default(),
+                };
+                let child_path = if path.is_empty() {
+                    name
+                } else {
+                    format!("{}.{}", path, name)
+                };
+                match known.get(key) {
+                    Some(known_value) => {
+                        collect_unknown_keys(value, known_value, &child_path, unknown)
+                    }
+                    None if CONFIG_KEY_ALIASES.contains(&child_path.as_str()) => {}
+                    // Empty optional fields are skipped when serializing
+                    None if is_empty_yaml_value(value) => {}
+                    None => unknown.push(child_path),
+                }
+            }
+        }
+        (serde_yaml::Value::Sequence(original), serde_yaml::Value::Sequence(known)) => {
+            for (index, (value, known_value)) in original.iter().zip(known).enumerate() {
+                collect_unknown_keys(value, known_value, &format!("{}[{}]", path, index), unknown);
+            }
+        }
+        (serde_yaml::Value::Tagged(original), serde_yaml::Value::Tagged(known)) => {
+            collect_unknown_keys(&original.value, &known.value, path, unknown);
+        }
+        _ => {}
+    }
+}
+
+/// Returns `true` for null and empty mappings or sequences
+fn is_empty_yaml_value(value: &serde_yaml::Value) -> bool {
+    match value {
+        serde_yaml::Value::Null => true,
+        serde_yaml::Value::Mapping(mapping) => mapping.is_empty(),
+        serde_yaml::Value::Sequence(items) => items.is_empty(),
+        _ => false,
+    }
+}
+
+/// Expands environment variable references in every string of a YAML tree
+///
+/// Supported forms in string values:
+///
+/// * `${NAME}` - the value of `NAME`; an error when it is not set
+/// * `${NAME:-default}` - the value of `NAME`, or `default` when it is unset
+///   or empty
+/// * `$${` - a literal `${`
+///
+/// Mapping keys are not expanded.
+///
+/// # Arguments
+///
+/// * `value` - The parsed YAML document, expanded in place
+/// * `path` - Dotted config path of `value`, used in error messages
+/// * `lookup` - Returns the value of an environment variable
+///
+/// # Returns
+///
+/// Returns whether any string was changed.
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Config` naming the variable and the config path
+/// when a variable without a default is not set, or when a reference is
+/// malformed.
+fn expand_env_vars(
+    value: &mut serde_yaml::Value,
+    path: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<bool> {
+    match value {
+        serde_yaml::Value::String(text) => {
+            if !text.contains('$') {
+                return Ok(false);
+            }
+            let expanded = expand_env_string(text, path, lookup)?;
+            let changed = expanded != *text;
+            *text = expanded;
+            Ok(changed)
+        }
+        serde_yaml::Value::Sequence(items) => {
+            let mut changed = false;
+            for (index, item) in items.iter_mut().enumerate() {
+                changed |= expand_env_vars(item, &format!("{}[{}]", path, index), lookup)?;
+            }
+            Ok(changed)
+        }
+        serde_yaml::Value::Mapping(mapping) => {
+            let mut changed = false;
+            for (key, item) in mapping.iter_mut() {
+                let key = match key {
+                    serde_yaml::Value::String(key) => key.clone(),
+                    other => serde_yaml::to_string(other)
+                        .map(|key| key.trim().to_string())
+                        .unwrap_or_default(),
+                };
+                let child_path = if path.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                changed |= expand_env_vars(item, &child_path, lookup)?;
+            }
+            Ok(changed)
+        }
+        serde_yaml::Value::Tagged(tagged) => expand_env_vars(&mut tagged.value, path, lookup),
+        _ => Ok(false),
+    }
+}
+
+/// Expands the environment variable references in one string
+fn expand_env_string(
+    text: &str,
+    path: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start..];
+
+        if let Some(remaining) = after.strip_prefix("$${") {
+            expanded.push_str("${");
+            rest = remaining;
+            continue;
+        }
+        let Some(reference) = after.strip_prefix("${") else {
+            expanded.push('$');
+            rest = &after[1..];
+            continue;
+        };
+
+        let end = reference.find('}').ok_or_else(|| {
+            XzatomaError::Config(format!("Unterminated '${{' in config value at '{}'", path))
+        })?;
+        let (name, default) = match reference[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&reference[..end], None),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(XzatomaError::Config(format!(
+                "Invalid environment variable reference '${{{}}}' at '{}'",
+                &reference[..end],
+                path
+            )));
+        }
+
+        let value = match (lookup(name), default) {
+            (Some(value), Some(default)) if value.is_empty() => default.to_string(),
+            (Some(value), _) => value,
+            (None, Some(default)) => default.to_string(),
+            (None, None) => {
+                return Err(XzatomaError::Config(format!(
+                    "Environment variable '{}' referenced at '{}' is not set",
+                    name, path
+                )))
+            }
+        };
+        expanded.push_str(&value);
+        rest = &reference[end + 1..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
 fn parse_env_bool(value: &str) -> Option<bool> {
     match value.trim().to_ascii_lowercase().as_str() {
         "1" | "true" | "yes" | "on" => Some(true),
@@ -3067,67 +5847,286 @@ enabled: true
             std::env::remove_var("XZATOMA_SKILLS_TRUST_STORE_PATH");
         }
 
-        std::env::set_var("XZATOMA_SKILLS_ENABLED", "false");
-        std::env::set_var("XZATOMA_SKILLS_PROJECT_ENABLED", "false");
-        std::env::set_var("XZATOMA_SKILLS_USER_ENABLED", "false");
-        std::env::set_var("XZATOMA_SKILLS_ACTIVATION_TOOL_ENABLED", "false");
-        std::env::set_var("XZATOMA_SKILLS_PROJECT_TRUST_REQUIRED", "false");
-        std::env::set_var("XZATOMA_SKILLS_ALLOW_CUSTOM_PATHS_WITHOUT_TRUST", "true");
-        std::env::set_var("XZATOMA_SKILLS_STRICT_FRONTMATTER", "false");
-        std::env::set_var(
-            "XZATOMA_SKILLS_ADDITIONAL_PATHS",
-            "/opt/xzatoma/skills:./custom_skills",
-        );
-        std::env::set_var("XZATOMA_SKILLS_MAX_DISCOVERED_SKILLS", "64");
-        std::env::set_var("XZATOMA_SKILLS_MAX_SCAN_DIRECTORIES", "400");
-        std::env::set_var("XZATOMA_SKILLS_MAX_SCAN_DEPTH", "3");
-        std::env::set_var("XZATOMA_SKILLS_CATALOG_MAX_ENTRIES", "16");
-        std::env::set_var(
-            "XZATOMA_SKILLS_TRUST_STORE_PATH",
-            "~/.xzatoma/custom_skills_trust.yaml",
-        );
+        std::env::set_var("XZATOMA_SKILLS_ENABLED", "false");
+        std::env::set_var("XZATOMA_SKILLS_PROJECT_ENABLED", "false");
+        std::env::set_var("XZATOMA_SKILLS_USER_ENABLED", "false");
+        std::env::set_var("XZATOMA_SKILLS_ACTIVATION_TOOL_ENABLED", "false");
+        std::env::set_var("XZATOMA_SKILLS_PROJECT_TRUST_REQUIRED", "false");
+        std::env::set_var("XZATOMA_SKILLS_ALLOW_CUSTOM_PATHS_WITHOUT_TRUST", "true");
+        std::env::set_var("XZATOMA_SKILLS_STRICT_FRONTMATTER", "false");
+        std::env::set_var(
+            "XZATOMA_SKILLS_ADDITIONAL_PATHS",
+            "/opt/xzatoma/skills:./custom_skills",
+        );
+        std::env::set_var("XZATOMA_SKILLS_MAX_DISCOVERED_SKILLS", "64");
+        std::env::set_var("XZATOMA_SKILLS_MAX_SCAN_DIRECTORIES", "400");
+        std::env::set_var("XZATOMA_SKILLS_MAX_SCAN_DEPTH", "3");
+        std::env::set_var("XZATOMA_SKILLS_CATALOG_MAX_ENTRIES", "16");
+        std::env::set_var(
+            "XZATOMA_SKILLS_TRUST_STORE_PATH",
+            "~/.xzatoma/custom_skills_trust.yaml",
+        );
+
+        let mut config = Config::default();
+        config.apply_env_vars();
+
+        assert!(!config.skills.enabled);
+        assert!(!config.skills.project_enabled);
+        assert!(!config.skills.user_enabled);
+        assert!(!config.skills.activation_tool_enabled);
+        assert!(!config.skills.project_trust_required);
+        assert!(config.skills.allow_custom_paths_without_trust);
+        assert!(!config.skills.strict_frontmatter);
+        assert_eq!(
+            config.skills.additional_paths,
+            vec![
+                "/opt/xzatoma/skills".to_string(),
+                "./custom_skills".to_string()
+            ]
+        );
+        assert_eq!(config.skills.max_discovered_skills, 64);
+        assert_eq!(config.skills.max_scan_directories, 400);
+        assert_eq!(config.skills.max_scan_depth, 3);
+        assert_eq!(config.skills.catalog_max_entries, 16);
+        assert_eq!(
+            config.skills.trust_store_path,
+            Some("~/.xzatoma/custom_skills_trust.yaml".to_string())
+        );
+
+        unsafe {
+            std::env::remove_var("XZATOMA_SKILLS_ENABLED");
+            std::env::remove_var("XZATOMA_SKILLS_PROJECT_ENABLED");
+            std::env::remove_var("XZATOMA_SKILLS_USER_ENABLED");
+            std::env::remove_var("XZATOMA_SKILLS_ACTIVATION_TOOL_ENABLED");
+            std::env::remove_var("XZATOMA_SKILLS_PROJECT_TRUST_REQUIRED");
+            std::env::remove_var("XZATOMA_SKILLS_ALLOW_CUSTOM_PATHS_WITHOUT_TRUST");
+            std::env::remove_var("XZATOMA_SKILLS_STRICT_FRONTMATTER");
+            std::env::remove_var("XZATOMA_SKILLS_ADDITIONAL_PATHS");
+            std::env::remove_var("XZATOMA_SKILLS_MAX_DISCOVERED_SKILLS");
+            std::env::remove_var("XZATOMA_SKILLS_MAX_SCAN_DIRECTORIES");
+            std::env::remove_var("XZATOMA_SKILLS_MAX_SCAN_DEPTH");
+            std::env::remove_var("XZATOMA_SKILLS_CATALOG_MAX_ENTRIES");
+            std::env::remove_var("XZATOMA_SKILLS_TRUST_STORE_PATH");
+        }
+    }
+
+    #[test]
+    fn test_config_validation_offline_requires_ollama() {
+        let mut config = Config::default();
+        config.agent.offline = true;
+        config.provider.provider_type = "copilot".to_string();
+        let err = config.validate().unwrap_err();
+        assert!(matches!(err, XzatomaError::NetworkDisabled(_)));
+
+        config.provider.provider_type = "ollama".to_string();
+        assert!(config.validate().is_ok());
+
+        config.agent.subagent.provider = Some("openai".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_offline_cli_flag_enables_offline_config() {
+        let cli = crate::cli::Cli {
+            offline: true,
+            ..crate::cli::Cli::default()
+        };
+        let mut config = Config::default();
+        config.apply_cli_overrides(&cli);
+        assert!(config.agent.offline);
+    }
+
+    #[test]
+    fn test_read_only_cli_flags_enable_read_only_config() {
+        let cli = crate::cli::Cli {
+            read_only: true,
+            allow_fetch: true,
+            ..crate::cli::Cli::default()
+        };
+        let mut config = Config::default();
+        assert!(!config.agent.read_only);
+        config.apply_cli_overrides(&cli);
+        assert!(config.agent.read_only);
+        assert!(config.agent.read_only_allow_fetch);
+    }
+
+    #[test]
+    fn test_no_cache_cli_flag_disables_provider_cache() {
+        let cli = crate::cli::Cli {
+            no_cache: true,
+            ..crate::cli::Cli::default()
+        };
+        let mut config = Config::default();
+        config.provider.cache.enabled = true;
+        config.apply_cli_overrides(&cli);
+        assert!(!config.provider.cache.enabled);
+    }
+
+    #[test]
+    fn test_strict_budget_flag_enables_strict_preflight() {
+        use clap::Parser;
+
+        let cli = crate::cli::Cli::try_parse_from([
+            "xzatoma",
+            "run",
+            "--prompt",
+            "hi",
+            "--strict-budget",
+        ])
+        .unwrap();
+        let mut config = Config::default();
+        config.apply_cli_overrides(&cli);
+        assert!(config.agent.preflight.strict);
+    }
+
+    #[test]
+    fn test_record_flag_enables_run_recording() {
+        use clap::Parser;
+
+        let mut config = Config::default();
+        assert!(!config.agent.recording.enabled);
+
+        let cli = crate::cli::Cli::try_parse_from(["xzatoma", "run", "--prompt", "hi", "--record"])
+            .unwrap();
+        config.apply_cli_overrides(&cli);
+        assert!(config.agent.recording.enabled);
+
+        config.agent.recording.max_turn_bytes = 100;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("recording.max_turn_bytes"));
+    }
+
+    #[test]
+    fn test_postmortem_flag_enables_postmortem_reports() {
+        use clap::Parser;
+
+        let mut config = Config::default();
+        assert!(!config.run.postmortem);
+
+        let cli =
+            crate::cli::Cli::try_parse_from(["xzatoma", "run", "--prompt", "hi", "--postmortem"])
+                .unwrap();
+        config.apply_cli_overrides(&cli);
+        assert!(config.run.postmortem);
+    }
+
+    #[test]
+    fn test_auto_refresh_context_parses_and_validates() {
+        let yaml = r#"
+auto_refresh_context: true
+auto_refresh_max_bytes: 4096
+"#;
+        let agent: AgentConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(agent.auto_refresh_context);
+        assert_eq!(agent.auto_refresh_max_bytes, 4096);
+
+        let mut config = Config::default();
+        config.agent = agent;
+        assert!(config.validate().is_ok());
+        assert_eq!(AgentConfig::default().auto_refresh_max_bytes, 65_536);
+
+        config.agent.auto_refresh_max_bytes = 0;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("agent.auto_refresh_max_bytes"));
+    }
+
+    #[test]
+    fn test_chat_transcript_flag_overrides_config() {
+        use clap::Parser;
+
+        let cli = crate::cli::Cli::try_parse_from(["xzatoma", "chat", "--transcript", "pair.md"])
+            .unwrap();
+        let mut config = Config::default();
+        config.agent.chat.transcript_path = Some("configured.md".to_string());
+        config.apply_cli_overrides(&cli);
+        assert_eq!(
+            config.agent.chat.transcript_path.as_deref(),
+            Some("pair.md")
+        );
+    }
+
+    #[test]
+    fn test_chat_quiet_flag_turns_the_banner_off() {
+        use clap::Parser;
+
+        let chat: ChatConfig = serde_yaml::from_str("banner: minimal\n").unwrap();
+        assert_eq!(chat.banner, ChatBanner::Minimal);
+        assert_eq!(ChatConfig::default().banner, ChatBanner::Full);
+
+        let mut config = Config::default();
+        config.agent.chat = chat;
+
+        let cli = crate::cli::Cli::try_parse_from(["xzatoma", "chat", "--quiet"]).unwrap();
+        config.apply_cli_overrides(&cli);
+        assert_eq!(config.agent.chat.banner, ChatBanner::Off);
+    }
+
+    #[test]
+    fn test_provider_cache_config_parses_from_yaml() {
+        let yaml = r#"
+type: ollama
+cache:
+  enabled: true
+  dir: /tmp/xzatoma-cache
+  max_size_mb: 20
+"#;
+        let provider: ProviderConfig = serde_yaml::from_str(yaml).unwrap();
+        let cache = &provider.cache;
+        assert!(cache.enabled);
+        assert_eq!(cache.dir.as_deref(), Some("/tmp/xzatoma-cache"));
+        assert_eq!(cache.max_size_mb, 20);
+        assert_eq!(cache.ttl_seconds, 7 * 24 * 60 * 60);
+        assert!(!cache.force);
+    }
+
+    #[test]
+    fn test_provider_quirks_validation() {
+        let mut config = Config::default();
+        config.provider.quirks.models.push(ModelQuirksConfig {
+            pattern: "qwen*".to_string(),
+            max_temperature: Some(0.7),
+            ..Default::default()
+        });
+        assert!(config.validate().is_ok());
+
+        config.provider.quirks.models[0].max_temperature = Some(3.0);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("max_temperature"), "{err}");
+
+        config.provider.quirks.models[0].max_temperature = None;
+        config.provider.quirks.models[0].pattern = " ".to_string();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("non-empty pattern"), "{err}");
+    }
 
+    #[test]
+    fn test_provider_rate_limits_validation() {
         let mut config = Config::default();
-        config.apply_env_vars();
+        config.provider.rate_limits.copilot = RateLimitConfig {
+            requests_per_minute: Some(30),
+            max_concurrent: Some(2),
+        };
+        assert!(config.validate().is_ok());
 
-        assert!(!config.skills.enabled);
-        assert!(!config.skills.project_enabled);
-        assert!(!config.skills.user_enabled);
-        assert!(!config.skills.activation_tool_enabled);
-        assert!(!config.skills.project_trust_required);
-        assert!(config.skills.allow_custom_paths_without_trust);
-        assert!(!config.skills.strict_frontmatter);
-        assert_eq!(
-            config.skills.additional_paths,
-            vec![
-                "/opt/xzatoma/skills".to_string(),
-                "./custom_skills".to_string()
-            ]
+        config.provider.rate_limits.openai.requests_per_minute = Some(0);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("provider.rate_limits.openai.requests_per_minute"),
+            "{err}"
         );
-        assert_eq!(config.skills.max_discovered_skills, 64);
-        assert_eq!(config.skills.max_scan_directories, 400);
-        assert_eq!(config.skills.max_scan_depth, 3);
-        assert_eq!(config.skills.catalog_max_entries, 16);
-        assert_eq!(
-            config.skills.trust_store_path,
-            Some("~/.xzatoma/custom_skills_trust.yaml".to_string())
+
+        config.provider.rate_limits.openai.requests_per_minute = None;
+        config.provider.rate_limits.ollama.max_concurrent = Some(0);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("provider.rate_limits.ollama.max_concurrent"),
+            "{err}"
         );
 
-        unsafe {
-            std::env::remove_var("XZATOMA_SKILLS_ENABLED");
-            std::env::remove_var("XZATOMA_SKILLS_PROJECT_ENABLED");
-            std::env::remove_var("XZATOMA_SKILLS_USER_ENABLED");
-            std::env::remove_var("XZATOMA_SKILLS_ACTIVATION_TOOL_ENABLED");
-            std::env::remove_var("XZATOMA_SKILLS_PROJECT_TRUST_REQUIRED");
-            std::env::remove_var("XZATOMA_SKILLS_ALLOW_CUSTOM_PATHS_WITHOUT_TRUST");
-            std::env::remove_var("XZATOMA_SKILLS_STRICT_FRONTMATTER");
-            std::env::remove_var("XZATOMA_SKILLS_ADDITIONAL_PATHS");
-            std::env::remove_var("XZATOMA_SKILLS_MAX_DISCOVERED_SKILLS");
-            std::env::remove_var("XZATOMA_SKILLS_MAX_SCAN_DIRECTORIES");
-            std::env::remove_var("XZATOMA_SKILLS_MAX_SCAN_DEPTH");
-            std::env::remove_var("XZATOMA_SKILLS_CATALOG_MAX_ENTRIES");
-            std::env::remove_var("XZATOMA_SKILLS_TRUST_STORE_PATH");
-        }
+        config.provider.rate_limits.ollama.max_concurrent = None;
+        config.provider.rate_limits.max_wait_seconds = 0;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("max_wait_seconds"), "{err}");
     }
 
     #[test]
@@ -3194,6 +6193,27 @@ enabled: true
         assert!(chat_config.persist_special_commands);
     }
 
+    #[test]
+    fn test_chat_config_strip_mentions_default_and_yaml() {
+        assert!(ChatConfig::default().strip_mentions);
+
+        let chat_config: ChatConfig = serde_yaml::from_str("strip_mentions: false").unwrap();
+        assert!(!chat_config.strip_mentions);
+        assert!(chat_config.persist_special_commands);
+    }
+
+    #[test]
+    fn test_chat_config_max_image_bytes_default_and_validation() {
+        assert_eq!(ChatConfig::default().max_image_bytes, 5 * 1024 * 1024);
+
+        let mut config = Config::default();
+        config.agent.chat.max_image_bytes = 0;
+        let result = config.validate();
+        assert!(
+            matches!(result, Err(XzatomaError::Config(message)) if message.contains("agent.chat.max_image_bytes"))
+        );
+    }
+
     #[test]
     fn test_config_from_yaml() {
         let yaml = r#"
@@ -3281,6 +6301,17 @@ kafka:
 
         let config: WatcherConfig = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(config.watcher_type, WatcherType::XZepr);
+        assert!(config.reload.enabled);
+        assert_eq!(config.reload.debounce_ms, 500);
+    }
+
+    #[test]
+    fn test_zero_max_concurrent_executions_is_rejected() {
+        let mut config = Config::default();
+        config.watcher.execution.max_concurrent_executions = 0;
+
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("watcher.execution.max_concurrent_executions"));
     }
 
     #[test]
@@ -3395,12 +6426,185 @@ kafka:
         assert_eq!(mode, ExecutionMode::RestrictedAutonomous);
     }
 
+    #[test]
+    fn test_unknown_keys_reports_nested_typos() {
+        let yaml = r#"
+provider:
+  type: openai
+  openai:
+    host: http://localhost:8080/v1
+    modle: gpt-4o
+agent:
+  max_turns: 10
+  terminal:
+    timeout_secs: 5
+telemtry:
+  enabled: true
+"#;
+        let unknown = Config::unknown_keys(yaml).unwrap();
+        assert_eq!(
+            unknown,
+            vec![
+                "provider.openai.modle".to_string(),
+                "agent.terminal.timeout_secs".to_string(),
+                "telemtry".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_keys_empty_for_valid_config() {
+        let yaml = r#"
+provider:
+  type: ollama
+  ollama:
+    host: http://localhost:11434
+    model: llama3.2:latest
+agent:
+  max_turns: 10
+"#;
+        assert!(Config::unknown_keys(yaml).unwrap().is_empty());
+    }
+
+    fn lookup_from(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: Vec<(String, String)> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| {
+            vars.iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        }
+    }
+
+    #[test]
+    fn test_expand_env_vars_walks_nested_mappings_and_sequences() {
+        let mut value: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+provider:
+  copilot:
+    api_base: "${COPILOT_BASE}/v1"
+watcher:
+  kafka:
+    security:
+      sasl_password: ${SASL_PASSWORD}
+mcp:
+  servers:
+    - args: ["--token", "${TOKEN}", "plain"]
+agent:
+  max_turns: 5
+"#,
+        )
+        .unwrap();
+        let lookup = lookup_from(&[
+            ("COPILOT_BASE", "https://copilot.example.com"),
+            ("SASL_PASSWORD", "s3cret"),
+            ("TOKEN", "abc"),
+        ]);
+
+        assert!(expand_env_vars(&mut value, "", &lookup).unwrap());
+        assert_eq!(
+            value["provider"]["copilot"]["api_base"],
+            "https://copilot.example.com/v1"
+        );
+        assert_eq!(
+            value["watcher"]["kafka"]["security"]["sasl_password"],
+            "s3cret"
+        );
+        assert_eq!(value["mcp"]["servers"][0]["args"][1], "abc");
+        assert_eq!(value["agent"]["max_turns"], 5);
+    }
+
+    #[test]
+    fn test_expand_env_string_defaults_and_escapes() {
+        let lookup = lookup_from(&[("SET", "value"), ("EMPTY", "")]);
+
+        let expand = |text: &str| expand_env_string(text, "field", &lookup).unwrap();
+        assert_eq!(expand("${SET:-fallback}"), "value");
+        assert_eq!(expand("${MISSING:-fallback}"), "fallback");
+        assert_eq!(expand("${EMPTY:-fallback}"), "fallback");
+        assert_eq!(expand("${EMPTY}"), "");
+        assert_eq!(expand("${MISSING:-}"), "");
+        assert_eq!(expand("$${SET}"), "${SET}");
+        assert_eq!(
+            expand("cost: $5 and $${literal} and ${SET}"),
+            "cost: $5 and ${literal} and value"
+        );
+    }
+
+    #[test]
+    fn test_expand_env_vars_missing_variable_names_variable_and_path() {
+        let mut value: serde_yaml::Value = serde_yaml::from_str(
+            "watcher:\n  kafka:\n    security:\n      sasl_password: ${XZATOMA_TEST_UNSET}\n",
+        )
+        .unwrap();
+
+        let error = expand_env_vars(&mut value, "", &lookup_from(&[]))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("XZATOMA_TEST_UNSET"), "{}", error);
+        assert!(
+            error.contains("watcher.kafka.security.sasl_password"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_expand_env_string_rejects_malformed_references() {
+        let lookup = lookup_from(&[]);
+        assert!(expand_env_string("${UNTERMINATED", "field", &lookup).is_err());
+        assert!(expand_env_string("${}", "field", &lookup).is_err());
+        assert!(expand_env_string("${BAD-NAME}", "field", &lookup).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_from_file_expands_environment_variables() {
+        let _base = EnvVarGuard::set("XZATOMA_TEST_EXPAND_API_BASE", "https://proxy.example.com");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(
+            &path,
+            r#"
+provider:
+  type: copilot
+  copilot:
+    api_base: ${XZATOMA_TEST_EXPAND_API_BASE}
+agent:
+  max_turns: 10
+watcher:
+  kafka:
+    brokers: ${XZATOMA_TEST_EXPAND_BROKERS:-localhost:9092}
+    topic: events
+"#,
+        )
+        .unwrap();
+
+        let config = Config::from_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(
+            config.provider.copilot.api_base.as_deref(),
+            Some("https://proxy.example.com")
+        );
+        assert_eq!(config.watcher.kafka.unwrap().brokers, "localhost:9092");
+    }
+
     #[test]
     fn test_load_nonexistent_file_uses_defaults() {
         let cli = crate::cli::Cli {
             config: None,
             verbose: false,
             storage_path: None,
+            offline: false,
+            read_only: false,
+            allow_fetch: false,
+            no_cache: false,
+            ignore_budget: false,
+            no_project_mcp: false,
+            no_setup: false,
+            raw_numbers: false,
+            color: "auto".to_string(),
             command: crate::cli::Commands::Auth {
                 provider: Some("copilot".to_string()),
             },
@@ -3411,59 +6615,256 @@ kafka:
     }
 
     #[test]
-    fn test_conversation_config_defaults() {
-        let config = ConversationConfig::default();
-        assert_eq!(config.max_tokens, 100_000);
-        assert_eq!(config.min_retain_turns, 5);
-        assert_eq!(config.prune_threshold, 0.8);
-        assert_eq!(config.warning_threshold, 0.85);
-        assert_eq!(config.auto_summary_threshold, 0.90);
-        assert_eq!(config.summary_model, None);
+    fn test_locate_file_only_falls_back_from_the_default_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let user_config = dir.path().join("user.yaml");
+        std::fs::write(&user_config, "provider:\n  type: ollama\n").unwrap();
+        let explicit = dir.path().join("explicit.yaml");
+        let explicit = explicit.to_str().unwrap();
+
+        assert_eq!(Config::locate_file(explicit, Some(&user_config)), None);
+
+        std::fs::write(explicit, "provider:\n  type: copilot\n").unwrap();
+        assert_eq!(
+            Config::locate_file(explicit, Some(&user_config)),
+            Some(PathBuf::from(explicit))
+        );
+    }
+
+    #[test]
+    fn test_conversation_config_defaults() {
+        let config = ConversationConfig::default();
+        assert_eq!(config.max_tokens, 100_000);
+        assert_eq!(config.min_retain_turns, 5);
+        assert_eq!(config.prune_threshold, 0.8);
+        assert_eq!(config.warning_threshold, 0.85);
+        assert_eq!(config.auto_summary_threshold, 0.90);
+        assert_eq!(config.summary_model, None);
+    }
+
+    #[test]
+    fn test_conversation_config_warning_threshold_validation() {
+        let mut config = Config::default();
+        config.agent.conversation.warning_threshold = 0.0;
+        assert!(config.validate().is_err());
+
+        config.agent.conversation.warning_threshold = 1.5;
+        assert!(config.validate().is_err());
+
+        config.agent.conversation.warning_threshold = 0.85;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_conversation_config_auto_summary_threshold_validation() {
+        let mut config = Config::default();
+        config.agent.conversation.auto_summary_threshold = 0.0;
+        assert!(config.validate().is_err());
+
+        config.agent.conversation.auto_summary_threshold = 1.5;
+        assert!(config.validate().is_err());
+
+        config.agent.conversation.auto_summary_threshold = 0.90;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_conversation_config_overflow_target_validation() {
+        let mut config = Config::default();
+        config.agent.conversation.overflow_target = 0.0;
+        assert!(config.validate().is_err());
+
+        config.agent.conversation.overflow_target = 1.5;
+        assert!(config.validate().is_err());
+
+        config.agent.conversation.overflow_target = 0.6;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_conversation_config_threshold_ordering_validation() {
+        let mut config = Config::default();
+        config.agent.conversation.warning_threshold = 0.90;
+        config.agent.conversation.auto_summary_threshold = 0.85;
+        assert!(config.validate().is_err());
+
+        config.agent.conversation.warning_threshold = 0.85;
+        config.agent.conversation.auto_summary_threshold = 0.90;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_tools_config_defaults() {
+        let config = ToolsConfig::default();
+        assert_eq!(config.max_output_size, 5_242_880);
+        assert_eq!(config.max_file_read_size, 10_485_760);
+    }
+
+    #[test]
+    fn test_rate_limit_parse_and_display() {
+        let limit: RateLimit = "2/min".parse().unwrap();
+        assert_eq!(limit, RateLimit::per_minute(2));
+        assert_eq!("5/s".parse::<RateLimit>().unwrap().period.as_secs(), 1);
+        assert_eq!(
+            "100/hour".parse::<RateLimit>().unwrap().to_string(),
+            "100/hour"
+        );
+        assert!("ten/min".parse::<RateLimit>().is_err());
+        assert!("10/day".parse::<RateLimit>().is_err());
+        assert!("10".parse::<RateLimit>().is_err());
+    }
+
+    #[test]
+    fn test_fetch_rate_limits_from_yaml() {
+        let yaml = r#"
+fetch_rate_limits:
+  global: 20/min
+  overrides:
+    api.github.com: 2/min
+"#;
+        let tools: ToolsConfig = serde_yaml::from_str(yaml).unwrap();
+        let limits = tools.effective_fetch_rate_limits();
+        assert_eq!(limits.global, RateLimit::per_minute(20));
+        assert_eq!(limits.per_domain, RateLimit::per_minute(4));
+        assert_eq!(limits.overrides["api.github.com"], RateLimit::per_minute(2));
+    }
+
+    #[test]
+    fn test_fetch_rate_limits_fall_back_to_max_fetches_per_minute() {
+        let tools = ToolsConfig {
+            max_fetches_per_minute: 7,
+            ..ToolsConfig::default()
+        };
+        let limits = tools.effective_fetch_rate_limits();
+        assert_eq!(limits.global, RateLimit::per_minute(7));
+        assert_eq!(limits.per_domain, RateLimit::per_minute(4));
+    }
+
+    #[test]
+    fn test_fetch_ssrf_settings_defaults_and_validation() {
+        let tools = ToolsConfig::default();
+        assert!(tools.fetch_allowed_ports.is_empty());
+        assert_eq!(tools.fetch_max_redirects, 5);
+
+        let mut config = Config::default();
+        config.agent.tools.fetch_max_redirects = 21;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_untrusted_content_defaults_and_validation() {
+        let untrusted = ToolsConfig::default().untrusted_content;
+        assert!(untrusted.enabled);
+        assert!(untrusted.use_default_patterns);
+        assert!(untrusted.patterns.is_empty());
+
+        let mut config = Config::default();
+        config.agent.tools.untrusted_content.patterns = vec!["(unclosed".to_string()];
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("tools.untrusted_content.patterns"));
+    }
+
+    #[test]
+    fn test_tool_definition_limits_defaults_and_validation() {
+        let tools: ToolsConfig =
+            serde_yaml::from_str("definition_limits:\n  strategy: fail\n").unwrap();
+        assert_eq!(tools.definition_limits.strategy, ToolLimitStrategy::Fail);
+        assert!(tools.definition_limits.mcp_priorities.is_empty());
+        assert_eq!(
+            ToolsConfig::default().definition_limits.strategy,
+            ToolLimitStrategy::Truncate
+        );
+
+        let mut config = Config::default();
+        config.agent.tools.definition_limits.max_tools = Some(0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_tool_calls_defaults_and_validation() {
+        let tools: ToolsConfig =
+            serde_yaml::from_str("tool_calls:\n  max_tools_per_turn: 4\n").unwrap();
+        assert_eq!(tools.tool_calls.max_tools_per_turn, Some(4));
+        assert_eq!(tools.tool_calls.tool_choice, ToolChoiceMode::Auto);
+        assert!(tools.tool_calls.supports_tool_choice.is_none());
+
+        let mut config = Config::default();
+        config.agent.tools.tool_calls.max_tools_per_turn = Some(0);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("max_tools_per_turn"));
+    }
+
+    #[test]
+    fn test_preflight_validation() {
+        let mut config = Config::default();
+        config.agent.preflight.context_fraction = 1.5;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("preflight.context_fraction"));
+
+        let mut config = Config::default();
+        config
+            .agent
+            .preflight
+            .pricing
+            .insert("gpt-5".to_string(), -1.0);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("preflight.pricing.gpt-5"));
     }
 
     #[test]
-    fn test_conversation_config_warning_threshold_validation() {
+    fn test_loop_detection_validation() {
         let mut config = Config::default();
-        config.agent.conversation.warning_threshold = 0.0;
-        assert!(config.validate().is_err());
-
-        config.agent.conversation.warning_threshold = 1.5;
-        assert!(config.validate().is_err());
+        config.agent.tools.loop_detection.repeat_threshold = 1;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("repeat_threshold"));
 
-        config.agent.conversation.warning_threshold = 0.85;
-        assert!(config.validate().is_ok());
+        let mut config = Config::default();
+        config.agent.tools.loop_detection.stop_threshold = 3;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("stop_threshold"));
     }
 
     #[test]
-    fn test_conversation_config_auto_summary_threshold_validation() {
+    fn test_tool_health_validation() {
         let mut config = Config::default();
-        config.agent.conversation.auto_summary_threshold = 0.0;
-        assert!(config.validate().is_err());
+        config.agent.tools.health.degraded_failure_rate = 0.0;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("failure rates"));
 
-        config.agent.conversation.auto_summary_threshold = 1.5;
-        assert!(config.validate().is_err());
+        let mut config = Config::default();
+        config.agent.tools.health.withhold_failure_rate = 0.4;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("withhold_failure_rate"));
 
-        config.agent.conversation.auto_summary_threshold = 0.90;
-        assert!(config.validate().is_ok());
+        let mut config = Config::default();
+        config.agent.tools.health.window = 4;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("withhold_min_calls <= window"));
     }
 
     #[test]
-    fn test_conversation_config_threshold_ordering_validation() {
+    fn test_run_snapshot_validation() {
         let mut config = Config::default();
-        config.agent.conversation.warning_threshold = 0.90;
-        config.agent.conversation.auto_summary_threshold = 0.85;
-        assert!(config.validate().is_err());
+        config.run.snapshot.max_total_bytes = config.run.snapshot.max_file_size_bytes - 1;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("max_file_size_bytes <= max_total_bytes"));
 
-        config.agent.conversation.warning_threshold = 0.85;
-        config.agent.conversation.auto_summary_threshold = 0.90;
-        assert!(config.validate().is_ok());
+        let mut config = Config::default();
+        config.run.snapshot.keep = 0;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("run.snapshot.keep"));
     }
 
     #[test]
-    fn test_tools_config_defaults() {
-        let config = ToolsConfig::default();
-        assert_eq!(config.max_output_size, 5_242_880);
-        assert_eq!(config.max_file_read_size, 10_485_760);
+    fn test_validate_rejects_zero_fetch_rate_limit() {
+        let mut config = Config::default();
+        let mut limits = FetchRateLimitsConfig::default();
+        limits
+            .overrides
+            .insert("example.com".to_string(), RateLimit::per_minute(0));
+        config.agent.tools.fetch_rate_limits = Some(limits);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("overrides.example.com"));
     }
 
     #[test]
@@ -3493,6 +6894,19 @@ allow_mode_switching: false
         assert_eq!(config.default_mode, "write");
         assert_eq!(config.default_safety, "yolo");
         assert!(!config.allow_mode_switching);
+        assert!(!config.confirm_each_delete);
+    }
+
+    #[test]
+    fn test_chat_config_confirm_once_from_yaml() {
+        let yaml = r#"
+default_safety: confirm_once
+confirm_each_delete: true
+"#;
+        let config: ChatConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.default_safety, "confirm_once");
+        assert!(config.confirm_each_delete);
+        assert!(!config.confirm_each_dangerous_command);
     }
 
     #[test]
@@ -3510,7 +6924,8 @@ allow_mode_switching: false
         assert_eq!(config.default_max_turns, 10);
         assert_eq!(config.output_max_size, 1_048_576);
         assert!(config.telemetry_enabled);
-        assert!(!config.persistence_enabled);
+        assert!(!config.saves_conversations());
+        assert!(config.deprecated_keys().is_empty());
     }
 
     #[test]
@@ -3555,7 +6970,7 @@ persistence_enabled: true
         assert_eq!(config.default_max_turns, 20);
         assert_eq!(config.output_max_size, 8192);
         assert!(!config.telemetry_enabled);
-        assert!(config.persistence_enabled);
+        assert!(config.saves_conversations());
     }
 
     #[test]
@@ -3672,6 +7087,46 @@ output_max_size: 4096
         assert_eq!(config.provider.ollama.request_timeout_seconds, 300);
     }
 
+    #[test]
+    fn test_ollama_max_concurrent_requests_default_env_and_validation() {
+        assert_eq!(OllamaConfig::default().max_concurrent_requests, 2);
+        let config: OllamaConfig =
+            serde_yaml::from_str("max_concurrent_requests: 4\n").expect("deserialize failed");
+        assert_eq!(config.max_concurrent_requests, 4);
+
+        let _limit = EnvVarGuard::set("XZATOMA_OLLAMA_MAX_CONCURRENT_REQUESTS", "3");
+        let mut config = Config::default();
+        config.apply_env_vars();
+        assert_eq!(config.provider.ollama.max_concurrent_requests, 3);
+
+        config.provider.ollama.max_concurrent_requests = 0;
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("max_concurrent_requests"));
+    }
+
+    #[test]
+    fn test_apply_env_vars_overrides_ollama_prompt_style() {
+        let _style = EnvVarGuard::set("XZATOMA_OLLAMA_PROMPT_STYLE", "concise");
+        let mut config = Config::default();
+        config.apply_env_vars();
+        assert_eq!(
+            config.provider.ollama.prompt_style,
+            Some(PromptStyle::Concise)
+        );
+    }
+
+    #[test]
+    fn test_ollama_prompt_style_deserializes_from_yaml() {
+        let yaml = r#"
+host: http://localhost:11434
+model: granite4:3b
+prompt_style: full
+"#;
+        let config: OllamaConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.prompt_style, Some(PromptStyle::Full));
+        assert!(OllamaConfig::default().prompt_style.is_none());
+    }
+
     #[test]
     fn test_openai_config_defaults() {
         let config = OpenAIConfig::default();
@@ -3758,7 +7213,7 @@ agent: {}
     #[test]
     fn test_openai_config_request_timeout_default() {
         let config = OpenAIConfig::default();
-        assert_eq!(config.request_timeout_seconds, 600);
+        assert_eq!(config.request_timeout_seconds, 120);
     }
 
     #[test]
@@ -3772,7 +7227,7 @@ agent: {}
     fn test_openai_config_deserialize_omits_timeout_uses_default() {
         let yaml = "model: gpt-4o\n";
         let config: OpenAIConfig = serde_yaml::from_str(yaml).expect("deserialize failed");
-        assert_eq!(config.request_timeout_seconds, 600);
+        assert_eq!(config.request_timeout_seconds, 120);
     }
 
     #[test]
@@ -4389,6 +7844,33 @@ agent:
         assert!(!cfg.agent.subagent.chat_enabled);
     }
 
+    #[test]
+    fn test_telemetry_otlp_config_parses_and_validates() {
+        let config = r#"
+provider:
+  type: copilot
+  copilot:
+    model: gpt-5.3-codex
+
+telemetry:
+  otlp:
+    endpoint: http://localhost:4318
+    headers:
+      authorization: Bearer token
+"#;
+
+        let mut cfg: Config = serde_yaml::from_str(config).unwrap();
+        assert!(cfg.validate().is_ok());
+        let otlp = cfg.telemetry.otlp.clone().unwrap();
+        assert_eq!(otlp.endpoint, "http://localhost:4318");
+        assert_eq!(otlp.headers["authorization"], "Bearer token");
+        assert_eq!(otlp.service_name, "xzatoma");
+
+        cfg.telemetry.otlp.as_mut().unwrap().endpoint = " ".to_string();
+        let err = cfg.validate().unwrap_err();
+        assert!(err.to_string().contains("telemetry.otlp.endpoint"));
+    }
+
     #[test]
     fn test_subagent_config_all_fields_valid() {
         let config = r#"
@@ -4423,7 +7905,7 @@ agent:
         assert_eq!(cfg.agent.subagent.default_max_turns, 15);
         assert_eq!(cfg.agent.subagent.output_max_size, 8192);
         assert!(cfg.agent.subagent.telemetry_enabled);
-        assert!(!cfg.agent.subagent.persistence_enabled);
+        assert!(!cfg.agent.subagent.saves_conversations());
         assert_eq!(cfg.agent.subagent.max_executions, Some(100));
         assert_eq!(cfg.agent.subagent.max_total_tokens, Some(50000));
         assert_eq!(cfg.agent.subagent.max_total_time, Some(3600));
@@ -4644,7 +8126,7 @@ agent:
         assert_eq!(cfg.agent.subagent.default_max_turns, 10);
         assert_eq!(cfg.agent.subagent.output_max_size, 1_048_576);
         assert!(cfg.agent.subagent.telemetry_enabled);
-        assert!(!cfg.agent.subagent.persistence_enabled);
+        assert!(!cfg.agent.subagent.saves_conversations());
         assert_eq!(cfg.agent.subagent.provider, None);
         assert_eq!(cfg.agent.subagent.model, None);
         assert!(!cfg.agent.subagent.chat_enabled);
@@ -4728,11 +8210,91 @@ agent:
 
         let cfg: Config = serde_yaml::from_str(config).unwrap();
         assert!(cfg.validate().is_ok());
-        assert!(cfg.agent.subagent.persistence_enabled);
+        assert!(cfg.agent.subagent.saves_conversations());
+        assert_eq!(
+            cfg.agent.subagent.conversation_database(),
+            Some(PathBuf::from("/tmp/xzatoma_conversations.db"))
+        );
+        assert_eq!(
+            cfg.agent.subagent.deprecated_keys(),
+            vec![
+                ("persistence_enabled", "persistence.enabled"),
+                ("persistence_path", "persistence.database"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_subagent_config_persistence_section() {
+        let config = r#"
+provider:
+  type: copilot
+  copilot:
+    model: gpt-5.3-codex
+
+agent:
+  max_turns: 10
+  subagent:
+    persistence:
+      enabled: true
+      database: /tmp/xzatoma_history.db
+"#;
+
+        let cfg: Config = serde_yaml::from_str(config).unwrap();
+        assert!(cfg.validate().is_ok());
+        assert!(cfg.agent.subagent.saves_conversations());
         assert_eq!(
-            cfg.agent.subagent.persistence_path,
-            "/tmp/xzatoma_conversations.db"
+            cfg.agent.subagent.conversation_database(),
+            Some(PathBuf::from("/tmp/xzatoma_history.db"))
         );
+        assert!(cfg.agent.subagent.deprecated_keys().is_empty());
+    }
+
+    #[test]
+    fn test_subagent_config_persistence_section_database_is_optional() {
+        let cfg: SubagentConfig = serde_yaml::from_str("persistence:\n  enabled: true\n").unwrap();
+        assert!(cfg.saves_conversations());
+        assert_eq!(cfg.conversation_database(), None);
+    }
+
+    #[test]
+    fn test_subagent_config_persistence_enabled_conflict_is_rejected() {
+        let mut config = Config::default();
+        config.agent.subagent.persistence_enabled = Some(true);
+        config.agent.subagent.persistence = Some(SubagentPersistenceConfig {
+            enabled: false,
+            database: None,
+        });
+
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("persistence_enabled"), "{}", err);
+        assert!(err.contains("remove the deprecated key"), "{}", err);
+    }
+
+    #[test]
+    fn test_subagent_config_persistence_path_conflict_is_rejected() {
+        let mut config = Config::default();
+        config.agent.subagent.persistence_path = Some("/tmp/old.db".to_string());
+        config.agent.subagent.persistence = Some(SubagentPersistenceConfig {
+            enabled: true,
+            database: Some(PathBuf::from("/tmp/new.db")),
+        });
+
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("persistence_path"), "{}", err);
+    }
+
+    #[test]
+    fn test_subagent_config_matching_deprecated_keys_are_accepted() {
+        let mut config = Config::default();
+        config.agent.subagent.persistence_enabled = Some(true);
+        config.agent.subagent.persistence_path = Some("/tmp/same.db".to_string());
+        config.agent.subagent.persistence = Some(SubagentPersistenceConfig {
+            enabled: true,
+            database: Some(PathBuf::from("/tmp/same.db")),
+        });
+
+        assert!(config.validate().is_ok());
     }
 
     #[test]
@@ -4829,6 +8391,198 @@ agent:
         assert_eq!(cfg.agent.subagent.model, Some("granite3.2:2b".to_string()));
         assert_eq!(cfg.agent.subagent.default_max_turns, 5);
     }
+
+    #[test]
+    fn test_storage_retention_parses_from_yaml() {
+        let config = r#"
+provider:
+  type: ollama
+agent:
+  max_turns: 10
+storage:
+  retention:
+    max_sessions: 200
+    max_age_days: 90
+"#;
+
+        let cfg: Config = serde_yaml::from_str(config).unwrap();
+        assert!(cfg.validate().is_ok());
+        assert_eq!(cfg.storage.retention.max_sessions, Some(200));
+        assert_eq!(cfg.storage.retention.max_age_days, Some(90));
+        assert_eq!(cfg.storage.retention.max_db_size_mb, None);
+        assert!(!Config::default().storage.retention.is_enabled());
+    }
+
+    #[test]
+    fn test_session_id_flags_set_storage_config() {
+        use crate::cli::Cli;
+        use crate::storage::types::IfExists;
+        use clap::Parser;
+
+        let mut config = Config::default();
+        assert_eq!(config.storage.if_exists, IfExists::Replace);
+        let cli = Cli::try_parse_from([
+            "xzatoma",
+            "chat",
+            "--session-id",
+            "nightly-lint",
+            "--if-exists",
+            "fail",
+        ])
+        .unwrap();
+        config.apply_cli_overrides(&cli);
+        assert_eq!(config.storage.session_id.as_deref(), Some("nightly-lint"));
+        assert_eq!(config.storage.if_exists, IfExists::Fail);
+
+        config.storage.session_id = Some("  ".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_paths_config_parses_and_rejects_empty_dirs() {
+        let config = r#"
+provider:
+  type: ollama
+agent:
+  max_turns: 10
+paths:
+  data_dir: /srv/xzatoma/data
+  state_dir: /srv/xzatoma/state
+"#;
+
+        let cfg: Config = serde_yaml::from_str(config).unwrap();
+        assert!(cfg.validate().is_ok());
+        assert_eq!(cfg.paths.data_dir.as_deref(), Some("/srv/xzatoma/data"));
+        assert!(cfg.paths.cache_dir.is_none());
+
+        let mut config = Config::default();
+        config.paths.cache_dir = Some(" ".to_string());
+        assert!(
+            matches!(config.validate(), Err(XzatomaError::Config(message)) if message.contains("paths.cache_dir"))
+        );
+    }
+
+    #[test]
+    fn test_semantic_config_defaults_and_validation() {
+        let config = Config::default();
+        assert_eq!(config.semantic.embedding_model, "nomic-embed-text");
+        assert_eq!(config.semantic.top_k, 5);
+        assert!(config.validate().is_ok());
+
+        let mut config = Config::default();
+        config.semantic.chunk_overlap = config.semantic.chunk_lines;
+        assert!(
+            matches!(config.validate(), Err(XzatomaError::Config(message)) if message.contains("semantic.chunk_overlap"))
+        );
+
+        let mut config = Config::default();
+        config.semantic.top_k = 0;
+        assert!(
+            matches!(config.validate(), Err(XzatomaError::Config(message)) if message.contains("semantic.top_k"))
+        );
+    }
+
+    #[test]
+    fn test_config_validate_rejects_zero_retention_limits() {
+        let mut config = Config::default();
+        config.storage.retention.max_db_size_mb = Some(0);
+
+        let result = config.validate();
+
+        assert!(
+            matches!(result, Err(XzatomaError::Config(message)) if message.contains("storage.retention.max_db_size_mb"))
+        );
+    }
+
+    #[test]
+    fn test_summarize_overflow_parses_and_validates_chunking() {
+        let yaml = "summarize_overflow: true\nsummary_chunk_bytes: 16384\n";
+        let tools: ToolsConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(tools.summarize_overflow);
+        assert_eq!(tools.summary_chunk_bytes, 16384);
+        assert_eq!(tools.summary_max_chunks, 8);
+        assert!(!ToolsConfig::default().summarize_overflow);
+
+        let mut config = Config::default();
+        config.agent.tools.summary_chunk_bytes = 100;
+        assert!(
+            matches!(config.validate(), Err(XzatomaError::Config(message)) if message.contains("tools.summary_chunk_bytes"))
+        );
+    }
+
+    #[test]
+    fn test_custom_tools_parse_and_reject_duplicate_and_built_in_names() {
+        let yaml = r#"
+custom:
+  - name: query_inventory
+    description: Look up a host in the inventory API
+    parameters:
+      type: object
+      properties:
+        host: { type: string }
+      required: [host]
+    command: ./scripts/inventory.sh {{host}}
+    timeout_seconds: 20
+    env:
+      INVENTORY_URL: https://inventory.internal
+"#;
+        let tools: ToolsConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(tools.custom.len(), 1);
+        assert_eq!(tools.custom[0].timeout_seconds, Some(20));
+        assert_eq!(tools.custom[0].working_dir, None);
+
+        let mut config = Config::default();
+        config.agent.tools = tools;
+        assert!(config.validate().is_ok());
+
+        let duplicate = config.agent.tools.custom[0].clone();
+        config.agent.tools.custom.push(duplicate);
+        assert!(
+            matches!(config.validate(), Err(XzatomaError::Config(message)) if message.contains("more than once"))
+        );
+
+        config.agent.tools.custom.pop();
+        config.agent.tools.custom[0].name = "grep".to_string();
+        assert!(
+            matches!(config.validate(), Err(XzatomaError::Config(message)) if message.contains("built-in tool"))
+        );
+    }
+
+    #[test]
+    fn test_config_validate_rejects_zero_keyring_timeout() {
+        let mut config = Config::default();
+        config.credentials.keyring_timeout_seconds = 0;
+
+        let result = config.validate();
+
+        assert!(
+            matches!(result, Err(XzatomaError::Config(message)) if message.contains("credentials.keyring_timeout_seconds"))
+        );
+    }
+
+    #[test]
+    fn test_copilot_timeouts_default_and_parse() {
+        let config = CopilotConfig::default();
+        assert_eq!(config.request_timeout_seconds, 120);
+        assert_eq!(config.stream_idle_timeout_seconds, 60);
+
+        let yaml = "request_timeout_seconds: 45\nstream_idle_timeout_seconds: 15\n";
+        let config: CopilotConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.request_timeout_seconds, 45);
+        assert_eq!(config.stream_idle_timeout_seconds, 15);
+    }
+
+    #[test]
+    fn test_config_validate_rejects_zero_stream_idle_timeout() {
+        let mut config = Config::default();
+        config.provider.openai.stream_idle_timeout_seconds = 0;
+
+        let result = config.validate();
+
+        assert!(
+            matches!(result, Err(XzatomaError::Config(message)) if message.contains("provider.openai.stream_idle_timeout_seconds"))
+        );
+    }
 }
 
 /// Watcher backend type.
@@ -4938,6 +8692,10 @@ pub struct WatcherConfig {
     /// Plan execution configuration
     #[serde(default)]
     pub execution: WatcherExecutionConfig,
+
+    /// Config hot-reload settings
+    #[serde(default)]
+    pub reload: WatcherReloadConfig,
 }
 
 /// Kafka consumer configuration for the watcher.
@@ -5076,6 +8834,11 @@ pub struct EventFilterConfig {
     /// Only process successful events
     #[serde(default = "default_success_only")]
     pub success_only: bool,
+
+    /// CloudEvents extension attributes that must match exactly
+    /// (e.g. `platformid: k8s-prod`)
+    #[serde(default, skip_serializing_if = "std::collections::HashMap::is_empty")]
+    pub extensions: std::collections::HashMap<String, String>,
 }
 
 /// Watcher logging configuration
@@ -5111,6 +8874,45 @@ pub struct WatcherExecutionConfig {
     /// Execution timeout in seconds
     #[serde(default = "default_execution_timeout")]
     pub execution_timeout_secs: u64,
+
+    /// Directory under which each execution gets its own workspace
+    ///
+    /// Defaults to `xzatoma-watcher` in the system temp directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace_root: Option<PathBuf>,
+
+    /// Keep the workspace of a failed execution for debugging
+    #[serde(default)]
+    pub keep_workspace_on_failure: bool,
+}
+
+/// Watcher config hot-reload settings
+///
+/// While `xzatoma watch` runs, changes to the config file and `SIGHUP`
+/// reload `watcher.filters`, `watcher.logging.level`,
+/// `watcher.execution.allow_dangerous`, and
+/// `watcher.execution.max_concurrent_executions` without a restart.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::WatcherReloadConfig;
+///
+/// let config: WatcherReloadConfig = serde_yaml::from_str("debounce_ms: 1000").unwrap();
+/// assert!(config.enabled);
+/// assert_eq!(config.debounce_ms, 1000);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatcherReloadConfig {
+    /// Reload when the config file changes
+    ///
+    /// `SIGHUP` reloads the configuration even when this is disabled.
+    #[serde(default = "default_reload_enabled")]
+    pub enabled: bool,
+
+    /// Time to wait after a file change before reloading, in milliseconds
+    #[serde(default = "default_reload_debounce_ms")]
+    pub debounce_ms: u64,
 }
 
 /// Default watcher consumer group ID
@@ -5148,6 +8950,16 @@ fn default_execution_timeout() -> u64 {
     300
 }
 
+/// Default config hot-reload setting
+fn default_reload_enabled() -> bool {
+    true
+}
+
+/// Default delay between a config file change and the reload
+fn default_reload_debounce_ms() -> u64 {
+    500
+}
+
 impl Default for WatcherLoggingConfig {
     fn default() -> Self {
         Self {
@@ -5165,6 +8977,17 @@ impl Default for WatcherExecutionConfig {
             allow_dangerous: false,
             max_concurrent_executions: default_max_concurrent(),
             execution_timeout_secs: default_execution_timeout(),
+            workspace_root: None,
+            keep_workspace_on_failure: false,
+        }
+    }
+}
+
+impl Default for WatcherReloadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_reload_enabled(),
+            debounce_ms: default_reload_debounce_ms(),
         }
     }
 }
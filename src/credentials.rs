@@ -0,0 +1,1359 @@
+//! Credential storage for provider and MCP tokens
+//!
+//! Copilot and MCP OAuth tokens are stored through the [`CredentialStore`]
+//! trait. The OS keyring is used when it is available. On headless servers
+//! and in containers, where there is no keyring, tokens go to a credentials
+//! file in the data directory instead.
+//!
+//! The file is encrypted with XChaCha20-Poly1305 using a key derived with
+//! Argon2id from the passphrase in `XZATOMA_CREDENTIALS_KEY`. Without a
+//! passphrase the file is only used when `credentials.allow_plaintext` is
+//! true, and a warning is printed the first time a plaintext secret is
+//! written.
+//!
+//! The backend is selected with `credentials.backend`:
+//!
+//! - `auto` (default): the keyring, falling back to the file when a keyring
+//!   operation fails
+//! - `keyring`: only the keyring
+//! - `file`: only the file
+//!
+//! A token found in the other backend is moved to the selected one the first
+//! time it is read, so switching backends does not require signing in again.
+//!
+//! Keyring operations run with a timeout, `credentials.keyring_timeout_seconds`.
+//! Over SSH, or on Linux without a D-Bus session, the keyring may block on
+//! an unlock prompt nobody can answer or fail with a platform error. Both
+//! become [`XzatomaError::CredentialBackendUnavailable`], naming the session
+//! type detected from the environment. `auto` then falls back to the file,
+//! and the other backends report the error with instructions.
+//!
+//! # Examples
+//!
+//! ```
+//! use xzatoma::credentials::{CredentialStore, MemoryStore};
+//!
+//! let store = MemoryStore::default();
+//! store.set("xzatoma", "github_copilot", "secret").unwrap();
+//! assert_eq!(
+//!     store.get("xzatoma", "github_copilot").unwrap().as_deref(),
+//!     Some("secret")
+//! );
+//! store.delete("xzatoma", "github_copilot").unwrap();
+//! assert!(store.get("xzatoma", "github_copilot").unwrap().is_none());
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{CredentialBackend, CredentialsConfig};
+use crate::error::{Result, XzatomaError};
+use crate::paths::Paths;
+
+/// Environment variable holding the passphrase for the credentials file
+pub const CREDENTIALS_KEY_ENV: &str = "XZATOMA_CREDENTIALS_KEY";
+
+const FILE_VERSION: u32 = 1;
+const ALGORITHM: &str = "xchacha20poly1305";
+const KDF: &str = "argon2id";
+const VERIFIER_PLAINTEXT: &[u8] = b"xzatoma-credentials";
+const VERIFIER_AAD: &[u8] = b"xzatoma-credentials-verifier";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Get, set, and delete secrets by service and account name
+///
+/// Implementations must be safe to share between threads.
+pub trait CredentialStore: Send + Sync {
+    /// Returns the secret, or `None` when nothing is stored
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the backend cannot be read.
+    fn get(&self, service: &str, account: &str) -> Result<Option<String>>;
+
+    /// Stores the secret, replacing any previous value
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the backend cannot be written.
+    fn set(&self, service: &str, account: &str, secret: &str) -> Result<()>;
+
+    /// Removes the secret; succeeds when nothing is stored
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the backend cannot be written.
+    fn delete(&self, service: &str, account: &str) -> Result<()>;
+
+    /// Short description used in log messages
+    fn name(&self) -> String;
+}
+
+// ---------------------------------------------------------------------------
+// Keyring
+// ---------------------------------------------------------------------------
+
+/// Credential store backed by the OS keyring
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeyringStore;
+
+impl CredentialStore for KeyringStore {
+    fn get(&self, service: &str, account: &str) -> Result<Option<String>> {
+        match keyring::Entry::new(service, account)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(XzatomaError::Keyring(e)),
+        }
+    }
+
+    fn set(&self, service: &str, account: &str, secret: &str) -> Result<()> {
+        keyring::Entry::new(service, account)?.set_password(secret)?;
+        Ok(())
+    }
+
+    fn delete(&self, service: &str, account: &str) -> Result<()> {
+        match keyring::Entry::new(service, account)?.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(XzatomaError::Keyring(e)),
+        }
+    }
+
+    fn name(&self) -> String {
+        "keyring".to_string()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Keyring availability
+// ---------------------------------------------------------------------------
+
+/// Kind of login session, detected from the environment
+///
+/// Used to explain why the keyring is unavailable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionType {
+    /// Logged in over SSH (`SSH_TTY` or `SSH_CONNECTION` is set)
+    Ssh,
+    /// No D-Bus session bus (`DBUS_SESSION_BUS_ADDRESS` is unset), so the
+    /// Secret Service keyring cannot be reached
+    NoSessionBus,
+    /// No graphical display (`DISPLAY` and `WAYLAND_DISPLAY` are unset)
+    Headless,
+    /// A local graphical session
+    Desktop,
+}
+
+impl SessionType {
+    /// Detects the session type of the current process
+    ///
+    /// The D-Bus and display checks only apply on platforms whose keyring is
+    /// the Secret Service, that is Unix other than macOS.
+    pub fn detect() -> Self {
+        Self::detect_with(cfg!(all(unix, not(target_os = "macos"))), |name| {
+            std::env::var(name).ok()
+        })
+    }
+
+    fn detect_with(uses_session_bus: bool, env: impl Fn(&str) -> Option<String>) -> Self {
+        let set = |name: &str| env(name).is_some_and(|value| !value.is_empty());
+        if set("SSH_TTY") || set("SSH_CONNECTION") {
+            Self::Ssh
+        } else if uses_session_bus && !set("DBUS_SESSION_BUS_ADDRESS") {
+            Self::NoSessionBus
+        } else if uses_session_bus && !set("DISPLAY") && !set("WAYLAND_DISPLAY") {
+            Self::Headless
+        } else {
+            Self::Desktop
+        }
+    }
+}
+
+impl std::fmt::Display for SessionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Ssh => "SSH session",
+            Self::NoSessionBus => "no D-Bus session bus",
+            Self::Headless => "no graphical display",
+            Self::Desktop => "desktop session",
+        })
+    }
+}
+
+/// Keyring store whose operations give up after a timeout
+///
+/// Each operation runs on its own thread. One that does not finish within
+/// the timeout, or that fails with a keyring platform error, returns
+/// [`XzatomaError::CredentialBackendUnavailable`]. After that the keyring
+/// is not tried again: later operations fail immediately, so a hung unlock
+/// prompt delays only the first one.
+pub struct GuardedKeyring {
+    inner: Arc<dyn CredentialStore>,
+    timeout: Duration,
+    session: SessionType,
+    /// Why the keyring is unavailable, once it has failed
+    unavailable: Arc<Mutex<Option<String>>>,
+}
+
+impl GuardedKeyring {
+    /// Guards `inner` with `timeout`
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The keyring store
+    /// * `timeout` - Longest time one operation may take
+    /// * `session` - Session type named in errors
+    pub fn new(inner: Arc<dyn CredentialStore>, timeout: Duration, session: SessionType) -> Self {
+        Self {
+            inner,
+            timeout,
+            session,
+            unavailable: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns the OS keyring guarded by `credentials.keyring_timeout_seconds`
+    ///
+    /// System keyrings share their availability: once one has failed, the
+    /// others fail immediately too.
+    pub fn system(config: &CredentialsConfig) -> Self {
+        static UNAVAILABLE: OnceLock<Arc<Mutex<Option<String>>>> = OnceLock::new();
+        Self {
+            unavailable: Arc::clone(UNAVAILABLE.get_or_init(Default::default)),
+            ..Self::new(
+                Arc::new(KeyringStore),
+                Duration::from_secs(config.keyring_timeout_seconds),
+                SessionType::detect(),
+            )
+        }
+    }
+
+    fn unavailable_error(&self, reason: String) -> XzatomaError {
+        XzatomaError::CredentialBackendUnavailable {
+            backend: self.inner.name(),
+            session: self.session.to_string(),
+            reason,
+        }
+    }
+
+    fn mark_unavailable(&self, reason: String) -> XzatomaError {
+        tracing::warn!(
+            "Credential backend {} is unavailable ({}): {}",
+            self.inner.name(),
+            self.session,
+            reason
+        );
+        *self.unavailable.lock().unwrap_or_else(|e| e.into_inner()) = Some(reason.clone());
+        self.unavailable_error(reason)
+    }
+
+    fn run<T: Send + 'static>(
+        &self,
+        operation: impl FnOnce(&dyn CredentialStore) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let known = self
+            .unavailable
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some(reason) = known {
+            return Err(self.unavailable_error(reason));
+        }
+
+        let inner = Arc::clone(&self.inner);
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .name("xzatoma-keyring".to_string())
+            .spawn(move || {
+                let _ = tx.send(operation(inner.as_ref()));
+            })?;
+        match rx.recv_timeout(self.timeout) {
+            Ok(Err(XzatomaError::Keyring(
+                e @ (keyring::Error::PlatformFailure(_) | keyring::Error::NoStorageAccess(_)),
+            ))) => Err(self.mark_unavailable(e.to_string())),
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(self.mark_unavailable(format!(
+                "no response within {:?}; it may be waiting for an unlock prompt",
+                self.timeout
+            ))),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(self.mark_unavailable("the keyring operation panicked".to_string()))
+            }
+        }
+    }
+}
+
+impl CredentialStore for GuardedKeyring {
+    fn get(&self, service: &str, account: &str) -> Result<Option<String>> {
+        let (service, account) = (service.to_string(), account.to_string());
+        self.run(move |store| store.get(&service, &account))
+    }
+
+    fn set(&self, service: &str, account: &str, secret: &str) -> Result<()> {
+        let (service, account, secret) =
+            (service.to_string(), account.to_string(), secret.to_string());
+        self.run(move |store| store.set(&service, &account, &secret))
+    }
+
+    fn delete(&self, service: &str, account: &str) -> Result<()> {
+        let (service, account) = (service.to_string(), account.to_string());
+        self.run(move |store| store.delete(&service, &account))
+    }
+
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Memory
+// ---------------------------------------------------------------------------
+
+/// Credential store that keeps secrets in memory for the life of the process
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<(String, String), String>>,
+}
+
+impl MemoryStore {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(String, String), String>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl CredentialStore for MemoryStore {
+    fn get(&self, service: &str, account: &str) -> Result<Option<String>> {
+        Ok(self
+            .lock()
+            .get(&(service.to_string(), account.to_string()))
+            .cloned())
+    }
+
+    fn set(&self, service: &str, account: &str, secret: &str) -> Result<()> {
+        self.lock().insert(
+            (service.to_string(), account.to_string()),
+            secret.to_string(),
+        );
+        Ok(())
+    }
+
+    fn delete(&self, service: &str, account: &str) -> Result<()> {
+        self.lock()
+            .remove(&(service.to_string(), account.to_string()));
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "memory".to_string()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// File
+// ---------------------------------------------------------------------------
+
+/// On-disk layout of the credentials file
+#[derive(Debug, Serialize, Deserialize)]
+struct CredentialFile {
+    version: u32,
+    /// Absent when the file is plaintext
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encryption: Option<EncryptionHeader>,
+    #[serde(default)]
+    entries: Vec<StoredEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptionHeader {
+    algorithm: String,
+    kdf: String,
+    salt: String,
+    /// A known value sealed with the key, used to detect a wrong passphrase
+    verifier: Sealed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredEntry {
+    service: String,
+    account: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sealed: Option<Sealed>,
+}
+
+type Entries = BTreeMap<(String, String), String>;
+
+/// Credential store backed by a JSON file in the data directory
+///
+/// Secrets are encrypted when the store has a passphrase. A plaintext file
+/// written earlier is encrypted on the next write once a passphrase is set.
+pub struct FileStore {
+    path: PathBuf,
+    passphrase: Option<String>,
+    /// Serializes read-modify-write cycles within the process
+    write_lock: Mutex<()>,
+    /// Key derived for the salt in the file, so Argon2 runs once per salt
+    key_cache: Mutex<Option<([u8; SALT_LEN], [u8; 32])>>,
+}
+
+impl FileStore {
+    /// Creates a store that encrypts secrets with a key derived from
+    /// `passphrase`
+    pub fn encrypted(path: impl Into<PathBuf>, passphrase: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            passphrase: Some(passphrase.into()),
+            write_lock: Mutex::new(()),
+            key_cache: Mutex::new(None),
+        }
+    }
+
+    /// Creates a store that writes secrets unencrypted
+    ///
+    /// Only used when `credentials.allow_plaintext` is true.
+    pub fn plaintext(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            passphrase: None,
+            write_lock: Mutex::new(()),
+            key_cache: Mutex::new(None),
+        }
+    }
+
+    /// Returns `credentials.json` in the data directory
+    pub fn default_path(paths: &Paths) -> PathBuf {
+        paths.credentials_file()
+    }
+
+    /// Returns the path of the credentials file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns true when secrets are written unencrypted
+    pub fn is_plaintext(&self) -> bool {
+        self.passphrase.is_none()
+    }
+
+    fn derive_key(&self, passphrase: &str, salt: &[u8; SALT_LEN]) -> Result<[u8; 32]> {
+        let mut cache = self.key_cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((cached_salt, key)) = cache.as_ref() {
+            if cached_salt == salt {
+                return Ok(*key);
+            }
+        }
+        let mut key = [0u8; 32];
+        argon2::Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| XzatomaError::Credentials(format!("Key derivation failed: {}", e)))?;
+        *cache = Some((*salt, key));
+        Ok(key)
+    }
+
+    fn read(&self) -> Result<(Option<[u8; SALT_LEN]>, Entries)> {
+        let raw = match std::fs::read_to_string(&self.path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok((None, Entries::new()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let file: CredentialFile = serde_json::from_str(&raw).map_err(|e| {
+            XzatomaError::Credentials(format!(
+                "{} is not a credentials file: {}",
+                self.display(),
+                e
+            ))
+        })?;
+
+        let mut entries = Entries::new();
+        let header = match file.encryption {
+            None => {
+                for entry in file.entries {
+                    if let Some(secret) = entry.secret {
+                        entries.insert((entry.service, entry.account), secret);
+                    }
+                }
+                return Ok((None, entries));
+            }
+            Some(header) => header,
+        };
+
+        let passphrase = self.passphrase.as_deref().ok_or_else(|| {
+            XzatomaError::Credentials(format!(
+                "{} is encrypted; set {} to read it",
+                self.display(),
+                CREDENTIALS_KEY_ENV
+            ))
+        })?;
+        if header.algorithm != ALGORITHM || header.kdf != KDF {
+            return Err(XzatomaError::Credentials(format!(
+                "{} uses unsupported encryption {}/{}",
+                self.display(),
+                header.algorithm,
+                header.kdf
+            )));
+        }
+        let salt: [u8; SALT_LEN] = decode(&header.salt)?
+            .try_into()
+            .map_err(|_| XzatomaError::Credentials("Invalid salt length".to_string()))?;
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(&self.derive_key(passphrase, &salt)?));
+
+        if open(&cipher, &header.verifier, VERIFIER_AAD)
+            .ok()
+            .as_deref()
+            != Some(VERIFIER_PLAINTEXT)
+        {
+            return Err(XzatomaError::Credentials(format!(
+                "Cannot decrypt {}: {} does not match the passphrase the file was encrypted with",
+                self.display(),
+                CREDENTIALS_KEY_ENV
+            )));
+        }
+
+        for entry in file.entries {
+            let Some(sealed) = entry.sealed else { continue };
+            let aad = entry_aad(&entry.service, &entry.account);
+            let secret = open(&cipher, &sealed, &aad).map_err(|_| {
+                XzatomaError::Credentials(format!(
+                    "Cannot decrypt the {}/{} entry in {}",
+                    entry.service,
+                    entry.account,
+                    self.display()
+                ))
+            })?;
+            let secret = String::from_utf8(secret).map_err(|_| {
+                XzatomaError::Credentials("Decrypted secret is not UTF-8".to_string())
+            })?;
+            entries.insert((entry.service, entry.account), secret);
+        }
+        Ok((Some(salt), entries))
+    }
+
+    fn write(&self, salt: Option<[u8; SALT_LEN]>, entries: &Entries) -> Result<()> {
+        let file = match self.passphrase.as_deref() {
+            Some(passphrase) => {
+                let salt = salt.unwrap_or_else(random_bytes);
+                let cipher =
+                    XChaCha20Poly1305::new(Key::from_slice(&self.derive_key(passphrase, &salt)?));
+                let mut stored = Vec::with_capacity(entries.len());
+                for ((service, account), secret) in entries {
+                    stored.push(StoredEntry {
+                        service: service.clone(),
+                        account: account.clone(),
+                        secret: None,
+                        sealed: Some(seal(
+                            &cipher,
+                            secret.as_bytes(),
+                            &entry_aad(service, account),
+                        )?),
+                    });
+                }
+                CredentialFile {
+                    version: FILE_VERSION,
+                    encryption: Some(EncryptionHeader {
+                        algorithm: ALGORITHM.to_string(),
+                        kdf: KDF.to_string(),
+                        salt: encode(&salt),
+                        verifier: seal(&cipher, VERIFIER_PLAINTEXT, VERIFIER_AAD)?,
+                    }),
+                    entries: stored,
+                }
+            }
+            None => {
+                if !entries.is_empty() {
+                    warn_plaintext(&self.path);
+                }
+                CredentialFile {
+                    version: FILE_VERSION,
+                    encryption: None,
+                    entries: entries
+                        .iter()
+                        .map(|((service, account), secret)| StoredEntry {
+                            service: service.clone(),
+                            account: account.clone(),
+                            secret: Some(secret.clone()),
+                            sealed: None,
+                        })
+                        .collect(),
+                }
+            }
+        };
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        // A temp file left behind by a crash may have been created with
+        // looser permissions; never write secrets into it.
+        match std::fs::remove_file(&tmp) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        {
+            use std::io::Write;
+            let mut out = options.open(&tmp)?;
+            out.write_all(&serde_json::to_vec_pretty(&file)?)?;
+            out.sync_all()?;
+        }
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn update(&self, change: impl FnOnce(&mut Entries) -> bool) -> Result<()> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (salt, mut entries) = self.read()?;
+        if change(&mut entries) {
+            self.write(salt, &entries)?;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+impl CredentialStore for FileStore {
+    fn get(&self, service: &str, account: &str) -> Result<Option<String>> {
+        let (_, mut entries) = self.read()?;
+        Ok(entries.remove(&(service.to_string(), account.to_string())))
+    }
+
+    fn set(&self, service: &str, account: &str, secret: &str) -> Result<()> {
+        self.update(|entries| {
+            entries.insert(
+                (service.to_string(), account.to_string()),
+                secret.to_string(),
+            );
+            true
+        })
+    }
+
+    fn delete(&self, service: &str, account: &str) -> Result<()> {
+        self.update(|entries| {
+            entries
+                .remove(&(service.to_string(), account.to_string()))
+                .is_some()
+        })
+    }
+
+    fn name(&self) -> String {
+        format!("file {}", self.display())
+    }
+}
+
+fn entry_aad(service: &str, account: &str) -> Vec<u8> {
+    format!("{}\n{}", service, account).into_bytes()
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    use rand::RngCore as _;
+    let mut bytes = [0u8; N];
+    rand::rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+fn encode(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+fn decode(text: &str) -> Result<Vec<u8>> {
+    base64::engine::general_purpose::STANDARD
+        .decode(text)
+        .map_err(|e| {
+            XzatomaError::Credentials(format!("Invalid base64 in credentials file: {}", e))
+        })
+}
+
+fn seal(cipher: &XChaCha20Poly1305, plaintext: &[u8], aad: &[u8]) -> Result<Sealed> {
+    let nonce: [u8; NONCE_LEN] = random_bytes();
+    let ciphertext = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| XzatomaError::Credentials("Encryption failed".to_string()))?;
+    Ok(Sealed {
+        nonce: encode(&nonce),
+        ciphertext: encode(&ciphertext),
+    })
+}
+
+fn open(cipher: &XChaCha20Poly1305, sealed: &Sealed, aad: &[u8]) -> Result<Vec<u8>> {
+    let nonce = decode(&sealed.nonce)?;
+    if nonce.len() != NONCE_LEN {
+        return Err(XzatomaError::Credentials(
+            "Invalid nonce length".to_string(),
+        ));
+    }
+    let ciphertext = decode(&sealed.ciphertext)?;
+    cipher
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| XzatomaError::Credentials("Decryption failed".to_string()))
+}
+
+fn warn_plaintext(path: &Path) {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+    tracing::warn!("Storing credentials unencrypted in {}", path.display());
+    if !WARNED.swap(true, Ordering::SeqCst) {
+        eprintln!(
+            "WARNING: storing credentials UNENCRYPTED in {} because credentials.allow_plaintext \
+             is true and {} is not set. Anyone who can read this file can use your tokens.",
+            path.display(),
+            CREDENTIALS_KEY_ENV
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Layered store (fallback and migration)
+// ---------------------------------------------------------------------------
+
+/// A primary store with an optional secondary store
+///
+/// Reads that find nothing in the primary store look in the secondary store
+/// and move the secret to the primary store. When `fallback_on_error` is
+/// set, operations that fail in the primary store use the secondary store.
+pub struct LayeredStore {
+    primary: Arc<dyn CredentialStore>,
+    secondary: Option<Arc<dyn CredentialStore>>,
+    fallback_on_error: bool,
+}
+
+impl LayeredStore {
+    /// Creates a layered store
+    ///
+    /// # Arguments
+    ///
+    /// * `primary` - Store that receives all writes when it is working
+    /// * `secondary` - Store that secrets are migrated from
+    /// * `fallback_on_error` - Use `secondary` when `primary` fails
+    pub fn new(
+        primary: Arc<dyn CredentialStore>,
+        secondary: Option<Arc<dyn CredentialStore>>,
+        fallback_on_error: bool,
+    ) -> Self {
+        Self {
+            primary,
+            secondary,
+            fallback_on_error,
+        }
+    }
+
+    /// Returns the secondary store when it may be used after `error`
+    fn fallback(&self, error: &XzatomaError) -> Option<&Arc<dyn CredentialStore>> {
+        let secondary = self.secondary.as_ref().filter(|_| self.fallback_on_error)?;
+        tracing::warn!(
+            "Credential store {} failed ({}); using {}",
+            self.primary.name(),
+            error,
+            secondary.name()
+        );
+        Some(secondary)
+    }
+
+    fn migrate(&self, service: &str, account: &str) -> Result<Option<String>> {
+        let Some(secondary) = &self.secondary else {
+            return Ok(None);
+        };
+        let secret = match secondary.get(service, account) {
+            Ok(Some(secret)) => secret,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                tracing::debug!("Skipping migration from {}: {}", secondary.name(), e);
+                return Ok(None);
+            }
+        };
+        match self.primary.set(service, account, &secret) {
+            Ok(()) => {
+                tracing::info!(
+                    "Moved credential {}/{} from {} to {}",
+                    service,
+                    account,
+                    secondary.name(),
+                    self.primary.name()
+                );
+                if let Err(e) = secondary.delete(service, account) {
+                    tracing::warn!("Failed to remove migrated credential: {}", e);
+                }
+            }
+            Err(e) => tracing::warn!("Failed to migrate credential: {}", e),
+        }
+        Ok(Some(secret))
+    }
+}
+
+impl CredentialStore for LayeredStore {
+    fn get(&self, service: &str, account: &str) -> Result<Option<String>> {
+        match self.primary.get(service, account) {
+            Ok(Some(secret)) => Ok(Some(secret)),
+            Ok(None) => self.migrate(service, account),
+            Err(e) => match self.fallback(&e) {
+                Some(secondary) => secondary.get(service, account),
+                None => Err(e),
+            },
+        }
+    }
+
+    fn set(&self, service: &str, account: &str, secret: &str) -> Result<()> {
+        match self.primary.set(service, account, secret) {
+            Ok(()) => {
+                // Drop any stale copy so it cannot be migrated back later
+                if let Some(secondary) = &self.secondary {
+                    let _ = secondary.delete(service, account);
+                }
+                Ok(())
+            }
+            Err(e) => match self.fallback(&e) {
+                Some(secondary) => secondary.set(service, account, secret),
+                None => Err(e),
+            },
+        }
+    }
+
+    fn delete(&self, service: &str, account: &str) -> Result<()> {
+        let primary = self.primary.delete(service, account);
+        let secondary = self
+            .secondary
+            .as_ref()
+            .map(|secondary| secondary.delete(service, account));
+        match (primary, secondary) {
+            (Err(e), Some(Ok(()))) if self.fallback_on_error => {
+                tracing::debug!("Ignoring {} delete failure: {}", self.primary.name(), e);
+                Ok(())
+            }
+            (Err(e), _) => Err(e),
+            (Ok(()), _) => Ok(()),
+        }
+    }
+
+    fn name(&self) -> String {
+        self.primary.name()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Configuration
+// ---------------------------------------------------------------------------
+
+/// Builds the credential store selected by the configuration
+///
+/// # Arguments
+///
+/// * `config` - The `credentials` configuration section
+/// * `passphrase` - Passphrase for the credentials file, usually from
+///   `XZATOMA_CREDENTIALS_KEY`
+///
+/// # Errors
+///
+/// Returns [`XzatomaError::Credentials`] when the file backend is selected
+/// but there is no passphrase and plaintext storage is not allowed.
+pub fn store_from_config(
+    config: &CredentialsConfig,
+    passphrase: Option<String>,
+) -> Result<Arc<dyn CredentialStore>> {
+    let path = match &config.path {
+        Some(path) => PathBuf::from(path),
+        None => FileStore::default_path(&Paths::from_env()?),
+    };
+    let file: Option<Arc<dyn CredentialStore>> = match passphrase.filter(|p| !p.is_empty()) {
+        Some(passphrase) => Some(Arc::new(FileStore::encrypted(path, passphrase))),
+        None if config.allow_plaintext => Some(Arc::new(FileStore::plaintext(path))),
+        None => None,
+    };
+    let keyring: Arc<dyn CredentialStore> = Arc::new(GuardedKeyring::system(config));
+
+    let store = match config.backend {
+        CredentialBackend::File => {
+            let file = file.ok_or_else(|| {
+                XzatomaError::Credentials(format!(
+                    "credentials.backend is 'file' but {} is not set; set it, or set \
+                     credentials.allow_plaintext: true to store credentials unencrypted",
+                    CREDENTIALS_KEY_ENV
+                ))
+            })?;
+            LayeredStore::new(file, Some(keyring), false)
+        }
+        CredentialBackend::Keyring => LayeredStore::new(keyring, file, false),
+        CredentialBackend::Auto => LayeredStore::new(keyring, file, true),
+    };
+    Ok(Arc::new(store))
+}
+
+fn shared_config() -> &'static RwLock<CredentialsConfig> {
+    static CONFIG: OnceLock<RwLock<CredentialsConfig>> = OnceLock::new();
+    CONFIG.get_or_init(|| RwLock::new(CredentialsConfig::default()))
+}
+
+/// Selects the credential store used by [`default_store`]
+///
+/// # Arguments
+///
+/// * `config` - The `credentials` configuration section
+pub fn configure(config: &CredentialsConfig) {
+    *shared_config().write().unwrap_or_else(|e| e.into_inner()) = config.clone();
+}
+
+/// Returns the configured credential store
+///
+/// The passphrase is read from `XZATOMA_CREDENTIALS_KEY` on each call.
+///
+/// # Errors
+///
+/// See [`store_from_config`].
+pub fn default_store() -> Result<Arc<dyn CredentialStore>> {
+    let config = shared_config()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    store_from_config(&config, std::env::var(CREDENTIALS_KEY_ENV).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Store whose every operation fails, standing in for a missing keyring
+    struct BrokenStore;
+
+    impl CredentialStore for BrokenStore {
+        fn get(&self, _: &str, _: &str) -> Result<Option<String>> {
+            Err(XzatomaError::Credentials("no keyring".to_string()))
+        }
+        fn set(&self, _: &str, _: &str, _: &str) -> Result<()> {
+            Err(XzatomaError::Credentials("no keyring".to_string()))
+        }
+        fn delete(&self, _: &str, _: &str) -> Result<()> {
+            Err(XzatomaError::Credentials("no keyring".to_string()))
+        }
+        fn name(&self) -> String {
+            "broken".to_string()
+        }
+    }
+
+    /// Keyring stand-in whose operations block, like a pending unlock prompt
+    #[derive(Default)]
+    struct HangingKeyring {
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    impl HangingKeyring {
+        fn hang<T>(&self) -> Result<T> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_secs(30));
+            Err(XzatomaError::Credentials("unreachable".to_string()))
+        }
+    }
+
+    impl CredentialStore for HangingKeyring {
+        fn get(&self, _: &str, _: &str) -> Result<Option<String>> {
+            self.hang()
+        }
+        fn set(&self, _: &str, _: &str, _: &str) -> Result<()> {
+            self.hang()
+        }
+        fn delete(&self, _: &str, _: &str) -> Result<()> {
+            self.hang()
+        }
+        fn name(&self) -> String {
+            "keyring".to_string()
+        }
+    }
+
+    /// Keyring stand-in that reports a platform failure, like a missing
+    /// Secret Service
+    struct ErroringKeyring;
+
+    impl ErroringKeyring {
+        fn fail<T>() -> Result<T> {
+            Err(XzatomaError::Keyring(keyring::Error::PlatformFailure(
+                "org.freedesktop.DBus.Error.ServiceUnknown".into(),
+            )))
+        }
+    }
+
+    impl CredentialStore for ErroringKeyring {
+        fn get(&self, _: &str, _: &str) -> Result<Option<String>> {
+            Self::fail()
+        }
+        fn set(&self, _: &str, _: &str, _: &str) -> Result<()> {
+            Self::fail()
+        }
+        fn delete(&self, _: &str, _: &str) -> Result<()> {
+            Self::fail()
+        }
+        fn name(&self) -> String {
+            "keyring".to_string()
+        }
+    }
+
+    /// Memory store that answers after a delay, like a slow but working keyring
+    #[derive(Default)]
+    struct SlowKeyring(MemoryStore);
+
+    impl CredentialStore for SlowKeyring {
+        fn get(&self, service: &str, account: &str) -> Result<Option<String>> {
+            std::thread::sleep(Duration::from_millis(50));
+            self.0.get(service, account)
+        }
+        fn set(&self, service: &str, account: &str, secret: &str) -> Result<()> {
+            std::thread::sleep(Duration::from_millis(50));
+            self.0.set(service, account, secret)
+        }
+        fn delete(&self, service: &str, account: &str) -> Result<()> {
+            self.0.delete(service, account)
+        }
+        fn name(&self) -> String {
+            "keyring".to_string()
+        }
+    }
+
+    #[test]
+    fn test_session_type_detection() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert_eq!(
+            SessionType::detect_with(true, env(&[("SSH_TTY", "/dev/pts/1")])),
+            SessionType::Ssh
+        );
+        assert_eq!(
+            SessionType::detect_with(false, env(&[("SSH_CONNECTION", "10.0.0.1 22")])),
+            SessionType::Ssh
+        );
+        assert_eq!(
+            SessionType::detect_with(true, env(&[("DISPLAY", ":0")])),
+            SessionType::NoSessionBus
+        );
+        assert_eq!(
+            SessionType::detect_with(true, env(&[("DBUS_SESSION_BUS_ADDRESS", "unix:path=/b")])),
+            SessionType::Headless
+        );
+        assert_eq!(
+            SessionType::detect_with(
+                true,
+                env(&[
+                    ("DBUS_SESSION_BUS_ADDRESS", "unix:path=/b"),
+                    ("WAYLAND_DISPLAY", "wayland-0"),
+                ])
+            ),
+            SessionType::Desktop
+        );
+        // macOS and Windows keyrings do not depend on D-Bus or a display
+        assert_eq!(
+            SessionType::detect_with(false, env(&[])),
+            SessionType::Desktop
+        );
+    }
+
+    #[test]
+    fn test_guarded_keyring_times_out_on_hanging_backend() {
+        let hanging = Arc::new(HangingKeyring::default());
+        let keyring = GuardedKeyring::new(
+            hanging.clone(),
+            Duration::from_millis(100),
+            SessionType::Ssh,
+        );
+
+        let started = std::time::Instant::now();
+        let err = keyring.get("xzatoma", "github_copilot").unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        match err {
+            XzatomaError::CredentialBackendUnavailable {
+                backend,
+                session,
+                reason,
+            } => {
+                assert_eq!(backend, "keyring");
+                assert_eq!(session, "SSH session");
+                assert!(reason.contains("100ms"), "{}", reason);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        // The keyring is not tried again after it hung once
+        let started = std::time::Instant::now();
+        assert!(matches!(
+            keyring.set("xzatoma", "github_copilot", "token"),
+            Err(XzatomaError::CredentialBackendUnavailable { .. })
+        ));
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert_eq!(hanging.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_guarded_keyring_converts_platform_errors() {
+        let keyring = GuardedKeyring::new(
+            Arc::new(ErroringKeyring),
+            Duration::from_secs(5),
+            SessionType::NoSessionBus,
+        );
+
+        let err = keyring.get("xzatoma", "github_copilot").unwrap_err();
+        assert!(matches!(
+            &err,
+            XzatomaError::CredentialBackendUnavailable { session, reason, .. }
+                if session == "no D-Bus session bus" && reason.contains("ServiceUnknown")
+        ));
+        assert!(err.to_string().contains("keyring is unavailable"));
+        assert!(err.remediation_hint().contains(CREDENTIALS_KEY_ENV));
+    }
+
+    #[test]
+    fn test_guarded_keyring_waits_for_slow_backend() {
+        let keyring = GuardedKeyring::new(
+            Arc::new(SlowKeyring::default()),
+            Duration::from_secs(5),
+            SessionType::Desktop,
+        );
+
+        keyring.set("xzatoma", "github_copilot", "token").unwrap();
+        assert_eq!(
+            keyring.get("xzatoma", "github_copilot").unwrap().as_deref(),
+            Some("token")
+        );
+    }
+
+    #[test]
+    fn test_auto_backend_falls_back_when_keyring_hangs() {
+        let dir = tempfile::tempdir().unwrap();
+        let file: Arc<dyn CredentialStore> = Arc::new(FileStore::encrypted(
+            dir.path().join("credentials.json"),
+            "key",
+        ));
+        let keyring = GuardedKeyring::new(
+            Arc::new(HangingKeyring::default()),
+            Duration::from_millis(100),
+            SessionType::Ssh,
+        );
+        let store = LayeredStore::new(Arc::new(keyring), Some(file.clone()), true);
+
+        store.set("xzatoma", "github_copilot", "token").unwrap();
+        assert_eq!(
+            store.get("xzatoma", "github_copilot").unwrap().as_deref(),
+            Some("token")
+        );
+        assert_eq!(
+            file.get("xzatoma", "github_copilot").unwrap().as_deref(),
+            Some("token")
+        );
+    }
+
+    #[test]
+    fn test_file_store_encrypted_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json");
+        let store = FileStore::encrypted(&path, "correct horse");
+
+        store
+            .set("xzatoma", "github_copilot", "s3cret-token")
+            .unwrap();
+        store.set("xzatoma-mcp-gh", "gh", "{\"a\":1}").unwrap();
+        assert_eq!(
+            store.get("xzatoma", "github_copilot").unwrap().as_deref(),
+            Some("s3cret-token")
+        );
+
+        // A fresh store with the same passphrase reads the file
+        let reopened = FileStore::encrypted(&path, "correct horse");
+        assert_eq!(
+            reopened.get("xzatoma-mcp-gh", "gh").unwrap().as_deref(),
+            Some("{\"a\":1}")
+        );
+
+        // Secrets are not stored in the clear
+        let raw = std::fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains("s3cret-token"));
+        assert!(raw.contains(ALGORITHM));
+
+        reopened.delete("xzatoma", "github_copilot").unwrap();
+        assert!(store.get("xzatoma", "github_copilot").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_file_store_wrong_key_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json");
+        FileStore::encrypted(&path, "right")
+            .set("xzatoma", "github_copilot", "token")
+            .unwrap();
+
+        let wrong = FileStore::encrypted(&path, "wrong");
+        let err = wrong.get("xzatoma", "github_copilot").unwrap_err();
+        assert!(matches!(err, XzatomaError::Credentials(_)));
+        assert!(err.to_string().contains(CREDENTIALS_KEY_ENV));
+
+        // Writing with the wrong key must not clobber the file either
+        assert!(wrong.set("xzatoma", "other", "x").is_err());
+        assert_eq!(
+            FileStore::encrypted(&path, "right")
+                .get("xzatoma", "github_copilot")
+                .unwrap()
+                .as_deref(),
+            Some("token")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_store_replaces_stale_temp_file_with_private_one() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json");
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, "leftover").unwrap();
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        FileStore::plaintext(&path)
+            .set("xzatoma", "github_copilot", "token")
+            .unwrap();
+
+        assert!(!tmp.exists());
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_encrypted_file_requires_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json");
+        FileStore::encrypted(&path, "key")
+            .set("xzatoma", "github_copilot", "token")
+            .unwrap();
+
+        let err = FileStore::plaintext(&path)
+            .get("xzatoma", "github_copilot")
+            .unwrap_err();
+        assert!(err.to_string().contains("is encrypted"));
+    }
+
+    #[test]
+    fn test_plaintext_file_is_encrypted_once_passphrase_is_set() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json");
+        FileStore::plaintext(&path)
+            .set("xzatoma", "github_copilot", "token")
+            .unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().contains("token"));
+
+        let encrypted = FileStore::encrypted(&path, "key");
+        assert_eq!(
+            encrypted
+                .get("xzatoma", "github_copilot")
+                .unwrap()
+                .as_deref(),
+            Some("token")
+        );
+        encrypted.set("xzatoma", "other", "value").unwrap();
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("token"));
+    }
+
+    #[test]
+    fn test_file_backend_requires_key_or_plaintext_opt_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = CredentialsConfig {
+            backend: CredentialBackend::File,
+            allow_plaintext: false,
+            path: Some(dir.path().join("c.json").display().to_string()),
+            ..CredentialsConfig::default()
+        };
+        let err = store_from_config(&config, None).err().unwrap();
+        assert!(err.to_string().contains("allow_plaintext"));
+
+        config.allow_plaintext = true;
+        let store = store_from_config(&config, None).unwrap();
+        assert!(store.name().starts_with("file"));
+    }
+
+    #[test]
+    fn test_layered_store_falls_back_when_primary_fails() {
+        let memory = Arc::new(MemoryStore::default());
+        let store = LayeredStore::new(Arc::new(BrokenStore), Some(memory.clone()), true);
+
+        store.set("svc", "acct", "secret").unwrap();
+        assert_eq!(
+            memory.get("svc", "acct").unwrap().as_deref(),
+            Some("secret")
+        );
+        assert_eq!(store.get("svc", "acct").unwrap().as_deref(), Some("secret"));
+        store.delete("svc", "acct").unwrap();
+        assert!(memory.get("svc", "acct").unwrap().is_none());
+
+        let strict = LayeredStore::new(Arc::new(BrokenStore), Some(memory), false);
+        assert!(strict.set("svc", "acct", "secret").is_err());
+    }
+
+    #[test]
+    fn test_layered_store_migrates_on_first_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let old: Arc<dyn CredentialStore> = Arc::new(MemoryStore::default());
+        old.set("xzatoma", "github_copilot", "token").unwrap();
+        let file: Arc<dyn CredentialStore> = Arc::new(FileStore::encrypted(
+            dir.path().join("credentials.json"),
+            "key",
+        ));
+
+        // Switching to the file backend moves the secret into the file
+        let to_file = LayeredStore::new(file.clone(), Some(old.clone()), false);
+        assert_eq!(
+            to_file.get("xzatoma", "github_copilot").unwrap().as_deref(),
+            Some("token")
+        );
+        assert_eq!(
+            file.get("xzatoma", "github_copilot").unwrap().as_deref(),
+            Some("token")
+        );
+        assert!(old.get("xzatoma", "github_copilot").unwrap().is_none());
+
+        // And switching back moves it out again
+        let back = LayeredStore::new(old.clone(), Some(file.clone()), false);
+        assert_eq!(
+            back.get("xzatoma", "github_copilot").unwrap().as_deref(),
+            Some("token")
+        );
+        assert!(file.get("xzatoma", "github_copilot").unwrap().is_none());
+        assert_eq!(
+            old.get("xzatoma", "github_copilot").unwrap().as_deref(),
+            Some("token")
+        );
+    }
+}
@@ -79,6 +79,17 @@ pub enum XzatomaError {
         message: String,
     },
 
+    /// The agent kept repeating the same tool call and the turn was stopped
+    #[error(
+        "Tool call loop detected: '{tool}' was called {repeats} times with the same arguments"
+    )]
+    ToolLoopDetected {
+        /// Name of the repeated tool
+        tool: String,
+        /// Number of identical calls in the turn
+        repeats: usize,
+    },
+
     /// Command is considered dangerous and requires confirmation
     #[error("Dangerous command detected: {0}")]
     DangerousCommand(String),
@@ -99,9 +110,129 @@ pub enum XzatomaError {
     #[error("Missing credentials for provider: {0}")]
     MissingCredentials(String),
 
-    /// Authentication errors (e.g., 401 Unauthorized)
-    #[error("Authentication error: {0}")]
-    Authentication(String),
+    /// Authentication failed for a specific provider (e.g., 401 Unauthorized)
+    #[error("Authentication error ({provider}): {reason}")]
+    Auth {
+        /// Provider or service that rejected the credentials
+        provider: String,
+        /// Explanation returned by the provider or detected locally
+        reason: String,
+    },
+
+    /// Requested model is not offered by the provider
+    #[error("Model not found: {model}")]
+    ModelNotFound {
+        /// Model name that was requested
+        model: String,
+        /// Model names the provider reported as available (may be empty)
+        available: Vec<String>,
+    },
+
+    /// Provider rejected the request because of rate limiting (HTTP 429)
+    #[error("Rate limited by {provider}{}", format_retry_after(.retry_after))]
+    RateLimited {
+        /// Provider that returned the rate limit response
+        provider: String,
+        /// Delay suggested by the provider before retrying, when known
+        retry_after: Option<std::time::Duration>,
+    },
+
+    /// Remote endpoint could not be reached (connection refused, DNS, etc.)
+    #[error("Network unreachable: {endpoint}: {reason}")]
+    NetworkUnreachable {
+        /// URL or host that could not be reached
+        endpoint: String,
+        /// Underlying connection failure
+        reason: String,
+    },
+
+    /// A provider request exceeded its timeout
+    ///
+    /// `idle` is set when a streaming response stopped sending data, as
+    /// opposed to the whole request running out of time.
+    #[error("{}", format_request_timeout(.provider, .timeout, .idle))]
+    RequestTimeout {
+        /// Provider that did not answer in time
+        provider: String,
+        /// Timeout that elapsed
+        timeout: std::time::Duration,
+        /// Whether the idle timeout of a streaming response elapsed
+        idle: bool,
+    },
+
+    /// A provider request timed out while waiting for a free request slot
+    ///
+    /// Raised when the client-side concurrency limit for a host was reached
+    /// and no earlier request finished in time; the request was never sent.
+    #[error(
+        "{provider} request timed out after {:.0}s while queued behind {max_concurrent} in-flight request(s) to {host}",
+        .waited.as_secs_f64()
+    )]
+    RequestQueueTimeout {
+        /// Provider whose request was queued
+        provider: String,
+        /// Host the request was queued for
+        host: String,
+        /// Time spent waiting in the queue
+        waited: std::time::Duration,
+        /// Concurrency limit of the host
+        max_concurrent: usize,
+    },
+
+    /// A provider request timed out waiting for the process-wide rate limits
+    ///
+    /// Raised when the request governor for a provider could not start the
+    /// request within `provider.rate_limits.max_wait_seconds`; the request was
+    /// never sent.
+    #[error(
+        "{provider} request timed out after {:.0}s waiting for provider.rate_limits",
+        .waited.as_secs_f64()
+    )]
+    RateLimitQueueTimeout {
+        /// Provider whose request was held back
+        provider: String,
+        /// Time spent waiting for the governor
+        waited: std::time::Duration,
+    },
+
+    /// The Ollama server did not answer its health check
+    #[error(
+        "Ollama is not reachable at {host} ({}): {reason}",
+        format_dns_status(.dns_resolved)
+    )]
+    OllamaUnavailable {
+        /// Configured Ollama host URL
+        host: String,
+        /// Whether the host name resolved to at least one address
+        dns_resolved: bool,
+        /// Underlying connection failure
+        reason: String,
+    },
+
+    /// The requested model has not been pulled onto the Ollama server
+    #[error("Model '{model}' is not available on the Ollama server")]
+    ModelNotPulled {
+        /// Model name that was requested
+        model: String,
+    },
+
+    /// A named tool failed while executing
+    #[error("Tool '{tool}' execution failed: {reason}")]
+    ToolFailed {
+        /// Name of the tool that failed
+        tool: String,
+        /// Failure reported by the tool
+        reason: String,
+    },
+
+    /// A configured MCP server is unavailable or misbehaving
+    #[error("MCP server '{server}' error: {reason}")]
+    McpServer {
+        /// Server identifier
+        server: String,
+        /// Description of the failure
+        reason: String,
+    },
 
     /// IO errors
     #[error("IO error: {0}")]
@@ -131,14 +262,71 @@ pub enum XzatomaError {
     #[error("Keyring error: {0}")]
     Keyring(#[from] keyring::Error),
 
+    /// Credential file errors (missing key, wrong key, corrupt file)
+    #[error("Credential store error: {0}")]
+    Credentials(String),
+
+    /// A credential backend hung or reported a platform failure
+    ///
+    /// Raised when the OS keyring does not answer within
+    /// `credentials.keyring_timeout_seconds`, typically because it waits for
+    /// a graphical unlock prompt, or when it reports that no keyring service
+    /// is reachable.
+    #[error("Credential backend {backend} is unavailable ({session}): {reason}")]
+    CredentialBackendUnavailable {
+        /// Backend that failed, for example `keyring`
+        backend: String,
+        /// Session type detected from the environment, for example `SSH session`
+        session: String,
+        /// What went wrong
+        reason: String,
+    },
+
     /// Conversation storage errors (database operations)
     #[error("Storage error: {0}")]
     Storage(String),
 
+    /// A workspace snapshot could not be taken or restored
+    #[error("Workspace snapshot error: {0}")]
+    Snapshot(String),
+
+    /// Tool definitions too large for the provider under the `fail` strategy
+    #[error("Tool definitions exceed provider limits: {0}")]
+    ToolDefinitionsExceedLimits(String),
+
+    /// The request did not fit the model's context window
+    ///
+    /// Token counts are set when the provider reported them.
+    #[error("{}", format_context_overflow(.limit, .attempted))]
+    ContextOverflow {
+        /// Context window of the model, in tokens
+        limit: Option<usize>,
+        /// Size of the rejected request, in tokens
+        attempted: Option<usize>,
+    },
+
+    /// A single user message does not fit the model's context window
+    #[error("The message is about {tokens} tokens, more than the model's context window of {limit} tokens")]
+    InputTooLarge {
+        /// Estimated size of the message, in tokens
+        tokens: usize,
+        /// Context window of the model, in tokens
+        limit: usize,
+    },
+
     /// Resource quota exceeded
     #[error("Resource quota exceeded: {0}")]
     QuotaExceeded(String),
 
+    /// The estimated cost this month reached `budget.monthly_cost_limit`
+    #[error("Monthly budget exceeded: ${spent:.2} of the ${limit:.2} limit spent this month")]
+    BudgetExceeded {
+        /// Estimated cost of this month's requests
+        spent: f64,
+        /// Configured monthly limit
+        limit: f64,
+    },
+
     /// Internal runtime error
     #[error("Internal error: {0}")]
     Internal(String),
@@ -155,6 +343,16 @@ pub enum XzatomaError {
     #[error("Stream interrupted: {0}")]
     StreamInterrupted(String),
 
+    /// Provider reported an error in the middle of a streamed response
+    ///
+    /// `partial_content` holds the text received before the failure, if any.
+    #[error("Stream error from {provider}: {reason}")]
+    StreamFailed {
+        provider: String,
+        reason: String,
+        partial_content: Option<String>,
+    },
+
     /// Response format does not match expected structure
     #[error("Invalid response format: {0}")]
     InvalidResponseFormat(String),
@@ -222,9 +420,523 @@ pub enum XzatomaError {
     #[error("ACP error: {0}")]
     Acp(#[from] crate::acp::error::AcpError),
 
+    /// A network feature was used while offline mode is enabled
+    #[error("Offline mode is enabled: {0} cannot be used")]
+    NetworkDisabled(String),
+
     /// Execution was cancelled via a cancellation token.
     #[error("Execution cancelled")]
     Cancelled,
+
+    /// A plan failed schema or semantic validation
+    #[error("Invalid plan: {0}")]
+    InvalidPlan(#[from] crate::tools::plan_validation::PlanValidationError),
+
+    /// Autonomous execution was requested in a workspace that is not trusted
+    #[error("Workspace is not trusted: {0}")]
+    UntrustedWorkspace(String),
+
+    /// A plan that runs in `full_autonomous` was not confirmed, or was
+    /// confirmed with the hash of a different version of the plan
+    #[error("Dangerous plan run not confirmed: {0}")]
+    DangerousPlanNotConfirmed(String),
+
+    /// A change, command, or fetch was attempted while read-only mode is
+    /// enabled
+    #[error("Read-only mode is enabled: {0}")]
+    ReadOnly(String),
+
+    /// The agent finished with status `failure`
+    #[error("Task failed: {0}")]
+    TaskFailed(String),
+
+    /// The agent finished with status `needs_input`
+    #[error("Task needs input: {0}")]
+    TaskNeedsInput(String),
+
+    /// Another session saved the conversation since this one loaded or
+    /// last saved it
+    #[error("Conversation changed by another session: {0}")]
+    ConversationConflict(#[from] crate::storage::types::ConversationConflict),
+}
+
+/// Process exit codes returned by the `xzatoma` binary.
+///
+/// Values follow the BSD `sysexits.h` conventions so scripts can tell
+/// configuration problems apart from authentication or network failures.
+pub mod exit_codes {
+    /// Generic failure with no more specific category
+    pub const GENERAL: i32 = 1;
+    /// Invalid command usage or user input
+    pub const USAGE: i32 = 64;
+    /// Input data (JSON, plan contents) was malformed
+    pub const DATA: i32 = 65;
+    /// A remote service (provider, MCP server, network) was unavailable
+    pub const UNAVAILABLE: i32 = 69;
+    /// Internal software error
+    pub const SOFTWARE: i32 = 70;
+    /// Local I/O or storage failure
+    pub const IO: i32 = 74;
+    /// Temporary failure; retrying later may succeed
+    pub const TEMPFAIL: i32 = 75;
+    /// Remote peer violated the expected protocol
+    pub const PROTOCOL: i32 = 76;
+    /// Credentials missing, expired, or rejected
+    pub const NOPERM: i32 = 77;
+    /// Configuration error
+    pub const CONFIG: i32 = 78;
+    /// Operation was cancelled by the user (matches 128 + SIGINT)
+    pub const CANCELLED: i32 = 130;
+}
+
+/// Maximum number of available models listed in a user-facing message.
+const MAX_LISTED_MODELS: usize = 10;
+
+fn format_retry_after(retry_after: &Option<std::time::Duration>) -> String {
+    match retry_after {
+        Some(delay) => format!(" (retry after {}s)", delay.as_secs().max(1)),
+        None => String::new(),
+    }
+}
+
+fn format_request_timeout(provider: &str, timeout: &std::time::Duration, idle: &bool) -> String {
+    let seconds = timeout.as_secs_f64();
+    if *idle {
+        format!("{} stream sent no data for {:.0}s", provider, seconds)
+    } else {
+        format!("{} request timed out after {:.0}s", provider, seconds)
+    }
+}
+
+fn format_context_overflow(limit: &Option<usize>, attempted: &Option<usize>) -> String {
+    match (limit, attempted) {
+        (Some(limit), Some(attempted)) => format!(
+            "Request of {} tokens exceeds the model's context window of {} tokens",
+            attempted, limit
+        ),
+        (Some(limit), None) => format!(
+            "Request exceeds the model's context window of {} tokens",
+            limit
+        ),
+        _ => "Request exceeds the model's context window".to_string(),
+    }
+}
+
+fn format_dns_status(dns_resolved: &bool) -> &'static str {
+    if *dns_resolved {
+        "host resolved"
+    } else {
+        "host did not resolve"
+    }
+}
+
+impl XzatomaError {
+    /// Returns a short remediation hint describing how the user can resolve
+    /// the error.
+    ///
+    /// Every variant produces a non-empty hint.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::error::XzatomaError;
+    ///
+    /// let error = XzatomaError::Auth {
+    ///     provider: "copilot".to_string(),
+    ///     reason: "token expired".to_string(),
+    /// };
+    /// assert!(error.remediation_hint().contains("xzatoma auth --provider copilot"));
+    /// ```
+    pub fn remediation_hint(&self) -> String {
+        match self {
+            XzatomaError::Config(_) => {
+                "Check your configuration file (default: config/config.yaml) and fix the reported value.".to_string()
+            }
+            XzatomaError::Provider(_) => {
+                "Check the provider configuration and your network connection, then retry.".to_string()
+            }
+            XzatomaError::Tool(_) => {
+                "Review the tool arguments; run with --verbose to see the full error.".to_string()
+            }
+            XzatomaError::Watcher(_) => {
+                "Verify Kafka broker connectivity and the `watcher` section of the configuration.".to_string()
+            }
+            XzatomaError::Command(_) => {
+                "Run `xzatoma --help` (or `/help` in chat) to see the available commands.".to_string()
+            }
+            XzatomaError::Fetch(_) => {
+                "Check that the URL is reachable and permitted by the fetch tool settings.".to_string()
+            }
+            XzatomaError::MentionParse(_) => {
+                "Check the mention syntax; see docs/reference/mention_syntax.md.".to_string()
+            }
+            XzatomaError::FileLoad(_) => {
+                "Verify the file exists, is readable, and is within the size limit.".to_string()
+            }
+            XzatomaError::Search(_) => {
+                "Simplify the search pattern or narrow the path being searched.".to_string()
+            }
+            XzatomaError::RateLimitExceeded { .. } => {
+                "Wait a moment before retrying, or raise the configured limit.".to_string()
+            }
+            XzatomaError::MaxIterationsExceeded { .. } => {
+                "Break the task into smaller steps or increase `agent.max_turns` in the configuration.".to_string()
+            }
+            XzatomaError::ToolLoopDetected { .. } => {
+                "Rephrase the request or point the agent at a different approach; the limits are `agent.tools.loop_detection` in the configuration.".to_string()
+            }
+            XzatomaError::DangerousCommand(_) => {
+                "Review the command; re-run with --allow-dangerous only if you trust it.".to_string()
+            }
+            XzatomaError::CommandRequiresConfirmation(_) => {
+                "Run interactively to confirm, or pass --allow-dangerous to skip confirmation.".to_string()
+            }
+            XzatomaError::PathOutsideWorkingDirectory(_) => {
+                "Use a path inside the current working directory.".to_string()
+            }
+            XzatomaError::StreamingNotSupported => {
+                "Disable streaming for this provider in the configuration.".to_string()
+            }
+            XzatomaError::MissingCredentials(provider) => format!(
+                "Run `xzatoma auth --provider {}` or add credentials to the configuration.",
+                provider
+            ),
+            XzatomaError::Auth { provider, .. } => match provider.as_str() {
+                "copilot" => {
+                    "Run `xzatoma auth --provider copilot` to re-authenticate.".to_string()
+                }
+                "openai" => {
+                    "Set `provider.openai.api_key` (or XZATOMA_OPENAI_API_KEY) to a valid key.".to_string()
+                }
+                other => format!("Check the credentials configured for {}.", other),
+            },
+            XzatomaError::ModelNotFound { .. } => {
+                "Run `xzatoma models list` to see available models and update the configured model.".to_string()
+            }
+            XzatomaError::RateLimited { retry_after, .. } => match retry_after {
+                Some(delay) => format!(
+                    "Wait {} seconds before retrying, or switch to a less busy model.",
+                    delay.as_secs().max(1)
+                ),
+                None => "Wait a moment before retrying, or switch to a less busy model.".to_string(),
+            },
+            XzatomaError::NetworkUnreachable { endpoint, .. } => format!(
+                "Check your network connection and that {} is reachable.",
+                endpoint
+            ),
+            XzatomaError::RequestTimeout { provider, .. } => format!(
+                "Retry the request, or raise `provider.{}.request_timeout_seconds` (and `agent.timeout_seconds`) if responses are legitimately slow.",
+                provider
+            ),
+            XzatomaError::RequestQueueTimeout { provider, .. } => format!(
+                "Other requests to the same host were still running. Raise `provider.{}.max_concurrent_requests` if the server can run more requests at once, or run fewer subagents and watcher plans in parallel.",
+                provider
+            ),
+            XzatomaError::RateLimitQueueTimeout { provider, .. } => format!(
+                "Too many requests were waiting for the {} rate limits. Raise `provider.rate_limits.{}.requests_per_minute` or `provider.rate_limits.max_wait_seconds`, or run fewer subagents and parallel plan steps.",
+                provider, provider
+            ),
+            XzatomaError::OllamaUnavailable { .. } => {
+                "Is Ollama running? Start the server with `ollama serve`, or set XZATOMA_OLLAMA_HOST to the correct host.".to_string()
+            }
+            XzatomaError::ModelNotPulled { model } => {
+                format!("Download the model with `ollama pull {}`.", model)
+            }
+            XzatomaError::ToolFailed { tool, .. } => format!(
+                "Check the arguments passed to `{}`; run with --verbose for the full error.",
+                tool
+            ),
+            XzatomaError::McpServer { server, .. } => format!(
+                "Run `xzatoma mcp list` to check `{}` and verify its entry under `mcp.servers`.",
+                server
+            ),
+            XzatomaError::Io(_) => {
+                "Check that the path exists and that you have permission to access it.".to_string()
+            }
+            XzatomaError::Serialization(_) => {
+                "The data could not be parsed as JSON; check the input for corruption.".to_string()
+            }
+            XzatomaError::InvalidPlan(_) => {
+                "Fix the listed plan problems; `xzatoma run --plan <file> --validate-only` checks a plan without running it.".to_string()
+            }
+            XzatomaError::Yaml(_) => {
+                "Fix the YAML syntax in the configuration or plan file.".to_string()
+            }
+            XzatomaError::UntrustedWorkspace(_) => {
+                "Review the workspace's project-local files, then run `xzatoma trust add .` to trust it.".to_string()
+            }
+            XzatomaError::DangerousPlanNotConfirmed(_) => {
+                "Review the plan with `xzatoma run --plan <file> --dry-run --allow-dangerous`, then pass the printed hash with --confirm-dangerous.".to_string()
+            }
+            XzatomaError::ReadOnly(_) => {
+                "Start a new session without --read-only and with `agent.read_only: false` to make changes; add --allow-fetch to load URLs in read-only mode.".to_string()
+            }
+            XzatomaError::TaskFailed(_) => {
+                "Read the agent's summary above, fix the reported problem, and run the task again.".to_string()
+            }
+            XzatomaError::TaskNeedsInput(_) => {
+                "Answer the agent's question in the prompt or plan and run the task again.".to_string()
+            }
+            XzatomaError::ConversationConflict(_) => {
+                "Reload the conversation to merge the other session's messages, or save this session under a new conversation ID.".to_string()
+            }
+            XzatomaError::Http(_) => {
+                "Check your network connection and proxy settings, then retry.".to_string()
+            }
+            XzatomaError::Regex(_) => "Fix the regular expression syntax.".to_string(),
+            XzatomaError::TracingFilter(_) => {
+                "Fix the RUST_LOG filter value (for example `RUST_LOG=xzatoma=debug`).".to_string()
+            }
+            XzatomaError::Keyring(_) => {
+                "Ensure the system keyring is unlocked and accessible, then re-run `xzatoma auth`.".to_string()
+            }
+            XzatomaError::Credentials(_) => {
+                "Set XZATOMA_CREDENTIALS_KEY to the passphrase for the credentials file, or change `credentials.backend`.".to_string()
+            }
+            XzatomaError::CredentialBackendUnavailable { .. } => {
+                "Export XZATOMA_CREDENTIALS_KEY (or set `credentials.allow_plaintext: true`) so credentials use the credentials file, or unlock the keyring first: `security unlock-keychain` over SSH on macOS, `dbus-run-session -- xzatoma ...` on Linux without a session bus. If the keyring is only slow, raise `credentials.keyring_timeout_seconds`.".to_string()
+            }
+            XzatomaError::ToolDefinitionsExceedLimits(_) => {
+                "Disable tools on some MCP servers, or set `agent.tools.definition_limits.strategy` to `truncate` or `drop`.".to_string()
+            }
+            XzatomaError::Storage(_) => {
+                "Check that the history database is writable, or relocate it with --storage-path.".to_string()
+            }
+            XzatomaError::Snapshot(_) => {
+                "Ignore large or generated files in .gitignore, raise `run.snapshot.max_total_bytes`, or run without --snapshot.".to_string()
+            }
+            XzatomaError::ContextOverflow { .. } => {
+                "Start a new conversation, or set `agent.conversation.max_tokens` to the model's context window so pruning starts earlier.".to_string()
+            }
+            XzatomaError::InputTooLarge { .. } => {
+                "Split the input into smaller messages, or mention a line range of large files (for example @file.rs#L1-200).".to_string()
+            }
+            XzatomaError::QuotaExceeded(_) => {
+                "Reduce the workload or raise the quota limits in the configuration.".to_string()
+            }
+            XzatomaError::BudgetExceeded { .. } => {
+                "Check spending with `xzatoma usage`, raise `budget.monthly_cost_limit`, or rerun with --ignore-budget.".to_string()
+            }
+            XzatomaError::Internal(_) => {
+                "This is a bug; please report it with the output of --verbose.".to_string()
+            }
+            XzatomaError::UnsupportedEndpoint(_, _) => {
+                "Choose a model that supports this endpoint, or enable endpoint fallback for the provider.".to_string()
+            }
+            XzatomaError::SseParseError(_)
+            | XzatomaError::StreamInterrupted(_)
+            | XzatomaError::InvalidResponseFormat(_) => {
+                "Retry the request; if it keeps failing, disable streaming for the provider.".to_string()
+            }
+            XzatomaError::StreamFailed {
+                partial_content: Some(_),
+                ..
+            } => "Retry the request; the partial response received before the error was discarded.".to_string(),
+            XzatomaError::StreamFailed { .. } => {
+                "Retry the request; if it keeps failing, disable streaming for the provider.".to_string()
+            }
+            XzatomaError::EndpointFallbackFailed => {
+                "Run `xzatoma models info <model>` to check which endpoints the model supports.".to_string()
+            }
+            XzatomaError::MessageConversionError(_) => {
+                "Start a new conversation; the history may contain an unsupported message.".to_string()
+            }
+            XzatomaError::Mcp(_) | XzatomaError::McpTransport(_) => {
+                "Check the MCP server logs and its entry under `mcp.servers`.".to_string()
+            }
+            XzatomaError::McpServerNotFound(server) => format!(
+                "Add `{}` under `mcp.servers` in the configuration or check the server id.",
+                server
+            ),
+            XzatomaError::McpToolNotFound { server, .. } => format!(
+                "Run `xzatoma mcp list` to see the tools exposed by `{}`.",
+                server
+            ),
+            XzatomaError::McpProtocolVersion { .. } => {
+                "Upgrade the MCP server to a supported protocol version.".to_string()
+            }
+            XzatomaError::McpTimeout { server, .. } => format!(
+                "Check that `{}` is healthy, or increase its timeout in the configuration.",
+                server
+            ),
+            XzatomaError::McpAuth(_) => {
+                "Re-authorize the MCP server; remove its cached token if the problem persists.".to_string()
+            }
+            XzatomaError::McpElicitation(_) => {
+                "Answer the server's request for input, or disable elicitation for the server.".to_string()
+            }
+            XzatomaError::McpTask(_) => "Check the MCP server logs for the failed task.".to_string(),
+            XzatomaError::Acp(_) => {
+                "Check the ACP client request and the `acp` section of the configuration.".to_string()
+            }
+            XzatomaError::NetworkDisabled(_) => {
+                "Run without --offline and set `agent.offline: false` to use network features.".to_string()
+            }
+            XzatomaError::Cancelled => "Re-run the command to try again.".to_string(),
+        }
+    }
+
+    /// Returns a concise, user-facing explanation of the error followed by a
+    /// remediation hint.
+    ///
+    /// This is what the CLI prints on failure. The full error chain is only
+    /// shown with `--verbose`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::error::XzatomaError;
+    ///
+    /// let error = XzatomaError::ModelNotFound {
+    ///     model: "gpt-9".to_string(),
+    ///     available: vec!["gpt-4o".to_string()],
+    /// };
+    /// let message = error.user_message();
+    /// assert!(message.contains("gpt-9"));
+    /// assert!(message.contains("gpt-4o"));
+    /// assert!(message.contains("Hint:"));
+    /// ```
+    pub fn user_message(&self) -> String {
+        let summary = match self {
+            XzatomaError::ModelNotFound { model, available } if !available.is_empty() => {
+                let mut listed: Vec<&str> = available
+                    .iter()
+                    .take(MAX_LISTED_MODELS)
+                    .map(String::as_str)
+                    .collect();
+                if available.len() > MAX_LISTED_MODELS {
+                    listed.push("...");
+                }
+                format!(
+                    "Model not found: {}. Available models: {}",
+                    model,
+                    listed.join(", ")
+                )
+            }
+            other => other.to_string(),
+        };
+        format!("{}\nHint: {}", summary, self.remediation_hint())
+    }
+
+    /// Returns the process exit code associated with this error.
+    ///
+    /// See [`exit_codes`] for the meaning of each value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::error::{exit_codes, XzatomaError};
+    ///
+    /// assert_eq!(
+    ///     XzatomaError::Config("bad".to_string()).exit_code(),
+    ///     exit_codes::CONFIG
+    /// );
+    /// assert_eq!(XzatomaError::Cancelled.exit_code(), exit_codes::CANCELLED);
+    /// ```
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            XzatomaError::Config(_)
+            | XzatomaError::Yaml(_)
+            | XzatomaError::TracingFilter(_)
+            | XzatomaError::ToolDefinitionsExceedLimits(_) => exit_codes::CONFIG,
+            XzatomaError::Auth { .. }
+            | XzatomaError::MissingCredentials(_)
+            | XzatomaError::Keyring(_)
+            | XzatomaError::Credentials(_)
+            | XzatomaError::CredentialBackendUnavailable { .. }
+            | XzatomaError::McpAuth(_)
+            | XzatomaError::UntrustedWorkspace(_)
+            | XzatomaError::DangerousPlanNotConfirmed(_)
+            | XzatomaError::ReadOnly(_) => exit_codes::NOPERM,
+            XzatomaError::RateLimited { .. }
+            | XzatomaError::RateLimitExceeded { .. }
+            | XzatomaError::RequestTimeout { .. }
+            | XzatomaError::RequestQueueTimeout { .. }
+            | XzatomaError::RateLimitQueueTimeout { .. }
+            | XzatomaError::QuotaExceeded(_)
+            | XzatomaError::BudgetExceeded { .. }
+            | XzatomaError::McpTimeout { .. }
+            | XzatomaError::TaskNeedsInput(_) => exit_codes::TEMPFAIL,
+            XzatomaError::Provider(_)
+            | XzatomaError::NetworkUnreachable { .. }
+            | XzatomaError::OllamaUnavailable { .. }
+            | XzatomaError::Http(_)
+            | XzatomaError::Fetch(_)
+            | XzatomaError::StreamInterrupted(_)
+            | XzatomaError::StreamFailed { .. }
+            | XzatomaError::EndpointFallbackFailed
+            | XzatomaError::UnsupportedEndpoint(_, _)
+            | XzatomaError::Mcp(_)
+            | XzatomaError::McpTransport(_)
+            | XzatomaError::McpServer { .. }
+            | XzatomaError::ContextOverflow { .. } => exit_codes::UNAVAILABLE,
+            XzatomaError::SseParseError(_)
+            | XzatomaError::InvalidResponseFormat(_)
+            | XzatomaError::MessageConversionError(_)
+            | XzatomaError::McpProtocolVersion { .. } => exit_codes::PROTOCOL,
+            XzatomaError::Io(_)
+            | XzatomaError::Storage(_)
+            | XzatomaError::Snapshot(_)
+            | XzatomaError::FileLoad(_) => exit_codes::IO,
+            XzatomaError::Serialization(_) | XzatomaError::InvalidPlan(_) => exit_codes::DATA,
+            XzatomaError::Command(_)
+            | XzatomaError::MentionParse(_)
+            | XzatomaError::ModelNotFound { .. }
+            | XzatomaError::ModelNotPulled { .. }
+            | XzatomaError::DangerousCommand(_)
+            | XzatomaError::CommandRequiresConfirmation(_)
+            | XzatomaError::PathOutsideWorkingDirectory(_)
+            | XzatomaError::StreamingNotSupported
+            | XzatomaError::McpServerNotFound(_)
+            | XzatomaError::NetworkDisabled(_)
+            | XzatomaError::InputTooLarge { .. } => exit_codes::USAGE,
+            XzatomaError::Internal(_) | XzatomaError::Regex(_) => exit_codes::SOFTWARE,
+            XzatomaError::Cancelled => exit_codes::CANCELLED,
+            XzatomaError::Tool(_)
+            | XzatomaError::ToolFailed { .. }
+            | XzatomaError::Search(_)
+            | XzatomaError::MaxIterationsExceeded { .. }
+            | XzatomaError::ToolLoopDetected { .. }
+            | XzatomaError::TaskFailed(_)
+            | XzatomaError::ConversationConflict(_)
+            | XzatomaError::Watcher(_)
+            | XzatomaError::McpToolNotFound { .. }
+            | XzatomaError::McpElicitation(_)
+            | XzatomaError::McpTask(_)
+            | XzatomaError::Acp(_) => exit_codes::GENERAL,
+        }
+    }
+
+    /// Returns `true` for transient failures that may succeed if retried.
+    ///
+    /// Timeouts, rate limits, and dropped connections are retryable; errors
+    /// that would fail the same way again are not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use xzatoma::error::XzatomaError;
+    ///
+    /// let timeout = XzatomaError::RequestTimeout {
+    ///     provider: "copilot".to_string(),
+    ///     timeout: Duration::from_secs(120),
+    ///     idle: false,
+    /// };
+    /// assert!(timeout.is_retryable());
+    /// assert!(!XzatomaError::Config("bad".to_string()).is_retryable());
+    /// ```
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            XzatomaError::RequestTimeout { .. }
+                | XzatomaError::RequestQueueTimeout { .. }
+                | XzatomaError::RateLimitQueueTimeout { .. }
+                | XzatomaError::RateLimited { .. }
+                | XzatomaError::NetworkUnreachable { .. }
+                | XzatomaError::StreamInterrupted(_)
+        )
+    }
 }
 
 /// Result type alias for XZatoma operations.
@@ -358,10 +1070,23 @@ impl From<crate::watcher::xzepr::consumer::kafka::ConsumerError> for XzatomaErro
     }
 }
 
-/// Converts `ClientError` to `XzatomaError::Provider`
+/// Converts `ClientError` to `XzatomaError::Auth` for authentication failures,
+/// `XzatomaError::NetworkDisabled` in offline mode, and
+/// `XzatomaError::Provider` otherwise
 impl From<crate::watcher::xzepr::consumer::client::ClientError> for XzatomaError {
     fn from(err: crate::watcher::xzepr::consumer::client::ClientError) -> Self {
-        XzatomaError::Provider(err.to_string())
+        match err {
+            crate::watcher::xzepr::consumer::client::ClientError::Authentication(reason) => {
+                XzatomaError::Auth {
+                    provider: "xzepr".to_string(),
+                    reason,
+                }
+            }
+            crate::watcher::xzepr::consumer::client::ClientError::NetworkDisabled(capability) => {
+                XzatomaError::NetworkDisabled(capability)
+            }
+            other => XzatomaError::Provider(other.to_string()),
+        }
     }
 }
 
@@ -508,9 +1233,479 @@ mod tests {
     }
 
     #[test]
-    fn test_authentication_error_display() {
-        let error = XzatomaError::Authentication("token expired".to_string());
-        assert_eq!(error.to_string(), "Authentication error: token expired");
+    fn test_auth_error_display() {
+        let error = XzatomaError::Auth {
+            provider: "copilot".to_string(),
+            reason: "token expired".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Authentication error (copilot): token expired"
+        );
+    }
+
+    #[test]
+    fn test_model_not_found_display() {
+        let error = XzatomaError::ModelNotFound {
+            model: "gpt-9".to_string(),
+            available: vec!["gpt-4o".to_string()],
+        };
+        assert_eq!(error.to_string(), "Model not found: gpt-9");
+    }
+
+    #[test]
+    fn test_request_timeout_display() {
+        let total = XzatomaError::RequestTimeout {
+            provider: "ollama".to_string(),
+            timeout: std::time::Duration::from_secs(120),
+            idle: false,
+        };
+        assert_eq!(total.to_string(), "ollama request timed out after 120s");
+        assert!(total.is_retryable());
+
+        let idle = XzatomaError::RequestTimeout {
+            provider: "copilot".to_string(),
+            timeout: std::time::Duration::from_secs(60),
+            idle: true,
+        };
+        assert_eq!(idle.to_string(), "copilot stream sent no data for 60s");
+    }
+
+    #[test]
+    fn test_request_queue_timeout_display() {
+        let queued = XzatomaError::RequestQueueTimeout {
+            provider: "ollama".to_string(),
+            host: "http://localhost:11434".to_string(),
+            waited: std::time::Duration::from_secs(30),
+            max_concurrent: 2,
+        };
+        assert_eq!(
+            queued.to_string(),
+            "ollama request timed out after 30s while queued behind 2 in-flight request(s) to http://localhost:11434"
+        );
+        assert!(queued.is_retryable());
+        assert_eq!(queued.exit_code(), exit_codes::TEMPFAIL);
+        assert!(queued
+            .remediation_hint()
+            .contains("provider.ollama.max_concurrent_requests"));
+    }
+
+    #[test]
+    fn test_rate_limit_queue_timeout_display() {
+        let error = XzatomaError::RateLimitQueueTimeout {
+            provider: "copilot".to_string(),
+            waited: std::time::Duration::from_secs(300),
+        };
+        assert_eq!(
+            error.to_string(),
+            "copilot request timed out after 300s waiting for provider.rate_limits"
+        );
+        assert!(error.is_retryable());
+        assert_eq!(error.exit_code(), exit_codes::TEMPFAIL);
+        assert!(error
+            .remediation_hint()
+            .contains("provider.rate_limits.copilot.requests_per_minute"));
+    }
+
+    #[test]
+    fn test_is_retryable_excludes_non_transient_errors() {
+        assert!(!XzatomaError::Config("bad".to_string()).is_retryable());
+        assert!(!XzatomaError::ModelNotPulled {
+            model: "llama3.2".to_string()
+        }
+        .is_retryable());
+    }
+
+    #[test]
+    fn test_ollama_unavailable_display_and_hint() {
+        let error = XzatomaError::OllamaUnavailable {
+            host: "http://ollama.local:11434".to_string(),
+            dns_resolved: false,
+            reason: "dns error".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Ollama is not reachable at http://ollama.local:11434 (host did not resolve): dns error"
+        );
+        assert!(error.remediation_hint().contains("ollama serve"));
+        assert!(error.remediation_hint().contains("XZATOMA_OLLAMA_HOST"));
+    }
+
+    #[test]
+    fn test_model_not_pulled_hint_names_model() {
+        let error = XzatomaError::ModelNotPulled {
+            model: "qwen2.5-coder".to_string(),
+        };
+        assert!(error
+            .remediation_hint()
+            .contains("ollama pull qwen2.5-coder"));
+    }
+
+    #[test]
+    fn test_rate_limited_display_with_retry_after() {
+        let error = XzatomaError::RateLimited {
+            provider: "copilot".to_string(),
+            retry_after: Some(std::time::Duration::from_secs(30)),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Rate limited by copilot (retry after 30s)"
+        );
+    }
+
+    #[test]
+    fn test_rate_limited_display_without_retry_after() {
+        let error = XzatomaError::RateLimited {
+            provider: "openai".to_string(),
+            retry_after: None,
+        };
+        assert_eq!(error.to_string(), "Rate limited by openai");
+    }
+
+    #[test]
+    fn test_tool_failed_display() {
+        let error = XzatomaError::ToolFailed {
+            tool: "read_file".to_string(),
+            reason: "permission denied".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Tool 'read_file' execution failed: permission denied"
+        );
+    }
+
+    #[test]
+    fn test_mcp_server_display() {
+        let error = XzatomaError::McpServer {
+            server: "github".to_string(),
+            reason: "server is not connected".to_string(),
+        };
+        assert!(error.to_string().contains("github"));
+        assert!(error.to_string().contains("not connected"));
+    }
+
+    /// Builds one instance of every variant. The exhaustive matches in
+    /// `remediation_hint` and `exit_code` guarantee new variants are handled;
+    /// this list exists so their output can be asserted at runtime.
+    fn sample_errors() -> Vec<XzatomaError> {
+        use std::str::FromStr;
+
+        vec![
+            XzatomaError::Config("bad".to_string()),
+            XzatomaError::Provider("down".to_string()),
+            XzatomaError::Tool("oops".to_string()),
+            XzatomaError::Watcher("lag".to_string()),
+            XzatomaError::Command("unknown".to_string()),
+            XzatomaError::Fetch("timeout".to_string()),
+            XzatomaError::MentionParse("bad mention".to_string()),
+            XzatomaError::FileLoad("missing".to_string()),
+            XzatomaError::Search("bad pattern".to_string()),
+            XzatomaError::RateLimitExceeded {
+                limit: 1,
+                message: "slow down".to_string(),
+            },
+            XzatomaError::MaxIterationsExceeded {
+                limit: 1,
+                message: "loop".to_string(),
+            },
+            XzatomaError::DangerousCommand("rm -rf /".to_string()),
+            XzatomaError::CommandRequiresConfirmation("sudo".to_string()),
+            XzatomaError::PathOutsideWorkingDirectory("/etc".to_string()),
+            XzatomaError::StreamingNotSupported,
+            XzatomaError::MissingCredentials("copilot".to_string()),
+            XzatomaError::Auth {
+                provider: "copilot".to_string(),
+                reason: "expired".to_string(),
+            },
+            XzatomaError::ModelNotFound {
+                model: "gpt-9".to_string(),
+                available: Vec::new(),
+            },
+            XzatomaError::RateLimited {
+                provider: "copilot".to_string(),
+                retry_after: None,
+            },
+            XzatomaError::NetworkUnreachable {
+                endpoint: "http://localhost:11434".to_string(),
+                reason: "connection refused".to_string(),
+            },
+            XzatomaError::RequestTimeout {
+                provider: "copilot".to_string(),
+                timeout: std::time::Duration::from_secs(120),
+                idle: false,
+            },
+            XzatomaError::RequestQueueTimeout {
+                provider: "ollama".to_string(),
+                host: "http://localhost:11434".to_string(),
+                waited: std::time::Duration::from_secs(600),
+                max_concurrent: 2,
+            },
+            XzatomaError::RateLimitQueueTimeout {
+                provider: "copilot".to_string(),
+                waited: std::time::Duration::from_secs(300),
+            },
+            XzatomaError::OllamaUnavailable {
+                host: "http://localhost:11434".to_string(),
+                dns_resolved: true,
+                reason: "connection refused".to_string(),
+            },
+            XzatomaError::ModelNotPulled {
+                model: "llama3.2".to_string(),
+            },
+            XzatomaError::ToolFailed {
+                tool: "terminal".to_string(),
+                reason: "exit 1".to_string(),
+            },
+            XzatomaError::ToolLoopDetected {
+                tool: "grep".to_string(),
+                repeats: 5,
+            },
+            XzatomaError::McpServer {
+                server: "github".to_string(),
+                reason: "crashed".to_string(),
+            },
+            XzatomaError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "disk")),
+            XzatomaError::Serialization(
+                serde_json::from_str::<serde_json::Value>("{").unwrap_err(),
+            ),
+            XzatomaError::Yaml(serde_yaml::from_str::<serde_yaml::Value>(": :").unwrap_err()),
+            XzatomaError::Http(reqwest::Client::new().get("not a url").build().unwrap_err()),
+            XzatomaError::Regex(regex::Regex::new("(").unwrap_err()),
+            XzatomaError::TracingFilter(
+                tracing_subscriber::filter::Directive::from_str("xzatoma=[").unwrap_err(),
+            ),
+            XzatomaError::Keyring(keyring::Error::NoEntry),
+            XzatomaError::Credentials("wrong key".to_string()),
+            XzatomaError::CredentialBackendUnavailable {
+                backend: "keyring".to_string(),
+                session: "SSH session".to_string(),
+                reason: "no response within 5s".to_string(),
+            },
+            XzatomaError::Storage("locked".to_string()),
+            XzatomaError::Snapshot("workspace is larger than 512 MiB".to_string()),
+            XzatomaError::ToolDefinitionsExceedLimits("140 tools".to_string()),
+            XzatomaError::ContextOverflow {
+                limit: Some(128000),
+                attempted: Some(140213),
+            },
+            XzatomaError::InputTooLarge {
+                tokens: 200000,
+                limit: 128000,
+            },
+            XzatomaError::QuotaExceeded("tokens".to_string()),
+            XzatomaError::BudgetExceeded {
+                spent: 51.2,
+                limit: 50.0,
+            },
+            XzatomaError::Internal("poisoned".to_string()),
+            XzatomaError::UnsupportedEndpoint("m".to_string(), "responses".to_string()),
+            XzatomaError::SseParseError("bad".to_string()),
+            XzatomaError::StreamInterrupted("reset".to_string()),
+            XzatomaError::StreamFailed {
+                provider: "copilot".to_string(),
+                reason: "overloaded".to_string(),
+                partial_content: Some("Hel".to_string()),
+            },
+            XzatomaError::InvalidResponseFormat("shape".to_string()),
+            XzatomaError::EndpointFallbackFailed,
+            XzatomaError::MessageConversionError("role".to_string()),
+            XzatomaError::Mcp("protocol".to_string()),
+            XzatomaError::McpTransport("pipe".to_string()),
+            XzatomaError::McpServerNotFound("ghost".to_string()),
+            XzatomaError::McpToolNotFound {
+                server: "s".to_string(),
+                tool: "t".to_string(),
+            },
+            XzatomaError::McpProtocolVersion {
+                expected: vec!["2025-11-25".to_string()],
+                got: "2024-01-01".to_string(),
+            },
+            XzatomaError::McpTimeout {
+                server: "s".to_string(),
+                method: "tools/list".to_string(),
+            },
+            XzatomaError::McpAuth("expired".to_string()),
+            XzatomaError::McpElicitation("declined".to_string()),
+            XzatomaError::McpTask("failed".to_string()),
+            XzatomaError::Acp(crate::acp::error::AcpError::validation("bad")),
+            XzatomaError::NetworkDisabled("URL mentions".to_string()),
+            XzatomaError::Cancelled,
+            XzatomaError::InvalidPlan(crate::tools::plan_validation::PlanValidationError::single(
+                crate::tools::plan_validation::PlanIssue::new("/steps", "empty"),
+            )),
+            XzatomaError::UntrustedWorkspace("/tmp/repo".to_string()),
+            XzatomaError::DangerousPlanNotConfirmed("plan changed".to_string()),
+            XzatomaError::ReadOnly("write_file cannot be used".to_string()),
+            XzatomaError::TaskFailed("tests still fail".to_string()),
+            XzatomaError::TaskNeedsInput("which branch?".to_string()),
+            XzatomaError::ConversationConflict(crate::storage::types::ConversationConflict {
+                id: "c1".to_string(),
+                expected_revision: 1,
+                stored_revision: 2,
+                stored_updated_at: None,
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_every_variant_has_non_empty_remediation_hint() {
+        for error in sample_errors() {
+            let hint = error.remediation_hint();
+            assert!(!hint.trim().is_empty(), "empty hint for {:?}", error);
+        }
+    }
+
+    #[test]
+    fn test_every_variant_has_non_zero_exit_code() {
+        for error in sample_errors() {
+            assert_ne!(error.exit_code(), 0, "zero exit code for {:?}", error);
+        }
+    }
+
+    #[test]
+    fn test_user_message_contains_summary_and_hint() {
+        for error in sample_errors() {
+            let message = error.user_message();
+            assert!(message.starts_with(&error.to_string()));
+            assert!(message.contains("\nHint: "));
+        }
+    }
+
+    #[test]
+    fn test_auth_hint_for_copilot_points_to_auth_command() {
+        let error = XzatomaError::Auth {
+            provider: "copilot".to_string(),
+            reason: "401".to_string(),
+        };
+        assert!(error
+            .remediation_hint()
+            .contains("xzatoma auth --provider copilot"));
+    }
+
+    #[test]
+    fn test_model_not_found_user_message_lists_available_models() {
+        let available: Vec<String> = (0..15).map(|i| format!("model-{}", i)).collect();
+        let error = XzatomaError::ModelNotFound {
+            model: "missing".to_string(),
+            available,
+        };
+        let message = error.user_message();
+        assert!(message.contains("model-0"));
+        assert!(message.contains("model-9"));
+        assert!(!message.contains("model-10"));
+        assert!(message.contains("..."));
+    }
+
+    #[test]
+    fn test_context_overflow_message_includes_known_token_counts() {
+        let error = XzatomaError::ContextOverflow {
+            limit: Some(8192),
+            attempted: Some(9100),
+        };
+        assert!(error.to_string().contains("9100 tokens"));
+        assert!(error.to_string().contains("8192 tokens"));
+
+        let error = XzatomaError::ContextOverflow {
+            limit: None,
+            attempted: None,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Request exceeds the model's context window"
+        );
+    }
+
+    #[test]
+    fn test_rate_limited_hint_includes_retry_after() {
+        let error = XzatomaError::RateLimited {
+            provider: "copilot".to_string(),
+            retry_after: Some(std::time::Duration::from_secs(12)),
+        };
+        assert!(error.remediation_hint().contains("12 seconds"));
+    }
+
+    #[test]
+    fn test_exit_code_mapping() {
+        assert_eq!(
+            XzatomaError::Config("x".to_string()).exit_code(),
+            exit_codes::CONFIG
+        );
+        assert_eq!(
+            XzatomaError::Auth {
+                provider: "copilot".to_string(),
+                reason: "x".to_string(),
+            }
+            .exit_code(),
+            exit_codes::NOPERM
+        );
+        assert_eq!(
+            XzatomaError::RateLimited {
+                provider: "copilot".to_string(),
+                retry_after: None,
+            }
+            .exit_code(),
+            exit_codes::TEMPFAIL
+        );
+        assert_eq!(
+            XzatomaError::NetworkUnreachable {
+                endpoint: "h".to_string(),
+                reason: "r".to_string(),
+            }
+            .exit_code(),
+            exit_codes::UNAVAILABLE
+        );
+        assert_eq!(
+            XzatomaError::ModelNotFound {
+                model: "m".to_string(),
+                available: Vec::new(),
+            }
+            .exit_code(),
+            exit_codes::USAGE
+        );
+        assert_eq!(XzatomaError::Cancelled.exit_code(), exit_codes::CANCELLED);
+        assert_eq!(
+            XzatomaError::TaskFailed("x".to_string()).exit_code(),
+            exit_codes::GENERAL
+        );
+        assert_eq!(
+            XzatomaError::TaskNeedsInput("x".to_string()).exit_code(),
+            exit_codes::TEMPFAIL
+        );
+    }
+
+    #[test]
+    fn test_client_authentication_error_converts_to_auth_variant() {
+        let error: XzatomaError =
+            crate::watcher::xzepr::consumer::client::ClientError::Authentication(
+                "XZEPR_API_TOKEN not set".to_string(),
+            )
+            .into();
+        match error {
+            XzatomaError::Auth { provider, reason } => {
+                assert_eq!(provider, "xzepr");
+                assert!(reason.contains("XZEPR_API_TOKEN"));
+            }
+            other => panic!("expected Auth variant, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_client_not_found_error_converts_to_provider_variant() {
+        let error: XzatomaError =
+            crate::watcher::xzepr::consumer::client::ClientError::NotFound("receiver".to_string())
+                .into();
+        assert!(matches!(error, XzatomaError::Provider(_)));
+    }
+
+    #[test]
+    fn test_consumer_config_error_converts_to_config_exit_code() {
+        let error: XzatomaError =
+            crate::watcher::xzepr::consumer::config::ConfigError::MissingConfig(
+                "brokers".to_string(),
+            )
+            .into();
+        assert!(matches!(error, XzatomaError::Config(_)));
+        assert_eq!(error.exit_code(), exit_codes::CONFIG);
     }
 
     #[test]
@@ -618,6 +1813,20 @@ mod tests {
         assert!(msg.contains("Connection reset"));
     }
 
+    #[test]
+    fn test_stream_failed_error() {
+        let err = XzatomaError::StreamFailed {
+            provider: "copilot".to_string(),
+            reason: "upstream overloaded".to_string(),
+            partial_content: Some("Hello".to_string()),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Stream error from copilot: upstream overloaded"
+        );
+        assert!(err.remediation_hint().contains("partial response"));
+    }
+
     #[test]
     fn test_invalid_response_format_error() {
         let err = XzatomaError::InvalidResponseFormat("Missing required field".to_string());
@@ -0,0 +1,268 @@
+//! Human-readable numbers for terminal output
+//!
+//! Token counts, costs, durations, and byte sizes shown to the user go
+//! through this module, so every command renders them the same way:
+//!
+//! | Value     | Function            | Examples                                |
+//! | --------- | ------------------- | --------------------------------------- |
+//! | tokens    | [`format_tokens`]   | `999`, `1.0k`, `12.3k`, `1.2M`          |
+//! | counts    | [`format_count`]    | `999`, `1,000`, `123,456`               |
+//! | costs     | [`format_cost`]     | `$0.00`, `<$0.01`, `$0.03`, `$1,250.00` |
+//! | durations | [`format_duration`] | `850ms`, `4.2s`, `1m 42s`, `2h 05m`     |
+//! | sizes     | [`format_bytes`]    | `512 B`, `1.5 KiB`, `1.0 MiB`           |
+//!
+//! `--raw-numbers` switches every function to plain values for scripts that
+//! read the text output: `123456`, `0.031200`, `102000ms`, `1048576 B`.
+//! JSON output serializes the numbers themselves and is never formatted
+//! here. The file tool summaries in `tools::file_summary` and `read_file`
+//! also format sizes and counts here, so the model reads them in the same
+//! form as the terminal does, and the flag applies to them too.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static RAW_NUMBERS: AtomicBool = AtomicBool::new(false);
+
+/// Compact suffixes for token counts, each 1000 times the previous
+const TOKEN_UNITS: [&str; 3] = ["k", "M", "B"];
+
+/// Binary suffixes for byte sizes, each 1024 times the previous
+const BYTE_UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+/// Prints plain numbers instead of formatted ones
+///
+/// Called once from `main` with the `--raw-numbers` flag.
+pub fn set_raw_numbers(raw: bool) {
+    RAW_NUMBERS.store(raw, Ordering::Relaxed);
+}
+
+/// Returns true when numbers are printed unformatted
+pub fn raw_numbers() -> bool {
+    RAW_NUMBERS.load(Ordering::Relaxed)
+}
+
+/// Formats a token count, compacted above 999
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::format::format_tokens;
+///
+/// assert_eq!(format_tokens(999), "999");
+/// assert_eq!(format_tokens(12_345), "12.3k");
+/// assert_eq!(format_tokens(1_234_567), "1.2M");
+/// ```
+pub fn format_tokens(tokens: u64) -> String {
+    tokens_with(tokens, raw_numbers())
+}
+
+/// Formats a count with thousands separators, for exact table columns
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::format::format_count;
+///
+/// assert_eq!(format_count(123_456), "123,456");
+/// ```
+pub fn format_count(count: u64) -> String {
+    count_with(count, raw_numbers())
+}
+
+/// Formats an estimated cost in US dollars
+///
+/// Amounts below one cent show as `<$0.01` so a cheap request is not
+/// mistaken for a free one.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::format::format_cost;
+///
+/// assert_eq!(format_cost(0.0), "$0.00");
+/// assert_eq!(format_cost(0.004), "<$0.01");
+/// assert_eq!(format_cost(1250.0), "$1,250.00");
+/// ```
+pub fn format_cost(usd: f64) -> String {
+    cost_with(usd, raw_numbers())
+}
+
+/// Formats a duration with the two most significant units
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use xzatoma::format::format_duration;
+///
+/// assert_eq!(format_duration(Duration::from_millis(850)), "850ms");
+/// assert_eq!(format_duration(Duration::from_secs(102)), "1m 42s");
+/// ```
+pub fn format_duration(duration: Duration) -> String {
+    duration_with(duration, raw_numbers())
+}
+
+/// Formats a byte size in binary units
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::format::format_bytes;
+///
+/// assert_eq!(format_bytes(512), "512 B");
+/// assert_eq!(format_bytes(1024 * 1024), "1.0 MiB");
+/// ```
+pub fn format_bytes(bytes: u64) -> String {
+    bytes_with(bytes, raw_numbers())
+}
+
+fn tokens_with(tokens: u64, raw: bool) -> String {
+    if raw || tokens < 1000 {
+        return tokens.to_string();
+    }
+    scaled(tokens as f64, 1000.0, &TOKEN_UNITS, "")
+}
+
+fn count_with(count: u64, raw: bool) -> String {
+    if raw {
+        return count.to_string();
+    }
+    group_thousands(&count.to_string())
+}
+
+fn cost_with(usd: f64, raw: bool) -> String {
+    let usd = if usd.is_finite() { usd.max(0.0) } else { 0.0 };
+    if raw {
+        return format!("{:.6}", usd);
+    }
+    if usd > 0.0 && usd < 0.005 {
+        return "<$0.01".to_string();
+    }
+    let cents = format!("{:.2}", usd);
+    let (dollars, cents) = cents.split_once('.').unwrap_or((&cents, "00"));
+    format!("${}.{}", group_thousands(dollars), cents)
+}
+
+fn duration_with(duration: Duration, raw: bool) -> String {
+    let millis = duration.as_millis();
+    if raw {
+        return format!("{}ms", millis);
+    }
+    if millis < 1000 {
+        return format!("{}ms", millis);
+    }
+    // Tenths of a second, rounded, so 59.96s shows as 1m 00s, not 60.0s
+    let tenths = (millis + 50) / 100;
+    if tenths < 600 {
+        return format!("{}.{}s", tenths / 10, tenths % 10);
+    }
+    let seconds = (millis + 500) / 1000;
+    if seconds < 3600 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        let minutes = (seconds + 30) / 60;
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+fn bytes_with(bytes: u64, raw: bool) -> String {
+    if raw || bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    scaled(bytes as f64, 1024.0, &BYTE_UNITS, " ")
+}
+
+/// Divides `value` by `base` until it fits the next unit, to one decimal
+///
+/// A value that would round up to `base` moves to the next unit, so
+/// 999,960 tokens show as `1.0M` rather than `1000.0k`.
+fn scaled(value: f64, base: f64, units: &[&str], separator: &str) -> String {
+    let mut value = value / base;
+    let mut unit = 0;
+    while (value * 10.0).round() / 10.0 >= base && unit + 1 < units.len() {
+        value /= base;
+        unit += 1;
+    }
+    format!("{:.1}{}{}", value, separator, units[unit])
+}
+
+/// Inserts a comma between every group of three digits
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_compact_from_one_thousand() {
+        assert_eq!(tokens_with(0, false), "0");
+        assert_eq!(tokens_with(999, false), "999");
+        assert_eq!(tokens_with(1000, false), "1.0k");
+        assert_eq!(tokens_with(12_345, false), "12.3k");
+        assert_eq!(tokens_with(999_949, false), "999.9k");
+        assert_eq!(tokens_with(999_950, false), "1.0M");
+        assert_eq!(tokens_with(1_234_567, false), "1.2M");
+    }
+
+    #[test]
+    fn test_count_groups_thousands() {
+        assert_eq!(count_with(999, false), "999");
+        assert_eq!(count_with(1000, false), "1,000");
+        assert_eq!(count_with(1_234_567, false), "1,234,567");
+    }
+
+    #[test]
+    fn test_cost_precision_and_sub_cent_amounts() {
+        assert_eq!(cost_with(0.0, false), "$0.00");
+        assert_eq!(cost_with(0.0001, false), "<$0.01");
+        assert_eq!(cost_with(0.0049, false), "<$0.01");
+        assert_eq!(cost_with(0.005, false), "$0.01");
+        assert_eq!(cost_with(0.0312, false), "$0.03");
+        assert_eq!(cost_with(1234.5, false), "$1,234.50");
+        assert_eq!(cost_with(-1.0, false), "$0.00");
+    }
+
+    #[test]
+    fn test_duration_units_by_magnitude() {
+        let ms = Duration::from_millis;
+        assert_eq!(duration_with(ms(0), false), "0ms");
+        assert_eq!(duration_with(ms(999), false), "999ms");
+        assert_eq!(duration_with(ms(1000), false), "1.0s");
+        assert_eq!(duration_with(ms(4_240), false), "4.2s");
+        assert_eq!(duration_with(ms(59_960), false), "1m 00s");
+        assert_eq!(duration_with(ms(102_000), false), "1m 42s");
+        assert_eq!(duration_with(ms(3_599_400), false), "59m 59s");
+        assert_eq!(duration_with(ms(3_600_000), false), "1h 00m");
+        assert_eq!(duration_with(ms(7_500_000), false), "2h 05m");
+    }
+
+    #[test]
+    fn test_bytes_binary_units() {
+        assert_eq!(bytes_with(0, false), "0 B");
+        assert_eq!(bytes_with(1023, false), "1023 B");
+        assert_eq!(bytes_with(1024, false), "1.0 KiB");
+        assert_eq!(bytes_with(1536, false), "1.5 KiB");
+        assert_eq!(bytes_with(1024 * 1024 - 1, false), "1.0 MiB");
+        assert_eq!(bytes_with(1024 * 1024, false), "1.0 MiB");
+        assert_eq!(bytes_with(5 * 1024 * 1024 * 1024, false), "5.0 GiB");
+    }
+
+    #[test]
+    fn test_raw_numbers_are_plain() {
+        assert_eq!(tokens_with(123_456, true), "123456");
+        assert_eq!(count_with(123_456, true), "123456");
+        assert_eq!(cost_with(0.0312, true), "0.031200");
+        assert_eq!(cost_with(0.0, true), "0.000000");
+        assert_eq!(duration_with(Duration::from_secs(102), true), "102000ms");
+        assert_eq!(bytes_with(1024 * 1024, true), "1048576 B");
+    }
+}
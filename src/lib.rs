@@ -11,6 +11,20 @@
 //! - `providers`: AI provider abstraction and implementations (Copilot, Ollama, OpenAI)
 //! - `tools`: File operations, terminal execution, and tool registry
 //! - `config`: Configuration management and validation
+//! - `credentials`: Keyring and encrypted file storage for tokens
+//! - `format`: Token, cost, duration, and size formatting for terminal output
+//! - `markdown`: Code block extraction from markdown text
+//! - `paths`: Data, cache, and state directory resolution
+//! - `read_only`: Read-only mode that refuses changes and commands
+//! - `semantic_index`: Embedding index for `@semantic` workspace search
+//! - `serve`: JSON-RPC server mode for editor integrations
+//! - `session_cwd`: Session working directory behind `--cwd` and `/cd`
+//! - `telemetry`: Structured JSONL telemetry events for agent runs
+//! - `testing`: Mock provider, mock tool, and in-memory storage for tests
+//!   (`testing` feature)
+//! - `trace_context`: W3C trace context propagation into agent execution spans
+//! - `tracing_setup`: Tracing subscriber setup and optional OTLP span export
+//! - `transcript`: Live markdown transcript of chat sessions
 //! - `error`: Error types and result aliases
 //! - `cli`: Command-line interface definition
 //!
@@ -35,15 +49,34 @@ pub mod chat_mode;
 pub mod cli;
 pub mod commands;
 pub mod config;
+pub mod credentials;
 pub mod error;
+pub mod format;
+pub mod markdown;
 pub mod mcp;
 pub mod mention_parser;
+pub mod network_policy;
+pub mod paths;
 pub mod prompts;
 pub mod providers;
+pub mod read_only;
+pub mod semantic_index;
+pub mod serve;
+pub mod session_cwd;
+pub mod shutdown;
 pub mod skills;
 pub mod storage;
+pub mod telemetry;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod tools;
+pub mod trace_context;
+pub mod tracing_setup;
+pub mod transcript;
+pub mod ui;
+pub mod untrusted_content;
 pub mod watcher;
+pub mod workspace_trust;
 pub mod xzepr;
 
 // Re-export commonly used types
@@ -52,8 +85,10 @@ pub use chat_mode::{ChatMode, SafetyMode};
 pub use config::Config;
 pub use error::{Result, XzatomaError};
 pub use mention_parser::{
-    augment_prompt_with_mentions, load_file_content, parse_mentions, FileMention, LoadError,
-    LoadErrorKind, Mention, MentionCache, MentionContent, SearchMention, UrlMention,
+    augment_prompt_with_mentions, load_file_content, load_image_attachment, parse_mentions,
+    parse_mentions_with_options, FileMention, ImageMention, LoadError, LoadErrorKind, Mention,
+    MentionCache, MentionCacheStats, MentionContent, MentionParseOptions, SearchMention, StripMode,
+    UrlMention,
 };
 pub use tools::{GrepTool, SearchMatch};
 
@@ -3,41 +3,188 @@
 #![doc = "XZatoma - Autonomous AI agent CLI"]
 #![doc = "Main entry point for the XZatoma agent application."]
 
-use xzatoma::error::Result;
-
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use xzatoma::error::{exit_codes, Result, XzatomaError};
 
 // Removed unused grouped imports to satisfy clippy
 
-use xzatoma::cli::{AcpCommand, Cli, Commands, ModelCommand, SkillsCommand};
+use xzatoma::cli::{AcpCommand, Cli, Commands, ModelCommand, RunCommand, SkillsCommand};
 use xzatoma::commands;
 
-use xzatoma::config::Config;
+use xzatoma::config::{Config, DEFAULT_CONFIG_PATH};
+use xzatoma::network_policy::NetworkPolicy;
+use xzatoma::session_cwd::SessionCwd;
+use xzatoma::tracing_setup::{init_tracing, TracingHandle};
+use xzatoma::ui::{self, ColorChoice};
+use xzatoma::watcher::reload::ReloadSource;
+use xzatoma::workspace_trust::{self, WorkspaceTrustStore};
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize tracing
-    init_tracing();
+use std::io::IsTerminal;
 
+#[tokio::main]
+async fn main() {
     // Parse command line arguments
     let cli = Cli::parse_args();
+    let verbose = cli.verbose;
+
+    // Log to stderr before anything else runs, so warnings about the
+    // configuration and workspace trust are not lost
+    let tracing_handle = match init_tracing() {
+        Ok(handle) => handle,
+        Err(error) => {
+            report_error(&error, verbose);
+            std::process::exit(error.exit_code());
+        }
+    };
+
+    xzatoma::format::set_raw_numbers(cli.raw_numbers);
+    match ColorChoice::parse(&cli.color) {
+        Ok(choice) => ui::init(choice, cli.command.json_output()),
+        Err(error) => {
+            report_error(&error, verbose);
+            std::process::exit(error.exit_code());
+        }
+    }
+
+    if let Err(error) = run(cli, tracing_handle).await {
+        report_error(&error, verbose);
+        std::process::exit(error.exit_code());
+    }
+}
+
+/// Resolve the working directory a command runs in.
+///
+/// `chat` and `run` start in `--cwd` when it is given; every other command
+/// runs in the launch directory. Workspace trust is evaluated for this
+/// directory, so it must be known before any configuration is loaded.
+fn session_cwd_for(command: &Commands) -> Result<SessionCwd> {
+    match command {
+        Commands::Chat {
+            cwd,
+            allow_cwd_escape,
+            ..
+        } => SessionCwd::from_launch_dir(cwd.as_deref(), *allow_cwd_escape),
+        Commands::Run {
+            cwd,
+            validate_only: false,
+            command: None,
+            ..
+        } => SessionCwd::from_launch_dir(cwd.as_deref(), true),
+        _ => SessionCwd::from_launch_dir(None, true),
+    }
+}
+
+/// Print a failed command's error to stderr.
+///
+/// By default only the concise user message and remediation hint are shown;
+/// `--verbose` adds the debug representation and the full source chain.
+fn report_error(error: &XzatomaError, verbose: bool) {
+    eprintln!("Error: {}", error.user_message());
+    if verbose {
+        eprintln!();
+        eprintln!("Details: {:?}", error);
+        let mut source = std::error::Error::source(error);
+        while let Some(cause) = source {
+            eprintln!("Caused by: {}", cause);
+            source = cause.source();
+        }
+    } else {
+        eprintln!("Run with --verbose for full error details.");
+    }
+}
+
+/// Dispatch the parsed command line to the matching command handler.
+async fn run(cli: Cli, tracing_handle: TracingHandle) -> Result<()> {
+    // Find the configuration file: `--config` or `config/config.yaml`, then
+    // the user configuration file
+    let requested_path = cli.config.as_deref().unwrap_or(DEFAULT_CONFIG_PATH);
+    let user_config = xzatoma::paths::user_config_file();
+    let located = Config::locate_file(requested_path, user_config.as_deref());
+    let mut config_path = located.as_ref().map_or_else(
+        || requested_path.to_string(),
+        |path| path.display().to_string(),
+    );
+
+    // Trust management runs before any project-local config is read
+    if let Commands::Trust { command } = cli.command {
+        return commands::trust::handle_trust(command, &config_path);
+    }
+
+    // Doctor loads the configuration itself so it can report why loading fails
+    if let Commands::Doctor { json, timeout } = cli.command {
+        let report = commands::doctor::run_doctor(
+            &config_path,
+            &cli,
+            json,
+            std::time::Duration::from_secs(timeout),
+        )
+        .await?;
+        if report.has_failures() {
+            std::process::exit(exit_codes::GENERAL);
+        }
+        return Ok(());
+    }
+
+    // First run: offer a guided setup instead of the defaults
+    if located.is_none() && commands::setup::offers_setup(&cli, std::io::stdin().is_terminal()) {
+        if let Some(path) = &user_config {
+            if commands::setup::first_run_setup(&cli, path).await? {
+                config_path = path.display().to_string();
+            }
+        }
+    }
+
+    // Commands that run the agent only load project-local files from
+    // trusted workspaces. Trust is evaluated for the directory the session
+    // runs in, which `--cwd` may move away from the launch directory.
+    let session_cwd = session_cwd_for(&cli.command)?;
+    let config_file = std::env::current_dir()?.join(&config_path);
+    let config = if workspace_trust::requires_trust(&cli.command) {
+        let mut store = WorkspaceTrustStore::load_default()?;
+        let trust = workspace_trust::ensure_trusted(
+            &mut store,
+            session_cwd.current(),
+            &config_file,
+            std::io::stdin().is_terminal(),
+        )?;
+        workspace_trust::load_config(&config_path, &cli, &trust)?
+    } else {
+        Config::load(&config_path, &cli)?
+    };
+
+    // Export spans now that the telemetry settings are known. The guard
+    // flushes exported spans when `run` returns.
+    let _tracing_guard =
+        tracing_handle.install_exporter(&config.telemetry, NetworkPolicy::from_config(&config))?;
 
     // If the user supplied a storage path on the CLI (or via env),
     // mirror it into XZATOMA_HISTORY_DB so the storage initializer can pick it up.
-    // This keeps callers unchanged while allowing `SqliteStorage::new()` to
+    // This keeps callers unchanged while allowing `SqliteStorage::new` to
     // honor an override.
     if let Some(db_path) = &cli.storage_path {
         std::env::set_var("XZATOMA_HISTORY_DB", db_path);
         tracing::info!("Using storage DB override from CLI: {}", db_path);
     }
 
-    // Load configuration
-    let config_path = cli.config.as_deref().unwrap_or("config/config.yaml");
-    let config = Config::load(config_path, &cli)?;
-
     // Validate configuration
     config.validate()?;
 
+    // Fetches and URL mentions share one SSRF policy and rate limiter
+    xzatoma::tools::fetch::configure_shared(&config.agent.tools);
+    xzatoma::untrusted_content::configure_shared(&config.agent.tools.untrusted_content);
+
+    // Copilot and MCP tokens are stored through the configured backend
+    xzatoma::credentials::configure(&config.credentials);
+
+    // The watcher hot-reloads its configuration the same way it was loaded
+    let watch_reload = matches!(cli.command, Commands::Watch { .. }).then(|| {
+        let (path, cli) = (config_path.clone(), cli.clone());
+        let working_dir = session_cwd.current().to_path_buf();
+        ReloadSource::new(&config_path, move || {
+            let store = WorkspaceTrustStore::load_default()?;
+            workspace_trust::reload_config(&store, &working_dir, &path, &cli)
+        })
+    });
+
     // Execute command
     match cli.command {
         Commands::Chat {
@@ -46,6 +193,16 @@ async fn main() -> Result<()> {
             safe,
             resume,
             thinking_effort,
+            // Resolved into `session_cwd` before the trust check
+            cwd: _,
+            allow_cwd_escape: _,
+            // Applied to `agent.chat.transcript_path` when the config loads
+            transcript: _,
+            // Applied to `storage.session_id` and `storage.if_exists` when the config loads
+            session_id: _,
+            if_exists: _,
+            // Applied to `agent.chat.banner` when the config loads
+            quiet: _,
         } => {
             tracing::info!("Starting interactive chat mode");
             if let Some(p) = &provider {
@@ -66,7 +223,25 @@ async fn main() -> Result<()> {
 
             // Delegate to the chat command handler
             // Moves `config` into the handler (match arms are exclusive)
-            commands::chat::run_chat(config, provider, mode, safe, resume, thinking_effort).await?;
+            commands::chat::run_chat(
+                config,
+                provider,
+                mode,
+                safe,
+                resume,
+                thinking_effort,
+                session_cwd,
+                &config_file,
+            )
+            .await?;
+            Ok(())
+        }
+        Commands::Run {
+            command: Some(RunCommand::Rollback { run_id, yes }),
+            ..
+        } => {
+            tracing::info!("Starting run rollback command");
+            commands::snapshot::handle_rollback(&config, &run_id, yes)?;
             Ok(())
         }
         Commands::Run {
@@ -74,7 +249,52 @@ async fn main() -> Result<()> {
             prompt,
             allow_dangerous,
             thinking_effort,
+            json,
+            validate_only,
+            dry_run,
+            // Resolved into `session_cwd` before the trust check
+            cwd: _,
+            confirm_dangerous,
+            // Applied to the config as agent.preflight.strict
+            strict_budget: _,
+            // Applied to the config as agent.recording.enabled
+            record: _,
+            // Applied to the config as run.postmortem
+            postmortem: _,
+            // Applied to the config as storage.session_id and storage.if_exists
+            session_id: _,
+            if_exists: _,
+            // Applied to the config as run.snapshot.enabled
+            snapshot: _,
+            command: None,
         } => {
+            if validate_only {
+                // `--validate-only` requires `--plan`, so the path is present
+                let plan_path = plan.unwrap_or_default();
+                return commands::run::validate_plan_file(&plan_path, json);
+            }
+            if dry_run {
+                // `--dry-run` requires `--plan` as well
+                let plan_path = plan.unwrap_or_default();
+                return commands::run::dry_run_plan(
+                    &config,
+                    &plan_path,
+                    allow_dangerous,
+                    session_cwd.current(),
+                    json,
+                );
+            }
+            if let Some(plan_path) = &plan {
+                commands::run::confirm_dangerous_plan(
+                    &config,
+                    plan_path,
+                    allow_dangerous,
+                    confirm_dangerous.as_deref(),
+                    session_cwd.current(),
+                    std::io::stdin().is_terminal(),
+                )?;
+            }
+
             tracing::info!("Starting plan execution mode");
             if let Some(plan_path) = &plan {
                 tracing::debug!("Loading plan from: {}", plan_path.display());
@@ -91,12 +311,14 @@ async fn main() -> Result<()> {
 
             // Convert plan PathBuf to String before passing it to the command handler.
             let plan_str = plan.map(|p| p.to_string_lossy().to_string());
-            commands::run::run_plan_with_options(
+            commands::run::run_plan_with_output(
                 config,
                 plan_str,
                 prompt,
                 allow_dangerous,
                 thinking_effort,
+                json,
+                session_cwd.current(),
             )
             .await?;
             Ok(())
@@ -136,6 +358,8 @@ async fn main() -> Result<()> {
                     brokers,
                     match_version,
                 },
+                watch_reload,
+                &tracing_handle,
             )
             .await?;
             Ok(())
@@ -184,7 +408,7 @@ async fn main() -> Result<()> {
         }
         Commands::History { command } => {
             tracing::info!("Starting history command");
-            commands::history::handle_history(command)?;
+            commands::history::handle_history(&config, command)?;
             Ok(())
         }
         Commands::Replay {
@@ -194,6 +418,11 @@ async fn main() -> Result<()> {
             limit,
             offset,
             tree,
+            conversation_id,
+            provider,
+            model,
+            diff,
+            allow_tools,
         } => {
             tracing::info!("Starting replay command for conversation debugging");
             let args = commands::replay::ReplayArgs {
@@ -203,8 +432,18 @@ async fn main() -> Result<()> {
                 limit,
                 offset,
                 tree,
+                conversation_id,
+                provider,
+                model,
+                diff,
+                allow_tools,
             };
-            commands::replay::run_replay(args).await?;
+            commands::replay::run_replay(args, &config).await?;
+            Ok(())
+        }
+        Commands::Debug { run_id } => {
+            tracing::info!("Starting debug inspector for run {}", run_id);
+            commands::debug::run_debug(&config, &run_id)?;
             Ok(())
         }
         Commands::Mcp { command } => {
@@ -222,6 +461,10 @@ async fn main() -> Result<()> {
                 .await?;
             Ok(())
         }
+        Commands::Serve { stdio, working_dir } => {
+            commands::serve::handle_serve(stdio, working_dir, config).await?;
+            Ok(())
+        }
         Commands::Acp { command } => {
             tracing::info!("Starting ACP command");
             match &command {
@@ -259,16 +502,42 @@ async fn main() -> Result<()> {
                 }
             }
         }
+        Commands::Audit { command } => {
+            tracing::info!("Starting audit command");
+            commands::audit::handle_audit(&config, command)?;
+            Ok(())
+        }
+        Commands::Plan { command } => {
+            tracing::info!("Starting plan command");
+            commands::plan::handle_plan(config, command).await?;
+            Ok(())
+        }
+        Commands::Cache { command } => {
+            tracing::info!("Starting cache command");
+            commands::cache::handle_cache(&config, command)?;
+            Ok(())
+        }
+        Commands::Index { command } => {
+            tracing::info!("Starting index command");
+            commands::index::handle_index(&config, command).await?;
+            Ok(())
+        }
+        Commands::ToolOutput { command } => {
+            tracing::info!("Starting tool-output command");
+            commands::tool_output::handle_tool_output(&config, command)?;
+            Ok(())
+        }
+        Commands::Usage { month, by, json } => {
+            tracing::info!("Starting usage command");
+            commands::usage::handle_usage(&config, month, &by, json)?;
+            Ok(())
+        }
+        Commands::Paths => {
+            tracing::info!("Starting paths command");
+            commands::paths::handle_paths(&config)?;
+            Ok(())
+        }
+        // Handled before the configuration is loaded
+        Commands::Trust { .. } | Commands::Doctor { .. } => Ok(()),
     }
 }
-
-/// Initialize tracing subscriber with environment filter
-fn init_tracing() {
-    let env_filter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("xzatoma=info"));
-
-    tracing_subscriber::registry()
-        .with(env_filter)
-        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
-        .init();
-}
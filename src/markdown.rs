@@ -0,0 +1,946 @@
+//! Code blocks in markdown text
+//!
+//! Chat quick actions, plan drafting, and the markdown plan parser all need
+//! the code blocks of a markdown document. [`extract_code_blocks`] finds them
+//! following the CommonMark rules that matter for model output:
+//!
+//! - fences of three or more backticks or tildes, closed by a fence of the
+//!   same character that is at least as long and has no info string;
+//! - longer fences that contain shorter ones, and tilde fences that contain
+//!   backtick fences;
+//! - fences inside block quotes, which the end of the quote closes;
+//! - code indented by four spaces, unless [`ExtractOptions::fenced_only`]
+//!   is used;
+//! - a fence that is never closed runs to the end of the document.
+//!
+//! [`suggested_filename`] maps a block back to a file named in the sentence
+//! before it, such as "Create `src/foo.rs`:". It only answers when that
+//! sentence names exactly one plausible path.
+
+use std::ops::Range;
+
+/// Columns of indentation that make a line indented code
+const CODE_INDENT: usize = 4;
+
+/// File names without an extension that are still recognized as files
+const KNOWN_FILE_NAMES: &[&str] = &[
+    "Makefile",
+    "Dockerfile",
+    "Containerfile",
+    "Justfile",
+    "Rakefile",
+    "Gemfile",
+    "Procfile",
+    "Jenkinsfile",
+];
+
+/// Extensions of each fence language, used to reject a suggested file that
+/// does not match the block's language
+const LANGUAGE_EXTENSIONS: &[(&[&str], &[&str])] = &[
+    (&["rust", "rs"], &["rs"]),
+    (&["python", "py"], &["py", "pyi"]),
+    (&["javascript", "js", "jsx"], &["js", "mjs", "cjs", "jsx"]),
+    (&["typescript", "ts", "tsx"], &["ts", "mts", "cts", "tsx"]),
+    (&["go", "golang"], &["go"]),
+    (&["java"], &["java"]),
+    (&["kotlin", "kt"], &["kt", "kts"]),
+    (&["c"], &["c", "h"]),
+    (&["cpp", "c++"], &["cpp", "cc", "cxx", "hpp", "hh", "h"]),
+    (&["csharp", "cs"], &["cs"]),
+    (&["ruby", "rb"], &["rb"]),
+    (&["php"], &["php"]),
+    (&["swift"], &["swift"]),
+    (&["toml"], &["toml"]),
+    (&["yaml", "yml"], &["yaml", "yml"]),
+    (&["json"], &["json"]),
+    (&["html"], &["html", "htm"]),
+    (&["css"], &["css"]),
+    (&["scss"], &["scss"]),
+    (&["sql"], &["sql"]),
+    (&["markdown", "md"], &["md", "markdown"]),
+];
+
+/// Extensions accepted for a suggested file without a directory
+const COMMON_EXTENSIONS: &[&str] = &[
+    "txt",
+    "ini",
+    "cfg",
+    "conf",
+    "env",
+    "lock",
+    "xml",
+    "proto",
+    "sh",
+    "bash",
+    "zsh",
+    "ps1",
+    "gradle",
+    "properties",
+    "csv",
+    "lua",
+    "vue",
+    "svelte",
+    "tf",
+    "dart",
+    "scala",
+    "ex",
+    "exs",
+];
+
+/// One code block of a markdown document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// Language from the first word of the fence's info string, if any
+    pub language: Option<String>,
+    /// The whole info string, empty for indented code
+    pub info: String,
+    /// The code, ending in a newline, or empty for a block without code
+    pub content: String,
+    /// Byte range of the block in the document, fences included
+    pub span: Range<usize>,
+}
+
+impl CodeBlock {
+    /// Returns true when the block holds no code
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    /// Number of lines in the block
+    pub fn line_count(&self) -> usize {
+        self.content.lines().count()
+    }
+
+    /// Short description such as `rust, 12 lines`
+    pub fn label(&self) -> String {
+        let lines = self.line_count();
+        format!(
+            "{}, {} line{}",
+            self.language.as_deref().unwrap_or("text"),
+            lines,
+            if lines == 1 { "" } else { "s" }
+        )
+    }
+}
+
+/// Which blocks [`extract_code_blocks_with`] returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractOptions {
+    /// Include code indented by four spaces (default: true)
+    pub indented: bool,
+}
+
+impl ExtractOptions {
+    /// Only fenced blocks; indented lines are left as text
+    pub fn fenced_only() -> Self {
+        Self { indented: false }
+    }
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self { indented: true }
+    }
+}
+
+/// Finds the fenced and indented code blocks of `text`, in order
+///
+/// Blocks without code are included, with empty content.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::markdown::extract_code_blocks;
+///
+/// let text = "Add this:\n\n```rust title=\"main\"\nfn main() {}\n```\n\n> ~~~toml\n> [package]\n> ~~~\n";
+/// let blocks = extract_code_blocks(text);
+/// assert_eq!(blocks.len(), 2);
+/// assert_eq!(blocks[0].language.as_deref(), Some("rust"));
+/// assert_eq!(blocks[0].info, "rust title=\"main\"");
+/// assert_eq!(&text[blocks[0].span.clone()], "```rust title=\"main\"\nfn main() {}\n```\n");
+/// assert_eq!(blocks[1].content, "[package]\n");
+/// ```
+pub fn extract_code_blocks(text: &str) -> Vec<CodeBlock> {
+    extract_code_blocks_with(text, ExtractOptions::default())
+}
+
+/// Finds the code blocks of `text` selected by `options`, in order
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::markdown::{extract_code_blocks_with, ExtractOptions};
+///
+/// let text = "Run:\n\n    cargo test\n\n```sh\nmake\n```\n";
+/// let blocks = extract_code_blocks_with(text, ExtractOptions::fenced_only());
+/// assert_eq!(blocks.len(), 1);
+/// assert_eq!(blocks[0].content, "make\n");
+/// ```
+pub fn extract_code_blocks_with(text: &str, options: ExtractOptions) -> Vec<CodeBlock> {
+    let mut parser = BlockParser::new(options);
+    let mut offset = 0;
+    for raw in text.split_inclusive('\n') {
+        let start = offset;
+        offset += raw.len();
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        parser.line(line.strip_suffix('\r').unwrap_or(line), start, offset);
+    }
+    parser.finish(text.len())
+}
+
+/// Returns the file the prose just before `block` says it belongs in
+///
+/// The last non-blank line before the block must end with a colon, as in
+/// "Create `src/foo.rs`:" or "**Cargo.toml**:", or consist of the path
+/// alone, optionally as a heading. Paths in inline code are preferred;
+/// without inline code the last word before the colon is used. `None` is
+/// returned when the line names no path or more than one, when the path is
+/// absolute or leaves the directory, when a bare file name has an unfamiliar
+/// extension, or when the extension does not match the block's language.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::markdown::{extract_code_blocks, suggested_filename};
+///
+/// let text = "Create `src/lib.rs`:\n\n```rust\npub fn f() {}\n```\n\nThen run:\n\n```sh\ncargo test\n```\n";
+/// let blocks = extract_code_blocks(text);
+/// assert_eq!(suggested_filename(text, &blocks[0]).as_deref(), Some("src/lib.rs"));
+/// assert_eq!(suggested_filename(text, &blocks[1]), None);
+/// ```
+pub fn suggested_filename(text: &str, block: &CodeBlock) -> Option<String> {
+    let before = text.get(..block.span.start)?;
+    let line = before.lines().rev().find(|line| !line.trim().is_empty())?;
+    let (_, line) = strip_quotes(line, usize::MAX);
+    let line = line.trim();
+    let line = line.trim_start_matches('#').trim();
+    let line = match line.strip_prefix(['-', '*', '+']) {
+        Some(rest) if rest.starts_with(' ') => rest.trim_start(),
+        _ => line,
+    };
+
+    let sentence = match line.trim_end().strip_suffix(':') {
+        Some(sentence) => sentence,
+        None if !line.contains(' ') => line,
+        None => return None,
+    };
+    let sentence = sentence.trim_end().trim_end_matches(['*', '_']);
+
+    let spans = inline_code_spans(sentence);
+    let candidate = if spans.is_empty() {
+        sentence
+            .split_whitespace()
+            .last()?
+            .trim_matches(['*', '_', '"', '\''])
+    } else {
+        let mut paths: Vec<&str> = spans.into_iter().filter(|span| is_path(span)).collect();
+        paths.dedup();
+        match paths.as_slice() {
+            [path] => *path,
+            _ => return None,
+        }
+    };
+    if !is_path(candidate) {
+        return None;
+    }
+    // A bare name such as `self.value` is only a file with a familiar extension
+    if !candidate.contains('/') && extension(candidate).is_some_and(|ext| !is_known_extension(ext))
+    {
+        return None;
+    }
+    if !matches_language(candidate, block.language.as_deref()) {
+        return None;
+    }
+    Some(candidate.to_string())
+}
+
+/// A fence line: its indentation, marker character, length, and info string
+struct Fence<'a> {
+    indent: usize,
+    marker: char,
+    len: usize,
+    info: &'a str,
+}
+
+impl<'a> Fence<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let (indent, start) = leading_columns(line);
+        let rest = &line[start..];
+        let marker = rest.chars().next().filter(|c| *c == '`' || *c == '~')?;
+        let len = rest.chars().take_while(|c| *c == marker).count();
+        if len < 3 {
+            return None;
+        }
+        let info = rest[len..].trim();
+        // A backtick in the info string makes the line inline code
+        if marker == '`' && info.contains('`') {
+            return None;
+        }
+        Some(Self {
+            indent,
+            marker,
+            len,
+            info,
+        })
+    }
+}
+
+/// A fenced block being read
+struct OpenFence {
+    start: usize,
+    quote_depth: usize,
+    indent: usize,
+    marker: char,
+    len: usize,
+    info: String,
+    lines: Vec<String>,
+}
+
+impl OpenFence {
+    /// Returns true when `line` closes this fence
+    fn closed_by(&self, line: &str) -> bool {
+        Fence::parse(line).is_some_and(|fence| {
+            fence.marker == self.marker
+                && fence.len >= self.len
+                && fence.info.is_empty()
+                && fence.indent <= self.indent + 3
+        })
+    }
+}
+
+/// An indented code block being read
+struct IndentedBlock {
+    start: usize,
+    /// End of the last line with code; trailing blank lines are not part of
+    /// the block
+    end: usize,
+    quote_depth: usize,
+    lines: Vec<String>,
+}
+
+/// Line-by-line code block parser
+struct BlockParser {
+    options: ExtractOptions,
+    blocks: Vec<CodeBlock>,
+    fence: Option<OpenFence>,
+    indented: Option<IndentedBlock>,
+    previous_blank: bool,
+    in_list: bool,
+}
+
+impl BlockParser {
+    fn new(options: ExtractOptions) -> Self {
+        Self {
+            options,
+            blocks: Vec::new(),
+            fence: None,
+            indented: None,
+            previous_blank: true,
+            in_list: false,
+        }
+    }
+
+    /// Reads one line without its line ending; `start..end` is its byte
+    /// range in the document, line ending included
+    fn line(&mut self, raw: &str, start: usize, end: usize) {
+        if let Some(fence) = &mut self.fence {
+            let (depth, line) = strip_quotes(raw, fence.quote_depth);
+            if depth == fence.quote_depth {
+                if fence.closed_by(line) {
+                    self.close_fence(end);
+                    self.previous_blank = false;
+                } else {
+                    fence
+                        .lines
+                        .push(strip_columns(line, fence.indent).to_string());
+                }
+                return;
+            }
+            // The block quote holding the fence ended
+            self.close_fence(start);
+        }
+
+        let (depth, line) = strip_quotes(raw, usize::MAX);
+        let blank = line.trim().is_empty();
+        let (indent, _) = leading_columns(line);
+
+        if let Some(block) = &mut self.indented {
+            if block.quote_depth == depth && (blank || indent >= CODE_INDENT) {
+                block
+                    .lines
+                    .push(strip_columns(line, CODE_INDENT).to_string());
+                if !blank {
+                    block.end = end;
+                }
+                self.previous_blank = blank;
+                return;
+            }
+            self.close_indented();
+        }
+
+        if let Some(fence) = Fence::parse(line) {
+            if fence.indent < CODE_INDENT || self.in_list {
+                self.fence = Some(OpenFence {
+                    start,
+                    quote_depth: depth,
+                    indent: fence.indent,
+                    marker: fence.marker,
+                    len: fence.len,
+                    info: fence.info.to_string(),
+                    lines: Vec::new(),
+                });
+                self.previous_blank = false;
+                return;
+            }
+        }
+
+        // Indented code cannot interrupt a paragraph or continue a list item
+        if self.options.indented
+            && !blank
+            && indent >= CODE_INDENT
+            && self.previous_blank
+            && !self.in_list
+        {
+            self.indented = Some(IndentedBlock {
+                start,
+                end,
+                quote_depth: depth,
+                lines: vec![strip_columns(line, CODE_INDENT).to_string()],
+            });
+            self.previous_blank = false;
+            return;
+        }
+
+        if !blank && indent < CODE_INDENT {
+            if is_list_item(line.trim_start()) {
+                self.in_list = true;
+            } else if indent == 0 && self.previous_blank {
+                self.in_list = false;
+            }
+        }
+        self.previous_blank = blank;
+    }
+
+    fn close_fence(&mut self, end: usize) {
+        if let Some(fence) = self.fence.take() {
+            self.blocks.push(CodeBlock {
+                language: language_of(&fence.info),
+                info: fence.info,
+                content: join_lines(fence.lines),
+                span: fence.start..end,
+            });
+        }
+    }
+
+    fn close_indented(&mut self) {
+        if let Some(block) = self.indented.take() {
+            self.blocks.push(CodeBlock {
+                language: None,
+                info: String::new(),
+                content: join_lines(block.lines),
+                span: block.start..block.end,
+            });
+        }
+    }
+
+    fn finish(mut self, len: usize) -> Vec<CodeBlock> {
+        self.close_fence(len);
+        self.close_indented();
+        self.blocks
+    }
+}
+
+/// Joins code lines, dropping trailing blank lines
+fn join_lines(mut lines: Vec<String>) -> String {
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    if lines.is_empty() {
+        return String::new();
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Language of an info string: its first word, without the attribute
+/// syntax of `{.rust}` or `rust,ignore`
+fn language_of(info: &str) -> Option<String> {
+    let word = info.split_whitespace().next()?;
+    let word = word.trim_start_matches('{').trim_start_matches('.');
+    let word = word.split([',', '}']).next().unwrap_or_default();
+    (!word.is_empty()).then(|| word.to_string())
+}
+
+/// Removes up to `max` block quote markers and returns how many were removed
+fn strip_quotes(line: &str, max: usize) -> (usize, &str) {
+    let mut rest = line;
+    let mut depth = 0;
+    while depth < max {
+        let trimmed = rest.trim_start_matches(' ');
+        if rest.len() - trimmed.len() > 3 {
+            break;
+        }
+        match trimmed.strip_prefix('>') {
+            Some(after) => {
+                rest = after.strip_prefix(' ').unwrap_or(after);
+                depth += 1;
+            }
+            None => break,
+        }
+    }
+    (depth, rest)
+}
+
+/// Returns the indentation of `line` in columns, with tabs to the next
+/// multiple of four, and the byte offset of its first other character
+fn leading_columns(line: &str) -> (usize, usize) {
+    let mut columns = 0;
+    for (offset, c) in line.char_indices() {
+        match c {
+            ' ' => columns += 1,
+            '\t' => columns += CODE_INDENT - columns % CODE_INDENT,
+            _ => return (columns, offset),
+        }
+    }
+    (columns, line.len())
+}
+
+/// Removes up to `columns` columns of indentation
+fn strip_columns(line: &str, columns: usize) -> &str {
+    let mut width = 0;
+    for (offset, c) in line.char_indices() {
+        if width >= columns {
+            return &line[offset..];
+        }
+        match c {
+            ' ' => width += 1,
+            '\t' => width += CODE_INDENT - width % CODE_INDENT,
+            _ => return &line[offset..],
+        }
+    }
+    ""
+}
+
+fn is_list_item(line: &str) -> bool {
+    if let Some(rest) = line.strip_prefix(['-', '*', '+']) {
+        return rest.is_empty() || rest.starts_with(' ');
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    (1..=9).contains(&digits)
+        && line[digits..]
+            .strip_prefix(['.', ')'])
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+}
+
+/// Contents of the single-backtick inline code spans of `line`
+fn inline_code_spans(line: &str) -> Vec<&str> {
+    let mut spans = Vec::new();
+    let mut rest = line;
+    while let Some(open) = rest.find('`') {
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('`') else {
+            break;
+        };
+        spans.push(after[..close].trim());
+        rest = &after[close + 1..];
+    }
+    spans
+}
+
+/// Returns true for a relative file path made of ordinary characters
+fn is_path(candidate: &str) -> bool {
+    let ordinary = candidate
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/' | '+' | '@'));
+    if candidate.is_empty()
+        || candidate.len() > 200
+        || !ordinary
+        || (candidate.starts_with(['/', '-', '.'])
+            && !candidate.starts_with("./")
+            && !is_dotfile(candidate))
+        || candidate.ends_with(['/', '.'])
+        || candidate
+            .split('/')
+            .any(|part| part.is_empty() || part == "..")
+    {
+        return false;
+    }
+    let name = candidate.rsplit('/').next().unwrap_or(candidate);
+    KNOWN_FILE_NAMES.contains(&name) || is_dotfile(name) || extension(name).is_some()
+}
+
+/// Returns true for a name such as `.gitignore` or `.env`
+fn is_dotfile(name: &str) -> bool {
+    name.strip_prefix('.').is_some_and(|rest| {
+        !rest.is_empty()
+            && !rest.contains(['.', '/'])
+            && rest
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    })
+}
+
+/// Extension of the file name in `path`, if it contains a letter
+fn extension(path: &str) -> Option<&str> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let (stem, extension) = name.rsplit_once('.')?;
+    (!stem.is_empty()
+        && (1..=10).contains(&extension.len())
+        && extension.chars().any(|c| c.is_ascii_alphabetic()))
+    .then_some(extension)
+}
+
+fn is_known_extension(extension: &str) -> bool {
+    let extension = extension.to_ascii_lowercase();
+    COMMON_EXTENSIONS.contains(&extension.as_str())
+        || LANGUAGE_EXTENSIONS
+            .iter()
+            .any(|(_, extensions)| extensions.contains(&extension.as_str()))
+}
+
+/// Returns false when both the block's language and the path's extension
+/// are known and they do not belong together
+fn matches_language(path: &str, language: Option<&str>) -> bool {
+    let (Some(language), Some(extension)) = (language, extension(path)) else {
+        return true;
+    };
+    let language = language.to_ascii_lowercase();
+    let extension = extension.to_ascii_lowercase();
+    let Some((_, expected)) = LANGUAGE_EXTENSIONS
+        .iter()
+        .find(|(names, _)| names.contains(&language.as_str()))
+    else {
+        return true;
+    };
+    let known = LANGUAGE_EXTENSIONS
+        .iter()
+        .any(|(_, extensions)| extensions.contains(&extension.as_str()));
+    !known || expected.contains(&extension.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (language, content) of each block
+    fn summary(text: &str, options: ExtractOptions) -> Vec<(Option<String>, String)> {
+        extract_code_blocks_with(text, options)
+            .into_iter()
+            .map(|block| (block.language, block.content))
+            .collect()
+    }
+
+    #[test]
+    fn test_extract_code_blocks_cases() {
+        let cases: &[(&str, &str, &[(Option<&str>, &str)])] = &[
+            ("no code", "Just prose.\nMore prose.\n", &[]),
+            (
+                "backtick fence with language",
+                "Intro\n```rust\nfn main() {}\n```\n",
+                &[(Some("rust"), "fn main() {}\n")],
+            ),
+            ("tilde fence", "~~~\nplain\n~~~\n", &[(None, "plain\n")]),
+            (
+                "longer closing fence",
+                "```\ncode\n`````\nafter\n",
+                &[(None, "code\n")],
+            ),
+            (
+                "shorter closing fence is content",
+                "````\n```\ncode\n```\n````\n",
+                &[(None, "```\ncode\n```\n")],
+            ),
+            (
+                "nested fences in a markdown block",
+                "````markdown\nExample:\n```rust\nlet x = 1;\n```\n````\n",
+                &[(Some("markdown"), "Example:\n```rust\nlet x = 1;\n```\n")],
+            ),
+            (
+                "tilde fence holds backtick fences",
+                "~~~md\n```\ninner\n```\n~~~\n",
+                &[(Some("md"), "```\ninner\n```\n")],
+            ),
+            (
+                "other marker does not close",
+                "```\n~~~\n```\n",
+                &[(None, "~~~\n")],
+            ),
+            (
+                "fence with info string does not close",
+                "```\n```rust\n```\n",
+                &[(None, "```rust\n")],
+            ),
+            (
+                "unterminated fence runs to the end",
+                "Run:\n```sh\nmake\nmake test",
+                &[(Some("sh"), "make\nmake test\n")],
+            ),
+            (
+                "unterminated fence after a closed one",
+                "```a\n1\n```\n```b\n2\n",
+                &[(Some("a"), "1\n"), (Some("b"), "2\n")],
+            ),
+            (
+                "info string with attributes",
+                "```rust title=\"main.rs\" linenos\nfn main() {}\n```\n",
+                &[(Some("rust"), "fn main() {}\n")],
+            ),
+            (
+                "comma attributes",
+                "```rust,ignore\nlet x;\n```\n",
+                &[(Some("rust"), "let x;\n")],
+            ),
+            (
+                "brace attributes",
+                "```{.python .numberLines}\nprint(1)\n```\n",
+                &[(Some("python"), "print(1)\n")],
+            ),
+            (
+                "tilde fence may have backticks in the info string",
+                "~~~ `odd`\nx\n~~~\n",
+                &[(Some("`odd`"), "x\n")],
+            ),
+            (
+                "inline triple backticks are not a fence",
+                "Use ```let x = 1``` inline\n",
+                &[],
+            ),
+            ("two backticks are not a fence", "``\nnot code\n``\n", &[]),
+            (
+                "empty fence is kept without content",
+                "```\n\n```\n",
+                &[(None, "")],
+            ),
+            (
+                "fence indented up to three spaces strips its indent",
+                "   ```\n   a\n     b\n   ```\n",
+                &[(None, "a\n  b\n")],
+            ),
+            (
+                "fence in a block quote",
+                "> ```js\n> let x;\n> ```\n",
+                &[(Some("js"), "let x;\n")],
+            ),
+            (
+                "end of a block quote closes its fence",
+                "> ```py\n> print(1)\nOutside\n```\nx\n",
+                &[(Some("py"), "print(1)\n"), (None, "x\n")],
+            ),
+            (
+                "quote markers inside a quoted fence are content",
+                "> ````md\n> > quoted\n> ````\n",
+                &[(Some("md"), "> quoted\n")],
+            ),
+            (
+                "indented code after a blank line",
+                "Run:\n\n    cargo build\n\n    cargo test\nDone.\n",
+                &[(None, "cargo build\n\ncargo test\n")],
+            ),
+            (
+                "indented lines cannot interrupt a paragraph",
+                "Done.\n    not code\n",
+                &[],
+            ),
+            (
+                "indented lines continue a list item",
+                "- step\n\n    more of the step\n",
+                &[],
+            ),
+            (
+                "fence under a list item",
+                "- step\n\n  ```toml\n  [x]\n  ```\n",
+                &[(Some("toml"), "[x]\n")],
+            ),
+            (
+                "tab indentation",
+                "Text\n\n\tindented\n",
+                &[(None, "indented\n")],
+            ),
+            (
+                "windows line endings",
+                "```sh\r\nls\r\n```\r\n",
+                &[(Some("sh"), "ls\n")],
+            ),
+        ];
+
+        for (name, text, expected) in cases {
+            let expected: Vec<(Option<String>, String)> = expected
+                .iter()
+                .map(|(language, content)| (language.map(str::to_string), content.to_string()))
+                .collect();
+            assert_eq!(
+                summary(text, ExtractOptions::default()),
+                expected,
+                "case: {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_fenced_only_leaves_indented_code_as_text() {
+        let text = "Text\n\n    indented\n\n```\nfenced\n```\n";
+        assert_eq!(summary(text, ExtractOptions::default()).len(), 2);
+        assert_eq!(
+            summary(text, ExtractOptions::fenced_only()),
+            vec![(None, "fenced\n".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_spans_cover_the_fences() {
+        let text = "a\n```rust x\ncode\n```\nb\n\n    indented\n\nc\n> ```\n> q\nend";
+        let blocks = extract_code_blocks(text);
+        let spans: Vec<&str> = blocks
+            .iter()
+            .map(|block| &text[block.span.clone()])
+            .collect();
+        assert_eq!(
+            spans,
+            ["```rust x\ncode\n```\n", "    indented\n", "> ```\n> q\n"]
+        );
+        assert_eq!(blocks[0].info, "rust x");
+        assert_eq!(blocks[1].info, "");
+
+        let unterminated = "x\n```\nopen";
+        let block = &extract_code_blocks(unterminated)[0];
+        assert_eq!(block.span, 2..unterminated.len());
+    }
+
+    #[test]
+    fn test_block_labels() {
+        let blocks = extract_code_blocks("```rust\na\nb\n```\n```\nc\n```\n```\n```\n");
+        assert_eq!(blocks[0].label(), "rust, 2 lines");
+        assert_eq!(blocks[1].label(), "text, 1 line");
+        assert!(blocks[2].is_empty());
+        assert_eq!(blocks[2].line_count(), 0);
+    }
+
+    #[test]
+    fn test_suggested_filename_cases() {
+        let cases: &[(&str, &str, Option<&str>)] = &[
+            (
+                "inline code before a colon",
+                "Create `src/foo.rs`:\n\n```rust\nx\n```\n",
+                Some("src/foo.rs"),
+            ),
+            (
+                "no blank line",
+                "Update `Cargo.toml`:\n```toml\nx\n```\n",
+                Some("Cargo.toml"),
+            ),
+            (
+                "bold path",
+                "**src/main.py**:\n```python\nx\n```\n",
+                Some("src/main.py"),
+            ),
+            (
+                "bare word before a colon",
+                "Add this to src/lib.rs:\n```\nx\n```\n",
+                Some("src/lib.rs"),
+            ),
+            (
+                "heading with a path",
+                "### `web/app.ts`\n\n```ts\nx\n```\n",
+                Some("web/app.ts"),
+            ),
+            (
+                "path alone on its line",
+                "`Makefile`\n```\nx\n```\n",
+                Some("Makefile"),
+            ),
+            (
+                "dotfile",
+                "Write `.gitignore`:\n```\ntarget\n```\n",
+                Some(".gitignore"),
+            ),
+            (
+                "list item",
+                "- `config/app.yaml`:\n\n  ```yaml\n  x\n  ```\n",
+                Some("config/app.yaml"),
+            ),
+            (
+                "block quote",
+                "> Save as `notes.md`:\n> ```\n> x\n> ```\n",
+                Some("notes.md"),
+            ),
+            (
+                "no colon",
+                "This is `src/foo.rs` and more\n```rust\nx\n```\n",
+                None,
+            ),
+            (
+                "generic prose",
+                "Here is the code:\n```rust\nx\n```\n",
+                None,
+            ),
+            (
+                "two paths",
+                "Rename `a.rs` to `b.rs`:\n```rust\nx\n```\n",
+                None,
+            ),
+            (
+                "one path among other inline code",
+                "Put `fn main` in `src/main.rs`:\n```rust\nx\n```\n",
+                Some("src/main.rs"),
+            ),
+            (
+                "absolute path",
+                "Edit `/etc/hosts.conf`:\n```\nx\n```\n",
+                None,
+            ),
+            (
+                "parent directory",
+                "Edit `../secrets.env`:\n```\nx\n```\n",
+                None,
+            ),
+            (
+                "url",
+                "From `https://example.com/a.js`:\n```js\nx\n```\n",
+                None,
+            ),
+            (
+                "method call",
+                "Call `self.value()`:\n```rust\nx\n```\n",
+                None,
+            ),
+            ("version number", "Bump to `1.2.3`:\n```\nx\n```\n", None),
+            (
+                "unfamiliar bare extension",
+                "Use `self.value`:\n```rust\nx\n```\n",
+                None,
+            ),
+            (
+                "unfamiliar extension with a directory",
+                "Create `assets/logo.svgz`:\n```\nx\n```\n",
+                Some("assets/logo.svgz"),
+            ),
+            (
+                "language mismatch",
+                "Create `main.py`:\n```rust\nx\n```\n",
+                None,
+            ),
+            (
+                "unknown language",
+                "Create `main.py`:\n```diff\nx\n```\n",
+                Some("main.py"),
+            ),
+            (
+                "previous block's fence",
+                "Create `a.rs`:\n```rust\nx\n```\n```rust\ny\n```\n",
+                None,
+            ),
+            ("start of the document", "```rust\nx\n```\n", None),
+        ];
+
+        for (name, text, expected) in cases {
+            let blocks = extract_code_blocks(text);
+            let block = blocks.last().unwrap();
+            assert_eq!(
+                suggested_filename(text, block).as_deref(),
+                *expected,
+                "case: {}",
+                name
+            );
+        }
+    }
+}
@@ -27,7 +27,7 @@
 //!
 //! # async fn example() -> anyhow::Result<()> {
 //! let http = Arc::new(reqwest::Client::new());
-//! let token_store = Arc::new(TokenStore);
+//! let token_store = Arc::new(TokenStore::new());
 //! let mut manager = AuthManager::new(http, token_store);
 //!
 //! manager.add_server(
@@ -80,7 +80,7 @@ use crate::mcp::auth::token_store::{OAuthToken, TokenStore};
 /// use xzatoma::mcp::auth::token_store::TokenStore;
 ///
 /// let http = Arc::new(reqwest::Client::new());
-/// let token_store = Arc::new(TokenStore);
+/// let token_store = Arc::new(TokenStore::new());
 /// let manager = AuthManager::new(http, token_store);
 /// ```
 pub struct AuthManager {
@@ -114,7 +114,7 @@ impl AuthManager {
     ///
     /// let manager = AuthManager::new(
     ///     Arc::new(reqwest::Client::new()),
-    ///     Arc::new(TokenStore),
+    ///     Arc::new(TokenStore::new()),
     /// );
     /// ```
     pub fn new(http: Arc<reqwest::Client>, token_store: Arc<TokenStore>) -> Self {
@@ -145,7 +145,7 @@ impl AuthManager {
     ///
     /// let mut manager = AuthManager::new(
     ///     Arc::new(reqwest::Client::new()),
-    ///     Arc::new(TokenStore),
+    ///     Arc::new(TokenStore::new()),
     /// );
     ///
     /// manager.add_server(
@@ -206,7 +206,7 @@ impl AuthManager {
     /// # async fn example() -> anyhow::Result<()> {
     /// let mut manager = AuthManager::new(
     ///     Arc::new(reqwest::Client::new()),
-    ///     Arc::new(TokenStore),
+    ///     Arc::new(TokenStore::new()),
     /// );
     /// manager.add_server(
     ///     "srv".to_string(),
@@ -312,7 +312,7 @@ impl AuthManager {
     /// # use xzatoma::mcp::auth::discovery::AuthorizationServerMetadata;
     /// # use std::collections::HashMap;
     /// # async fn example() -> anyhow::Result<()> {
-    /// # let mut manager = AuthManager::new(Arc::new(reqwest::Client::new()), Arc::new(TokenStore));
+    /// # let mut manager = AuthManager::new(Arc::new(reqwest::Client::new()), Arc::new(TokenStore::new()));
     /// # manager.add_server("srv".to_string(), OAuthFlowConfig {
     /// #     server_id: "srv".to_string(),
     /// #     resource_url: Url::parse("https://api.example.com/mcp")?,
@@ -380,7 +380,7 @@ impl AuthManager {
     /// # use xzatoma::mcp::auth::discovery::AuthorizationServerMetadata;
     /// # use std::collections::HashMap;
     /// # async fn example() -> anyhow::Result<()> {
-    /// # let mut manager = AuthManager::new(Arc::new(reqwest::Client::new()), Arc::new(TokenStore));
+    /// # let mut manager = AuthManager::new(Arc::new(reqwest::Client::new()), Arc::new(TokenStore::new()));
     /// # manager.add_server("srv".to_string(), OAuthFlowConfig {
     /// #     server_id: "srv".to_string(),
     /// #     resource_url: Url::parse("https://api.example.com/mcp")?,
@@ -485,7 +485,10 @@ mod tests {
     // -----------------------------------------------------------------------
 
     fn make_manager() -> AuthManager {
-        AuthManager::new(Arc::new(reqwest::Client::new()), Arc::new(TokenStore))
+        AuthManager::new(
+            Arc::new(reqwest::Client::new()),
+            Arc::new(TokenStore::new()),
+        )
     }
 
     fn make_config(server_id: &str) -> OAuthFlowConfig {
@@ -1,17 +1,21 @@
-//! OAuth token persistence via OS keyring
+//! OAuth token persistence via the configured credential store
 //!
 //! This module provides secure storage and retrieval of OAuth 2.1 tokens
-//! using the operating system's native credential store (Keychain on macOS,
-//! Secret Service on Linux, Windows Credential Manager on Windows).
+//! through a [`CredentialStore`]: the operating system's native credential
+//! store (Keychain on macOS, Secret Service on Linux, Windows Credential
+//! Manager on Windows) or the encrypted credentials file when no keyring is
+//! available. See [`crate::credentials`].
 //!
 //! Tokens are serialized to JSON before storage and deserialized on load.
-//! The keyring is stateless; [`TokenStore`] is a zero-field struct that acts
-//! as a namespaced accessor.
+//! [`TokenStore`] is a namespaced accessor over the credential store.
+
+use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::error::{Result, XzatomaError};
+use crate::credentials::{self, CredentialStore};
+use crate::error::Result;
 
 // ---------------------------------------------------------------------------
 // OAuthToken
@@ -121,18 +125,22 @@ impl OAuthToken {
 // TokenStore
 // ---------------------------------------------------------------------------
 
-/// Stateless accessor for the OS native keyring.
+/// Namespaced accessor for MCP OAuth tokens in the credential store.
 ///
 /// Each MCP server's token is stored under a unique service name derived from
-/// the server identifier, preventing collisions between servers.
+/// the server identifier, preventing collisions between servers. The store
+/// configured with `credentials` is used unless one is given with
+/// [`TokenStore::with_store`].
 ///
 /// # Examples
 ///
-/// ```no_run
+/// ```
+/// use std::sync::Arc;
+/// use xzatoma::credentials::MemoryStore;
 /// use xzatoma::mcp::auth::token_store::{OAuthToken, TokenStore};
 ///
-/// # async fn example() -> xzatoma::error::Result<()> {
-/// let store = TokenStore;
+/// # fn example() -> xzatoma::error::Result<()> {
+/// let store = TokenStore::with_store(Arc::new(MemoryStore::default()));
 /// let token = OAuthToken {
 ///     access_token: "my_token".to_string(),
 ///     token_type: "Bearer".to_string(),
@@ -145,11 +153,39 @@ impl OAuthToken {
 /// assert!(loaded.is_some());
 /// # Ok(())
 /// # }
+/// # example().unwrap();
 /// ```
-pub struct TokenStore;
+#[derive(Clone, Default)]
+pub struct TokenStore {
+    credentials: Option<Arc<dyn CredentialStore>>,
+}
 
 impl TokenStore {
-    /// Builds the keyring service name for the given MCP server identifier.
+    /// Creates a token store that uses the configured credential store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a token store backed by a specific credential store.
+    ///
+    /// # Arguments
+    ///
+    /// * `credentials` - Store that holds the serialized tokens
+    pub fn with_store(credentials: Arc<dyn CredentialStore>) -> Self {
+        Self {
+            credentials: Some(credentials),
+        }
+    }
+
+    /// Returns the credential store used for the next operation.
+    fn credentials(&self) -> Result<Arc<dyn CredentialStore>> {
+        match &self.credentials {
+            Some(store) => Ok(store.clone()),
+            None => credentials::default_store(),
+        }
+    }
+
+    /// Builds the credential service name for the given MCP server identifier.
     ///
     /// The name is prefixed with `xzatoma-mcp-` to avoid collisions with
     /// other applications that use the same keyring.
@@ -159,8 +195,8 @@ impl TokenStore {
 
     /// Persists an [`OAuthToken`] for the named MCP server.
     ///
-    /// The token is serialized to JSON and stored in the OS keyring under the
-    /// service name derived from `server_id`.
+    /// The token is serialized to JSON and stored in the credential store
+    /// under the service name derived from `server_id`.
     ///
     /// # Arguments
     ///
@@ -170,15 +206,16 @@ impl TokenStore {
     ///
     /// # Errors
     ///
-    /// Returns [`XzatomaError::Serialization`] if JSON serialization fails or
-    /// [`XzatomaError::Keyring`] if the OS credential store rejects the write.
+    /// Returns [`XzatomaError::Serialization`](crate::error::XzatomaError::Serialization)
+    /// if JSON serialization fails, or the credential store's error if it
+    /// rejects the write.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use xzatoma::mcp::auth::token_store::{OAuthToken, TokenStore};
     ///
-    /// let store = TokenStore;
+    /// let store = TokenStore::new();
     /// let token = OAuthToken {
     ///     access_token: "access".to_string(),
     ///     token_type: "Bearer".to_string(),
@@ -190,19 +227,15 @@ impl TokenStore {
     /// ```
     pub fn save_token(&self, server_id: &str, token: &OAuthToken) -> Result<()> {
         let json_str = serde_json::to_string(token)?;
-        let service = Self::service_name(server_id);
-        let entry = keyring::Entry::new(&service, server_id).map_err(XzatomaError::Keyring)?;
-        entry
-            .set_password(&json_str)
-            .map_err(XzatomaError::Keyring)?;
-        Ok(())
+        self.credentials()?
+            .set(&Self::service_name(server_id), server_id, &json_str)
     }
 
     /// Loads the stored [`OAuthToken`] for the named MCP server.
     ///
     /// Returns `Ok(None)` when no token has been saved for the server,
     /// allowing callers to distinguish between "not authenticated yet" and a
-    /// genuine keyring error.
+    /// genuine credential store error.
     ///
     /// # Arguments
     ///
@@ -215,32 +248,28 @@ impl TokenStore {
     ///
     /// # Errors
     ///
-    /// Returns [`XzatomaError::Keyring`] if the OS credential store returns
-    /// an unexpected error, or [`XzatomaError::Serialization`] if the stored
-    /// JSON is malformed.
+    /// Returns the credential store's error if it cannot be read, or
+    /// [`XzatomaError::Serialization`](crate::error::XzatomaError::Serialization)
+    /// if the stored JSON is malformed.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use xzatoma::mcp::auth::token_store::TokenStore;
     ///
-    /// let store = TokenStore;
+    /// let store = TokenStore::new();
     /// match store.load_token("server1").unwrap() {
     ///     Some(token) => println!("Found token: {}", token.access_token),
     ///     None => println!("No token stored"),
     /// }
     /// ```
     pub fn load_token(&self, server_id: &str) -> Result<Option<OAuthToken>> {
-        let service = Self::service_name(server_id);
-        let entry = keyring::Entry::new(&service, server_id).map_err(XzatomaError::Keyring)?;
-
-        match entry.get_password() {
-            Ok(json_str) => {
-                let token: OAuthToken = serde_json::from_str(&json_str)?;
-                Ok(Some(token))
-            }
-            Err(keyring::Error::NoEntry) => Ok(None),
-            Err(e) => Err(XzatomaError::Keyring(e)),
+        match self
+            .credentials()?
+            .get(&Self::service_name(server_id), server_id)?
+        {
+            Some(json_str) => Ok(Some(serde_json::from_str(&json_str)?)),
+            None => Ok(None),
         }
     }
 
@@ -256,26 +285,19 @@ impl TokenStore {
     ///
     /// # Errors
     ///
-    /// Returns [`XzatomaError::Keyring`] if the OS credential store returns
-    /// an unexpected error.
+    /// Returns the credential store's error if it cannot be written.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use xzatoma::mcp::auth::token_store::TokenStore;
     ///
-    /// let store = TokenStore;
+    /// let store = TokenStore::new();
     /// store.delete_token("server1").unwrap();
     /// ```
     pub fn delete_token(&self, server_id: &str) -> Result<()> {
-        let service = Self::service_name(server_id);
-        let entry = keyring::Entry::new(&service, server_id).map_err(XzatomaError::Keyring)?;
-
-        match entry.delete_password() {
-            Ok(()) => Ok(()),
-            Err(keyring::Error::NoEntry) => Ok(()),
-            Err(e) => Err(XzatomaError::Keyring(e)),
-        }
+        self.credentials()?
+            .delete(&Self::service_name(server_id), server_id)
     }
 }
 
@@ -403,6 +425,37 @@ mod tests {
         assert_ne!(a, b);
     }
 
+    // -----------------------------------------------------------------------
+    // Credential store
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn test_token_store_uses_given_credential_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = Arc::new(crate::credentials::FileStore::encrypted(
+            dir.path().join("credentials.json"),
+            "passphrase",
+        ));
+        let store = TokenStore::with_store(file.clone());
+        let token = OAuthToken {
+            access_token: "file_access".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_at: None,
+            refresh_token: Some("file_refresh".to_string()),
+            scope: None,
+        };
+
+        store.save_token("gh", &token).unwrap();
+        let raw = file.get("xzatoma-mcp-gh", "gh").unwrap().unwrap();
+        assert!(raw.contains("file_access"));
+
+        let loaded = store.load_token("gh").unwrap().unwrap();
+        assert_eq!(loaded.refresh_token.as_deref(), Some("file_refresh"));
+
+        store.delete_token("gh").unwrap();
+        assert!(store.load_token("gh").unwrap().is_none());
+    }
+
     // -----------------------------------------------------------------------
     // Keyring integration tests  (require system keyring; skipped in CI)
     // -----------------------------------------------------------------------
@@ -410,7 +463,7 @@ mod tests {
     #[test]
     #[ignore = "requires system keyring"]
     fn test_save_and_load_token_roundtrip_via_keyring() {
-        let store = TokenStore;
+        let store = TokenStore::with_store(Arc::new(crate::credentials::KeyringStore));
         let server_id = "test_integration_server";
 
         let token = OAuthToken {
@@ -437,7 +490,7 @@ mod tests {
     #[test]
     #[ignore = "requires system keyring"]
     fn test_load_token_returns_none_when_absent() {
-        let store = TokenStore;
+        let store = TokenStore::with_store(Arc::new(crate::credentials::KeyringStore));
         let result = store
             .load_token("definitely_nonexistent_server_xzatoma_test")
             .expect("should not error");
@@ -447,7 +500,7 @@ mod tests {
     #[test]
     #[ignore = "requires system keyring"]
     fn test_delete_token_is_idempotent() {
-        let store = TokenStore;
+        let store = TokenStore::with_store(Arc::new(crate::credentials::KeyringStore));
         let server_id = "idempotent_delete_test_xzatoma";
         // Deleting a non-existent entry must not return an error.
         store.delete_token(server_id).expect("first delete");
@@ -17,6 +17,9 @@
 //! which calls it automatically) to catch duplicate server IDs and
 //! per-server configuration errors before the application starts.
 
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
 use crate::error::{Result, XzatomaError};
@@ -88,6 +91,24 @@ pub struct McpConfig {
     /// from all connected servers.
     #[serde(default = "default_true")]
     pub expose_prompts_tool: bool,
+
+    /// Load servers from `.mcp.json` and `.vscode/mcp.json` in the working
+    /// directory.
+    ///
+    /// Project servers are only loaded in trusted workspaces, and a server
+    /// in [`servers`][Self::servers] wins over a project server with the
+    /// same id. Disabled for one invocation by `--no-project-mcp`. See
+    /// [`crate::mcp::project_config`].
+    #[serde(default = "default_true")]
+    pub discover_project_config: bool,
+
+    /// Servers that came from a project MCP file, mapped to that file
+    /// relative to the working directory.
+    ///
+    /// Filled in when the project files are merged; never read from or
+    /// written to the configuration file.
+    #[serde(skip)]
+    pub project_servers: BTreeMap<String, PathBuf>,
 }
 
 impl Default for McpConfig {
@@ -98,6 +119,8 @@ impl Default for McpConfig {
             auto_connect: true,
             expose_resources_tool: true,
             expose_prompts_tool: true,
+            discover_project_config: true,
+            project_servers: BTreeMap::new(),
         }
     }
 }
@@ -141,6 +164,7 @@ impl McpConfig {
     ///     prompts_enabled: false,
     ///     sampling_enabled: false,
     ///     elicitation_enabled: true,
+    ///     read_safe_tools: vec![],
     /// });
     ///
     /// assert!(cfg.validate().is_ok());
@@ -164,6 +188,29 @@ impl McpConfig {
 
         Ok(())
     }
+
+    /// Describe where the server with `id` was defined.
+    ///
+    /// Returns `"config"` for servers from xzatoma's configuration and
+    /// `"project (<file>)"` for servers merged from a project MCP file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::mcp::config::McpConfig;
+    ///
+    /// let mut cfg = McpConfig::default();
+    /// cfg.project_servers.insert("github".to_string(), ".mcp.json".into());
+    ///
+    /// assert_eq!(cfg.server_origin("github"), "project (.mcp.json)");
+    /// assert_eq!(cfg.server_origin("fs"), "config");
+    /// ```
+    pub fn server_origin(&self, id: &str) -> String {
+        match self.project_servers.get(id) {
+            Some(file) => format!("project ({})", file.display()),
+            None => "config".to_string(),
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -272,6 +319,7 @@ mod tests {
             prompts_enabled: false,
             sampling_enabled: false,
             elicitation_enabled: true,
+            read_safe_tools: vec![],
         });
         assert!(cfg.validate().is_err());
     }
@@ -363,6 +411,8 @@ servers:
             auto_connect: false,
             expose_resources_tool: false,
             expose_prompts_tool: false,
+            discover_project_config: false,
+            project_servers: BTreeMap::new(),
         };
         original.servers.push(make_http_server("srv-2"));
 
@@ -374,6 +424,7 @@ servers:
         assert!(!restored.auto_connect);
         assert!(!restored.expose_resources_tool);
         assert!(!restored.expose_prompts_tool);
+        assert!(!restored.discover_project_config);
     }
 
     // -----------------------------------------------------------------------
@@ -396,6 +447,7 @@ servers:
             prompts_enabled: false,
             sampling_enabled: false,
             elicitation_enabled: true,
+            read_safe_tools: vec![],
         }
     }
 
@@ -420,6 +472,7 @@ servers:
             prompts_enabled: false,
             sampling_enabled: false,
             elicitation_enabled: true,
+            read_safe_tools: vec![],
         }
     }
 }
@@ -54,6 +54,8 @@ use crate::mcp::types::{
     McpTool, Prompt, Resource, ResourceContents, RootsCapability, SamplingCapability, TaskParams,
     TasksCapability,
 };
+use crate::network_policy::NetworkPolicy;
+use crate::untrusted_content::{ContentGuard, GuardedContent};
 
 // ---------------------------------------------------------------------------
 // McpServerState
@@ -157,7 +159,7 @@ impl std::fmt::Debug for McpServerEntry {
 ///
 /// # async fn example() -> anyhow::Result<()> {
 /// let http = Arc::new(reqwest::Client::new());
-/// let store = Arc::new(TokenStore);
+/// let store = Arc::new(TokenStore::new());
 /// let manager = McpClientManager::new(http, store);
 /// # Ok(())
 /// # }
@@ -181,6 +183,9 @@ pub struct McpClientManager {
     // background-thread callbacks.
     #[allow(dead_code)]
     task_manager: Arc<std::sync::Mutex<crate::mcp::task_manager::TaskManager>>,
+
+    /// Network policy consulted before connecting a server.
+    network_policy: NetworkPolicy,
 }
 
 impl std::fmt::Debug for McpClientManager {
@@ -210,7 +215,7 @@ impl McpClientManager {
     ///
     /// let manager = McpClientManager::new(
     ///     Arc::new(reqwest::Client::new()),
-    ///     Arc::new(TokenStore),
+    ///     Arc::new(TokenStore::new()),
     /// );
     /// ```
     pub fn new(http_client: Arc<reqwest::Client>, token_store: Arc<TokenStore>) -> Self {
@@ -221,9 +226,38 @@ impl McpClientManager {
             task_manager: Arc::new(std::sync::Mutex::new(
                 crate::mcp::task_manager::TaskManager::default(),
             )),
+            network_policy: NetworkPolicy::default(),
         }
     }
 
+    /// Set the network policy consulted before connecting a server.
+    ///
+    /// In offline mode, HTTP servers are refused by
+    /// [`connect`][Self::connect]; stdio servers are still allowed.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Network policy to enforce.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use xzatoma::mcp::auth::token_store::TokenStore;
+    /// use xzatoma::mcp::manager::McpClientManager;
+    /// use xzatoma::network_policy::NetworkPolicy;
+    ///
+    /// let manager = McpClientManager::new(
+    ///     Arc::new(reqwest::Client::new()),
+    ///     Arc::new(TokenStore::new()),
+    /// )
+    /// .with_network_policy(NetworkPolicy::offline());
+    /// ```
+    pub fn with_network_policy(mut self, policy: NetworkPolicy) -> Self {
+        self.network_policy = policy;
+        self
+    }
+
     /// Connect to all enabled servers listed in `config`.
     ///
     /// Iterates over [`McpConfig::servers`] and calls [`connect`][Self::connect]
@@ -295,12 +329,15 @@ impl McpClientManager {
     ///
     /// # Errors
     ///
-    /// Returns [`XzatomaError::McpTransport`] if the transport cannot be
+    /// Returns [`XzatomaError::NetworkDisabled`] for HTTP servers in offline
+    /// mode, [`XzatomaError::McpTransport`] if the transport cannot be
     /// established, [`XzatomaError::McpProtocolVersion`] if the server
     /// returns an unsupported protocol version, or any other
     /// [`XzatomaError`] variant for network, authentication, or protocol
     /// errors.
     pub async fn connect(&mut self, config: McpServerConfig) -> Result<()> {
+        self.network_policy.check_mcp_transport(&config.transport)?;
+
         let id = config.id.clone();
         tracing::info!(id = %id, "Connecting to MCP server");
 
@@ -505,15 +542,17 @@ impl McpClientManager {
     /// # Errors
     ///
     /// Returns [`XzatomaError::McpServerNotFound`] if the server is unknown,
-    /// [`XzatomaError::Mcp`] if the server is not currently connected, or
-    /// any JSON-RPC error returned by `tools/list`.
+    /// [`XzatomaError::McpServer`] if the server is not currently connected,
+    /// or any JSON-RPC error returned by `tools/list`.
     pub async fn refresh_tools(&mut self, id: &str) -> Result<()> {
         let protocol = self
             .servers
             .get(id)
-            .and_then(|e| e.protocol.as_ref())
+            .ok_or_else(|| XzatomaError::McpServerNotFound(id.to_string()))?
+            .protocol
+            .as_ref()
             .map(Arc::clone)
-            .ok_or_else(|| XzatomaError::McpServerNotFound(id.to_string()))?;
+            .ok_or_else(|| not_connected_error(id))?;
 
         let tools = protocol.list_tools().await?;
         if let Some(entry) = self.servers.get_mut(id) {
@@ -550,6 +589,23 @@ impl McpClientManager {
             .collect()
     }
 
+    /// Returns true when the server's config lists the tool in
+    /// `read_safe_tools`, so read-only mode may register it.
+    ///
+    /// # Arguments
+    ///
+    /// * `server_id` - Server identifier.
+    /// * `tool_name` - Tool name as reported by the server.
+    pub fn is_read_safe(&self, server_id: &str, tool_name: &str) -> bool {
+        self.servers.get(server_id).is_some_and(|entry| {
+            entry
+                .config
+                .read_safe_tools
+                .iter()
+                .any(|name| name == tool_name)
+        })
+    }
+
     /// Invoke a tool on the named server.
     ///
     /// Looks up the server, verifies the tool is in the cached list, and
@@ -572,7 +628,8 @@ impl McpClientManager {
     ///
     /// Returns [`XzatomaError::McpServerNotFound`] when `server_id` is
     /// unknown, [`XzatomaError::McpToolNotFound`] when `tool_name` is not in
-    /// the cached list, or any other protocol/transport error.
+    /// the cached list, [`XzatomaError::McpServer`] when the server is not
+    /// connected, or any other protocol/transport error.
     pub async fn call_tool(
         &self,
         server_id: &str,
@@ -595,7 +652,7 @@ impl McpClientManager {
         let protocol = entry
             .protocol
             .as_ref()
-            .ok_or_else(|| XzatomaError::McpServerNotFound(server_id.to_string()))?;
+            .ok_or_else(|| not_connected_error(server_id))?;
 
         // First attempt.
         let result = protocol.call_tool(tool_name, arguments.clone(), None).await;
@@ -672,7 +729,7 @@ impl McpClientManager {
         let protocol = entry
             .protocol
             .as_ref()
-            .ok_or_else(|| XzatomaError::McpServerNotFound(server_id.to_string()))?;
+            .ok_or_else(|| not_connected_error(server_id))?;
 
         let task_params = TaskParams { ttl };
         let response = protocol
@@ -715,10 +772,12 @@ impl McpClientManager {
 
     /// Read the content of a resource by URI from the named server.
     ///
-    /// For [`ResourceContents::Text`] the text is returned directly.
-    /// For [`ResourceContents::Blob`] the blob string is returned prefixed
-    /// with `"[base64 <mime_type>] "` so that callers can detect binary
-    /// payloads.
+    /// For [`ResourceContents::Text`] the text is used directly.
+    /// For [`ResourceContents::Blob`] the blob string is prefixed with
+    /// `"[base64 <mime_type>] "` so that callers can detect binary
+    /// payloads. The joined content is wrapped and scanned by the
+    /// process-wide [`ContentGuard`], since resources are written by the
+    /// server rather than the user.
     ///
     /// # Arguments
     ///
@@ -729,7 +788,7 @@ impl McpClientManager {
     ///
     /// Returns [`XzatomaError::McpServerNotFound`] or any JSON-RPC error
     /// from `resources/read`.
-    pub async fn read_resource(&self, server_id: &str, uri: &str) -> Result<String> {
+    pub async fn read_resource(&self, server_id: &str, uri: &str) -> Result<GuardedContent> {
         let protocol = self.require_protocol(server_id)?;
         let contents_list = protocol.read_resource(uri).await?;
 
@@ -745,7 +804,8 @@ impl McpClientManager {
             };
             parts.push(text);
         }
-        Ok(parts.join("\n"))
+        let source = format!("MCP resource {} on server {}", uri, server_id);
+        Ok(ContentGuard::shared().wrap(&source, &parts.join("\n")))
     }
 
     /// List all prompts advertised by the named server.
@@ -940,7 +1000,7 @@ impl McpClientManager {
     /// # fn example() {
     /// let mut manager = McpClientManager::new(
     ///     Arc::new(reqwest::Client::new()),
-    ///     Arc::new(TokenStore),
+    ///     Arc::new(TokenStore::new()),
     /// );
     /// // In tests only: insert a pre-built entry.
     /// # }
@@ -1003,6 +1063,14 @@ fn is_mcp_auth_error(err: &XzatomaError) -> bool {
     matches!(err, XzatomaError::McpAuth(_))
 }
 
+/// Builds the error returned when a registered server has no live session.
+fn not_connected_error(server_id: &str) -> XzatomaError {
+    XzatomaError::McpServer {
+        server: server_id.to_string(),
+        reason: "server is not connected".to_string(),
+    }
+}
+
 // ---------------------------------------------------------------------------
 // build_mcp_manager_from_config
 // ---------------------------------------------------------------------------
@@ -1058,8 +1126,9 @@ pub async fn build_mcp_manager_from_config(
     }
 
     let http_client = Arc::new(reqwest::Client::new());
-    let token_store = Arc::new(TokenStore);
-    let mut manager = McpClientManager::new(http_client, token_store);
+    let token_store = Arc::new(TokenStore::new());
+    let mut manager = McpClientManager::new(http_client, token_store)
+        .with_network_policy(NetworkPolicy::from_config(config));
 
     for server_config in config.mcp.servers.iter().filter(|s| s.enabled) {
         if let Err(e) = manager.connect(server_config.clone()).await {
@@ -1089,7 +1158,10 @@ mod tests {
     // -----------------------------------------------------------------------
 
     fn make_manager() -> McpClientManager {
-        McpClientManager::new(Arc::new(reqwest::Client::new()), Arc::new(TokenStore))
+        McpClientManager::new(
+            Arc::new(reqwest::Client::new()),
+            Arc::new(TokenStore::new()),
+        )
     }
 
     /// Build a pre-wired manager entry that uses a [`FakeTransport`].
@@ -1145,6 +1217,7 @@ mod tests {
             prompts_enabled: false,
             sampling_enabled: false,
             elicitation_enabled: false,
+            read_safe_tools: vec![],
         };
 
         let entry = McpServerEntry {
@@ -1421,6 +1494,7 @@ mod tests {
             prompts_enabled: false,
             sampling_enabled: false,
             elicitation_enabled: true,
+            read_safe_tools: vec![],
         }];
 
         let result = build_mcp_manager_from_config(&config).await;
@@ -1429,6 +1503,32 @@ mod tests {
         assert!(result.unwrap().is_some());
     }
 
+    #[tokio::test]
+    async fn test_offline_manager_refuses_http_servers() {
+        let mut manager = make_manager().with_network_policy(NetworkPolicy::offline());
+        let config = McpServerConfig {
+            id: "remote".to_string(),
+            enabled: true,
+            transport: McpServerTransportConfig::Http {
+                endpoint: "https://api.example.com/mcp".parse().unwrap(),
+                headers: HashMap::new(),
+                timeout_seconds: None,
+                oauth: None,
+            },
+            timeout_seconds: 5,
+            tools_enabled: true,
+            resources_enabled: false,
+            prompts_enabled: false,
+            sampling_enabled: false,
+            elicitation_enabled: false,
+            read_safe_tools: vec![],
+        };
+
+        let err = manager.connect(config).await.unwrap_err();
+        assert!(matches!(err, XzatomaError::NetworkDisabled(_)));
+        assert!(manager.connected_servers().is_empty());
+    }
+
     #[tokio::test]
     async fn test_build_mcp_manager_from_config_skips_disabled_servers() {
         use crate::mcp::server::{McpServerConfig, McpServerTransportConfig};
@@ -1451,6 +1551,7 @@ mod tests {
             prompts_enabled: false,
             sampling_enabled: false,
             elicitation_enabled: true,
+            read_safe_tools: vec![],
         }];
 
         let result = build_mcp_manager_from_config(&config).await;
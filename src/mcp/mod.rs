@@ -15,6 +15,7 @@
 //! - `config`       -- MCP client configuration structures
 //! - `elicitation`  -- Elicitation handler for structured user input collection
 //! - `manager`      -- Client lifecycle and server manager
+//! - `project_config` -- Server definitions from `.mcp.json` and `.vscode/mcp.json`
 //! - `protocol`     -- Typed MCP lifecycle wrapper over `JsonRpcClient`
 //! - `sampling`     -- Sampling handler forwarding LLM inference to the Provider
 //! - `server`       -- Per-server connection descriptors
@@ -30,6 +31,7 @@ pub mod client;
 pub mod config;
 pub mod elicitation;
 pub mod manager;
+pub mod project_config;
 pub mod protocol;
 pub mod sampling;
 pub mod server;
@@ -0,0 +1,610 @@
+//! Project-local MCP server definitions
+//!
+//! Many repositories describe the MCP servers they work with in a
+//! `.mcp.json` file at the project root, and VS Code reads
+//! `.vscode/mcp.json`. This module parses both files and merges their
+//! servers into [`McpConfig::servers`].
+//!
+//! # File Format
+//!
+//! Servers are listed under `mcpServers` (`.mcp.json`) or `servers`
+//! (`.vscode/mcp.json`), keyed by name:
+//!
+//! ```json
+//! {
+//!   "mcpServers": {
+//!     "filesystem": {
+//!       "command": "npx",
+//!       "args": ["-y", "@modelcontextprotocol/server-filesystem", "${workspaceFolder}"],
+//!       "env": { "LOG_LEVEL": "${LOG_LEVEL:-info}" }
+//!     },
+//!     "issues": {
+//!       "type": "http",
+//!       "url": "https://mcp.example.com/mcp",
+//!       "headers": { "Authorization": "Bearer ${env:ISSUES_TOKEN}" }
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! A server with `command` (or `"type": "stdio"`) is launched as a
+//! subprocess; a server with `url` (or `"type"` of `http`, `sse`, or
+//! `streamable-http`) is reached over HTTP. `//` and `/* */` comments are
+//! allowed, as VS Code allows them.
+//!
+//! # Placeholders
+//!
+//! String values may reference `${NAME}`, `${NAME:-default}`, `${env:NAME}`,
+//! and `${workspaceFolder}`. A server that references an unset variable
+//! without a default, or that asks for input with `${input:...}`, is skipped
+//! with a warning; the other servers in the file still load.
+//!
+//! # Trust
+//!
+//! A project MCP file can launch arbitrary commands, so both files count as
+//! project-local files for [`crate::workspace_trust`]. The caller only
+//! merges them for a trusted workspace.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::{Result, XzatomaError};
+use crate::mcp::config::McpConfig;
+use crate::mcp::server::{McpServerConfig, McpServerTransportConfig};
+
+/// Project MCP files, relative to the working directory, in merge order
+///
+/// When both files define the same server, the first file wins.
+pub const PROJECT_MCP_FILES: &[&str] = &[".mcp.json", ".vscode/mcp.json"];
+
+// ---------------------------------------------------------------------------
+// File format
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProjectMcpFile {
+    #[serde(rename = "mcpServers")]
+    mcp_servers: BTreeMap<String, ProjectServer>,
+    servers: BTreeMap<String, ProjectServer>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProjectServer {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    command: Option<String>,
+    args: Vec<String>,
+    env: BTreeMap<String, String>,
+    cwd: Option<String>,
+    url: Option<String>,
+    headers: BTreeMap<String, String>,
+    disabled: bool,
+}
+
+// ---------------------------------------------------------------------------
+// Merging
+// ---------------------------------------------------------------------------
+
+/// Merge the servers of the project MCP files in `working_dir` into `mcp`
+///
+/// Does nothing when [`McpConfig::discover_project_config`] is false.
+/// Servers already in `mcp.servers` win over project servers with the same
+/// id. Each merged server is recorded in [`McpConfig::project_servers`].
+/// Files that cannot be read or parsed are skipped with a warning.
+///
+/// # Arguments
+///
+/// * `mcp` - The MCP configuration loaded from xzatoma's config
+/// * `working_dir` - The trusted workspace directory
+pub fn merge_project_servers(mcp: &mut McpConfig, working_dir: &Path) {
+    if !mcp.discover_project_config {
+        return;
+    }
+
+    let lookup = |name: &str| std::env::var(name).ok();
+    for file in project_mcp_files(working_dir) {
+        let relative = file
+            .strip_prefix(working_dir)
+            .unwrap_or(&file)
+            .to_path_buf();
+        let servers = std::fs::read_to_string(&file)
+            .map_err(XzatomaError::from)
+            .and_then(|contents| parse_project_mcp(&contents, working_dir, &lookup));
+        let servers = match servers {
+            Ok(servers) => servers,
+            Err(e) => {
+                tracing::warn!(file = %file.display(), error = %e, "Ignoring project MCP file");
+                continue;
+            }
+        };
+
+        for server in servers {
+            if mcp.servers.iter().any(|existing| existing.id == server.id) {
+                tracing::debug!(
+                    server_id = %server.id,
+                    file = %relative.display(),
+                    "Project MCP server shadowed by an existing server with the same id"
+                );
+                continue;
+            }
+            tracing::info!(
+                server_id = %server.id,
+                file = %relative.display(),
+                "Loaded MCP server from project file"
+            );
+            mcp.project_servers
+                .insert(server.id.clone(), relative.clone());
+            mcp.servers.push(server);
+        }
+    }
+}
+
+/// List the project MCP files that exist in `working_dir`
+pub fn project_mcp_files(working_dir: &Path) -> Vec<PathBuf> {
+    PROJECT_MCP_FILES
+        .iter()
+        .map(|file| working_dir.join(file))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Parsing
+// ---------------------------------------------------------------------------
+
+/// Parse the contents of a project MCP file
+///
+/// Server names are turned into valid ids by lowercasing them and replacing
+/// every character outside `[a-z0-9_-]` with `-`. Disabled servers and
+/// servers that cannot be converted are skipped with a warning.
+///
+/// # Arguments
+///
+/// * `contents` - The file contents, JSON with optional comments
+/// * `workspace` - The directory `${workspaceFolder}` expands to
+/// * `lookup` - Returns the value of an environment variable
+///
+/// # Returns
+///
+/// Returns the servers in name order.
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Config` if the file is not valid JSON.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use xzatoma::mcp::project_config::parse_project_mcp;
+///
+/// let json = r#"{ "mcpServers": { "Git": { "command": "uvx", "args": ["mcp-server-git"] } } }"#;
+/// let servers = parse_project_mcp(json, Path::new("/repo"), &|_| None).unwrap();
+///
+/// assert_eq!(servers[0].id, "git");
+/// ```
+pub fn parse_project_mcp(
+    contents: &str,
+    workspace: &Path,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<Vec<McpServerConfig>> {
+    let file: ProjectMcpFile = serde_json::from_str(&strip_json_comments(contents))
+        .map_err(|e| XzatomaError::Config(format!("Invalid project MCP file: {}", e)))?;
+
+    let mut servers = Vec::new();
+    for (name, server) in file.mcp_servers.into_iter().chain(file.servers) {
+        if server.disabled {
+            tracing::debug!(server = %name, "Skipping disabled project MCP server");
+            continue;
+        }
+        match convert_server(&name, server, workspace, lookup) {
+            Ok(config) => servers.push(config),
+            Err(e) => {
+                tracing::warn!(server = %name, error = %e, "Skipping project MCP server")
+            }
+        }
+    }
+    Ok(servers)
+}
+
+/// Convert one project server entry into an [`McpServerConfig`]
+fn convert_server(
+    name: &str,
+    server: ProjectServer,
+    workspace: &Path,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<McpServerConfig> {
+    let expand = |text: &str| expand_placeholders(text, workspace, lookup);
+    let expand_map = |map: &BTreeMap<String, String>| -> Result<HashMap<String, String>> {
+        map.iter()
+            .map(|(key, value)| expand(value).map(|value| (key.clone(), value)))
+            .collect()
+    };
+
+    let kind = server.kind.as_deref().map(str::to_ascii_lowercase);
+    let transport = match (kind.as_deref(), &server.command, &server.url) {
+        (Some("stdio") | None, Some(command), _) => McpServerTransportConfig::Stdio {
+            executable: expand(command)?,
+            args: server
+                .args
+                .iter()
+                .map(|arg| expand(arg))
+                .collect::<Result<_>>()?,
+            env: expand_map(&server.env)?,
+            working_dir: server.cwd.as_deref().map(expand).transpose()?,
+        },
+        (Some("http" | "sse" | "streamable-http") | None, _, Some(url)) => {
+            let url = expand(url)?;
+            McpServerTransportConfig::Http {
+                endpoint: url::Url::parse(&url)
+                    .map_err(|e| XzatomaError::Config(format!("Invalid URL '{}': {}", url, e)))?,
+                headers: expand_map(&server.headers)?,
+                timeout_seconds: None,
+                oauth: None,
+            }
+        }
+        (Some(kind), _, _) => {
+            return Err(XzatomaError::Config(format!(
+                "Unsupported or incomplete server type '{}'",
+                kind
+            )))
+        }
+        (None, None, None) => {
+            return Err(XzatomaError::Config(
+                "Server has neither a command nor a url".to_string(),
+            ))
+        }
+    };
+
+    let config = McpServerConfig {
+        id: server_id(name),
+        transport,
+        enabled: true,
+        timeout_seconds: 30,
+        tools_enabled: true,
+        resources_enabled: false,
+        prompts_enabled: false,
+        sampling_enabled: false,
+        elicitation_enabled: true,
+        read_safe_tools: vec![],
+    };
+    config.validate()?;
+    Ok(config)
+}
+
+/// Turn a server name into an id matching `^[a-z0-9_-]{1,64}$`
+fn server_id(name: &str) -> String {
+    name.trim()
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .take(64)
+        .collect()
+}
+
+/// Expand the placeholders in one string value
+///
+/// See the module documentation for the supported forms.
+fn expand_placeholders(
+    text: &str,
+    workspace: &Path,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let reference = &rest[start + 2..];
+        let end = reference
+            .find('}')
+            .ok_or_else(|| XzatomaError::Config(format!("Unterminated '${{' in '{}'", text)))?;
+        let body = &reference[..end];
+
+        if body == "workspaceFolder" {
+            expanded.push_str(&workspace.to_string_lossy());
+        } else if body.starts_with("input:") {
+            return Err(XzatomaError::Config(format!(
+                "'${{{}}}' asks for input, which xzatoma does not support",
+                body
+            )));
+        } else {
+            let body = body.strip_prefix("env:").unwrap_or(body);
+            let (name, default) = match body.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (body, None),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(XzatomaError::Config(format!(
+                    "Unsupported placeholder '${{{}}}'",
+                    &reference[..end]
+                )));
+            }
+            let value = match (lookup(name), default) {
+                (Some(value), Some(default)) if value.is_empty() => default.to_string(),
+                (Some(value), _) => value,
+                (None, Some(default)) => default.to_string(),
+                (None, None) => {
+                    return Err(XzatomaError::Config(format!(
+                        "Environment variable '{}' is not set",
+                        name
+                    )))
+                }
+            };
+            expanded.push_str(&value);
+        }
+        rest = &reference[end + 1..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Remove `//` and `/* */` comments outside of JSON strings
+fn strip_json_comments(contents: &str) -> String {
+    let mut stripped = String::with_capacity(contents.len());
+    let mut chars = contents.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            stripped.push(c);
+            match c {
+                '\\' => {
+                    if let Some(escaped) = chars.next() {
+                        stripped.push(escaped);
+                    }
+                }
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                stripped.push(c);
+            }
+            ('/', Some(&'/')) => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        stripped.push('\n');
+                        break;
+                    }
+                }
+            }
+            ('/', Some(&'*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    if next == '\n' {
+                        stripped.push('\n');
+                    }
+                    previous = next;
+                }
+            }
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A `.mcp.json` as committed to many repositories
+    const DOT_MCP_JSON: &str = r#"{
+  "mcpServers": {
+    "filesystem": {
+      "command": "npx",
+      "args": ["-y", "@modelcontextprotocol/server-filesystem", "${workspaceFolder}/docs"],
+      "env": {}
+    },
+    "GitHub": {
+      "command": "docker",
+      "args": ["run", "-i", "--rm", "-e", "GITHUB_PERSONAL_ACCESS_TOKEN", "ghcr.io/github/github-mcp-server"],
+      "env": { "GITHUB_PERSONAL_ACCESS_TOKEN": "${GITHUB_TOKEN}" }
+    },
+    "sentry": {
+      "type": "http",
+      "url": "https://mcp.sentry.dev/mcp",
+      "headers": { "X-Org": "${SENTRY_ORG:-acme}" }
+    },
+    "postgres": {
+      "command": "uvx",
+      "args": ["postgres-mcp", "--access-mode=restricted"],
+      "env": { "DATABASE_URI": "${DATABASE_URL}" }
+    }
+  }
+}"#;
+
+    /// A `.vscode/mcp.json` with comments and VS Code placeholders
+    const VSCODE_MCP_JSON: &str = r#"{
+  // Servers for this workspace
+  "inputs": [
+    { "type": "promptString", "id": "perplexity-key", "description": "API key", "password": true }
+  ],
+  "servers": {
+    "memory": {
+      "type": "stdio",
+      "command": "npx",
+      "args": ["-y", "@modelcontextprotocol/server-memory"],
+      "env": { "MEMORY_FILE_PATH": "${env:HOME}/memory.json" } /* per user */
+    },
+    "perplexity": {
+      "type": "stdio",
+      "command": "npx",
+      "args": ["-y", "server-perplexity-ask"],
+      "env": { "PERPLEXITY_API_KEY": "${input:perplexity-key}" }
+    },
+    "docs.search": {
+      "type": "sse",
+      "url": "http://localhost:3001/sse"
+    }
+  }
+}"#;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "GITHUB_TOKEN" => Some("ghp_test".to_string()),
+            "HOME" => Some("/home/dev".to_string()),
+            _ => None,
+        }
+    }
+
+    fn env_of(server: &McpServerConfig) -> &HashMap<String, String> {
+        match &server.transport {
+            McpServerTransportConfig::Stdio { env, .. } => env,
+            _ => panic!("expected stdio transport"),
+        }
+    }
+
+    #[test]
+    fn test_parse_dot_mcp_json_expands_placeholders() {
+        let servers = parse_project_mcp(DOT_MCP_JSON, Path::new("/repo"), &lookup).unwrap();
+
+        // postgres references an unset variable without a default
+        let ids: Vec<&str> = servers.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["github", "filesystem", "sentry"]);
+
+        assert_eq!(
+            env_of(&servers[0])["GITHUB_PERSONAL_ACCESS_TOKEN"],
+            "ghp_test"
+        );
+        match &servers[1].transport {
+            McpServerTransportConfig::Stdio {
+                executable, args, ..
+            } => {
+                assert_eq!(executable, "npx");
+                assert_eq!(args[2], "/repo/docs");
+            }
+            _ => panic!("expected stdio transport"),
+        }
+        match &servers[2].transport {
+            McpServerTransportConfig::Http {
+                endpoint, headers, ..
+            } => {
+                assert_eq!(endpoint.as_str(), "https://mcp.sentry.dev/mcp");
+                assert_eq!(headers["X-Org"], "acme");
+            }
+            _ => panic!("expected http transport"),
+        }
+    }
+
+    #[test]
+    fn test_parse_vscode_mcp_json_with_comments_and_inputs() {
+        let servers = parse_project_mcp(VSCODE_MCP_JSON, Path::new("/repo"), &lookup).unwrap();
+
+        // perplexity asks for input and is skipped
+        let ids: Vec<&str> = servers.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["docs-search", "memory"]);
+        assert!(matches!(
+            servers[0].transport,
+            McpServerTransportConfig::Http { .. }
+        ));
+        assert_eq!(
+            env_of(&servers[1])["MEMORY_FILE_PATH"],
+            "/home/dev/memory.json"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_json_and_skips_incomplete_servers() {
+        assert!(matches!(
+            parse_project_mcp("{ \"mcpServers\": ", Path::new("/repo"), &lookup),
+            Err(XzatomaError::Config(_))
+        ));
+
+        let json = r#"{ "mcpServers": {
+            "empty": {},
+            "ws": { "type": "websocket", "url": "ws://localhost:9000" },
+            "off": { "command": "true", "disabled": true },
+            "remote": { "url": "https://example.com/mcp" }
+        } }"#;
+        let servers = parse_project_mcp(json, Path::new("/repo"), &lookup).unwrap();
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].id, "remote");
+    }
+
+    #[test]
+    fn test_merge_keeps_config_servers_on_collision_and_records_origin() {
+        let workspace = TempDir::new().unwrap();
+        std::fs::write(
+            workspace.path().join(".mcp.json"),
+            r#"{ "mcpServers": {
+                "fs": { "command": "project-fs" },
+                "git": { "command": "uvx", "args": ["mcp-server-git"] }
+            } }"#,
+        )
+        .unwrap();
+        std::fs::create_dir(workspace.path().join(".vscode")).unwrap();
+        std::fs::write(
+            workspace.path().join(".vscode/mcp.json"),
+            r#"{ "servers": { "git": { "command": "other-git" } } }"#,
+        )
+        .unwrap();
+
+        let mut mcp = McpConfig::default();
+        mcp.servers = parse_project_mcp(
+            r#"{ "mcpServers": { "fs": { "command": "config-fs" } } }"#,
+            workspace.path(),
+            &lookup,
+        )
+        .unwrap();
+        merge_project_servers(&mut mcp, workspace.path());
+
+        assert_eq!(mcp.servers.len(), 2);
+        match &mcp.servers[0].transport {
+            McpServerTransportConfig::Stdio { executable, .. } => {
+                assert_eq!(executable, "config-fs")
+            }
+            _ => panic!("expected stdio transport"),
+        }
+        match &mcp.servers[1].transport {
+            McpServerTransportConfig::Stdio { executable, .. } => assert_eq!(executable, "uvx"),
+            _ => panic!("expected stdio transport"),
+        }
+        assert_eq!(mcp.server_origin("fs"), "config");
+        assert_eq!(mcp.server_origin("git"), "project (.mcp.json)");
+        assert!(mcp.validate().is_ok());
+    }
+
+    #[test]
+    fn test_merge_does_nothing_when_discovery_is_disabled() {
+        let workspace = TempDir::new().unwrap();
+        std::fs::write(
+            workspace.path().join(".mcp.json"),
+            r#"{ "mcpServers": { "git": { "command": "uvx" } } }"#,
+        )
+        .unwrap();
+
+        let mut mcp = McpConfig {
+            discover_project_config: false,
+            ..McpConfig::default()
+        };
+        merge_project_servers(&mut mcp, workspace.path());
+
+        assert!(mcp.servers.is_empty());
+        assert!(mcp.project_servers.is_empty());
+    }
+}
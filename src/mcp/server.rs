@@ -211,6 +211,7 @@ pub enum McpServerTransportConfig {
 ///     prompts_enabled: false,
 ///     sampling_enabled: false,
 ///     elicitation_enabled: true,
+///     read_safe_tools: vec![],
 /// };
 ///
 /// assert!(cfg.validate().is_ok());
@@ -255,6 +256,14 @@ pub struct McpServerConfig {
     /// Allow the server to request structured user input via elicitation.
     #[serde(default = "default_true")]
     pub elicitation_enabled: bool,
+
+    /// Tools of this server that only read, and stay available in read-only
+    /// mode.
+    ///
+    /// Read-only mode registers no other tool of the server. Project
+    /// `.mcp.json` files cannot set this list.
+    #[serde(default)]
+    pub read_safe_tools: Vec<String>,
 }
 
 impl McpServerConfig {
@@ -295,6 +304,7 @@ impl McpServerConfig {
     ///     prompts_enabled: false,
     ///     sampling_enabled: false,
     ///     elicitation_enabled: true,
+    ///     read_safe_tools: vec![],
     /// };
     ///
     /// assert!(cfg.validate().is_ok());
@@ -422,6 +432,7 @@ mod tests {
             prompts_enabled: false,
             sampling_enabled: false,
             elicitation_enabled: true,
+            read_safe_tools: vec![],
         };
         let err = cfg.validate().unwrap_err();
         assert!(err.to_string().contains("non-empty executable"));
@@ -535,6 +546,7 @@ mod tests {
             prompts_enabled: false,
             sampling_enabled: false,
             elicitation_enabled: true,
+            read_safe_tools: vec![],
         }
     }
 
@@ -554,6 +566,7 @@ mod tests {
             prompts_enabled: false,
             sampling_enabled: false,
             elicitation_enabled: true,
+            read_safe_tools: vec![],
         }
     }
 }
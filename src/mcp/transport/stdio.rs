@@ -35,6 +35,7 @@ use tokio::sync::{mpsc, Mutex};
 
 use crate::error::{Result, XzatomaError};
 use crate::mcp::transport::Transport;
+use crate::shutdown::{ChildProcessRegistry, ChildRegistration};
 
 /// Stdio-based MCP transport that drives a child process.
 ///
@@ -70,6 +71,9 @@ pub struct StdioTransport {
     stderr_rx: Arc<Mutex<mpsc::UnboundedReceiver<String>>>,
     /// Handle to the spawned child process; used by `Drop`.
     child: Arc<Mutex<Child>>,
+    /// Keeps the child registered for shutdown cleanup while the transport
+    /// is alive.
+    _registration: Option<ChildRegistration>,
 }
 
 impl StdioTransport {
@@ -126,6 +130,10 @@ impl StdioTransport {
             ))
         })?;
 
+        let registration = child.id().map(|pid| {
+            ChildProcessRegistry::global().register(pid, format!("mcp: {}", executable.display()))
+        });
+
         // Take ownership of all three stdio handles. Each is guaranteed to be
         // Some because we set Stdio::piped() above.
         let stdin = child.stdin.take().ok_or_else(|| {
@@ -190,6 +198,7 @@ impl StdioTransport {
             stdout_rx: Arc::new(Mutex::new(stdout_rx)),
             stderr_rx: Arc::new(Mutex::new(stderr_rx)),
             child: Arc::new(Mutex::new(child)),
+            _registration: registration,
         })
     }
 }
@@ -0,0 +1,308 @@
+//! Network capability policy for offline operation
+//!
+//! Air-gapped environments need a guarantee that the agent makes no outbound
+//! connections except to the local Ollama host. Every feature that reaches
+//! the network declares a [`NetworkCapability`] and asks a [`NetworkPolicy`]
+//! before connecting, so offline mode is enforced in one place and new
+//! network features inherit it by adding a capability.
+//!
+//! Offline mode is enabled with the global `--offline` flag or
+//! `agent.offline: true` in the configuration. Read-only mode
+//! ([`crate::read_only`]) also blocks the fetch tool and `@url:` mentions
+//! unless `--allow-fetch` is given.
+//!
+//! # Examples
+//!
+//! ```
+//! use xzatoma::network_policy::{NetworkCapability, NetworkPolicy};
+//!
+//! let policy = NetworkPolicy::offline();
+//! assert!(!policy.allows(NetworkCapability::UrlMention));
+//! assert!(policy.check_provider("ollama").is_ok());
+//! assert!(policy.check_provider("copilot").is_err());
+//!
+//! assert!(NetworkPolicy::online().allows(NetworkCapability::UrlMention));
+//! ```
+
+use std::fmt;
+
+use crate::config::Config;
+use crate::error::{Result, XzatomaError};
+use crate::mcp::server::McpServerTransportConfig;
+
+/// Provider type that offline mode permits
+pub const OFFLINE_PROVIDER: &str = "ollama";
+
+/// A feature that opens outbound network connections
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NetworkCapability {
+    /// A hosted model provider such as Copilot or OpenAI
+    RemoteProvider,
+    /// The `fetch` tool
+    FetchTool,
+    /// `@url:` mentions in chat prompts
+    UrlMention,
+    /// MCP servers reached over HTTP
+    HttpMcpServer,
+    /// Calls to the XZepr API
+    XzeprClient,
+    /// Span export to an OTLP collector
+    TelemetryExport,
+}
+
+impl NetworkCapability {
+    /// Returns the capability required by a registered tool, if any
+    ///
+    /// # Arguments
+    ///
+    /// * `tool_name` - Registry name of the tool
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::network_policy::NetworkCapability;
+    ///
+    /// assert_eq!(
+    ///     NetworkCapability::for_tool("fetch"),
+    ///     Some(NetworkCapability::FetchTool)
+    /// );
+    /// assert_eq!(NetworkCapability::for_tool("read_file"), None);
+    /// ```
+    pub fn for_tool(tool_name: &str) -> Option<Self> {
+        match tool_name {
+            "fetch" => Some(Self::FetchTool),
+            _ => None,
+        }
+    }
+
+    /// Returns a short human-readable name for the capability
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::RemoteProvider => "remote providers",
+            Self::FetchTool => "the fetch tool",
+            Self::UrlMention => "URL mentions",
+            Self::HttpMcpServer => "HTTP MCP servers",
+            Self::XzeprClient => "XZepr API calls",
+            Self::TelemetryExport => "OTLP span export",
+        }
+    }
+}
+
+impl fmt::Display for NetworkCapability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Decides which network capabilities are available
+///
+/// The default policy is online and allows every capability.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NetworkPolicy {
+    offline: bool,
+    fetch_blocked: bool,
+}
+
+impl NetworkPolicy {
+    /// Creates a policy that allows every capability
+    pub fn online() -> Self {
+        Self {
+            offline: false,
+            fetch_blocked: false,
+        }
+    }
+
+    /// Creates a policy that denies every capability
+    pub fn offline() -> Self {
+        Self {
+            offline: true,
+            fetch_blocked: false,
+        }
+    }
+
+    /// Creates the policy selected by `agent.offline`, `agent.read_only`, and
+    /// `agent.read_only_allow_fetch` in the configuration
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            offline: config.agent.offline,
+            fetch_blocked: config.agent.read_only && !config.agent.read_only_allow_fetch,
+        }
+    }
+
+    /// Returns a copy that denies the fetch tool and `@url:` mentions
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::network_policy::{NetworkCapability, NetworkPolicy};
+    ///
+    /// let policy = NetworkPolicy::online().with_fetch_blocked(true);
+    /// assert!(!policy.allows(NetworkCapability::UrlMention));
+    /// assert!(policy.allows(NetworkCapability::RemoteProvider));
+    /// ```
+    pub fn with_fetch_blocked(mut self, blocked: bool) -> Self {
+        self.fetch_blocked = blocked;
+        self
+    }
+
+    /// Returns true when offline mode is enabled
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Returns true when the capability may be used
+    pub fn allows(&self, capability: NetworkCapability) -> bool {
+        // Offline mode denies every capability; the local Ollama provider is
+        // handled separately by `check_provider`.
+        if self.offline {
+            return false;
+        }
+        !(self.fetch_blocked && Self::is_fetch(capability))
+    }
+
+    /// Fails when the capability may not be used
+    ///
+    /// # Errors
+    ///
+    /// Returns [`XzatomaError::NetworkDisabled`] in offline mode, and
+    /// [`XzatomaError::ReadOnly`] for fetching in read-only mode without
+    /// `--allow-fetch`.
+    pub fn check(&self, capability: NetworkCapability) -> Result<()> {
+        if self.allows(capability) {
+            Ok(())
+        } else if self.offline {
+            Err(XzatomaError::NetworkDisabled(capability.to_string()))
+        } else {
+            Err(XzatomaError::ReadOnly(format!(
+                "{} cannot be used without --allow-fetch",
+                capability
+            )))
+        }
+    }
+
+    fn is_fetch(capability: NetworkCapability) -> bool {
+        matches!(
+            capability,
+            NetworkCapability::FetchTool | NetworkCapability::UrlMention
+        )
+    }
+
+    /// Fails when the provider would connect to a remote service
+    ///
+    /// Offline mode permits only the Ollama provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider_type` - Provider type, such as `"copilot"` or `"ollama"`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`XzatomaError::NetworkDisabled`] for any other provider in
+    /// offline mode.
+    pub fn check_provider(&self, provider_type: &str) -> Result<()> {
+        if provider_type == OFFLINE_PROVIDER {
+            return Ok(());
+        }
+        self.check(NetworkCapability::RemoteProvider).map_err(|_| {
+            XzatomaError::NetworkDisabled(format!(
+                "the '{}' provider (only '{}' is allowed)",
+                provider_type, OFFLINE_PROVIDER
+            ))
+        })
+    }
+
+    /// Fails when an MCP server transport would reach the network
+    ///
+    /// Stdio servers run as local subprocesses and are always allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`XzatomaError::NetworkDisabled`] for HTTP transports in
+    /// offline mode.
+    pub fn check_mcp_transport(&self, transport: &McpServerTransportConfig) -> Result<()> {
+        match transport {
+            McpServerTransportConfig::Stdio { .. } => Ok(()),
+            McpServerTransportConfig::Http { .. } => self.check(NetworkCapability::HttpMcpServer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_online_policy_allows_everything() {
+        let policy = NetworkPolicy::default();
+        assert!(!policy.is_offline());
+        assert!(policy.check(NetworkCapability::FetchTool).is_ok());
+        assert!(policy.check(NetworkCapability::XzeprClient).is_ok());
+        assert!(policy.check_provider("copilot").is_ok());
+    }
+
+    #[test]
+    fn test_offline_policy_denies_capabilities() {
+        let policy = NetworkPolicy::offline();
+        let err = policy.check(NetworkCapability::UrlMention).unwrap_err();
+        assert!(matches!(err, XzatomaError::NetworkDisabled(_)));
+        assert!(err.to_string().contains("URL mentions"));
+        assert!(!policy.allows(NetworkCapability::HttpMcpServer));
+        assert!(!policy.allows(NetworkCapability::TelemetryExport));
+    }
+
+    #[test]
+    fn test_offline_policy_allows_only_ollama_provider() {
+        let policy = NetworkPolicy::offline();
+        assert!(policy.check_provider("ollama").is_ok());
+        let err = policy.check_provider("copilot").unwrap_err();
+        assert!(err.to_string().contains("copilot"));
+        assert!(policy.check_provider("openai").is_err());
+    }
+
+    #[test]
+    fn test_offline_policy_allows_only_stdio_mcp_servers() {
+        let policy = NetworkPolicy::offline();
+        let stdio = McpServerTransportConfig::Stdio {
+            executable: "npx".to_string(),
+            args: Vec::new(),
+            env: HashMap::new(),
+            working_dir: None,
+        };
+        let http = McpServerTransportConfig::Http {
+            endpoint: "https://api.example.com/mcp".parse().unwrap(),
+            headers: HashMap::new(),
+            timeout_seconds: None,
+            oauth: None,
+        };
+        assert!(policy.check_mcp_transport(&stdio).is_ok());
+        assert!(policy.check_mcp_transport(&http).is_err());
+        assert!(NetworkPolicy::online().check_mcp_transport(&http).is_ok());
+    }
+
+    #[test]
+    fn test_policy_from_config() {
+        let mut config = Config::default();
+        assert!(!NetworkPolicy::from_config(&config).is_offline());
+        config.agent.offline = true;
+        assert!(NetworkPolicy::from_config(&config).is_offline());
+    }
+
+    #[test]
+    fn test_read_only_config_blocks_fetching_unless_allowed() {
+        let mut config = Config::default();
+        config.agent.read_only = true;
+        let policy = NetworkPolicy::from_config(&config);
+        assert!(!policy.is_offline());
+        assert!(policy.check_provider("copilot").is_ok());
+        assert!(policy.allows(NetworkCapability::HttpMcpServer));
+        let err = policy.check(NetworkCapability::UrlMention).unwrap_err();
+        assert!(matches!(err, XzatomaError::ReadOnly(_)));
+        assert!(!policy.allows(NetworkCapability::FetchTool));
+
+        config.agent.read_only_allow_fetch = true;
+        let policy = NetworkPolicy::from_config(&config);
+        assert!(policy.check(NetworkCapability::UrlMention).is_ok());
+        assert!(policy.allows(NetworkCapability::FetchTool));
+    }
+}
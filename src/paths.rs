@@ -0,0 +1,508 @@
+//! Locations of the files xzatoma writes
+//!
+//! Every component that writes to disk takes its location from a [`Paths`]
+//! value instead of asking the platform itself, so one setting moves all of
+//! them. Three directories are resolved, each from the first of:
+//!
+//! 1. an environment variable (`XZATOMA_DATA_DIR`, `XZATOMA_CACHE_DIR`,
+//!    `XZATOMA_STATE_DIR`)
+//! 2. the `paths` config section
+//! 3. the platform directory (`$XDG_DATA_HOME/xzatoma`,
+//!    `$XDG_CACHE_HOME/xzatoma` and `$XDG_STATE_HOME/xzatoma` on Linux)
+//!
+//! | Directory | Contents                                                      |
+//! | --------- | ------------------------------------------------------------- |
+//! | data      | history database, credentials file, semantic index, trust stores |
+//! | cache     | provider response cache                                       |
+//! | state     | file mutation audit log                                       |
+//!
+//! Platforms without a state directory use the data directory. The trust
+//! stores predate the platform directories and stay in `~/.xzatoma` unless
+//! the data directory is set explicitly.
+//!
+//! # Examples
+//!
+//! ```
+//! use xzatoma::config::PathsConfig;
+//! use xzatoma::paths::{PathSource, Paths};
+//!
+//! let config = PathsConfig {
+//!     data_dir: Some("/srv/xzatoma".to_string()),
+//!     ..Default::default()
+//! };
+//! let paths = Paths::resolve_with(&config, |_| None).unwrap();
+//! assert_eq!(paths.history_db(), std::path::Path::new("/srv/xzatoma/history.db"));
+//! assert_eq!(paths.data().source, PathSource::Config);
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use directories::ProjectDirs;
+
+use crate::config::{Config, PathsConfig};
+use crate::error::{Result, XzatomaError};
+use crate::tools::audit_log::AUDIT_LOG_FILE_NAME;
+
+/// Environment variable overriding the data directory
+pub const DATA_DIR_ENV: &str = "XZATOMA_DATA_DIR";
+
+/// Environment variable overriding the cache directory
+pub const CACHE_DIR_ENV: &str = "XZATOMA_CACHE_DIR";
+
+/// Environment variable overriding the state directory
+pub const STATE_DIR_ENV: &str = "XZATOMA_STATE_DIR";
+
+/// Environment variable overriding the user configuration directory
+pub const CONFIG_DIR_ENV: &str = "XZATOMA_CONFIG_DIR";
+
+/// File name of the user configuration file
+pub const USER_CONFIG_FILE_NAME: &str = "config.yaml";
+
+/// Where a directory's location came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSource {
+    /// An `XZATOMA_*_DIR` environment variable
+    Env,
+    /// The `paths` config section
+    Config,
+    /// The platform default
+    Default,
+}
+
+impl fmt::Display for PathSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSource::Env => write!(f, "env"),
+            PathSource::Config => write!(f, "config"),
+            PathSource::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// A resolved directory and where its location came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedDir {
+    /// The directory
+    pub path: PathBuf,
+    /// Where the location came from
+    pub source: PathSource,
+}
+
+/// Resolved data, cache, and state directories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paths {
+    data: ResolvedDir,
+    cache: ResolvedDir,
+    state: ResolvedDir,
+    legacy_dir: Option<PathBuf>,
+}
+
+impl Paths {
+    /// Resolves the directories for a loaded configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory has no override and the platform
+    /// directories cannot be determined.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Self::resolve(&config.paths)
+    }
+
+    /// Resolves the directories from the environment and platform defaults
+    ///
+    /// Used where no configuration has been loaded yet, such as the
+    /// workspace trust check that decides whether the config may be read.
+    ///
+    /// # Errors
+    ///
+    /// See [`Paths::from_config`].
+    pub fn from_env() -> Result<Self> {
+        Self::resolve(&PathsConfig::default())
+    }
+
+    /// Resolves the directories from `config` and the process environment
+    ///
+    /// # Errors
+    ///
+    /// See [`Paths::from_config`].
+    pub fn resolve(config: &PathsConfig) -> Result<Self> {
+        Self::resolve_with(config, |name| std::env::var(name).ok())
+    }
+
+    /// Resolves the directories with `env` standing in for the process
+    /// environment
+    ///
+    /// Empty environment values are ignored.
+    ///
+    /// # Errors
+    ///
+    /// See [`Paths::from_config`].
+    pub fn resolve_with(
+        config: &PathsConfig,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let platform = ProjectDirs::from("com", "xbcsmith", "xzatoma");
+        let platform_dir = |dir: fn(&ProjectDirs) -> PathBuf| {
+            platform
+                .as_ref()
+                .map(dir)
+                .ok_or_else(|| XzatomaError::Config("Could not determine data directory".into()))
+        };
+        let pick = |env_name: &str,
+                    configured: &Option<String>,
+                    default: &dyn Fn() -> Result<PathBuf>|
+         -> Result<ResolvedDir> {
+            if let Some(value) = env(env_name).filter(|value| !value.trim().is_empty()) {
+                return Ok(ResolvedDir {
+                    path: expand_home(&value, &env),
+                    source: PathSource::Env,
+                });
+            }
+            if let Some(value) = configured {
+                return Ok(ResolvedDir {
+                    path: expand_home(value, &env),
+                    source: PathSource::Config,
+                });
+            }
+            Ok(ResolvedDir {
+                path: default()?,
+                source: PathSource::Default,
+            })
+        };
+
+        let data = pick(DATA_DIR_ENV, &config.data_dir, &|| {
+            platform_dir(|dirs| dirs.data_dir().to_path_buf())
+        })?;
+        let cache = pick(CACHE_DIR_ENV, &config.cache_dir, &|| {
+            platform_dir(|dirs| dirs.cache_dir().to_path_buf())
+        })?;
+        let state = pick(STATE_DIR_ENV, &config.state_dir, &|| {
+            Ok(platform
+                .as_ref()
+                .and_then(|dirs| dirs.state_dir().map(Path::to_path_buf))
+                .unwrap_or_else(|| data.path.clone()))
+        })?;
+        let legacy_dir = match data.source {
+            PathSource::Default => env("HOME").map(|home| PathBuf::from(home).join(".xzatoma")),
+            PathSource::Env | PathSource::Config => None,
+        };
+
+        Ok(Self {
+            data,
+            cache,
+            state,
+            legacy_dir,
+        })
+    }
+
+    /// The data directory and where it came from
+    pub fn data(&self) -> &ResolvedDir {
+        &self.data
+    }
+
+    /// The cache directory and where it came from
+    pub fn cache(&self) -> &ResolvedDir {
+        &self.cache
+    }
+
+    /// The state directory and where it came from
+    pub fn state(&self) -> &ResolvedDir {
+        &self.state
+    }
+
+    /// Directory for persistent data
+    pub fn data_dir(&self) -> &Path {
+        &self.data.path
+    }
+
+    /// Directory for data that can be rebuilt
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache.path
+    }
+
+    /// Directory for logs
+    pub fn state_dir(&self) -> &Path {
+        &self.state.path
+    }
+
+    /// Conversation history database
+    pub fn history_db(&self) -> PathBuf {
+        self.data_dir().join("history.db")
+    }
+
+    /// Encrypted credentials file
+    pub fn credentials_file(&self) -> PathBuf {
+        self.data_dir().join("credentials.json")
+    }
+
+    /// Semantic index directory
+    pub fn semantic_index_dir(&self) -> PathBuf {
+        self.data_dir().join("semantic_index")
+    }
+
+    /// Original output of tool results that were summarized
+    pub fn tool_outputs_dir(&self) -> PathBuf {
+        self.data_dir().join("tool_outputs")
+    }
+
+    /// Files written by one `xzatoma run`, such as its post-mortem report
+    pub fn run_artifacts_dir(&self, run_id: &str) -> PathBuf {
+        self.data_dir().join("runs").join(run_id)
+    }
+
+    /// Workspace snapshots taken by `xzatoma run --snapshot`
+    pub fn snapshots_dir(&self) -> PathBuf {
+        self.data_dir().join("snapshots")
+    }
+
+    /// Workspace trust store
+    pub fn workspace_trust_file(&self) -> PathBuf {
+        self.legacy_or_data_dir().join("workspace_trust.yaml")
+    }
+
+    /// Skills trust store
+    pub fn skills_trust_file(&self) -> PathBuf {
+        self.legacy_or_data_dir().join("skills_trust.yaml")
+    }
+
+    /// Provider response cache directory
+    pub fn response_cache_dir(&self) -> PathBuf {
+        self.cache_dir().join("provider_cache")
+    }
+
+    /// File mutation audit log
+    pub fn audit_log(&self) -> PathBuf {
+        self.state_dir().join(AUDIT_LOG_FILE_NAME)
+    }
+
+    /// Default location of every file, for `xzatoma paths`
+    pub fn files(&self) -> Vec<(&'static str, PathBuf)> {
+        vec![
+            ("history database", self.history_db()),
+            ("credentials file", self.credentials_file()),
+            ("semantic index", self.semantic_index_dir()),
+            ("tool outputs", self.tool_outputs_dir()),
+            ("workspace trust store", self.workspace_trust_file()),
+            ("skills trust store", self.skills_trust_file()),
+            ("provider cache", self.response_cache_dir()),
+            ("audit log", self.audit_log()),
+        ]
+    }
+
+    fn legacy_or_data_dir(&self) -> &Path {
+        self.legacy_dir.as_deref().unwrap_or(self.data_dir())
+    }
+}
+
+/// User configuration file, read when `config/config.yaml` does not exist
+///
+/// Lives in `XZATOMA_CONFIG_DIR` when set, otherwise in the platform config
+/// directory (`$XDG_CONFIG_HOME/xzatoma` on Linux). First-run setup writes
+/// it. Returns `None` when neither can be determined.
+pub fn user_config_file() -> Option<PathBuf> {
+    user_config_file_with(|name| std::env::var(name).ok())
+}
+
+/// Resolves the user configuration file with `env` standing in for the
+/// process environment
+pub fn user_config_file_with(env: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    let dir = match env(CONFIG_DIR_ENV).filter(|value| !value.trim().is_empty()) {
+        Some(value) => expand_home(&value, &env),
+        None => ProjectDirs::from("com", "xbcsmith", "xzatoma")?
+            .config_dir()
+            .to_path_buf(),
+    };
+    Some(dir.join(USER_CONFIG_FILE_NAME))
+}
+
+/// Expands a leading `~/` using `HOME` from `env`
+fn expand_home(path: &str, env: &impl Fn(&str) -> Option<String>) -> PathBuf {
+    match (path.strip_prefix("~/"), env("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::ConversationStore;
+    use crate::config::ToolsConfig;
+    use crate::credentials::{CredentialStore, FileStore};
+    use crate::providers::cache::{CacheEntry, ResponseCache};
+    use crate::providers::{FinishReason, Message};
+    use crate::semantic_index::SemanticIndex;
+    use crate::skills::trust::{default_trust_store_path, SkillTrustStore};
+    use crate::storage::SqliteStorage;
+    use crate::tools::audit_log::{AuditLog, AuditOperation};
+    use crate::tools::overflow_summary::ToolOutputStore;
+    use crate::workspace_trust::{WorkspaceTrustStore, TRUST_STORE_ENV};
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    fn env_from(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let pairs: Vec<(String, String)> = pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| {
+            pairs
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        }
+    }
+
+    #[test]
+    fn test_env_overrides_config_and_moves_every_file() {
+        let config = PathsConfig {
+            data_dir: Some("/from/config".to_string()),
+            ..Default::default()
+        };
+        let env = env_from(&[
+            (DATA_DIR_ENV, "/env/data"),
+            (CACHE_DIR_ENV, "/env/cache"),
+            (STATE_DIR_ENV, "/env/state"),
+            ("HOME", "/home/user"),
+        ]);
+
+        let paths = Paths::resolve_with(&config, env).unwrap();
+        assert_eq!(paths.data().source, PathSource::Env);
+        for (name, file) in paths.files() {
+            assert!(file.starts_with("/env"), "{} at {}", name, file.display());
+        }
+        assert_eq!(paths.audit_log(), Path::new("/env/state/audit.jsonl"));
+        assert_eq!(
+            paths.response_cache_dir(),
+            Path::new("/env/cache/provider_cache")
+        );
+    }
+
+    #[test]
+    fn test_config_dirs_expand_home_and_ignore_empty_env() {
+        let config = PathsConfig {
+            data_dir: Some("~/xz/data".to_string()),
+            cache_dir: Some("/c".to_string()),
+            state_dir: Some("/s".to_string()),
+        };
+        let env = env_from(&[(DATA_DIR_ENV, " "), ("HOME", "/home/user")]);
+
+        let paths = Paths::resolve_with(&config, env).unwrap();
+        assert_eq!(paths.data_dir(), Path::new("/home/user/xz/data"));
+        assert_eq!(paths.data().source, PathSource::Config);
+        assert_eq!(
+            paths.workspace_trust_file(),
+            Path::new("/home/user/xz/data/workspace_trust.yaml")
+        );
+    }
+
+    #[test]
+    fn test_user_config_file_honors_env_override() {
+        let env = env_from(&[(CONFIG_DIR_ENV, "~/xz"), ("HOME", "/home/user")]);
+        assert_eq!(
+            user_config_file_with(env),
+            Some(PathBuf::from("/home/user/xz/config.yaml"))
+        );
+
+        if let Some(path) = user_config_file_with(env_from(&[(CONFIG_DIR_ENV, "")])) {
+            assert!(path.ends_with(USER_CONFIG_FILE_NAME));
+        }
+    }
+
+    #[test]
+    fn test_default_data_dir_keeps_legacy_files_in_home() {
+        let env = env_from(&[
+            (CACHE_DIR_ENV, "/c"),
+            (STATE_DIR_ENV, "/s"),
+            ("HOME", "/home/user"),
+        ]);
+        let Ok(paths) = Paths::resolve_with(&PathsConfig::default(), env) else {
+            // No platform data directory in this environment
+            return;
+        };
+
+        assert_eq!(paths.data().source, PathSource::Default);
+        assert_eq!(
+            paths.skills_trust_file(),
+            Path::new("/home/user/.xzatoma/skills_trust.yaml")
+        );
+        assert!(paths.history_db().starts_with(paths.data_dir()));
+    }
+
+    #[test]
+    #[serial]
+    fn test_env_overrides_relocate_every_component() {
+        let data = TempDir::new().unwrap();
+        let cache = TempDir::new().unwrap();
+        let state = TempDir::new().unwrap();
+        let saved: Vec<(&str, Option<String>)> = [
+            DATA_DIR_ENV,
+            CACHE_DIR_ENV,
+            STATE_DIR_ENV,
+            "XZATOMA_HISTORY_DB",
+            TRUST_STORE_ENV,
+        ]
+        .into_iter()
+        .map(|name| (name, std::env::var(name).ok()))
+        .collect();
+        std::env::set_var(DATA_DIR_ENV, data.path());
+        std::env::set_var(CACHE_DIR_ENV, cache.path());
+        std::env::set_var(STATE_DIR_ENV, state.path());
+        std::env::remove_var("XZATOMA_HISTORY_DB");
+        std::env::remove_var(TRUST_STORE_ENV);
+
+        let paths = Paths::from_env().unwrap();
+        SqliteStorage::new(&paths).unwrap();
+        ConversationStore::new(SqliteStorage::default_database_path(&paths)).unwrap();
+        FileStore::plaintext(FileStore::default_path(&paths))
+            .set("xzatoma", "test", "secret")
+            .unwrap();
+        SemanticIndex::new(data.path(), "model")
+            .save(&SemanticIndex::path_for(
+                &paths.semantic_index_dir(),
+                data.path(),
+            ))
+            .unwrap();
+        ToolOutputStore::from_paths(&paths)
+            .save("full output")
+            .unwrap();
+        WorkspaceTrustStore::load_default().unwrap().save().unwrap();
+        SkillTrustStore::load_or_create(default_trust_store_path().unwrap())
+            .unwrap()
+            .save()
+            .unwrap();
+        let entry = CacheEntry {
+            created_at: chrono::Utc::now(),
+            provider: "ollama".to_string(),
+            model: "llama3.2:latest".to_string(),
+            message: Message::assistant("cached"),
+            usage: None,
+            response_model: None,
+            reasoning: None,
+            finish_reason: FinishReason::Stop,
+        };
+        ResponseCache::from_config(&Default::default(), &paths)
+            .put("entry", &entry)
+            .unwrap();
+        let audit = AuditLog::from_config(&ToolsConfig::default(), &paths).unwrap();
+        audit
+            .append(&audit.entry("write_file", AuditOperation::Create, "a.txt"))
+            .unwrap();
+
+        for (name, value) in saved {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+
+        for (name, file) in paths.files() {
+            assert!(file.exists(), "{} missing at {}", name, file.display());
+        }
+        assert!(paths.history_db().starts_with(data.path()));
+        assert!(paths.skills_trust_file().starts_with(data.path()));
+        assert!(paths.response_cache_dir().starts_with(cache.path()));
+        assert!(paths.audit_log().starts_with(state.path()));
+    }
+}
@@ -1,13 +1,188 @@
 //! System prompts for different chat modes
 //!
 //! This module provides mode-specific system prompts that guide the AI agent's
-//! behavior in different operating modes (Planning vs Write).
+//! behavior in different operating modes (Planning vs Write). Each mode has a
+//! full prompt tuned for large models and a concise variant for small local
+//! models, selected through [`PromptStyle`].
 
 pub mod planning_prompt;
 pub mod write_prompt;
 
 use crate::chat_mode::{ChatMode, SafetyMode};
+use crate::config::ProviderConfig;
+use crate::providers::Provider;
 use crate::skills::activation::ActiveSkillRegistry;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Largest model, in parameters, that receives the concise prompt by default.
+pub const CONCISE_MAX_PARAMETERS: u64 = 4_000_000_000;
+
+/// Estimated token budget the concise prompts must stay within.
+pub const CONCISE_PROMPT_TOKEN_BUDGET: usize = 200;
+
+/// Verbosity of the mode-specific system prompt.
+///
+/// `Full` is tuned for large hosted models. `Concise` is a short, imperative
+/// version for small local models that otherwise start role-playing long
+/// instruction blocks instead of following them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptStyle {
+    /// Full prompt with explanations and examples
+    #[default]
+    Full,
+    /// Short prompt with only the mode-critical rules
+    Concise,
+}
+
+impl PromptStyle {
+    /// Parse a prompt style from a string
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The string to parse ("full" or "concise", case-insensitive)
+    ///
+    /// # Returns
+    ///
+    /// Returns the PromptStyle if valid, or an error message if invalid
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::prompts::PromptStyle;
+    ///
+    /// assert_eq!(PromptStyle::parse_str("concise").unwrap(), PromptStyle::Concise);
+    /// assert!(PromptStyle::parse_str("tiny").is_err());
+    /// ```
+    pub fn parse_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "concise" => Ok(Self::Concise),
+            other => Err(format!("Unknown prompt style: {}", other)),
+        }
+    }
+
+    /// Choose a prompt style from a model's parameter count
+    ///
+    /// Models with at most [`CONCISE_MAX_PARAMETERS`] parameters get the
+    /// concise prompt; larger models get the full prompt.
+    ///
+    /// # Arguments
+    ///
+    /// * `parameters` - Number of model parameters
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::prompts::PromptStyle;
+    ///
+    /// assert_eq!(PromptStyle::for_parameter_count(3_200_000_000), PromptStyle::Concise);
+    /// assert_eq!(PromptStyle::for_parameter_count(70_000_000_000), PromptStyle::Full);
+    /// ```
+    pub fn for_parameter_count(parameters: u64) -> Self {
+        if parameters <= CONCISE_MAX_PARAMETERS {
+            Self::Concise
+        } else {
+            Self::Full
+        }
+    }
+}
+
+impl fmt::Display for PromptStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => write!(f, "full"),
+            Self::Concise => write!(f, "concise"),
+        }
+    }
+}
+
+/// Parses a human-readable parameter size such as Ollama's `"3.2B"`.
+///
+/// Accepts an optional `K`, `M`, `B`, or `T` suffix (case-insensitive).
+///
+/// # Arguments
+///
+/// * `size` - The parameter size string reported by the provider
+///
+/// # Returns
+///
+/// The parameter count, or `None` when the string cannot be parsed
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::prompts::parse_parameter_count;
+///
+/// assert_eq!(parse_parameter_count("3.2B"), Some(3_200_000_000));
+/// assert_eq!(parse_parameter_count("567M"), Some(567_000_000));
+/// assert_eq!(parse_parameter_count("8x7B"), None);
+/// ```
+pub fn parse_parameter_count(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let unit = size.chars().last()?;
+    let (number, multiplier) = match unit.to_ascii_uppercase() {
+        'K' => (&size[..size.len() - 1], 1e3),
+        'M' => (&size[..size.len() - 1], 1e6),
+        'B' => (&size[..size.len() - 1], 1e9),
+        'T' => (&size[..size.len() - 1], 1e12),
+        _ => (size, 1.0),
+    };
+    let value: f64 = number.trim().parse().ok()?;
+    if !value.is_finite() || value < 0.0 {
+        return None;
+    }
+    Some((value * multiplier).round() as u64)
+}
+
+/// Selects the prompt style for the active provider and model.
+///
+/// For Ollama, `provider.ollama.prompt_style` wins when set; otherwise the
+/// model's `parameter_size` from `/api/show` decides. All other providers, and
+/// Ollama models whose size cannot be determined, use [`PromptStyle::Full`].
+///
+/// # Arguments
+///
+/// * `provider_config` - Provider configuration holding any style override
+/// * `provider_type` - Active provider type (e.g. "ollama")
+/// * `provider` - Active provider, queried for model details
+///
+/// # Returns
+///
+/// The prompt style to use for mode system prompts
+pub async fn detect_prompt_style(
+    provider_config: &ProviderConfig,
+    provider_type: &str,
+    provider: &dyn Provider,
+) -> PromptStyle {
+    if provider_type != "ollama" {
+        return PromptStyle::Full;
+    }
+    if let Some(style) = provider_config.ollama.prompt_style {
+        return style;
+    }
+
+    let model = provider.get_current_model();
+    match provider.get_model_info(&model).await {
+        Ok(info) => match info
+            .provider_specific
+            .get("parameter_size")
+            .and_then(|size| parse_parameter_count(size))
+        {
+            Some(parameters) => {
+                let style = PromptStyle::for_parameter_count(parameters);
+                tracing::debug!(model = %model, parameters, style = %style, "Selected prompt style");
+                style
+            }
+            None => PromptStyle::Full,
+        },
+        Err(e) => {
+            tracing::debug!(model = %model, error = %e, "Could not detect model size; using full prompts");
+            PromptStyle::Full
+        }
+    }
+}
 
 /// Builds a mode-specific system prompt.
 ///
@@ -17,7 +192,8 @@ use crate::skills::activation::ActiveSkillRegistry;
 /// # Arguments
 ///
 /// * `mode` - The current ChatMode (Planning or Write)
-/// * `safety` - The current SafetyMode (AlwaysConfirm or NeverConfirm)
+/// * `safety` - The current SafetyMode (AlwaysConfirm, NeverConfirm, or ConfirmOnce)
+/// * `style` - The PromptStyle (Full or Concise)
 ///
 /// # Returns
 ///
@@ -26,15 +202,15 @@ use crate::skills::activation::ActiveSkillRegistry;
 /// # Examples
 ///
 /// ```
-/// use xzatoma::prompts::build_system_prompt;
+/// use xzatoma::prompts::{build_system_prompt, PromptStyle};
 /// use xzatoma::chat_mode::{ChatMode, SafetyMode};
 ///
-/// let prompt = build_system_prompt(ChatMode::Planning, SafetyMode::AlwaysConfirm);
+/// let prompt = build_system_prompt(ChatMode::Planning, SafetyMode::AlwaysConfirm, PromptStyle::Full);
 /// assert!(prompt.contains("PLANNING"));
 /// assert!(prompt.contains("read"));
 /// ```
-pub fn build_system_prompt(mode: ChatMode, safety: SafetyMode) -> String {
-    build_system_prompt_with_skill_disclosure(mode, safety, None)
+pub fn build_system_prompt(mode: ChatMode, safety: SafetyMode, style: PromptStyle) -> String {
+    build_system_prompt_with_skill_disclosure(mode, safety, style, None)
 }
 
 /// Builds a mode-specific system prompt with an optional skill disclosure section.
@@ -47,7 +223,8 @@ pub fn build_system_prompt(mode: ChatMode, safety: SafetyMode) -> String {
 /// # Arguments
 ///
 /// * `mode` - The current ChatMode (Planning or Write)
-/// * `safety` - The current SafetyMode (AlwaysConfirm or NeverConfirm)
+/// * `safety` - The current SafetyMode (AlwaysConfirm, NeverConfirm, or ConfirmOnce)
+/// * `style` - The PromptStyle (Full or Concise)
 /// * `skill_disclosure` - Optional rendered skill disclosure section
 ///
 /// # Returns
@@ -59,11 +236,12 @@ pub fn build_system_prompt(mode: ChatMode, safety: SafetyMode) -> String {
 ///
 /// ```
 /// use xzatoma::chat_mode::{ChatMode, SafetyMode};
-/// use xzatoma::prompts::build_system_prompt_with_skill_disclosure;
+/// use xzatoma::prompts::{build_system_prompt_with_skill_disclosure, PromptStyle};
 ///
 /// let prompt = build_system_prompt_with_skill_disclosure(
 ///     ChatMode::Planning,
 ///     SafetyMode::AlwaysConfirm,
+///     PromptStyle::Full,
 ///     Some("## Available Skills\n- example_skill: Example description"),
 /// );
 ///
@@ -74,11 +252,20 @@ pub fn build_system_prompt(mode: ChatMode, safety: SafetyMode) -> String {
 pub fn build_system_prompt_with_skill_disclosure(
     mode: ChatMode,
     safety: SafetyMode,
+    style: PromptStyle,
     skill_disclosure: Option<&str>,
 ) -> String {
-    let base_prompt = match mode {
-        ChatMode::Planning => planning_prompt::generate_planning_prompt(safety),
-        ChatMode::Write => write_prompt::generate_write_prompt(safety),
+    let base_prompt = match (mode, style) {
+        (ChatMode::Planning, PromptStyle::Full) => {
+            planning_prompt::generate_planning_prompt(safety)
+        }
+        (ChatMode::Planning, PromptStyle::Concise) => {
+            planning_prompt::generate_concise_planning_prompt(safety)
+        }
+        (ChatMode::Write, PromptStyle::Full) => write_prompt::generate_write_prompt(safety),
+        (ChatMode::Write, PromptStyle::Concise) => {
+            write_prompt::generate_concise_write_prompt(safety)
+        }
     };
 
     append_skill_disclosure_section(&base_prompt, skill_disclosure)
@@ -138,7 +325,8 @@ pub fn append_skill_disclosure_section(
 /// # Arguments
 ///
 /// * `mode` - The current ChatMode (Planning or Write)
-/// * `safety` - The current SafetyMode (AlwaysConfirm or NeverConfirm)
+/// * `safety` - The current SafetyMode (AlwaysConfirm, NeverConfirm, or ConfirmOnce)
+/// * `style` - The PromptStyle (Full or Concise)
 /// * `skill_disclosure` - Optional rendered skill disclosure section
 /// * `active_skills` - Active skill registry for prompt-layer injection
 ///
@@ -150,13 +338,14 @@ pub fn append_skill_disclosure_section(
 ///
 /// ```
 /// use xzatoma::chat_mode::{ChatMode, SafetyMode};
-/// use xzatoma::prompts::build_system_prompt_with_skills;
+/// use xzatoma::prompts::{build_system_prompt_with_skills, PromptStyle};
 /// use xzatoma::skills::activation::ActiveSkillRegistry;
 ///
 /// let registry = ActiveSkillRegistry::new();
 /// let prompt = build_system_prompt_with_skills(
 ///     ChatMode::Planning,
 ///     SafetyMode::AlwaysConfirm,
+///     PromptStyle::Full,
 ///     Some("## Available Skills\n- example_skill: Example description"),
 ///     &registry,
 /// );
@@ -167,10 +356,11 @@ pub fn append_skill_disclosure_section(
 pub fn build_system_prompt_with_skills(
     mode: ChatMode,
     safety: SafetyMode,
+    style: PromptStyle,
     skill_disclosure: Option<&str>,
     active_skills: &ActiveSkillRegistry,
 ) -> String {
-    let prompt = build_system_prompt_with_skill_disclosure(mode, safety, skill_disclosure);
+    let prompt = build_system_prompt_with_skill_disclosure(mode, safety, style, skill_disclosure);
     append_active_skills_section(&prompt, active_skills)
 }
 
@@ -218,7 +408,11 @@ mod tests {
 
     #[test]
     fn test_build_system_prompt_planning_safe() {
-        let prompt = build_system_prompt(ChatMode::Planning, SafetyMode::AlwaysConfirm);
+        let prompt = build_system_prompt(
+            ChatMode::Planning,
+            SafetyMode::AlwaysConfirm,
+            PromptStyle::Full,
+        );
         assert!(prompt.contains("PLANNING"));
         assert!(prompt.to_lowercase().contains("read"));
         assert!(prompt.to_lowercase().contains("cannot"));
@@ -227,14 +421,22 @@ mod tests {
 
     #[test]
     fn test_build_system_prompt_planning_yolo() {
-        let prompt = build_system_prompt(ChatMode::Planning, SafetyMode::NeverConfirm);
+        let prompt = build_system_prompt(
+            ChatMode::Planning,
+            SafetyMode::NeverConfirm,
+            PromptStyle::Full,
+        );
         assert!(prompt.contains("PLANNING"));
         assert!(prompt.to_lowercase().contains("read"));
     }
 
     #[test]
     fn test_build_system_prompt_write_safe() {
-        let prompt = build_system_prompt(ChatMode::Write, SafetyMode::AlwaysConfirm);
+        let prompt = build_system_prompt(
+            ChatMode::Write,
+            SafetyMode::AlwaysConfirm,
+            PromptStyle::Full,
+        );
         assert!(prompt.contains("WRITE"));
         assert!(prompt.to_lowercase().contains("modify"));
         assert!(prompt.to_lowercase().contains("execute"));
@@ -243,7 +445,8 @@ mod tests {
 
     #[test]
     fn test_build_system_prompt_write_yolo() {
-        let prompt = build_system_prompt(ChatMode::Write, SafetyMode::NeverConfirm);
+        let prompt =
+            build_system_prompt(ChatMode::Write, SafetyMode::NeverConfirm, PromptStyle::Full);
         assert!(prompt.contains("WRITE"));
         assert!(prompt.to_lowercase().contains("modify"));
         assert!(prompt.to_lowercase().contains("execute"));
@@ -252,11 +455,15 @@ mod tests {
     #[test]
     fn test_build_system_prompt_not_empty() {
         let modes = vec![ChatMode::Planning, ChatMode::Write];
-        let safeties = vec![SafetyMode::AlwaysConfirm, SafetyMode::NeverConfirm];
+        let safeties = vec![
+            SafetyMode::AlwaysConfirm,
+            SafetyMode::NeverConfirm,
+            SafetyMode::ConfirmOnce,
+        ];
 
         for mode in modes {
             for safety in &safeties {
-                let prompt = build_system_prompt(mode, *safety);
+                let prompt = build_system_prompt(mode, *safety, PromptStyle::Full);
                 assert!(!prompt.is_empty());
                 assert!(
                     prompt.len() > 50,
@@ -270,8 +477,13 @@ mod tests {
 
     #[test]
     fn test_build_system_prompt_includes_safety_instructions() {
-        let safe_prompt = build_system_prompt(ChatMode::Write, SafetyMode::AlwaysConfirm);
-        let yolo_prompt = build_system_prompt(ChatMode::Write, SafetyMode::NeverConfirm);
+        let safe_prompt = build_system_prompt(
+            ChatMode::Write,
+            SafetyMode::AlwaysConfirm,
+            PromptStyle::Full,
+        );
+        let yolo_prompt =
+            build_system_prompt(ChatMode::Write, SafetyMode::NeverConfirm, PromptStyle::Full);
 
         // Prompts should be different based on safety mode
         assert_ne!(safe_prompt, yolo_prompt);
@@ -314,6 +526,7 @@ mod tests {
         let prompt = build_system_prompt_with_skill_disclosure(
             ChatMode::Planning,
             SafetyMode::AlwaysConfirm,
+            PromptStyle::Full,
             Some("## Available Skills\n- example_skill: Example description"),
         );
 
@@ -324,10 +537,15 @@ mod tests {
 
     #[test]
     fn test_build_system_prompt_with_skill_disclosure_omits_empty_section() {
-        let base = build_system_prompt(ChatMode::Write, SafetyMode::AlwaysConfirm);
+        let base = build_system_prompt(
+            ChatMode::Write,
+            SafetyMode::AlwaysConfirm,
+            PromptStyle::Full,
+        );
         let with_empty = build_system_prompt_with_skill_disclosure(
             ChatMode::Write,
             SafetyMode::AlwaysConfirm,
+            PromptStyle::Full,
             Some(""),
         );
 
@@ -348,6 +566,7 @@ mod tests {
         let prompt = build_system_prompt_with_skills(
             ChatMode::Planning,
             SafetyMode::AlwaysConfirm,
+            PromptStyle::Full,
             Some("## Available Skills\n- example_skill: Example description"),
             &registry,
         );
@@ -361,14 +580,135 @@ mod tests {
     fn test_build_system_prompt_with_skills_without_disclosure_matches_base_when_no_active_skills()
     {
         let registry = ActiveSkillRegistry::new();
-        let base = build_system_prompt(ChatMode::Write, SafetyMode::AlwaysConfirm);
+        let base = build_system_prompt(
+            ChatMode::Write,
+            SafetyMode::AlwaysConfirm,
+            PromptStyle::Full,
+        );
         let prompt = build_system_prompt_with_skills(
             ChatMode::Write,
             SafetyMode::AlwaysConfirm,
+            PromptStyle::Full,
             None,
             &registry,
         );
 
         assert_eq!(base, prompt);
     }
+
+    #[test]
+    fn test_prompt_style_parse_str() {
+        assert_eq!(PromptStyle::parse_str("FULL").unwrap(), PromptStyle::Full);
+        assert_eq!(
+            PromptStyle::parse_str("concise").unwrap(),
+            PromptStyle::Concise
+        );
+        assert!(PromptStyle::parse_str("auto").is_err());
+        assert_eq!(PromptStyle::default(), PromptStyle::Full);
+    }
+
+    #[test]
+    fn test_parse_parameter_count_units() {
+        assert_eq!(parse_parameter_count("3.2B"), Some(3_200_000_000));
+        assert_eq!(parse_parameter_count("8.0B"), Some(8_000_000_000));
+        assert_eq!(parse_parameter_count("567M"), Some(567_000_000));
+        assert_eq!(parse_parameter_count("1.5t"), Some(1_500_000_000_000));
+        assert_eq!(parse_parameter_count("1000"), Some(1000));
+        assert_eq!(parse_parameter_count(""), None);
+        assert_eq!(parse_parameter_count("8x7B"), None);
+        assert_eq!(parse_parameter_count("-3B"), None);
+    }
+
+    #[test]
+    fn test_prompt_style_for_parameter_count_threshold() {
+        assert_eq!(
+            PromptStyle::for_parameter_count(CONCISE_MAX_PARAMETERS),
+            PromptStyle::Concise
+        );
+        assert_eq!(
+            PromptStyle::for_parameter_count(CONCISE_MAX_PARAMETERS + 1),
+            PromptStyle::Full
+        );
+    }
+
+    #[test]
+    fn test_concise_prompts_stay_within_token_budget() {
+        for mode in [ChatMode::Planning, ChatMode::Write] {
+            for safety in [
+                SafetyMode::AlwaysConfirm,
+                SafetyMode::NeverConfirm,
+                SafetyMode::ConfirmOnce,
+            ] {
+                let prompt = build_system_prompt(mode, safety, PromptStyle::Concise);
+                let estimated_tokens = (prompt.chars().count() + 3) / 4;
+                assert!(
+                    estimated_tokens <= CONCISE_PROMPT_TOKEN_BUDGET,
+                    "Concise prompt for {:?} {:?} is {} tokens",
+                    mode,
+                    safety,
+                    estimated_tokens
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_concise_planning_prompt_keeps_mode_keywords() {
+        for safety in [SafetyMode::AlwaysConfirm, SafetyMode::NeverConfirm] {
+            let prompt = build_system_prompt(ChatMode::Planning, safety, PromptStyle::Concise);
+            let lower = prompt.to_lowercase();
+            assert!(prompt.contains("PLANNING"));
+            assert!(lower.contains("read"));
+            assert!(lower.contains("cannot"));
+            assert!(lower.contains("modify"));
+            assert!(lower.contains("execute"));
+        }
+        let safe = build_system_prompt(
+            ChatMode::Planning,
+            SafetyMode::AlwaysConfirm,
+            PromptStyle::Concise,
+        );
+        assert!(safe.contains("ENABLED"));
+    }
+
+    #[test]
+    fn test_concise_write_prompt_keeps_mode_keywords() {
+        for safety in [SafetyMode::AlwaysConfirm, SafetyMode::NeverConfirm] {
+            let prompt = build_system_prompt(ChatMode::Write, safety, PromptStyle::Concise);
+            let lower = prompt.to_lowercase();
+            assert!(prompt.contains("WRITE"));
+            assert!(lower.contains("modify"));
+            assert!(lower.contains("execute"));
+            assert!(lower.contains("old_text"));
+        }
+        let safe = build_system_prompt(
+            ChatMode::Write,
+            SafetyMode::AlwaysConfirm,
+            PromptStyle::Concise,
+        );
+        let yolo = build_system_prompt(
+            ChatMode::Write,
+            SafetyMode::NeverConfirm,
+            PromptStyle::Concise,
+        );
+        assert!(safe.to_lowercase().contains("confirm"));
+        assert!(safe.contains("ENABLED"));
+        assert!(yolo.contains("DISABLED (YOLO)"));
+    }
+
+    #[test]
+    fn test_concise_prompt_differs_from_full() {
+        let full = build_system_prompt(
+            ChatMode::Write,
+            SafetyMode::AlwaysConfirm,
+            PromptStyle::Full,
+        );
+        let concise = build_system_prompt(
+            ChatMode::Write,
+            SafetyMode::AlwaysConfirm,
+            PromptStyle::Concise,
+        );
+        assert_ne!(full, concise);
+        assert!(concise.len() < full.len());
+    }
 }
@@ -37,6 +37,9 @@ pub fn generate_planning_prompt(safety: SafetyMode) -> String {
         SafetyMode::NeverConfirm => {
             "Note: Safety mode is DISABLED (YOLO). Operations will proceed without confirmation."
         }
+        SafetyMode::ConfirmOnce => {
+            "Note: Safety mode is CONFIRM ONCE. In Write mode the first action of each kind needs confirmation."
+        }
     };
 
     format!(
@@ -102,11 +105,59 @@ REMEMBER:
 - Create comprehensive plans that leave nothing ambiguous
 - For complex tasks, break them into smaller, clear steps
 - Note any assumptions or open questions in your plan
+- When the plan is complete, call the `finish` tool with status success and the plan as the summary; use needs_input if you must ask the user first
 "#,
         safety_note
     )
 }
 
+/// Generates the concise system prompt for planning mode
+///
+/// A short, imperative variant of [`generate_planning_prompt`] for small local
+/// models that lose track of long instruction blocks. It keeps the read-only
+/// constraints and safety state but drops the format examples.
+///
+/// # Arguments
+///
+/// * `safety` - The current SafetyMode (affects messaging about confirmations)
+///
+/// # Returns
+///
+/// A concise system prompt string for planning mode
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::prompts::planning_prompt::generate_concise_planning_prompt;
+/// use xzatoma::chat_mode::SafetyMode;
+///
+/// let prompt = generate_concise_planning_prompt(SafetyMode::AlwaysConfirm);
+/// assert!(prompt.contains("PLANNING"));
+/// ```
+pub fn generate_concise_planning_prompt(safety: SafetyMode) -> String {
+    let safety_note = match safety {
+        SafetyMode::AlwaysConfirm => "Safety mode: ENABLED.",
+        SafetyMode::NeverConfirm => "Safety mode: DISABLED (YOLO).",
+        SafetyMode::ConfirmOnce => "Safety mode: CONFIRM ONCE.",
+    };
+
+    format!(
+        r#"You are in PLANNING mode. You only read; you never change anything.
+
+You can: read files, list directories, search files.
+You cannot: modify, create, or delete files, or execute commands.
+
+Do this:
+1. Read and search the code you need.
+2. Write a clear, numbered plan in Markdown or YAML.
+3. List assumptions and open questions.
+4. Call finish with the plan as the summary.
+
+{}"#,
+        safety_note
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -132,6 +183,16 @@ mod tests {
         assert!(prompt.contains("DISABLED (YOLO)"));
     }
 
+    #[test]
+    fn test_planning_prompts_ask_for_finish_call() {
+        for prompt in [
+            generate_planning_prompt(SafetyMode::AlwaysConfirm),
+            generate_concise_planning_prompt(SafetyMode::AlwaysConfirm),
+        ] {
+            assert!(prompt.contains("finish"));
+        }
+    }
+
     #[test]
     fn test_planning_prompt_includes_format_info() {
         let prompt = generate_planning_prompt(SafetyMode::AlwaysConfirm);
@@ -155,6 +216,21 @@ mod tests {
         assert!(prompt.len() > 200);
     }
 
+    #[test]
+    fn test_concise_planning_prompt_is_shorter_than_full() {
+        let full = generate_planning_prompt(SafetyMode::AlwaysConfirm);
+        let concise = generate_concise_planning_prompt(SafetyMode::AlwaysConfirm);
+        assert!(concise.len() * 3 < full.len());
+    }
+
+    #[test]
+    fn test_concise_planning_prompt_reflects_safety_mode() {
+        let safe = generate_concise_planning_prompt(SafetyMode::AlwaysConfirm);
+        let yolo = generate_concise_planning_prompt(SafetyMode::NeverConfirm);
+        assert!(safe.contains("ENABLED"));
+        assert!(yolo.contains("DISABLED (YOLO)"));
+    }
+
     #[test]
     fn test_planning_prompt_different_from_write() {
         let planning = generate_planning_prompt(SafetyMode::AlwaysConfirm);
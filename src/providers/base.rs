@@ -3,10 +3,11 @@
 //! The types and trait that previously lived in this file have been moved to
 //! dedicated submodules:
 //!
-//! | Item                   | New canonical location            |
-//! | ---------------------- | --------------------------------- |
-//! | Domain types           | `crate::providers::types`         |
-//! | `Provider` trait       | `crate::providers::trait_mod`     |
+//! | Item                   | New canonical location             |
+//! | ---------------------- | ---------------------------------- |
+//! | Domain types           | `crate::providers::types`          |
+//! | `Provider` trait       | `crate::providers::trait_mod`      |
+//! | Tool argument parsing  | `crate::providers::tool_arguments` |
 //!
 //! This file re-exports everything so that existing code paths
 //! (e.g. `use xzatoma::providers::base::ProviderTool`) continue to compile
@@ -16,6 +17,9 @@
 //! Prefer importing from `crate::providers` directly rather than from this
 //! module.
 
+pub use super::tool_arguments::{
+    parse_tool_arguments, ArgumentRepair, MalformedArguments, ParsedArguments,
+};
 pub use super::trait_mod::Provider;
 pub use super::types::{
     convert_tools_from_json, messages_contain_image_content, validate_message_sequence,
@@ -23,6 +27,6 @@ pub use super::types::{
     ModelInfo, ModelInfoSummary, MultimodalPromptInput, PromptInputPart, ProviderCapabilities,
     ProviderFunction, ProviderFunctionCall, ProviderImagePromptPart, ProviderImagePromptSource,
     ProviderMessage, ProviderMessageContentPart, ProviderMessageContentParts, ProviderPromptInput,
-    ProviderPromptInputPart, ProviderRequest, ProviderTextPromptPart, ProviderTool,
-    ProviderToolCall, TextPromptPart, TokenUsage, ToolCall,
+    ProviderPromptInputPart, ProviderRequest, ProviderRequestOptions, ProviderTextPromptPart,
+    ProviderTool, ProviderToolCall, TextPromptPart, TokenUsage, ToolCall,
 };
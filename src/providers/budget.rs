@@ -0,0 +1,631 @@
+//! Usage logging and the monthly budget
+//!
+//! A [`BudgetProvider`] wraps the provider of a chat, run, or plan. Every
+//! response it forwards is passed to a [`UsageLedger`], which estimates its
+//! cost and appends a [`UsageRecord`] to the `usage_log` table of the
+//! history database. Records are written by a background task, so a slow
+//! database never delays a completion; [`UsageLedger::flush`] waits for
+//! pending records before the process exits.
+//!
+//! The ledger also keeps the estimated cost of the current calendar month
+//! (UTC): the stored total when it started, plus the requests it recorded
+//! since. Once that reaches `budget.monthly_cost_limit`, requests are
+//! refused with [`XzatomaError::BudgetExceeded`] unless overruns are
+//! allowed. Responses served from the response cache never reach the
+//! wrapper, so they are neither logged nor refused.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::config::Config;
+use crate::error::{Result, XzatomaError};
+use crate::format::format_cost;
+use crate::storage::types::UsageRecord;
+use crate::storage::SqliteStorage;
+
+use super::trait_mod::Provider;
+use super::types::{
+    CompletionResponse, Message, ModelInfo, ModelInfoSummary, ProviderCapabilities, TokenUsage,
+    ToolCallOptions,
+};
+
+/// Source of the current time, replaceable in tests
+pub trait Clock: Send + Sync {
+    /// Returns the current time
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// [`Clock`] reading the system time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Returns the start of the UTC month containing `time`
+///
+/// # Examples
+///
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use xzatoma::providers::budget::{month_start, next_month};
+///
+/// let time = Utc.with_ymd_and_hms(2025, 12, 31, 23, 59, 59).unwrap();
+/// let start = month_start(time);
+/// assert_eq!(start, Utc.with_ymd_and_hms(2025, 12, 1, 0, 0, 0).unwrap());
+/// assert_eq!(next_month(start), Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap());
+/// ```
+pub fn month_start(time: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(time.year(), time.month(), 1, 0, 0, 0)
+        .single()
+        .unwrap_or(time)
+}
+
+/// Returns the start of the month after the one starting at `start`
+pub fn next_month(start: DateTime<Utc>) -> DateTime<Utc> {
+    let (year, month) = if start.month() == 12 {
+        (start.year() + 1, 1)
+    } else {
+        (start.year(), start.month() + 1)
+    };
+    Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0)
+        .single()
+        .unwrap_or(start)
+}
+
+/// Estimates the cost of one request in US dollars
+///
+/// Prompt tokens are priced with `input_pricing` and completion tokens with
+/// `completion_pricing`, falling back to the input price. Both are USD per
+/// million tokens, keyed by model name. Unpriced tokens cost nothing.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use xzatoma::providers::budget::estimate_cost;
+/// use xzatoma::providers::TokenUsage;
+///
+/// let input = HashMap::from([("gpt-5-mini".to_string(), 0.25)]);
+/// let completion = HashMap::from([("gpt-5-mini".to_string(), 2.0)]);
+/// let cost = estimate_cost("gpt-5-mini", &TokenUsage::new(1_000_000, 500_000), &input, &completion);
+/// assert!((cost - 1.25).abs() < 1e-9);
+/// ```
+pub fn estimate_cost(
+    model: &str,
+    usage: &TokenUsage,
+    input_pricing: &HashMap<String, f64>,
+    completion_pricing: &HashMap<String, f64>,
+) -> f64 {
+    let input_price = input_pricing.get(model).copied();
+    let completion_price = completion_pricing.get(model).copied().or(input_price);
+    (usage.prompt_tokens as f64 * input_price.unwrap_or(0.0)
+        + usage.completion_tokens as f64 * completion_price.unwrap_or(0.0))
+        / 1_000_000.0
+}
+
+/// Month-to-date spending against the configured limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BudgetStatus {
+    /// Estimated cost of this month's requests
+    pub spent: f64,
+    /// `budget.monthly_cost_limit`, if set
+    pub limit: Option<f64>,
+    /// `budget.warn_at_percent`
+    pub warn_at_percent: u8,
+}
+
+enum LedgerMessage {
+    Record(UsageRecord),
+    Flush(oneshot::Sender<()>),
+}
+
+#[derive(Debug)]
+struct LedgerState {
+    month_start: DateTime<Utc>,
+    spent: f64,
+    warned: bool,
+    warned_over: bool,
+}
+
+/// Records provider usage and tracks spending against the monthly budget
+pub struct UsageLedger {
+    storage: SqliteStorage,
+    provider: String,
+    session_id: String,
+    limit: Option<f64>,
+    warn_at_percent: u8,
+    allow_overrun: bool,
+    input_pricing: HashMap<String, f64>,
+    completion_pricing: HashMap<String, f64>,
+    clock: Arc<dyn Clock>,
+    sender: mpsc::UnboundedSender<LedgerMessage>,
+    state: Mutex<LedgerState>,
+}
+
+impl UsageLedger {
+    /// Loads this month's spending and starts the background writer
+    ///
+    /// Must be called from within a Tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - History database holding the usage log
+    /// * `config` - Configuration with the budget and prices
+    /// * `provider` - Provider name stored with each record
+    /// * `session_id` - Session identifier stored with each record
+    /// * `clock` - Source of record timestamps and the current month
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if this month's usage cannot be read.
+    pub fn start(
+        storage: SqliteStorage,
+        config: &Config,
+        provider: &str,
+        session_id: &str,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Arc<Self>> {
+        let month_start = month_start(clock.now());
+        let spent = storage
+            .usage_total(month_start, next_month(month_start))?
+            .estimated_cost;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(write_usage(storage.clone(), receiver));
+
+        let ledger = Self {
+            storage,
+            provider: provider.to_string(),
+            session_id: session_id.to_string(),
+            limit: config.budget.monthly_cost_limit,
+            warn_at_percent: config.budget.warn_at_percent,
+            allow_overrun: config.budget.allow_overrun,
+            input_pricing: config.agent.preflight.pricing.clone(),
+            completion_pricing: config.budget.completion_pricing.clone(),
+            clock,
+            sender,
+            state: Mutex::new(LedgerState {
+                month_start,
+                spent,
+                warned: false,
+                warned_over: false,
+            }),
+        };
+        Ok(Arc::new(ledger))
+    }
+
+    /// Refuses a request when this month's spending reached the limit
+    ///
+    /// # Errors
+    ///
+    /// Returns [`XzatomaError::BudgetExceeded`] past the limit, unless
+    /// overruns are allowed.
+    pub fn check(&self) -> Result<()> {
+        let Some(limit) = self.limit else {
+            return Ok(());
+        };
+        let spent = self.status().spent;
+        if spent >= limit && !self.allow_overrun {
+            return Err(XzatomaError::BudgetExceeded { spent, limit });
+        }
+        Ok(())
+    }
+
+    /// Logs the usage of one response and adds its cost to this month
+    pub fn record(&self, model: &str, usage: &TokenUsage) {
+        let estimated_cost =
+            estimate_cost(model, usage, &self.input_pricing, &self.completion_pricing);
+        let timestamp = self.clock.now();
+        {
+            let mut state = self.state();
+            self.roll_over(&mut state, timestamp);
+            state.spent += estimated_cost;
+        }
+
+        let record = UsageRecord {
+            timestamp,
+            provider: self.provider.clone(),
+            model: model.to_string(),
+            session_id: self.session_id.clone(),
+            prompt_tokens: usage.prompt_tokens as u64,
+            completion_tokens: usage.completion_tokens as u64,
+            estimated_cost,
+        };
+        if self.sender.send(LedgerMessage::Record(record)).is_err() {
+            tracing::warn!("Usage log writer stopped; usage record dropped");
+        }
+    }
+
+    /// Returns this month's spending
+    pub fn status(&self) -> BudgetStatus {
+        let mut state = self.state();
+        self.roll_over(&mut state, self.clock.now());
+        BudgetStatus {
+            spent: state.spent,
+            limit: self.limit,
+            warn_at_percent: self.warn_at_percent,
+        }
+    }
+
+    /// Returns a warning the first time spending crosses a threshold
+    ///
+    /// One warning is returned when `warn_at_percent` of the limit is
+    /// reached and another when the limit is, each once per month.
+    pub fn take_warning(&self) -> Option<String> {
+        let limit = self.limit?;
+        let mut state = self.state();
+        self.roll_over(&mut state, self.clock.now());
+
+        if state.spent >= limit {
+            if state.warned_over {
+                return None;
+            }
+            state.warned_over = true;
+            state.warned = true;
+            let consequence = if self.allow_overrun {
+                "Requests continue because budget overruns are allowed."
+            } else {
+                "Further requests are refused; pass --ignore-budget to continue."
+            };
+            return Some(format!(
+                "Monthly budget reached: {} of {} spent this month. {}",
+                format_cost(state.spent),
+                format_cost(limit),
+                consequence
+            ));
+        }
+
+        let percent = state.spent / limit * 100.0;
+        if percent >= f64::from(self.warn_at_percent) && !state.warned {
+            state.warned = true;
+            return Some(format!(
+                "Budget warning: {} of the {} monthly limit spent ({:.0}%).",
+                format_cost(state.spent),
+                format_cost(limit),
+                percent
+            ));
+        }
+        None
+    }
+
+    /// Waits until every record logged so far is written
+    pub async fn flush(&self) {
+        let (done, wait) = oneshot::channel();
+        if self.sender.send(LedgerMessage::Flush(done)).is_ok() {
+            let _ = wait.await;
+        }
+    }
+
+    /// Starts a new month when `now` is past the current one
+    ///
+    /// Records from this process are counted as they are logged, so the
+    /// stored total of the new month only holds other sessions' requests.
+    fn roll_over(&self, state: &mut LedgerState, now: DateTime<Utc>) {
+        let current = month_start(now);
+        if current == state.month_start {
+            return;
+        }
+        state.spent = match self.storage.usage_total(current, next_month(current)) {
+            Ok(total) => total.estimated_cost,
+            Err(e) => {
+                tracing::warn!("Failed to read this month's usage: {}", e);
+                0.0
+            }
+        };
+        state.month_start = current;
+        state.warned = false;
+        state.warned_over = false;
+    }
+
+    fn state(&self) -> MutexGuard<'_, LedgerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Writes queued records in batches until every sender is dropped
+async fn write_usage(storage: SqliteStorage, mut receiver: mpsc::UnboundedReceiver<LedgerMessage>) {
+    while let Some(message) = receiver.recv().await {
+        let mut records = Vec::new();
+        let mut flushes = Vec::new();
+        let mut next = Some(message);
+        while let Some(message) = next {
+            match message {
+                LedgerMessage::Record(record) => records.push(record),
+                LedgerMessage::Flush(done) => flushes.push(done),
+            }
+            next = receiver.try_recv().ok();
+        }
+
+        if !records.is_empty() {
+            let storage = storage.clone();
+            match tokio::task::spawn_blocking(move || storage.save_usage_records(&records)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => tracing::warn!("Failed to write usage records: {}", e),
+                Err(e) => tracing::warn!("Usage log writer failed: {}", e),
+            }
+        }
+        for done in flushes {
+            let _ = done.send(());
+        }
+    }
+}
+
+/// Provider wrapper that enforces the budget and logs usage
+///
+/// Both `complete` and `chat_completion_stream` are covered. Failed
+/// requests and responses without token usage are not logged.
+pub struct BudgetProvider<P: Provider> {
+    inner: P,
+    ledger: Arc<UsageLedger>,
+}
+
+impl<P: Provider> BudgetProvider<P> {
+    /// Wraps `inner`, logging its usage with `ledger`
+    pub fn new(inner: P, ledger: Arc<UsageLedger>) -> Self {
+        Self { inner, ledger }
+    }
+
+    async fn metered(
+        &self,
+        messages: &[Message],
+        tools: &[Value],
+        stream: bool,
+    ) -> Result<CompletionResponse> {
+        self.ledger.check()?;
+        let response = if stream {
+            self.inner.chat_completion_stream(messages, tools).await?
+        } else {
+            self.inner.complete(messages, tools).await?
+        };
+        if let Some(usage) = &response.usage {
+            self.ledger.record(&self.inner.get_current_model(), usage);
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for BudgetProvider<P> {
+    fn is_authenticated(&self) -> bool {
+        self.inner.is_authenticated()
+    }
+
+    fn current_model(&self) -> Option<&str> {
+        self.inner.current_model()
+    }
+
+    fn set_model(&mut self, model: &str) {
+        self.inner.set_model(model)
+    }
+
+    async fn fetch_models(&self) -> Result<Vec<ModelInfo>> {
+        self.inner.fetch_models().await
+    }
+
+    async fn complete(&self, messages: &[Message], tools: &[Value]) -> Result<CompletionResponse> {
+        self.metered(messages, tools, false).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        self.inner.list_models().await
+    }
+
+    async fn get_model_info(&self, model_name: &str) -> Result<ModelInfo> {
+        self.inner.get_model_info(model_name).await
+    }
+
+    fn get_current_model(&self) -> String {
+        self.inner.get_current_model()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        messages: &[Message],
+        tools: &[Value],
+    ) -> Result<CompletionResponse> {
+        self.metered(messages, tools, true).await
+    }
+
+    fn get_provider_capabilities(&self) -> ProviderCapabilities {
+        self.inner.get_provider_capabilities()
+    }
+
+    fn set_thinking_effort(&self, effort: Option<&str>) -> Result<()> {
+        self.inner.set_thinking_effort(effort)
+    }
+
+    fn set_tool_call_options(&self, options: ToolCallOptions) -> Result<()> {
+        self.inner.set_tool_call_options(options)
+    }
+
+    fn set_temperature(&self, temperature: Option<f32>) -> Result<()> {
+        self.inner.set_temperature(temperature)
+    }
+
+    fn needs_tool_call_nudge(&self) -> bool {
+        self.inner.needs_tool_call_nudge()
+    }
+
+    async fn list_models_summary(&self) -> Result<Vec<ModelInfoSummary>> {
+        self.inner.list_models_summary().await
+    }
+
+    async fn get_model_info_summary(&self, model_name: &str) -> Result<ModelInfoSummary> {
+        self.inner.get_model_info_summary(model_name).await
+    }
+}
+
+/// Wraps `provider` in a [`BudgetProvider`] when a ledger is running
+pub fn wrap_with_budget(
+    provider: Box<dyn Provider>,
+    ledger: Option<&Arc<UsageLedger>>,
+) -> Box<dyn Provider> {
+    match ledger {
+        Some(ledger) => Box::new(BudgetProvider::new(provider, Arc::clone(ledger))),
+        None => provider,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockProvider;
+    use tempfile::TempDir;
+
+    /// Clock that only moves when told to
+    struct FakeClock(Mutex<DateTime<Utc>>);
+
+    impl FakeClock {
+        fn at(value: &str) -> Arc<Self> {
+            Arc::new(Self(Mutex::new(time(value))))
+        }
+
+        fn set(&self, value: &str) {
+            *self.0.lock().unwrap() = time(value);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> DateTime<Utc> {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn time(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    /// Provider that answers every request with one million prompt tokens
+    fn metered_provider() -> MockProvider {
+        MockProvider::new()
+            .with_model("fake-model")
+            .with_fallback(CompletionResponse::with_usage(
+                Message::assistant("done"),
+                TokenUsage::new(1_000_000, 0),
+            ))
+    }
+
+    fn budget_config(limit: f64) -> Config {
+        let mut config = Config::default();
+        config.budget.monthly_cost_limit = Some(limit);
+        config
+            .agent
+            .preflight
+            .pricing
+            .insert("fake-model".to_string(), 1.0);
+        config
+    }
+
+    fn usage_record(timestamp: &str, cost: f64) -> UsageRecord {
+        UsageRecord {
+            timestamp: time(timestamp),
+            provider: "ollama".to_string(),
+            model: "fake-model".to_string(),
+            session_id: "earlier".to_string(),
+            prompt_tokens: 10,
+            completion_tokens: 10,
+            estimated_cost: cost,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limit_refuses_requests_until_the_next_month() {
+        let dir = TempDir::new().unwrap();
+        let storage = SqliteStorage::new_with_path(dir.path().join("history.db")).unwrap();
+        storage
+            .save_usage_records(&[
+                usage_record("2025-01-31T12:00:00Z", 50.0),
+                usage_record("2025-02-10T08:00:00Z", 8.0),
+            ])
+            .unwrap();
+
+        let clock = FakeClock::at("2025-02-27T09:00:00Z");
+        let ledger = UsageLedger::start(
+            storage.clone(),
+            &budget_config(10.0),
+            "ollama",
+            "session-1",
+            clock.clone(),
+        )
+        .unwrap();
+        let provider = BudgetProvider::new(metered_provider(), Arc::clone(&ledger));
+        assert_eq!(ledger.status().spent, 8.0);
+        assert!(ledger.take_warning().unwrap().starts_with("Budget warning"));
+        assert!(ledger.take_warning().is_none());
+
+        provider.complete(&[], &[]).await.unwrap();
+        provider.complete(&[], &[]).await.unwrap();
+        assert!(ledger
+            .take_warning()
+            .unwrap()
+            .starts_with("Monthly budget reached"));
+        let error = provider.complete(&[], &[]).await.unwrap_err();
+        assert!(matches!(
+            error,
+            XzatomaError::BudgetExceeded { spent, limit } if spent == 10.0 && limit == 10.0
+        ));
+
+        ledger.flush().await;
+        let february = time("2025-02-01T00:00:00Z");
+        let stored = storage.usage_total(february, next_month(february)).unwrap();
+        assert_eq!(stored.requests, 3);
+        assert_eq!(stored.estimated_cost, 10.0);
+
+        clock.set("2025-03-01T00:00:00Z");
+        assert_eq!(ledger.status().spent, 0.0);
+        provider.complete(&[], &[]).await.unwrap();
+        assert!(ledger.take_warning().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_allow_overrun_only_warns() {
+        let dir = TempDir::new().unwrap();
+        let storage = SqliteStorage::new_with_path(dir.path().join("history.db")).unwrap();
+        let mut config = budget_config(0.5);
+        config.budget.allow_overrun = true;
+        let ledger = UsageLedger::start(
+            storage,
+            &config,
+            "ollama",
+            "session-1",
+            FakeClock::at("2025-01-15T00:00:00Z"),
+        )
+        .unwrap();
+        let provider = BudgetProvider::new(metered_provider(), Arc::clone(&ledger));
+
+        provider.complete(&[], &[]).await.unwrap();
+        provider.complete(&[], &[]).await.unwrap();
+
+        assert_eq!(ledger.status().spent, 2.0);
+        assert!(ledger
+            .take_warning()
+            .unwrap()
+            .contains("overruns are allowed"));
+    }
+
+    #[test]
+    fn test_completion_price_falls_back_to_input_price() {
+        let input = HashMap::from([("a".to_string(), 2.0)]);
+        let completion = HashMap::from([("b".to_string(), 4.0)]);
+        let usage = TokenUsage::new(500_000, 500_000);
+
+        assert_eq!(estimate_cost("a", &usage, &input, &completion), 2.0);
+        assert_eq!(estimate_cost("b", &usage, &input, &completion), 2.0);
+        assert_eq!(estimate_cost("c", &usage, &input, &completion), 0.0);
+    }
+}
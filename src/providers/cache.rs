@@ -0,0 +1,887 @@
+//! Provider response cache
+//!
+//! Re-running nearly identical requests while iterating on prompts or plans
+//! pays for every call. [`CachingProvider`] wraps any [`Provider`] and stores
+//! completed responses on disk in a [`ResponseCache`], keyed by a SHA-256 hash
+//! of the provider type, model, messages, tools, and temperature. An identical
+//! request is answered from disk without a network call, and the returned
+//! [`CompletionResponse`] has `cached` set.
+//!
+//! The cache is bypassed when:
+//!
+//! - streaming is requested through `chat_completion_stream`, or
+//! - the wrapper was given a temperature above zero,
+//!
+//! unless `force` is set. Entries expire after the configured TTL, and the
+//! cache is trimmed to `max_size_mb` after every write, oldest entries first.
+//! Cache failures never fail a completion; they are logged and the request
+//! goes to the wrapped provider.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use xzatoma::providers::cache::{CachingProvider, ResponseCache};
+//! use xzatoma::providers::{CompletionResponse, Message, ModelInfo, Provider};
+//! use xzatoma::error::Result;
+//! use async_trait::async_trait;
+//!
+//! struct EchoProvider;
+//!
+//! #[async_trait]
+//! impl Provider for EchoProvider {
+//!     fn is_authenticated(&self) -> bool { true }
+//!     fn current_model(&self) -> Option<&str> { Some("echo") }
+//!     fn set_model(&mut self, _model: &str) {}
+//!     async fn fetch_models(&self) -> Result<Vec<ModelInfo>> { Ok(vec![]) }
+//!     async fn complete(&self, _messages: &[Message], _tools: &[serde_json::Value]) -> Result<CompletionResponse> {
+//!         Ok(CompletionResponse::new(Message::assistant("echo")))
+//!     }
+//! }
+//!
+//! # tokio_test::block_on(async {
+//! let dir = tempfile::tempdir()?;
+//! let cache = ResponseCache::new(dir.path().to_path_buf(), 1024 * 1024, Duration::from_secs(60));
+//! let provider = CachingProvider::new(EchoProvider, "echo", cache);
+//!
+//! let messages = vec![Message::user("hello")];
+//! assert!(!provider.complete(&messages, &[]).await?.cached);
+//! assert!(provider.complete(&messages, &[]).await?.cached);
+//! assert_eq!(provider.counters().hits(), 1);
+//! # Ok::<(), anyhow::Error>(())
+//! # })?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::ProviderCacheConfig;
+use crate::error::{Result, XzatomaError};
+use crate::paths::Paths;
+
+use super::trait_mod::Provider;
+use super::types::{
+    CompletionResponse, FinishReason, Message, ModelInfo, ModelInfoSummary, ProviderCapabilities,
+    TokenUsage, ToolCallOptions,
+};
+
+/// File extension of cache entries
+const ENTRY_EXTENSION: &str = "json";
+
+/// A cached completion together with the request it answers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// When the response was stored
+    pub created_at: DateTime<Utc>,
+    /// Provider type that produced the response
+    pub provider: String,
+    /// Model the request was sent to
+    pub model: String,
+    /// The assistant message
+    pub message: Message,
+    /// Token usage reported for the original request
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+    /// Model reported in the response, if any
+    #[serde(default)]
+    pub response_model: Option<String>,
+    /// Reasoning content, if any
+    #[serde(default)]
+    pub reasoning: Option<String>,
+    /// Reason the model stopped generating
+    #[serde(default)]
+    pub finish_reason: FinishReason,
+}
+
+impl CacheEntry {
+    fn from_response(provider: &str, model: &str, response: &CompletionResponse) -> Self {
+        Self {
+            created_at: Utc::now(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            message: response.message.clone(),
+            usage: response.usage,
+            response_model: response.model.clone(),
+            reasoning: response.reasoning.clone(),
+            finish_reason: response.finish_reason,
+        }
+    }
+
+    fn into_response(self) -> CompletionResponse {
+        let mut response = CompletionResponse::new(self.message)
+            .with_finish_reason(self.finish_reason)
+            .mark_cached();
+        response.usage = self.usage;
+        response.model = self.response_model;
+        response.reasoning = self.reasoning;
+        response
+    }
+}
+
+/// Summary of the cache contents
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    /// Number of stored entries, including expired ones
+    pub entries: usize,
+    /// Total size of all entries in bytes
+    pub size_bytes: u64,
+    /// Entries older than the TTL that the next write removes
+    pub expired: usize,
+    /// Write time of the oldest entry
+    pub oldest: Option<DateTime<Utc>>,
+    /// Write time of the newest entry
+    pub newest: Option<DateTime<Utc>>,
+}
+
+/// Cache outcomes counted by a [`CachingProvider`]
+#[derive(Debug, Default)]
+pub struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    bypassed: AtomicU64,
+}
+
+impl CacheCounters {
+    /// Requests answered from the cache
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Requests sent to the wrapped provider and then stored
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Requests that skipped the cache because of streaming or temperature
+    pub fn bypassed(&self) -> u64 {
+        self.bypassed.load(Ordering::Relaxed)
+    }
+
+    fn record(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A cache entry file on disk
+struct EntryFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// On-disk store of cached provider responses
+///
+/// Each entry is a JSON file named after its key. Expiry and size eviction
+/// use the file's write time.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    max_size_bytes: u64,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    /// Creates a cache in `dir`
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory holding the entries; created on first write
+    /// * `max_size_bytes` - Total size the cache is trimmed to after writes
+    /// * `ttl` - Age after which an entry is a miss
+    pub fn new(dir: PathBuf, max_size_bytes: u64, ttl: Duration) -> Self {
+        Self {
+            dir,
+            max_size_bytes,
+            ttl,
+        }
+    }
+
+    /// Creates the cache described by `config`
+    ///
+    /// Without a configured directory the cache lives in
+    /// [`Paths::response_cache_dir`].
+    pub fn from_config(config: &ProviderCacheConfig, paths: &Paths) -> Self {
+        let dir = match &config.dir {
+            Some(dir) => PathBuf::from(dir),
+            None => paths.response_cache_dir(),
+        };
+        Self::new(
+            dir,
+            config.max_size_mb.saturating_mul(1024 * 1024),
+            Duration::from_secs(config.ttl_seconds),
+        )
+    }
+
+    /// Returns the cache directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the size limit in bytes
+    pub fn max_size_bytes(&self) -> u64 {
+        self.max_size_bytes
+    }
+
+    /// Computes the cache key of a request
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - Provider type
+    /// * `model` - Model the request is sent to
+    /// * `messages` - Conversation messages
+    /// * `tools` - Tool schemas
+    /// * `temperature` - Sampling temperature, if one is set
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request cannot be serialized.
+    pub fn key(
+        provider: &str,
+        model: &str,
+        messages: &[Message],
+        tools: &[serde_json::Value],
+        temperature: Option<f32>,
+    ) -> Result<String> {
+        let request = serde_json::json!({
+            "provider": provider,
+            "model": model,
+            "messages": messages,
+            "tools": tools,
+            "temperature": temperature,
+        });
+        let digest = Sha256::digest(serde_json::to_vec(&request)?);
+        Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", key, ENTRY_EXTENSION))
+    }
+
+    fn is_expired(&self, modified: SystemTime, now: SystemTime) -> bool {
+        now.duration_since(modified).unwrap_or_default() > self.ttl
+    }
+
+    /// Looks up an entry, removing it when it has expired
+    ///
+    /// Unreadable entries are treated as misses.
+    pub fn get(&self, key: &str) -> Option<CacheEntry> {
+        let path = self.entry_path(key);
+        let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok()?;
+        if self.is_expired(modified, SystemTime::now()) {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+
+        let contents = fs::read(&path).ok()?;
+        match serde_json::from_slice(&contents) {
+            Ok(entry) => Some(entry),
+            Err(error) => {
+                tracing::warn!(path = %path.display(), "Ignoring unreadable cache entry: {}", error);
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    /// Stores an entry and then evicts expired and excess entries
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Storage` if the entry cannot be written.
+    pub fn put(&self, key: &str, entry: &CacheEntry) -> Result<()> {
+        fs::create_dir_all(&self.dir).map_err(|error| {
+            XzatomaError::Storage(format!(
+                "Failed to create provider cache directory '{}': {}",
+                self.dir.display(),
+                error
+            ))
+        })?;
+
+        // Write to a temporary file first so readers never see a partial entry
+        let path = self.entry_path(key);
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec(entry)?)
+            .and_then(|_| fs::rename(&temp_path, &path))
+            .map_err(|error| {
+                XzatomaError::Storage(format!(
+                    "Failed to write provider cache entry '{}': {}",
+                    path.display(),
+                    error
+                ))
+            })?;
+
+        self.evict()?;
+        Ok(())
+    }
+
+    /// Removes expired entries, then the oldest entries until the cache fits
+    /// within its size limit
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of removed entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be read.
+    pub fn evict(&self) -> Result<usize> {
+        let now = SystemTime::now();
+        let mut entries = self.entry_files()?;
+        entries.sort_by_key(|entry| entry.modified);
+
+        let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+        let mut removed = 0;
+        for entry in entries {
+            if !self.is_expired(entry.modified, now) && total <= self.max_size_bytes {
+                continue;
+            }
+            if fs::remove_file(&entry.path).is_ok() {
+                total = total.saturating_sub(entry.size);
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Summarizes the cache contents
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be read.
+    pub fn stats(&self) -> Result<CacheStats> {
+        let now = SystemTime::now();
+        let entries = self.entry_files()?;
+        let modified = entries.iter().map(|entry| entry.modified);
+
+        Ok(CacheStats {
+            entries: entries.len(),
+            size_bytes: entries.iter().map(|entry| entry.size).sum(),
+            expired: entries
+                .iter()
+                .filter(|entry| self.is_expired(entry.modified, now))
+                .count(),
+            oldest: modified.clone().min().map(DateTime::<Utc>::from),
+            newest: modified.max().map(DateTime::<Utc>::from),
+        })
+    }
+
+    /// Removes every entry
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of removed entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be read or an entry
+    /// cannot be removed.
+    pub fn clear(&self) -> Result<usize> {
+        let entries = self.entry_files()?;
+        for entry in &entries {
+            fs::remove_file(&entry.path).map_err(|error| {
+                XzatomaError::Storage(format!(
+                    "Failed to remove provider cache entry '{}': {}",
+                    entry.path.display(),
+                    error
+                ))
+            })?;
+        }
+        Ok(entries.len())
+    }
+
+    /// Lists the entry files; a missing directory is an empty cache
+    fn entry_files(&self) -> Result<Vec<EntryFile>> {
+        let read_dir = match fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => {
+                return Err(XzatomaError::Storage(format!(
+                    "Failed to read provider cache directory '{}': {}",
+                    self.dir.display(),
+                    error
+                )))
+            }
+        };
+
+        let mut entries = Vec::new();
+        for dir_entry in read_dir.flatten() {
+            let path = dir_entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(ENTRY_EXTENSION) {
+                continue;
+            }
+            let Ok(metadata) = dir_entry.metadata() else {
+                continue;
+            };
+            entries.push(EntryFile {
+                path,
+                size: metadata.len(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+        Ok(entries)
+    }
+}
+
+/// Provider wrapper that answers repeated requests from a [`ResponseCache`]
+///
+/// Every other [`Provider`] method is forwarded to the wrapped provider, so
+/// the wrapper composes with other wrappers and with `Box<dyn Provider>`.
+pub struct CachingProvider<P> {
+    inner: P,
+    provider_type: String,
+    cache: ResponseCache,
+    temperature: Mutex<Option<f32>>,
+    force: bool,
+    counters: Arc<CacheCounters>,
+}
+
+impl<P: Provider> CachingProvider<P> {
+    /// Wraps `inner`, storing responses in `cache`
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - Provider that answers cache misses
+    /// * `provider_type` - Provider type recorded in the cache key
+    /// * `cache` - Response store
+    pub fn new(inner: P, provider_type: impl Into<String>, cache: ResponseCache) -> Self {
+        Self {
+            inner,
+            provider_type: provider_type.into(),
+            cache,
+            temperature: Mutex::new(None),
+            force: false,
+            counters: Arc::new(CacheCounters::default()),
+        }
+    }
+
+    /// Sets the sampling temperature of the wrapped provider's requests
+    ///
+    /// The temperature is part of the cache key. Requests with a temperature
+    /// above zero bypass the cache unless `force` is set. Later calls to
+    /// [`Provider::set_temperature`] replace it.
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        *self
+            .temperature
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner()) = temperature;
+        self
+    }
+
+    /// Caches streaming and non-zero-temperature requests too
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Returns the shared hit, miss, and bypass counters
+    pub fn counters(&self) -> Arc<CacheCounters> {
+        Arc::clone(&self.counters)
+    }
+
+    /// Returns the wrapped provider
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    fn temperature(&self) -> Option<f32> {
+        *self.temperature.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn bypasses_temperature(&self) -> bool {
+        !self.force
+            && self
+                .temperature()
+                .is_some_and(|temperature| temperature > 0.0)
+    }
+
+    async fn complete_cached(
+        &self,
+        messages: &[Message],
+        tools: &[serde_json::Value],
+    ) -> Result<CompletionResponse> {
+        let model = self.inner.get_current_model();
+        let key = match ResponseCache::key(
+            &self.provider_type,
+            &model,
+            messages,
+            tools,
+            self.temperature(),
+        ) {
+            Ok(key) => key,
+            Err(error) => {
+                tracing::warn!("Skipping provider cache: {}", error);
+                CacheCounters::record(&self.counters.bypassed);
+                return self.inner.complete(messages, tools).await;
+            }
+        };
+
+        if let Some(entry) = self.cache.get(&key) {
+            CacheCounters::record(&self.counters.hits);
+            tracing::debug!(model = %model, key = %key, "Provider cache hit");
+            return Ok(entry.into_response());
+        }
+
+        CacheCounters::record(&self.counters.misses);
+        let response = self.inner.complete(messages, tools).await?;
+        let entry = CacheEntry::from_response(&self.provider_type, &model, &response);
+        if let Err(error) = self.cache.put(&key, &entry) {
+            tracing::warn!("Failed to store provider response in cache: {}", error);
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for CachingProvider<P> {
+    fn is_authenticated(&self) -> bool {
+        self.inner.is_authenticated()
+    }
+
+    fn current_model(&self) -> Option<&str> {
+        self.inner.current_model()
+    }
+
+    fn set_model(&mut self, model: &str) {
+        self.inner.set_model(model)
+    }
+
+    async fn fetch_models(&self) -> Result<Vec<ModelInfo>> {
+        self.inner.fetch_models().await
+    }
+
+    async fn complete(
+        &self,
+        messages: &[Message],
+        tools: &[serde_json::Value],
+    ) -> Result<CompletionResponse> {
+        if self.bypasses_temperature() {
+            CacheCounters::record(&self.counters.bypassed);
+            return self.inner.complete(messages, tools).await;
+        }
+        self.complete_cached(messages, tools).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        self.inner.list_models().await
+    }
+
+    async fn get_model_info(&self, model_name: &str) -> Result<ModelInfo> {
+        self.inner.get_model_info(model_name).await
+    }
+
+    fn get_current_model(&self) -> String {
+        self.inner.get_current_model()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        messages: &[Message],
+        tools: &[serde_json::Value],
+    ) -> Result<CompletionResponse> {
+        if !self.force {
+            CacheCounters::record(&self.counters.bypassed);
+            return self.inner.chat_completion_stream(messages, tools).await;
+        }
+        self.complete(messages, tools).await
+    }
+
+    fn get_provider_capabilities(&self) -> ProviderCapabilities {
+        self.inner.get_provider_capabilities()
+    }
+
+    fn set_thinking_effort(&self, effort: Option<&str>) -> Result<()> {
+        self.inner.set_thinking_effort(effort)
+    }
+
+    fn set_tool_call_options(&self, options: ToolCallOptions) -> Result<()> {
+        self.inner.set_tool_call_options(options)
+    }
+
+    fn set_temperature(&self, temperature: Option<f32>) -> Result<()> {
+        self.inner.set_temperature(temperature)?;
+        *self.temperature.lock().unwrap_or_else(|e| e.into_inner()) = temperature;
+        Ok(())
+    }
+
+    fn needs_tool_call_nudge(&self) -> bool {
+        self.inner.needs_tool_call_nudge()
+    }
+
+    async fn list_models_summary(&self) -> Result<Vec<ModelInfoSummary>> {
+        self.inner.list_models_summary().await
+    }
+
+    async fn get_model_info_summary(&self, model_name: &str) -> Result<ModelInfoSummary> {
+        self.inner.get_model_info_summary(model_name).await
+    }
+}
+
+/// Wraps `provider` in a [`CachingProvider`] when the cache is enabled
+///
+/// # Arguments
+///
+/// * `provider` - Provider created by the factory
+/// * `provider_type` - Provider type recorded in the cache key
+/// * `config` - Cache configuration
+/// * `paths` - Resolved directories; the cache defaults to the cache directory
+///
+/// # Returns
+///
+/// Returns the provider to use and, when caching, its counters.
+pub fn wrap_with_cache(
+    provider: Box<dyn Provider>,
+    provider_type: &str,
+    config: &ProviderCacheConfig,
+    paths: &Paths,
+) -> (Box<dyn Provider>, Option<Arc<CacheCounters>>) {
+    if !config.enabled {
+        return (provider, None);
+    }
+
+    let cache = ResponseCache::from_config(config, paths);
+    tracing::debug!(dir = %cache.dir().display(), "Provider response cache enabled");
+    let caching = CachingProvider::new(provider, provider_type, cache).with_force(config.force);
+    let counters = caching.counters();
+    (Box::new(caching), Some(counters))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PathsConfig;
+    use crate::paths::{CACHE_DIR_ENV, DATA_DIR_ENV};
+    use crate::testing::MockProvider;
+    use tempfile::TempDir;
+
+    /// Provider that answers every request with the same response
+    fn answering_provider() -> MockProvider {
+        MockProvider::new()
+            .with_model("fake-model")
+            .with_fallback(CompletionResponse::with_usage(
+                Message::assistant("response"),
+                TokenUsage::new(10, 5),
+            ))
+    }
+
+    fn cache_in(dir: &TempDir, max_size_bytes: u64, ttl: Duration) -> ResponseCache {
+        ResponseCache::new(dir.path().join("cache"), max_size_bytes, ttl)
+    }
+
+    fn caching(dir: &TempDir) -> (CachingProvider<MockProvider>, MockProvider) {
+        let inner = answering_provider();
+        let cache = cache_in(dir, 1024 * 1024, Duration::from_secs(3600));
+        (CachingProvider::new(inner.clone(), "fake", cache), inner)
+    }
+
+    fn backdate(path: &Path, age: Duration) {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - age)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_identical_request_is_served_from_cache() {
+        let dir = TempDir::new().unwrap();
+        let (provider, inner) = caching(&dir);
+        let messages = vec![Message::user("hello")];
+
+        let first = provider.complete(&messages, &[]).await.unwrap();
+        let second = provider.complete(&messages, &[]).await.unwrap();
+
+        assert!(!first.cached);
+        assert!(second.cached);
+        assert_eq!(second.message.content.as_deref(), Some("response"));
+        assert_eq!(second.usage.unwrap().total_tokens, 15);
+        assert_eq!(inner.request_count(), 1);
+        assert_eq!(provider.counters().hits(), 1);
+        assert_eq!(provider.counters().misses(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_different_messages_tools_or_model_miss() {
+        let dir = TempDir::new().unwrap();
+        let (mut provider, inner) = caching(&dir);
+        let tool = serde_json::json!({"name": "read_file"});
+
+        provider.complete(&[Message::user("a")], &[]).await.unwrap();
+        provider.complete(&[Message::user("b")], &[]).await.unwrap();
+        provider
+            .complete(&[Message::user("a")], std::slice::from_ref(&tool))
+            .await
+            .unwrap();
+        provider.set_model("other-model");
+        provider.complete(&[Message::user("a")], &[]).await.unwrap();
+
+        assert_eq!(inner.request_count(), 4);
+        assert_eq!(provider.counters().hits(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_positive_temperature_bypasses_unless_forced() {
+        let dir = TempDir::new().unwrap();
+        let (provider, inner) = caching(&dir);
+        let provider = provider.with_temperature(Some(0.7));
+        let messages = vec![Message::user("hello")];
+
+        provider.complete(&messages, &[]).await.unwrap();
+        provider.complete(&messages, &[]).await.unwrap();
+        assert_eq!(inner.request_count(), 2);
+        assert_eq!(provider.counters().bypassed(), 2);
+
+        let provider = provider.with_force(true);
+        provider.complete(&messages, &[]).await.unwrap();
+        assert!(provider.complete(&messages, &[]).await.unwrap().cached);
+        assert_eq!(inner.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_set_temperature_controls_bypass_and_cache_key() {
+        let dir = TempDir::new().unwrap();
+        let (provider, inner) = caching(&dir);
+        let messages = vec![Message::user("hello")];
+
+        provider.set_temperature(Some(0.7)).unwrap();
+        provider.complete(&messages, &[]).await.unwrap();
+        provider.complete(&messages, &[]).await.unwrap();
+        assert_eq!(provider.counters().bypassed(), 2);
+
+        provider.set_temperature(Some(0.0)).unwrap();
+        provider.complete(&messages, &[]).await.unwrap();
+        assert!(provider.complete(&messages, &[]).await.unwrap().cached);
+
+        // The temperature is part of the key
+        provider.set_temperature(None).unwrap();
+        assert!(!provider.complete(&messages, &[]).await.unwrap().cached);
+        assert_eq!(inner.request_count(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_bypasses_cache() {
+        let dir = TempDir::new().unwrap();
+        let (provider, inner) = caching(&dir);
+        let messages = vec![Message::user("hello")];
+
+        provider.complete(&messages, &[]).await.unwrap();
+        let streamed = provider
+            .chat_completion_stream(&messages, &[])
+            .await
+            .unwrap();
+
+        assert!(!streamed.cached);
+        assert_eq!(inner.request_count(), 2);
+        assert_eq!(provider.counters().bypassed(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_expired_entry_is_a_miss() {
+        let dir = TempDir::new().unwrap();
+        let inner = answering_provider();
+        let cache = cache_in(&dir, 1024 * 1024, Duration::from_secs(60));
+        let provider = CachingProvider::new(inner.clone(), "fake", cache.clone());
+        let messages = vec![Message::user("hello")];
+
+        provider.complete(&messages, &[]).await.unwrap();
+        let key = ResponseCache::key("fake", "fake-model", &messages, &[], None).unwrap();
+        backdate(&cache.entry_path(&key), Duration::from_secs(120));
+
+        assert!(!provider.complete(&messages, &[]).await.unwrap().cached);
+        assert_eq!(inner.request_count(), 2);
+    }
+
+    #[test]
+    fn test_evict_trims_oldest_entries_to_size_limit() {
+        let dir = TempDir::new().unwrap();
+        let entry = CacheEntry::from_response(
+            "fake",
+            "fake-model",
+            &CompletionResponse::new(Message::assistant("x".repeat(200))),
+        );
+        let entry_size = serde_json::to_vec(&entry).unwrap().len() as u64;
+        let cache = cache_in(&dir, entry_size * 2, Duration::from_secs(3600));
+
+        for (index, key) in ["first", "second", "third"].iter().enumerate() {
+            cache.put(key, &entry).unwrap();
+            backdate(
+                &cache.entry_path(key),
+                Duration::from_secs(30 - index as u64 * 10),
+            );
+        }
+        cache.evict().unwrap();
+
+        assert!(cache.get("first").is_none());
+        assert!(cache.get("second").is_some());
+        assert!(cache.get("third").is_some());
+        assert_eq!(cache.stats().unwrap().entries, 2);
+    }
+
+    #[test]
+    fn test_stats_and_clear() {
+        let dir = TempDir::new().unwrap();
+        let cache = cache_in(&dir, 1024 * 1024, Duration::from_secs(60));
+        assert_eq!(cache.stats().unwrap(), CacheStats::default());
+
+        let entry = CacheEntry::from_response(
+            "fake",
+            "fake-model",
+            &CompletionResponse::new(Message::assistant("hi")),
+        );
+        cache.put("fresh", &entry).unwrap();
+        cache.put("stale", &entry).unwrap();
+        backdate(&cache.entry_path("stale"), Duration::from_secs(120));
+
+        let stats = cache.stats().unwrap();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.expired, 1);
+        assert!(stats.size_bytes > 0);
+        assert!(stats.oldest < stats.newest);
+
+        assert_eq!(cache.clear().unwrap(), 2);
+        assert_eq!(cache.stats().unwrap().entries, 0);
+    }
+
+    #[test]
+    fn test_wrap_with_cache_only_when_enabled() {
+        let dir = TempDir::new().unwrap();
+        let paths = Paths::resolve_with(&PathsConfig::default(), |name| {
+            (name == CACHE_DIR_ENV || name == DATA_DIR_ENV)
+                .then(|| dir.path().to_string_lossy().to_string())
+        })
+        .unwrap();
+        let (_, counters) = wrap_with_cache(
+            Box::new(answering_provider()),
+            "fake",
+            &ProviderCacheConfig::default(),
+            &paths,
+        );
+        assert!(counters.is_none());
+
+        let config = ProviderCacheConfig {
+            enabled: true,
+            ..ProviderCacheConfig::default()
+        };
+        let (provider, counters) =
+            wrap_with_cache(Box::new(answering_provider()), "fake", &config, &paths);
+        assert!(counters.is_some());
+        assert_eq!(
+            ResponseCache::from_config(&config, &paths).dir(),
+            dir.path().join("provider_cache")
+        );
+        assert_eq!(provider.get_current_model(), "fake-model");
+    }
+}
@@ -0,0 +1,280 @@
+//! Client-side request limits for provider hosts
+//!
+//! A local inference server runs a fixed number of requests at once (for
+//! Ollama, `OLLAMA_NUM_PARALLEL`). Requests beyond that wait on the server,
+//! invisibly, until they hit their HTTP timeout. [`HostLimiter`] makes them
+//! wait on the client instead, in arrival order, so the wait can be
+//! measured, logged, and reported apart from a slow response.
+//!
+//! Limiters live in a process-wide registry keyed by host URL. Every
+//! provider pointing at a host, including the ones subagents and watcher
+//! plans create, shares one limiter.
+
+use crate::error::{Result, XzatomaError};
+
+use metrics::histogram;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+/// Queue waits at least this long are logged as warnings.
+pub const QUEUE_WAIT_WARNING: Duration = Duration::from_secs(10);
+
+/// Concurrency limit shared by every request to one host
+///
+/// Slots are handed out in the order requests asked for them.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use xzatoma::providers::concurrency::HostLimiter;
+///
+/// # #[tokio::main]
+/// # async fn main() -> xzatoma::error::Result<()> {
+/// let limiter = HostLimiter::new("http://localhost:11434", 2);
+/// let slot = limiter.acquire("ollama", Duration::from_secs(5)).await?;
+/// assert_eq!(limiter.in_flight(), 1);
+/// drop(slot);
+/// assert_eq!(limiter.in_flight(), 0);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct HostLimiter {
+    host: String,
+    max_concurrent: usize,
+    semaphore: Arc<Semaphore>,
+}
+
+/// A held request slot; the slot is released when this is dropped
+#[derive(Debug)]
+pub struct RequestSlot {
+    _permit: OwnedSemaphorePermit,
+    waited: Duration,
+}
+
+impl RequestSlot {
+    /// Time the request spent queued before it got the slot
+    pub fn waited(&self) -> Duration {
+        self.waited
+    }
+}
+
+impl HostLimiter {
+    /// Creates a limiter that is not shared through the registry
+    ///
+    /// A limit of 0 is treated as 1.
+    pub fn new(host: &str, max_concurrent: usize) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        Self {
+            host: host.to_string(),
+            max_concurrent,
+            semaphore: Arc::new(Semaphore::new(max_concurrent)),
+        }
+    }
+
+    /// Returns the process-wide limiter for `host`
+    ///
+    /// The first caller for a host sets its limit. Later callers share that
+    /// limiter; a different `max_concurrent` is logged and ignored, so a
+    /// second provider cannot widen the limit for the first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use xzatoma::providers::concurrency::HostLimiter;
+    ///
+    /// let first = HostLimiter::for_host("http://limiter-example:11434", 2);
+    /// let second = HostLimiter::for_host("http://limiter-example:11434/", 4);
+    /// assert!(Arc::ptr_eq(&first, &second));
+    /// assert_eq!(second.max_concurrent(), 2);
+    /// ```
+    pub fn for_host(host: &str, max_concurrent: usize) -> Arc<Self> {
+        static REGISTRY: OnceLock<Mutex<HashMap<String, Arc<HostLimiter>>>> = OnceLock::new();
+
+        let key = host_key(host);
+        let mut limiters = REGISTRY
+            .get_or_init(Mutex::default)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let limiter = limiters
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(HostLimiter::new(&key, max_concurrent)));
+        if limiter.max_concurrent != max_concurrent.max(1) {
+            tracing::warn!(
+                "Ignoring max_concurrent_requests={} for {}; already limited to {}",
+                max_concurrent,
+                key,
+                limiter.max_concurrent
+            );
+        }
+        Arc::clone(limiter)
+    }
+
+    /// Host this limiter applies to
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Maximum number of requests in flight at once
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    /// Number of slots currently held
+    pub fn in_flight(&self) -> usize {
+        self.max_concurrent - self.semaphore.available_permits()
+    }
+
+    /// Waits for a free slot, giving up after `timeout`
+    ///
+    /// The wait is recorded in the `provider_queue_wait_seconds` histogram,
+    /// and logged as a warning when it reaches [`QUEUE_WAIT_WARNING`].
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - Provider name used in metrics and errors
+    /// * `timeout` - Longest time to wait for a slot
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::RequestQueueTimeout` when no slot frees up in
+    /// time.
+    pub async fn acquire(&self, provider: &str, timeout: Duration) -> Result<RequestSlot> {
+        let started = Instant::now();
+        let acquired =
+            tokio::time::timeout(timeout, Arc::clone(&self.semaphore).acquire_owned()).await;
+        let waited = started.elapsed();
+        histogram!(
+            "provider_queue_wait_seconds",
+            waited.as_secs_f64(),
+            "provider" => provider.to_string()
+        );
+
+        let permit = match acquired {
+            Ok(Ok(permit)) => permit,
+            Ok(Err(_)) => {
+                return Err(XzatomaError::Internal(format!(
+                    "Request limiter for {} was closed",
+                    self.host
+                )))
+            }
+            Err(_) => {
+                tracing::warn!(
+                    "{} request to {} gave up after {:?} in the queue",
+                    provider,
+                    self.host,
+                    waited
+                );
+                return Err(XzatomaError::RequestQueueTimeout {
+                    provider: provider.to_string(),
+                    host: self.host.clone(),
+                    waited,
+                    max_concurrent: self.max_concurrent,
+                });
+            }
+        };
+
+        if waited >= QUEUE_WAIT_WARNING {
+            tracing::warn!(
+                "{} request to {} waited {:.1}s for one of {} request slot(s)",
+                provider,
+                self.host,
+                waited.as_secs_f64(),
+                self.max_concurrent
+            );
+        } else if !waited.is_zero() {
+            tracing::debug!(
+                "{} request to {} waited {:?} in the queue",
+                provider,
+                self.host,
+                waited
+            );
+        }
+
+        Ok(RequestSlot {
+            _permit: permit,
+            waited,
+        })
+    }
+}
+
+/// Registry key for a host URL: lowercased, without trailing slashes
+fn host_key(host: &str) -> String {
+    host.trim().trim_end_matches('/').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acquire_hands_out_slots_in_arrival_order() {
+        let limiter = Arc::new(HostLimiter::new("http://order-test", 1));
+        let held = limiter
+            .acquire("ollama", Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut tasks = Vec::new();
+        for index in 0..3 {
+            let limiter = Arc::clone(&limiter);
+            let order = Arc::clone(&order);
+            tasks.push(tokio::spawn(async move {
+                let _slot = limiter
+                    .acquire("ollama", Duration::from_secs(5))
+                    .await
+                    .unwrap();
+                order.lock().unwrap().push(index);
+            }));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        drop(held);
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(*order.lock().unwrap(), vec![0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_times_out_while_queued() {
+        let limiter = HostLimiter::new("http://queue-timeout-test", 1);
+        let _held = limiter
+            .acquire("ollama", Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        let err = limiter
+            .acquire("ollama", Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        match err {
+            XzatomaError::RequestQueueTimeout {
+                host,
+                max_concurrent,
+                ..
+            } => {
+                assert_eq!(host, "http://queue-timeout-test");
+                assert_eq!(max_concurrent, 1);
+            }
+            other => panic!("Expected RequestQueueTimeout, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_for_host_shares_one_limiter_per_host() {
+        let first = HostLimiter::for_host("HTTP://Shared-Test:11434/", 3);
+        let second = HostLimiter::for_host("http://shared-test:11434", 1);
+        let other = HostLimiter::for_host("http://other-test:11434", 1);
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(second.max_concurrent(), 3);
+        assert!(!Arc::ptr_eq(&first, &other));
+    }
+}
@@ -0,0 +1,138 @@
+//! Recognition of context-overflow errors in provider responses
+//!
+//! Providers reject a request that does not fit the model's context window
+//! with an HTTP error whose body names the problem in a provider-specific
+//! way: OpenAI and Copilot use error codes such as `context_length_exceeded`,
+//! Ollama and llama.cpp servers describe it in the message. [`detect`] maps
+//! all of them to [`XzatomaError::ContextOverflow`] so the agent can compact
+//! the conversation and retry, and extracts the token limit and the size of
+//! the rejected request when the body mentions them.
+
+use crate::error::XzatomaError;
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Lowercase fragments that identify a context-overflow error body
+const OVERFLOW_MARKERS: &[&str] = &[
+    "context_length_exceeded",
+    "model_max_prompt_tokens_exceeded",
+    "maximum context length",
+    "exceeds the context length",
+    "exceeds maximum context length",
+    "exceeds the available context size",
+];
+
+/// Returns a `ContextOverflow` error if `body` reports a context overflow
+///
+/// # Arguments
+///
+/// * `body` - Response body of a failed provider request
+///
+/// # Returns
+///
+/// `Some(XzatomaError::ContextOverflow)` with the limit and attempted token
+/// counts when the body mentions them, or `None` for any other error.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::error::XzatomaError;
+/// use xzatoma::providers::context_overflow::detect;
+///
+/// let body = r#"{"error":{"code":"context_length_exceeded","message":"This model's maximum context length is 8192 tokens. However, your messages resulted in 9100 tokens."}}"#;
+/// assert!(matches!(
+///     detect(body),
+///     Some(XzatomaError::ContextOverflow {
+///         limit: Some(8192),
+///         attempted: Some(9100),
+///     })
+/// ));
+/// assert!(detect(r#"{"error":"model not found"}"#).is_none());
+/// ```
+pub fn detect(body: &str) -> Option<XzatomaError> {
+    let lower = body.to_lowercase();
+    if !OVERFLOW_MARKERS.iter().any(|marker| lower.contains(marker)) {
+        return None;
+    }
+
+    let (limit, attempted) = token_counts(&lower);
+    Some(XzatomaError::ContextOverflow { limit, attempted })
+}
+
+/// Extracts `(limit, attempted)` token counts from a lowercased error body
+fn token_counts(lower: &str) -> (Option<usize>, Option<usize>) {
+    static PATTERNS: OnceLock<[(Regex, usize, usize); 3]> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        // SAFETY: the patterns are constant and known to compile.
+        let pattern = |source: &str| Regex::new(source).unwrap();
+        [
+            // OpenAI and Copilot chat completions
+            (
+                pattern(
+                    r"maximum context length is (\d+) tokens.*?(?:resulted in|requested) (\d+) tokens",
+                ),
+                1,
+                2,
+            ),
+            // Copilot prompt token limit
+            (
+                pattern(r"prompt token count of (\d+) exceeds the limit of (\d+)"),
+                2,
+                1,
+            ),
+            // llama.cpp servers, including Ollama's runner
+            (
+                pattern(r#""n_prompt_tokens"\s*:\s*(\d+).*?"n_ctx"\s*:\s*(\d+)"#),
+                2,
+                1,
+            ),
+        ]
+    });
+
+    for (regex, limit_group, attempted_group) in patterns {
+        if let Some(captures) = regex.captures(lower) {
+            let number = |group: usize| captures.get(group)?.as_str().parse().ok();
+            return (number(*limit_group), number(*attempted_group));
+        }
+    }
+    (None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(body: &str) -> Option<(Option<usize>, Option<usize>)> {
+        match detect(body)? {
+            XzatomaError::ContextOverflow { limit, attempted } => Some((limit, attempted)),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_detect_copilot_error_codes() {
+        let body = r#"{"error":{"message":"prompt token count of 140213 exceeds the limit of 128000","code":"model_max_prompt_tokens_exceeded"}}"#;
+        assert_eq!(counts(body), Some((Some(128000), Some(140213))));
+
+        let body = r#"{"error":{"message":"This model's maximum context length is 128000 tokens. However, your messages resulted in 131072 tokens.","type":"invalid_request_error","code":"context_length_exceeded"}}"#;
+        assert_eq!(counts(body), Some((Some(128000), Some(131072))));
+    }
+
+    #[test]
+    fn test_detect_ollama_messages() {
+        assert_eq!(
+            counts(r#"{"error":"input length exceeds maximum context length"}"#),
+            Some((None, None))
+        );
+        let body = r#"{"error":{"code":400,"message":"the request exceeds the available context size, try increasing it","type":"exceed_context_size_error","n_prompt_tokens":9000,"n_ctx":8192}}"#;
+        assert_eq!(counts(body), Some((Some(8192), Some(9000))));
+    }
+
+    #[test]
+    fn test_detect_ignores_other_errors() {
+        assert!(detect(r#"{"error":"model 'llama9' not found"}"#).is_none());
+        assert!(detect("rate limit exceeded").is_none());
+        assert!(detect("").is_none());
+    }
+}
@@ -1,22 +1,35 @@
 //! GitHub Copilot provider implementation for XZatoma
 //!
 //! This module implements the Provider trait for GitHub Copilot, including
-//! OAuth device flow authentication and token caching in the system keyring.
+//! OAuth device flow authentication and token caching in the configured
+//! credential store.
+//!
+//! The Copilot session token is refreshed before it expires. A request that
+//! is still rejected with 401 refreshes the token once and is retried.
 
-use crate::config::CopilotConfig;
+use crate::config::{
+    CopilotConfig, DEFAULT_COMPLETION_REQUEST_TIMEOUT_SECONDS, DEFAULT_STREAM_IDLE_TIMEOUT_SECONDS,
+};
+use crate::credentials::{self, CredentialStore};
 use crate::error::{Result, XzatomaError};
+use crate::providers::context_overflow;
+use crate::providers::retry_after;
+use crate::providers::timeouts;
 use crate::providers::{
     convert_tools_from_json, messages_contain_image_content, CompletionResponse, FinishReason,
-    FunctionCall, Message, ModelCapability, ModelInfo, ModelInfoSummary, Provider,
-    ProviderCapabilities, ProviderFunction, ProviderTool, TokenUsage, ToolCall,
+    FunctionCall, ImagePromptSource, Message, ModelCapability, ModelInfo, ModelInfoSummary,
+    Provider, ProviderCapabilities, ProviderFunction, ProviderMessageContentPart, ProviderTool,
+    TokenUsage, ToolCall, ToolCallOptions, OPENAI_MAX_TOOLS, OPENAI_MAX_TOOL_DESCRIPTION_CHARS,
 };
+use base64::Engine;
 
 use async_trait::async_trait;
 use futures::stream::{Stream, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
 use std::pin::Pin;
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
@@ -34,6 +47,9 @@ const COPILOT_RESPONSES_URL: &str = "https://api.githubcopilot.com/responses";
 /// Copilot models endpoint
 const COPILOT_MODELS_URL: &str = "https://api.githubcopilot.com/models";
 /// GitHub Copilot OAuth client ID
+/// Lifetime assumed for a Copilot token when the exchange response has no expiry
+const DEFAULT_COPILOT_TOKEN_LIFETIME_SECS: u64 = 3600;
+
 const GITHUB_CLIENT_ID: &str = "Iv1.b507a08c87ecfe98";
 
 /// Supported model endpoints
@@ -113,7 +129,7 @@ type ResponseStream = Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>;
 ///
 /// This provider connects to GitHub Copilot's API to generate completions.
 /// It implements OAuth device flow for authentication and caches tokens
-/// in the system keyring.
+/// in the configured credential store (the system keyring by default).
 ///
 /// # Examples
 ///
@@ -143,8 +159,7 @@ type ResponseStream = Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>;
 struct CopilotCache {
     /// Converted model list, populated after the first successful fetch.
     models: Option<Vec<ModelInfo>>,
-    /// Raw model data from the API, used by `list_models_summary`.
-    #[allow(dead_code)]
+    /// Raw model data from the API, used to report per-model capabilities.
     raw_models: Option<Vec<CopilotModelData>>,
     /// Instant at which the cache was last populated.
     cached_at: Option<Instant>,
@@ -185,7 +200,7 @@ impl CopilotCache {
 ///
 /// This provider connects to GitHub Copilot's API to generate completions.
 /// It implements OAuth device flow for authentication and caches tokens
-/// in the system keyring.
+/// in the configured credential store (the system keyring by default).
 ///
 /// # Examples
 ///
@@ -210,9 +225,17 @@ pub struct CopilotProvider {
     config: Arc<RwLock<CopilotConfig>>,
     keyring_service: String,
     keyring_user: String,
+    /// Store for the cached token; `None` uses [`credentials::default_store`].
+    credentials: Option<Arc<dyn CredentialStore>>,
     /// Cached model list and raw data. All accesses go through `CopilotCache`
     /// methods (`is_valid`, `invalidate`) rather than inline TTL arithmetic.
     models_cache: Arc<RwLock<CopilotCache>>,
+    /// Current Copilot session token, loaded from the credential store on first use.
+    /// Holding the lock while refreshing makes concurrent requests wait for
+    /// one token exchange instead of each starting their own.
+    session: Arc<tokio::sync::Mutex<Option<CachedToken>>>,
+    /// Tool-call parameters set by the agent for the next requests.
+    tool_call_options: Arc<RwLock<ToolCallOptions>>,
 }
 
 /// Request for GitHub device code
@@ -240,22 +263,63 @@ struct TokenRequest {
     grant_type: String,
 }
 
-/// Cached token information stored in keyring
-#[derive(Debug, Serialize, Deserialize)]
+/// Cached token information stored in the credential store
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct CachedToken {
     github_token: String,
     copilot_token: String,
+    /// Unix timestamp at which the Copilot token expires
     expires_at: u64,
 }
 
+impl CachedToken {
+    /// Returns `true` when the Copilot token expires within `margin` seconds
+    /// of `now`
+    fn expires_within(&self, now: u64, margin: u64) -> bool {
+        self.expires_at <= now.saturating_add(margin)
+    }
+}
+
+/// Response from the Copilot token exchange
+#[derive(Debug, Deserialize)]
+struct CopilotTokenResponse {
+    token: String,
+    /// Unix timestamp at which the token expires
+    #[serde(default)]
+    expires_at: Option<u64>,
+}
+
+/// Current Unix time in seconds
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// Auth error telling the user to sign in to Copilot again
+fn copilot_reauth_error(reason: &str) -> XzatomaError {
+    XzatomaError::Auth {
+        provider: "copilot".to_string(),
+        reason: format!(
+            "{}. Run `xzatoma auth --provider copilot` to sign in again",
+            reason
+        ),
+    }
+}
+
 /// Request structure for Copilot API
 #[derive(Debug, Serialize)]
 struct CopilotRequest {
     model: String,
-    messages: Vec<CopilotMessage>,
+    messages: Vec<CopilotRequestMessage>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     tools: Vec<ProviderTool>,
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parallel_tool_calls: Option<bool>,
 }
 
 /// Message structure for Copilot API
@@ -270,6 +334,69 @@ struct CopilotMessage {
     tool_call_id: Option<String>,
 }
 
+/// Outgoing message structure for the Copilot chat completions API
+///
+/// Unlike [`CopilotMessage`], the content may be an array of typed parts so
+/// that image input can be sent alongside text.
+#[derive(Debug, Serialize)]
+struct CopilotRequestMessage {
+    role: String,
+    content: CopilotMessageContent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<CopilotToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+/// Message content for the Copilot chat completions API
+///
+/// Serializes as a plain string for text-only messages and as the OpenAI
+/// content array when image parts are present.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum CopilotMessageContent {
+    Text(String),
+    Parts(Vec<CopilotContentPart>),
+}
+
+/// A single part of a Copilot content array
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum CopilotContentPart {
+    Text { text: String },
+    ImageUrl { image_url: CopilotImageUrl },
+}
+
+/// Image reference within a Copilot content array
+#[derive(Debug, Serialize)]
+struct CopilotImageUrl {
+    url: String,
+}
+
+/// Build the URL Copilot expects for an image part
+///
+/// Inline data becomes a `data:` URL and remote URLs pass through unchanged.
+/// File references must be resolved before conversion and yield `None`.
+fn image_part_url(mime_type: &str, source: &ImagePromptSource) -> Option<String> {
+    match source {
+        ImagePromptSource::InlineBase64(data) => {
+            Some(format!("data:{};base64,{}", mime_type, data))
+        }
+        ImagePromptSource::InlineBytes(bytes) => {
+            let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+            Some(format!("data:{};base64,{}", mime_type, encoded))
+        }
+        ImagePromptSource::RemoteUrl(url) => Some(url.clone()),
+        ImagePromptSource::FilePath(path) => {
+            tracing::warn!(
+                path = %path.display(),
+                "Skipping unresolved image file reference in Copilot request"
+            );
+            None
+        }
+    }
+}
+
 /// Type alias kept for backwards compatibility within this module.
 ///
 /// Both `CopilotTool` and `CopilotFunction` are now the shared
@@ -416,6 +543,19 @@ impl CopilotModelData {
     pub(crate) fn supports_endpoint(&self, endpoint: &str) -> bool {
         self.supported_endpoints.iter().any(|e| e == endpoint)
     }
+
+    /// Check if the model accepts image input
+    ///
+    /// # Returns
+    ///
+    /// Returns true only when the API reports `capabilities.supports.vision`
+    pub(crate) fn supports_vision(&self) -> bool {
+        self.capabilities
+            .as_ref()
+            .and_then(|c| c.supports.as_ref())
+            .and_then(|s| s.vision)
+            .unwrap_or(false)
+    }
 }
 
 /// Model policy information
@@ -556,8 +696,8 @@ pub enum ResponseInputContent {
     InputText { text: String },
     /// Assistant output text
     OutputText { text: String },
-    /// Image content
-    InputImage { url: String },
+    /// Image content, as a `data:` URL or remote URL
+    InputImage { image_url: String },
 }
 
 /// SSE stream events
@@ -579,10 +719,28 @@ pub enum StreamEvent {
     Reasoning { content: Vec<ResponseInputContent> },
     /// Status event
     Status { status: String },
+    /// Token usage reported by the server, typically in a trailing chunk
+    Usage {
+        prompt_tokens: usize,
+        completion_tokens: usize,
+    },
+    /// Reason the model stopped generating
+    Finish { reason: String },
     /// Done event
     Done,
 }
 
+/// Values of the `type` tag that deserialize into a [`StreamEvent`].
+const STREAM_EVENT_TYPES: &[&str] = &[
+    "message",
+    "function_call",
+    "reasoning",
+    "status",
+    "usage",
+    "finish",
+    "done",
+];
+
 /// Tool definition for responses endpoint
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -673,6 +831,25 @@ pub(crate) fn convert_messages_to_response_input(
 
     for message in messages {
         match message.role.as_str() {
+            "user" if message.has_image_content() => {
+                let content = message
+                    .multimodal_parts()
+                    .iter()
+                    .filter_map(|part| match part {
+                        ProviderMessageContentPart::Text { text } => {
+                            Some(ResponseInputContent::InputText { text: text.clone() })
+                        }
+                        ProviderMessageContentPart::Image {
+                            mime_type, source, ..
+                        } => image_part_url(mime_type, source)
+                            .map(|image_url| ResponseInputContent::InputImage { image_url }),
+                    })
+                    .collect();
+                result.push(ResponseInputItem::Message {
+                    role: "user".to_string(),
+                    content,
+                });
+            }
             "user" => {
                 let content = message.content.as_ref().unwrap_or(&String::new()).clone();
                 result.push(ResponseInputItem::Message {
@@ -968,7 +1145,11 @@ pub(crate) fn convert_stream_event_to_message(event: &StreamEvent) -> Option<Mes
             }]),
             tool_call_id: None,
         }),
-        StreamEvent::Reasoning { .. } | StreamEvent::Status { .. } | StreamEvent::Done => None,
+        StreamEvent::Reasoning { .. }
+        | StreamEvent::Status { .. }
+        | StreamEvent::Usage { .. }
+        | StreamEvent::Finish { .. }
+        | StreamEvent::Done => None,
     }
 }
 
@@ -1041,8 +1222,6 @@ pub(crate) fn convert_tools_to_response_format(
 /// let choice = convert_tool_choice(None);
 /// assert!(choice.is_none());
 /// ```
-// Used in tests; retained for Responses-endpoint tool-choice wiring.
-#[allow(dead_code)]
 pub(crate) fn convert_tool_choice(choice: Option<&str>) -> Option<ToolChoice> {
     choice.map(|c| match c {
         "auto" => ToolChoice::Auto { auto: true },
@@ -1056,37 +1235,93 @@ pub(crate) fn convert_tool_choice(choice: Option<&str>) -> Option<ToolChoice> {
     })
 }
 
-/// Parse SSE (Server-Sent Events) line
-///
-/// # Arguments
-///
-/// * `line` - Line from SSE stream
-///
-/// # Returns
+/// One Server-Sent Event assembled from its field lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct SseFrame {
+    /// Value of the `event:` field, if present.
+    event: Option<String>,
+    /// `data:` field values joined with newlines.
+    data: String,
+}
+
+/// Incremental Server-Sent Events decoder.
 ///
-/// Returns optional parsed event data
-fn parse_sse_line(line: &str) -> Option<String> {
-    let line = line.trim();
+/// Bytes are buffered until a complete line is available, so payloads split
+/// across network reads (including inside a multi-byte UTF-8 character or a
+/// `data:` field) are reassembled before parsing. An event is only emitted
+/// once the blank line terminating it has been received. Comment lines
+/// (keep-alives starting with `:`) and unknown fields are ignored.
+#[derive(Debug, Default)]
+struct SseDecoder {
+    /// Bytes received after the last complete line.
+    buffer: Vec<u8>,
+    /// `event:` field of the event being assembled.
+    event: Option<String>,
+    /// `data:` lines of the event being assembled.
+    data_lines: Vec<String>,
+}
 
-    if line.is_empty() {
-        return None;
+impl SseDecoder {
+    /// Feed a chunk of bytes and return every event it completes.
+    fn push(&mut self, chunk: &[u8]) -> Vec<SseFrame> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut frames = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let mut line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            if let Some(frame) = self.process_line(&String::from_utf8_lossy(&line)) {
+                frames.push(frame);
+            }
+        }
+        frames
     }
 
-    // Handle data: lines
-    if let Some(data) = line.strip_prefix("data: ") {
-        // Check for [DONE] sentinel
-        if data.trim() == "[DONE]" {
-            return Some("[DONE]".to_string());
+    /// Flush an event left unterminated when the stream ended.
+    fn finish(&mut self) -> Option<SseFrame> {
+        if !self.buffer.is_empty() {
+            let line = std::mem::take(&mut self.buffer);
+            let line = String::from_utf8_lossy(&line);
+            if let Some(frame) = self.process_line(line.trim_end_matches('\r')) {
+                return Some(frame);
+            }
         }
-        return Some(data.to_string());
+        self.dispatch()
     }
 
-    // Ignore event:, id:, and other SSE fields
-    if line.starts_with("event:") || line.starts_with("id:") || line.starts_with(":") {
-        return None;
+    fn process_line(&mut self, line: &str) -> Option<SseFrame> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => self.data_lines.push(value.to_string()),
+            "event" => self.event = Some(value.to_string()),
+            _ => {}
+        }
+        None
     }
 
-    None
+    fn dispatch(&mut self) -> Option<SseFrame> {
+        let event = self.event.take();
+        if self.data_lines.is_empty() {
+            return None;
+        }
+        Some(SseFrame {
+            event,
+            data: std::mem::take(&mut self.data_lines).join("\n"),
+        })
+    }
 }
 
 /// Parse SSE data line to StreamEvent
@@ -1107,6 +1342,284 @@ fn parse_sse_event(data: &str) -> Result<StreamEvent> {
         .map_err(|e| XzatomaError::SseParseError(format!("Invalid JSON: {}", e)))
 }
 
+/// Build the typed error for an `error` event received mid-stream.
+fn stream_error_from_payload(data: &str) -> XzatomaError {
+    if let Some(overflow) = context_overflow::detect(data) {
+        return overflow;
+    }
+
+    let reason = serde_json::from_str::<serde_json::Value>(data)
+        .ok()
+        .and_then(|value| {
+            let error = value.get("error").unwrap_or(&value);
+            error
+                .get("message")
+                .and_then(|message| message.as_str())
+                .or_else(|| error.as_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| data.trim().to_string());
+
+    XzatomaError::StreamFailed {
+        provider: "copilot".to_string(),
+        reason,
+        partial_content: None,
+    }
+}
+
+/// Converts decoded SSE events into [`StreamEvent`]s.
+///
+/// Handles both `/responses` events, which carry a `type` tag, and
+/// `/chat/completions` chunks, which carry `choices[].delta`. Tool call deltas
+/// in chat chunks only name their call id in the first fragment, so the id is
+/// remembered per tool call index.
+#[derive(Debug, Default)]
+struct SseEventInterpreter {
+    /// Tool call ids keyed by their `index` in chat completion chunks.
+    tool_call_ids: HashMap<u64, String>,
+}
+
+impl SseEventInterpreter {
+    /// Interpret one decoded event.
+    ///
+    /// # Errors
+    ///
+    /// Returns `StreamFailed` for `error` events and `SseParseError` for data
+    /// that is not valid JSON or does not match a known event type.
+    fn interpret(&mut self, frame: &SseFrame) -> Result<Vec<StreamEvent>> {
+        let data = frame.data.trim();
+
+        if data == "[DONE]" {
+            return Ok(vec![StreamEvent::Done]);
+        }
+        if frame.event.as_deref() == Some("error") {
+            return Err(stream_error_from_payload(data));
+        }
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let value: serde_json::Value = serde_json::from_str(data)
+            .map_err(|e| XzatomaError::SseParseError(format!("Invalid JSON: {}", e)))?;
+
+        let event_type = value.get("type").and_then(|t| t.as_str());
+        if value.get("error").is_some() || event_type == Some("error") {
+            return Err(stream_error_from_payload(data));
+        }
+        if value.get("choices").is_some() {
+            return Ok(self.interpret_chat_chunk(&value));
+        }
+
+        match event_type {
+            Some(kind) if STREAM_EVENT_TYPES.contains(&kind) => {
+                parse_sse_event(data).map(|event| vec![event])
+            }
+            other => {
+                tracing::debug!("Ignoring SSE event with unknown type {:?}", other);
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    fn interpret_chat_chunk(&mut self, chunk: &serde_json::Value) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+
+        let choices = chunk
+            .get("choices")
+            .and_then(|choices| choices.as_array())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        for choice in choices {
+            let delta = choice.get("delta");
+
+            if let Some(text) = delta
+                .and_then(|delta| delta.get("content"))
+                .and_then(|content| content.as_str())
+                .filter(|text| !text.is_empty())
+            {
+                events.push(StreamEvent::Message {
+                    role: "assistant".to_string(),
+                    content: vec![ResponseInputContent::OutputText {
+                        text: text.to_string(),
+                    }],
+                });
+            }
+
+            let tool_calls = delta
+                .and_then(|delta| delta.get("tool_calls"))
+                .and_then(|calls| calls.as_array())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            for call in tool_calls {
+                let index = call.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                if let Some(id) = call.get("id").and_then(|id| id.as_str()) {
+                    self.tool_call_ids.insert(index, id.to_string());
+                }
+                let call_id = self
+                    .tool_call_ids
+                    .get(&index)
+                    .cloned()
+                    .unwrap_or_else(|| format!("call_{}", index));
+                let function = call.get("function");
+                let field = |name: &str| {
+                    function
+                        .and_then(|f| f.get(name))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default()
+                        .to_string()
+                };
+                events.push(StreamEvent::FunctionCall {
+                    call_id,
+                    name: field("name"),
+                    arguments: field("arguments"),
+                });
+            }
+
+            if let Some(reason) = choice.get("finish_reason").and_then(|r| r.as_str()) {
+                events.push(StreamEvent::Finish {
+                    reason: reason.to_string(),
+                });
+            }
+        }
+
+        if let Some(usage) = chunk.get("usage").filter(|usage| usage.is_object()) {
+            let tokens =
+                |name: &str| usage.get(name).and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+            events.push(StreamEvent::Usage {
+                prompt_tokens: tokens("prompt_tokens"),
+                completion_tokens: tokens("completion_tokens"),
+            });
+        }
+
+        events
+    }
+}
+
+/// Send a streaming request, waiting at most `idle_timeout` for the headers.
+///
+/// Streaming requests carry no total timeout; once the headers arrive, the
+/// body is guarded by the same idle timeout in [`sse_event_stream`].
+async fn send_streaming_request(
+    request: reqwest::RequestBuilder,
+    idle_timeout: Duration,
+) -> Result<reqwest::Response> {
+    let wait = timeouts::request_timeout(idle_timeout);
+    match tokio::time::timeout(wait, request.send()).await {
+        Ok(Ok(response)) => Ok(response),
+        Ok(Err(e)) => Err(timeouts::request_error(
+            "copilot",
+            wait,
+            "Streaming request failed",
+            e,
+        )),
+        Err(_) => Err(timeouts::timeout_error("copilot", wait)),
+    }
+}
+
+/// Turn a response body byte stream into a stream of [`StreamEvent`]s.
+///
+/// The stream ends after a `[DONE]` sentinel or when the body ends; an event
+/// left unterminated at the end of the body is still parsed. The stream fails
+/// with `RequestTimeout` if no bytes arrive for `idle_timeout`.
+fn sse_event_stream<S, B, E>(byte_stream: S, idle_timeout: Duration) -> ResponseStream
+where
+    S: Stream<Item = std::result::Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]> + 'static,
+    E: std::fmt::Display + 'static,
+{
+    struct State {
+        byte_stream: Pin<Box<dyn Stream<Item = std::result::Result<Vec<u8>, String>> + Send>>,
+        decoder: SseDecoder,
+        interpreter: SseEventInterpreter,
+        pending: VecDeque<Result<StreamEvent>>,
+        idle_timeout: Duration,
+        finished: bool,
+    }
+
+    let byte_stream = byte_stream.map(|chunk| {
+        chunk
+            .map(|bytes| bytes.as_ref().to_vec())
+            .map_err(|e| e.to_string())
+    });
+
+    let state = State {
+        byte_stream: Box::pin(byte_stream),
+        decoder: SseDecoder::default(),
+        interpreter: SseEventInterpreter::default(),
+        pending: VecDeque::new(),
+        idle_timeout,
+        finished: false,
+    };
+
+    let events = futures::stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(item) = state.pending.pop_front() {
+                if matches!(item, Ok(StreamEvent::Done) | Err(_)) {
+                    state.pending.clear();
+                    state.finished = true;
+                }
+                return Some((item, state));
+            }
+            if state.finished {
+                return None;
+            }
+
+            let next = timeouts::next_within_idle_timeout(
+                "copilot",
+                state.idle_timeout,
+                &mut state.byte_stream,
+            )
+            .await;
+            let frames = match next {
+                Ok(Some(Ok(chunk))) => state.decoder.push(&chunk),
+                Ok(Some(Err(e))) => {
+                    state.finished = true;
+                    return Some((Err(XzatomaError::StreamInterrupted(e)), state));
+                }
+                Err(e) => {
+                    state.finished = true;
+                    return Some((Err(e), state));
+                }
+                Ok(None) => {
+                    state.finished = true;
+                    state.decoder.finish().into_iter().collect()
+                }
+            };
+
+            for frame in frames {
+                match state.interpreter.interpret(&frame) {
+                    Ok(events) => state.pending.extend(events.into_iter().map(Ok)),
+                    Err(e) => state.pending.push_back(Err(e)),
+                }
+            }
+        }
+    });
+
+    Box::pin(events)
+}
+
+/// Attach the text received so far to an error raised mid-stream.
+///
+/// Errors raised before any content arrived are returned unchanged.
+fn with_partial_content(error: XzatomaError, partial: &str) -> XzatomaError {
+    match error {
+        XzatomaError::StreamFailed {
+            provider, reason, ..
+        } => XzatomaError::StreamFailed {
+            provider,
+            reason,
+            partial_content: (!partial.is_empty()).then(|| partial.to_string()),
+        },
+        other if !partial.is_empty() => XzatomaError::StreamFailed {
+            provider: "copilot".to_string(),
+            reason: other.to_string(),
+            partial_content: Some(partial.to_string()),
+        },
+        other => other,
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Streaming accumulator helper types
 // ---------------------------------------------------------------------------
@@ -1199,6 +1712,15 @@ impl ResponsesAccumulator {
                 }
                 entry.arguments.push_str(arguments);
             }
+            StreamEvent::Usage {
+                prompt_tokens,
+                completion_tokens,
+            } => {
+                self.usage = Some(TokenUsage::new(*prompt_tokens, *completion_tokens));
+            }
+            StreamEvent::Finish { reason } => {
+                self.finish_reason = map_finish_reason(reason);
+            }
             StreamEvent::Status { .. } | StreamEvent::Done => {}
         }
     }
@@ -1331,6 +1853,15 @@ impl ChatCompletionsAccumulator {
                 }
                 entry.arguments.push_str(arguments);
             }
+            StreamEvent::Usage {
+                prompt_tokens,
+                completion_tokens,
+            } => {
+                self.usage = Some(TokenUsage::new(*prompt_tokens, *completion_tokens));
+            }
+            StreamEvent::Finish { reason } => {
+                self.finish_reason = map_finish_reason(reason);
+            }
             StreamEvent::Reasoning { .. } | StreamEvent::Status { .. } | StreamEvent::Done => {}
         }
     }
@@ -1369,14 +1900,43 @@ impl ChatCompletionsAccumulator {
     }
 }
 
-fn format_copilot_api_error(status: reqwest::StatusCode, body: &str) -> XzatomaError {
-    if status == reqwest::StatusCode::UNAUTHORIZED {
-        XzatomaError::Authentication(format!(
-            "Copilot returned error {}: {}. Token may have expired; please re-authenticate with `xzatoma auth --provider copilot`",
-            status, body
-        ))
-    } else {
-        XzatomaError::Provider(format!("Copilot returned error {}: {}", status, body))
+/// Map a streamed finish-reason string to a typed [`FinishReason`].
+///
+/// Unrecognized values map to [`FinishReason::Other`].
+fn map_finish_reason(reason: &str) -> FinishReason {
+    match reason {
+        "stop" | "completed" => FinishReason::Stop,
+        "length" | "max_output_tokens" => FinishReason::Length,
+        "tool_calls" | "function_call" => FinishReason::ToolCalls,
+        "content_filter" => FinishReason::ContentFilter,
+        _ => FinishReason::Other,
+    }
+}
+
+/// Maps a failed Copilot response to an `XzatomaError`
+///
+/// `429 Too Many Requests` becomes `RateLimited` with the wait parsed from
+/// the `Retry-After` header.
+fn format_copilot_api_error(
+    status: reqwest::StatusCode,
+    retry_after: Option<Duration>,
+    body: &str,
+) -> XzatomaError {
+    match status {
+        reqwest::StatusCode::UNAUTHORIZED => XzatomaError::Auth {
+            provider: "copilot".to_string(),
+            reason: format!(
+                "Copilot returned error {}: {}. Token may have expired",
+                status, body
+            ),
+        },
+        reqwest::StatusCode::TOO_MANY_REQUESTS => XzatomaError::RateLimited {
+            provider: "copilot".to_string(),
+            retry_after,
+        },
+        _ => context_overflow::detect(body).unwrap_or_else(|| {
+            XzatomaError::Provider(format!("Copilot returned error {}: {}", status, body))
+        }),
     }
 }
 
@@ -1410,7 +1970,7 @@ impl CopilotProvider {
     /// ```
     pub fn new(config: CopilotConfig) -> Result<Self> {
         let client = Client::builder()
-            .timeout(Duration::from_secs(120))
+            .connect_timeout(timeouts::CONNECT_TIMEOUT)
             .user_agent("xzatoma/0.1.0")
             .build()
             .map_err(|e| XzatomaError::Provider(format!("Failed to create HTTP client: {}", e)))?;
@@ -1422,10 +1982,89 @@ impl CopilotProvider {
             config: Arc::new(RwLock::new(config)),
             keyring_service: super::factory::KEYRING_SERVICE.to_string(),
             keyring_user: super::factory::KEYRING_COPILOT_USER.to_string(),
+            credentials: None,
             models_cache: Arc::new(RwLock::new(CopilotCache::new())),
+            session: Arc::new(tokio::sync::Mutex::new(None)),
+            tool_call_options: Arc::new(RwLock::new(ToolCallOptions::default())),
         })
     }
 
+    /// Use `store` for the cached token instead of the configured store
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use xzatoma::config::CopilotConfig;
+    /// use xzatoma::credentials::MemoryStore;
+    /// use xzatoma::providers::CopilotProvider;
+    ///
+    /// let provider = CopilotProvider::new(CopilotConfig::default())
+    ///     .unwrap()
+    ///     .with_credential_store(Arc::new(MemoryStore::default()));
+    /// ```
+    pub fn with_credential_store(mut self, store: Arc<dyn CredentialStore>) -> Self {
+        self.credentials = Some(store);
+        self
+    }
+
+    /// Timeout for a non-streaming completion, capped by the agent deadline.
+    fn completion_timeout(&self) -> Duration {
+        let configured = self
+            .config
+            .read()
+            .map(|config| config.request_timeout_seconds)
+            .unwrap_or(DEFAULT_COMPLETION_REQUEST_TIMEOUT_SECONDS);
+        timeouts::request_timeout(Duration::from_secs(configured))
+    }
+
+    /// Seconds before expiry at which the Copilot token is refreshed.
+    fn token_refresh_margin(&self) -> u64 {
+        self.config
+            .read()
+            .map(|config| config.token_refresh_margin_seconds)
+            .unwrap_or(300)
+    }
+
+    /// How long a streaming response may stay silent before it is abandoned.
+    fn stream_idle_timeout(&self) -> Duration {
+        let configured = self
+            .config
+            .read()
+            .map(|config| config.stream_idle_timeout_seconds)
+            .unwrap_or(DEFAULT_STREAM_IDLE_TIMEOUT_SECONDS);
+        Duration::from_secs(configured)
+    }
+
+    /// Tool-call parameters for a request; empty when it carries no tools.
+    fn tool_call_options(&self, has_tools: bool) -> ToolCallOptions {
+        if !has_tools {
+            return ToolCallOptions::default();
+        }
+        self.tool_call_options
+            .read()
+            .map(|options| *options)
+            .unwrap_or_default()
+    }
+
+    /// Capability flags the cached models metadata reports for `model`
+    ///
+    /// `None` until the model list has been fetched.
+    fn cached_model_supports(&self, model: &str) -> Option<CopilotModelSupports> {
+        let cache = self.models_cache.read().ok()?;
+        cache
+            .raw_models
+            .as_ref()?
+            .iter()
+            .find(|data| data.id == model)
+            .map(|data| {
+                data.capabilities
+                    .as_ref()
+                    .and_then(|caps| caps.supports.clone())
+                    .unwrap_or_default()
+            })
+    }
+
     /// Get the configured model name
     ///
     /// # Examples
@@ -1444,44 +2083,154 @@ impl CopilotProvider {
     ///
     /// Authenticate and get Copilot token
     ///
-    /// Checks keyring for cached token first. If not found or expired,
-    /// performs OAuth device flow to get new token.
+    /// Uses the session token, loading it from the credential store on first use. A
+    /// token that expires within `token_refresh_margin_seconds` is refreshed
+    /// with the cached GitHub token first. Performs the OAuth device flow when
+    /// there is no GitHub token or the refresh fails.
     pub async fn authenticate(&self) -> Result<String> {
-        if let Ok(cached) = self.get_cached_token() {
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
+        let mut session = self.session.lock().await;
+        if session.is_none() {
+            *session = self.get_cached_token().ok();
+        }
 
-            if cached.expires_at > now + 300 {
+        let now = unix_now();
+        let margin = self.token_refresh_margin();
+        if let Some(cached) = session.as_ref() {
+            if !cached.expires_within(now, margin) {
                 tracing::debug!("Using cached Copilot token");
-                return Ok(cached.copilot_token);
+                return Ok(cached.copilot_token.clone());
             }
+        }
 
-            tracing::debug!("Cached token expired, refreshing");
+        if let Some(github_token) = session.as_ref().map(|cached| cached.github_token.clone()) {
+            tracing::debug!("Copilot token expires within {}s, refreshing", margin);
+            match self.refresh_session(&mut session, &github_token).await {
+                Ok(token) => return Ok(token),
+                Err(e) => {
+                    tracing::warn!("Failed to refresh Copilot token: {}", e);
+                    if let Some(cached) = session.as_ref().filter(|c| c.expires_at > now) {
+                        return Ok(cached.copilot_token.clone());
+                    }
+                }
+            }
         }
 
         tracing::info!("Starting GitHub OAuth device flow");
         let github_token = self.device_flow().await?;
 
         tracing::debug!("Exchanging GitHub token for Copilot token");
-        let copilot_token = self.get_copilot_token(&github_token).await?;
+        self.refresh_session(&mut session, &github_token).await
+    }
 
-        let cached = CachedToken {
-            github_token,
-            copilot_token: copilot_token.clone(),
-            expires_at: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-                + 3600,
+    /// Exchange the GitHub token for a new Copilot token
+    ///
+    /// Stores the new token in the session and the credential store.
+    async fn refresh_session(
+        &self,
+        session: &mut Option<CachedToken>,
+        github_token: &str,
+    ) -> Result<String> {
+        let refreshed = self.get_copilot_token(github_token).await?;
+        if let Err(e) = self.cache_token(&refreshed) {
+            tracing::warn!("Failed to cache token: {}", e);
+        }
+        tracing::info!(
+            "Refreshed Copilot token, expires in {}s",
+            refreshed.expires_at.saturating_sub(unix_now())
+        );
+
+        let token = refreshed.copilot_token.clone();
+        *session = Some(refreshed);
+        Ok(token)
+    }
+
+    /// Replace a Copilot token that the API rejected
+    ///
+    /// Returns the session token when another request already replaced
+    /// `rejected`, so concurrent requests refresh only once.
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Auth` asking the user to sign in again when
+    /// there is no cached GitHub token or the token exchange fails. The
+    /// cached credentials are cleared in that case.
+    async fn force_refresh(&self, rejected: &str) -> Result<String> {
+        let mut session = self.session.lock().await;
+        if session.is_none() {
+            *session = self.get_cached_token().ok();
+        }
+
+        let github_token = match session.as_ref() {
+            Some(cached) if cached.copilot_token != rejected => {
+                return Ok(cached.copilot_token.clone());
+            }
+            Some(cached) => cached.github_token.clone(),
+            None => {
+                return Err(copilot_reauth_error(
+                    "Copilot rejected the session token and no GitHub token is cached",
+                ))
+            }
         };
 
-        if let Err(e) = self.cache_token(&cached) {
-            tracing::warn!("Failed to cache token: {}", e);
+        match self.refresh_session(&mut session, &github_token).await {
+            Ok(token) => Ok(token),
+            Err(e) => {
+                *session = None;
+                if let Err(e) = self.clear_cached_token() {
+                    tracing::warn!("Failed to clear cached Copilot token: {}", e);
+                }
+                Err(copilot_reauth_error(&format!(
+                    "Failed to refresh the Copilot token: {}",
+                    e
+                )))
+            }
+        }
+    }
+
+    /// Send a Copilot API request, refreshing the token once on 401
+    ///
+    /// `send` sends the request with the given bearer token. When the API
+    /// rejects the token, it is refreshed and the request is sent one more
+    /// time. Responses other than 401 are returned to the caller unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Auth` asking the user to sign in again when the
+    /// refreshed token is rejected as well. The cached credentials are
+    /// cleared in that case.
+    async fn send_authorized<F, Fut>(&self, send: F) -> Result<reqwest::Response>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<reqwest::Response>>,
+    {
+        let token = self.authenticate().await?;
+        let response = send(token.clone()).await?;
+        if response.status() != reqwest::StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        tracing::warn!(
+            "Copilot returned 401 Unauthorized ({}); refreshing token and retrying once",
+            body
+        );
+        let token = self.force_refresh(&token).await?;
+
+        let response = send(token).await?;
+        if response.status() != reqwest::StatusCode::UNAUTHORIZED {
+            return Ok(response);
         }
 
-        Ok(copilot_token)
+        let body = response.text().await.unwrap_or_default();
+        tracing::error!("Copilot rejected the refreshed token: {}", body);
+        *self.session.lock().await = None;
+        if let Err(e) = self.clear_cached_token() {
+            tracing::warn!("Failed to clear cached Copilot token: {}", e);
+        }
+        Err(copilot_reauth_error(&format!(
+            "Copilot rejected the refreshed token: {}",
+            body
+        )))
     }
 
     /// Perform OAuth device flow to get GitHub token
@@ -1493,6 +2242,7 @@ impl CopilotProvider {
         let resp = self
             .client
             .post(GITHUB_DEVICE_CODE_URL)
+            .timeout(timeouts::METADATA_TIMEOUT)
             .header("Accept", "application/json")
             .form(&DeviceCodeRequest {
                 client_id: GITHUB_CLIENT_ID.to_string(),
@@ -1534,6 +2284,7 @@ impl CopilotProvider {
             let response = self
                 .client
                 .post(GITHUB_TOKEN_URL)
+                .timeout(timeouts::METADATA_TIMEOUT)
                 .header("Accept", "application/json")
                 .form(&TokenRequest {
                     client_id: GITHUB_CLIENT_ID.to_string(),
@@ -1595,103 +2346,129 @@ impl CopilotProvider {
     }
 
     /// Exchange GitHub token for Copilot token
-    async fn get_copilot_token(&self, github_token: &str) -> Result<String> {
-        #[derive(Deserialize)]
-        struct CopilotTokenResponse {
-            token: String,
-        }
-
+    ///
+    /// Uses the expiry from the exchange response, or one hour when the
+    /// response has none.
+    async fn get_copilot_token(&self, github_token: &str) -> Result<CachedToken> {
         let token_url = self.api_endpoint("copilot_internal/v2/token");
-        let response: CopilotTokenResponse = self
+        let timeout = timeouts::request_timeout(timeouts::METADATA_TIMEOUT);
+        let response = self
             .client
             .get(&token_url)
+            .timeout(timeout)
             .header("Authorization", format!("token {}", github_token))
             .send()
             .await
-            .map_err(|e| XzatomaError::Provider(format!("Copilot token request failed: {}", e)))?
+            .map_err(|e| {
+                timeouts::request_error("copilot", timeout, "Copilot token request failed", e)
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(XzatomaError::Auth {
+                provider: "copilot".to_string(),
+                reason: format!("Copilot token exchange returned {}: {}", status, body),
+            });
+        }
+
+        let response: CopilotTokenResponse = response
             .json()
             .await
             .map_err(|e| XzatomaError::Provider(format!("Failed to parse Copilot token: {}", e)))?;
 
-        Ok(response.token)
+        Ok(CachedToken {
+            github_token: github_token.to_string(),
+            copilot_token: response.token,
+            expires_at: response
+                .expires_at
+                .unwrap_or_else(|| unix_now() + DEFAULT_COPILOT_TOKEN_LIFETIME_SECS),
+        })
+    }
+
+    /// Credential store holding the cached token
+    fn credential_store(&self) -> Result<Arc<dyn CredentialStore>> {
+        match &self.credentials {
+            Some(store) => Ok(store.clone()),
+            None => credentials::default_store(),
+        }
     }
 
-    /// Get cached token from system keyring
+    /// Get cached token from the credential store
     fn get_cached_token(&self) -> Result<CachedToken> {
-        let entry = keyring::Entry::new(&self.keyring_service, &self.keyring_user)?;
-
-        let json = entry.get_password()?;
+        let json = self
+            .credential_store()?
+            .get(&self.keyring_service, &self.keyring_user)?
+            .ok_or_else(|| XzatomaError::Credentials("no cached Copilot token".to_string()))?;
 
         Ok(serde_json::from_str(&json)?)
     }
 
-    /// Cache token in system keyring
+    /// Cache token in the credential store
     fn cache_token(&self, token: &CachedToken) -> Result<()> {
-        let entry = keyring::Entry::new(&self.keyring_service, &self.keyring_user)?;
-
         let json = serde_json::to_string(token)?;
 
-        entry.set_password(&json)?;
-
-        Ok(())
+        self.credential_store()?
+            .set(&self.keyring_service, &self.keyring_user, &json)
     }
 
-    /// Clear cached token from system keyring (best-effort).
+    /// Clear cached token from the credential store (best-effort).
     ///
     /// If the provider sees an authentication failure (401) it will attempt
     /// to invalidate the cached token so the next `authenticate()` call will
-    /// perform the device flow again. This uses `set_password("")` as a
-    /// safe, widely-available invalidation step rather than relying on a
-    /// specific delete API that may vary between environments.
+    /// perform the device flow again.
     fn clear_cached_token(&self) -> Result<()> {
-        match keyring::Entry::new(&self.keyring_service, &self.keyring_user) {
-            Ok(entry) => {
-                if let Err(e) = entry.set_password("") {
-                    tracing::warn!("Failed to clear cached Copilot token: {}", e);
-                } else {
-                    tracing::info!("Cleared cached Copilot token (set empty password) in keyring");
-                }
-            }
-            Err(e) => {
-                tracing::warn!("Keyring not available while clearing cached token: {}", e);
-            }
+        let result = self
+            .credential_store()
+            .and_then(|store| store.delete(&self.keyring_service, &self.keyring_user));
+        match result {
+            Ok(()) => tracing::info!("Cleared cached Copilot token"),
+            Err(e) => tracing::warn!("Failed to clear cached Copilot token: {}", e),
         }
         Ok(())
     }
 
     /// Convert XZatoma messages to Copilot format.
     ///
-    /// Copilot does not yet serialize image content, so callers must reject
-    /// image-bearing messages before invoking this conversion. Text-only
-    /// multimodal content is folded into the legacy text field.
-    fn convert_messages(&self, messages: &[Message]) -> Vec<CopilotMessage> {
+    /// Messages with image content are sent as an OpenAI-style content array
+    /// of `text` and `image_url` parts. Text-only multimodal content is folded
+    /// into the legacy text field so text requests keep their original shape.
+    fn convert_messages(&self, messages: &[Message]) -> Vec<CopilotRequestMessage> {
         let validated_messages = crate::providers::validate_message_sequence(messages);
         validated_messages
             .iter()
             .filter_map(|m| {
-                if m.has_image_content() {
-                    tracing::warn!(
-                        role = %m.role,
-                        "Dropping Copilot message with image content after validation should have rejected it"
-                    );
-                    return None;
-                }
-
-                let content = if m.has_text_only_multimodal_content() {
-                    Some(
+                let content = if m.has_image_content() {
+                    Some(CopilotMessageContent::Parts(
                         m.multimodal_parts()
                             .iter()
                             .filter_map(|part| match part {
-                                crate::providers::ProviderMessageContentPart::Text { text } => {
-                                    Some(text.as_str())
+                                ProviderMessageContentPart::Text { text } => {
+                                    Some(CopilotContentPart::Text { text: text.clone() })
                                 }
-                                crate::providers::ProviderMessageContentPart::Image { .. } => None,
+                                ProviderMessageContentPart::Image {
+                                    mime_type, source, ..
+                                } => image_part_url(mime_type, source).map(|url| {
+                                    CopilotContentPart::ImageUrl {
+                                        image_url: CopilotImageUrl { url },
+                                    }
+                                }),
+                            })
+                            .collect(),
+                    ))
+                } else if m.has_text_only_multimodal_content() {
+                    Some(CopilotMessageContent::Text(
+                        m.multimodal_parts()
+                            .iter()
+                            .filter_map(|part| match part {
+                                ProviderMessageContentPart::Text { text } => Some(text.as_str()),
+                                ProviderMessageContentPart::Image { .. } => None,
                             })
                             .collect::<Vec<_>>()
                             .join("\n\n"),
-                    )
+                    ))
                 } else {
-                    m.content.clone()
+                    m.content.clone().map(CopilotMessageContent::Text)
                 };
 
                 if content.is_none() && m.tool_calls.is_none() {
@@ -1712,9 +2489,9 @@ impl CopilotProvider {
                         .collect()
                 });
 
-                Some(CopilotMessage {
+                Some(CopilotRequestMessage {
                     role: m.role.clone(),
-                    content: content.unwrap_or_default(),
+                    content: content.unwrap_or_else(|| CopilotMessageContent::Text(String::new())),
                     tool_calls,
                     tool_call_id: m.tool_call_id.clone(),
                 })
@@ -1836,179 +2613,40 @@ impl CopilotProvider {
             }
         }
 
-        let token = self.authenticate().await?;
         let models_url = self.api_endpoint("models");
 
+        let timeout = timeouts::request_timeout(timeouts::METADATA_TIMEOUT);
         let response = self
-            .client
-            .get(&models_url)
-            .header("Authorization", format!("Bearer {}", token))
-            .header("Editor-Version", "vscode/1.85.0")
-            .send()
-            .await
-            .map_err(|e| {
-                tracing::error!("Failed to fetch Copilot models: {}", e);
-                XzatomaError::Provider(format!("Failed to fetch Copilot models: {}", e))
-            })?;
+            .send_authorized(|token| {
+                let request = self
+                    .client
+                    .get(&models_url)
+                    .timeout(timeout)
+                    .header("Authorization", format!("Bearer {}", token))
+                    .header("Editor-Version", "vscode/1.85.0");
+                async move {
+                    request.send().await.map_err(|e| {
+                        timeouts::request_error(
+                            "copilot",
+                            timeout,
+                            "Failed to fetch Copilot models",
+                            e,
+                        )
+                    })
+                }
+            })
+            .await?;
 
         let status = response.status();
         if !status.is_success() {
+            let retry_after = retry_after::from_headers(response.headers());
             let error_text = response.text().await.unwrap_or_default();
             tracing::error!(
                 "Copilot models API returned error {}: {}",
                 status,
                 error_text
             );
-
-            // If unauthorized, attempt a non-interactive refresh using the cached GitHub token.
-            if status == reqwest::StatusCode::UNAUTHORIZED {
-                tracing::warn!("Copilot returned 401 Unauthorized; attempting non-interactive refresh using cached GitHub token");
-                if let Ok(cached) = self.get_cached_token() {
-                    match self.get_copilot_token(&cached.github_token).await {
-                        Ok(new_token) => {
-                            // Store the refreshed Copilot token (best-effort)
-                            let now = SystemTime::now()
-                                .duration_since(UNIX_EPOCH)
-                                .unwrap()
-                                .as_secs();
-                            let refreshed = CachedToken {
-                                github_token: cached.github_token.clone(),
-                                copilot_token: new_token.clone(),
-                                expires_at: now + 3600,
-                            };
-                            if let Err(e) = self.cache_token(&refreshed) {
-                                tracing::warn!("Failed to cache refreshed Copilot token: {}", e);
-                            } else {
-                                tracing::info!("Successfully refreshed Copilot token using cached GitHub token");
-                            }
-
-                            // Retry models request with refreshed token
-                            let retry_resp = self
-                                .client
-                                .get(&models_url)
-                                .header("Authorization", format!("Bearer {}", new_token))
-                                .header("Editor-Version", "vscode/1.85.0")
-                                .send()
-                                .await
-                                .map_err(|e| {
-                                    tracing::error!(
-                                        "Failed to fetch Copilot models on retry: {}",
-                                        e
-                                    );
-                                    XzatomaError::Provider(format!(
-                                        "Failed to fetch Copilot models: {}",
-                                        e
-                                    ))
-                                })?;
-
-                            let status2 = retry_resp.status();
-                            if !status2.is_success() {
-                                let error_text2 = retry_resp.text().await.unwrap_or_default();
-                                tracing::error!(
-                                    "Copilot models API retry returned error {}: {}",
-                                    status2,
-                                    error_text2
-                                );
-                                if status2 == reqwest::StatusCode::UNAUTHORIZED {
-                                    if let Err(e) = self.clear_cached_token() {
-                                        tracing::warn!(
-                                            "Failed to clear cached Copilot token: {}",
-                                            e
-                                        );
-                                    }
-                                }
-                                return Err(format_copilot_api_error(status2, &error_text2));
-                            }
-
-                            // Parse and return models from the successful retry response
-                            let models_response: CopilotModelsResponse =
-                                retry_resp.json().await.map_err(|e| {
-                                    tracing::error!(
-                                        "Failed to parse Copilot models response on retry: {}",
-                                        e
-                                    );
-                                    XzatomaError::Provider(format!(
-                                        "Failed to parse Copilot models response: {}",
-                                        e
-                                    ))
-                                })?;
-
-                            let mut models = Vec::new();
-                            for model_data in models_response.data {
-                                // Only include enabled models
-                                if let Some(policy) = &model_data.policy {
-                                    if policy.state != "enabled" {
-                                        continue;
-                                    }
-                                }
-
-                                // Extract context window size
-                                let context_window = model_data
-                                    .capabilities
-                                    .as_ref()
-                                    .and_then(|c| c.limits.as_ref())
-                                    .and_then(|l| l.max_context_window_tokens)
-                                    .unwrap_or(128000);
-
-                                let mut model_info = ModelInfo::new(
-                                    &model_data.id,
-                                    &model_data.name,
-                                    context_window,
-                                );
-
-                                // Add capabilities based on supports flags
-                                if let Some(caps) = &model_data.capabilities {
-                                    if let Some(supports) = &caps.supports {
-                                        if supports.tool_calls.unwrap_or(false) {
-                                            model_info
-                                                .add_capability(ModelCapability::FunctionCalling);
-                                        }
-                                        if supports.vision.unwrap_or(false) {
-                                            model_info.add_capability(ModelCapability::Vision);
-                                        }
-                                        if supports.streaming.unwrap_or(false) {
-                                            model_info.add_capability(ModelCapability::Streaming);
-                                        }
-                                    }
-                                }
-
-                                // Add LongContext capability for models with >32k context
-                                if context_window > 32000 {
-                                    model_info.add_capability(ModelCapability::LongContext);
-                                }
-
-                                models.push(model_info);
-                            }
-
-                            // Populate cache via CopilotCache fields.
-                            if let Ok(mut cache_guard) = self.models_cache.write() {
-                                cache_guard.models = Some(models.clone());
-                                cache_guard.cached_at = Some(Instant::now());
-                            } else {
-                                tracing::warn!("Failed to acquire write lock on models cache");
-                            }
-
-                            return Ok(models);
-                        }
-                        Err(e) => {
-                            tracing::warn!("Non-interactive refresh failed: {}", e);
-                            if let Err(e) = self.clear_cached_token() {
-                                tracing::warn!("Failed to clear cached Copilot token: {}", e);
-                            }
-                            return Err(format_copilot_api_error(status, &error_text));
-                        }
-                    }
-                } else {
-                    // No cached GitHub token available; invalidate cache and return auth error
-                    if let Err(e) = self.clear_cached_token() {
-                        tracing::warn!("Failed to clear cached Copilot token: {}", e);
-                    }
-                    return Err(format_copilot_api_error(status, &error_text));
-                }
-            }
-
-            // Non-auth failures fall back to provider error
-            return Err(format_copilot_api_error(status, &error_text));
+            return Err(format_copilot_api_error(status, retry_after, &error_text));
         }
 
         let models_response: CopilotModelsResponse = response.json().await.map_err(|e| {
@@ -2017,6 +2655,7 @@ impl CopilotProvider {
         })?;
 
         let mut models = Vec::new();
+        let mut raw_models = Vec::new();
         for model_data in models_response.data {
             // Only include enabled models
             if let Some(policy) = &model_data.policy {
@@ -2056,11 +2695,13 @@ impl CopilotProvider {
             }
 
             models.push(model_info);
+            raw_models.push(model_data);
         }
 
         // Populate cache via CopilotCache fields.
         if let Ok(mut cache_guard) = self.models_cache.write() {
             cache_guard.models = Some(models.clone());
+            cache_guard.raw_models = Some(raw_models);
             cache_guard.cached_at = Some(Instant::now());
         } else {
             tracing::warn!("Failed to acquire write lock on models cache");
@@ -2074,30 +2715,40 @@ impl CopilotProvider {
         // Note: We're caching ModelInfo, not raw data, so we fetch fresh for raw data
         // This is acceptable since list_models_summary is not called as frequently
 
-        let token = self.authenticate().await?;
         let models_url = self.api_endpoint("models");
 
+        let timeout = timeouts::request_timeout(timeouts::METADATA_TIMEOUT);
         let response = self
-            .client
-            .get(&models_url)
-            .header("Authorization", format!("Bearer {}", token))
-            .header("Editor-Version", "vscode/1.85.0")
-            .send()
-            .await
-            .map_err(|e| {
-                tracing::error!("Failed to fetch Copilot models: {}", e);
-                XzatomaError::Provider(format!("Failed to fetch Copilot models: {}", e))
-            })?;
+            .send_authorized(|token| {
+                let request = self
+                    .client
+                    .get(&models_url)
+                    .timeout(timeout)
+                    .header("Authorization", format!("Bearer {}", token))
+                    .header("Editor-Version", "vscode/1.85.0");
+                async move {
+                    request.send().await.map_err(|e| {
+                        timeouts::request_error(
+                            "copilot",
+                            timeout,
+                            "Failed to fetch Copilot models",
+                            e,
+                        )
+                    })
+                }
+            })
+            .await?;
 
         let status = response.status();
         if !status.is_success() {
+            let retry_after = retry_after::from_headers(response.headers());
             let error_text = response.text().await.unwrap_or_default();
             tracing::error!(
                 "Copilot models API returned error {}: {}",
                 status,
                 error_text
             );
-            return Err(format_copilot_api_error(status, &error_text));
+            return Err(format_copilot_api_error(status, retry_after, &error_text));
         }
 
         let models_response: CopilotModelsResponse = response.json().await.map_err(|e| {
@@ -2205,7 +2856,6 @@ impl CopilotProvider {
         tools: Vec<ToolDefinition>,
     ) -> crate::error::Result<ResponseStream> {
         let url = self.endpoint_url(ModelEndpoint::Responses);
-        let token = self.authenticate().await?;
 
         // Build request
         let request = ResponsesRequest {
@@ -2213,83 +2863,40 @@ impl CopilotProvider {
             input,
             stream: true,
             temperature: None,
+            tool_choice: convert_tool_choice(
+                self.tool_call_options(!tools.is_empty())
+                    .tool_choice
+                    .map(|choice| choice.to_string())
+                    .as_deref(),
+            ),
             tools: if tools.is_empty() { None } else { Some(tools) },
-            tool_choice: None,
             reasoning: None,
             include: None,
         };
 
         // Make HTTP request with streaming
+        let idle_timeout = self.stream_idle_timeout();
         let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .header("Editor-Version", "xzatoma/0.1.0")
-            .header("Accept", "text/event-stream")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| XzatomaError::Provider(e.to_string()))?;
+            .send_authorized(|token| {
+                let request = self
+                    .client
+                    .post(&url)
+                    .header("Authorization", format!("Bearer {}", token))
+                    .header("Editor-Version", "xzatoma/0.1.0")
+                    .header("Accept", "text/event-stream")
+                    .json(&request);
+                send_streaming_request(request, idle_timeout)
+            })
+            .await?;
 
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(XzatomaError::Provider(format!("HTTP {}: {}", status, body)));
+            return Err(context_overflow::detect(&body)
+                .unwrap_or_else(|| XzatomaError::Provider(format!("HTTP {}: {}", status, body))));
         }
 
-        // Create async stream from response body
-        let stream = response.bytes_stream();
-
-        // Build stream using boxed closure
-        let event_stream = futures::stream::unfold(
-            (stream.boxed(), String::new()),
-            |(mut byte_stream, mut buffer)| async move {
-                loop {
-                    match byte_stream.next().await {
-                        Some(Ok(chunk)) => {
-                            buffer.push_str(&String::from_utf8_lossy(&chunk));
-
-                            // Look for complete lines
-                            if let Some(pos) = buffer.find('\n') {
-                                let line = buffer[..pos].to_string();
-                                buffer.drain(..=pos);
-
-                                // Parse SSE line
-                                if let Some(data) = parse_sse_line(&line) {
-                                    let result = parse_sse_event(&data);
-                                    return Some((result, (byte_stream, buffer)));
-                                }
-                                // Continue loop to find next line
-                                continue;
-                            }
-                            // No complete line yet, wait for more chunks
-                            continue;
-                        }
-                        Some(Err(e)) => {
-                            return Some((
-                                Err(XzatomaError::StreamInterrupted(e.to_string())),
-                                (byte_stream, buffer),
-                            ))
-                        }
-                        None => {
-                            // Stream ended
-                            if !buffer.is_empty() {
-                                // Try to parse any remaining content
-                                if let Some(data) = parse_sse_line(&buffer) {
-                                    let result = parse_sse_event(&data);
-                                    buffer.clear();
-                                    return Some((result, (byte_stream, buffer)));
-                                }
-                            }
-                            // Stream complete
-                            return None;
-                        }
-                    }
-                }
-            },
-        );
-
-        Ok(Box::pin(event_stream))
+        Ok(sse_event_stream(response.bytes_stream(), idle_timeout))
     }
 
     /// Stream completions from chat/completions endpoint
@@ -2314,90 +2921,44 @@ impl CopilotProvider {
         tools: &[crate::tools::Tool],
     ) -> crate::error::Result<ResponseStream> {
         let url = self.endpoint_url(ModelEndpoint::ChatCompletions);
-        let token = self.authenticate().await?;
 
         // Build completions request (existing format)
         let copilot_messages = self.convert_messages(messages);
         let copilot_tools = self.convert_tools_legacy(tools);
+        let options = self.tool_call_options(!copilot_tools.is_empty());
 
         let request = CopilotRequest {
             model: model.to_string(),
             messages: copilot_messages,
             tools: copilot_tools,
             stream: true,
+            tool_choice: options.tool_choice.map(|choice| choice.to_string()),
+            parallel_tool_calls: options.parallel_tool_calls,
         };
 
         // Make HTTP request
+        let idle_timeout = self.stream_idle_timeout();
         let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .header("Editor-Version", "xzatoma/0.1.0")
-            .header("Accept", "text/event-stream")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| XzatomaError::Provider(e.to_string()))?;
+            .send_authorized(|token| {
+                let request = self
+                    .client
+                    .post(&url)
+                    .header("Authorization", format!("Bearer {}", token))
+                    .header("Editor-Version", "xzatoma/0.1.0")
+                    .header("Accept", "text/event-stream")
+                    .json(&request);
+                send_streaming_request(request, idle_timeout)
+            })
+            .await?;
 
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(XzatomaError::Provider(format!("HTTP {}: {}", status, body)));
+            return Err(context_overflow::detect(&body)
+                .unwrap_or_else(|| XzatomaError::Provider(format!("HTTP {}: {}", status, body))));
         }
 
-        // Create stream (similar to stream_response but for completions format)
-        let stream = response.bytes_stream();
-
-        // Build stream using boxed closure
-        let event_stream = futures::stream::unfold(
-            (stream.boxed(), String::new()),
-            |(mut byte_stream, mut buffer)| async move {
-                loop {
-                    match byte_stream.next().await {
-                        Some(Ok(chunk)) => {
-                            buffer.push_str(&String::from_utf8_lossy(&chunk));
-
-                            // Look for complete lines
-                            if let Some(pos) = buffer.find('\n') {
-                                let line = buffer[..pos].to_string();
-                                buffer.drain(..=pos);
-
-                                // Parse SSE line
-                                if let Some(data) = parse_sse_line(&line) {
-                                    let result = parse_sse_event(&data);
-                                    return Some((result, (byte_stream, buffer)));
-                                }
-                                // Continue loop to find next line
-                                continue;
-                            }
-                            // No complete line yet, wait for more chunks
-                            continue;
-                        }
-                        Some(Err(e)) => {
-                            return Some((
-                                Err(XzatomaError::StreamInterrupted(e.to_string())),
-                                (byte_stream, buffer),
-                            ))
-                        }
-                        None => {
-                            // Stream ended
-                            if !buffer.is_empty() {
-                                // Try to parse any remaining content
-                                if let Some(data) = parse_sse_line(&buffer) {
-                                    let result = parse_sse_event(&data);
-                                    buffer.clear();
-                                    return Some((result, (byte_stream, buffer)));
-                                }
-                            }
-                            // Stream complete
-                            return None;
-                        }
-                    }
-                }
-            },
-        );
-
-        Ok(Box::pin(event_stream))
+        Ok(sse_event_stream(response.bytes_stream(), idle_timeout))
     }
 
     /// Convert XZatoma tools to Copilot format (legacy for completions endpoint)
@@ -2495,7 +3056,7 @@ impl CopilotProvider {
 
         futures::pin_mut!(stream);
         while let Some(event_result) = stream.next().await {
-            let event = event_result?;
+            let event = event_result.map_err(|e| with_partial_content(e, &acc.content))?;
             acc.apply_event(&event);
         }
 
@@ -2513,8 +3074,6 @@ impl CopilotProvider {
         input: Vec<ResponseInputItem>,
         tools: Vec<ToolDefinition>,
     ) -> Result<CompletionResponse> {
-        let token = self.authenticate().await?;
-
         tracing::debug!(
             "Sending blocking /responses request: {} input items",
             input.len()
@@ -2550,7 +3109,13 @@ impl CopilotProvider {
             temperature: None,
             tools: if has_tools { Some(tools) } else { None },
             tool_choice: if has_tools {
-                Some(ToolChoice::Auto { auto: true })
+                convert_tool_choice(
+                    self.tool_call_options(true)
+                        .tool_choice
+                        .map(|choice| choice.to_string())
+                        .as_deref(),
+                )
+                .or(Some(ToolChoice::Auto { auto: true }))
             } else {
                 None
             },
@@ -2559,25 +3124,31 @@ impl CopilotProvider {
         };
 
         let url = self.endpoint_url(ModelEndpoint::Responses);
+        let timeout = self.completion_timeout();
 
         let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .header("Editor-Version", "vscode/1.85.0")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| {
-                tracing::error!("/responses request failed: {}", e);
-                XzatomaError::Provider(format!("/responses request failed: {}", e))
-            })?;
+            .send_authorized(|token| {
+                let request = self
+                    .client
+                    .post(&url)
+                    .timeout(timeout)
+                    .header("Authorization", format!("Bearer {}", token))
+                    .header("Editor-Version", "vscode/1.85.0")
+                    .json(&request);
+                async move {
+                    request.send().await.map_err(|e| {
+                        timeouts::request_error("copilot", timeout, "/responses request failed", e)
+                    })
+                }
+            })
+            .await?;
 
         let status = response.status();
         if !status.is_success() {
+            let retry_after = retry_after::from_headers(response.headers());
             let error_text = response.text().await.unwrap_or_default();
             tracing::error!("/responses returned error {}: {}", status, error_text);
-            return Err(format_copilot_api_error(status, &error_text));
+            return Err(format_copilot_api_error(status, retry_after, &error_text));
         }
 
         // Parse response - for /responses endpoint, we expect a message-like response
@@ -2604,8 +3175,7 @@ impl CopilotProvider {
         }
 
         let responses_resp: ResponsesResponse = response.json().await.map_err(|e| {
-            tracing::error!("Failed to parse /responses response: {}", e);
-            XzatomaError::Provider(format!("Failed to parse /responses response: {}", e))
+            timeouts::request_error("copilot", timeout, "Failed to parse /responses response", e)
         })?;
 
         let response_model = responses_resp.model.unwrap_or_else(|| model.to_string());
@@ -2660,7 +3230,7 @@ impl CopilotProvider {
 
         futures::pin_mut!(stream);
         while let Some(event_result) = stream.next().await {
-            let event = event_result?;
+            let event = event_result.map_err(|e| with_partial_content(e, &acc.content))?;
             acc.apply_chunk(&event);
         }
 
@@ -2678,13 +3248,15 @@ impl CopilotProvider {
         messages: &[Message],
         tools: &[crate::tools::Tool],
     ) -> Result<CompletionResponse> {
-        let token = self.authenticate().await?;
-
+        let copilot_tools = self.convert_tools_legacy(tools);
+        let options = self.tool_call_options(!copilot_tools.is_empty());
         let copilot_request = CopilotRequest {
             model: model.to_string(),
             messages: self.convert_messages(messages),
-            tools: self.convert_tools_legacy(tools),
+            tools: copilot_tools,
             stream: false,
+            tool_choice: options.tool_choice.map(|choice| choice.to_string()),
+            parallel_tool_calls: options.parallel_tool_calls,
         };
 
         tracing::debug!(
@@ -2694,105 +3266,44 @@ impl CopilotProvider {
         );
 
         let url = self.endpoint_url(ModelEndpoint::ChatCompletions);
+        let timeout = self.completion_timeout();
 
         let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .header("Editor-Version", "vscode/1.85.0")
-            .json(&copilot_request)
-            .send()
-            .await
-            .map_err(|e| {
-                tracing::error!("/chat/completions request failed: {}", e);
-                XzatomaError::Provider(format!("/chat/completions request failed: {}", e))
-            })?;
+            .send_authorized(|token| {
+                let request = self
+                    .client
+                    .post(&url)
+                    .timeout(timeout)
+                    .header("Authorization", format!("Bearer {}", token))
+                    .header("Editor-Version", "vscode/1.85.0")
+                    .json(&copilot_request);
+                async move {
+                    request.send().await.map_err(|e| {
+                        timeouts::request_error(
+                            "copilot",
+                            timeout,
+                            "/chat/completions request failed",
+                            e,
+                        )
+                    })
+                }
+            })
+            .await?;
 
         let status = response.status();
         if !status.is_success() {
+            let retry_after = retry_after::from_headers(response.headers());
             let error_text = response.text().await.unwrap_or_default();
             tracing::error!(
                 "/chat/completions returned error {}: {}",
                 status,
                 error_text
             );
-
-            if status == reqwest::StatusCode::UNAUTHORIZED {
-                tracing::warn!("Attempting token refresh");
-                if let Ok(cached) = self.get_cached_token() {
-                    if let Ok(new_token) = self.get_copilot_token(&cached.github_token).await {
-                        let refreshed = CachedToken {
-                            github_token: cached.github_token.clone(),
-                            copilot_token: new_token.clone(),
-                            expires_at: SystemTime::now()
-                                .duration_since(UNIX_EPOCH)
-                                .unwrap()
-                                .as_secs()
-                                + 3600,
-                        };
-                        if let Err(e) = self.cache_token(&refreshed) {
-                            tracing::warn!("Failed to cache refreshed token: {}", e);
-                        }
-
-                        // Retry with new token
-                        let retry_response = self
-                            .client
-                            .post(&url)
-                            .header("Authorization", format!("Bearer {}", new_token))
-                            .header("Editor-Version", "vscode/1.85.0")
-                            .json(&copilot_request)
-                            .send()
-                            .await
-                            .map_err(|e| {
-                                tracing::error!("Retry failed: {}", e);
-                                XzatomaError::Provider(format!("Retry failed: {}", e))
-                            })?;
-
-                        let retry_status = retry_response.status();
-                        if !retry_status.is_success() {
-                            let error_text = retry_response.text().await.unwrap_or_default();
-                            tracing::error!(
-                                "/chat/completions retry returned error {}: {}",
-                                retry_status,
-                                error_text
-                            );
-                            return Err(format_copilot_api_error(retry_status, &error_text));
-                        }
-
-                        let copilot_response: CopilotResponse =
-                            retry_response.json().await.map_err(|e| {
-                                tracing::error!("Failed to parse response: {}", e);
-                                XzatomaError::Provider(format!("Failed to parse response: {}", e))
-                            })?;
-
-                        let choice =
-                            copilot_response.choices.into_iter().next().ok_or_else(|| {
-                                XzatomaError::Provider("No choices in response".to_string())
-                            })?;
-
-                        let message = self.convert_response_message(choice.message);
-                        let usage = copilot_response
-                            .usage
-                            .map(|u| TokenUsage::new(u.prompt_tokens, u.completion_tokens));
-
-                        return Ok(match usage {
-                            Some(u) => CompletionResponse::with_usage(message, u)
-                                .set_model(model.to_string()),
-                            None => CompletionResponse::new(message).set_model(model.to_string()),
-                        });
-                    }
-                }
-                if let Err(e) = self.clear_cached_token() {
-                    tracing::warn!("Failed to clear cached token: {}", e);
-                }
-            }
-
-            return Err(format_copilot_api_error(status, &error_text));
+            return Err(format_copilot_api_error(status, retry_after, &error_text));
         }
 
         let copilot_response: CopilotResponse = response.json().await.map_err(|e| {
-            tracing::error!("Failed to parse response: {}", e);
-            XzatomaError::Provider(format!("Failed to parse response: {}", e))
+            timeouts::request_error("copilot", timeout, "Failed to parse response", e)
         })?;
 
         let choice = copilot_response
@@ -2898,7 +3409,10 @@ impl CopilotProvider {
         let model = models_data
             .iter()
             .find(|m| m.id == model_name || m.name == model_name)
-            .ok_or_else(|| XzatomaError::Provider(format!("Model not found: {}", model_name)))?;
+            .ok_or_else(|| XzatomaError::ModelNotFound {
+                model: model_name.to_string(),
+                available: models_data.iter().map(|m| m.id.clone()).collect(),
+            })?;
 
         // If supported_endpoints is empty, assume all legacy endpoints are supported
         if model.supported_endpoints.is_empty() {
@@ -2908,6 +3422,35 @@ impl CopilotProvider {
         let endpoint_name = endpoint.as_str();
         Ok(model.supports_endpoint(endpoint_name))
     }
+
+    /// Check if a model accepts image input
+    ///
+    /// Queries the models API for the `capabilities.supports.vision` flag.
+    ///
+    /// # Arguments
+    ///
+    /// * `model_name` - Name of the model to check
+    ///
+    /// # Returns
+    ///
+    /// Returns true if the model reports vision support
+    ///
+    /// # Errors
+    ///
+    /// Returns error if models API is unreachable or model not found
+    async fn model_supports_vision(&self, model_name: &str) -> Result<bool> {
+        let models_data = self.fetch_copilot_models_raw().await?;
+
+        let model = models_data
+            .iter()
+            .find(|m| m.id == model_name || m.name == model_name)
+            .ok_or_else(|| XzatomaError::ModelNotFound {
+                model: model_name.to_string(),
+                available: models_data.iter().map(|m| m.id.clone()).collect(),
+            })?;
+
+        Ok(model.supports_vision())
+    }
 }
 
 #[async_trait]
@@ -2924,9 +3467,9 @@ impl Provider for CopilotProvider {
             (config.model.clone(), config.enable_streaming)
         }; // Drop the read guard before awaits
 
-        if messages_contain_image_content(messages) {
+        if messages_contain_image_content(messages) && !self.model_supports_vision(&model).await? {
             return Err(XzatomaError::Provider(format!(
-                "Copilot model '{}' cannot receive image input because native Copilot image serialization is not implemented",
+                "Copilot model '{}' does not support image input",
                 model
             )));
         }
@@ -2973,7 +3516,7 @@ impl Provider for CopilotProvider {
     }
 
     /// Returns `true` if this provider has a valid non-expired Copilot token
-    /// cached in the system keyring.
+    /// cached in the credential store.
     fn is_authenticated(&self) -> bool {
         if let Ok(cached) = self.get_cached_token() {
             let now = SystemTime::now()
@@ -3018,10 +3561,14 @@ impl Provider for CopilotProvider {
     async fn get_model_info(&self, model_name: &str) -> Result<ModelInfo> {
         tracing::debug!("Getting info for model: {}", model_name);
         let models = self.fetch_copilot_models().await?;
+        let available: Vec<String> = models.iter().map(|m| m.name.clone()).collect();
         models
             .into_iter()
             .find(|m| m.name == model_name)
-            .ok_or_else(|| XzatomaError::Provider(format!("Model not found: {}", model_name)))
+            .ok_or_else(|| XzatomaError::ModelNotFound {
+                model: model_name.to_string(),
+                available,
+            })
     }
 
     /// Get the name of the currently active model.
@@ -3046,9 +3593,30 @@ impl Provider for CopilotProvider {
             supports_token_counts: true,
             supports_streaming: true,
             supports_vision: false,
+            max_tools: Some(OPENAI_MAX_TOOLS),
+            max_tool_description_chars: Some(OPENAI_MAX_TOOL_DESCRIPTION_CHARS),
+            max_tool_definitions_bytes: None,
+            // The models metadata lists parallel tool calls per model; until
+            // it has been fetched, assume the OpenAI-compatible default.
+            supports_parallel_tool_calls: self
+                .cached_model_supports(&self.get_current_model())
+                .map(|supports| supports.parallel_tool_calls.unwrap_or(false))
+                .unwrap_or(true),
+            supports_tool_choice: true,
+            max_tools_per_turn: None,
         }
     }
 
+    fn set_tool_call_options(&self, options: ToolCallOptions) -> crate::error::Result<()> {
+        let mut current = self.tool_call_options.write().map_err(|_| {
+            crate::error::XzatomaError::Provider(
+                "Failed to acquire write lock on Copilot tool-call options".to_string(),
+            )
+        })?;
+        *current = options;
+        Ok(())
+    }
+
     fn set_thinking_effort(&self, effort: Option<&str>) -> crate::error::Result<()> {
         let mut config = self.config.write().map_err(|_| {
             crate::error::XzatomaError::Provider(
@@ -3080,10 +3648,14 @@ impl Provider for CopilotProvider {
 
     async fn get_model_info_summary(&self, model_name: &str) -> Result<ModelInfoSummary> {
         let models_data = self.fetch_copilot_models_raw().await?;
+        let available: Vec<String> = models_data.iter().map(|m| m.id.clone()).collect();
         let data = models_data
             .into_iter()
             .find(|m| m.id == model_name || m.name == model_name)
-            .ok_or_else(|| XzatomaError::Provider(format!("Model '{}' not found", model_name)))?;
+            .ok_or_else(|| XzatomaError::ModelNotFound {
+                model: model_name.to_string(),
+                available,
+            })?;
         Ok(self.convert_to_summary(data))
     }
 }
@@ -3389,6 +3961,28 @@ mod tests {
         assert!(caps.supports_model_switching);
         assert!(caps.supports_token_counts);
         assert!(caps.supports_streaming);
+        assert!(caps.supports_parallel_tool_calls);
+        assert!(caps.supports_tool_choice);
+    }
+
+    #[test]
+    fn test_provider_capabilities_use_cached_model_metadata() {
+        let config = CopilotConfig {
+            model: "o1-mini".to_string(),
+            ..Default::default()
+        };
+        let provider = CopilotProvider::new(config).unwrap();
+        let model: CopilotModelData = serde_json::from_str(
+            r#"{"id":"o1-mini","name":"o1 mini","capabilities":{"supports":{"tool_calls":true}}}"#,
+        )
+        .unwrap();
+        provider.models_cache.write().unwrap().raw_models = Some(vec![model]);
+
+        assert!(
+            !provider
+                .get_provider_capabilities()
+                .supports_parallel_tool_calls
+        );
     }
 
     #[test]
@@ -3530,25 +4124,65 @@ mod tests {
 
         let err = format_copilot_api_error(
             reqwest::StatusCode::UNAUTHORIZED,
+            None,
             "unauthorized: token expired",
         );
-        assert!(matches!(err, XzatomaError::Authentication(_)));
+        assert!(matches!(err, XzatomaError::Auth { .. }));
         assert!(
             err.to_string().contains("token expired")
                 || err.to_string().contains("Token may have expired")
         );
+        assert!(err
+            .remediation_hint()
+            .contains("xzatoma auth --provider copilot"));
     }
 
     #[test]
-    fn test_format_copilot_api_error_other() {
+    fn test_format_copilot_api_error_rate_limited() {
         use crate::error::XzatomaError;
 
         let err =
-            format_copilot_api_error(reqwest::StatusCode::INTERNAL_SERVER_ERROR, "internal error");
+            format_copilot_api_error(reqwest::StatusCode::TOO_MANY_REQUESTS, None, "slow down");
+        assert!(matches!(
+            err,
+            XzatomaError::RateLimited {
+                retry_after: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_format_copilot_api_error_other() {
+        use crate::error::XzatomaError;
+
+        let err = format_copilot_api_error(
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+            None,
+            "internal error",
+        );
         assert!(matches!(err, XzatomaError::Provider(_)));
         assert!(err.to_string().contains("internal error"));
     }
 
+    #[test]
+    fn test_format_copilot_api_error_context_overflow() {
+        use crate::error::XzatomaError;
+
+        let err = format_copilot_api_error(
+            reqwest::StatusCode::BAD_REQUEST,
+            None,
+            r#"{"error":{"message":"prompt token count of 140213 exceeds the limit of 128000","code":"model_max_prompt_tokens_exceeded"}}"#,
+        );
+        assert!(matches!(
+            err,
+            XzatomaError::ContextOverflow {
+                limit: Some(128000),
+                attempted: Some(140213),
+            }
+        ));
+    }
+
     #[test]
     fn test_convert_to_summary_full_data() {
         let config = CopilotConfig::default();
@@ -3725,6 +4359,72 @@ mod tests {
         assert_eq!(converted[2].tool_call_id, Some("call_123".to_string()));
     }
 
+    fn image_message() -> Message {
+        use crate::providers::{ImagePromptPart, MultimodalPromptInput, PromptInputPart};
+
+        Message::try_user_from_multimodal_input(MultimodalPromptInput::new(vec![
+            PromptInputPart::text("What is in this screenshot?"),
+            PromptInputPart::image(ImagePromptPart::inline_base64("image/png", "iVBORw0KGgo=")),
+        ]))
+        .expect("valid multimodal message")
+    }
+
+    #[test]
+    fn test_convert_messages_emits_content_array_for_images() {
+        let provider = CopilotProvider::new(CopilotConfig::default()).unwrap();
+
+        let converted = provider.convert_messages(&[image_message()]);
+        let json = serde_json::to_value(&converted[0]).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "What is in this screenshot?"},
+                    {
+                        "type": "image_url",
+                        "image_url": {"url": "data:image/png;base64,iVBORw0KGgo="}
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_convert_messages_keeps_string_content_for_text() {
+        let provider = CopilotProvider::new(CopilotConfig::default()).unwrap();
+
+        let converted = provider.convert_messages(&[Message::user("hi")]);
+        let json = serde_json::to_value(&converted[0]).unwrap();
+
+        assert_eq!(json, serde_json::json!({"role": "user", "content": "hi"}));
+    }
+
+    #[test]
+    fn test_convert_messages_to_response_input_emits_input_image() {
+        let input = convert_messages_to_response_input(&[image_message()]).unwrap();
+        let json = serde_json::to_value(&input[0]).unwrap();
+
+        assert_eq!(json["content"][0]["type"], "input_text");
+        assert_eq!(json["content"][1]["type"], "input_image");
+        assert_eq!(
+            json["content"][1]["image_url"],
+            "data:image/png;base64,iVBORw0KGgo="
+        );
+    }
+
+    #[test]
+    fn test_copilot_model_data_supports_vision() {
+        let json = r#"{"id":"gpt-4o","name":"GPT-4o","capabilities":{"supports":{"vision":true}}}"#;
+        let model: CopilotModelData = serde_json::from_str(json).unwrap();
+        assert!(model.supports_vision());
+
+        let json = r#"{"id":"o3-mini","name":"o3-mini"}"#;
+        let model: CopilotModelData = serde_json::from_str(json).unwrap();
+        assert!(!model.supports_vision());
+    }
+
     // ========================================================================
     // PHASE 1 TESTS: Core Data Structures and Endpoint Detection
     // ========================================================================
@@ -4005,6 +4705,9 @@ mod tests {
             enable_endpoint_fallback: true,
             reasoning_effort: None,
             include_reasoning: false,
+            request_timeout_seconds: 120,
+            stream_idle_timeout_seconds: 60,
+            token_refresh_margin_seconds: 300,
         };
 
         let provider = CopilotProvider::new(config).expect("Failed to create provider");
@@ -4028,6 +4731,9 @@ mod tests {
             enable_endpoint_fallback: true,
             reasoning_effort: None,
             include_reasoning: false,
+            request_timeout_seconds: 120,
+            stream_idle_timeout_seconds: 60,
+            token_refresh_margin_seconds: 300,
         };
 
         let provider = CopilotProvider::new(config).expect("Failed to create provider");
@@ -4471,34 +5177,328 @@ mod tests {
 
     // Phase 3: SSE Parsing Tests
 
+    /// Feed `chunks` through a decoder and interpreter as a stream would.
+    fn decode_chunks(chunks: &[&[u8]]) -> Vec<Result<StreamEvent>> {
+        let mut decoder = SseDecoder::default();
+        let mut interpreter = SseEventInterpreter::default();
+        let mut frames = Vec::new();
+        for chunk in chunks {
+            frames.extend(decoder.push(chunk));
+        }
+        frames.extend(decoder.finish());
+        frames
+            .iter()
+            .flat_map(|frame| match interpreter.interpret(frame) {
+                Ok(events) => events.into_iter().map(Ok).collect::<Vec<_>>(),
+                Err(e) => vec![Err(e)],
+            })
+            .collect()
+    }
+
+    fn message_text(event: &StreamEvent) -> Option<&str> {
+        match event {
+            StreamEvent::Message { content, .. } => content.iter().find_map(|c| match c {
+                ResponseInputContent::OutputText { text } => Some(text.as_str()),
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_sse_decoder_waits_for_blank_line() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(b"data: {\"a\":1}\n").is_empty());
+        let frames = decoder.push(b"\n");
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].data, r#"{"a":1}"#);
+        assert_eq!(frames[0].event, None);
+    }
+
+    #[test]
+    fn test_sse_decoder_multiple_events_in_one_chunk() {
+        let mut decoder = SseDecoder::default();
+        let frames = decoder.push(b"data: one\n\ndata: two\n\ndata: [DONE]\n\n");
+        let data: Vec<&str> = frames.iter().map(|f| f.data.as_str()).collect();
+        assert_eq!(data, vec!["one", "two", "[DONE]"]);
+    }
+
+    #[test]
+    fn test_sse_decoder_split_inside_data_field() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(b"da").is_empty());
+        assert!(decoder.push(b"ta: hel").is_empty());
+        let frames = decoder.push(b"lo\n\n");
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].data, "hello");
+    }
+
+    #[test]
+    fn test_sse_decoder_split_inside_utf8_char() {
+        let payload = "data: caf\u{e9} \u{1f600}\n\n".as_bytes();
+        // Split inside both the two-byte and the four-byte sequences.
+        let e_acute = payload.iter().position(|b| *b == 0xc3).unwrap();
+        let emoji = payload.iter().position(|b| *b == 0xf0).unwrap();
+
+        let mut decoder = SseDecoder::default();
+        let mut frames = decoder.push(&payload[..e_acute + 1]);
+        frames.extend(decoder.push(&payload[e_acute + 1..emoji + 2]));
+        frames.extend(decoder.push(&payload[emoji + 2..]));
+
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].data, "caf\u{e9} \u{1f600}");
+    }
+
+    #[test]
+    fn test_sse_decoder_ignores_interleaved_comments() {
+        let mut decoder = SseDecoder::default();
+        let frames = decoder.push(b": keep-alive\n\ndata: a\n: ping\ndata: b\n\n:\n\n");
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].data, "a\nb");
+    }
+
+    #[test]
+    fn test_sse_decoder_handles_crlf_and_event_field() {
+        let mut decoder = SseDecoder::default();
+        let frames = decoder.push(b"event: error\r\ndata: {}\r\n\r\n");
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].event.as_deref(), Some("error"));
+        assert_eq!(frames[0].data, "{}");
+    }
+
+    #[test]
+    fn test_sse_decoder_flushes_unterminated_event() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(b"data: [DONE]").is_empty());
+        let frame = decoder.finish().expect("pending event");
+        assert_eq!(frame.data, "[DONE]");
+        assert!(decoder.finish().is_none());
+    }
+
+    #[test]
+    fn test_sse_adversarial_chunking_preserves_content() {
+        let body = concat!(
+            ": connected\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Gr\u{fc}\"}}]}\n\n",
+            ": ping\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"\u{df}e \u{1f44b}\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        )
+        .as_bytes();
+
+        // Every possible single split point must decode identically.
+        for split in 0..=body.len() {
+            let events = decode_chunks(&[&body[..split], &body[split..]]);
+            let text: String = events
+                .iter()
+                .filter_map(|e| e.as_ref().ok().and_then(message_text))
+                .collect();
+            assert_eq!(text, "Gr\u{fc}\u{df}e \u{1f44b}", "split at {}", split);
+            assert!(matches!(events.last(), Some(Ok(StreamEvent::Done))));
+        }
+
+        // One byte at a time.
+        let bytes: Vec<&[u8]> = body.chunks(1).collect();
+        let events = decode_chunks(&bytes);
+        assert_eq!(events.iter().filter(|e| e.is_ok()).count(), 3);
+    }
+
+    #[test]
+    fn test_sse_trailing_usage_chunk_does_not_alter_content() {
+        let body = concat!(
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"lo\"}}]}\n\n",
+            "data: {\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"length\"}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":7,\"completion_tokens\":2,\"total_tokens\":9}}\n\n",
+            "data: [DONE]\n\n",
+        );
+
+        let mut acc = ChatCompletionsAccumulator::new();
+        for event in decode_chunks(&[body.as_bytes()]) {
+            acc.apply_chunk(&event.expect("valid event"));
+        }
+        let response = acc.finalize();
+
+        assert_eq!(response.message.content.as_deref(), Some("Hello"));
+        assert_eq!(response.finish_reason, FinishReason::Length);
+        let usage = response.usage.expect("usage");
+        assert_eq!(usage.prompt_tokens, 7);
+        assert_eq!(usage.completion_tokens, 2);
+    }
+
+    #[test]
+    fn test_sse_chat_tool_call_deltas_keep_call_id() {
+        let body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"function\":{\"name\":\"read_file\",\"arguments\":\"{\\\"pa\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"th\\\":\\\"a\\\"}\"}}]},\"finish_reason\":\"tool_calls\"}]}\n\n",
+        );
+
+        let mut acc = ChatCompletionsAccumulator::new();
+        for event in decode_chunks(&[body.as_bytes()]) {
+            acc.apply_chunk(&event.expect("valid event"));
+        }
+        let response = acc.finalize();
+
+        let calls = response.message.tool_calls.expect("tool calls");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(calls[0].function.name, "read_file");
+        assert_eq!(calls[0].function.arguments, r#"{"path":"a"}"#);
+        assert_eq!(response.finish_reason, FinishReason::ToolCalls);
+    }
+
     #[test]
-    fn test_parse_sse_data_line() {
-        let line = r#"data: {"type":"message"}"#;
-        let result = parse_sse_line(line);
-        assert!(result.is_some());
-        assert_eq!(result.unwrap(), r#"{"type":"message"}"#);
+    fn test_sse_error_event_becomes_stream_failed() {
+        let body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"partial\"}}]}\n\n",
+            "event: error\n",
+            "data: {\"error\":{\"message\":\"upstream overloaded\"}}\n\n",
+        );
+
+        let events = decode_chunks(&[body.as_bytes()]);
+        assert_eq!(events.len(), 2);
+        match &events[1] {
+            Err(XzatomaError::StreamFailed {
+                provider, reason, ..
+            }) => {
+                assert_eq!(provider, "copilot");
+                assert_eq!(reason, "upstream overloaded");
+            }
+            other => panic!("Expected StreamFailed, got {:?}", other),
+        }
     }
 
     #[test]
-    fn test_parse_sse_done_sentinel() {
-        let line = "data: [DONE]";
-        let result = parse_sse_line(line);
-        assert!(result.is_some());
-        assert_eq!(result.unwrap(), "[DONE]");
+    fn test_sse_error_payload_without_event_field() {
+        let events = decode_chunks(&[b"data: {\"type\":\"error\",\"message\":\"boom\"}\n\n"]);
+        assert!(matches!(
+            &events[0],
+            Err(XzatomaError::StreamFailed { reason, .. }) if reason == "boom"
+        ));
     }
 
     #[test]
-    fn test_parse_sse_ignore_metadata() {
-        assert!(parse_sse_line("event: message").is_none());
-        assert!(parse_sse_line("id: 123").is_none());
-        assert!(parse_sse_line(": comment").is_none());
+    fn test_sse_unknown_event_type_is_ignored() {
+        let events = decode_chunks(&[
+            b"data: {\"type\":\"response.created\",\"id\":\"r1\"}\n\n",
+            b"event: ping\ndata: {}\n\n",
+            b"data: {\"type\":\"status\",\"status\":\"in_progress\"}\n\n",
+        ]);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], Ok(StreamEvent::Status { .. })));
     }
 
     #[test]
-    fn test_parse_sse_empty_lines() {
-        assert!(parse_sse_line("").is_none());
-        assert!(parse_sse_line("   ").is_none());
-        assert!(parse_sse_line("\n").is_none());
+    fn test_with_partial_content_attaches_received_text() {
+        let err = with_partial_content(
+            XzatomaError::StreamInterrupted("reset".to_string()),
+            "Hello",
+        );
+        match err {
+            XzatomaError::StreamFailed {
+                partial_content, ..
+            } => assert_eq!(partial_content.as_deref(), Some("Hello")),
+            other => panic!("Expected StreamFailed, got {:?}", other),
+        }
+
+        let err = with_partial_content(XzatomaError::StreamInterrupted("reset".to_string()), "");
+        assert!(matches!(err, XzatomaError::StreamInterrupted(_)));
+    }
+
+    #[tokio::test]
+    async fn test_sse_event_stream_over_http() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let body = concat!(
+            ": keep-alive\n\n",
+            "data: {\"type\":\"message\",\"role\":\"assistant\",\"content\":[{\"type\":\"output_text\",\"text\":\"Hi \"}]}\n\n",
+            "data: {\"type\":\"message\",\"role\":\"assistant\",\"content\":[{\"type\":\"output_text\",\"text\":\"there\"}]}\n\n",
+            "data: {\"type\":\"usage\",\"prompt_tokens\":3,\"completion_tokens\":2}\n\n",
+            "data: [DONE]\n\n",
+            "data: {\"type\":\"message\",\"role\":\"assistant\",\"content\":[{\"type\":\"output_text\",\"text\":\"ignored\"}]}\n\n",
+        );
+        Mock::given(method("POST"))
+            .and(path("/responses"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "text/event-stream")
+                    .set_body_string(body),
+            )
+            .mount(&server)
+            .await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/responses", server.uri()))
+            .send()
+            .await
+            .expect("request");
+        let mut stream = sse_event_stream(response.bytes_stream(), Duration::from_secs(5));
+
+        let mut acc = ResponsesAccumulator::new();
+        while let Some(event) = stream.next().await {
+            acc.apply_event(&event.expect("valid event"));
+        }
+        let response = acc.finalize();
+
+        assert_eq!(response.message.content.as_deref(), Some("Hi there"));
+        assert_eq!(response.usage.expect("usage").total_tokens, 5);
+    }
+
+    #[tokio::test]
+    async fn test_send_streaming_request_times_out_waiting_for_headers() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let request = reqwest::Client::new().post(format!("{}/responses", server.uri()));
+        let err = send_streaming_request(request, Duration::from_millis(100))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, XzatomaError::RequestTimeout { .. }));
+        assert!(err.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_sse_event_stream_fails_when_idle() {
+        let chunks = futures::stream::iter(vec![Ok::<_, std::io::Error>(
+            b"data: {\"type\":\"status\",\"status\":\"in_progress\"}\n\n".to_vec(),
+        )])
+        .chain(futures::stream::pending());
+        let mut stream = sse_event_stream(chunks, Duration::from_millis(50));
+
+        assert!(matches!(
+            stream.next().await,
+            Some(Ok(StreamEvent::Status { .. }))
+        ));
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(XzatomaError::RequestTimeout { idle: true, .. }))
+        ));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sse_event_stream_respects_deadline() {
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(50);
+        let result = timeouts::with_deadline(deadline, async {
+            let chunks = futures::stream::pending::<std::result::Result<Vec<u8>, std::io::Error>>();
+            let mut stream = sse_event_stream(chunks, Duration::from_secs(60));
+            stream.next().await
+        })
+        .await;
+
+        assert!(matches!(
+            result,
+            Some(Err(XzatomaError::RequestTimeout { idle: false, .. }))
+        ));
     }
 
     #[test]
@@ -4635,11 +5635,15 @@ mod tests {
             messages: copilot_messages,
             tools: vec![],
             stream: true,
+            tool_choice: None,
+            parallel_tool_calls: None,
         };
 
         let json = serde_json::to_string(&request).expect("Serialize failed");
         assert!(json.contains("\"stream\":true"));
         assert!(json.contains("\"messages\""));
+        assert!(!json.contains("tool_choice"));
+        assert!(!json.contains("parallel_tool_calls"));
     }
 
     // --- Task 4.1: Endpoint Selection Tests ---
@@ -4690,6 +5694,9 @@ mod tests {
             enable_endpoint_fallback: false,
             reasoning_effort: Some("high".to_string()),
             include_reasoning: true,
+            request_timeout_seconds: 120,
+            stream_idle_timeout_seconds: 60,
+            token_refresh_margin_seconds: 300,
         };
 
         let yaml = serde_yaml::to_string(&config).expect("Serialize failed");
@@ -4802,10 +5809,13 @@ include_reasoning: true
             ..Default::default()
         };
         let _provider = CopilotProvider::new(config).unwrap();
-        // In real usage: select_endpoint returns Err(Provider("Model not found: ..."))
+        // In real usage: select_endpoint returns Err(ModelNotFound { .. })
         // because fetch_copilot_models_raw would not contain "nonexistent-model-xyz".
-        // We verify the error message format via UnsupportedEndpoint / Provider errors.
-        let err = XzatomaError::Provider("Model not found: nonexistent-model-xyz".to_string());
+        // We verify the error message format of the ModelNotFound variant.
+        let err = XzatomaError::ModelNotFound {
+            model: "nonexistent-model-xyz".to_string(),
+            available: vec!["gpt-4o".to_string()],
+        };
         assert!(err.to_string().contains("nonexistent-model-xyz"));
     }
 
@@ -4974,6 +5984,142 @@ include_reasoning: true
         assert!(supports.dimensions.is_none());
     }
 
+    // -----------------------------------------------------------------------
+    // Token refresh unit tests
+    // -----------------------------------------------------------------------
+
+    #[test]
+    fn test_cached_token_expires_within_margin() {
+        let token = CachedToken {
+            github_token: "gho".to_string(),
+            copilot_token: "tok".to_string(),
+            expires_at: 1_000,
+        };
+        assert!(!token.expires_within(600, 300));
+        assert!(token.expires_within(700, 300));
+        assert!(token.expires_within(1_000, 0));
+        assert!(token.expires_within(2_000, 300));
+        assert!(token.expires_within(u64::MAX, 300));
+    }
+
+    #[test]
+    fn test_copilot_token_response_reads_expiry() {
+        let response: CopilotTokenResponse = serde_json::from_str(
+            r#"{"token":"tid=abc","expires_at":1700000000,"refresh_in":1500}"#,
+        )
+        .unwrap();
+        assert_eq!(response.token, "tid=abc");
+        assert_eq!(response.expires_at, Some(1_700_000_000));
+
+        let response: CopilotTokenResponse =
+            serde_json::from_str(r#"{"token":"tid=abc"}"#).unwrap();
+        assert!(response.expires_at.is_none());
+    }
+
+    #[test]
+    fn test_copilot_reauth_error_names_auth_command() {
+        match copilot_reauth_error("Copilot rejected the refreshed token") {
+            XzatomaError::Auth { provider, reason } => {
+                assert_eq!(provider, "copilot");
+                assert!(reason.contains("xzatoma auth --provider copilot"));
+            }
+            other => panic!("Expected Auth error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_token_refresh_margin_from_config() {
+        let provider = CopilotProvider::new(CopilotConfig {
+            token_refresh_margin_seconds: 120,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(provider.token_refresh_margin(), 120);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_response_carries_retry_after() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after", "7")
+                    .set_body_string("slow down"),
+            )
+            .mount(&server)
+            .await;
+
+        let store = Arc::new(crate::credentials::MemoryStore::default());
+        let cached = CachedToken {
+            github_token: "gho_stored".to_string(),
+            copilot_token: "tid=stored".to_string(),
+            expires_at: unix_now() + 3_600,
+        };
+        store
+            .set(
+                crate::providers::factory::KEYRING_SERVICE,
+                crate::providers::factory::KEYRING_COPILOT_USER,
+                &serde_json::to_string(&cached).unwrap(),
+            )
+            .unwrap();
+        let provider = CopilotProvider::new(CopilotConfig {
+            api_base: Some(server.uri()),
+            ..Default::default()
+        })
+        .unwrap()
+        .with_credential_store(store);
+
+        let err = Provider::list_models(&provider).await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                XzatomaError::RateLimited {
+                    retry_after: Some(wait),
+                    ..
+                } if wait == Duration::from_secs(7)
+            ),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cached_token_uses_given_credential_store() {
+        let store = Arc::new(crate::credentials::MemoryStore::default());
+        let cached = CachedToken {
+            github_token: "gho_stored".to_string(),
+            copilot_token: "tid=stored".to_string(),
+            expires_at: unix_now() + 3_600,
+        };
+        store
+            .set(
+                crate::providers::factory::KEYRING_SERVICE,
+                crate::providers::factory::KEYRING_COPILOT_USER,
+                &serde_json::to_string(&cached).unwrap(),
+            )
+            .unwrap();
+        let provider = CopilotProvider::new(CopilotConfig::default())
+            .unwrap()
+            .with_credential_store(store.clone());
+
+        assert!(provider.is_authenticated());
+        assert_eq!(provider.authenticate().await.unwrap(), "tid=stored");
+
+        provider.clear_cached_token().unwrap();
+        assert!(!provider.is_authenticated());
+        assert!(store
+            .get(
+                crate::providers::factory::KEYRING_SERVICE,
+                crate::providers::factory::KEYRING_COPILOT_USER,
+            )
+            .unwrap()
+            .is_none());
+    }
+
     // -----------------------------------------------------------------------
     // Phase 5: CopilotCache unit tests
     // -----------------------------------------------------------------------
@@ -0,0 +1,180 @@
+//! Text embeddings for semantic search
+//!
+//! An [`EmbeddingProvider`] turns text into a vector whose direction captures
+//! its meaning, so passages about the same topic end up close together even
+//! when they share no keywords. The semantic index in
+//! [`crate::semantic_index`] embeds workspace chunks and queries with the
+//! same provider and ranks chunks by cosine similarity.
+//!
+//! [`OllamaEmbeddingProvider`] calls Ollama's `/api/embeddings` endpoint with
+//! the model configured in `semantic.embedding_model`.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::{Result, XzatomaError};
+use crate::providers::timeouts;
+
+/// Timeout for a single embedding request
+const EMBEDDING_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Turns text into embedding vectors
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Name of the embedding model
+    ///
+    /// Stored in the index, so vectors from different models are never
+    /// compared.
+    fn model(&self) -> &str;
+
+    /// Embeds one piece of text
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the embedding service fails or returns no vector.
+    async fn embed(&self, text: &str) -> Result<Vec<f32>>;
+}
+
+/// Request body for Ollama's `/api/embeddings`
+#[derive(Debug, Serialize)]
+struct OllamaEmbeddingRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+}
+
+/// Response body from Ollama's `/api/embeddings`
+#[derive(Debug, Deserialize)]
+struct OllamaEmbeddingResponse {
+    #[serde(default)]
+    embedding: Vec<f32>,
+}
+
+/// Embeddings from an Ollama server
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::providers::embeddings::{EmbeddingProvider, OllamaEmbeddingProvider};
+///
+/// let embedder =
+///     OllamaEmbeddingProvider::new("http://localhost:11434", "nomic-embed-text").unwrap();
+/// assert_eq!(embedder.model(), "nomic-embed-text");
+/// ```
+#[derive(Debug, Clone)]
+pub struct OllamaEmbeddingProvider {
+    client: Client,
+    host: String,
+    model: String,
+}
+
+impl OllamaEmbeddingProvider {
+    /// Creates an embedder for `model` on the Ollama server at `host`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be created.
+    pub fn new(host: impl Into<String>, model: impl Into<String>) -> Result<Self> {
+        let client = Client::builder()
+            .connect_timeout(timeouts::CONNECT_TIMEOUT)
+            .user_agent("xzatoma/0.1.0")
+            .build()
+            .map_err(|e| XzatomaError::Provider(format!("Failed to create HTTP client: {}", e)))?;
+        Ok(Self {
+            client,
+            host: host.into().trim_end_matches('/').to_string(),
+            model: model.into(),
+        })
+    }
+
+    /// Creates an embedder from `provider.ollama.host` and
+    /// `semantic.embedding_model`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be created.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Self::new(
+            config.provider.ollama.host.clone(),
+            config.semantic.embedding_model.clone(),
+        )
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaEmbeddingProvider {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let url = format!("{}/api/embeddings", self.host);
+        let timeout = timeouts::request_timeout(EMBEDDING_TIMEOUT);
+        let response = self
+            .client
+            .post(&url)
+            .timeout(timeout)
+            .json(&OllamaEmbeddingRequest {
+                model: &self.model,
+                prompt: text,
+            })
+            .send()
+            .await
+            .map_err(|e| {
+                timeouts::request_error("ollama", timeout, "Failed to connect to Ollama server", e)
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(XzatomaError::Provider(format!(
+                "Ollama embeddings with model '{}' failed with {}: {}. Pull the model with `ollama pull {}`",
+                self.model, status, error_text, self.model
+            )));
+        }
+
+        let body: OllamaEmbeddingResponse = response.json().await.map_err(|e| {
+            XzatomaError::Provider(format!("Failed to parse Ollama embeddings response: {}", e))
+        })?;
+        if body.embedding.is_empty() {
+            return Err(XzatomaError::Provider(format!(
+                "Ollama returned an empty embedding for model '{}'",
+                self.model
+            )));
+        }
+        Ok(body.embedding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_ollama_embed_posts_model_and_prompt() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/embeddings"))
+            .and(body_partial_json(serde_json::json!({
+                "model": "nomic-embed-text",
+                "prompt": "kafka config",
+            })))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"embedding": [0.1, 0.2]})),
+            )
+            .mount(&server)
+            .await;
+
+        let embedder = OllamaEmbeddingProvider::new(server.uri(), "nomic-embed-text").unwrap();
+        assert_eq!(
+            embedder.embed("kafka config").await.unwrap(),
+            vec![0.1, 0.2]
+        );
+    }
+}
@@ -15,6 +15,7 @@ use crate::config::ProviderConfig;
 use crate::error::Result;
 
 use super::copilot::CopilotProvider;
+use super::governor::wrap_with_governor;
 use super::ollama::OllamaProvider;
 use super::openai::OpenAIProvider;
 use super::trait_mod::Provider;
@@ -44,7 +45,7 @@ pub(crate) const KEYRING_COPILOT_USER: &str = "github_copilot";
 ///
 /// ```no_run
 /// use xzatoma::providers::ProviderFactory;
-/// use xzatoma::config::{ProviderConfig, CopilotConfig, OllamaConfig, OpenAIConfig};
+/// use xzatoma::config::{ProviderConfig, CopilotConfig, OllamaConfig, OpenAIConfig, ProviderCacheConfig, ProviderQuirksConfig, ProviderRateLimitsConfig};
 ///
 /// # fn example() -> xzatoma::error::Result<()> {
 /// let config = ProviderConfig {
@@ -52,6 +53,9 @@ pub(crate) const KEYRING_COPILOT_USER: &str = "github_copilot";
 ///     copilot: CopilotConfig::default(),
 ///     ollama: OllamaConfig::default(),
 ///     openai: OpenAIConfig::default(),
+///     cache: ProviderCacheConfig::default(),
+///     quirks: ProviderQuirksConfig::default(),
+///     rate_limits: ProviderRateLimitsConfig::default(),
 /// };
 ///
 /// let provider = ProviderFactory::create_provider("ollama", &config)?;
@@ -81,7 +85,7 @@ impl ProviderFactory {
     ///
     /// ```no_run
     /// use xzatoma::providers::ProviderFactory;
-    /// use xzatoma::config::{ProviderConfig, CopilotConfig, OllamaConfig, OpenAIConfig};
+    /// use xzatoma::config::{ProviderConfig, CopilotConfig, OllamaConfig, OpenAIConfig, ProviderCacheConfig, ProviderQuirksConfig, ProviderRateLimitsConfig};
     ///
     /// # fn example() -> xzatoma::error::Result<()> {
     /// let config = ProviderConfig {
@@ -89,6 +93,9 @@ impl ProviderFactory {
     ///     copilot: CopilotConfig::default(),
     ///     ollama: OllamaConfig::default(),
     ///     openai: OpenAIConfig::default(),
+    ///     cache: ProviderCacheConfig::default(),
+    ///     quirks: ProviderQuirksConfig::default(),
+    ///     rate_limits: ProviderRateLimitsConfig::default(),
     /// };
     /// let provider = ProviderFactory::create_provider("ollama", &config)?;
     /// # Ok(())
@@ -98,15 +105,18 @@ impl ProviderFactory {
         provider_type: &str,
         config: &ProviderConfig,
     ) -> Result<Box<dyn Provider>> {
-        match provider_type {
-            "copilot" => Ok(Box::new(CopilotProvider::new(config.copilot.clone())?)),
-            "ollama" => Ok(Box::new(OllamaProvider::new(config.ollama.clone())?)),
-            "openai" => Ok(Box::new(OpenAIProvider::new(config.openai.clone())?)),
-            _ => Err(crate::error::XzatomaError::Provider(format!(
-                "Unknown provider type: '{}'. Supported types are: copilot, ollama, openai",
-                provider_type
-            ))),
-        }
+        let provider: Box<dyn Provider> = match provider_type {
+            "copilot" => Box::new(CopilotProvider::new(config.copilot.clone())?),
+            "ollama" => Box::new(OllamaProvider::new(config.ollama.clone())?),
+            "openai" => Box::new(OpenAIProvider::new(config.openai.clone())?),
+            _ => {
+                return Err(crate::error::XzatomaError::Provider(format!(
+                    "Unknown provider type: '{}'. Supported types are: copilot, ollama, openai",
+                    provider_type
+                )))
+            }
+        };
+        Ok(wrap_with_governor(provider, provider_type, config))
     }
 
     /// Create a provider instance with optional type and model overrides.
@@ -137,7 +147,7 @@ impl ProviderFactory {
     ///
     /// ```no_run
     /// use xzatoma::providers::ProviderFactory;
-    /// use xzatoma::config::{ProviderConfig, CopilotConfig, OllamaConfig, OpenAIConfig};
+    /// use xzatoma::config::{ProviderConfig, CopilotConfig, OllamaConfig, OpenAIConfig, ProviderCacheConfig, ProviderQuirksConfig, ProviderRateLimitsConfig};
     ///
     /// # fn example() -> xzatoma::error::Result<()> {
     /// let config = ProviderConfig {
@@ -145,6 +155,9 @@ impl ProviderFactory {
     ///     copilot: CopilotConfig::default(),
     ///     ollama: OllamaConfig::default(),
     ///     openai: OpenAIConfig::default(),
+    ///     cache: ProviderCacheConfig::default(),
+    ///     quirks: ProviderQuirksConfig::default(),
+    ///     rate_limits: ProviderRateLimitsConfig::default(),
     /// };
     ///
     /// // Use default provider from config
@@ -166,33 +179,37 @@ impl ProviderFactory {
     ) -> Result<Box<dyn Provider>> {
         let provider_type = provider_override.unwrap_or(&config.provider_type);
 
-        match provider_type {
+        let provider: Box<dyn Provider> = match provider_type {
             "copilot" => {
                 let mut copilot_config = config.copilot.clone();
                 if let Some(model) = model_override {
                     copilot_config.model = model.to_string();
                 }
-                Ok(Box::new(CopilotProvider::new(copilot_config)?))
+                Box::new(CopilotProvider::new(copilot_config)?)
             }
             "ollama" => {
                 let mut ollama_config = config.ollama.clone();
                 if let Some(model) = model_override {
                     ollama_config.model = model.to_string();
                 }
-                Ok(Box::new(OllamaProvider::new(ollama_config)?))
+                Box::new(OllamaProvider::new(ollama_config)?)
             }
             "openai" => {
                 let mut openai_config = config.openai.clone();
                 if let Some(model) = model_override {
                     openai_config.model = model.to_string();
                 }
-                Ok(Box::new(OpenAIProvider::new(openai_config)?))
+                Box::new(OpenAIProvider::new(openai_config)?)
             }
-            _ => Err(crate::error::XzatomaError::Provider(format!(
-                "Unknown provider type: '{}'. Supported types are: copilot, ollama, openai",
-                provider_type
-            ))),
-        }
+            _ => {
+                return Err(crate::error::XzatomaError::Provider(format!(
+                    "Unknown provider type: '{}'. Supported types are: copilot, ollama, openai",
+                    provider_type
+                )))
+            }
+        };
+        // Subagent providers join the same governor as their parent's
+        Ok(wrap_with_governor(provider, provider_type, config))
     }
 }
 
@@ -248,7 +265,7 @@ pub fn create_provider(provider_type: &str, config: &ProviderConfig) -> Result<B
 ///
 /// ```no_run
 /// use xzatoma::providers::create_provider_with_override;
-/// use xzatoma::config::{ProviderConfig, CopilotConfig, OllamaConfig, OpenAIConfig};
+/// use xzatoma::config::{ProviderConfig, CopilotConfig, OllamaConfig, OpenAIConfig, ProviderCacheConfig, ProviderQuirksConfig, ProviderRateLimitsConfig};
 ///
 /// # fn example() -> xzatoma::error::Result<()> {
 /// let config = ProviderConfig {
@@ -256,6 +273,9 @@ pub fn create_provider(provider_type: &str, config: &ProviderConfig) -> Result<B
 ///     copilot: CopilotConfig::default(),
 ///     ollama: OllamaConfig::default(),
 ///     openai: OpenAIConfig::default(),
+///     cache: ProviderCacheConfig::default(),
+///     quirks: ProviderQuirksConfig::default(),
+///     rate_limits: ProviderRateLimitsConfig::default(),
 /// };
 ///
 /// // Use default provider from config
@@ -288,7 +308,10 @@ pub fn create_provider_with_override(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{CopilotConfig, OllamaConfig, OpenAIConfig};
+    use crate::config::{
+        CopilotConfig, OllamaConfig, OpenAIConfig, ProviderCacheConfig, ProviderQuirksConfig,
+        ProviderRateLimitsConfig,
+    };
 
     #[test]
     fn test_create_provider_invalid_type() {
@@ -297,6 +320,9 @@ mod tests {
             copilot: CopilotConfig::default(),
             ollama: OllamaConfig::default(),
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
 
         let result = create_provider("invalid", &config);
@@ -328,9 +354,15 @@ mod tests {
                 enable_endpoint_fallback: true,
                 reasoning_effort: None,
                 include_reasoning: false,
+                request_timeout_seconds: 120,
+                stream_idle_timeout_seconds: 60,
+                token_refresh_margin_seconds: 300,
             },
             ollama: OllamaConfig::default(),
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
 
         // No overrides - should use config defaults
@@ -345,6 +377,9 @@ mod tests {
             copilot: CopilotConfig::default(),
             ollama: OllamaConfig::default(),
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
 
         // Override provider to ollama
@@ -359,6 +394,9 @@ mod tests {
             copilot: CopilotConfig::default(),
             ollama: OllamaConfig::default(),
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
 
         // Override both provider and model
@@ -377,9 +415,15 @@ mod tests {
                 enable_endpoint_fallback: true,
                 reasoning_effort: None,
                 include_reasoning: false,
+                request_timeout_seconds: 120,
+                stream_idle_timeout_seconds: 60,
+                token_refresh_margin_seconds: 300,
             },
             ollama: OllamaConfig::default(),
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
 
         // Override model only (uses config provider type)
@@ -394,6 +438,9 @@ mod tests {
             copilot: CopilotConfig::default(),
             ollama: OllamaConfig::default(),
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
 
         // Invalid provider override
@@ -420,9 +467,15 @@ mod tests {
                 enable_endpoint_fallback: true,
                 reasoning_effort: None,
                 include_reasoning: false,
+                request_timeout_seconds: 120,
+                stream_idle_timeout_seconds: 60,
+                token_refresh_margin_seconds: 300,
             },
             ollama: OllamaConfig::default(),
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
 
         // Override to copilot with custom model
@@ -439,8 +492,13 @@ mod tests {
                 host: "http://localhost:11434".to_string(),
                 model: "llama3.2:latest".to_string(),
                 request_timeout_seconds: 600,
+                max_concurrent_requests: 2,
+                prompt_style: None,
             },
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
 
         // Override to ollama with custom model
@@ -455,6 +513,9 @@ mod tests {
             copilot: CopilotConfig::default(),
             ollama: OllamaConfig::default(),
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
 
         let result = create_provider("openai", &config);
@@ -468,6 +529,9 @@ mod tests {
             copilot: CopilotConfig::default(),
             ollama: OllamaConfig::default(),
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
 
         // Override from copilot config to openai
@@ -482,6 +546,9 @@ mod tests {
             copilot: CopilotConfig::default(),
             ollama: OllamaConfig::default(),
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
 
         // Override to openai with custom model
@@ -496,6 +563,9 @@ mod tests {
             copilot: CopilotConfig::default(),
             ollama: OllamaConfig::default(),
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
         let result = ProviderFactory::create_provider("unknown", &config);
         assert!(result.is_err());
@@ -522,6 +592,9 @@ mod tests {
             copilot: CopilotConfig::default(),
             ollama: OllamaConfig::default(),
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
         let result = ProviderFactory::create_provider_with_override(&config, Some("unknown"), None);
         assert!(result.is_err());
@@ -548,6 +621,9 @@ mod tests {
             copilot: CopilotConfig::default(),
             ollama: OllamaConfig::default(),
             openai: OpenAIConfig::default(),
+            cache: ProviderCacheConfig::default(),
+            quirks: ProviderQuirksConfig::default(),
+            rate_limits: ProviderRateLimitsConfig::default(),
         };
 
         let result = ProviderFactory::create_provider("xyz", &config);
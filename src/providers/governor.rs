@@ -0,0 +1,924 @@
+//! Process-wide pacing of provider requests
+//!
+//! Parallel plan steps and subagents each build their own provider, so
+//! without coordination they exceed a provider's rate limit together. A
+//! [`RequestGovernor`] is shared by every provider instance talking to the
+//! same provider and host (and, for OpenAI, the same API key). It limits how
+//! many requests start per minute and how many run at once, using the
+//! `provider.rate_limits` entry for the provider type.
+//!
+//! Each [`GovernedProvider`] is one client of its governor. Waiting requests
+//! are served round-robin across clients, so a subagent with a backlog of
+//! requests cannot starve its parent. A [`XzatomaError::RateLimited`] answer
+//! pauses the whole governor for the `Retry-After` time, or for
+//! `provider.rate_limits.backoff_seconds`, so every instance backs off
+//! together instead of each retrying into the same limit.
+//!
+//! [`ProviderFactory`](super::ProviderFactory) wraps every provider it
+//! creates, so subagent providers share the governor automatically.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use metrics::{histogram, increment_counter};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+use crate::config::{ProviderConfig, RateLimitConfig};
+use crate::error::{Result, XzatomaError};
+
+use super::trait_mod::Provider;
+use super::types::{
+    CompletionResponse, Message, ModelInfo, ModelInfoSummary, ProviderCapabilities, ToolCallOptions,
+};
+
+/// Copilot API host used when `provider.copilot.api_base` is not set
+const DEFAULT_COPILOT_HOST: &str = "https://api.githubcopilot.com";
+
+/// Waits shorter than this count as starting right away
+const DELAYED_WAIT: Duration = Duration::from_millis(1);
+
+/// Counters kept by a [`RequestGovernor`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GovernorStats {
+    /// Requests that were allowed to start
+    pub started: u64,
+    /// Requests that had to wait before starting
+    pub delayed: u64,
+    /// Total time requests spent waiting
+    pub total_wait: Duration,
+    /// Longest time one request waited
+    pub max_wait: Duration,
+    /// Rate-limit answers that paused the governor
+    pub backoffs: u64,
+}
+
+/// Shared request limits for one provider and host
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use xzatoma::config::RateLimitConfig;
+/// use xzatoma::providers::governor::RequestGovernor;
+///
+/// # #[tokio::main]
+/// # async fn main() -> xzatoma::error::Result<()> {
+/// let limits = RateLimitConfig { requests_per_minute: None, max_concurrent: Some(1) };
+/// let governor = RequestGovernor::new("ollama|http://localhost:11434", limits);
+/// let client = governor.register_client();
+/// let permit = governor.acquire(client, "ollama", Duration::from_secs(5)).await?;
+/// assert_eq!(governor.in_flight(), 1);
+/// drop(permit);
+/// assert_eq!(governor.in_flight(), 0);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RequestGovernor {
+    key: String,
+    limits: RateLimitConfig,
+    interval: Option<Duration>,
+    next_client: AtomicU64,
+    state: Mutex<GovernorState>,
+}
+
+#[derive(Debug, Default)]
+struct GovernorState {
+    /// Permits handed out and not yet dropped
+    in_flight: usize,
+    /// A permit was handed out but its request has not started yet
+    pending_start: bool,
+    /// Waiting requests per client, oldest first
+    waiting: HashMap<u64, VecDeque<oneshot::Sender<GovernorPermit>>>,
+    /// Clients with waiting requests, in the order they are served
+    turns: VecDeque<u64>,
+    /// Earliest start of the next request under `requests_per_minute`
+    next_start: Option<Instant>,
+    /// Nothing starts before this after a rate-limit answer
+    paused_until: Option<Instant>,
+    stats: GovernorStats,
+}
+
+/// Permission to run one request; released when dropped
+#[derive(Debug)]
+pub struct GovernorPermit {
+    governor: Arc<RequestGovernor>,
+    start: Instant,
+    started: bool,
+}
+
+impl Drop for GovernorPermit {
+    fn drop(&mut self) {
+        let next = {
+            let mut state = self.governor.lock();
+            state.in_flight -= 1;
+            if !self.started {
+                state.pending_start = false;
+            }
+            self.governor.dispatch(&mut state)
+        };
+        RequestGovernor::hand_over(next);
+    }
+}
+
+impl RequestGovernor {
+    /// Creates a governor that is not shared through the registry
+    ///
+    /// A limit of 0 is treated as 1.
+    pub fn new(key: &str, limits: RateLimitConfig) -> Arc<Self> {
+        let limits = normalize(limits);
+        Arc::new(Self {
+            key: key.to_string(),
+            limits,
+            interval: limits
+                .requests_per_minute
+                .map(|rpm| Duration::from_secs(60) / rpm),
+            next_client: AtomicU64::new(0),
+            state: Mutex::default(),
+        })
+    }
+
+    /// Returns the process-wide governor for `key`
+    ///
+    /// The first caller for a key sets its limits. Later callers share that
+    /// governor; different limits are logged and ignored, so a second
+    /// provider cannot loosen the limits for the first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use xzatoma::config::RateLimitConfig;
+    /// use xzatoma::providers::governor::RequestGovernor;
+    ///
+    /// let limits = RateLimitConfig { requests_per_minute: Some(60), max_concurrent: None };
+    /// let first = RequestGovernor::for_key("openai|governor-example", limits);
+    /// let second = RequestGovernor::for_key("openai|governor-example", RateLimitConfig::default());
+    /// assert!(Arc::ptr_eq(&first, &second));
+    /// assert_eq!(second.limits().requests_per_minute, Some(60));
+    /// ```
+    pub fn for_key(key: &str, limits: RateLimitConfig) -> Arc<Self> {
+        static REGISTRY: OnceLock<Mutex<HashMap<String, Arc<RequestGovernor>>>> = OnceLock::new();
+
+        let mut governors = REGISTRY
+            .get_or_init(Mutex::default)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let governor = governors
+            .entry(key.to_string())
+            .or_insert_with(|| RequestGovernor::new(key, limits));
+        if governor.limits != normalize(limits) {
+            tracing::warn!(
+                "Ignoring rate limits {:?} for {}; already limited to {:?}",
+                limits,
+                key,
+                governor.limits
+            );
+        }
+        Arc::clone(governor)
+    }
+
+    /// Provider and host this governor applies to
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Limits this governor enforces
+    pub fn limits(&self) -> RateLimitConfig {
+        self.limits
+    }
+
+    /// Number of permits currently held
+    pub fn in_flight(&self) -> usize {
+        self.lock().in_flight
+    }
+
+    /// Counters since the governor was created
+    pub fn stats(&self) -> GovernorStats {
+        self.lock().stats
+    }
+
+    /// Returns a new client id for round-robin queueing
+    ///
+    /// Requests with the same id are served in order; requests of different
+    /// clients take turns.
+    pub fn register_client(&self) -> u64 {
+        self.next_client.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Waits until a request may start, giving up after `max_wait`
+    ///
+    /// The wait is recorded in the `provider_rate_limit_wait_seconds`
+    /// histogram.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Id from [`RequestGovernor::register_client`]
+    /// * `provider` - Provider name used in metrics and errors
+    /// * `max_wait` - Longest time to wait
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::RateLimitQueueTimeout` when the request could
+    /// not start in time.
+    pub async fn acquire(
+        self: &Arc<Self>,
+        client: u64,
+        provider: &str,
+        max_wait: Duration,
+    ) -> Result<GovernorPermit> {
+        let asked = Instant::now();
+        let permit = tokio::time::timeout(max_wait, self.wait_for_start(client))
+            .await
+            .map_err(|_| {
+                tracing::warn!(
+                    "{} request gave up after {:?} waiting for the rate limits of {}",
+                    provider,
+                    max_wait,
+                    self.key
+                );
+                XzatomaError::RateLimitQueueTimeout {
+                    provider: provider.to_string(),
+                    waited: asked.elapsed(),
+                }
+            })??;
+
+        let waited = asked.elapsed();
+        histogram!(
+            "provider_rate_limit_wait_seconds",
+            waited.as_secs_f64(),
+            "provider" => provider.to_string()
+        );
+        let delayed = waited >= DELAYED_WAIT;
+        {
+            let mut state = self.lock();
+            state.stats.started += 1;
+            state.stats.total_wait += waited;
+            state.stats.max_wait = state.stats.max_wait.max(waited);
+            if delayed {
+                state.stats.delayed += 1;
+            }
+        }
+        if delayed {
+            tracing::debug!(
+                "{} request waited {:?} for the rate limits of {}",
+                provider,
+                waited,
+                self.key
+            );
+        }
+        Ok(permit)
+    }
+
+    /// Pauses every request of this governor for `delay`
+    ///
+    /// Requests already running are not affected. Overlapping pauses keep
+    /// the later end.
+    pub fn back_off(&self, provider: &str, delay: Duration) {
+        let mut state = self.lock();
+        let until = Instant::now() + delay;
+        state.paused_until = Some(state.paused_until.map_or(until, |paused| paused.max(until)));
+        state.stats.backoffs += 1;
+        increment_counter!(
+            "provider_rate_limit_backoffs_total",
+            "provider" => provider.to_string()
+        );
+        tracing::warn!(
+            "{} rate limited; pausing requests to {} for {:?}",
+            provider,
+            self.key,
+            delay
+        );
+    }
+
+    async fn wait_for_start(self: &Arc<Self>, client: u64) -> Result<GovernorPermit> {
+        let mut permit = {
+            let receiver = {
+                let mut state = self.lock();
+                if state.turns.is_empty() && self.has_room(&state) {
+                    Ok(self.reserve(&mut state))
+                } else {
+                    let (sender, receiver) = oneshot::channel();
+                    let queue = state.waiting.entry(client).or_default();
+                    queue.push_back(sender);
+                    if queue.len() == 1 {
+                        state.turns.push_back(client);
+                    }
+                    Err(receiver)
+                }
+            };
+            match receiver {
+                Ok(permit) => permit,
+                Err(receiver) => receiver.await.map_err(|_| {
+                    XzatomaError::Internal(format!("Rate limiter for {} was closed", self.key))
+                })?,
+            }
+        };
+
+        // A rate-limit answer may have arrived while this request was waiting
+        let mut start = permit.start;
+        loop {
+            if start > Instant::now() {
+                tokio::time::sleep_until(start).await;
+            }
+            match self.lock().paused_until {
+                Some(paused) if paused > start => start = paused,
+                _ => break,
+            }
+        }
+
+        let next = {
+            let mut state = self.lock();
+            state.pending_start = false;
+            permit.started = true;
+            self.dispatch(&mut state)
+        };
+        Self::hand_over(next);
+        Ok(permit)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, GovernorState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Whether another request may be handed a permit now
+    ///
+    /// Only one permit at a time may be waiting for its start time, so
+    /// paced requests are handed out in turn order rather than all at once.
+    fn has_room(&self, state: &GovernorState) -> bool {
+        !state.pending_start
+            && self
+                .limits
+                .max_concurrent
+                .map_or(true, |max| state.in_flight < max)
+    }
+
+    fn reserve(self: &Arc<Self>, state: &mut GovernorState) -> GovernorPermit {
+        let start = [state.next_start, state.paused_until]
+            .into_iter()
+            .flatten()
+            .fold(Instant::now(), Instant::max);
+        if let Some(interval) = self.interval {
+            state.next_start = Some(start + interval);
+        }
+        state.in_flight += 1;
+        state.pending_start = true;
+        GovernorPermit {
+            governor: Arc::clone(self),
+            start,
+            started: false,
+        }
+    }
+
+    /// Picks the next waiting request, taking clients in turn
+    fn dispatch(
+        self: &Arc<Self>,
+        state: &mut GovernorState,
+    ) -> Option<(oneshot::Sender<GovernorPermit>, GovernorPermit)> {
+        while self.has_room(state) {
+            let client = state.turns.pop_front()?;
+            let Some(queue) = state.waiting.get_mut(&client) else {
+                continue;
+            };
+            let Some(sender) = queue.pop_front() else {
+                state.waiting.remove(&client);
+                continue;
+            };
+            if queue.is_empty() {
+                state.waiting.remove(&client);
+            } else {
+                state.turns.push_back(client);
+            }
+            if sender.is_closed() {
+                continue;
+            }
+            return Some((sender, self.reserve(state)));
+        }
+        None
+    }
+
+    /// Sends a permit picked by [`RequestGovernor::dispatch`]
+    ///
+    /// Called without the state lock held: when the waiter gave up in the
+    /// meantime, the returned permit is dropped here, which releases it and
+    /// picks the next waiter.
+    fn hand_over(next: Option<(oneshot::Sender<GovernorPermit>, GovernorPermit)>) {
+        if let Some((sender, permit)) = next {
+            let _ = sender.send(permit);
+        }
+    }
+}
+
+/// Raises limits of 0 to 1
+fn normalize(limits: RateLimitConfig) -> RateLimitConfig {
+    RateLimitConfig {
+        requests_per_minute: limits.requests_per_minute.map(|rpm| rpm.max(1)),
+        max_concurrent: limits.max_concurrent.map(|max| max.max(1)),
+    }
+}
+
+/// Registry key for a provider type and its configured host or account
+///
+/// Copilot is keyed by its API base, Ollama by its host, and OpenAI by its
+/// base URL and a short hash of the API key, so two keys on one server get
+/// separate limits.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::Config;
+/// use xzatoma::providers::governor::governor_key;
+///
+/// let config = Config::default();
+/// assert_eq!(
+///     governor_key("ollama", &config.provider),
+///     "ollama|http://localhost:11434"
+/// );
+/// ```
+pub fn governor_key(provider_type: &str, config: &ProviderConfig) -> String {
+    let host = |url: &str| url.trim().trim_end_matches('/').to_lowercase();
+    match provider_type {
+        "copilot" => format!(
+            "copilot|{}",
+            host(
+                config
+                    .copilot
+                    .api_base
+                    .as_deref()
+                    .unwrap_or(DEFAULT_COPILOT_HOST)
+            )
+        ),
+        "ollama" => format!("ollama|{}", host(&config.ollama.host)),
+        "openai" => {
+            let mut key = format!("openai|{}", host(&config.openai.base_url));
+            if !config.openai.api_key.is_empty() {
+                let digest = Sha256::digest(config.openai.api_key.as_bytes());
+                let account: String = digest[..6]
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect();
+                key = format!("{}|{}", key, account);
+            }
+            key
+        }
+        other => other.to_string(),
+    }
+}
+
+/// Provider wrapper that asks a [`RequestGovernor`] before each completion
+///
+/// Model listing and other metadata calls are passed through unpaced.
+pub struct GovernedProvider<P> {
+    inner: P,
+    governor: Arc<RequestGovernor>,
+    client: u64,
+    provider: String,
+    max_wait: Duration,
+    backoff: Duration,
+}
+
+impl<P: Provider> GovernedProvider<P> {
+    /// Wraps `inner` as a new client of `governor`
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - Provider to pace
+    /// * `governor` - Governor shared with other instances of the provider
+    /// * `provider` - Provider name used in metrics and errors
+    /// * `max_wait` - Longest time a request waits before failing
+    /// * `backoff` - Pause after a rate-limit answer without `Retry-After`
+    pub fn new(
+        inner: P,
+        governor: Arc<RequestGovernor>,
+        provider: &str,
+        max_wait: Duration,
+        backoff: Duration,
+    ) -> Self {
+        let client = governor.register_client();
+        Self {
+            inner,
+            governor,
+            client,
+            provider: provider.to_string(),
+            max_wait,
+            backoff,
+        }
+    }
+
+    /// Governor this provider shares
+    pub fn governor(&self) -> &Arc<RequestGovernor> {
+        &self.governor
+    }
+
+    async fn governed(
+        &self,
+        messages: &[Message],
+        tools: &[Value],
+        stream: bool,
+    ) -> Result<CompletionResponse> {
+        let permit = self
+            .governor
+            .acquire(self.client, &self.provider, self.max_wait)
+            .await?;
+        let result = if stream {
+            self.inner.chat_completion_stream(messages, tools).await
+        } else {
+            self.inner.complete(messages, tools).await
+        };
+        drop(permit);
+
+        if let Err(XzatomaError::RateLimited { retry_after, .. }) = &result {
+            self.governor
+                .back_off(&self.provider, retry_after.unwrap_or(self.backoff));
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for GovernedProvider<P> {
+    fn is_authenticated(&self) -> bool {
+        self.inner.is_authenticated()
+    }
+
+    fn current_model(&self) -> Option<&str> {
+        self.inner.current_model()
+    }
+
+    fn set_model(&mut self, model: &str) {
+        self.inner.set_model(model)
+    }
+
+    async fn fetch_models(&self) -> Result<Vec<ModelInfo>> {
+        self.inner.fetch_models().await
+    }
+
+    async fn complete(&self, messages: &[Message], tools: &[Value]) -> Result<CompletionResponse> {
+        self.governed(messages, tools, false).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        self.inner.list_models().await
+    }
+
+    async fn get_model_info(&self, model_name: &str) -> Result<ModelInfo> {
+        self.inner.get_model_info(model_name).await
+    }
+
+    fn get_current_model(&self) -> String {
+        self.inner.get_current_model()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        messages: &[Message],
+        tools: &[Value],
+    ) -> Result<CompletionResponse> {
+        self.governed(messages, tools, true).await
+    }
+
+    fn get_provider_capabilities(&self) -> ProviderCapabilities {
+        self.inner.get_provider_capabilities()
+    }
+
+    fn set_thinking_effort(&self, effort: Option<&str>) -> Result<()> {
+        self.inner.set_thinking_effort(effort)
+    }
+
+    fn set_tool_call_options(&self, options: ToolCallOptions) -> Result<()> {
+        self.inner.set_tool_call_options(options)
+    }
+
+    fn set_temperature(&self, temperature: Option<f32>) -> Result<()> {
+        self.inner.set_temperature(temperature)
+    }
+
+    fn needs_tool_call_nudge(&self) -> bool {
+        self.inner.needs_tool_call_nudge()
+    }
+
+    async fn list_models_summary(&self) -> Result<Vec<ModelInfoSummary>> {
+        self.inner.list_models_summary().await
+    }
+
+    async fn get_model_info_summary(&self, model_name: &str) -> Result<ModelInfoSummary> {
+        self.inner.get_model_info_summary(model_name).await
+    }
+}
+
+/// Wraps `provider` in a [`GovernedProvider`] sharing the process-wide
+/// governor for its provider type and host
+///
+/// Returns `provider` unchanged when `provider.rate_limits.enabled` is off.
+pub fn wrap_with_governor(
+    provider: Box<dyn Provider>,
+    provider_type: &str,
+    config: &ProviderConfig,
+) -> Box<dyn Provider> {
+    let rate_limits = &config.rate_limits;
+    if !rate_limits.enabled {
+        return provider;
+    }
+    let governor = RequestGovernor::for_key(
+        &governor_key(provider_type, config),
+        rate_limits.for_provider(provider_type),
+    );
+    Box::new(GovernedProvider::new(
+        provider,
+        governor,
+        provider_type,
+        Duration::from_secs(rate_limits.max_wait_seconds),
+        Duration::from_secs(rate_limits.backoff_seconds),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::testing::MockProvider;
+
+    fn limits(requests_per_minute: Option<u32>, max_concurrent: Option<usize>) -> RateLimitConfig {
+        RateLimitConfig {
+            requests_per_minute,
+            max_concurrent,
+        }
+    }
+
+    /// Provider that takes `latency` per completion and answers "ok"
+    fn slow_provider(latency: Duration) -> MockProvider {
+        MockProvider::new()
+            .with_model("fake")
+            .with_latency(latency)
+            .with_fallback(CompletionResponse::new(Message::assistant("ok")))
+    }
+
+    fn governed(
+        fake: MockProvider,
+        governor: &Arc<RequestGovernor>,
+    ) -> GovernedProvider<MockProvider> {
+        GovernedProvider::new(
+            fake,
+            Arc::clone(governor),
+            "fake",
+            Duration::from_secs(10),
+            Duration::from_millis(100),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_stay_under_the_ceiling() {
+        let governor = RequestGovernor::new("fake|ceiling", limits(None, Some(3)));
+        let fake = slow_provider(Duration::from_millis(20));
+
+        // Separate wrappers, as separate subagents would create
+        let mut tasks = Vec::new();
+        for _ in 0..8 {
+            let provider = Arc::new(governed(fake.clone(), &governor));
+            for _ in 0..4 {
+                let provider = Arc::clone(&provider);
+                tasks.push(tokio::spawn(async move {
+                    provider.complete(&[Message::user("hi")], &[]).await
+                }));
+            }
+        }
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        assert_eq!(fake.max_in_flight(), 3);
+        assert_eq!(governor.in_flight(), 0);
+        let stats = governor.stats();
+        assert_eq!(stats.started, 32);
+        assert!(stats.delayed > 0);
+        assert!(stats.max_wait > Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_requests_per_minute_spaces_out_starts() {
+        // 1200 per minute is one start every 50ms
+        let governor = RequestGovernor::new("fake|pacing", limits(Some(1200), None));
+        let fake = slow_provider(Duration::from_millis(1));
+
+        let mut tasks = Vec::new();
+        for _ in 0..6 {
+            let provider = governed(fake.clone(), &governor);
+            tasks.push(tokio::spawn(async move {
+                provider.complete(&[Message::user("hi")], &[]).await
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        let mut starts = fake.request_times();
+        starts.sort();
+        assert_eq!(starts.len(), 6);
+        for pair in starts.windows(2) {
+            assert!(
+                pair[1] - pair[0] >= Duration::from_millis(45),
+                "starts only {:?} apart",
+                pair[1] - pair[0]
+            );
+        }
+        assert!(starts[5] - starts[0] >= Duration::from_millis(240));
+    }
+
+    #[tokio::test]
+    async fn test_waiting_clients_take_turns() {
+        let governor = RequestGovernor::new("fake|fairness", limits(None, Some(1)));
+        let greedy = governor.register_client();
+        let parent = governor.register_client();
+        let held = governor
+            .acquire(greedy, "fake", Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut tasks = Vec::new();
+        for (client, label) in [
+            (greedy, "greedy"),
+            (greedy, "greedy"),
+            (greedy, "greedy"),
+            (parent, "parent"),
+        ] {
+            let governor = Arc::clone(&governor);
+            let order = Arc::clone(&order);
+            tasks.push(tokio::spawn(async move {
+                let _permit = governor
+                    .acquire(client, "fake", Duration::from_secs(5))
+                    .await
+                    .unwrap();
+                order.lock().unwrap().push(label);
+            }));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        drop(held);
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec!["greedy", "parent", "greedy", "greedy"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_answer_pauses_every_client() {
+        let governor = RequestGovernor::new("fake|backoff", limits(None, None));
+        let limited =
+            slow_provider(Duration::from_millis(1)).then_failure(XzatomaError::RateLimited {
+                provider: "fake".to_string(),
+                retry_after: Some(Duration::from_millis(200)),
+            });
+        let limited = governed(limited, &governor);
+        let other = governed(slow_provider(Duration::from_millis(1)), &governor);
+
+        let err = limited
+            .complete(&[Message::user("hi")], &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, XzatomaError::RateLimited { .. }));
+
+        // The answer asked for 200ms; the other client waits it out too
+        let started = Instant::now();
+        other.complete(&[Message::user("hi")], &[]).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert_eq!(governor.stats().backoffs, 1);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_times_out_while_waiting() {
+        let governor = RequestGovernor::new("fake|timeout", limits(None, Some(1)));
+        let client = governor.register_client();
+        let _held = governor
+            .acquire(client, "fake", Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        let err = governor
+            .acquire(client, "fake", Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            XzatomaError::RateLimitQueueTimeout { ref provider, .. } if provider == "fake"
+        ));
+        // The abandoned wait does not hold a slot
+        assert_eq!(governor.in_flight(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_waiters_do_not_block_the_queue() {
+        let governor = RequestGovernor::new("fake|abandoned", limits(None, Some(1)));
+        let client = governor.register_client();
+        let held = governor
+            .acquire(client, "fake", Duration::from_secs(1))
+            .await
+            .unwrap();
+        let _ = governor
+            .acquire(client, "fake", Duration::from_millis(20))
+            .await;
+
+        drop(held);
+        let permit = governor
+            .acquire(client, "fake", Duration::from_millis(200))
+            .await;
+        assert!(permit.is_ok());
+    }
+
+    #[test]
+    fn test_for_key_shares_one_governor_per_key() {
+        let first = RequestGovernor::for_key("fake|shared-test", limits(Some(30), Some(2)));
+        let second = RequestGovernor::for_key("fake|shared-test", limits(None, None));
+        let other = RequestGovernor::for_key("fake|other-test", limits(None, None));
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(second.limits(), limits(Some(30), Some(2)));
+        assert!(!Arc::ptr_eq(&first, &other));
+    }
+
+    #[test]
+    fn test_governor_key_separates_hosts_and_accounts() {
+        let mut config = Config::default().provider;
+        config.openai.base_url = "https://api.openai.com/v1/".to_string();
+        config.openai.api_key = "sk-one".to_string();
+        let one = governor_key("openai", &config);
+        config.openai.api_key = "sk-two".to_string();
+        let two = governor_key("openai", &config);
+
+        assert!(one.starts_with("openai|https://api.openai.com/v1|"));
+        assert!(!one.contains("sk-one"));
+        assert_ne!(one, two);
+        assert_eq!(
+            governor_key("copilot", &config),
+            "copilot|https://api.githubcopilot.com"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_factory_providers_share_the_governor() {
+        use crate::providers::{create_provider, create_provider_with_override};
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/version"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"version":"0.5.7"}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(
+                        r#"{"message":{"role":"assistant","content":"ok"},"done":true}"#,
+                    )
+                    .set_delay(Duration::from_millis(150)),
+            )
+            .mount(&server)
+            .await;
+
+        let mut config = Config::default().provider;
+        config.provider_type = "ollama".to_string();
+        config.ollama.host = server.uri();
+        config.ollama.max_concurrent_requests = 8;
+        config.rate_limits.ollama.max_concurrent = Some(1);
+        let parent = create_provider("ollama", &config).unwrap();
+        // A subagent's provider, built separately with its own model
+        let subagent = create_provider_with_override(&config, None, Some("qwen3:8b")).unwrap();
+
+        let started = Instant::now();
+        let messages = [Message::user("hi")];
+        let (first, second) = tokio::join!(
+            parent.complete(&messages, &[]),
+            subagent.complete(&messages, &[])
+        );
+        first.unwrap();
+        second.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_wrap_with_governor_respects_enabled() {
+        let mut config = Config::default().provider;
+        config.rate_limits.enabled = false;
+        let provider =
+            wrap_with_governor(Box::new(slow_provider(Duration::ZERO)), "ollama", &config);
+        assert_eq!(provider.get_current_model(), "fake");
+    }
+}
@@ -5,21 +5,42 @@
 //!
 //! ## Module Layout
 //!
-//! | Submodule      | Contents                                              |
-//! | -------------- | ----------------------------------------------------- |
-//! | `types`        | All shared domain types and wire-format structs       |
-//! | `trait_mod`    | The `Provider` trait                                  |
-//! | `factory`      | `ProviderFactory` and backward-compatible free funcs  |
-//! | `base`         | Compatibility re-export shim (prefer direct imports)  |
-//! | `copilot`      | GitHub Copilot provider implementation                |
-//! | `ollama`       | Ollama provider implementation                        |
-//! | `openai`       | OpenAI provider implementation                        |
+//! | Submodule          | Contents                                              |
+//! | ------------------ | ----------------------------------------------------- |
+//! | `types`            | All shared domain types and wire-format structs       |
+//! | `trait_mod`        | The `Provider` trait                                  |
+//! | `factory`          | `ProviderFactory` and backward-compatible free funcs  |
+//! | `base`             | Compatibility re-export shim (prefer direct imports)  |
+//! | `budget`           | Usage logging and the `BudgetProvider` wrapper        |
+//! | `cache`            | On-disk response cache and `CachingProvider` wrapper  |
+//! | `context_overflow` | Recognition of context-window overflow errors         |
+//! | `copilot`          | GitHub Copilot provider implementation                |
+//! | `embeddings`       | `EmbeddingProvider` trait and Ollama embeddings       |
+//! | `governor`         | Process-wide rate limits and `GovernedProvider`       |
+//! | `ollama`           | Ollama provider implementation                        |
+//! | `openai`           | OpenAI provider implementation                        |
+//! | `quirks`           | Per-model adjustments and the `QuirksProvider`        |
+//! | `recording`        | Per-turn run recording and `RecordingProvider`        |
+//! | `retry_after`      | Parsing of `Retry-After` on rate-limited responses    |
+//! | `timeouts`         | Per-request timeouts and agent deadline propagation   |
+//! | `tool_arguments`   | Lenient parsing and repair of tool-call arguments     |
 
 pub mod base;
+pub mod budget;
+pub mod cache;
+pub mod concurrency;
+pub mod context_overflow;
 pub mod copilot;
+pub mod embeddings;
 pub mod factory;
+pub mod governor;
 pub mod ollama;
 pub mod openai;
+pub mod quirks;
+pub mod recording;
+pub mod retry_after;
+pub mod timeouts;
+pub mod tool_arguments;
 pub mod trait_mod;
 pub mod types;
 
@@ -34,7 +55,8 @@ pub use types::{
     ProviderCapabilities, ProviderFunction, ProviderFunctionCall, ProviderImagePromptPart,
     ProviderImagePromptSource, ProviderMessage, ProviderMessageContentPart,
     ProviderMessageContentParts, ProviderPromptInput, ProviderPromptInputPart, ProviderRequest,
-    ProviderTextPromptPart, ProviderTool, ProviderToolCall, TextPromptPart, TokenUsage, ToolCall,
+    ProviderRequestOptions, ProviderTextPromptPart, ProviderTool, ProviderToolCall, TextPromptPart,
+    TokenUsage, ToolCall, ToolCallOptions, OPENAI_MAX_TOOLS, OPENAI_MAX_TOOL_DESCRIPTION_CHARS,
 };
 
 // ---------------------------------------------------------------------------
@@ -49,6 +71,44 @@ pub use trait_mod::Provider;
 
 pub use factory::{create_provider, create_provider_with_override, ProviderFactory};
 
+// ---------------------------------------------------------------------------
+// Response cache (from cache.rs)
+// ---------------------------------------------------------------------------
+
+pub use cache::{wrap_with_cache, CacheCounters, CachingProvider, ResponseCache};
+
+// ---------------------------------------------------------------------------
+// Usage logging and budget enforcement (from budget.rs)
+// ---------------------------------------------------------------------------
+
+pub use budget::{wrap_with_budget, BudgetProvider, UsageLedger};
+
+// ---------------------------------------------------------------------------
+// Run recording (from recording.rs)
+// ---------------------------------------------------------------------------
+
+pub use recording::{wrap_with_recorder, RecordingProvider, RunRecorder};
+
+// ---------------------------------------------------------------------------
+// Model quirks (from quirks.rs)
+// ---------------------------------------------------------------------------
+
+pub use quirks::{wrap_with_quirks, ModelQuirks, QuirksProvider};
+
+// ---------------------------------------------------------------------------
+// Process-wide rate limits (from governor.rs)
+// ---------------------------------------------------------------------------
+
+pub use governor::{wrap_with_governor, GovernedProvider, RequestGovernor};
+
+// ---------------------------------------------------------------------------
+// Tool argument parsing (from tool_arguments.rs)
+// ---------------------------------------------------------------------------
+
+pub use tool_arguments::{
+    parse_tool_arguments, ArgumentRepair, MalformedArguments, ParsedArguments,
+};
+
 // ---------------------------------------------------------------------------
 // Provider implementations
 // ---------------------------------------------------------------------------
@@ -6,23 +6,33 @@
 
 use crate::config::OllamaConfig;
 use crate::error::{Result, XzatomaError};
+use crate::providers::concurrency::HostLimiter;
+use crate::providers::context_overflow;
+use crate::providers::timeouts;
 use crate::providers::{
     convert_tools_from_json, messages_contain_image_content, CompletionResponse, FunctionCall,
     Message, ModelCapability, ModelInfo, Provider, ProviderCapabilities, ProviderFunctionCall,
-    ProviderMessage, ProviderRequest, ProviderToolCall, TokenUsage, ToolCall,
+    ProviderMessage, ProviderRequest, ProviderRequestOptions, ProviderToolCall, TokenUsage,
+    ToolCall,
 };
 
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
+/// Timeout for the `/api/version` health check.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
 /// Ollama API provider
 ///
 /// This provider connects to an Ollama server (local or remote) to generate
 /// completions. It supports tool calling, model listing, model switching,
 /// and token usage tracking. Models are cached for 5 minutes to reduce API calls.
+/// Completions to one host share a [`HostLimiter`] sized by
+/// `max_concurrent_requests`.
 ///
 /// # Examples
 ///
@@ -35,6 +45,8 @@ use std::time::{Duration, Instant};
 ///     host: "http://localhost:11434".to_string(),
 ///     model: "llama3.2:latest".to_string(),
 ///     request_timeout_seconds: 600,
+///     max_concurrent_requests: 2,
+///     prompt_style: None,
 /// };
 /// let provider = OllamaProvider::new(config)?;
 /// let messages = vec![Message::user("Hello!")];
@@ -54,6 +66,18 @@ pub struct OllamaProvider {
     client: Client,
     config: Arc<RwLock<OllamaConfig>>,
     model_cache: ModelCache,
+    /// Set once a health check has succeeded; later completions skip it.
+    healthy: Arc<AtomicBool>,
+    /// Completion slots shared with every provider for the same host.
+    limiter: Arc<HostLimiter>,
+    /// Sampling temperature sent with each request, if set.
+    temperature: Arc<RwLock<Option<f32>>>,
+}
+
+/// Response from Ollama's /api/version endpoint
+#[derive(Debug, Deserialize)]
+struct OllamaVersionResponse {
+    version: String,
 }
 
 /// Response from Ollama's /api/tags endpoint
@@ -141,7 +165,9 @@ impl OllamaProvider {
     /// # Arguments
     ///
     /// * `config` - Ollama configuration containing host, model, and request timeout.
-    ///   The HTTP client timeout is set to `config.request_timeout_seconds`.
+    ///   Completion requests time out after `config.request_timeout_seconds`,
+    ///   and at most `config.max_concurrent_requests` of them run against the
+    ///   host at once.
     ///
     /// # Returns
     ///
@@ -161,13 +187,15 @@ impl OllamaProvider {
     ///     host: "http://localhost:11434".to_string(),
     ///     model: "llama3.2:latest".to_string(),
     ///     request_timeout_seconds: 600,
+    ///     max_concurrent_requests: 2,
+    ///     prompt_style: None,
     /// };
     /// let provider = OllamaProvider::new(config);
     /// assert!(provider.is_ok());
     /// ```
     pub fn new(config: OllamaConfig) -> Result<Self> {
         let client = Client::builder()
-            .timeout(Duration::from_secs(config.request_timeout_seconds))
+            .connect_timeout(timeouts::CONNECT_TIMEOUT)
             .user_agent("xzatoma/0.1.0")
             .build()
             .map_err(|e| XzatomaError::Provider(format!("Failed to create HTTP client: {}", e)))?;
@@ -178,13 +206,77 @@ impl OllamaProvider {
             config.model
         );
 
+        let limiter = HostLimiter::for_host(&config.host, config.max_concurrent_requests);
+
         Ok(Self {
             client,
             config: Arc::new(RwLock::new(config)),
             model_cache: Arc::new(RwLock::new(None)),
+            healthy: Arc::new(AtomicBool::new(false)),
+            limiter,
+            temperature: Arc::new(RwLock::new(None)),
         })
     }
 
+    /// Check that the Ollama server is reachable
+    ///
+    /// Requests `/api/version` with a short timeout. A successful check is
+    /// remembered, so completions only pay for it once per session.
+    ///
+    /// # Returns
+    ///
+    /// Returns the server version reported by Ollama
+    ///
+    /// # Errors
+    ///
+    /// Returns `OllamaUnavailable` if the server cannot be reached or does not
+    /// answer like an Ollama server
+    pub async fn health_check(&self) -> Result<String> {
+        let host = self.host();
+        let url = format!("{}/api/version", host.trim_end_matches('/'));
+
+        let response = match self
+            .client
+            .get(&url)
+            .timeout(timeouts::request_timeout(HEALTH_CHECK_TIMEOUT))
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => return Err(unavailable_error(&host, e.to_string()).await),
+        };
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(
+                unavailable_error(&host, format!("/api/version returned {}", status)).await,
+            );
+        }
+
+        let version = match response.json::<OllamaVersionResponse>().await {
+            Ok(body) => body.version,
+            Err(e) => {
+                return Err(unavailable_error(
+                    &host,
+                    format!("unexpected /api/version response: {}", e),
+                )
+                .await)
+            }
+        };
+
+        tracing::debug!("Ollama server at {} is version {}", host, version);
+        self.healthy.store(true, Ordering::Release);
+        Ok(version)
+    }
+
+    /// Run the health check unless one has already succeeded.
+    async fn ensure_healthy(&self) -> Result<()> {
+        if !self.healthy.load(Ordering::Acquire) {
+            self.health_check().await?;
+        }
+        Ok(())
+    }
+
     /// Get the configured Ollama host
     ///
     /// # Examples
@@ -197,6 +289,8 @@ impl OllamaProvider {
     ///     host: "http://localhost:11434".to_string(),
     ///     model: "llama3.2:latest".to_string(),
     ///     request_timeout_seconds: 600,
+    ///     max_concurrent_requests: 2,
+    ///     prompt_style: None,
     /// };
     /// let provider = OllamaProvider::new(config).unwrap();
     /// assert_eq!(provider.host(), "http://localhost:11434");
@@ -220,6 +314,8 @@ impl OllamaProvider {
     ///     host: "http://localhost:11434".to_string(),
     ///     model: "llama3.2:latest".to_string(),
     ///     request_timeout_seconds: 600,
+    ///     max_concurrent_requests: 2,
+    ///     prompt_style: None,
     /// };
     /// let provider = OllamaProvider::new(config).unwrap();
     /// assert_eq!(provider.model(), "llama3.2:latest");
@@ -331,10 +427,16 @@ impl OllamaProvider {
         let url = format!("{}/api/tags", host);
         tracing::debug!("Fetching models from Ollama: {}", url);
 
-        let response = self.client.get(&url).send().await.map_err(|e| {
-            tracing::warn!("Failed to fetch Ollama models: {}", e);
-            XzatomaError::Provider(format!("Failed to connect to Ollama server: {}", e))
-        })?;
+        let timeout = timeouts::request_timeout(timeouts::METADATA_TIMEOUT);
+        let response = self
+            .client
+            .get(&url)
+            .timeout(timeout)
+            .send()
+            .await
+            .map_err(|e| {
+                timeouts::request_error("ollama", timeout, "Failed to connect to Ollama server", e)
+            })?;
 
         let status = response.status();
         if !status.is_success() {
@@ -405,27 +507,28 @@ impl OllamaProvider {
             name: String,
         }
 
+        let timeout = timeouts::request_timeout(timeouts::METADATA_TIMEOUT);
         let response = self
             .client
             .post(&url)
+            .timeout(timeout)
             .json(&ShowRequest {
                 name: model_name.to_string(),
             })
             .send()
             .await
             .map_err(|e| {
-                tracing::warn!("Failed to fetch Ollama model details: {}", e);
-                XzatomaError::Provider(format!("Failed to fetch model details: {}", e))
+                timeouts::request_error("ollama", timeout, "Failed to fetch model details", e)
             })?;
 
         let status = response.status();
         if !status.is_success() {
             let error_text = response.text().await.unwrap_or_default();
             tracing::error!("Ollama returned error {}: {}", status, error_text);
-            return Err(XzatomaError::Provider(format!(
-                "Model not found: {}",
-                model_name
-            )));
+            return Err(XzatomaError::ModelNotFound {
+                model: model_name.to_string(),
+                available: Vec::new(),
+            });
         }
 
         // Read the response body as text first so we can handle varying response shapes
@@ -640,6 +743,39 @@ fn build_model_info_from_show_response(
 }
 
 /// Format byte size for display
+/// Build an `OllamaUnavailable` error, recording whether `host` resolves.
+async fn unavailable_error(host: &str, reason: String) -> XzatomaError {
+    XzatomaError::OllamaUnavailable {
+        host: host.to_string(),
+        dns_resolved: host_resolves(host).await,
+        reason,
+    }
+}
+
+/// Check whether the host name of `host_url` resolves to any address.
+async fn host_resolves(host_url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(host_url) else {
+        return false;
+    };
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return false;
+    };
+
+    let lookup = tokio::net::lookup_host((host, port));
+    match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, lookup).await {
+        Ok(Ok(mut addrs)) => addrs.next().is_some(),
+        _ => false,
+    }
+}
+
+/// Whether an `/api/chat` error response means the model has not been pulled.
+///
+/// Ollama answers with 404 and a body such as
+/// `{"error":"model \"llama3\" not found, try pulling it first"}`.
+fn is_model_not_found(status: reqwest::StatusCode, body: &str) -> bool {
+    status == reqwest::StatusCode::NOT_FOUND && body.to_lowercase().contains("not found")
+}
+
 fn format_size(bytes: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
     let mut size = bytes as f64;
@@ -670,11 +806,15 @@ impl Provider for OllamaProvider {
         messages: &[Message],
         tools: &[serde_json::Value],
     ) -> Result<CompletionResponse> {
-        let (url, model) = {
+        let (url, model, configured_timeout) = {
             let config = self.config.read().map_err(|_| {
                 XzatomaError::Provider("Failed to acquire read lock on config".to_string())
             })?;
-            (format!("{}/api/chat", config.host), config.model.clone())
+            (
+                format!("{}/api/chat", config.host),
+                config.model.clone(),
+                Duration::from_secs(config.request_timeout_seconds),
+            )
         };
 
         if messages_contain_image_content(messages) && !ollama_model_supports_vision(&model) {
@@ -684,32 +824,69 @@ impl Provider for OllamaProvider {
             )));
         }
 
+        let temperature = *self.temperature.read().map_err(|_| {
+            XzatomaError::Provider("Failed to acquire read lock on temperature".to_string())
+        })?;
+
         let ollama_request = OllamaRequest {
             model,
             messages: self.convert_messages(messages),
             tools: self.convert_tools(tools),
             stream: false,
+            options: temperature.map(|temperature| ProviderRequestOptions {
+                temperature: Some(temperature),
+            }),
         };
         // OllamaRequest is an alias for ProviderRequest which serializes
         // tools as a JSON object -- the format Ollama expects.
 
+        self.ensure_healthy().await?;
+
         tracing::debug!(
             "Sending Ollama request: {} messages, {} tools",
             ollama_request.messages.len(),
             ollama_request.tools.len()
         );
 
-        let response = self
+        // Time spent waiting for a slot counts against the request timeout
+        let timeout = timeouts::request_timeout(configured_timeout);
+        let slot = self.limiter.acquire("ollama", timeout).await?;
+        let timeout = timeout.saturating_sub(slot.waited());
+        let response = match self
             .client
             .post(&url)
+            .timeout(timeout)
             .json(&ollama_request)
             .send()
             .await
-            .map_err(|e| XzatomaError::Provider(format!("Ollama request failed: {}", e)))?;
+        {
+            Ok(response) => response,
+            Err(e) if e.is_connect() => {
+                // The server went away after a successful health check.
+                self.healthy.store(false, Ordering::Release);
+                return Err(unavailable_error(&self.host(), e.to_string()).await);
+            }
+            Err(e) => {
+                return Err(timeouts::request_error(
+                    "ollama",
+                    timeout,
+                    "Ollama request failed",
+                    e,
+                ))
+            }
+        };
 
         let status = response.status();
         if !status.is_success() {
             let error_text = response.text().await.unwrap_or_default();
+            if is_model_not_found(status, &error_text) {
+                return Err(XzatomaError::ModelNotPulled {
+                    model: ollama_request.model,
+                });
+            }
+            if let Some(overflow) = context_overflow::detect(&error_text) {
+                return Err(overflow);
+            }
             return Err(XzatomaError::Provider(format!(
                 "Ollama returned error {}: {}",
                 status, error_text
@@ -717,9 +894,9 @@ impl Provider for OllamaProvider {
         }
 
         let ollama_response: OllamaResponse = response.json().await.map_err(|e| {
-            tracing::error!("Failed to parse Ollama response: {}", e);
-            XzatomaError::Provider(format!("Failed to parse Ollama response: {}", e))
+            timeouts::request_error("ollama", timeout, "Failed to parse Ollama response", e)
         })?;
+        drop(slot);
 
         tracing::debug!(
             "Ollama response: done={}, prompt_tokens={}, completion_tokens={}",
@@ -834,9 +1011,23 @@ impl Provider for OllamaProvider {
             supports_token_counts: true,
             supports_streaming: false,
             supports_vision: true,
+            // Local models follow one tool call per turn far more reliably,
+            // and the chat API has no `tool_choice` parameter.
+            supports_parallel_tool_calls: false,
+            supports_tool_choice: false,
+            ..Default::default()
         }
     }
 
+    /// Sends the temperature as `options.temperature` on later requests.
+    fn set_temperature(&self, temperature: Option<f32>) -> Result<()> {
+        let mut current = self.temperature.write().map_err(|_| {
+            XzatomaError::Provider("Failed to acquire write lock on temperature".to_string())
+        })?;
+        *current = temperature;
+        Ok(())
+    }
+
     /// Set the active model in memory without any API validation. Callers
     /// that need model-existence validation should call `list_models` before
     /// calling this method.
@@ -857,6 +1048,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "llama3.2:latest".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config);
         assert!(provider.is_ok());
@@ -868,6 +1061,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "llama3.2:latest".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
         assert_eq!(provider.host(), "http://localhost:11434");
@@ -879,6 +1074,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "llama3.2:latest".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
         assert_eq!(provider.model(), "llama3.2:latest");
@@ -890,6 +1087,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "llama3.2:latest".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
 
@@ -912,6 +1111,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "llama3.2:latest".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
 
@@ -936,6 +1137,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "llama3.2:latest".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
 
@@ -962,6 +1165,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "llava:latest".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
         let message = Message::try_user_from_multimodal_input(
@@ -991,6 +1196,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "llama3.2:latest".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
         let message = Message::try_user_from_multimodal_input(
@@ -1023,6 +1230,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "llama3.2:latest".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
 
@@ -1047,6 +1256,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "llama3.2:latest".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
 
@@ -1079,6 +1290,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "llama3.2:latest".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
 
@@ -1190,6 +1403,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "test".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
         let capabilities = provider.get_provider_capabilities();
@@ -1208,6 +1423,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "test-model".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
         assert_eq!(provider.get_current_model(), "test-model");
@@ -1292,6 +1509,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "test".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
 
@@ -1312,6 +1531,8 @@ mod tests {
             host: "http://localhost:11434".to_string(),
             model: "test".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         };
         let provider = OllamaProvider::new(config).unwrap();
 
@@ -1337,4 +1558,547 @@ mod tests {
         assert_eq!(converted[2].role, "tool");
         assert_eq!(converted[2].content, "Result");
     }
+
+    fn provider_for_host(host: String) -> OllamaProvider {
+        OllamaProvider::new(OllamaConfig {
+            host,
+            model: "llama3.2:latest".to_string(),
+            request_timeout_seconds: 10,
+            max_concurrent_requests: 2,
+            prompt_style: None,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_complete_reports_unavailable_server_on_closed_port() {
+        // Bind and release a port so nothing is listening on it.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let host = format!("http://127.0.0.1:{}", port);
+        let provider = provider_for_host(host.clone());
+
+        let err = provider
+            .complete(&[Message::user("Hello")], &[])
+            .await
+            .unwrap_err();
+
+        match &err {
+            XzatomaError::OllamaUnavailable {
+                host: reported,
+                dns_resolved,
+                ..
+            } => {
+                assert_eq!(reported, &host);
+                assert!(*dns_resolved);
+            }
+            other => panic!("Expected OllamaUnavailable, got {:?}", other),
+        }
+        assert!(err.remediation_hint().contains("ollama serve"));
+    }
+
+    #[tokio::test]
+    async fn test_complete_suggests_pull_when_model_missing() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/version"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"version":"0.5.7"}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .respond_with(ResponseTemplate::new(404).set_body_string(
+                r#"{"error":"model \"llama3.2:latest\" not found, try pulling it first"}"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let provider = provider_for_host(server.uri());
+        let err = provider
+            .complete(&[Message::user("Hello")], &[])
+            .await
+            .unwrap_err();
+
+        match &err {
+            XzatomaError::ModelNotPulled { model } => assert_eq!(model, "llama3.2:latest"),
+            other => panic!("Expected ModelNotPulled, got {:?}", other),
+        }
+        assert!(err
+            .remediation_hint()
+            .contains("ollama pull llama3.2:latest"));
+    }
+
+    #[tokio::test]
+    async fn test_complete_sends_images_field_for_vision_model() {
+        use crate::providers::{ImagePromptPart, MultimodalPromptInput, PromptInputPart};
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/version"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"version":"0.5.7"}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(body_partial_json(serde_json::json!({
+                "model": "llava:latest",
+                "messages": [{
+                    "role": "user",
+                    "content": "Describe this",
+                    "images": ["iVBORw0KGgo="]
+                }]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{"message":{"role":"assistant","content":"A cat"},"done":true,"prompt_eval_count":3,"eval_count":2}"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = OllamaProvider::new(OllamaConfig {
+            host: server.uri(),
+            model: "llava:latest".to_string(),
+            request_timeout_seconds: 10,
+            max_concurrent_requests: 2,
+            prompt_style: None,
+        })
+        .unwrap();
+        let message = Message::try_user_from_multimodal_input(MultimodalPromptInput::new(vec![
+            PromptInputPart::text("Describe this"),
+            PromptInputPart::image(ImagePromptPart::inline_base64("image/png", "iVBORw0KGgo=")),
+        ]))
+        .unwrap();
+
+        let response = provider.complete(&[message], &[]).await.unwrap();
+        assert_eq!(response.message.content.as_deref(), Some("A cat"));
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_complete_sends_temperature_option_when_set() {
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/version"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"version":"0.5.7"}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(body_partial_json(serde_json::json!({
+                "options": {"temperature": 0.25}
+            })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(
+                    r#"{"message":{"role":"assistant","content":"ok"},"done":true}"#,
+                ),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = provider_for_host(server.uri());
+        provider.set_temperature(Some(0.25)).unwrap();
+        let response = provider
+            .complete(&[Message::user("Hi")], &[])
+            .await
+            .unwrap();
+        assert_eq!(response.message.content.as_deref(), Some("ok"));
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_complete_rejects_images_for_text_only_model_before_request() {
+        use crate::providers::{ImagePromptPart, MultimodalPromptInput, PromptInputPart};
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let provider = provider_for_host(server.uri());
+        let message = Message::try_user_from_multimodal_input(MultimodalPromptInput::new(vec![
+            PromptInputPart::image(ImagePromptPart::inline_base64("image/png", "iVBORw0KGgo=")),
+        ]))
+        .unwrap();
+
+        let err = provider.complete(&[message], &[]).await.unwrap_err();
+        assert!(err.to_string().contains("llama3.2:latest"));
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_health_check_success_is_cached() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/version"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"version":"0.5.7"}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{"message":{"role":"assistant","content":"Hi"},"done":true,"prompt_eval_count":3,"eval_count":1}"#,
+            ))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let provider = provider_for_host(server.uri());
+        for _ in 0..2 {
+            let response = provider
+                .complete(&[Message::user("Hello")], &[])
+                .await
+                .unwrap();
+            assert_eq!(response.message.content.as_deref(), Some("Hi"));
+        }
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_complete_times_out_with_typed_error() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/version"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"version":"0.5.7"}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let provider = OllamaProvider::new(OllamaConfig {
+            host: server.uri(),
+            model: "llama3.2:latest".to_string(),
+            request_timeout_seconds: 1,
+            max_concurrent_requests: 2,
+            prompt_style: None,
+        })
+        .unwrap();
+        let err = provider
+            .complete(&[Message::user("Hello")], &[])
+            .await
+            .unwrap_err();
+
+        match &err {
+            XzatomaError::RequestTimeout {
+                provider, timeout, ..
+            } => {
+                assert_eq!(provider, "ollama");
+                assert_eq!(*timeout, Duration::from_secs(1));
+            }
+            other => panic!("Expected RequestTimeout, got {:?}", other),
+        }
+        assert!(err.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_complete_timeout_is_capped_by_agent_deadline() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/version"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"version":"0.5.7"}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let provider = provider_for_host(server.uri());
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(500);
+        let started = Instant::now();
+        let err = timeouts::with_deadline(deadline, provider.complete(&[Message::user("Hi")], &[]))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, XzatomaError::RequestTimeout { .. }));
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+
+    /// Fake Ollama server that records how many chats run at once and the
+    /// order in which their prompts arrive
+    struct ConcurrencyServer {
+        uri: String,
+        max_in_flight: Arc<std::sync::atomic::AtomicUsize>,
+        arrivals: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    async fn start_concurrency_server(delay: Duration) -> ConcurrencyServer {
+        use std::sync::atomic::AtomicUsize;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let uri = format!("http://{}", listener.local_addr().unwrap());
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let arrivals = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let state = (
+            Arc::clone(&in_flight),
+            Arc::clone(&max_in_flight),
+            Arc::clone(&arrivals),
+        );
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (in_flight, max_in_flight, arrivals) = state.clone();
+                tokio::spawn(async move {
+                    serve_chats(stream, delay, in_flight, max_in_flight, arrivals).await;
+                });
+            }
+        });
+
+        ConcurrencyServer {
+            uri,
+            max_in_flight,
+            arrivals,
+        }
+    }
+
+    async fn serve_chats(
+        mut stream: tokio::net::TcpStream,
+        delay: Duration,
+        in_flight: Arc<std::sync::atomic::AtomicUsize>,
+        max_in_flight: Arc<std::sync::atomic::AtomicUsize>,
+        arrivals: Arc<std::sync::Mutex<Vec<String>>>,
+    ) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut buffer = Vec::new();
+        loop {
+            // Read one request: headers, then a Content-Length body
+            let head_end = loop {
+                if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break end + 4;
+                }
+                let mut chunk = [0u8; 4096];
+                match stream.read(&mut chunk).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                }
+            };
+            let head = String::from_utf8_lossy(&buffer[..head_end]).to_lowercase();
+            let length = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            while buffer.len() < head_end + length {
+                let mut chunk = [0u8; 4096];
+                match stream.read(&mut chunk).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                }
+            }
+            let body: Vec<u8> = buffer.drain(..head_end + length).skip(head_end).collect();
+
+            let reply = if head.starts_with("get /api/version") {
+                r#"{"version":"0.5.7"}"#.to_string()
+            } else {
+                let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                let prompt = request["messages"][0]["content"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string();
+                let running = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(running, Ordering::SeqCst);
+                arrivals.lock().unwrap().push(prompt.clone());
+                tokio::time::sleep(delay).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                serde_json::json!({
+                    "message": {"role": "assistant", "content": prompt},
+                    "done": true
+                })
+                .to_string()
+            };
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                reply.len(),
+                reply
+            );
+            if stream.write_all(response.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    fn limited_provider(host: &str, max_concurrent_requests: usize) -> OllamaProvider {
+        OllamaProvider::new(OllamaConfig {
+            host: host.to_string(),
+            model: "llama3.2:latest".to_string(),
+            request_timeout_seconds: 10,
+            max_concurrent_requests,
+            prompt_style: None,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_complete_limits_concurrent_requests_per_host() {
+        let server = start_concurrency_server(Duration::from_millis(200)).await;
+        let provider = Arc::new(limited_provider(&server.uri, 2));
+
+        let started = Instant::now();
+        let tasks: Vec<_> = (0..6)
+            .map(|index| {
+                let provider = Arc::clone(&provider);
+                tokio::spawn(async move {
+                    provider
+                        .complete(&[Message::user(format!("request {}", index))], &[])
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            assert!(task.await.unwrap().is_ok());
+        }
+
+        assert_eq!(server.max_in_flight.load(Ordering::SeqCst), 2);
+        assert_eq!(server.arrivals.lock().unwrap().len(), 6);
+        assert!(started.elapsed() >= Duration::from_millis(600));
+    }
+
+    #[tokio::test]
+    async fn test_complete_sends_queued_requests_in_order() {
+        let server = start_concurrency_server(Duration::from_millis(150)).await;
+        let provider = Arc::new(limited_provider(&server.uri, 1));
+        provider.health_check().await.unwrap();
+
+        let mut tasks = Vec::new();
+        for index in 0..4 {
+            let provider = Arc::clone(&provider);
+            tasks.push(tokio::spawn(async move {
+                provider
+                    .complete(&[Message::user(format!("request {}", index))], &[])
+                    .await
+            }));
+            tokio::time::sleep(Duration::from_millis(30)).await;
+        }
+        for task in tasks {
+            assert!(task.await.unwrap().is_ok());
+        }
+
+        assert_eq!(
+            *server.arrivals.lock().unwrap(),
+            vec!["request 0", "request 1", "request 2", "request 3"]
+        );
+        assert_eq!(server.max_in_flight.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_providers_for_one_host_share_the_limit() {
+        let server = start_concurrency_server(Duration::from_millis(150)).await;
+        let first = Arc::new(limited_provider(&server.uri, 1));
+        // A subagent-style second provider asking for more slots still
+        // shares the first one's limit
+        let second = Arc::new(limited_provider(&format!("{}/", server.uri), 4));
+
+        let tasks: Vec<_> = (0..4)
+            .map(|index| {
+                let provider = if index % 2 == 0 {
+                    Arc::clone(&first)
+                } else {
+                    Arc::clone(&second)
+                };
+                tokio::spawn(async move {
+                    provider
+                        .complete(&[Message::user(format!("request {}", index))], &[])
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            assert!(task.await.unwrap().is_ok());
+        }
+
+        assert_eq!(server.max_in_flight.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_complete_reports_queue_timeout_apart_from_execution_timeout() {
+        let server = start_concurrency_server(Duration::from_secs(2)).await;
+        let slow = Arc::new(limited_provider(&server.uri, 1));
+        let impatient = OllamaProvider::new(OllamaConfig {
+            host: server.uri.clone(),
+            model: "llama3.2:latest".to_string(),
+            request_timeout_seconds: 1,
+            max_concurrent_requests: 1,
+            prompt_style: None,
+        })
+        .unwrap();
+        slow.health_check().await.unwrap();
+        impatient.health_check().await.unwrap();
+
+        let running = {
+            let slow = Arc::clone(&slow);
+            tokio::spawn(async move { slow.complete(&[Message::user("first")], &[]).await })
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let err = impatient
+            .complete(&[Message::user("second")], &[])
+            .await
+            .unwrap_err();
+        match &err {
+            XzatomaError::RequestQueueTimeout {
+                provider,
+                max_concurrent,
+                ..
+            } => {
+                assert_eq!(provider, "ollama");
+                assert_eq!(*max_concurrent, 1);
+            }
+            other => panic!("Expected RequestQueueTimeout, got {:?}", other),
+        }
+        assert!(err.to_string().contains("while queued"));
+
+        assert!(running.await.unwrap().is_ok());
+        assert_eq!(*server.arrivals.lock().unwrap(), vec!["first"]);
+    }
+
+    #[test]
+    fn test_is_model_not_found_requires_404() {
+        let body = r#"{"error":"model 'x' not found"}"#;
+        assert!(is_model_not_found(reqwest::StatusCode::NOT_FOUND, body));
+        assert!(!is_model_not_found(
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+            body
+        ));
+        assert!(!is_model_not_found(
+            reqwest::StatusCode::NOT_FOUND,
+            "404 page"
+        ));
+    }
 }
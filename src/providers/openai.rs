@@ -22,13 +22,18 @@
 //! TTS, Whisper, DALL-E, and moderation) and infers per-model capabilities
 //! from the model identifier using [`build_capabilities_from_id`].
 
-use crate::config::OpenAIConfig;
+use crate::config::{
+    OpenAIConfig, DEFAULT_COMPLETION_REQUEST_TIMEOUT_SECONDS, DEFAULT_STREAM_IDLE_TIMEOUT_SECONDS,
+};
 use crate::error::{Result, XzatomaError};
+use crate::providers::context_overflow;
+use crate::providers::retry_after;
+use crate::providers::timeouts;
 use crate::providers::{
     convert_tools_from_json, messages_contain_image_content, validate_message_sequence,
     CompletionResponse, FinishReason, FunctionCall, ImagePromptSource, Message, ModelCapability,
     ModelInfo, Provider, ProviderCapabilities, ProviderMessageContentPart, ProviderTool,
-    TokenUsage, ToolCall,
+    TokenUsage, ToolCall, ToolCallOptions, OPENAI_MAX_TOOLS, OPENAI_MAX_TOOL_DESCRIPTION_CHARS,
 };
 use async_trait::async_trait;
 use base64::Engine;
@@ -67,6 +72,12 @@ struct OpenAIRequest {
     /// request body when `None` so non-reasoning models are unaffected.
     #[serde(skip_serializing_if = "Option::is_none")]
     reasoning_effort: Option<String>,
+    /// `tool_choice` negotiated by the agent; omitted when `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<String>,
+    /// `parallel_tool_calls` negotiated by the agent; omitted when `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parallel_tool_calls: Option<bool>,
 }
 
 /// Single message in an OpenAI request or response body.
@@ -526,6 +537,7 @@ impl StreamAccumulator {
 ///     enable_streaming: false,
 ///     request_timeout_seconds: 600,
 ///     reasoning_effort: None,
+///     stream_idle_timeout_seconds: 60,
 /// };
 /// let provider = OpenAIProvider::new(config)?;
 /// let messages = vec![Message::user("Hello!")];
@@ -538,20 +550,21 @@ pub struct OpenAIProvider {
     client: Client,
     config: Arc<RwLock<OpenAIConfig>>,
     model_cache: ModelCache,
+    /// Tool-call parameters set by the agent for the next requests.
+    tool_call_options: Arc<RwLock<ToolCallOptions>>,
 }
 
 impl OpenAIProvider {
     /// Create a new OpenAI provider instance.
     ///
-    /// Builds an HTTP client with a timeout derived from
-    /// `config.request_timeout_seconds` and the `xzatoma/0.1.0` user-agent
-    /// string, then wraps the provided configuration in an
+    /// Builds an HTTP client with a connect timeout and the `xzatoma/0.1.0`
+    /// user-agent string, then wraps the provided configuration in an
     /// `Arc<RwLock<_>>` for safe shared access.
     ///
     /// # Arguments
     ///
     /// * `config` - OpenAI configuration containing the API key, base URL, model,
-    ///   streaming preference, and per-request HTTP timeout
+    ///   streaming preference, and per-request HTTP timeouts
     ///
     /// # Returns
     ///
@@ -574,7 +587,7 @@ impl OpenAIProvider {
     /// ```
     pub fn new(config: OpenAIConfig) -> Result<Self> {
         let client = Client::builder()
-            .timeout(Duration::from_secs(config.request_timeout_seconds))
+            .connect_timeout(timeouts::CONNECT_TIMEOUT)
             .user_agent("xzatoma/0.1.0")
             .build()
             .map_err(|e| XzatomaError::Provider(format!("Failed to create HTTP client: {}", e)))?;
@@ -589,9 +602,30 @@ impl OpenAIProvider {
             client,
             config: Arc::new(RwLock::new(config)),
             model_cache: Arc::new(RwLock::new(None)),
+            tool_call_options: Arc::new(RwLock::new(ToolCallOptions::default())),
         })
     }
 
+    /// Timeout for a non-streaming completion, capped by the agent deadline.
+    fn completion_timeout(&self) -> Duration {
+        let configured = self
+            .config
+            .read()
+            .map(|config| config.request_timeout_seconds)
+            .unwrap_or(DEFAULT_COMPLETION_REQUEST_TIMEOUT_SECONDS);
+        timeouts::request_timeout(Duration::from_secs(configured))
+    }
+
+    /// How long a streaming response may stay silent before it is abandoned.
+    fn stream_idle_timeout(&self) -> Duration {
+        let configured = self
+            .config
+            .read()
+            .map(|config| config.stream_idle_timeout_seconds)
+            .unwrap_or(DEFAULT_STREAM_IDLE_TIMEOUT_SECONDS);
+        Duration::from_secs(configured)
+    }
+
     /// Return the configured base URL for this provider.
     ///
     /// Reads the value under the read lock and returns a clone. Returns an
@@ -881,23 +915,26 @@ impl OpenAIProvider {
             request.tools.len()
         );
 
+        let timeout = self.completion_timeout();
         let response = self
             .client
             .post(&url)
+            .timeout(timeout)
             .headers(headers)
             .json(request)
             .send()
             .await
-            .map_err(|e| XzatomaError::Provider(format!("OpenAI request failed: {}", e)))?;
+            .map_err(|e| timeouts::request_error("openai", timeout, "OpenAI request failed", e))?;
 
         let status = response.status();
         if !status.is_success() {
+            let retry_after = retry_after::from_headers(response.headers());
             let body = response.text().await.unwrap_or_default();
-            return Err(self.http_error(status, body));
+            return Err(self.http_error(status, retry_after, body));
         }
 
         let openai_response: OpenAIResponse = response.json().await.map_err(|e| {
-            XzatomaError::Provider(format!("Failed to parse OpenAI response: {}", e))
+            timeouts::request_error("openai", timeout, "Failed to parse OpenAI response", e)
         })?;
 
         let choice = openai_response
@@ -965,28 +1002,30 @@ impl OpenAIProvider {
             request.messages.len()
         );
 
-        let response = self
-            .client
-            .post(&url)
-            .headers(headers)
-            .json(request)
-            .send()
-            .await
-            .map_err(|e| {
-                XzatomaError::Provider(format!("OpenAI streaming request failed: {}", e))
-            })?;
+        let idle_timeout = self.stream_idle_timeout();
+        let wait = timeouts::request_timeout(idle_timeout);
+        let send = self.client.post(&url).headers(headers).json(request).send();
+        let response = match tokio::time::timeout(wait, send).await {
+            Ok(result) => result.map_err(|e| {
+                timeouts::request_error("openai", wait, "OpenAI streaming request failed", e)
+            })?,
+            Err(_) => return Err(timeouts::timeout_error("openai", wait)),
+        };
 
         let status = response.status();
         if !status.is_success() {
+            let retry_after = retry_after::from_headers(response.headers());
             let body = response.text().await.unwrap_or_default();
-            return Err(self.http_error(status, body));
+            return Err(self.http_error(status, retry_after, body));
         }
 
         let mut stream = response.bytes_stream();
         let mut acc = StreamAccumulator::new();
         let mut line_buf: Vec<u8> = Vec::new();
 
-        'stream: while let Some(chunk_result) = stream.next().await {
+        'stream: while let Some(chunk_result) =
+            timeouts::next_within_idle_timeout("openai", idle_timeout, &mut stream).await?
+        {
             let chunk = chunk_result
                 .map_err(|e| XzatomaError::Provider(format!("Error reading SSE stream: {}", e)))?;
 
@@ -1048,45 +1087,71 @@ impl OpenAIProvider {
     /// or if the list request fails.
     async fn find_in_model_list(&self, model_name: &str) -> Result<ModelInfo> {
         let models = self.list_models().await?;
+        let available: Vec<String> = models.iter().map(|info| info.name.clone()).collect();
         models
             .into_iter()
             .find(|info| info.name == model_name)
-            .ok_or_else(|| XzatomaError::Provider(format!("Model not found: {}", model_name)))
+            .ok_or_else(|| XzatomaError::ModelNotFound {
+                model: model_name.to_string(),
+                available,
+            })
     }
 
-    /// Build an `XzatomaError::Provider` for a non-success HTTP response.
+    /// Build an `XzatomaError` for a non-success HTTP response.
     ///
-    /// When the server returns `401 Unauthorized` and no `api_key` is
-    /// configured, the error message appends a hint explaining how to set up
+    /// `401 Unauthorized` maps to `XzatomaError::Auth`; when no `api_key` is
+    /// configured, the reason appends a hint explaining how to set up
     /// authentication. This is the most common source of 401 errors when
     /// pointing at a local inference server that has been started with an API
-    /// key requirement.
+    /// key requirement. `429 Too Many Requests` maps to
+    /// `XzatomaError::RateLimited` with the wait from the `Retry-After`
+    /// header.
     ///
     /// # Arguments
     ///
     /// * `status` - The HTTP status code received
+    /// * `retry_after` - Wait parsed from the `Retry-After` header
     /// * `body` - The raw response body text
     ///
     /// # Returns
     ///
-    /// An `XzatomaError::Provider` with a contextual error message.
-    fn http_error(&self, status: reqwest::StatusCode, body: String) -> XzatomaError {
-        if status == reqwest::StatusCode::UNAUTHORIZED {
-            let api_key_empty = self
-                .config
-                .read()
-                .map(|c| c.api_key.is_empty())
-                .unwrap_or(true);
-            if api_key_empty {
-                return XzatomaError::Provider(format!(
-                    "HTTP {}: {} -- server requires authentication; \
-                     set api_key in the OpenAI provider configuration \
-                     or start the server without requiring authentication",
-                    status, body
-                ));
+    /// An `XzatomaError::Auth`, `XzatomaError::RateLimited`, or
+    /// `XzatomaError::Provider` with a contextual error message.
+    fn http_error(
+        &self,
+        status: reqwest::StatusCode,
+        retry_after: Option<Duration>,
+        body: String,
+    ) -> XzatomaError {
+        match status {
+            reqwest::StatusCode::UNAUTHORIZED => {
+                let api_key_empty = self
+                    .config
+                    .read()
+                    .map(|c| c.api_key.is_empty())
+                    .unwrap_or(true);
+                let reason = if api_key_empty {
+                    format!(
+                        "HTTP {}: {} -- server requires authentication; \
+                         set api_key in the OpenAI provider configuration \
+                         or start the server without requiring authentication",
+                        status, body
+                    )
+                } else {
+                    format!("HTTP {}: {}", status, body)
+                };
+                XzatomaError::Auth {
+                    provider: "openai".to_string(),
+                    reason,
+                }
             }
+            reqwest::StatusCode::TOO_MANY_REQUESTS => XzatomaError::RateLimited {
+                provider: "openai".to_string(),
+                retry_after,
+            },
+            _ => context_overflow::detect(body)
+                .unwrap_or_else(|| XzatomaError::Provider(format!("HTTP {}: {}", status, body))),
         }
-        XzatomaError::Provider(format!("HTTP {}: {}", status, body))
     }
 }
 
@@ -1176,6 +1241,14 @@ impl Provider for OpenAIProvider {
 
         let openai_tools = convert_tools_from_json(tools);
         let use_streaming = enable_streaming && openai_tools.is_empty();
+        let tool_call_options = if openai_tools.is_empty() {
+            ToolCallOptions::default()
+        } else {
+            self.tool_call_options
+                .read()
+                .map(|options| *options)
+                .unwrap_or_default()
+        };
 
         let request = OpenAIRequest {
             model,
@@ -1183,6 +1256,10 @@ impl Provider for OpenAIProvider {
             tools: openai_tools,
             stream: use_streaming,
             reasoning_effort,
+            tool_choice: tool_call_options
+                .tool_choice
+                .map(|choice| choice.to_string()),
+            parallel_tool_calls: tool_call_options.parallel_tool_calls,
         };
 
         if use_streaming {
@@ -1225,16 +1302,19 @@ impl Provider for OpenAIProvider {
         let url = format!("{}/models", self.base_url());
         tracing::debug!("Fetching OpenAI models from: {}", url);
 
+        let timeout = timeouts::request_timeout(timeouts::METADATA_TIMEOUT);
         let response = self
             .client
             .get(&url)
+            .timeout(timeout)
             .headers(headers)
             .send()
             .await
-            .map_err(|e| XzatomaError::Provider(format!("Failed to fetch models: {}", e)))?;
+            .map_err(|e| timeouts::request_error("openai", timeout, "Failed to fetch models", e))?;
 
         let status = response.status();
         if !status.is_success() {
+            let retry_after = retry_after::from_headers(response.headers());
             let body = response.text().await.unwrap_or_default();
             // When the server returns 401 Unauthorized with no api_key configured,
             // fall back to returning the currently configured model rather than
@@ -1260,7 +1340,7 @@ impl Provider for OpenAIProvider {
                     return Ok(vec![info]);
                 }
             }
-            return Err(self.http_error(status, body));
+            return Err(self.http_error(status, retry_after, body));
         }
 
         let models_response: OpenAIModelsResponse = response.json().await.map_err(|e| {
@@ -1308,13 +1388,17 @@ impl Provider for OpenAIProvider {
         tracing::debug!("Fetching model info from: {}", url);
 
         let headers = self.build_get_headers()?;
+        let timeout = timeouts::request_timeout(timeouts::METADATA_TIMEOUT);
         let response = self
             .client
             .get(&url)
+            .timeout(timeout)
             .headers(headers)
             .send()
             .await
-            .map_err(|e| XzatomaError::Provider(format!("Failed to fetch model info: {}", e)))?;
+            .map_err(|e| {
+                timeouts::request_error("openai", timeout, "Failed to fetch model info", e)
+            })?;
 
         let status = response.status();
 
@@ -1327,8 +1411,9 @@ impl Provider for OpenAIProvider {
         }
 
         if !status.is_success() {
+            let retry_after = retry_after::from_headers(response.headers());
             let body = response.text().await.unwrap_or_default();
-            return Err(self.http_error(status, body));
+            return Err(self.http_error(status, retry_after, body));
         }
 
         match response.json::<OpenAIModelEntry>().await {
@@ -1378,6 +1463,12 @@ impl Provider for OpenAIProvider {
             supports_token_counts: true,
             supports_streaming: true,
             supports_vision: true,
+            max_tools: Some(OPENAI_MAX_TOOLS),
+            max_tool_description_chars: Some(OPENAI_MAX_TOOL_DESCRIPTION_CHARS),
+            max_tool_definitions_bytes: None,
+            supports_parallel_tool_calls: true,
+            supports_tool_choice: true,
+            max_tools_per_turn: None,
         }
     }
 
@@ -1394,6 +1485,16 @@ impl Provider for OpenAIProvider {
         );
         Ok(())
     }
+
+    fn set_tool_call_options(&self, options: ToolCallOptions) -> crate::error::Result<()> {
+        let mut current = self.tool_call_options.write().map_err(|_| {
+            crate::error::XzatomaError::Provider(
+                "Failed to acquire write lock on OpenAI tool-call options".to_string(),
+            )
+        })?;
+        *current = options;
+        Ok(())
+    }
 }
 
 fn openai_model_supports_vision(model: &str) -> bool {
@@ -1429,6 +1530,7 @@ mod tests {
             enable_streaming: false,
             request_timeout_seconds: 600,
             reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
         }
     }
 
@@ -2255,6 +2357,7 @@ mod tests {
             enable_streaming: true,
             request_timeout_seconds: 600,
             reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
         };
         let provider = OpenAIProvider::new(config).unwrap();
         let messages = vec![Message::user("Hello")];
@@ -2305,6 +2408,7 @@ mod tests {
             enable_streaming: true,
             request_timeout_seconds: 600,
             reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
         };
         let provider = OpenAIProvider::new(config).unwrap();
 
@@ -2348,6 +2452,7 @@ mod tests {
             enable_streaming: true,
             request_timeout_seconds: 600,
             reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
         };
         let provider = OpenAIProvider::new(config).unwrap();
 
@@ -2549,6 +2654,7 @@ mod tests {
             enable_streaming: true, // streaming enabled but tools force non-streaming
             request_timeout_seconds: 600,
             reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
         };
         let provider = OpenAIProvider::new(config).unwrap();
         let messages = vec![Message::user("Hello")];
@@ -2566,6 +2672,36 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    #[ignore = "disabled in CI because wiremock-backed OpenAI provider tests touch local network sockets"]
+    async fn test_complete_sends_tool_call_options_with_tools() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("\"tool_choice\":\"required\""))
+            .and(body_string_contains("\"parallel_tool_calls\":false"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(non_streaming_completion_body()))
+            .mount(&server)
+            .await;
+
+        let provider = OpenAIProvider::new(make_config(&server.uri())).unwrap();
+        provider
+            .set_tool_call_options(ToolCallOptions {
+                tool_choice: Some(crate::config::ToolChoiceMode::Required),
+                parallel_tool_calls: Some(false),
+            })
+            .unwrap();
+        let tools = vec![json!({
+            "name": "read_file",
+            "description": "Read a file",
+            "parameters": { "type": "object", "properties": {} }
+        })];
+
+        let result = provider.complete(&[Message::user("Hello")], &tools).await;
+        assert!(result.is_ok(), "Expected Ok, got: {:?}", result.err());
+    }
+
     #[tokio::test]
     #[ignore = "disabled in CI because wiremock-backed OpenAI provider tests touch local network sockets"]
     async fn test_bearer_token_sent_in_header() {
@@ -2606,6 +2742,7 @@ mod tests {
             enable_streaming: false,
             request_timeout_seconds: 600,
             reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
         };
         let provider = OpenAIProvider::new(config).unwrap();
         let messages = vec![Message::user("Hello")];
@@ -2643,6 +2780,7 @@ mod tests {
             enable_streaming: false,
             request_timeout_seconds: 600,
             reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
         };
         let provider = OpenAIProvider::new(config).unwrap();
         assert!(
@@ -2661,6 +2799,7 @@ mod tests {
             enable_streaming: false,
             request_timeout_seconds: 600,
             reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
         };
         let provider = OpenAIProvider::new(config).unwrap();
         assert!(
@@ -2693,6 +2832,7 @@ mod tests {
             enable_streaming: false,
             request_timeout_seconds: 600,
             reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
         };
         let provider = OpenAIProvider::new(config).unwrap();
         let result = provider.list_models().await;
@@ -2734,6 +2874,7 @@ mod tests {
             enable_streaming: false,
             request_timeout_seconds: 600,
             reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
         };
         let provider = OpenAIProvider::new(config).unwrap();
         let result = provider.list_models().await;
@@ -2763,6 +2904,7 @@ mod tests {
             enable_streaming: false,
             request_timeout_seconds: 600,
             reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
         };
         let provider = OpenAIProvider::new(config).unwrap();
         let result = provider.list_models().await;
@@ -2810,6 +2952,7 @@ mod tests {
             enable_streaming: false,
             request_timeout_seconds: 600,
             reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
         };
         let provider = OpenAIProvider::new(config).unwrap();
         let messages = vec![Message::user("Hello")];
@@ -2824,6 +2967,52 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    #[ignore = "disabled in CI because wiremock-backed OpenAI provider tests touch local network sockets"]
+    async fn test_post_completions_429_carries_retry_after() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after", "12")
+                    .set_body_json(serde_json::json!({
+                        "error": {"message": "Rate limit reached", "type": "requests"}
+                    })),
+            )
+            .mount(&server)
+            .await;
+
+        let config = OpenAIConfig {
+            api_key: "sk-test".to_string(),
+            base_url: server.uri(),
+            model: "test-model".to_string(),
+            organization_id: None,
+            enable_streaming: false,
+            request_timeout_seconds: 600,
+            reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
+        };
+        let provider = OpenAIProvider::new(config).unwrap();
+        let err = provider
+            .complete(&[Message::user("Hello")], &[])
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(
+                err,
+                XzatomaError::RateLimited {
+                    retry_after: Some(wait),
+                    ..
+                } if wait == Duration::from_secs(12)
+            ),
+            "{:?}",
+            err
+        );
+    }
+
     #[tokio::test]
     #[ignore = "disabled in CI because wiremock-backed OpenAI provider tests touch local network sockets"]
     async fn test_org_header_sent_when_set() {
@@ -2845,6 +3034,7 @@ mod tests {
             enable_streaming: false,
             request_timeout_seconds: 600,
             reasoning_effort: None,
+            stream_idle_timeout_seconds: 60,
         };
         let provider = OpenAIProvider::new(config).unwrap();
         let messages = vec![Message::user("Hello")];
@@ -2918,6 +3108,8 @@ mod tests {
             tools: vec![],
             stream: false,
             reasoning_effort: None,
+            tool_choice: None,
+            parallel_tool_calls: None,
         };
         let json = serde_json::to_string(&request).expect("serialize failed");
         assert!(
@@ -2936,11 +3128,15 @@ mod tests {
             tools: vec![],
             stream: false,
             reasoning_effort: Some("high".to_string()),
+            tool_choice: None,
+            parallel_tool_calls: None,
         };
         let json = serde_json::to_string(&request).expect("serialize failed");
         assert!(
             json.contains("\"reasoning_effort\":\"high\""),
             "reasoning_effort must appear in serialized JSON when set; got: {json}"
         );
+        assert!(!json.contains("tool_choice"));
+        assert!(!json.contains("parallel_tool_calls"));
     }
 }
@@ -0,0 +1,697 @@
+//! Model-specific request and response adjustments
+//!
+//! Models differ in small ways that matter to the agent. Some open every
+//! answer with "Sure, here's...", some wrap a plain-text answer in a code
+//! fence, and some answer a task in prose instead of calling a tool. The
+//! [`QuirksProvider`] wraps a provider and smooths these over for the
+//! models that need it:
+//!
+//! | Quirk                      | Applied to | Effect                                                  |
+//! | -------------------------- | ---------- | ------------------------------------------------------- |
+//! | `strip_preamble`           | response   | Drops a leading "Sure," or "Here's the file:" line      |
+//! | `unwrap_code_fence`        | response   | Unwraps an answer that is one plain code fence          |
+//! | `repeat_tool_instructions` | request    | Repeats the tool-use rules in the last user message     |
+//! | `max_temperature`          | request    | Caps temperatures passed to `set_temperature`           |
+//! | `tool_call_nudge`          | agent      | Reported by `needs_tool_call_nudge`; the agent nudges   |
+//!
+//! Quirks are looked up by the current model name each time, so a model
+//! switch takes effect on the next request. Every applied quirk is logged
+//! at debug level.
+
+use std::borrow::Cow;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::config::{ModelQuirksConfig, ProviderQuirksConfig};
+use crate::error::Result;
+
+use super::trait_mod::Provider;
+use super::types::{
+    CompletionResponse, Message, ModelInfo, ModelInfoSummary, ProviderCapabilities,
+    ProviderMessageContentPart, ToolCallOptions,
+};
+
+/// Appended to the last user message by `repeat_tool_instructions`
+pub const TOOL_INSTRUCTIONS_REMINDER: &str = "Reminder: when an action is needed, respond \
+with a tool call instead of describing it. Tool arguments must be a JSON object that \
+matches the tool's parameters. Call one tool at a time and wait for its result.";
+
+/// Openers removed by `strip_preamble` when followed by `,`, `!`, or `.`
+const PREAMBLE_OPENERS: &[&str] = &["sure", "certainly", "of course", "absolutely"];
+
+/// Lead-ins of a line removed by `strip_preamble` when it ends with `:`
+const PREAMBLE_LEAD_INS: &[&str] = &["here is", "here's", "here’s", "here are"];
+
+/// Code fence info strings that mark an answer as plain text
+const PLAIN_FENCE_INFO: &[&str] = &["", "text", "plain", "plaintext", "txt", "markdown", "md"];
+
+/// Quirks resolved for one model
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModelQuirks {
+    /// Remove leading filler from answers
+    pub strip_preamble: bool,
+    /// Unwrap an answer that is one plain code fence
+    pub unwrap_code_fence: bool,
+    /// Repeat the tool-use rules in the last user message
+    pub repeat_tool_instructions: bool,
+    /// Highest sampling temperature passed on
+    pub max_temperature: Option<f32>,
+    /// Remind the model once to use its tools
+    pub tool_call_nudge: bool,
+}
+
+impl ModelQuirks {
+    /// Resolves the quirks of `model`
+    ///
+    /// Starts from the built-in table when `config.builtin` is set, then
+    /// applies every matching entry of `config.models` in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::config::{ModelQuirksConfig, ProviderQuirksConfig};
+    /// use xzatoma::providers::ModelQuirks;
+    ///
+    /// let mut config = ProviderQuirksConfig::default();
+    /// config.models.push(ModelQuirksConfig {
+    ///     pattern: "llama3*".to_string(),
+    ///     strip_preamble: Some(false),
+    ///     ..Default::default()
+    /// });
+    ///
+    /// let quirks = ModelQuirks::resolve(&config, "llama3.1:8b");
+    /// assert!(!quirks.strip_preamble);
+    /// assert!(quirks.tool_call_nudge);
+    /// assert_eq!(ModelQuirks::resolve(&config, "gpt-4o"), ModelQuirks::default());
+    /// ```
+    pub fn resolve(config: &ProviderQuirksConfig, model: &str) -> Self {
+        let model = model.to_ascii_lowercase();
+        let mut quirks = Self::default();
+        let builtin = if config.builtin {
+            builtin_quirks()
+        } else {
+            Vec::new()
+        };
+        for entry in builtin.iter().chain(&config.models) {
+            if glob_match::glob_match(&entry.pattern.to_ascii_lowercase(), &model) {
+                quirks.apply(entry);
+            }
+        }
+        quirks
+    }
+
+    fn apply(&mut self, entry: &ModelQuirksConfig) {
+        if let Some(value) = entry.strip_preamble {
+            self.strip_preamble = value;
+        }
+        if let Some(value) = entry.unwrap_code_fence {
+            self.unwrap_code_fence = value;
+        }
+        if let Some(value) = entry.repeat_tool_instructions {
+            self.repeat_tool_instructions = value;
+        }
+        if let Some(value) = entry.max_temperature {
+            self.max_temperature = Some(value);
+        }
+        if let Some(value) = entry.tool_call_nudge {
+            self.tool_call_nudge = value;
+        }
+    }
+}
+
+/// Returns the built-in quirks table for common Ollama models
+pub fn builtin_quirks() -> Vec<ModelQuirksConfig> {
+    let entry = |pattern: &str| ModelQuirksConfig {
+        pattern: pattern.to_string(),
+        ..Default::default()
+    };
+    vec![
+        ModelQuirksConfig {
+            strip_preamble: Some(true),
+            tool_call_nudge: Some(true),
+            ..entry("llama3*")
+        },
+        ModelQuirksConfig {
+            strip_preamble: Some(true),
+            repeat_tool_instructions: Some(true),
+            tool_call_nudge: Some(true),
+            ..entry("mistral*")
+        },
+        ModelQuirksConfig {
+            unwrap_code_fence: Some(true),
+            tool_call_nudge: Some(true),
+            max_temperature: Some(0.7),
+            ..entry("qwen*")
+        },
+        ModelQuirksConfig {
+            strip_preamble: Some(true),
+            unwrap_code_fence: Some(true),
+            ..entry("gemma*")
+        },
+        ModelQuirksConfig {
+            strip_preamble: Some(true),
+            unwrap_code_fence: Some(true),
+            repeat_tool_instructions: Some(true),
+            ..entry("phi*")
+        },
+    ]
+}
+
+/// Removes a leading "Sure," opener and a "Here's ...:" line
+///
+/// Returns `None` when there is nothing to remove or nothing would be left.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::providers::quirks::strip_preamble;
+///
+/// assert_eq!(
+///     strip_preamble("Sure! Here's the summary:\nAll tests pass.").as_deref(),
+///     Some("All tests pass.")
+/// );
+/// assert_eq!(strip_preamble("All tests pass."), None);
+/// ```
+pub fn strip_preamble(text: &str) -> Option<String> {
+    let trimmed = text.trim_start();
+    let mut rest = trimmed;
+
+    if let Some(after) = PREAMBLE_OPENERS
+        .iter()
+        .find_map(|opener| strip_opener(rest, opener))
+    {
+        rest = after.trim_start();
+    }
+
+    if PREAMBLE_LEAD_INS
+        .iter()
+        .any(|lead_in| starts_with_ignore_case(rest, lead_in))
+    {
+        let line_end = rest.find('\n').unwrap_or(rest.len());
+        if rest[..line_end].trim_end().ends_with(':') {
+            rest = rest[line_end..].trim_start();
+        }
+    }
+
+    if rest.len() == trimmed.len() || rest.trim().is_empty() {
+        return None;
+    }
+    Some(rest.to_string())
+}
+
+/// Returns the text after `opener` and its punctuation, if `text` starts so
+fn strip_opener<'a>(text: &'a str, opener: &str) -> Option<&'a str> {
+    if !starts_with_ignore_case(text, opener) {
+        return None;
+    }
+    let after = &text[opener.len()..];
+    let mut chars = after.chars();
+    if !matches!(chars.next(), Some(',' | '!' | '.')) {
+        return None;
+    }
+    let rest = chars.as_str();
+    if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.get(..prefix.len())
+        .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+}
+
+/// Unwraps an answer that is exactly one plain-text code fence
+///
+/// Fences tagged with a programming language are left alone, as are
+/// answers with text outside the fence or more than one fence.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::providers::quirks::unwrap_code_fence;
+///
+/// assert_eq!(
+///     unwrap_code_fence("```\nFix the parser\n```").as_deref(),
+///     Some("Fix the parser")
+/// );
+/// assert_eq!(unwrap_code_fence("```rust\nfn main() {}\n```"), None);
+/// ```
+pub fn unwrap_code_fence(text: &str) -> Option<String> {
+    let trimmed = text.trim();
+    let (opening, rest) = trimmed.split_once('\n')?;
+    let ticks = opening.len() - opening.trim_start_matches('`').len();
+    if ticks < 3 {
+        return None;
+    }
+    let info = opening[ticks..].trim().to_ascii_lowercase();
+    if !PLAIN_FENCE_INFO.contains(&info.as_str()) {
+        return None;
+    }
+
+    let fence = &opening[..ticks];
+    let body = rest.strip_suffix(fence)?;
+    if !(body.is_empty() || body.ends_with('\n')) {
+        return None;
+    }
+    if body
+        .lines()
+        .any(|line| line.trim_start().starts_with(fence))
+    {
+        return None;
+    }
+
+    let body = body.trim_end_matches('\n');
+    if body.trim().is_empty() {
+        return None;
+    }
+    Some(body.to_string())
+}
+
+/// Provider wrapper that applies model quirks
+pub struct QuirksProvider<P: Provider> {
+    inner: P,
+    config: ProviderQuirksConfig,
+    /// Temperature last asked for, before the cap
+    temperature: Mutex<Option<f32>>,
+}
+
+impl<P: Provider> QuirksProvider<P> {
+    /// Wraps `inner`, applying the quirks `config` resolves for its model
+    pub fn new(inner: P, config: ProviderQuirksConfig) -> Self {
+        Self {
+            inner,
+            config,
+            temperature: Mutex::new(None),
+        }
+    }
+
+    /// Returns the quirks of the current model
+    pub fn quirks(&self) -> ModelQuirks {
+        ModelQuirks::resolve(&self.config, &self.inner.get_current_model())
+    }
+
+    /// Adds the tool-use reminder to the last user message
+    fn prepare_messages<'a>(
+        &self,
+        quirks: &ModelQuirks,
+        messages: &'a [Message],
+        tools: &[Value],
+    ) -> Cow<'a, [Message]> {
+        if !quirks.repeat_tool_instructions || tools.is_empty() {
+            return Cow::Borrowed(messages);
+        }
+        let Some(index) = messages.iter().rposition(|message| message.role == "user") else {
+            return Cow::Borrowed(messages);
+        };
+
+        let mut messages = messages.to_vec();
+        let message = &mut messages[index];
+        message.content = Some(match message.content.take() {
+            Some(content) if !content.is_empty() => {
+                format!("{}\n\n{}", content, TOOL_INSTRUCTIONS_REMINDER)
+            }
+            _ => TOOL_INSTRUCTIONS_REMINDER.to_string(),
+        });
+        if let Some(parts) = &mut message.content_parts {
+            parts.push(ProviderMessageContentPart::text(TOOL_INSTRUCTIONS_REMINDER));
+        }
+        self.applied("repeat_tool_instructions");
+        Cow::Owned(messages)
+    }
+
+    /// Applies the response quirks to a completion
+    fn process_response(
+        &self,
+        quirks: &ModelQuirks,
+        tools: &[Value],
+        mut response: CompletionResponse,
+    ) -> CompletionResponse {
+        let Some(mut text) = response.message.content.take() else {
+            return response;
+        };
+        if quirks.strip_preamble {
+            if let Some(stripped) = strip_preamble(&text) {
+                text = stripped;
+                self.applied("strip_preamble");
+            }
+        }
+        let has_tool_calls = response
+            .message
+            .tool_calls
+            .as_ref()
+            .is_some_and(|calls| !calls.is_empty());
+        // Without tools the request asked for an answer, not code
+        if quirks.unwrap_code_fence && tools.is_empty() && !has_tool_calls {
+            if let Some(unwrapped) = unwrap_code_fence(&text) {
+                text = unwrapped;
+                self.applied("unwrap_code_fence");
+            }
+        }
+        response.message.content = Some(text);
+        response
+    }
+
+    /// Caps `temperature` at the current model's `max_temperature`
+    fn capped(&self, temperature: Option<f32>) -> Option<f32> {
+        match (temperature, self.quirks().max_temperature) {
+            (Some(value), Some(max)) if value > max => {
+                self.applied("max_temperature");
+                Some(max)
+            }
+            _ => temperature,
+        }
+    }
+
+    fn applied(&self, quirk: &str) {
+        tracing::debug!(
+            model = %self.inner.get_current_model(),
+            quirk,
+            "Applied model quirk"
+        );
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for QuirksProvider<P> {
+    fn is_authenticated(&self) -> bool {
+        self.inner.is_authenticated()
+    }
+
+    fn current_model(&self) -> Option<&str> {
+        self.inner.current_model()
+    }
+
+    fn set_model(&mut self, model: &str) {
+        self.inner.set_model(model);
+        // The new model may have a different cap
+        let requested = *self.temperature.lock().unwrap();
+        if requested.is_some() {
+            if let Err(error) = self.inner.set_temperature(self.capped(requested)) {
+                tracing::warn!(
+                    "Failed to reapply temperature after model switch: {}",
+                    error
+                );
+            }
+        }
+    }
+
+    async fn fetch_models(&self) -> Result<Vec<ModelInfo>> {
+        self.inner.fetch_models().await
+    }
+
+    async fn complete(&self, messages: &[Message], tools: &[Value]) -> Result<CompletionResponse> {
+        let quirks = self.quirks();
+        let messages = self.prepare_messages(&quirks, messages, tools);
+        let response = self.inner.complete(&messages, tools).await?;
+        Ok(self.process_response(&quirks, tools, response))
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        self.inner.list_models().await
+    }
+
+    async fn get_model_info(&self, model_name: &str) -> Result<ModelInfo> {
+        self.inner.get_model_info(model_name).await
+    }
+
+    fn get_current_model(&self) -> String {
+        self.inner.get_current_model()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        messages: &[Message],
+        tools: &[Value],
+    ) -> Result<CompletionResponse> {
+        let quirks = self.quirks();
+        let messages = self.prepare_messages(&quirks, messages, tools);
+        let response = self.inner.chat_completion_stream(&messages, tools).await?;
+        Ok(self.process_response(&quirks, tools, response))
+    }
+
+    fn get_provider_capabilities(&self) -> ProviderCapabilities {
+        self.inner.get_provider_capabilities()
+    }
+
+    fn set_thinking_effort(&self, effort: Option<&str>) -> Result<()> {
+        self.inner.set_thinking_effort(effort)
+    }
+
+    fn set_tool_call_options(&self, options: ToolCallOptions) -> Result<()> {
+        self.inner.set_tool_call_options(options)
+    }
+
+    fn set_temperature(&self, temperature: Option<f32>) -> Result<()> {
+        *self.temperature.lock().unwrap() = temperature;
+        self.inner.set_temperature(self.capped(temperature))
+    }
+
+    fn needs_tool_call_nudge(&self) -> bool {
+        self.quirks().tool_call_nudge || self.inner.needs_tool_call_nudge()
+    }
+
+    async fn list_models_summary(&self) -> Result<Vec<ModelInfoSummary>> {
+        self.inner.list_models_summary().await
+    }
+
+    async fn get_model_info_summary(&self, model_name: &str) -> Result<ModelInfoSummary> {
+        self.inner.get_model_info_summary(model_name).await
+    }
+}
+
+/// Wraps `provider` in a [`QuirksProvider`] when quirks are enabled
+pub fn wrap_with_quirks(
+    provider: Box<dyn Provider>,
+    config: &ProviderQuirksConfig,
+) -> Box<dyn Provider> {
+    if !config.enabled {
+        return provider;
+    }
+    Box::new(QuirksProvider::new(provider, config.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{FunctionCall, ToolCall};
+    use crate::testing::MockProvider;
+
+    /// Provider that answers every request with `answer`
+    fn canned(model: &str, answer: Message) -> MockProvider {
+        MockProvider::new()
+            .with_model(model)
+            .with_fallback(CompletionResponse::new(answer))
+    }
+
+    fn quirks_for(model: &str, quirks: ModelQuirksConfig) -> ProviderQuirksConfig {
+        ProviderQuirksConfig {
+            enabled: true,
+            builtin: false,
+            models: vec![ModelQuirksConfig {
+                pattern: model.to_string(),
+                ..quirks
+            }],
+        }
+    }
+
+    #[test]
+    fn test_strip_preamble_table() {
+        let cases = [
+            ("Sure, here's the summary:\nAll good.", Some("All good.")),
+            ("Certainly! I'll add the test.", Some("I'll add the test.")),
+            ("Of course. Here is the plan:\n\n1. Read", Some("1. Read")),
+            ("Here are the files:\n- a.rs", Some("- a.rs")),
+            ("  absolutely!\nDone.", Some("Done.")),
+            ("Sure thing, done.", None),
+            ("Surely this works.", None),
+            ("Here is why it fails: the lock is held.", None),
+            ("Sure!", None),
+            ("Here's the summary:", None),
+            ("The build passes.", None),
+            ("", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(strip_preamble(input).as_deref(), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn test_unwrap_code_fence_table() {
+        let cases = [
+            ("```\nFix the parser\n```", Some("Fix the parser")),
+            (
+                "```text\nline one\nline two\n```\n",
+                Some("line one\nline two"),
+            ),
+            ("  ```markdown\n# Title\n```  ", Some("# Title")),
+            ("````\nuses ``` inside\n````", Some("uses ``` inside")),
+            ("````\nquoted\n````", Some("quoted")),
+            ("```rust\nfn main() {}\n```", None),
+            ("Intro\n```\nbody\n```", None),
+            ("```\nbody\n```\nOutro", None),
+            ("```\none\n```\n\n```\ntwo\n```", None),
+            ("```\n\n```", None),
+            ("```\nunterminated", None),
+            ("plain text", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(unwrap_code_fence(input).as_deref(), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn test_resolve_applies_builtin_then_config_in_order() {
+        let config = ProviderQuirksConfig {
+            enabled: true,
+            builtin: true,
+            models: vec![
+                ModelQuirksConfig {
+                    pattern: "QWEN2.5*".to_string(),
+                    max_temperature: Some(0.3),
+                    ..Default::default()
+                },
+                ModelQuirksConfig {
+                    pattern: "*coder*".to_string(),
+                    unwrap_code_fence: Some(false),
+                    ..Default::default()
+                },
+            ],
+        };
+
+        let quirks = ModelQuirks::resolve(&config, "qwen2.5-coder:7b");
+        assert!(quirks.tool_call_nudge);
+        assert!(!quirks.unwrap_code_fence);
+        assert_eq!(quirks.max_temperature, Some(0.3));
+
+        let without_builtin = ProviderQuirksConfig {
+            builtin: false,
+            ..config
+        };
+        let quirks = ModelQuirks::resolve(&without_builtin, "qwen2.5-coder:7b");
+        assert!(!quirks.tool_call_nudge);
+        assert_eq!(quirks.max_temperature, Some(0.3));
+    }
+
+    #[tokio::test]
+    async fn test_response_quirks_only_apply_to_matching_models() {
+        let answer = Message::assistant("Sure, here's the message:\n```\nFix the parser\n```");
+        let config = quirks_for(
+            "llama3*",
+            ModelQuirksConfig {
+                strip_preamble: Some(true),
+                unwrap_code_fence: Some(true),
+                ..Default::default()
+            },
+        );
+
+        let provider = QuirksProvider::new(canned("llama3.2", answer.clone()), config);
+        let response = provider
+            .complete(&[Message::user("Hi")], &[])
+            .await
+            .unwrap();
+        assert_eq!(response.message.content.as_deref(), Some("Fix the parser"));
+
+        // With tools offered, the fence may be the point of the answer
+        let tools = vec![serde_json::json!({"name": "read_file"})];
+        let response = provider
+            .complete(&[Message::user("Hi")], &tools)
+            .await
+            .unwrap();
+        assert_eq!(
+            response.message.content.as_deref(),
+            Some("```\nFix the parser\n```")
+        );
+
+        let mut provider = provider;
+        provider.set_model("gpt-4o");
+        let response = provider
+            .complete(&[Message::user("Hi")], &[])
+            .await
+            .unwrap();
+        assert_eq!(response.message.content, answer.content);
+    }
+
+    #[tokio::test]
+    async fn test_repeats_tool_instructions_in_last_user_message() {
+        let config = quirks_for(
+            "phi*",
+            ModelQuirksConfig {
+                repeat_tool_instructions: Some(true),
+                ..Default::default()
+            },
+        );
+        let answer = Message::assistant_with_tools(vec![ToolCall {
+            id: "call-1".to_string(),
+            function: FunctionCall {
+                name: "read_file".to_string(),
+                arguments: "{}".to_string(),
+            },
+        }]);
+        let mock = canned("phi4", answer);
+        let provider = QuirksProvider::new(mock.clone(), config);
+        let tools = vec![serde_json::json!({"name": "read_file"})];
+        let messages = vec![
+            Message::system("You are helpful."),
+            Message::user("Read a.txt"),
+        ];
+
+        provider.complete(&messages, &tools).await.unwrap();
+        let sent = mock.last_request().unwrap().messages;
+        assert_eq!(sent[0].content.as_deref(), Some("You are helpful."));
+        assert_eq!(
+            sent[1].content.as_deref(),
+            Some(format!("Read a.txt\n\n{}", TOOL_INSTRUCTIONS_REMINDER).as_str())
+        );
+
+        // Nothing to remind about without tools
+        provider.complete(&messages, &[]).await.unwrap();
+        let sent = mock.last_request().unwrap().messages;
+        assert_eq!(sent[1].content.as_deref(), Some("Read a.txt"));
+    }
+
+    #[test]
+    fn test_caps_temperature_and_reports_nudge() {
+        let config = quirks_for(
+            "mistral*",
+            ModelQuirksConfig {
+                max_temperature: Some(0.5),
+                tool_call_nudge: Some(true),
+                ..Default::default()
+            },
+        );
+        let mock = canned("mistral:7b", Message::assistant("ok"));
+        let mut provider = QuirksProvider::new(mock.clone(), config);
+        assert!(provider.needs_tool_call_nudge());
+
+        provider.set_temperature(Some(0.9)).unwrap();
+        assert_eq!(mock.temperature(), Some(0.5));
+        provider.set_temperature(Some(0.2)).unwrap();
+        assert_eq!(mock.temperature(), Some(0.2));
+
+        provider.set_temperature(Some(0.9)).unwrap();
+        provider.set_model("llama3.2");
+        assert_eq!(mock.temperature(), Some(0.9));
+        assert!(!provider.needs_tool_call_nudge());
+    }
+
+    #[test]
+    fn test_wrap_with_quirks_skips_when_disabled() {
+        let config = ProviderQuirksConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        let provider = wrap_with_quirks(
+            Box::new(canned("llama3.2", Message::assistant("ok"))),
+            &config,
+        );
+        assert!(!provider.needs_tool_call_nudge());
+    }
+}
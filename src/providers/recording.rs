@@ -0,0 +1,576 @@
+//! Per-turn recording of agent runs
+//!
+//! `xzatoma run --record` wraps the provider in a [`RecordingProvider`].
+//! Every request it forwards is stored by a [`RunRecorder`] as one
+//! [`StoredRunTurn`] in the history database: the exact messages and tool
+//! definitions sent, the response or error, the tool calls the response
+//! asked for, and how long the provider took. `xzatoma debug <run-id>`
+//! reads the turns back.
+//!
+//! Tool results are not part of a response. They reach the provider as tool
+//! messages in a later request, so the recorder fills in a turn's results,
+//! matched by tool call ID, when such a request arrives, and once more from
+//! the final conversation in [`RunRecorder::finish`].
+//!
+//! A turn whose JSON exceeds `max_turn_bytes` has its longest strings cut,
+//! each ending in a marker such as `...[truncated 1200 bytes]`, until it
+//! fits. Recording failures never fail a completion; they are logged.
+
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_json::Value;
+
+use crate::config::RecordingConfig;
+use crate::error::Result;
+use crate::paths::Paths;
+use crate::storage::types::{RecordedResponse, RecordedToolCall, StoredRunTurn};
+use crate::storage::SqliteStorage;
+
+use super::trait_mod::Provider;
+use super::types::{
+    CompletionResponse, Message, ModelInfo, ModelInfoSummary, ProviderCapabilities, ToolCallOptions,
+};
+
+/// Start of the marker that ends a truncated string
+pub const TRUNCATION_MARKER: &str = "...[truncated";
+
+/// Strings are never cut below this many bytes
+const MIN_STRING_BYTES: usize = 256;
+
+/// Turns recorded so far and turns still waiting for tool results
+#[derive(Default)]
+struct RecorderState {
+    turns: u32,
+    pending: Vec<StoredRunTurn>,
+}
+
+/// Stores the turns of one recorded run
+pub struct RunRecorder {
+    run_id: String,
+    storage: SqliteStorage,
+    max_turn_bytes: usize,
+    state: Mutex<RecorderState>,
+}
+
+impl RunRecorder {
+    /// Creates a recorder for a new run with a fresh run ID
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - History database the turns are written to
+    /// * `max_turn_bytes` - Largest serialized size of one turn
+    pub fn new(storage: SqliteStorage, max_turn_bytes: usize) -> Self {
+        Self {
+            run_id: uuid::Uuid::new_v4().to_string(),
+            storage,
+            max_turn_bytes,
+            state: Mutex::new(RecorderState::default()),
+        }
+    }
+
+    /// Returns the ID the run's turns are stored under
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Returns the number of turns recorded so far
+    pub fn turns_recorded(&self) -> u32 {
+        self.lock().turns
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RecorderState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records one provider request and its outcome
+    fn record(
+        &self,
+        messages: &[Message],
+        tools: &[Value],
+        model: String,
+        started_at: chrono::DateTime<Utc>,
+        elapsed: std::time::Duration,
+        result: &Result<CompletionResponse>,
+    ) {
+        let mut state = self.lock();
+        let resolved = attach_tool_results(&mut state.pending, messages);
+        for turn in &resolved {
+            self.save(turn);
+        }
+
+        state.turns += 1;
+        let (response, error, tool_calls) = match result {
+            Ok(response) => (
+                Some(RecordedResponse {
+                    message: response.message.clone(),
+                    usage: response.usage,
+                    model: response.model.clone(),
+                    reasoning: response.reasoning.clone(),
+                    finish_reason: response.finish_reason,
+                    cached: response.cached,
+                }),
+                None,
+                response
+                    .message
+                    .tool_calls
+                    .iter()
+                    .flatten()
+                    .map(|call| RecordedToolCall {
+                        id: call.id.clone(),
+                        name: call.function.name.clone(),
+                        arguments: call.function.arguments.clone(),
+                        result: None,
+                    })
+                    .collect(),
+            ),
+            Err(error) => (None, Some(error.to_string()), Vec::new()),
+        };
+        let turn = StoredRunTurn {
+            run_id: self.run_id.clone(),
+            turn: state.turns,
+            started_at,
+            duration_ms: elapsed.as_millis() as u64,
+            model,
+            messages: messages.to_vec(),
+            tools: tools.to_vec(),
+            response,
+            error,
+            tool_calls,
+            truncated: false,
+        };
+        self.save(&turn);
+        if !turn.tool_calls.is_empty() {
+            state.pending.push(turn);
+        }
+    }
+
+    /// Fills in the tool results still missing from recorded turns
+    ///
+    /// Call once the run is over with the agent's final conversation, which
+    /// holds the results of the last turn's tool calls.
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - Final conversation messages
+    pub fn finish(&self, messages: &[Message]) {
+        let mut state = self.lock();
+        let resolved = attach_tool_results(&mut state.pending, messages);
+        for turn in &resolved {
+            self.save(turn);
+        }
+        state.pending.clear();
+    }
+
+    fn save(&self, turn: &StoredRunTurn) {
+        let turn = cap_turn_payload(turn, self.max_turn_bytes);
+        if let Err(error) = self.storage.save_run_turn(&turn) {
+            tracing::warn!(
+                run_id = %self.run_id,
+                turn = turn.turn,
+                "Failed to record run turn: {}",
+                error
+            );
+        }
+    }
+}
+
+/// Copies tool results from `messages` into the pending turns
+///
+/// Returns the turns that changed; turns with every result filled in are
+/// removed from `pending`.
+fn attach_tool_results(
+    pending: &mut Vec<StoredRunTurn>,
+    messages: &[Message],
+) -> Vec<StoredRunTurn> {
+    let mut changed = Vec::new();
+    pending.retain_mut(|turn| {
+        let mut updated = false;
+        for call in turn
+            .tool_calls
+            .iter_mut()
+            .filter(|call| call.result.is_none())
+        {
+            let result = messages
+                .iter()
+                .find(|message| message.tool_call_id.as_deref() == Some(call.id.as_str()));
+            if let Some(result) = result {
+                call.result = Some(result.content.clone().unwrap_or_default());
+                updated = true;
+            }
+        }
+        if updated {
+            changed.push(turn.clone());
+        }
+        turn.tool_calls.iter().any(|call| call.result.is_none())
+    });
+    changed
+}
+
+/// Returns `turn` with its strings cut until its JSON fits in `max_bytes`
+///
+/// Each pass starts again from the original turn with half the previous
+/// string limit, so every cut string carries one marker with the number of
+/// bytes removed. Strings are never cut below 256 bytes, so a turn made of
+/// many short strings may stay over the limit.
+///
+/// # Examples
+///
+/// ```
+/// use chrono::Utc;
+/// use xzatoma::providers::recording::{cap_turn_payload, TRUNCATION_MARKER};
+/// use xzatoma::providers::Message;
+/// use xzatoma::storage::types::StoredRunTurn;
+///
+/// let turn = StoredRunTurn {
+///     run_id: "run".to_string(),
+///     turn: 1,
+///     started_at: Utc::now(),
+///     duration_ms: 1,
+///     model: "fake".to_string(),
+///     messages: vec![Message::user("x".repeat(10_000))],
+///     tools: Vec::new(),
+///     response: None,
+///     error: None,
+///     tool_calls: Vec::new(),
+///     truncated: false,
+/// };
+///
+/// let capped = cap_turn_payload(&turn, 2048);
+/// assert!(capped.truncated);
+/// assert!(serde_json::to_vec(&capped).unwrap().len() <= 2048);
+/// assert!(capped.messages[0].content.as_ref().unwrap().contains(TRUNCATION_MARKER));
+/// ```
+pub fn cap_turn_payload(turn: &StoredRunTurn, max_bytes: usize) -> StoredRunTurn {
+    let Ok(original) = serde_json::to_value(turn) else {
+        return turn.clone();
+    };
+    let fits = |value: &Value| serde_json::to_vec(value).map_or(0, |json| json.len()) <= max_bytes;
+    if fits(&original) {
+        return turn.clone();
+    }
+
+    let mut limit = max_bytes;
+    let mut capped = original.clone();
+    while !fits(&capped) && limit > MIN_STRING_BYTES {
+        limit = (limit / 2).max(MIN_STRING_BYTES);
+        capped = original.clone();
+        truncate_strings(&mut capped, limit);
+    }
+
+    match serde_json::from_value::<StoredRunTurn>(capped) {
+        Ok(mut capped) => {
+            capped.truncated = true;
+            capped
+        }
+        Err(_) => turn.clone(),
+    }
+}
+
+/// Cuts every string in `value` longer than `limit` bytes
+fn truncate_strings(value: &mut Value, limit: usize) {
+    match value {
+        Value::String(text) if text.len() > limit => {
+            let mut end = limit;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            let removed = text.len() - end;
+            text.truncate(end);
+            text.push_str(&format!("{} {} bytes]", TRUNCATION_MARKER, removed));
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| truncate_strings(item, limit)),
+        Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|field| truncate_strings(field, limit)),
+        _ => {}
+    }
+}
+
+/// Provider wrapper that records every request through a [`RunRecorder`]
+///
+/// Both `complete` and `chat_completion_stream` are recorded, including
+/// failed requests.
+pub struct RecordingProvider<P: Provider> {
+    inner: P,
+    recorder: Arc<RunRecorder>,
+}
+
+impl<P: Provider> RecordingProvider<P> {
+    /// Wraps `inner`, recording its requests with `recorder`
+    pub fn new(inner: P, recorder: Arc<RunRecorder>) -> Self {
+        Self { inner, recorder }
+    }
+
+    /// Returns the recorder
+    pub fn recorder(&self) -> Arc<RunRecorder> {
+        Arc::clone(&self.recorder)
+    }
+
+    async fn recorded(
+        &self,
+        messages: &[Message],
+        tools: &[Value],
+        stream: bool,
+    ) -> Result<CompletionResponse> {
+        let model = self.inner.get_current_model();
+        let started_at = Utc::now();
+        let started = Instant::now();
+        let result = if stream {
+            self.inner.chat_completion_stream(messages, tools).await
+        } else {
+            self.inner.complete(messages, tools).await
+        };
+        self.recorder.record(
+            messages,
+            tools,
+            model,
+            started_at,
+            started.elapsed(),
+            &result,
+        );
+        result
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for RecordingProvider<P> {
+    fn is_authenticated(&self) -> bool {
+        self.inner.is_authenticated()
+    }
+
+    fn current_model(&self) -> Option<&str> {
+        self.inner.current_model()
+    }
+
+    fn set_model(&mut self, model: &str) {
+        self.inner.set_model(model)
+    }
+
+    async fn fetch_models(&self) -> Result<Vec<ModelInfo>> {
+        self.inner.fetch_models().await
+    }
+
+    async fn complete(&self, messages: &[Message], tools: &[Value]) -> Result<CompletionResponse> {
+        self.recorded(messages, tools, false).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        self.inner.list_models().await
+    }
+
+    async fn get_model_info(&self, model_name: &str) -> Result<ModelInfo> {
+        self.inner.get_model_info(model_name).await
+    }
+
+    fn get_current_model(&self) -> String {
+        self.inner.get_current_model()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        messages: &[Message],
+        tools: &[Value],
+    ) -> Result<CompletionResponse> {
+        self.recorded(messages, tools, true).await
+    }
+
+    fn get_provider_capabilities(&self) -> ProviderCapabilities {
+        self.inner.get_provider_capabilities()
+    }
+
+    fn set_thinking_effort(&self, effort: Option<&str>) -> Result<()> {
+        self.inner.set_thinking_effort(effort)
+    }
+
+    fn set_tool_call_options(&self, options: ToolCallOptions) -> Result<()> {
+        self.inner.set_tool_call_options(options)
+    }
+
+    fn set_temperature(&self, temperature: Option<f32>) -> Result<()> {
+        self.inner.set_temperature(temperature)
+    }
+
+    fn needs_tool_call_nudge(&self) -> bool {
+        self.inner.needs_tool_call_nudge()
+    }
+
+    async fn list_models_summary(&self) -> Result<Vec<ModelInfoSummary>> {
+        self.inner.list_models_summary().await
+    }
+
+    async fn get_model_info_summary(&self, model_name: &str) -> Result<ModelInfoSummary> {
+        self.inner.get_model_info_summary(model_name).await
+    }
+}
+
+/// Wraps `provider` in a [`RecordingProvider`] when recording is enabled
+///
+/// # Arguments
+///
+/// * `provider` - Provider to record
+/// * `config` - Recording configuration
+/// * `paths` - Resolved directories; turns go to the history database
+///
+/// # Returns
+///
+/// Returns the provider to use and, when recording, its recorder.
+///
+/// # Errors
+///
+/// Returns an error if recording is enabled and the history database
+/// cannot be opened.
+pub fn wrap_with_recorder(
+    provider: Box<dyn Provider>,
+    config: &RecordingConfig,
+    paths: &Paths,
+) -> Result<(Box<dyn Provider>, Option<Arc<RunRecorder>>)> {
+    if !config.enabled {
+        return Ok((provider, None));
+    }
+
+    let recorder = Arc::new(RunRecorder::new(
+        SqliteStorage::new(paths)?,
+        config.max_turn_bytes,
+    ));
+    tracing::debug!(run_id = %recorder.run_id(), "Run recording enabled");
+    let recording = RecordingProvider::new(provider, Arc::clone(&recorder));
+    Ok((Box::new(recording), Some(recorder)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PathsConfig;
+    use crate::paths::DATA_DIR_ENV;
+    use crate::providers::{FunctionCall, ToolCall};
+    use crate::testing::MockProvider;
+    use tempfile::TempDir;
+
+    /// Provider for the model the recorded turns name
+    fn scripted() -> MockProvider {
+        MockProvider::new().with_model("fake-model")
+    }
+
+    fn recorder(dir: &TempDir, max_turn_bytes: usize) -> Arc<RunRecorder> {
+        let storage = SqliteStorage::new_with_path(dir.path().join("history.db")).unwrap();
+        Arc::new(RunRecorder::new(storage, max_turn_bytes))
+    }
+
+    fn tool_call(id: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            function: FunctionCall {
+                name: "read_file".to_string(),
+                arguments: r#"{"path":"a.txt"}"#.to_string(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_records_requests_errors_and_tool_results() {
+        let dir = TempDir::new().unwrap();
+        let recorder = recorder(&dir, 64 * 1024);
+        let provider = RecordingProvider::new(
+            scripted()
+                .then_response(CompletionResponse::new(Message::assistant_with_tools(
+                    vec![tool_call("call-1")],
+                )))
+                .then_error("rate limited"),
+            Arc::clone(&recorder),
+        );
+        let tools = vec![serde_json::json!({"name": "read_file"})];
+
+        let mut messages = vec![Message::user("Read a.txt")];
+        let response = provider.complete(&messages, &tools).await.unwrap();
+        messages.push(response.message);
+        messages.push(Message::tool_result("call-1", "hello"));
+        assert!(provider.complete(&messages, &tools).await.is_err());
+        recorder.finish(&messages);
+
+        let turns = recorder.storage.load_run_turns(recorder.run_id()).unwrap();
+        assert_eq!(recorder.turns_recorded(), 2);
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].model, "fake-model");
+        assert_eq!(turns[0].messages.len(), 1);
+        assert_eq!(turns[0].tools, tools);
+        assert_eq!(
+            turns[0].tool_calls,
+            vec![RecordedToolCall {
+                id: "call-1".to_string(),
+                name: "read_file".to_string(),
+                arguments: r#"{"path":"a.txt"}"#.to_string(),
+                result: Some("hello".to_string()),
+            }]
+        );
+        assert_eq!(turns[1].messages.len(), 3);
+        assert!(turns[1].response.is_none());
+        assert!(turns[1].error.as_deref().unwrap().contains("rate limited"));
+    }
+
+    #[tokio::test]
+    async fn test_finish_fills_results_of_the_last_turn() {
+        let dir = TempDir::new().unwrap();
+        let recorder = recorder(&dir, 64 * 1024);
+        let provider = RecordingProvider::new(
+            scripted().then_response(CompletionResponse::new(Message::assistant_with_tools(
+                vec![tool_call("call-9")],
+            ))),
+            Arc::clone(&recorder),
+        );
+
+        let messages = vec![Message::user("go")];
+        provider.complete(&messages, &[]).await.unwrap();
+        let stored = recorder.storage.load_run_turns(recorder.run_id()).unwrap();
+        assert_eq!(stored[0].tool_calls[0].result, None);
+
+        recorder.finish(&[Message::tool_result("call-9", "done")]);
+        let stored = recorder.storage.load_run_turns(recorder.run_id()).unwrap();
+        assert_eq!(stored[0].tool_calls[0].result.as_deref(), Some("done"));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_turns_are_truncated_with_markers() {
+        let dir = TempDir::new().unwrap();
+        let recorder = recorder(&dir, 4096);
+        let provider =
+            RecordingProvider::new(scripted().then_text("short answer"), Arc::clone(&recorder));
+
+        let huge = "é".repeat(20_000);
+        provider
+            .complete(&[Message::user(huge.clone())], &[])
+            .await
+            .unwrap();
+
+        let turn = &recorder.storage.load_run_turns(recorder.run_id()).unwrap()[0];
+        assert!(turn.truncated);
+        assert!(serde_json::to_vec(turn).unwrap().len() <= 4096);
+        let content = turn.messages[0].content.as_deref().unwrap();
+        assert!(content.starts_with("éé"));
+        assert_eq!(content.matches(TRUNCATION_MARKER).count(), 1);
+        assert_eq!(
+            turn.response.as_ref().unwrap().message.content.as_deref(),
+            Some("short answer")
+        );
+    }
+
+    #[test]
+    fn test_disabled_recording_leaves_provider_unwrapped() {
+        let dir = TempDir::new().unwrap();
+        let paths = Paths::resolve_with(&PathsConfig::default(), |name| {
+            (name == DATA_DIR_ENV).then(|| dir.path().to_string_lossy().to_string())
+        })
+        .unwrap();
+        let (_, recorder) =
+            wrap_with_recorder(Box::new(scripted()), &RecordingConfig::default(), &paths).unwrap();
+        assert!(recorder.is_none());
+    }
+}
@@ -0,0 +1,97 @@
+//! Parsing of the `Retry-After` header on rate-limited responses
+//!
+//! A `429 Too Many Requests` answer may say how long to wait, either as a
+//! number of seconds (`Retry-After: 30`) or as an HTTP date
+//! (`Retry-After: Wed, 21 Oct 2015 07:28:00 GMT`). Providers read it with
+//! [`from_headers`] when they build [`XzatomaError::RateLimited`], so the
+//! request governor pauses for the time the server asked for.
+//!
+//! [`XzatomaError::RateLimited`]: crate::error::XzatomaError::RateLimited
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::time::Duration;
+
+/// Returns the wait requested by the `Retry-After` header, if any
+///
+/// # Arguments
+///
+/// * `headers` - Headers of the failed response
+///
+/// # Returns
+///
+/// `None` when the header is missing or cannot be parsed. A date in the
+/// past yields a zero duration.
+///
+/// # Examples
+///
+/// ```
+/// use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+/// use std::time::Duration;
+/// use xzatoma::providers::retry_after::from_headers;
+///
+/// let mut headers = HeaderMap::new();
+/// assert_eq!(from_headers(&headers), None);
+///
+/// headers.insert(RETRY_AFTER, HeaderValue::from_static("30"));
+/// assert_eq!(from_headers(&headers), Some(Duration::from_secs(30)));
+/// ```
+pub fn from_headers(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    parse(value, Utc::now())
+}
+
+/// Parses a `Retry-After` value given as delta-seconds or an HTTP date
+///
+/// # Arguments
+///
+/// * `value` - The header value
+/// * `now` - Current time, used to turn a date into a wait
+pub fn parse(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parse_delta_seconds() {
+        assert_eq!(parse("120", now()), Some(Duration::from_secs(120)));
+        assert_eq!(parse(" 0 ", now()), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(
+            parse("Wed, 21 Oct 2015 07:28:45 GMT", now()),
+            Some(Duration::from_secs(45))
+        );
+        assert_eq!(
+            parse("Wed, 21 Oct 2015 07:27:00 GMT", now()),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_values() {
+        assert_eq!(parse("", now()), None);
+        assert_eq!(parse("-5", now()), None);
+        assert_eq!(parse("soon", now()), None);
+    }
+}
@@ -0,0 +1,176 @@
+//! Request timeouts and deadlines for provider HTTP calls
+//!
+//! Every provider request gets its own timeout so that a single stuck call
+//! cannot consume the whole agent budget. The agent runs each provider call
+//! inside [`with_deadline`]; providers then size their per-request timeouts
+//! with [`request_timeout`], which never exceeds the time left before that
+//! deadline. Streaming responses use an idle timeout instead of a total cap,
+//! see [`next_within_idle_timeout`].
+
+use crate::error::XzatomaError;
+
+use futures::stream::{Stream, StreamExt};
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Maximum time allowed to establish a connection to a provider.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Timeout for metadata calls such as model listing and token exchange.
+pub const METADATA_TIMEOUT: Duration = Duration::from_secs(30);
+
+tokio::task_local! {
+    static DEADLINE: Instant;
+}
+
+/// Run `future` with `deadline` as the overall budget for provider calls
+///
+/// Provider requests made while `future` runs are capped at the time left
+/// before `deadline`.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use xzatoma::providers::timeouts::{remaining_budget, with_deadline};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let deadline = tokio::time::Instant::now() + Duration::from_secs(60);
+/// let remaining = with_deadline(deadline, async { remaining_budget() }).await;
+/// assert!(remaining.unwrap() <= Duration::from_secs(60));
+/// assert!(remaining_budget().is_none());
+/// # }
+/// ```
+pub async fn with_deadline<F: Future>(deadline: Instant, future: F) -> F::Output {
+    DEADLINE.scope(deadline, future).await
+}
+
+/// Time left before the current deadline, or `None` outside [`with_deadline`]
+pub fn remaining_budget() -> Option<Duration> {
+    DEADLINE
+        .try_with(|deadline| deadline.saturating_duration_since(Instant::now()))
+        .ok()
+}
+
+/// Timeout for one request: `configured`, capped by the remaining budget
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use xzatoma::providers::timeouts::request_timeout;
+///
+/// assert_eq!(request_timeout(Duration::from_secs(120)), Duration::from_secs(120));
+/// ```
+pub fn request_timeout(configured: Duration) -> Duration {
+    match remaining_budget() {
+        Some(remaining) => configured.min(remaining),
+        None => configured,
+    }
+}
+
+/// Build the error for a request that ran out of time
+pub fn timeout_error(provider: &str, timeout: Duration) -> XzatomaError {
+    XzatomaError::RequestTimeout {
+        provider: provider.to_string(),
+        timeout,
+        idle: false,
+    }
+}
+
+/// Convert a `reqwest` failure into a typed error
+///
+/// Timeouts become [`XzatomaError::RequestTimeout`] so callers can tell them
+/// apart from other network failures; anything else becomes a provider error
+/// prefixed with `context`.
+pub fn request_error(
+    provider: &str,
+    timeout: Duration,
+    context: &str,
+    error: reqwest::Error,
+) -> XzatomaError {
+    if error.is_timeout() {
+        tracing::warn!("{} timed out after {:?}", context, timeout);
+        timeout_error(provider, timeout)
+    } else {
+        tracing::error!("{}: {}", context, error);
+        XzatomaError::Provider(format!("{}: {}", context, error))
+    }
+}
+
+/// Await the next stream item, failing if none arrives within `idle_timeout`
+///
+/// The wait is also capped by the remaining deadline budget, if any.
+///
+/// # Errors
+///
+/// Returns `RequestTimeout` with `idle: true` when the stream stays silent for
+/// the whole timeout, or with `idle: false` when the deadline runs out first.
+pub async fn next_within_idle_timeout<S>(
+    provider: &str,
+    idle_timeout: Duration,
+    stream: &mut S,
+) -> Result<Option<S::Item>, XzatomaError>
+where
+    S: Stream + Unpin,
+{
+    let wait = request_timeout(idle_timeout);
+    match tokio::time::timeout(wait, stream.next()).await {
+        Ok(item) => Ok(item),
+        Err(_) if wait < idle_timeout => Err(timeout_error(provider, wait)),
+        Err(_) => Err(XzatomaError::RequestTimeout {
+            provider: provider.to_string(),
+            timeout: idle_timeout,
+            idle: true,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_timeout_is_capped_by_deadline() {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let timeout = with_deadline(deadline, async {
+            request_timeout(Duration::from_secs(120))
+        })
+        .await;
+        assert!(timeout <= Duration::from_secs(5));
+        assert!(timeout > Duration::from_secs(4));
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout_after_deadline_is_zero() {
+        let deadline = Instant::now();
+        let timeout = with_deadline(deadline, async {
+            request_timeout(Duration::from_secs(120))
+        })
+        .await;
+        assert_eq!(timeout, Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_next_within_idle_timeout_reports_idle_stream() {
+        let mut stream = futures::stream::pending::<u8>();
+        let err = next_within_idle_timeout("copilot", Duration::from_millis(20), &mut stream)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            XzatomaError::RequestTimeout { idle: true, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_next_within_idle_timeout_returns_items() {
+        let mut stream = futures::stream::iter(vec![1, 2]);
+        let item = next_within_idle_timeout("copilot", Duration::from_secs(1), &mut stream)
+            .await
+            .unwrap();
+        assert_eq!(item, Some(1));
+    }
+}
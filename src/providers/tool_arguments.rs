@@ -0,0 +1,526 @@
+//! Lenient parsing of tool-call arguments
+//!
+//! Models return tool-call `arguments` that are not valid JSON more often
+//! than the APIs suggest. Ollama models add prose after the object, put raw
+//! newlines or Windows paths in strings, forget to escape quotes, or send two
+//! objects back to back, and Copilot occasionally does the same under load.
+//! [`parse_tool_arguments`] tries a strict parse first, then applies repairs
+//! one at a time, retrying after each, and reports which repairs were needed.
+//! Arguments that no repair saves are returned as [`MalformedArguments`],
+//! whose [`corrective_message`](MalformedArguments::corrective_message) asks
+//! the model to send the call again.
+//!
+//! Truncated arguments are deliberately not completed: closing a cut-off
+//! object would run the tool with partial content, such as half a file.
+
+use std::fmt;
+
+use serde_json::Value;
+
+/// Longest excerpt of malformed arguments quoted back to the model
+const MAX_QUOTED_BYTES: usize = 2000;
+
+/// A repair applied to tool-call arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArgumentRepair {
+    /// Blank arguments were read as an empty object
+    Empty,
+    /// Text before or after the object, such as prose or a code fence, was
+    /// removed
+    SurroundingText,
+    /// Only the first of several concatenated objects was kept
+    ConcatenatedObjects,
+    /// Raw control characters, invalid backslash escapes, or unescaped
+    /// quotes inside strings were escaped
+    Escapes,
+    /// Commas before a closing brace or bracket were removed
+    TrailingCommas,
+    /// The object was sent encoded as a JSON string and was decoded
+    DoubleEncoded,
+}
+
+impl ArgumentRepair {
+    /// Returns the label used in logs and metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArgumentRepair::Empty => "empty",
+            ArgumentRepair::SurroundingText => "surrounding_text",
+            ArgumentRepair::ConcatenatedObjects => "concatenated_objects",
+            ArgumentRepair::Escapes => "escapes",
+            ArgumentRepair::TrailingCommas => "trailing_commas",
+            ArgumentRepair::DoubleEncoded => "double_encoded",
+        }
+    }
+}
+
+impl fmt::Display for ArgumentRepair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Arguments parsed by [`parse_tool_arguments`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedArguments {
+    /// The parsed arguments
+    pub value: Value,
+    /// Repairs applied, in order; empty when the arguments were valid JSON
+    pub repairs: Vec<ArgumentRepair>,
+}
+
+impl ParsedArguments {
+    /// Returns true when a repair was needed
+    pub fn was_repaired(&self) -> bool {
+        !self.repairs.is_empty()
+    }
+}
+
+/// Arguments that stayed invalid after every repair
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MalformedArguments {
+    /// The arguments as received
+    pub raw: String,
+    /// The error from the strict parse
+    pub error: String,
+}
+
+impl MalformedArguments {
+    /// Returns the tool result text asking the model to resend the call
+    ///
+    /// The message quotes the arguments as received, shortened when long, so
+    /// the model can see what went wrong.
+    pub fn corrective_message(&self, tool_name: &str) -> String {
+        format!(
+            "The arguments for tool '{}' are not valid JSON ({}), so the tool was not run. Arguments received:\n{}\nCall '{}' again with its arguments as one JSON object, escaping quotes, backslashes, and newlines inside strings.",
+            tool_name,
+            self.error,
+            excerpt(&self.raw),
+            tool_name
+        )
+    }
+}
+
+impl fmt::Display for MalformedArguments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid tool arguments: {}", self.error)
+    }
+}
+
+impl std::error::Error for MalformedArguments {}
+
+/// Parses tool-call arguments, repairing common mistakes
+///
+/// # Arguments
+///
+/// * `raw` - The `arguments` string of a tool call
+///
+/// # Returns
+///
+/// The parsed value with the repairs that were needed, or
+/// [`MalformedArguments`] when the arguments could not be repaired.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::providers::base::{parse_tool_arguments, ArgumentRepair};
+///
+/// let parsed = parse_tool_arguments(r#"{"path": "src/lib.rs"} Reading the file now."#).unwrap();
+/// assert_eq!(parsed.value["path"], "src/lib.rs");
+/// assert_eq!(parsed.repairs, vec![ArgumentRepair::SurroundingText]);
+///
+/// assert!(parse_tool_arguments(r#"{"path": "src/lib.rs", "content": "fn main"#).is_err());
+/// ```
+pub fn parse_tool_arguments(raw: &str) -> Result<ParsedArguments, MalformedArguments> {
+    let mut repairs = Vec::new();
+    let value = match serde_json::from_str::<Value>(raw) {
+        Ok(value) => value,
+        Err(error) => match repair(raw, &mut repairs) {
+            Some(value) => value,
+            None => {
+                return Err(MalformedArguments {
+                    raw: raw.to_string(),
+                    error: error.to_string(),
+                })
+            }
+        },
+    };
+
+    if let Value::String(inner) = &value {
+        if let Ok(decoded) = parse_tool_arguments(inner) {
+            if decoded.value.is_object() {
+                repairs.push(ArgumentRepair::DoubleEncoded);
+                repairs.extend(decoded.repairs);
+                return Ok(ParsedArguments {
+                    value: decoded.value,
+                    repairs,
+                });
+            }
+        }
+    }
+    Ok(ParsedArguments { value, repairs })
+}
+
+/// A repair step returning the changed text, or `None` when it does not apply
+type RepairStep = fn(&str) -> Option<(String, ArgumentRepair)>;
+
+/// Applies repair steps in turn until the text parses
+///
+/// The object is cut out again after escaping, because unescaped quotes
+/// hide where it ends.
+fn repair(raw: &str, repairs: &mut Vec<ArgumentRepair>) -> Option<Value> {
+    let mut text = raw.trim().to_string();
+    if text.is_empty() {
+        repairs.push(ArgumentRepair::Empty);
+        return Some(Value::Object(serde_json::Map::new()));
+    }
+
+    let steps: [RepairStep; 4] = [
+        first_object,
+        fix_escapes,
+        first_object,
+        remove_trailing_commas,
+    ];
+    for step in steps {
+        let Some((fixed, applied)) = step(&text) else {
+            continue;
+        };
+        text = fixed;
+        if !repairs.contains(&applied) {
+            repairs.push(applied);
+        }
+        if let Ok(value) = serde_json::from_str(&text) {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Cuts the first complete object out of `text`
+fn first_object(text: &str) -> Option<(String, ArgumentRepair)> {
+    let start = text.find('{')?;
+    let end = start + object_len(&text[start..])?;
+    let (before, after) = (text[..start].trim(), text[end..].trim());
+    let applied = if after.starts_with('{') {
+        ArgumentRepair::ConcatenatedObjects
+    } else if before.is_empty() && after.is_empty() {
+        return None;
+    } else {
+        ArgumentRepair::SurroundingText
+    };
+    Some((text[start..end].to_string(), applied))
+}
+
+/// Returns the byte length of the object starting `text`, or `None` when it
+/// is not closed
+fn object_len(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Escapes control characters, stray backslashes, and inner quotes in strings
+fn fix_escapes(text: &str) -> Option<(String, ArgumentRepair)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut fixed = String::with_capacity(text.len() + 16);
+    let mut in_string = false;
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        index += 1;
+        if !in_string {
+            in_string = c == '"';
+            fixed.push(c);
+            continue;
+        }
+        match c {
+            '\\' => match chars.get(index) {
+                Some(&next @ ('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't')) => {
+                    fixed.push('\\');
+                    fixed.push(next);
+                    index += 1;
+                }
+                Some('u')
+                    if chars
+                        .get(index + 1..index + 5)
+                        .is_some_and(|hex| hex.iter().all(char::is_ascii_hexdigit)) =>
+                {
+                    fixed.push('\\');
+                }
+                _ => fixed.push_str("\\\\"),
+            },
+            '"' if closes_string(&chars[index..]) => {
+                in_string = false;
+                fixed.push('"');
+            }
+            '"' => fixed.push_str("\\\""),
+            '\n' => fixed.push_str("\\n"),
+            '\r' => fixed.push_str("\\r"),
+            '\t' => fixed.push_str("\\t"),
+            c if (c as u32) < 0x20 => fixed.push_str(&format!("\\u{:04x}", c as u32)),
+            c => fixed.push(c),
+        }
+    }
+    (fixed != text).then_some((fixed, ArgumentRepair::Escapes))
+}
+
+/// Returns true when a quote followed by `rest` can end a string
+///
+/// A quote ends a string when JSON structure follows it. After a comma the
+/// next value must start too, so `"say "hi", then"` keeps the quotes
+/// around `hi` inside the string.
+fn closes_string(rest: &[char]) -> bool {
+    let mut rest = rest.iter().skip_while(|c| c.is_whitespace());
+    match rest.next() {
+        None | Some(':' | '}' | ']') => true,
+        Some(',') => {
+            let next: String = rest.skip_while(|c| c.is_whitespace()).take(5).collect();
+            next.is_empty()
+                || next.starts_with(['"', '{', '[', '}', ']', '-'])
+                || next.starts_with(|c: char| c.is_ascii_digit())
+                || ["true", "false", "null"]
+                    .iter()
+                    .any(|literal| next.starts_with(literal))
+        }
+        _ => false,
+    }
+}
+
+/// Removes commas directly before a closing brace or bracket
+fn remove_trailing_commas(text: &str) -> Option<(String, ArgumentRepair)> {
+    let mut fixed = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' && text[index + 1..].trim_start().starts_with(['}', ']']) {
+            continue;
+        }
+        fixed.push(c);
+    }
+    (fixed.len() != text.len()).then_some((fixed, ArgumentRepair::TrailingCommas))
+}
+
+/// Shortens `raw` to [`MAX_QUOTED_BYTES`] at a character boundary
+fn excerpt(raw: &str) -> String {
+    if raw.len() <= MAX_QUOTED_BYTES {
+        return raw.to_string();
+    }
+    let mut end = MAX_QUOTED_BYTES;
+    while !raw.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}... ({} more bytes)", &raw[..end], raw.len() - end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use ArgumentRepair::*;
+
+    /// A payload seen from a model and the expected outcome
+    struct Case {
+        name: &'static str,
+        raw: String,
+        /// Expected arguments, or `None` when the payload cannot be repaired
+        expected: Option<Value>,
+        repairs: &'static [ArgumentRepair],
+    }
+
+    fn case(
+        name: &'static str,
+        raw: impl Into<String>,
+        expected: Option<Value>,
+        repairs: &'static [ArgumentRepair],
+    ) -> Case {
+        Case {
+            name,
+            raw: raw.into(),
+            expected,
+            repairs,
+        }
+    }
+
+    fn cases() -> Vec<Case> {
+        vec![
+            case(
+                "valid arguments",
+                r#"{"path":"src/main.rs"}"#,
+                Some(json!({"path": "src/main.rs"})),
+                &[],
+            ),
+            case(
+                "valid string is left for schema validation",
+                r#""src/main.rs""#,
+                Some(json!("src/main.rs")),
+                &[],
+            ),
+            case("empty arguments", "", Some(json!({})), &[Empty]),
+            case("blank arguments", " \n", Some(json!({})), &[Empty]),
+            case(
+                "prose after the object",
+                r#"{"path": "src/lib.rs"} I will now read the file."#,
+                Some(json!({"path": "src/lib.rs"})),
+                &[SurroundingText],
+            ),
+            case(
+                "code fence around the object",
+                "```json\n{\"command\": \"cargo test\"}\n```",
+                Some(json!({"command": "cargo test"})),
+                &[SurroundingText],
+            ),
+            case(
+                "two concatenated objects",
+                r#"{"path":"a.rs"}{"path":"b.rs"}"#,
+                Some(json!({"path": "a.rs"})),
+                &[ConcatenatedObjects],
+            ),
+            case(
+                "objects on separate lines",
+                "{\"query\":\"todo\"}\n{\"query\":\"fixme\"}",
+                Some(json!({"query": "todo"})),
+                &[ConcatenatedObjects],
+            ),
+            case(
+                "raw newlines and tabs in a string",
+                "{\"path\":\"a.txt\",\"content\":\"line one\n\tline two\"}",
+                Some(json!({"path": "a.txt", "content": "line one\n\tline two"})),
+                &[Escapes],
+            ),
+            case(
+                "Windows path with single backslashes",
+                r#"{"path":"C:\Users\dev\src\main.rs"}"#,
+                Some(json!({"path": "C:\\Users\\dev\\src\\main.rs"})),
+                &[Escapes],
+            ),
+            case(
+                "unescaped quotes around a word",
+                r#"{"command":"echo "hello world""}"#,
+                Some(json!({"command": "echo \"hello world\""})),
+                &[Escapes],
+            ),
+            case(
+                "unescaped quotes followed by a comma",
+                r#"{"content":"say "hi", then leave","path":"a.txt"}"#,
+                Some(json!({"content": "say \"hi\", then leave", "path": "a.txt"})),
+                &[Escapes],
+            ),
+            case(
+                "trailing commas",
+                r#"{"paths":["a.rs","b.rs",],"recursive":true,}"#,
+                Some(json!({"paths": ["a.rs", "b.rs"], "recursive": true})),
+                &[TrailingCommas],
+            ),
+            case(
+                "prose after an object with a raw newline",
+                "{\"content\":\"a\nb\"}\nDone.",
+                Some(json!({"content": "a\nb"})),
+                &[SurroundingText, Escapes],
+            ),
+            case(
+                "unescaped quotes hiding where the object ends",
+                r#"{"code":"print("{")"} ok"#,
+                Some(json!({"code": "print(\"{\")"})),
+                &[Escapes, SurroundingText],
+            ),
+            case(
+                "object sent as a JSON string",
+                r#""{\"path\":\"a.txt\"}""#,
+                Some(json!({"path": "a.txt"})),
+                &[DoubleEncoded],
+            ),
+            case(
+                "JSON string holding an object and prose",
+                json!("{\"path\":\"a.txt\"} done").to_string(),
+                Some(json!({"path": "a.txt"})),
+                &[DoubleEncoded, SurroundingText],
+            ),
+            case(
+                "truncated object",
+                r#"{"path":"src/main.rs","content":"fn main() {"#,
+                None,
+                &[],
+            ),
+            case(
+                "prose without an object",
+                "I'll read the file now.",
+                None,
+                &[],
+            ),
+            case("unclosed array", r#"{"paths":["a.rs","b.rs""#, None, &[]),
+        ]
+    }
+
+    #[test]
+    fn test_real_world_payloads() {
+        for case in cases() {
+            match (parse_tool_arguments(&case.raw), case.expected) {
+                (Ok(parsed), Some(expected)) => {
+                    assert_eq!(parsed.value, expected, "{}", case.name);
+                    assert_eq!(parsed.repairs, case.repairs, "{}", case.name);
+                }
+                (Err(malformed), None) => assert_eq!(malformed.raw, case.raw, "{}", case.name),
+                (outcome, expected) => {
+                    panic!("{}: expected {:?}, got {:?}", case.name, expected, outcome)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_valid_arguments_need_no_repair() {
+        let parsed = parse_tool_arguments(r#"{"a":"\u00e9\\n\"x\""}"#).unwrap();
+        assert!(!parsed.was_repaired());
+        assert_eq!(parsed.value, json!({"a": "é\\n\"x\""}));
+    }
+
+    #[test]
+    fn test_corrective_message_quotes_the_arguments() {
+        let malformed = parse_tool_arguments(r#"{"path":"a.txt""#).unwrap_err();
+        let message = malformed.corrective_message("read_file");
+        assert!(message.starts_with("The arguments for tool 'read_file' are not valid JSON"));
+        assert!(message.contains("\n{\"path\":\"a.txt\"\n"));
+        assert!(message.contains("Call 'read_file' again"));
+
+        let long = format!("{{\"content\":\"{}", "é".repeat(MAX_QUOTED_BYTES));
+        let message = parse_tool_arguments(&long)
+            .unwrap_err()
+            .corrective_message("write_file");
+        assert!(message.contains("more bytes)"));
+        assert!(message.len() < MAX_QUOTED_BYTES + 500);
+    }
+}
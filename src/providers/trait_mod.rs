@@ -14,7 +14,7 @@ use crate::error::Result;
 use async_trait::async_trait;
 
 use super::types::{
-    CompletionResponse, Message, ModelInfo, ModelInfoSummary, ProviderCapabilities,
+    CompletionResponse, Message, ModelInfo, ModelInfoSummary, ProviderCapabilities, ToolCallOptions,
 };
 
 /// Provider trait for AI providers.
@@ -269,6 +269,54 @@ pub trait Provider: Send + Sync {
         Ok(())
     }
 
+    /// Set the tool-call parameters for subsequent completions.
+    ///
+    /// The agent calls this before each request with the `tool_choice` and
+    /// `parallel_tool_calls` values negotiated from the provider's
+    /// capabilities and the `agent.tools.tool_calls` configuration.
+    /// Providers whose API accepts those parameters override this method;
+    /// the default no-op is used by providers that do not, such as Ollama.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Parameters to send; `None` fields are omitted
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Provider` if the internal lock cannot be
+    /// acquired.
+    fn set_tool_call_options(&self, _options: ToolCallOptions) -> crate::error::Result<()> {
+        Ok(())
+    }
+
+    /// Set the sampling temperature for subsequent completions.
+    ///
+    /// `None` leaves the temperature to the provider or server default.
+    /// Providers whose requests carry a temperature override this method;
+    /// the default no-op is used by providers that do not.
+    ///
+    /// # Arguments
+    ///
+    /// * `temperature` - Sampling temperature, or `None` for the default
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Provider` if the internal lock cannot be
+    /// acquired.
+    fn set_temperature(&self, _temperature: Option<f32>) -> crate::error::Result<()> {
+        Ok(())
+    }
+
+    /// Whether the current model should be reminded to use its tools
+    ///
+    /// Some models answer a task in prose on the first turn instead of
+    /// calling a tool. When this returns true, the agent follows such an
+    /// answer with one reminder to use the tools before accepting it. Only
+    /// the model quirks layer overrides the default of `false`.
+    fn needs_tool_call_nudge(&self) -> bool {
+        false
+    }
+
     /// List models with full summary data.
     ///
     /// # Returns
@@ -319,6 +367,90 @@ pub trait Provider: Send + Sync {
     }
 }
 
+/// Boxed providers forward every method to the boxed value.
+///
+/// This lets wrappers such as `CachingProvider<P>` compose over the
+/// `Box<dyn Provider>` returned by the provider factory, including the
+/// methods that have default implementations.
+#[async_trait]
+impl<P: Provider + ?Sized> Provider for Box<P> {
+    fn is_authenticated(&self) -> bool {
+        (**self).is_authenticated()
+    }
+
+    fn current_model(&self) -> Option<&str> {
+        (**self).current_model()
+    }
+
+    fn set_model(&mut self, model: &str) {
+        (**self).set_model(model)
+    }
+
+    async fn fetch_models(&self) -> Result<Vec<ModelInfo>> {
+        (**self).fetch_models().await
+    }
+
+    async fn complete(
+        &self,
+        messages: &[Message],
+        tools: &[serde_json::Value],
+    ) -> Result<CompletionResponse> {
+        (**self).complete(messages, tools).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        (**self).list_models().await
+    }
+
+    async fn get_model_info(&self, model_name: &str) -> Result<ModelInfo> {
+        (**self).get_model_info(model_name).await
+    }
+
+    fn get_current_model(&self) -> String {
+        (**self).get_current_model()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        (**self).supports_streaming()
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        messages: &[Message],
+        tools: &[serde_json::Value],
+    ) -> Result<CompletionResponse> {
+        (**self).chat_completion_stream(messages, tools).await
+    }
+
+    fn get_provider_capabilities(&self) -> ProviderCapabilities {
+        (**self).get_provider_capabilities()
+    }
+
+    fn set_thinking_effort(&self, effort: Option<&str>) -> crate::error::Result<()> {
+        (**self).set_thinking_effort(effort)
+    }
+
+    fn set_tool_call_options(&self, options: ToolCallOptions) -> crate::error::Result<()> {
+        (**self).set_tool_call_options(options)
+    }
+
+    fn set_temperature(&self, temperature: Option<f32>) -> crate::error::Result<()> {
+        (**self).set_temperature(temperature)
+    }
+
+    fn needs_tool_call_nudge(&self) -> bool {
+        (**self).needs_tool_call_nudge()
+    }
+
+    async fn list_models_summary(&self) -> Result<Vec<ModelInfoSummary>> {
+        (**self).list_models_summary().await
+    }
+
+    async fn get_model_info_summary(&self, model_name: &str) -> Result<ModelInfoSummary> {
+        (**self).get_model_info_summary(model_name).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::Provider;
@@ -1539,6 +1539,12 @@ pub enum FinishReason {
     Other,
 }
 
+/// Most function tools the OpenAI-compatible chat APIs accept in one request
+pub const OPENAI_MAX_TOOLS: usize = 128;
+
+/// Longest function description the OpenAI-compatible chat APIs accept
+pub const OPENAI_MAX_TOOL_DESCRIPTION_CHARS: usize = 1024;
+
 /// Provider-level capabilities and features
 ///
 /// Describes which features and operations a provider supports.
@@ -1556,6 +1562,30 @@ pub struct ProviderCapabilities {
     pub supports_streaming: bool,
     /// Provider supports image input in user prompts.
     pub supports_vision: bool,
+    /// Most tool definitions accepted in one request, if limited.
+    pub max_tools: Option<usize>,
+    /// Longest tool description accepted, in characters, if limited.
+    pub max_tool_description_chars: Option<usize>,
+    /// Largest combined size of the serialized tool definitions, if limited.
+    pub max_tool_definitions_bytes: Option<usize>,
+    /// The model may return several tool calls in one response.
+    pub supports_parallel_tool_calls: bool,
+    /// Provider accepts a `tool_choice` request parameter.
+    pub supports_tool_choice: bool,
+    /// Most tool calls the agent should execute from one response, if limited.
+    pub max_tools_per_turn: Option<usize>,
+}
+
+/// Tool-call parameters sent with the next completion requests
+///
+/// Set through [`Provider::set_tool_call_options`](crate::providers::Provider::set_tool_call_options).
+/// `None` fields are left out of the request so the provider default applies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolCallOptions {
+    /// `tool_choice` for requests that carry tool definitions.
+    pub tool_choice: Option<crate::config::ToolChoiceMode>,
+    /// `parallel_tool_calls` for requests that carry tool definitions.
+    pub parallel_tool_calls: Option<bool>,
 }
 
 /// Completion response with message and optional token usage
@@ -1575,6 +1605,9 @@ pub struct CompletionResponse {
     pub reasoning: Option<String>,
     /// Reason the model stopped generating tokens.
     pub finish_reason: FinishReason,
+    /// Whether the response was served from the provider response cache
+    /// instead of a network call.
+    pub cached: bool,
 }
 
 impl CompletionResponse {
@@ -1602,6 +1635,7 @@ impl CompletionResponse {
             model: None,
             reasoning: None,
             finish_reason: FinishReason::Stop,
+            cached: false,
         }
     }
 
@@ -1631,6 +1665,7 @@ impl CompletionResponse {
             model: None,
             reasoning: None,
             finish_reason: FinishReason::Stop,
+            cached: false,
         }
     }
 
@@ -1661,6 +1696,7 @@ impl CompletionResponse {
             model: Some(model),
             reasoning: None,
             finish_reason: FinishReason::Stop,
+            cached: false,
         }
     }
 
@@ -1718,6 +1754,21 @@ impl CompletionResponse {
         self.finish_reason = reason;
         self
     }
+
+    /// Mark the response as served from the provider response cache.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::providers::{CompletionResponse, Message};
+    ///
+    /// let response = CompletionResponse::new(Message::assistant("Done")).mark_cached();
+    /// assert!(response.cached);
+    /// ```
+    pub fn mark_cached(mut self) -> Self {
+        self.cached = true;
+        self
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -2030,6 +2081,7 @@ fn content_parts_to_ollama_images(parts: Option<&[ProviderMessageContentPart]>)
 ///     }],
 ///     tools: vec![],
 ///     stream: false,
+///     options: None,
 /// };
 /// assert_eq!(req.model, "gpt-4o");
 /// ```
@@ -2044,6 +2096,29 @@ pub struct ProviderRequest {
     pub tools: Vec<ProviderTool>,
     /// Whether to stream the response token-by-token.
     pub stream: bool,
+    /// Sampling options; omitted to use the server's defaults.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<ProviderRequestOptions>,
+}
+
+/// Sampling options of a [`ProviderRequest`]
+///
+/// Serialized as Ollama's `options` object.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::providers::ProviderRequestOptions;
+///
+/// let options = ProviderRequestOptions { temperature: Some(0.2) };
+/// let json = serde_json::to_value(&options).unwrap();
+/// assert!((json["temperature"].as_f64().unwrap() - 0.2).abs() < 1e-6);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ProviderRequestOptions {
+    /// Sampling temperature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
 }
 
 /// Convert raw tool-definition JSON values from the tool registry into
@@ -2244,6 +2319,32 @@ mod tests {
         assert!(json.contains("\"content\":\"Test\""));
     }
 
+    #[test]
+    fn test_text_message_serialization_omits_content_parts() {
+        let json = serde_json::to_value(Message::user("hi")).unwrap();
+        assert_eq!(json, serde_json::json!({"role": "user", "content": "hi"}));
+
+        let parsed: Message = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.content.as_deref(), Some("hi"));
+        assert!(parsed.content_parts.is_none());
+    }
+
+    #[test]
+    fn test_image_message_round_trips_content_parts() {
+        let msg = Message::try_user_from_multimodal_input(MultimodalPromptInput::new(vec![
+            PromptInputPart::text("Describe"),
+            PromptInputPart::image(ImagePromptPart::inline_base64("image/png", "iVBORw0KGgo=")),
+        ]))
+        .unwrap();
+
+        let json = serde_json::to_string(&msg).unwrap();
+        let parsed: Message = serde_json::from_str(&json).unwrap();
+
+        assert!(parsed.has_image_content());
+        assert_eq!(parsed.content.as_deref(), Some("Describe"));
+        assert_eq!(parsed.multimodal_parts(), msg.multimodal_parts());
+    }
+
     #[test]
     fn test_tool_call_serialization() {
         let tool_call = ToolCall {
@@ -2402,6 +2503,9 @@ mod tests {
         assert!(!caps.supports_token_counts);
         assert!(!caps.supports_streaming);
         assert!(!caps.supports_vision);
+        assert!(!caps.supports_parallel_tool_calls);
+        assert!(!caps.supports_tool_choice);
+        assert!(caps.max_tools_per_turn.is_none());
     }
 
     #[test]
@@ -2437,6 +2541,7 @@ mod tests {
             supports_token_counts: true,
             supports_streaming: true,
             supports_vision: true,
+            ..Default::default()
         };
 
         assert!(caps.supports_model_listing);
@@ -2857,6 +2962,7 @@ mod tests {
             messages: vec![],
             tools: vec![],
             stream: false,
+            options: None,
         };
         let json = serde_json::to_value(&req).unwrap();
         assert!(!json.as_object().unwrap().contains_key("tools"));
@@ -2876,6 +2982,7 @@ mod tests {
                 },
             }],
             stream: false,
+            options: None,
         };
         let json = serde_json::to_value(&req).unwrap();
         assert!(json.as_object().unwrap().contains_key("tools"));
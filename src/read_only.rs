@@ -0,0 +1,324 @@
+//! Read-only mode for audits
+//!
+//! Planning mode keeps the agent from changing files through its prompt and
+//! a gate the user can approve past. Read-only mode is a hard guarantee: it
+//! is enforced where tools are registered, so no registered tool can change
+//! the workspace or run a command, whatever the prompt or the user says.
+//!
+//! Read-only mode is enabled with the global `--read-only` flag or
+//! `agent.read_only: true` in the configuration. A [`ToolRegistry`] with an
+//! enabled [`ReadOnlyPolicy`] asks [`ReadOnlyPolicy::admit`] about every
+//! registration:
+//!
+//! - the terminal is never registered;
+//! - a tool that reports [`ToolExecutor::mutates`] is wrapped in
+//!   [`ReadOnlyRefusal`], which refuses every call with the same error;
+//! - every other tool is registered unchanged.
+//!
+//! MCP tools are registered only when their server lists them in
+//! `read_safe_tools`. The fetch tool and `@url:` mentions are blocked by the
+//! [`NetworkPolicy`](crate::network_policy::NetworkPolicy) unless
+//! `--allow-fetch` is given.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Arc;
+//! use xzatoma::read_only::ReadOnlyPolicy;
+//! use xzatoma::tools::finish::FinishTool;
+//! use xzatoma::tools::ToolRegistry;
+//!
+//! let mut registry = ToolRegistry::new().with_read_only_policy(ReadOnlyPolicy::enforced());
+//! registry.register("finish", Arc::new(FinishTool));
+//! assert!(registry.get("finish").is_some());
+//! ```
+//!
+//! [`ToolRegistry`]: crate::tools::ToolRegistry
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::chat_mode::ChatMode;
+use crate::config::Config;
+use crate::error::{Result, XzatomaError};
+use crate::tools::{ToolExecutor, ToolResult};
+
+/// Registry name of the terminal tool, which read-only mode never registers
+const TERMINAL_TOOL: &str = "terminal";
+
+/// System prompt section added to every read-only session
+pub const READ_ONLY_PROMPT: &str = "## Read-Only Mode\n\n\
+This session is read-only. Tools that change files fail without making \
+changes, and no terminal is available. This is a hard constraint that the \
+user cannot lift during the session, so do not ask to switch to Write mode. \
+Analyze the workspace with the read tools and report findings and proposed \
+changes as text.";
+
+/// Decides which tools a session may register
+///
+/// The default policy is off and admits every tool.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadOnlyPolicy {
+    enabled: bool,
+}
+
+impl ReadOnlyPolicy {
+    /// Creates a policy that admits every tool
+    pub fn off() -> Self {
+        Self { enabled: false }
+    }
+
+    /// Creates a policy that refuses changes and commands
+    pub fn enforced() -> Self {
+        Self { enabled: true }
+    }
+
+    /// Creates the policy selected by `agent.read_only` in the configuration
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            enabled: config.agent.read_only,
+        }
+    }
+
+    /// Returns true when read-only mode is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the executor to register under `name`, or `None` to skip it
+    ///
+    /// With read-only mode enabled the terminal is skipped and mutating
+    /// tools are wrapped in [`ReadOnlyRefusal`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Registry name of the tool
+    /// * `executor` - Executor being registered
+    pub fn admit(
+        &self,
+        name: &str,
+        executor: Arc<dyn ToolExecutor>,
+    ) -> Option<Arc<dyn ToolExecutor>> {
+        if !self.enabled {
+            return Some(executor);
+        }
+        if name == TERMINAL_TOOL {
+            tracing::debug!("Skipping tool '{}': read-only mode", name);
+            return None;
+        }
+        if executor.mutates() {
+            tracing::debug!("Registering tool '{}' as refused: read-only mode", name);
+            return Some(Arc::new(ReadOnlyRefusal::new(name, executor)));
+        }
+        Some(executor)
+    }
+
+    /// Returns the chat mode a session may run in
+    ///
+    /// Read-only sessions always run in Planning mode.
+    pub fn effective_mode(&self, requested: ChatMode) -> ChatMode {
+        if self.enabled {
+            ChatMode::Planning
+        } else {
+            requested
+        }
+    }
+
+    /// Fails when switching to `mode` would allow changes
+    ///
+    /// # Errors
+    ///
+    /// Returns [`XzatomaError::ReadOnly`] for Write mode in read-only mode.
+    pub fn check_mode(&self, mode: ChatMode) -> Result<()> {
+        if self.enabled && mode == ChatMode::Write {
+            return Err(XzatomaError::ReadOnly(
+                "Write mode cannot be enabled; read-only mode is a hard constraint for the whole session"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Stands in for a mutating tool in read-only mode
+///
+/// The tool keeps its name and parameters so the model can still describe
+/// what it would do, but every call returns a failed result without calling
+/// the wrapped tool. The refusal itself changes nothing, so it does not
+/// report [`ToolExecutor::mutates`].
+pub struct ReadOnlyRefusal {
+    name: String,
+    inner: Arc<dyn ToolExecutor>,
+}
+
+impl ReadOnlyRefusal {
+    /// Wraps the tool registered as `name`
+    pub fn new(name: impl Into<String>, inner: Arc<dyn ToolExecutor>) -> Self {
+        Self {
+            name: name.into(),
+            inner,
+        }
+    }
+
+    /// The error every call returns
+    pub fn message(&self) -> String {
+        XzatomaError::ReadOnly(format!("{} cannot be used", self.name)).to_string()
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for ReadOnlyRefusal {
+    fn tool_definition(&self) -> Value {
+        let mut definition = self.inner.tool_definition();
+        let description = definition["description"].as_str().unwrap_or_default();
+        definition["description"] = Value::String(format!(
+            "Unavailable: this session is read-only and every call fails. {}",
+            description
+        ));
+        definition
+    }
+
+    async fn execute(&self, _args: Value) -> Result<ToolResult> {
+        Ok(ToolResult::error(self.message()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_mode::SafetyMode;
+    use crate::tools::registry_builder::ToolRegistryBuilder;
+    use crate::tools::ToolRegistry;
+    use serde_json::json;
+
+    /// Tools in the test registry that change the workspace or run commands
+    const MUTATING: [&str; 8] = [
+        "write_file",
+        "edit_file",
+        "delete_path",
+        "copy_path",
+        "move_path",
+        "create_directory",
+        "deploy",
+        "ide_edit",
+    ];
+
+    /// Arguments that would change the workspace for each built-in tool
+    fn mutation_attempt(tool: &str) -> Value {
+        match tool {
+            "write_file" => json!({"path": "created.txt", "content": "changed"}),
+            "edit_file" => json!({"path": "kept.txt", "mode": "overwrite", "content": "changed"}),
+            "delete_path" => json!({"path": "kept.txt"}),
+            "copy_path" => json!({"source_path": "kept.txt", "destination_path": "copy.txt"}),
+            "move_path" => json!({"source_path": "kept.txt", "destination_path": "moved.txt"}),
+            "create_directory" => json!({"path": "new_dir"}),
+            "deploy" => json!({}),
+            _ => json!({"path": "kept.txt"}),
+        }
+    }
+
+    #[test]
+    fn test_disabled_policy_admits_everything_unchanged() {
+        let policy = ReadOnlyPolicy::default();
+        let tool: Arc<dyn ToolExecutor> = Arc::new(
+            crate::testing::MockToolExecutor::builder("terminal")
+                .mutates(true)
+                .build(),
+        );
+        let admitted = policy.admit("terminal", Arc::clone(&tool)).unwrap();
+        assert!(Arc::ptr_eq(&admitted, &tool));
+        assert_eq!(policy.effective_mode(ChatMode::Write), ChatMode::Write);
+        assert!(policy.check_mode(ChatMode::Write).is_ok());
+    }
+
+    #[test]
+    fn test_enforced_policy_refuses_write_mode() {
+        let policy = ReadOnlyPolicy::enforced();
+        assert_eq!(policy.effective_mode(ChatMode::Write), ChatMode::Planning);
+        assert!(policy.check_mode(ChatMode::Planning).is_ok());
+        let err = policy.check_mode(ChatMode::Write).unwrap_err();
+        assert!(matches!(err, XzatomaError::ReadOnly(_)));
+        assert!(err.to_string().contains("hard constraint"));
+    }
+
+    #[tokio::test]
+    async fn test_every_registered_tool_fails_closed_on_mutation_attempts() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("kept.txt"), "original").unwrap();
+
+        let mut custom = crate::config::ToolsConfig::default();
+        custom.custom.push(
+            serde_yaml::from_str("name: deploy\ndescription: Deploy\ncommand: touch deployed")
+                .unwrap(),
+        );
+        let mut registry = ToolRegistryBuilder::new(
+            ChatMode::Write,
+            SafetyMode::NeverConfirm,
+            dir.path().to_path_buf(),
+        )
+        .with_tools_config(custom)
+        .with_read_only_policy(ReadOnlyPolicy::enforced())
+        .build_for_write()
+        .unwrap();
+        registry.register(
+            "ide_edit",
+            Arc::new(
+                crate::testing::MockToolExecutor::builder("ide_edit")
+                    .mutates(true)
+                    .build(),
+            ),
+        );
+
+        assert!(registry.get("terminal").is_none());
+        for name in MUTATING {
+            assert!(registry.get(name).is_some(), "{} is missing", name);
+        }
+        for name in registry.tool_names() {
+            let tool = registry.get(&name).unwrap();
+            assert!(!tool.mutates(), "{} still reports mutations", name);
+            if MUTATING.contains(&name.as_str()) {
+                let result = tool.execute(mutation_attempt(&name)).await.unwrap();
+                assert!(!result.success, "{} was not refused", name);
+                assert!(
+                    result.error.as_deref().unwrap().contains("Read-only mode"),
+                    "{} refused with an unexpected error",
+                    name
+                );
+            }
+        }
+
+        let mut entries: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, vec!["kept.txt"]);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("kept.txt")).unwrap(),
+            "original"
+        );
+    }
+
+    #[test]
+    fn test_refusal_keeps_name_and_parameters() {
+        let schema = json!({"type": "object", "properties": {"path": {"type": "string"}}});
+        let inner = crate::testing::MockToolExecutor::builder("write_file")
+            .description("Writes a file")
+            .parameters(schema.clone())
+            .mutates(true)
+            .build();
+        let mut registry = ToolRegistry::new().with_read_only_policy(ReadOnlyPolicy::enforced());
+        registry.register("write_file", Arc::new(inner.clone()));
+
+        let definition = registry.get("write_file").unwrap().tool_definition();
+        assert_eq!(definition["name"], "write_file");
+        assert_eq!(definition["parameters"], schema);
+        assert!(definition["description"]
+            .as_str()
+            .unwrap()
+            .starts_with("Unavailable"));
+        assert_eq!(inner.call_count(), 0);
+    }
+}
@@ -0,0 +1,651 @@
+//! Local semantic search over the workspace
+//!
+//! `xzatoma index build` splits the workspace's text files into overlapping
+//! line chunks, embeds each chunk with an [`EmbeddingProvider`], and stores
+//! the vectors with their file and line range in one JSON file under the
+//! project data directory. `@semantic:"..."` mentions embed the query with
+//! the same model and rank every stored chunk by cosine similarity; a flat
+//! scan is fast enough for the few thousand chunks of a typical repository.
+//!
+//! The walk honours `.gitignore`, `.ignore` and hidden-file rules, and skips
+//! binary files and files over `semantic.max_file_size_bytes`. Rebuilds are
+//! incremental: a file whose SHA-256 is unchanged keeps its vectors, and
+//! changing `semantic.embedding_model` re-embeds everything.
+//!
+//! # Examples
+//!
+//! ```
+//! use xzatoma::semantic_index::chunk_text;
+//!
+//! let text = "one\ntwo\nthree\nfour\nfive\n";
+//! let chunks = chunk_text(text, 3, 1);
+//! assert_eq!(chunks.len(), 2);
+//! assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 3));
+//! assert_eq!((chunks[1].start_line, chunks[1].end_line), (3, 5));
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::{Config, SemanticConfig};
+use crate::error::{Result, XzatomaError};
+use crate::paths::Paths;
+use crate::providers::embeddings::{EmbeddingProvider, OllamaEmbeddingProvider};
+
+/// Version of the on-disk index format
+const INDEX_VERSION: u32 = 1;
+
+/// Bytes inspected for NUL when deciding whether a file is binary
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+/// A range of lines taken from one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    /// First line of the chunk (1-based)
+    pub start_line: usize,
+    /// Last line of the chunk (1-based, inclusive)
+    pub end_line: usize,
+    /// Text of the chunk
+    pub text: String,
+}
+
+/// Splits text into chunks of `lines` lines, consecutive chunks sharing
+/// `overlap` lines
+///
+/// Chunks containing only whitespace are dropped. An `overlap` of `lines` or
+/// more is treated as `lines - 1` so the chunker always advances.
+///
+/// # Arguments
+///
+/// * `text` - File contents
+/// * `lines` - Lines per chunk
+/// * `overlap` - Lines shared by consecutive chunks
+pub fn chunk_text(text: &str, lines: usize, overlap: usize) -> Vec<TextChunk> {
+    let all: Vec<&str> = text.lines().collect();
+    let lines = lines.max(1);
+    let step = lines - overlap.min(lines - 1);
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < all.len() {
+        let end = (start + lines).min(all.len());
+        let text = all[start..end].join("\n");
+        if !text.trim().is_empty() {
+            chunks.push(TextChunk {
+                start_line: start + 1,
+                end_line: end,
+                text,
+            });
+        }
+        if end == all.len() {
+            break;
+        }
+        start += step;
+    }
+    chunks
+}
+
+/// Cosine similarity of two vectors
+///
+/// Returns 0.0 when the lengths differ or either vector is all zeros.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// Chunking and file limits used while indexing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexOptions {
+    /// Lines per chunk
+    pub chunk_lines: usize,
+    /// Lines shared by consecutive chunks
+    pub chunk_overlap: usize,
+    /// Files larger than this are skipped
+    pub max_file_size_bytes: u64,
+}
+
+impl From<&SemanticConfig> for IndexOptions {
+    fn from(config: &SemanticConfig) -> Self {
+        Self {
+            chunk_lines: config.chunk_lines,
+            chunk_overlap: config.chunk_overlap,
+            max_file_size_bytes: config.max_file_size_bytes,
+        }
+    }
+}
+
+/// An embedded chunk stored in the index
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IndexedChunk {
+    /// First line of the chunk (1-based)
+    pub start_line: usize,
+    /// Last line of the chunk (1-based, inclusive)
+    pub end_line: usize,
+    /// Text of the chunk
+    pub text: String,
+    /// Embedding of the chunk
+    pub vector: Vec<f32>,
+}
+
+/// The indexed chunks of one file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IndexedFile {
+    /// SHA-256 of the file contents when it was indexed
+    pub hash: String,
+    /// Embedded chunks in file order
+    pub chunks: Vec<IndexedChunk>,
+}
+
+/// Counts reported after building an index
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BuildReport {
+    /// Files embedded because they were new or changed
+    pub indexed: usize,
+    /// Files whose vectors were kept
+    pub unchanged: usize,
+    /// Files dropped because they were deleted or are now skipped
+    pub removed: usize,
+    /// Chunks in the index after the build
+    pub chunks: usize,
+}
+
+/// A chunk matching a semantic query
+#[derive(Debug, Clone, PartialEq)]
+pub struct SemanticHit {
+    /// Path relative to the indexed root, with `/` separators
+    pub path: String,
+    /// First line of the chunk (1-based)
+    pub start_line: usize,
+    /// Last line of the chunk (1-based, inclusive)
+    pub end_line: usize,
+    /// Text of the chunk
+    pub text: String,
+    /// Cosine similarity to the query
+    pub score: f32,
+}
+
+/// Embedded chunks of one workspace
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SemanticIndex {
+    /// Index format version
+    pub version: u32,
+    /// Embedding model that produced the vectors
+    pub model: String,
+    /// Indexed workspace root
+    pub root: PathBuf,
+    /// Indexed files keyed by relative path
+    pub files: BTreeMap<String, IndexedFile>,
+}
+
+impl SemanticIndex {
+    /// Creates an empty index for `root` embedded with `model`
+    pub fn new(root: impl Into<PathBuf>, model: impl Into<String>) -> Self {
+        Self {
+            version: INDEX_VERSION,
+            model: model.into(),
+            root: root.into(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Directory for index files
+    ///
+    /// `semantic.index_dir` when set, otherwise `semantic_index` in the data
+    /// directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data directory cannot be determined.
+    pub fn dir_for(config: &Config) -> Result<PathBuf> {
+        match &config.semantic.index_dir {
+            Some(dir) => Ok(PathBuf::from(dir)),
+            None => Ok(Paths::from_config(config)?.semantic_index_dir()),
+        }
+    }
+
+    /// Path of the index file for `root` inside `dir`
+    ///
+    /// Each workspace gets its own file, named after a hash of its
+    /// canonical path.
+    pub fn path_for(dir: &Path, root: &Path) -> PathBuf {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let digest = Sha256::digest(root.to_string_lossy().as_bytes());
+        let name: String = digest
+            .iter()
+            .take(8)
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        dir.join(format!("{}.json", name))
+    }
+
+    /// Loads the index at `path`
+    ///
+    /// Returns `None` when the file does not exist or was written by an
+    /// older format version.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(path)?;
+        let index: Self = serde_json::from_str(&contents).map_err(|e| {
+            XzatomaError::Search(format!(
+                "Failed to parse semantic index {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok((index.version == INDEX_VERSION).then_some(index))
+    }
+
+    /// Writes the index to `path`, replacing any existing file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec(self)?)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Number of chunks in the index
+    pub fn chunk_count(&self) -> usize {
+        self.files.values().map(|file| file.chunks.len()).sum()
+    }
+
+    /// Brings the index up to date with the files under `root`
+    ///
+    /// New and changed files are embedded, unchanged files keep their
+    /// vectors, and files that disappeared are dropped. When `embedder`
+    /// uses a different model from the one that built the index, every file
+    /// is embedded again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the workspace cannot be walked or an embedding
+    /// request fails. Files embedded before the failure are kept in `self`.
+    pub async fn update(
+        &mut self,
+        root: &Path,
+        embedder: &dyn EmbeddingProvider,
+        options: IndexOptions,
+    ) -> Result<BuildReport> {
+        if self.model != embedder.model() {
+            self.model = embedder.model().to_string();
+            self.files.clear();
+        }
+        self.root = root.to_path_buf();
+
+        let mut report = BuildReport::default();
+        let mut seen = HashSet::new();
+        for (relative, contents) in workspace_files(root, options.max_file_size_bytes)? {
+            let hash = content_hash(&contents);
+            seen.insert(relative.clone());
+            if self
+                .files
+                .get(&relative)
+                .is_some_and(|file| file.hash == hash)
+            {
+                report.unchanged += 1;
+                continue;
+            }
+
+            let mut chunks = Vec::new();
+            for chunk in chunk_text(&contents, options.chunk_lines, options.chunk_overlap) {
+                let vector = embedder
+                    .embed(&format!("{}\n{}", relative, chunk.text))
+                    .await?;
+                chunks.push(IndexedChunk {
+                    start_line: chunk.start_line,
+                    end_line: chunk.end_line,
+                    text: chunk.text,
+                    vector,
+                });
+            }
+            self.files.insert(relative, IndexedFile { hash, chunks });
+            report.indexed += 1;
+        }
+
+        let before = self.files.len();
+        self.files.retain(|path, _| seen.contains(path));
+        report.removed = before - self.files.len();
+        report.chunks = self.chunk_count();
+        Ok(report)
+    }
+
+    /// The `top_k` chunks most similar to `query`, best first
+    pub fn search(&self, query: &[f32], top_k: usize) -> Vec<SemanticHit> {
+        let mut hits: Vec<SemanticHit> = self
+            .files
+            .iter()
+            .flat_map(|(path, file)| {
+                file.chunks.iter().map(move |chunk| SemanticHit {
+                    path: path.clone(),
+                    start_line: chunk.start_line,
+                    end_line: chunk.end_line,
+                    text: chunk.text.clone(),
+                    score: cosine_similarity(query, &chunk.vector),
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(top_k);
+        hits
+    }
+}
+
+/// Text files under `root` as `(relative path, contents)`, sorted by path
+fn workspace_files(root: &Path, max_file_size_bytes: u64) -> Result<Vec<(String, String)>> {
+    let mut files = Vec::new();
+    for entry in ignore::WalkBuilder::new(root).build() {
+        let entry =
+            entry.map_err(|e| XzatomaError::Search(format!("Failed to walk workspace: {}", e)))?;
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue;
+        }
+        let path = entry.path();
+        if fs::metadata(path).map_or(true, |meta| meta.len() > max_file_size_bytes) {
+            continue;
+        }
+        let Ok(bytes) = fs::read(path) else {
+            continue;
+        };
+        if bytes.iter().take(BINARY_SNIFF_BYTES).any(|&b| b == 0) {
+            continue;
+        }
+        let Ok(contents) = String::from_utf8(bytes) else {
+            continue;
+        };
+        let Ok(relative) = path.strip_prefix(root) else {
+            continue;
+        };
+        let relative = relative
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        files.push((relative, contents));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Hex SHA-256 of file contents
+fn content_hash(contents: &str) -> String {
+    Sha256::digest(contents.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Queries a workspace's semantic index
+#[derive(Clone)]
+pub struct SemanticSearch {
+    index_path: PathBuf,
+    embedder: Arc<dyn EmbeddingProvider>,
+    top_k: usize,
+}
+
+impl std::fmt::Debug for SemanticSearch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SemanticSearch")
+            .field("index_path", &self.index_path)
+            .field("model", &self.embedder.model())
+            .field("top_k", &self.top_k)
+            .finish()
+    }
+}
+
+impl SemanticSearch {
+    /// Creates a search over the index file at `index_path`
+    pub fn new(
+        index_path: impl Into<PathBuf>,
+        embedder: Arc<dyn EmbeddingProvider>,
+        top_k: usize,
+    ) -> Self {
+        Self {
+            index_path: index_path.into(),
+            embedder,
+            top_k,
+        }
+    }
+
+    /// Creates a search over the index for `root` using the `semantic`
+    /// config section and the Ollama embedder
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data directory cannot be determined or the
+    /// HTTP client cannot be created.
+    pub fn from_config(config: &Config, root: &Path) -> Result<Self> {
+        Ok(Self::new(
+            SemanticIndex::path_for(&SemanticIndex::dir_for(config)?, root),
+            Arc::new(OllamaEmbeddingProvider::from_config(config)?),
+            config.semantic.top_k,
+        ))
+    }
+
+    /// Path of the index file
+    pub fn index_path(&self) -> &Path {
+        &self.index_path
+    }
+
+    /// Whether the index has been built
+    pub fn index_exists(&self) -> bool {
+        self.index_path.exists()
+    }
+
+    /// The chunks most similar to `query`, best first
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index is missing, was built with a different
+    /// embedding model, or the query cannot be embedded.
+    pub async fn query(&self, query: &str) -> Result<Vec<SemanticHit>> {
+        let index = SemanticIndex::load(&self.index_path)?.ok_or_else(|| {
+            XzatomaError::Search(
+                "Semantic index not found. Run `xzatoma index build` first".to_string(),
+            )
+        })?;
+        if index.model != self.embedder.model() {
+            return Err(XzatomaError::Search(format!(
+                "Semantic index was built with '{}' but semantic.embedding_model is '{}'. \
+                 Run `xzatoma index build` to rebuild it",
+                index.model,
+                self.embedder.model()
+            )));
+        }
+        let vector = self.embedder.embed(query).await?;
+        Ok(index.search(&vector, self.top_k))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+
+    const DIMENSIONS: usize = 64;
+
+    /// Bag-of-words embedder: each lowercase word bumps one hashed bucket
+    struct FakeEmbedder {
+        model: String,
+        calls: AtomicUsize,
+    }
+
+    impl FakeEmbedder {
+        fn new(model: &str) -> Self {
+            Self {
+                model: model.to_string(),
+                calls: AtomicUsize::new(0),
+            }
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for FakeEmbedder {
+        fn model(&self) -> &str {
+            &self.model
+        }
+
+        async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let mut vector = vec![0.0; DIMENSIONS];
+            for word in text
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+            {
+                let digest = Sha256::digest(word.to_lowercase().as_bytes());
+                vector[digest[0] as usize % DIMENSIONS] += 1.0;
+            }
+            Ok(vector)
+        }
+    }
+
+    fn options() -> IndexOptions {
+        IndexOptions {
+            chunk_lines: 4,
+            chunk_overlap: 1,
+            max_file_size_bytes: 1024,
+        }
+    }
+
+    #[test]
+    fn test_chunk_text_overlaps_and_skips_blank_chunks() {
+        let text = (1..=10)
+            .map(|n| format!("line {}", n))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let ranges: Vec<_> = chunk_text(&text, 4, 1)
+            .iter()
+            .map(|chunk| (chunk.start_line, chunk.end_line))
+            .collect();
+        assert_eq!(ranges, vec![(1, 4), (4, 7), (7, 10)]);
+
+        assert!(chunk_text("\n\n   \n", 2, 0).is_empty());
+        assert_eq!(chunk_text("a\nb\nc", 2, 5).len(), 2);
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_search_ranks_relevant_chunk_first() {
+        let workspace = TempDir::new().unwrap();
+        fs::write(
+            workspace.path().join("kafka.rs"),
+            "fn kafka_consumer() {}\n// kafka broker topic consumer group\n",
+        )
+        .unwrap();
+        fs::write(
+            workspace.path().join("http.rs"),
+            "fn http_server() {}\n// http request response router\n",
+        )
+        .unwrap();
+        fs::write(workspace.path().join("blob.bin"), b"kafka\0kafka").unwrap();
+
+        let embedder = FakeEmbedder::new("fake");
+        let mut index = SemanticIndex::new(workspace.path(), "fake");
+        let report = index
+            .update(workspace.path(), &embedder, options())
+            .await
+            .unwrap();
+        assert_eq!(report.indexed, 2);
+        assert_eq!(report.chunks, 2);
+
+        let query = embedder.embed("kafka consumer group").await.unwrap();
+        let hits = index.search(&query, 1);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, "kafka.rs");
+        assert_eq!((hits[0].start_line, hits[0].end_line), (1, 2));
+    }
+
+    #[tokio::test]
+    async fn test_update_reembeds_only_changed_files() {
+        let workspace = TempDir::new().unwrap();
+        let root = workspace.path();
+        fs::write(root.join("a.txt"), "alpha\n").unwrap();
+        fs::write(root.join("b.txt"), "beta\n").unwrap();
+        fs::write(root.join("c.txt"), "gamma\n").unwrap();
+
+        let embedder = FakeEmbedder::new("fake");
+        let mut index = SemanticIndex::new(root, "fake");
+        index.update(root, &embedder, options()).await.unwrap();
+        assert_eq!(embedder.calls(), 3);
+
+        fs::write(root.join("b.txt"), "beta changed\n").unwrap();
+        fs::remove_file(root.join("c.txt")).unwrap();
+        let report = index.update(root, &embedder, options()).await.unwrap();
+        assert_eq!(
+            report,
+            BuildReport {
+                indexed: 1,
+                unchanged: 1,
+                removed: 1,
+                chunks: 2,
+            }
+        );
+        assert_eq!(embedder.calls(), 4);
+
+        let other = FakeEmbedder::new("other");
+        let report = index.update(root, &other, options()).await.unwrap();
+        assert_eq!(report.indexed, 2);
+        assert_eq!(index.model, "other");
+    }
+
+    #[tokio::test]
+    async fn test_semantic_search_requires_built_index() {
+        let workspace = TempDir::new().unwrap();
+        let index_dir = TempDir::new().unwrap();
+        fs::write(workspace.path().join("notes.md"), "kafka consumer notes\n").unwrap();
+
+        let embedder = Arc::new(FakeEmbedder::new("fake"));
+        let path = SemanticIndex::path_for(index_dir.path(), workspace.path());
+        let search = SemanticSearch::new(path.clone(), embedder.clone(), 3);
+        assert!(!search.index_exists());
+        assert!(search.query("kafka").await.is_err());
+
+        let mut index = SemanticIndex::new(workspace.path(), "fake");
+        index
+            .update(workspace.path(), embedder.as_ref(), options())
+            .await
+            .unwrap();
+        index.save(&path).unwrap();
+
+        let hits = search.query("kafka").await.unwrap();
+        assert_eq!(hits[0].path, "notes.md");
+        assert_eq!(SemanticIndex::load(&path).unwrap(), Some(index));
+    }
+}
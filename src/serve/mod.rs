@@ -0,0 +1,27 @@
+//! Long-running JSON-RPC server for editor integrations
+//!
+//! `xzatoma serve --stdio` speaks JSON-RPC 2.0 over stdin and stdout, one
+//! message per line. Editors and IDE plugins keep a single process running
+//! and drive any number of agent sessions through it, instead of spawning
+//! `xzatoma run` and scraping its output for every prompt.
+//!
+//! | Method           | Purpose                                           |
+//! |------------------|---------------------------------------------------|
+//! | `initialize`     | Version handshake; must come first                |
+//! | `session/new`    | Start or resume a session with config overrides   |
+//! | `session/prompt` | Run a prompt; answers when the agent stops        |
+//! | `session/cancel` | Cancel the prompt running in a session            |
+//! | `history/list`   | Page through stored conversations                 |
+//! | `tools/list`     | Tool definitions available to a session           |
+//!
+//! While a prompt runs, the server sends `session/update` notifications
+//! with model text, reasoning, token usage, and tool activity.
+//!
+//! The wire types are those of the MCP client in [`crate::mcp::types`];
+//! [`protocol`] defines the method parameters and results.
+
+pub mod protocol;
+pub mod server;
+
+pub use protocol::{SessionUpdate, StopReason, SERVE_PROTOCOL_VERSION};
+pub use server::{run_stdio_server, serve_connection, ServeServer};
@@ -0,0 +1,469 @@
+//! Method names, error codes, and message types of the serve protocol
+//!
+//! Requests and responses use the JSON-RPC 2.0 wire types from
+//! [`crate::mcp::types`]. This module defines what goes inside them: the
+//! parameters and results of each method and the progress updates sent
+//! while a prompt runs. Field names are camelCase on the wire.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::events::AgentExecutionEvent;
+use crate::agent::ToolCallStatus;
+use crate::mcp::types::JsonRpcError;
+use crate::tools::ToolOutputStream;
+
+/// Protocol version spoken by this server
+pub const SERVE_PROTOCOL_VERSION: u32 = 1;
+
+/// Handshake that must precede every other request
+pub const METHOD_INITIALIZE: &str = "initialize";
+/// Creates a session, optionally resuming a stored conversation
+pub const METHOD_SESSION_NEW: &str = "session/new";
+/// Runs a prompt in a session
+pub const METHOD_SESSION_PROMPT: &str = "session/prompt";
+/// Cancels the prompt running in a session
+pub const METHOD_SESSION_CANCEL: &str = "session/cancel";
+/// Lists stored conversations
+pub const METHOD_HISTORY_LIST: &str = "history/list";
+/// Lists the tools available to a session
+pub const METHOD_TOOLS_LIST: &str = "tools/list";
+/// Notification carrying progress of a running prompt
+pub const NOTIF_SESSION_UPDATE: &str = "session/update";
+
+/// Methods the server answers, in the order `initialize` reports them
+pub const SERVE_METHODS: &[&str] = &[
+    METHOD_INITIALIZE,
+    METHOD_SESSION_NEW,
+    METHOD_SESSION_PROMPT,
+    METHOD_SESSION_CANCEL,
+    METHOD_HISTORY_LIST,
+    METHOD_TOOLS_LIST,
+];
+
+/// The message was not valid JSON
+pub const PARSE_ERROR: i64 = -32700;
+/// The message was not a valid JSON-RPC request
+pub const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// The parameters were missing or invalid
+pub const INVALID_PARAMS: i64 = -32602;
+/// The request failed inside the server
+pub const INTERNAL_ERROR: i64 = -32603;
+/// The session ID is unknown
+pub const SESSION_NOT_FOUND: i64 = -32001;
+/// A request other than `initialize` arrived before the handshake
+pub const NOT_INITIALIZED: i64 = -32002;
+/// The session is already running a prompt
+pub const SESSION_BUSY: i64 = -32003;
+
+/// Builds a JSON-RPC error object
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::serve::protocol::{rpc_error, METHOD_NOT_FOUND};
+///
+/// let error = rpc_error(METHOD_NOT_FOUND, "Unknown method: session/fork");
+/// assert_eq!(error.code, -32601);
+/// ```
+pub fn rpc_error(code: i64, message: impl Into<String>) -> JsonRpcError {
+    JsonRpcError {
+        code,
+        message: message.into(),
+        data: None,
+    }
+}
+
+/// Parameters of `initialize`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeParams {
+    /// Highest protocol version the client speaks
+    pub protocol_version: u32,
+    /// Name and version of the client, for logging
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_info: Option<ServeImplementation>,
+}
+
+/// Name and version of a client or server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServeImplementation {
+    /// Program name
+    pub name: String,
+    /// Program version
+    pub version: String,
+}
+
+/// Result of `initialize`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeResult {
+    /// Protocol version the server will speak
+    pub protocol_version: u32,
+    /// Name and version of the server
+    pub server_info: ServeImplementation,
+    /// Methods the server answers
+    pub methods: Vec<String>,
+}
+
+/// Parameters of `session/new`
+///
+/// Every field is optional; omitted fields keep the server's
+/// configuration. The overrides apply to this session only.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SessionNewParams {
+    /// Absolute workspace directory for the session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<PathBuf>,
+    /// Provider override (copilot, ollama, openai)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Model override within the provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Chat mode override (planning, write)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// Safety mode override (confirm, confirm_once, yolo)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safety: Option<String>,
+    /// Stored conversation to continue, by ID or unique prefix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resume: Option<String>,
+}
+
+/// Result of `session/new`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionNewResult {
+    /// Session identifier; also the ID of its stored conversation
+    pub session_id: String,
+    /// Workspace directory of the session
+    pub working_dir: PathBuf,
+    /// Provider serving the session
+    pub provider: String,
+    /// Model serving the session
+    pub model: String,
+    /// Messages carried over from a resumed conversation
+    pub resumed_messages: usize,
+}
+
+/// Parameters of `session/prompt`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionPromptParams {
+    /// Session to run the prompt in
+    pub session_id: String,
+    /// The user prompt
+    pub prompt: String,
+}
+
+/// Why a prompt stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// The model gave its final response
+    EndTurn,
+    /// The model asked the user a question
+    NeedsInput,
+    /// All turns allowed by `max_turns` were used
+    MaxTurns,
+    /// The prompt ran longer than `timeout_seconds`
+    Timeout,
+    /// The client cancelled the prompt
+    Cancelled,
+}
+
+/// Result of `session/prompt`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionPromptResult {
+    /// Why the prompt stopped
+    pub stop_reason: StopReason,
+    /// Final response or question; absent when the prompt was cut short
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+}
+
+/// Parameters of `session/cancel`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionCancelParams {
+    /// Session whose prompt to cancel
+    pub session_id: String,
+}
+
+/// Result of `session/cancel`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionCancelResult {
+    /// Whether a running prompt was cancelled
+    pub cancelled: bool,
+}
+
+/// Parameters of `history/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryListParams {
+    /// Maximum number of conversations to return
+    pub limit: usize,
+    /// Number of conversations to skip
+    pub offset: usize,
+    /// Tags a conversation must all carry
+    pub tags: Vec<String>,
+}
+
+impl Default for HistoryListParams {
+    fn default() -> Self {
+        Self {
+            limit: 25,
+            offset: 0,
+            tags: Vec::new(),
+        }
+    }
+}
+
+/// Parameters of `tools/list`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ToolsListParams {
+    /// Session whose tools to list; the server defaults are listed without it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+}
+
+/// Result of `tools/list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolsListResult {
+    /// Tool definitions as sent to the model, sorted by name
+    pub tools: Vec<serde_json::Value>,
+}
+
+/// Parameters of a `session/update` notification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionUpdateParams {
+    /// Session the prompt runs in
+    pub session_id: String,
+    /// ID of the `session/prompt` request the update belongs to
+    pub request_id: serde_json::Value,
+    /// What happened
+    pub update: SessionUpdate,
+}
+
+/// Progress of a running prompt
+///
+/// The agent does not stream tokens from the provider, so assistant text
+/// arrives once per model response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SessionUpdate {
+    /// Text of a model response
+    Text {
+        /// The assistant text
+        text: String,
+    },
+    /// Reasoning returned alongside a model response
+    Reasoning {
+        /// The reasoning text
+        text: String,
+    },
+    /// Tokens used by a model response
+    #[serde(rename_all = "camelCase")]
+    Usage {
+        /// Prompt tokens consumed
+        prompt_tokens: u64,
+        /// Completion tokens generated
+        completion_tokens: u64,
+    },
+    /// A tool call started
+    #[serde(rename_all = "camelCase")]
+    ToolStarted {
+        /// Tool call identifier
+        tool_call_id: String,
+        /// Tool name
+        name: String,
+        /// JSON arguments as sent by the model
+        arguments: String,
+    },
+    /// A running tool wrote a line of output
+    #[serde(rename_all = "camelCase")]
+    ToolOutput {
+        /// Tool call identifier
+        tool_call_id: String,
+        /// Tool name
+        name: String,
+        /// `stdout` or `stderr`
+        stream: String,
+        /// The line, without its newline
+        line: String,
+    },
+    /// A tool call returned a result
+    #[serde(rename_all = "camelCase")]
+    ToolCompleted {
+        /// Tool call identifier
+        tool_call_id: String,
+        /// Tool name
+        name: String,
+        /// Output returned to the model
+        output: String,
+        /// Whether the tool succeeded, failed, or timed out
+        status: ToolCallStatus,
+    },
+    /// A tool call failed with an error
+    #[serde(rename_all = "camelCase")]
+    ToolFailed {
+        /// Tool call identifier
+        tool_call_id: String,
+        /// Tool name
+        name: String,
+        /// Error description
+        error: String,
+    },
+}
+
+impl SessionUpdate {
+    /// Converts an agent event into an update, when clients care about it
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::agent::events::AgentExecutionEvent;
+    /// use xzatoma::serve::protocol::SessionUpdate;
+    ///
+    /// let update = SessionUpdate::from_event(AgentExecutionEvent::AssistantTextEmitted {
+    ///     text: "Done".to_string(),
+    /// });
+    /// assert_eq!(update, Some(SessionUpdate::Text { text: "Done".to_string() }));
+    /// assert_eq!(SessionUpdate::from_event(AgentExecutionEvent::PromptStarted), None);
+    /// ```
+    pub fn from_event(event: AgentExecutionEvent) -> Option<Self> {
+        let update = match event {
+            AgentExecutionEvent::AssistantTextEmitted { text } => Self::Text { text },
+            AgentExecutionEvent::ReasoningEmitted { text } => Self::Reasoning { text },
+            AgentExecutionEvent::TokenUsageReported {
+                prompt_tokens,
+                completion_tokens,
+            } => Self::Usage {
+                prompt_tokens,
+                completion_tokens,
+            },
+            AgentExecutionEvent::ToolCallStarted {
+                id,
+                name,
+                arguments,
+            } => Self::ToolStarted {
+                tool_call_id: id,
+                name,
+                arguments,
+            },
+            AgentExecutionEvent::ToolOutputChunk {
+                id,
+                name,
+                stream,
+                line,
+            } => Self::ToolOutput {
+                tool_call_id: id,
+                name,
+                stream: match stream {
+                    ToolOutputStream::Stdout => "stdout",
+                    ToolOutputStream::Stderr => "stderr",
+                }
+                .to_string(),
+                line,
+            },
+            AgentExecutionEvent::ToolCallCompleted {
+                id,
+                name,
+                output,
+                status,
+            } => Self::ToolCompleted {
+                tool_call_id: id,
+                name,
+                output,
+                status,
+            },
+            AgentExecutionEvent::ToolCallFailed { id, name, error } => Self::ToolFailed {
+                tool_call_id: id,
+                name,
+                error,
+            },
+            _ => return None,
+        };
+        Some(update)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_session_update_serializes_with_kind_and_camel_case_fields() {
+        let update = SessionUpdate::ToolStarted {
+            tool_call_id: "call_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: "{}".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            json!({
+                "kind": "tool_started",
+                "toolCallId": "call_1",
+                "name": "read_file",
+                "arguments": "{}",
+            })
+        );
+    }
+
+    #[test]
+    fn test_tool_output_event_maps_stream_name() {
+        let update = SessionUpdate::from_event(AgentExecutionEvent::ToolOutputChunk {
+            id: "call_2".to_string(),
+            name: "terminal".to_string(),
+            stream: ToolOutputStream::Stderr,
+            line: "warning".to_string(),
+        })
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&update).unwrap()["stream"],
+            json!("stderr")
+        );
+    }
+
+    #[test]
+    fn test_session_new_params_accept_missing_fields() {
+        let params: SessionNewParams = serde_json::from_value(json!({
+            "workingDir": "/work",
+            "model": "llama3.2:latest",
+        }))
+        .unwrap();
+        assert_eq!(params.working_dir, Some(PathBuf::from("/work")));
+        assert_eq!(params.model.as_deref(), Some("llama3.2:latest"));
+        assert!(params.provider.is_none());
+        assert!(params.resume.is_none());
+    }
+
+    #[test]
+    fn test_prompt_result_omits_missing_response() {
+        let result = SessionPromptResult {
+            stop_reason: StopReason::Cancelled,
+            response: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            json!({ "stopReason": "cancelled" })
+        );
+    }
+
+    #[test]
+    fn test_history_list_params_default_limit() {
+        let params: HistoryListParams = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(params.limit, 25);
+        assert_eq!(params.offset, 0);
+    }
+}
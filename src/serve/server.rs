@@ -0,0 +1,978 @@
+//! Session registry and request dispatch for `xzatoma serve`
+//!
+//! [`serve_connection`] reads newline-delimited JSON-RPC messages from one
+//! client and answers them through a [`ServeServer`]. Each request runs in
+//! its own task, so prompts in different sessions proceed concurrently
+//! while `history/list` and `tools/list` stay responsive. `initialize` and
+//! `session/cancel` are answered before the next line is read: the
+//! handshake must finish before anything else is dispatched, and a
+//! cancellation should not wait behind other work.
+//!
+//! Every session owns an [`Agent`] whose conversation persists across
+//! prompts. Each prompt runs as an [`AgentSession`](crate::agent::AgentSession),
+//! and the conversation is saved to history afterwards under the session
+//! ID, so `session/new` with `resume` can continue it later, in this server
+//! or in `xzatoma chat --resume`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::agent::events::{AgentExecutionEvent, AgentObserver};
+use crate::agent::{Agent, BudgetReason, Conversation, TurnOutcome};
+use crate::chat_mode::{ChatMode, SafetyMode};
+use crate::commands::{
+    build_agent_environment, build_output_pager, build_overflow_summarizer, start_usage_ledger,
+};
+use crate::config::Config;
+use crate::error::{Result, XzatomaError};
+use crate::mcp::manager::McpClientManager;
+use crate::mcp::types::{JsonRpcError, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse};
+use crate::network_policy::NetworkPolicy;
+use crate::paths::Paths;
+use crate::providers::{
+    create_provider, wrap_with_budget, wrap_with_cache, wrap_with_quirks, Provider,
+};
+use crate::read_only::READ_ONLY_PROMPT;
+use crate::serve::protocol::{
+    rpc_error, HistoryListParams, InitializeParams, InitializeResult, ServeImplementation,
+    SessionCancelParams, SessionCancelResult, SessionNewParams, SessionNewResult,
+    SessionPromptParams, SessionPromptResult, SessionUpdate, SessionUpdateParams, StopReason,
+    ToolsListParams, ToolsListResult, INTERNAL_ERROR, INVALID_PARAMS, INVALID_REQUEST,
+    METHOD_HISTORY_LIST, METHOD_INITIALIZE, METHOD_NOT_FOUND, METHOD_SESSION_CANCEL,
+    METHOD_SESSION_NEW, METHOD_SESSION_PROMPT, METHOD_TOOLS_LIST, NOTIF_SESSION_UPDATE,
+    NOT_INITIALIZED, PARSE_ERROR, SERVE_METHODS, SERVE_PROTOCOL_VERSION, SESSION_BUSY,
+    SESSION_NOT_FOUND,
+};
+use crate::storage::types::SessionPage;
+use crate::storage::SqliteStorage;
+
+type RpcResult<T> = std::result::Result<T, JsonRpcError>;
+
+/// Serves the protocol over stdin and stdout until stdin closes
+///
+/// # Arguments
+///
+/// * `config` - Loaded configuration; sessions start from a copy of it
+/// * `working_dir` - Workspace for sessions that do not name one
+///
+/// # Errors
+///
+/// Returns an error if stdin or stdout fails.
+pub async fn run_stdio_server(config: Config, working_dir: PathBuf) -> Result<()> {
+    let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+    serve_connection(config, working_dir, stdin, tokio::io::stdout()).await
+}
+
+/// Serves the protocol for one client until its input closes
+///
+/// Running prompts are cancelled when the input closes, and their final
+/// responses are written before this returns.
+///
+/// # Errors
+///
+/// Returns an error if reading from `reader` or writing to `writer` fails.
+pub async fn serve_connection<R, W>(
+    config: Config,
+    working_dir: PathBuf,
+    reader: R,
+    writer: W,
+) -> Result<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (outbound_tx, outbound_rx) = mpsc::unbounded_channel::<String>();
+    let writer_task = tokio::spawn(write_messages(outbound_rx, writer));
+    let server = Arc::new(ServeServer::new(config, working_dir, outbound_tx));
+
+    let mut requests = JoinSet::new();
+    let mut lines = reader.lines();
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else { break };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if answer_inline(&line) {
+                    server.respond(&line).await;
+                } else {
+                    let server = Arc::clone(&server);
+                    requests.spawn(async move { server.respond(&line).await });
+                }
+            }
+            Some(joined) = requests.join_next(), if !requests.is_empty() => {
+                if let Err(e) = joined {
+                    tracing::warn!(error = %e, "Serve request task failed");
+                }
+            }
+        }
+    }
+
+    tracing::info!("Client input closed; stopping the serve session");
+    server.cancel_all().await;
+    while let Some(joined) = requests.join_next().await {
+        if let Err(e) = joined {
+            tracing::warn!(error = %e, "Serve request task failed");
+        }
+    }
+
+    // Dropping the server closes the outbound channel, which ends the writer
+    drop(server);
+    writer_task
+        .await
+        .map_err(|e| XzatomaError::Internal(format!("Serve writer task failed: {}", e)))??;
+    Ok(())
+}
+
+/// Whether a message is answered before the next line is read
+fn answer_inline(line: &str) -> bool {
+    serde_json::from_str::<Value>(line)
+        .ok()
+        .and_then(|message| {
+            message
+                .get("method")
+                .and_then(Value::as_str)
+                .map(|method| method == METHOD_INITIALIZE || method == METHOD_SESSION_CANCEL)
+        })
+        // Malformed messages get their error response right away
+        .unwrap_or(true)
+}
+
+async fn write_messages<W>(
+    mut outbound: mpsc::UnboundedReceiver<String>,
+    mut writer: W,
+) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    while let Some(message) = outbound.recv().await {
+        writer.write_all(message.as_bytes()).await?;
+        writer.write_all(b"\n").await?;
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+/// One session: an agent, its tools, and the prompt it is running
+struct ServeSession {
+    agent: Mutex<Agent>,
+    model: String,
+    tools: Vec<Value>,
+    running: std::sync::Mutex<Option<CancellationToken>>,
+    // Keeps MCP tool executors connected for the life of the session
+    _mcp_manager: Option<Arc<RwLock<McpClientManager>>>,
+}
+
+impl ServeSession {
+    fn running(&self) -> std::sync::MutexGuard<'_, Option<CancellationToken>> {
+        self.running
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Answers requests from one client
+///
+/// Messages are passed to [`ServeServer::handle_message`]; responses and
+/// `session/update` notifications are sent as JSON strings on the outbound
+/// channel given to [`ServeServer::new`].
+pub struct ServeServer {
+    config: Config,
+    working_dir: PathBuf,
+    outbound: mpsc::UnboundedSender<String>,
+    initialized: AtomicBool,
+    sessions: RwLock<HashMap<String, Arc<ServeSession>>>,
+    storage: Option<SqliteStorage>,
+}
+
+impl ServeServer {
+    /// Creates a server for one client
+    ///
+    /// When the history database cannot be opened, sessions are not saved
+    /// and `history/list` and `resume` report an error.
+    pub fn new(
+        config: Config,
+        working_dir: PathBuf,
+        outbound: mpsc::UnboundedSender<String>,
+    ) -> Self {
+        let storage = match Paths::from_config(&config).and_then(|paths| SqliteStorage::new(&paths))
+        {
+            Ok(storage) => Some(storage),
+            Err(e) => {
+                tracing::warn!(error = %e, "History storage is unavailable; sessions will not be saved");
+                None
+            }
+        };
+        Self {
+            config,
+            working_dir,
+            outbound,
+            initialized: AtomicBool::new(false),
+            sessions: RwLock::new(HashMap::new()),
+            storage,
+        }
+    }
+
+    /// Handles one message and sends the response, if it needs one
+    pub async fn respond(&self, raw: &str) {
+        if let Some(response) = self.handle_message(raw).await {
+            send_json(&self.outbound, &response);
+        }
+    }
+
+    /// Handles one message from the client
+    ///
+    /// # Returns
+    ///
+    /// The response to send, or `None` for notifications.
+    pub async fn handle_message(&self, raw: &str) -> Option<JsonRpcResponse> {
+        let value = match serde_json::from_str::<Value>(raw) {
+            Ok(value) => value,
+            Err(e) => {
+                return Some(error_response(
+                    Value::Null,
+                    rpc_error(PARSE_ERROR, format!("Parse error: {}", e)),
+                ))
+            }
+        };
+        let id = value.get("id").cloned().unwrap_or(Value::Null);
+        let request: JsonRpcRequest = match serde_json::from_value(value) {
+            Ok(request) => request,
+            Err(e) => {
+                return Some(error_response(
+                    id,
+                    rpc_error(INVALID_REQUEST, format!("Invalid request: {}", e)),
+                ))
+            }
+        };
+        if request.jsonrpc != "2.0" {
+            return Some(error_response(
+                id,
+                rpc_error(INVALID_REQUEST, "jsonrpc must be \"2.0\""),
+            ));
+        }
+
+        let Some(id) = request.id else {
+            self.handle_notification(&request.method, request.params)
+                .await;
+            return None;
+        };
+        Some(
+            match self.dispatch(&id, &request.method, request.params).await {
+                Ok(result) => JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    id: Some(id),
+                    result: Some(result),
+                    error: None,
+                },
+                Err(error) => error_response(id, error),
+            },
+        )
+    }
+
+    /// Cancels every running prompt
+    pub async fn cancel_all(&self) {
+        for session in self.sessions.read().await.values() {
+            if let Some(token) = session.running().as_ref() {
+                token.cancel();
+            }
+        }
+    }
+
+    async fn handle_notification(&self, method: &str, params: Option<Value>) {
+        // Clients may cancel without waiting for an answer
+        if method == METHOD_SESSION_CANCEL && self.initialized.load(Ordering::Acquire) {
+            if let Ok(params) = parse_params::<SessionCancelParams>(params) {
+                let _ = self.cancel(params).await;
+            }
+            return;
+        }
+        tracing::debug!(method = %method, "Ignoring serve notification");
+    }
+
+    async fn dispatch(&self, id: &Value, method: &str, params: Option<Value>) -> RpcResult<Value> {
+        if method == METHOD_INITIALIZE {
+            return to_result(self.initialize(parse_params(params)?)?);
+        }
+        if !self.initialized.load(Ordering::Acquire) {
+            return Err(rpc_error(
+                NOT_INITIALIZED,
+                "Send initialize before any other request",
+            ));
+        }
+        match method {
+            METHOD_SESSION_NEW => {
+                to_result(self.new_session(parse_params_or_default(params)?).await?)
+            }
+            METHOD_SESSION_PROMPT => to_result(self.prompt(id, parse_params(params)?).await?),
+            METHOD_SESSION_CANCEL => to_result(self.cancel(parse_params(params)?).await?),
+            METHOD_HISTORY_LIST => to_result(self.history(parse_params_or_default(params)?)?),
+            METHOD_TOOLS_LIST => to_result(self.tools(parse_params_or_default(params)?).await?),
+            other => Err(rpc_error(
+                METHOD_NOT_FOUND,
+                format!("Unknown method: {}", other),
+            )),
+        }
+    }
+
+    fn initialize(&self, params: InitializeParams) -> RpcResult<InitializeResult> {
+        if params.protocol_version < 1 {
+            return Err(rpc_error(
+                INVALID_PARAMS,
+                format!(
+                    "Unsupported protocol version {}; this server speaks version {}",
+                    params.protocol_version, SERVE_PROTOCOL_VERSION
+                ),
+            ));
+        }
+        if self.initialized.swap(true, Ordering::AcqRel) {
+            return Err(rpc_error(
+                INVALID_REQUEST,
+                "The server is already initialized",
+            ));
+        }
+        if let Some(client) = &params.client_info {
+            tracing::info!(client = %client.name, version = %client.version, "Serve client connected");
+        }
+        Ok(InitializeResult {
+            protocol_version: SERVE_PROTOCOL_VERSION.min(params.protocol_version),
+            server_info: ServeImplementation {
+                name: "xzatoma".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            methods: SERVE_METHODS
+                .iter()
+                .map(|method| method.to_string())
+                .collect(),
+        })
+    }
+
+    async fn new_session(&self, params: SessionNewParams) -> RpcResult<SessionNewResult> {
+        let mut config = self.config.clone();
+        apply_session_overrides(&mut config, &params)?;
+        NetworkPolicy::from_config(&config)
+            .check_provider(&config.provider.provider_type)
+            .map_err(|e| rpc_error(INVALID_PARAMS, e.to_string()))?;
+
+        let working_dir = match &params.working_dir {
+            Some(dir) if dir.is_absolute() && dir.is_dir() => dir.clone(),
+            Some(dir) => {
+                return Err(rpc_error(
+                    INVALID_PARAMS,
+                    format!(
+                        "workingDir must be an absolute path to a directory: {}",
+                        dir.display()
+                    ),
+                ))
+            }
+            None => self.working_dir.clone(),
+        };
+        let resumed = match &params.resume {
+            Some(id) => Some(self.load_stored_conversation(id, &config)?),
+            None => None,
+        };
+        let resumed_messages = resumed
+            .as_ref()
+            .map_or(0, |conversation| conversation.messages().len());
+
+        let session = build_session(&config, &working_dir, resumed)
+            .await
+            .map_err(internal_error)?;
+        let session_id = session.agent.lock().await.conversation().id().to_string();
+        let model = session.model.clone();
+
+        let mut sessions = self.sessions.write().await;
+        if sessions.contains_key(&session_id) {
+            return Err(rpc_error(
+                INVALID_PARAMS,
+                format!("Conversation {} is already open in a session", session_id),
+            ));
+        }
+        sessions.insert(session_id.clone(), Arc::new(session));
+        tracing::info!(session_id = %session_id, working_dir = %working_dir.display(), "Serve session created");
+
+        Ok(SessionNewResult {
+            session_id,
+            working_dir,
+            provider: config.provider.provider_type,
+            model,
+            resumed_messages,
+        })
+    }
+
+    fn load_stored_conversation(&self, id: &str, config: &Config) -> RpcResult<Conversation> {
+        let storage = self.history_storage()?;
+        let not_found = || {
+            rpc_error(
+                INVALID_PARAMS,
+                format!("No stored conversation matches '{}'", id),
+            )
+        };
+        let stored_id = storage
+            .resolve_conversation_id(id)
+            .map_err(internal_error)?
+            .ok_or_else(not_found)?;
+        let (title, _model, messages) = storage
+            .load_conversation(&stored_id)
+            .map_err(internal_error)?
+            .ok_or_else(not_found)?;
+
+        let settings = &config.agent.conversation;
+        let mut conversation = Conversation::with_history(
+            Uuid::parse_str(&stored_id).unwrap_or_else(|_| Uuid::new_v4()),
+            title,
+            messages,
+            settings.max_tokens,
+            settings.min_retain_turns,
+            settings.prune_threshold as f64,
+        );
+        for index in storage.load_pinned_messages(&stored_id).unwrap_or_default() {
+            if let Err(e) = conversation.pin(index) {
+                tracing::debug!("Skipping stored pin {}: {}", index, e);
+            }
+        }
+        conversation.set_message_cwds(storage.load_message_cwds(&stored_id).unwrap_or_default());
+        Ok(conversation)
+    }
+
+    async fn session(&self, session_id: &str) -> RpcResult<Arc<ServeSession>> {
+        self.sessions
+            .read()
+            .await
+            .get(session_id)
+            .cloned()
+            .ok_or_else(|| {
+                rpc_error(
+                    SESSION_NOT_FOUND,
+                    format!("Unknown session: {}", session_id),
+                )
+            })
+    }
+
+    async fn prompt(
+        &self,
+        request_id: &Value,
+        params: SessionPromptParams,
+    ) -> RpcResult<SessionPromptResult> {
+        if params.prompt.trim().is_empty() {
+            return Err(rpc_error(INVALID_PARAMS, "prompt must not be empty"));
+        }
+        let session = self.session(&params.session_id).await?;
+        let Ok(mut agent) = session.agent.try_lock() else {
+            return Err(rpc_error(
+                SESSION_BUSY,
+                format!("Session {} is already running a prompt", params.session_id),
+            ));
+        };
+
+        let token = CancellationToken::new();
+        *session.running() = Some(token.clone());
+        if agent.conversation().last_user_message_index().is_none() {
+            agent
+                .conversation_mut()
+                .set_title(prompt_title(&params.prompt));
+        }
+
+        let mut observer = UpdateObserver {
+            outbound: self.outbound.clone(),
+            session_id: params.session_id.clone(),
+            request_id: request_id.clone(),
+        };
+        let result = run_prompt(&mut agent, params.prompt, &token, &mut observer).await;
+        session.running().take();
+        self.save_conversation(&agent, &session.model);
+
+        match result {
+            Ok(result) => Ok(result),
+            Err(XzatomaError::Cancelled) => Ok(SessionPromptResult {
+                stop_reason: StopReason::Cancelled,
+                response: None,
+            }),
+            Err(e) => Err(internal_error(e)),
+        }
+    }
+
+    async fn cancel(&self, params: SessionCancelParams) -> RpcResult<SessionCancelResult> {
+        let session = self.session(&params.session_id).await?;
+        let running = session.running();
+        let cancelled = match running.as_ref() {
+            Some(token) if !token.is_cancelled() => {
+                token.cancel();
+                true
+            }
+            _ => false,
+        };
+        Ok(SessionCancelResult { cancelled })
+    }
+
+    fn history(&self, params: HistoryListParams) -> RpcResult<SessionPage> {
+        self.history_storage()?
+            .list_sessions_page(&params.tags, params.limit, params.offset)
+            .map_err(internal_error)
+    }
+
+    async fn tools(&self, params: ToolsListParams) -> RpcResult<ToolsListResult> {
+        let tools = match &params.session_id {
+            Some(session_id) => self.session(session_id).await?.tools.clone(),
+            None => {
+                let env = build_agent_environment(&self.config, &self.working_dir, true)
+                    .await
+                    .map_err(internal_error)?;
+                sorted_definitions(env.tool_registry.all_definitions())
+            }
+        };
+        Ok(ToolsListResult { tools })
+    }
+
+    fn history_storage(&self) -> RpcResult<&SqliteStorage> {
+        self.storage
+            .as_ref()
+            .ok_or_else(|| rpc_error(INTERNAL_ERROR, "History storage is unavailable"))
+    }
+
+    fn save_conversation(&self, agent: &Agent, model: &str) {
+        let Some(storage) = &self.storage else {
+            return;
+        };
+        let conversation = agent.conversation();
+        let id = conversation.id().to_string();
+        if let Err(e) = storage
+            .save_conversation(
+                &id,
+                conversation.title(),
+                Some(model),
+                conversation.messages(),
+            )
+            .and_then(|()| storage.set_pinned_messages(&id, &conversation.pinned_indices()))
+            .and_then(|_| storage.set_message_cwds(&id, conversation.message_cwds()))
+        {
+            tracing::warn!(session_id = %id, error = %e, "Failed to save serve session");
+        }
+    }
+}
+
+/// Applies the `session/new` overrides to a copy of the configuration
+fn apply_session_overrides(config: &mut Config, params: &SessionNewParams) -> RpcResult<()> {
+    if let Some(provider) = &params.provider {
+        config.provider.provider_type = provider.clone();
+    }
+    let model = match config.provider.provider_type.as_str() {
+        "copilot" => &mut config.provider.copilot.model,
+        "ollama" => &mut config.provider.ollama.model,
+        "openai" => &mut config.provider.openai.model,
+        other => {
+            return Err(rpc_error(
+                INVALID_PARAMS,
+                format!(
+                    "Unknown provider '{}'; expected copilot, ollama, or openai",
+                    other
+                ),
+            ))
+        }
+    };
+    if let Some(override_model) = &params.model {
+        *model = override_model.clone();
+    }
+    if let Some(mode) = &params.mode {
+        ChatMode::parse_str(mode).map_err(|e| rpc_error(INVALID_PARAMS, e))?;
+        config.agent.chat.default_mode = mode.clone();
+    }
+    if let Some(safety) = &params.safety {
+        SafetyMode::parse_str(safety).map_err(|e| rpc_error(INVALID_PARAMS, e))?;
+        config.agent.chat.default_safety = safety.clone();
+    }
+    Ok(())
+}
+
+/// Builds the agent, tools, and provider of a new session
+async fn build_session(
+    config: &Config,
+    working_dir: &Path,
+    resumed: Option<Conversation>,
+) -> Result<ServeSession> {
+    let env = build_agent_environment(config, working_dir, true).await?;
+
+    // Requests are logged and checked against the monthly budget
+    let usage_ledger = start_usage_ledger(config, &config.provider.provider_type)?;
+    let (provider, _) = wrap_with_cache(
+        wrap_with_budget(
+            wrap_with_quirks(
+                create_provider(&config.provider.provider_type, &config.provider)?,
+                &config.provider.quirks,
+            ),
+            usage_ledger.as_ref(),
+        ),
+        &config.provider.provider_type,
+        &config.provider.cache,
+        &Paths::from_config(config)?,
+    );
+    let provider: Arc<dyn Provider> = Arc::from(provider);
+    let model = provider.get_current_model();
+    let overflow_summarizer = build_overflow_summarizer(config, &provider).await;
+
+    let mut agent = match resumed {
+        Some(conversation) => Agent::with_conversation_and_shared_provider(
+            provider,
+            env.tool_registry,
+            config.agent.clone(),
+            conversation,
+        )?,
+        None => Agent::new_from_shared_provider(provider, env.tool_registry, config.agent.clone())?,
+    };
+    agent.set_overflow_summarizer(overflow_summarizer);
+    agent.set_output_pager(build_output_pager(config));
+    let tools = sorted_definitions(agent.tools().all_definitions());
+    agent
+        .conversation_mut()
+        .set_cwd(Some(working_dir.to_path_buf()));
+
+    // A resumed conversation may already carry these
+    let mut system_messages = Vec::new();
+    if let Some(disclosure) = env.skill_disclosure {
+        system_messages.push(disclosure);
+    }
+    if config.agent.read_only {
+        system_messages.push(READ_ONLY_PROMPT.to_string());
+    }
+    for message in system_messages {
+        let present = agent.conversation().messages().iter().any(|existing| {
+            existing.role == "system" && existing.content.as_deref() == Some(message.as_str())
+        });
+        if !present {
+            agent.conversation_mut().add_system_message(message);
+        }
+    }
+
+    Ok(ServeSession {
+        agent: Mutex::new(agent),
+        model,
+        tools,
+        running: std::sync::Mutex::new(None),
+        _mcp_manager: env.mcp_manager,
+    })
+}
+
+/// Runs a prompt turn by turn until the agent stops
+async fn run_prompt(
+    agent: &mut Agent,
+    prompt: String,
+    token: &CancellationToken,
+    observer: &mut dyn AgentObserver,
+) -> Result<SessionPromptResult> {
+    let mut session = agent.start_session(prompt);
+    loop {
+        let (stop_reason, response) = match session.next_turn(token, observer).await? {
+            TurnOutcome::Continue => continue,
+            TurnOutcome::Finished(response) => (StopReason::EndTurn, Some(response)),
+            TurnOutcome::NeedsInput(question) => (StopReason::NeedsInput, Some(question)),
+            TurnOutcome::Budget {
+                reason: BudgetReason::MaxTurns { .. },
+            } => (StopReason::MaxTurns, None),
+            TurnOutcome::Budget {
+                reason: BudgetReason::Timeout { .. },
+            } => (StopReason::Timeout, None),
+        };
+        return Ok(SessionPromptResult {
+            stop_reason,
+            response,
+        });
+    }
+}
+
+/// Sends agent events of one prompt as `session/update` notifications
+struct UpdateObserver {
+    outbound: mpsc::UnboundedSender<String>,
+    session_id: String,
+    request_id: Value,
+}
+
+impl AgentObserver for UpdateObserver {
+    fn on_event(&mut self, event: AgentExecutionEvent) {
+        let Some(update) = SessionUpdate::from_event(event) else {
+            return;
+        };
+        let params = SessionUpdateParams {
+            session_id: self.session_id.clone(),
+            request_id: self.request_id.clone(),
+            update,
+        };
+        let notification = JsonRpcNotification {
+            jsonrpc: "2.0".to_string(),
+            method: NOTIF_SESSION_UPDATE.to_string(),
+            params: serde_json::to_value(params).ok(),
+        };
+        send_json(&self.outbound, &notification);
+    }
+}
+
+fn send_json<T: Serialize>(outbound: &mpsc::UnboundedSender<String>, message: &T) {
+    match serde_json::to_string(message) {
+        // The receiver is gone only after the client disconnected
+        Ok(json) => {
+            let _ = outbound.send(json);
+        }
+        Err(e) => tracing::warn!(error = %e, "Failed to serialize serve message"),
+    }
+}
+
+fn error_response(id: Value, error: JsonRpcError) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: Some(id),
+        result: None,
+        error: Some(error),
+    }
+}
+
+fn internal_error(error: XzatomaError) -> JsonRpcError {
+    rpc_error(INTERNAL_ERROR, error.user_message())
+}
+
+fn parse_params<T: DeserializeOwned>(params: Option<Value>) -> RpcResult<T> {
+    let params = params.ok_or_else(|| rpc_error(INVALID_PARAMS, "Missing params"))?;
+    serde_json::from_value(params)
+        .map_err(|e| rpc_error(INVALID_PARAMS, format!("Invalid params: {}", e)))
+}
+
+fn parse_params_or_default<T: DeserializeOwned + Default>(params: Option<Value>) -> RpcResult<T> {
+    match params {
+        Some(Value::Null) | None => Ok(T::default()),
+        Some(params) => parse_params(Some(params)),
+    }
+}
+
+fn to_result<T: Serialize>(result: T) -> RpcResult<Value> {
+    serde_json::to_value(result).map_err(|e| rpc_error(INTERNAL_ERROR, e.to_string()))
+}
+
+fn sorted_definitions(mut definitions: Vec<Value>) -> Vec<Value> {
+    definitions.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+    definitions
+}
+
+/// Title for a saved session: the first line of its first prompt, shortened
+/// to 50 characters
+fn prompt_title(prompt: &str) -> String {
+    let line = prompt.lines().next().unwrap_or_default().trim();
+    if line.chars().count() <= 50 {
+        return line.to_string();
+    }
+    let mut title: String = line.chars().take(47).collect();
+    title.push_str("...");
+    title
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn test_server() -> (
+        ServeServer,
+        mpsc::UnboundedReceiver<String>,
+        tempfile::TempDir,
+    ) {
+        let data_dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.paths.data_dir = Some(data_dir.path().to_string_lossy().to_string());
+        let (tx, rx) = mpsc::unbounded_channel();
+        let server = ServeServer::new(config, data_dir.path().to_path_buf(), tx);
+        (server, rx, data_dir)
+    }
+
+    async fn call(server: &ServeServer, message: Value) -> JsonRpcResponse {
+        server
+            .handle_message(&message.to_string())
+            .await
+            .expect("requests get a response")
+    }
+
+    async fn initialize(server: &ServeServer) -> JsonRpcResponse {
+        call(
+            server,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": { "protocolVersion": 1 },
+            }),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_initialize_reports_version_and_methods() {
+        let (server, _rx, _dir) = test_server();
+        let response = initialize(&server).await;
+        let result = response.result.unwrap();
+        assert_eq!(result["protocolVersion"], json!(1));
+        assert_eq!(result["serverInfo"]["name"], json!("xzatoma"));
+        assert!(result["methods"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("session/prompt")));
+    }
+
+    #[tokio::test]
+    async fn test_initialize_negotiates_down_to_server_version() {
+        let (server, _rx, _dir) = test_server();
+        let response = call(
+            &server,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": { "protocolVersion": 7 },
+            }),
+        )
+        .await;
+        assert_eq!(response.result.unwrap()["protocolVersion"], json!(1));
+    }
+
+    #[tokio::test]
+    async fn test_requests_before_initialize_are_rejected() {
+        let (server, _rx, _dir) = test_server();
+        let response = call(
+            &server,
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "history/list" }),
+        )
+        .await;
+        assert_eq!(response.error.unwrap().code, NOT_INITIALIZED);
+    }
+
+    #[tokio::test]
+    async fn test_second_initialize_is_rejected() {
+        let (server, _rx, _dir) = test_server();
+        initialize(&server).await;
+        let response = initialize(&server).await;
+        assert_eq!(response.error.unwrap().code, INVALID_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_method_and_parse_errors() {
+        let (server, _rx, _dir) = test_server();
+        initialize(&server).await;
+
+        let response = call(
+            &server,
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "session/fork" }),
+        )
+        .await;
+        assert_eq!(response.error.unwrap().code, METHOD_NOT_FOUND);
+
+        let response = server.handle_message("{not json").await.unwrap();
+        assert_eq!(response.id, Some(Value::Null));
+        assert_eq!(response.error.unwrap().code, PARSE_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_prompt_for_unknown_session_is_rejected() {
+        let (server, _rx, _dir) = test_server();
+        initialize(&server).await;
+        let response = call(
+            &server,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 4,
+                "method": "session/prompt",
+                "params": { "sessionId": "missing", "prompt": "Hello" },
+            }),
+        )
+        .await;
+        assert_eq!(response.error.unwrap().code, SESSION_NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_session_new_rejects_unknown_provider_and_relative_dir() {
+        let (server, _rx, _dir) = test_server();
+        initialize(&server).await;
+
+        let response = call(
+            &server,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 5,
+                "method": "session/new",
+                "params": { "provider": "bard" },
+            }),
+        )
+        .await;
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+
+        let response = call(
+            &server,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 6,
+                "method": "session/new",
+                "params": { "workingDir": "relative/dir" },
+            }),
+        )
+        .await;
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_history_list_returns_empty_page() {
+        let (server, _rx, _dir) = test_server();
+        initialize(&server).await;
+        let response = call(
+            &server,
+            json!({ "jsonrpc": "2.0", "id": 7, "method": "history/list" }),
+        )
+        .await;
+        let result = response.result.unwrap();
+        assert_eq!(result["sessions"], json!([]));
+        assert_eq!(result["total"], json!(0));
+    }
+
+    #[tokio::test]
+    async fn test_notifications_get_no_response() {
+        let (server, _rx, _dir) = test_server();
+        initialize(&server).await;
+        let response = server
+            .handle_message(
+                &json!({
+                    "jsonrpc": "2.0",
+                    "method": "session/cancel",
+                    "params": { "sessionId": "missing" },
+                })
+                .to_string(),
+            )
+            .await;
+        assert!(response.is_none());
+    }
+
+    #[test]
+    fn test_answer_inline_only_for_handshake_cancel_and_garbage() {
+        assert!(answer_inline(
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize"}"#
+        ));
+        assert!(answer_inline(
+            r#"{"jsonrpc":"2.0","id":2,"method":"session/cancel"}"#
+        ));
+        assert!(answer_inline("not json"));
+        assert!(!answer_inline(
+            r#"{"jsonrpc":"2.0","id":3,"method":"session/prompt"}"#
+        ));
+    }
+
+    #[test]
+    fn test_prompt_title_shortens_first_line() {
+        assert_eq!(prompt_title("Fix the build\nDetails"), "Fix the build");
+        let long = "x".repeat(80);
+        assert_eq!(prompt_title(&long).chars().count(), 50);
+    }
+}
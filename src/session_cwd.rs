@@ -0,0 +1,257 @@
+//! Session working directory for chat and run
+//!
+//! The agent's file tools, terminal tool, and mention resolution are rooted
+//! in one directory. By default that is the directory XZatoma was launched
+//! from; `--cwd` starts the session somewhere else and `/cd` moves it during
+//! a chat. A [`SessionCwd`] tracks the current directory and keeps `/cd`
+//! inside the launch directory unless escaping was allowed with
+//! `--allow-cwd-escape`.
+//!
+//! # Examples
+//!
+//! ```
+//! use tempfile::tempdir;
+//! use xzatoma::session_cwd::SessionCwd;
+//!
+//! let launch = tempdir()?;
+//! std::fs::create_dir_all(launch.path().join("services/billing"))?;
+//!
+//! let mut cwd = SessionCwd::new(launch.path(), false)?;
+//! cwd.change("services/billing")?;
+//! assert_eq!(cwd.display_label(), "services/billing");
+//!
+//! // Leaving the launch directory needs --allow-cwd-escape
+//! assert!(cwd.change("../../..").is_err());
+//!
+//! cwd.change("..")?;
+//! assert_eq!(cwd.display_label(), "services");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, XzatomaError};
+
+/// Current working directory of a chat or run session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionCwd {
+    root: PathBuf,
+    current: PathBuf,
+    allow_escape: bool,
+}
+
+impl SessionCwd {
+    /// Creates a session rooted at the launch directory
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - Directory the session was launched from
+    /// * `allow_escape` - Allow `/cd` to leave `root`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `root` is not an existing directory.
+    pub fn new(root: &Path, allow_escape: bool) -> Result<Self> {
+        let root = canonical_dir(root, root)?;
+        Ok(Self {
+            current: root.clone(),
+            root,
+            allow_escape,
+        })
+    }
+
+    /// Creates a session rooted at the process working directory
+    ///
+    /// When `start` is given, the session starts there instead, subject to
+    /// the same rules as [`SessionCwd::change`].
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Optional `--cwd` value, relative to the launch directory
+    /// * `allow_escape` - Allow the session to leave the launch directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the start directory does not exist or lies
+    /// outside the launch directory without `allow_escape`.
+    pub fn from_launch_dir(start: Option<&Path>, allow_escape: bool) -> Result<Self> {
+        let mut cwd = Self::new(&std::env::current_dir()?, allow_escape)?;
+        if let Some(start) = start {
+            cwd.change(start)?;
+        }
+        Ok(cwd)
+    }
+
+    /// Returns the launch directory `/cd` is confined to
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the current working directory
+    pub fn current(&self) -> &Path {
+        &self.current
+    }
+
+    /// Whether the session may leave the launch directory
+    pub fn allows_escape(&self) -> bool {
+        self.allow_escape
+    }
+
+    /// Moves the session to `path`
+    ///
+    /// Relative paths are resolved against the current directory. The
+    /// target must be an existing directory and, unless escaping is
+    /// allowed, the launch directory or one of its subdirectories.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Target directory, absolute or relative
+    ///
+    /// # Returns
+    ///
+    /// The previous working directory
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Command` if the target does not exist, is not
+    /// a directory, or escapes the launch directory.
+    pub fn change(&mut self, path: impl AsRef<Path>) -> Result<PathBuf> {
+        let target = canonical_dir(&self.current.join(path.as_ref()), path.as_ref())?;
+        if !self.allow_escape && !target.starts_with(&self.root) {
+            return Err(XzatomaError::Command(format!(
+                "{} is outside the launch directory {}; start with --allow-cwd-escape to leave it",
+                target.display(),
+                self.root.display()
+            )));
+        }
+        Ok(std::mem::replace(&mut self.current, target))
+    }
+
+    /// Returns the current directory relative to the launch directory
+    ///
+    /// The launch directory itself is `.`. Outside the launch directory the
+    /// absolute path is returned.
+    pub fn display_label(&self) -> String {
+        match self.current.strip_prefix(&self.root) {
+            Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+            Ok(relative) => relative.display().to_string(),
+            Err(_) => self.current.display().to_string(),
+        }
+    }
+
+    /// Returns the current directory for the chat prompt indicator
+    ///
+    /// Inside the launch directory the path starts at the launch directory's
+    /// name, so `services/billing` in a checkout named `monorepo` is shown
+    /// as `monorepo/services/billing`.
+    pub fn prompt_label(&self) -> String {
+        let root_name = self
+            .root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string());
+        match (self.current.strip_prefix(&self.root), root_name) {
+            (Ok(relative), Some(root_name)) if relative.as_os_str().is_empty() => root_name,
+            (Ok(relative), Some(root_name)) => {
+                Path::new(&root_name).join(relative).display().to_string()
+            }
+            _ => self.current.display().to_string(),
+        }
+    }
+}
+
+fn canonical_dir(path: &Path, requested: &Path) -> Result<PathBuf> {
+    let canonical = path.canonicalize().map_err(|e| {
+        XzatomaError::Command(format!(
+            "Cannot change directory to {}: {}",
+            requested.display(),
+            e
+        ))
+    })?;
+    if !canonical.is_dir() {
+        return Err(XzatomaError::Command(format!(
+            "Cannot change directory to {}: not a directory",
+            requested.display()
+        )));
+    }
+    Ok(canonical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn launch_dir() -> tempfile::TempDir {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("services/billing")).unwrap();
+        std::fs::write(dir.path().join("README.md"), "readme").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_change_resolves_relative_to_current_directory() {
+        let dir = launch_dir();
+        let mut cwd = SessionCwd::new(dir.path(), false).unwrap();
+
+        cwd.change("services").unwrap();
+        let previous = cwd.change("billing").unwrap();
+
+        assert_eq!(previous, cwd.root().join("services"));
+        assert_eq!(cwd.current(), cwd.root().join("services/billing"));
+        assert_eq!(cwd.display_label(), "services/billing");
+
+        cwd.change("../..").unwrap();
+        assert_eq!(cwd.display_label(), ".");
+    }
+
+    #[test]
+    fn test_prompt_label_starts_at_launch_directory_name() {
+        let dir = launch_dir();
+        let mut cwd = SessionCwd::new(dir.path(), false).unwrap();
+        let root_name = cwd
+            .root()
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+
+        assert_eq!(cwd.prompt_label(), root_name);
+        cwd.change("services/billing").unwrap();
+        assert_eq!(
+            cwd.prompt_label(),
+            format!("{}/services/billing", root_name)
+        );
+    }
+
+    #[test]
+    fn test_change_rejects_escape_without_flag() {
+        let dir = launch_dir();
+        let mut cwd = SessionCwd::new(&dir.path().join("services"), false).unwrap();
+
+        let err = cwd.change("..").unwrap_err().to_string();
+        assert!(err.contains("--allow-cwd-escape"));
+        assert_eq!(cwd.current(), cwd.root());
+    }
+
+    #[test]
+    fn test_change_allows_escape_with_flag() {
+        let dir = launch_dir();
+        let mut cwd = SessionCwd::new(&dir.path().join("services"), true).unwrap();
+
+        cwd.change("..").unwrap();
+
+        assert_eq!(cwd.current(), dir.path().canonicalize().unwrap());
+        assert_eq!(cwd.display_label(), cwd.current().display().to_string());
+    }
+
+    #[test]
+    fn test_change_rejects_missing_directories_and_files() {
+        let dir = launch_dir();
+        let mut cwd = SessionCwd::new(dir.path(), false).unwrap();
+
+        assert!(cwd.change("missing").is_err());
+        let err = cwd.change("README.md").unwrap_err().to_string();
+        assert!(err.contains("not a directory"));
+        assert_eq!(cwd.display_label(), ".");
+    }
+}
@@ -0,0 +1,574 @@
+//! Safe shutdown for run and watch mode
+//!
+//! Interrupting `xzatoma run` used to leave background terminal commands and
+//! MCP server processes running. Anything that spawns a child process
+//! registers it with a [`ChildProcessRegistry`]; the registration is removed
+//! when the child exits or its owner drops it.
+//!
+//! A [`ShutdownCoordinator`] listens for Ctrl-C and SIGTERM. The first signal
+//! cancels the agent turn through the coordinator's cancellation token. The
+//! command then calls [`ShutdownCoordinator::shutdown`], which sends SIGTERM
+//! to every registered child, waits for a grace period, and sends SIGKILL to
+//! the rest. A second signal exits immediately with
+//! [`exit_codes::CANCELLED`].
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use xzatoma::shutdown::{ChildProcessRegistry, ShutdownCoordinator, ShutdownSignal};
+//!
+//! # tokio_test::block_on(async {
+//! let registry = ChildProcessRegistry::new();
+//! let coordinator =
+//!     ShutdownCoordinator::new(registry).with_grace_period(Duration::from_millis(100));
+//!
+//! assert!(coordinator.trigger(ShutdownSignal::Interrupt));
+//! assert!(coordinator.token().is_cancelled());
+//!
+//! let summary = coordinator.shutdown().await;
+//! assert!(summary.report.terminated.is_empty());
+//! # });
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::error::exit_codes;
+
+/// Time children get to exit after SIGTERM before they are killed
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// How often children are checked while waiting for them to exit
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A child process registered for cleanup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChildProcess {
+    /// Operating system process id
+    pub pid: u32,
+    /// What the process is, for example `terminal: cargo test`
+    pub label: String,
+}
+
+#[derive(Debug, Default)]
+struct RegistryState {
+    next_id: u64,
+    children: HashMap<u64, ChildProcess>,
+}
+
+/// Registry of child processes that must not outlive an interrupted command
+///
+/// Cloning the registry shares the same set of children. Components use
+/// [`ChildProcessRegistry::global`] unless a caller provides another one.
+#[derive(Debug, Clone, Default)]
+pub struct ChildProcessRegistry {
+    state: Arc<Mutex<RegistryState>>,
+}
+
+impl ChildProcessRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the process-wide registry
+    pub fn global() -> Self {
+        static GLOBAL: OnceLock<ChildProcessRegistry> = OnceLock::new();
+        GLOBAL.get_or_init(ChildProcessRegistry::new).clone()
+    }
+
+    /// Registers a running child process
+    ///
+    /// # Arguments
+    ///
+    /// * `pid` - Process id of the child
+    /// * `label` - Description shown in the shutdown summary
+    ///
+    /// # Returns
+    ///
+    /// Returns a registration that removes the child when dropped. Keep it
+    /// alive until the child has exited.
+    pub fn register(&self, pid: u32, label: impl Into<String>) -> ChildRegistration {
+        let mut state = self.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.children.insert(
+            id,
+            ChildProcess {
+                pid,
+                label: label.into(),
+            },
+        );
+        ChildRegistration {
+            registry: self.clone(),
+            id,
+        }
+    }
+
+    /// Returns the registered children in registration order
+    pub fn children(&self) -> Vec<ChildProcess> {
+        let state = self.lock();
+        let mut children: Vec<(u64, ChildProcess)> = state
+            .children
+            .iter()
+            .map(|(id, child)| (*id, child.clone()))
+            .collect();
+        children.sort_by_key(|(id, _)| *id);
+        children.into_iter().map(|(_, child)| child).collect()
+    }
+
+    /// Returns whether no children are registered
+    pub fn is_empty(&self) -> bool {
+        self.lock().children.is_empty()
+    }
+
+    /// Terminates every registered child
+    ///
+    /// Sends SIGTERM to each child, waits up to `grace_period` for them to
+    /// exit, and sends SIGKILL to the ones still running.
+    ///
+    /// # Arguments
+    ///
+    /// * `grace_period` - Time children get to exit after SIGTERM
+    pub async fn terminate_all(&self, grace_period: Duration) -> TerminationReport {
+        let children = self.children();
+        for child in &children {
+            tracing::debug!(pid = child.pid, label = %child.label, "Terminating child process");
+            terminate(child.pid);
+        }
+
+        let deadline = tokio::time::Instant::now() + grace_period;
+        while children.iter().any(|child| is_running(child.pid))
+            && tokio::time::Instant::now() < deadline
+        {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+
+        let killed: Vec<ChildProcess> = children
+            .iter()
+            .filter(|child| is_running(child.pid))
+            .cloned()
+            .collect();
+        for child in &killed {
+            tracing::warn!(pid = child.pid, label = %child.label, "Killing child process");
+            kill(child.pid);
+        }
+
+        TerminationReport {
+            terminated: children,
+            killed,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RegistryState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Keeps a child registered until dropped
+#[must_use = "the child is unregistered when the registration is dropped"]
+#[derive(Debug)]
+pub struct ChildRegistration {
+    registry: ChildProcessRegistry,
+    id: u64,
+}
+
+impl Drop for ChildRegistration {
+    fn drop(&mut self) {
+        self.registry.lock().children.remove(&self.id);
+    }
+}
+
+/// Children stopped by [`ChildProcessRegistry::terminate_all`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TerminationReport {
+    /// Every child that was sent SIGTERM
+    pub terminated: Vec<ChildProcess>,
+    /// Children still running after the grace period that were killed
+    pub killed: Vec<ChildProcess>,
+}
+
+/// Signal that requested the shutdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownSignal {
+    /// Ctrl-C (SIGINT)
+    Interrupt,
+    /// SIGTERM
+    Terminate,
+}
+
+impl fmt::Display for ShutdownSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShutdownSignal::Interrupt => write!(f, "Ctrl-C"),
+            ShutdownSignal::Terminate => write!(f, "SIGTERM"),
+        }
+    }
+}
+
+/// Coordinates cancellation and child cleanup when a command is interrupted
+#[derive(Debug, Clone)]
+pub struct ShutdownCoordinator {
+    token: CancellationToken,
+    children: ChildProcessRegistry,
+    signal: Arc<Mutex<Option<ShutdownSignal>>>,
+    grace_period: Duration,
+}
+
+impl ShutdownCoordinator {
+    /// Creates a coordinator for `children` without listening for signals
+    pub fn new(children: ChildProcessRegistry) -> Self {
+        Self {
+            token: CancellationToken::new(),
+            children,
+            signal: Arc::new(Mutex::new(None)),
+            grace_period: DEFAULT_GRACE_PERIOD,
+        }
+    }
+
+    /// Creates a coordinator for the global registry and starts listening
+    /// for Ctrl-C and SIGTERM
+    ///
+    /// Must be called inside a Tokio runtime.
+    pub fn install() -> Self {
+        let coordinator = Self::new(ChildProcessRegistry::global());
+        coordinator.listen();
+        coordinator
+    }
+
+    /// Sets the time children get to exit after SIGTERM
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    /// Returns the token cancelled by the first signal
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Returns the signal that requested the shutdown, if any
+    pub fn signal(&self) -> Option<ShutdownSignal> {
+        *self.signal.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns whether a shutdown has been requested
+    pub fn is_requested(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Waits until a shutdown is requested
+    pub async fn requested(&self) {
+        self.token.cancelled().await
+    }
+
+    /// Requests a shutdown
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` for the first request and `false` when a shutdown was
+    /// already requested.
+    pub fn trigger(&self, signal: ShutdownSignal) -> bool {
+        let mut current = self.signal.lock().unwrap_or_else(|e| e.into_inner());
+        if current.is_some() {
+            return false;
+        }
+        *current = Some(signal);
+        drop(current);
+        self.token.cancel();
+        true
+    }
+
+    /// Stops every registered child process
+    ///
+    /// Call this after the interrupted work has returned, before flushing
+    /// output and exiting.
+    pub async fn shutdown(&self) -> ShutdownSummary {
+        let report = self.children.terminate_all(self.grace_period).await;
+        ShutdownSummary {
+            signal: self.signal(),
+            report,
+            grace_period: self.grace_period,
+        }
+    }
+
+    /// Spawns the task that turns signals into shutdown requests
+    fn listen(&self) {
+        let coordinator = self.clone();
+        tokio::spawn(async move {
+            #[cfg(unix)]
+            let mut terminate =
+                tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                    .map_err(|e| tracing::warn!("Failed to listen for SIGTERM: {}", e))
+                    .ok();
+
+            loop {
+                #[cfg(unix)]
+                let signal = tokio::select! {
+                    result = tokio::signal::ctrl_c() => match result {
+                        Ok(()) => ShutdownSignal::Interrupt,
+                        Err(e) => {
+                            tracing::error!(error = %e, "Failed to set up signal handler");
+                            return;
+                        }
+                    },
+                    Some(()) = recv_terminate(&mut terminate) => ShutdownSignal::Terminate,
+                };
+                #[cfg(not(unix))]
+                let signal = match tokio::signal::ctrl_c().await {
+                    Ok(()) => ShutdownSignal::Interrupt,
+                    Err(e) => {
+                        tracing::error!(error = %e, "Failed to set up signal handler");
+                        return;
+                    }
+                };
+
+                if !coordinator.trigger(signal) {
+                    eprintln!("\n{} received again; exiting immediately.", signal);
+                    std::process::exit(exit_codes::CANCELLED);
+                }
+                tracing::info!(signal = %signal, "Shutdown requested");
+                eprintln!(
+                    "\n{} received; stopping. Press Ctrl-C again to exit immediately.",
+                    signal
+                );
+            }
+        });
+    }
+}
+
+#[cfg(unix)]
+async fn recv_terminate(signal: &mut Option<tokio::signal::unix::Signal>) -> Option<()> {
+    match signal {
+        Some(signal) => signal.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// What a shutdown interrupted and stopped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownSummary {
+    /// Signal that requested the shutdown
+    pub signal: Option<ShutdownSignal>,
+    /// Child processes that were stopped
+    pub report: TerminationReport,
+    /// Grace period given before killing
+    pub grace_period: Duration,
+}
+
+impl fmt::Display for ShutdownSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.signal {
+            Some(signal) => writeln!(f, "Interrupted by {}.", signal)?,
+            None => writeln!(f, "Shut down.")?,
+        }
+
+        let terminated = &self.report.terminated;
+        if terminated.is_empty() {
+            return write!(f, "No child processes were running.");
+        }
+        write!(
+            f,
+            "Stopped {} child process{}:",
+            terminated.len(),
+            if terminated.len() == 1 { "" } else { "es" }
+        )?;
+        for child in terminated {
+            write!(f, "\n  {} (pid {})", child.label, child.pid)?;
+            if self.report.killed.contains(child) {
+                write!(
+                    f,
+                    ", killed after {}s",
+                    self.grace_period.as_secs_f64().ceil() as u64
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) {
+    // SAFETY: sending a signal has no memory-safety requirements.
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGTERM);
+    }
+}
+
+#[cfg(unix)]
+fn kill(pid: u32) {
+    // SAFETY: sending a signal has no memory-safety requirements.
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGKILL);
+    }
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks whether the process exists.
+    let exists = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+    exists && !is_zombie(pid)
+}
+
+/// An exited child that its owner has not reaped yet still exists
+#[cfg(target_os = "linux")]
+fn is_zombie(pid: u32) -> bool {
+    std::fs::read_to_string(format!("/proc/{}/stat", pid))
+        .ok()
+        .and_then(|stat| {
+            stat.rsplit_once(')')
+                .map(|(_, rest)| rest.trim_start().starts_with('Z'))
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn is_zombie(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+fn terminate(pid: u32) {
+    kill(pid);
+}
+
+#[cfg(not(unix))]
+fn kill(pid: u32) {
+    let _ = std::process::Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/F"])
+        .status();
+}
+
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registration_is_removed_on_drop() {
+        let registry = ChildProcessRegistry::new();
+        let first = registry.register(100, "terminal: first");
+        let second = registry.register(200, "mcp: second");
+        assert_eq!(
+            registry
+                .children()
+                .iter()
+                .map(|c| c.pid)
+                .collect::<Vec<_>>(),
+            vec![100, 200]
+        );
+
+        drop(first);
+        assert_eq!(registry.children().len(), 1);
+        drop(second);
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_second_trigger_is_reported() {
+        let coordinator = ShutdownCoordinator::new(ChildProcessRegistry::new());
+        assert!(!coordinator.is_requested());
+        assert!(coordinator.trigger(ShutdownSignal::Terminate));
+        assert!(!coordinator.trigger(ShutdownSignal::Interrupt));
+        assert_eq!(coordinator.signal(), Some(ShutdownSignal::Terminate));
+        assert!(coordinator.token().is_cancelled());
+    }
+
+    #[test]
+    fn test_summary_lists_stopped_and_killed_children() {
+        let quiet = ChildProcess {
+            pid: 10,
+            label: "terminal: sleep 30".to_string(),
+        };
+        let stubborn = ChildProcess {
+            pid: 11,
+            label: "mcp: server".to_string(),
+        };
+        let summary = ShutdownSummary {
+            signal: Some(ShutdownSignal::Interrupt),
+            report: TerminationReport {
+                terminated: vec![quiet, stubborn.clone()],
+                killed: vec![stubborn],
+            },
+            grace_period: Duration::from_secs(5),
+        };
+
+        assert_eq!(
+            summary.to_string(),
+            "Interrupted by Ctrl-C.\nStopped 2 child processes:\n  terminal: sleep 30 (pid 10)\n  mcp: server (pid 11), killed after 5s"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_terminate_all_kills_children_that_ignore_sigterm() {
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "trap '' TERM; exec sleep 30"])
+            .spawn()
+            .unwrap();
+        // Give the shell time to install the trap before signalling it
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let registry = ChildProcessRegistry::new();
+        let _registration = registry.register(child.id(), "stubborn");
+        let report = registry.terminate_all(Duration::from_millis(200)).await;
+
+        assert_eq!(report.terminated.len(), 1);
+        assert_eq!(report.killed.len(), 1);
+        child.wait().unwrap();
+        assert!(!is_running(child.id()));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shutdown_stops_terminal_tool_child() {
+        use crate::config::{ExecutionMode, TerminalConfig};
+        use crate::tools::terminal::{CommandValidator, TerminalTool};
+        use crate::tools::ToolExecutor;
+
+        let dir = tempfile::tempdir().unwrap();
+        let registry = ChildProcessRegistry::new();
+        let validator =
+            CommandValidator::new(ExecutionMode::FullAutonomous, dir.path().to_path_buf());
+        let tool = TerminalTool::new(validator, TerminalConfig::default())
+            .with_child_registry(registry.clone());
+        let run = tokio::spawn(async move {
+            tool.execute(serde_json::json!({ "command": "sleep 30" }))
+                .await
+        });
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while registry.is_empty() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        let pid = registry.children()[0].pid;
+
+        // Simulate the first Ctrl-C
+        let coordinator =
+            ShutdownCoordinator::new(registry.clone()).with_grace_period(Duration::from_secs(2));
+        assert!(coordinator.trigger(ShutdownSignal::Interrupt));
+        let summary = coordinator.shutdown().await;
+
+        assert_eq!(summary.report.terminated.len(), 1);
+        assert_eq!(summary.report.terminated[0].label, "terminal: sleep 30");
+        assert!(summary.report.killed.is_empty());
+
+        let result = tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .expect("terminal tool should return once its child is gone")
+            .unwrap()
+            .unwrap();
+        assert!(!result.success);
+        assert!(!is_running(pid));
+        assert!(registry.is_empty());
+    }
+}
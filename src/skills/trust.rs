@@ -32,6 +32,7 @@
 //! ```
 
 use crate::error::{Result, XzatomaError};
+use crate::paths::Paths;
 use crate::skills::{SkillCatalog, SkillRecord};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
@@ -470,9 +471,13 @@ impl SkillTrustStore {
 
 /// Returns the default trust store path for skills.
 ///
-/// The default path is:
+/// The default path is [`Paths::skills_trust_file`]:
 ///
-/// - `~/.xzatoma/skills_trust.yaml`
+/// - `~/.xzatoma/skills_trust.yaml`, or
+/// - `skills_trust.yaml` in `XZATOMA_DATA_DIR` when it is set
+///
+/// A configured `paths.data_dir` is applied when the configuration is
+/// loaded, by filling in `skills.trust_store_path`.
 ///
 /// # Returns
 ///
@@ -480,7 +485,7 @@ impl SkillTrustStore {
 ///
 /// # Errors
 ///
-/// Returns an error if the home directory cannot be determined.
+/// Returns an error if the data directory cannot be determined.
 ///
 /// # Examples
 ///
@@ -492,11 +497,7 @@ impl SkillTrustStore {
 /// # Ok::<(), anyhow::Error>(())
 /// ```
 pub fn default_trust_store_path() -> Result<PathBuf> {
-    let home = std::env::var("HOME")
-        .map_err(|_| XzatomaError::Config("HOME environment variable is not set".to_string()))?;
-    Ok(PathBuf::from(home)
-        .join(".xzatoma")
-        .join("skills_trust.yaml"))
+    Ok(Paths::from_env()?.skills_trust_file())
 }
 
 /// Resolves the configured trust store path.
@@ -514,7 +515,7 @@ pub fn default_trust_store_path() -> Result<PathBuf> {
 ///
 /// # Errors
 ///
-/// Returns an error if the default home-based path cannot be determined.
+/// Returns an error if the default data directory cannot be determined.
 ///
 /// # Examples
 ///
@@ -0,0 +1,871 @@
+//! Import of conversations exported by other tools
+//!
+//! `xzatoma history import --format <format>` reads sessions exported by
+//! other assistants and stores them next to xzatoma's own history, so
+//! `history list` and `history search` find them too:
+//!
+//! - `claude-json`: `conversations.json` from a Claude data export;
+//! - `openai-json`: `conversations.json` from a ChatGPT data export, or
+//!   objects with a chat completions `messages` array;
+//! - `aider-md`: an `.aider.chat.history.md` file.
+//!
+//! Each format is parsed into [`ImportedSession`]s. Tool calls and tool
+//! results cannot be replayed against xzatoma's tools, so they are flattened
+//! into annotated assistant text such as `[tool call: search]`. A session or
+//! message that cannot be read is skipped and counted in the per-file
+//! [`FileImportSummary`]; it does not abort the rest of the import.
+//!
+//! Imported conversations get an ID derived from the tool and the session's
+//! own ID, so importing the same export twice does not duplicate them.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::error::{Result, XzatomaError};
+use crate::providers::Message;
+use crate::storage::SqliteStorage;
+
+/// Maximum number of characters in a synthesized title
+const MAX_TITLE_CHARS: usize = 80;
+
+/// Heading that starts a session in an aider chat history file
+const AIDER_SESSION_HEADER: &str = "# aider chat started at ";
+
+/// Format of the files passed to `history import`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// A directory written by `history backup`
+    Native,
+    /// A Claude data export
+    ClaudeJson,
+    /// A ChatGPT data export or chat completions transcript
+    OpenaiJson,
+    /// An aider chat history file
+    AiderMd,
+}
+
+impl ImportFormat {
+    /// Parses a `--format` value
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Config` for an unknown format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::storage::import::ImportFormat;
+    ///
+    /// assert_eq!(ImportFormat::parse("claude-json").unwrap(), ImportFormat::ClaudeJson);
+    /// assert!(ImportFormat::parse("csv").is_err());
+    /// ```
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "native" => Ok(ImportFormat::Native),
+            "claude-json" => Ok(ImportFormat::ClaudeJson),
+            "openai-json" => Ok(ImportFormat::OpenaiJson),
+            "aider-md" => Ok(ImportFormat::AiderMd),
+            other => Err(XzatomaError::Config(format!(
+                "Unknown import format '{}'. Expected native, claude-json, openai-json, or aider-md",
+                other
+            ))),
+        }
+    }
+
+    /// Name of the tool that wrote the export, used in the `imported:<tool>` tag
+    pub fn tool(self) -> &'static str {
+        match self {
+            ImportFormat::Native => "xzatoma",
+            ImportFormat::ClaudeJson => "claude",
+            ImportFormat::OpenaiJson => "openai",
+            ImportFormat::AiderMd => "aider",
+        }
+    }
+
+    /// Tag attached to every conversation imported in this format
+    pub fn tag(self) -> String {
+        format!("imported:{}", self.tool())
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ImportFormat::AiderMd => "md",
+            _ => "json",
+        }
+    }
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ImportFormat::Native => "native",
+            ImportFormat::ClaudeJson => "claude-json",
+            ImportFormat::OpenaiJson => "openai-json",
+            ImportFormat::AiderMd => "aider-md",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One conversation read from another tool's export
+#[derive(Debug, Clone)]
+pub struct ImportedSession {
+    /// The session's ID in the export, or a hash of its contents
+    pub source_id: String,
+    /// Title from the export, or synthesized from the first user message
+    pub title: String,
+    /// When the session started, if the export records it
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the session was last updated, if the export records it
+    pub updated_at: Option<DateTime<Utc>>,
+    /// The conversation, with tool activity flattened to text
+    pub messages: Vec<Message>,
+}
+
+/// Sessions read from one export file
+#[derive(Debug, Default)]
+pub struct ParsedExport {
+    /// Sessions that could be read
+    pub sessions: Vec<ImportedSession>,
+    /// One reason per session that was skipped
+    pub skipped: Vec<String>,
+    /// Messages skipped inside sessions that were read
+    pub skipped_messages: usize,
+}
+
+/// Outcome of importing one export file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileImportSummary {
+    /// The export file
+    pub path: PathBuf,
+    /// Conversations imported, or that would be imported in a dry run
+    pub imported: usize,
+    /// Messages in those conversations
+    pub messages: usize,
+    /// Conversations already in the history from an earlier import
+    pub existing: usize,
+    /// One reason per session that was skipped
+    pub skipped: Vec<String>,
+    /// Messages skipped inside imported sessions
+    pub skipped_messages: usize,
+    /// Why the whole file could not be read
+    pub error: Option<String>,
+}
+
+/// Parses the contents of one export file
+///
+/// # Errors
+///
+/// Returns an error when the file as a whole cannot be read, for example
+/// because it is not valid JSON. Unreadable sessions inside a readable file
+/// are reported in [`ParsedExport::skipped`] instead.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::storage::import::{parse_export, ImportFormat};
+///
+/// let export = "# aider chat started at 2024-05-01 10:00:00\n\n#### fix the build\n\nDone.\n";
+/// let parsed = parse_export(ImportFormat::AiderMd, export).unwrap();
+/// assert_eq!(parsed.sessions[0].title, "fix the build");
+/// assert_eq!(parsed.sessions[0].messages.len(), 2);
+/// ```
+pub fn parse_export(format: ImportFormat, text: &str) -> Result<ParsedExport> {
+    match format {
+        ImportFormat::Native => Err(XzatomaError::Config(
+            "Native backups are imported from their directory, not parsed per file".to_string(),
+        )),
+        ImportFormat::ClaudeJson => parse_json_export(text, claude_session),
+        ImportFormat::OpenaiJson => parse_json_export(text, openai_session),
+        ImportFormat::AiderMd => Ok(parse_aider(text)),
+    }
+}
+
+/// Imports every export file at `path` into `storage`
+///
+/// `path` is either one file or a directory, whose files with the format's
+/// extension (`.json` or `.md`) are imported in name order. Each imported
+/// conversation is tagged `imported:<tool>`. With `dry_run` nothing is
+/// written, and the summaries show what would be imported.
+///
+/// # Errors
+///
+/// Returns an error if `path` cannot be listed or a conversation cannot be
+/// stored. Files that cannot be read or parsed are reported in their
+/// summary instead.
+pub fn import_exports(
+    storage: &SqliteStorage,
+    format: ImportFormat,
+    path: &Path,
+    dry_run: bool,
+) -> Result<Vec<FileImportSummary>> {
+    let tag = format.tag();
+    let mut summaries = Vec::new();
+    for file in export_files(format, path)? {
+        let mut summary = FileImportSummary {
+            path: file.clone(),
+            ..Default::default()
+        };
+        let parsed = std::fs::read_to_string(&file)
+            .map_err(XzatomaError::from)
+            .and_then(|text| parse_export(format, &text));
+        let parsed = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                summary.error = Some(e.to_string());
+                summaries.push(summary);
+                continue;
+            }
+        };
+
+        summary.skipped = parsed.skipped;
+        summary.skipped_messages = parsed.skipped_messages;
+        for session in &parsed.sessions {
+            let id = conversation_id(format, &session.source_id);
+            let stored = if dry_run {
+                !storage.conversation_exists(&id)?
+            } else {
+                storage.insert_imported_conversation(&id, session, &[tag.clone()])?
+            };
+            if stored {
+                summary.imported += 1;
+                summary.messages += session.messages.len();
+            } else {
+                summary.existing += 1;
+            }
+        }
+        summaries.push(summary);
+    }
+    Ok(summaries)
+}
+
+/// Derives a stable conversation ID from the tool and the session's own ID
+///
+/// The ID has the shape of a UUID so prefix lookups work as for native
+/// conversations.
+pub fn conversation_id(format: ImportFormat, source_id: &str) -> String {
+    let digest = Sha256::digest(format!("{}:{}", format.tool(), source_id).as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_custom_bytes(bytes)
+        .into_uuid()
+        .to_string()
+}
+
+fn export_files(format: ImportFormat, path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = std::fs::read_dir(path)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| {
+            file.is_file()
+                && file
+                    .extension()
+                    .is_some_and(|extension| extension == format.extension())
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+/// Reads a JSON export holding one session object or an array of them
+fn parse_json_export(
+    text: &str,
+    session: fn(&Value, &mut usize) -> std::result::Result<ImportedSession, String>,
+) -> Result<ParsedExport> {
+    let value: Value = serde_json::from_str(text)
+        .map_err(|e| XzatomaError::Storage(format!("Failed to parse export: {}", e)))?;
+    let entries = match value {
+        Value::Array(entries) => entries,
+        object @ Value::Object(_) => vec![object],
+        _ => {
+            return Err(XzatomaError::Storage(
+                "Export must be a JSON object or array of conversations".to_string(),
+            ))
+        }
+    };
+
+    let mut parsed = ParsedExport::default();
+    for (index, entry) in entries.iter().enumerate() {
+        match session(entry, &mut parsed.skipped_messages) {
+            Ok(session) => parsed.sessions.push(session),
+            Err(reason) => parsed
+                .skipped
+                .push(format!("conversation {}: {}", index + 1, reason)),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Maps one conversation of a Claude export
+///
+/// Messages carry a `sender` of `human` or `assistant` and either `content`
+/// blocks or plain `text`.
+fn claude_session(
+    conversation: &Value,
+    skipped_messages: &mut usize,
+) -> std::result::Result<ImportedSession, String> {
+    let entries = conversation
+        .get("chat_messages")
+        .and_then(Value::as_array)
+        .ok_or("missing chat_messages")?;
+
+    let mut messages = Vec::new();
+    for entry in entries {
+        let text = match entry.get("content").and_then(Value::as_array) {
+            Some(blocks) if !blocks.is_empty() => claude_blocks_text(blocks),
+            _ => str_field(entry, "text").unwrap_or_default().to_string(),
+        };
+        let message = match str_field(entry, "sender") {
+            _ if text.trim().is_empty() => None,
+            Some("human") | Some("user") => Some(Message::user(text)),
+            Some("assistant") => Some(Message::assistant(text)),
+            _ => None,
+        };
+        match message {
+            Some(message) => messages.push(message),
+            None => *skipped_messages += 1,
+        }
+    }
+
+    finish_session(
+        source_id(conversation, &["uuid", "id"]),
+        str_field(conversation, "name"),
+        conversation.get("created_at").and_then(parse_time),
+        conversation.get("updated_at").and_then(parse_time),
+        messages,
+    )
+}
+
+/// Flattens Claude content blocks into text
+///
+/// `tool_use` and `tool_result` blocks become annotated text. Thinking and
+/// unknown blocks are dropped.
+fn claude_blocks_text(blocks: &[Value]) -> String {
+    let parts = blocks
+        .iter()
+        .filter_map(|block| match str_field(block, "type") {
+            Some("text") => str_field(block, "text").map(str::to_string),
+            Some("tool_use") => Some(tool_call_text(
+                str_field(block, "name").unwrap_or("tool"),
+                &block.get("input").map(Value::to_string).unwrap_or_default(),
+            )),
+            Some("tool_result") => Some(tool_result_text(
+                str_field(block, "name").unwrap_or("tool"),
+                &content_text(block.get("content").unwrap_or(&Value::Null)),
+            )),
+            _ => None,
+        });
+    join_parts(parts)
+}
+
+/// Maps one conversation of a ChatGPT export or a chat completions transcript
+fn openai_session(
+    conversation: &Value,
+    skipped_messages: &mut usize,
+) -> std::result::Result<ImportedSession, String> {
+    let mut messages = Vec::new();
+    if let Some(entries) = conversation.get("messages").and_then(Value::as_array) {
+        for entry in entries {
+            match chat_completions_message(entry) {
+                Some(message) => messages.push(message),
+                None => *skipped_messages += 1,
+            }
+        }
+    } else {
+        let mapping = conversation
+            .get("mapping")
+            .and_then(Value::as_object)
+            .ok_or("missing mapping or messages")?;
+        for node in chatgpt_thread(conversation, mapping) {
+            match node.get("message") {
+                Some(Value::Null) | None => {}
+                Some(entry) => match chatgpt_message(entry) {
+                    Some(message) => messages.push(message),
+                    None => *skipped_messages += 1,
+                },
+            }
+        }
+    }
+
+    finish_session(
+        source_id(conversation, &["conversation_id", "id"]),
+        str_field(conversation, "title"),
+        conversation.get("create_time").and_then(parse_time),
+        conversation.get("update_time").and_then(parse_time),
+        messages,
+    )
+}
+
+/// Returns the nodes of the branch that ends at `current_node`, oldest first
+///
+/// Without `current_node` every node is returned in creation order.
+fn chatgpt_thread<'a>(
+    conversation: &'a Value,
+    mapping: &'a serde_json::Map<String, Value>,
+) -> Vec<&'a Value> {
+    let mut thread = Vec::new();
+    let mut current = str_field(conversation, "current_node");
+    if current.is_none() {
+        thread = mapping.values().collect();
+        thread.sort_by(|a, b| {
+            let time = |node: &Value| {
+                node.pointer("/message/create_time")
+                    .and_then(Value::as_f64)
+                    .unwrap_or(0.0)
+            };
+            time(a).total_cmp(&time(b))
+        });
+        return thread;
+    }
+    while let Some(node) = current.and_then(|id| mapping.get(id)) {
+        if thread.len() > mapping.len() {
+            break;
+        }
+        thread.push(node);
+        current = str_field(node, "parent");
+    }
+    thread.reverse();
+    thread
+}
+
+/// Maps one message of a ChatGPT export
+///
+/// Assistant messages addressed to a tool (`recipient` other than `all`)
+/// and messages from the `tool` role are flattened into annotated text.
+/// Returns `None` for empty or unknown messages.
+fn chatgpt_message(entry: &Value) -> Option<Message> {
+    let role = entry.pointer("/author/role").and_then(Value::as_str)?;
+    let content = entry.get("content")?;
+    let text = match content.get("parts").and_then(Value::as_array) {
+        Some(parts) => join_parts(parts.iter().map(|part| match part {
+            Value::String(text) => text.clone(),
+            _ => "[attachment omitted]".to_string(),
+        })),
+        None => str_field(content, "text").unwrap_or_default().to_string(),
+    };
+    if text.trim().is_empty() {
+        return None;
+    }
+
+    match role {
+        "user" => Some(Message::user(text)),
+        "system" => Some(Message::system(text)),
+        "assistant" => match str_field(entry, "recipient") {
+            Some(recipient) if recipient != "all" => {
+                Some(Message::assistant(tool_call_text(recipient, &text)))
+            }
+            _ => Some(Message::assistant(text)),
+        },
+        "tool" => {
+            let name = entry
+                .pointer("/author/name")
+                .and_then(Value::as_str)
+                .unwrap_or("tool");
+            Some(Message::assistant(tool_result_text(name, &text)))
+        }
+        _ => None,
+    }
+}
+
+/// Maps one chat completions message
+///
+/// `tool_calls` and `tool` role messages are flattened into annotated
+/// assistant text, because the imported conversation has no tool results to
+/// pair them with.
+fn chat_completions_message(entry: &Value) -> Option<Message> {
+    let text = content_text(entry.get("content").unwrap_or(&Value::Null));
+    match str_field(entry, "role")? {
+        "user" if !text.trim().is_empty() => Some(Message::user(text)),
+        "system" | "developer" if !text.trim().is_empty() => Some(Message::system(text)),
+        "assistant" => {
+            let calls = entry
+                .get("tool_calls")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .map(|call| {
+                    tool_call_text(
+                        call.pointer("/function/name")
+                            .and_then(Value::as_str)
+                            .unwrap_or("tool"),
+                        call.pointer("/function/arguments")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default(),
+                    )
+                });
+            let text = join_parts(std::iter::once(text).chain(calls));
+            (!text.is_empty()).then(|| Message::assistant(text))
+        }
+        "tool" | "function" if !text.trim().is_empty() => {
+            let name = str_field(entry, "name")
+                .or_else(|| str_field(entry, "tool_call_id"))
+                .unwrap_or("tool");
+            Some(Message::assistant(tool_result_text(name, &text)))
+        }
+        _ => None,
+    }
+}
+
+/// Splits an aider chat history into sessions
+///
+/// Lines starting with `####` are the user's, lines starting with `>` are
+/// aider's own output (edits applied, commits, command output), and the
+/// rest is the model's reply.
+fn parse_aider(text: &str) -> ParsedExport {
+    let mut parsed = ParsedExport::default();
+    let mut chunks: Vec<(Option<&str>, Vec<&str>)> = Vec::new();
+    for line in text.lines() {
+        if let Some(started) = line.strip_prefix(AIDER_SESSION_HEADER) {
+            chunks.push((Some(started.trim()), Vec::new()));
+        } else {
+            if chunks.is_empty() {
+                chunks.push((None, Vec::new()));
+            }
+            if let Some((_, lines)) = chunks.last_mut() {
+                lines.push(line);
+            }
+        }
+    }
+
+    // Text before the first heading counts only when it is not blank
+    chunks.retain(|(started, lines)| {
+        started.is_some() || lines.iter().any(|line| !line.trim().is_empty())
+    });
+
+    for (index, (started, lines)) in chunks.into_iter().enumerate() {
+        let created_at = started
+            .and_then(|started| NaiveDateTime::parse_from_str(started, "%Y-%m-%d %H:%M:%S").ok())
+            .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+            .map(|local| local.with_timezone(&Utc));
+        let source_id = format!(
+            "{}:{}",
+            started.unwrap_or_default(),
+            sha256_prefix(&lines.join("\n"))
+        );
+        let messages = aider_messages(&lines);
+        match finish_session(source_id, None, created_at, None, messages) {
+            Ok(session) => parsed.sessions.push(session),
+            Err(reason) => parsed
+                .skipped
+                .push(format!("session {}: {}", index + 1, reason)),
+        }
+    }
+    parsed
+}
+
+fn aider_messages(lines: &[&str]) -> Vec<Message> {
+    #[derive(PartialEq, Clone, Copy)]
+    enum Speaker {
+        User,
+        Aider,
+        Model,
+    }
+
+    let mut messages = Vec::new();
+    let mut current: Option<(Speaker, Vec<&str>)> = None;
+    let mut flush = |block: Option<(Speaker, Vec<&str>)>| {
+        let Some((speaker, lines)) = block else {
+            return;
+        };
+        let text = lines.join("\n").trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+        messages.push(match speaker {
+            Speaker::User => Message::user(text),
+            Speaker::Aider => Message::assistant(format!("[aider output]\n{}", text)),
+            Speaker::Model => Message::assistant(text),
+        });
+    };
+
+    for line in lines {
+        let (speaker, content) = if let Some(rest) = line.strip_prefix("####") {
+            (Speaker::User, rest.strip_prefix(' ').unwrap_or(rest))
+        } else if let Some(rest) = line.strip_prefix('>') {
+            (Speaker::Aider, rest.strip_prefix(' ').unwrap_or(rest))
+        } else if line.trim().is_empty() {
+            // Blank lines separate blocks but belong to the model's reply
+            if let Some((Speaker::Model, block)) = current.as_mut() {
+                block.push(*line);
+            }
+            continue;
+        } else {
+            (Speaker::Model, *line)
+        };
+
+        match current.as_mut() {
+            Some((current_speaker, block)) if *current_speaker == speaker => block.push(content),
+            _ => {
+                flush(current.take());
+                current = Some((speaker, vec![content]));
+            }
+        }
+    }
+    flush(current);
+    messages
+}
+
+/// Builds the session, synthesizing a title when the export has none
+fn finish_session(
+    source_id: String,
+    title: Option<&str>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+    messages: Vec<Message>,
+) -> std::result::Result<ImportedSession, String> {
+    let first_user = messages
+        .iter()
+        .find(|message| message.role == "user")
+        .and_then(|message| message.content.as_deref())
+        .ok_or("no user messages")?;
+    let title = match title.map(str::trim) {
+        Some(title) if !title.is_empty() => title.to_string(),
+        _ => synthesize_title(first_user),
+    };
+    Ok(ImportedSession {
+        source_id,
+        title,
+        created_at,
+        updated_at,
+        messages,
+    })
+}
+
+/// First line of the first user message, at most [`MAX_TITLE_CHARS`] long
+fn synthesize_title(first_user: &str) -> String {
+    let line = first_user
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    line.chars().take(MAX_TITLE_CHARS).collect()
+}
+
+fn tool_call_text(name: &str, arguments: &str) -> String {
+    if arguments.trim().is_empty() {
+        format!("[tool call: {}]", name)
+    } else {
+        format!("[tool call: {}]\n{}", name, arguments)
+    }
+}
+
+fn tool_result_text(name: &str, output: &str) -> String {
+    format!("[tool result: {}]\n{}", name, output)
+}
+
+/// Reads message content that is a string or an array of text parts
+fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => join_parts(parts.iter().filter_map(|part| match part {
+            Value::String(text) => Some(text.clone()),
+            _ => str_field(part, "text").map(str::to_string),
+        })),
+        _ => String::new(),
+    }
+}
+
+fn join_parts(parts: impl Iterator<Item = String>) -> String {
+    parts
+        .filter(|part| !part.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(Value::as_str)
+}
+
+/// Uses the first string field in `keys`, or a hash of the whole entry
+fn source_id(entry: &Value, keys: &[&str]) -> String {
+    keys.iter()
+        .find_map(|key| str_field(entry, key))
+        .map(str::to_string)
+        .unwrap_or_else(|| sha256_prefix(&entry.to_string()))
+}
+
+fn sha256_prefix(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Reads an RFC 3339 string or seconds since the Unix epoch
+fn parse_time(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(text) => DateTime::parse_from_rfc3339(text)
+            .ok()
+            .map(|time| time.with_timezone(&Utc)),
+        Value::Number(number) => {
+            let seconds = number.as_f64()?;
+            let nanos = (seconds.fract() * 1e9) as u32;
+            Utc.timestamp_opt(seconds.trunc() as i64, nanos).single()
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const CLAUDE_EXPORT: &str = include_str!("../../testdata/history_import/claude.json");
+    const OPENAI_EXPORT: &str = include_str!("../../testdata/history_import/openai.json");
+    const AIDER_HISTORY: &str = include_str!("../../testdata/history_import/aider.chat.history.md");
+
+    fn contents(session: &ImportedSession) -> Vec<(String, String)> {
+        session
+            .messages
+            .iter()
+            .map(|m| (m.role.clone(), m.content.clone().unwrap_or_default()))
+            .collect()
+    }
+
+    #[test]
+    fn test_claude_export_flattens_tool_use() {
+        let parsed = parse_export(ImportFormat::ClaudeJson, CLAUDE_EXPORT).unwrap();
+
+        assert_eq!(parsed.sessions.len(), 1);
+        assert_eq!(
+            parsed.skipped,
+            vec!["conversation 2: missing chat_messages"]
+        );
+        assert_eq!(parsed.skipped_messages, 1);
+        let session = &parsed.sessions[0];
+        assert_eq!(session.title, "Kafka consumer lag");
+        assert_eq!(
+            session.created_at.unwrap().to_rfc3339(),
+            "2024-03-02T09:15:00+00:00"
+        );
+        let messages = contents(session);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].0, "user");
+        assert_eq!(messages[1].0, "assistant");
+        assert!(messages[1]
+            .1
+            .starts_with("Let me check the consumer group."));
+        assert!(messages[1].1.contains(
+            "[tool call: run_command]\n{\"command\":\"kafka-consumer-groups --describe\"}"
+        ));
+        assert!(messages[1]
+            .1
+            .contains("[tool result: run_command]\nLAG 1200"));
+        assert_eq!(messages[2].1, "The lag is 1200 messages.");
+    }
+
+    #[test]
+    fn test_openai_export_follows_current_branch() {
+        let parsed = parse_export(ImportFormat::OpenaiJson, OPENAI_EXPORT).unwrap();
+
+        assert_eq!(parsed.sessions.len(), 2);
+        let chatgpt = &parsed.sessions[0];
+        assert_eq!(chatgpt.title, "Regex help");
+        assert_eq!(chatgpt.source_id, "conv-1");
+        assert_eq!(chatgpt.created_at.unwrap().timestamp(), 1_700_000_000);
+        assert_eq!(
+            contents(chatgpt),
+            vec![
+                ("user".to_string(), "Match an ISO date".to_string()),
+                (
+                    "assistant".to_string(),
+                    "[tool call: python]\nimport re".to_string()
+                ),
+                (
+                    "assistant".to_string(),
+                    "[tool result: python]\nok".to_string()
+                ),
+                (
+                    "assistant".to_string(),
+                    "Use \\d{4}-\\d{2}-\\d{2}".to_string()
+                ),
+            ]
+        );
+
+        let transcript = &parsed.sessions[1];
+        assert_eq!(transcript.title, "List the files");
+        assert!(transcript.created_at.is_none());
+        assert_eq!(
+            contents(transcript)[1].1,
+            "[tool call: list_directory]\n{\"path\":\".\"}"
+        );
+        assert_eq!(
+            contents(transcript)[2].1,
+            "[tool result: call_1]\nsrc\nCargo.toml"
+        );
+    }
+
+    #[test]
+    fn test_aider_history_splits_sessions() {
+        let parsed = parse_export(ImportFormat::AiderMd, AIDER_HISTORY).unwrap();
+
+        assert_eq!(parsed.sessions.len(), 2);
+        assert_eq!(parsed.skipped, vec!["session 3: no user messages"]);
+        let first = &parsed.sessions[0];
+        assert_eq!(first.title, "add a --verbose flag to the cli");
+        let expected = Local
+            .with_ymd_and_hms(2024, 5, 1, 10, 0, 0)
+            .earliest()
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(first.created_at, Some(expected));
+        let messages = contents(first);
+        assert_eq!(messages.len(), 4);
+        assert!(messages[0].1.starts_with("[aider output]\nAider v0.35.0"));
+        assert_eq!(
+            messages[1],
+            (
+                "user".to_string(),
+                "add a --verbose flag to the cli\nkeep it optional".to_string()
+            )
+        );
+        assert!(messages[2]
+            .1
+            .starts_with("I'll add the flag.\n\nsrc/cli.py"));
+        assert!(messages[2].1.ends_with("```"));
+        assert_eq!(
+            messages[3].1,
+            "[aider output]\nApplied edit to src/cli.py\nCommit 1a2b3c4 add --verbose flag"
+        );
+    }
+
+    #[test]
+    fn test_import_exports_tags_and_deduplicates() {
+        let tmp = tempdir().unwrap();
+        let storage = SqliteStorage::new_with_path(tmp.path().join("history.db")).unwrap();
+        let export = tmp.path().join("conversations.json");
+        std::fs::write(&export, CLAUDE_EXPORT).unwrap();
+        std::fs::write(tmp.path().join("broken.json"), "{not json").unwrap();
+
+        let dry_run = import_exports(&storage, ImportFormat::ClaudeJson, tmp.path(), true).unwrap();
+        assert_eq!(dry_run.len(), 2);
+        assert!(dry_run[0].error.is_some());
+        assert_eq!(dry_run[1].imported, 1);
+        assert!(storage.list_sessions().unwrap().is_empty());
+
+        let first = import_exports(&storage, ImportFormat::ClaudeJson, &export, false).unwrap();
+        assert_eq!(first[0].imported, 1);
+        assert_eq!(first[0].messages, 3);
+        let again = import_exports(&storage, ImportFormat::ClaudeJson, &export, false).unwrap();
+        assert_eq!(again[0].imported, 0);
+        assert_eq!(again[0].existing, 1);
+
+        let sessions = storage
+            .list_sessions_with_tags(&["imported:claude".to_string()])
+            .unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].title, "Kafka consumer lag");
+        assert_eq!(
+            sessions[0].created_at.to_rfc3339(),
+            "2024-03-02T09:15:00+00:00"
+        );
+    }
+}
@@ -3,20 +3,33 @@ use crate::acp::{
     AcpAwaitPayload, AcpEvent, AcpEventKind, AcpRun, AcpRunCreateRequest, AcpRunId, AcpRunOutput,
     AcpRunSession, AcpRunState, AcpRunStatus, AcpSessionId,
 };
+use crate::agent::persistence::ConversationRecord;
+use crate::config::RetentionConfig;
 use crate::error::{Result, XzatomaError};
+use crate::paths::Paths;
 use crate::providers::Message;
 use crate::storage::types::{
-    StoredAcpAwaitState, StoredAcpCancellation, StoredAcpRun, StoredAcpRunEvent, StoredAcpSession,
-    StoredAcpStdioSession, StoredSession,
+    BackupEntry, BackupManifest, ConversationConflict, ExpectedRevision, HistoryFilter,
+    HistoryStats, IfExists, ImportReport, ModelUsage, PeriodUsage, PruneReason, PruneReport,
+    PrunedSession, SessionLength, SessionPage, StoredAcpAwaitState, StoredAcpCancellation,
+    StoredAcpRun, StoredAcpRunEvent, StoredAcpSession, StoredAcpStdioSession, StoredRunTurn,
+    StoredSession, ToolUsage, UsageAggregate, UsageGrouping, UsageRecord,
 };
 use anyhow::Context;
-use chrono::{DateTime, Utc};
-use directories::ProjectDirs;
-use rusqlite::{params, Connection, OptionalExtension};
+use chrono::{DateTime, Duration as ChronoDuration, SecondsFormat, Utc};
+use rusqlite::{
+    params, params_from_iter, Connection, ErrorCode, OptionalExtension, TransactionBehavior,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
 use serde_json::Value;
-use std::collections::BTreeMap;
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
+pub mod import;
 pub mod types;
 pub use types::{
     StoredAcpAwaitState as PublicStoredAcpAwaitState,
@@ -28,12 +41,76 @@ pub use types::{
 /// Alias for a deserialized conversation record: (title, model, messages).
 type LoadedConversation = (String, Option<String>, Vec<Message>);
 
+/// How long SQLite waits for a lock held by another connection or process
+/// before reporting `SQLITE_BUSY`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum number of attempts for saves that still fail with `SQLITE_BUSY`
+/// after the busy timeout elapsed.
+const BUSY_RETRY_ATTEMPTS: u32 = 5;
+
+/// Delay before the first busy retry; doubled after every failed attempt.
+const BUSY_RETRY_INITIAL_DELAY: Duration = Duration::from_millis(50);
+
+/// Pruning vacuums the database when free pages make up at least this
+/// fraction of the file.
+const VACUUM_FREE_PAGE_RATIO: f64 = 0.25;
+
+/// Title new conversations carry until they are given one.
+const DEFAULT_CONVERSATION_TITLE: &str = "New Conversation";
+
+/// Content of the system message that separates an appended session from the
+/// messages stored before it.
+pub const SESSION_SEPARATOR: &str = "--- New session appended to this conversation ---";
+
+/// Prefix hashed with a session key to derive its conversation ID.
+const SESSION_KEY_NAMESPACE: &str = "xzatoma-session:";
+
+/// File listing the conversations of a history backup.
+const BACKUP_MANIFEST_FILE: &str = "manifest.json";
+
+/// Format version written to history backup manifests.
+const BACKUP_FORMAT_VERSION: u32 = 1;
+
+/// Conversation row fields used to select sessions for retention pruning.
+struct RetentionCandidate {
+    id: String,
+    title: String,
+    updated_at: DateTime<Utc>,
+    pinned: bool,
+    size_bytes: u64,
+}
+
+/// One conversation as written to a history backup file.
+///
+/// JSON columns are carried as raw values and timestamps as the stored
+/// strings, so a restore writes back exactly what was backed up.
+#[derive(Serialize, Deserialize)]
+struct ConversationBackup {
+    id: String,
+    title: String,
+    created_at: String,
+    updated_at: String,
+    model: Option<String>,
+    pinned: bool,
+    tags: Vec<String>,
+    replayed_from: Option<String>,
+    pinned_messages: Box<RawValue>,
+    message_cwds: Box<RawValue>,
+    messages: Box<RawValue>,
+}
+
 /// Storage backend for conversation history and ACP persistence.
 ///
 /// This type provides the existing conversation storage surface together with
 /// durable ACP session, run, event, await-state, and cancellation persistence
 /// backed by the same SQLite database.
 ///
+/// Each instance owns a single connection opened in WAL journal mode with a
+/// busy timeout, shared by all clones of the instance. This lets several
+/// xzatoma processes (for example a chat session and a watcher) use the same
+/// database file without failing with "database is locked".
+///
 /// # Examples
 ///
 /// ```
@@ -46,44 +123,65 @@ type LoadedConversation = (String, Option<String>, Vec<Message>);
 #[derive(Clone)]
 pub struct SqliteStorage {
     db_path: PathBuf,
+    conn: Arc<Mutex<Connection>>,
 }
 
 impl SqliteStorage {
     /// Create a new storage instance.
     ///
-    /// Initializes the database file in the user's data directory.
+    /// Initializes the database file in the data directory of `paths`.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - Resolved data directories
     ///
     /// # Errors
     ///
-    /// Returns an error if the data directory cannot be determined, created, or
+    /// Returns an error if the data directory cannot be created or
     /// initialized.
     ///
     /// # Examples
     ///
     /// ```no_run
+    /// use xzatoma::paths::Paths;
     /// use xzatoma::storage::SqliteStorage;
     ///
-    /// let storage = SqliteStorage::new()?;
+    /// let storage = SqliteStorage::new(&Paths::from_env()?)?;
     /// let _ = storage;
     /// # Ok::<(), anyhow::Error>(())
     /// ```
-    pub fn new() -> Result<Self> {
-        if let Ok(override_path) = std::env::var("XZATOMA_HISTORY_DB") {
-            return Self::new_with_path(override_path);
+    pub fn new(paths: &Paths) -> Result<Self> {
+        let db_path = Self::default_database_path(paths);
+        if let Some(data_dir) = db_path.parent() {
+            std::fs::create_dir_all(data_dir)
+                .context("Failed to create data directory")
+                .map_err(|e| XzatomaError::Storage(e.to_string()))?;
         }
 
-        let proj_dirs = ProjectDirs::from("com", "xbcsmith", "xzatoma")
-            .ok_or_else(|| XzatomaError::Storage("Could not determine data directory".into()))?;
+        Self::new_with_path(db_path)
+    }
 
-        let data_dir = proj_dirs.data_dir();
-        std::fs::create_dir_all(data_dir)
-            .context("Failed to create data directory")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+    /// Returns the database path used by [`SqliteStorage::new`].
+    ///
+    /// This is `XZATOMA_HISTORY_DB` when set, otherwise `history.db` in the
+    /// data directory of `paths`. Nothing is created.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xzatoma::paths::Paths;
+    /// use xzatoma::storage::SqliteStorage;
+    ///
+    /// let path = SqliteStorage::default_database_path(&Paths::from_env()?);
+    /// assert!(path.ends_with("history.db"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn default_database_path(paths: &Paths) -> PathBuf {
+        if let Ok(override_path) = std::env::var("XZATOMA_HISTORY_DB") {
+            return PathBuf::from(override_path);
+        }
 
-        let db_path = data_dir.join("history.db");
-        let storage = Self { db_path };
-        storage.init()?;
-        Ok(storage)
+        paths.history_db()
     }
 
     /// Create a new storage instance that uses the specified database path.
@@ -118,9 +216,54 @@ impl SqliteStorage {
                 .map_err(|e| XzatomaError::Storage(e.to_string()))?;
         }
 
-        let storage = Self { db_path };
-        storage.init()?;
-        Ok(storage)
+        Self::init(&db_path)?;
+        let conn = open_configured_connection(&db_path)?;
+
+        Ok(Self {
+            db_path,
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Create a storage instance backed by a private in-memory database.
+    ///
+    /// The database has the full schema and lives until the last clone of
+    /// the instance is dropped. Nothing is written to disk, which suits
+    /// tests of code that takes a `SqliteStorage`. `database_path` returns
+    /// `:memory:`; opening a second connection to it reaches a different,
+    /// empty database.
+    ///
+    /// Requires the `testing` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the schema cannot be created.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::storage::SqliteStorage;
+    ///
+    /// let storage = SqliteStorage::new_in_memory()?;
+    /// assert!(storage.list_sessions()?.is_empty());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    #[cfg(any(test, feature = "testing"))]
+    pub fn new_in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory()
+            .context("Failed to open in-memory database")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        Self::create_schema(&conn)?;
+        // Match file-backed instances, whose shared connection never
+        // enables foreign keys
+        conn.execute_batch("PRAGMA foreign_keys = OFF;")
+            .context("Failed to configure in-memory database")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        Ok(Self {
+            db_path: PathBuf::from(":memory:"),
+            conn: Arc::new(Mutex::new(conn)),
+        })
     }
 
     /// Returns the database path used by this storage instance.
@@ -144,14 +287,26 @@ impl SqliteStorage {
 
     /// Initialize the database schema.
     ///
+    /// Schema setup runs on its own short-lived connection so the
+    /// `foreign_keys` pragma below does not change the behavior of the shared
+    /// connection used for regular operations.
+    ///
     /// # Errors
     ///
     /// Returns an error if any table or index creation fails.
-    fn init(&self) -> Result<()> {
-        let conn = Connection::open(&self.db_path)
-            .context("Failed to open database")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+    fn init(db_path: &Path) -> Result<()> {
+        let conn = open_configured_connection(db_path)?;
+        Self::create_schema(&conn)
+    }
 
+    /// Create missing tables, indexes, and columns on `conn`.
+    ///
+    /// Turns `foreign_keys` on for `conn`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any table or index creation fails.
+    fn create_schema(conn: &Connection) -> Result<()> {
         conn.execute_batch(
             "
             PRAGMA foreign_keys = ON;
@@ -162,9 +317,23 @@ impl SqliteStorage {
                 created_at TEXT NOT NULL,
                 updated_at TEXT NOT NULL,
                 model TEXT,
-                messages JSON NOT NULL
+                messages JSON NOT NULL,
+                pinned INTEGER NOT NULL DEFAULT 0
             );
 
+            CREATE TABLE IF NOT EXISTS conversation_tags (
+                conversation_id TEXT NOT NULL,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (conversation_id, tag),
+                FOREIGN KEY(conversation_id) REFERENCES conversations(id) ON DELETE CASCADE
+            );
+
+            CREATE TRIGGER IF NOT EXISTS conversations_delete_tags
+                AFTER DELETE ON conversations
+            BEGIN
+                DELETE FROM conversation_tags WHERE conversation_id = OLD.id;
+            END;
+
             CREATE TABLE IF NOT EXISTS acp_sessions (
                 session_id TEXT PRIMARY KEY,
                 conversation_id TEXT,
@@ -240,9 +409,42 @@ impl SqliteStorage {
                 FOREIGN KEY(run_id) REFERENCES acp_runs(run_id)
             );
 
+            CREATE TABLE IF NOT EXISTS run_turns (
+                run_id TEXT NOT NULL,
+                turn INTEGER NOT NULL,
+                created_at TEXT NOT NULL,
+                payload BLOB NOT NULL,
+                payload_bytes INTEGER NOT NULL,
+                PRIMARY KEY (run_id, turn)
+            );
+
+            CREATE TABLE IF NOT EXISTS usage_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                created_at TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                prompt_tokens INTEGER NOT NULL,
+                completion_tokens INTEGER NOT NULL,
+                estimated_cost REAL NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS subagent_conversations (
+                id TEXT PRIMARY KEY,
+                parent_id TEXT,
+                started_at TEXT NOT NULL,
+                record JSON NOT NULL
+            );
+
             CREATE INDEX IF NOT EXISTS idx_conversations_updated_at
                 ON conversations(updated_at DESC);
 
+            CREATE INDEX IF NOT EXISTS idx_conversations_model
+                ON conversations(model);
+
+            CREATE INDEX IF NOT EXISTS idx_conversation_tags_tag
+                ON conversation_tags(tag);
+
             CREATE INDEX IF NOT EXISTS idx_acp_sessions_updated_at
                 ON acp_sessions(updated_at DESC);
 
@@ -263,16 +465,52 @@ impl SqliteStorage {
 
             CREATE INDEX IF NOT EXISTS idx_acp_stdio_sessions_updated_at
                 ON acp_stdio_sessions(updated_at DESC);
+
+            CREATE INDEX IF NOT EXISTS idx_usage_log_created_at
+                ON usage_log(created_at);
+
+            CREATE INDEX IF NOT EXISTS idx_subagent_conversations_parent_id
+                ON subagent_conversations(parent_id);
             ",
         )
         .context("Failed to create tables")
         .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
+        ensure_column(
+            conn,
+            "conversations",
+            "pinned",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+        ensure_column(
+            conn,
+            "conversations",
+            "pinned_messages",
+            "TEXT NOT NULL DEFAULT '[]'",
+        )?;
+        ensure_column(conn, "conversations", "replayed_from", "TEXT")?;
+        ensure_column(
+            conn,
+            "conversations",
+            "message_cwds",
+            "TEXT NOT NULL DEFAULT '[]'",
+        )?;
+        ensure_column(
+            conn,
+            "conversations",
+            "revision",
+            "INTEGER NOT NULL DEFAULT 1",
+        )?;
+
         Ok(())
     }
 
     /// Save or update a conversation.
     ///
+    /// The save overwrites the stored conversation whatever its revision, so
+    /// the last writer wins. Callers that may race another session, such as
+    /// a resumed chat, use [`SqliteStorage::save_conversation_checked`].
+    ///
     /// # Arguments
     ///
     /// * `id` - Conversation identifier
@@ -280,6 +518,10 @@ impl SqliteStorage {
     /// * `model` - Optional model name
     /// * `messages` - Serialized conversation messages
     ///
+    /// The write runs in an `IMMEDIATE` transaction and is retried with
+    /// exponential backoff if another connection keeps the database busy past
+    /// the busy timeout.
+    ///
     /// # Errors
     ///
     /// Returns an error if the conversation cannot be persisted.
@@ -290,63 +532,258 @@ impl SqliteStorage {
         model: Option<&str>,
         messages: &[Message],
     ) -> Result<()> {
-        let mut conn = Connection::open(&self.db_path)
-            .context("Failed to open database")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        self.save_conversation_checked(id, title, model, messages, ExpectedRevision::Any)
+            .map(|_| ())
+    }
 
+    /// Save or update a conversation if nobody else saved it first.
+    ///
+    /// Every save increments the conversation's revision. With
+    /// [`ExpectedRevision::Exactly`], the save only happens when the stored
+    /// revision matches, so a session that saves the revision it last loaded
+    /// or saved cannot overwrite messages another session saved meanwhile.
+    /// [`ExpectedRevision::Any`] saves unconditionally, like
+    /// [`SqliteStorage::save_conversation`].
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Conversation identifier
+    /// * `title` - Conversation title
+    /// * `model` - Optional model name
+    /// * `messages` - Serialized conversation messages
+    /// * `expected` - Revision the stored conversation must have
+    ///
+    /// # Returns
+    ///
+    /// Returns the conversation's new revision.
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::ConversationConflict` with both revisions when
+    /// the stored revision is not the expected one, and a storage error if
+    /// the conversation cannot be persisted.
+    pub fn save_conversation_checked(
+        &self,
+        id: &str,
+        title: &str,
+        model: Option<&str>,
+        messages: &[Message],
+        expected: ExpectedRevision,
+    ) -> Result<u64> {
         let messages_json = serde_json::to_string(messages)
             .context("Failed to serialize messages")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        let now = Utc::now().to_rfc3339();
+        let mut conn = self.connection()?;
 
-        let tx = conn
-            .transaction()
-            .context("Failed to start transaction")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let saved = retry_on_busy(|| {
+            let now = Utc::now().to_rfc3339();
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
 
-        let exists: bool = tx
-            .query_row(
-                "SELECT 1 FROM conversations WHERE id = ?",
-                params![id],
-                |_| Ok(true),
-            )
-            .optional()
-            .unwrap_or(Some(false))
-            .unwrap_or(false);
+            let stored: Option<(i64, String)> = tx
+                .query_row(
+                    "SELECT revision, updated_at FROM conversations WHERE id = ?",
+                    params![id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            let stored_revision = stored.as_ref().map_or(0, |(revision, _)| *revision as u64);
+
+            if let ExpectedRevision::Exactly(expected) = expected {
+                if expected != stored_revision {
+                    return Ok(Err(ConversationConflict {
+                        id: id.to_string(),
+                        expected_revision: expected,
+                        stored_revision,
+                        stored_updated_at: stored
+                            .as_ref()
+                            .and_then(|(_, updated_at)| parse_rfc3339_to_utc(updated_at).ok()),
+                    }));
+                }
+            }
 
-        if exists {
-            tx.execute(
-                "UPDATE conversations SET
-                    title = ?,
-                    updated_at = ?,
-                    model = ?,
-                    messages = ?
-                 WHERE id = ?",
-                params![title, now, model, messages_json, id],
-            )
-            .context("Failed to update conversation")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-        } else {
-            tx.execute(
-                "INSERT INTO conversations (id, title, created_at, updated_at, model, messages)
-                 VALUES (?, ?, ?, ?, ?, ?)",
-                params![id, title, now, now, model, messages_json],
-            )
-            .context("Failed to insert conversation")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-        }
+            if stored.is_some() {
+                tx.execute(
+                    "UPDATE conversations SET
+                        title = ?,
+                        updated_at = ?,
+                        model = ?,
+                        messages = ?,
+                        revision = revision + 1
+                     WHERE id = ?",
+                    params![title, now, model, messages_json, id],
+                )?;
+            } else {
+                tx.execute(
+                    "INSERT INTO conversations (id, title, created_at, updated_at, model, messages)
+                     VALUES (?, ?, ?, ?, ?, ?)",
+                    params![id, title, now, now, model, messages_json],
+                )?;
+            }
 
-        tx.commit()
-            .context("Failed to commit transaction")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+            tx.commit()?;
+            Ok(Ok(stored_revision + 1))
+        })
+        .context("Failed to save conversation")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        saved.map_err(XzatomaError::from)
+    }
+
+    /// Return the revision of a stored conversation.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Full conversation ID
+    ///
+    /// # Returns
+    ///
+    /// Returns the revision, or `None` when the conversation is not stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lookup fails.
+    pub fn conversation_revision(&self, id: &str) -> Result<Option<u64>> {
+        let conn = self.connection()?;
+        conn.query_row(
+            "SELECT revision FROM conversations WHERE id = ?",
+            params![id],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map(|revision| revision.map(|revision| revision as u64))
+        .context("Failed to query conversation revision")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))
+    }
+
+    /// Save a conversation under a caller-chosen ID.
+    ///
+    /// Behaves like [`SqliteStorage::save_conversation`] when `id` is not
+    /// stored yet. Otherwise `if_exists` decides:
+    ///
+    /// - `Replace` overwrites the title, model, and messages. `created_at`
+    ///   and the tags are kept; pinned messages and message directories are
+    ///   cleared because they index the old messages.
+    /// - `Append` adds a [`SESSION_SEPARATOR`] system message and then
+    ///   `messages` after the stored messages.
+    /// - `Fail` returns an error and changes nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Full conversation ID, usually from [`session_conversation_id`]
+    /// * `title` - Conversation title
+    /// * `model` - Optional model name
+    /// * `messages` - Messages of the session being saved
+    /// * `if_exists` - What to do when `id` is already stored
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the conversation exists and `if_exists` is
+    /// `Fail`, or if it cannot be persisted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::providers::Message;
+    /// use xzatoma::storage::types::IfExists;
+    /// use xzatoma::storage::{session_conversation_id, SqliteStorage};
+    ///
+    /// let dir = tempfile::tempdir()?;
+    /// let storage = SqliteStorage::new_with_path(dir.path().join("history.db"))?;
+    /// let id = session_conversation_id("nightly-lint").to_string();
+    /// storage.upsert_conversation(&id, "Lint", None, &[Message::user("one")], IfExists::Append)?;
+    /// storage.upsert_conversation(&id, "Lint", None, &[Message::user("two")], IfExists::Append)?;
+    /// let (_, _, messages) = storage.load_conversation("nightly-lint")?.unwrap();
+    /// assert_eq!(messages.len(), 3);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn upsert_conversation(
+        &self,
+        id: &str,
+        title: &str,
+        model: Option<&str>,
+        messages: &[Message],
+        if_exists: IfExists,
+    ) -> Result<()> {
+        let mut conn = self.connection()?;
+
+        let saved = retry_on_busy(|| {
+            let now = Utc::now().to_rfc3339();
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+            let stored: Option<String> = tx
+                .query_row(
+                    "SELECT messages FROM conversations WHERE id = ?",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            match (stored, if_exists) {
+                (None, _) => {
+                    tx.execute(
+                        "INSERT INTO conversations (id, title, created_at, updated_at, model, messages)
+                         VALUES (?, ?, ?, ?, ?, ?)",
+                        params![id, title, now, now, model, to_json(messages)?],
+                    )?;
+                }
+                (Some(_), IfExists::Fail) => return Ok(false),
+                (Some(_), IfExists::Replace) => {
+                    tx.execute(
+                        "UPDATE conversations SET
+                            title = ?,
+                            updated_at = ?,
+                            model = ?,
+                            messages = ?,
+                            pinned_messages = '[]',
+                            message_cwds = '[]',
+                            revision = revision + 1
+                         WHERE id = ?",
+                        params![title, now, model, to_json(messages)?, id],
+                    )?;
+                }
+                (Some(stored), IfExists::Append) => {
+                    let mut combined: Vec<Message> =
+                        serde_json::from_str(&stored).map_err(|e| {
+                            rusqlite::Error::FromSqlConversionFailure(
+                                0,
+                                rusqlite::types::Type::Text,
+                                Box::new(e),
+                            )
+                        })?;
+                    combined.push(Message::system(SESSION_SEPARATOR));
+                    combined.extend_from_slice(messages);
+                    tx.execute(
+                        "UPDATE conversations SET
+                            title = ?,
+                            updated_at = ?,
+                            model = ?,
+                            messages = ?,
+                            revision = revision + 1
+                         WHERE id = ?",
+                        params![title, now, model, to_json(&combined)?, id],
+                    )?;
+                }
+            }
+
+            tx.commit()?;
+            Ok(true)
+        })
+        .context("Failed to save conversation")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
+        if !saved {
+            return Err(XzatomaError::Storage(format!(
+                "Conversation {} already exists and the if-exists policy is fail",
+                id
+            )));
+        }
         Ok(())
     }
 
     /// Load a conversation by ID.
     ///
-    /// Supports full UUID or prefix matching.
+    /// Supports full UUID, session key, or prefix matching.
     ///
     /// # Arguments
     ///
@@ -360,24 +797,16 @@ impl SqliteStorage {
     ///
     /// Returns an error if the conversation lookup or deserialization fails.
     pub fn load_conversation(&self, id: &str) -> Result<Option<LoadedConversation>> {
-        let conn = Connection::open(&self.db_path)
-            .context("Failed to open database")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let conn = self.connection()?;
 
-        let query = if id.len() == 36 {
-            "SELECT title, model, messages FROM conversations WHERE id = ?"
-        } else {
-            "SELECT title, model, messages FROM conversations WHERE id LIKE ?"
-        };
-
-        let search_param = if id.len() == 36 {
-            id.to_string()
-        } else {
-            format!("{}%", id)
-        };
+        let (condition, param) = id_condition(&conn, id)?;
+        let query = format!(
+            "SELECT title, model, messages FROM conversations WHERE {}",
+            condition
+        );
 
         let result = conn
-            .query_row(query, params![search_param], |row| {
+            .query_row(&query, params![param], |row| {
                 let title: String = row.get(0)?;
                 let model: Option<String> = row.get(1)?;
                 let messages_json: String = row.get(2)?;
@@ -408,1826 +837,5728 @@ impl SqliteStorage {
     ///
     /// Returns an error if session listing fails.
     pub fn list_sessions(&self) -> Result<Vec<StoredSession>> {
-        let conn = Connection::open(&self.db_path)
-            .context("Failed to open database")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        let mut stmt = conn
-            .prepare(
-                "SELECT id, title, created_at, updated_at, model, messages
-                 FROM conversations
-                 ORDER BY updated_at DESC",
-            )
-            .context("Failed to prepare statement")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        let sessions_iter = stmt
-            .query_map([], |row| {
-                let id: String = row.get(0)?;
-                let title: String = row.get(1)?;
-                let created_at_str: String = row.get(2)?;
-                let updated_at_str: String = row.get(3)?;
-                let model: Option<String> = row.get(4)?;
-                let messages_json: String = row.get(5)?;
-
-                let created_at =
-                    parse_rfc3339_to_utc(&created_at_str).unwrap_or_else(|_| Utc::now());
-                let updated_at =
-                    parse_rfc3339_to_utc(&updated_at_str).unwrap_or_else(|_| Utc::now());
-
-                let message_count =
-                    if let Ok(value) = serde_json::from_str::<serde_json::Value>(&messages_json) {
-                        value.as_array().map(|items| items.len()).unwrap_or(0)
-                    } else {
-                        0
-                    };
-
-                Ok(StoredSession {
-                    id,
-                    title,
-                    created_at,
-                    updated_at,
-                    model,
-                    message_count,
-                })
-            })
-            .context("Failed to query sessions")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        let mut sessions = Vec::new();
-        for session in sessions_iter.flatten() {
-            sessions.push(session);
-        }
-
-        Ok(sessions)
+        self.query_sessions(None, &HistoryFilter::default(), None)
     }
 
-    /// Delete a conversation.
+    /// List stored sessions that carry every one of `tags`.
     ///
-    /// Supports full UUID or prefix matching.
+    /// Tags are normalized before matching. An empty slice lists all sessions.
     ///
     /// # Arguments
     ///
-    /// * `id` - Full conversation ID or prefix
+    /// * `tags` - Tags a session must all carry to be listed
     ///
-    /// # Errors
+    /// # Returns
     ///
-    /// Returns an error if deletion fails.
-    pub fn delete_conversation(&self, id: &str) -> Result<()> {
-        let conn = Connection::open(&self.db_path)
-            .context("Failed to open database")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        let (query, param) = if id.len() == 36 {
-            ("DELETE FROM conversations WHERE id = ?", id.to_string())
-        } else {
-            (
-                "DELETE FROM conversations WHERE id LIKE ?",
-                format!("{}%", id),
-            )
-        };
-
-        conn.execute(query, params![param])
-            .context("Failed to delete conversation")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        Ok(())
-    }
-
-    /// Save or update an ACP stdio session mapping.
+    /// Returns matching session summaries ordered by last update time.
     ///
-    /// # Arguments
+    /// # Errors
     ///
-    /// * `session` - ACP stdio session mapping to persist
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if the mapping cannot be serialized or saved.
+    /// Returns an error if a tag is empty or session listing fails.
     ///
     /// # Examples
     ///
     /// ```
-    /// use chrono::Utc;
-    /// use xzatoma::storage::{PublicStoredAcpStdioSession, SqliteStorage};
-    ///
-    /// let storage = SqliteStorage::new_with_path("/tmp/acp_stdio_session_save.db")?;
-    /// let now = Utc::now();
-    /// storage.save_conversation(
-    ///     "conversation-1",
-    ///     "New Conversation",
-    ///     Some("llama3.2:latest"),
-    ///     &[xzatoma::providers::Message::user("hello")],
-    /// )?;
+    /// use xzatoma::storage::SqliteStorage;
     ///
-    /// storage.save_acp_stdio_session(&PublicStoredAcpStdioSession {
-    ///     session_id: "xzatoma-session-1".to_string(),
-    ///     workspace_root: "/tmp/workspace".to_string(),
-    ///     conversation_id: "conversation-1".to_string(),
-    ///     provider_type: "ollama".to_string(),
-    ///     model: Some("llama3.2:latest".to_string()),
-    ///     created_at: now,
-    ///     updated_at: now,
-    ///     metadata: Default::default(),
-    /// })?;
+    /// let storage = SqliteStorage::new_with_path("/tmp/xzatoma_tag_filter_example.db")?;
+    /// let sessions = storage.list_sessions_with_tags(&["billing".to_string()])?;
+    /// assert!(sessions.iter().all(|s| s.tags.contains(&"billing".to_string())));
     /// # Ok::<(), anyhow::Error>(())
     /// ```
-    pub fn save_acp_stdio_session(&self, session: &StoredAcpStdioSession) -> Result<()> {
-        let conn = Connection::open(&self.db_path)
-            .context("Failed to open database")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        let metadata_json = serde_json::to_string(&session.metadata)
-            .context("Failed to serialize ACP stdio session metadata")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        conn.execute(
-            "INSERT INTO acp_stdio_sessions (
-                session_id,
-                workspace_root,
-                conversation_id,
-                provider_type,
-                model,
-                created_at,
-                updated_at,
-                metadata_json
-             )
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?)
-             ON CONFLICT(session_id) DO UPDATE SET
-                workspace_root = excluded.workspace_root,
-                conversation_id = excluded.conversation_id,
-                provider_type = excluded.provider_type,
-                model = excluded.model,
-                updated_at = excluded.updated_at,
-                metadata_json = excluded.metadata_json",
-            params![
-                session.session_id,
-                session.workspace_root,
-                session.conversation_id,
-                session.provider_type,
-                session.model,
-                session.created_at.to_rfc3339(),
-                session.updated_at.to_rfc3339(),
-                metadata_json,
-            ],
+    pub fn list_sessions_with_tags(&self, tags: &[String]) -> Result<Vec<StoredSession>> {
+        self.query_sessions(
+            None,
+            &HistoryFilter {
+                tags: tags.to_vec(),
+                ..HistoryFilter::default()
+            },
+            None,
         )
-        .context("Failed to save ACP stdio session")
-        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        Ok(())
     }
 
-    /// Load the most recently updated ACP stdio session for a workspace root.
+    /// Search stored sessions by title and message content.
+    ///
+    /// Matching is a case-insensitive substring match. When `tags` is not
+    /// empty, only sessions carrying every tag are returned.
     ///
     /// # Arguments
     ///
-    /// * `workspace_root` - Normalized workspace root to search for
+    /// * `query` - Text to look for in titles and messages
+    /// * `tags` - Tags a session must all carry to be returned
     ///
     /// # Returns
     ///
-    /// Returns the newest matching ACP stdio session mapping, if present.
+    /// Returns matching session summaries ordered by last update time.
     ///
     /// # Errors
     ///
-    /// Returns an error if querying or deserializing the mapping fails.
-    pub fn load_latest_acp_stdio_session_by_workspace_root(
-        &self,
-        workspace_root: &str,
-    ) -> Result<Option<StoredAcpStdioSession>> {
-        let conn = Connection::open(&self.db_path)
-            .context("Failed to open database")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        let row = conn
-            .query_row(
-                "SELECT session_id, workspace_root, conversation_id, provider_type, model, created_at, updated_at, metadata_json
-                 FROM acp_stdio_sessions
-                 WHERE workspace_root = ?
-                 ORDER BY updated_at DESC
-                 LIMIT 1",
-                params![workspace_root],
-                stored_acp_stdio_session_from_row,
-            )
-            .optional()
-            .context("Failed to query ACP stdio session by workspace root")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+    /// Returns an error if a tag is empty or the search fails.
+    pub fn search_sessions(&self, query: &str, tags: &[String]) -> Result<Vec<StoredSession>> {
+        self.query_sessions(
+            Some(query),
+            &HistoryFilter {
+                tags: tags.to_vec(),
+                ..HistoryFilter::default()
+            },
+            None,
+        )
+    }
 
-        row.transpose()
+    /// Find stored sessions matching every filter that is set.
+    ///
+    /// Tags use the same AND semantics as [`SqliteStorage::list_sessions_with_tags`].
+    /// A session is untitled when its title is blank or still the default
+    /// title new conversations start with. `older_than` compares the parsed
+    /// last update time, so sessions whose date cannot be parsed never match
+    /// it.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Filters a session must all match
+    ///
+    /// # Returns
+    ///
+    /// Returns matching session summaries ordered by last update time.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a tag is empty or the query fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::storage::types::HistoryFilter;
+    /// use xzatoma::storage::SqliteStorage;
+    ///
+    /// let storage = SqliteStorage::new_with_path("/tmp/xzatoma_find_sessions_example.db")?;
+    /// let filter = HistoryFilter {
+    ///     model: Some("gpt-4o".to_string()),
+    ///     untitled: true,
+    ///     ..HistoryFilter::default()
+    /// };
+    /// let sessions = storage.find_sessions(&filter)?;
+    /// assert!(sessions.iter().all(|s| s.model.as_deref() == Some("gpt-4o")));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn find_sessions(&self, filter: &HistoryFilter) -> Result<Vec<StoredSession>> {
+        self.query_sessions(None, filter, None)
     }
 
-    /// Load an ACP stdio session mapping by ACP session ID.
+    /// List one page of stored sessions that carry every one of `tags`.
+    ///
+    /// The page is cut in SQL with `LIMIT` and `OFFSET`, so only the
+    /// requested rows are loaded. The total number of matching sessions is
+    /// counted separately so callers can tell how many are left.
     ///
     /// # Arguments
     ///
-    /// * `session_id` - ACP stdio session identifier
+    /// * `tags` - Tags a session must all carry to be listed
+    /// * `limit` - Maximum number of sessions on the page
+    /// * `offset` - Number of matching sessions to skip
     ///
     /// # Returns
     ///
-    /// Returns the matching ACP stdio session mapping, if present.
+    /// Returns the page, ordered by last update time, and the total count.
     ///
     /// # Errors
     ///
-    /// Returns an error if querying or deserializing the mapping fails.
-    pub fn load_acp_stdio_session_by_session_id(
+    /// Returns an error if a tag is empty or session listing fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::storage::SqliteStorage;
+    ///
+    /// let storage = SqliteStorage::new_with_path("/tmp/xzatoma_session_page_example.db")?;
+    /// let page = storage.list_sessions_page(&[], 25, 0)?;
+    /// assert!(page.sessions.len() <= 25);
+    /// assert!(page.sessions.len() <= page.total);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn list_sessions_page(
         &self,
-        session_id: &str,
-    ) -> Result<Option<StoredAcpStdioSession>> {
-        let conn = Connection::open(&self.db_path)
-            .context("Failed to open database")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        tags: &[String],
+        limit: usize,
+        offset: usize,
+    ) -> Result<SessionPage> {
+        let filter = HistoryFilter {
+            tags: tags.to_vec(),
+            ..HistoryFilter::default()
+        };
+        let sessions = self.query_sessions(None, &filter, Some((limit, offset)))?;
 
-        let row = conn
+        let (where_clause, values) = session_conditions(None, &filter)?;
+        let conn = self.connection()?;
+        let total: i64 = conn
             .query_row(
-                "SELECT session_id, workspace_root, conversation_id, provider_type, model, created_at, updated_at, metadata_json
-                 FROM acp_stdio_sessions
-                 WHERE session_id = ?",
-                params![session_id],
-                stored_acp_stdio_session_from_row,
+                &format!("SELECT COUNT(*) FROM conversations {}", where_clause),
+                params_from_iter(values.iter()),
+                |row| row.get(0),
             )
-            .optional()
-            .context("Failed to query ACP stdio session by session ID")
+            .context("Failed to count sessions")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        row.transpose()
+        Ok(SessionPage {
+            sessions,
+            offset,
+            total: usize::try_from(total).unwrap_or(0),
+        })
     }
 
-    /// Update the last activity timestamp for an ACP stdio session.
+    /// Query session summaries, optionally cut to a `(limit, offset)` page.
+    ///
+    /// Paging happens in SQL, before the `older_than` filter, so callers
+    /// must not combine the two.
+    fn query_sessions(
+        &self,
+        text: Option<&str>,
+        filter: &HistoryFilter,
+        page: Option<(usize, usize)>,
+    ) -> Result<Vec<StoredSession>> {
+        let (where_clause, values) = session_conditions(text, filter)?;
+        let page_clause = match page {
+            Some((limit, offset)) => format!("LIMIT {} OFFSET {}", limit, offset),
+            None => String::new(),
+        };
+
+        let conn = self.connection()?;
+        let mut session_tags = load_tags_by_conversation(&conn)?;
+
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT id, title, created_at, updated_at, model, messages, pinned
+                 FROM conversations
+                 {}
+                 ORDER BY updated_at DESC, id
+                 {}",
+                where_clause, page_clause
+            ))
+            .context("Failed to prepare statement")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let sessions_iter = stmt
+            .query_map(params_from_iter(values.iter()), |row| {
+                let id: String = row.get(0)?;
+                let title: String = row.get(1)?;
+                let created_at_str: String = row.get(2)?;
+                let updated_at_str: String = row.get(3)?;
+                let model: Option<String> = row.get(4)?;
+                let messages_json: String = row.get(5)?;
+                let pinned: i64 = row.get(6)?;
+
+                let created_at = parse_listed_timestamp(&id, "created_at", &created_at_str);
+                let updated_at = parse_listed_timestamp(&id, "updated_at", &updated_at_str);
+
+                let message_count =
+                    if let Ok(value) = serde_json::from_str::<serde_json::Value>(&messages_json) {
+                        value.as_array().map(|items| items.len()).unwrap_or(0)
+                    } else {
+                        0
+                    };
+
+                Ok(StoredSession {
+                    tags: session_tags.remove(&id).unwrap_or_default(),
+                    id,
+                    title,
+                    created_at,
+                    updated_at,
+                    model,
+                    message_count,
+                    pinned: sqlite_to_bool(pinned),
+                })
+            })
+            .context("Failed to query sessions")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let mut sessions = Vec::new();
+        for session in sessions_iter.flatten() {
+            sessions.push(session);
+        }
+        // Stored strings may carry different offsets, so compare parsed
+        // instants rather than filtering in SQL
+        if let Some(cutoff) = filter.older_than {
+            sessions.retain(|session| {
+                session.updated_at != DateTime::<Utc>::UNIX_EPOCH && session.updated_at < cutoff
+            });
+        }
+        // SQL orders the stored strings; order by the parsed instants so mixed
+        // offsets and unparseable dates (sorted last) land where they belong
+        sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+
+        Ok(sessions)
+    }
+
+    /// Delete a conversation.
+    ///
+    /// Supports full UUID or prefix matching.
     ///
     /// # Arguments
     ///
-    /// * `session_id` - ACP stdio session identifier
+    /// * `id` - Full conversation ID or prefix
     ///
     /// # Errors
     ///
-    /// Returns an error if the update fails.
-    pub fn touch_acp_stdio_session(&self, session_id: &str) -> Result<()> {
-        let conn = Connection::open(&self.db_path)
-            .context("Failed to open database")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+    /// Returns an error if deletion fails.
+    pub fn delete_conversation(&self, id: &str) -> Result<()> {
+        let conn = self.connection()?;
 
-        conn.execute(
-            "UPDATE acp_stdio_sessions SET updated_at = ? WHERE session_id = ?",
-            params![Utc::now().to_rfc3339(), session_id],
-        )
-        .context("Failed to update ACP stdio session activity")
-        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let (condition, param) = id_condition(&conn, id)?;
+        let query = format!("DELETE FROM conversations WHERE {}", condition);
+
+        conn.execute(&query, params![param])
+            .context("Failed to delete conversation")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
         Ok(())
     }
 
-    /// Prune ACP stdio session mappings older than a cutoff timestamp.
+    /// Delete several conversations in one transaction.
+    ///
+    /// IDs must be full conversation IDs, such as those returned by
+    /// [`SqliteStorage::find_sessions`]. ACP stdio session mappings of the
+    /// deleted conversations are removed with them. Unknown IDs are ignored.
     ///
     /// # Arguments
     ///
-    /// * `older_than` - Delete mappings whose `updated_at` value is older than
-    ///   this timestamp
+    /// * `ids` - Full IDs of the conversations to delete
     ///
     /// # Returns
     ///
-    /// Returns the number of deleted mappings.
+    /// Returns the number of conversations deleted.
     ///
     /// # Errors
     ///
-    /// Returns an error if pruning fails.
-    pub fn prune_acp_stdio_sessions_older_than(&self, older_than: DateTime<Utc>) -> Result<usize> {
-        let conn = Connection::open(&self.db_path)
-            .context("Failed to open database")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        let deleted = conn
-            .execute(
-                "DELETE FROM acp_stdio_sessions WHERE updated_at < ?",
-                params![older_than.to_rfc3339()],
-            )
-            .context("Failed to prune ACP stdio sessions")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        Ok(deleted)
+    /// Returns an error if deletion fails; nothing is deleted in that case.
+    pub fn delete_conversations(&self, ids: &[String]) -> Result<usize> {
+        let mut conn = self.connection()?;
+
+        retry_on_busy(|| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let mut deleted = 0;
+            for id in ids {
+                tx.execute(
+                    "DELETE FROM acp_stdio_sessions WHERE conversation_id = ?",
+                    params![id],
+                )?;
+                deleted += tx.execute("DELETE FROM conversations WHERE id = ?", params![id])?;
+            }
+            tx.commit()?;
+            Ok(deleted)
+        })
+        .context("Failed to delete conversations")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))
     }
 
-    /// Save or update an ACP session summary.
+    /// Export every conversation to `dir` as individual JSON files.
+    ///
+    /// Conversations are read and written one at a time, so memory use does
+    /// not grow with the size of the history. Each file holds the stored
+    /// messages, tags, pins, and timestamps of one conversation. The
+    /// manifest, listing every file with its SHA-256, is written last; a
+    /// directory without one is not a complete backup.
     ///
     /// # Arguments
     ///
-    /// * `session` - Persisted ACP session metadata
+    /// * `dir` - Directory to write the backup to; created if missing
+    ///
+    /// # Returns
+    ///
+    /// Returns the manifest that was written.
     ///
     /// # Errors
     ///
-    /// Returns an error if the ACP session cannot be saved.
+    /// Returns an error if `dir` already holds a backup, or if reading the
+    /// database or writing a file fails.
     ///
     /// # Examples
     ///
     /// ```
-    /// use chrono::Utc;
-    /// use xzatoma::storage::{types::StoredAcpSession, SqliteStorage};
+    /// use tempfile::tempdir;
+    /// use xzatoma::storage::SqliteStorage;
     ///
-    /// let storage = SqliteStorage::new_with_path("/tmp/acp_session_save_example.db")?;
-    /// let now = Utc::now();
-    /// let session = StoredAcpSession {
-    ///     session_id: "session_123".to_string(),
-    ///     conversation_id: None,
-    ///     title: Some("Example".to_string()),
-    ///     created_at: now,
-    ///     updated_at: now,
-    ///     run_count: 0,
-    ///     last_run_id: None,
-    ///     metadata: Default::default(),
-    /// };
+    /// let dir = tempdir()?;
+    /// let storage = SqliteStorage::new_with_path(dir.path().join("history.db"))?;
+    /// storage.save_conversation("backup-example", "Example", None, &[])?;
     ///
-    /// storage.save_acp_session(&session)?;
+    /// let manifest = storage.backup_conversations(&dir.path().join("backup"))?;
+    /// assert_eq!(manifest.conversations.len(), 1);
+    /// assert!(dir.path().join("backup/manifest.json").exists());
     /// # Ok::<(), anyhow::Error>(())
     /// ```
-    pub fn save_acp_session(&self, session: &StoredAcpSession) -> Result<()> {
-        let mut conn = self.open_connection()?;
-        let metadata_json = serialize_metadata(&session.metadata)?;
-        let tx = conn
-            .transaction()
-            .context("Failed to start ACP session transaction")
+    pub fn backup_conversations(&self, dir: &Path) -> Result<BackupManifest> {
+        let manifest_path = dir.join(BACKUP_MANIFEST_FILE);
+        if manifest_path.exists() {
+            return Err(XzatomaError::Storage(format!(
+                "{} already contains a history backup",
+                dir.display()
+            )));
+        }
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create backup directory {}", dir.display()))
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        tx.execute(
-            "
-            INSERT INTO acp_sessions (
-                session_id,
-                conversation_id,
-                title,
-                created_at,
-                updated_at,
-                metadata_json,
-                last_run_id
+        let conn = self.connection()?;
+        let mut tag_stmt = conn
+            .prepare("SELECT tag FROM conversation_tags WHERE conversation_id = ? ORDER BY tag")
+            .context("Failed to prepare statement")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, title, created_at, updated_at, model, pinned, replayed_from,
+                        pinned_messages, message_cwds, messages
+                 FROM conversations
+                 ORDER BY created_at, id",
             )
-            VALUES (?, ?, ?, ?, ?, ?, ?)
-            ON CONFLICT(session_id) DO UPDATE SET
-                conversation_id = excluded.conversation_id,
-                title = excluded.title,
-                updated_at = excluded.updated_at,
-                metadata_json = excluded.metadata_json,
-                last_run_id = excluded.last_run_id
-            ",
-            params![
-                session.session_id,
-                session.conversation_id,
-                session.title,
-                session.created_at.to_rfc3339(),
-                session.updated_at.to_rfc3339(),
-                metadata_json,
-                session.last_run_id
-            ],
-        )
-        .context("Failed to save ACP session")
-        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+            .context("Failed to prepare statement")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let mut rows = stmt
+            .query([])
+            .context("Failed to query conversations")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        tx.commit()
-            .context("Failed to commit ACP session transaction")
+        let mut manifest = BackupManifest {
+            version: BACKUP_FORMAT_VERSION,
+            created_at: Utc::now(),
+            conversations: Vec::new(),
+        };
+        let mut used_files = HashSet::new();
+
+        while let Some(row) = rows
+            .next()
+            .context("Failed to read conversation")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?
+        {
+            let backup = read_backup_row(row, &mut tag_stmt)?;
+            let bytes = serde_json::to_vec_pretty(&backup)
+                .context("Failed to serialize conversation")
+                .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+            let file = backup_file_name(&backup.id, &mut used_files);
+            std::fs::write(dir.join(&file), &bytes)
+                .with_context(|| format!("Failed to write {}", file))
+                .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+            let message_count =
+                serde_json::from_str::<Vec<serde::de::IgnoredAny>>(backup.messages.get())
+                    .map(|messages| messages.len())
+                    .unwrap_or(0);
+            manifest.conversations.push(BackupEntry {
+                id: backup.id,
+                title: backup.title,
+                file,
+                sha256: sha256_hex(&bytes),
+                message_count,
+            });
+        }
+
+        let manifest_json = serde_json::to_vec_pretty(&manifest)
+            .context("Failed to serialize backup manifest")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        std::fs::write(&manifest_path, manifest_json)
+            .context("Failed to write backup manifest")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        Ok(())
+        Ok(manifest)
     }
 
-    /// Load an ACP session by identifier.
+    /// Restore conversations from a backup written by
+    /// [`SqliteStorage::backup_conversations`].
+    ///
+    /// Every file is checked against the SHA-256 in the manifest before it
+    /// is imported, one conversation at a time. Stored messages, tags, pins,
+    /// and timestamps are restored exactly as they were backed up.
+    /// Conversations whose ID already exists are skipped rather than
+    /// overwritten. The import runs in one transaction, so a corrupt file
+    /// leaves the database untouched.
     ///
     /// # Arguments
     ///
-    /// * `session_id` - ACP session identifier
+    /// * `dir` - Directory containing the backup manifest
+    /// * `dry_run` - Verify the backup and report what would be imported,
+    ///   then roll back instead of committing
     ///
     /// # Returns
     ///
-    /// Returns the stored ACP session when found.
+    /// Returns the IDs of the imported and skipped conversations.
     ///
     /// # Errors
     ///
-    /// Returns an error if the session cannot be loaded or deserialized.
-    pub fn load_acp_session(&self, session_id: &str) -> Result<Option<StoredAcpSession>> {
-        let conn = self.open_connection()?;
+    /// Returns an error if the manifest is missing or has an unsupported
+    /// version, a file is missing or does not match its hash, or the import
+    /// fails.
+    pub fn import_conversations(&self, dir: &Path, dry_run: bool) -> Result<ImportReport> {
+        let manifest_path = dir.join(BACKUP_MANIFEST_FILE);
+        let manifest_json = std::fs::read(&manifest_path)
+            .with_context(|| format!("Failed to read {}", manifest_path.display()))
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let manifest: BackupManifest = serde_json::from_slice(&manifest_json)
+            .context("Failed to parse backup manifest")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        if manifest.version != BACKUP_FORMAT_VERSION {
+            return Err(XzatomaError::Storage(format!(
+                "Unsupported history backup version {} (expected {})",
+                manifest.version, BACKUP_FORMAT_VERSION
+            )));
+        }
 
-        let result = conn
-            .query_row(
-                "
-                SELECT
-                    session_id,
-                    conversation_id,
-                    title,
-                    created_at,
-                    updated_at,
-                    metadata_json,
-                    last_run_id
-                FROM acp_sessions
-                WHERE session_id = ?
-                ",
-                params![session_id],
-                |row| {
-                    let session_id: String = row.get(0)?;
-                    let conversation_id: Option<String> = row.get(1)?;
-                    let title: Option<String> = row.get(2)?;
-                    let created_at: String = row.get(3)?;
-                    let updated_at: String = row.get(4)?;
-                    let metadata_json: String = row.get(5)?;
-                    let last_run_id: Option<String> = row.get(6)?;
-                    Ok((
-                        session_id,
-                        conversation_id,
-                        title,
-                        created_at,
-                        updated_at,
-                        metadata_json,
-                        last_run_id,
-                    ))
-                },
-            )
-            .optional()
-            .context("Failed to load ACP session")
+        let mut conn = self.connection()?;
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .context("Failed to start import")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let mut report = ImportReport::default();
 
-        match result {
-            Some((
-                session_id,
-                conversation_id,
-                title,
-                created_at,
-                updated_at,
-                metadata_json,
-                last_run_id,
-            )) => {
-                let created_at = parse_rfc3339_to_utc(&created_at)?;
-                let updated_at = parse_rfc3339_to_utc(&updated_at)?;
-                let metadata = deserialize_metadata(&metadata_json)?;
-                let run_count = self.count_acp_runs_for_session(&session_id)?;
+        for entry in &manifest.conversations {
+            let backup = read_backup_file(dir, entry)?;
 
-                Ok(Some(StoredAcpSession {
-                    session_id,
-                    conversation_id,
-                    title,
-                    created_at,
-                    updated_at,
-                    run_count,
-                    last_run_id,
-                    metadata,
-                }))
+            let exists = tx
+                .query_row(
+                    "SELECT 1 FROM conversations WHERE id = ?",
+                    params![backup.id],
+                    |_| Ok(()),
+                )
+                .optional()
+                .context("Failed to query conversation")
+                .map_err(|e| XzatomaError::Storage(e.to_string()))?
+                .is_some();
+            if exists {
+                report.skipped.push(backup.id);
+                continue;
             }
-            None => Ok(None),
+
+            tx.execute(
+                "INSERT INTO conversations (
+                    id, title, created_at, updated_at, model, messages, pinned,
+                    pinned_messages, message_cwds, replayed_from
+                 ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    backup.id,
+                    backup.title,
+                    backup.created_at,
+                    backup.updated_at,
+                    backup.model,
+                    backup.messages.get(),
+                    bool_to_sqlite(backup.pinned),
+                    backup.pinned_messages.get(),
+                    backup.message_cwds.get(),
+                    backup.replayed_from,
+                ],
+            )
+            .with_context(|| format!("Failed to import conversation {}", backup.id))
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+            for tag in &backup.tags {
+                tx.execute(
+                    "INSERT OR IGNORE INTO conversation_tags (conversation_id, tag) VALUES (?, ?)",
+                    params![backup.id, tag],
+                )
+                .context("Failed to import conversation tags")
+                .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+            }
+            report.imported.push(backup.id);
         }
+
+        if !dry_run {
+            tx.commit()
+                .context("Failed to commit import")
+                .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        }
+
+        Ok(report)
     }
 
-    /// Save or update an ACP run record.
+    /// Store a conversation imported from another tool.
+    ///
+    /// Unlike [`SqliteStorage::save_conversation`], the session's own
+    /// timestamps are kept; a missing start time falls back to now and a
+    /// missing update time to the start time. `tags` are normalized and
+    /// attached. A conversation whose ID already exists is left untouched.
     ///
     /// # Arguments
     ///
-    /// * `run` - Persisted ACP run summary
+    /// * `id` - Conversation identifier
+    /// * `session` - The imported session
+    /// * `tags` - Tags to attach, such as `imported:claude`
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` when the conversation was stored and `false` when the
+    /// ID already exists.
     ///
     /// # Errors
     ///
-    /// Returns an error if the run cannot be persisted.
-    pub fn save_acp_run(&self, run: &StoredAcpRun) -> Result<()> {
-        let mut conn = self.open_connection()?;
-        let metadata_json = serialize_metadata(&run.metadata)?;
-        let tx = conn
-            .transaction()
-            .context("Failed to start ACP run transaction")
+    /// Returns an error if a tag is empty or the insert fails.
+    pub fn insert_imported_conversation(
+        &self,
+        id: &str,
+        session: &import::ImportedSession,
+        tags: &[String],
+    ) -> Result<bool> {
+        let tags = tags
+            .iter()
+            .map(|tag| normalize_tag(tag))
+            .collect::<Result<Vec<_>>>()?;
+        let messages_json = serde_json::to_string(&session.messages)
+            .context("Failed to serialize messages")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let created_at = session.created_at.unwrap_or_else(Utc::now);
+        let updated_at = session.updated_at.unwrap_or(created_at);
+
+        let mut conn = self.connection()?;
+        retry_on_busy(|| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let inserted = tx.execute(
+                "INSERT OR IGNORE INTO conversations (id, title, created_at, updated_at, messages)
+                 VALUES (?, ?, ?, ?, ?)",
+                params![
+                    id,
+                    session.title,
+                    created_at.to_rfc3339(),
+                    updated_at.to_rfc3339(),
+                    messages_json
+                ],
+            )? > 0;
+            if inserted {
+                for tag in &tags {
+                    tx.execute(
+                        "INSERT OR IGNORE INTO conversation_tags (conversation_id, tag) VALUES (?, ?)",
+                        params![id, tag],
+                    )?;
+                }
+            }
+            tx.commit()?;
+            Ok(inserted)
+        })
+        .with_context(|| format!("Failed to import conversation {}", id))
+        .map_err(|e| XzatomaError::Storage(e.to_string()))
+    }
 
-        tx.execute(
-            "
-            INSERT INTO acp_runs (
-                run_id,
-                session_id,
-                conversation_id,
-                mode,
-                state,
-                created_at,
-                updated_at,
-                completed_at,
-                failure_reason,
-                cancellation_reason,
-                await_kind,
-                await_detail,
-                input_json,
-                output_json,
-                metadata_json
-            )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            ON CONFLICT(run_id) DO UPDATE SET
-                session_id = excluded.session_id,
-                conversation_id = excluded.conversation_id,
-                mode = excluded.mode,
-                state = excluded.state,
-                updated_at = excluded.updated_at,
-                completed_at = excluded.completed_at,
-                failure_reason = excluded.failure_reason,
-                cancellation_reason = excluded.cancellation_reason,
-                await_kind = excluded.await_kind,
-                await_detail = excluded.await_detail,
-                input_json = excluded.input_json,
-                output_json = excluded.output_json,
-                metadata_json = excluded.metadata_json
-            ",
-            params![
-                run.run_id,
-                run.session_id,
-                run.conversation_id,
-                run.mode,
-                run.state,
-                run.created_at.to_rfc3339(),
-                run.updated_at.to_rfc3339(),
-                run.completed_at.map(|value| value.to_rfc3339()),
-                run.failure_reason,
-                run.cancellation_reason,
-                run.await_kind,
-                run.await_detail,
-                run.input_json,
-                run.output_json,
-                metadata_json,
-            ],
-        )
-        .context("Failed to save ACP run")
-        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        tx.execute(
-            "
-            UPDATE acp_sessions
-            SET updated_at = ?, last_run_id = ?
-            WHERE session_id = ?
-            ",
-            params![run.updated_at.to_rfc3339(), run.run_id, run.session_id],
+    /// Check whether a conversation with exactly this ID exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lookup fails.
+    pub fn conversation_exists(&self, id: &str) -> Result<bool> {
+        let conn = self.connection()?;
+        conn.query_row(
+            "SELECT 1 FROM conversations WHERE id = ?",
+            params![id],
+            |_| Ok(()),
         )
-        .context("Failed to update ACP session last_run_id")
-        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        tx.commit()
-            .context("Failed to commit ACP run transaction")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-
-        Ok(())
+        .optional()
+        .map(|row| row.is_some())
+        .context("Failed to query conversation")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))
     }
 
-    /// Load an ACP run by identifier.
+    /// Attach a tag to a conversation.
+    ///
+    /// The tag is normalized (trimmed and lowercased). Adding a tag the
+    /// conversation already carries has no effect. Supports full UUID or
+    /// unique prefix matching.
     ///
     /// # Arguments
     ///
-    /// * `run_id` - ACP run identifier
+    /// * `id` - Full conversation ID or unique prefix
+    /// * `tag` - Tag to attach
     ///
     /// # Returns
     ///
-    /// Returns the stored ACP run when found.
+    /// Returns the normalized tag.
     ///
     /// # Errors
     ///
-    /// Returns an error if the run cannot be loaded or deserialized.
-    pub fn load_acp_run(&self, run_id: &str) -> Result<Option<StoredAcpRun>> {
-        let conn = self.open_connection()?;
+    /// Returns an error if the tag is empty, the conversation does not exist,
+    /// the prefix is ambiguous, or the insert fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::storage::SqliteStorage;
+    ///
+    /// let storage = SqliteStorage::new_with_path("/tmp/xzatoma_tag_example.db")?;
+    /// storage.save_conversation("tag-example", "Example", None, &[])?;
+    /// let tag = storage.add_conversation_tag("tag-example", "  Billing ")?;
+    /// assert_eq!(tag, "billing");
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn add_conversation_tag(&self, id: &str, tag: &str) -> Result<String> {
+        let tag = normalize_tag(tag)?;
+        let conn = self.connection()?;
+        let conversation_id = resolve_conversation_id(&conn, id)?;
 
-        let result = conn
-            .query_row(
-                "
-                SELECT
-                    run_id,
-                    session_id,
-                    conversation_id,
-                    mode,
-                    state,
-                    created_at,
-                    updated_at,
-                    completed_at,
-                    failure_reason,
-                    cancellation_reason,
-                    await_kind,
-                    await_detail,
-                    input_json,
-                    output_json,
-                    metadata_json
-                FROM acp_runs
-                WHERE run_id = ?
-                ",
-                params![run_id],
-                |row| {
-                    Ok(StoredAcpRun {
-                        run_id: row.get(0)?,
-                        session_id: row.get(1)?,
-                        conversation_id: row.get(2)?,
-                        mode: row.get(3)?,
-                        state: row.get(4)?,
-                        created_at: parse_rfc3339_to_utc(&row.get::<_, String>(5)?)
-                            .map_err(to_rusqlite_error)?,
-                        updated_at: parse_rfc3339_to_utc(&row.get::<_, String>(6)?)
-                            .map_err(to_rusqlite_error)?,
-                        completed_at: match row.get::<_, Option<String>>(7)? {
-                            Some(value) => {
-                                Some(parse_rfc3339_to_utc(&value).map_err(to_rusqlite_error)?)
-                            }
-                            None => None,
-                        },
-                        failure_reason: row.get(8)?,
-                        cancellation_reason: row.get(9)?,
-                        await_kind: row.get(10)?,
-                        await_detail: row.get(11)?,
-                        input_json: row.get(12)?,
-                        output_json: row.get(13)?,
-                        metadata: deserialize_metadata(&row.get::<_, String>(14)?)
-                            .map_err(to_rusqlite_error)?,
-                    })
-                },
+        conn.execute(
+            "INSERT OR IGNORE INTO conversation_tags (conversation_id, tag) VALUES (?, ?)",
+            params![conversation_id, tag],
+        )
+        .context("Failed to tag conversation")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        Ok(tag)
+    }
+
+    /// Remove a tag from a conversation.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Full conversation ID or unique prefix
+    /// * `tag` - Tag to remove; normalized before matching
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` when the conversation carried the tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tag is empty, the conversation does not exist,
+    /// the prefix is ambiguous, or the delete fails.
+    pub fn remove_conversation_tag(&self, id: &str, tag: &str) -> Result<bool> {
+        let tag = normalize_tag(tag)?;
+        let conn = self.connection()?;
+        let conversation_id = resolve_conversation_id(&conn, id)?;
+
+        let removed = conn
+            .execute(
+                "DELETE FROM conversation_tags WHERE conversation_id = ? AND tag = ?",
+                params![conversation_id, tag],
             )
-            .optional()
-            .context("Failed to load ACP run")
+            .context("Failed to untag conversation")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        Ok(result)
+        Ok(removed > 0)
     }
 
-    /// List persisted ACP runs for a session.
+    /// List the tags attached to a conversation.
     ///
     /// # Arguments
     ///
-    /// * `session_id` - ACP session identifier
+    /// * `id` - Full conversation ID or unique prefix
     ///
     /// # Returns
     ///
-    /// Returns runs ordered by creation time.
+    /// Returns the normalized tags sorted alphabetically.
     ///
     /// # Errors
     ///
-    /// Returns an error if the query fails.
-    pub fn list_acp_runs_for_session(&self, session_id: &str) -> Result<Vec<StoredAcpRun>> {
-        let conn = self.open_connection()?;
+    /// Returns an error if the conversation does not exist, the prefix is
+    /// ambiguous, or the query fails.
+    pub fn list_conversation_tags(&self, id: &str) -> Result<Vec<String>> {
+        let conn = self.connection()?;
+        let conversation_id = resolve_conversation_id(&conn, id)?;
+
         let mut stmt = conn
-            .prepare(
-                "
-                SELECT
-                    run_id,
-                    session_id,
-                    conversation_id,
-                    mode,
-                    state,
-                    created_at,
-                    updated_at,
-                    completed_at,
-                    failure_reason,
-                    cancellation_reason,
-                    await_kind,
-                    await_detail,
-                    input_json,
-                    output_json,
-                    metadata_json
-                FROM acp_runs
-                WHERE session_id = ?
-                ORDER BY created_at ASC
-                ",
-            )
-            .context("Failed to prepare ACP runs statement")
+            .prepare("SELECT tag FROM conversation_tags WHERE conversation_id = ? ORDER BY tag")
+            .context("Failed to prepare statement")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        let iter = stmt
-            .query_map(params![session_id], |row| {
-                Ok(StoredAcpRun {
-                    run_id: row.get(0)?,
-                    session_id: row.get(1)?,
-                    conversation_id: row.get(2)?,
-                    mode: row.get(3)?,
-                    state: row.get(4)?,
-                    created_at: parse_rfc3339_to_utc(&row.get::<_, String>(5)?)
-                        .map_err(to_rusqlite_error)?,
-                    updated_at: parse_rfc3339_to_utc(&row.get::<_, String>(6)?)
-                        .map_err(to_rusqlite_error)?,
-                    completed_at: match row.get::<_, Option<String>>(7)? {
-                        Some(value) => {
-                            Some(parse_rfc3339_to_utc(&value).map_err(to_rusqlite_error)?)
-                        }
-                        None => None,
-                    },
-                    failure_reason: row.get(8)?,
-                    cancellation_reason: row.get(9)?,
-                    await_kind: row.get(10)?,
-                    await_detail: row.get(11)?,
-                    input_json: row.get(12)?,
-                    output_json: row.get(13)?,
-                    metadata: deserialize_metadata(&row.get::<_, String>(14)?)
-                        .map_err(to_rusqlite_error)?,
-                })
-            })
-            .context("Failed to query ACP runs")
+        let tags = stmt
+            .query_map(params![conversation_id], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .context("Failed to query conversation tags")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        let mut runs = Vec::new();
-        for run in iter.flatten() {
-            runs.push(run);
-        }
-        Ok(runs)
+        Ok(tags)
     }
 
-    /// Save or replace ACP run events for a run.
+    /// Pin or unpin a conversation.
+    ///
+    /// Pinned conversations are never removed by
+    /// [`prune_old_sessions`](Self::prune_old_sessions). Supports full UUID,
+    /// session key, or unique prefix matching.
     ///
     /// # Arguments
     ///
-    /// * `run_id` - ACP run identifier
-    /// * `events` - Ordered ACP runtime events
+    /// * `id` - Full conversation ID, session key, or unique prefix
+    /// * `pinned` - Whether the conversation should be pinned
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` when a conversation matched `id`.
     ///
     /// # Errors
     ///
-    /// Returns an error if event persistence fails.
-    pub fn save_acp_run_events(&self, run_id: &str, events: &[StoredAcpRunEvent]) -> Result<()> {
-        let mut conn = self.open_connection()?;
-        let tx = conn
-            .transaction()
-            .context("Failed to start ACP event transaction")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+    /// Returns an error if the prefix is ambiguous or the update fails.
+    pub fn set_conversation_pinned(&self, id: &str, pinned: bool) -> Result<bool> {
+        let conn = self.connection()?;
 
-        tx.execute(
-            "DELETE FROM acp_run_events WHERE run_id = ?",
-            params![run_id],
-        )
-        .context("Failed to clear existing ACP events")
-        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let conversation_id = match find_conversation_id(&conn, id)? {
+            Some(conversation_id) => conversation_id,
+            None => return Ok(false),
+        };
 
-        for event in events {
-            tx.execute(
-                "
-                INSERT INTO acp_run_events (
-                    run_id,
-                    sequence,
-                    kind,
-                    created_at,
-                    payload_json,
-                    terminal
-                )
-                VALUES (?, ?, ?, ?, ?, ?)
-                ",
-                params![
-                    event.run_id,
-                    i64::try_from(event.sequence).map_err(|error| {
-                        XzatomaError::Storage(format!(
-                            "ACP event sequence overflow for run '{}': {}",
-                            event.run_id, error
-                        ))
-                    })?,
-                    event.kind,
-                    event.created_at.to_rfc3339(),
-                    event.payload_json,
-                    bool_to_sqlite(event.terminal),
-                ],
+        let updated = conn
+            .execute(
+                "UPDATE conversations SET pinned = ? WHERE id = ?",
+                params![bool_to_sqlite(pinned), conversation_id],
             )
-            .context("Failed to insert ACP event")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-        }
-
-        tx.commit()
-            .context("Failed to commit ACP event transaction")
+            .context("Failed to update conversation pin state")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        Ok(())
+        Ok(updated > 0)
     }
 
-    /// Load ACP run events for a run.
+    /// Record which messages of a conversation are pinned.
+    ///
+    /// Pinned messages are stored as a JSON array of message indices next to
+    /// the messages themselves. Supports full UUID, session key, or unique
+    /// prefix matching.
     ///
     /// # Arguments
     ///
-    /// * `run_id` - ACP run identifier
+    /// * `id` - Full conversation ID, session key, or unique prefix
+    /// * `indices` - Indices of the pinned messages
     ///
     /// # Returns
     ///
-    /// Returns persisted run events ordered by sequence.
+    /// Returns `true` when a conversation matched `id`.
     ///
     /// # Errors
     ///
-    /// Returns an error if event loading fails.
-    pub fn load_acp_run_events(&self, run_id: &str) -> Result<Vec<StoredAcpRunEvent>> {
-        let conn = self.open_connection()?;
-        let mut stmt = conn
-            .prepare(
-                "
-                SELECT
-                    run_id,
-                    sequence,
-                    kind,
-                    created_at,
-                    payload_json,
-                    terminal
-                FROM acp_run_events
-                WHERE run_id = ?
-                ORDER BY sequence ASC
-                ",
-            )
-            .context("Failed to prepare ACP event query")
+    /// Returns an error if the prefix matches more than one conversation or
+    /// the update fails.
+    pub fn set_pinned_messages(&self, id: &str, indices: &[usize]) -> Result<bool> {
+        let indices_json = serde_json::to_string(indices)
+            .context("Failed to serialize pinned messages")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        let iter = stmt
-            .query_map(params![run_id], |row| {
-                Ok(StoredAcpRunEvent {
-                    run_id: row.get(0)?,
-                    sequence: {
-                        let value: i64 = row.get(1)?;
-                        u64::try_from(value).map_err(|error| {
-                            to_rusqlite_error(XzatomaError::Storage(format!(
-                                "Invalid persisted ACP event sequence for run '{}': {}",
-                                run_id, error
-                            )))
-                        })?
-                    },
-                    kind: row.get(2)?,
-                    created_at: parse_rfc3339_to_utc(&row.get::<_, String>(3)?)
-                        .map_err(to_rusqlite_error)?,
-                    payload_json: row.get(4)?,
-                    terminal: sqlite_to_bool(row.get::<_, i64>(5)?),
-                })
-            })
-            .context("Failed to load ACP run events")
+        let conn = self.connection()?;
+
+        let conversation_id = match find_conversation_id(&conn, id)? {
+            Some(conversation_id) => conversation_id,
+            None => return Ok(false),
+        };
+
+        let updated = conn
+            .execute(
+                "UPDATE conversations SET pinned_messages = ? WHERE id = ?",
+                params![indices_json, conversation_id],
+            )
+            .context("Failed to update pinned messages")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        let mut events = Vec::new();
-        for event in iter.flatten() {
-            events.push(event);
-        }
-        Ok(events)
+        Ok(updated > 0)
     }
 
-    /// Save or update ACP await state.
+    /// Load the indices of the pinned messages of a conversation.
+    ///
+    /// Conversations saved before message pinning existed have no pinned
+    /// messages. Supports full UUID or prefix matching.
     ///
     /// # Arguments
     ///
-    /// * `await_state` - Persisted await state
+    /// * `id` - Full conversation ID or prefix
+    ///
+    /// # Returns
+    ///
+    /// Returns the pinned message indices, or an empty list when the
+    /// conversation does not exist.
     ///
     /// # Errors
     ///
-    /// Returns an error if the await state cannot be persisted.
-    pub fn save_acp_await_state(&self, await_state: &StoredAcpAwaitState) -> Result<()> {
-        let conn = self.open_connection()?;
-        conn.execute(
-            "
-            INSERT INTO acp_await_states (
-                run_id,
-                session_id,
-                kind,
-                detail,
-                created_at,
-                updated_at,
-                resumed_at,
-                resume_payload_json
-            )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
-            ON CONFLICT(run_id) DO UPDATE SET
-                session_id = excluded.session_id,
-                kind = excluded.kind,
-                detail = excluded.detail,
-                updated_at = excluded.updated_at,
-                resumed_at = excluded.resumed_at,
-                resume_payload_json = excluded.resume_payload_json
-            ",
-            params![
-                await_state.run_id,
-                await_state.session_id,
-                await_state.kind,
-                await_state.detail,
-                await_state.created_at.to_rfc3339(),
-                await_state.updated_at.to_rfc3339(),
-                await_state.resumed_at.map(|value| value.to_rfc3339()),
-                await_state.resume_payload_json,
-            ],
-        )
-        .context("Failed to save ACP await state")
-        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-        Ok(())
+    /// Returns an error if the lookup or deserialization fails.
+    pub fn load_pinned_messages(&self, id: &str) -> Result<Vec<usize>> {
+        let conn = self.connection()?;
+
+        let (condition, param) = id_condition(&conn, id)?;
+        let query = format!(
+            "SELECT pinned_messages FROM conversations WHERE {}",
+            condition
+        );
+
+        let indices_json: Option<String> = conn
+            .query_row(&query, params![param], |row| row.get(0))
+            .optional()
+            .context("Failed to query pinned messages")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        match indices_json {
+            Some(json) => serde_json::from_str(&json)
+                .context("Failed to deserialize pinned messages")
+                .map_err(|e| XzatomaError::Storage(e.to_string())),
+            None => Ok(Vec::new()),
+        }
     }
 
-    /// Load ACP await state for a run.
+    /// Record the working directory each message of a conversation was
+    /// added in.
+    ///
+    /// Directories are stored as a JSON array parallel to the messages, with
+    /// `null` for messages whose directory is unknown. Supports full UUID or
+    /// prefix matching.
     ///
     /// # Arguments
     ///
-    /// * `run_id` - ACP run identifier
+    /// * `id` - Full conversation ID or prefix
+    /// * `cwds` - Working directory of each message, in order
     ///
     /// # Returns
     ///
-    /// Returns the stored await state when present.
+    /// Returns `true` when at least one conversation matched `id`.
     ///
     /// # Errors
     ///
-    /// Returns an error if loading fails.
-    pub fn load_acp_await_state(&self, run_id: &str) -> Result<Option<StoredAcpAwaitState>> {
-        let conn = self.open_connection()?;
+    /// Returns an error if the update fails.
+    pub fn set_message_cwds(&self, id: &str, cwds: &[Option<PathBuf>]) -> Result<bool> {
+        let cwds_json = serde_json::to_string(cwds)
+            .context("Failed to serialize message directories")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        let result = conn
-            .query_row(
-                "
-                SELECT
-                    run_id,
-                    session_id,
-                    kind,
-                    detail,
-                    created_at,
-                    updated_at,
-                    resumed_at,
-                    resume_payload_json
-                FROM acp_await_states
-                WHERE run_id = ?
-                ",
-                params![run_id],
-                |row| {
-                    Ok(StoredAcpAwaitState {
-                        run_id: row.get(0)?,
-                        session_id: row.get(1)?,
-                        kind: row.get(2)?,
-                        detail: row.get(3)?,
-                        created_at: parse_rfc3339_to_utc(&row.get::<_, String>(4)?)
-                            .map_err(to_rusqlite_error)?,
-                        updated_at: parse_rfc3339_to_utc(&row.get::<_, String>(5)?)
-                            .map_err(to_rusqlite_error)?,
-                        resumed_at: match row.get::<_, Option<String>>(6)? {
-                            Some(value) => {
-                                Some(parse_rfc3339_to_utc(&value).map_err(to_rusqlite_error)?)
-                            }
-                            None => None,
-                        },
-                        resume_payload_json: row.get(7)?,
-                    })
-                },
-            )
+        let conn = self.connection()?;
+
+        let (condition, param) = id_condition(&conn, id)?;
+        let query = format!(
+            "UPDATE conversations SET message_cwds = ? WHERE {}",
+            condition
+        );
+
+        let updated = conn
+            .execute(&query, params![cwds_json, param])
+            .context("Failed to update message directories")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        Ok(updated > 0)
+    }
+
+    /// Load the working directory each message of a conversation was added in.
+    ///
+    /// Conversations saved before session working directories existed have
+    /// no recorded directories. Supports full UUID or prefix matching.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Full conversation ID or prefix
+    ///
+    /// # Returns
+    ///
+    /// Returns the directory of each message, or an empty list when the
+    /// conversation does not exist or has none recorded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lookup or deserialization fails.
+    pub fn load_message_cwds(&self, id: &str) -> Result<Vec<Option<PathBuf>>> {
+        let conn = self.connection()?;
+
+        let (condition, param) = id_condition(&conn, id)?;
+        let query = format!("SELECT message_cwds FROM conversations WHERE {}", condition);
+
+        let cwds_json: Option<String> = conn
+            .query_row(&query, params![param], |row| row.get(0))
             .optional()
-            .context("Failed to load ACP await state")
+            .context("Failed to query message directories")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        Ok(result)
+        match cwds_json {
+            Some(json) => serde_json::from_str(&json)
+                .context("Failed to deserialize message directories")
+                .map_err(|e| XzatomaError::Storage(e.to_string())),
+            None => Ok(Vec::new()),
+        }
     }
 
-    /// Save or update ACP cancellation state.
+    /// Resolve a conversation ID or prefix to the full stored ID.
     ///
     /// # Arguments
     ///
-    /// * `cancellation` - Persisted cancellation state
+    /// * `id` - Full conversation ID or prefix
+    ///
+    /// # Returns
+    ///
+    /// Returns the full ID of the first matching conversation, or `None` when
+    /// nothing matches.
     ///
     /// # Errors
     ///
-    /// Returns an error if the cancellation state cannot be saved.
-    pub fn save_acp_cancellation(&self, cancellation: &StoredAcpCancellation) -> Result<()> {
-        let conn = self.open_connection()?;
-        conn.execute(
-            "
-            INSERT INTO acp_cancellations (
-                run_id,
-                requested_at,
-                acknowledged_at,
-                completed_at,
-                reason,
-                acknowledged
-            )
-            VALUES (?, ?, ?, ?, ?, ?)
-            ON CONFLICT(run_id) DO UPDATE SET
-                requested_at = excluded.requested_at,
-                acknowledged_at = excluded.acknowledged_at,
-                completed_at = excluded.completed_at,
-                reason = excluded.reason,
-                acknowledged = excluded.acknowledged
-            ",
-            params![
-                cancellation.run_id,
-                cancellation.requested_at.to_rfc3339(),
-                cancellation.acknowledged_at.map(|value| value.to_rfc3339()),
-                cancellation.completed_at.map(|value| value.to_rfc3339()),
-                cancellation.reason,
-                bool_to_sqlite(cancellation.acknowledged),
-            ],
-        )
-        .context("Failed to save ACP cancellation")
-        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-        Ok(())
+    /// Returns an error if the lookup fails.
+    pub fn resolve_conversation_id(&self, id: &str) -> Result<Option<String>> {
+        let conn = self.connection()?;
+
+        let (condition, param) = id_condition(&conn, id)?;
+        let query = format!("SELECT id FROM conversations WHERE {}", condition);
+
+        conn.query_row(&query, params![param], |row| row.get(0))
+            .optional()
+            .context("Failed to resolve conversation id")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))
     }
 
-    /// Load ACP cancellation state for a run.
+    /// Record that a conversation is a replay of another conversation.
     ///
     /// # Arguments
     ///
-    /// * `run_id` - ACP run identifier
+    /// * `id` - Full ID of the replay conversation
+    /// * `original_id` - Full ID of the conversation that was replayed
     ///
     /// # Returns
     ///
-    /// Returns the stored cancellation state when present.
+    /// Returns `true` when the replay conversation exists.
     ///
     /// # Errors
     ///
-    /// Returns an error if loading fails.
-    pub fn load_acp_cancellation(&self, run_id: &str) -> Result<Option<StoredAcpCancellation>> {
-        let conn = self.open_connection()?;
+    /// Returns an error if the update fails.
+    pub fn set_replayed_from(&self, id: &str, original_id: &str) -> Result<bool> {
+        let conn = self.connection()?;
 
-        let result = conn
-            .query_row(
-                "
-                SELECT
-                    run_id,
-                    requested_at,
-                    acknowledged_at,
-                    completed_at,
-                    reason,
-                    acknowledged
-                FROM acp_cancellations
-                WHERE run_id = ?
-                ",
-                params![run_id],
-                |row| {
-                    Ok(StoredAcpCancellation {
-                        run_id: row.get(0)?,
-                        requested_at: parse_rfc3339_to_utc(&row.get::<_, String>(1)?)
-                            .map_err(to_rusqlite_error)?,
-                        acknowledged_at: match row.get::<_, Option<String>>(2)? {
-                            Some(value) => {
-                                Some(parse_rfc3339_to_utc(&value).map_err(to_rusqlite_error)?)
-                            }
-                            None => None,
-                        },
-                        completed_at: match row.get::<_, Option<String>>(3)? {
-                            Some(value) => {
-                                Some(parse_rfc3339_to_utc(&value).map_err(to_rusqlite_error)?)
-                            }
-                            None => None,
-                        },
-                        reason: row.get(4)?,
-                        acknowledged: sqlite_to_bool(row.get::<_, i64>(5)?),
-                    })
-                },
+        let updated = conn
+            .execute(
+                "UPDATE conversations SET replayed_from = ? WHERE id = ?",
+                params![original_id, id],
             )
+            .context("Failed to update replay link")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        Ok(updated > 0)
+    }
+
+    /// Load the ID of the conversation a replay was created from.
+    ///
+    /// Supports full UUID or prefix matching.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Full conversation ID or prefix
+    ///
+    /// # Returns
+    ///
+    /// Returns the original conversation ID, or `None` when the conversation
+    /// does not exist or is not a replay.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lookup fails.
+    pub fn load_replayed_from(&self, id: &str) -> Result<Option<String>> {
+        let conn = self.connection()?;
+
+        let (condition, param) = id_condition(&conn, id)?;
+        let query = format!(
+            "SELECT replayed_from FROM conversations WHERE {}",
+            condition
+        );
+
+        let replayed_from: Option<Option<String>> = conn
+            .query_row(&query, params![param], |row| row.get(0))
             .optional()
-            .context("Failed to load ACP cancellation")
+            .context("Failed to query replay link")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        Ok(result)
+        Ok(replayed_from.flatten())
     }
 
-    /// Persist a canonical ACP run and related durable session linkage.
+    /// Save or replace one recorded run turn.
     ///
-    /// This helper converts the canonical ACP runtime run shape into the durable
-    /// storage records used by the SQLite schema.
+    /// The turn is stored as zlib-compressed JSON in the `run_turns` table,
+    /// keyed by run ID and turn number, so saving a turn again replaces it.
     ///
     /// # Arguments
     ///
-    /// * `run` - Canonical ACP run
-    /// * `mode` - Effective runtime mode
-    /// * `conversation_id` - Optional mapped conversation identifier
+    /// * `turn` - Recorded turn to store
     ///
     /// # Errors
     ///
-    /// Returns an error if serialization or persistence fails.
+    /// Returns an error if the turn cannot be serialized or written.
     ///
     /// # Examples
     ///
     /// ```
-    /// use xzatoma::acp::{AcpMessage, AcpMessagePart, AcpRole, AcpRun, AcpRunCreateRequest, AcpRunId, AcpRunSession, AcpSessionId, AcpTextPart};
-    /// use xzatoma::acp::runtime::AcpRuntimeExecuteMode;
+    /// use chrono::Utc;
+    /// use xzatoma::providers::Message;
+    /// use xzatoma::storage::types::StoredRunTurn;
     /// use xzatoma::storage::SqliteStorage;
     ///
-    /// let storage = SqliteStorage::new_with_path("/tmp/persist_acp_run_example.db")?;
-    /// let session_id = AcpSessionId::new("session_123".to_string())?;
-    /// let session = AcpRunSession::new(session_id.clone())?;
-    /// let request = AcpRunCreateRequest::new(
-    ///     session_id,
-    ///     vec![AcpMessage::new(
-    ///         AcpRole::User,
-    ///         vec![AcpMessagePart::Text(AcpTextPart::new("hello".to_string()))],
-    ///     )?],
-    /// )?;
-    /// let run = AcpRun::new(AcpRunId::new("run_123".to_string())?, request, session)?;
-    ///
-    /// storage.persist_acp_run(&run, AcpRuntimeExecuteMode::Sync, None)?;
+    /// let storage = SqliteStorage::new_with_path("/tmp/xzatoma_run_turns_example.db")?;
+    /// storage.save_run_turn(&StoredRunTurn {
+    ///     run_id: "example-run".to_string(),
+    ///     turn: 1,
+    ///     started_at: Utc::now(),
+    ///     duration_ms: 5,
+    ///     model: "fake".to_string(),
+    ///     messages: vec![Message::user("hi")],
+    ///     tools: Vec::new(),
+    ///     response: None,
+    ///     error: None,
+    ///     tool_calls: Vec::new(),
+    ///     truncated: false,
+    /// })?;
+    /// assert_eq!(storage.load_run_turns("example-run")?.len(), 1);
     /// # Ok::<(), anyhow::Error>(())
     /// ```
-    pub fn persist_acp_run(
-        &self,
-        run: &AcpRun,
-        mode: AcpRuntimeExecuteMode,
-        conversation_id: Option<String>,
-    ) -> Result<()> {
-        let existing_session = self.load_acp_session(run.session.id.as_str())?;
-        let existing_run_count = existing_session
-            .as_ref()
-            .map(|session| session.run_count)
-            .unwrap_or_else(|| {
-                self.count_acp_runs_for_session(run.session.id.as_str())
-                    .unwrap_or(0)
-            });
-        let session = StoredAcpSession {
-            session_id: run.session.id.as_str().to_string(),
-            conversation_id: conversation_id.clone().or_else(|| {
-                existing_session
-                    .as_ref()
-                    .and_then(|session| session.conversation_id.clone())
-            }),
-            title: existing_session
-                .as_ref()
-                .and_then(|session| session.title.clone()),
-            created_at: existing_session
-                .as_ref()
-                .map(|session| session.created_at)
-                .unwrap_or(parse_rfc3339_to_utc(&run.session.created_at)?),
-            updated_at: parse_rfc3339_to_utc(&run.status.updated_at)?,
-            run_count: existing_run_count.max(if existing_session.is_some() {
-                existing_run_count
-            } else {
-                existing_run_count + 1
-            }),
-            last_run_id: Some(run.id.as_str().to_string()),
-            metadata: existing_session
-                .map(|session| session.metadata)
-                .unwrap_or_default(),
-        };
-        self.save_acp_session(&session)?;
-
-        let input_json = serde_json::to_string(&run.request.input)
-            .context("Failed to serialize ACP run input")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-        let output_json = serde_json::to_string(&run.output)
-            .context("Failed to serialize ACP run output")
+    pub fn save_run_turn(&self, turn: &StoredRunTurn) -> Result<()> {
+        let json = serde_json::to_vec(turn)
+            .context("Failed to serialize run turn")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let payload = compress_payload(&json)?;
 
-        let stored_run = StoredAcpRun {
-            run_id: run.id.as_str().to_string(),
-            session_id: run.session.id.as_str().to_string(),
-            conversation_id,
-            mode: mode.as_str().to_string(),
-            state: run.status.state.to_string(),
-            created_at: parse_rfc3339_to_utc(&run.status.created_at)?,
-            updated_at: parse_rfc3339_to_utc(&run.status.updated_at)?,
-            completed_at: match &run.status.completed_at {
-                Some(value) => Some(parse_rfc3339_to_utc(value)?),
-                None => None,
-            },
-            failure_reason: run.status.failure_reason.clone(),
-            cancellation_reason: run.status.cancellation_reason.clone(),
-            await_kind: run
-                .await_payload
-                .as_ref()
-                .map(|payload| payload.kind.clone()),
-            await_detail: run
-                .await_payload
-                .as_ref()
-                .map(|payload| payload.detail.clone()),
-            input_json,
-            output_json,
-            metadata: BTreeMap::new(),
-        };
-        self.save_acp_run(&stored_run)?;
-
-        if let Some(await_payload) = &run.await_payload {
-            let await_state = StoredAcpAwaitState {
-                run_id: run.id.as_str().to_string(),
-                session_id: run.session.id.as_str().to_string(),
-                kind: await_payload.kind.clone(),
-                detail: await_payload.detail.clone(),
-                created_at: parse_rfc3339_to_utc(&run.status.updated_at)?,
-                updated_at: parse_rfc3339_to_utc(&run.status.updated_at)?,
-                resumed_at: None,
-                resume_payload_json: None,
-            };
-            self.save_acp_await_state(&await_state)?;
-        }
+        let conn = self.connection()?;
+        conn.execute(
+            "
+            INSERT INTO run_turns (run_id, turn, created_at, payload, payload_bytes)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(run_id, turn) DO UPDATE SET
+                created_at = excluded.created_at,
+                payload = excluded.payload,
+                payload_bytes = excluded.payload_bytes
+            ",
+            params![
+                turn.run_id,
+                turn.turn,
+                turn.started_at.to_rfc3339(),
+                payload,
+                json.len() as i64
+            ],
+        )
+        .context("Failed to save run turn")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
         Ok(())
     }
 
-    /// Restore a canonical ACP run from persistent storage.
+    /// Load the recorded turns of a run, in turn order.
     ///
     /// # Arguments
     ///
-    /// * `run_id` - ACP run identifier
+    /// * `run_id` - Full run ID
     ///
     /// # Returns
     ///
-    /// Returns the restored canonical ACP run when found.
+    /// Returns the turns, or an empty list when the run was not recorded.
     ///
     /// # Errors
     ///
-    /// Returns an error if deserialization or reconstruction fails.
-    pub fn restore_acp_run(&self, run_id: &str) -> Result<Option<AcpRun>> {
-        let stored = match self.load_acp_run(run_id)? {
-            Some(value) => value,
-            None => return Ok(None),
-        };
-
-        let session_id = AcpSessionId::new(stored.session_id.clone())?;
-        let run_session = AcpRunSession {
-            id: session_id.clone(),
-            created_at: stored.created_at.to_rfc3339(),
-        };
+    /// Returns an error if the query fails or a turn cannot be decoded.
+    pub fn load_run_turns(&self, run_id: &str) -> Result<Vec<StoredRunTurn>> {
+        let conn = self.connection()?;
 
-        let input: Vec<crate::acp::AcpMessage> = serde_json::from_str(&stored.input_json)
-            .context("Failed to deserialize stored ACP input")
+        let mut stmt = conn
+            .prepare("SELECT payload FROM run_turns WHERE run_id = ? ORDER BY turn")
+            .context("Failed to prepare run turn query")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-        let output: AcpRunOutput = serde_json::from_str(&stored.output_json)
-            .context("Failed to deserialize stored ACP output")
+        let payloads = stmt
+            .query_map(params![run_id], |row| row.get::<_, Vec<u8>>(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .context("Failed to query run turns")
             .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-        let mut request = AcpRunCreateRequest::new(session_id, input)?;
-        request.metadata = BTreeMap::new();
-
-        let run_id = AcpRunId::new(stored.run_id.clone())?;
-        let state = parse_run_state(&stored.state)?;
-        let status = AcpRunStatus {
-            state,
-            created_at: stored.created_at.to_rfc3339(),
-            updated_at: stored.updated_at.to_rfc3339(),
-            completed_at: stored.completed_at.map(|value| value.to_rfc3339()),
-            failure_reason: stored.failure_reason.clone(),
-            cancellation_reason: stored.cancellation_reason.clone(),
-        };
-
-        let await_payload = match (&stored.await_kind, &stored.await_detail) {
-            (Some(kind), Some(detail)) => Some(AcpAwaitPayload::new(kind.clone(), detail.clone())),
-            _ => None,
-        };
-
-        Ok(Some(AcpRun {
-            id: run_id,
-            session: run_session,
-            request,
-            status,
-            output,
-            await_payload,
-        }))
+        payloads
+            .iter()
+            .map(|payload| {
+                let json = decompress_payload(payload)?;
+                serde_json::from_slice(&json)
+                    .context("Failed to decode run turn")
+                    .map_err(|e| XzatomaError::Storage(e.to_string()))
+            })
+            .collect()
     }
 
-    /// Restore persisted ACP runtime events for one run.
+    /// Resolve a recorded run ID or prefix to the full run ID.
     ///
     /// # Arguments
     ///
-    /// * `run_id` - ACP run identifier
+    /// * `id` - Full run ID or prefix
     ///
     /// # Returns
     ///
-    /// Returns restored runtime events ordered by sequence.
+    /// Returns the full ID of the first matching run, or `None` when nothing
+    /// matches.
     ///
     /// # Errors
     ///
-    /// Returns an error if event deserialization fails.
-    pub fn restore_acp_runtime_events(&self, run_id: &str) -> Result<Vec<AcpRuntimeEvent>> {
-        let stored_events = self.load_acp_run_events(run_id)?;
-        let mut restored = Vec::with_capacity(stored_events.len());
+    /// Returns an error if the lookup fails.
+    pub fn resolve_run_id(&self, id: &str) -> Result<Option<String>> {
+        let conn = self.connection()?;
 
-        for stored in stored_events {
-            let payload: Value = serde_json::from_str(&stored.payload_json)
-                .context("Failed to deserialize stored ACP event payload")
-                .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-            let event = AcpEvent {
-                kind: parse_event_kind(&stored.kind)?,
-                run_id: Some(stored.run_id.clone()),
-                created_at: stored.created_at.to_rfc3339(),
-                payload,
-            };
-            restored.push(AcpRuntimeEvent {
-                sequence: stored.sequence,
-                event,
-                terminal: stored.terminal,
-            });
+        conn.query_row(
+            "SELECT run_id FROM run_turns WHERE run_id = ?1 OR run_id LIKE ?2
+             ORDER BY run_id = ?1 DESC, created_at DESC LIMIT 1",
+            params![id, format!("{}%", id)],
+            |row| row.get(0),
+        )
+        .optional()
+        .context("Failed to resolve run id")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))
+    }
+
+    /// Append provider usage records to the usage log.
+    ///
+    /// All records are written in one transaction, retried if another
+    /// connection keeps the database busy.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - Records to append
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the records cannot be written; none are written in
+    /// that case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use xzatoma::storage::types::UsageRecord;
+    /// use xzatoma::storage::SqliteStorage;
+    ///
+    /// let storage = SqliteStorage::new_with_path("/tmp/xzatoma_usage_example.db")?;
+    /// let now = Utc::now();
+    /// storage.save_usage_records(&[UsageRecord {
+    ///     timestamp: now,
+    ///     provider: "openai".to_string(),
+    ///     model: "gpt-5-mini".to_string(),
+    ///     session_id: "example".to_string(),
+    ///     prompt_tokens: 100,
+    ///     completion_tokens: 20,
+    ///     estimated_cost: 0.001,
+    /// }])?;
+    /// let total = storage.usage_total(now, now + chrono::Duration::seconds(1))?;
+    /// assert!(total.requests >= 1);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn save_usage_records(&self, records: &[UsageRecord]) -> Result<()> {
+        if records.is_empty() {
+            return Ok(());
         }
+        let mut conn = self.connection()?;
+
+        retry_on_busy(|| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            {
+                let mut stmt = tx.prepare(
+                    "INSERT INTO usage_log (created_at, provider, model, session_id,
+                                            prompt_tokens, completion_tokens, estimated_cost)
+                     VALUES (?, ?, ?, ?, ?, ?, ?)",
+                )?;
+                for record in records {
+                    stmt.execute(params![
+                        usage_timestamp(record.timestamp),
+                        record.provider,
+                        record.model,
+                        record.session_id,
+                        record.prompt_tokens as i64,
+                        record.completion_tokens as i64,
+                        record.estimated_cost
+                    ])?;
+                }
+            }
+            tx.commit()
+        })
+        .context("Failed to save usage records")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))
+    }
 
-        Ok(restored)
+    /// Total usage of the requests made in `[start, end)`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn usage_total(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<UsageAggregate> {
+        let conn = self.connection()?;
+
+        conn.query_row(
+            "SELECT COUNT(*),
+                    COALESCE(SUM(prompt_tokens), 0),
+                    COALESCE(SUM(completion_tokens), 0),
+                    COALESCE(SUM(estimated_cost), 0.0)
+             FROM usage_log
+             WHERE created_at >= ?1 AND created_at < ?2",
+            params![usage_timestamp(start), usage_timestamp(end)],
+            |row| {
+                Ok(UsageAggregate {
+                    key: String::new(),
+                    requests: row.get::<_, i64>(0)? as u64,
+                    prompt_tokens: row.get::<_, i64>(1)? as u64,
+                    completion_tokens: row.get::<_, i64>(2)? as u64,
+                    estimated_cost: row.get(3)?,
+                })
+            },
+        )
+        .context("Failed to aggregate usage")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))
     }
 
-    /// Count persisted ACP runs for a session.
+    /// Usage of the requests made in `[start, end)`, grouped by model, day,
+    /// or session.
+    ///
+    /// Days are ordered chronologically; models and sessions by estimated
+    /// cost, highest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn usage_by(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        grouping: UsageGrouping,
+    ) -> Result<Vec<UsageAggregate>> {
+        let conn = self.connection()?;
+        let (key, order) = match grouping {
+            UsageGrouping::Model => ("model", "cost DESC, group_key"),
+            UsageGrouping::Day => ("date(created_at)", "group_key"),
+            UsageGrouping::Session => ("session_id", "cost DESC, group_key"),
+        };
+
+        conn.prepare(&format!(
+            "SELECT {} AS group_key,
+                    COUNT(*),
+                    SUM(prompt_tokens),
+                    SUM(completion_tokens),
+                    SUM(estimated_cost) AS cost
+             FROM usage_log
+             WHERE created_at >= ?1 AND created_at < ?2
+             GROUP BY group_key
+             ORDER BY {}",
+            key, order
+        ))
+        .and_then(|mut stmt| {
+            let rows = stmt.query_map(
+                params![usage_timestamp(start), usage_timestamp(end)],
+                |row| {
+                    Ok(UsageAggregate {
+                        key: row.get(0)?,
+                        requests: row.get::<_, i64>(1)? as u64,
+                        prompt_tokens: row.get::<_, i64>(2)? as u64,
+                        completion_tokens: row.get::<_, i64>(3)? as u64,
+                        estimated_cost: row.get(4)?,
+                    })
+                },
+            )?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .context("Failed to aggregate usage per group")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))
+    }
+
+    /// Remove conversations that exceed the configured retention limits.
+    ///
+    /// Limits are applied in order: conversations older than `max_age_days`
+    /// first, then the least recently updated conversations beyond
+    /// `max_sessions`, then the least recently updated conversations until the
+    /// estimated database size fits within `max_db_size_mb`. Pinned
+    /// conversations are never selected. After a real pass the database is
+    /// vacuumed when free pages make up a significant share of the file.
     ///
     /// # Arguments
     ///
-    /// * `session_id` - ACP session identifier
+    /// * `retention` - Retention limits to enforce
+    /// * `dry_run` - When true, report what would be removed without deleting
     ///
     /// # Returns
     ///
-    /// Returns the number of stored runs associated with the session.
+    /// Returns a report listing the selected conversations and database sizes.
     ///
     /// # Errors
     ///
-    /// Returns an error if the count query fails.
-    pub fn count_acp_runs_for_session(&self, session_id: &str) -> Result<usize> {
-        let conn = self.open_connection()?;
-        let count: i64 = conn
-            .query_row(
-                "SELECT COUNT(*) FROM acp_runs WHERE session_id = ?",
-                params![session_id],
-                |row| row.get(0),
-            )
-            .context("Failed to count ACP runs for session")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
-        usize::try_from(count)
-            .map_err(|e| XzatomaError::Storage(format!("Invalid ACP run count: {}", e)))
-    }
+    /// Returns an error if the candidates cannot be read or deletion fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::config::RetentionConfig;
+    /// use xzatoma::storage::SqliteStorage;
+    ///
+    /// let storage = SqliteStorage::new_with_path("/tmp/xzatoma_prune_example.db")?;
+    /// let retention = RetentionConfig {
+    ///     max_sessions: Some(100),
+    ///     ..RetentionConfig::default()
+    /// };
+    /// let report = storage.prune_old_sessions(&retention, true)?;
+    /// assert!(report.dry_run);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn prune_old_sessions(
+        &self,
+        retention: &RetentionConfig,
+        dry_run: bool,
+    ) -> Result<PruneReport> {
+        let mut conn = self.connection()?;
+
+        let candidates = load_retention_candidates(&conn)?;
+        let size_before_bytes = used_database_bytes(&conn)?;
+        let (removed, estimated_size_after) =
+            select_sessions_to_prune(&candidates, retention, size_before_bytes, Utc::now());
+
+        let mut report = PruneReport {
+            removed,
+            dry_run,
+            size_before_bytes,
+            size_after_bytes: estimated_size_after,
+            vacuumed: false,
+        };
 
-    fn open_connection(&self) -> Result<Connection> {
-        Connection::open(&self.db_path)
-            .context("Failed to open database")
-            .map_err(|e| XzatomaError::Storage(e.to_string()))
-    }
-}
+        if dry_run || report.removed.is_empty() {
+            return Ok(report);
+        }
 
-fn serialize_metadata(metadata: &BTreeMap<String, String>) -> Result<String> {
-    serde_json::to_string(metadata)
-        .context("Failed to serialize metadata")
-        .map_err(|e| XzatomaError::Storage(e.to_string()))
-}
+        let ids: Vec<&str> = report
+            .removed
+            .iter()
+            .map(|session| session.id.as_str())
+            .collect();
+
+        retry_on_busy(|| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            for id in &ids {
+                tx.execute(
+                    "DELETE FROM acp_stdio_sessions WHERE conversation_id = ?",
+                    params![id],
+                )?;
+                tx.execute("DELETE FROM conversations WHERE id = ?", params![id])?;
+            }
+            tx.commit()
+        })
+        .context("Failed to prune conversations")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
 
-fn deserialize_metadata(json_str: &str) -> Result<BTreeMap<String, String>> {
-    serde_json::from_str(json_str)
-        .context("Failed to deserialize metadata")
-        .map_err(|e| XzatomaError::Storage(e.to_string()))
-}
+        let (page_count, free_pages) = page_counts(&conn)?;
+        if page_count > 0 && free_pages as f64 / page_count as f64 >= VACUUM_FREE_PAGE_RATIO {
+            let vacuum = conn
+                .execute_batch("VACUUM")
+                .and_then(|_| conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(())));
+            match vacuum {
+                Ok(()) => report.vacuumed = true,
+                Err(e) => tracing::warn!("Failed to vacuum history database: {}", e),
+            }
+        }
 
-fn parse_rfc3339_to_utc(value: &str) -> Result<DateTime<Utc>> {
-    DateTime::parse_from_rfc3339(value)
-        .map(|value| value.with_timezone(&Utc))
-        .context("Failed to parse RFC 3339 timestamp")
-        .map_err(|e| XzatomaError::Storage(e.to_string()))
-}
+        report.size_after_bytes = used_database_bytes(&conn)?;
 
-fn bool_to_sqlite(value: bool) -> i64 {
-    if value {
-        1
-    } else {
-        0
+        Ok(report)
     }
-}
-
-fn sqlite_to_bool(value: i64) -> bool {
-    value != 0
-}
 
-fn to_rusqlite_error(error: crate::error::XzatomaError) -> rusqlite::Error {
-    rusqlite::Error::FromSqlConversionFailure(
-        0,
-        rusqlite::types::Type::Text,
-        Box::new(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            error.to_string(),
+    /// Run SQLite's `PRAGMA integrity_check` on the database.
+    ///
+    /// # Returns
+    ///
+    /// Returns the problems reported by SQLite; empty when the database is
+    /// intact.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the check cannot be run.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::storage::SqliteStorage;
+    ///
+    /// let storage = SqliteStorage::new_with_path("/tmp/xzatoma_integrity_example.db")?;
+    /// assert!(storage.integrity_check()?.is_empty());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn integrity_check(&self) -> Result<Vec<String>> {
+        let conn = self.connection()?;
+        let mut stmt = conn
+            .prepare("PRAGMA integrity_check")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let mut problems = Vec::new();
+        for row in rows {
+            let row = row.map_err(|e| XzatomaError::Storage(e.to_string()))?;
+            if row != "ok" {
+                problems.push(row);
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Aggregate usage statistics over stored conversations.
+    ///
+    /// All aggregation runs in SQL, using the JSON functions to count messages
+    /// and tool calls, so the conversations are never loaded into memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - Only include sessions updated at or after this time
+    /// * `limit` - Maximum rows for the per-day, per-week, tool, and
+    ///   longest-session rankings
+    ///
+    /// # Returns
+    ///
+    /// Returns the aggregated statistics.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any aggregation query fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::storage::SqliteStorage;
+    ///
+    /// let storage = SqliteStorage::new_with_path("/tmp/xzatoma_stats_example.db")?;
+    /// let stats = storage.history_stats(None, 10)?;
+    /// assert!(stats.per_day.len() <= 10);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn history_stats(
+        &self,
+        since: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<HistoryStats> {
+        let conn = self.connection()?;
+        let since_param = since.map(|since| since.to_rfc3339());
+        let limit = limit as i64;
+
+        let (total_sessions, total_messages, average_duration_minutes) = conn
+            .query_row(
+                "SELECT COUNT(*),
+                        COALESCE(SUM(json_array_length(messages)), 0),
+                        COALESCE(AVG((julianday(updated_at) - julianday(created_at)) * 1440.0), 0.0)
+                 FROM conversations
+                 WHERE ?1 IS NULL OR updated_at >= ?1",
+                params![since_param],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)? as u64,
+                        row.get::<_, i64>(1)? as u64,
+                        row.get::<_, f64>(2)?,
+                    ))
+                },
+            )
+            .context("Failed to aggregate conversation totals")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let per_day = query_period_usage(&conn, "date(updated_at)", &since_param, limit)?;
+        let per_week =
+            query_period_usage(&conn, "strftime('%G-W%V', updated_at)", &since_param, limit)?;
+
+        let per_model = conn
+            .prepare(
+                "SELECT COALESCE(model, 'unknown') AS model_name,
+                        COUNT(*),
+                        SUM(json_array_length(c.messages)) AS message_total,
+                        SUM((SELECT COALESCE(SUM((length(json_extract(m.value, '$.content')) + 3) / 4), 0)
+                             FROM json_each(c.messages) AS m))
+                 FROM conversations AS c
+                 WHERE ?1 IS NULL OR c.updated_at >= ?1
+                 GROUP BY model_name
+                 ORDER BY message_total DESC, model_name",
+            )
+            .and_then(|mut stmt| {
+                let rows = stmt.query_map(params![since_param], |row| {
+                    Ok(ModelUsage {
+                        model: row.get(0)?,
+                        sessions: row.get::<_, i64>(1)? as u64,
+                        messages: row.get::<_, i64>(2)? as u64,
+                        estimated_tokens: row.get::<_, i64>(3)? as u64,
+                    })
+                })?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .context("Failed to aggregate usage per model")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let top_tools = conn
+            .prepare(
+                "SELECT json_extract(call.value, '$.function.name') AS tool, COUNT(*) AS calls
+                 FROM conversations AS c,
+                      json_each(c.messages) AS m,
+                      json_each(m.value, '$.tool_calls') AS call
+                 WHERE (?1 IS NULL OR c.updated_at >= ?1) AND tool IS NOT NULL
+                 GROUP BY tool
+                 ORDER BY calls DESC, tool
+                 LIMIT ?2",
+            )
+            .and_then(|mut stmt| {
+                let rows = stmt.query_map(params![since_param, limit], |row| {
+                    Ok(ToolUsage {
+                        tool: row.get(0)?,
+                        calls: row.get::<_, i64>(1)? as u64,
+                    })
+                })?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .context("Failed to aggregate tool usage")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let longest_sessions = conn
+            .prepare(
+                "SELECT id, title, json_array_length(messages) AS message_total,
+                        (julianday(updated_at) - julianday(created_at)) * 1440.0
+                 FROM conversations
+                 WHERE ?1 IS NULL OR updated_at >= ?1
+                 ORDER BY message_total DESC, updated_at DESC
+                 LIMIT ?2",
+            )
+            .and_then(|mut stmt| {
+                let rows = stmt.query_map(params![since_param, limit], |row| {
+                    Ok(SessionLength {
+                        id: row.get(0)?,
+                        title: row.get(1)?,
+                        messages: row.get::<_, i64>(2)? as u64,
+                        duration_minutes: row.get(3)?,
+                    })
+                })?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .context("Failed to rank sessions by length")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let average_messages = if total_sessions == 0 {
+            0.0
+        } else {
+            total_messages as f64 / total_sessions as f64
+        };
+
+        Ok(HistoryStats {
+            since,
+            total_sessions,
+            total_messages,
+            average_messages,
+            average_duration_minutes,
+            per_day,
+            per_week,
+            per_model,
+            top_tools,
+            longest_sessions,
+        })
+    }
+
+    /// Save or update an ACP stdio session mapping.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - ACP stdio session mapping to persist
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mapping cannot be serialized or saved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use xzatoma::storage::{PublicStoredAcpStdioSession, SqliteStorage};
+    ///
+    /// let storage = SqliteStorage::new_with_path("/tmp/acp_stdio_session_save.db")?;
+    /// let now = Utc::now();
+    /// storage.save_conversation(
+    ///     "conversation-1",
+    ///     "New Conversation",
+    ///     Some("llama3.2:latest"),
+    ///     &[xzatoma::providers::Message::user("hello")],
+    /// )?;
+    ///
+    /// storage.save_acp_stdio_session(&PublicStoredAcpStdioSession {
+    ///     session_id: "xzatoma-session-1".to_string(),
+    ///     workspace_root: "/tmp/workspace".to_string(),
+    ///     conversation_id: "conversation-1".to_string(),
+    ///     provider_type: "ollama".to_string(),
+    ///     model: Some("llama3.2:latest".to_string()),
+    ///     created_at: now,
+    ///     updated_at: now,
+    ///     metadata: Default::default(),
+    /// })?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn save_acp_stdio_session(&self, session: &StoredAcpStdioSession) -> Result<()> {
+        let conn = self.connection()?;
+
+        let metadata_json = serde_json::to_string(&session.metadata)
+            .context("Failed to serialize ACP stdio session metadata")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        conn.execute(
+            "INSERT INTO acp_stdio_sessions (
+                session_id,
+                workspace_root,
+                conversation_id,
+                provider_type,
+                model,
+                created_at,
+                updated_at,
+                metadata_json
+             )
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(session_id) DO UPDATE SET
+                workspace_root = excluded.workspace_root,
+                conversation_id = excluded.conversation_id,
+                provider_type = excluded.provider_type,
+                model = excluded.model,
+                updated_at = excluded.updated_at,
+                metadata_json = excluded.metadata_json",
+            params![
+                session.session_id,
+                session.workspace_root,
+                session.conversation_id,
+                session.provider_type,
+                session.model,
+                session.created_at.to_rfc3339(),
+                session.updated_at.to_rfc3339(),
+                metadata_json,
+            ],
+        )
+        .context("Failed to save ACP stdio session")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Load the most recently updated ACP stdio session for a workspace root.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace_root` - Normalized workspace root to search for
+    ///
+    /// # Returns
+    ///
+    /// Returns the newest matching ACP stdio session mapping, if present.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if querying or deserializing the mapping fails.
+    pub fn load_latest_acp_stdio_session_by_workspace_root(
+        &self,
+        workspace_root: &str,
+    ) -> Result<Option<StoredAcpStdioSession>> {
+        let conn = self.connection()?;
+
+        let row = conn
+            .query_row(
+                "SELECT session_id, workspace_root, conversation_id, provider_type, model, created_at, updated_at, metadata_json
+                 FROM acp_stdio_sessions
+                 WHERE workspace_root = ?
+                 ORDER BY updated_at DESC
+                 LIMIT 1",
+                params![workspace_root],
+                stored_acp_stdio_session_from_row,
+            )
+            .optional()
+            .context("Failed to query ACP stdio session by workspace root")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        row.transpose()
+    }
+
+    /// Load an ACP stdio session mapping by ACP session ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - ACP stdio session identifier
+    ///
+    /// # Returns
+    ///
+    /// Returns the matching ACP stdio session mapping, if present.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if querying or deserializing the mapping fails.
+    pub fn load_acp_stdio_session_by_session_id(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<StoredAcpStdioSession>> {
+        let conn = self.connection()?;
+
+        let row = conn
+            .query_row(
+                "SELECT session_id, workspace_root, conversation_id, provider_type, model, created_at, updated_at, metadata_json
+                 FROM acp_stdio_sessions
+                 WHERE session_id = ?",
+                params![session_id],
+                stored_acp_stdio_session_from_row,
+            )
+            .optional()
+            .context("Failed to query ACP stdio session by session ID")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        row.transpose()
+    }
+
+    /// Update the last activity timestamp for an ACP stdio session.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - ACP stdio session identifier
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the update fails.
+    pub fn touch_acp_stdio_session(&self, session_id: &str) -> Result<()> {
+        let conn = self.connection()?;
+
+        conn.execute(
+            "UPDATE acp_stdio_sessions SET updated_at = ? WHERE session_id = ?",
+            params![Utc::now().to_rfc3339(), session_id],
+        )
+        .context("Failed to update ACP stdio session activity")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Prune ACP stdio session mappings older than a cutoff timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `older_than` - Delete mappings whose `updated_at` value is older than
+    ///   this timestamp
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of deleted mappings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if pruning fails.
+    pub fn prune_acp_stdio_sessions_older_than(&self, older_than: DateTime<Utc>) -> Result<usize> {
+        let conn = self.connection()?;
+
+        let deleted = conn
+            .execute(
+                "DELETE FROM acp_stdio_sessions WHERE updated_at < ?",
+                params![older_than.to_rfc3339()],
+            )
+            .context("Failed to prune ACP stdio sessions")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        Ok(deleted)
+    }
+
+    /// Save or update an ACP session summary.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - Persisted ACP session metadata
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ACP session cannot be saved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use xzatoma::storage::{types::StoredAcpSession, SqliteStorage};
+    ///
+    /// let storage = SqliteStorage::new_with_path("/tmp/acp_session_save_example.db")?;
+    /// let now = Utc::now();
+    /// let session = StoredAcpSession {
+    ///     session_id: "session_123".to_string(),
+    ///     conversation_id: None,
+    ///     title: Some("Example".to_string()),
+    ///     created_at: now,
+    ///     updated_at: now,
+    ///     run_count: 0,
+    ///     last_run_id: None,
+    ///     metadata: Default::default(),
+    /// };
+    ///
+    /// storage.save_acp_session(&session)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn save_acp_session(&self, session: &StoredAcpSession) -> Result<()> {
+        let mut conn = self.connection()?;
+        let metadata_json = serialize_metadata(&session.metadata)?;
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .context("Failed to start ACP session transaction")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        tx.execute(
+            "
+            INSERT INTO acp_sessions (
+                session_id,
+                conversation_id,
+                title,
+                created_at,
+                updated_at,
+                metadata_json,
+                last_run_id
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(session_id) DO UPDATE SET
+                conversation_id = excluded.conversation_id,
+                title = excluded.title,
+                updated_at = excluded.updated_at,
+                metadata_json = excluded.metadata_json,
+                last_run_id = excluded.last_run_id
+            ",
+            params![
+                session.session_id,
+                session.conversation_id,
+                session.title,
+                session.created_at.to_rfc3339(),
+                session.updated_at.to_rfc3339(),
+                metadata_json,
+                session.last_run_id
+            ],
+        )
+        .context("Failed to save ACP session")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        tx.commit()
+            .context("Failed to commit ACP session transaction")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Load an ACP session by identifier.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - ACP session identifier
+    ///
+    /// # Returns
+    ///
+    /// Returns the stored ACP session when found.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session cannot be loaded or deserialized.
+    pub fn load_acp_session(&self, session_id: &str) -> Result<Option<StoredAcpSession>> {
+        let conn = self.connection()?;
+
+        let result = conn
+            .query_row(
+                "
+                SELECT
+                    session_id,
+                    conversation_id,
+                    title,
+                    created_at,
+                    updated_at,
+                    metadata_json,
+                    last_run_id
+                FROM acp_sessions
+                WHERE session_id = ?
+                ",
+                params![session_id],
+                |row| {
+                    let session_id: String = row.get(0)?;
+                    let conversation_id: Option<String> = row.get(1)?;
+                    let title: Option<String> = row.get(2)?;
+                    let created_at: String = row.get(3)?;
+                    let updated_at: String = row.get(4)?;
+                    let metadata_json: String = row.get(5)?;
+                    let last_run_id: Option<String> = row.get(6)?;
+                    Ok((
+                        session_id,
+                        conversation_id,
+                        title,
+                        created_at,
+                        updated_at,
+                        metadata_json,
+                        last_run_id,
+                    ))
+                },
+            )
+            .optional()
+            .context("Failed to load ACP session")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        // Release the connection before counting runs, which locks it again.
+        drop(conn);
+
+        match result {
+            Some((
+                session_id,
+                conversation_id,
+                title,
+                created_at,
+                updated_at,
+                metadata_json,
+                last_run_id,
+            )) => {
+                let created_at = parse_rfc3339_to_utc(&created_at)?;
+                let updated_at = parse_rfc3339_to_utc(&updated_at)?;
+                let metadata = deserialize_metadata(&metadata_json)?;
+                let run_count = self.count_acp_runs_for_session(&session_id)?;
+
+                Ok(Some(StoredAcpSession {
+                    session_id,
+                    conversation_id,
+                    title,
+                    created_at,
+                    updated_at,
+                    run_count,
+                    last_run_id,
+                    metadata,
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Save or update an ACP run record.
+    ///
+    /// # Arguments
+    ///
+    /// * `run` - Persisted ACP run summary
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the run cannot be persisted.
+    pub fn save_acp_run(&self, run: &StoredAcpRun) -> Result<()> {
+        let mut conn = self.connection()?;
+        let metadata_json = serialize_metadata(&run.metadata)?;
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .context("Failed to start ACP run transaction")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        tx.execute(
+            "
+            INSERT INTO acp_runs (
+                run_id,
+                session_id,
+                conversation_id,
+                mode,
+                state,
+                created_at,
+                updated_at,
+                completed_at,
+                failure_reason,
+                cancellation_reason,
+                await_kind,
+                await_detail,
+                input_json,
+                output_json,
+                metadata_json
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(run_id) DO UPDATE SET
+                session_id = excluded.session_id,
+                conversation_id = excluded.conversation_id,
+                mode = excluded.mode,
+                state = excluded.state,
+                updated_at = excluded.updated_at,
+                completed_at = excluded.completed_at,
+                failure_reason = excluded.failure_reason,
+                cancellation_reason = excluded.cancellation_reason,
+                await_kind = excluded.await_kind,
+                await_detail = excluded.await_detail,
+                input_json = excluded.input_json,
+                output_json = excluded.output_json,
+                metadata_json = excluded.metadata_json
+            ",
+            params![
+                run.run_id,
+                run.session_id,
+                run.conversation_id,
+                run.mode,
+                run.state,
+                run.created_at.to_rfc3339(),
+                run.updated_at.to_rfc3339(),
+                run.completed_at.map(|value| value.to_rfc3339()),
+                run.failure_reason,
+                run.cancellation_reason,
+                run.await_kind,
+                run.await_detail,
+                run.input_json,
+                run.output_json,
+                metadata_json,
+            ],
+        )
+        .context("Failed to save ACP run")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        tx.execute(
+            "
+            UPDATE acp_sessions
+            SET updated_at = ?, last_run_id = ?
+            WHERE session_id = ?
+            ",
+            params![run.updated_at.to_rfc3339(), run.run_id, run.session_id],
+        )
+        .context("Failed to update ACP session last_run_id")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        tx.commit()
+            .context("Failed to commit ACP run transaction")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Load an ACP run by identifier.
+    ///
+    /// # Arguments
+    ///
+    /// * `run_id` - ACP run identifier
+    ///
+    /// # Returns
+    ///
+    /// Returns the stored ACP run when found.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the run cannot be loaded or deserialized.
+    pub fn load_acp_run(&self, run_id: &str) -> Result<Option<StoredAcpRun>> {
+        let conn = self.connection()?;
+
+        let result = conn
+            .query_row(
+                "
+                SELECT
+                    run_id,
+                    session_id,
+                    conversation_id,
+                    mode,
+                    state,
+                    created_at,
+                    updated_at,
+                    completed_at,
+                    failure_reason,
+                    cancellation_reason,
+                    await_kind,
+                    await_detail,
+                    input_json,
+                    output_json,
+                    metadata_json
+                FROM acp_runs
+                WHERE run_id = ?
+                ",
+                params![run_id],
+                |row| {
+                    Ok(StoredAcpRun {
+                        run_id: row.get(0)?,
+                        session_id: row.get(1)?,
+                        conversation_id: row.get(2)?,
+                        mode: row.get(3)?,
+                        state: row.get(4)?,
+                        created_at: parse_rfc3339_to_utc(&row.get::<_, String>(5)?)
+                            .map_err(to_rusqlite_error)?,
+                        updated_at: parse_rfc3339_to_utc(&row.get::<_, String>(6)?)
+                            .map_err(to_rusqlite_error)?,
+                        completed_at: match row.get::<_, Option<String>>(7)? {
+                            Some(value) => {
+                                Some(parse_rfc3339_to_utc(&value).map_err(to_rusqlite_error)?)
+                            }
+                            None => None,
+                        },
+                        failure_reason: row.get(8)?,
+                        cancellation_reason: row.get(9)?,
+                        await_kind: row.get(10)?,
+                        await_detail: row.get(11)?,
+                        input_json: row.get(12)?,
+                        output_json: row.get(13)?,
+                        metadata: deserialize_metadata(&row.get::<_, String>(14)?)
+                            .map_err(to_rusqlite_error)?,
+                    })
+                },
+            )
+            .optional()
+            .context("Failed to load ACP run")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        Ok(result)
+    }
+
+    /// List persisted ACP runs for a session.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - ACP session identifier
+    ///
+    /// # Returns
+    ///
+    /// Returns runs ordered by creation time.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn list_acp_runs_for_session(&self, session_id: &str) -> Result<Vec<StoredAcpRun>> {
+        let conn = self.connection()?;
+        let mut stmt = conn
+            .prepare(
+                "
+                SELECT
+                    run_id,
+                    session_id,
+                    conversation_id,
+                    mode,
+                    state,
+                    created_at,
+                    updated_at,
+                    completed_at,
+                    failure_reason,
+                    cancellation_reason,
+                    await_kind,
+                    await_detail,
+                    input_json,
+                    output_json,
+                    metadata_json
+                FROM acp_runs
+                WHERE session_id = ?
+                ORDER BY created_at ASC
+                ",
+            )
+            .context("Failed to prepare ACP runs statement")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let iter = stmt
+            .query_map(params![session_id], |row| {
+                Ok(StoredAcpRun {
+                    run_id: row.get(0)?,
+                    session_id: row.get(1)?,
+                    conversation_id: row.get(2)?,
+                    mode: row.get(3)?,
+                    state: row.get(4)?,
+                    created_at: parse_rfc3339_to_utc(&row.get::<_, String>(5)?)
+                        .map_err(to_rusqlite_error)?,
+                    updated_at: parse_rfc3339_to_utc(&row.get::<_, String>(6)?)
+                        .map_err(to_rusqlite_error)?,
+                    completed_at: match row.get::<_, Option<String>>(7)? {
+                        Some(value) => {
+                            Some(parse_rfc3339_to_utc(&value).map_err(to_rusqlite_error)?)
+                        }
+                        None => None,
+                    },
+                    failure_reason: row.get(8)?,
+                    cancellation_reason: row.get(9)?,
+                    await_kind: row.get(10)?,
+                    await_detail: row.get(11)?,
+                    input_json: row.get(12)?,
+                    output_json: row.get(13)?,
+                    metadata: deserialize_metadata(&row.get::<_, String>(14)?)
+                        .map_err(to_rusqlite_error)?,
+                })
+            })
+            .context("Failed to query ACP runs")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let mut runs = Vec::new();
+        for run in iter.flatten() {
+            runs.push(run);
+        }
+        Ok(runs)
+    }
+
+    /// Save or replace ACP run events for a run.
+    ///
+    /// # Arguments
+    ///
+    /// * `run_id` - ACP run identifier
+    /// * `events` - Ordered ACP runtime events
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if event persistence fails.
+    pub fn save_acp_run_events(&self, run_id: &str, events: &[StoredAcpRunEvent]) -> Result<()> {
+        let mut conn = self.connection()?;
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .context("Failed to start ACP event transaction")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        tx.execute(
+            "DELETE FROM acp_run_events WHERE run_id = ?",
+            params![run_id],
+        )
+        .context("Failed to clear existing ACP events")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        for event in events {
+            tx.execute(
+                "
+                INSERT INTO acp_run_events (
+                    run_id,
+                    sequence,
+                    kind,
+                    created_at,
+                    payload_json,
+                    terminal
+                )
+                VALUES (?, ?, ?, ?, ?, ?)
+                ",
+                params![
+                    event.run_id,
+                    i64::try_from(event.sequence).map_err(|error| {
+                        XzatomaError::Storage(format!(
+                            "ACP event sequence overflow for run '{}': {}",
+                            event.run_id, error
+                        ))
+                    })?,
+                    event.kind,
+                    event.created_at.to_rfc3339(),
+                    event.payload_json,
+                    bool_to_sqlite(event.terminal),
+                ],
+            )
+            .context("Failed to insert ACP event")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        }
+
+        tx.commit()
+            .context("Failed to commit ACP event transaction")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Load ACP run events for a run.
+    ///
+    /// # Arguments
+    ///
+    /// * `run_id` - ACP run identifier
+    ///
+    /// # Returns
+    ///
+    /// Returns persisted run events ordered by sequence.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if event loading fails.
+    pub fn load_acp_run_events(&self, run_id: &str) -> Result<Vec<StoredAcpRunEvent>> {
+        let conn = self.connection()?;
+        let mut stmt = conn
+            .prepare(
+                "
+                SELECT
+                    run_id,
+                    sequence,
+                    kind,
+                    created_at,
+                    payload_json,
+                    terminal
+                FROM acp_run_events
+                WHERE run_id = ?
+                ORDER BY sequence ASC
+                ",
+            )
+            .context("Failed to prepare ACP event query")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let iter = stmt
+            .query_map(params![run_id], |row| {
+                Ok(StoredAcpRunEvent {
+                    run_id: row.get(0)?,
+                    sequence: {
+                        let value: i64 = row.get(1)?;
+                        u64::try_from(value).map_err(|error| {
+                            to_rusqlite_error(XzatomaError::Storage(format!(
+                                "Invalid persisted ACP event sequence for run '{}': {}",
+                                run_id, error
+                            )))
+                        })?
+                    },
+                    kind: row.get(2)?,
+                    created_at: parse_rfc3339_to_utc(&row.get::<_, String>(3)?)
+                        .map_err(to_rusqlite_error)?,
+                    payload_json: row.get(4)?,
+                    terminal: sqlite_to_bool(row.get::<_, i64>(5)?),
+                })
+            })
+            .context("Failed to load ACP run events")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let mut events = Vec::new();
+        for event in iter.flatten() {
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    /// Save or update ACP await state.
+    ///
+    /// # Arguments
+    ///
+    /// * `await_state` - Persisted await state
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the await state cannot be persisted.
+    pub fn save_acp_await_state(&self, await_state: &StoredAcpAwaitState) -> Result<()> {
+        let conn = self.connection()?;
+        conn.execute(
+            "
+            INSERT INTO acp_await_states (
+                run_id,
+                session_id,
+                kind,
+                detail,
+                created_at,
+                updated_at,
+                resumed_at,
+                resume_payload_json
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(run_id) DO UPDATE SET
+                session_id = excluded.session_id,
+                kind = excluded.kind,
+                detail = excluded.detail,
+                updated_at = excluded.updated_at,
+                resumed_at = excluded.resumed_at,
+                resume_payload_json = excluded.resume_payload_json
+            ",
+            params![
+                await_state.run_id,
+                await_state.session_id,
+                await_state.kind,
+                await_state.detail,
+                await_state.created_at.to_rfc3339(),
+                await_state.updated_at.to_rfc3339(),
+                await_state.resumed_at.map(|value| value.to_rfc3339()),
+                await_state.resume_payload_json,
+            ],
+        )
+        .context("Failed to save ACP await state")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Load ACP await state for a run.
+    ///
+    /// # Arguments
+    ///
+    /// * `run_id` - ACP run identifier
+    ///
+    /// # Returns
+    ///
+    /// Returns the stored await state when present.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if loading fails.
+    pub fn load_acp_await_state(&self, run_id: &str) -> Result<Option<StoredAcpAwaitState>> {
+        let conn = self.connection()?;
+
+        let result = conn
+            .query_row(
+                "
+                SELECT
+                    run_id,
+                    session_id,
+                    kind,
+                    detail,
+                    created_at,
+                    updated_at,
+                    resumed_at,
+                    resume_payload_json
+                FROM acp_await_states
+                WHERE run_id = ?
+                ",
+                params![run_id],
+                |row| {
+                    Ok(StoredAcpAwaitState {
+                        run_id: row.get(0)?,
+                        session_id: row.get(1)?,
+                        kind: row.get(2)?,
+                        detail: row.get(3)?,
+                        created_at: parse_rfc3339_to_utc(&row.get::<_, String>(4)?)
+                            .map_err(to_rusqlite_error)?,
+                        updated_at: parse_rfc3339_to_utc(&row.get::<_, String>(5)?)
+                            .map_err(to_rusqlite_error)?,
+                        resumed_at: match row.get::<_, Option<String>>(6)? {
+                            Some(value) => {
+                                Some(parse_rfc3339_to_utc(&value).map_err(to_rusqlite_error)?)
+                            }
+                            None => None,
+                        },
+                        resume_payload_json: row.get(7)?,
+                    })
+                },
+            )
+            .optional()
+            .context("Failed to load ACP await state")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        Ok(result)
+    }
+
+    /// Save or update ACP cancellation state.
+    ///
+    /// # Arguments
+    ///
+    /// * `cancellation` - Persisted cancellation state
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cancellation state cannot be saved.
+    pub fn save_acp_cancellation(&self, cancellation: &StoredAcpCancellation) -> Result<()> {
+        let conn = self.connection()?;
+        conn.execute(
+            "
+            INSERT INTO acp_cancellations (
+                run_id,
+                requested_at,
+                acknowledged_at,
+                completed_at,
+                reason,
+                acknowledged
+            )
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(run_id) DO UPDATE SET
+                requested_at = excluded.requested_at,
+                acknowledged_at = excluded.acknowledged_at,
+                completed_at = excluded.completed_at,
+                reason = excluded.reason,
+                acknowledged = excluded.acknowledged
+            ",
+            params![
+                cancellation.run_id,
+                cancellation.requested_at.to_rfc3339(),
+                cancellation.acknowledged_at.map(|value| value.to_rfc3339()),
+                cancellation.completed_at.map(|value| value.to_rfc3339()),
+                cancellation.reason,
+                bool_to_sqlite(cancellation.acknowledged),
+            ],
+        )
+        .context("Failed to save ACP cancellation")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Load ACP cancellation state for a run.
+    ///
+    /// # Arguments
+    ///
+    /// * `run_id` - ACP run identifier
+    ///
+    /// # Returns
+    ///
+    /// Returns the stored cancellation state when present.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if loading fails.
+    pub fn load_acp_cancellation(&self, run_id: &str) -> Result<Option<StoredAcpCancellation>> {
+        let conn = self.connection()?;
+
+        let result = conn
+            .query_row(
+                "
+                SELECT
+                    run_id,
+                    requested_at,
+                    acknowledged_at,
+                    completed_at,
+                    reason,
+                    acknowledged
+                FROM acp_cancellations
+                WHERE run_id = ?
+                ",
+                params![run_id],
+                |row| {
+                    Ok(StoredAcpCancellation {
+                        run_id: row.get(0)?,
+                        requested_at: parse_rfc3339_to_utc(&row.get::<_, String>(1)?)
+                            .map_err(to_rusqlite_error)?,
+                        acknowledged_at: match row.get::<_, Option<String>>(2)? {
+                            Some(value) => {
+                                Some(parse_rfc3339_to_utc(&value).map_err(to_rusqlite_error)?)
+                            }
+                            None => None,
+                        },
+                        completed_at: match row.get::<_, Option<String>>(3)? {
+                            Some(value) => {
+                                Some(parse_rfc3339_to_utc(&value).map_err(to_rusqlite_error)?)
+                            }
+                            None => None,
+                        },
+                        reason: row.get(4)?,
+                        acknowledged: sqlite_to_bool(row.get::<_, i64>(5)?),
+                    })
+                },
+            )
+            .optional()
+            .context("Failed to load ACP cancellation")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        Ok(result)
+    }
+
+    /// Persist a canonical ACP run and related durable session linkage.
+    ///
+    /// This helper converts the canonical ACP runtime run shape into the durable
+    /// storage records used by the SQLite schema.
+    ///
+    /// # Arguments
+    ///
+    /// * `run` - Canonical ACP run
+    /// * `mode` - Effective runtime mode
+    /// * `conversation_id` - Optional mapped conversation identifier
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or persistence fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::acp::{AcpMessage, AcpMessagePart, AcpRole, AcpRun, AcpRunCreateRequest, AcpRunId, AcpRunSession, AcpSessionId, AcpTextPart};
+    /// use xzatoma::acp::runtime::AcpRuntimeExecuteMode;
+    /// use xzatoma::storage::SqliteStorage;
+    ///
+    /// let storage = SqliteStorage::new_with_path("/tmp/persist_acp_run_example.db")?;
+    /// let session_id = AcpSessionId::new("session_123".to_string())?;
+    /// let session = AcpRunSession::new(session_id.clone())?;
+    /// let request = AcpRunCreateRequest::new(
+    ///     session_id,
+    ///     vec![AcpMessage::new(
+    ///         AcpRole::User,
+    ///         vec![AcpMessagePart::Text(AcpTextPart::new("hello".to_string()))],
+    ///     )?],
+    /// )?;
+    /// let run = AcpRun::new(AcpRunId::new("run_123".to_string())?, request, session)?;
+    ///
+    /// storage.persist_acp_run(&run, AcpRuntimeExecuteMode::Sync, None)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn persist_acp_run(
+        &self,
+        run: &AcpRun,
+        mode: AcpRuntimeExecuteMode,
+        conversation_id: Option<String>,
+    ) -> Result<()> {
+        let existing_session = self.load_acp_session(run.session.id.as_str())?;
+        let existing_run_count = existing_session
+            .as_ref()
+            .map(|session| session.run_count)
+            .unwrap_or_else(|| {
+                self.count_acp_runs_for_session(run.session.id.as_str())
+                    .unwrap_or(0)
+            });
+        let session = StoredAcpSession {
+            session_id: run.session.id.as_str().to_string(),
+            conversation_id: conversation_id.clone().or_else(|| {
+                existing_session
+                    .as_ref()
+                    .and_then(|session| session.conversation_id.clone())
+            }),
+            title: existing_session
+                .as_ref()
+                .and_then(|session| session.title.clone()),
+            created_at: existing_session
+                .as_ref()
+                .map(|session| session.created_at)
+                .unwrap_or(parse_rfc3339_to_utc(&run.session.created_at)?),
+            updated_at: parse_rfc3339_to_utc(&run.status.updated_at)?,
+            run_count: existing_run_count.max(if existing_session.is_some() {
+                existing_run_count
+            } else {
+                existing_run_count + 1
+            }),
+            last_run_id: Some(run.id.as_str().to_string()),
+            metadata: existing_session
+                .map(|session| session.metadata)
+                .unwrap_or_default(),
+        };
+        self.save_acp_session(&session)?;
+
+        let input_json = serde_json::to_string(&run.request.input)
+            .context("Failed to serialize ACP run input")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let output_json = serde_json::to_string(&run.output)
+            .context("Failed to serialize ACP run output")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let stored_run = StoredAcpRun {
+            run_id: run.id.as_str().to_string(),
+            session_id: run.session.id.as_str().to_string(),
+            conversation_id,
+            mode: mode.as_str().to_string(),
+            state: run.status.state.to_string(),
+            created_at: parse_rfc3339_to_utc(&run.status.created_at)?,
+            updated_at: parse_rfc3339_to_utc(&run.status.updated_at)?,
+            completed_at: match &run.status.completed_at {
+                Some(value) => Some(parse_rfc3339_to_utc(value)?),
+                None => None,
+            },
+            failure_reason: run.status.failure_reason.clone(),
+            cancellation_reason: run.status.cancellation_reason.clone(),
+            await_kind: run
+                .await_payload
+                .as_ref()
+                .map(|payload| payload.kind.clone()),
+            await_detail: run
+                .await_payload
+                .as_ref()
+                .map(|payload| payload.detail.clone()),
+            input_json,
+            output_json,
+            metadata: BTreeMap::new(),
+        };
+        self.save_acp_run(&stored_run)?;
+
+        if let Some(await_payload) = &run.await_payload {
+            let await_state = StoredAcpAwaitState {
+                run_id: run.id.as_str().to_string(),
+                session_id: run.session.id.as_str().to_string(),
+                kind: await_payload.kind.clone(),
+                detail: await_payload.detail.clone(),
+                created_at: parse_rfc3339_to_utc(&run.status.updated_at)?,
+                updated_at: parse_rfc3339_to_utc(&run.status.updated_at)?,
+                resumed_at: None,
+                resume_payload_json: None,
+            };
+            self.save_acp_await_state(&await_state)?;
+        }
+
+        Ok(())
+    }
+
+    /// Restore a canonical ACP run from persistent storage.
+    ///
+    /// # Arguments
+    ///
+    /// * `run_id` - ACP run identifier
+    ///
+    /// # Returns
+    ///
+    /// Returns the restored canonical ACP run when found.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if deserialization or reconstruction fails.
+    pub fn restore_acp_run(&self, run_id: &str) -> Result<Option<AcpRun>> {
+        let stored = match self.load_acp_run(run_id)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let session_id = AcpSessionId::new(stored.session_id.clone())?;
+        let run_session = AcpRunSession {
+            id: session_id.clone(),
+            created_at: stored.created_at.to_rfc3339(),
+        };
+
+        let input: Vec<crate::acp::AcpMessage> = serde_json::from_str(&stored.input_json)
+            .context("Failed to deserialize stored ACP input")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let output: AcpRunOutput = serde_json::from_str(&stored.output_json)
+            .context("Failed to deserialize stored ACP output")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let mut request = AcpRunCreateRequest::new(session_id, input)?;
+        request.metadata = BTreeMap::new();
+
+        let run_id = AcpRunId::new(stored.run_id.clone())?;
+        let state = parse_run_state(&stored.state)?;
+        let status = AcpRunStatus {
+            state,
+            created_at: stored.created_at.to_rfc3339(),
+            updated_at: stored.updated_at.to_rfc3339(),
+            completed_at: stored.completed_at.map(|value| value.to_rfc3339()),
+            failure_reason: stored.failure_reason.clone(),
+            cancellation_reason: stored.cancellation_reason.clone(),
+        };
+
+        let await_payload = match (&stored.await_kind, &stored.await_detail) {
+            (Some(kind), Some(detail)) => Some(AcpAwaitPayload::new(kind.clone(), detail.clone())),
+            _ => None,
+        };
+
+        Ok(Some(AcpRun {
+            id: run_id,
+            session: run_session,
+            request,
+            status,
+            output,
+            await_payload,
+        }))
+    }
+
+    /// Restore persisted ACP runtime events for one run.
+    ///
+    /// # Arguments
+    ///
+    /// * `run_id` - ACP run identifier
+    ///
+    /// # Returns
+    ///
+    /// Returns restored runtime events ordered by sequence.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if event deserialization fails.
+    pub fn restore_acp_runtime_events(&self, run_id: &str) -> Result<Vec<AcpRuntimeEvent>> {
+        let stored_events = self.load_acp_run_events(run_id)?;
+        let mut restored = Vec::with_capacity(stored_events.len());
+
+        for stored in stored_events {
+            let payload: Value = serde_json::from_str(&stored.payload_json)
+                .context("Failed to deserialize stored ACP event payload")
+                .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+            let event = AcpEvent {
+                kind: parse_event_kind(&stored.kind)?,
+                run_id: Some(stored.run_id.clone()),
+                created_at: stored.created_at.to_rfc3339(),
+                payload,
+            };
+            restored.push(AcpRuntimeEvent {
+                sequence: stored.sequence,
+                event,
+                terminal: stored.terminal,
+            });
+        }
+
+        Ok(restored)
+    }
+
+    /// Count persisted ACP runs for a session.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - ACP session identifier
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of stored runs associated with the session.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the count query fails.
+    pub fn count_acp_runs_for_session(&self, session_id: &str) -> Result<usize> {
+        let conn = self.connection()?;
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM acp_runs WHERE session_id = ?",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .context("Failed to count ACP runs for session")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        usize::try_from(count)
+            .map_err(|e| XzatomaError::Storage(format!("Invalid ACP run count: {}", e)))
+    }
+
+    /// Save or update a subagent conversation record.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - Subagent conversation to persist
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the record cannot be serialized or saved.
+    pub fn save_subagent_conversation(&self, record: &ConversationRecord) -> Result<()> {
+        let json = serde_json::to_string(record)
+            .context("Failed to serialize subagent conversation")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let conn = self.connection()?;
+        conn.execute(
+            "
+            INSERT INTO subagent_conversations (id, parent_id, started_at, record)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                parent_id = excluded.parent_id,
+                started_at = excluded.started_at,
+                record = excluded.record
+            ",
+            params![record.id, record.parent_id, record.started_at, json],
+        )
+        .context("Failed to save subagent conversation")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Load a subagent conversation record by ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Subagent conversation identifier
+    ///
+    /// # Returns
+    ///
+    /// Returns the stored record when present.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if loading or deserialization fails.
+    pub fn load_subagent_conversation(&self, id: &str) -> Result<Option<ConversationRecord>> {
+        let conn = self.connection()?;
+        let json: Option<String> = conn
+            .query_row(
+                "SELECT record FROM subagent_conversations WHERE id = ?",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to load subagent conversation")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        json.map(|json| parse_subagent_conversation(&json))
+            .transpose()
+    }
+
+    /// List subagent conversation records ordered by ID.
+    ///
+    /// IDs are ULIDs, so the order is the order the conversations started.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Maximum number of records to return
+    /// * `offset` - Number of records to skip
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query or deserialization fails.
+    pub fn list_subagent_conversations(
+        &self,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<ConversationRecord>> {
+        self.query_subagent_conversations(
+            "SELECT record FROM subagent_conversations ORDER BY id LIMIT ? OFFSET ?",
+            params![limit as i64, offset as i64],
+        )
+    }
+
+    /// List the subagent conversations spawned from `parent_id`.
+    ///
+    /// # Arguments
+    ///
+    /// * `parent_id` - Parent conversation identifier
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query or deserialization fails.
+    pub fn find_subagent_conversations_by_parent(
+        &self,
+        parent_id: &str,
+    ) -> Result<Vec<ConversationRecord>> {
+        self.query_subagent_conversations(
+            "SELECT record FROM subagent_conversations WHERE parent_id = ? ORDER BY id",
+            params![parent_id],
+        )
+    }
+
+    fn query_subagent_conversations(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<ConversationRecord>> {
+        let conn = self.connection()?;
+        let mut stmt = conn
+            .prepare(sql)
+            .context("Failed to prepare subagent conversation query")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+        let rows = stmt
+            .query_map(params, |row| row.get::<_, String>(0))
+            .context("Failed to list subagent conversations")
+            .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+        let mut records = Vec::new();
+        for json in rows {
+            let json = json
+                .context("Failed to read subagent conversation")
+                .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+            records.push(parse_subagent_conversation(&json)?);
+        }
+        Ok(records)
+    }
+
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|_| XzatomaError::Storage("Database connection lock poisoned".into()))
+    }
+}
+
+/// Deserialize a stored subagent conversation record.
+fn parse_subagent_conversation(json: &str) -> Result<ConversationRecord> {
+    serde_json::from_str(json)
+        .context("Failed to deserialize subagent conversation")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))
+}
+
+/// Open a connection configured for concurrent use across processes.
+///
+/// The busy timeout is applied before switching to WAL mode because changing
+/// the journal mode itself needs a lock another process may be holding.
+fn open_configured_connection(db_path: &Path) -> Result<Connection> {
+    let conn = Connection::open(db_path)
+        .context("Failed to open database")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+    conn.busy_timeout(BUSY_TIMEOUT)
+        .context("Failed to set database busy timeout")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+    conn.query_row("PRAGMA journal_mode = WAL", [], |row| {
+        row.get::<_, String>(0)
+    })
+    .context("Failed to enable WAL journal mode")
+    .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+    Ok(conn)
+}
+
+/// Normalize a conversation tag by trimming whitespace and lowercasing it.
+///
+/// # Errors
+///
+/// Returns an error if the tag is empty after trimming.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::storage::normalize_tag;
+///
+/// assert_eq!(normalize_tag("  Infra ").unwrap(), "infra");
+/// assert!(normalize_tag("   ").is_err());
+/// ```
+pub fn normalize_tag(tag: &str) -> Result<String> {
+    let normalized = tag.trim().to_lowercase();
+    if normalized.is_empty() {
+        return Err(XzatomaError::Storage("Tag cannot be empty".to_string()));
+    }
+    Ok(normalized)
+}
+
+/// Derive the conversation ID for a caller-chosen session key.
+///
+/// A key that already is a UUID is used as is. Any other key is hashed with
+/// SHA-256 into a version 8 UUID, so the same key always names the same
+/// conversation.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::storage::session_conversation_id;
+///
+/// let id = session_conversation_id("ci/nightly-lint");
+/// assert_eq!(id, session_conversation_id("ci/nightly-lint"));
+/// assert_ne!(id, session_conversation_id("ci/weekly-lint"));
+///
+/// let uuid = "0b8e8c4c-54bb-4a6e-9d0c-1f5b2f0e7a11";
+/// assert_eq!(session_conversation_id(uuid).to_string(), uuid);
+/// ```
+pub fn session_conversation_id(key: &str) -> uuid::Uuid {
+    if let Some(id) = parse_full_id(key) {
+        return id;
+    }
+    let digest = Sha256::digest(format!("{}{}", SESSION_KEY_NAMESPACE, key).as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_custom_bytes(bytes).into_uuid()
+}
+
+/// Parse `id` when it is a full hyphenated UUID.
+fn parse_full_id(id: &str) -> Option<uuid::Uuid> {
+    if id.len() != 36 {
+        return None;
+    }
+    uuid::Uuid::parse_str(id).ok()
+}
+
+/// Serialize messages for the `messages` column inside a transaction.
+fn to_json(messages: &[Message]) -> rusqlite::Result<String> {
+    serde_json::to_string(messages)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+/// Return the stored ID derived from the session key `id`, if any.
+fn stored_session_id(conn: &Connection, id: &str) -> Result<Option<String>> {
+    if parse_full_id(id).is_some() {
+        return Ok(None);
+    }
+    let derived = session_conversation_id(id).to_string();
+    conn.query_row(
+        "SELECT id FROM conversations WHERE id = ?",
+        params![derived],
+        |row| row.get(0),
+    )
+    .optional()
+    .context("Failed to look up session key")
+    .map_err(|e| XzatomaError::Storage(e.to_string()))
+}
+
+/// Build the `id` condition and bound value that find a conversation by full
+/// ID, session key, or ID prefix.
+///
+/// A full UUID matches exactly. A session key matches the conversation
+/// stored under its derived ID. Anything else is a prefix, escaped so that
+/// `_` and `%` match literally.
+fn id_condition(conn: &Connection, id: &str) -> Result<(&'static str, String)> {
+    if parse_full_id(id).is_some() {
+        return Ok(("id = ?", id.to_string()));
+    }
+    if let Some(stored) = stored_session_id(conn, id)? {
+        return Ok(("id = ?", stored));
+    }
+    Ok(("id LIKE ? ESCAPE '\\'", format!("{}%", escape_like(id))))
+}
+
+/// Resolve a full conversation ID, session key, or unique prefix to the
+/// stored ID.
+fn resolve_conversation_id(conn: &Connection, id: &str) -> Result<String> {
+    find_conversation_id(conn, id)?
+        .ok_or_else(|| XzatomaError::Storage(format!("Conversation not found: {}", id)))
+}
+
+/// Find the stored ID for a full conversation ID, session key, or unique
+/// prefix.
+///
+/// Returns `None` when nothing matches, and an error when a prefix matches
+/// more than one conversation.
+fn find_conversation_id(conn: &Connection, id: &str) -> Result<Option<String>> {
+    if let Some(stored) = stored_session_id(conn, id)? {
+        return Ok(Some(stored));
+    }
+    let mut stmt = conn
+        .prepare(
+            "SELECT id FROM conversations WHERE id = ?1
+             UNION
+             SELECT id FROM (
+                 SELECT id FROM conversations WHERE id LIKE ?2 ESCAPE '\\' LIMIT 2
+             )",
+        )
+        .context("Failed to prepare statement")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+    let matches = stmt
+        .query_map(params![id, format!("{}%", escape_like(id))], |row| {
+            row.get::<_, String>(0)
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+        .context("Failed to look up conversation")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+    if matches.iter().any(|candidate| candidate == id) {
+        return Ok(Some(id.to_string()));
+    }
+
+    match matches.as_slice() {
+        [single] => Ok(Some(single.clone())),
+        [] => Ok(None),
+        _ => Err(XzatomaError::Storage(format!(
+            "Conversation ID prefix '{}' is ambiguous",
+            id
+        ))),
+    }
+}
+
+/// Load every conversation tag, grouped by conversation and sorted by tag.
+/// Build the `WHERE` clause and bound values for a session query.
+///
+/// `older_than` is not part of the clause; it compares parsed instants and
+/// is applied after the rows are loaded.
+fn session_conditions(text: Option<&str>, filter: &HistoryFilter) -> Result<(String, Vec<String>)> {
+    let mut tags = filter
+        .tags
+        .iter()
+        .map(|tag| normalize_tag(tag))
+        .collect::<Result<Vec<_>>>()?;
+    tags.sort();
+    tags.dedup();
+
+    let mut clauses: Vec<String> = Vec::new();
+    let mut values: Vec<String> = Vec::new();
+
+    if let Some(text) = text {
+        clauses.push("(title LIKE ? ESCAPE '\\' OR messages LIKE ? ESCAPE '\\')".to_string());
+        let pattern = format!("%{}%", escape_like(text));
+        values.push(pattern.clone());
+        values.push(pattern);
+    }
+
+    if !tags.is_empty() {
+        clauses.push(format!(
+            "id IN (SELECT conversation_id FROM conversation_tags
+                    WHERE tag IN ({})
+                    GROUP BY conversation_id
+                    HAVING COUNT(DISTINCT tag) = {})",
+            vec!["?"; tags.len()].join(", "),
+            tags.len()
+        ));
+        values.extend(tags);
+    }
+
+    if let Some(model) = &filter.model {
+        clauses.push("model = ?".to_string());
+        values.push(model.clone());
+    }
+
+    if filter.untitled {
+        clauses.push("(TRIM(title) = '' OR title = ?)".to_string());
+        values.push(DEFAULT_CONVERSATION_TITLE.to_string());
+    }
+
+    let where_clause = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    };
+
+    Ok((where_clause, values))
+}
+
+fn load_tags_by_conversation(conn: &Connection) -> Result<HashMap<String, Vec<String>>> {
+    let mut stmt = conn
+        .prepare("SELECT conversation_id, tag FROM conversation_tags ORDER BY tag")
+        .context("Failed to prepare statement")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .context("Failed to query conversation tags")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    for (conversation_id, tag) in rows {
+        tags.entry(conversation_id).or_default().push(tag);
+    }
+
+    Ok(tags)
+}
+
+/// Escape `%`, `_`, and the escape character itself for a `LIKE ... ESCAPE '\'`
+/// pattern.
+/// Read one conversation row and its tags for a history backup
+fn read_backup_row(
+    row: &rusqlite::Row<'_>,
+    tag_stmt: &mut rusqlite::Statement<'_>,
+) -> Result<ConversationBackup> {
+    let read = || -> rusqlite::Result<_> {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, i64>(5)?,
+            row.get::<_, Option<String>>(6)?,
+            row.get::<_, String>(7)?,
+            row.get::<_, String>(8)?,
+            row.get::<_, String>(9)?,
+        ))
+    };
+    let (
+        id,
+        title,
+        created_at,
+        updated_at,
+        model,
+        pinned,
+        replayed_from,
+        pinned_messages,
+        message_cwds,
+        messages,
+    ) = read()
+        .context("Failed to read conversation")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+    let tags = tag_stmt
+        .query_map(params![id], |row| row.get(0))
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+        .context("Failed to read conversation tags")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+    let raw = |field: &str, json: String| {
+        RawValue::from_string(json)
+            .with_context(|| format!("Conversation {} has invalid {} JSON", id, field))
+            .map_err(|e| XzatomaError::Storage(e.to_string()))
+    };
+
+    let pinned_messages = raw("pinned_messages", pinned_messages)?;
+    let message_cwds = raw("message_cwds", message_cwds)?;
+    let messages = raw("messages", messages)?;
+
+    Ok(ConversationBackup {
+        id,
+        title,
+        created_at,
+        updated_at,
+        model,
+        pinned: sqlite_to_bool(pinned),
+        tags,
+        replayed_from,
+        pinned_messages,
+        message_cwds,
+        messages,
+    })
+}
+
+/// Read a backed-up conversation file and check it against the manifest
+fn read_backup_file(dir: &Path, entry: &BackupEntry) -> Result<ConversationBackup> {
+    // Only plain file names, so a crafted manifest cannot read outside `dir`
+    if Path::new(&entry.file)
+        .file_name()
+        .and_then(|name| name.to_str())
+        != Some(entry.file.as_str())
+    {
+        return Err(XzatomaError::Storage(format!(
+            "Invalid file name '{}' for conversation {} in backup manifest",
+            entry.file, entry.id
+        )));
+    }
+
+    let bytes = std::fs::read(dir.join(&entry.file))
+        .with_context(|| format!("Failed to read {}", entry.file))
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+    if sha256_hex(&bytes) != entry.sha256 {
+        return Err(XzatomaError::Storage(format!(
+            "{} does not match the hash in the backup manifest",
+            entry.file
+        )));
+    }
+
+    let backup: ConversationBackup = serde_json::from_slice(&bytes)
+        .with_context(|| format!("Failed to parse {}", entry.file))
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+    if backup.id != entry.id {
+        return Err(XzatomaError::Storage(format!(
+            "{} holds conversation {} but the manifest lists {}",
+            entry.file, backup.id, entry.id
+        )));
+    }
+
+    Ok(backup)
+}
+
+/// Choose a unique, filesystem-safe file name for a backed-up conversation
+fn backup_file_name(id: &str, used: &mut HashSet<String>) -> String {
+    let stem: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    let mut file = format!("{}.json", stem);
+    let mut suffix = 1;
+    while !used.insert(file.clone()) {
+        suffix += 1;
+        file = format!("{}-{}.json", stem, suffix);
+    }
+    file
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// Compress a recorded run turn for storage.
+fn compress_payload(json: &[u8]) -> Result<Vec<u8>> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(json)
+        .and_then(|()| encoder.finish())
+        .context("Failed to compress run turn")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))
+}
+
+/// Decompress a stored run turn.
+fn decompress_payload(payload: &[u8]) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut json = Vec::new();
+    flate2::read::ZlibDecoder::new(payload)
+        .read_to_end(&mut json)
+        .context("Failed to decompress run turn")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+    Ok(json)
+}
+
+/// Add `column` to `table` when a database created by an older version lacks it.
+fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let exists = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .and_then(|mut stmt| {
+            let names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+            names.collect::<rusqlite::Result<Vec<String>>>()
+        })
+        .context("Failed to inspect table schema")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?
+        .iter()
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))
+        .context("Failed to migrate table schema")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Count sessions and messages grouped by the SQL period expression `period`.
+fn query_period_usage(
+    conn: &Connection,
+    period: &str,
+    since: &Option<String>,
+    limit: i64,
+) -> Result<Vec<PeriodUsage>> {
+    conn.prepare(&format!(
+        "SELECT {} AS period, COUNT(*), SUM(json_array_length(messages))
+         FROM conversations
+         WHERE ?1 IS NULL OR updated_at >= ?1
+         GROUP BY period
+         ORDER BY period DESC
+         LIMIT ?2",
+        period
+    ))
+    .and_then(|mut stmt| {
+        let rows = stmt.query_map(params![since, limit], |row| {
+            Ok(PeriodUsage {
+                period: row.get(0)?,
+                sessions: row.get::<_, i64>(1)? as u64,
+                messages: row.get::<_, i64>(2)? as u64,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })
+    .context("Failed to aggregate usage per period")
+    .map_err(|e| XzatomaError::Storage(e.to_string()))
+}
+
+/// Format a usage timestamp so that text comparison orders it correctly.
+fn usage_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Load every conversation ordered from least to most recently updated.
+fn load_retention_candidates(conn: &Connection) -> Result<Vec<RetentionCandidate>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, title, updated_at, pinned,
+                    LENGTH(CAST(messages AS BLOB)) + LENGTH(CAST(title AS BLOB))
+             FROM conversations
+             ORDER BY updated_at ASC, id ASC",
+        )
+        .context("Failed to prepare statement")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            let updated_at: String = row.get(2)?;
+            let pinned: i64 = row.get(3)?;
+            let size_bytes: i64 = row.get(4)?;
+            Ok(RetentionCandidate {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                updated_at: parse_rfc3339_to_utc(&updated_at).unwrap_or_else(|_| Utc::now()),
+                pinned: sqlite_to_bool(pinned),
+                size_bytes: u64::try_from(size_bytes).unwrap_or(0),
+            })
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .context("Failed to query conversations for pruning")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+    Ok(rows)
+}
+
+/// Choose the conversations that exceed `retention`, oldest first.
+///
+/// `used_bytes` is the number of database bytes in use before pruning. Returns
+/// the selected conversations and the estimated bytes in use once they are
+/// removed.
+fn select_sessions_to_prune(
+    candidates: &[RetentionCandidate],
+    retention: &RetentionConfig,
+    used_bytes: u64,
+    now: DateTime<Utc>,
+) -> (Vec<PrunedSession>, u64) {
+    let mut removed: Vec<PrunedSession> = Vec::new();
+    let mut removed_ids: HashSet<&str> = HashSet::new();
+    let mut remaining_bytes = used_bytes;
+
+    let mut remove = |candidate: &RetentionCandidate, reason: PruneReason| {
+        removed.push(PrunedSession {
+            id: candidate.id.clone(),
+            title: candidate.title.clone(),
+            updated_at: candidate.updated_at,
+            reason,
+        });
+    };
+
+    let age_cutoff = retention
+        .max_age_days
+        .and_then(|days| i64::try_from(days).ok())
+        .and_then(ChronoDuration::try_days)
+        .and_then(|age| now.checked_sub_signed(age));
+
+    if let Some(cutoff) = age_cutoff {
+        for candidate in candidates
+            .iter()
+            .filter(|candidate| !candidate.pinned && candidate.updated_at < cutoff)
+        {
+            removed_ids.insert(candidate.id.as_str());
+            remaining_bytes = remaining_bytes.saturating_sub(candidate.size_bytes);
+            remove(candidate, PruneReason::MaxAge);
+        }
+    }
+
+    if let Some(max_sessions) = retention.max_sessions {
+        let mut remaining = candidates.len() - removed_ids.len();
+        for candidate in candidates.iter().filter(|candidate| !candidate.pinned) {
+            if remaining <= max_sessions {
+                break;
+            }
+            if removed_ids.insert(candidate.id.as_str()) {
+                remaining -= 1;
+                remaining_bytes = remaining_bytes.saturating_sub(candidate.size_bytes);
+                remove(candidate, PruneReason::MaxSessions);
+            }
+        }
+    }
+
+    if let Some(max_db_size_mb) = retention.max_db_size_mb {
+        let limit_bytes = max_db_size_mb.saturating_mul(1024 * 1024);
+        for candidate in candidates.iter().filter(|candidate| !candidate.pinned) {
+            if remaining_bytes <= limit_bytes {
+                break;
+            }
+            if removed_ids.insert(candidate.id.as_str()) {
+                remaining_bytes = remaining_bytes.saturating_sub(candidate.size_bytes);
+                remove(candidate, PruneReason::MaxDbSize);
+            }
+        }
+    }
+
+    (removed, remaining_bytes)
+}
+
+/// Returns the total and free page counts of the main database.
+fn page_counts(conn: &Connection) -> Result<(u64, u64)> {
+    let page_count: i64 = conn
+        .query_row("PRAGMA page_count", [], |row| row.get(0))
+        .context("Failed to read database page count")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+    let free_pages: i64 = conn
+        .query_row("PRAGMA freelist_count", [], |row| row.get(0))
+        .context("Failed to read database free page count")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+
+    Ok((
+        u64::try_from(page_count).unwrap_or(0),
+        u64::try_from(free_pages).unwrap_or(0),
+    ))
+}
+
+/// Returns the number of bytes held by pages that are in use.
+fn used_database_bytes(conn: &Connection) -> Result<u64> {
+    let page_size: i64 = conn
+        .query_row("PRAGMA page_size", [], |row| row.get(0))
+        .context("Failed to read database page size")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))?;
+    let (page_count, free_pages) = page_counts(conn)?;
+
+    Ok(page_count.saturating_sub(free_pages) * u64::try_from(page_size).unwrap_or(0))
+}
+
+/// Returns `true` when SQLite reported that the database is busy or locked.
+fn is_busy_error(error: &rusqlite::Error) -> bool {
+    matches!(
+        error,
+        rusqlite::Error::SqliteFailure(failure, _)
+            if failure.code == ErrorCode::DatabaseBusy || failure.code == ErrorCode::DatabaseLocked
+    )
+}
+
+/// Run `operation`, retrying with exponential backoff while it fails with
+/// `SQLITE_BUSY`.
+fn retry_on_busy<T>(mut operation: impl FnMut() -> rusqlite::Result<T>) -> rusqlite::Result<T> {
+    let mut delay = BUSY_RETRY_INITIAL_DELAY;
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Err(error) if attempt < BUSY_RETRY_ATTEMPTS && is_busy_error(&error) => {
+                tracing::debug!(
+                    "Database busy (attempt {}/{}), retrying in {:?}",
+                    attempt,
+                    BUSY_RETRY_ATTEMPTS,
+                    delay
+                );
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn serialize_metadata(metadata: &BTreeMap<String, String>) -> Result<String> {
+    serde_json::to_string(metadata)
+        .context("Failed to serialize metadata")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))
+}
+
+fn deserialize_metadata(json_str: &str) -> Result<BTreeMap<String, String>> {
+    serde_json::from_str(json_str)
+        .context("Failed to deserialize metadata")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))
+}
+
+fn parse_rfc3339_to_utc(value: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|value| value.with_timezone(&Utc))
+        .context("Failed to parse RFC 3339 timestamp")
+        .map_err(|e| XzatomaError::Storage(e.to_string()))
+}
+
+/// Parse a listed session timestamp, falling back to the Unix epoch
+///
+/// An unparseable date must not masquerade as recent, so the epoch sentinel
+/// is returned instead of the current time and a warning is logged.
+fn parse_listed_timestamp(id: &str, field: &str, value: &str) -> DateTime<Utc> {
+    parse_rfc3339_to_utc(value).unwrap_or_else(|_| {
+        tracing::warn!(
+            "Conversation {} has an unparseable {} {:?}; listing it as unknown",
+            id,
+            field,
+            value
+        );
+        DateTime::<Utc>::UNIX_EPOCH
+    })
+}
+
+fn bool_to_sqlite(value: bool) -> i64 {
+    if value {
+        1
+    } else {
+        0
+    }
+}
+
+fn sqlite_to_bool(value: i64) -> bool {
+    value != 0
+}
+
+fn to_rusqlite_error(error: crate::error::XzatomaError) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(
+        0,
+        rusqlite::types::Type::Text,
+        Box::new(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            error.to_string(),
         )),
     )
 }
 
-fn parse_run_state(value: &str) -> Result<AcpRunState> {
-    match value {
-        "created" => Ok(AcpRunState::Created),
-        "queued" => Ok(AcpRunState::Queued),
-        "running" => Ok(AcpRunState::Running),
-        "awaiting" => Ok(AcpRunState::Awaiting),
-        "completed" => Ok(AcpRunState::Completed),
-        "failed" => Ok(AcpRunState::Failed),
-        "cancelled" => Ok(AcpRunState::Cancelled),
-        other => Err(XzatomaError::Storage(format!(
-            "Unknown stored ACP run state: {}",
-            other
-        ))),
+fn parse_run_state(value: &str) -> Result<AcpRunState> {
+    match value {
+        "created" => Ok(AcpRunState::Created),
+        "queued" => Ok(AcpRunState::Queued),
+        "running" => Ok(AcpRunState::Running),
+        "awaiting" => Ok(AcpRunState::Awaiting),
+        "completed" => Ok(AcpRunState::Completed),
+        "failed" => Ok(AcpRunState::Failed),
+        "cancelled" => Ok(AcpRunState::Cancelled),
+        other => Err(XzatomaError::Storage(format!(
+            "Unknown stored ACP run state: {}",
+            other
+        ))),
+    }
+}
+
+fn parse_event_kind(value: &str) -> Result<AcpEventKind> {
+    match value {
+        "run_created" => Ok(AcpEventKind::RunCreated),
+        "run_status_changed" => Ok(AcpEventKind::RunStatusChanged),
+        "run_output_appended" => Ok(AcpEventKind::RunOutputAppended),
+        "run_completed" => Ok(AcpEventKind::RunCompleted),
+        "run_failed" => Ok(AcpEventKind::RunFailed),
+        "run_cancelled" => Ok(AcpEventKind::RunCancelled),
+        "run_awaiting_input" => Ok(AcpEventKind::RunAwaitingInput),
+        "session_created" => Ok(AcpEventKind::SessionCreated),
+        other => Err(XzatomaError::Storage(format!(
+            "Unknown stored ACP event kind: {}",
+            other
+        ))),
+    }
+}
+
+fn stored_acp_stdio_session_from_row(
+    row: &rusqlite::Row<'_>,
+) -> rusqlite::Result<Result<StoredAcpStdioSession>> {
+    let session_id: String = row.get(0)?;
+    let workspace_root: String = row.get(1)?;
+    let conversation_id: String = row.get(2)?;
+    let provider_type: String = row.get(3)?;
+    let model: Option<String> = row.get(4)?;
+    let created_at_str: String = row.get(5)?;
+    let updated_at_str: String = row.get(6)?;
+    let metadata_json: String = row.get(7)?;
+
+    let created_at = parse_rfc3339_to_utc(&created_at_str);
+    let updated_at = parse_rfc3339_to_utc(&updated_at_str);
+    let metadata = serde_json::from_str::<BTreeMap<String, String>>(&metadata_json)
+        .context("Failed to deserialize ACP stdio session metadata")
+        .map_err(|e| XzatomaError::Storage(e.to_string()));
+
+    Ok(created_at.and_then(|created_at| {
+        updated_at.and_then(|updated_at| {
+            metadata.map(|metadata| StoredAcpStdioSession {
+                session_id,
+                workspace_root,
+                conversation_id,
+                provider_type,
+                model,
+                created_at,
+                updated_at,
+                metadata,
+            })
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acp::{AcpMessage, AcpMessagePart, AcpRole, AcpTextPart};
+    use chrono::Timelike;
+    use serial_test::serial;
+    use std::thread::sleep;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    fn create_test_storage() -> (SqliteStorage, tempfile::TempDir) {
+        let dir = tempdir().expect("failed to create tempdir");
+        let db_path = dir.path().join("history.db");
+        let storage = SqliteStorage::new_with_path(db_path).expect("failed to create storage");
+        (storage, dir)
+    }
+
+    fn sample_run() -> AcpRun {
+        let session_id = AcpSessionId::new("session_123".to_string()).expect("valid session id");
+        let session = AcpRunSession::new(session_id.clone()).expect("valid session");
+        let request = AcpRunCreateRequest::new(
+            session_id,
+            vec![AcpMessage::new(
+                AcpRole::User,
+                vec![AcpMessagePart::Text(AcpTextPart::new(
+                    "hello from acp".to_string(),
+                ))],
+            )
+            .expect("valid message")],
+        )
+        .expect("valid create request");
+
+        AcpRun::new(
+            AcpRunId::new("run_123".to_string()).expect("valid run id"),
+            request,
+            session,
+        )
+        .expect("valid run")
+    }
+
+    #[test]
+    fn test_sqlite_storage_init_creates_conversation_and_acp_tables() {
+        let (storage, _dir) = create_test_storage();
+        let conn = Connection::open(storage.database_path()).expect("open connection");
+
+        let tables = [
+            "conversations",
+            "conversation_tags",
+            "acp_sessions",
+            "acp_stdio_sessions",
+            "acp_runs",
+            "acp_run_events",
+            "acp_await_states",
+            "acp_cancellations",
+            "usage_log",
+        ];
+
+        for table in tables {
+            let count: i64 = conn
+                .query_row(
+                    "SELECT count(*) FROM sqlite_master WHERE type='table' AND name=?",
+                    params![table],
+                    |row| row.get(0),
+                )
+                .expect("query row");
+            assert_eq!(count, 1, "missing table {}", table);
+        }
+    }
+
+    #[test]
+    fn test_sqlite_storage_enables_wal_journal_mode() {
+        let (storage, _dir) = create_test_storage();
+        let conn = Connection::open(storage.database_path()).expect("open connection");
+
+        let mode: String = conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .expect("query journal mode");
+        assert_eq!(mode.to_lowercase(), "wal");
+    }
+
+    #[test]
+    fn test_save_conversation_concurrent_instances_never_fail() {
+        let dir = tempdir().expect("failed to create tempdir");
+        let db_path = dir.path().join("history.db");
+        SqliteStorage::new_with_path(&db_path).expect("failed to create storage");
+
+        // Separate instances own separate connections, which is how two
+        // xzatoma processes sharing one database file behave.
+        let handles: Vec<_> = (0..8)
+            .map(|worker| {
+                let db_path = db_path.clone();
+                std::thread::spawn(move || {
+                    let storage =
+                        SqliteStorage::new_with_path(db_path).expect("failed to open storage");
+                    for i in 0..25 {
+                        storage
+                            .save_conversation(
+                                &format!("worker-{}-{}", worker, i),
+                                "Concurrent",
+                                None,
+                                &[Message::user("hello")],
+                            )
+                            .expect("concurrent save failed");
+                        storage
+                            .save_conversation(
+                                "shared-conversation",
+                                &format!("Worker {}", worker),
+                                None,
+                                &[Message::user("shared")],
+                            )
+                            .expect("concurrent save of shared id failed");
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().expect("worker panicked");
+        }
+
+        let storage = SqliteStorage::new_with_path(&db_path).expect("failed to open storage");
+        let sessions = storage.list_sessions().expect("list failed");
+        assert_eq!(sessions.len(), 8 * 25 + 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_save_conversation_from_many_tokio_tasks_on_shared_instance() {
+        let (storage, _dir) = create_test_storage();
+
+        let handles: Vec<_> = (0..32)
+            .map(|task| {
+                let storage = storage.clone();
+                tokio::task::spawn_blocking(move || {
+                    storage.save_conversation(
+                        &format!("task-{}", task),
+                        "Task",
+                        Some("model"),
+                        &[Message::user("hello")],
+                    )
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle
+                .await
+                .expect("task panicked")
+                .expect("save from task failed");
+        }
+
+        assert_eq!(storage.list_sessions().expect("list failed").len(), 32);
+    }
+
+    #[test]
+    fn test_save_conversation_waits_for_lock_held_by_other_connection() {
+        let (storage, _dir) = create_test_storage();
+        let other = Connection::open(storage.database_path()).expect("open connection");
+        other
+            .execute_batch("BEGIN IMMEDIATE")
+            .expect("acquire write lock");
+
+        let releaser = std::thread::spawn(move || {
+            sleep(Duration::from_millis(200));
+            other.execute_batch("COMMIT").expect("release write lock");
+        });
+
+        storage
+            .save_conversation("blocked", "Blocked", None, &[Message::user("hi")])
+            .expect("save should wait for the lock instead of failing");
+        releaser.join().expect("releaser panicked");
+
+        assert!(storage
+            .load_conversation("blocked")
+            .expect("load failed")
+            .is_some());
+    }
+
+    #[test]
+    fn test_retry_on_busy_retries_until_success() {
+        let mut calls = 0;
+        let result = retry_on_busy(|| {
+            calls += 1;
+            if calls < 3 {
+                Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+                    None,
+                ))
+            } else {
+                Ok(calls)
+            }
+        });
+
+        assert_eq!(result.expect("retry should succeed"), 3);
+    }
+
+    #[test]
+    fn test_retry_on_busy_gives_up_after_max_attempts() {
+        let mut calls = 0;
+        let result: rusqlite::Result<()> = retry_on_busy(|| {
+            calls += 1;
+            Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+                None,
+            ))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(calls, BUSY_RETRY_ATTEMPTS);
+    }
+
+    #[test]
+    fn test_retry_on_busy_does_not_retry_other_errors() {
+        let mut calls = 0;
+        let result: rusqlite::Result<()> = retry_on_busy(|| {
+            calls += 1;
+            Err(rusqlite::Error::QueryReturnedNoRows)
+        });
+
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_load_acp_session_with_runs_does_not_deadlock() {
+        let (storage, _dir) = create_test_storage();
+        let run = sample_run();
+        storage
+            .persist_acp_run(&run, AcpRuntimeExecuteMode::Sync, None)
+            .expect("persist failed");
+
+        let session = storage
+            .load_acp_session("session_123")
+            .expect("load failed")
+            .expect("session should exist");
+        assert_eq!(session.run_count, 1);
+    }
+
+    #[test]
+    fn test_sqlite_storage_init_creates_acp_stdio_session_indexes() {
+        let (storage, _dir) = create_test_storage();
+        let conn = Connection::open(storage.database_path()).expect("open connection");
+
+        for index in [
+            "idx_acp_stdio_sessions_workspace_root",
+            "idx_acp_stdio_sessions_updated_at",
+        ] {
+            let count: i64 = conn
+                .query_row(
+                    "SELECT count(*) FROM sqlite_master WHERE type='index' AND name=?",
+                    params![index],
+                    |row| row.get(0),
+                )
+                .expect("query row");
+            assert_eq!(count, 1, "missing index {}", index);
+        }
+    }
+
+    fn save_test_conversation(storage: &SqliteStorage, id: &str) {
+        storage
+            .save_conversation(
+                id,
+                "New Conversation",
+                Some("llama3.2:latest"),
+                &[crate::providers::Message::user("hello")],
+            )
+            .expect("conversation save failed");
+    }
+
+    fn sample_stdio_session(
+        session_id: &str,
+        workspace_root: &str,
+        conversation_id: &str,
+    ) -> StoredAcpStdioSession {
+        let now = Utc::now();
+        StoredAcpStdioSession {
+            session_id: session_id.to_string(),
+            workspace_root: workspace_root.to_string(),
+            conversation_id: conversation_id.to_string(),
+            provider_type: "ollama".to_string(),
+            model: Some("llama3.2:latest".to_string()),
+            created_at: now,
+            updated_at: now,
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_acp_stdio_session_by_workspace_root() {
+        let (storage, _dir) = create_test_storage();
+        save_test_conversation(&storage, "conversation-stdio-1");
+
+        let mut session = sample_stdio_session(
+            "xzatoma-session-1",
+            "/tmp/xzatoma-workspace",
+            "conversation-stdio-1",
+        );
+        session
+            .metadata
+            .insert("source".to_string(), "zed".to_string());
+
+        storage
+            .save_acp_stdio_session(&session)
+            .expect("stdio session save failed");
+
+        let loaded = storage
+            .load_latest_acp_stdio_session_by_workspace_root("/tmp/xzatoma-workspace")
+            .expect("stdio session load failed")
+            .expect("stdio session should exist");
+
+        assert_eq!(loaded.session_id, "xzatoma-session-1");
+        assert_eq!(loaded.workspace_root, "/tmp/xzatoma-workspace");
+        assert_eq!(loaded.conversation_id, "conversation-stdio-1");
+        assert_eq!(
+            loaded.metadata.get("source").map(String::as_str),
+            Some("zed")
+        );
+    }
+
+    #[test]
+    fn test_save_acp_stdio_session_updates_existing_mapping() {
+        let (storage, _dir) = create_test_storage();
+        save_test_conversation(&storage, "conversation-stdio-2");
+        save_test_conversation(&storage, "conversation-stdio-3");
+
+        let original = sample_stdio_session(
+            "xzatoma-session-2",
+            "/tmp/xzatoma-original",
+            "conversation-stdio-2",
+        );
+        storage
+            .save_acp_stdio_session(&original)
+            .expect("initial stdio session save failed");
+
+        let mut updated = sample_stdio_session(
+            "xzatoma-session-2",
+            "/tmp/xzatoma-updated",
+            "conversation-stdio-3",
+        );
+        updated.model = Some("gpt-5-mini".to_string());
+        updated.provider_type = "copilot".to_string();
+
+        storage
+            .save_acp_stdio_session(&updated)
+            .expect("updated stdio session save failed");
+
+        let loaded = storage
+            .load_acp_stdio_session_by_session_id("xzatoma-session-2")
+            .expect("stdio session lookup failed")
+            .expect("stdio session should exist");
+
+        assert_eq!(loaded.workspace_root, "/tmp/xzatoma-updated");
+        assert_eq!(loaded.conversation_id, "conversation-stdio-3");
+        assert_eq!(loaded.provider_type, "copilot");
+        assert_eq!(loaded.model.as_deref(), Some("gpt-5-mini"));
+    }
+
+    #[test]
+    fn test_touch_acp_stdio_session_updates_last_activity() {
+        let (storage, _dir) = create_test_storage();
+        save_test_conversation(&storage, "conversation-stdio-4");
+
+        let session = sample_stdio_session(
+            "xzatoma-session-4",
+            "/tmp/xzatoma-touch",
+            "conversation-stdio-4",
+        );
+        storage
+            .save_acp_stdio_session(&session)
+            .expect("stdio session save failed");
+
+        sleep(Duration::from_millis(5));
+
+        storage
+            .touch_acp_stdio_session("xzatoma-session-4")
+            .expect("touch should succeed");
+
+        let loaded = storage
+            .load_acp_stdio_session_by_session_id("xzatoma-session-4")
+            .expect("stdio session lookup failed")
+            .expect("stdio session should exist");
+
+        assert!(loaded.updated_at > session.updated_at);
+    }
+
+    #[test]
+    fn test_load_latest_acp_stdio_session_by_workspace_returns_newest_mapping() {
+        let (storage, _dir) = create_test_storage();
+        save_test_conversation(&storage, "conversation-stdio-5");
+        save_test_conversation(&storage, "conversation-stdio-6");
+
+        let older = sample_stdio_session(
+            "xzatoma-session-5",
+            "/tmp/xzatoma-shared",
+            "conversation-stdio-5",
+        );
+        storage
+            .save_acp_stdio_session(&older)
+            .expect("older stdio session save failed");
+
+        sleep(Duration::from_millis(5));
+
+        let newer = sample_stdio_session(
+            "xzatoma-session-6",
+            "/tmp/xzatoma-shared",
+            "conversation-stdio-6",
+        );
+        storage
+            .save_acp_stdio_session(&newer)
+            .expect("newer stdio session save failed");
+
+        let loaded = storage
+            .load_latest_acp_stdio_session_by_workspace_root("/tmp/xzatoma-shared")
+            .expect("stdio session lookup failed")
+            .expect("stdio session should exist");
+
+        assert_eq!(loaded.session_id, "xzatoma-session-6");
+        assert_eq!(loaded.conversation_id, "conversation-stdio-6");
+    }
+
+    #[test]
+    fn test_prune_acp_stdio_sessions_older_than_removes_stale_mappings() {
+        let (storage, _dir) = create_test_storage();
+        save_test_conversation(&storage, "conversation-stdio-7");
+
+        let session = sample_stdio_session(
+            "xzatoma-session-7",
+            "/tmp/xzatoma-prune",
+            "conversation-stdio-7",
+        );
+        storage
+            .save_acp_stdio_session(&session)
+            .expect("stdio session save failed");
+
+        let deleted = storage
+            .prune_acp_stdio_sessions_older_than(Utc::now() + chrono::Duration::seconds(1))
+            .expect("prune should succeed");
+
+        assert_eq!(deleted, 1);
+        assert!(storage
+            .load_acp_stdio_session_by_session_id("xzatoma-session-7")
+            .expect("stdio session lookup failed")
+            .is_none());
+    }
+
+    #[test]
+    fn test_save_conversation_creates_new_record() {
+        let (storage, _dir) = create_test_storage();
+        let id = "test-save-1";
+        let title = "Test Save 1";
+        let model = Some("gpt-4");
+        let messages = vec![crate::providers::Message::user("Hello")];
+
+        storage
+            .save_conversation(id, title, model, &messages)
+            .expect("save failed");
+
+        let loaded = storage.load_conversation(id).expect("load failed");
+        assert!(loaded.is_some());
+
+        let (loaded_title, loaded_model, loaded_messages) =
+            loaded.expect("conversation should exist");
+        assert_eq!(loaded_title, title.to_string());
+        assert_eq!(loaded_model, model.map(|value| value.to_string()));
+        assert_eq!(loaded_messages.len(), 1);
+        assert_eq!(loaded_messages[0].content.as_deref(), Some("Hello"));
+    }
+
+    #[test]
+    fn test_save_conversation_updates_existing_record() {
+        let (storage, _dir) = create_test_storage();
+        let id = "test-update-1";
+        let title = "Original Title";
+        let messages = vec![crate::providers::Message::user("First")];
+
+        storage
+            .save_conversation(id, title, Some("gpt-4"), &messages)
+            .expect("initial save failed");
+
+        let sessions = storage.list_sessions().expect("list failed");
+        let session = sessions
+            .into_iter()
+            .find(|value| value.id == id)
+            .expect("session found");
+        let created_at = session.created_at;
+        let updated_at = session.updated_at;
+
+        sleep(Duration::from_millis(10));
+
+        let new_title = "Updated Title";
+        let new_messages = vec![crate::providers::Message::user("Second")];
+        storage
+            .save_conversation(id, new_title, Some("gpt-4"), &new_messages)
+            .expect("update failed");
+
+        let sessions = storage.list_sessions().expect("list failed");
+        let session_updated = sessions
+            .into_iter()
+            .find(|value| value.id == id)
+            .expect("updated session found");
+
+        assert_eq!(session_updated.created_at, created_at);
+        assert!(session_updated.updated_at > updated_at);
+    }
+
+    fn stored_session(storage: &SqliteStorage, id: &str) -> StoredSession {
+        storage
+            .list_sessions()
+            .expect("list failed")
+            .into_iter()
+            .find(|session| session.id == id)
+            .expect("session found")
+    }
+
+    #[test]
+    fn test_upsert_conversation_replace_keeps_created_at_and_tags() {
+        let (storage, _dir) = create_test_storage();
+        let id = session_conversation_id("nightly-lint").to_string();
+        storage
+            .upsert_conversation(
+                &id,
+                "First",
+                None,
+                &[Message::user("one")],
+                IfExists::Replace,
+            )
+            .expect("first save failed");
+        storage.add_conversation_tag(&id, "ci").expect("tag failed");
+        storage.set_pinned_messages(&id, &[0]).expect("pin failed");
+        let created_at = stored_session(&storage, &id).created_at;
+
+        sleep(Duration::from_millis(10));
+        storage
+            .upsert_conversation(
+                &id,
+                "Second",
+                None,
+                &[Message::user("two")],
+                IfExists::Replace,
+            )
+            .expect("replace failed");
+
+        let (title, _, messages) = storage.load_conversation(&id).unwrap().unwrap();
+        assert_eq!(title, "Second");
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].content.as_deref(), Some("two"));
+        let session = stored_session(&storage, &id);
+        assert_eq!(session.created_at, created_at);
+        assert_eq!(session.tags, vec!["ci"]);
+        assert!(storage.load_pinned_messages(&id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_upsert_conversation_append_adds_separator_before_new_messages() {
+        let (storage, _dir) = create_test_storage();
+        let id = session_conversation_id("nightly-lint").to_string();
+        storage
+            .upsert_conversation(&id, "Lint", None, &[Message::user("one")], IfExists::Append)
+            .expect("first save failed");
+        storage
+            .upsert_conversation(
+                &id,
+                "Lint",
+                None,
+                &[Message::user("two"), Message::assistant("done")],
+                IfExists::Append,
+            )
+            .expect("append failed");
+
+        let (_, _, messages) = storage.load_conversation(&id).unwrap().unwrap();
+        let contents: Vec<_> = messages
+            .iter()
+            .map(|message| (message.role.as_str(), message.content.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                ("user", "one"),
+                ("system", SESSION_SEPARATOR),
+                ("user", "two"),
+                ("assistant", "done"),
+            ]
+        );
+        assert_eq!(storage.list_sessions().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_upsert_conversation_fail_refuses_existing_id() {
+        let (storage, _dir) = create_test_storage();
+        let id = session_conversation_id("nightly-lint").to_string();
+        storage
+            .upsert_conversation(&id, "Lint", None, &[Message::user("one")], IfExists::Fail)
+            .expect("a new id is saved");
+
+        let err = storage
+            .upsert_conversation(&id, "Lint", None, &[Message::user("two")], IfExists::Fail)
+            .unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+        let (_, _, messages) = storage.load_conversation(&id).unwrap().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].content.as_deref(), Some("one"));
+    }
+
+    #[test]
+    fn test_session_key_lookup_and_literal_prefix_matching() {
+        let (storage, _dir) = create_test_storage();
+        let key = "deploy_job/42";
+        let id = session_conversation_id(key).to_string();
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+        storage
+            .upsert_conversation(&id, "Deploy", None, &[], IfExists::Replace)
+            .expect("save failed");
+        storage
+            .save_conversation("deployXjob-other", "Other", None, &[])
+            .expect("save failed");
+
+        // The key finds its conversation through every prefix-matching loader
+        assert_eq!(storage.load_conversation(key).unwrap().unwrap().0, "Deploy");
+        assert_eq!(
+            storage.resolve_conversation_id(key).unwrap(),
+            Some(id.clone())
+        );
+        assert!(storage.set_conversation_pinned(key, true).unwrap());
+        assert_eq!(storage.add_conversation_tag(key, "ci").unwrap(), "ci");
+        assert_eq!(storage.list_conversation_tags(&id).unwrap(), vec!["ci"]);
+
+        // `_` in a prefix is literal, so it does not match "deployXjob-other"
+        assert!(storage.load_conversation("deploy_").unwrap().is_none());
+        assert_eq!(
+            storage.resolve_conversation_id(&id[..8]).unwrap(),
+            Some(id.clone())
+        );
+
+        storage.delete_conversation(key).expect("delete failed");
+        assert!(!storage.conversation_exists(&id).unwrap());
+        assert!(storage.conversation_exists("deployXjob-other").unwrap());
+    }
+
+    #[test]
+    fn test_load_conversation_returns_none_for_missing_id() {
+        let (storage, _dir) = create_test_storage();
+        let result = storage
+            .load_conversation("non-existent-id")
+            .expect("load failed");
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_list_sessions_returns_ordered_by_updated_at() {
+        let (storage, _dir) = create_test_storage();
+
+        let id1 = "session-1";
+        storage
+            .save_conversation(
+                id1,
+                "A",
+                Some("gpt-4"),
+                &[crate::providers::Message::user("a")],
+            )
+            .expect("save1 failed");
+
+        sleep(Duration::from_millis(10));
+
+        let id2 = "session-2";
+        storage
+            .save_conversation(
+                id2,
+                "B",
+                Some("gpt-4"),
+                &[crate::providers::Message::user("b")],
+            )
+            .expect("save2 failed");
+
+        let sessions = storage.list_sessions().expect("list failed");
+        assert!(sessions.len() >= 2);
+        assert_eq!(sessions[0].id, id2);
+        assert_eq!(sessions[1].id, id1);
     }
-}
 
-fn parse_event_kind(value: &str) -> Result<AcpEventKind> {
-    match value {
-        "run_created" => Ok(AcpEventKind::RunCreated),
-        "run_status_changed" => Ok(AcpEventKind::RunStatusChanged),
-        "run_output_appended" => Ok(AcpEventKind::RunOutputAppended),
-        "run_completed" => Ok(AcpEventKind::RunCompleted),
-        "run_failed" => Ok(AcpEventKind::RunFailed),
-        "run_cancelled" => Ok(AcpEventKind::RunCancelled),
-        "run_awaiting_input" => Ok(AcpEventKind::RunAwaitingInput),
-        "session_created" => Ok(AcpEventKind::SessionCreated),
-        other => Err(XzatomaError::Storage(format!(
-            "Unknown stored ACP event kind: {}",
-            other
-        ))),
+    #[test]
+    fn test_list_sessions_reports_unparseable_dates_as_epoch() {
+        let (storage, _dir) = create_test_storage();
+        for id in ["good", "corrupt"] {
+            storage
+                .save_conversation(id, id, None, &[crate::providers::Message::user(id)])
+                .expect("save failed");
+        }
+        storage
+            .connection()
+            .unwrap()
+            .execute(
+                "UPDATE conversations SET created_at = 'garbage', updated_at = 'garbage'
+                 WHERE id = 'corrupt'",
+                [],
+            )
+            .expect("update failed");
+
+        let sessions = storage.list_sessions().expect("list failed");
+        let ids: Vec<&str> = sessions.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["good", "corrupt"]);
+        assert_eq!(sessions[1].updated_at, DateTime::<Utc>::UNIX_EPOCH);
+        assert_eq!(sessions[1].created_at, DateTime::<Utc>::UNIX_EPOCH);
+        assert!(sessions[0].updated_at > DateTime::<Utc>::UNIX_EPOCH);
     }
-}
 
-fn stored_acp_stdio_session_from_row(
-    row: &rusqlite::Row<'_>,
-) -> rusqlite::Result<Result<StoredAcpStdioSession>> {
-    let session_id: String = row.get(0)?;
-    let workspace_root: String = row.get(1)?;
-    let conversation_id: String = row.get(2)?;
-    let provider_type: String = row.get(3)?;
-    let model: Option<String> = row.get(4)?;
-    let created_at_str: String = row.get(5)?;
-    let updated_at_str: String = row.get(6)?;
-    let metadata_json: String = row.get(7)?;
+    #[test]
+    fn test_list_sessions_returns_empty_for_new_db() {
+        let (storage, _dir) = create_test_storage();
+        let sessions = storage.list_sessions().expect("list failed");
+        assert!(sessions.is_empty());
+    }
 
-    let created_at = parse_rfc3339_to_utc(&created_at_str);
-    let updated_at = parse_rfc3339_to_utc(&updated_at_str);
-    let metadata = serde_json::from_str::<BTreeMap<String, String>>(&metadata_json)
-        .context("Failed to deserialize ACP stdio session metadata")
-        .map_err(|e| XzatomaError::Storage(e.to_string()));
+    #[test]
+    fn test_delete_conversation_removes_record() {
+        let (storage, _dir) = create_test_storage();
+        let id = "to-delete";
+        storage
+            .save_conversation(id, "Title", None, &[crate::providers::Message::user("x")])
+            .expect("save failed");
+
+        storage.delete_conversation(id).expect("delete failed");
+        assert!(storage
+            .load_conversation(id)
+            .expect("load failed")
+            .is_none());
+    }
+
+    #[test]
+    fn test_delete_conversation_is_idempotent() {
+        let (storage, _dir) = create_test_storage();
+        let id = "to-delete-2";
+        storage
+            .save_conversation(id, "Title", None, &[crate::providers::Message::user("x")])
+            .expect("save failed");
+
+        storage
+            .delete_conversation(id)
+            .expect("first delete failed");
+        storage
+            .delete_conversation(id)
+            .expect("second delete failed");
+    }
+
+    fn save_backdated_conversation(
+        storage: &SqliteStorage,
+        id: &str,
+        days_ago: i64,
+        content: &str,
+    ) {
+        storage
+            .save_conversation(id, id, None, &[crate::providers::Message::user(content)])
+            .expect("save failed");
+
+        let updated_at = (Utc::now() - ChronoDuration::days(days_ago)).to_rfc3339();
+        let conn = Connection::open(storage.database_path()).expect("open connection");
+        conn.execute(
+            "UPDATE conversations SET updated_at = ? WHERE id = ?",
+            params![updated_at, id],
+        )
+        .expect("backdate failed");
+    }
+
+    fn remaining_ids(storage: &SqliteStorage) -> Vec<String> {
+        let mut ids: Vec<String> = storage
+            .list_sessions()
+            .expect("list failed")
+            .into_iter()
+            .map(|session| session.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn removed_ids(report: &PruneReport) -> Vec<String> {
+        report
+            .removed
+            .iter()
+            .map(|session| session.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_init_adds_pinned_column_to_existing_database() {
+        let dir = tempdir().expect("failed to create tempdir");
+        let db_path = dir.path().join("history.db");
+        let conn = Connection::open(&db_path).expect("open connection");
+        conn.execute_batch(
+            "CREATE TABLE conversations (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                model TEXT,
+                messages JSON NOT NULL
+            );
+            INSERT INTO conversations VALUES (
+                'legacy', 'Legacy', '2024-01-01T00:00:00+00:00',
+                '2024-01-01T00:00:00+00:00', NULL, '[]'
+            );",
+        )
+        .expect("create legacy schema");
+        drop(conn);
+
+        let storage = SqliteStorage::new_with_path(&db_path).expect("failed to create storage");
+        let sessions = storage.list_sessions().expect("list failed");
+
+        assert_eq!(sessions.len(), 1);
+        assert!(!sessions[0].pinned);
+        assert!(storage
+            .load_pinned_messages("legacy")
+            .expect("load pinned failed")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_init_adds_revision_column_to_existing_database() {
+        let dir = tempdir().expect("failed to create tempdir");
+        let db_path = dir.path().join("history.db");
+        let conn = Connection::open(&db_path).expect("open connection");
+        conn.execute_batch(
+            "CREATE TABLE conversations (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                model TEXT,
+                messages JSON NOT NULL
+            );
+            INSERT INTO conversations VALUES (
+                'legacy', 'Legacy', '2024-01-01T00:00:00+00:00',
+                '2024-01-01T00:00:00+00:00', NULL, '[]'
+            );",
+        )
+        .expect("create legacy schema");
+        drop(conn);
+
+        let storage = SqliteStorage::new_with_path(&db_path).expect("failed to create storage");
+
+        assert_eq!(
+            storage.conversation_revision("legacy").expect("revision"),
+            Some(1)
+        );
+        let revision = storage
+            .save_conversation_checked(
+                "legacy",
+                "Legacy",
+                None,
+                &[Message::user("hi")],
+                ExpectedRevision::Exactly(1),
+            )
+            .expect("save failed");
+        assert_eq!(revision, 2);
+    }
+
+    #[test]
+    fn test_every_save_increments_the_revision() {
+        let storage = SqliteStorage::new_in_memory().expect("failed to create storage");
+        assert_eq!(storage.conversation_revision("c1").expect("revision"), None);
+
+        storage
+            .save_conversation("c1", "Chat", None, &[Message::user("one")])
+            .expect("save failed");
+        assert_eq!(
+            storage.conversation_revision("c1").expect("revision"),
+            Some(1)
+        );
+        storage
+            .save_conversation("c1", "Chat", None, &[Message::user("two")])
+            .expect("save failed");
+        storage
+            .upsert_conversation(
+                "c1",
+                "Chat",
+                None,
+                &[Message::user("three")],
+                IfExists::Append,
+            )
+            .expect("upsert failed");
+        assert_eq!(
+            storage.conversation_revision("c1").expect("revision"),
+            Some(3)
+        );
+
+        // Pins and directories are not message saves
+        storage.set_pinned_messages("c1", &[0]).expect("pin failed");
+        assert_eq!(
+            storage.conversation_revision("c1").expect("revision"),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_interleaved_checked_saves_lose_no_messages() {
+        let storage = SqliteStorage::new_in_memory().expect("failed to create storage");
+        let base = vec![Message::user("shared start")];
+        let revision = storage
+            .save_conversation_checked("c1", "Chat", None, &base, ExpectedRevision::Exactly(0))
+            .expect("first save failed");
+
+        // Two sessions resume revision 1 and each add a message
+        let mut first = base.clone();
+        first.push(Message::user("from the first session"));
+        let mut second = base.clone();
+        second.push(Message::user("from the second session"));
+
+        let first_revision = storage
+            .save_conversation_checked(
+                "c1",
+                "Chat",
+                None,
+                &first,
+                ExpectedRevision::Exactly(revision),
+            )
+            .expect("first writer should save");
+        assert_eq!(first_revision, 2);
+
+        let error = storage
+            .save_conversation_checked(
+                "c1",
+                "Chat",
+                None,
+                &second,
+                ExpectedRevision::Exactly(revision),
+            )
+            .expect_err("second writer must not overwrite the first");
+        let XzatomaError::ConversationConflict(conflict) = error else {
+            panic!("expected a conflict, got {:?}", error);
+        };
+        assert_eq!(conflict.id, "c1");
+        assert_eq!(conflict.expected_revision, 1);
+        assert_eq!(conflict.stored_revision, 2);
+        assert!(conflict.stored_updated_at.is_some());
+        let (_, _, stored) = storage.load_conversation("c1").unwrap().unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[1].content.as_deref(), Some("from the first session"));
+
+        // The second writer merges its unsaved message onto the stored state
+        let mut merged = stored;
+        merged.extend_from_slice(&second[base.len()..]);
+        let merged_revision = storage
+            .save_conversation_checked(
+                "c1",
+                "Chat",
+                None,
+                &merged,
+                ExpectedRevision::Exactly(conflict.stored_revision),
+            )
+            .expect("merged save failed");
+        assert_eq!(merged_revision, 3);
+
+        let (_, _, stored) = storage.load_conversation("c1").unwrap().unwrap();
+        let contents: Vec<&str> = stored
+            .iter()
+            .filter_map(|message| message.content.as_deref())
+            .collect();
+        assert_eq!(
+            contents,
+            [
+                "shared start",
+                "from the first session",
+                "from the second session"
+            ]
+        );
+
+        // A forced save still overwrites
+        storage
+            .save_conversation_checked("c1", "Chat", None, &base, ExpectedRevision::Any)
+            .expect("forced save failed");
+        assert_eq!(
+            storage.conversation_revision("c1").expect("revision"),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn test_in_memory_storage_is_shared_by_clones_only() {
+        let storage = SqliteStorage::new_in_memory().expect("failed to create storage");
+        let clone = storage.clone();
+        storage
+            .save_conversation("mem", "Memory", None, &[Message::user("hi")])
+            .expect("save failed");
+
+        assert!(clone
+            .load_conversation("mem")
+            .expect("load failed")
+            .is_some());
+        assert_eq!(storage.database_path(), &PathBuf::from(":memory:"));
+
+        let other = SqliteStorage::new_in_memory().expect("failed to create storage");
+        assert!(other.list_sessions().expect("list failed").is_empty());
+    }
+
+    fn sample_run_turn(run_id: &str, turn: u32, content: &str) -> StoredRunTurn {
+        StoredRunTurn {
+            run_id: run_id.to_string(),
+            turn,
+            started_at: Utc::now(),
+            duration_ms: 12,
+            model: "fake-model".to_string(),
+            messages: vec![crate::providers::Message::user(content)],
+            tools: Vec::new(),
+            response: None,
+            error: None,
+            tool_calls: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn test_run_turns_round_trip_compressed_in_order() {
+        let (storage, _dir) = create_test_storage();
+        let content = "repeat ".repeat(2000);
+        storage
+            .save_run_turn(&sample_run_turn("run-abc", 2, "second"))
+            .expect("save failed");
+        storage
+            .save_run_turn(&sample_run_turn("run-abc", 1, &content))
+            .expect("save failed");
+        storage
+            .save_run_turn(&sample_run_turn("run-other", 1, "other"))
+            .expect("save failed");
+
+        let turns = storage.load_run_turns("run-abc").expect("load failed");
+        let numbers: Vec<u32> = turns.iter().map(|turn| turn.turn).collect();
+        assert_eq!(numbers, vec![1, 2]);
+        assert_eq!(
+            turns[0].messages[0].content.as_deref(),
+            Some(content.as_str())
+        );
+
+        let (blob, raw): (i64, i64) = storage
+            .connection()
+            .unwrap()
+            .query_row(
+                "SELECT length(payload), payload_bytes FROM run_turns WHERE run_id = ? AND turn = 1",
+                params!["run-abc"],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert!(
+            blob * 10 < raw,
+            "payload should be compressed: {blob} of {raw}"
+        );
+
+        let mut replaced = sample_run_turn("run-abc", 2, "second");
+        replaced.error = Some("boom".to_string());
+        storage.save_run_turn(&replaced).expect("save failed");
+        let turns = storage.load_run_turns("run-abc").expect("load failed");
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[1].error.as_deref(), Some("boom"));
+
+        assert!(storage.load_run_turns("missing").unwrap().is_empty());
+        assert_eq!(
+            storage.resolve_run_id("run-a").unwrap().as_deref(),
+            Some("run-abc")
+        );
+        assert_eq!(storage.resolve_run_id("nope").unwrap(), None);
+    }
+
+    #[test]
+    fn test_replayed_from_links_conversations() {
+        let (storage, _dir) = create_test_storage();
+        let message = [crate::providers::Message::user("task")];
+        storage
+            .save_conversation("original-conv-1", "Original", None, &message)
+            .expect("save failed");
+        storage
+            .save_conversation("replay-conv-1", "Replay", None, &message)
+            .expect("save failed");
+
+        assert_eq!(
+            storage
+                .resolve_conversation_id("original")
+                .expect("resolve failed")
+                .as_deref(),
+            Some("original-conv-1")
+        );
+        assert!(storage
+            .set_replayed_from("replay-conv-1", "original-conv-1")
+            .expect("set replay link failed"));
+        assert_eq!(
+            storage
+                .load_replayed_from("replay-conv")
+                .expect("load replay link failed")
+                .as_deref(),
+            Some("original-conv-1")
+        );
+        assert!(storage
+            .load_replayed_from("original-conv-1")
+            .expect("load replay link failed")
+            .is_none());
+        assert!(!storage
+            .set_replayed_from("missing", "original-conv-1")
+            .expect("set replay link failed"));
+    }
+
+    #[test]
+    fn test_pinned_messages_round_trip() {
+        let (storage, _dir) = create_test_storage();
+        storage
+            .save_conversation(
+                "pinned-msgs-1",
+                "Title",
+                None,
+                &[
+                    crate::providers::Message::user("task"),
+                    crate::providers::Message::assistant("ok"),
+                ],
+            )
+            .expect("save failed");
+
+        assert!(storage
+            .set_pinned_messages("pinned-msgs", &[0])
+            .expect("set pinned failed"));
+        assert_eq!(
+            storage
+                .load_pinned_messages("pinned-msgs-1")
+                .expect("load pinned failed"),
+            vec![0]
+        );
 
-    Ok(created_at.and_then(|created_at| {
-        updated_at.and_then(|updated_at| {
-            metadata.map(|metadata| StoredAcpStdioSession {
-                session_id,
-                workspace_root,
-                conversation_id,
-                provider_type,
-                model,
-                created_at,
-                updated_at,
-                metadata,
-            })
-        })
-    }))
-}
+        // Saving the conversation again leaves the pinned indices alone
+        storage
+            .save_conversation(
+                "pinned-msgs-1",
+                "Title",
+                None,
+                &[crate::providers::Message::user("task")],
+            )
+            .expect("save failed");
+        assert_eq!(
+            storage
+                .load_pinned_messages("pinned-msgs-1")
+                .expect("load pinned failed"),
+            vec![0]
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::acp::{AcpMessage, AcpMessagePart, AcpRole, AcpTextPart};
-    use chrono::Timelike;
-    use serial_test::serial;
-    use std::thread::sleep;
-    use std::time::Duration;
-    use tempfile::tempdir;
+        assert!(!storage
+            .set_pinned_messages("missing", &[1])
+            .expect("set pinned failed"));
 
-    fn create_test_storage() -> (SqliteStorage, tempfile::TempDir) {
-        let dir = tempdir().expect("failed to create tempdir");
-        let db_path = dir.path().join("history.db");
-        let storage = SqliteStorage::new_with_path(db_path).expect("failed to create storage");
-        (storage, dir)
+        // An ambiguous prefix pins nothing
+        storage
+            .save_conversation("pinned-msgs-2", "Other", None, &[])
+            .expect("save failed");
+        let error = storage
+            .set_pinned_messages("pinned-msgs", &[1])
+            .expect_err("ambiguous prefix accepted");
+        assert!(error.to_string().contains("ambiguous"));
+        assert_eq!(
+            storage
+                .load_pinned_messages("pinned-msgs-1")
+                .expect("load pinned failed"),
+            vec![0]
+        );
+        assert!(storage
+            .load_pinned_messages("pinned-msgs-2")
+            .expect("load pinned failed")
+            .is_empty());
+        assert!(storage
+            .load_pinned_messages("missing")
+            .expect("load pinned failed")
+            .is_empty());
     }
 
-    fn sample_run() -> AcpRun {
-        let session_id = AcpSessionId::new("session_123".to_string()).expect("valid session id");
-        let session = AcpRunSession::new(session_id.clone()).expect("valid session");
-        let request = AcpRunCreateRequest::new(
-            session_id,
-            vec![AcpMessage::new(
-                AcpRole::User,
-                vec![AcpMessagePart::Text(AcpTextPart::new(
-                    "hello from acp".to_string(),
-                ))],
+    #[test]
+    fn test_message_cwds_round_trip() {
+        let (storage, _dir) = create_test_storage();
+        storage
+            .save_conversation(
+                "cwd-msgs-1",
+                "Title",
+                None,
+                &[
+                    crate::providers::Message::user("task"),
+                    crate::providers::Message::assistant("ok"),
+                ],
             )
-            .expect("valid message")],
-        )
-        .expect("valid create request");
+            .expect("save failed");
 
-        AcpRun::new(
-            AcpRunId::new("run_123".to_string()).expect("valid run id"),
-            request,
-            session,
-        )
-        .expect("valid run")
+        assert!(storage
+            .load_message_cwds("cwd-msgs-1")
+            .expect("load cwds failed")
+            .is_empty());
+
+        let cwds = vec![Some(PathBuf::from("/repo/services/billing")), None];
+        assert!(storage
+            .set_message_cwds("cwd-msgs", &cwds)
+            .expect("set cwds failed"));
+        assert_eq!(
+            storage
+                .load_message_cwds("cwd-msgs-1")
+                .expect("load cwds failed"),
+            cwds
+        );
+
+        assert!(!storage
+            .set_message_cwds("missing", &cwds)
+            .expect("set cwds failed"));
     }
 
     #[test]
-    fn test_sqlite_storage_init_creates_conversation_and_acp_tables() {
+    fn test_set_conversation_pinned_supports_prefix_and_reports_missing() {
         let (storage, _dir) = create_test_storage();
-        let conn = Connection::open(storage.database_path()).expect("open connection");
+        storage
+            .save_conversation("pin-me-123", "Title", None, &[])
+            .expect("save failed");
 
-        let tables = [
-            "conversations",
-            "acp_sessions",
-            "acp_stdio_sessions",
-            "acp_runs",
-            "acp_run_events",
-            "acp_await_states",
-            "acp_cancellations",
-        ];
+        assert!(storage
+            .set_conversation_pinned("pin-me", true)
+            .expect("pin failed"));
+        assert!(storage.list_sessions().expect("list failed")[0].pinned);
 
-        for table in tables {
-            let count: i64 = conn
-                .query_row(
-                    "SELECT count(*) FROM sqlite_master WHERE type='table' AND name=?",
-                    params![table],
-                    |row| row.get(0),
-                )
-                .expect("query row");
-            assert_eq!(count, 1, "missing table {}", table);
-        }
+        assert!(storage
+            .set_conversation_pinned("pin-me-123", false)
+            .expect("unpin failed"));
+        assert!(!storage.list_sessions().expect("list failed")[0].pinned);
+
+        assert!(!storage
+            .set_conversation_pinned("missing", true)
+            .expect("pin failed"));
     }
 
     #[test]
-    fn test_sqlite_storage_init_creates_acp_stdio_session_indexes() {
+    fn test_set_conversation_pinned_rejects_ambiguous_prefix() {
         let (storage, _dir) = create_test_storage();
-        let conn = Connection::open(storage.database_path()).expect("open connection");
+        storage
+            .save_conversation("shared-a", "A", None, &[])
+            .expect("save failed");
+        storage
+            .save_conversation("shared-b", "B", None, &[])
+            .expect("save failed");
 
-        for index in [
-            "idx_acp_stdio_sessions_workspace_root",
-            "idx_acp_stdio_sessions_updated_at",
-        ] {
-            let count: i64 = conn
-                .query_row(
-                    "SELECT count(*) FROM sqlite_master WHERE type='index' AND name=?",
-                    params![index],
-                    |row| row.get(0),
-                )
-                .expect("query row");
-            assert_eq!(count, 1, "missing index {}", index);
+        for id in ["shared", ""] {
+            let result = storage.set_conversation_pinned(id, true);
+            assert!(
+                matches!(result, Err(XzatomaError::Storage(message)) if message.contains("ambiguous"))
+            );
         }
+        assert!(storage
+            .list_sessions()
+            .expect("list failed")
+            .iter()
+            .all(|session| !session.pinned));
     }
 
-    fn save_test_conversation(storage: &SqliteStorage, id: &str) {
-        storage
-            .save_conversation(
-                id,
-                "New Conversation",
-                Some("llama3.2:latest"),
-                &[crate::providers::Message::user("hello")],
-            )
-            .expect("conversation save failed");
-    }
+    /// Seed 30 sessions, one per day. Sessions 0..20 use `model-a` and the
+    /// rest `model-b`. Even sessions call `read_file`; odd sessions also call
+    /// `terminal`. Session `i` lasts `i` minutes.
+    fn seed_stats_sessions(storage: &SqliteStorage, now: DateTime<Utc>) {
+        use crate::providers::{FunctionCall, ToolCall};
 
-    fn sample_stdio_session(
-        session_id: &str,
-        workspace_root: &str,
-        conversation_id: &str,
-    ) -> StoredAcpStdioSession {
-        let now = Utc::now();
-        StoredAcpStdioSession {
-            session_id: session_id.to_string(),
-            workspace_root: workspace_root.to_string(),
-            conversation_id: conversation_id.to_string(),
-            provider_type: "ollama".to_string(),
-            model: Some("llama3.2:latest".to_string()),
-            created_at: now,
-            updated_at: now,
-            metadata: BTreeMap::new(),
+        let conn = Connection::open(storage.database_path()).expect("open connection");
+        for i in 0..30i64 {
+            let id = format!("session-{:02}", i);
+            let model = if i < 20 { "model-a" } else { "model-b" };
+            let tools: &[&str] = if i % 2 == 0 {
+                &["read_file"]
+            } else {
+                &["read_file", "terminal"]
+            };
+
+            let mut messages = vec![Message::user("abcdefgh")];
+            messages.push(Message::assistant_with_tools(
+                tools
+                    .iter()
+                    .enumerate()
+                    .map(|(index, name)| ToolCall {
+                        id: format!("call-{}", index),
+                        function: FunctionCall {
+                            name: name.to_string(),
+                            arguments: "{}".to_string(),
+                        },
+                    })
+                    .collect(),
+            ));
+            for index in 0..tools.len() {
+                messages.push(Message::tool_result(format!("call-{}", index), "ok"));
+            }
+            storage
+                .save_conversation(&id, &id, Some(model), &messages)
+                .expect("save failed");
+
+            let updated_at = now - ChronoDuration::days(i);
+            let created_at = updated_at - ChronoDuration::minutes(i);
+            conn.execute(
+                "UPDATE conversations SET created_at = ?, updated_at = ? WHERE id = ?",
+                params![created_at.to_rfc3339(), updated_at.to_rfc3339(), id],
+            )
+            .expect("backdate failed");
         }
     }
 
     #[test]
-    fn test_save_and_load_acp_stdio_session_by_workspace_root() {
+    fn test_history_stats_aggregates_synthetic_sessions() {
         let (storage, _dir) = create_test_storage();
-        save_test_conversation(&storage, "conversation-stdio-1");
+        let now = Utc::now();
+        seed_stats_sessions(&storage, now);
 
-        let mut session = sample_stdio_session(
-            "xzatoma-session-1",
-            "/tmp/xzatoma-workspace",
-            "conversation-stdio-1",
-        );
-        session
-            .metadata
-            .insert("source".to_string(), "zed".to_string());
+        let stats = storage.history_stats(None, 3).expect("stats failed");
 
-        storage
-            .save_acp_stdio_session(&session)
-            .expect("stdio session save failed");
+        assert_eq!(stats.total_sessions, 30);
+        // Even sessions hold 3 messages, odd sessions 4
+        assert_eq!(stats.total_messages, 105);
+        assert!((stats.average_messages - 3.5).abs() < 1e-9);
+        assert!((stats.average_duration_minutes - 14.5).abs() < 0.01);
 
-        let loaded = storage
-            .load_latest_acp_stdio_session_by_workspace_root("/tmp/xzatoma-workspace")
-            .expect("stdio session load failed")
-            .expect("stdio session should exist");
+        assert_eq!(stats.per_day.len(), 3);
+        assert!(stats.per_day.iter().all(|day| day.sessions == 1));
+        assert!(stats.per_day[0].period > stats.per_day[1].period);
 
-        assert_eq!(loaded.session_id, "xzatoma-session-1");
-        assert_eq!(loaded.workspace_root, "/tmp/xzatoma-workspace");
-        assert_eq!(loaded.conversation_id, "conversation-stdio-1");
         assert_eq!(
-            loaded.metadata.get("source").map(String::as_str),
-            Some("zed")
+            stats.per_model,
+            vec![
+                ModelUsage {
+                    model: "model-a".to_string(),
+                    sessions: 20,
+                    messages: 70,
+                    estimated_tokens: 70,
+                },
+                ModelUsage {
+                    model: "model-b".to_string(),
+                    sessions: 10,
+                    messages: 35,
+                    estimated_tokens: 35,
+                },
+            ]
+        );
+
+        assert_eq!(
+            stats.top_tools,
+            vec![
+                ToolUsage {
+                    tool: "read_file".to_string(),
+                    calls: 30,
+                },
+                ToolUsage {
+                    tool: "terminal".to_string(),
+                    calls: 15,
+                },
+            ]
         );
+
+        let longest: Vec<&str> = stats
+            .longest_sessions
+            .iter()
+            .map(|session| session.id.as_str())
+            .collect();
+        assert_eq!(longest, vec!["session-01", "session-03", "session-05"]);
+        assert!((stats.longest_sessions[1].duration_minutes - 3.0).abs() < 0.01);
     }
 
     #[test]
-    fn test_save_acp_stdio_session_updates_existing_mapping() {
+    fn test_history_stats_weeks_cover_every_session() {
         let (storage, _dir) = create_test_storage();
-        save_test_conversation(&storage, "conversation-stdio-2");
-        save_test_conversation(&storage, "conversation-stdio-3");
+        seed_stats_sessions(&storage, Utc::now());
 
-        let original = sample_stdio_session(
-            "xzatoma-session-2",
-            "/tmp/xzatoma-original",
-            "conversation-stdio-2",
-        );
+        let stats = storage.history_stats(None, 100).expect("stats failed");
+
+        assert_eq!(stats.per_day.len(), 30);
+        assert!(stats.per_week.len() >= 5);
+        assert!(stats.per_week.iter().all(|week| week.period.contains("-W")));
+        let sessions: u64 = stats.per_week.iter().map(|week| week.sessions).sum();
+        let messages: u64 = stats.per_week.iter().map(|week| week.messages).sum();
+        assert_eq!(sessions, 30);
+        assert_eq!(messages, 105);
+    }
+
+    #[test]
+    fn test_history_stats_since_filters_by_last_update() {
+        let (storage, _dir) = create_test_storage();
+        let now = Utc::now();
+        seed_stats_sessions(&storage, now);
+
+        let since = now - ChronoDuration::hours(9 * 24 + 12);
+        let stats = storage
+            .history_stats(Some(since), 10)
+            .expect("stats failed");
+
+        assert_eq!(stats.since, Some(since));
+        assert_eq!(stats.total_sessions, 10);
+        assert_eq!(stats.total_messages, 35);
+        assert_eq!(stats.per_model.len(), 1);
+        assert_eq!(stats.per_model[0].model, "model-a");
+        assert_eq!(stats.top_tools[1].calls, 5);
+    }
+
+    #[test]
+    fn test_history_stats_empty_database() {
+        let (storage, _dir) = create_test_storage();
+
+        let stats = storage.history_stats(None, 10).expect("stats failed");
+
+        assert_eq!(stats, HistoryStats::default());
+    }
+
+    fn usage_record(timestamp: &str, model: &str, session: &str, cost: f64) -> UsageRecord {
+        UsageRecord {
+            timestamp: DateTime::parse_from_rfc3339(timestamp)
+                .expect("valid timestamp")
+                .with_timezone(&Utc),
+            provider: "openai".to_string(),
+            model: model.to_string(),
+            session_id: session.to_string(),
+            prompt_tokens: 1000,
+            completion_tokens: 100,
+            estimated_cost: cost,
+        }
+    }
+
+    fn month(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .expect("valid timestamp")
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_usage_aggregation_respects_month_boundaries() {
+        let (storage, _dir) = create_test_storage();
         storage
-            .save_acp_stdio_session(&original)
-            .expect("initial stdio session save failed");
+            .save_usage_records(&[
+                usage_record("2024-12-31T23:59:59.999Z", "gpt-5", "s1", 5.0),
+                usage_record("2025-01-01T00:00:00Z", "gpt-5", "s1", 1.0),
+                usage_record("2025-01-15T12:00:00Z", "gpt-5-mini", "s2", 0.25),
+                usage_record("2025-01-31T23:59:59.999Z", "gpt-5", "s2", 2.0),
+                usage_record("2025-02-01T00:00:00Z", "gpt-5", "s3", 7.0),
+            ])
+            .expect("save usage");
+        let (january, february) = (month("2025-01-01T00:00:00Z"), month("2025-02-01T00:00:00Z"));
+
+        let total = storage.usage_total(january, february).expect("usage total");
+        assert_eq!(total.requests, 3);
+        assert_eq!(total.prompt_tokens, 3000);
+        assert_eq!(total.completion_tokens, 300);
+        assert!((total.estimated_cost - 3.25).abs() < 1e-9);
+
+        let by_model = storage
+            .usage_by(january, february, UsageGrouping::Model)
+            .expect("usage by model");
+        let keys: Vec<&str> = by_model.iter().map(|row| row.key.as_str()).collect();
+        assert_eq!(keys, vec!["gpt-5", "gpt-5-mini"]);
+        assert_eq!(by_model[0].requests, 2);
+
+        let by_day = storage
+            .usage_by(january, february, UsageGrouping::Day)
+            .expect("usage by day");
+        let days: Vec<&str> = by_day.iter().map(|row| row.key.as_str()).collect();
+        assert_eq!(days, vec!["2025-01-01", "2025-01-15", "2025-01-31"]);
+
+        let by_session = storage
+            .usage_by(january, february, UsageGrouping::Session)
+            .expect("usage by session");
+        assert_eq!(by_session[0].key, "s2");
+        assert!((by_session[0].estimated_cost - 2.25).abs() < 1e-9);
+
+        let empty = storage
+            .usage_total(month("2025-03-01T00:00:00Z"), month("2025-04-01T00:00:00Z"))
+            .expect("usage total");
+        assert_eq!(empty, UsageAggregate::default());
+    }
 
-        let mut updated = sample_stdio_session(
-            "xzatoma-session-2",
-            "/tmp/xzatoma-updated",
-            "conversation-stdio-3",
+    #[test]
+    fn test_usage_queries_use_created_at_index() {
+        let (storage, _dir) = create_test_storage();
+        let conn = storage.connection().expect("connection");
+
+        let plan: Vec<String> = conn
+            .prepare(
+                "EXPLAIN QUERY PLAN
+                 SELECT model, SUM(estimated_cost) FROM usage_log
+                 WHERE created_at >= ?1 AND created_at < ?2
+                 GROUP BY model",
+            )
+            .and_then(|mut stmt| {
+                let rows = stmt.query_map(params!["a", "b"], |row| row.get::<_, String>(3))?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .expect("query plan");
+
+        assert!(
+            plan.iter()
+                .any(|step| step.contains("idx_usage_log_created_at")),
+            "unexpected plan: {:?}",
+            plan
         );
-        updated.model = Some("gpt-5-mini".to_string());
-        updated.provider_type = "copilot".to_string();
+    }
 
-        storage
-            .save_acp_stdio_session(&updated)
-            .expect("updated stdio session save failed");
+    #[test]
+    fn test_prune_old_sessions_removes_sessions_older_than_max_age() {
+        let (storage, _dir) = create_test_storage();
+        save_backdated_conversation(&storage, "old", 40, "a");
+        save_backdated_conversation(&storage, "recent", 5, "b");
 
-        let loaded = storage
-            .load_acp_stdio_session_by_session_id("xzatoma-session-2")
-            .expect("stdio session lookup failed")
-            .expect("stdio session should exist");
+        let retention = RetentionConfig {
+            max_age_days: Some(30),
+            ..RetentionConfig::default()
+        };
+        let report = storage
+            .prune_old_sessions(&retention, false)
+            .expect("prune failed");
 
-        assert_eq!(loaded.workspace_root, "/tmp/xzatoma-updated");
-        assert_eq!(loaded.conversation_id, "conversation-stdio-3");
-        assert_eq!(loaded.provider_type, "copilot");
-        assert_eq!(loaded.model.as_deref(), Some("gpt-5-mini"));
+        assert_eq!(removed_ids(&report), vec!["old".to_string()]);
+        assert_eq!(report.removed[0].reason, PruneReason::MaxAge);
+        assert_eq!(remaining_ids(&storage), vec!["recent".to_string()]);
+    }
+
+    #[test]
+    fn test_prune_old_sessions_keeps_most_recent_max_sessions() {
+        let (storage, _dir) = create_test_storage();
+        for (index, id) in ["s1", "s2", "s3", "s4"].iter().enumerate() {
+            save_backdated_conversation(&storage, id, 10 - index as i64, "x");
+        }
+
+        let retention = RetentionConfig {
+            max_sessions: Some(2),
+            ..RetentionConfig::default()
+        };
+        let report = storage
+            .prune_old_sessions(&retention, false)
+            .expect("prune failed");
+
+        assert_eq!(
+            removed_ids(&report),
+            vec!["s1".to_string(), "s2".to_string()]
+        );
+        assert!(report
+            .removed
+            .iter()
+            .all(|session| session.reason == PruneReason::MaxSessions));
+        assert_eq!(
+            remaining_ids(&storage),
+            vec!["s3".to_string(), "s4".to_string()]
+        );
     }
 
     #[test]
-    fn test_touch_acp_stdio_session_updates_last_activity() {
+    fn test_prune_old_sessions_shrinks_database_below_max_size() {
         let (storage, _dir) = create_test_storage();
-        save_test_conversation(&storage, "conversation-stdio-4");
-
-        let session = sample_stdio_session(
-            "xzatoma-session-4",
-            "/tmp/xzatoma-touch",
-            "conversation-stdio-4",
-        );
-        storage
-            .save_acp_stdio_session(&session)
-            .expect("stdio session save failed");
+        let payload = "x".repeat(400 * 1024);
+        for (index, id) in ["big1", "big2", "big3", "big4", "big5"].iter().enumerate() {
+            save_backdated_conversation(&storage, id, 10 - index as i64, &payload);
+        }
 
-        sleep(Duration::from_millis(5));
+        let retention = RetentionConfig {
+            max_db_size_mb: Some(1),
+            ..RetentionConfig::default()
+        };
+        let report = storage
+            .prune_old_sessions(&retention, false)
+            .expect("prune failed");
+
+        assert!(report.size_before_bytes > 1024 * 1024);
+        assert!(!report.removed.is_empty());
+        assert_eq!(report.removed[0].id, "big1");
+        assert!(report
+            .removed
+            .iter()
+            .all(|session| session.reason == PruneReason::MaxDbSize));
+        assert!(remaining_ids(&storage).contains(&"big5".to_string()));
+        assert!(report.vacuumed);
+        assert!(report.size_after_bytes <= 1024 * 1024 + 64 * 1024);
+    }
 
+    #[test]
+    fn test_prune_old_sessions_never_removes_pinned_sessions() {
+        let (storage, _dir) = create_test_storage();
+        save_backdated_conversation(&storage, "pinned-old", 100, "a");
+        save_backdated_conversation(&storage, "unpinned-old", 90, "b");
+        save_backdated_conversation(&storage, "new", 1, "c");
         storage
-            .touch_acp_stdio_session("xzatoma-session-4")
-            .expect("touch should succeed");
+            .set_conversation_pinned("pinned-old", true)
+            .expect("pin failed");
 
-        let loaded = storage
-            .load_acp_stdio_session_by_session_id("xzatoma-session-4")
-            .expect("stdio session lookup failed")
-            .expect("stdio session should exist");
+        let retention = RetentionConfig {
+            max_sessions: Some(1),
+            max_age_days: Some(30),
+            max_db_size_mb: Some(1),
+        };
+        let report = storage
+            .prune_old_sessions(&retention, false)
+            .expect("prune failed");
 
-        assert!(loaded.updated_at > session.updated_at);
+        assert!(!removed_ids(&report).contains(&"pinned-old".to_string()));
+        assert_eq!(remaining_ids(&storage), vec!["pinned-old".to_string()]);
     }
 
     #[test]
-    fn test_load_latest_acp_stdio_session_by_workspace_returns_newest_mapping() {
+    fn test_prune_old_sessions_dry_run_matches_real_run() {
         let (storage, _dir) = create_test_storage();
-        save_test_conversation(&storage, "conversation-stdio-5");
-        save_test_conversation(&storage, "conversation-stdio-6");
+        save_backdated_conversation(&storage, "a", 50, "a");
+        save_backdated_conversation(&storage, "b", 20, "b");
+        save_backdated_conversation(&storage, "c", 10, "c");
+        save_backdated_conversation(&storage, "d", 1, "d");
+
+        let retention = RetentionConfig {
+            max_sessions: Some(2),
+            max_age_days: Some(30),
+            max_db_size_mb: None,
+        };
 
-        let older = sample_stdio_session(
-            "xzatoma-session-5",
-            "/tmp/xzatoma-shared",
-            "conversation-stdio-5",
+        let dry_run = storage
+            .prune_old_sessions(&retention, true)
+            .expect("dry run failed");
+        assert!(dry_run.dry_run);
+        assert_eq!(remaining_ids(&storage).len(), 4);
+
+        let real = storage
+            .prune_old_sessions(&retention, false)
+            .expect("prune failed");
+        assert_eq!(dry_run.removed, real.removed);
+        assert_eq!(real.removed[0].reason, PruneReason::MaxAge);
+        assert_eq!(real.removed[1].reason, PruneReason::MaxSessions);
+        assert_eq!(
+            remaining_ids(&storage),
+            vec!["c".to_string(), "d".to_string()]
         );
-        storage
-            .save_acp_stdio_session(&older)
-            .expect("older stdio session save failed");
+    }
 
-        sleep(Duration::from_millis(5));
+    #[test]
+    fn test_add_conversation_tag_normalizes_and_deduplicates() {
+        let (storage, _dir) = create_test_storage();
+        storage
+            .save_conversation("tagged-1", "Title", None, &[])
+            .expect("save failed");
 
-        let newer = sample_stdio_session(
-            "xzatoma-session-6",
-            "/tmp/xzatoma-shared",
-            "conversation-stdio-6",
+        assert_eq!(
+            storage
+                .add_conversation_tag("tagged", "  Billing ")
+                .expect("tag failed"),
+            "billing"
         );
         storage
-            .save_acp_stdio_session(&newer)
-            .expect("newer stdio session save failed");
-
-        let loaded = storage
-            .load_latest_acp_stdio_session_by_workspace_root("/tmp/xzatoma-shared")
-            .expect("stdio session lookup failed")
-            .expect("stdio session should exist");
+            .add_conversation_tag("tagged-1", "BILLING")
+            .expect("tag failed");
+        storage
+            .add_conversation_tag("tagged-1", "infra")
+            .expect("tag failed");
 
-        assert_eq!(loaded.session_id, "xzatoma-session-6");
-        assert_eq!(loaded.conversation_id, "conversation-stdio-6");
+        assert_eq!(
+            storage
+                .list_conversation_tags("tagged-1")
+                .expect("list tags failed"),
+            vec!["billing".to_string(), "infra".to_string()]
+        );
+        assert!(storage.add_conversation_tag("tagged-1", "   ").is_err());
+        assert!(storage.add_conversation_tag("missing", "billing").is_err());
     }
 
     #[test]
-    fn test_prune_acp_stdio_sessions_older_than_removes_stale_mappings() {
+    fn test_remove_conversation_tag_reports_whether_tag_existed() {
         let (storage, _dir) = create_test_storage();
-        save_test_conversation(&storage, "conversation-stdio-7");
-
-        let session = sample_stdio_session(
-            "xzatoma-session-7",
-            "/tmp/xzatoma-prune",
-            "conversation-stdio-7",
-        );
         storage
-            .save_acp_stdio_session(&session)
-            .expect("stdio session save failed");
-
-        let deleted = storage
-            .prune_acp_stdio_sessions_older_than(Utc::now() + chrono::Duration::seconds(1))
-            .expect("prune should succeed");
+            .save_conversation("untag-me", "Title", None, &[])
+            .expect("save failed");
+        storage
+            .add_conversation_tag("untag-me", "experiment")
+            .expect("tag failed");
 
-        assert_eq!(deleted, 1);
         assert!(storage
-            .load_acp_stdio_session_by_session_id("xzatoma-session-7")
-            .expect("stdio session lookup failed")
-            .is_none());
+            .remove_conversation_tag("untag-me", "Experiment")
+            .expect("untag failed"));
+        assert!(!storage
+            .remove_conversation_tag("untag-me", "experiment")
+            .expect("untag failed"));
+        assert!(storage
+            .list_conversation_tags("untag-me")
+            .expect("list tags failed")
+            .is_empty());
     }
 
     #[test]
-    fn test_save_conversation_creates_new_record() {
+    fn test_tag_rejects_ambiguous_prefix() {
         let (storage, _dir) = create_test_storage();
-        let id = "test-save-1";
-        let title = "Test Save 1";
-        let model = Some("gpt-4");
-        let messages = vec![crate::providers::Message::user("Hello")];
-
         storage
-            .save_conversation(id, title, model, &messages)
+            .save_conversation("shared-a", "A", None, &[])
+            .expect("save failed");
+        storage
+            .save_conversation("shared-b", "B", None, &[])
             .expect("save failed");
 
-        let loaded = storage.load_conversation(id).expect("load failed");
-        assert!(loaded.is_some());
-
-        let (loaded_title, loaded_model, loaded_messages) =
-            loaded.expect("conversation should exist");
-        assert_eq!(loaded_title, title.to_string());
-        assert_eq!(loaded_model, model.map(|value| value.to_string()));
-        assert_eq!(loaded_messages.len(), 1);
-        assert_eq!(loaded_messages[0].content.as_deref(), Some("Hello"));
+        let result = storage.add_conversation_tag("shared", "infra");
+        assert!(
+            matches!(result, Err(XzatomaError::Storage(message)) if message.contains("ambiguous"))
+        );
     }
 
     #[test]
-    fn test_save_conversation_updates_existing_record() {
+    fn test_list_sessions_with_tags_uses_and_semantics() {
         let (storage, _dir) = create_test_storage();
-        let id = "test-update-1";
-        let title = "Original Title";
-        let messages = vec![crate::providers::Message::user("First")];
-
+        for id in ["both", "billing-only", "infra-only", "none"] {
+            storage
+                .save_conversation(id, id, None, &[])
+                .expect("save failed");
+        }
+        storage.add_conversation_tag("both", "billing").unwrap();
+        storage.add_conversation_tag("both", "infra").unwrap();
         storage
-            .save_conversation(id, title, Some("gpt-4"), &messages)
-            .expect("initial save failed");
-
-        let sessions = storage.list_sessions().expect("list failed");
-        let session = sessions
-            .into_iter()
-            .find(|value| value.id == id)
-            .expect("session found");
-        let created_at = session.created_at;
-        let updated_at = session.updated_at;
+            .add_conversation_tag("billing-only", "billing")
+            .unwrap();
+        storage.add_conversation_tag("infra-only", "infra").unwrap();
+
+        let ids = |sessions: Vec<StoredSession>| {
+            let mut ids: Vec<String> = sessions.into_iter().map(|s| s.id).collect();
+            ids.sort();
+            ids
+        };
 
-        sleep(Duration::from_millis(10));
+        let billing = storage
+            .list_sessions_with_tags(&["Billing".to_string()])
+            .expect("filter failed");
+        assert_eq!(
+            ids(billing),
+            vec!["billing-only".to_string(), "both".to_string()]
+        );
 
-        let new_title = "Updated Title";
-        let new_messages = vec![crate::providers::Message::user("Second")];
-        storage
-            .save_conversation(id, new_title, Some("gpt-4"), &new_messages)
-            .expect("update failed");
+        let both = storage
+            .list_sessions_with_tags(&["billing".to_string(), "INFRA".to_string()])
+            .expect("filter failed");
+        assert_eq!(both.len(), 1);
+        assert_eq!(both[0].id, "both");
+        assert_eq!(
+            both[0].tags,
+            vec!["billing".to_string(), "infra".to_string()]
+        );
 
-        let sessions = storage.list_sessions().expect("list failed");
-        let session_updated = sessions
-            .into_iter()
-            .find(|value| value.id == id)
-            .expect("updated session found");
+        let duplicate = storage
+            .list_sessions_with_tags(&["infra".to_string(), " infra".to_string()])
+            .expect("filter failed");
+        assert_eq!(
+            ids(duplicate),
+            vec!["both".to_string(), "infra-only".to_string()]
+        );
 
-        assert_eq!(session_updated.created_at, created_at);
-        assert!(session_updated.updated_at > updated_at);
+        assert_eq!(storage.list_sessions_with_tags(&[]).unwrap().len(), 4);
     }
 
     #[test]
-    fn test_load_conversation_returns_none_for_missing_id() {
+    fn test_list_sessions_page_cuts_pages_in_order_and_counts_total() {
         let (storage, _dir) = create_test_storage();
-        let result = storage
-            .load_conversation("non-existent-id")
-            .expect("load failed");
-        assert!(result.is_none());
+        for index in 0..7 {
+            let id = format!("session-{}", index);
+            save_backdated_conversation(&storage, &id, index, "hello");
+            if index % 2 == 0 {
+                storage.add_conversation_tag(&id, "even").unwrap();
+            }
+        }
+
+        let ids = |page: &SessionPage| -> Vec<String> {
+            page.sessions.iter().map(|s| s.id.clone()).collect()
+        };
+
+        let first = storage.list_sessions_page(&[], 3, 0).expect("page failed");
+        assert_eq!(ids(&first), vec!["session-0", "session-1", "session-2"]);
+        assert_eq!(first.total, 7);
+        assert_eq!(first.remaining(), 4);
+
+        let last = storage.list_sessions_page(&[], 3, 6).expect("page failed");
+        assert_eq!(ids(&last), vec!["session-6"]);
+        assert_eq!(last.remaining(), 0);
+
+        let past_end = storage.list_sessions_page(&[], 3, 10).expect("page failed");
+        assert!(past_end.sessions.is_empty());
+        assert_eq!(past_end.total, 7);
+
+        let tagged = storage
+            .list_sessions_page(&["even".to_string()], 2, 1)
+            .expect("page failed");
+        assert_eq!(ids(&tagged), vec!["session-2", "session-4"]);
+        assert_eq!(tagged.total, 4);
+        assert_eq!(tagged.remaining(), 1);
     }
 
     #[test]
-    fn test_list_sessions_returns_ordered_by_updated_at() {
+    fn test_search_sessions_matches_content_and_filters_by_tag() {
         let (storage, _dir) = create_test_storage();
-
-        let id1 = "session-1";
         storage
             .save_conversation(
-                id1,
-                "A",
-                Some("gpt-4"),
-                &[crate::providers::Message::user("a")],
+                "invoice",
+                "Invoices",
+                None,
+                &[crate::providers::Message::user("Fix the 100% discount bug")],
             )
-            .expect("save1 failed");
-
-        sleep(Duration::from_millis(10));
-
-        let id2 = "session-2";
+            .unwrap();
         storage
             .save_conversation(
-                id2,
-                "B",
-                Some("gpt-4"),
-                &[crate::providers::Message::user("b")],
+                "deploy",
+                "Deploy",
+                None,
+                &[crate::providers::Message::user("Fix the deploy script")],
             )
-            .expect("save2 failed");
+            .unwrap();
+        storage.add_conversation_tag("invoice", "billing").unwrap();
 
-        let sessions = storage.list_sessions().expect("list failed");
-        assert!(sessions.len() >= 2);
-        assert_eq!(sessions[0].id, id2);
-        assert_eq!(sessions[1].id, id1);
+        assert_eq!(storage.search_sessions("fix", &[]).unwrap().len(), 2);
+        assert_eq!(storage.search_sessions("100%", &[]).unwrap().len(), 1);
+        assert_eq!(
+            storage.search_sessions("deploy", &[]).unwrap()[0].id,
+            "deploy"
+        );
+
+        let tagged = storage
+            .search_sessions("fix", &["billing".to_string()])
+            .unwrap();
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].id, "invoice");
     }
 
     #[test]
-    fn test_list_sessions_returns_empty_for_new_db() {
+    fn test_deleting_conversation_cascades_tags() {
         let (storage, _dir) = create_test_storage();
-        let sessions = storage.list_sessions().expect("list failed");
-        assert!(sessions.is_empty());
+        storage
+            .save_conversation("cascade", "Title", None, &[])
+            .unwrap();
+        storage.add_conversation_tag("cascade", "infra").unwrap();
+
+        storage.delete_conversation("cascade").unwrap();
+
+        let conn = Connection::open(storage.database_path()).expect("open connection");
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM conversation_tags WHERE conversation_id = 'cascade'",
+                [],
+                |row| row.get(0),
+            )
+            .expect("count tags");
+        assert_eq!(count, 0);
+
+        storage
+            .save_conversation("cascade", "Title", None, &[])
+            .unwrap();
+        assert!(storage
+            .list_conversation_tags("cascade")
+            .unwrap()
+            .is_empty());
     }
 
     #[test]
-    fn test_delete_conversation_removes_record() {
+    fn test_find_sessions_composes_filters() {
         let (storage, _dir) = create_test_storage();
-        let id = "to-delete";
-        storage
-            .save_conversation(id, "Title", None, &[crate::providers::Message::user("x")])
-            .expect("save failed");
+        let sessions = [
+            (
+                "old-scratch-untitled",
+                DEFAULT_CONVERSATION_TITLE,
+                "gpt-4o",
+                120,
+            ),
+            ("old-scratch-titled", "Refactor", "gpt-4o", 120),
+            ("old-scratch-blank", "  ", "llama3", 120),
+            (
+                "new-scratch-untitled",
+                DEFAULT_CONVERSATION_TITLE,
+                "gpt-4o",
+                5,
+            ),
+            ("old-untitled", DEFAULT_CONVERSATION_TITLE, "gpt-4o", 120),
+        ];
+        for (id, title, model, days_ago) in sessions {
+            storage
+                .save_conversation(id, title, Some(model), &[])
+                .expect("save failed");
+            let updated_at = (Utc::now() - ChronoDuration::days(days_ago)).to_rfc3339();
+            let conn = Connection::open(storage.database_path()).expect("open connection");
+            conn.execute(
+                "UPDATE conversations SET updated_at = ? WHERE id = ?",
+                params![updated_at, id],
+            )
+            .expect("backdate failed");
+            if id.contains("scratch") {
+                storage.add_conversation_tag(id, "scratch").unwrap();
+            }
+        }
 
-        storage.delete_conversation(id).expect("delete failed");
-        assert!(storage
-            .load_conversation(id)
-            .expect("load failed")
-            .is_none());
+        let find = |filter: HistoryFilter| {
+            let mut ids: Vec<String> = storage
+                .find_sessions(&filter)
+                .expect("find failed")
+                .into_iter()
+                .map(|session| session.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        let cutoff = Some(Utc::now() - ChronoDuration::days(90));
+
+        assert_eq!(find(HistoryFilter::default()).len(), 5);
+        assert_eq!(
+            find(HistoryFilter {
+                older_than: cutoff,
+                tags: vec!["Scratch".to_string()],
+                untitled: true,
+                ..HistoryFilter::default()
+            }),
+            vec!["old-scratch-blank", "old-scratch-untitled"]
+        );
+        assert_eq!(
+            find(HistoryFilter {
+                older_than: cutoff,
+                tags: vec!["scratch".to_string()],
+                untitled: true,
+                model: Some("gpt-4o".to_string()),
+            }),
+            vec!["old-scratch-untitled"]
+        );
+        assert_eq!(
+            find(HistoryFilter {
+                untitled: true,
+                model: Some("gpt-4o".to_string()),
+                ..HistoryFilter::default()
+            }),
+            vec![
+                "new-scratch-untitled",
+                "old-scratch-untitled",
+                "old-untitled"
+            ]
+        );
+        assert!(find(HistoryFilter {
+            model: Some("gpt".to_string()),
+            ..HistoryFilter::default()
+        })
+        .is_empty());
     }
 
     #[test]
-    fn test_delete_conversation_is_idempotent() {
+    fn test_delete_conversations_removes_only_listed_ids() {
         let (storage, _dir) = create_test_storage();
-        let id = "to-delete-2";
-        storage
-            .save_conversation(id, "Title", None, &[crate::providers::Message::user("x")])
-            .expect("save failed");
+        for id in ["a", "b", "c"] {
+            storage.save_conversation(id, id, None, &[]).unwrap();
+        }
+        storage.add_conversation_tag("a", "scratch").unwrap();
+
+        let deleted = storage
+            .delete_conversations(&["a".to_string(), "c".to_string(), "missing".to_string()])
+            .expect("delete failed");
+
+        assert_eq!(deleted, 2);
+        assert_eq!(remaining_ids(&storage), vec!["b".to_string()]);
+        let conn = Connection::open(storage.database_path()).expect("open connection");
+        let tags: i64 = conn
+            .query_row("SELECT COUNT(*) FROM conversation_tags", [], |row| {
+                row.get(0)
+            })
+            .expect("count tags");
+        assert_eq!(tags, 0);
+    }
 
+    #[test]
+    fn test_backup_and_import_round_trip_preserves_stored_json() {
+        let (storage, dir) = create_test_storage();
+        let messages = vec![
+            crate::providers::Message::system("Be brief"),
+            crate::providers::Message::user("Explain \"naïve\" caching 🚀"),
+            crate::providers::Message::assistant("It trades memory for time."),
+        ];
         storage
-            .delete_conversation(id)
-            .expect("first delete failed");
+            .save_conversation("first", "Caching", Some("gpt-4o"), &messages)
+            .unwrap();
         storage
-            .delete_conversation(id)
-            .expect("second delete failed");
+            .save_conversation("second/odd id", DEFAULT_CONVERSATION_TITLE, None, &[])
+            .unwrap();
+        storage.add_conversation_tag("first", "infra").unwrap();
+        storage.set_conversation_pinned("first", true).unwrap();
+        storage.set_pinned_messages("first", &[1]).unwrap();
+        storage
+            .set_message_cwds("first", &[None, Some(PathBuf::from("/repo")), None])
+            .unwrap();
+
+        let stored_rows = |storage: &SqliteStorage| {
+            let conn = Connection::open(storage.database_path()).expect("open connection");
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id, title, created_at, updated_at, model, messages, pinned,
+                            pinned_messages, message_cwds, replayed_from
+                     FROM conversations ORDER BY id",
+                )
+                .unwrap();
+            stmt.query_map([], |row| {
+                (0..10)
+                    .map(|i| row.get::<_, rusqlite::types::Value>(i))
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap()
+        };
+
+        let backup_dir = dir.path().join("backup");
+        let manifest = storage.backup_conversations(&backup_dir).unwrap();
+        assert_eq!(manifest.conversations.len(), 2);
+        assert!(manifest
+            .conversations
+            .iter()
+            .all(|entry| backup_dir.join(&entry.file).is_file()));
+        let first = manifest
+            .conversations
+            .iter()
+            .find(|entry| entry.id == "first")
+            .unwrap();
+        assert_eq!(first.message_count, 3);
+        assert!(storage.backup_conversations(&backup_dir).is_err());
+
+        let restore_dir = tempdir().unwrap();
+        let restored = SqliteStorage::new_with_path(restore_dir.path().join("history.db")).unwrap();
+        let report = restored.import_conversations(&backup_dir, false).unwrap();
+
+        assert_eq!(report.imported.len(), 2);
+        assert!(report.skipped.is_empty());
+        assert_eq!(stored_rows(&restored), stored_rows(&storage));
+        assert_eq!(
+            restored.list_conversation_tags("first").unwrap(),
+            vec!["infra".to_string()]
+        );
+
+        let again = restored.import_conversations(&backup_dir, false).unwrap();
+        assert!(again.imported.is_empty());
+        assert_eq!(again.skipped.len(), 2);
+    }
+
+    #[test]
+    fn test_import_rejects_tampered_backup_without_importing() {
+        let (storage, dir) = create_test_storage();
+        for id in ["a", "b"] {
+            storage
+                .save_conversation(id, id, None, &[crate::providers::Message::user(id)])
+                .unwrap();
+        }
+        let backup_dir = dir.path().join("backup");
+        let manifest = storage.backup_conversations(&backup_dir).unwrap();
+
+        let tampered = backup_dir.join(&manifest.conversations[1].file);
+        let contents = std::fs::read_to_string(&tampered).unwrap();
+        std::fs::write(
+            &tampered,
+            contents.replace("\"title\": \"", "\"title\": \"edited "),
+        )
+        .unwrap();
+
+        let restore_dir = tempdir().unwrap();
+        let restored = SqliteStorage::new_with_path(restore_dir.path().join("history.db")).unwrap();
+        let err = restored
+            .import_conversations(&backup_dir, false)
+            .unwrap_err();
+
+        assert!(err.to_string().contains("does not match the hash"));
+        assert!(restored.list_sessions().unwrap().is_empty());
     }
 
     #[test]
@@ -2554,9 +6885,29 @@ mod tests {
         let db_path = dir.path().join("override_history.db");
         std::env::set_var("XZATOMA_HISTORY_DB", &db_path);
 
-        let storage = SqliteStorage::new().expect("storage should initialize");
+        let paths = Paths::from_env().expect("paths should resolve");
+        let storage = SqliteStorage::new(&paths).expect("storage should initialize");
         assert_eq!(storage.database_path(), &db_path);
 
         std::env::remove_var("XZATOMA_HISTORY_DB");
     }
+
+    #[test]
+    fn test_integrity_check_reports_no_problems_for_new_database() {
+        let (storage, _dir) = create_test_storage();
+        assert!(storage.integrity_check().unwrap().is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_default_database_path_honors_override() {
+        let original = std::env::var("XZATOMA_HISTORY_DB").ok();
+        std::env::set_var("XZATOMA_HISTORY_DB", "/tmp/xzatoma-override/history.db");
+        let path = SqliteStorage::default_database_path(&Paths::from_env().unwrap());
+        match original {
+            Some(value) => std::env::set_var("XZATOMA_HISTORY_DB", value),
+            None => std::env::remove_var("XZATOMA_HISTORY_DB"),
+        }
+        assert_eq!(path, PathBuf::from("/tmp/xzatoma-override/history.db"));
+    }
 }
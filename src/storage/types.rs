@@ -1,3 +1,5 @@
+use crate::error::{Result, XzatomaError};
+use crate::providers::{FinishReason, Message, TokenUsage};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -21,6 +23,8 @@ use std::collections::BTreeMap;
 ///     updated_at: now,
 ///     model: Some("gpt-5-mini".to_string()),
 ///     message_count: 3,
+///     pinned: false,
+///     tags: vec!["billing".to_string()],
 /// };
 ///
 /// assert_eq!(session.id, "session-1");
@@ -40,6 +44,45 @@ pub struct StoredSession {
     pub model: Option<String>,
     /// Number of messages in the session.
     pub message_count: usize,
+    /// Whether the session is exempt from retention pruning.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Normalized tags attached to the session, sorted alphabetically.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// One page of conversation summaries and the number of matching conversations.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::storage::types::SessionPage;
+///
+/// let page = SessionPage {
+///     sessions: Vec::new(),
+///     offset: 25,
+///     total: 30,
+/// };
+/// assert_eq!(page.remaining(), 5);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionPage {
+    /// Conversations on this page, most recently updated first.
+    pub sessions: Vec<StoredSession>,
+    /// Number of matching conversations skipped before this page.
+    pub offset: usize,
+    /// Number of conversations matching the query across all pages.
+    pub total: usize,
+}
+
+impl SessionPage {
+    /// Returns the number of matching conversations after this page.
+    pub fn remaining(&self) -> usize {
+        self.total
+            .saturating_sub(self.offset)
+            .saturating_sub(self.sessions.len())
+    }
 }
 
 /// Persisted ACP session summary.
@@ -330,3 +373,552 @@ pub struct StoredAcpCancellation {
     /// Whether cancellation has been acknowledged by the executor.
     pub acknowledged: bool,
 }
+
+/// Retention limit that caused a conversation to be pruned.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneReason {
+    /// The conversation was not updated within `max_age_days`.
+    MaxAge,
+    /// More than `max_sessions` conversations were stored.
+    MaxSessions,
+    /// The database exceeded `max_db_size_mb`.
+    MaxDbSize,
+}
+
+impl std::fmt::Display for PruneReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            Self::MaxAge => "max_age_days",
+            Self::MaxSessions => "max_sessions",
+            Self::MaxDbSize => "max_db_size_mb",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// A conversation selected for removal by retention pruning.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PrunedSession {
+    /// Conversation identifier.
+    pub id: String,
+    /// Conversation title.
+    pub title: String,
+    /// When the conversation was last updated.
+    pub updated_at: DateTime<Utc>,
+    /// Limit that selected the conversation.
+    pub reason: PruneReason,
+}
+
+/// Outcome of a retention pruning pass.
+///
+/// Sizes count the database pages in use, excluding free pages. For a dry run
+/// `size_after_bytes` is an estimate based on the size of the selected rows.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::storage::types::PruneReport;
+///
+/// let report = PruneReport::default();
+/// assert!(report.removed.is_empty());
+/// assert!(!report.vacuumed);
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Conversations removed, or that would be removed for a dry run.
+    pub removed: Vec<PrunedSession>,
+    /// Whether the pass only reported what it would remove.
+    pub dry_run: bool,
+    /// Bytes in use before pruning.
+    pub size_before_bytes: u64,
+    /// Bytes in use after pruning.
+    pub size_after_bytes: u64,
+    /// Whether the database was vacuumed after pruning.
+    pub vacuumed: bool,
+}
+
+/// Session and message counts for one day or week.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PeriodUsage {
+    /// Period label, `YYYY-MM-DD` for days and `YYYY-Www` for weeks.
+    pub period: String,
+    /// Sessions last updated in the period.
+    pub sessions: u64,
+    /// Messages in those sessions.
+    pub messages: u64,
+}
+
+/// Usage aggregated per model.
+///
+/// Tokens are estimated from message content at four characters per token,
+/// the same heuristic the conversation uses for context management.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ModelUsage {
+    /// Model name, or `unknown` when the session recorded none.
+    pub model: String,
+    /// Sessions that used the model.
+    pub sessions: u64,
+    /// Messages in those sessions.
+    pub messages: u64,
+    /// Estimated tokens of message content.
+    pub estimated_tokens: u64,
+}
+
+/// Number of calls to one tool.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ToolUsage {
+    /// Tool name.
+    pub tool: String,
+    /// Calls requested by the model.
+    pub calls: u64,
+}
+
+/// Summary of one session in the longest-sessions ranking.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionLength {
+    /// Conversation identifier.
+    pub id: String,
+    /// Conversation title.
+    pub title: String,
+    /// Number of messages in the session.
+    pub messages: u64,
+    /// Minutes between creation and the last update.
+    pub duration_minutes: f64,
+}
+
+/// Aggregate usage statistics over stored conversations.
+///
+/// Computed by `SqliteStorage::history_stats`. Every aggregate only covers
+/// sessions updated at or after `since` when it is set.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::storage::types::HistoryStats;
+///
+/// let stats = HistoryStats::default();
+/// assert_eq!(stats.total_sessions, 0);
+/// assert!(stats.per_model.is_empty());
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HistoryStats {
+    /// Lower bound on the last update time, if filtered.
+    pub since: Option<DateTime<Utc>>,
+    /// Number of sessions.
+    pub total_sessions: u64,
+    /// Number of messages across all sessions.
+    pub total_messages: u64,
+    /// Average number of messages per session.
+    pub average_messages: f64,
+    /// Average minutes between session creation and the last update.
+    pub average_duration_minutes: f64,
+    /// Usage per day, most recent first.
+    pub per_day: Vec<PeriodUsage>,
+    /// Usage per ISO week, most recent first.
+    pub per_week: Vec<PeriodUsage>,
+    /// Usage per model, most messages first.
+    pub per_model: Vec<ModelUsage>,
+    /// Most-called tools, most calls first.
+    pub top_tools: Vec<ToolUsage>,
+    /// Sessions with the most messages.
+    pub longest_sessions: Vec<SessionLength>,
+}
+
+/// Filters that select conversations for bulk history operations.
+///
+/// Every filter that is set must match; an empty filter matches every
+/// conversation.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::storage::types::HistoryFilter;
+///
+/// let filter = HistoryFilter {
+///     tags: vec!["scratch".to_string()],
+///     untitled: true,
+///     ..HistoryFilter::default()
+/// };
+/// assert!(!filter.is_empty());
+/// assert!(HistoryFilter::default().is_empty());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryFilter {
+    /// Only conversations last updated before this instant.
+    pub older_than: Option<DateTime<Utc>>,
+    /// Only conversations carrying every one of these tags.
+    pub tags: Vec<String>,
+    /// Only conversations that were never given a title.
+    pub untitled: bool,
+    /// Only conversations that used this model.
+    pub model: Option<String>,
+}
+
+impl HistoryFilter {
+    /// Returns true when no filter is set.
+    pub fn is_empty(&self) -> bool {
+        self.older_than.is_none() && self.tags.is_empty() && !self.untitled && self.model.is_none()
+    }
+}
+
+/// One conversation written by a history backup.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BackupEntry {
+    /// Conversation identifier.
+    pub id: String,
+    /// Conversation title.
+    pub title: String,
+    /// File name of the conversation, relative to the backup directory.
+    pub file: String,
+    /// Hex-encoded SHA-256 of the conversation file.
+    pub sha256: String,
+    /// Number of messages in the conversation.
+    pub message_count: usize,
+}
+
+/// Manifest written next to the conversation files of a history backup.
+///
+/// # Examples
+///
+/// ```
+/// use chrono::Utc;
+/// use xzatoma::storage::types::BackupManifest;
+///
+/// let manifest = BackupManifest {
+///     version: 1,
+///     created_at: Utc::now(),
+///     conversations: Vec::new(),
+/// };
+/// assert!(manifest.conversations.is_empty());
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BackupManifest {
+    /// Backup format version.
+    pub version: u32,
+    /// When the backup was taken.
+    pub created_at: DateTime<Utc>,
+    /// Conversations in the backup, in the order they were written.
+    pub conversations: Vec<BackupEntry>,
+}
+
+/// Outcome of restoring a history backup.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Conversations restored from the backup.
+    pub imported: Vec<String>,
+    /// Conversations skipped because a conversation with the same ID exists.
+    pub skipped: Vec<String>,
+}
+
+/// One provider request recorded by `xzatoma run --record`.
+///
+/// Turns are numbered from 1 in the order the requests were sent. Strings
+/// that pushed the turn over the configured payload limit end with a
+/// truncation marker, and `truncated` is set.
+///
+/// # Examples
+///
+/// ```
+/// use chrono::Utc;
+/// use xzatoma::providers::Message;
+/// use xzatoma::storage::types::StoredRunTurn;
+///
+/// let turn = StoredRunTurn {
+///     run_id: "run-1".to_string(),
+///     turn: 1,
+///     started_at: Utc::now(),
+///     duration_ms: 120,
+///     model: "gpt-5-mini".to_string(),
+///     messages: vec![Message::user("List the files")],
+///     tools: Vec::new(),
+///     response: None,
+///     error: Some("connection reset".to_string()),
+///     tool_calls: Vec::new(),
+///     truncated: false,
+/// };
+///
+/// assert_eq!(turn.messages.len(), 1);
+/// assert!(turn.response.is_none());
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredRunTurn {
+    /// Recorded run identifier.
+    pub run_id: String,
+    /// Turn number within the run, starting at 1.
+    pub turn: u32,
+    /// When the request was sent.
+    pub started_at: DateTime<Utc>,
+    /// How long the provider took to answer.
+    pub duration_ms: u64,
+    /// Model the request was sent to.
+    pub model: String,
+    /// Messages sent to the provider, exactly as passed to it.
+    pub messages: Vec<Message>,
+    /// Tool definitions sent with the request.
+    #[serde(default)]
+    pub tools: Vec<serde_json::Value>,
+    /// Provider response, when the request succeeded.
+    #[serde(default)]
+    pub response: Option<RecordedResponse>,
+    /// Error message, when the request failed.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Tool calls the response asked for, with their results once known.
+    #[serde(default)]
+    pub tool_calls: Vec<RecordedToolCall>,
+    /// Whether any payload string was cut to fit the size limit.
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// Provider response of a recorded run turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    /// Message returned by the provider.
+    pub message: Message,
+    /// Token usage reported by the provider.
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+    /// Model that produced the response, as reported by the provider.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Reasoning content from extended-thinking models.
+    #[serde(default)]
+    pub reasoning: Option<String>,
+    /// Why the model stopped generating.
+    #[serde(default)]
+    pub finish_reason: FinishReason,
+    /// Whether the response came from the provider response cache.
+    #[serde(default)]
+    pub cached: bool,
+}
+
+/// Tool call of a recorded run turn.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RecordedToolCall {
+    /// Tool call identifier assigned by the provider.
+    pub id: String,
+    /// Tool name.
+    pub name: String,
+    /// Arguments as the JSON string the provider sent.
+    pub arguments: String,
+    /// Tool result sent back to the provider, once a later request carried it.
+    #[serde(default)]
+    pub result: Option<String>,
+}
+
+/// Token usage and estimated cost of one provider request.
+///
+/// # Examples
+///
+/// ```
+/// use chrono::Utc;
+/// use xzatoma::storage::types::UsageRecord;
+///
+/// let record = UsageRecord {
+///     timestamp: Utc::now(),
+///     provider: "openai".to_string(),
+///     model: "gpt-5-mini".to_string(),
+///     session_id: "3f2b".to_string(),
+///     prompt_tokens: 1200,
+///     completion_tokens: 300,
+///     estimated_cost: 0.0009,
+/// };
+///
+/// assert_eq!(record.prompt_tokens + record.completion_tokens, 1500);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UsageRecord {
+    /// When the response arrived.
+    pub timestamp: DateTime<Utc>,
+    /// Provider the request was sent to.
+    pub provider: String,
+    /// Model the request was sent to.
+    pub model: String,
+    /// Conversation the request belonged to.
+    pub session_id: String,
+    /// Prompt tokens reported by the provider.
+    pub prompt_tokens: u64,
+    /// Completion tokens reported by the provider.
+    pub completion_tokens: u64,
+    /// Estimated cost in US dollars, 0 when the model has no configured price.
+    pub estimated_cost: f64,
+}
+
+/// Column that usage records are grouped by.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGrouping {
+    /// One row per model.
+    Model,
+    /// One row per UTC day, `YYYY-MM-DD`.
+    Day,
+    /// One row per conversation.
+    Session,
+}
+
+impl std::fmt::Display for UsageGrouping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            Self::Model => "model",
+            Self::Day => "day",
+            Self::Session => "session",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// What saving a conversation does when its ID is already stored.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::storage::types::IfExists;
+///
+/// let policy: IfExists = "append".parse().unwrap();
+/// assert_eq!(policy, IfExists::Append);
+/// assert_eq!(IfExists::default(), IfExists::Replace);
+/// ```
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IfExists {
+    /// Overwrite the stored messages, keeping `created_at` and the tags.
+    #[default]
+    Replace,
+    /// Add the new messages after a separator message.
+    Append,
+    /// Refuse to save.
+    Fail,
+}
+
+impl std::str::FromStr for IfExists {
+    type Err = XzatomaError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "replace" => Ok(Self::Replace),
+            "append" => Ok(Self::Append),
+            "fail" => Ok(Self::Fail),
+            _ => Err(XzatomaError::Config(format!(
+                "Invalid if-exists policy '{}': expected replace, append, or fail",
+                s
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for IfExists {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            Self::Replace => "replace",
+            Self::Append => "append",
+            Self::Fail => "fail",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// Usage records aggregated over a period or group.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UsageAggregate {
+    /// Model, day, or session the row covers; empty for a total.
+    pub key: String,
+    /// Number of provider requests.
+    pub requests: u64,
+    /// Prompt tokens across those requests.
+    pub prompt_tokens: u64,
+    /// Completion tokens across those requests.
+    pub completion_tokens: u64,
+    /// Estimated cost in US dollars.
+    pub estimated_cost: f64,
+}
+
+/// Revision a checked conversation save expects the stored conversation to have.
+///
+/// Every save of a conversation's messages increments its revision, starting
+/// at 1 for a new conversation.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::providers::Message;
+/// use xzatoma::storage::types::ExpectedRevision;
+/// use xzatoma::storage::SqliteStorage;
+///
+/// let storage = SqliteStorage::new_in_memory()?;
+/// let messages = [Message::user("hello")];
+/// let revision =
+///     storage.save_conversation_checked("c1", "Chat", None, &messages, ExpectedRevision::Exactly(0))?;
+/// assert_eq!(revision, 1);
+///
+/// // A save that still expects the conversation to be new is refused
+/// assert!(storage
+///     .save_conversation_checked("c1", "Chat", None, &messages, ExpectedRevision::Exactly(0))
+///     .is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpectedRevision {
+    /// Save whatever the stored revision is; the last writer wins.
+    Any,
+    /// Save only when the stored revision is this one. `0` means the
+    /// conversation must not be stored yet.
+    Exactly(u64),
+}
+
+/// A checked conversation save found the conversation changed by another writer.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::storage::types::ConversationConflict;
+///
+/// let conflict = ConversationConflict {
+///     id: "c1".to_string(),
+///     expected_revision: 2,
+///     stored_revision: 0,
+///     stored_updated_at: None,
+/// };
+/// assert_eq!(
+///     conflict.to_string(),
+///     "conversation c1 is no longer stored, but the save expected revision 2"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversationConflict {
+    /// Conversation ID.
+    pub id: String,
+    /// Revision the save expected.
+    pub expected_revision: u64,
+    /// Revision stored now; `0` when the conversation is no longer stored.
+    pub stored_revision: u64,
+    /// When the stored revision was saved, if the conversation is stored.
+    pub stored_updated_at: Option<DateTime<Utc>>,
+}
+
+impl std::fmt::Display for ConversationConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.stored_updated_at {
+            Some(updated_at) => write!(
+                f,
+                "conversation {} is at revision {} (saved {})",
+                self.id,
+                self.stored_revision,
+                updated_at.format("%Y-%m-%d %H:%M:%S UTC")
+            )?,
+            None => write!(f, "conversation {} is no longer stored", self.id)?,
+        }
+        if self.expected_revision == 0 {
+            write!(f, ", but the save expected a new conversation")
+        } else {
+            write!(
+                f,
+                ", but the save expected revision {}",
+                self.expected_revision
+            )
+        }
+    }
+}
+
+impl std::error::Error for ConversationConflict {}
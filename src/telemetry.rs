@@ -0,0 +1,881 @@
+//! Structured telemetry events for agent runs
+//!
+//! Teams aggregating agent behavior need machine-readable records beyond
+//! tracing logs. When `telemetry.file` or `telemetry.stdout` is configured,
+//! the agent writes one JSON object per line for each [`TelemetryEvent`].
+//!
+//! Telemetry is a consumer of the [`AgentExecutionEvent`] stream rather than
+//! separate instrumentation: [`TelemetryObserver`] wraps the caller's
+//! observer, forwards every event unchanged, and translates the ones it
+//! records. Subagent events are derived from `subagent` and
+//! `parallel_subagent` tool calls.
+//!
+//! Every event carries `schema_version`, `timestamp`, `session_id`, and an
+//! `event` tag naming the variant. The structs are public so external tooling
+//! can deserialize the file with serde.
+//!
+//! # Examples
+//!
+//! ```
+//! use xzatoma::telemetry::{TelemetryEvent, TelemetryEventKind, TELEMETRY_SCHEMA_VERSION};
+//!
+//! let line = r#"{"schema_version":1,"timestamp":"2025-01-01T00:00:00Z","session_id":"s1","event":"turn_start","turn":1}"#;
+//! let event: TelemetryEvent = serde_json::from_str(line).unwrap();
+//! assert_eq!(event.schema_version, TELEMETRY_SCHEMA_VERSION);
+//! assert_eq!(event.kind, TelemetryEventKind::TurnStart { turn: 1 });
+//! ```
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::agent::events::{AgentExecutionEvent, AgentObserver};
+use crate::agent::pruning::{PruneReason, PrunedMessage};
+use crate::agent::ToolCallStatus;
+use crate::config::TelemetryConfig;
+use crate::error::{Result, XzatomaError};
+
+/// Version of the telemetry event schema
+///
+/// Incremented when a field is removed or changes meaning. Adding fields or
+/// event types does not change the version.
+pub const TELEMETRY_SCHEMA_VERSION: u32 = 1;
+
+/// Tool names whose calls are reported as subagent events
+const SUBAGENT_TOOLS: &[&str] = &["subagent", "parallel_subagent"];
+
+/// One telemetry record, serialized as a single JSON line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TelemetryEvent {
+    /// Schema version of this record
+    pub schema_version: u32,
+    /// When the event was recorded
+    pub timestamp: DateTime<Utc>,
+    /// Session the event belongs to
+    pub session_id: String,
+    /// Event type and its fields, flattened into the record
+    #[serde(flatten)]
+    pub kind: TelemetryEventKind,
+}
+
+/// Outcome of a session, turn, or subagent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TelemetryStatus {
+    /// Finished normally
+    Success,
+    /// Finished with an error
+    Error,
+    /// Stopped by cancellation
+    Cancelled,
+}
+
+/// Telemetry event types, tagged by the `event` field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TelemetryEventKind {
+    /// A command started a telemetry session
+    SessionStart {
+        /// CLI command that started the session, such as `run` or `chat`
+        command: String,
+        /// Provider type
+        provider: String,
+        /// Model in use, when known
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        model: Option<String>,
+        /// XZatoma version
+        version: String,
+    },
+    /// The session ended
+    SessionEnd {
+        /// How the session ended
+        status: TelemetryStatus,
+        /// Session duration in milliseconds
+        duration_ms: u64,
+        /// Number of turns started in the session
+        turns: u64,
+    },
+    /// The agent started executing a prompt
+    TurnStart {
+        /// 1-based turn number within the session
+        turn: u64,
+    },
+    /// The agent finished executing a prompt
+    TurnEnd {
+        /// Turn number
+        turn: u64,
+        /// How the turn ended
+        status: TelemetryStatus,
+        /// Turn duration in milliseconds
+        duration_ms: u64,
+        /// Provider completion requests made during the turn
+        provider_requests: u64,
+        /// Prompt tokens reported by the provider during the turn
+        prompt_tokens: u64,
+        /// Completion tokens reported by the provider during the turn
+        completion_tokens: u64,
+        /// Sum of prompt and completion tokens
+        total_tokens: u64,
+        /// Provider requests answered from the response cache; their tokens
+        /// are not counted above
+        #[serde(default)]
+        cached_requests: u64,
+    },
+    /// A tool call finished
+    ToolCall {
+        /// Turn the call belongs to
+        turn: u64,
+        /// Provider-assigned call identifier
+        call_id: String,
+        /// Tool name
+        name: String,
+        /// Call duration in milliseconds
+        duration_ms: u64,
+        /// Call outcome
+        status: ToolCallStatus,
+        /// Error text for failed calls
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// A subagent or parallel subagent batch was started
+    SubagentSpawn {
+        /// Turn the spawn belongs to
+        turn: u64,
+        /// Call identifier of the spawning tool call
+        call_id: String,
+        /// Spawning tool, `subagent` or `parallel_subagent`
+        tool: String,
+        /// Subagent label, when one was given
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    },
+    /// A subagent or parallel subagent batch finished
+    SubagentComplete {
+        /// Turn the spawn belongs to
+        turn: u64,
+        /// Call identifier of the spawning tool call
+        call_id: String,
+        /// Spawning tool, `subagent` or `parallel_subagent`
+        tool: String,
+        /// Subagent label, when one was given
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+        /// Duration in milliseconds
+        duration_ms: u64,
+        /// How the subagent finished
+        status: TelemetryStatus,
+    },
+    /// The conversation was summarized to free context
+    Summarization {
+        /// Turn during which summarization ran
+        turn: u64,
+        /// Messages before summarization
+        messages_before: u64,
+        /// Messages after summarization
+        messages_after: u64,
+        /// Estimated tokens in use after summarization
+        tokens_after: u64,
+    },
+    /// Messages were summarized or stubbed to save context
+    Pruning {
+        /// Turn during which pruning ran
+        turn: u64,
+        /// Why the messages were pruned
+        reason: PruneReason,
+        /// Affected messages with their role, preview, and tokens
+        messages: Vec<PrunedMessage>,
+        /// Estimated tokens in use before pruning
+        tokens_before: u64,
+        /// Estimated tokens in use after pruning
+        tokens_after: u64,
+        /// Estimated tokens of the summary message inserted, if any
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        summary_tokens: Option<u64>,
+    },
+    /// Execution failed
+    Error {
+        /// Turn the error ended, if a turn was running
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        turn: Option<u64>,
+        /// Error description
+        message: String,
+    },
+}
+
+/// Destination for telemetry events
+///
+/// A sink writes to one or more outputs and numbers turns across the whole
+/// session, so agents rebuilt mid-session keep counting. Write failures are
+/// logged and never interrupt the agent.
+pub struct TelemetrySink {
+    session_id: String,
+    writers: Mutex<Vec<Box<dyn Write + Send>>>,
+    started: Instant,
+    turns: AtomicU64,
+    ended: AtomicBool,
+}
+
+impl std::fmt::Debug for TelemetrySink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TelemetrySink")
+            .field("session_id", &self.session_id)
+            .field("turns", &self.turns.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl TelemetrySink {
+    /// Creates a sink that writes to `writers`
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - Identifier stamped on every event
+    /// * `writers` - Outputs that receive each JSON line
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::telemetry::{TelemetryEventKind, TelemetrySink};
+    ///
+    /// let sink = TelemetrySink::new("session-1", vec![Box::new(std::io::sink())]);
+    /// sink.emit(TelemetryEventKind::TurnStart { turn: 1 });
+    /// assert_eq!(sink.session_id(), "session-1");
+    /// ```
+    pub fn new(session_id: impl Into<String>, writers: Vec<Box<dyn Write + Send>>) -> Self {
+        Self {
+            session_id: session_id.into(),
+            writers: Mutex::new(writers),
+            started: Instant::now(),
+            turns: AtomicU64::new(0),
+            ended: AtomicBool::new(false),
+        }
+    }
+
+    /// Builds a sink from configuration
+    ///
+    /// Returns `None` when neither `telemetry.file` nor `telemetry.stdout` is
+    /// set. The file is opened for appending and its parent directory is
+    /// created if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the telemetry file cannot be opened.
+    pub fn from_config(
+        config: &TelemetryConfig,
+        session_id: impl Into<String>,
+    ) -> Result<Option<Arc<Self>>> {
+        if !config.is_enabled() {
+            return Ok(None);
+        }
+
+        let mut writers: Vec<Box<dyn Write + Send>> = Vec::new();
+        if let Some(file) = &config.file {
+            writers.push(Box::new(open_append(Path::new(file)).map_err(|e| {
+                XzatomaError::Config(format!("Cannot open telemetry file {}: {}", file, e))
+            })?));
+        }
+        if config.stdout {
+            writers.push(Box::new(io::stdout()));
+        }
+
+        Ok(Some(Arc::new(Self::new(session_id, writers))))
+    }
+
+    /// Returns the session identifier stamped on every event
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Writes one event to every output
+    pub fn emit(&self, kind: TelemetryEventKind) {
+        let event = TelemetryEvent {
+            schema_version: TELEMETRY_SCHEMA_VERSION,
+            timestamp: Utc::now(),
+            session_id: self.session_id.clone(),
+            kind,
+        };
+        let mut line = match serde_json::to_string(&event) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!("Failed to serialize telemetry event: {}", e);
+                return;
+            }
+        };
+        line.push('\n');
+
+        let mut writers = self.writers.lock().unwrap_or_else(|e| e.into_inner());
+        for writer in writers.iter_mut() {
+            if let Err(e) = writer
+                .write_all(line.as_bytes())
+                .and_then(|_| writer.flush())
+            {
+                tracing::warn!("Failed to write telemetry event: {}", e);
+            }
+        }
+    }
+
+    /// Records the start of the session
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - CLI command that started the session
+    /// * `provider` - Provider type
+    /// * `model` - Model in use, when known
+    pub fn start_session(&self, command: &str, provider: &str, model: Option<String>) {
+        self.emit(TelemetryEventKind::SessionStart {
+            command: command.to_string(),
+            provider: provider.to_string(),
+            model,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        });
+    }
+
+    /// Records the end of the session
+    ///
+    /// Only the first call emits an event, so callers can end the session on
+    /// every exit path.
+    pub fn end_session(&self, status: TelemetryStatus) {
+        if self.ended.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.emit(TelemetryEventKind::SessionEnd {
+            status,
+            duration_ms: elapsed_ms(self.started),
+            turns: self.turns.load(Ordering::SeqCst),
+        });
+    }
+
+    /// Returns a guard that ends the session as `Error` when dropped
+    ///
+    /// Commands hold the guard for their whole run, so an early return or a
+    /// panic still closes the session. Ending the session explicitly first
+    /// makes the guard a no-op.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use xzatoma::telemetry::{TelemetrySink, TelemetryStatus};
+    ///
+    /// let sink = Arc::new(TelemetrySink::new("session-1", vec![Box::new(std::io::sink())]));
+    /// let guard = sink.end_on_drop();
+    /// sink.end_session(TelemetryStatus::Success);
+    /// drop(guard);
+    /// ```
+    pub fn end_on_drop(self: &Arc<Self>) -> SessionEndGuard {
+        SessionEndGuard {
+            sink: Arc::clone(self),
+        }
+    }
+
+    /// Allocates the next turn number
+    fn next_turn(&self) -> u64 {
+        self.turns.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+/// Ends a telemetry session as `Error` when dropped
+///
+/// Created by [`TelemetrySink::end_on_drop`].
+pub struct SessionEndGuard {
+    sink: Arc<TelemetrySink>,
+}
+
+impl Drop for SessionEndGuard {
+    fn drop(&mut self) {
+        self.sink.end_session(TelemetryStatus::Error);
+    }
+}
+
+/// State of the turn being recorded
+struct TurnState {
+    number: u64,
+    started: Instant,
+    provider_requests: u64,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    cached_requests: u64,
+}
+
+/// A tool call that has started but not finished
+struct PendingToolCall {
+    started: Instant,
+    subagent_label: Option<Option<String>>,
+}
+
+/// Observer that records telemetry and forwards every event to `inner`
+///
+/// Created per execution by the agent when a [`TelemetrySink`] is attached.
+/// Call [`TelemetryObserver::finish`] with the execution result so a turn that
+/// ended without a terminal event (for example a provider error) is still
+/// closed.
+pub struct TelemetryObserver<'a> {
+    sink: Arc<TelemetrySink>,
+    inner: &'a mut dyn AgentObserver,
+    turn: Option<TurnState>,
+    last_turn: Option<u64>,
+    tool_calls: HashMap<String, PendingToolCall>,
+}
+
+impl<'a> TelemetryObserver<'a> {
+    /// Wraps `inner`, recording telemetry to `sink`
+    pub fn new(sink: Arc<TelemetrySink>, inner: &'a mut dyn AgentObserver) -> Self {
+        Self {
+            sink,
+            inner,
+            turn: None,
+            last_turn: None,
+            tool_calls: HashMap::new(),
+        }
+    }
+
+    /// Closes the current turn from the execution result if it is still open
+    pub fn finish(&mut self, result: &Result<String>) {
+        if self.turn.is_none() {
+            return;
+        }
+        match result {
+            Ok(_) => self.end_turn(TelemetryStatus::Success),
+            Err(XzatomaError::Cancelled) => self.end_turn(TelemetryStatus::Cancelled),
+            Err(error) => {
+                self.record_error(error.to_string());
+                self.end_turn(TelemetryStatus::Error);
+            }
+        }
+    }
+
+    fn current_turn(&self) -> u64 {
+        self.turn
+            .as_ref()
+            .map(|turn| turn.number)
+            .or(self.last_turn)
+            .unwrap_or(0)
+    }
+
+    fn end_turn(&mut self, status: TelemetryStatus) {
+        let Some(turn) = self.turn.take() else {
+            return;
+        };
+        self.last_turn = Some(turn.number);
+        self.sink.emit(TelemetryEventKind::TurnEnd {
+            turn: turn.number,
+            status,
+            duration_ms: elapsed_ms(turn.started),
+            provider_requests: turn.provider_requests,
+            prompt_tokens: turn.prompt_tokens,
+            completion_tokens: turn.completion_tokens,
+            total_tokens: turn.prompt_tokens + turn.completion_tokens,
+            cached_requests: turn.cached_requests,
+        });
+    }
+
+    fn record_error(&mut self, message: String) {
+        self.sink.emit(TelemetryEventKind::Error {
+            turn: self.turn.as_ref().map(|turn| turn.number),
+            message,
+        });
+    }
+
+    fn end_tool_call(
+        &mut self,
+        id: String,
+        name: String,
+        status: ToolCallStatus,
+        error: Option<String>,
+    ) {
+        let Some(pending) = self.tool_calls.remove(&id) else {
+            return;
+        };
+        let turn = self.current_turn();
+        let duration_ms = elapsed_ms(pending.started);
+        self.sink.emit(TelemetryEventKind::ToolCall {
+            turn,
+            call_id: id.clone(),
+            name: name.clone(),
+            duration_ms,
+            status,
+            error,
+        });
+        if let Some(label) = pending.subagent_label {
+            self.sink.emit(TelemetryEventKind::SubagentComplete {
+                turn,
+                call_id: id,
+                tool: name,
+                label,
+                duration_ms,
+                status: match status {
+                    ToolCallStatus::Success => TelemetryStatus::Success,
+                    ToolCallStatus::Failure | ToolCallStatus::Timeout => TelemetryStatus::Error,
+                },
+            });
+        }
+    }
+
+    fn record(&mut self, event: &AgentExecutionEvent) {
+        match event {
+            AgentExecutionEvent::PromptStarted => {
+                // A previous turn that never saw a terminal event is closed first
+                self.end_turn(TelemetryStatus::Error);
+                let number = self.sink.next_turn();
+                self.sink
+                    .emit(TelemetryEventKind::TurnStart { turn: number });
+                self.turn = Some(TurnState {
+                    number,
+                    started: Instant::now(),
+                    provider_requests: 0,
+                    prompt_tokens: 0,
+                    completion_tokens: 0,
+                    cached_requests: 0,
+                });
+            }
+            AgentExecutionEvent::ProviderRequestStarted => {
+                if let Some(turn) = &mut self.turn {
+                    turn.provider_requests += 1;
+                }
+            }
+            AgentExecutionEvent::TokenUsageReported {
+                prompt_tokens,
+                completion_tokens,
+            } => {
+                if let Some(turn) = &mut self.turn {
+                    turn.prompt_tokens += prompt_tokens;
+                    turn.completion_tokens += completion_tokens;
+                }
+            }
+            AgentExecutionEvent::ProviderCacheHit { .. } => {
+                if let Some(turn) = &mut self.turn {
+                    turn.cached_requests += 1;
+                }
+            }
+            AgentExecutionEvent::ToolCallStarted {
+                id,
+                name,
+                arguments,
+            } => {
+                let subagent_label = SUBAGENT_TOOLS
+                    .contains(&name.as_str())
+                    .then(|| subagent_label(arguments));
+                if let Some(label) = &subagent_label {
+                    self.sink.emit(TelemetryEventKind::SubagentSpawn {
+                        turn: self.current_turn(),
+                        call_id: id.clone(),
+                        tool: name.clone(),
+                        label: label.clone(),
+                    });
+                }
+                self.tool_calls.insert(
+                    id.clone(),
+                    PendingToolCall {
+                        started: Instant::now(),
+                        subagent_label,
+                    },
+                );
+            }
+            AgentExecutionEvent::ToolCallCompleted {
+                id, name, status, ..
+            } => self.end_tool_call(id.clone(), name.clone(), *status, None),
+            AgentExecutionEvent::ToolCallFailed { id, name, error } => self.end_tool_call(
+                id.clone(),
+                name.clone(),
+                ToolCallStatus::classify(false, Some(error)),
+                Some(error.clone()),
+            ),
+            AgentExecutionEvent::ConversationSummarized {
+                messages_before,
+                messages_after,
+                tokens_after,
+            } => self.sink.emit(TelemetryEventKind::Summarization {
+                turn: self.current_turn(),
+                messages_before: *messages_before as u64,
+                messages_after: *messages_after as u64,
+                tokens_after: *tokens_after as u64,
+            }),
+            AgentExecutionEvent::ConversationPruned { report } => {
+                self.sink.emit(TelemetryEventKind::Pruning {
+                    turn: self.current_turn(),
+                    reason: report.reason,
+                    messages: report.messages.clone(),
+                    tokens_before: report.tokens_before as u64,
+                    tokens_after: report.tokens_after as u64,
+                    summary_tokens: report.summary_tokens.map(|tokens| tokens as u64),
+                })
+            }
+            AgentExecutionEvent::CancellationRequested => self.end_turn(TelemetryStatus::Cancelled),
+            AgentExecutionEvent::ExecutionCompleted { .. } => {
+                self.end_turn(TelemetryStatus::Success)
+            }
+            AgentExecutionEvent::ExecutionFailed { error } => {
+                self.record_error(error.clone());
+                self.end_turn(TelemetryStatus::Error);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl AgentObserver for TelemetryObserver<'_> {
+    fn on_event(&mut self, event: AgentExecutionEvent) {
+        self.record(&event);
+        self.inner.on_event(event);
+    }
+}
+
+/// Extracts the `label` argument of a subagent tool call
+fn subagent_label(arguments: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(arguments)
+        .ok()?
+        .get("label")?
+        .as_str()
+        .map(str::to_string)
+}
+
+fn open_append(path: &Path) -> io::Result<std::fs::File> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::events::NoOpObserver;
+    use tempfile::TempDir;
+
+    fn read_events(path: &Path) -> Vec<TelemetryEvent> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn file_sink(dir: &TempDir) -> (Arc<TelemetrySink>, std::path::PathBuf) {
+        let path = dir.path().join("nested/telemetry.jsonl");
+        let config = TelemetryConfig {
+            file: Some(path.display().to_string()),
+            stdout: false,
+            otlp: None,
+        };
+        let sink = TelemetrySink::from_config(&config, "session-1")
+            .unwrap()
+            .unwrap();
+        (sink, path)
+    }
+
+    #[test]
+    fn test_from_config_disabled_returns_none() {
+        let sink = TelemetrySink::from_config(&TelemetryConfig::default(), "s").unwrap();
+        assert!(sink.is_none());
+    }
+
+    #[test]
+    fn test_event_serializes_with_tag_and_schema_version() {
+        let event = TelemetryEvent {
+            schema_version: TELEMETRY_SCHEMA_VERSION,
+            timestamp: Utc::now(),
+            session_id: "s1".to_string(),
+            kind: TelemetryEventKind::ToolCall {
+                turn: 2,
+                call_id: "call-1".to_string(),
+                name: "grep".to_string(),
+                duration_ms: 12,
+                status: ToolCallStatus::Success,
+                error: None,
+            },
+        };
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["event"], "tool_call");
+        assert_eq!(value["schema_version"], 1);
+        assert_eq!(value["status"], "success");
+        assert!(value.get("error").is_none());
+
+        let round_trip: TelemetryEvent = serde_json::from_value(value).unwrap();
+        assert_eq!(round_trip, event);
+    }
+
+    #[test]
+    fn test_observer_records_turn_tools_and_subagents() {
+        let dir = TempDir::new().unwrap();
+        let (sink, path) = file_sink(&dir);
+        let mut inner = NoOpObserver;
+        let mut observer = TelemetryObserver::new(Arc::clone(&sink), &mut inner);
+
+        observer.on_event(AgentExecutionEvent::PromptStarted);
+        observer.on_event(AgentExecutionEvent::ProviderRequestStarted);
+        observer.on_event(AgentExecutionEvent::TokenUsageReported {
+            prompt_tokens: 100,
+            completion_tokens: 10,
+        });
+        observer.on_event(AgentExecutionEvent::ProviderRequestStarted);
+        observer.on_event(AgentExecutionEvent::ProviderCacheHit {
+            prompt_tokens: 100,
+            completion_tokens: 10,
+        });
+        observer.on_event(AgentExecutionEvent::ToolCallStarted {
+            id: "call-1".to_string(),
+            name: "subagent".to_string(),
+            arguments: r#"{"label":"research","task_prompt":"x"}"#.to_string(),
+        });
+        observer.on_event(AgentExecutionEvent::ToolCallFailed {
+            id: "call-1".to_string(),
+            name: "subagent".to_string(),
+            error: "boom".to_string(),
+        });
+        observer.on_event(AgentExecutionEvent::ExecutionFailed {
+            error: "boom".to_string(),
+        });
+        observer.finish(&Err(XzatomaError::Tool("boom".to_string())));
+        sink.end_session(TelemetryStatus::Error);
+        sink.end_session(TelemetryStatus::Success);
+
+        let kinds: Vec<TelemetryEventKind> =
+            read_events(&path).into_iter().map(|e| e.kind).collect();
+        assert_eq!(kinds.len(), 7, "{:?}", kinds);
+        assert_eq!(kinds[0], TelemetryEventKind::TurnStart { turn: 1 });
+        assert!(matches!(
+            &kinds[1],
+            TelemetryEventKind::SubagentSpawn { label: Some(label), .. } if label == "research"
+        ));
+        assert!(matches!(
+            &kinds[2],
+            TelemetryEventKind::ToolCall {
+                status: ToolCallStatus::Failure,
+                error: Some(_),
+                ..
+            }
+        ));
+        assert!(matches!(
+            &kinds[3],
+            TelemetryEventKind::SubagentComplete {
+                status: TelemetryStatus::Error,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &kinds[4],
+            TelemetryEventKind::Error { turn: Some(1), .. }
+        ));
+        assert!(matches!(
+            &kinds[5],
+            TelemetryEventKind::TurnEnd {
+                status: TelemetryStatus::Error,
+                provider_requests: 2,
+                total_tokens: 110,
+                cached_requests: 1,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &kinds[6],
+            TelemetryEventKind::SessionEnd {
+                status: TelemetryStatus::Error,
+                turns: 1,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_observer_records_pruning_reports() {
+        let dir = TempDir::new().unwrap();
+        let (sink, path) = file_sink(&dir);
+        let mut inner = NoOpObserver;
+        let mut observer = TelemetryObserver::new(Arc::clone(&sink), &mut inner);
+
+        let mut conversation = crate::agent::Conversation::new(8000, 10, 0.8);
+        conversation.add_user_message("x".repeat(4000));
+        conversation.add_assistant_message("done");
+        conversation.add_user_message("next question");
+        conversation.compact_to(100);
+        let report = conversation.take_prune_reports().remove(0);
+
+        observer.on_event(AgentExecutionEvent::PromptStarted);
+        observer.on_event(AgentExecutionEvent::ConversationPruned { report });
+
+        let events = read_events(&path);
+        let value = serde_json::to_value(&events[1]).unwrap();
+        assert_eq!(value["event"], "pruning");
+        assert_eq!(value["turn"], 1);
+        assert_eq!(value["reason"], "overflow_recovery");
+        assert_eq!(value["messages"][0]["role"], "user");
+        assert_eq!(value["messages"][0]["tokens"], 1000);
+        assert_eq!(value["messages"][1]["action"], "summarized");
+        assert!(value["summary_tokens"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_end_on_drop_ends_session_as_error() {
+        let dir = TempDir::new().unwrap();
+        let (sink, path) = file_sink(&dir);
+
+        drop(sink.end_on_drop());
+
+        let kinds: Vec<TelemetryEventKind> =
+            read_events(&path).into_iter().map(|e| e.kind).collect();
+        assert_eq!(kinds.len(), 1);
+        assert!(matches!(
+            &kinds[0],
+            TelemetryEventKind::SessionEnd {
+                status: TelemetryStatus::Error,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_end_on_drop_keeps_explicit_status() {
+        let dir = TempDir::new().unwrap();
+        let (sink, path) = file_sink(&dir);
+
+        let guard = sink.end_on_drop();
+        sink.end_session(TelemetryStatus::Success);
+        drop(guard);
+
+        let kinds: Vec<TelemetryEventKind> =
+            read_events(&path).into_iter().map(|e| e.kind).collect();
+        assert_eq!(kinds.len(), 1);
+        assert!(matches!(
+            &kinds[0],
+            TelemetryEventKind::SessionEnd {
+                status: TelemetryStatus::Success,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_finish_closes_turn_without_terminal_event() {
+        let dir = TempDir::new().unwrap();
+        let (sink, path) = file_sink(&dir);
+        let mut inner = NoOpObserver;
+        let mut observer = TelemetryObserver::new(Arc::clone(&sink), &mut inner);
+
+        observer.on_event(AgentExecutionEvent::PromptStarted);
+        observer.finish(&Err(XzatomaError::Provider("unreachable".to_string())));
+
+        let kinds: Vec<TelemetryEventKind> =
+            read_events(&path).into_iter().map(|e| e.kind).collect();
+        assert!(
+            matches!(&kinds[1], TelemetryEventKind::Error { message, .. } if message.contains("unreachable"))
+        );
+        assert!(matches!(
+            &kinds[2],
+            TelemetryEventKind::TurnEnd {
+                status: TelemetryStatus::Error,
+                ..
+            }
+        ));
+    }
+}
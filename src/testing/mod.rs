@@ -0,0 +1,27 @@
+//! Test harness for code that embeds xzatoma
+//!
+//! Enabled by the `testing` cargo feature:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! xzatoma = { version = "0.2", features = ["testing"] }
+//! ```
+//!
+//! - [`MockProvider`]: a provider that replays scripted responses, including
+//!   tool calls and errors, and records every request.
+//! - [`MockToolExecutor`]: a tool built with [`MockToolExecutor::builder`]
+//!   that returns configured results and records its calls.
+//! - `SqliteStorage::new_in_memory`: history storage that never touches
+//!   the disk.
+//!
+//! These types are public API and follow the crate's semver like every
+//! other module. The crate's own integration tests use them, so they stay
+//! exercised.
+//!
+//! See [`MockProvider`] for a complete scripted agent turn.
+
+pub mod provider;
+pub mod tool;
+
+pub use provider::{MockProvider, RecordedRequest};
+pub use tool::{MockToolExecutor, MockToolExecutorBuilder};
@@ -0,0 +1,669 @@
+//! Scriptable in-memory provider
+//!
+//! [`MockProvider`] answers requests from a script of responses queued up
+//! front, in order, and records every request it receives as a
+//! [`RecordedRequest`]. Clones share the script and the recorded requests,
+//! so a test can hand one clone to an [`Agent`](crate::agent::Agent) and
+//! inspect another after the run.
+//!
+//! A request that arrives after the script is used up fails with
+//! `XzatomaError::Provider`, which surfaces unexpected extra turns, unless a
+//! fallback response is set with [`MockProvider::with_fallback`].
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::agent::conversation::message_tokens;
+use crate::error::{Result, XzatomaError};
+use crate::providers::{
+    CompletionResponse, FunctionCall, Message, ModelInfo, Provider, ProviderCapabilities, ToolCall,
+    ToolCallOptions,
+};
+
+/// Context window reported for the mock model
+const MOCK_CONTEXT_WINDOW: usize = 128_000;
+
+/// One request received by a [`MockProvider`]
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    /// Messages exactly as passed to the provider
+    pub messages: Vec<Message>,
+    /// Tool definitions passed with the request
+    pub tools: Vec<Value>,
+    /// Whether the request came through `chat_completion_stream`
+    pub streaming: bool,
+    /// Model that was active when the request arrived
+    pub model: String,
+}
+
+impl RecordedRequest {
+    /// Names of the tools offered with the request
+    pub fn tool_names(&self) -> Vec<String> {
+        self.tools
+            .iter()
+            .filter_map(|tool| {
+                tool.pointer("/function/name")
+                    .or_else(|| tool.get("name"))
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            })
+            .collect()
+    }
+
+    /// The last message of the request
+    pub fn last_message(&self) -> Option<&Message> {
+        self.messages.last()
+    }
+
+    /// Whether any message with `role` has text containing `text`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::providers::Message;
+    /// use xzatoma::testing::RecordedRequest;
+    ///
+    /// let request = RecordedRequest {
+    ///     messages: vec![Message::user("list the files")],
+    ///     tools: Vec::new(),
+    ///     streaming: false,
+    ///     model: "mock-model".to_string(),
+    /// };
+    /// assert!(request.has_message("user", "files"));
+    /// assert!(!request.has_message("tool", "files"));
+    /// ```
+    pub fn has_message(&self, role: &str, text: &str) -> bool {
+        self.messages.iter().any(|message| {
+            message.role == role && message.content.as_deref().is_some_and(|c| c.contains(text))
+        })
+    }
+
+    /// Whether the request carries the result of tool call `tool_call_id`
+    pub fn has_tool_result(&self, tool_call_id: &str) -> bool {
+        self.messages.iter().any(|message| {
+            message.role == "tool" && message.tool_call_id.as_deref() == Some(tool_call_id)
+        })
+    }
+}
+
+/// A queued reply
+#[derive(Debug)]
+enum Scripted {
+    Response(CompletionResponse),
+    Error(String),
+    Failure(XzatomaError),
+}
+
+/// Script and requests shared by the clones of a [`MockProvider`]
+#[derive(Debug, Default)]
+struct MockState {
+    script: VecDeque<Scripted>,
+    fallback: Option<CompletionResponse>,
+    requests: Vec<RecordedRequest>,
+    request_times: Vec<Instant>,
+    temperatures: Vec<Option<f32>>,
+    tool_call_options: Vec<ToolCallOptions>,
+    tool_calls: usize,
+    in_flight: usize,
+    max_in_flight: usize,
+}
+
+/// Provider that replays scripted responses and records every request
+///
+/// Responses are queued with the `then_*` builder methods and consumed one
+/// per request, by both `complete` and `chat_completion_stream`.
+///
+/// # Examples
+///
+/// A full agent turn: the model calls a tool, then answers with its output.
+///
+/// ```
+/// use std::sync::Arc;
+/// use serde_json::json;
+/// use xzatoma::agent::Agent;
+/// use xzatoma::config::AgentConfig;
+/// use xzatoma::testing::{MockProvider, MockToolExecutor};
+/// use xzatoma::tools::ToolRegistry;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> xzatoma::error::Result<()> {
+/// let provider = MockProvider::new()
+///     .then_tool_call("lookup", json!({"key": "answer"}))
+///     .then_text("The answer is 42.");
+/// let lookup = MockToolExecutor::builder("lookup").output("42").build();
+///
+/// let mut tools = ToolRegistry::new();
+/// tools.register("lookup", Arc::new(lookup.clone()));
+/// let mut agent = Agent::new(provider.clone(), tools, AgentConfig::default())?;
+///
+/// let reply = agent.execute("What is the answer?").await?;
+///
+/// assert_eq!(reply, "The answer is 42.");
+/// assert_eq!(lookup.calls(), vec![json!({"key": "answer"})]);
+/// provider.assert_request_count(2);
+/// let first = &provider.requests()[0];
+/// assert!(first.has_message("user", "What is the answer?"));
+/// assert!(first.tool_names().contains(&"lookup".to_string()));
+/// let second = provider.last_request().unwrap();
+/// assert!(second.has_tool_result("call_1"));
+/// assert!(second.has_message("tool", "42"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockProvider {
+    model: String,
+    streaming: bool,
+    tool_call_nudge: bool,
+    latency: Duration,
+    context_window: Option<usize>,
+    capabilities: ProviderCapabilities,
+    authenticated: bool,
+    models: Option<std::result::Result<Vec<ModelInfo>, String>>,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl Default for MockProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockProvider {
+    /// Creates a provider for `mock-model` with an empty script
+    pub fn new() -> Self {
+        Self {
+            model: "mock-model".to_string(),
+            streaming: false,
+            tool_call_nudge: false,
+            latency: Duration::ZERO,
+            context_window: None,
+            capabilities: ProviderCapabilities {
+                supports_model_listing: true,
+                ..ProviderCapabilities::default()
+            },
+            authenticated: true,
+            models: None,
+            state: Arc::new(Mutex::new(MockState::default())),
+        }
+    }
+
+    /// Sets the model name the provider reports
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Sets whether the provider reports streaming support
+    ///
+    /// Either way, both `complete` and `chat_completion_stream` answer from
+    /// the script; [`RecordedRequest::streaming`] tells them apart.
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Sets whether the provider asks for tool-call nudging
+    pub fn with_tool_call_nudge(mut self, nudge: bool) -> Self {
+        self.tool_call_nudge = nudge;
+        self
+    }
+
+    /// Sets how long each request takes to answer
+    ///
+    /// Together with [`MockProvider::max_in_flight`], this lets tests check
+    /// how many requests a wrapper lets run at once.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Rejects requests larger than `tokens`
+    ///
+    /// Requests are measured with the agent's own token estimate, and a
+    /// larger one fails with `XzatomaError::ContextOverflow` without using a
+    /// scripted reply. The window is also what `fetch_models` reports.
+    pub fn with_context_window(mut self, tokens: usize) -> Self {
+        self.context_window = Some(tokens);
+        self
+    }
+
+    /// Sets the capabilities the provider reports
+    ///
+    /// `supports_streaming` still follows [`MockProvider::with_streaming`].
+    pub fn with_capabilities(mut self, capabilities: ProviderCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Sets what `is_authenticated` reports
+    pub fn with_authenticated(mut self, authenticated: bool) -> Self {
+        self.authenticated = authenticated;
+        self
+    }
+
+    /// Sets the models `fetch_models` lists
+    ///
+    /// Without this, the only model listed is the current one.
+    pub fn with_models(mut self, models: Vec<ModelInfo>) -> Self {
+        self.models = Some(Ok(models));
+        self
+    }
+
+    /// Makes `fetch_models` fail with `XzatomaError::Provider`
+    pub fn with_model_list_error(mut self, message: impl Into<String>) -> Self {
+        self.models = Some(Err(message.into()));
+        self
+    }
+
+    /// Sets the response returned once the script is used up
+    pub fn with_fallback(self, response: CompletionResponse) -> Self {
+        self.lock().fallback = Some(response);
+        self
+    }
+
+    /// Queues a response
+    pub fn then_response(self, response: CompletionResponse) -> Self {
+        self.push(Scripted::Response(response));
+        self
+    }
+
+    /// Queues a plain assistant message
+    pub fn then_text(self, text: impl Into<String>) -> Self {
+        self.then_response(CompletionResponse::new(Message::assistant(text)))
+    }
+
+    /// Queues an assistant message that calls one tool
+    ///
+    /// Tool call IDs are numbered across the script: `call_1`, `call_2`, ...
+    pub fn then_tool_call(self, name: impl Into<String>, arguments: Value) -> Self {
+        self.then_tool_calls(vec![(name.into(), arguments)])
+    }
+
+    /// Queues an assistant message that calls several tools at once
+    pub fn then_tool_calls(self, calls: Vec<(String, Value)>) -> Self {
+        let mut state = self.lock();
+        let tool_calls = calls
+            .into_iter()
+            .map(|(name, arguments)| {
+                state.tool_calls += 1;
+                ToolCall {
+                    id: format!("call_{}", state.tool_calls),
+                    function: FunctionCall {
+                        name,
+                        arguments: arguments.to_string(),
+                    },
+                }
+            })
+            .collect();
+        drop(state);
+        self.then_response(CompletionResponse::new(Message::assistant_with_tools(
+            tool_calls,
+        )))
+    }
+
+    /// Queues a failed request, returned as `XzatomaError::Provider`
+    pub fn then_error(self, message: impl Into<String>) -> Self {
+        self.push(Scripted::Error(message.into()));
+        self
+    }
+
+    /// Queues a failed request, returned as `error`
+    ///
+    /// Use this for errors other than `XzatomaError::Provider`, such as
+    /// `XzatomaError::RateLimited`.
+    pub fn then_failure(self, error: XzatomaError) -> Self {
+        self.push(Scripted::Failure(error));
+        self
+    }
+
+    /// Every request received so far, in order
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().requests.clone()
+    }
+
+    /// The most recent request
+    pub fn last_request(&self) -> Option<RecordedRequest> {
+        self.lock().requests.last().cloned()
+    }
+
+    /// Number of requests received so far
+    pub fn request_count(&self) -> usize {
+        self.lock().requests.len()
+    }
+
+    /// When each request arrived, in the order of [`MockProvider::requests`]
+    pub fn request_times(&self) -> Vec<Instant> {
+        self.lock().request_times.clone()
+    }
+
+    /// The most requests that were being answered at the same time
+    pub fn max_in_flight(&self) -> usize {
+        self.lock().max_in_flight
+    }
+
+    /// Every temperature passed to `set_temperature`, in order
+    pub fn temperatures(&self) -> Vec<Option<f32>> {
+        self.lock().temperatures.clone()
+    }
+
+    /// Every option set passed to `set_tool_call_options`, in order
+    pub fn tool_call_options(&self) -> Vec<ToolCallOptions> {
+        self.lock().tool_call_options.clone()
+    }
+
+    /// The most recent temperature passed to `set_temperature`
+    pub fn temperature(&self) -> Option<f32> {
+        self.lock().temperatures.last().copied().flatten()
+    }
+
+    /// Number of scripted replies not yet used
+    pub fn remaining(&self) -> usize {
+        self.lock().script.len()
+    }
+
+    /// Panics unless exactly `expected` requests were received
+    pub fn assert_request_count(&self, expected: usize) {
+        let actual = self.request_count();
+        assert_eq!(
+            actual, expected,
+            "MockProvider received {} requests, expected {}",
+            actual, expected
+        );
+    }
+
+    /// Panics unless every scripted reply was used
+    pub fn assert_script_consumed(&self) {
+        let remaining = self.remaining();
+        assert_eq!(
+            remaining, 0,
+            "MockProvider has {} scripted replies left",
+            remaining
+        );
+    }
+
+    fn push(&self, reply: Scripted) {
+        self.lock().script.push_back(reply);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MockState> {
+        // A test that panicked while holding the lock has already failed
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records the request and returns the next scripted reply
+    async fn answer(
+        &self,
+        messages: &[Message],
+        tools: &[Value],
+        streaming: bool,
+    ) -> Result<CompletionResponse> {
+        let reply = {
+            let mut state = self.lock();
+            state.requests.push(RecordedRequest {
+                messages: messages.to_vec(),
+                tools: tools.to_vec(),
+                streaming,
+                model: self.model.clone(),
+            });
+            state.request_times.push(Instant::now());
+            state.in_flight += 1;
+            state.max_in_flight = state.max_in_flight.max(state.in_flight);
+
+            let tokens: usize = messages.iter().map(message_tokens).sum();
+            if let Some(limit) = self.context_window.filter(|limit| tokens > *limit) {
+                Err(XzatomaError::ContextOverflow {
+                    limit: Some(limit),
+                    attempted: Some(tokens),
+                })
+            } else {
+                match state.script.pop_front() {
+                    Some(Scripted::Response(mut response)) => {
+                        response.model.get_or_insert_with(|| self.model.clone());
+                        Ok(response)
+                    }
+                    Some(Scripted::Error(message)) => Err(XzatomaError::Provider(message)),
+                    Some(Scripted::Failure(error)) => Err(error),
+                    None => state.fallback.clone().ok_or_else(|| {
+                        XzatomaError::Provider(format!(
+                            "MockProvider has no scripted response left for request {}",
+                            state.requests.len()
+                        ))
+                    }),
+                }
+            }
+        };
+
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        self.lock().in_flight -= 1;
+        reply
+    }
+}
+
+#[async_trait]
+impl Provider for MockProvider {
+    fn is_authenticated(&self) -> bool {
+        self.authenticated
+    }
+
+    fn current_model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn set_model(&mut self, model: &str) {
+        self.model = model.to_string();
+    }
+
+    async fn fetch_models(&self) -> Result<Vec<ModelInfo>> {
+        match &self.models {
+            Some(Ok(models)) => Ok(models.clone()),
+            Some(Err(message)) => Err(XzatomaError::Provider(message.clone())),
+            None => Ok(vec![ModelInfo::new(
+                &self.model,
+                &self.model,
+                self.context_window.unwrap_or(MOCK_CONTEXT_WINDOW),
+            )]),
+        }
+    }
+
+    async fn complete(&self, messages: &[Message], tools: &[Value]) -> Result<CompletionResponse> {
+        self.answer(messages, tools, false).await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.streaming
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        messages: &[Message],
+        tools: &[Value],
+    ) -> Result<CompletionResponse> {
+        self.answer(messages, tools, true).await
+    }
+
+    fn get_provider_capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            supports_streaming: self.streaming,
+            ..self.capabilities
+        }
+    }
+
+    fn set_tool_call_options(&self, options: ToolCallOptions) -> Result<()> {
+        self.lock().tool_call_options.push(options);
+        Ok(())
+    }
+
+    fn set_temperature(&self, temperature: Option<f32>) -> Result<()> {
+        self.lock().temperatures.push(temperature);
+        Ok(())
+    }
+
+    fn needs_tool_call_nudge(&self) -> bool {
+        self.tool_call_nudge
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_script_is_replayed_in_order_and_requests_are_recorded() {
+        let provider = MockProvider::new()
+            .then_tool_call("read_file", json!({"path": "a.txt"}))
+            .then_text("done");
+        let tool = json!({"type": "function", "function": {"name": "read_file"}});
+
+        let first = provider
+            .complete(&[Message::user("read a.txt")], &[tool])
+            .await
+            .unwrap();
+        let calls = first.message.tool_calls.unwrap();
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(calls[0].function.arguments, r#"{"path":"a.txt"}"#);
+        assert_eq!(first.model.as_deref(), Some("mock-model"));
+
+        let second = provider
+            .chat_completion_stream(&[Message::user("again")], &[])
+            .await
+            .unwrap();
+        assert_eq!(second.message.content.as_deref(), Some("done"));
+
+        let requests = provider.requests();
+        assert_eq!(requests[0].tool_names(), vec!["read_file"]);
+        assert!(!requests[0].streaming);
+        assert!(requests[1].streaming);
+        assert!(requests[1].has_message("user", "again"));
+        provider.assert_script_consumed();
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_script_fails_unless_a_fallback_is_set() {
+        let provider = MockProvider::new().then_error("rate limited");
+        assert!(matches!(
+            provider.complete(&[], &[]).await,
+            Err(XzatomaError::Provider(message)) if message == "rate limited"
+        ));
+        assert!(provider.complete(&[], &[]).await.is_err());
+        provider.assert_request_count(2);
+
+        let provider = MockProvider::new()
+            .with_fallback(CompletionResponse::new(Message::assistant("fallback")));
+        let response = provider.complete(&[], &[]).await.unwrap();
+        assert_eq!(response.message.content.as_deref(), Some("fallback"));
+    }
+
+    #[test]
+    fn test_clones_share_script_and_requests() {
+        let provider = MockProvider::new().with_streaming(true);
+        let clone = provider.clone().then_text("shared");
+        assert_eq!(provider.remaining(), 1);
+        assert!(clone.supports_streaming());
+        assert!(clone.get_provider_capabilities().supports_streaming);
+    }
+
+    #[tokio::test]
+    async fn test_latency_overlaps_are_counted_and_failures_returned() {
+        let provider = MockProvider::new()
+            .with_latency(Duration::from_millis(20))
+            .then_failure(XzatomaError::RateLimited {
+                provider: "mock".to_string(),
+                retry_after: None,
+            })
+            .with_fallback(CompletionResponse::new(Message::assistant("ok")));
+
+        let (first, second) = tokio::join!(
+            provider.complete(&[], &[]),
+            provider.chat_completion_stream(&[], &[])
+        );
+        assert!(matches!(first, Err(XzatomaError::RateLimited { .. })));
+        assert!(second.is_ok());
+        assert_eq!(provider.max_in_flight(), 2);
+        assert_eq!(provider.request_times().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_context_window_rejects_large_requests_without_using_the_script() {
+        let provider = MockProvider::new()
+            .with_context_window(10)
+            .then_text("small");
+
+        let error = provider
+            .complete(&[Message::user("x".repeat(100))], &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            XzatomaError::ContextOverflow {
+                limit: Some(10),
+                attempted: Some(25)
+            }
+        ));
+        assert_eq!(provider.remaining(), 1);
+        let response = provider
+            .complete(&[Message::user("hi")], &[])
+            .await
+            .unwrap();
+        assert_eq!(response.message.content.as_deref(), Some("small"));
+        assert_eq!(provider.fetch_models().await.unwrap()[0].context_window, 10);
+        provider.assert_request_count(2);
+    }
+
+    #[test]
+    fn test_capabilities_and_tool_call_options_are_reported() {
+        let provider = MockProvider::new().with_capabilities(ProviderCapabilities {
+            supports_tool_choice: true,
+            ..ProviderCapabilities::default()
+        });
+        let capabilities = provider.get_provider_capabilities();
+        assert!(capabilities.supports_tool_choice);
+        assert!(!capabilities.supports_model_listing);
+
+        let options = ToolCallOptions {
+            parallel_tool_calls: Some(false),
+            ..ToolCallOptions::default()
+        };
+        provider.set_tool_call_options(options).unwrap();
+        assert_eq!(provider.tool_call_options(), vec![options]);
+    }
+
+    #[tokio::test]
+    async fn test_model_list_and_authentication_can_be_configured() {
+        let provider = MockProvider::new();
+        assert!(provider.is_authenticated());
+        assert_eq!(provider.fetch_models().await.unwrap()[0].name, "mock-model");
+
+        let provider = MockProvider::new()
+            .with_authenticated(false)
+            .with_models(vec![ModelInfo::new("a", "a", 8192)]);
+        assert!(!provider.is_authenticated());
+        assert_eq!(provider.fetch_models().await.unwrap()[0].name, "a");
+
+        let provider = MockProvider::new().with_model_list_error("connection refused");
+        assert!(matches!(
+            provider.fetch_models().await,
+            Err(XzatomaError::Provider(message)) if message == "connection refused"
+        ));
+    }
+
+    #[test]
+    fn test_temperatures_and_nudge_are_reported() {
+        let provider = MockProvider::new().with_tool_call_nudge(true);
+        assert!(provider.needs_tool_call_nudge());
+        assert_eq!(provider.temperature(), None);
+
+        provider.set_temperature(Some(0.4)).unwrap();
+        provider.clone().set_temperature(None).unwrap();
+        assert_eq!(provider.temperatures(), vec![Some(0.4), None]);
+        assert_eq!(provider.temperature(), None);
+        assert!(!MockProvider::new().needs_tool_call_nudge());
+    }
+}
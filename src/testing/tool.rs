@@ -0,0 +1,241 @@
+//! Configurable in-memory tool
+//!
+//! [`MockToolExecutor`] is built with [`MockToolExecutor::builder`]. It
+//! returns configured results without side effects and records the
+//! arguments of every call. Clones share the recorded calls and queued
+//! results, so a test can register one clone and inspect another.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::error::Result;
+use crate::tools::{ToolExecutor, ToolResult};
+
+/// Calls and queued results shared by the clones of a [`MockToolExecutor`]
+#[derive(Debug, Default)]
+struct ToolState {
+    queued: VecDeque<ToolResult>,
+    calls: Vec<Value>,
+}
+
+/// Builder for [`MockToolExecutor`]
+///
+/// # Examples
+///
+/// ```
+/// use serde_json::json;
+/// use xzatoma::testing::MockToolExecutor;
+/// use xzatoma::tools::{ToolExecutor, ToolResult};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> xzatoma::error::Result<()> {
+/// let tool = MockToolExecutor::builder("deploy")
+///     .description("Deploys the service")
+///     .mutates(true)
+///     .then(ToolResult::error("cluster busy"))
+///     .output("deployed")
+///     .build();
+///
+/// assert!(!tool.execute(json!({"env": "staging"})).await?.success);
+/// assert_eq!(tool.execute(json!({"env": "staging"})).await?.output, "deployed");
+/// assert_eq!(tool.call_count(), 2);
+/// assert!(tool.mutates());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockToolExecutorBuilder {
+    name: String,
+    description: String,
+    parameters: Value,
+    result: ToolResult,
+    queued: VecDeque<ToolResult>,
+    mutates: bool,
+    read_only: bool,
+}
+
+impl MockToolExecutorBuilder {
+    /// Sets the description in the tool definition
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Sets the JSON schema of the tool's parameters
+    pub fn parameters(mut self, parameters: Value) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    /// Returns a successful result with `output` from every call
+    pub fn output(self, output: impl Into<String>) -> Self {
+        self.result(ToolResult::success(output))
+    }
+
+    /// Returns a failed result with `error` from every call
+    pub fn error(self, error: impl Into<String>) -> Self {
+        self.result(ToolResult::error(error))
+    }
+
+    /// Returns `result` from every call without a queued result
+    pub fn result(mut self, result: ToolResult) -> Self {
+        self.result = result;
+        self
+    }
+
+    /// Queues `result` for one call, ahead of the default result
+    pub fn then(mut self, result: ToolResult) -> Self {
+        self.queued.push_back(result);
+        self
+    }
+
+    /// Sets whether the tool reports that it changes the workspace
+    pub fn mutates(mut self, mutates: bool) -> Self {
+        self.mutates = mutates;
+        self
+    }
+
+    /// Sets whether the tool reports that it only reads the workspace
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Builds the tool
+    pub fn build(self) -> MockToolExecutor {
+        MockToolExecutor {
+            name: self.name,
+            description: self.description,
+            parameters: self.parameters,
+            result: self.result,
+            mutates: self.mutates,
+            read_only: self.read_only,
+            state: Arc::new(Mutex::new(ToolState {
+                queued: self.queued,
+                calls: Vec::new(),
+            })),
+        }
+    }
+}
+
+/// Tool that returns configured results and records its calls
+///
+/// Without configuration, every call succeeds with empty output.
+#[derive(Debug, Clone)]
+pub struct MockToolExecutor {
+    name: String,
+    description: String,
+    parameters: Value,
+    result: ToolResult,
+    mutates: bool,
+    read_only: bool,
+    state: Arc<Mutex<ToolState>>,
+}
+
+impl MockToolExecutor {
+    /// Starts building a tool named `name`
+    pub fn builder(name: impl Into<String>) -> MockToolExecutorBuilder {
+        let name = name.into();
+        MockToolExecutorBuilder {
+            description: format!("Mock tool {}", name),
+            name,
+            parameters: serde_json::json!({"type": "object", "properties": {}}),
+            result: ToolResult::success(""),
+            queued: VecDeque::new(),
+            mutates: false,
+            read_only: false,
+        }
+    }
+
+    /// The tool name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Arguments of every call so far, in order
+    pub fn calls(&self) -> Vec<Value> {
+        self.lock().calls.clone()
+    }
+
+    /// Number of calls so far
+    pub fn call_count(&self) -> usize {
+        self.lock().calls.len()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ToolState> {
+        // A test that panicked while holding the lock has already failed
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for MockToolExecutor {
+    fn tool_definition(&self) -> Value {
+        serde_json::json!({
+            "name": self.name,
+            "description": self.description,
+            "parameters": self.parameters,
+        })
+    }
+
+    async fn execute(&self, args: Value) -> Result<ToolResult> {
+        let mut state = self.lock();
+        state.calls.push(args);
+        Ok(state
+            .queued
+            .pop_front()
+            .unwrap_or_else(|| self.result.clone()))
+    }
+
+    fn mutates(&self) -> bool {
+        self.mutates
+    }
+
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_queued_results_come_before_the_default() {
+        let tool = MockToolExecutor::builder("grep")
+            .then(ToolResult::success("first"))
+            .error("no matches")
+            .read_only(true)
+            .build();
+        let clone = tool.clone();
+
+        assert_eq!(
+            tool.execute(json!({"q": "a"})).await.unwrap().output,
+            "first"
+        );
+        let second = clone.execute(json!({"q": "b"})).await.unwrap();
+        assert_eq!(second.error.as_deref(), Some("no matches"));
+
+        assert_eq!(tool.calls(), vec![json!({"q": "a"}), json!({"q": "b"})]);
+        assert!(tool.read_only());
+        assert!(!tool.mutates());
+    }
+
+    #[test]
+    fn test_definition_uses_name_description_and_parameters() {
+        let schema = json!({"type": "object", "properties": {"path": {"type": "string"}}});
+        let tool = MockToolExecutor::builder("read")
+            .parameters(schema.clone())
+            .build();
+
+        let definition = tool.tool_definition();
+        assert_eq!(definition["name"], "read");
+        assert_eq!(definition["description"], "Mock tool read");
+        assert_eq!(definition["parameters"], schema);
+        assert_eq!(tool.name(), "read");
+    }
+}
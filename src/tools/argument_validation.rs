@@ -0,0 +1,472 @@
+//! Tool argument validation against declared JSON schemas
+//!
+//! Models often send tool arguments that do not match the `parameters` schema
+//! from `tool_definition()`: numbers as strings, missing required fields, or
+//! parameters the tool never declared. This module checks arguments before a
+//! tool runs:
+//! - Lenient coercions: numeric strings become numbers and `"true"`/`"false"`
+//!   become booleans where the schema expects them
+//! - Sanitization: undeclared parameters are dropped from objects that list
+//!   their properties
+//! - Validation: the result is checked against the schema and every violation
+//!   is reported with its JSON pointer
+
+use jsonschema::JSONSchema;
+use serde_json::{Map, Number, Value};
+use std::fmt;
+
+/// Outcome of validating a tool call's arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentValidationOutcome {
+    /// Arguments matched the schema as sent
+    Valid,
+    /// Arguments matched the schema after coercion or sanitization
+    Coerced,
+    /// Arguments violate the schema and the tool must not run
+    Invalid,
+    /// The tool declares no usable schema, so nothing was checked
+    Unchecked,
+}
+
+impl ArgumentValidationOutcome {
+    /// Returns the label used in tool result metadata and metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Valid => "valid",
+            Self::Coerced => "coerced",
+            Self::Invalid => "invalid",
+            Self::Unchecked => "unchecked",
+        }
+    }
+}
+
+impl fmt::Display for ArgumentValidationOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Result of validating and sanitizing tool arguments
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgumentValidation {
+    /// Arguments to pass to the tool, including any coercions
+    pub args: serde_json::Value,
+    /// Human-readable description of each change made to the arguments
+    pub adjustments: Vec<String>,
+    /// Schema violations that remain after coercion
+    pub violations: Vec<String>,
+    /// Overall outcome
+    pub outcome: ArgumentValidationOutcome,
+}
+
+impl ArgumentValidation {
+    /// Returns true when the tool may run with `args`
+    pub fn is_valid(&self) -> bool {
+        self.outcome != ArgumentValidationOutcome::Invalid
+    }
+
+    /// Formats the violations as an error the model can act on
+    ///
+    /// # Arguments
+    ///
+    /// * `tool_name` - Name of the tool that was called
+    ///
+    /// # Returns
+    ///
+    /// Returns a message quoting every violation
+    pub fn error_message(&self, tool_name: &str) -> String {
+        let mut message = format!("Invalid arguments for tool '{}':", tool_name);
+        for violation in &self.violations {
+            message.push_str("\n- ");
+            message.push_str(violation);
+        }
+        message.push_str(
+            "\nCorrect the arguments to match the tool's parameter schema and call the tool again.",
+        );
+        message
+    }
+}
+
+/// Validates tool arguments against the `parameters` schema of a tool definition
+///
+/// Coercions and sanitization are applied before validation, so a call that
+/// only differs from the schema in value encoding still succeeds.
+///
+/// # Arguments
+///
+/// * `definition` - Tool definition as returned by `ToolExecutor::tool_definition`
+/// * `args` - Arguments sent by the model
+///
+/// # Returns
+///
+/// Returns the possibly adjusted arguments, the adjustments made, and any
+/// remaining violations
+///
+/// # Examples
+///
+/// ```
+/// use serde_json::json;
+/// use xzatoma::tools::{validate_tool_arguments, ArgumentValidationOutcome};
+///
+/// let definition = json!({
+///     "name": "read_file",
+///     "parameters": {
+///         "type": "object",
+///         "properties": {
+///             "path": {"type": "string"},
+///             "start_line": {"type": "integer"}
+///         },
+///         "required": ["path"]
+///     }
+/// });
+///
+/// let validation =
+///     validate_tool_arguments(&definition, json!({"path": "a.rs", "start_line": "10"}));
+/// assert_eq!(validation.outcome, ArgumentValidationOutcome::Coerced);
+/// assert_eq!(validation.args["start_line"], json!(10));
+///
+/// let validation = validate_tool_arguments(&definition, json!({"start_line": 10}));
+/// assert_eq!(validation.outcome, ArgumentValidationOutcome::Invalid);
+/// ```
+pub fn validate_tool_arguments(
+    definition: &serde_json::Value,
+    args: serde_json::Value,
+) -> ArgumentValidation {
+    let unchecked = |args| ArgumentValidation {
+        args,
+        adjustments: Vec::new(),
+        violations: Vec::new(),
+        outcome: ArgumentValidationOutcome::Unchecked,
+    };
+
+    let schema = match definition.get("parameters") {
+        Some(schema) if schema.is_object() => schema,
+        _ => return unchecked(args),
+    };
+
+    let compiled = match JSONSchema::compile(schema) {
+        Ok(compiled) => compiled,
+        Err(e) => {
+            tracing::debug!(
+                "Skipping argument validation, schema does not compile: {}",
+                e
+            );
+            return unchecked(args);
+        }
+    };
+
+    let mut args = args;
+    let mut adjustments = Vec::new();
+    sanitize_value(schema, &mut args, "", &mut adjustments);
+
+    let violations: Vec<String> = match compiled.validate(&args) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors
+            .map(|error| {
+                let path = error.instance_path.to_string();
+                let path = if path.is_empty() {
+                    "(root)".to_string()
+                } else {
+                    path
+                };
+                format!("{}: {}", path, error)
+            })
+            .collect(),
+    };
+
+    let outcome = if !violations.is_empty() {
+        ArgumentValidationOutcome::Invalid
+    } else if !adjustments.is_empty() {
+        ArgumentValidationOutcome::Coerced
+    } else {
+        ArgumentValidationOutcome::Valid
+    };
+
+    ArgumentValidation {
+        args,
+        adjustments,
+        violations,
+        outcome,
+    }
+}
+
+/// Returns the types a schema accepts, from either a string or an array `type`
+fn schema_types(schema: &Value) -> Vec<&str> {
+    match schema.get("type") {
+        Some(Value::String(kind)) => vec![kind.as_str()],
+        Some(Value::Array(kinds)) => kinds.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns true when `value` already satisfies one of the schema types
+fn matches_any_type(value: &Value, types: &[&str]) -> bool {
+    types.iter().any(|kind| match *kind {
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "null" => value.is_null(),
+        _ => false,
+    })
+}
+
+/// Converts a string to the first schema type it can represent
+fn coerce_string(text: &str, types: &[&str]) -> Option<Value> {
+    let text = text.trim();
+    types.iter().find_map(|kind| match *kind {
+        "integer" => text.parse::<i64>().ok().map(Value::from),
+        "number" => text
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number),
+        "boolean" => match text.to_ascii_lowercase().as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Applies coercions and drops undeclared properties, recursing into nested
+/// objects and arrays
+fn sanitize_value(schema: &Value, value: &mut Value, path: &str, adjustments: &mut Vec<String>) {
+    let types = schema_types(schema);
+    if !types.is_empty() && !matches_any_type(value, &types) {
+        if let Value::String(text) = value {
+            if let Some(coerced) = coerce_string(text, &types) {
+                adjustments.push(format!(
+                    "{}: converted \"{}\" to {}",
+                    display_path(path),
+                    text,
+                    coerced
+                ));
+                *value = coerced;
+            }
+        }
+    }
+
+    match value {
+        Value::Object(map) => sanitize_object(schema, map, path, adjustments),
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items").filter(|s| s.is_object()) {
+                for (index, item) in items.iter_mut().enumerate() {
+                    sanitize_value(
+                        item_schema,
+                        item,
+                        &format!("{}/{}", path, index),
+                        adjustments,
+                    );
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Sanitizes the properties of an object against its schema
+fn sanitize_object(
+    schema: &Value,
+    map: &mut Map<String, Value>,
+    path: &str,
+    adjustments: &mut Vec<String>,
+) {
+    let properties = match schema.get("properties").and_then(Value::as_object) {
+        Some(properties) if !properties.is_empty() => properties,
+        _ => return,
+    };
+
+    // Only drop unknown keys when the schema does not explicitly allow them
+    let allows_additional = matches!(
+        schema.get("additionalProperties"),
+        Some(Value::Bool(true)) | Some(Value::Object(_))
+    ) || schema.get("patternProperties").is_some();
+    if !allows_additional {
+        let unknown: Vec<String> = map
+            .keys()
+            .filter(|key| !properties.contains_key(*key))
+            .cloned()
+            .collect();
+        for key in unknown {
+            map.remove(&key);
+            adjustments.push(format!(
+                "{}: removed unknown parameter '{}'",
+                display_path(path),
+                key
+            ));
+        }
+    }
+
+    for (key, value) in map.iter_mut() {
+        if let Some(property_schema) = properties.get(key) {
+            sanitize_value(
+                property_schema,
+                value,
+                &format!("{}/{}", path, key),
+                adjustments,
+            );
+        }
+    }
+}
+
+/// Formats a JSON pointer for messages, naming the root explicitly
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "(root)"
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::read_file::ReadFileTool;
+    use crate::tools::ToolExecutor;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn read_file_definition() -> Value {
+        ReadFileTool::new(PathBuf::from("/project"), 1024 * 1024, 100).tool_definition()
+    }
+
+    fn mock_definition() -> Value {
+        json!({
+            "name": "mock",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "enabled": {"type": "boolean"},
+                    "ratio": {"type": "number"},
+                    "items": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {"count": {"type": "integer"}},
+                            "required": ["count"]
+                        }
+                    },
+                    "extra": {"type": "object", "additionalProperties": true}
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_valid_arguments_pass_unchanged() {
+        let args = json!({"path": "src/main.rs", "start_line": 1});
+        let validation = validate_tool_arguments(&read_file_definition(), args.clone());
+        assert_eq!(validation.outcome, ArgumentValidationOutcome::Valid);
+        assert_eq!(validation.args, args);
+        assert!(validation.adjustments.is_empty());
+    }
+
+    #[test]
+    fn test_numeric_strings_are_coerced_to_integers() {
+        let validation = validate_tool_arguments(
+            &read_file_definition(),
+            json!({"path": "src/main.rs", "start_line": "5", "end_line": " 10 "}),
+        );
+        assert_eq!(validation.outcome, ArgumentValidationOutcome::Coerced);
+        assert_eq!(validation.args["start_line"], json!(5));
+        assert_eq!(validation.args["end_line"], json!(10));
+        assert_eq!(validation.adjustments.len(), 2);
+    }
+
+    #[test]
+    fn test_non_numeric_string_is_reported() {
+        let validation = validate_tool_arguments(
+            &read_file_definition(),
+            json!({"path": "src/main.rs", "start_line": "ten"}),
+        );
+        assert_eq!(validation.outcome, ArgumentValidationOutcome::Invalid);
+        assert_eq!(validation.violations.len(), 1);
+        assert!(validation.violations[0].starts_with("/start_line: "));
+        assert!(validation.violations[0].contains("integer"));
+    }
+
+    #[test]
+    fn test_missing_required_field_is_reported() {
+        let validation = validate_tool_arguments(&read_file_definition(), json!({"start_line": 3}));
+        assert_eq!(validation.outcome, ArgumentValidationOutcome::Invalid);
+        assert!(validation.violations[0].starts_with("(root): "));
+        assert!(validation.violations[0].contains("path"));
+
+        let message = validation.error_message("read_file");
+        assert!(message.starts_with("Invalid arguments for tool 'read_file':"));
+        assert!(message.contains(&validation.violations[0]));
+    }
+
+    #[test]
+    fn test_unknown_parameters_are_removed() {
+        let validation = validate_tool_arguments(
+            &read_file_definition(),
+            json!({"path": "src/main.rs", "encoding": "utf-8"}),
+        );
+        assert_eq!(validation.outcome, ArgumentValidationOutcome::Coerced);
+        assert_eq!(validation.args, json!({"path": "src/main.rs"}));
+        assert!(validation.adjustments[0].contains("encoding"));
+    }
+
+    #[test]
+    fn test_wrong_argument_shape_is_reported() {
+        let validation = validate_tool_arguments(&read_file_definition(), json!(["src/main.rs"]));
+        assert_eq!(validation.outcome, ArgumentValidationOutcome::Invalid);
+        assert!(!validation.is_valid());
+    }
+
+    #[test]
+    fn test_booleans_numbers_and_nested_values_are_coerced() {
+        let validation = validate_tool_arguments(
+            &mock_definition(),
+            json!({
+                "enabled": "TRUE",
+                "ratio": "0.5",
+                "items": [{"count": "2"}, {"count": 3}],
+                "extra": {"anything": "goes"}
+            }),
+        );
+        assert_eq!(validation.outcome, ArgumentValidationOutcome::Coerced);
+        assert_eq!(
+            validation.args,
+            json!({
+                "enabled": true,
+                "ratio": 0.5,
+                "items": [{"count": 2}, {"count": 3}],
+                "extra": {"anything": "goes"}
+            })
+        );
+        assert!(validation
+            .adjustments
+            .iter()
+            .any(|a| a.starts_with("/items/0/count: ")));
+    }
+
+    #[test]
+    fn test_nested_violations_quote_the_pointer() {
+        let validation = validate_tool_arguments(
+            &mock_definition(),
+            json!({"enabled": "maybe", "items": [{}]}),
+        );
+        assert_eq!(validation.outcome, ArgumentValidationOutcome::Invalid);
+        assert!(validation
+            .violations
+            .iter()
+            .any(|v| v.starts_with("/enabled: ")));
+        assert!(validation
+            .violations
+            .iter()
+            .any(|v| v.starts_with("/items/0: ")));
+    }
+
+    #[test]
+    fn test_missing_schema_is_unchecked() {
+        let validation = validate_tool_arguments(&json!({"name": "bare"}), json!({"anything": 1}));
+        assert_eq!(validation.outcome, ArgumentValidationOutcome::Unchecked);
+        assert!(validation.is_valid());
+    }
+}
@@ -0,0 +1,521 @@
+//! Append-only audit log of file mutations
+//!
+//! Compliance reviews need a record of every file the agent created,
+//! modified, or deleted that does not depend on the conversation history.
+//! The file-mutating tools append one JSON line per mutation to the audit
+//! log. Each line carries a timestamp, the session id, the tool, the
+//! operation, the absolute path, the byte delta, and SHA-256 hashes of the
+//! content before and after.
+//!
+//! Every line is written, flushed, and synced on its own, so a crash loses
+//! at most the line being written. When `tools.audit_required` is set, the
+//! line is written before the mutation and a failed write aborts it, so no
+//! file changes without a record; a mutation that fails afterwards still
+//! leaves its line. Otherwise the line is written once the mutation has
+//! succeeded and a failed write is logged as a warning.
+//!
+//! # Examples
+//!
+//! ```
+//! use xzatoma::tools::audit_log::{AuditLog, AuditOperation};
+//!
+//! let dir = tempfile::tempdir().unwrap();
+//! let log = AuditLog::new(dir.path().join("audit.jsonl"), "session-1");
+//!
+//! let entry = log
+//!     .entry("write_file", AuditOperation::Create, dir.path().join("notes.txt"))
+//!     .with_byte_delta(5);
+//! log.append(&entry).unwrap();
+//!
+//! let entries = AuditLog::read_entries(log.path(), Some("session-1")).unwrap();
+//! assert_eq!(entries.len(), 1);
+//! assert_eq!(entries[0].tool, "write_file");
+//! ```
+
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::ToolsConfig;
+use crate::error::{Result, XzatomaError};
+use crate::paths::Paths;
+use crate::tools::ToolResult;
+
+/// File name of the audit log inside the state directory
+pub const AUDIT_LOG_FILE_NAME: &str = "audit.jsonl";
+
+/// Kind of file mutation recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    /// A new file was written
+    Create,
+    /// An existing file was overwritten or edited
+    Modify,
+    /// A file or directory was deleted
+    Delete,
+    /// A file or directory was moved or renamed
+    Move,
+    /// A file or directory was copied to a new path
+    Copy,
+    /// A directory was created
+    CreateDirectory,
+}
+
+impl AuditOperation {
+    /// Returns the name used in the log and in `xzatoma audit list`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Modify => "modify",
+            Self::Delete => "delete",
+            Self::Move => "move",
+            Self::Copy => "copy",
+            Self::CreateDirectory => "create_directory",
+        }
+    }
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// When the mutation was recorded
+    pub timestamp: DateTime<Utc>,
+    /// Session that performed the mutation
+    pub session_id: String,
+    /// Tool that performed the mutation
+    pub tool: String,
+    /// Kind of mutation
+    pub operation: AuditOperation,
+    /// Absolute path that was mutated (the destination for copies)
+    pub path: PathBuf,
+    /// Source path for copies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<PathBuf>,
+    /// Destination path for moves
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<PathBuf>,
+    /// Change in size on disk, in bytes
+    pub byte_delta: i64,
+    /// SHA-256 of the file content before the mutation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_before: Option<String>,
+    /// SHA-256 of the file content after the mutation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_after: Option<String>,
+}
+
+impl AuditEntry {
+    /// Sets the change in size on disk
+    pub fn with_byte_delta(mut self, byte_delta: i64) -> Self {
+        self.byte_delta = byte_delta;
+        self
+    }
+
+    /// Sets the content hashes before and after the mutation
+    pub fn with_hashes(mut self, before: Option<String>, after: Option<String>) -> Self {
+        self.hash_before = before;
+        self.hash_after = after;
+        self
+    }
+
+    /// Sets the source path of a copy
+    pub fn with_source(mut self, source: impl Into<PathBuf>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Sets the destination path of a move
+    pub fn with_destination(mut self, destination: impl Into<PathBuf>) -> Self {
+        self.destination = Some(destination.into());
+        self
+    }
+}
+
+/// Writer for the append-only audit log
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    session_id: String,
+    required: bool,
+    write_lock: Mutex<()>,
+}
+
+impl AuditLog {
+    /// Creates an audit log that appends to `path`
+    ///
+    /// The file and its parent directory are created on the first write.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Location of the JSON lines file
+    /// * `session_id` - Session id stamped on every entry
+    pub fn new(path: impl Into<PathBuf>, session_id: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            session_id: session_id.into(),
+            required: false,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Sets whether a failed write aborts the mutation
+    pub fn with_required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// Creates the audit log selected by the tools configuration
+    ///
+    /// Returns `None` when `tools.audit_log_enabled` is false. Entries
+    /// are stamped with [`process_session_id`].
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The tools configuration
+    /// * `paths` - Resolved directories; the default log is in the state
+    ///   directory
+    pub fn from_config(config: &ToolsConfig, paths: &Paths) -> Option<Arc<Self>> {
+        if !config.audit_log_enabled {
+            return None;
+        }
+        let path = Self::resolve_path(config, paths);
+        Some(Arc::new(
+            Self::new(path, process_session_id()).with_required(config.audit_required),
+        ))
+    }
+
+    /// Returns the configured audit log path, or `audit.jsonl` in the state
+    /// directory
+    pub fn resolve_path(config: &ToolsConfig, paths: &Paths) -> PathBuf {
+        match &config.audit_log_path {
+            Some(path) => PathBuf::from(path),
+            None => paths.audit_log(),
+        }
+    }
+
+    /// Returns the path of the log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the session id stamped on entries
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Returns true when a failed write aborts the mutation
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    /// Starts an entry for this session with the current time
+    pub fn entry(
+        &self,
+        tool: &str,
+        operation: AuditOperation,
+        path: impl Into<PathBuf>,
+    ) -> AuditEntry {
+        AuditEntry {
+            timestamp: Utc::now(),
+            session_id: self.session_id.clone(),
+            tool: tool.to_string(),
+            operation,
+            path: path.into(),
+            source: None,
+            destination: None,
+            byte_delta: 0,
+            hash_before: None,
+            hash_after: None,
+        }
+    }
+
+    /// Appends one entry, then flushes and syncs the file
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the file cannot be opened, written, or synced.
+    pub fn append(&self, entry: &AuditEntry) -> std::io::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let _guard = self
+            .write_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        file.flush()?;
+        file.sync_data()
+    }
+
+    /// Appends the entry ahead of the mutation in strict mode
+    ///
+    /// Tools call this before mutating anything. In strict mode the entry is
+    /// written now, and when the write fails an error result is returned
+    /// that the tool should return without performing the mutation. In
+    /// lenient mode nothing is written and `None` is returned; the entry is
+    /// written by [`AuditLog::record`] once the mutation succeeds.
+    pub fn record_intent(&self, entry: &AuditEntry) -> Option<ToolResult> {
+        if !self.required {
+            return None;
+        }
+        self.append(entry).err().map(|e| {
+            ToolResult::error(format!(
+                "{} of {} aborted: the audit log write to {} failed ({}); tools.audit_required is enabled",
+                entry.operation.as_str(),
+                entry.path.display(),
+                self.path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Appends the entry for a completed mutation in lenient mode
+    ///
+    /// Tools call this after the mutation succeeds. In strict mode the entry
+    /// was already written by [`AuditLog::record_intent`] and nothing is
+    /// done. A failed write is logged as a warning.
+    pub fn record(&self, entry: &AuditEntry) {
+        if self.required {
+            return;
+        }
+        if let Err(e) = self.append(entry) {
+            tracing::warn!(
+                path = %self.path.display(),
+                error = %e,
+                "Failed to write audit log entry"
+            );
+        }
+    }
+
+    /// Records `entry` when the tool result reports success
+    ///
+    /// Failed mutations are not recorded in lenient mode. Returns `result`.
+    pub fn record_outcome(&self, entry: &AuditEntry, result: ToolResult) -> ToolResult {
+        if result.success {
+            self.record(entry);
+        }
+        result
+    }
+
+    /// Reads entries back, optionally filtered by session id
+    ///
+    /// A missing file yields no entries. Lines that cannot be parsed are
+    /// skipped with a warning.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read.
+    pub fn read_entries(path: &Path, session_id: Option<&str>) -> Result<Vec<AuditEntry>> {
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(XzatomaError::Io(e)),
+        };
+
+        let mut entries = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<AuditEntry>(&line) {
+                Ok(entry) => {
+                    if session_id.map_or(true, |id| entry.session_id == id) {
+                        entries.push(entry);
+                    }
+                }
+                Err(e) => tracing::warn!(
+                    line = index + 1,
+                    error = %e,
+                    "Skipping malformed audit log entry"
+                ),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Returns the session id shared by every audit entry from this process
+///
+/// The id is generated once, so rebuilding the tool registry (for example on
+/// a chat mode switch) keeps the same session.
+pub fn process_session_id() -> &'static str {
+    static SESSION_ID: OnceLock<String> = OnceLock::new();
+    SESSION_ID.get_or_init(crate::agent::new_conversation_id)
+}
+
+/// Returns the hex SHA-256 digest of `bytes`
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Returns the size and SHA-256 digest of a regular file
+///
+/// Returns `None` when the path does not exist, is not a regular file, or
+/// cannot be read.
+pub async fn file_fingerprint(path: &Path) -> Option<(u64, String)> {
+    if !path.is_file() {
+        return None;
+    }
+    let bytes = tokio::fs::read(path).await.ok()?;
+    Some((bytes.len() as u64, hash_bytes(&bytes)))
+}
+
+/// Returns the total size of the regular files under a path
+pub fn tree_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_append_and_read_entries_filters_by_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("nested/audit.jsonl");
+        let first = AuditLog::new(&path, "one");
+        let second = AuditLog::new(&path, "two");
+
+        first
+            .append(
+                &first
+                    .entry("write_file", AuditOperation::Create, "/w/a.txt")
+                    .with_byte_delta(3)
+                    .with_hashes(None, Some(hash_bytes(b"abc"))),
+            )
+            .unwrap();
+        second
+            .append(&second.entry("delete_path", AuditOperation::Delete, "/w/b.txt"))
+            .unwrap();
+
+        let all = AuditLog::read_entries(&path, None).unwrap();
+        assert_eq!(all.len(), 2);
+        let one = AuditLog::read_entries(&path, Some("one")).unwrap();
+        assert_eq!(one.len(), 1);
+        assert_eq!(one[0].operation, AuditOperation::Create);
+        assert_eq!(one[0].byte_delta, 3);
+        assert_eq!(
+            one[0].hash_after.as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+    }
+
+    #[test]
+    fn test_read_entries_skips_malformed_lines_and_missing_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("audit.jsonl");
+        assert!(AuditLog::read_entries(&path, None).unwrap().is_empty());
+
+        let log = AuditLog::new(&path, "s");
+        log.append(&log.entry("write_file", AuditOperation::Modify, "/w/a"))
+            .unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(file, "{{not json").unwrap();
+
+        assert_eq!(AuditLog::read_entries(&path, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_record_intent_writes_only_in_strict_mode() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("audit.jsonl");
+
+        let lenient = AuditLog::new(&path, "s");
+        let entry = lenient.entry("write_file", AuditOperation::Create, "/w/a");
+        assert!(lenient.record_intent(&entry).is_none());
+        assert!(AuditLog::read_entries(&path, None).unwrap().is_empty());
+
+        let strict = AuditLog::new(&path, "s").with_required(true);
+        assert!(strict.record_intent(&entry).is_none());
+        strict.record(&entry);
+        assert_eq!(AuditLog::read_entries(&path, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_record_intent_in_strict_mode_returns_error_result() {
+        let temp_dir = TempDir::new().unwrap();
+        // A directory cannot be opened for appending
+        let path = temp_dir.path().to_path_buf();
+
+        let lenient = AuditLog::new(&path, "s");
+        let entry = lenient.entry("write_file", AuditOperation::Create, "/w/a");
+        assert!(lenient.record_intent(&entry).is_none());
+        lenient.record(&entry);
+
+        let strict = AuditLog::new(&path, "s").with_required(true);
+        let result = strict.record_intent(&entry).unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("audit_required"));
+    }
+
+    #[test]
+    fn test_record_outcome_skips_failed_mutations() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("audit.jsonl");
+        let log = AuditLog::new(&path, "s");
+        let entry = log.entry("write_file", AuditOperation::Create, "/w/a");
+
+        let failed = log.record_outcome(&entry, ToolResult::error("disk full"));
+        assert!(!failed.success);
+        assert!(AuditLog::read_entries(&path, None).unwrap().is_empty());
+
+        let done = log.record_outcome(&entry, ToolResult::success("written"));
+        assert!(done.success);
+        assert_eq!(AuditLog::read_entries(&path, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_from_config_respects_enabled_flag() {
+        let paths = Paths::resolve_with(
+            &crate::config::PathsConfig {
+                data_dir: Some("/d".to_string()),
+                cache_dir: Some("/c".to_string()),
+                state_dir: Some("/s".to_string()),
+            },
+            |_| None,
+        )
+        .unwrap();
+        let mut config = ToolsConfig {
+            audit_log_enabled: false,
+            ..ToolsConfig::default()
+        };
+        assert!(AuditLog::from_config(&config, &paths).is_none());
+
+        config.audit_log_enabled = true;
+        let log = AuditLog::from_config(&config, &paths).unwrap();
+        assert_eq!(log.path(), Path::new("/s/audit.jsonl"));
+
+        config.audit_log_path = Some("/tmp/custom-audit.jsonl".to_string());
+        config.audit_required = true;
+        let log = AuditLog::from_config(&config, &paths).unwrap();
+        assert_eq!(log.path(), Path::new("/tmp/custom-audit.jsonl"));
+        assert!(log.is_required());
+        assert_eq!(log.session_id(), process_session_id());
+    }
+}
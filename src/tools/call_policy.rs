@@ -0,0 +1,183 @@
+//! Negotiating how many tool calls run per turn
+//!
+//! Providers differ in how they handle tool calls: OpenAI-compatible APIs
+//! return several calls in one response and accept `tool_choice` and
+//! `parallel_tool_calls` parameters, while Ollama models are reliable only
+//! with one call at a time and ignore both parameters. [`ToolCallPolicy`]
+//! combines the provider's [`ProviderCapabilities`] with the
+//! `agent.tools.tool_calls` overrides into the rules the agent applies to
+//! each request and each response:
+//!
+//! - [`ToolCallPolicy::options`] gives the `tool_choice` and
+//!   `parallel_tool_calls` values to send, only when the provider accepts
+//!   them
+//! - [`ToolCallPolicy::calls_per_turn`] caps the tool calls executed from
+//!   one response; the rest receive [`deferred_call_message`] as their
+//!   result so the model can request them again
+//!
+//! # Examples
+//!
+//! ```
+//! use xzatoma::config::ToolCallsConfig;
+//! use xzatoma::providers::ProviderCapabilities;
+//! use xzatoma::tools::call_policy::ToolCallPolicy;
+//!
+//! let capabilities = ProviderCapabilities {
+//!     supports_parallel_tool_calls: true,
+//!     max_tools_per_turn: Some(8),
+//!     ..Default::default()
+//! };
+//! let config = ToolCallsConfig {
+//!     max_tools_per_turn: Some(3),
+//!     ..Default::default()
+//! };
+//!
+//! let policy = ToolCallPolicy::resolve(&capabilities, &config);
+//! assert_eq!(policy.calls_per_turn(), Some(3));
+//! assert!(policy.options(true, true).tool_choice.is_none());
+//! ```
+
+use crate::config::{ToolCallsConfig, ToolChoiceMode};
+use crate::providers::{ProviderCapabilities, ToolCallOptions};
+
+/// Tool-call rules for one provider and configuration
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolCallPolicy {
+    /// The model may return several tool calls in one response
+    pub parallel: bool,
+    /// The provider accepts a `tool_choice` parameter
+    pub supports_tool_choice: bool,
+    /// Most tool calls executed from one response
+    pub max_per_turn: Option<usize>,
+    /// Configured `tool_choice`
+    pub tool_choice: ToolChoiceMode,
+}
+
+impl ToolCallPolicy {
+    /// Combines the provider's capabilities with the configured overrides
+    ///
+    /// # Arguments
+    ///
+    /// * `capabilities` - Capabilities reported by the provider
+    /// * `config` - The `agent.tools.tool_calls` section
+    pub fn resolve(capabilities: &ProviderCapabilities, config: &ToolCallsConfig) -> Self {
+        Self {
+            parallel: config
+                .supports_parallel_tool_calls
+                .unwrap_or(capabilities.supports_parallel_tool_calls),
+            supports_tool_choice: config
+                .supports_tool_choice
+                .unwrap_or(capabilities.supports_tool_choice),
+            max_per_turn: config
+                .max_tools_per_turn
+                .or(capabilities.max_tools_per_turn),
+            tool_choice: config.tool_choice,
+        }
+    }
+
+    /// Most tool calls to execute from one response
+    ///
+    /// One when the provider does not support parallel tool calls,
+    /// otherwise the configured or reported limit. `None` means unlimited.
+    pub fn calls_per_turn(&self) -> Option<usize> {
+        if self.parallel {
+            self.max_per_turn
+        } else {
+            Some(1)
+        }
+    }
+
+    /// Tool-call parameters for the next request
+    ///
+    /// `required` is sent only on the first request of a prompt, so the
+    /// model can answer without a tool once it has results; later requests
+    /// leave `tool_choice` at the provider default. `parallel_tool_calls` is
+    /// sent as `false` when parallel calls are disabled. Nothing is sent
+    /// when the provider does not accept `tool_choice` or the request has no
+    /// tools.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_request` - Whether this is the first request of the prompt
+    /// * `has_tools` - Whether the request carries tool definitions
+    pub fn options(&self, first_request: bool, has_tools: bool) -> ToolCallOptions {
+        if !self.supports_tool_choice || !has_tools {
+            return ToolCallOptions::default();
+        }
+        ToolCallOptions {
+            tool_choice: (first_request && self.tool_choice == ToolChoiceMode::Required)
+                .then_some(ToolChoiceMode::Required),
+            parallel_tool_calls: (!self.parallel).then_some(false),
+        }
+    }
+}
+
+/// Result recorded for a tool call beyond the per-turn limit
+///
+/// # Arguments
+///
+/// * `limit` - Tool calls executed per turn
+/// * `name` - Name of the tool that was not run
+pub fn deferred_call_message(limit: usize, name: &str) -> String {
+    format!(
+        "Not run: only {} tool call{} can run per turn, so `{}` was skipped. \
+         Review the results above and request it again if it is still needed.",
+        limit,
+        if limit == 1 { "" } else { "s" },
+        name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_prefers_configured_overrides() {
+        let capabilities = ProviderCapabilities {
+            supports_parallel_tool_calls: false,
+            supports_tool_choice: true,
+            max_tools_per_turn: Some(4),
+            ..Default::default()
+        };
+        let config = ToolCallsConfig {
+            supports_parallel_tool_calls: Some(true),
+            ..Default::default()
+        };
+
+        let policy = ToolCallPolicy::resolve(&capabilities, &config);
+        assert!(policy.parallel);
+        assert!(policy.supports_tool_choice);
+        assert_eq!(policy.calls_per_turn(), Some(4));
+
+        let policy = ToolCallPolicy::resolve(&capabilities, &ToolCallsConfig::default());
+        assert_eq!(policy.calls_per_turn(), Some(1));
+    }
+
+    #[test]
+    fn test_options_follow_capabilities() {
+        let mut policy = ToolCallPolicy {
+            parallel: false,
+            supports_tool_choice: true,
+            max_per_turn: None,
+            tool_choice: ToolChoiceMode::Required,
+        };
+
+        let first = policy.options(true, true);
+        assert_eq!(first.tool_choice, Some(ToolChoiceMode::Required));
+        assert_eq!(first.parallel_tool_calls, Some(false));
+        assert!(policy.options(false, true).tool_choice.is_none());
+        assert_eq!(policy.options(true, false), ToolCallOptions::default());
+
+        policy.supports_tool_choice = false;
+        assert_eq!(policy.options(true, true), ToolCallOptions::default());
+    }
+
+    #[test]
+    fn test_deferred_call_message_names_the_tool() {
+        let message = deferred_call_message(1, "grep");
+        assert!(message.contains("only 1 tool call can run"));
+        assert!(message.contains("`grep`"));
+        assert!(deferred_call_message(3, "grep").contains("3 tool calls"));
+    }
+}
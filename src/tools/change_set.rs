@@ -0,0 +1,254 @@
+//! Previews of file changes for change set review
+//!
+//! When the model proposes several file changes in one response, chat mode
+//! shows them to the user as one change set before any of them is applied.
+//! File tools describe what a call would do through
+//! [`ToolExecutor::preview_change`](crate::tools::ToolExecutor::preview_change),
+//! which returns a [`FileChange`] without touching the workspace.
+//!
+//! Calls in a change set are previewed in order against [`StagedFiles`],
+//! which holds the contents the earlier calls would leave behind. Two edits
+//! to the same file are therefore shown as they would apply, the second on
+//! top of the first.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use similar::{ChangeTag, TextDiff};
+
+use crate::tools::file_utils::generate_diff;
+
+/// What a previewed call does to its target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// Creates a file that does not exist yet
+    Create,
+    /// Replaces the contents of an existing file
+    Modify,
+    /// Deletes a file or directory
+    Delete,
+    /// Copies `from` to the target
+    Copy {
+        /// Source path as given by the model
+        from: String,
+    },
+    /// Moves `from` to the target
+    Move {
+        /// Source path as given by the model
+        from: String,
+    },
+    /// Creates a directory
+    CreateDirectory,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::Create => write!(f, "create"),
+            ChangeKind::Modify => write!(f, "modify"),
+            ChangeKind::Delete => write!(f, "delete"),
+            ChangeKind::Copy { from } => write!(f, "copy from {}", from),
+            ChangeKind::Move { from } => write!(f, "move from {}", from),
+            ChangeKind::CreateDirectory => write!(f, "create directory"),
+        }
+    }
+}
+
+/// One proposed change to the workspace
+///
+/// `before` and `after` hold the text contents of the target, `None` where
+/// the file does not exist or is not text.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::PathBuf;
+/// use xzatoma::tools::change_set::{ChangeKind, FileChange};
+///
+/// let change = FileChange::new("notes.md", ChangeKind::Modify, PathBuf::from("/p/notes.md"))
+///     .with_contents(Some("a\nb\n".to_string()), Some("a\nc\n".to_string()));
+/// assert_eq!(change.line_counts(), (1, 1));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// Target path as given by the model
+    pub path: String,
+    /// What the call does to the target
+    pub kind: ChangeKind,
+    /// Resolved target path
+    pub target: PathBuf,
+    /// Resolved source path of a copy or move
+    pub source: Option<PathBuf>,
+    /// Contents before the change
+    pub before: Option<String>,
+    /// Contents after the change
+    pub after: Option<String>,
+    /// Extra detail shown with the change, such as a recursive delete
+    pub note: Option<String>,
+}
+
+impl FileChange {
+    /// Creates a change with no contents
+    pub fn new(path: impl Into<String>, kind: ChangeKind, target: PathBuf) -> Self {
+        Self {
+            path: path.into(),
+            kind,
+            target,
+            source: None,
+            before: None,
+            after: None,
+            note: None,
+        }
+    }
+
+    /// Sets the contents before and after the change
+    pub fn with_contents(mut self, before: Option<String>, after: Option<String>) -> Self {
+        self.before = before;
+        self.after = after;
+        self
+    }
+
+    /// Sets the resolved source path of a copy or move
+    pub fn with_source(mut self, source: PathBuf) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Sets the note shown with the change
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+
+    /// Returns the number of lines added and removed
+    pub fn line_counts(&self) -> (usize, usize) {
+        let before = self.before.as_deref().unwrap_or("");
+        let after = self.after.as_deref().unwrap_or("");
+        TextDiff::from_lines(before, after).iter_all_changes().fold(
+            (0, 0),
+            |(added, removed), change| match change.tag() {
+                ChangeTag::Insert => (added + 1, removed),
+                ChangeTag::Delete => (added, removed + 1),
+                ChangeTag::Equal => (added, removed),
+            },
+        )
+    }
+
+    /// Returns the line diff between the contents before and after
+    pub fn diff(&self) -> String {
+        generate_diff(
+            self.before.as_deref().unwrap_or(""),
+            self.after.as_deref().unwrap_or(""),
+        )
+        .unwrap_or_default()
+    }
+
+    /// Returns the resolved paths the change writes or removes
+    pub fn touched_paths(&self) -> impl Iterator<Item = &Path> {
+        let source = match self.kind {
+            ChangeKind::Move { .. } => self.source.as_deref(),
+            _ => None,
+        };
+        std::iter::once(self.target.as_path()).chain(source)
+    }
+}
+
+/// Workspace contents as earlier changes in a change set would leave them
+///
+/// Paths that no change has touched are read from disk.
+#[derive(Debug, Default)]
+pub struct StagedFiles {
+    files: HashMap<PathBuf, Option<String>>,
+    directories: HashSet<PathBuf>,
+}
+
+impl StagedFiles {
+    /// Creates an empty overlay
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the text contents of `path`, or `None` when it does not
+    /// exist or is not text
+    pub fn read(&self, path: &Path) -> Option<String> {
+        match self.files.get(path) {
+            Some(contents) => contents.clone(),
+            None => std::fs::read_to_string(path).ok(),
+        }
+    }
+
+    /// Returns true when `path` exists after the staged changes
+    pub fn exists(&self, path: &Path) -> bool {
+        match self.files.get(path) {
+            Some(contents) => contents.is_some(),
+            None => self.directories.contains(path) || path.exists(),
+        }
+    }
+
+    /// Returns true when `path` is a directory after the staged changes
+    pub fn is_dir(&self, path: &Path) -> bool {
+        !self.files.contains_key(path) && (self.directories.contains(path) || path.is_dir())
+    }
+
+    /// Records the result of `change` so later previews see it
+    pub fn stage(&mut self, change: &FileChange) {
+        match &change.kind {
+            ChangeKind::Delete => {
+                self.directories.remove(&change.target);
+                self.files.insert(change.target.clone(), None);
+            }
+            ChangeKind::CreateDirectory => {
+                self.directories.insert(change.target.clone());
+            }
+            ChangeKind::Move { .. } => {
+                if let Some(source) = &change.source {
+                    self.files.insert(source.clone(), None);
+                }
+                self.files
+                    .insert(change.target.clone(), change.after.clone());
+            }
+            ChangeKind::Create | ChangeKind::Modify | ChangeKind::Copy { .. } => {
+                self.files
+                    .insert(change.target.clone(), change.after.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_staged_files_overlay_disk_contents() {
+        let dir = TempDir::new().unwrap();
+        let notes = dir.path().join("notes.md");
+        std::fs::write(&notes, "disk\n").unwrap();
+        let mut staged = StagedFiles::new();
+        assert_eq!(staged.read(&notes).as_deref(), Some("disk\n"));
+
+        staged.stage(
+            &FileChange::new("notes.md", ChangeKind::Modify, notes.clone())
+                .with_contents(Some("disk\n".to_string()), Some("staged\n".to_string())),
+        );
+        assert_eq!(staged.read(&notes).as_deref(), Some("staged\n"));
+
+        staged.stage(&FileChange::new(
+            "notes.md",
+            ChangeKind::Delete,
+            notes.clone(),
+        ));
+        assert!(!staged.exists(&notes));
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "disk\n");
+    }
+
+    #[test]
+    fn test_line_counts_for_created_file() {
+        let change = FileChange::new("a.txt", ChangeKind::Create, PathBuf::from("/p/a.txt"))
+            .with_contents(None, Some("one\ntwo\n".to_string()));
+        assert_eq!(change.line_counts(), (2, 0));
+        assert!(change.diff().contains("+ two"));
+    }
+}
@@ -0,0 +1,363 @@
+//! Session-scoped confirmation grants for `SafetyMode::ConfirmOnce`
+//!
+//! In ConfirmOnce mode the first action of each category needs confirmation.
+//! The tool answers that first call with a failed [`ToolResult`] stating the
+//! scope being granted; the model asks the user and calls the tool again with
+//! `"confirm": true`. The grant is then recorded in [`ConfirmationGrants`],
+//! which is shared by every tool of the chat session and survives agent
+//! rebuilds after `/mode` and `/model` switches.
+//!
+//! Grants are scoped: file writes and deletes by the top-level directory of
+//! the path, terminal commands by category only.
+//! Deletes and dangerous terminal commands can be configured to stay
+//! per-invocation, in which case a confirmation is never recorded.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Component, Path};
+use std::sync::{Arc, Mutex};
+
+use crate::chat_mode::SafetyMode;
+use crate::config::ChatConfig;
+use crate::tools::ToolResult;
+
+/// Metadata key carrying the scope a confirmation would grant
+pub const CONFIRMATION_SCOPE_METADATA: &str = "confirmation_scope";
+
+/// Kind of action that needs confirmation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActionCategory {
+    /// Creating, overwriting, editing, copying, or moving files
+    FileWrite,
+    /// Deleting files or directories
+    FileDelete,
+    /// Terminal commands the validator accepts without confirmation
+    TerminalSafe,
+    /// Terminal commands the validator flags for confirmation
+    TerminalDangerous,
+}
+
+impl ActionCategory {
+    /// Describes the actions a grant would cover
+    fn describe(&self, scope: &str) -> String {
+        match self {
+            Self::FileWrite => format!("all file writes {}", path_scope_phrase(scope)),
+            Self::FileDelete => format!("all deletes {}", path_scope_phrase(scope)),
+            Self::TerminalSafe => "all allowlisted terminal commands".to_string(),
+            Self::TerminalDangerous => "all terminal commands outside the allowlist".to_string(),
+        }
+    }
+
+    /// Describes a single action for per-invocation confirmations
+    fn describe_once(&self, target: &str) -> String {
+        match self {
+            Self::FileWrite => format!("writing {}", target),
+            Self::FileDelete => format!("deleting {}", target),
+            Self::TerminalSafe | Self::TerminalDangerous => format!("running `{}`", target),
+        }
+    }
+}
+
+impl fmt::Display for ActionCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::FileWrite => "file write",
+            Self::FileDelete => "file delete",
+            Self::TerminalSafe => "terminal command",
+            Self::TerminalDangerous => "dangerous terminal command",
+        };
+        f.write_str(name)
+    }
+}
+
+fn path_scope_phrase(scope: &str) -> String {
+    if scope.is_empty() {
+        "in the workspace root".to_string()
+    } else {
+        format!("under {}/", scope)
+    }
+}
+
+/// Returns the grant scope for a workspace path: its top-level directory
+///
+/// Files directly in the workspace root share the empty scope.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::tools::confirmation::path_scope;
+///
+/// assert_eq!(path_scope("src/tools/mod.rs"), "src");
+/// assert_eq!(path_scope("./README.md"), "");
+/// ```
+pub fn path_scope(path: &str) -> String {
+    let components: Vec<_> = Path::new(path)
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    if components.len() > 1 {
+        components[0].clone()
+    } else {
+        String::new()
+    }
+}
+
+/// Confirmations granted during a session
+///
+/// Cloning shares the underlying set, so every tool built for a session sees
+/// the same grants.
+#[derive(Debug, Clone, Default)]
+pub struct ConfirmationGrants {
+    granted: Arc<Mutex<HashSet<(ActionCategory, String)>>>,
+}
+
+impl ConfirmationGrants {
+    /// Creates an empty set of grants
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true when `category` has been granted for `scope`
+    pub fn is_granted(&self, category: ActionCategory, scope: &str) -> bool {
+        self.granted
+            .lock()
+            .map(|granted| granted.contains(&(category, scope.to_string())))
+            .unwrap_or(false)
+    }
+
+    /// Records a grant for `category` within `scope`
+    pub fn grant(&self, category: ActionCategory, scope: &str) {
+        if let Ok(mut granted) = self.granted.lock() {
+            granted.insert((category, scope.to_string()));
+        }
+    }
+
+    /// Returns the number of recorded grants
+    pub fn len(&self) -> usize {
+        self.granted
+            .lock()
+            .map(|granted| granted.len())
+            .unwrap_or(0)
+    }
+
+    /// Returns true when nothing has been granted
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Decides whether a tool call may proceed without further confirmation
+///
+/// Only [`SafetyMode::ConfirmOnce`] is handled here. The other modes keep
+/// their existing behavior, so [`ConfirmationPolicy::check`] lets every call
+/// through.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::chat_mode::SafetyMode;
+/// use xzatoma::tools::confirmation::{ActionCategory, ConfirmationPolicy};
+///
+/// let policy = ConfirmationPolicy::new(SafetyMode::ConfirmOnce);
+/// assert!(policy.check(ActionCategory::FileWrite, "src", "src/lib.rs", false).is_some());
+/// assert!(policy.check(ActionCategory::FileWrite, "src", "src/lib.rs", true).is_none());
+/// assert!(policy.check(ActionCategory::FileWrite, "src", "src/main.rs", false).is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConfirmationPolicy {
+    mode: SafetyMode,
+    grants: ConfirmationGrants,
+    per_invocation: HashSet<ActionCategory>,
+}
+
+impl ConfirmationPolicy {
+    /// Creates a policy for `mode` with its own empty grants
+    pub fn new(mode: SafetyMode) -> Self {
+        Self {
+            mode,
+            grants: ConfirmationGrants::new(),
+            per_invocation: HashSet::new(),
+        }
+    }
+
+    /// Shares `grants` with other tools of the same session
+    pub fn with_grants(mut self, grants: ConfirmationGrants) -> Self {
+        self.grants = grants;
+        self
+    }
+
+    /// Confirms every action of `category` instead of granting it once
+    pub fn confirm_each(mut self, category: ActionCategory) -> Self {
+        self.per_invocation.insert(category);
+        self
+    }
+
+    /// Applies the per-invocation settings from the chat configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Chat configuration with `confirm_each_delete` and
+    ///   `confirm_each_dangerous_command`
+    pub fn with_chat_config(mut self, config: &ChatConfig) -> Self {
+        if config.confirm_each_delete {
+            self = self.confirm_each(ActionCategory::FileDelete);
+        }
+        if config.confirm_each_dangerous_command {
+            self = self.confirm_each(ActionCategory::TerminalDangerous);
+        }
+        self
+    }
+
+    /// Returns the safety mode the policy applies
+    pub fn mode(&self) -> SafetyMode {
+        self.mode
+    }
+
+    /// Returns the grants recorded by this policy
+    pub fn grants(&self) -> &ConfirmationGrants {
+        &self.grants
+    }
+
+    /// Checks an action against the recorded grants
+    ///
+    /// # Arguments
+    ///
+    /// * `category` - Kind of action
+    /// * `scope` - Grant scope, such as a top-level directory or a domain
+    /// * `target` - The path, command, or URL, used in per-invocation prompts
+    /// * `confirmed` - Whether the call carried `"confirm": true`
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` when the call may proceed, or the result asking for
+    /// confirmation. A confirmed call records the grant unless `category` is
+    /// confirmed per invocation.
+    pub fn check(
+        &self,
+        category: ActionCategory,
+        scope: &str,
+        target: &str,
+        confirmed: bool,
+    ) -> Option<ToolResult> {
+        if self.mode != SafetyMode::ConfirmOnce {
+            return None;
+        }
+
+        let per_invocation = self.per_invocation.contains(&category);
+        if !per_invocation && self.grants.is_granted(category, scope) {
+            return None;
+        }
+        if confirmed {
+            if !per_invocation {
+                tracing::info!(%category, scope, "Granted confirmation for the session");
+                self.grants.grant(category, scope);
+            }
+            return None;
+        }
+
+        let question = if per_invocation {
+            format!(
+                "allow {}? This is asked every time.",
+                category.describe_once(target)
+            )
+        } else {
+            format!("allow {} for this session?", category.describe(scope))
+        };
+        Some(
+            ToolResult::error(format!(
+                "Confirmation required: {} Ask the user this question, then call the tool again with \"confirm\": true.",
+                question
+            ))
+            .with_metadata(CONFIRMATION_SCOPE_METADATA.to_string(), question),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn confirm_once() -> ConfirmationPolicy {
+        ConfirmationPolicy::new(SafetyMode::ConfirmOnce)
+    }
+
+    #[test]
+    fn test_other_modes_never_ask() {
+        for mode in [SafetyMode::AlwaysConfirm, SafetyMode::NeverConfirm] {
+            let policy = ConfirmationPolicy::new(mode);
+            assert!(policy
+                .check(ActionCategory::FileWrite, "src", "src/lib.rs", false)
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn test_first_action_states_scope() {
+        let result = confirm_once()
+            .check(ActionCategory::FileWrite, "src", "src/lib.rs", false)
+            .expect("first write needs confirmation");
+        assert!(!result.success);
+        assert_eq!(
+            result.metadata[CONFIRMATION_SCOPE_METADATA],
+            "allow all file writes under src/ for this session?"
+        );
+    }
+
+    #[test]
+    fn test_grant_persists_for_the_session() {
+        let policy = confirm_once();
+        assert!(policy
+            .check(ActionCategory::TerminalSafe, "", "cargo test", true)
+            .is_none());
+        assert!(policy
+            .check(ActionCategory::TerminalSafe, "", "cargo fmt", false)
+            .is_none());
+
+        // A second tool sharing the grants sees the same grant
+        let other = confirm_once().with_grants(policy.grants().clone());
+        assert!(other
+            .check(ActionCategory::TerminalSafe, "", "ls", false)
+            .is_none());
+    }
+
+    #[test]
+    fn test_categories_and_scopes_are_independent() {
+        let policy = confirm_once();
+        policy.check(ActionCategory::FileWrite, "src", "src/lib.rs", true);
+
+        assert!(policy
+            .check(ActionCategory::FileWrite, "docs", "docs/a.md", false)
+            .is_some());
+        assert!(policy
+            .check(ActionCategory::FileDelete, "src", "src/lib.rs", false)
+            .is_some());
+        assert!(policy
+            .check(ActionCategory::TerminalSafe, "", "ls", false)
+            .is_some());
+        assert_eq!(policy.grants().len(), 1);
+    }
+
+    #[test]
+    fn test_per_invocation_category_is_never_granted() {
+        let policy = confirm_once().confirm_each(ActionCategory::FileDelete);
+        assert!(policy
+            .check(ActionCategory::FileDelete, "", "old.txt", true)
+            .is_none());
+
+        let result = policy
+            .check(ActionCategory::FileDelete, "", "old.txt", false)
+            .expect("deletes are confirmed every time");
+        assert!(result.error.unwrap().contains("deleting old.txt"));
+        assert!(policy.grants().is_empty());
+    }
+
+    #[test]
+    fn test_path_scope_uses_top_level_directory() {
+        assert_eq!(path_scope("src/tools/mod.rs"), "src");
+        assert_eq!(path_scope("./src/lib.rs"), "src");
+        assert_eq!(path_scope("Cargo.toml"), "");
+    }
+}
@@ -3,11 +3,15 @@
 //! Supports recursive directory copying with overwrite control.
 
 use crate::error::Result;
+use crate::tools::audit_log::{self, AuditLog, AuditOperation};
+use crate::tools::change_set::{ChangeKind, FileChange, StagedFiles};
+use crate::tools::confirmation::{path_scope, ActionCategory, ConfirmationPolicy};
 use crate::tools::file_utils::PathValidator;
 use crate::tools::{parse_tool_args, ToolExecutor, ToolResult, TOOL_COPY_PATH};
 use async_trait::async_trait;
 use serde::Deserialize;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use walkdir::WalkDir;
 
 /// Copy path tool for copying files and directories
@@ -35,6 +39,8 @@ use walkdir::WalkDir;
 /// ```
 pub struct CopyPathTool {
     path_validator: PathValidator,
+    audit_log: Option<Arc<AuditLog>>,
+    confirmation: Option<ConfirmationPolicy>,
 }
 
 impl CopyPathTool {
@@ -46,9 +52,23 @@ impl CopyPathTool {
     pub fn new(working_dir: PathBuf) -> Self {
         Self {
             path_validator: PathValidator::new(working_dir),
+            audit_log: None,
+            confirmation: None,
         }
     }
 
+    /// Sets the audit log that records every copy
+    pub fn with_audit_log(mut self, audit_log: Option<Arc<AuditLog>>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// Sets the policy that asks for confirmation in ConfirmOnce mode
+    pub fn with_confirmation(mut self, confirmation: Option<ConfirmationPolicy>) -> Self {
+        self.confirmation = confirmation;
+        self
+    }
+
     /// Returns the working directory
     pub fn working_dir(&self) -> &Path {
         self.path_validator.working_dir()
@@ -61,10 +81,41 @@ struct CopyPathParams {
     destination_path: String,
     #[serde(default)]
     overwrite: bool,
+    /// Set after the user approves a confirmation request
+    #[serde(default)]
+    confirm: bool,
 }
 
 #[async_trait]
 impl ToolExecutor for CopyPathTool {
+    fn mutates(&self) -> bool {
+        true
+    }
+
+    fn preview_change(&self, args: &serde_json::Value, staged: &StagedFiles) -> Option<FileChange> {
+        let params: CopyPathParams = serde_json::from_value(args.clone()).ok()?;
+        let source = self.path_validator.validate(&params.source_path).ok()?;
+        let destination = self
+            .path_validator
+            .validate(&params.destination_path)
+            .ok()?;
+        if !staged.exists(&source) || (staged.exists(&destination) && !params.overwrite) {
+            return None;
+        }
+        let change = FileChange::new(
+            params.destination_path,
+            ChangeKind::Copy {
+                from: params.source_path,
+            },
+            destination.clone(),
+        )
+        .with_source(source.clone());
+        if staged.is_dir(&source) {
+            return Some(change.with_note("directory and all of its contents"));
+        }
+        Some(change.with_contents(staged.read(&destination), staged.read(&source)))
+    }
+
     fn tool_definition(&self) -> serde_json::Value {
         serde_json::json!({
             "name": TOOL_COPY_PATH,
@@ -84,6 +135,10 @@ impl ToolExecutor for CopyPathTool {
                         "type": "boolean",
                         "description": "Whether to overwrite existing destination (default: false)",
                         "default": false
+                    },
+                    "confirm": {
+                        "type": "boolean",
+                        "description": "Set to true after the user approves a confirmation request"
                     }
                 },
                 "required": ["source_path", "destination_path"]
@@ -109,6 +164,17 @@ impl ToolExecutor for CopyPathTool {
             }
         };
 
+        if let Some(refusal) = self.confirmation.as_ref().and_then(|policy| {
+            policy.check(
+                ActionCategory::FileWrite,
+                &path_scope(&params.destination_path),
+                &params.destination_path,
+                params.confirm,
+            )
+        }) {
+            return Ok(refusal);
+        }
+
         if !source.exists() {
             return Ok(ToolResult::error(format!(
                 "Source not found: {}",
@@ -123,17 +189,66 @@ impl ToolExecutor for CopyPathTool {
             )));
         }
 
+        // Captured before the copy replaces the destination. Strict mode
+        // records the entry before the copy, lenient mode once it succeeds
+        let audit = match &self.audit_log {
+            Some(log) => {
+                let before = audit_log::file_fingerprint(&destination).await;
+                let before_size = if destination.is_dir() {
+                    audit_log::tree_size(&destination)
+                } else {
+                    before.as_ref().map_or(0, |(size, _)| *size)
+                };
+                let after = audit_log::file_fingerprint(&source).await;
+                let after_size = if source.is_dir() {
+                    audit_log::tree_size(&source)
+                } else {
+                    after.as_ref().map_or(0, |(size, _)| *size)
+                };
+                let entry = log
+                    .entry(TOOL_COPY_PATH, AuditOperation::Copy, &destination)
+                    .with_source(&source)
+                    .with_byte_delta(after_size as i64 - before_size as i64)
+                    .with_hashes(before.map(|(_, hash)| hash), after.map(|(_, hash)| hash));
+                Some((log, entry))
+            }
+            None => None,
+        };
+
+        if let Some(aborted) = audit
+            .as_ref()
+            .and_then(|(log, entry)| log.record_intent(entry))
+        {
+            return Ok(aborted);
+        }
+
+        let result = self.copy(&source, &destination, &params).await?;
+        Ok(match audit {
+            Some((log, entry)) => log.record_outcome(&entry, result),
+            None => result,
+        })
+    }
+}
+
+impl CopyPathTool {
+    /// Copies `source` to `destination`, replacing it when `overwrite` is set
+    async fn copy(
+        &self,
+        source: &Path,
+        destination: &Path,
+        params: &CopyPathParams,
+    ) -> Result<ToolResult> {
         // Remove existing destination if overwrite is true
         if destination.exists() && params.overwrite {
             if destination.is_file() {
-                if let Err(e) = tokio::fs::remove_file(&destination).await {
+                if let Err(e) = tokio::fs::remove_file(destination).await {
                     return Ok(ToolResult::error(format!(
                         "Failed to remove existing file: {}",
                         e
                     )));
                 }
             } else if destination.is_dir() {
-                if let Err(e) = tokio::fs::remove_dir_all(&destination).await {
+                if let Err(e) = tokio::fs::remove_dir_all(destination).await {
                     return Ok(ToolResult::error(format!(
                         "Failed to remove existing directory: {}",
                         e
@@ -155,7 +270,7 @@ impl ToolExecutor for CopyPathTool {
         }
 
         if source.is_file() {
-            match tokio::fs::copy(&source, &destination).await {
+            match tokio::fs::copy(source, destination).await {
                 Ok(bytes) => Ok(ToolResult::success(format!(
                     "Copied {} bytes from {} to {}",
                     bytes, params.source_path, params.destination_path
@@ -163,7 +278,7 @@ impl ToolExecutor for CopyPathTool {
                 Err(e) => Ok(ToolResult::error(format!("Failed to copy file: {}", e))),
             }
         } else if source.is_dir() {
-            match self.copy_directory(&source, &destination).await {
+            match self.copy_directory(source, destination).await {
                 Ok(count) => Ok(ToolResult::success(format!(
                     "Copied directory with {} items from {} to {}",
                     count, params.source_path, params.destination_path
@@ -180,9 +295,7 @@ impl ToolExecutor for CopyPathTool {
             )))
         }
     }
-}
 
-impl CopyPathTool {
     /// Recursively copies a directory from source to destination
     async fn copy_directory(&self, source: &Path, destination: &Path) -> Result<usize> {
         let mut count = 0;
@@ -3,11 +3,15 @@
 //! Creates directories with automatic parent directory creation.
 
 use crate::error::Result;
+use crate::tools::audit_log::{AuditLog, AuditOperation};
+use crate::tools::change_set::{ChangeKind, FileChange, StagedFiles};
+use crate::tools::confirmation::{path_scope, ActionCategory, ConfirmationPolicy};
 use crate::tools::file_utils::PathValidator;
 use crate::tools::{parse_tool_args, ToolExecutor, ToolResult, TOOL_CREATE_DIRECTORY};
 use async_trait::async_trait;
 use serde::Deserialize;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// Create directory tool for creating directories
 ///
@@ -33,6 +37,8 @@ use std::path::{Path, PathBuf};
 /// ```
 pub struct CreateDirectoryTool {
     path_validator: PathValidator,
+    audit_log: Option<Arc<AuditLog>>,
+    confirmation: Option<ConfirmationPolicy>,
 }
 
 impl CreateDirectoryTool {
@@ -54,9 +60,23 @@ impl CreateDirectoryTool {
     pub fn new(working_dir: PathBuf) -> Self {
         Self {
             path_validator: PathValidator::new(working_dir),
+            audit_log: None,
+            confirmation: None,
         }
     }
 
+    /// Sets the audit log that records every directory it creates
+    pub fn with_audit_log(mut self, audit_log: Option<Arc<AuditLog>>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// Sets the policy that asks for confirmation in ConfirmOnce mode
+    pub fn with_confirmation(mut self, confirmation: Option<ConfirmationPolicy>) -> Self {
+        self.confirmation = confirmation;
+        self
+    }
+
     /// Returns the working directory
     pub fn working_dir(&self) -> &Path {
         self.path_validator.working_dir()
@@ -66,10 +86,30 @@ impl CreateDirectoryTool {
 #[derive(Debug, Deserialize)]
 struct CreateDirectoryParams {
     path: String,
+    /// Set after the user approves a confirmation request
+    #[serde(default)]
+    confirm: bool,
 }
 
 #[async_trait]
 impl ToolExecutor for CreateDirectoryTool {
+    fn mutates(&self) -> bool {
+        true
+    }
+
+    fn preview_change(&self, args: &serde_json::Value, staged: &StagedFiles) -> Option<FileChange> {
+        let params: CreateDirectoryParams = serde_json::from_value(args.clone()).ok()?;
+        let path = self.path_validator.validate(&params.path).ok()?;
+        if staged.exists(&path) {
+            return None;
+        }
+        Some(FileChange::new(
+            params.path,
+            ChangeKind::CreateDirectory,
+            path,
+        ))
+    }
+
     fn tool_definition(&self) -> serde_json::Value {
         serde_json::json!({
             "name": TOOL_CREATE_DIRECTORY,
@@ -80,6 +120,10 @@ impl ToolExecutor for CreateDirectoryTool {
                     "path": {
                         "type": "string",
                         "description": "Relative path to the directory to create"
+                    },
+                    "confirm": {
+                        "type": "boolean",
+                        "description": "Set to true after the user approves a confirmation request"
                     }
                 },
                 "required": ["path"]
@@ -95,6 +139,17 @@ impl ToolExecutor for CreateDirectoryTool {
             Err(e) => return Ok(ToolResult::error(format!("Invalid path: {}", e))),
         };
 
+        if let Some(refusal) = self.confirmation.as_ref().and_then(|policy| {
+            policy.check(
+                ActionCategory::FileWrite,
+                &path_scope(&params.path),
+                &params.path,
+                params.confirm,
+            )
+        }) {
+            return Ok(refusal);
+        }
+
         if dir_path.exists() {
             if dir_path.is_dir() {
                 return Ok(ToolResult::success(format!(
@@ -109,16 +164,30 @@ impl ToolExecutor for CreateDirectoryTool {
             }
         }
 
-        match tokio::fs::create_dir_all(&dir_path).await {
-            Ok(_) => Ok(ToolResult::success(format!(
-                "Created directory: {}",
-                params.path
-            ))),
-            Err(e) => Ok(ToolResult::error(format!(
-                "Failed to create directory: {}",
-                e
-            ))),
+        let audit = self.audit_log.as_ref().map(|log| {
+            let entry = log.entry(
+                TOOL_CREATE_DIRECTORY,
+                AuditOperation::CreateDirectory,
+                &dir_path,
+            );
+            (log, entry)
+        });
+        if let Some(aborted) = audit
+            .as_ref()
+            .and_then(|(log, entry)| log.record_intent(entry))
+        {
+            return Ok(aborted);
         }
+
+        let result = match tokio::fs::create_dir_all(&dir_path).await {
+            Ok(_) => ToolResult::success(format!("Created directory: {}", params.path)),
+            Err(e) => ToolResult::error(format!("Failed to create directory: {}", e)),
+        };
+
+        Ok(match audit {
+            Some((log, entry)) => log.record_outcome(&entry, result),
+            None => result,
+        })
     }
 }
 
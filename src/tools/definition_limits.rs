@@ -0,0 +1,639 @@
+//! Fitting tool definitions within provider limits
+//!
+//! Providers cap how many tools one request may carry and how long a tool
+//! description may be. With several MCP servers connected, the combined
+//! definitions can exceed those caps and the provider rejects every request
+//! with an opaque 400. [`fit_tool_definitions`] measures the definitions
+//! against [`ToolDefinitionLimits`] before each request and applies the
+//! configured [`ToolLimitStrategy`]:
+//!
+//! - `truncate` shortens long descriptions, then drops tools when the count
+//!   or total size is still over the limit
+//! - `drop` removes tools, lowest priority first; a tool whose description
+//!   is too long is always dropped
+//! - `fail` returns an error listing the offending tools and their sizes
+//!
+//! Built-in tools rank above every MCP tool. MCP tools, registered as
+//! `<server_id>__<tool>`, rank by the priority of their server in
+//! `mcp_priorities` (default 0).
+//!
+//! # Examples
+//!
+//! ```
+//! use serde_json::json;
+//! use xzatoma::config::{ToolDefinitionLimitsConfig, ToolLimitStrategy};
+//! use xzatoma::tools::definition_limits::{fit_tool_definitions, ToolDefinitionLimits};
+//!
+//! let definitions = vec![
+//!     json!({"name": "read_file", "description": "Read a file", "parameters": {}}),
+//!     json!({"name": "github__search", "description": "Search", "parameters": {}}),
+//! ];
+//! let limits = ToolDefinitionLimits {
+//!     max_tools: Some(1),
+//!     ..Default::default()
+//! };
+//! let config = ToolDefinitionLimitsConfig {
+//!     strategy: ToolLimitStrategy::Drop,
+//!     ..Default::default()
+//! };
+//!
+//! let (kept, report) = fit_tool_definitions(definitions, &limits, &config).unwrap();
+//! assert_eq!(kept.len(), 1);
+//! assert_eq!(kept[0]["name"], "read_file");
+//! assert_eq!(report.dropped[0].name, "github__search");
+//! ```
+
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::config::{ToolDefinitionLimitsConfig, ToolLimitStrategy};
+use crate::error::{Result, XzatomaError};
+use crate::providers::ProviderCapabilities;
+
+/// Separator between the server id and the tool name of an MCP tool
+const MCP_SEPARATOR: &str = "__";
+
+/// Suffix appended to truncated descriptions
+const TRUNCATION_MARKER: &str = "...";
+
+/// Most offenders named in a `fail` error
+const MAX_LISTED_OFFENDERS: usize = 10;
+
+/// Limits that the tool definitions of one request must respect
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolDefinitionLimits {
+    /// Most tool definitions per request
+    pub max_tools: Option<usize>,
+    /// Longest description, in characters
+    pub max_description_chars: Option<usize>,
+    /// Largest combined size of the serialized definitions, in bytes
+    pub max_total_bytes: Option<usize>,
+}
+
+impl ToolDefinitionLimits {
+    /// Combines the provider's limits with the configured overrides
+    ///
+    /// # Arguments
+    ///
+    /// * `capabilities` - Capabilities reported by the provider
+    /// * `config` - The `agent.tools.definition_limits` section
+    pub fn resolve(
+        capabilities: &ProviderCapabilities,
+        config: &ToolDefinitionLimitsConfig,
+    ) -> Self {
+        Self {
+            max_tools: config.max_tools.or(capabilities.max_tools),
+            max_description_chars: config
+                .max_description_chars
+                .or(capabilities.max_tool_description_chars),
+            max_total_bytes: config
+                .max_total_bytes
+                .or(capabilities.max_tool_definitions_bytes),
+        }
+    }
+}
+
+/// A description that was shortened to fit the limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TruncatedDescription {
+    /// Tool name
+    pub name: String,
+    /// Description length before truncation, in characters
+    pub original_chars: usize,
+    /// Description length after truncation, in characters
+    pub truncated_chars: usize,
+}
+
+/// A tool that was left out of the request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DroppedTool {
+    /// Tool name
+    pub name: String,
+    /// Serialized size of the definition, in bytes
+    pub bytes: usize,
+}
+
+/// What [`fit_tool_definitions`] changed to satisfy the limits
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolFitReport {
+    /// Strategy that was applied
+    pub strategy: ToolLimitStrategy,
+    /// Limits the definitions were measured against
+    pub limits: ToolDefinitionLimits,
+    /// Number of definitions before fitting
+    pub original_count: usize,
+    /// Combined size of the definitions before fitting, in bytes
+    pub original_bytes: usize,
+    /// Number of definitions sent
+    pub final_count: usize,
+    /// Combined size of the definitions sent, in bytes
+    pub final_bytes: usize,
+    /// Descriptions that were shortened, sorted by tool name
+    pub truncated: Vec<TruncatedDescription>,
+    /// Tools that were dropped, sorted by tool name
+    pub dropped: Vec<DroppedTool>,
+}
+
+impl ToolFitReport {
+    /// Returns true when any definition was truncated or dropped
+    pub fn is_trimmed(&self) -> bool {
+        !self.truncated.is_empty() || !self.dropped.is_empty()
+    }
+}
+
+impl fmt::Display for ToolFitReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "strategy {}: {} tools ({} bytes) reduced to {} tools ({} bytes)",
+            self.strategy,
+            self.original_count,
+            self.original_bytes,
+            self.final_count,
+            self.final_bytes
+        )?;
+        if !self.truncated.is_empty() {
+            let names: Vec<&str> = self.truncated.iter().map(|t| t.name.as_str()).collect();
+            write!(f, "; truncated descriptions: {}", names.join(", "))?;
+        }
+        if !self.dropped.is_empty() {
+            let names: Vec<&str> = self.dropped.iter().map(|d| d.name.as_str()).collect();
+            write!(f, "; dropped: {}", names.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// One definition with its measurements
+struct Measured {
+    name: String,
+    definition: Value,
+    bytes: usize,
+    description_chars: usize,
+}
+
+impl Measured {
+    fn new(definition: Value) -> Self {
+        let name = definition
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let mut measured = Self {
+            name,
+            definition,
+            bytes: 0,
+            description_chars: 0,
+        };
+        measured.measure();
+        measured
+    }
+
+    fn measure(&mut self) {
+        self.bytes = serde_json::to_vec(&self.definition)
+            .map(|bytes| bytes.len())
+            .unwrap_or_default();
+        self.description_chars = self
+            .definition
+            .get("description")
+            .and_then(Value::as_str)
+            .map(|description| description.chars().count())
+            .unwrap_or_default();
+    }
+
+    fn truncate_description(&mut self, max_chars: usize) {
+        let keep = max_chars.saturating_sub(TRUNCATION_MARKER.len());
+        if let Some(description) = self.definition.get("description").and_then(Value::as_str) {
+            let mut shortened: String = description.chars().take(keep).collect();
+            shortened.push_str(TRUNCATION_MARKER);
+            self.definition["description"] = Value::String(shortened);
+            self.measure();
+        }
+    }
+}
+
+/// Returns the drop rank of a tool; lower ranks are dropped first
+///
+/// Built-in tools rank above every MCP tool. An MCP tool takes the priority
+/// of the longest configured server id that prefixes its name.
+fn rank(name: &str, config: &ToolDefinitionLimitsConfig) -> (bool, i32) {
+    if !name.contains(MCP_SEPARATOR) {
+        return (true, 0);
+    }
+    let priority = config
+        .mcp_priorities
+        .iter()
+        .filter(|(server, _)| {
+            name.strip_prefix(server.as_str())
+                .is_some_and(|rest| rest.starts_with(MCP_SEPARATOR))
+        })
+        .max_by_key(|(server, _)| server.len())
+        .map(|(_, priority)| *priority)
+        .unwrap_or(0);
+    (false, priority)
+}
+
+fn total_bytes(tools: &[Measured]) -> usize {
+    tools.iter().map(|tool| tool.bytes).sum()
+}
+
+fn over_count_or_size(tools: &[Measured], limits: &ToolDefinitionLimits) -> bool {
+    limits.max_tools.is_some_and(|max| tools.len() > max)
+        || limits
+            .max_total_bytes
+            .is_some_and(|max| total_bytes(tools) > max)
+}
+
+fn describe_offenders(tools: &[Measured], limits: &ToolDefinitionLimits) -> String {
+    let mut problems = Vec::new();
+    if let Some(max) = limits.max_tools.filter(|max| tools.len() > *max) {
+        problems.push(format!("{} tools (limit {})", tools.len(), max));
+    }
+    let bytes = total_bytes(tools);
+    if let Some(max) = limits.max_total_bytes.filter(|max| bytes > *max) {
+        problems.push(format!("{} bytes of definitions (limit {})", bytes, max));
+    }
+    if let Some(max) = limits.max_description_chars {
+        let mut long: Vec<&Measured> = tools
+            .iter()
+            .filter(|tool| tool.description_chars > max)
+            .collect();
+        long.sort_by(|a, b| b.description_chars.cmp(&a.description_chars));
+        if !long.is_empty() {
+            let listed: Vec<String> = long
+                .iter()
+                .take(MAX_LISTED_OFFENDERS)
+                .map(|tool| format!("{} ({} chars)", tool.name, tool.description_chars))
+                .collect();
+            problems.push(format!(
+                "descriptions over {} chars: {}",
+                max,
+                listed.join(", ")
+            ));
+        }
+    }
+    if over_count_or_size(tools, limits) {
+        let mut largest: Vec<&Measured> = tools.iter().collect();
+        largest.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+        let listed: Vec<String> = largest
+            .iter()
+            .take(MAX_LISTED_OFFENDERS)
+            .map(|tool| format!("{} ({} bytes)", tool.name, tool.bytes))
+            .collect();
+        problems.push(format!("largest tools: {}", listed.join(", ")));
+    }
+    problems.join("; ")
+}
+
+/// Fits tool definitions within the provider's limits
+///
+/// Definitions that already fit are returned unchanged with an empty report.
+///
+/// # Arguments
+///
+/// * `definitions` - Tool definitions in the registry's JSON format
+/// * `limits` - Limits resolved with [`ToolDefinitionLimits::resolve`]
+/// * `config` - Strategy and MCP server priorities
+///
+/// # Returns
+///
+/// The definitions to send and a report of what was changed
+///
+/// # Errors
+///
+/// Returns [`XzatomaError::ToolDefinitionsExceedLimits`] listing the
+/// offending tools and their sizes when the strategy is `fail` and a limit
+/// is exceeded.
+pub fn fit_tool_definitions(
+    definitions: Vec<Value>,
+    limits: &ToolDefinitionLimits,
+    config: &ToolDefinitionLimitsConfig,
+) -> Result<(Vec<Value>, ToolFitReport)> {
+    let mut tools: Vec<Measured> = definitions.into_iter().map(Measured::new).collect();
+    let mut report = ToolFitReport {
+        strategy: config.strategy,
+        limits: *limits,
+        original_count: tools.len(),
+        original_bytes: total_bytes(&tools),
+        ..Default::default()
+    };
+
+    let has_long_descriptions = limits
+        .max_description_chars
+        .is_some_and(|max| tools.iter().any(|tool| tool.description_chars > max));
+    if !has_long_descriptions && !over_count_or_size(&tools, limits) {
+        report.final_count = report.original_count;
+        report.final_bytes = report.original_bytes;
+        let definitions = tools.into_iter().map(|tool| tool.definition).collect();
+        return Ok((definitions, report));
+    }
+
+    if config.strategy == ToolLimitStrategy::Fail {
+        return Err(XzatomaError::ToolDefinitionsExceedLimits(
+            describe_offenders(&tools, limits),
+        ));
+    }
+
+    if let Some(max) = limits.max_description_chars {
+        match config.strategy {
+            ToolLimitStrategy::Truncate => {
+                for tool in tools.iter_mut().filter(|tool| tool.description_chars > max) {
+                    let original_chars = tool.description_chars;
+                    tool.truncate_description(max);
+                    report.truncated.push(TruncatedDescription {
+                        name: tool.name.clone(),
+                        original_chars,
+                        truncated_chars: tool.description_chars,
+                    });
+                }
+            }
+            ToolLimitStrategy::Drop => {
+                let (long, kept): (Vec<Measured>, Vec<Measured>) = tools
+                    .into_iter()
+                    .partition(|tool| tool.description_chars > max);
+                report
+                    .dropped
+                    .extend(long.into_iter().map(|tool| DroppedTool {
+                        name: tool.name,
+                        bytes: tool.bytes,
+                    }));
+                tools = kept;
+            }
+            ToolLimitStrategy::Fail => {}
+        }
+    }
+
+    if over_count_or_size(&tools, limits) {
+        let mut order: Vec<usize> = (0..tools.len()).collect();
+        order.sort_by(|&a, &b| {
+            rank(&tools[a].name, config)
+                .cmp(&rank(&tools[b].name, config))
+                .then_with(|| tools[b].bytes.cmp(&tools[a].bytes))
+                .then_with(|| tools[a].name.cmp(&tools[b].name))
+        });
+
+        let mut dropped = vec![false; tools.len()];
+        let mut count = tools.len();
+        let mut bytes = total_bytes(&tools);
+        for index in order {
+            let fits = limits.max_tools.map_or(true, |max| count <= max)
+                && limits.max_total_bytes.map_or(true, |max| bytes <= max);
+            if fits {
+                break;
+            }
+            dropped[index] = true;
+            count -= 1;
+            bytes -= tools[index].bytes;
+        }
+
+        let mut kept = Vec::with_capacity(count);
+        for (tool, is_dropped) in tools.into_iter().zip(dropped) {
+            if is_dropped {
+                report.dropped.push(DroppedTool {
+                    name: tool.name,
+                    bytes: tool.bytes,
+                });
+            } else {
+                kept.push(tool);
+            }
+        }
+        tools = kept;
+    }
+
+    report.truncated.sort_by(|a, b| a.name.cmp(&b.name));
+    report.dropped.sort_by(|a, b| a.name.cmp(&b.name));
+    report.final_count = tools.len();
+    report.final_bytes = total_bytes(&tools);
+    let definitions = tools.into_iter().map(|tool| tool.definition).collect();
+    Ok((definitions, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tool(name: &str, description_chars: usize) -> Value {
+        json!({
+            "name": name,
+            "description": "d".repeat(description_chars),
+            "parameters": {"type": "object", "properties": {}},
+        })
+    }
+
+    fn names(definitions: &[Value]) -> Vec<String> {
+        let mut names: Vec<String> = definitions
+            .iter()
+            .map(|d| d["name"].as_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn config(strategy: ToolLimitStrategy) -> ToolDefinitionLimitsConfig {
+        ToolDefinitionLimitsConfig {
+            strategy,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_definitions_within_limits_are_unchanged() {
+        let definitions = vec![tool("read_file", 20), tool("github__search", 20)];
+        let limits = ToolDefinitionLimits {
+            max_tools: Some(2),
+            max_description_chars: Some(20),
+            max_total_bytes: Some(10_000),
+        };
+
+        let (kept, report) = fit_tool_definitions(
+            definitions.clone(),
+            &limits,
+            &config(ToolLimitStrategy::Fail),
+        )
+        .unwrap();
+        assert_eq!(kept, definitions);
+        assert!(!report.is_trimmed());
+        assert_eq!(report.final_count, 2);
+    }
+
+    #[test]
+    fn test_truncate_shortens_long_descriptions() {
+        let definitions = vec![tool("read_file", 50), tool("github__search", 5_000)];
+        let limits = ToolDefinitionLimits {
+            max_description_chars: Some(1024),
+            ..Default::default()
+        };
+
+        let (kept, report) =
+            fit_tool_definitions(definitions, &limits, &config(ToolLimitStrategy::Truncate))
+                .unwrap();
+        assert_eq!(kept.len(), 2);
+        let search = kept.iter().find(|d| d["name"] == "github__search").unwrap();
+        let description = search["description"].as_str().unwrap();
+        assert_eq!(description.chars().count(), 1024);
+        assert!(description.ends_with("..."));
+        assert_eq!(
+            report.truncated,
+            vec![TruncatedDescription {
+                name: "github__search".to_string(),
+                original_chars: 5_000,
+                truncated_chars: 1024,
+            }]
+        );
+        assert!(report.dropped.is_empty());
+        assert!(report.final_bytes < report.original_bytes);
+    }
+
+    #[test]
+    fn test_truncate_falls_back_to_dropping_when_count_exceeded() {
+        let definitions = vec![
+            tool("read_file", 10),
+            tool("github__search", 10),
+            tool("github__issues", 10),
+        ];
+        let limits = ToolDefinitionLimits {
+            max_tools: Some(2),
+            ..Default::default()
+        };
+
+        let (kept, report) =
+            fit_tool_definitions(definitions, &limits, &config(ToolLimitStrategy::Truncate))
+                .unwrap();
+        assert_eq!(kept.len(), 2);
+        assert!(names(&kept).contains(&"read_file".to_string()));
+        assert_eq!(report.dropped.len(), 1);
+    }
+
+    #[test]
+    fn test_drop_removes_lowest_priority_mcp_tools_first() {
+        let definitions = vec![
+            tool("read_file", 10),
+            tool("terminal", 10),
+            tool("github__search", 10),
+            tool("scratch__echo", 10),
+            tool("scratch__ping", 10),
+            tool("docs__lookup", 10),
+        ];
+        let limits = ToolDefinitionLimits {
+            max_tools: Some(4),
+            ..Default::default()
+        };
+        let mut cfg = config(ToolLimitStrategy::Drop);
+        cfg.mcp_priorities.insert("github".to_string(), 10);
+        cfg.mcp_priorities.insert("scratch".to_string(), -5);
+
+        let (kept, report) = fit_tool_definitions(definitions, &limits, &cfg).unwrap();
+        assert_eq!(
+            names(&kept),
+            vec!["docs__lookup", "github__search", "read_file", "terminal"]
+        );
+        let dropped: Vec<&str> = report.dropped.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(dropped, vec!["scratch__echo", "scratch__ping"]);
+        assert_eq!(report.original_count, 6);
+        assert_eq!(report.final_count, 4);
+    }
+
+    #[test]
+    fn test_drop_keeps_builtins_over_high_priority_mcp_tools() {
+        let definitions = vec![tool("read_file", 10), tool("github__search", 10)];
+        let limits = ToolDefinitionLimits {
+            max_tools: Some(1),
+            ..Default::default()
+        };
+        let mut cfg = config(ToolLimitStrategy::Drop);
+        cfg.mcp_priorities.insert("github".to_string(), i32::MAX);
+
+        let (kept, _) = fit_tool_definitions(definitions, &limits, &cfg).unwrap();
+        assert_eq!(names(&kept), vec!["read_file"]);
+    }
+
+    #[test]
+    fn test_drop_meets_total_size_limit() {
+        let definitions = vec![
+            tool("read_file", 10),
+            tool("big__one", 4_000),
+            tool("small__one", 10),
+        ];
+        let small = serde_json::to_vec(&tool("read_file", 10)).unwrap().len();
+        let limits = ToolDefinitionLimits {
+            max_total_bytes: Some(small * 2 + 10),
+            ..Default::default()
+        };
+
+        let (kept, report) =
+            fit_tool_definitions(definitions, &limits, &config(ToolLimitStrategy::Drop)).unwrap();
+        assert_eq!(names(&kept), vec!["read_file", "small__one"]);
+        assert_eq!(report.dropped[0].name, "big__one");
+        assert!(report.final_bytes <= small * 2 + 10);
+    }
+
+    #[test]
+    fn test_drop_removes_tools_with_long_descriptions() {
+        let definitions = vec![tool("read_file", 10), tool("github__search", 2_000)];
+        let limits = ToolDefinitionLimits {
+            max_description_chars: Some(1024),
+            ..Default::default()
+        };
+
+        let (kept, report) =
+            fit_tool_definitions(definitions, &limits, &config(ToolLimitStrategy::Drop)).unwrap();
+        assert_eq!(names(&kept), vec!["read_file"]);
+        assert_eq!(report.dropped[0].name, "github__search");
+        assert!(report.truncated.is_empty());
+    }
+
+    #[test]
+    fn test_fail_lists_offenders_and_sizes() {
+        let definitions = vec![
+            tool("read_file", 10),
+            tool("github__search", 3_000),
+            tool("github__issues", 10),
+        ];
+        let limits = ToolDefinitionLimits {
+            max_tools: Some(2),
+            max_description_chars: Some(1024),
+            ..Default::default()
+        };
+
+        let err = fit_tool_definitions(definitions, &limits, &config(ToolLimitStrategy::Fail))
+            .unwrap_err();
+        assert!(matches!(err, XzatomaError::ToolDefinitionsExceedLimits(_)));
+        let message = err.to_string();
+        assert!(message.contains("3 tools (limit 2)"));
+        assert!(message.contains("github__search (3000 chars)"));
+        assert!(message.contains("largest tools: github__search ("));
+    }
+
+    #[test]
+    fn test_resolve_prefers_configured_overrides() {
+        let capabilities = ProviderCapabilities {
+            max_tools: Some(128),
+            max_tool_description_chars: Some(1024),
+            ..Default::default()
+        };
+        let cfg = ToolDefinitionLimitsConfig {
+            max_tools: Some(40),
+            max_total_bytes: Some(65_536),
+            ..Default::default()
+        };
+
+        let limits = ToolDefinitionLimits::resolve(&capabilities, &cfg);
+        assert_eq!(limits.max_tools, Some(40));
+        assert_eq!(limits.max_description_chars, Some(1024));
+        assert_eq!(limits.max_total_bytes, Some(65_536));
+    }
+
+    #[test]
+    fn test_rank_uses_longest_matching_server_id() {
+        let mut cfg = config(ToolLimitStrategy::Drop);
+        cfg.mcp_priorities.insert("git".to_string(), 1);
+        cfg.mcp_priorities.insert("github".to_string(), 5);
+
+        assert_eq!(rank("github__search", &cfg), (false, 5));
+        assert_eq!(rank("git__log", &cfg), (false, 1));
+        assert_eq!(rank("other__tool", &cfg), (false, 0));
+        assert_eq!(rank("read_file", &cfg), (true, 0));
+    }
+}
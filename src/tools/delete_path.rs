@@ -3,11 +3,15 @@
 //! Provides a tool to delete files and directories with optional recursive deletion.
 
 use crate::error::{Result, XzatomaError};
+use crate::tools::audit_log::{self, AuditEntry, AuditLog, AuditOperation};
+use crate::tools::change_set::{ChangeKind, FileChange, StagedFiles};
+use crate::tools::confirmation::{path_scope, ActionCategory, ConfirmationPolicy};
 use crate::tools::parse_tool_args;
-use crate::tools::{file_utils, ToolExecutor, ToolResult};
+use crate::tools::{file_utils, ToolExecutor, ToolResult, TOOL_DELETE_PATH};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// Parameters for the delete_path tool
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +21,9 @@ struct DeletePathParams {
     /// Whether to recursively delete directories
     #[serde(default)]
     recursive: bool,
+    /// Set after the user approves a confirmation request
+    #[serde(default)]
+    confirm: bool,
 }
 
 /// Tool for deleting files and directories
@@ -40,6 +47,8 @@ struct DeletePathParams {
 /// ```
 pub struct DeletePathTool {
     path_validator: file_utils::PathValidator,
+    audit_log: Option<Arc<AuditLog>>,
+    confirmation: Option<ConfirmationPolicy>,
 }
 
 impl DeletePathTool {
@@ -64,15 +73,86 @@ impl DeletePathTool {
     pub fn new(working_dir: PathBuf) -> Self {
         Self {
             path_validator: file_utils::PathValidator::new(working_dir),
+            audit_log: None,
+            confirmation: None,
+        }
+    }
+
+    /// Sets the audit log that records every deletion
+    pub fn with_audit_log(mut self, audit_log: Option<Arc<AuditLog>>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// Sets the policy that asks for confirmation in ConfirmOnce mode
+    pub fn with_confirmation(mut self, confirmation: Option<ConfirmationPolicy>) -> Self {
+        self.confirmation = confirmation;
+        self
+    }
+
+    /// Builds the audit entry for a deletion while the path still exists
+    ///
+    /// Strict audit mode records the entry with [`Self::record_intent`]
+    /// before the deletion; lenient mode records it with
+    /// [`Self::record_delete`] once the deletion succeeds.
+    async fn delete_entry(&self, path: &Path) -> Option<AuditEntry> {
+        let log = self.audit_log.as_ref()?;
+        let entry = log.entry(TOOL_DELETE_PATH, AuditOperation::Delete, path);
+        Some(if path.is_dir() {
+            entry.with_byte_delta(-(audit_log::tree_size(path) as i64))
+        } else {
+            let before = audit_log::file_fingerprint(path).await;
+            let size = before.as_ref().map_or(0, |(size, _)| *size);
+            entry
+                .with_byte_delta(-(size as i64))
+                .with_hashes(before.map(|(_, hash)| hash), None)
+        })
+    }
+
+    /// Records a deletion before it happens in strict audit mode
+    ///
+    /// Returns the error result to return, without deleting, when the audit
+    /// log write fails.
+    fn record_intent(&self, entry: Option<&AuditEntry>) -> Option<ToolResult> {
+        self.audit_log.as_ref()?.record_intent(entry?)
+    }
+
+    /// Records a completed deletion in lenient audit mode
+    fn record_delete(&self, entry: Option<&AuditEntry>) {
+        if let (Some(log), Some(entry)) = (&self.audit_log, entry) {
+            log.record(entry);
         }
     }
 }
 
 #[async_trait::async_trait]
 impl ToolExecutor for DeletePathTool {
+    fn mutates(&self) -> bool {
+        true
+    }
+
+    fn preview_change(&self, args: &serde_json::Value, staged: &StagedFiles) -> Option<FileChange> {
+        let params: DeletePathParams = serde_json::from_value(args.clone()).ok()?;
+        let path = self.path_validator.validate(&params.path).ok()?;
+        if !staged.exists(&path) {
+            return None;
+        }
+        if staged.is_dir(&path) {
+            if !params.recursive {
+                return None;
+            }
+            return Some(
+                FileChange::new(params.path, ChangeKind::Delete, path)
+                    .with_note("directory and all of its contents"),
+            );
+        }
+        let before = staged.read(&path);
+        Some(FileChange::new(params.path, ChangeKind::Delete, path).with_contents(before, None))
+    }
+
     fn tool_definition(&self) -> serde_json::Value {
         json!({
-            "name": "delete_path",
+            "name": TOOL_DELETE_PATH,
             "description": "Delete a file or directory. For directories, use recursive=true to delete contents.",
             "parameters": {
                 "type": "object",
@@ -84,6 +164,10 @@ impl ToolExecutor for DeletePathTool {
                     "recursive": {
                         "type": "boolean",
                         "description": "Whether to recursively delete directories and their contents (default: false)"
+                    },
+                    "confirm": {
+                        "type": "boolean",
+                        "description": "Set to true after the user approves a confirmation request"
                     }
                 },
                 "required": ["path"]
@@ -105,6 +189,17 @@ impl ToolExecutor for DeletePathTool {
             )));
         }
 
+        if let Some(refusal) = self.confirmation.as_ref().and_then(|policy| {
+            policy.check(
+                ActionCategory::FileDelete,
+                &path_scope(&params.path),
+                &params.path,
+                params.confirm,
+            )
+        }) {
+            return Ok(refusal);
+        }
+
         // Handle directory deletion
         if path.is_dir() {
             if !params.recursive {
@@ -114,22 +209,36 @@ impl ToolExecutor for DeletePathTool {
                 )));
             }
 
+            let audit_entry = self.delete_entry(&path).await;
+            if let Some(aborted) = self.record_intent(audit_entry.as_ref()) {
+                return Ok(aborted);
+            }
+
             // Recursively remove directory
             tokio::fs::remove_dir_all(&path)
                 .await
                 .map_err(XzatomaError::Io)?;
 
+            self.record_delete(audit_entry.as_ref());
+
             return Ok(ToolResult::success(format!(
                 "Directory deleted successfully: {}",
                 params.path
             )));
         }
 
+        let audit_entry = self.delete_entry(&path).await;
+        if let Some(aborted) = self.record_intent(audit_entry.as_ref()) {
+            return Ok(aborted);
+        }
+
         // Delete file
         tokio::fs::remove_file(&path)
             .await
             .map_err(XzatomaError::Io)?;
 
+        self.record_delete(audit_entry.as_ref());
+
         Ok(ToolResult::success(format!(
             "File deleted successfully: {}",
             params.path
@@ -206,4 +315,34 @@ mod tests {
         assert!(!result.success);
         assert!(result.error.is_some());
     }
+
+    #[tokio::test]
+    async fn test_execute_records_delete_in_audit_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("old.txt");
+        fs::write(&test_file, "stale").unwrap();
+        fs::create_dir_all(temp_dir.path().join("build/out")).unwrap();
+        fs::write(temp_dir.path().join("build/out/a.bin"), "1234").unwrap();
+
+        let audit_path = temp_dir.path().join("audit.jsonl");
+        let tool = DeletePathTool::new(temp_dir.path().to_path_buf())
+            .with_audit_log(Some(Arc::new(AuditLog::new(&audit_path, "s"))));
+        tool.execute(json!({"path": "old.txt"})).await.unwrap();
+        tool.execute(json!({"path": "build", "recursive": true}))
+            .await
+            .unwrap();
+
+        let entries = AuditLog::read_entries(&audit_path, None).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].operation, AuditOperation::Delete);
+        assert_eq!(entries[0].path, test_file);
+        assert_eq!(entries[0].byte_delta, -5);
+        assert_eq!(
+            entries[0].hash_before.as_deref(),
+            Some(audit_log::hash_bytes(b"stale").as_str())
+        );
+        assert_eq!(entries[0].hash_after, None);
+        assert_eq!(entries[1].byte_delta, -4);
+        assert_eq!(entries[1].hash_before, None);
+    }
 }
@@ -15,11 +15,15 @@ delegating to `file_utils::PathValidator`.
 */
 
 use crate::error::{Result, XzatomaError};
-use crate::tools::{file_utils, parse_tool_args, ToolExecutor, ToolResult};
+use crate::tools::audit_log::{self, AuditEntry, AuditLog, AuditOperation};
+use crate::tools::change_set::{ChangeKind, FileChange, StagedFiles};
+use crate::tools::confirmation::{path_scope, ActionCategory, ConfirmationPolicy};
+use crate::tools::{file_utils, parse_tool_args, ToolExecutor, ToolResult, TOOL_EDIT_FILE};
 use async_trait::async_trait;
 use metrics::increment_counter;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::sync::Arc;
 
 use tokio::fs;
 
@@ -52,6 +56,9 @@ struct EditFileParams {
     /// Optional: the snippet of old text to find and replace (REQUIRED for edit mode)
     #[serde(skip_serializing_if = "Option::is_none")]
     old_text: Option<String>,
+    /// Set after the user approves a confirmation request
+    #[serde(default)]
+    confirm: bool,
 }
 
 /// Tool for intelligent editing of files
@@ -61,6 +68,8 @@ struct EditFileParams {
 pub struct EditFileTool {
     path_validator: file_utils::PathValidator,
     max_file_size: u64,
+    audit_log: Option<Arc<AuditLog>>,
+    confirmation: Option<ConfirmationPolicy>,
 }
 
 impl EditFileTool {
@@ -74,6 +83,61 @@ impl EditFileTool {
         Self {
             path_validator: file_utils::PathValidator::new(working_dir),
             max_file_size,
+            audit_log: None,
+            confirmation: None,
+        }
+    }
+
+    /// Set the audit log that records every write
+    pub fn with_audit_log(mut self, audit_log: Option<Arc<AuditLog>>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// Sets the policy that asks for confirmation in ConfirmOnce mode
+    pub fn with_confirmation(mut self, confirmation: Option<ConfirmationPolicy>) -> Self {
+        self.confirmation = confirmation;
+        self
+    }
+
+    /// Build the audit entry for a write, or `None` without an audit log
+    ///
+    /// `old` is `None` when the file is created.
+    fn audit_entry(
+        &self,
+        path: &std::path::Path,
+        old: Option<&str>,
+        new: &str,
+    ) -> Option<AuditEntry> {
+        let log = self.audit_log.as_ref()?;
+        let operation = if old.is_some() {
+            AuditOperation::Modify
+        } else {
+            AuditOperation::Create
+        };
+        let old_len = old.map_or(0, str::len);
+        Some(
+            log.entry(TOOL_EDIT_FILE, operation, path)
+                .with_byte_delta(new.len() as i64 - old_len as i64)
+                .with_hashes(
+                    old.map(|old| audit_log::hash_bytes(old.as_bytes())),
+                    Some(audit_log::hash_bytes(new.as_bytes())),
+                ),
+        )
+    }
+
+    /// Record a write before it happens in strict audit mode
+    ///
+    /// Returns the error result to return, without writing, when the audit
+    /// log write fails.
+    fn audit_intent(&self, entry: Option<&AuditEntry>) -> Option<ToolResult> {
+        self.audit_log.as_ref()?.record_intent(entry?)
+    }
+
+    /// Record a completed write in lenient audit mode
+    fn audit_record(&self, entry: Option<&AuditEntry>) {
+        if let (Some(log), Some(entry)) = (&self.audit_log, entry) {
+            log.record(entry);
         }
     }
 
@@ -103,9 +167,52 @@ pub static TEST_OVERWRITE_NO_FILE: AtomicU64 = AtomicU64::new(0);
 
 #[async_trait]
 impl ToolExecutor for EditFileTool {
+    fn mutates(&self) -> bool {
+        true
+    }
+
+    fn preview_change(&self, args: &serde_json::Value, staged: &StagedFiles) -> Option<FileChange> {
+        let params: EditFileParams = serde_json::from_value(args.clone()).ok()?;
+        let path = self.path_validator.validate(&params.path).ok()?;
+        if staged.is_dir(&path) {
+            return None;
+        }
+        let before = staged.read(&path);
+        let after = match params.mode {
+            EditMode::Create if staged.exists(&path) => return None,
+            EditMode::Create => params.content,
+            EditMode::Overwrite => {
+                before.as_ref()?;
+                params.content
+            }
+            EditMode::Append => {
+                let old = before.as_ref()?;
+                let separator = if old.ends_with('\n') { "" } else { "\n" };
+                format!("{}{}{}", old, separator, params.content)
+            }
+            EditMode::Edit => {
+                let old = before.as_ref()?;
+                let old_text = params.old_text.as_deref()?;
+                if old.matches(old_text).count() != 1 {
+                    return None;
+                }
+                Self::replace_first(old, old_text, &params.content)
+            }
+        };
+        if after.len() as u64 > self.max_file_size {
+            return None;
+        }
+        let kind = if before.is_some() {
+            ChangeKind::Modify
+        } else {
+            ChangeKind::Create
+        };
+        Some(FileChange::new(params.path, kind, path).with_contents(before, Some(after)))
+    }
+
     fn tool_definition(&self) -> serde_json::Value {
         json!({
-            "name": "edit_file",
+            "name": TOOL_EDIT_FILE,
             "description": "Edit a file with four modes: create, edit, overwrite, append. \
                             IMPORTANT: Strict mode is enabled — 'edit' now REQUIRES 'old_text' and \
                             the tool will reject edits without it (fallback behavior removed). \
@@ -130,6 +237,10 @@ impl ToolExecutor for EditFileTool {
                     "old_text": {
                         "type": "string",
                         "description": "Snippet of old text to be replaced. REQUIRED when mode == 'edit' (strict mode; no fallback)"
+                    },
+                    "confirm": {
+                        "type": "boolean",
+                        "description": "Set to true after the user approves a confirmation request"
                     }
                 },
                 "required": ["path", "mode", "content"],
@@ -158,6 +269,17 @@ impl ToolExecutor for EditFileTool {
         // Validate path (will return error on traversal/absolute paths)
         let full_path = self.path_validator.validate(&params.path)?;
 
+        if let Some(refusal) = self.confirmation.as_ref().and_then(|policy| {
+            policy.check(
+                ActionCategory::FileWrite,
+                &path_scope(&params.path),
+                &params.path,
+                params.confirm,
+            )
+        }) {
+            return Ok(refusal);
+        }
+
         match params.mode {
             EditMode::Create => {
                 // Must not already exist
@@ -168,13 +290,17 @@ impl ToolExecutor for EditFileTool {
                     )));
                 }
 
+                let audit = self.audit_entry(&full_path, None, &params.content);
+                if let Some(aborted) = self.audit_intent(audit.as_ref()) {
+                    return Ok(aborted);
+                }
+
                 // Ensure parents exist (creates as necessary)
                 file_utils::ensure_parent_dirs(&full_path).await?;
 
                 // Write new file
-                fs::write(&full_path, &params.content)
-                    .await
-                    .map_err(XzatomaError::Io)?;
+                file_utils::write_atomic(&full_path, params.content.as_bytes()).await?;
+                self.audit_record(audit.as_ref());
 
                 // Generate diff against empty original
                 let diff = crate::tools::generate_diff("", &params.content)?;
@@ -228,9 +354,13 @@ impl ToolExecutor for EditFileTool {
                     )));
                 }
 
-                fs::write(&full_path, &params.content)
-                    .await
-                    .map_err(XzatomaError::Io)?;
+                let audit = self.audit_entry(&full_path, Some(&old), &params.content);
+                if let Some(aborted) = self.audit_intent(audit.as_ref()) {
+                    return Ok(aborted);
+                }
+
+                file_utils::write_atomic(&full_path, params.content.as_bytes()).await?;
+                self.audit_record(audit.as_ref());
 
                 let diff = crate::tools::generate_diff(&old, &params.content)?;
                 Ok(ToolResult::success(format!(
@@ -286,9 +416,13 @@ impl ToolExecutor for EditFileTool {
                     )));
                 }
 
-                fs::write(&full_path, &new_content)
-                    .await
-                    .map_err(XzatomaError::Io)?;
+                let audit = self.audit_entry(&full_path, Some(&old), &new_content);
+                if let Some(aborted) = self.audit_intent(audit.as_ref()) {
+                    return Ok(aborted);
+                }
+
+                file_utils::write_atomic(&full_path, new_content.as_bytes()).await?;
+                self.audit_record(audit.as_ref());
 
                 let diff = crate::tools::generate_diff(&old, &new_content)?;
                 Ok(ToolResult::success(format!(
@@ -446,10 +580,14 @@ impl ToolExecutor for EditFileTool {
                     ));
                 }
 
+                let audit = self.audit_entry(&full_path, Some(&old), &new_content);
+                if let Some(aborted) = self.audit_intent(audit.as_ref()) {
+                    return Ok(aborted);
+                }
+
                 // Write new contents
-                fs::write(&full_path, &new_content)
-                    .await
-                    .map_err(XzatomaError::Io)?;
+                file_utils::write_atomic(&full_path, new_content.as_bytes()).await?;
+                self.audit_record(audit.as_ref());
 
                 let diff = crate::tools::generate_diff(&old, &new_content)?;
                 Ok(ToolResult::success(format!(
@@ -955,4 +1093,40 @@ mod tests {
 
         assert!(TEST_OVERWRITE_NO_FILE.load(Ordering::SeqCst) >= 1);
     }
+
+    #[test]
+    fn test_preview_change_applies_on_top_of_staged_edits() {
+        let td = TempDir::new().unwrap();
+        fs::write(td.path().join("lib.rs"), "one\ntwo\n").unwrap();
+        let tool = EditFileTool::new(td.path().to_path_buf(), 1024 * 1024);
+        let mut staged = StagedFiles::new();
+
+        let first = tool
+            .preview_change(
+                &json!({"path": "lib.rs", "mode": "edit", "old_text": "one", "content": "1"}),
+                &staged,
+            )
+            .unwrap();
+        staged.stage(&first);
+        let second = tool
+            .preview_change(
+                &json!({"path": "lib.rs", "mode": "edit", "old_text": "1\ntwo", "content": "1\n2"}),
+                &staged,
+            )
+            .unwrap();
+
+        assert_eq!(second.kind, ChangeKind::Modify);
+        assert_eq!(second.before.as_deref(), Some("1\ntwo\n"));
+        assert_eq!(second.after.as_deref(), Some("1\n2\n"));
+        assert!(tool
+            .preview_change(
+                &json!({"path": "lib.rs", "mode": "edit", "old_text": "missing", "content": "x"}),
+                &staged,
+            )
+            .is_none());
+        assert_eq!(
+            fs::read_to_string(td.path().join("lib.rs")).unwrap(),
+            "one\ntwo\n"
+        );
+    }
 }
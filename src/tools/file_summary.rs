@@ -0,0 +1,411 @@
+//! Summaries for binary and oversized files
+//!
+//! File tools should not fail outright when the model points them at a PNG
+//! or a multi-megabyte log. This module sniffs a bounded sample of a file to
+//! decide whether it is binary, identifies common formats from their magic
+//! bytes, renders an optional hexdump preview, and counts lines by streaming
+//! so that oversized text files can be described without loading them.
+
+use crate::format::{format_bytes, format_count};
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+/// Number of leading bytes sampled when deciding whether a file is binary
+pub const BINARY_SAMPLE_SIZE: usize = 8 * 1024;
+
+/// Default number of bytes shown in a hexdump preview
+pub const DEFAULT_HEXDUMP_BYTES: usize = 256;
+
+/// Upper bound on the number of bytes shown in a hexdump preview
+pub const MAX_HEXDUMP_BYTES: usize = 4 * 1024;
+
+/// Fraction of invalid UTF-8 bytes above which a sample is treated as binary
+const NON_UTF8_RATIO: f64 = 0.1;
+
+/// Magic byte signatures checked at offset 0, most specific first
+const MAGIC_TABLE: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "PNG image"),
+    (b"\xff\xd8\xff", "JPEG image"),
+    (b"GIF87a", "GIF image"),
+    (b"GIF89a", "GIF image"),
+    (b"%PDF-", "PDF document"),
+    (b"SQLite format 3\0", "SQLite database"),
+    (b"PK\x03\x04", "ZIP archive"),
+    (b"PK\x05\x06", "ZIP archive (empty)"),
+    (b"\x1f\x8b", "gzip compressed data"),
+    (b"BZh", "bzip2 compressed data"),
+    (b"\xfd7zXZ\0", "xz compressed data"),
+    (b"7z\xbc\xaf\x27\x1c", "7-Zip archive"),
+    (b"\x28\xb5\x2f\xfd", "Zstandard compressed data"),
+    (b"\x7fELF", "ELF executable"),
+    (b"\xcf\xfa\xed\xfe", "Mach-O executable (64-bit)"),
+    (b"\xce\xfa\xed\xfe", "Mach-O executable (32-bit)"),
+    (b"\xca\xfe\xba\xbe", "Mach-O universal binary or Java class"),
+    (b"MZ", "Windows PE executable"),
+    (b"\0asm", "WebAssembly module"),
+    (b"II*\0", "TIFF image"),
+    (b"MM\0*", "TIFF image"),
+    (b"\0\0\x01\0", "ICO image"),
+    (b"OggS", "Ogg media"),
+    (b"fLaC", "FLAC audio"),
+    (b"ID3", "MP3 audio"),
+    (b"\x1a\x45\xdf\xa3", "Matroska/WebM video"),
+];
+
+/// Returns true when a sample of a file looks like binary content
+///
+/// A sample is binary when it contains a null byte or when more than 10% of
+/// its bytes are not valid UTF-8. A multi-byte character cut off at the end
+/// of the sample is not counted as invalid.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::tools::file_summary::looks_binary;
+///
+/// assert!(looks_binary(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+/// assert!(!looks_binary("plain text, café".as_bytes()));
+/// ```
+pub fn looks_binary(sample: &[u8]) -> bool {
+    if sample.contains(&0) {
+        return true;
+    }
+    if sample.is_empty() {
+        return false;
+    }
+
+    let mut invalid = 0usize;
+    let mut rest = sample;
+    while !rest.is_empty() {
+        match std::str::from_utf8(rest) {
+            Ok(_) => break,
+            Err(e) => match e.error_len() {
+                Some(len) => {
+                    invalid += len;
+                    rest = &rest[e.valid_up_to() + len..];
+                }
+                // Truncated character at the end of the sample
+                None => break,
+            },
+        }
+    }
+
+    invalid as f64 / sample.len() as f64 > NON_UTF8_RATIO
+}
+
+/// Identifies a file format from its leading bytes
+///
+/// Uses a small built-in table of common signatures. Returns `None` for
+/// unrecognized content.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::tools::file_summary::detect_magic;
+///
+/// assert_eq!(detect_magic(b"\x89PNG\r\n\x1a\n...."), Some("PNG image"));
+/// assert_eq!(detect_magic(b"hello"), None);
+/// ```
+pub fn detect_magic(sample: &[u8]) -> Option<&'static str> {
+    if let Some((_, name)) = MAGIC_TABLE
+        .iter()
+        .find(|(magic, _)| sample.starts_with(magic))
+    {
+        return Some(name);
+    }
+    if sample.len() >= 12 && &sample[0..4] == b"RIFF" {
+        return match &sample[8..12] {
+            b"WEBP" => Some("WebP image"),
+            b"WAVE" => Some("WAV audio"),
+            b"AVI " => Some("AVI video"),
+            _ => Some("RIFF container"),
+        };
+    }
+    if sample.len() >= 12 && &sample[4..8] == b"ftyp" {
+        return Some("ISO media (MP4/MOV/HEIF)");
+    }
+    if sample.len() >= 262 && &sample[257..262] == b"ustar" {
+        return Some("tar archive");
+    }
+    if sample.starts_with(b"BM") && sample.len() >= 14 {
+        return Some("BMP image");
+    }
+    None
+}
+
+/// Renders bytes as a hexdump with offsets and an ASCII column
+///
+/// Each line shows 16 bytes in the familiar `hexdump -C` layout.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::tools::file_summary::hexdump;
+///
+/// let dump = hexdump(b"PNG\x00");
+/// assert_eq!(
+///     dump,
+///     "00000000  50 4e 47 00                                       |PNG.|"
+/// );
+/// ```
+pub fn hexdump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let mut hex = String::with_capacity(49);
+            for (i, byte) in chunk.iter().enumerate() {
+                if i == 8 {
+                    hex.push(' ');
+                }
+                hex.push_str(&format!("{:02x} ", byte));
+            }
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:08x}  {:<50}|{}|", row * 16, hex, ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Reads at most `limit` bytes from the start of a file
+///
+/// # Errors
+///
+/// Returns an I/O error if the file cannot be opened or read.
+pub async fn read_sample(path: &Path, limit: usize) -> std::io::Result<Vec<u8>> {
+    let file = tokio::fs::File::open(path).await?;
+    let mut sample = Vec::with_capacity(limit);
+    file.take(limit as u64).read_to_end(&mut sample).await?;
+    Ok(sample)
+}
+
+/// Counts the lines in a file without loading it into memory
+///
+/// Matches the semantics of `str::lines`: a trailing newline does not start
+/// an extra line, and a final line without a newline is counted.
+///
+/// # Errors
+///
+/// Returns an I/O error if the file cannot be opened or read.
+pub async fn count_lines(path: &Path) -> std::io::Result<usize> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut lines = 0usize;
+    let mut last_byte = None;
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        lines += buffer[..read].iter().filter(|&&b| b == b'\n').count();
+        last_byte = Some(buffer[read - 1]);
+    }
+    if last_byte.is_some_and(|b| b != b'\n') {
+        lines += 1;
+    }
+    Ok(lines)
+}
+
+/// Describes a binary file in place of its contents
+///
+/// # Arguments
+///
+/// * `display_path` - Path as the user or model wrote it
+/// * `size` - File size in bytes
+/// * `sample` - Leading bytes of the file
+/// * `preview_bytes` - Number of bytes to hexdump, or `None` for no preview
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::tools::file_summary::binary_summary;
+///
+/// let summary = binary_summary("logo.png", 68, b"\x89PNG\r\n\x1a\n", None);
+/// assert!(summary.contains("Binary file: logo.png"));
+/// assert!(summary.contains("Detected type: PNG image"));
+/// ```
+pub fn binary_summary(
+    display_path: &str,
+    size: u64,
+    sample: &[u8],
+    preview_bytes: Option<usize>,
+) -> String {
+    let mut summary = format!(
+        "Binary file: {}\nSize: {}\nDetected type: {}",
+        display_path,
+        format_bytes(size),
+        detect_magic(sample).unwrap_or("unknown binary data")
+    );
+    match preview_bytes {
+        Some(limit) => {
+            let shown = &sample[..sample.len().min(limit.min(MAX_HEXDUMP_BYTES))];
+            summary.push_str(&format!(
+                "\n\nFirst {} bytes:\n{}",
+                shown.len(),
+                hexdump(shown)
+            ));
+        }
+        None => summary.push_str(
+            "\n\nContents were not included. Pass allow_binary_preview: true to see a hexdump of the first bytes.",
+        ),
+    }
+    summary
+}
+
+/// Describes a text file that is too large to read in full
+///
+/// # Arguments
+///
+/// * `display_path` - Path as the user or model wrote it
+/// * `size` - File size in bytes
+/// * `line_count` - Number of lines in the file
+/// * `max_size` - Read limit that the file exceeds
+pub fn large_text_summary(
+    display_path: &str,
+    size: u64,
+    line_count: usize,
+    max_size: u64,
+) -> String {
+    format!(
+        "Text file too large to read in full: {}\nSize: {} (limit {})\nLines: {}\n\nRead part of the file with start_line/end_line, head, or tail, e.g. {{\"path\": \"{}\", \"tail\": 200}}.",
+        display_path,
+        format_bytes(size),
+        format_bytes(max_size),
+        format_count(line_count as u64),
+        display_path
+    )
+}
+
+/// Summarizes a file when it is binary
+///
+/// Samples the start of the file and returns `Ok(None)` when it looks like
+/// text.
+///
+/// # Errors
+///
+/// Returns an I/O error if the file cannot be read.
+pub async fn summarize_if_binary(
+    path: &Path,
+    display_path: &str,
+    preview_bytes: Option<usize>,
+) -> std::io::Result<Option<String>> {
+    let sample = read_sample(path, BINARY_SAMPLE_SIZE).await?;
+    if !looks_binary(&sample) {
+        return Ok(None);
+    }
+    let size = tokio::fs::metadata(path).await?.len();
+    Ok(Some(binary_summary(
+        display_path,
+        size,
+        &sample,
+        preview_bytes,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// A complete 1x1 transparent PNG
+    const PNG_FIXTURE: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f,
+        0x15, 0xc4, 0x89, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00,
+        0x01, 0x00, 0x00, 0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49,
+        0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    #[test]
+    fn test_looks_binary_detects_null_bytes_and_invalid_utf8() {
+        assert!(looks_binary(PNG_FIXTURE));
+        assert!(looks_binary(&[0xff, 0xfe, 0xfd, b'a', 0x80, 0x81]));
+        assert!(!looks_binary(b""));
+        assert!(!looks_binary("héllo wörld".as_bytes()));
+    }
+
+    #[test]
+    fn test_looks_binary_ignores_truncated_trailing_character() {
+        let mut sample = "text ".repeat(10).into_bytes();
+        sample.extend_from_slice(&"é".as_bytes()[..1]);
+        assert!(!looks_binary(&sample));
+    }
+
+    #[test]
+    fn test_detect_magic_recognizes_common_formats() {
+        assert_eq!(detect_magic(PNG_FIXTURE), Some("PNG image"));
+        assert_eq!(detect_magic(b"%PDF-1.7\n"), Some("PDF document"));
+        assert_eq!(detect_magic(b"\x7fELF\x02\x01"), Some("ELF executable"));
+        assert_eq!(detect_magic(b"RIFF\0\0\0\0WEBPVP8 "), Some("WebP image"));
+        assert_eq!(detect_magic(b"plain text"), None);
+    }
+
+    #[test]
+    fn test_hexdump_formats_offsets_and_ascii() {
+        let dump = hexdump(&PNG_FIXTURE[..20]);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "00000000  89 50 4e 47 0d 0a 1a 0a  00 00 00 0d 49 48 44 52  |.PNG........IHDR|"
+        );
+        assert!(lines[1].starts_with("00000010  00 00 00 01 "));
+        assert!(lines[1].ends_with("|....|"));
+    }
+
+    #[test]
+    fn test_binary_summary_with_preview_is_bounded() {
+        let summary = binary_summary("pixel.png", 67, PNG_FIXTURE, Some(16));
+        assert!(summary.contains("Detected type: PNG image"));
+        assert!(summary.contains("First 16 bytes:"));
+        assert!(!summary.contains("00000010"));
+
+        let summary = binary_summary("pixel.png", 67, PNG_FIXTURE, None);
+        assert!(summary.contains("allow_binary_preview"));
+        assert!(!summary.contains("00000000"));
+    }
+
+    #[tokio::test]
+    async fn test_summarize_if_binary_with_png_fixture() {
+        let temp_dir = TempDir::new().unwrap();
+        let png = temp_dir.path().join("pixel.png");
+        let text = temp_dir.path().join("notes.txt");
+        fs::write(&png, PNG_FIXTURE).unwrap();
+        fs::write(&text, "just text\n").unwrap();
+
+        let summary = summarize_if_binary(&png, "pixel.png", None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(summary.contains("Size: 67 B"));
+        assert!(summary.contains("PNG image"));
+        assert!(summarize_if_binary(&text, "notes.txt", None)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_count_lines_streams_large_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("big.log");
+        let content: String = (1..=200_000).map(|i| format!("entry {}\n", i)).collect();
+        fs::write(&path, &content).unwrap();
+        assert_eq!(count_lines(&path).await.unwrap(), 200_000);
+
+        fs::write(&path, "a\nb").unwrap();
+        assert_eq!(count_lines(&path).await.unwrap(), 2);
+        fs::write(&path, "").unwrap();
+        assert_eq!(count_lines(&path).await.unwrap(), 0);
+    }
+}
@@ -4,6 +4,7 @@
 //! - Path validation against security constraints
 //! - Parent directory creation
 //! - File size checking
+//! - Atomic file writes
 
 use std::path::{Component, Path, PathBuf};
 use thiserror::Error;
@@ -262,6 +263,57 @@ pub async fn check_file_size(path: &Path, max_size: u64) -> Result<u64, FileUtil
     Ok(file_size)
 }
 
+/// Writes `contents` to `path` so readers see either the old or the new file
+///
+/// The contents go to a temporary file next to `path`, which is then renamed
+/// over it. An existing file keeps its permissions. Parent directories must
+/// already exist.
+///
+/// # Arguments
+///
+/// * `path` - The file to write
+/// * `contents` - The complete new contents
+///
+/// # Errors
+///
+/// Returns `FileUtilsError::Io` if the temporary file cannot be written or
+/// renamed. The temporary file is removed on failure.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::tools::file_utils::write_atomic;
+///
+/// # tokio_test::block_on(async {
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("notes.txt");
+/// write_atomic(&path, b"hello").await.unwrap();
+/// assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello");
+/// # });
+/// ```
+pub async fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), FileUtilsError> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp_path =
+        path.with_file_name(format!(".{}.xzatoma-{}.tmp", file_name, std::process::id()));
+
+    let result = async {
+        tokio::fs::write(&temp_path, contents).await?;
+        if let Ok(metadata) = tokio::fs::metadata(path).await {
+            tokio::fs::set_permissions(&temp_path, metadata.permissions()).await?;
+        }
+        tokio::fs::rename(&temp_path, path).await
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+    }
+    result.map_err(FileUtilsError::Io)
+}
+
 /// Generate a unified diff between two text strings
 ///
 /// Creates a line-based unified diff showing changes between old and new content.
@@ -396,4 +448,27 @@ mod tests {
             Err(FileUtilsError::FileTooLarge(2000, 1000))
         ));
     }
+
+    #[tokio::test]
+    async fn test_write_atomic_replaces_contents_and_keeps_permissions() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("script.sh");
+        tokio::fs::write(&path, "old").await.unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        write_atomic(&path, b"new").await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(std::fs::read_dir(temp.path()).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
 }
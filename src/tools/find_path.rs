@@ -82,6 +82,10 @@ fn default_page_size() -> usize {
 
 #[async_trait]
 impl ToolExecutor for FindPathTool {
+    fn read_only(&self) -> bool {
+        true
+    }
+
     fn tool_definition(&self) -> serde_json::Value {
         serde_json::json!({
             "name": TOOL_FIND_PATH,
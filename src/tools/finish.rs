@@ -0,0 +1,172 @@
+//! Finish tool implementation
+//!
+//! The model calls `finish` once the task is done, has failed, or cannot
+//! continue without more input. The tool itself only validates the
+//! arguments; the agent notices the successful call, ends the turn loop,
+//! and reports the arguments as an [`ExecutionOutcome`].
+
+use crate::agent::outcome::{ExecutionOutcome, FinishStatus};
+use crate::error::Result;
+use crate::tools::{parse_tool_args, ToolExecutor, ToolResult};
+use async_trait::async_trait;
+use serde::Deserialize;
+
+/// Name of the finish tool
+pub const FINISH_TOOL_NAME: &str = "finish";
+
+/// Finish tool that ends the agent run with a structured result
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::tools::finish::FinishTool;
+/// use xzatoma::tools::ToolExecutor;
+///
+/// # tokio_test::block_on(async {
+/// let result = FinishTool
+///     .execute(serde_json::json!({
+///         "status": "success",
+///         "summary": "Added the --json flag and its tests"
+///     }))
+///     .await
+///     .unwrap();
+/// assert!(result.success);
+/// # });
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FinishTool;
+
+#[derive(Debug, Deserialize)]
+struct FinishParams {
+    status: FinishStatus,
+    summary: String,
+    #[serde(default)]
+    details: Option<serde_json::Value>,
+}
+
+/// Parses the arguments of a `finish` call into an outcome
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Tool` when the status is unknown, the summary is
+/// missing or blank, or `details` is not an object.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::agent::outcome::FinishStatus;
+/// use xzatoma::tools::finish::parse_outcome;
+///
+/// let outcome = parse_outcome(serde_json::json!({
+///     "status": "needs_input",
+///     "summary": "Which database should the migration target?"
+/// }))
+/// .unwrap();
+/// assert_eq!(outcome.status, FinishStatus::NeedsInput);
+/// assert!(outcome.explicit);
+/// ```
+pub fn parse_outcome(args: serde_json::Value) -> Result<ExecutionOutcome> {
+    let params: FinishParams = parse_tool_args(args)?;
+    if params.summary.trim().is_empty() {
+        return Err(crate::error::XzatomaError::Tool(
+            "Invalid tool parameters: summary must not be empty".to_string(),
+        ));
+    }
+    let details = params.details.filter(|details| !details.is_null());
+    if details.as_ref().is_some_and(|details| !details.is_object()) {
+        return Err(crate::error::XzatomaError::Tool(
+            "Invalid tool parameters: details must be an object".to_string(),
+        ));
+    }
+    Ok(ExecutionOutcome::finished(params.status, params.summary).with_details(details))
+}
+
+#[async_trait]
+impl ToolExecutor for FinishTool {
+    fn tool_definition(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": FINISH_TOOL_NAME,
+            "description": "Ends the task and reports its result. Call this exactly once when the task is done, has failed, or cannot continue without more input from the user. No further tools run after it.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "status": {
+                        "type": "string",
+                        "enum": ["success", "failure", "needs_input"],
+                        "description": "success if the task was completed, failure if it could not be, needs_input if the user must answer a question first"
+                    },
+                    "summary": {
+                        "type": "string",
+                        "description": "Short description of what was done, what went wrong, or what input is needed"
+                    },
+                    "details": {
+                        "type": "object",
+                        "description": "Optional structured details, such as the files changed or the tests run"
+                    }
+                },
+                "required": ["status", "summary"]
+            }
+        })
+    }
+
+    async fn execute(&self, args: serde_json::Value) -> Result<ToolResult> {
+        match parse_outcome(args) {
+            Ok(outcome) => Ok(ToolResult::success(format!(
+                "Finished with status {}",
+                outcome.status
+            ))),
+            Err(error) => Ok(ToolResult::error(error.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_outcome_accepts_each_status() {
+        for (name, status) in [
+            ("success", FinishStatus::Success),
+            ("failure", FinishStatus::Failure),
+            ("needs_input", FinishStatus::NeedsInput),
+        ] {
+            let outcome = parse_outcome(serde_json::json!({
+                "status": name,
+                "summary": "done",
+                "details": {"files": ["src/a.rs"]}
+            }))
+            .unwrap();
+            assert_eq!(outcome.status, status);
+            assert_eq!(outcome.details.unwrap()["files"][0], "src/a.rs");
+        }
+    }
+
+    #[test]
+    fn test_parse_outcome_rejects_invalid_arguments() {
+        for args in [
+            serde_json::json!({"status": "maybe", "summary": "x"}),
+            serde_json::json!({"status": "success"}),
+            serde_json::json!({"status": "success", "summary": "  "}),
+            serde_json::json!({"status": "success", "summary": "x", "details": [1]}),
+        ] {
+            assert!(parse_outcome(args).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_reports_invalid_arguments_as_tool_error() {
+        let result = FinishTool
+            .execute(serde_json::json!({"status": "done"}))
+            .await
+            .unwrap();
+        assert!(!result.success);
+
+        let result = FinishTool
+            .execute(serde_json::json!({"status": "failure", "summary": "tests fail"}))
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert!(result.output.contains("failure"));
+    }
+}
@@ -1,20 +1,30 @@
 //! Grep tool for regex-based code search
 //!
 //! This module provides a grep-like search tool that supports regex pattern matching,
-//! file filtering, case sensitivity control, and pagination.
+//! file filtering, case sensitivity control, and pagination. Files are walked
+//! with the `ignore` crate, so `.gitignore` and `.ignore` files are honored.
+//! Hidden files are searched like any other file; `.git` never is.
+//!
+//! Pages are addressed with an opaque cursor that encodes a fingerprint of the
+//! search and the offset of the next page. Files are walked in sorted order so
+//! consecutive pages of the same search never overlap or skip matches.
 
 use crate::error::Result;
 use crate::tools::{ToolExecutor, ToolResult};
 use async_trait::async_trait;
-use glob::Pattern;
+use base64::Engine;
 use ignore::WalkBuilder;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 use tracing::debug;
 
+/// Git metadata directory, never searched even with `include_ignored`
+const GIT_DIR: &str = ".git";
+
 /// Search result with file location and context
 ///
 /// Represents a single match found during a grep search, including
@@ -85,6 +95,21 @@ impl SearchMatch {
     }
 }
 
+/// Result of a grep search
+#[derive(Debug, Clone)]
+pub struct GrepSearchResult {
+    /// Matches on the requested page
+    pub matches: Vec<SearchMatch>,
+    /// Total number of matches across all pages
+    pub total_matches: usize,
+    /// Files and directories skipped by `.gitignore`, `.ignore`, or git
+    /// exclude rules
+    ///
+    /// An ignored directory counts once. `.git` is not counted. Always 0 when
+    /// ignored paths are included.
+    pub ignored_skipped: usize,
+}
+
 /// Grep tool for regex-based code search
 ///
 /// Supports searching through files with regex patterns, file filtering,
@@ -131,6 +156,10 @@ impl GrepTool {
 
     /// Search for a pattern in files
     ///
+    /// Files skipped by ignore rules are not searched. See
+    /// [`GrepTool::search_files`] to search them or to learn how many paths
+    /// were skipped.
+    ///
     /// # Arguments
     ///
     /// * `regex` - Regex pattern to search for
@@ -152,6 +181,40 @@ impl GrepTool {
         case_sensitive: bool,
         offset: usize,
     ) -> Result<(Vec<SearchMatch>, usize)> {
+        let result = self
+            .search_files(regex, include_pattern, case_sensitive, offset, false)
+            .await?;
+        Ok((result.matches, result.total_matches))
+    }
+
+    /// Search for a pattern in files, optionally including ignored paths
+    ///
+    /// Files are walked with the `ignore` crate, the walker used by ripgrep.
+    /// It honors `.gitignore` and `.ignore` files at every level (and in
+    /// parent directories), `.git/info/exclude`, and the global git excludes
+    /// file. `.gitignore` applies even outside a git repository. Hidden files
+    /// are searched, and `.git` directories are always skipped.
+    /// `excluded_patterns` and the file size cap apply on top.
+    ///
+    /// # Arguments
+    ///
+    /// * `regex` - Regex pattern to search for
+    /// * `include_pattern` - Optional glob pattern to include files
+    /// * `case_sensitive` - Whether search is case-sensitive
+    /// * `offset` - Starting result number for pagination
+    /// * `include_ignored` - Also search paths matched by ignore rules
+    ///
+    /// # Errors
+    ///
+    /// Returns error if regex is invalid or file operations fail
+    pub async fn search_files(
+        &self,
+        regex: &str,
+        include_pattern: Option<&str>,
+        case_sensitive: bool,
+        offset: usize,
+        include_ignored: bool,
+    ) -> Result<GrepSearchResult> {
         // Compile regex pattern
         let regex_str = if case_sensitive {
             regex.to_string()
@@ -165,44 +228,25 @@ impl GrepTool {
 
         let mut all_matches = Vec::new();
 
-        // Read .gitignore patterns (if any) from the working directory.
-        // We parse simple, line-based patterns: skip empty lines and comments.
-        // Plain filenames (no wildcard and no slash) are treated as exact names for deterministic behavior.
-        let mut gitignore_exact: HashSet<String> = HashSet::new();
-        let mut gitignore_globs: Vec<Pattern> = Vec::new();
-        let gitignore_path = self.working_dir.join(".gitignore");
-        if let Ok(contents) = fs::read_to_string(&gitignore_path) {
-            for line in contents.lines() {
-                let t = line.trim();
-                if t.is_empty() || t.starts_with('#') {
-                    continue;
-                }
-                // Skip negation patterns (advanced support could be added later)
-                if t.starts_with('!') {
-                    continue;
-                }
-                // Treat simple names (no wildcard, no slash) as exact filenames
-                if !t.contains('*') && !t.contains('?') && !t.contains('/') {
-                    gitignore_exact.insert(t.to_string());
-                } else if let Ok(pat) = Pattern::new(t) {
-                    // Store wildcard/slash patterns as compiled glob Patterns
-                    gitignore_globs.push(pat);
-                }
-            }
-        }
-
-        // Walk directory tree using ignore::WalkBuilder so we respect .gitignore and git excludes
         let mut builder = WalkBuilder::new(&self.working_dir);
-        // Respect .gitignore files discovered by the walker as well
-        builder.git_ignore(true);
-        // Respect .git/info/exclude
-        builder.git_exclude(true);
-        // Do not automatically skip hidden files; leave control to excluded_patterns or explicit options
+        // .ignore, .gitignore (including nested and parent files),
+        // .git/info/exclude, and global git excludes
+        builder.standard_filters(!include_ignored);
+        // Hidden files are ordinary files; only the git metadata is skipped,
+        // whether or not ignored paths are included
         builder.hidden(false);
-
-        let walker = builder.build();
-
-        for result in walker {
+        builder.filter_entry(|entry| entry.file_name() != GIT_DIR);
+        // Apply .gitignore files even when the directory is not a git repository
+        builder.require_git(false);
+        // Stable ordering keeps pages of the same search consistent
+        builder.sort_by_file_name(|a, b| a.cmp(b));
+
+        // Every path the walker yields; anything else under a yielded
+        // directory was skipped by ignore rules
+        let mut visited: HashSet<PathBuf> = HashSet::new();
+        let mut visited_dirs: Vec<PathBuf> = Vec::new();
+
+        for result in builder.build() {
             let entry = match result {
                 Ok(e) => e,
                 Err(err) => {
@@ -211,16 +255,16 @@ impl GrepTool {
                 }
             };
 
-            // Rely on WalkBuilder's configuration to respect .gitignore/git excludes.
-            // The `ignore` crate's DirEntry does not expose `is_ignored()` in all versions,
-            // so avoid calling it here; we've configured the walker with `git_ignore(true)`
-            // and `git_exclude(true)` above and also apply parsed .gitignore patterns where
-            // appropriate further down. Do not perform an entry-level `is_ignored()` call.
-
             let path = entry.path();
+            if !include_ignored {
+                visited.insert(path.to_path_buf());
+            }
 
             // Only consider files
             if !path.is_file() {
+                if !include_ignored && path.is_dir() {
+                    visited_dirs.push(path.to_path_buf());
+                }
                 continue;
             }
 
@@ -232,52 +276,6 @@ impl GrepTool {
                 }
             }
 
-            // Check if file matches any patterns derived from the working directory .gitignore we parsed above.
-            // Use exact-name set first for deterministic matches, then fall back to compiled globs.
-            if !gitignore_exact.is_empty() || !gitignore_globs.is_empty() {
-                // Compute path relative to working_dir for more accurate .gitignore matching
-                let rel_path = path
-                    .strip_prefix(&self.working_dir)
-                    .map(|p| p.display().to_string())
-                    .unwrap_or_else(|_| path.display().to_string());
-
-                // If filename can be decoded to &str, check exact-name matches and globs.
-                if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                    // Exact-name checks (fast and deterministic)
-                    if gitignore_exact.contains(file_name) || gitignore_exact.contains(&rel_path) {
-                        // Skip files explicitly listed by exact names in .gitignore
-                        continue;
-                    }
-
-                    // Check compiled glob patterns against relative path and file name
-                    let mut ignored = false;
-                    for gp in &gitignore_globs {
-                        if gp.matches(&rel_path)
-                            || gp.matches(file_name)
-                            || gp.matches(&path.display().to_string())
-                        {
-                            ignored = true;
-                            break;
-                        }
-                    }
-                    if ignored {
-                        continue;
-                    }
-                } else {
-                    // If we don't have a usable filename, apply glob checks against the relative path
-                    let mut ignored = false;
-                    for gp in &gitignore_globs {
-                        if gp.matches(&rel_path) || gp.matches(&path.display().to_string()) {
-                            ignored = true;
-                            break;
-                        }
-                    }
-                    if ignored {
-                        continue;
-                    }
-                }
-            }
-
             // Check if file is excluded via user-provided excluded_patterns
             if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
                 if self.should_exclude(file_name, path) {
@@ -350,13 +348,34 @@ impl GrepTool {
             Vec::new()
         };
 
-        Ok((paginated, total_matches))
+        let ignored_skipped = if include_ignored {
+            0
+        } else {
+            count_skipped_children(&visited_dirs, &visited)
+        };
+
+        Ok(GrepSearchResult {
+            matches: paginated,
+            total_matches,
+            ignored_skipped,
+        })
     }
 
     /// Check if path should be excluded based on patterns
+    ///
+    /// Patterns are matched against the file name, the path relative to the
+    /// working directory, and the full path, so `target/**` excludes files
+    /// under `<working_dir>/target`.
     fn should_exclude(&self, file_name: &str, path: &Path) -> bool {
+        let relative = path
+            .strip_prefix(&self.working_dir)
+            .map(|p| p.display().to_string())
+            .ok();
         for pattern in &self.excluded_patterns {
             if self.glob_match(file_name, pattern)
+                || relative
+                    .as_deref()
+                    .is_some_and(|relative| self.glob_match(relative, pattern))
                 || self.glob_match(&path.display().to_string(), pattern)
             {
                 return true;
@@ -387,6 +406,55 @@ impl GrepTool {
     }
 }
 
+/// Fingerprints the parameters that define a search's result set
+///
+/// A cursor is only valid for the search that produced it; a different regex
+/// or filter would silently mix two result sets.
+fn search_fingerprint(
+    regex: &str,
+    include_pattern: Option<&str>,
+    case_sensitive: bool,
+    include_ignored: bool,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(regex.as_bytes());
+    hasher.update([0]);
+    hasher.update(include_pattern.unwrap_or_default().as_bytes());
+    hasher.update([0, u8::from(case_sensitive), u8::from(include_ignored)]);
+    hasher.finalize()[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Encodes a pagination cursor for the page starting at `offset`
+fn encode_cursor(fingerprint: &str, offset: usize) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(format!("{}:{}", fingerprint, offset))
+}
+
+/// Decodes a pagination cursor into its search fingerprint and offset
+fn decode_cursor(cursor: &str) -> Option<(String, usize)> {
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(cursor.trim())
+        .ok()?;
+    let decoded = String::from_utf8(bytes).ok()?;
+    let (fingerprint, offset) = decoded.split_once(':')?;
+    Some((fingerprint.to_string(), offset.parse().ok()?))
+}
+
+/// Counts the direct children of walked directories that the walker skipped
+///
+/// Hidden files are walked and `.git` is excluded explicitly, so every other
+/// skipped child matched an ignore rule. An ignored directory such as
+/// `node_modules` counts once; its contents are never visited.
+fn count_skipped_children(dirs: &[PathBuf], visited: &HashSet<PathBuf>) -> usize {
+    dirs.iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.filter_map(|entry| entry.ok()))
+        .filter(|entry| entry.file_name() != GIT_DIR && !visited.contains(&entry.path()))
+        .count()
+}
+
 /// Recursive glob matching helper (free function; no struct state required).
 fn glob_match_recursive(
     text: &[char],
@@ -439,6 +507,10 @@ fn glob_match_recursive(
 
 #[async_trait]
 impl ToolExecutor for GrepTool {
+    fn read_only(&self) -> bool {
+        true
+    }
+
     fn tool_definition(&self) -> serde_json::Value {
         serde_json::json!({
             "name": "grep",
@@ -461,6 +533,14 @@ impl ToolExecutor for GrepTool {
                     "offset": {
                         "type": "integer",
                         "description": "Starting result number for pagination (default: 0)"
+                    },
+                    "cursor": {
+                        "type": "string",
+                        "description": "Opaque next_cursor from a previous grep result to fetch the next page. Repeat the same regex, include_pattern, case_sensitive, and include_ignored values"
+                    },
+                    "include_ignored": {
+                        "type": "boolean",
+                        "description": "Also search paths ignored by .gitignore or .ignore files. Only use when the ignored files are genuinely needed (default: false)"
                     }
                 },
                 "required": ["regex"]
@@ -478,34 +558,100 @@ impl ToolExecutor for GrepTool {
             .get("case_sensitive")
             .and_then(|v| v.as_bool())
             .unwrap_or(false);
-        let offset = args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        let include_ignored = args
+            .get("include_ignored")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
 
-        let (matches, total) = self
-            .search(regex, include_pattern, case_sensitive, offset)
+        let fingerprint =
+            search_fingerprint(regex, include_pattern, case_sensitive, include_ignored);
+        let offset = match args.get("cursor").and_then(|v| v.as_str()) {
+            Some(cursor) => match decode_cursor(cursor) {
+                Some((cursor_fingerprint, offset)) if cursor_fingerprint == fingerprint => offset,
+                Some(_) => {
+                    return Ok(ToolResult::error(
+                        "Cursor belongs to a different search. Pass the same regex, include_pattern, case_sensitive, and include_ignored values, or omit cursor to start over.",
+                    ));
+                }
+                None => {
+                    return Ok(ToolResult::error(format!(
+                        "Invalid cursor: {}. Omit cursor to start from the first page.",
+                        cursor
+                    )));
+                }
+            },
+            None => args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+        };
+        let page_size = self.max_results_per_page;
+
+        let GrepSearchResult {
+            matches,
+            total_matches: total,
+            ignored_skipped,
+        } = self
+            .search_files(
+                regex,
+                include_pattern,
+                case_sensitive,
+                offset,
+                include_ignored,
+            )
             .await?;
 
         if matches.is_empty() && total == 0 {
-            return Ok(ToolResult::success(format!(
-                "No matches found for pattern: {}",
-                regex
-            )));
+            let mut output = format!("No matches found for pattern: {}", regex);
+            if ignored_skipped > 0 {
+                output.push_str(&format!(
+                    "\n({} path(s) skipped by ignore rules; set include_ignored=true to search them)",
+                    ignored_skipped
+                ));
+            }
+            return Ok(ToolResult::success(output)
+                .with_metadata("total_matches".to_string(), "0".to_string())
+                .with_metadata("ignored_skipped".to_string(), ignored_skipped.to_string()));
         }
 
         let mut output = format!("Found {} match(es) total\n\n", total);
+        let shown = matches.len();
         for m in matches {
             output.push_str(&m.format_with_context(120));
             output.push_str("\n---\n");
         }
 
-        if offset + self.max_results_per_page < total {
+        let next_offset = offset + shown;
+        let next_cursor =
+            (shown > 0 && next_offset < total).then(|| encode_cursor(&fingerprint, next_offset));
+
+        if shown == 0 {
+            output.push_str(&format!(
+                "\nNo matches at offset {}; the search has {} match(es).",
+                offset, total
+            ));
+        } else {
             output.push_str(&format!(
-                "\n... and {} more matches. Use offset={} for next page.",
-                total - (offset + self.max_results_per_page),
-                offset + self.max_results_per_page
+                "\nShowing matches {}\u{2013}{} of {}",
+                offset + 1,
+                next_offset,
+                total
             ));
+            match &next_cursor {
+                Some(cursor) => output.push_str(&format!("; pass cursor={} for more.", cursor)),
+                None => output.push('.'),
+            }
         }
 
-        Ok(ToolResult::success(output))
+        let mut result = ToolResult::success(output)
+            .with_metadata("total_matches".to_string(), total.to_string())
+            .with_metadata(
+                "page".to_string(),
+                (offset / page_size.max(1) + 1).to_string(),
+            )
+            .with_metadata("page_size".to_string(), page_size.to_string())
+            .with_metadata("ignored_skipped".to_string(), ignored_skipped.to_string());
+        if let Some(cursor) = next_cursor {
+            result = result.with_metadata("next_cursor".to_string(), cursor);
+        }
+        Ok(result)
     }
 }
 
@@ -743,4 +889,255 @@ mod tests {
             Some("visible.txt")
         );
     }
+
+    #[tokio::test]
+    async fn test_grep_tool_respects_nested_ignore_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path().to_path_buf();
+
+        fs::create_dir_all(temp_path.join("app/generated")).unwrap();
+        fs::create_dir_all(temp_path.join("docs")).unwrap();
+        fs::write(temp_path.join("app/.gitignore"), "generated/\n").unwrap();
+        fs::write(temp_path.join("docs/.ignore"), "draft.md\n").unwrap();
+        fs::write(temp_path.join("app/main.rs"), "needle\n").unwrap();
+        fs::write(temp_path.join("app/generated/out.rs"), "needle\n").unwrap();
+        fs::write(temp_path.join("docs/guide.md"), "needle\n").unwrap();
+        fs::write(temp_path.join("docs/draft.md"), "needle\n").unwrap();
+
+        let tool = GrepTool::new(temp_path, 20, 0, 1_000_000, vec![]);
+        let result = tool
+            .search_files("needle", None, true, 0, false)
+            .await
+            .unwrap();
+
+        let mut names: Vec<String> = result
+            .matches
+            .iter()
+            .filter_map(|m| m.file.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["guide.md", "main.rs"]);
+        // app/generated and docs/draft.md; the ignore files themselves are
+        // hidden files and are walked
+        assert_eq!(result.ignored_skipped, 2);
+    }
+
+    #[tokio::test]
+    async fn test_grep_tool_include_ignored_searches_ignored_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path().to_path_buf();
+
+        fs::create_dir_all(temp_path.join("node_modules/pkg")).unwrap();
+        fs::write(temp_path.join(".gitignore"), "node_modules/\n").unwrap();
+        fs::write(temp_path.join("node_modules/pkg/index.js"), "needle\n").unwrap();
+        fs::write(temp_path.join("src.js"), "needle\n").unwrap();
+
+        let tool = GrepTool::new(temp_path, 20, 0, 1_000_000, vec![]);
+
+        let default = tool
+            .search_files("needle", None, true, 0, false)
+            .await
+            .unwrap();
+        assert_eq!(default.total_matches, 1);
+        assert_eq!(default.ignored_skipped, 1);
+
+        let included = tool
+            .search_files("needle", None, true, 0, true)
+            .await
+            .unwrap();
+        assert_eq!(included.total_matches, 2);
+        assert_eq!(included.ignored_skipped, 0);
+    }
+
+    #[tokio::test]
+    async fn test_grep_tool_searches_hidden_files_but_never_git_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path().to_path_buf();
+
+        fs::create_dir_all(temp_path.join(".git/refs")).unwrap();
+        fs::create_dir_all(temp_path.join(".github/workflows")).unwrap();
+        fs::write(temp_path.join(".git/config"), "needle\n").unwrap();
+        fs::write(temp_path.join(".git/refs/head"), "needle\n").unwrap();
+        fs::write(temp_path.join(".github/workflows/ci.yml"), "needle\n").unwrap();
+        fs::write(temp_path.join(".env.example"), "needle\n").unwrap();
+
+        let tool = GrepTool::new(temp_path, 20, 0, 1_000_000, vec![]);
+        for include_ignored in [false, true] {
+            let result = tool
+                .search_files("needle", None, true, 0, include_ignored)
+                .await
+                .unwrap();
+            let mut names: Vec<String> = result
+                .matches
+                .iter()
+                .filter_map(|m| m.file.file_name().map(|n| n.to_string_lossy().into_owned()))
+                .collect();
+            names.sort();
+            assert_eq!(names, vec![".env.example", "ci.yml"]);
+            assert_eq!(result.ignored_skipped, 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_grep_tool_excluded_patterns_apply_with_include_ignored() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path().to_path_buf();
+
+        fs::create_dir_all(temp_path.join("target/debug")).unwrap();
+        fs::write(temp_path.join(".gitignore"), "target/\n").unwrap();
+        fs::write(temp_path.join("target/debug/build.log"), "needle\n").unwrap();
+        fs::write(temp_path.join("lib.rs"), "needle\n").unwrap();
+
+        let tool = GrepTool::new(temp_path, 20, 0, 1_000_000, vec!["target/**".to_string()]);
+        let result = tool
+            .search_files("needle", None, true, 0, true)
+            .await
+            .unwrap();
+        assert_eq!(result.total_matches, 1);
+        assert_eq!(
+            result.matches[0].file.file_name().and_then(|s| s.to_str()),
+            Some("lib.rs")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_grep_tool_execute_reports_ignored_skipped() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path().to_path_buf();
+
+        fs::write(temp_path.join(".gitignore"), "ignored.txt\n").unwrap();
+        fs::write(temp_path.join("ignored.txt"), "needle\n").unwrap();
+
+        let tool = GrepTool::new(temp_path, 20, 0, 1_000_000, vec![]);
+        let result = tool
+            .execute(serde_json::json!({ "regex": "needle" }))
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert!(result.output.contains("include_ignored=true"));
+        assert_eq!(
+            result.metadata.get("ignored_skipped").map(String::as_str),
+            Some("1")
+        );
+
+        let result = tool
+            .execute(serde_json::json!({ "regex": "needle", "include_ignored": true }))
+            .await
+            .unwrap();
+        assert!(result.output.contains("Found 1 match(es) total"));
+        assert_eq!(
+            result.metadata.get("ignored_skipped").map(String::as_str),
+            Some("0")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_grep_tool_cursor_walks_three_pages() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path().to_path_buf();
+        for file in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(temp_path.join(file), "hit\nhit\nhit\nhit\n").unwrap();
+        }
+
+        let tool = GrepTool::new(temp_path, 5, 0, 1_000_000, vec![]);
+        let mut seen = Vec::new();
+        let mut cursor: Option<String> = None;
+        let mut pages = Vec::new();
+        loop {
+            let mut args = serde_json::json!({ "regex": "hit" });
+            if let Some(cursor) = &cursor {
+                args["cursor"] = serde_json::json!(cursor);
+            }
+            let result = tool.execute(args).await.unwrap();
+            assert!(result.success);
+            assert_eq!(result.metadata.get("total_matches").unwrap(), "12");
+            assert_eq!(result.metadata.get("page_size").unwrap(), "5");
+            pages.push(result.metadata.get("page").unwrap().clone());
+            for line in result.output.lines() {
+                if line.contains(".txt:") {
+                    seen.push(line.to_string());
+                }
+            }
+            cursor = result.metadata.get("next_cursor").cloned();
+            if cursor.is_none() {
+                assert!(result
+                    .output
+                    .contains("Showing matches 11\u{2013}12 of 12."));
+                break;
+            }
+            assert!(result.output.contains("pass cursor="));
+        }
+
+        assert_eq!(pages, vec!["1", "2", "3"]);
+        assert_eq!(seen.len(), 12);
+        let unique: HashSet<&String> = seen.iter().collect();
+        assert_eq!(unique.len(), 12, "pages must not overlap: {:?}", seen);
+    }
+
+    #[tokio::test]
+    async fn test_grep_tool_search_order_is_deterministic() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path().to_path_buf();
+        for file in ["zeta.txt", "alpha.txt", "mid.txt"] {
+            fs::write(temp_path.join(file), "hit\n").unwrap();
+        }
+
+        let tool = GrepTool::new(temp_path, 20, 0, 1_000_000, vec![]);
+        let (matches, _) = tool.search("hit", None, true, 0).await.unwrap();
+        let names: Vec<_> = matches
+            .iter()
+            .filter_map(|m| m.file.file_name().and_then(|n| n.to_str()))
+            .collect();
+        assert_eq!(names, vec!["alpha.txt", "mid.txt", "zeta.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_grep_tool_rejects_invalid_and_stale_cursor() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path().to_path_buf();
+        fs::write(temp_path.join("a.txt"), "hit\nhit\nhit\n").unwrap();
+
+        let tool = GrepTool::new(temp_path, 2, 0, 1_000_000, vec![]);
+        let result = tool
+            .execute(serde_json::json!({ "regex": "hit", "cursor": "not a cursor!" }))
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("Invalid cursor"));
+
+        let first = tool
+            .execute(serde_json::json!({ "regex": "hit" }))
+            .await
+            .unwrap();
+        let cursor = first.metadata.get("next_cursor").unwrap().clone();
+
+        let stale = tool
+            .execute(serde_json::json!({ "regex": "other", "cursor": cursor }))
+            .await
+            .unwrap();
+        assert!(!stale.success);
+        assert!(stale.error.unwrap().contains("different search"));
+
+        let stale = tool
+            .execute(serde_json::json!({
+                "regex": "hit",
+                "case_sensitive": true,
+                "cursor": cursor
+            }))
+            .await
+            .unwrap();
+        assert!(!stale.success);
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let fingerprint = search_fingerprint("fn main", Some("*.rs"), false, false);
+        let cursor = encode_cursor(&fingerprint, 40);
+        assert_eq!(decode_cursor(&cursor), Some((fingerprint.clone(), 40)));
+        assert_ne!(
+            fingerprint,
+            search_fingerprint("fn main", Some("*.rs"), true, false)
+        );
+        assert_eq!(decode_cursor("%%%"), None);
+    }
 }
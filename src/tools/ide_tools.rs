@@ -203,6 +203,10 @@ impl IdeWriteTextFileTool {
 
 #[async_trait::async_trait]
 impl ToolExecutor for IdeWriteTextFileTool {
+    fn mutates(&self) -> bool {
+        true
+    }
+
     fn tool_definition(&self) -> serde_json::Value {
         json!({
             "name": "ide_write_text_file",
@@ -272,6 +276,10 @@ impl IdeOpenTerminalTool {
 
 #[async_trait::async_trait]
 impl ToolExecutor for IdeOpenTerminalTool {
+    fn mutates(&self) -> bool {
+        true
+    }
+
     fn tool_definition(&self) -> serde_json::Value {
         json!({
             "name": "ide_open_terminal",
@@ -515,6 +523,10 @@ impl IdeKillTerminalTool {
 
 #[async_trait::async_trait]
 impl ToolExecutor for IdeKillTerminalTool {
+    fn mutates(&self) -> bool {
+        true
+    }
+
     fn tool_definition(&self) -> serde_json::Value {
         json!({
             "name": "ide_kill_terminal",
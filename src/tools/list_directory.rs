@@ -136,6 +136,10 @@ impl ListDirectoryTool {
 
 #[async_trait::async_trait]
 impl ToolExecutor for ListDirectoryTool {
+    fn read_only(&self) -> bool {
+        true
+    }
+
     fn tool_definition(&self) -> serde_json::Value {
         json!({
             "name": "list_directory",
@@ -4,23 +4,39 @@
 //! for file operations, terminal execution, and plan parsing.
 
 pub mod activate_skill;
+pub mod argument_validation;
+pub mod audit_log;
+pub mod call_policy;
+pub mod change_set;
+pub mod confirmation;
 pub mod copy_path;
 pub mod create_directory;
+pub mod definition_limits;
 pub mod delete_path;
 pub mod edit_file;
 pub mod fetch;
 pub mod file_metadata;
+pub mod file_summary;
 pub mod file_utils;
 pub mod find_path;
+pub mod finish;
 pub mod grep;
 pub mod ide_tools;
 pub mod list_directory;
 pub mod move_path;
+pub mod output_pages;
+pub mod overflow_summary;
 pub mod parallel_subagent;
 pub mod plan;
 pub mod plan_format;
+pub mod plan_markdown;
+pub mod plan_risk;
+pub mod plan_validation;
+pub mod rate_limit;
 pub mod read_file;
 pub mod registry_builder;
+pub mod script_tool;
+pub mod streaming;
 pub mod subagent;
 pub mod terminal;
 pub mod write_file;
@@ -31,6 +47,14 @@ pub use terminal::{
     TerminalTool,
 };
 
+// Re-export argument validation
+pub use argument_validation::{
+    validate_tool_arguments, ArgumentValidation, ArgumentValidationOutcome,
+};
+
+// Re-export incremental output streaming types
+pub use streaming::{ToolOutputLine, ToolOutputSink, ToolOutputStream};
+
 // Re-export grep tool and search types
 pub use grep::{GrepTool, SearchMatch};
 
@@ -50,10 +74,12 @@ pub use file_utils::{
 };
 
 use crate::error::Result;
+use crate::network_policy::{NetworkCapability, NetworkPolicy};
+use crate::read_only::ReadOnlyPolicy;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 /// Tool name constants for file operations
 ///
@@ -325,14 +351,115 @@ pub trait ToolExecutor: Send + Sync {
     ///
     /// Returns error if execution fails
     async fn execute(&self, args: serde_json::Value) -> Result<ToolResult>;
+
+    /// Executes the tool while pushing incremental output to `sink`
+    ///
+    /// Long-running tools override this to report progress as it happens.
+    /// The returned `ToolResult` must still contain the complete output. The
+    /// default implementation ignores the sink and calls `execute`.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Tool arguments as a JSON value
+    /// * `sink` - Handle for pushing incremental output lines
+    ///
+    /// # Returns
+    ///
+    /// Returns a ToolResult with the execution outcome
+    ///
+    /// # Errors
+    ///
+    /// Returns error if execution fails
+    async fn execute_streaming(
+        &self,
+        args: serde_json::Value,
+        sink: ToolOutputSink,
+    ) -> Result<ToolResult> {
+        let _ = sink;
+        self.execute(args).await
+    }
+
+    /// Returns true when the tool changes the workspace or runs commands
+    ///
+    /// Mutating tools are refused in Planning mode unless the user approves
+    /// switching to Write mode. The default is `false`.
+    fn mutates(&self) -> bool {
+        false
+    }
+
+    /// Returns true when the tool only reads the workspace
+    ///
+    /// A read-only call with the same arguments returns the same result as
+    /// long as no tool has changed the workspace in between, so the agent
+    /// may answer a repeated call with the previous result instead of
+    /// running it again. Tools that read state outside the workspace, such
+    /// as the network or the editor, should keep the default of `false`.
+    fn read_only(&self) -> bool {
+        false
+    }
+
+    /// Describes the file change a call would make, without making it
+    ///
+    /// Chat mode previews consecutive calls from one response and lets the
+    /// user review them together before any runs. `staged` holds the
+    /// contents earlier calls in the same change set would leave behind.
+    /// Returns `None` when the call cannot be previewed, for example because
+    /// the tool does not change files or the arguments are invalid; such a
+    /// call runs on its own. The default is `None`.
+    fn preview_change(
+        &self,
+        args: &serde_json::Value,
+        staged: &change_set::StagedFiles,
+    ) -> Option<change_set::FileChange> {
+        let _ = (args, staged);
+        None
+    }
 }
 
+/// Separator between a namespace and a tool name in a qualified tool name
+///
+/// Providers only accept letters, digits, `_` and `-` in function names, so
+/// the dots in a namespace such as `mcp.github` are replaced with this
+/// separator as well: `mcp.github` + `create_issue` is advertised as
+/// `mcp__github__create_issue`.
+pub const NAMESPACE_SEPARATOR: &str = "__";
+
+/// A tool executor stored in a [`ToolRegistry`]
+#[derive(Clone)]
+struct RegisteredTool {
+    /// Namespace given to [`ToolRegistry::register_namespaced`], if any
+    namespace: Option<String>,
+    /// Short tool name
+    name: String,
+    /// Shared executor
+    executor: Arc<dyn ToolExecutor>,
+}
+
+type ToolMap = HashMap<String, RegisteredTool>;
+
 /// Tool registry for managing available tools
 ///
 /// The registry maintains a collection of tools that can be executed
 /// by the agent during conversation.
+///
+/// Clones are handles to the same registry: a tool registered or removed
+/// through one clone is visible through all of them, and every clone
+/// shares the same executor instances. Use [`fork`](Self::fork) or
+/// [`filtered`](Self::filtered) for a registry that can change on its own
+/// while still sharing executors, and with them state such as rate
+/// limiters and caches.
+///
+/// Tools registered with [`register_namespaced`](Self::register_namespaced)
+/// are stored under their qualified name and can be looked up by either
+/// name as long as the short name is unambiguous.
+///
+/// Every registration goes through the registry's [`NetworkPolicy`] and
+/// [`ReadOnlyPolicy`], so a tool the session may not use is never reachable
+/// through the registry.
 pub struct ToolRegistry {
-    tools: HashMap<String, Arc<dyn ToolExecutor>>,
+    tools: Arc<RwLock<ToolMap>>,
+    network_policy: NetworkPolicy,
+    read_only: ReadOnlyPolicy,
 }
 
 impl ToolRegistry {
@@ -342,11 +469,113 @@ impl ToolRegistry {
     ///
     /// Returns a new ToolRegistry instance
     pub fn new() -> Self {
+        Self::from_map(
+            HashMap::new(),
+            NetworkPolicy::default(),
+            ReadOnlyPolicy::default(),
+        )
+    }
+
+    fn from_map(tools: ToolMap, network_policy: NetworkPolicy, read_only: ReadOnlyPolicy) -> Self {
         Self {
-            tools: HashMap::new(),
+            tools: Arc::new(RwLock::new(tools)),
+            network_policy,
+            read_only,
         }
     }
 
+    fn read(&self) -> RwLockReadGuard<'_, ToolMap> {
+        self.tools.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, ToolMap> {
+        self.tools.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Set the network policy consulted when tools are registered
+    ///
+    /// Tools that need a network capability the policy denies are skipped
+    /// by [`register`](Self::register). Registries cloned from this one
+    /// inherit the policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Network policy to enforce
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::network_policy::NetworkPolicy;
+    /// use xzatoma::tools::ToolRegistry;
+    ///
+    /// let registry = ToolRegistry::new().with_network_policy(NetworkPolicy::offline());
+    /// assert!(registry.network_policy().is_offline());
+    /// ```
+    pub fn with_network_policy(mut self, policy: NetworkPolicy) -> Self {
+        self.network_policy = policy;
+        self
+    }
+
+    /// Get the network policy consulted when tools are registered
+    pub fn network_policy(&self) -> NetworkPolicy {
+        self.network_policy
+    }
+
+    /// Set the read-only policy consulted when tools are registered
+    ///
+    /// With read-only mode enabled, [`register`](Self::register) skips the
+    /// terminal and wraps mutating tools so every call is refused. Set the
+    /// policy before registering tools; registries cloned from this one
+    /// inherit it.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Read-only policy to enforce
+    pub fn with_read_only_policy(mut self, policy: ReadOnlyPolicy) -> Self {
+        self.read_only = policy;
+        self
+    }
+
+    /// Get the read-only policy consulted when tools are registered
+    pub fn read_only_policy(&self) -> ReadOnlyPolicy {
+        self.read_only
+    }
+
+    /// Returns the qualified name of a tool in a namespace
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::tools::ToolRegistry;
+    ///
+    /// assert_eq!(
+    ///     ToolRegistry::qualified_name("mcp.github", "create_issue"),
+    ///     "mcp__github__create_issue"
+    /// );
+    /// ```
+    pub fn qualified_name(namespace: &str, name: &str) -> String {
+        format!(
+            "{}{}{}",
+            namespace.replace('.', NAMESPACE_SEPARATOR),
+            NAMESPACE_SEPARATOR,
+            name
+        )
+    }
+
+    fn allowed_by_policy(&self, name: &str) -> bool {
+        if let Some(capability) = NetworkCapability::for_tool(name) {
+            if !self.network_policy.allows(capability) {
+                tracing::debug!(
+                    "Skipping tool '{}': {} disabled by the network policy",
+                    name,
+                    capability
+                );
+                return false;
+            }
+        }
+        true
+    }
+
     /// Register a tool executor in the registry
     ///
     /// # Arguments
@@ -362,40 +591,155 @@ impl ToolRegistry {
     /// // registry.register("my_tool", Box::new(MyToolExecutor));
     /// ```
     pub fn register(&mut self, name: impl Into<String>, executor: Arc<dyn ToolExecutor>) {
-        self.tools.insert(name.into(), executor);
+        let name = name.into();
+        if !self.allowed_by_policy(&name) {
+            return;
+        }
+        let Some(executor) = self.read_only.admit(&name, executor) else {
+            return;
+        };
+        self.write().insert(
+            name.clone(),
+            RegisteredTool {
+                namespace: None,
+                name,
+                executor,
+            },
+        );
+    }
+
+    /// Register a tool executor under a namespace
+    ///
+    /// The tool is stored under its [qualified name](Self::qualified_name).
+    /// [`get`](Self::get) also finds it by its short name, and
+    /// [`all_definitions`](Self::all_definitions) advertises it by its short
+    /// name, unless another registered tool has the same short name.
+    ///
+    /// # Arguments
+    ///
+    /// * `namespace` - Dotted namespace, such as `mcp.github`
+    /// * `name` - Short tool name
+    /// * `executor` - Tool executor implementation
+    pub fn register_namespaced(
+        &mut self,
+        namespace: &str,
+        name: impl Into<String>,
+        executor: Arc<dyn ToolExecutor>,
+    ) {
+        let name = name.into();
+        if !self.allowed_by_policy(&name) {
+            return;
+        }
+        let Some(executor) = self.read_only.admit(&name, executor) else {
+            return;
+        };
+        self.write().insert(
+            Self::qualified_name(namespace, &name),
+            RegisteredTool {
+                namespace: Some(namespace.to_string()),
+                name,
+                executor,
+            },
+        );
+    }
+
+    /// Finds the key a name refers to
+    ///
+    /// An exact key wins. A dotted qualified name such as
+    /// `mcp.github.create_issue` is accepted too. Otherwise the name must be
+    /// the short name of exactly one namespaced tool.
+    fn resolve(tools: &ToolMap, name: &str) -> Option<String> {
+        if tools.contains_key(name) {
+            return Some(name.to_string());
+        }
+        let qualified = name.replace('.', NAMESPACE_SEPARATOR);
+        if tools.contains_key(&qualified) {
+            return Some(qualified);
+        }
+        let mut matches = tools.iter().filter(|(_, tool)| tool.name == name);
+        let (key, _) = matches.next()?;
+        if matches.next().is_some() {
+            tracing::debug!("Tool name '{}' is ambiguous; use the qualified name", name);
+            return None;
+        }
+        Some(key.clone())
     }
 
     /// Get a tool executor by name
     ///
     /// # Arguments
     ///
-    /// * `name` - Tool name
+    /// * `name` - Tool name, or the short or qualified name of a namespaced tool
     ///
     /// # Returns
     ///
-    /// Returns the tool executor if found
+    /// Returns the tool executor if found and unambiguous
     pub fn get(&self, name: &str) -> Option<Arc<dyn ToolExecutor>> {
-        self.tools.get(name).cloned()
+        let tools = self.read();
+        let key = Self::resolve(&tools, name)?;
+        tools.get(&key).map(|tool| Arc::clone(&tool.executor))
+    }
+
+    /// Remove a tool from the registry
+    ///
+    /// The name is resolved the same way as in [`get`](Self::get). The tool
+    /// disappears from every clone of this registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Tool name, or the short or qualified name of a namespaced tool
+    ///
+    /// # Returns
+    ///
+    /// Returns the removed executor, or `None` if no tool matched
+    pub fn remove(&mut self, name: &str) -> Option<Arc<dyn ToolExecutor>> {
+        let mut tools = self.write();
+        let key = Self::resolve(&tools, name)?;
+        tools.remove(&key).map(|tool| tool.executor)
     }
 
     /// Get all tool names in the registry
     ///
+    /// Namespaced tools are listed by their qualified names.
+    ///
     /// # Returns
     ///
     /// Returns a vector of all registered tool names
     pub fn tool_names(&self) -> Vec<String> {
-        self.tools.keys().cloned().collect()
+        self.read().keys().cloned().collect()
     }
 
     /// Get all tool definitions as JSON values
     ///
+    /// A namespaced tool is advertised by its short name unless another
+    /// registered tool shares it, in which case its qualified name is used.
+    ///
     /// # Returns
     ///
     /// Returns a vector of all tool definitions
     pub fn all_definitions(&self) -> Vec<serde_json::Value> {
-        self.tools
-            .values()
-            .map(|executor| executor.tool_definition())
+        let tools = self.read();
+        let mut short_names: HashMap<&str, usize> = HashMap::new();
+        for tool in tools.values() {
+            *short_names.entry(tool.name.as_str()).or_default() += 1;
+        }
+
+        tools
+            .iter()
+            .map(|(key, tool)| {
+                let mut definition = tool.executor.tool_definition();
+                if tool.namespace.is_some() {
+                    let advertised = if short_names[tool.name.as_str()] > 1 {
+                        key
+                    } else {
+                        &tool.name
+                    };
+                    if let Some(object) = definition.as_object_mut() {
+                        object.insert("name".to_string(), serde_json::json!(advertised));
+                    }
+                }
+                definition
+            })
             .collect()
     }
 
@@ -405,7 +749,7 @@ impl ToolRegistry {
     ///
     /// Returns the count of registered tools
     pub fn len(&self) -> usize {
-        self.tools.len()
+        self.read().len()
     }
 
     /// Check if the registry is empty
@@ -414,23 +758,78 @@ impl ToolRegistry {
     ///
     /// Returns true if no tools are registered
     pub fn is_empty(&self) -> bool {
-        self.tools.is_empty()
+        self.read().is_empty()
     }
 }
 
 impl Clone for ToolRegistry {
-    /// Clones the tool registry with all registered tools
+    /// Returns another handle to the same registry
     ///
-    /// Since tools are stored as Arc<dyn ToolExecutor>, cloning is cheap
-    /// and shares the underlying tool implementations.
+    /// Registrations and removals through either handle are visible through
+    /// both. Use [`ToolRegistry::fork`] for an independent copy.
     fn clone(&self) -> Self {
         Self {
-            tools: self.tools.clone(),
+            tools: Arc::clone(&self.tools),
+            network_policy: self.network_policy,
+            read_only: self.read_only,
         }
     }
 }
 
 impl ToolRegistry {
+    /// Creates an independent copy that shares the executors
+    ///
+    /// Tools registered or removed on the copy do not affect this registry,
+    /// but both call the same executor instances.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::tools::ToolRegistry;
+    ///
+    /// let registry = ToolRegistry::new();
+    /// let mut copy = registry.fork();
+    /// copy.register("finish", std::sync::Arc::new(xzatoma::tools::finish::FinishTool));
+    /// assert!(registry.is_empty());
+    /// assert_eq!(copy.len(), 1);
+    /// ```
+    pub fn fork(&self) -> Self {
+        Self::from_map(self.read().clone(), self.network_policy, self.read_only)
+    }
+
+    fn fork_where(&self, keep: impl Fn(&str) -> bool) -> Self {
+        let tools = self
+            .read()
+            .iter()
+            .filter(|(key, _)| keep(key))
+            .map(|(key, tool)| (key.clone(), tool.clone()))
+            .collect();
+        Self::from_map(tools, self.network_policy, self.read_only)
+    }
+
+    /// Creates an independent registry with only the named tools
+    ///
+    /// Names are resolved the same way as in [`get`](Self::get); names that
+    /// match nothing are skipped. The new registry shares the executors and
+    /// keeps the tools' namespaces.
+    ///
+    /// # Arguments
+    ///
+    /// * `names` - Tool names to keep
+    ///
+    /// # Returns
+    ///
+    /// A new registry with only the named tools
+    pub fn filtered(&self, names: &[&str]) -> Self {
+        let tools = self.read();
+        let kept = names
+            .iter()
+            .filter_map(|name| Self::resolve(&tools, name))
+            .filter_map(|key| tools.get(&key).map(|tool| (key, tool.clone())))
+            .collect();
+        Self::from_map(kept, self.network_policy, self.read_only)
+    }
+
     /// Creates a filtered clone with only allowed tools
     ///
     /// Returns a new registry containing only the specified tools.
@@ -452,13 +851,8 @@ impl ToolRegistry {
     /// let filtered = registry.clone_with_filter(&["file_ops".to_string(), "terminal".to_string()]);
     /// ```
     pub fn clone_with_filter(&self, allowed: &[String]) -> Self {
-        let mut filtered = ToolRegistry::new();
-        for tool_name in allowed {
-            if let Some(executor) = self.tools.get(tool_name) {
-                filtered.register(tool_name.clone(), Arc::clone(executor));
-            }
-        }
-        filtered
+        let names: Vec<&str> = allowed.iter().map(String::as_str).collect();
+        self.filtered(&names)
     }
 
     /// Creates a clone without the subagent tool
@@ -469,13 +863,7 @@ impl ToolRegistry {
     ///
     /// A new registry without the subagent tool
     pub fn clone_without(&self, excluded: &str) -> Self {
-        let mut filtered = ToolRegistry::new();
-        for (name, executor) in &self.tools {
-            if name != excluded {
-                filtered.register(name.clone(), Arc::clone(executor));
-            }
-        }
-        filtered
+        self.fork_where(|name| name != excluded)
     }
 
     /// Creates a clone without parallel subagent tools
@@ -487,14 +875,8 @@ impl ToolRegistry {
     ///
     /// A new registry without subagent/parallel_subagent tools
     pub fn clone_without_parallel(&self) -> Self {
-        let mut filtered = ToolRegistry::new();
         let excluded = ["subagent", "parallel_subagent"];
-        for (name, executor) in &self.tools {
-            if !excluded.contains(&name.as_str()) {
-                filtered.register(name.clone(), Arc::clone(executor));
-            }
-        }
-        filtered
+        self.fork_where(|name| !excluded.contains(&name))
     }
 }
 
@@ -633,6 +1015,37 @@ mod tests {
         assert!(retrieved.is_some());
     }
 
+    #[test]
+    fn test_offline_registry_skips_network_tools() {
+        let mut registry = ToolRegistry::new().with_network_policy(NetworkPolicy::offline());
+        for name in ["fetch", "read_file"] {
+            registry.register(
+                name,
+                Arc::new(MockToolExecutor {
+                    name: name.to_string(),
+                }),
+            );
+        }
+
+        assert!(registry.get("fetch").is_none());
+        assert!(registry.get("read_file").is_some());
+
+        let cloned = registry.clone_without("read_file");
+        assert!(cloned.network_policy().is_offline());
+    }
+
+    #[test]
+    fn test_online_registry_keeps_network_tools() {
+        let mut registry = ToolRegistry::new();
+        registry.register(
+            "fetch",
+            Arc::new(MockToolExecutor {
+                name: "fetch".to_string(),
+            }),
+        );
+        assert!(registry.get("fetch").is_some());
+    }
+
     #[test]
     fn test_tool_registry_get_nonexistent() {
         let registry = ToolRegistry::new();
@@ -670,4 +1083,177 @@ mod tests {
         let registry = ToolRegistry::default();
         assert!(registry.is_empty());
     }
+
+    fn mock(name: &str) -> Arc<dyn ToolExecutor> {
+        Arc::new(MockToolExecutor {
+            name: name.to_string(),
+        })
+    }
+
+    fn advertised_names(registry: &ToolRegistry) -> Vec<String> {
+        let mut names: Vec<String> = registry
+            .all_definitions()
+            .iter()
+            .map(|definition| definition["name"].as_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    struct CountingTool {
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ToolExecutor for CountingTool {
+        fn tool_definition(&self) -> serde_json::Value {
+            serde_json::json!({"name": "counter", "description": "", "parameters": {}})
+        }
+
+        async fn execute(&self, _args: serde_json::Value) -> crate::error::Result<ToolResult> {
+            let calls = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Ok(ToolResult::success(calls.to_string()))
+        }
+    }
+
+    #[test]
+    fn test_clones_share_registrations() {
+        let mut registry = ToolRegistry::new();
+        let mut clone = registry.clone();
+
+        clone.register("late", mock("late"));
+        assert!(registry.get("late").is_some());
+
+        registry.remove("late");
+        assert!(clone.get("late").is_none());
+        assert!(clone.is_empty());
+    }
+
+    #[test]
+    fn test_fork_is_independent_but_shares_executors() {
+        let mut registry = ToolRegistry::new();
+        registry.register("test", mock("test"));
+
+        let mut fork = registry.fork();
+        fork.register("extra", mock("extra"));
+        fork.remove("test");
+
+        assert!(registry.get("test").is_some());
+        assert!(registry.get("extra").is_none());
+
+        let fork = registry.fork();
+        assert!(Arc::ptr_eq(
+            &registry.get("test").unwrap(),
+            &fork.get("test").unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_filtered_view_shares_executor_state() {
+        let mut registry = ToolRegistry::new();
+        registry.register(
+            "counter",
+            Arc::new(CountingTool {
+                calls: std::sync::atomic::AtomicUsize::new(0),
+            }),
+        );
+        registry.register("other", mock("other"));
+
+        let filtered = registry.filtered(&["counter", "missing"]);
+        assert_eq!(filtered.tool_names(), vec!["counter".to_string()]);
+
+        let first = filtered.get("counter").unwrap();
+        first.execute(serde_json::json!({})).await.unwrap();
+        let second = registry.get("counter").unwrap();
+        let result = second.execute(serde_json::json!({})).await.unwrap();
+        assert_eq!(result.output, "2");
+    }
+
+    #[test]
+    fn test_namespaced_lookup_by_short_and_qualified_name() {
+        let mut registry = ToolRegistry::new();
+        registry.register_namespaced("mcp.github", "create_issue", mock("create_issue"));
+
+        assert_eq!(
+            registry.tool_names(),
+            vec!["mcp__github__create_issue".to_string()]
+        );
+        assert!(registry.get("create_issue").is_some());
+        assert!(registry.get("mcp__github__create_issue").is_some());
+        assert!(registry.get("mcp.github.create_issue").is_some());
+        assert_eq!(advertised_names(&registry), vec!["create_issue"]);
+
+        let filtered = registry.filtered(&["create_issue"]);
+        assert!(filtered.get("mcp.github.create_issue").is_some());
+    }
+
+    #[test]
+    fn test_namespaced_collisions_are_qualified() {
+        let mut registry = ToolRegistry::new();
+        registry.register_namespaced("mcp.github", "search", mock("search"));
+        registry.register_namespaced("mcp.gitlab", "search", mock("search"));
+        registry.register_namespaced("mcp.gitlab", "list_merge_requests", mock("mr"));
+
+        assert!(registry.get("search").is_none());
+        assert!(registry.remove("search").is_none());
+        assert!(registry.get("mcp__github__search").is_some());
+        assert!(registry.get("mcp.gitlab.search").is_some());
+        assert!(registry.get("list_merge_requests").is_some());
+        assert_eq!(
+            advertised_names(&registry),
+            vec![
+                "list_merge_requests",
+                "mcp__github__search",
+                "mcp__gitlab__search"
+            ]
+        );
+
+        registry.remove("mcp.github.search");
+        assert!(registry.get("search").is_some());
+        assert_eq!(
+            advertised_names(&registry),
+            vec!["list_merge_requests", "search"]
+        );
+    }
+
+    #[test]
+    fn test_plain_tool_wins_over_namespaced_short_name() {
+        let mut registry = ToolRegistry::new();
+        registry.register("read_file", mock("read_file"));
+        registry.register_namespaced("mcp.fs", "read_file", mock("read_file"));
+
+        assert_eq!(registry.len(), 2);
+        assert_eq!(
+            advertised_names(&registry),
+            vec!["mcp__fs__read_file", "read_file"]
+        );
+        registry.remove("read_file");
+        assert_eq!(registry.tool_names(), vec!["mcp__fs__read_file"]);
+        assert!(registry.get("read_file").is_some());
+    }
+
+    #[test]
+    fn test_offline_registry_skips_namespaced_network_tools() {
+        let mut registry = ToolRegistry::new().with_network_policy(NetworkPolicy::offline());
+        registry.register_namespaced("web", "fetch", mock("fetch"));
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_read_only_registry_skips_terminal_and_is_inherited() {
+        let mut registry = ToolRegistry::new().with_read_only_policy(ReadOnlyPolicy::enforced());
+        registry.register("terminal", mock("terminal"));
+        registry.register_namespaced("ide", "terminal", mock("terminal"));
+        registry.register("read_file", mock("read_file"));
+        assert_eq!(registry.tool_names(), vec!["read_file".to_string()]);
+
+        let mut fork = registry.fork();
+        assert!(fork.read_only_policy().is_enabled());
+        fork.register("terminal", mock("terminal"));
+        assert!(fork.get("terminal").is_none());
+        assert!(registry
+            .filtered(&["read_file"])
+            .read_only_policy()
+            .is_enabled());
+    }
 }
@@ -3,11 +3,15 @@
 //! Moves or renames files and directories with cross-filesystem fallback.
 
 use crate::error::Result;
+use crate::tools::audit_log::{self, AuditLog, AuditOperation};
+use crate::tools::change_set::{ChangeKind, FileChange, StagedFiles};
+use crate::tools::confirmation::{path_scope, ActionCategory, ConfirmationPolicy};
 use crate::tools::file_utils::PathValidator;
 use crate::tools::{parse_tool_args, ToolExecutor, ToolResult, TOOL_MOVE_PATH};
 use async_trait::async_trait;
 use serde::Deserialize;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// Move path tool for moving or renaming files and directories
 ///
@@ -35,6 +39,8 @@ use std::path::{Path, PathBuf};
 /// ```
 pub struct MovePathTool {
     path_validator: PathValidator,
+    audit_log: Option<Arc<AuditLog>>,
+    confirmation: Option<ConfirmationPolicy>,
 }
 
 impl MovePathTool {
@@ -46,9 +52,23 @@ impl MovePathTool {
     pub fn new(working_dir: PathBuf) -> Self {
         Self {
             path_validator: PathValidator::new(working_dir),
+            audit_log: None,
+            confirmation: None,
         }
     }
 
+    /// Sets the audit log that records every move
+    pub fn with_audit_log(mut self, audit_log: Option<Arc<AuditLog>>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// Sets the policy that asks for confirmation in ConfirmOnce mode
+    pub fn with_confirmation(mut self, confirmation: Option<ConfirmationPolicy>) -> Self {
+        self.confirmation = confirmation;
+        self
+    }
+
     /// Returns the working directory
     pub fn working_dir(&self) -> &Path {
         self.path_validator.working_dir()
@@ -59,10 +79,41 @@ impl MovePathTool {
 struct MovePathParams {
     source_path: String,
     destination_path: String,
+    /// Set after the user approves a confirmation request
+    #[serde(default)]
+    confirm: bool,
 }
 
 #[async_trait]
 impl ToolExecutor for MovePathTool {
+    fn mutates(&self) -> bool {
+        true
+    }
+
+    fn preview_change(&self, args: &serde_json::Value, staged: &StagedFiles) -> Option<FileChange> {
+        let params: MovePathParams = serde_json::from_value(args.clone()).ok()?;
+        let source = self.path_validator.validate(&params.source_path).ok()?;
+        let destination = self
+            .path_validator
+            .validate(&params.destination_path)
+            .ok()?;
+        if !staged.exists(&source) || staged.exists(&destination) {
+            return None;
+        }
+        let change = FileChange::new(
+            params.destination_path,
+            ChangeKind::Move {
+                from: params.source_path,
+            },
+            destination,
+        )
+        .with_source(source.clone());
+        if staged.is_dir(&source) {
+            return Some(change.with_note("directory and all of its contents"));
+        }
+        Some(change.with_contents(None, staged.read(&source)))
+    }
+
     fn tool_definition(&self) -> serde_json::Value {
         serde_json::json!({
             "name": TOOL_MOVE_PATH,
@@ -77,6 +128,10 @@ impl ToolExecutor for MovePathTool {
                     "destination_path": {
                         "type": "string",
                         "description": "Relative path to the destination"
+                    },
+                    "confirm": {
+                        "type": "boolean",
+                        "description": "Set to true after the user approves a confirmation request"
                     }
                 },
                 "required": ["source_path", "destination_path"]
@@ -102,6 +157,17 @@ impl ToolExecutor for MovePathTool {
             }
         };
 
+        if let Some(refusal) = self.confirmation.as_ref().and_then(|policy| {
+            policy.check(
+                ActionCategory::FileWrite,
+                &path_scope(&params.destination_path),
+                &params.destination_path,
+                params.confirm,
+            )
+        }) {
+            return Ok(refusal);
+        }
+
         if !source.exists() {
             return Ok(ToolResult::error(format!(
                 "Source not found: {}",
@@ -116,6 +182,45 @@ impl ToolExecutor for MovePathTool {
             )));
         }
 
+        // The source hash is read before the move. Strict mode records the
+        // entry before the move, lenient mode once it succeeds
+        let audit = match &self.audit_log {
+            Some(log) => {
+                let hash = audit_log::file_fingerprint(&source)
+                    .await
+                    .map(|(_, hash)| hash);
+                let entry = log
+                    .entry(TOOL_MOVE_PATH, AuditOperation::Move, &source)
+                    .with_destination(&destination)
+                    .with_hashes(hash.clone(), hash);
+                Some((log, entry))
+            }
+            None => None,
+        };
+
+        if let Some(aborted) = audit
+            .as_ref()
+            .and_then(|(log, entry)| log.record_intent(entry))
+        {
+            return Ok(aborted);
+        }
+
+        let result = self.move_path(&source, &destination, &params).await?;
+        Ok(match audit {
+            Some((log, entry)) => log.record_outcome(&entry, result),
+            None => result,
+        })
+    }
+}
+
+impl MovePathTool {
+    /// Moves `source` to `destination`, copying and deleting across filesystems
+    async fn move_path(
+        &self,
+        source: &Path,
+        destination: &Path,
+        params: &MovePathParams,
+    ) -> Result<ToolResult> {
         // Create destination parent directories
         if let Some(parent) = destination.parent() {
             if !parent.exists() {
@@ -129,7 +234,7 @@ impl ToolExecutor for MovePathTool {
         }
 
         // Try direct rename first
-        if tokio::fs::rename(&source, &destination).await.is_ok() {
+        if tokio::fs::rename(source, destination).await.is_ok() {
             return Ok(ToolResult::success(format!(
                 "Moved {} to {}",
                 params.source_path, params.destination_path
@@ -138,9 +243,9 @@ impl ToolExecutor for MovePathTool {
 
         // Fallback: copy + delete for cross-filesystem moves
         if source.is_file() {
-            match tokio::fs::copy(&source, &destination).await {
+            match tokio::fs::copy(source, destination).await {
                 Ok(bytes) => {
-                    if let Err(e) = tokio::fs::remove_file(&source).await {
+                    if let Err(e) = tokio::fs::remove_file(source).await {
                         return Ok(ToolResult::error(format!(
                             "Copied file but failed to remove source: {}",
                             e
@@ -154,9 +259,9 @@ impl ToolExecutor for MovePathTool {
                 Err(e) => Ok(ToolResult::error(format!("Failed to move file: {}", e))),
             }
         } else if source.is_dir() {
-            match self.copy_directory_recursive(&source, &destination).await {
+            match self.copy_directory_recursive(source, destination).await {
                 Ok(count) => {
-                    if let Err(e) = tokio::fs::remove_dir_all(&source).await {
+                    if let Err(e) = tokio::fs::remove_dir_all(source).await {
                         return Ok(ToolResult::error(format!(
                             "Copied directory but failed to remove source: {}",
                             e
@@ -179,9 +284,7 @@ impl ToolExecutor for MovePathTool {
             )))
         }
     }
-}
 
-impl MovePathTool {
     /// Recursively copies a directory for cross-filesystem moves
     async fn copy_directory_recursive(&self, source: &Path, destination: &Path) -> Result<usize> {
         use walkdir::WalkDir;
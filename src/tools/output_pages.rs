@@ -0,0 +1,575 @@
+//! Paged tool output
+//!
+//! Output over `tools.max_output_size` used to be cut at the limit, so the
+//! agent never saw the rest. With paging, the full output is kept in a
+//! session-scoped store and the agent receives the first page with a note
+//! naming the id to read more:
+//!
+//! ```text
+//! (output paged: 4 pages, call tool `read_tool_output` with id=out_... page=2)
+//! ```
+//!
+//! Pages end at line boundaries, so a page never splits a line unless the
+//! line alone is longer than a page. Outputs stay in memory while the total
+//! is within `tools.paging.memory_bytes` and are spilled to a private
+//! temporary directory beyond that. The store holds at most
+//! `tools.paging.max_outputs` outputs and evicts the least recently used
+//! one first. Everything is removed when the session ends.
+//!
+//! Ids are random, so one session cannot read another's output by guessing.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::config::ToolOutputPagingConfig;
+use crate::error::{Result, XzatomaError};
+use crate::tools::overflow_summary::split_chunks;
+use crate::tools::{parse_tool_args, ToolExecutor, ToolResult};
+
+/// Name of the tool that reads later pages
+pub const READ_TOOL_OUTPUT_TOOL_NAME: &str = "read_tool_output";
+
+/// Metadata key holding the id of a paged output
+pub const PAGED_OUTPUT_ID_METADATA: &str = "paged_output_id";
+
+/// Metadata key holding the number of pages of a paged output
+pub const OUTPUT_PAGES_METADATA: &str = "output_pages";
+
+/// Default number of outputs kept per session
+pub const DEFAULT_MAX_OUTPUTS: usize = 32;
+
+/// Default number of output bytes kept in memory before spilling to disk
+pub const DEFAULT_MEMORY_BYTES: usize = 16 * 1024 * 1024;
+
+/// Bytes kept free on every page for the paging note
+const NOTE_RESERVE: usize = 256;
+
+/// Where a stored output lives
+enum Content {
+    Memory(String),
+    Disk(PathBuf),
+}
+
+/// A stored output with the byte ranges of its pages
+struct StoredOutput {
+    tool: String,
+    content: Content,
+    pages: Vec<Range<usize>>,
+}
+
+impl StoredOutput {
+    fn memory_len(&self) -> usize {
+        match &self.content {
+            Content::Memory(text) => text.len(),
+            Content::Disk(_) => 0,
+        }
+    }
+
+    fn page_text(&self, range: Range<usize>) -> Result<String> {
+        match &self.content {
+            Content::Memory(text) => Ok(text[range].to_string()),
+            Content::Disk(path) => {
+                let mut file = std::fs::File::open(path)?;
+                file.seek(SeekFrom::Start(range.start as u64))?;
+                let mut bytes = vec![0; range.len()];
+                file.read_exact(&mut bytes)?;
+                String::from_utf8(bytes).map_err(|e| {
+                    XzatomaError::Tool(format!("Stored tool output is not valid UTF-8: {}", e))
+                })
+            }
+        }
+    }
+
+    fn discard(self) {
+        if let Content::Disk(path) = self.content {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Entries {
+    /// Ids from least to most recently used
+    order: VecDeque<String>,
+    outputs: HashMap<String, StoredOutput>,
+    memory_bytes: usize,
+}
+
+impl Entries {
+    fn evict_oldest(&mut self) {
+        if let Some(id) = self.order.pop_front() {
+            if let Some(output) = self.outputs.remove(&id) {
+                tracing::debug!("Evicting paged {} output {}", output.tool, id);
+                self.memory_bytes -= output.memory_len();
+                output.discard();
+            }
+        }
+    }
+
+    fn touch(&mut self, id: &str) {
+        if let Some(position) = self.order.iter().position(|entry| entry == id) {
+            if let Some(entry) = self.order.remove(position) {
+                self.order.push_back(entry);
+            }
+        }
+    }
+}
+
+/// One page read from a paged output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page {
+    /// Text of the page
+    pub text: String,
+    /// Page number, starting at 1
+    pub number: usize,
+    /// Number of pages in the output
+    pub total: usize,
+    /// Tool that produced the output
+    pub tool: String,
+}
+
+/// Session-scoped store of tool output split into pages
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::tools::output_pages::{OutputPager, PAGED_OUTPUT_ID_METADATA};
+/// use xzatoma::tools::ToolResult;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let pager = OutputPager::new(8, 1024 * 1024).with_spill_dir(dir.path().join("pages"));
+/// let log = "warning: unused variable\n".repeat(100);
+///
+/// let first = pager.page_result("terminal", ToolResult::success(log.clone()), 1024);
+/// assert!(first.output.contains("output paged"));
+/// let id = &first.metadata[PAGED_OUTPUT_ID_METADATA];
+/// assert_eq!(pager.read(id, 2).unwrap().number, 2);
+/// ```
+pub struct OutputPager {
+    max_outputs: usize,
+    memory_bytes: usize,
+    spill_dir: PathBuf,
+    entries: Mutex<Entries>,
+}
+
+impl OutputPager {
+    /// Creates an empty store
+    ///
+    /// # Arguments
+    ///
+    /// * `max_outputs` - Outputs kept before the least recently used is evicted
+    /// * `memory_bytes` - Output bytes kept in memory before spilling to disk
+    pub fn new(max_outputs: usize, memory_bytes: usize) -> Self {
+        Self {
+            max_outputs: max_outputs.max(1),
+            memory_bytes,
+            spill_dir: std::env::temp_dir().join(format!(
+                "xzatoma-tool-output-{}",
+                uuid::Uuid::new_v4().simple()
+            )),
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Creates the store configured by `tools.paging`
+    pub fn from_config(config: &ToolOutputPagingConfig) -> Self {
+        Self::new(config.max_outputs, config.memory_bytes)
+    }
+
+    /// Sets the directory outputs are spilled to
+    ///
+    /// The directory is created when the first output is spilled and
+    /// removed with the store.
+    pub fn with_spill_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spill_dir = dir.into();
+        self
+    }
+
+    /// Returns the directory outputs are spilled to
+    pub fn spill_dir(&self) -> &Path {
+        &self.spill_dir
+    }
+
+    /// Fits a tool result to `max_size` by paging it
+    ///
+    /// Results within the limit, and failed results, are returned as they
+    /// are. Otherwise the full output is stored and the result holds the
+    /// first page followed by the paging note. When the output cannot be
+    /// stored, it is truncated instead.
+    pub fn page_result(&self, tool_name: &str, result: ToolResult, max_size: usize) -> ToolResult {
+        if !result.success || result.output.len() <= max_size {
+            return result;
+        }
+
+        let page_bytes = max_size.saturating_sub(NOTE_RESERVE).max(1);
+        let mut start = 0;
+        let pages: Vec<Range<usize>> = split_chunks(&result.output, page_bytes)
+            .into_iter()
+            .map(|page| {
+                let range = start..start + page.len();
+                start = range.end;
+                range
+            })
+            .collect();
+        let total = pages.len();
+        let first_page = result.output[pages[0].clone()].to_string();
+
+        let mut result = result;
+        let output = std::mem::take(&mut result.output);
+        let id = format!("out_{}", uuid::Uuid::new_v4().simple());
+        if let Err(e) = self.store(&id, tool_name, output.clone(), pages) {
+            tracing::warn!("Could not store the full {} output: {}", tool_name, e);
+            result.output = output;
+            return result.truncate_if_needed(max_size);
+        }
+
+        result.output = format!(
+            "{}\n\n(output paged: {} pages, call tool `{}` with id={} page=2)",
+            first_page.trim_end_matches('\n'),
+            total,
+            READ_TOOL_OUTPUT_TOOL_NAME,
+            id
+        );
+        result
+            .with_metadata(PAGED_OUTPUT_ID_METADATA.to_string(), id)
+            .with_metadata(OUTPUT_PAGES_METADATA.to_string(), total.to_string())
+    }
+
+    /// Reads page `number` of the output stored under `id`
+    ///
+    /// Reading an output marks it as recently used.
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Tool` when no output is stored under `id`,
+    /// because it was evicted, the session ended, or the id is wrong, or
+    /// when the page number is out of range.
+    pub fn read(&self, id: &str, number: usize) -> Result<Page> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(output) = entries.outputs.get(id) else {
+            return Err(XzatomaError::Tool(format!(
+                "No paged output with id '{}': it expired or was never stored. Run the tool again to see its output",
+                id
+            )));
+        };
+        let total = output.pages.len();
+        if number == 0 || number > total {
+            return Err(XzatomaError::Tool(format!(
+                "Page {} is out of range: output {} has pages 1 to {}",
+                number, id, total
+            )));
+        }
+        let page = Page {
+            text: output.page_text(output.pages[number - 1].clone())?,
+            number,
+            total,
+            tool: output.tool.clone(),
+        };
+        entries.touch(id);
+        Ok(page)
+    }
+
+    /// Returns the number of stored outputs
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .outputs
+            .len()
+    }
+
+    /// Returns true when no output is stored
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes every stored output and the spill directory
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        for (_, output) in entries.outputs.drain() {
+            output.discard();
+        }
+        entries.order.clear();
+        entries.memory_bytes = 0;
+        match std::fs::remove_dir(&self.spill_dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::debug!("Could not remove {}: {}", self.spill_dir.display(), e),
+        }
+    }
+
+    fn store(
+        &self,
+        id: &str,
+        tool_name: &str,
+        output: String,
+        pages: Vec<Range<usize>>,
+    ) -> Result<()> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        while entries.outputs.len() >= self.max_outputs {
+            entries.evict_oldest();
+        }
+
+        let content = if entries.memory_bytes + output.len() <= self.memory_bytes {
+            entries.memory_bytes += output.len();
+            Content::Memory(output)
+        } else {
+            let mut builder = std::fs::DirBuilder::new();
+            builder.recursive(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::DirBuilderExt;
+                builder.mode(0o700);
+            }
+            builder.create(&self.spill_dir)?;
+            let path = self.spill_dir.join(format!("{}.txt", id));
+            std::fs::write(&path, output)?;
+            Content::Disk(path)
+        };
+
+        entries.order.push_back(id.to_string());
+        entries.outputs.insert(
+            id.to_string(),
+            StoredOutput {
+                tool: tool_name.to_string(),
+                content,
+                pages,
+            },
+        );
+        Ok(())
+    }
+}
+
+impl Drop for OutputPager {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// Tool that returns later pages of paged tool output
+pub struct ReadToolOutputTool {
+    pager: Arc<OutputPager>,
+}
+
+impl ReadToolOutputTool {
+    /// Creates the tool reading from `pager`
+    pub fn new(pager: Arc<OutputPager>) -> Self {
+        Self { pager }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ReadToolOutputParams {
+    id: String,
+    page: usize,
+}
+
+#[async_trait]
+impl ToolExecutor for ReadToolOutputTool {
+    fn tool_definition(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": READ_TOOL_OUTPUT_TOOL_NAME,
+            "description": "Reads one page of tool output that was too long to return at once. A paged result shows its first page and ends with a note like (output paged: N pages, call tool `read_tool_output` with id=... page=2). Pass that id and the page to read. Pages end at line boundaries. Paged output is kept for a limited number of outputs during this session only.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "The id from the paging note"
+                    },
+                    "page": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Page number to read, starting at 1"
+                    }
+                },
+                "required": ["id", "page"]
+            }
+        })
+    }
+
+    async fn execute(&self, args: serde_json::Value) -> Result<ToolResult> {
+        let params: ReadToolOutputParams = parse_tool_args(args)?;
+        let page = match self.pager.read(&params.id, params.page) {
+            Ok(page) => page,
+            Err(e) => return Ok(ToolResult::error(e.to_string())),
+        };
+
+        let footer = if page.number < page.total {
+            format!(
+                "(page {} of {}, call tool `{}` with id={} page={} for more)",
+                page.number,
+                page.total,
+                READ_TOOL_OUTPUT_TOOL_NAME,
+                params.id,
+                page.number + 1
+            )
+        } else {
+            format!("(page {} of {}, end of output)", page.number, page.total)
+        };
+        Ok(ToolResult::success(format!(
+            "{}\n\n{}",
+            page.text.trim_end_matches('\n'),
+            footer
+        ))
+        .with_metadata(PAGED_OUTPUT_ID_METADATA.to_string(), params.id)
+        .with_metadata(OUTPUT_PAGES_METADATA.to_string(), page.total.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Build log of numbered lines, each 20 bytes long
+    fn build_log(lines: usize) -> String {
+        (0..lines)
+            .map(|n| format!("line {:05} of output\n", n))
+            .collect()
+    }
+
+    fn pager(dir: &TempDir, max_outputs: usize, memory_bytes: usize) -> OutputPager {
+        OutputPager::new(max_outputs, memory_bytes).with_spill_dir(dir.path().join("pages"))
+    }
+
+    fn paged_id(result: &ToolResult) -> String {
+        result.metadata[PAGED_OUTPUT_ID_METADATA].clone()
+    }
+
+    #[tokio::test]
+    async fn test_every_page_can_be_read_back_at_line_boundaries() {
+        let dir = TempDir::new().unwrap();
+        let pager = Arc::new(pager(&dir, 4, 1024 * 1024));
+        let log = build_log(60);
+        let max_size = NOTE_RESERVE + 200;
+
+        let first = pager.page_result("terminal", ToolResult::success(log.clone()), max_size);
+        assert!(first.success);
+        assert!(!first.truncated);
+        assert!(first.output.len() <= max_size);
+        let id = paged_id(&first);
+        assert!(first.output.ends_with(&format!(
+            "(output paged: 6 pages, call tool `read_tool_output` with id={} page=2)",
+            id
+        )));
+
+        let mut pages = Vec::new();
+        for number in 1..=6 {
+            let page = pager.read(&id, number).unwrap();
+            assert_eq!((page.number, page.total), (number, 6));
+            assert!(page.text.ends_with('\n'));
+            pages.push(page.text);
+        }
+        assert_eq!(pages.concat(), log);
+
+        let tool = ReadToolOutputTool::new(Arc::clone(&pager));
+        let second = tool
+            .execute(serde_json::json!({"id": id, "page": 2}))
+            .await
+            .unwrap();
+        assert!(second.output.starts_with("line 00010 of output"));
+        assert!(second.output.ends_with("page=3 for more)"));
+        let last = tool
+            .execute(serde_json::json!({"id": id, "page": 6}))
+            .await
+            .unwrap();
+        assert!(last.output.ends_with("(page 6 of 6, end of output)"));
+    }
+
+    #[test]
+    fn test_least_recently_used_output_is_evicted() {
+        let dir = TempDir::new().unwrap();
+        let pager = pager(&dir, 2, 1024 * 1024);
+        let max_size = NOTE_RESERVE + 100;
+        let ids: Vec<String> = (0..3)
+            .map(|_| {
+                paged_id(&pager.page_result("grep", ToolResult::success(build_log(20)), max_size))
+            })
+            .collect();
+
+        assert_eq!(pager.len(), 2);
+        assert!(pager.read(&ids[0], 1).is_err());
+
+        // Reading the second output makes the third the oldest
+        pager.read(&ids[1], 1).unwrap();
+        let newest =
+            paged_id(&pager.page_result("grep", ToolResult::success(build_log(20)), max_size));
+        assert!(pager.read(&ids[1], 2).is_ok());
+        assert!(pager.read(&ids[2], 1).is_err());
+        assert!(pager.read(&newest, 1).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_expired_id_and_missing_page_are_reported_as_errors() {
+        let dir = TempDir::new().unwrap();
+        let pager = Arc::new(pager(&dir, 4, 1024 * 1024));
+        let id = paged_id(&pager.page_result(
+            "terminal",
+            ToolResult::success(build_log(30)),
+            NOTE_RESERVE + 200,
+        ));
+        let tool = ReadToolOutputTool::new(Arc::clone(&pager));
+
+        let result = tool
+            .execute(serde_json::json!({"id": id, "page": 9}))
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("out of range"));
+
+        pager.clear();
+        let result = tool
+            .execute(serde_json::json!({"id": id, "page": 2}))
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("expired"));
+    }
+
+    #[test]
+    fn test_outputs_over_the_memory_budget_spill_to_disk() {
+        let dir = TempDir::new().unwrap();
+        let spill_dir = dir.path().join("pages");
+        let log = build_log(40);
+        {
+            let pager = pager(&dir, 4, log.len());
+            let max_size = NOTE_RESERVE + 300;
+            let in_memory = paged_id(&pager.page_result(
+                "terminal",
+                ToolResult::success(log.clone()),
+                max_size,
+            ));
+            assert!(!spill_dir.exists());
+            let spilled = paged_id(&pager.page_result(
+                "terminal",
+                ToolResult::success(log.clone()),
+                max_size,
+            ));
+            assert!(spill_dir.join(format!("{}.txt", spilled)).exists());
+            assert_eq!(
+                pager.read(&spilled, 2).unwrap().text,
+                pager.read(&in_memory, 2).unwrap().text
+            );
+        }
+        // Dropping the store at the end of the session removes the spill files
+        assert!(!spill_dir.exists());
+    }
+
+    #[test]
+    fn test_small_and_failed_results_are_left_alone() {
+        let dir = TempDir::new().unwrap();
+        let pager = pager(&dir, 4, 1024);
+        let result = pager.page_result("terminal", ToolResult::success("ok"), 1024);
+        assert_eq!(result.output, "ok");
+        let result = pager.page_result("terminal", ToolResult::error("x".repeat(4096)), 1024);
+        assert!(!result.success);
+        assert!(pager.is_empty());
+    }
+}
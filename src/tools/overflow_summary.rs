@@ -0,0 +1,555 @@
+//! Summaries for tool output over the size limit
+//!
+//! Truncating a long build log or a large grep result keeps the start and
+//! throws away the rest, often including the errors the agent needs. With
+//! `agent.tools.summarize_overflow` set, oversized output is sent to the
+//! summary model instead, in bounded chunks, with a prompt chosen for the
+//! kind of output, and the summary replaces the raw output.
+//!
+//! The original output is stored under the data directory first. Its ID is
+//! placed in the result metadata (`output_id`) and in the summary header, and
+//! `xzatoma tool-output show <id>` prints it. When the summary model cannot
+//! be reached, the output is truncated as before.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use regex::Regex;
+
+use crate::error::{Result, XzatomaError};
+use crate::paths::Paths;
+use crate::providers::{Message, Provider, TokenUsage};
+use crate::tools::ToolResult;
+
+/// Default size of one chunk sent to the summary model, in bytes
+pub const DEFAULT_CHUNK_BYTES: usize = 32 * 1024;
+
+/// Default number of chunks summarized per output
+pub const DEFAULT_MAX_CHUNKS: usize = 8;
+
+/// Metadata key holding the ID of the stored original output
+pub const OUTPUT_ID_METADATA: &str = "output_id";
+
+/// Metadata key set to `true` when the output was replaced by a summary
+pub const SUMMARIZED_METADATA: &str = "summarized";
+
+const COMPILER_PROMPT: &str = "You summarize build, compiler, and test output for a coding agent that cannot see the original. \
+Keep every error and warning with its file:line location and message, verbatim where possible. \
+Keep the names of failing tests and the final result line. \
+Drop progress lines, passing tests, and repeated notes. Reply with the summary only.";
+
+const SEARCH_PROMPT: &str =
+    "You summarize search results for a coding agent that cannot see the original. \
+Give the total number of matches and the number of matches per file, most matches first. \
+Quote a few representative matching lines with their file:line. Reply with the summary only.";
+
+const GENERIC_PROMPT: &str =
+    "You summarize tool output for a coding agent that cannot see the original. \
+Keep errors, file paths, identifiers, numbers, and anything the agent may need to act on. \
+Drop repetition. Reply with the summary only.";
+
+/// Kind of tool output, which selects the summarization prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputKind {
+    /// Compiler, build, or test runner output
+    Compiler,
+    /// Search results, such as grep matches
+    Search,
+    /// Anything else
+    Generic,
+}
+
+impl OutputKind {
+    /// Picks the kind from the tool name and the output
+    ///
+    /// Search tools give [`OutputKind::Search`]. Any other output with
+    /// compiler-style diagnostics (`error:`, `warning[W1]:`, or
+    /// `file:line:col: error`) gives [`OutputKind::Compiler`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::tools::overflow_summary::OutputKind;
+    ///
+    /// assert_eq!(OutputKind::detect("grep", "src/a.rs:1: fn main"), OutputKind::Search);
+    /// assert_eq!(
+    ///     OutputKind::detect("terminal", "error[E0425]: cannot find value `x`"),
+    ///     OutputKind::Compiler
+    /// );
+    /// assert_eq!(OutputKind::detect("terminal", "total 0"), OutputKind::Generic);
+    /// ```
+    pub fn detect(tool_name: &str, output: &str) -> Self {
+        static DIAGNOSTIC: OnceLock<Regex> = OnceLock::new();
+        let diagnostic = DIAGNOSTIC.get_or_init(|| {
+            Regex::new(r"(?m)^\s*(error|warning)(\[[\w-]+\])?:|:\d+:\d+: (fatal )?(error|warning)")
+                .expect("diagnostic pattern is valid")
+        });
+        if tool_name.contains("grep") || tool_name.contains("search") {
+            Self::Search
+        } else if diagnostic.is_match(output) {
+            Self::Compiler
+        } else {
+            Self::Generic
+        }
+    }
+
+    /// System prompt for summarizing this kind of output
+    pub fn prompt(self) -> &'static str {
+        match self {
+            Self::Compiler => COMPILER_PROMPT,
+            Self::Search => SEARCH_PROMPT,
+            Self::Generic => GENERIC_PROMPT,
+        }
+    }
+}
+
+/// Original tool outputs kept under `tool_outputs` in the data directory
+#[derive(Debug, Clone)]
+pub struct ToolOutputStore {
+    dir: PathBuf,
+}
+
+impl ToolOutputStore {
+    /// Creates a store in `dir`
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Creates the store at its default location
+    pub fn from_paths(paths: &Paths) -> Self {
+        Self::new(paths.tool_outputs_dir())
+    }
+
+    /// Returns the store directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Stores an output and returns its ID
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the file cannot be written.
+    pub fn save(&self, output: &str) -> Result<String> {
+        std::fs::create_dir_all(&self.dir)?;
+        let id = uuid::Uuid::new_v4().simple().to_string()[..12].to_string();
+        std::fs::write(self.path_for(&id), output)?;
+        Ok(id)
+    }
+
+    /// Loads a stored output
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::FileLoad` when `id` is malformed or no output
+    /// is stored under it.
+    pub fn load(&self, id: &str) -> Result<String> {
+        let not_found = || {
+            XzatomaError::FileLoad(format!(
+                "No stored tool output with ID '{}' in {}",
+                id,
+                self.dir.display()
+            ))
+        };
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(not_found());
+        }
+        match std::fs::read_to_string(self.path_for(id)) {
+            Ok(output) => Ok(output),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(not_found()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn path_for(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.txt", id))
+    }
+}
+
+/// Tool result after [`OverflowSummarizer::shrink`], with the summary cost
+#[derive(Debug, Clone)]
+pub struct ShrunkResult {
+    /// The summarized or truncated result
+    pub result: ToolResult,
+    /// Tokens spent on the summary, `None` when no summary was made
+    pub usage: Option<TokenUsage>,
+}
+
+/// Replaces oversized tool output with a summary from the summary model
+pub struct OverflowSummarizer {
+    provider: Arc<dyn Provider>,
+    store: ToolOutputStore,
+    chunk_bytes: usize,
+    max_chunks: usize,
+}
+
+impl OverflowSummarizer {
+    /// Creates a summarizer with the default chunking
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - Provider set to the summary model
+    /// * `store` - Where the original outputs are kept
+    pub fn new(provider: Arc<dyn Provider>, store: ToolOutputStore) -> Self {
+        Self {
+            provider,
+            store,
+            chunk_bytes: DEFAULT_CHUNK_BYTES,
+            max_chunks: DEFAULT_MAX_CHUNKS,
+        }
+    }
+
+    /// Sets the chunk size and the number of chunks summarized per output
+    ///
+    /// Output beyond `max_chunks` chunks is summarized from its first and
+    /// last chunks, and the middle is left out.
+    pub fn with_chunking(mut self, chunk_bytes: usize, max_chunks: usize) -> Self {
+        self.chunk_bytes = chunk_bytes.max(1);
+        self.max_chunks = max_chunks.max(1);
+        self
+    }
+
+    /// Returns the store holding the original outputs
+    pub fn store(&self) -> &ToolOutputStore {
+        &self.store
+    }
+
+    /// Fits a tool result to `max_size`
+    ///
+    /// Results within the limit, and failed results, are returned as they
+    /// are. Otherwise the original output is stored and replaced by a
+    /// summary. When storing or summarizing fails, the output is truncated
+    /// instead.
+    pub async fn shrink(
+        &self,
+        tool_name: &str,
+        result: ToolResult,
+        max_size: usize,
+    ) -> ShrunkResult {
+        if !result.success || result.output.len() <= max_size {
+            return ShrunkResult {
+                result,
+                usage: None,
+            };
+        }
+
+        let output_id = match self.store.save(&result.output) {
+            Ok(id) => id,
+            Err(e) => {
+                tracing::warn!("Could not store the full {} output: {}", tool_name, e);
+                return ShrunkResult {
+                    result: result.truncate_if_needed(max_size),
+                    usage: None,
+                };
+            }
+        };
+        let result = result.with_metadata(OUTPUT_ID_METADATA.to_string(), output_id.clone());
+
+        match self.summarize(tool_name, &result.output).await {
+            Ok((summary, usage)) => {
+                let output = format!(
+                    "[Summary of {} bytes of {} output, over the {}-byte limit. Full output: `xzatoma tool-output show {}`]\n\n{}",
+                    result.output.len(),
+                    tool_name,
+                    max_size,
+                    output_id,
+                    summary
+                );
+                let mut result =
+                    result.with_metadata(SUMMARIZED_METADATA.to_string(), "true".to_string());
+                result.output = output;
+                ShrunkResult {
+                    result: result.truncate_if_needed(max_size),
+                    usage,
+                }
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Could not summarize {} output ({}); truncating it instead",
+                    tool_name,
+                    e
+                );
+                ShrunkResult {
+                    result: result.truncate_if_needed(max_size),
+                    usage: None,
+                }
+            }
+        }
+    }
+
+    /// Summarizes `output` chunk by chunk
+    ///
+    /// Returns the summary and the tokens spent, summed over all chunks.
+    ///
+    /// # Errors
+    ///
+    /// Returns the provider error, or `XzatomaError::Provider` when the
+    /// model returns no text.
+    pub async fn summarize(
+        &self,
+        tool_name: &str,
+        output: &str,
+    ) -> Result<(String, Option<TokenUsage>)> {
+        let kind = OutputKind::detect(tool_name, output);
+        let chunks = split_chunks(output, self.chunk_bytes);
+        let total = chunks.len();
+        let selected = select_chunks(total, self.max_chunks);
+
+        let mut usage: Option<TokenUsage> = None;
+        let mut parts = Vec::with_capacity(selected.len());
+        for index in selected {
+            let request = format!(
+                "Output of the `{}` tool, part {} of {}:\n\n{}",
+                tool_name,
+                index + 1,
+                total,
+                chunks[index]
+            );
+            let response = self
+                .provider
+                .complete(
+                    &[Message::system(kind.prompt()), Message::user(request)],
+                    &[],
+                )
+                .await?;
+            if let Some(chunk_usage) = response.usage {
+                usage = Some(match usage {
+                    Some(sum) => TokenUsage::new(
+                        sum.prompt_tokens + chunk_usage.prompt_tokens,
+                        sum.completion_tokens + chunk_usage.completion_tokens,
+                    ),
+                    None => chunk_usage,
+                });
+            }
+            let text = response
+                .message
+                .content
+                .filter(|text| !text.trim().is_empty())
+                .ok_or_else(|| {
+                    XzatomaError::Provider("The summary model returned no text".to_string())
+                })?;
+            parts.push((index, text.trim().to_string()));
+        }
+
+        if total == 1 {
+            return Ok((parts.remove(0).1, usage));
+        }
+        let mut summary = Vec::with_capacity(parts.len() + 1);
+        let mut previous = None;
+        for (index, text) in parts {
+            if previous.is_some_and(|previous| index > previous + 1) {
+                summary.push(format!(
+                    "(Parts {} to {} were not summarized.)",
+                    previous.unwrap_or_default() + 2,
+                    index
+                ));
+            }
+            summary.push(format!("Part {} of {}:\n{}", index + 1, total, text));
+            previous = Some(index);
+        }
+        Ok((summary.join("\n\n"), usage))
+    }
+}
+
+/// Splits `text` into chunks of at most `max_bytes`, at line ends when
+/// possible and always at character boundaries
+pub(crate) fn split_chunks(text: &str, max_bytes: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    while rest.len() > max_bytes {
+        let mut end = max_bytes;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if let Some(newline) = rest[..end].rfind('\n') {
+            end = newline + 1;
+        }
+        if end == 0 {
+            // A single character longer than the chunk size
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        chunks.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    if !rest.is_empty() || chunks.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+/// Indexes of the chunks to summarize: all of them, or the first and last
+/// ones when there are more than `max_chunks`
+fn select_chunks(total: usize, max_chunks: usize) -> Vec<usize> {
+    if total <= max_chunks {
+        return (0..total).collect();
+    }
+    let head = max_chunks / 2;
+    let tail = max_chunks - head;
+    (0..head).chain(total - tail..total).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::CompletionResponse;
+    use crate::testing::MockProvider;
+    use tempfile::TempDir;
+
+    /// Summary model that answers with `summaries` in order
+    fn canned_summaries(summaries: &[&str]) -> MockProvider {
+        summaries.iter().fold(
+            MockProvider::new().with_model("summary-model"),
+            |provider, summary| {
+                provider.then_response(CompletionResponse::with_usage(
+                    Message::assistant(*summary),
+                    TokenUsage::new(100, 10),
+                ))
+            },
+        )
+    }
+
+    /// Summary model whose host cannot be reached
+    fn unreachable() -> Arc<MockProvider> {
+        Arc::new(
+            MockProvider::new().then_failure(XzatomaError::NetworkUnreachable {
+                endpoint: "http://localhost:11434".to_string(),
+                reason: "connection refused".to_string(),
+            }),
+        )
+    }
+
+    fn build_log(lines: usize) -> String {
+        let mut log = String::new();
+        for i in 0..lines {
+            log.push_str(&format!("   Compiling crate-{} v0.1.0\n", i));
+        }
+        log.push_str("error[E0425]: cannot find value `x` in this scope\n --> src/main.rs:3:5\n");
+        log
+    }
+
+    #[tokio::test]
+    async fn test_oversized_output_is_replaced_by_summary_and_stored() {
+        let dir = TempDir::new().unwrap();
+        let provider = Arc::new(canned_summaries(&[
+            "error[E0425] at src/main.rs:3:5: cannot find value `x`",
+        ]));
+        let summarizer =
+            OverflowSummarizer::new(provider.clone(), ToolOutputStore::new(dir.path()));
+        let log = build_log(200);
+
+        let shrunk = summarizer
+            .shrink("terminal", ToolResult::success(log.clone()), 1024)
+            .await;
+
+        let result = shrunk.result;
+        assert!(!result.truncated);
+        assert_eq!(result.metadata[SUMMARIZED_METADATA], "true");
+        assert!(result.output.contains("src/main.rs:3:5"));
+        assert!(result.output.len() <= 1024);
+        let id = &result.metadata[OUTPUT_ID_METADATA];
+        assert!(result
+            .output
+            .contains(&format!("xzatoma tool-output show {}", id)));
+        assert_eq!(summarizer.store().load(id).unwrap(), log);
+
+        let usage = shrunk.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (100, 10));
+        let requests = provider.requests();
+        assert_eq!(
+            requests[0].messages[0].content.as_deref(),
+            Some(COMPILER_PROMPT)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_output_is_summarized_in_bounded_chunks() {
+        let dir = TempDir::new().unwrap();
+        let provider = Arc::new(canned_summaries(&[
+            "a.rs: 40 matches",
+            "b.rs: 12 matches",
+            "z.rs: 3 matches",
+        ]));
+        let summarizer =
+            OverflowSummarizer::new(provider.clone(), ToolOutputStore::new(dir.path()))
+                .with_chunking(1000, 3);
+        let matches: String = (0..400).map(|i| format!("src/{}.rs:1: hit\n", i)).collect();
+
+        let shrunk = summarizer
+            .shrink("grep", ToolResult::success(matches), 2_000)
+            .await;
+
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests
+            .iter()
+            .all(|request| request.messages[0].content.as_deref() == Some(SEARCH_PROMPT)));
+        assert!(requests
+            .iter()
+            .all(|request| request.messages[1].content.as_ref().unwrap().len() < 1100));
+        let output = &shrunk.result.output;
+        assert!(output.contains("Part 1 of "));
+        assert!(output.contains("a.rs: 40 matches\n\n(Parts 2 to "));
+        assert!(output.contains("were not summarized.)\n\nPart "));
+        assert!(output.ends_with("z.rs: 3 matches"));
+        let usage = shrunk.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (300, 30));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_provider_falls_back_to_truncation() {
+        let dir = TempDir::new().unwrap();
+        let summarizer = OverflowSummarizer::new(unreachable(), ToolOutputStore::new(dir.path()));
+        let log = build_log(200);
+
+        let shrunk = summarizer
+            .shrink("terminal", ToolResult::success(log.clone()), 1024)
+            .await;
+
+        assert!(shrunk.usage.is_none());
+        assert!(shrunk.result.truncated);
+        assert!(!shrunk.result.metadata.contains_key(SUMMARIZED_METADATA));
+        let id = &shrunk.result.metadata[OUTPUT_ID_METADATA];
+        assert_eq!(summarizer.store().load(id).unwrap(), log);
+    }
+
+    #[tokio::test]
+    async fn test_small_and_failed_results_are_left_alone() {
+        let dir = TempDir::new().unwrap();
+        let summarizer = OverflowSummarizer::new(unreachable(), ToolOutputStore::new(dir.path()));
+
+        let shrunk = summarizer
+            .shrink("terminal", ToolResult::success("ok"), 1024)
+            .await;
+        assert_eq!(shrunk.result.output, "ok");
+        let shrunk = summarizer
+            .shrink("terminal", ToolResult::error("x".repeat(4096)), 1024)
+            .await;
+        assert!(!shrunk.result.success);
+        assert!(std::fs::read_dir(dir.path()).unwrap().next().is_none());
+    }
+
+    #[test]
+    fn test_store_rejects_unknown_and_malformed_ids() {
+        let dir = TempDir::new().unwrap();
+        let store = ToolOutputStore::new(dir.path());
+        let id = store.save("full output").unwrap();
+        assert_eq!(store.load(&id).unwrap(), "full output");
+        assert!(matches!(
+            store.load("0123456789ab"),
+            Err(XzatomaError::FileLoad(_))
+        ));
+        assert!(matches!(
+            store.load("../history"),
+            Err(XzatomaError::FileLoad(_))
+        ));
+    }
+
+    #[test]
+    fn test_split_chunks_prefers_line_ends_and_char_boundaries() {
+        assert_eq!(split_chunks("ab\ncd\nef", 5), vec!["ab\n", "cd\nef"]);
+        assert_eq!(split_chunks("ééé", 3), vec!["é", "é", "é"]);
+        assert_eq!(split_chunks("", 3), vec![""]);
+        assert_eq!(select_chunks(10, 3), vec![0, 8, 9]);
+        assert_eq!(select_chunks(2, 3), vec![0, 1]);
+    }
+}
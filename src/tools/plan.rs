@@ -2,9 +2,16 @@
 //!
 //! This module provides plan file parsing functionality.
 //! Phase 5 implementation: YAML, JSON, Markdown parsing and validation.
+//!
+//! YAML and JSON plans are validated against the plan JSON Schema before
+//! deserialization; see [`crate::tools::plan_validation`].
 
+use crate::config::ExecutionMode;
 use crate::error::{Result, XzatomaError};
+use crate::tools::plan_markdown;
+use crate::tools::plan_validation::{self, PlanIssue, PlanLocation, PlanValidationError};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::fs;
 use std::path::Path;
 
@@ -47,6 +54,24 @@ pub struct PlanStep {
     /// Optional context (e.g., a code block or command)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub context: Option<String>,
+    /// Optional list of the tools this step may call
+    ///
+    /// When `None`, the step may call every tool the run provides. Names
+    /// are checked against the known tool names when the plan is parsed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<String>>,
+    /// Optional terminal execution mode for this step
+    ///
+    /// The mode is capped by the run's ceiling: a step cannot ask for
+    /// `full_autonomous` unless the run allows dangerous commands.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub execution_mode: Option<ExecutionMode>,
+    /// Optional number of times to retry the step after it fails
+    ///
+    /// Each retry starts the step over with a note describing the failed
+    /// attempts; see [`crate::agent::step_policy`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
 }
 
 impl Plan {
@@ -71,6 +96,28 @@ impl Plan {
         self.steps.is_empty()
     }
 
+    /// Returns true when any step restricts its tools or execution mode
+    ///
+    /// Such plans are run one step at a time so each step gets its own
+    /// tool set; see [`crate::agent::step_policy`].
+    pub fn has_step_restrictions(&self) -> bool {
+        self.steps
+            .iter()
+            .any(|step| step.tools.is_some() || step.execution_mode.is_some())
+    }
+
+    /// Returns true when the plan runs one step at a time
+    ///
+    /// That is the case when any step restricts its tools or execution mode,
+    /// or may be retried.
+    pub fn runs_stepwise(&self) -> bool {
+        self.has_step_restrictions()
+            || self
+                .steps
+                .iter()
+                .any(|step| step.retries.is_some_and(|retries| retries > 0))
+    }
+
     /// Format the plan as an instruction prompt for the agent executor.
     ///
     /// Produces a human-readable task description containing the plan name and all
@@ -124,6 +171,9 @@ impl PlanStep {
             name,
             action: String::new(),
             context: None,
+            tools: None,
+            execution_mode: None,
+            retries: None,
         }
     }
 
@@ -138,6 +188,24 @@ impl PlanStep {
         self.context = Some(context);
         self
     }
+
+    /// Restrict the step to the given tools
+    pub fn with_tools(mut self, tools: Vec<String>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    /// Override the terminal execution mode for the step
+    pub fn with_execution_mode(mut self, mode: ExecutionMode) -> Self {
+        self.execution_mode = Some(mode);
+        self
+    }
+
+    /// Retry the step up to `retries` times after it fails
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = Some(retries);
+        self
+    }
 }
 
 /// Plan Parser - supports YAML, JSON, Markdown formats
@@ -215,153 +283,132 @@ impl PlanParser {
     }
 
     /// Parse YAML plan content
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::InvalidPlan` listing every problem found; see
+    /// [`validate_yaml`](Self::validate_yaml).
     pub fn from_yaml(content: &str) -> Result<Plan> {
-        let plan: Plan = serde_yaml::from_str(content).map_err(XzatomaError::Yaml)?;
-        Self::validate(&plan)?;
-        Ok(plan)
+        Ok(Self::validate_yaml(content)?)
     }
 
     /// Parse JSON plan content
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::InvalidPlan` listing every problem found; see
+    /// [`validate_json`](Self::validate_json).
     pub fn from_json(content: &str) -> Result<Plan> {
-        let plan: Plan = serde_json::from_str(content).map_err(XzatomaError::Serialization)?;
-        Self::validate(&plan)?;
-        Ok(plan)
+        Ok(Self::validate_json(content)?)
     }
 
-    /// Parse Markdown plan content
+    /// Parse and validate YAML plan content, collecting every problem
     ///
-    /// - `#` H1 heading -> plan name
-    /// - `##` H2 headings -> steps
-    /// - First non-empty line under a step -> action
-    /// - Code fences -> step context
-    pub fn from_markdown(content: &str) -> Result<Plan> {
-        let mut name = String::new();
-        let mut description: Option<String> = None;
-        let mut steps: Vec<PlanStep> = Vec::new();
-
-        let mut current_step: Option<PlanStep> = None;
-        let mut in_code_block = false;
-        let mut code_buffer = String::new();
-
-        for line in content.lines() {
-            let trimmed = line.trim();
-
-            // Plan title: first H1
-            if let Some(stripped) = trimmed.strip_prefix("# ") {
-                if name.is_empty() {
-                    name = stripped.trim().to_string();
-                    continue;
-                }
-            }
-
-            // Step header H2
-            if let Some(stripped) = trimmed.strip_prefix("## ") {
-                // Save previous step (if present)
-                if let Some(mut step) = current_step.take() {
-                    if !code_buffer.is_empty() {
-                        step.context = Some(code_buffer.trim_end().to_string());
-                        code_buffer.clear();
-                    }
-                    steps.push(step);
-                }
-
-                current_step = Some(PlanStep::new(stripped.trim().to_string()));
-                continue;
-            }
-
-            // Code fence toggles
-            if trimmed.starts_with("```") {
-                in_code_block = !in_code_block;
-                if in_code_block {
-                    code_buffer.clear();
-                } else {
-                    if let Some(ref mut step) = current_step {
-                        let cat = if let Some(prev) = step.context.take() {
-                            if prev.is_empty() {
-                                code_buffer.clone()
-                            } else {
-                                format!("{}\n{}", prev, code_buffer)
-                            }
-                        } else {
-                            code_buffer.clone()
-                        };
-                        step.context = Some(cat.trim_end().to_string());
-                    }
-                    code_buffer.clear();
-                }
-                continue;
-            }
-
-            if in_code_block {
-                // Preserve exact lines in code blocks
-                code_buffer.push_str(line);
-                code_buffer.push('\n');
-                continue;
-            }
-
-            if let Some(ref mut step) = current_step {
-                if step.action.is_empty() && !trimmed.is_empty() {
-                    // The first non-empty line is the action
-                    step.action = trimmed.to_string();
-                } else if !trimmed.is_empty() {
-                    // Additional lines appended to action (paragraph form)
-                    step.action.push(' ');
-                    step.action.push_str(trimmed);
-                }
-                continue;
+    /// The document is checked against the plan schema and the semantic
+    /// rules before it is deserialized. Each issue carries the line and
+    /// column of the offending node.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - Raw plan YAML
+    ///
+    /// # Errors
+    ///
+    /// Returns all syntax, schema, and semantic issues found
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::tools::plan::PlanParser;
+    ///
+    /// let error = PlanParser::validate_yaml("name: Deploy\nsteps: []\n").unwrap_err();
+    /// assert_eq!(error.issues[0].message, "Plan must have at least one step");
+    /// assert_eq!(error.issues[0].location.unwrap().line, 2);
+    /// ```
+    pub fn validate_yaml(content: &str) -> std::result::Result<Plan, PlanValidationError> {
+        let value: serde_yaml::Value = serde_yaml::from_str(content).map_err(|error| {
+            let mut issue = PlanIssue::new("", error.to_string());
+            if let Some(location) = error.location() {
+                issue = issue.with_location(PlanLocation {
+                    line: location.line(),
+                    column: location.column(),
+                });
             }
+            PlanValidationError::single(issue)
+        })?;
+        let document = serde_json::to_value(value)
+            .map_err(|error| PlanValidationError::single(PlanIssue::new("", error.to_string())))?;
+        Self::validate_document(document, Some(content))
+    }
 
-            // Outside of steps: treat the first paragraph (non-empty) after H1 as description
-            if !name.is_empty() && description.is_none() && !trimmed.is_empty() {
-                description = Some(trimmed.to_string());
-            }
-        }
+    /// Parse and validate JSON plan content, collecting every problem
+    ///
+    /// Only syntax errors carry a source location.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - Raw plan JSON
+    ///
+    /// # Errors
+    ///
+    /// Returns all syntax, schema, and semantic issues found
+    pub fn validate_json(content: &str) -> std::result::Result<Plan, PlanValidationError> {
+        let document: Value = serde_json::from_str(content).map_err(|error| {
+            PlanValidationError::single(PlanIssue::new("", error.to_string()).with_location(
+                PlanLocation {
+                    line: error.line(),
+                    column: error.column(),
+                },
+            ))
+        })?;
+        Self::validate_document(document, None)
+    }
 
-        // Finalize last step
-        if let Some(mut step) = current_step {
-            if !code_buffer.is_empty() && step.context.is_none() {
-                step.context = Some(code_buffer.trim_end().to_string());
-            }
-            steps.push(step);
+    /// Validates a raw plan document and deserializes it
+    fn validate_document(
+        document: Value,
+        source: Option<&str>,
+    ) -> std::result::Result<Plan, PlanValidationError> {
+        let issues = plan_validation::document_issues(&document, source);
+        if !issues.is_empty() {
+            return Err(PlanValidationError { issues });
         }
+        serde_json::from_value(document)
+            .map_err(|error| PlanValidationError::single(PlanIssue::new("", error.to_string())))
+    }
 
-        let plan = Plan {
-            name,
-            description,
-            version: None,
-            action: None,
-            steps,
-        };
-
-        Self::validate(&plan)?;
-        Ok(plan)
+    /// Parse Markdown plan content
+    ///
+    /// Accepts either a `## Steps` checklist or one `##` heading per step;
+    /// see [`plan_markdown`](crate::tools::plan_markdown) for the layouts.
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::InvalidPlan` listing every problem found,
+    /// including a missing steps section
+    pub fn from_markdown(content: &str) -> Result<Plan> {
+        Ok(plan_markdown::parse_markdown(content)?)
     }
 
     /// Validate a plan instance (structure and content)
-    pub fn validate(plan: &Plan) -> Result<()> {
-        if plan.name.trim().is_empty() {
-            return Err(XzatomaError::Tool("Plan name cannot be empty".to_string()));
-        }
-
-        if plan.steps.is_empty() {
-            return Err(XzatomaError::Tool(
-                "Plan must have at least one step".to_string(),
-            ));
-        }
-
-        for (i, step) in plan.steps.iter().enumerate() {
-            if step.name.trim().is_empty() {
-                return Err(XzatomaError::Tool(format!("Step {} has no name", i + 1)));
-            }
-            if step.action.trim().is_empty() {
-                return Err(XzatomaError::Tool(format!(
-                    "Step '{}' has no action",
-                    step.name
-                )));
-            }
+    ///
+    /// Checks the semantic rules: the plan and every step need a non-empty
+    /// name, every step needs a non-empty action, step names must be
+    /// unique, and step tool lists may only name known tools.
+    ///
+    /// # Errors
+    ///
+    /// Returns every rule violation, not just the first. The issues have no
+    /// source location because the plan is already parsed.
+    pub fn validate(plan: &Plan) -> std::result::Result<(), PlanValidationError> {
+        let document = serde_json::to_value(plan)
+            .map_err(|error| PlanValidationError::single(PlanIssue::new("", error.to_string())))?;
+        let issues = plan_validation::semantic_issues(&document);
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(PlanValidationError { issues })
         }
-
-        Ok(())
     }
 }
 
@@ -482,6 +529,115 @@ cargo check
             .contains("cargo init"));
     }
 
+    #[test]
+    fn test_from_markdown_checklist_with_metadata_block() {
+        let md = include_str!("../../evals/run_command/plans/checklist_plan.md");
+        let plan = PlanParser::from_markdown(md).unwrap();
+        assert_eq!(plan.name, "Rotate Staging Certificates");
+        assert_eq!(
+            plan.description.as_deref(),
+            Some("Replace the TLS certificates on the staging load balancers before they expire.")
+        );
+        assert_eq!(plan.version.as_deref(), Some("2.1"));
+        assert_eq!(plan.action.as_deref(), Some("rotate"));
+        assert_eq!(plan.steps.len(), 4);
+        assert_eq!(plan.steps[0].name, "Back up the current certificates");
+        assert_eq!(plan.steps[0].action, "Back up the current certificates");
+        assert_eq!(
+            plan.steps[0].context.as_deref(),
+            Some("- Copy `/etc/ssl/staging` to `/var/backups/ssl`")
+        );
+        assert_eq!(plan.steps[1].context, None);
+        assert_eq!(
+            plan.steps[2].context.as_deref(),
+            Some("Restart one balancer at a time so traffic keeps flowing.")
+        );
+        assert!(plan.steps[3]
+            .context
+            .as_deref()
+            .unwrap()
+            .starts_with("openssl s_client"));
+    }
+
+    #[test]
+    fn test_from_markdown_numbered_steps_with_bold_names() {
+        let md = include_str!("../../evals/run_command/plans/numbered_steps_plan.md");
+        let plan = PlanParser::from_markdown(md).unwrap();
+        assert_eq!(plan.name, "Weekly Dependency Update");
+        assert_eq!(plan.description, None);
+        let names: Vec<&str> = plan.steps.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "Update",
+                "Audit",
+                "Test",
+                "Open a pull request with the lockfile changes"
+            ]
+        );
+        assert_eq!(
+            plan.steps[0].action,
+            "run `cargo update` in the workspace root"
+        );
+        assert_eq!(plan.steps[1].action, "check for advisories");
+        assert_eq!(
+            plan.steps[1].context.as_deref(),
+            Some("Fail the plan if any advisory is unpatched.")
+        );
+        assert_eq!(plan.steps[2].action, "run the full test suite");
+        assert_eq!(
+            plan.steps[2].context.as_deref(),
+            Some("cargo test --all-features")
+        );
+    }
+
+    #[test]
+    fn test_from_markdown_nested_steps_heading() {
+        let md = include_str!("../../evals/run_command/plans/nested_steps_heading_plan.md");
+        let plan = PlanParser::from_markdown(md).unwrap();
+        assert_eq!(plan.name, "Onboard New Service");
+        assert_eq!(plan.steps.len(), 3);
+        assert_eq!(plan.steps[1].name, "Add the dashboard");
+        assert_eq!(
+            plan.steps[1].context.as_deref(),
+            Some("- Use the standard latency and error panels")
+        );
+        assert_eq!(plan.steps[2].context, None);
+    }
+
+    #[test]
+    fn test_from_markdown_matches_yaml_shape() {
+        let md =
+            "# Deploy\n\n## Steps\n\n- [ ] **build**: cargo build\n- [x] **test**: cargo test\n";
+        let yaml = "name: Deploy\nsteps:\n  - name: build\n    action: cargo build\n  - name: test\n    action: cargo test\n";
+        assert_eq!(
+            serde_json::to_value(PlanParser::from_markdown(md).unwrap()).unwrap(),
+            serde_json::to_value(PlanParser::from_yaml(yaml).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_from_markdown_steps_heading_without_items() {
+        let md = include_str!("../../evals/run_command/plans/invalid_steps_no_items.md");
+        let error = match PlanParser::from_markdown(md) {
+            Err(XzatomaError::InvalidPlan(error)) => error,
+            other => panic!("expected InvalidPlan, got {:?}", other),
+        };
+        assert_eq!(error.issues.len(), 1);
+        assert_eq!(
+            error.issues[0].message,
+            "Plan must have at least one step: the `Steps` heading has no list items below it"
+        );
+        assert_eq!(error.issues[0].location.unwrap().line, 3);
+    }
+
+    #[test]
+    fn test_from_markdown_without_steps_explains_expected_layout() {
+        let md = include_str!("../../evals/run_command/plans/invalid_no_steps.md");
+        let message = PlanParser::from_markdown(md).unwrap_err().to_string();
+        assert!(message.contains("expected a `## Steps` heading followed by a list"));
+    }
+
     #[test]
     fn test_from_file_yaml() {
         let dir = tempdir().unwrap();
@@ -533,6 +689,134 @@ steps:
         assert!(PlanParser::validate(&plan3).is_err());
     }
 
+    #[test]
+    fn test_from_yaml_reads_step_tools_and_execution_mode() {
+        let yaml = "name: Fix
+steps:
+  - name: inspect
+    action: Find the bug
+    tools: [read_file, grep]
+  - name: fix
+    action: Fix it
+    execution_mode: interactive
+";
+        let plan = PlanParser::from_yaml(yaml).unwrap();
+        assert_eq!(
+            plan.steps[0].tools,
+            Some(vec!["read_file".to_string(), "grep".to_string()])
+        );
+        assert_eq!(plan.steps[0].execution_mode, None);
+        assert_eq!(plan.steps[1].tools, None);
+        assert_eq!(
+            plan.steps[1].execution_mode,
+            Some(ExecutionMode::Interactive)
+        );
+        assert!(plan.has_step_restrictions());
+
+        let json = serde_json::to_value(&plan.steps[1]).unwrap();
+        assert_eq!(json["execution_mode"], "interactive");
+        assert!(json.get("tools").is_none());
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_step_tools() {
+        let plan = Plan::new(
+            "Fix".to_string(),
+            vec![PlanStep::new("inspect".to_string())
+                .with_action("look".to_string())
+                .with_tools(vec!["read_file".to_string(), "shell".to_string()])],
+        );
+        let error = PlanParser::validate(&plan).unwrap_err();
+        assert_eq!(error.issues.len(), 1);
+        assert_eq!(error.issues[0].pointer, "/steps/0/tools/1");
+        assert_eq!(
+            error.issues[0].message,
+            "Step 'inspect' lists unknown tool 'shell'"
+        );
+
+        let yaml =
+            "name: Fix\nsteps:\n  - name: inspect\n    action: look\n    tools: [read_fil]\n";
+        let error = PlanParser::validate_yaml(yaml).unwrap_err();
+        assert_eq!(error.issues[0].location.unwrap().line, 5);
+    }
+
+    #[test]
+    fn test_validate_reports_every_semantic_issue() {
+        let plan = Plan::new(
+            " ".to_string(),
+            vec![
+                PlanStep::new("build".to_string()).with_action("go".to_string()),
+                PlanStep::new("build".to_string()),
+            ],
+        );
+
+        let error = PlanParser::validate(&plan).unwrap_err();
+        let messages: Vec<&str> = error.issues.iter().map(|i| i.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Plan name cannot be empty",
+                "Step name 'build' is already used by step 1",
+                "Step 'build' has no action",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_yaml_reports_all_errors_with_locations() {
+        let yaml = "name: Release
+description: Ship it
+steps:
+  - name: build
+    action: cargo build --release
+  - name: test
+  - name: build
+    action: cargo publish
+  - name: notify
+    action: 42
+";
+        let error = PlanParser::validate_yaml(yaml).unwrap_err();
+        let found: Vec<(&str, &str, Option<PlanLocation>)> = error
+            .issues
+            .iter()
+            .map(|i| (i.pointer.as_str(), i.message.as_str(), i.location))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "/steps/1",
+                    "missing field `action`",
+                    Some(PlanLocation { line: 6, column: 3 })
+                ),
+                (
+                    "/steps/2/name",
+                    "Step name 'build' is already used by step 1",
+                    Some(PlanLocation { line: 7, column: 5 })
+                ),
+                (
+                    "/steps/3/action",
+                    "invalid type: number, expected a string",
+                    Some(PlanLocation {
+                        line: 10,
+                        column: 5
+                    })
+                ),
+            ]
+        );
+
+        let err = PlanParser::from_yaml(yaml).unwrap_err();
+        assert!(matches!(err, XzatomaError::InvalidPlan(_)));
+        assert!(err.to_string().contains("3 problems found"));
+    }
+
+    #[test]
+    fn test_from_json_reports_syntax_error_location() {
+        let error = PlanParser::validate_json("{\"name\": \"x\",\n  \"steps\": [}").unwrap_err();
+        assert_eq!(error.issues.len(), 1);
+        assert_eq!(error.issues[0].location.map(|l| l.line), Some(2));
+    }
+
     #[tokio::test]
     async fn test_parse_plan_and_load_plan() {
         let yaml = r#"
@@ -0,0 +1,535 @@
+//! Markdown plan parsing
+//!
+//! Two layouts are recognized:
+//! - Checklist layout: a heading named `Steps` followed by a list. Each
+//!   top-level item (bullet, numbered, or checkbox) is a step, and checkbox
+//!   state is ignored. An item written as `**Name**: action` uses the bold
+//!   text as the step name. Nested items, following paragraphs, and code
+//!   blocks under an item become the step context.
+//! - Section layout, used when there is no `Steps` heading: every `##`
+//!   heading is a step, its text is the action, and its code blocks are the
+//!   context.
+//!
+//! In both layouts the first `#` heading is the plan name and the first
+//! paragraph after it is the description. A fenced `yaml` block outside the
+//! steps holds plan metadata (`name`, `description`, `version`, `action`) and
+//! takes precedence over values taken from the headings.
+//!
+//! The result is the same [`Plan`] the YAML parser produces, and it goes
+//! through the same semantic rules. Issues for a step point at the line of
+//! its list item or heading.
+//!
+//! # Examples
+//!
+//! ```
+//! use xzatoma::tools::plan_markdown::parse_markdown;
+//!
+//! let markdown = "# Release\n\n## Steps\n\n- [x] Build\n- [ ] **Test**: run cargo test\n  - include doc tests\n";
+//! let plan = parse_markdown(markdown).unwrap();
+//!
+//! assert_eq!(plan.name, "Release");
+//! assert_eq!(plan.steps[1].name, "Test");
+//! assert_eq!(plan.steps[1].action, "run cargo test");
+//! assert_eq!(plan.steps[1].context.as_deref(), Some("- include doc tests"));
+//! ```
+
+use crate::markdown::{extract_code_blocks_with, ExtractOptions};
+use crate::tools::plan::{Plan, PlanStep};
+use crate::tools::plan_validation::{self, PlanIssue, PlanLocation, PlanValidationError};
+use serde::Deserialize;
+
+/// Plan fields accepted in a fenced yaml metadata block
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Metadata {
+    name: Option<String>,
+    description: Option<String>,
+    version: Option<String>,
+    action: Option<String>,
+}
+
+/// A block-level element of the markdown source
+#[derive(Debug)]
+enum Block<'a> {
+    Heading {
+        level: usize,
+        text: &'a str,
+    },
+    Code {
+        language: Option<String>,
+        body: String,
+    },
+    Text {
+        indent: usize,
+        text: &'a str,
+    },
+}
+
+/// A block and the line it starts on
+#[derive(Debug)]
+struct Line<'a> {
+    number: usize,
+    block: Block<'a>,
+}
+
+/// A step and the line it was defined on
+struct ParsedStep {
+    line: usize,
+    step: PlanStep,
+}
+
+/// Parses a markdown plan and validates it
+///
+/// # Arguments
+///
+/// * `content` - Raw markdown text
+///
+/// # Errors
+///
+/// Returns every problem found: an invalid metadata block, a missing name or
+/// steps section, and the semantic rule violations shared with YAML plans
+pub fn parse_markdown(content: &str) -> Result<Plan, PlanValidationError> {
+    let lines = tokenize(content);
+    let title = lines
+        .iter()
+        .position(|line| matches!(line.block, Block::Heading { level: 1, .. }));
+    let steps_heading = lines.iter().enumerate().position(|(index, line)| {
+        Some(index) != title
+            && matches!(line.block, Block::Heading { text, .. } if is_steps_heading(text))
+    });
+
+    let mut plan = Plan {
+        name: String::new(),
+        description: None,
+        version: None,
+        action: None,
+        steps: Vec::new(),
+    };
+    if let Some(index) = title {
+        if let Block::Heading { text, .. } = lines[index].block {
+            plan.name = text.to_string();
+        }
+        plan.description = lines[index + 1..]
+            .iter()
+            .take_while(|line| !matches!(line.block, Block::Heading { .. }))
+            .find_map(|line| match line.block {
+                Block::Text { text, .. } => Some(text.to_string()),
+                _ => None,
+            });
+    }
+
+    let (steps, step_range) = match steps_heading {
+        Some(index) => checklist_steps(&lines, index),
+        None => section_steps(&lines),
+    };
+
+    let mut issues = Vec::new();
+    let metadata_block = lines.iter().enumerate().find(|(index, line)| {
+        !step_range.contains(index)
+            && matches!(
+                &line.block,
+                Block::Code { language: Some(language), .. } if language == "yaml" || language == "yml"
+            )
+    });
+    if let Some((_, line)) = metadata_block {
+        match read_metadata(line) {
+            Ok(metadata) => {
+                plan.name = metadata.name.unwrap_or(plan.name);
+                plan.description = metadata.description.or(plan.description);
+                plan.version = metadata.version;
+                plan.action = metadata.action;
+            }
+            Err(issue) => issues.push(issue),
+        }
+    }
+
+    if plan.name.trim().is_empty() {
+        issues.push(PlanIssue::new(
+            "/name",
+            "Plan name cannot be empty: expected a `# <plan name>` heading or a `name` field in a yaml block",
+        ));
+    }
+    if steps.is_empty() {
+        let issue = match steps_heading {
+            Some(index) => PlanIssue::new(
+                "/steps",
+                "Plan must have at least one step: the `Steps` heading has no list items below it",
+            )
+            .with_location(PlanLocation {
+                line: lines[index].number,
+                column: 1,
+            }),
+            None => PlanIssue::new(
+                "/steps",
+                "Plan must have at least one step: expected a `## Steps` heading followed by a list, or one `## <step name>` heading per step",
+            ),
+        };
+        issues.push(issue);
+    }
+
+    let step_lines: Vec<usize> = steps.iter().map(|parsed| parsed.line).collect();
+    plan.steps = steps.into_iter().map(|parsed| parsed.step).collect();
+
+    let document = serde_json::to_value(&plan)
+        .map_err(|error| PlanValidationError::single(PlanIssue::new("", error.to_string())))?;
+    for issue in plan_validation::semantic_issues(&document) {
+        if issues
+            .iter()
+            .any(|existing| existing.pointer == issue.pointer)
+        {
+            continue;
+        }
+        let line = step_index(&issue.pointer).and_then(|index| step_lines.get(index));
+        issues.push(match line {
+            Some(&line) => issue.with_location(PlanLocation { line, column: 1 }),
+            None => issue,
+        });
+    }
+
+    if issues.is_empty() {
+        Ok(plan)
+    } else {
+        Err(PlanValidationError { issues })
+    }
+}
+
+/// Splits markdown into headings, code blocks, and non-blank text lines
+fn tokenize(content: &str) -> Vec<Line<'_>> {
+    let code = extract_code_blocks_with(content, ExtractOptions::fenced_only());
+    let mut next_code = 0;
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    for (index, raw) in content.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += raw.len();
+
+        // A fenced block becomes one line, numbered by its opening fence
+        if let Some(block) = code
+            .get(next_code)
+            .filter(|block| block.span.start < offset)
+        {
+            if block.span.start == start {
+                lines.push(Line {
+                    number: index + 1,
+                    block: Block::Code {
+                        language: block.language.clone(),
+                        body: block.content.clone(),
+                    },
+                });
+            }
+            if block.span.end <= offset {
+                next_code += 1;
+            }
+            continue;
+        }
+
+        let trimmed = raw.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let indent = raw.len() - raw.trim_start().len();
+        let block = match heading(trimmed) {
+            Some((level, text)) if indent < 4 => Block::Heading { level, text },
+            _ => Block::Text {
+                indent,
+                text: trimmed,
+            },
+        };
+        lines.push(Line {
+            number: index + 1,
+            block,
+        });
+    }
+
+    lines
+}
+
+/// Returns the level and text of an ATX heading
+fn heading(text: &str) -> Option<(usize, &str)> {
+    let level = text.chars().take_while(|c| *c == '#').count();
+    let rest = &text[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim_end()))
+}
+
+/// Returns true for the heading that introduces the checklist layout
+fn is_steps_heading(text: &str) -> bool {
+    text.trim_end_matches(':')
+        .trim()
+        .eq_ignore_ascii_case("steps")
+}
+
+/// Returns the text of a list item with its marker and checkbox removed
+fn list_item(text: &str) -> Option<&str> {
+    let rest = if let Some(rest) = text
+        .strip_prefix("- ")
+        .or_else(|| text.strip_prefix("* "))
+        .or_else(|| text.strip_prefix("+ "))
+    {
+        rest
+    } else {
+        let digits = text.chars().take_while(char::is_ascii_digit).count();
+        let rest = &text[digits..];
+        if digits == 0 {
+            return None;
+        }
+        rest.strip_prefix(". ")
+            .or_else(|| rest.strip_prefix(") "))?
+    };
+
+    let rest = rest.trim_start();
+    for checkbox in ["[ ]", "[x]", "[X]"] {
+        if let Some(after) = rest.strip_prefix(checkbox) {
+            if after.is_empty() || after.starts_with(' ') {
+                return Some(after.trim());
+            }
+        }
+    }
+    Some(rest.trim_end())
+}
+
+/// Builds a step from list item text
+///
+/// `**Name**: action` (or `**Name** - action`) uses the bold text as the
+/// name; otherwise the whole item is both the name and the action.
+fn item_step(text: &str) -> PlanStep {
+    if let Some(rest) = text.strip_prefix("**") {
+        if let Some(end) = rest.find("**") {
+            let name = rest[..end].trim().trim_end_matches(':').trim_end();
+            let action = rest[end + 2..]
+                .trim_start_matches(|c: char| c.is_whitespace() || ":-–—".contains(c))
+                .trim();
+            if !name.is_empty() {
+                let action = if action.is_empty() { name } else { action };
+                return PlanStep::new(name.to_string()).with_action(action.to_string());
+            }
+        }
+    }
+    PlanStep::new(text.to_string()).with_action(text.to_string())
+}
+
+/// Reads steps from the list under the `Steps` heading
+///
+/// Returns the steps and the range of lines the section covers.
+fn checklist_steps(
+    lines: &[Line<'_>],
+    heading: usize,
+) -> (Vec<ParsedStep>, std::ops::Range<usize>) {
+    let section_level = match lines[heading].block {
+        Block::Heading { level, .. } => level,
+        _ => unreachable!("steps heading index always points at a heading"),
+    };
+    let end = lines[heading + 1..]
+        .iter()
+        .position(
+            |line| matches!(line.block, Block::Heading { level, .. } if level <= section_level),
+        )
+        .map_or(lines.len(), |offset| heading + 1 + offset);
+
+    let mut steps: Vec<ParsedStep> = Vec::new();
+    let mut details: Vec<Vec<String>> = Vec::new();
+    let mut top_indent: Option<usize> = None;
+
+    for line in &lines[heading + 1..end] {
+        let item = match line.block {
+            Block::Text { indent, text } => list_item(text).map(|item| (indent, item)),
+            _ => None,
+        };
+        if let Some((indent, item)) = item {
+            if indent <= *top_indent.get_or_insert(indent) {
+                steps.push(ParsedStep {
+                    line: line.number,
+                    step: item_step(item),
+                });
+                details.push(Vec::new());
+                continue;
+            }
+        }
+
+        let Some(detail) = details.last_mut() else {
+            // Text before the first item introduces the list
+            continue;
+        };
+        detail.push(match &line.block {
+            Block::Text { text, .. } => match list_item(text) {
+                Some(nested) => format!("- {}", nested),
+                None => text.to_string(),
+            },
+            Block::Code { body, .. } => body.trim_end().to_string(),
+            Block::Heading { text, .. } => text.to_string(),
+        });
+    }
+
+    for (parsed, detail) in steps.iter_mut().zip(details) {
+        if !detail.is_empty() {
+            parsed.step.context = Some(detail.join("\n"));
+        }
+    }
+    (steps, heading..end)
+}
+
+/// Reads one step per `##` heading
+///
+/// Returns the steps and the range of lines they cover.
+fn section_steps(lines: &[Line<'_>]) -> (Vec<ParsedStep>, std::ops::Range<usize>) {
+    let first = lines
+        .iter()
+        .position(|line| matches!(line.block, Block::Heading { level: 2, .. }));
+    let Some(first) = first else {
+        return (Vec::new(), 0..0);
+    };
+
+    let mut steps: Vec<ParsedStep> = Vec::new();
+    for line in &lines[first..] {
+        match &line.block {
+            Block::Heading { level: 2, text } => steps.push(ParsedStep {
+                line: line.number,
+                step: PlanStep::new(text.to_string()),
+            }),
+            Block::Heading { level: 1, .. } => {}
+            Block::Heading { text, .. } | Block::Text { text, .. } => {
+                let Some(parsed) = steps.last_mut() else {
+                    continue;
+                };
+                let action = &mut parsed.step.action;
+                if !action.is_empty() {
+                    action.push(' ');
+                }
+                action.push_str(text);
+            }
+            Block::Code { body, .. } => {
+                let Some(parsed) = steps.last_mut() else {
+                    continue;
+                };
+                let body = body.trim_end();
+                parsed.step.context = Some(match parsed.step.context.take() {
+                    Some(previous) if !previous.is_empty() => format!("{}\n{}", previous, body),
+                    _ => body.to_string(),
+                });
+            }
+        }
+    }
+    (steps, first..lines.len())
+}
+
+/// Deserializes a fenced yaml metadata block
+fn read_metadata(line: &Line<'_>) -> Result<Metadata, PlanIssue> {
+    let Block::Code { body, .. } = &line.block else {
+        return Ok(Metadata::default());
+    };
+    if body.trim().is_empty() {
+        return Ok(Metadata::default());
+    }
+
+    serde_yaml::from_str::<Option<Metadata>>(body)
+        .map(Option::unwrap_or_default)
+        .map_err(|error| {
+            let mut message = error.to_string();
+            let mut location = PlanLocation {
+                line: line.number,
+                column: 1,
+            };
+            if let Some(position) = error.location() {
+                let suffix = format!(" at line {} column {}", position.line(), position.column());
+                if let Some(stripped) = message.strip_suffix(&suffix) {
+                    message = stripped.to_string();
+                }
+                location = PlanLocation {
+                    line: line.number + position.line(),
+                    column: position.column(),
+                };
+            }
+            PlanIssue::new("", format!("Invalid yaml metadata block: {}", message))
+                .with_location(location)
+        })
+}
+
+/// Returns the step index of a `/steps/N...` pointer
+fn step_index(pointer: &str) -> Option<usize> {
+    pointer
+        .strip_prefix("/steps/")?
+        .split('/')
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_item_strips_markers_and_checkboxes() {
+        assert_eq!(list_item("- Build"), Some("Build"));
+        assert_eq!(list_item("* [ ] Build"), Some("Build"));
+        assert_eq!(list_item("+ [x] Build"), Some("Build"));
+        assert_eq!(list_item("12. [X] Build"), Some("Build"));
+        assert_eq!(list_item("3) Build"), Some("Build"));
+        assert_eq!(list_item("- [link] text"), Some("[link] text"));
+        assert_eq!(list_item("---"), None);
+        assert_eq!(list_item("2024 was a year"), None);
+    }
+
+    #[test]
+    fn test_item_step_uses_bold_lead_as_name() {
+        let step = item_step("**Build**: cargo build --release");
+        assert_eq!(step.name, "Build");
+        assert_eq!(step.action, "cargo build --release");
+
+        let step = item_step("**Test:** run the suite");
+        assert_eq!(step.name, "Test");
+        assert_eq!(step.action, "run the suite");
+
+        let step = item_step("**Publish**");
+        assert_eq!(step.action, "Publish");
+
+        let step = item_step("Tag the release");
+        assert_eq!(step.name, "Tag the release");
+        assert_eq!(step.action, "Tag the release");
+    }
+
+    #[test]
+    fn test_heading_requires_space_after_hashes() {
+        assert_eq!(heading("## Steps"), Some((2, "Steps")));
+        assert_eq!(heading("# Title #"), Some((1, "Title")));
+        assert_eq!(heading("#hashtag"), None);
+        assert_eq!(heading("####### too deep"), None);
+    }
+
+    #[test]
+    fn test_fenced_hash_lines_are_not_headings() {
+        let markdown =
+            "# Plan\n\n## Steps\n\n- Run\n  ```bash\n  # comment\n  make\n  ```\n- Check\n";
+        let plan = parse_markdown(markdown).unwrap();
+        assert_eq!(plan.steps.len(), 2);
+        assert_eq!(plan.steps[0].context.as_deref(), Some("# comment\nmake"));
+    }
+
+    #[test]
+    fn test_step_issues_point_at_item_lines() {
+        let markdown = "# Plan\n\n## Steps\n\n- Build\n- Test\n- Build\n";
+        let error = parse_markdown(markdown).unwrap_err();
+        assert_eq!(error.issues.len(), 1);
+        assert_eq!(
+            error.issues[0].message,
+            "Step name 'Build' is already used by step 1"
+        );
+        assert_eq!(error.issues[0].location.unwrap().line, 7);
+    }
+
+    #[test]
+    fn test_metadata_errors_use_document_lines() {
+        let markdown = "# Plan\n\n```yaml\nversion: \"1\"\nvariables:\n  env: prod\n```\n\n## Steps\n\n- Build\n";
+        let error = parse_markdown(markdown).unwrap_err();
+        assert_eq!(error.issues.len(), 1);
+        let issue = &error.issues[0];
+        assert!(issue
+            .message
+            .starts_with("Invalid yaml metadata block: unknown field `variables`"));
+        assert!(!issue.message.contains("at line"));
+        assert_eq!(issue.location.unwrap().line, 5);
+    }
+}
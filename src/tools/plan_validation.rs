@@ -0,0 +1,695 @@
+//! Schema and semantic validation for plan documents
+//!
+//! Plans are checked in two passes before the typed [`Plan`] is built:
+//! - The raw document is validated against the embedded JSON Schema
+//!   ([`PLAN_SCHEMA`]), which covers structure and field types
+//! - Semantic rules that a schema cannot express are checked next: the plan
+//!   and every step need a non-empty name, steps need a non-empty action,
+//!   step names must be unique, and a step's `tools` list may only name
+//!   [`KNOWN_TOOL_NAMES`] or MCP tools (`server__tool`)
+//!
+//! Every violation is collected into a [`PlanValidationError`] instead of
+//! stopping at the first one. For YAML input, each issue carries the line and
+//! column of the offending node so hand-written plans can be fixed quickly.
+//!
+//! Plans do not define variables, so there is no variable reference check.
+//!
+//! # Examples
+//!
+//! ```
+//! use xzatoma::tools::plan::PlanParser;
+//!
+//! let yaml = "name: Deploy\nsteps:\n  - name: build\n  - name: build\n    action: cargo build\n";
+//! let error = PlanParser::validate_yaml(yaml).unwrap_err();
+//!
+//! assert_eq!(error.issues.len(), 2);
+//! assert_eq!(error.issues[0].message, "missing field `action`");
+//! assert_eq!(error.issues[0].location.unwrap().line, 3);
+//! ```
+//!
+//! [`Plan`]: crate::tools::plan::Plan
+
+use jsonschema::error::{TypeKind, ValidationErrorKind};
+use jsonschema::JSONSchema;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// JSON Schema describing the plan document format
+pub const PLAN_SCHEMA: &str = include_str!("plan.schema.json");
+
+/// Built-in tool names a plan step may list in `tools`
+///
+/// MCP tools are registered as `server__tool` and are accepted by shape,
+/// since the servers are only known once the run connects to them.
+pub const KNOWN_TOOL_NAMES: &[&str] = &[
+    "activate_skill",
+    "copy_path",
+    "create_directory",
+    "delete_path",
+    "edit_file",
+    "fetch",
+    "find_path",
+    "finish",
+    "grep",
+    "list_directory",
+    "move_path",
+    "parallel_subagent",
+    "read_file",
+    "read_tool_output",
+    "subagent",
+    "terminal",
+    "write_file",
+];
+
+/// Returns true for a built-in tool name or an MCP tool name
+pub fn is_known_tool(name: &str) -> bool {
+    KNOWN_TOOL_NAMES.contains(&name)
+        || name
+            .split_once("__")
+            .is_some_and(|(server, tool)| !server.is_empty() && !tool.is_empty())
+}
+
+/// Position of a node in the plan source text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PlanLocation {
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Column number, starting at 1
+    pub column: usize,
+}
+
+impl fmt::Display for PlanLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// A single problem found in a plan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlanIssue {
+    /// JSON pointer to the offending value, e.g. `/steps/2/action`
+    pub pointer: String,
+    /// Description of the problem
+    pub message: String,
+    /// Source location; YAML plans locate every issue, JSON plans only
+    /// syntax errors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<PlanLocation>,
+}
+
+impl PlanIssue {
+    /// Creates an issue without a source location
+    ///
+    /// # Arguments
+    ///
+    /// * `pointer` - JSON pointer to the offending value
+    /// * `message` - Description of the problem
+    pub fn new(pointer: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            pointer: pointer.into(),
+            message: message.into(),
+            location: None,
+        }
+    }
+
+    /// Sets the source location of the issue
+    pub fn with_location(mut self, location: PlanLocation) -> Self {
+        self.location = Some(location);
+        self
+    }
+}
+
+impl fmt::Display for PlanIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+        f.write_str(&self.message)?;
+        if !self.pointer.is_empty() {
+            write!(f, " (at {})", self.pointer)?;
+        }
+        Ok(())
+    }
+}
+
+/// All problems found while validating a plan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanValidationError {
+    /// Issues in document order
+    pub issues: Vec<PlanIssue>,
+}
+
+impl PlanValidationError {
+    /// Creates an error from a single issue
+    pub fn single(issue: PlanIssue) -> Self {
+        Self {
+            issues: vec![issue],
+        }
+    }
+}
+
+impl fmt::Display for PlanValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.issues.as_slice() {
+            [issue] => write!(f, "{}", issue),
+            issues => {
+                write!(f, "{} problems found", issues.len())?;
+                for issue in issues {
+                    write!(f, "\n  - {}", issue)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for PlanValidationError {}
+
+/// Validates a raw plan document against [`PLAN_SCHEMA`]
+///
+/// A `null` document (an empty file) is treated as an empty mapping, and
+/// `null` mapping values are treated as absent, matching how optional plan
+/// fields deserialize.
+///
+/// # Arguments
+///
+/// * `document` - Plan document as a JSON value
+///
+/// # Returns
+///
+/// The schema violations, in document order
+pub fn schema_issues(document: &Value) -> Vec<PlanIssue> {
+    let document = normalize(document);
+    let schema = compiled_schema();
+    let mut issues: Vec<PlanIssue> = match schema.validate(&document) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors
+            .map(|error| {
+                let message = match &error.kind {
+                    ValidationErrorKind::Required { property } => {
+                        format!("missing field `{}`", property.as_str().unwrap_or_default())
+                    }
+                    ValidationErrorKind::Type {
+                        kind: TypeKind::Single(expected),
+                    } => format!(
+                        "invalid type: {}, expected {}",
+                        describe_value(&error.instance),
+                        describe_type(&expected.to_string())
+                    ),
+                    _ => error.to_string(),
+                };
+                PlanIssue::new(error.instance_path.to_string(), message)
+            })
+            .collect(),
+    };
+    issues.sort_by(|a, b| pointer_order(&a.pointer, &b.pointer));
+    issues
+}
+
+/// Checks the semantic plan rules that the schema cannot express
+///
+/// Values with the wrong type are skipped here; [`schema_issues`] reports
+/// them.
+///
+/// # Arguments
+///
+/// * `document` - Plan document as a JSON value
+///
+/// # Returns
+///
+/// The rule violations, in document order
+pub fn semantic_issues(document: &Value) -> Vec<PlanIssue> {
+    let mut issues = Vec::new();
+
+    if let Some(name) = document.get("name").and_then(Value::as_str) {
+        if name.trim().is_empty() {
+            issues.push(PlanIssue::new("/name", "Plan name cannot be empty"));
+        }
+    }
+
+    let Some(steps) = document.get("steps").and_then(Value::as_array) else {
+        return issues;
+    };
+    if steps.is_empty() {
+        issues.push(PlanIssue::new("/steps", "Plan must have at least one step"));
+    }
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for (i, step) in steps.iter().enumerate() {
+        let name = step.get("name").and_then(Value::as_str);
+        match name.map(str::trim) {
+            Some("") => issues.push(PlanIssue::new(
+                format!("/steps/{}/name", i),
+                format!("Step {} has no name", i + 1),
+            )),
+            Some(trimmed) => {
+                if let Some(first) = seen.get(trimmed) {
+                    issues.push(PlanIssue::new(
+                        format!("/steps/{}/name", i),
+                        format!(
+                            "Step name '{}' is already used by step {}",
+                            trimmed,
+                            first + 1
+                        ),
+                    ));
+                } else {
+                    seen.insert(trimmed, i);
+                }
+            }
+            None => {}
+        }
+
+        if let Some(action) = step.get("action").and_then(Value::as_str) {
+            if action.trim().is_empty() {
+                issues.push(PlanIssue::new(
+                    format!("/steps/{}/action", i),
+                    format!("Step '{}' has no action", name.unwrap_or_default()),
+                ));
+            }
+        }
+
+        if let Some(tools) = step.get("tools").and_then(Value::as_array) {
+            for (j, tool) in tools.iter().enumerate() {
+                let Some(tool) = tool.as_str() else {
+                    continue;
+                };
+                if !is_known_tool(tool) {
+                    issues.push(PlanIssue::new(
+                        format!("/steps/{}/tools/{}", i, j),
+                        format!(
+                            "Step '{}' lists unknown tool '{}'",
+                            name.unwrap_or_default(),
+                            tool
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    issues
+}
+
+/// Runs [`schema_issues`] and [`semantic_issues`] and attaches YAML locations
+///
+/// # Arguments
+///
+/// * `document` - Plan document as a JSON value
+/// * `source` - YAML (or JSON) source text used to locate issues, if any
+///
+/// # Returns
+///
+/// Every issue found, in document order
+pub fn document_issues(document: &Value, source: Option<&str>) -> Vec<PlanIssue> {
+    let mut issues = schema_issues(document);
+    issues.extend(semantic_issues(&normalize(document)));
+    issues.sort_by(|a, b| pointer_order(&a.pointer, &b.pointer));
+
+    if let Some(source) = source {
+        let locator = YamlLocator::new(source);
+        for issue in &mut issues {
+            issue.location = locator.locate(&issue.pointer);
+        }
+    }
+    issues
+}
+
+fn compiled_schema() -> &'static JSONSchema {
+    static SCHEMA: OnceLock<JSONSchema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        let schema: Value =
+            serde_json::from_str(PLAN_SCHEMA).expect("embedded plan schema is valid JSON");
+        JSONSchema::compile(&schema).expect("embedded plan schema compiles")
+    })
+}
+
+/// Replaces a null document with an empty mapping and drops null values
+fn normalize(document: &Value) -> Value {
+    fn strip_nulls(value: &Value) -> Value {
+        match value {
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .filter(|(_, v)| !v.is_null())
+                    .map(|(k, v)| (k.clone(), strip_nulls(v)))
+                    .collect(),
+            ),
+            Value::Array(items) => Value::Array(items.iter().map(strip_nulls).collect()),
+            other => other.clone(),
+        }
+    }
+
+    match document {
+        Value::Null => Value::Object(Default::default()),
+        other => strip_nulls(other),
+    }
+}
+
+/// Orders pointers by document position: array indices numerically
+fn pointer_order(a: &str, b: &str) -> std::cmp::Ordering {
+    let key = |pointer: &str| -> Vec<(usize, String)> {
+        pointer
+            .split('/')
+            .skip(1)
+            .map(|segment| match segment.parse::<usize>() {
+                Ok(index) => (index, String::new()),
+                Err(_) => (usize::MAX, segment.to_string()),
+            })
+            .collect()
+    };
+    key(a).cmp(&key(b))
+}
+
+fn describe_value(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "sequence",
+        Value::Object(_) => "mapping",
+    }
+}
+
+fn describe_type(expected: &str) -> String {
+    match expected {
+        "array" => "a sequence".to_string(),
+        "object" => "a mapping".to_string(),
+        "integer" => "an integer".to_string(),
+        other => format!("a {}", other),
+    }
+}
+
+/// Finds the source position of a JSON pointer in block-style YAML
+///
+/// The locator follows indentation rather than fully parsing YAML, which is
+/// enough for hand-written plans. When a path cannot be followed (for
+/// example into a flow-style mapping), the deepest node found is returned.
+struct YamlLocator<'a> {
+    lines: Vec<&'a str>,
+}
+
+/// A block of source lines holding one YAML node
+#[derive(Debug, Clone, Copy)]
+struct Scope {
+    /// Line holding the first key or item of the node
+    line: usize,
+    /// Column where keys or `-` markers of the node start
+    column: usize,
+    /// First line after the node
+    end: usize,
+}
+
+impl<'a> YamlLocator<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            lines: source.lines().collect(),
+        }
+    }
+
+    /// Returns the location of `pointer`, or of its deepest located ancestor
+    fn locate(&self, pointer: &str) -> Option<PlanLocation> {
+        let first = (0..self.lines.len()).find(|&i| self.is_content(i))?;
+        let mut scope = Scope {
+            line: first,
+            column: indent(self.lines[first]),
+            end: self.lines.len(),
+        };
+        let mut found = PlanLocation {
+            line: scope.line + 1,
+            column: scope.column + 1,
+        };
+
+        for segment in pointer.split('/').skip(1) {
+            let segment = segment.replace("~1", "/").replace("~0", "~");
+            let next = match segment.parse::<usize>() {
+                Ok(index) if self.is_sequence(scope) => self.item(scope, index),
+                _ => self.key(scope, &segment),
+            };
+            let Some((location, child)) = next else {
+                break;
+            };
+            found = location;
+            match child {
+                Some(child) => scope = child,
+                None => break,
+            }
+        }
+
+        Some(found)
+    }
+
+    fn is_content(&self, line: usize) -> bool {
+        let trimmed = self.lines[line].trim();
+        !trimmed.is_empty() && !trimmed.starts_with('#') && trimmed != "---"
+    }
+
+    /// Text of `line` starting at `column`
+    fn text_at(&self, line: usize, column: usize) -> &'a str {
+        self.lines[line].get(column..).unwrap_or_default()
+    }
+
+    fn is_sequence(&self, scope: Scope) -> bool {
+        is_item(self.text_at(scope.line, scope.column))
+    }
+
+    /// Lines within `scope` whose node content starts at the scope column
+    fn entries(&self, scope: Scope) -> Vec<usize> {
+        let mut entries = vec![scope.line];
+        entries.extend(
+            (scope.line + 1..scope.end)
+                .filter(|&i| self.is_content(i) && indent(self.lines[i]) == scope.column),
+        );
+        entries
+    }
+
+    /// Finds item `index` of the sequence in `scope`
+    fn item(&self, scope: Scope, index: usize) -> Option<(PlanLocation, Option<Scope>)> {
+        let items: Vec<usize> = self
+            .entries(scope)
+            .into_iter()
+            .filter(|&i| is_item(self.text_at(i, scope.column)))
+            .collect();
+        let line = *items.get(index)?;
+        let end = items.get(index + 1).copied().unwrap_or(scope.end);
+
+        // Content of the item starts after the `-` marker and its spaces
+        let rest = &self.text_at(line, scope.column)[1..];
+        let column = scope.column + 1 + (rest.len() - rest.trim_start().len());
+        let location = PlanLocation {
+            line: line + 1,
+            column: scope.column + 1,
+        };
+        let child = (!rest.trim().is_empty()).then_some(Scope { line, column, end });
+        Some((location, child))
+    }
+
+    /// Finds `key` in the mapping in `scope`
+    fn key(&self, scope: Scope, key: &str) -> Option<(PlanLocation, Option<Scope>)> {
+        let line = self
+            .entries(scope)
+            .into_iter()
+            .find(|&i| key_value(self.text_at(i, scope.column), key).is_some())?;
+        let location = PlanLocation {
+            line: line + 1,
+            column: scope.column + 1,
+        };
+
+        let value = key_value(self.text_at(line, scope.column), key).unwrap_or_default();
+        if !value.trim().is_empty() && !value.trim().starts_with('#') {
+            // Inline scalar or flow collection; nothing to descend into
+            return Some((location, None));
+        }
+
+        // Block value: it starts on the next content line. A sequence may sit
+        // at the same indentation as its key.
+        let Some(start) = (line + 1..scope.end).find(|&i| self.is_content(i)) else {
+            return Some((location, None));
+        };
+        let column = indent(self.lines[start]);
+        let nested = column > scope.column
+            || (column == scope.column && is_item(self.text_at(start, column)));
+        if !nested {
+            return Some((location, None));
+        }
+        let ends_value = |i: usize| {
+            let line_indent = indent(self.lines[i]);
+            line_indent < column
+                || (line_indent == column
+                    && column == scope.column
+                    && !is_item(self.text_at(i, column)))
+        };
+        let end = (start + 1..scope.end)
+            .find(|&i| self.is_content(i) && ends_value(i))
+            .unwrap_or(scope.end);
+        Some((
+            location,
+            Some(Scope {
+                line: start,
+                column,
+                end,
+            }),
+        ))
+    }
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Returns the text after `key:` when `text` starts with that mapping key
+fn key_value<'t>(text: &'t str, key: &str) -> Option<&'t str> {
+    let rest = [
+        key.to_string(),
+        format!("\"{}\"", key),
+        format!("'{}'", key),
+    ]
+    .iter()
+    .find_map(|candidate| text.strip_prefix(candidate.as_str()))?;
+    let rest = rest.trim_start_matches(' ').strip_prefix(':')?;
+    (rest.is_empty() || rest.starts_with(' ')).then_some(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues_for(yaml: &str) -> Vec<PlanIssue> {
+        let value: serde_yaml::Value = serde_yaml::from_str(yaml).unwrap();
+        let document = serde_json::to_value(value).unwrap();
+        document_issues(&document, Some(yaml))
+    }
+
+    #[test]
+    fn test_valid_plan_has_no_issues() {
+        let yaml = "name: Deploy\nsteps:\n  - name: build\n    action: cargo build\n";
+        assert!(issues_for(yaml).is_empty());
+    }
+
+    #[test]
+    fn test_empty_document_reports_missing_fields() {
+        let issues = issues_for("");
+        let messages: Vec<&str> = issues.iter().map(|i| i.message.as_str()).collect();
+        assert!(messages.contains(&"missing field `name`"));
+        assert!(messages.contains(&"missing field `steps`"));
+    }
+
+    #[test]
+    fn test_type_errors_use_yaml_terms() {
+        let issues = issues_for("name: [a, b]\nsteps:\n  - name: s\n    action: a\n");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].pointer, "/name");
+        assert_eq!(
+            issues[0].message,
+            "invalid type: sequence, expected a string"
+        );
+        assert_eq!(
+            issues[0].location,
+            Some(PlanLocation { line: 1, column: 1 })
+        );
+    }
+
+    #[test]
+    fn test_null_optional_fields_are_allowed() {
+        let yaml =
+            "name: Deploy\ndescription:\nsteps:\n  - name: build\n    action: go\n    context:\n";
+        assert!(issues_for(yaml).is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_step_names_are_reported() {
+        let yaml = "name: Deploy\nsteps:\n  - name: build\n    action: a\n  - name: build\n    action: b\n";
+        let issues = issues_for(yaml);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].pointer, "/steps/1/name");
+        assert!(issues[0].message.contains("already used by step 1"));
+        assert_eq!(
+            issues[0].location,
+            Some(PlanLocation { line: 5, column: 5 })
+        );
+    }
+
+    #[test]
+    fn test_unknown_step_tools_are_reported() {
+        let yaml = "name: Deploy\nsteps:\n  - name: inspect\n    action: look\n    tools: [read_file, github__search, wirte_file]\n";
+        let issues = issues_for(yaml);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].pointer, "/steps/0/tools/2");
+        assert_eq!(
+            issues[0].message,
+            "Step 'inspect' lists unknown tool 'wirte_file'"
+        );
+    }
+
+    #[test]
+    fn test_step_execution_mode_must_be_a_known_mode() {
+        let yaml =
+            "name: Deploy\nsteps:\n  - name: build\n    action: go\n    execution_mode: yolo\n";
+        let issues = issues_for(yaml);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].pointer, "/steps/0/execution_mode");
+
+        let yaml = "name: Deploy\nsteps:\n  - name: build\n    action: go\n    execution_mode: full_autonomous\n    tools: []\n";
+        assert!(issues_for(yaml).is_empty());
+    }
+
+    #[test]
+    fn test_step_retries_must_be_a_small_count() {
+        let yaml = "name: Deploy\nsteps:\n  - name: build\n    action: go\n    retries: 11\n";
+        let issues = issues_for(yaml);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].pointer, "/steps/0/retries");
+
+        let yaml = "name: Deploy\nsteps:\n  - name: build\n    action: go\n    retries: 2\n";
+        assert!(issues_for(yaml).is_empty());
+    }
+
+    #[test]
+    fn test_locator_follows_compact_sequences_and_comments() {
+        let yaml = "# plan\nname: Deploy\nsteps:\n- name: a\n  action: one\n\n# second\n- name: b\n  action: \"\"\n";
+        let locator = YamlLocator::new(yaml);
+        assert_eq!(
+            locator.locate("/steps/1/action"),
+            Some(PlanLocation { line: 9, column: 3 })
+        );
+        assert_eq!(
+            locator.locate("/steps/1"),
+            Some(PlanLocation { line: 8, column: 1 })
+        );
+        assert_eq!(
+            locator.locate("/name"),
+            Some(PlanLocation { line: 2, column: 1 })
+        );
+    }
+
+    #[test]
+    fn test_locator_falls_back_to_deepest_node() {
+        let yaml = "name: Deploy\nsteps: [{name: a}]\n";
+        let locator = YamlLocator::new(yaml);
+        assert_eq!(
+            locator.locate("/steps/0"),
+            Some(PlanLocation { line: 2, column: 1 })
+        );
+    }
+
+    #[test]
+    fn test_display_lists_every_issue() {
+        let error = PlanValidationError {
+            issues: vec![
+                PlanIssue::new("/name", "Plan name cannot be empty")
+                    .with_location(PlanLocation { line: 1, column: 1 }),
+                PlanIssue::new("/steps", "Plan must have at least one step"),
+            ],
+        };
+        let text = error.to_string();
+        assert!(text.starts_with("2 problems found"));
+        assert!(text.contains("line 1, column 1: Plan name cannot be empty (at /name)"));
+        assert!(text.contains("Plan must have at least one step (at /steps)"));
+    }
+}
@@ -0,0 +1,317 @@
+//! Token-bucket rate limiting for outbound fetches
+//!
+//! The fetch tool and URL mentions share one [`RateLimiter`] so a prompt
+//! full of links and an agent calling the fetch tool draw from the same
+//! budget. Each request takes a token from the global bucket and from the
+//! bucket of its domain; a request is refused when either bucket is empty,
+//! and the error names the bucket and when it refills.
+//!
+//! Time is passed in explicitly through [`RateLimiter::try_acquire_at`] so
+//! refill behaviour can be tested without sleeping.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::{Duration, Instant};
+//! use xzatoma::config::{FetchRateLimitsConfig, RateLimit};
+//! use xzatoma::tools::rate_limit::RateLimiter;
+//!
+//! let config = FetchRateLimitsConfig {
+//!     global: RateLimit::per_minute(10),
+//!     per_domain: RateLimit::per_minute(1),
+//!     ..FetchRateLimitsConfig::default()
+//! };
+//! let mut limiter = RateLimiter::new(&config);
+//! let start = Instant::now();
+//!
+//! assert!(limiter.try_acquire_at("example.com", start).is_ok());
+//! assert!(limiter.try_acquire_at("example.com", start).is_err());
+//! assert!(limiter.try_acquire_at("docs.rs", start).is_ok());
+//! assert!(limiter
+//!     .try_acquire_at("example.com", start + Duration::from_secs(60))
+//!     .is_ok());
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::config::{FetchRateLimitsConfig, RateLimit};
+use crate::error::{Result, XzatomaError};
+
+/// Rate limiter handle shared between fetch callers
+pub type SharedRateLimiter = Arc<Mutex<RateLimiter>>;
+
+const TOKEN_EPSILON: f64 = 1e-9;
+
+/// A bucket that holds up to `limit.requests` tokens and refills continuously
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: f64::from(limit.requests),
+            updated: now,
+        }
+    }
+
+    /// Seconds needed to refill one token
+    fn seconds_per_token(&self) -> f64 {
+        self.limit.period.as_secs_f64() / f64::from(self.limit.requests.max(1))
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        let capacity = f64::from(self.limit.requests);
+        self.tokens = (self.tokens + elapsed / self.seconds_per_token()).min(capacity);
+        self.updated = now;
+    }
+
+    fn has_token(&self) -> bool {
+        // Refills accumulate in small floating-point steps; tolerate the
+        // rounding so a token that is due is never refused.
+        self.tokens >= 1.0 - TOKEN_EPSILON
+    }
+
+    fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+
+    /// Time until the next token becomes available
+    fn reset_in(&self) -> Duration {
+        let missing = (1.0 - TOKEN_EPSILON - self.tokens).max(0.0);
+        Duration::from_secs_f64(missing * self.seconds_per_token())
+    }
+}
+
+/// Global and per-domain token buckets for fetch requests
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    global: TokenBucket,
+    per_domain: RateLimit,
+    overrides: HashMap<String, RateLimit>,
+    domains: HashMap<String, TokenBucket>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter with full buckets
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Global, per-domain, and override limits
+    pub fn new(config: &FetchRateLimitsConfig) -> Self {
+        Self::new_at(config, Instant::now())
+    }
+
+    /// Creates a limiter whose buckets were last filled at `now`
+    pub fn new_at(config: &FetchRateLimitsConfig, now: Instant) -> Self {
+        Self {
+            global: TokenBucket::new(config.global, now),
+            per_domain: config.per_domain,
+            overrides: config
+                .overrides
+                .iter()
+                .map(|(domain, limit)| (domain.to_ascii_lowercase(), *limit))
+                .collect(),
+            domains: HashMap::new(),
+        }
+    }
+
+    /// Returns the limit that applies to `domain`
+    ///
+    /// An entry in `overrides` takes precedence over `per_domain`.
+    pub fn domain_limit(&self, domain: &str) -> RateLimit {
+        self.overrides
+            .get(&domain.to_ascii_lowercase())
+            .copied()
+            .unwrap_or(self.per_domain)
+    }
+
+    /// Takes a token for a request to `domain` using the current time
+    ///
+    /// # Errors
+    ///
+    /// See [`RateLimiter::try_acquire_at`].
+    pub fn try_acquire(&mut self, domain: &str) -> Result<()> {
+        self.try_acquire_at(domain, Instant::now())
+    }
+
+    /// Takes a token for a request to `domain` at time `now`
+    ///
+    /// No token is taken from either bucket when the request is refused.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`XzatomaError::RateLimitExceeded`] naming the exhausted
+    /// bucket and the time until it allows another request.
+    pub fn try_acquire_at(&mut self, domain: &str, now: Instant) -> Result<()> {
+        let domain = domain.to_ascii_lowercase();
+        let domain_limit = self.domain_limit(&domain);
+
+        self.global.refill(now);
+        let bucket = self
+            .domains
+            .entry(domain.clone())
+            .or_insert_with(|| TokenBucket::new(domain_limit, now));
+        bucket.refill(now);
+
+        if !self.global.has_token() {
+            return Err(exhausted("global bucket", &self.global));
+        }
+        if !bucket.has_token() {
+            return Err(exhausted(&format!("domain bucket for {}", domain), bucket));
+        }
+
+        self.global.take();
+        bucket.take();
+        Ok(())
+    }
+}
+
+fn exhausted(name: &str, bucket: &TokenBucket) -> XzatomaError {
+    let reset_in = bucket.reset_in().as_secs_f64().ceil().max(1.0) as u64;
+    XzatomaError::RateLimitExceeded {
+        limit: bucket.limit.requests,
+        message: format!(
+            "fetch {} ({}) exhausted; resets in {}s",
+            name, bucket.limit, reset_in
+        ),
+    }
+}
+
+/// Returns the process-wide limiter used by fetches and URL mentions
+///
+/// The limiter starts with the default limits until [`configure_shared`]
+/// is called.
+pub fn shared() -> SharedRateLimiter {
+    static SHARED: OnceLock<SharedRateLimiter> = OnceLock::new();
+    SHARED
+        .get_or_init(|| {
+            Arc::new(Mutex::new(RateLimiter::new(
+                &FetchRateLimitsConfig::default(),
+            )))
+        })
+        .clone()
+}
+
+/// Replaces the limits of the process-wide limiter
+///
+/// # Arguments
+///
+/// * `config` - Limits from `tools.fetch_rate_limits`
+pub fn configure_shared(config: &FetchRateLimitsConfig) {
+    let limiter = shared();
+    let mut guard = limiter.lock().unwrap_or_else(|e| e.into_inner());
+    *guard = RateLimiter::new(config);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(global: u32, per_domain: u32) -> FetchRateLimitsConfig {
+        FetchRateLimitsConfig {
+            global: RateLimit::per_minute(global),
+            per_domain: RateLimit::per_minute(per_domain),
+            overrides: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_burst_up_to_domain_capacity() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new_at(&config(10, 4), start);
+
+        for _ in 0..4 {
+            assert!(limiter.try_acquire_at("example.com", start).is_ok());
+        }
+        let err = limiter
+            .try_acquire_at("example.com", start)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("domain bucket for example.com"));
+        assert!(err.contains("4/min"));
+        assert!(err.contains("resets in 15s"));
+    }
+
+    #[test]
+    fn test_global_bucket_limits_across_domains() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new_at(&config(3, 4), start);
+
+        assert!(limiter.try_acquire_at("a.example", start).is_ok());
+        assert!(limiter.try_acquire_at("b.example", start).is_ok());
+        assert!(limiter.try_acquire_at("c.example", start).is_ok());
+        let err = limiter
+            .try_acquire_at("d.example", start)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("global bucket"));
+        assert!(err.contains("resets in 20s"));
+    }
+
+    #[test]
+    fn test_refill_restores_tokens_over_time() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new_at(&config(10, 2), start);
+
+        assert!(limiter.try_acquire_at("example.com", start).is_ok());
+        assert!(limiter.try_acquire_at("example.com", start).is_ok());
+        assert!(limiter.try_acquire_at("example.com", start).is_err());
+
+        let later = start + Duration::from_secs(29);
+        assert!(limiter.try_acquire_at("example.com", later).is_err());
+
+        let refilled = start + Duration::from_secs(30);
+        assert!(limiter.try_acquire_at("example.com", refilled).is_ok());
+        assert!(limiter.try_acquire_at("example.com", refilled).is_err());
+    }
+
+    #[test]
+    fn test_refill_does_not_exceed_capacity() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new_at(&config(10, 2), start);
+
+        let much_later = start + Duration::from_secs(3600);
+        assert!(limiter.try_acquire_at("example.com", much_later).is_ok());
+        assert!(limiter.try_acquire_at("example.com", much_later).is_ok());
+        assert!(limiter.try_acquire_at("example.com", much_later).is_err());
+    }
+
+    #[test]
+    fn test_override_takes_precedence_over_per_domain() {
+        let start = Instant::now();
+        let mut cfg = config(10, 4);
+        cfg.overrides
+            .insert("API.github.com".to_string(), RateLimit::per_minute(2));
+        let mut limiter = RateLimiter::new_at(&cfg, start);
+
+        assert_eq!(limiter.domain_limit("api.github.com").requests, 2);
+        assert_eq!(limiter.domain_limit("example.com").requests, 4);
+
+        assert!(limiter.try_acquire_at("api.github.com", start).is_ok());
+        assert!(limiter.try_acquire_at("api.github.com", start).is_ok());
+        let err = limiter
+            .try_acquire_at("api.github.com", start)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("domain bucket for api.github.com (2/min)"));
+        assert!(limiter.try_acquire_at("example.com", start).is_ok());
+    }
+
+    #[test]
+    fn test_refused_request_does_not_consume_global_token() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new_at(&config(2, 1), start);
+
+        assert!(limiter.try_acquire_at("example.com", start).is_ok());
+        assert!(limiter.try_acquire_at("example.com", start).is_err());
+        assert!(limiter.try_acquire_at("docs.rs", start).is_ok());
+    }
+}
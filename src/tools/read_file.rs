@@ -1,13 +1,21 @@
 //! read_file tool for reading file contents
 //!
-//! Provides a tool to read file contents with optional line range support.
-//! Handles image files by returning outline information instead of raw content.
+//! Provides a tool to read file contents with optional line range, head, and
+//! tail support. Partial reads stream the file line by line, so a tail of a
+//! huge log never loads the whole file, and size limits apply to the selected
+//! lines rather than the file. Binary files and text files over the read
+//! limit produce a summary (size, detected type, line count) instead of an
+//! error, with an optional hexdump preview for binary content.
 
 use crate::error::{Result, XzatomaError};
+use crate::format::format_count;
+use crate::tools::file_summary;
 use crate::tools::{file_metadata, file_utils, parse_tool_args, ToolExecutor, ToolResult};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::VecDeque;
 use std::path::{Path, PathBuf};
+use tokio::io::AsyncBufReadExt;
 
 /// Parameters for the read_file tool
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,13 +28,95 @@ struct ReadFileParams {
     /// Optional ending line number (1-based index, inclusive)
     #[serde(skip_serializing_if = "Option::is_none")]
     end_line: Option<u32>,
+    /// Optional number of lines to read from the start of the file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    head: Option<u32>,
+    /// Optional number of lines to read from the end of the file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tail: Option<u32>,
+    /// Include a hexdump of the first bytes when the file is binary
+    #[serde(default)]
+    allow_binary_preview: bool,
+}
+
+/// Part of a file requested by the read_file parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineSelection {
+    /// Lines `start..=end` (1-based); `end` defaults to the last line
+    Range { start: usize, end: Option<usize> },
+    /// The first N lines
+    Head(usize),
+    /// The last N lines
+    Tail(usize),
+}
+
+impl LineSelection {
+    /// Validates the selection parameters
+    ///
+    /// `start_line`/`end_line`, `head`, and `tail` are mutually exclusive.
+    /// Returns `Ok(None)` when the whole file is requested.
+    fn from_params(params: &ReadFileParams) -> std::result::Result<Option<Self>, String> {
+        let range = params.start_line.is_some() || params.end_line.is_some();
+        let groups = [range, params.head.is_some(), params.tail.is_some()]
+            .iter()
+            .filter(|selected| **selected)
+            .count();
+        if groups > 1 {
+            return Err(
+                "start_line/end_line, head, and tail are mutually exclusive; use only one"
+                    .to_string(),
+            );
+        }
+
+        if let Some(head) = params.head {
+            if head == 0 {
+                return Err("head must be greater than 0".to_string());
+            }
+            return Ok(Some(Self::Head(head as usize)));
+        }
+        if let Some(tail) = params.tail {
+            if tail == 0 {
+                return Err("tail must be greater than 0".to_string());
+            }
+            return Ok(Some(Self::Tail(tail as usize)));
+        }
+        if !range {
+            return Ok(None);
+        }
+
+        let start = params.start_line.unwrap_or(1);
+        if start == 0 || params.end_line == Some(0) {
+            return Err("Line numbers must be greater than 0 (1-based index)".to_string());
+        }
+        if let Some(end) = params.end_line {
+            if start > end {
+                return Err("start_line must be less than or equal to end_line".to_string());
+            }
+        }
+        Ok(Some(Self::Range {
+            start: start as usize,
+            end: params.end_line.map(|end| end as usize),
+        }))
+    }
+
+    /// Returns true when a 1-based line number is part of a range or head
+    /// selection
+    fn contains(&self, line_number: usize) -> bool {
+        match *self {
+            Self::Range { start, end } => {
+                line_number >= start && end.map_or(true, |end| line_number <= end)
+            }
+            Self::Head(count) => line_number <= count,
+            Self::Tail(_) => false,
+        }
+    }
 }
 
 /// Tool for reading file contents
 ///
 /// Reads files with support for line range specification.
-/// For large files, generates an outline instead of loading full content.
-/// For image files, returns file information instead of raw content.
+/// For files with many lines, generates an outline instead of loading full
+/// content. Binary files and files over the size limit are summarized.
 ///
 /// # Examples
 ///
@@ -131,14 +221,118 @@ impl ReadFileTool {
 
         Ok(outline)
     }
+
+    /// Reads the selected lines of a file without loading the whole file
+    ///
+    /// Lines are streamed through a buffered reader. A tail read keeps only
+    /// the last N lines in memory. The size limit applies to the selected
+    /// lines, and the output is annotated with the line numbers shown and
+    /// the total line count.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Validated path to the file
+    /// * `selection` - Lines to return
+    ///
+    /// # Returns
+    ///
+    /// Returns the annotated lines, or an error result when the selection is
+    /// past the end of the file or larger than the read limit
+    async fn read_selection(&self, path: &Path, selection: LineSelection) -> Result<ToolResult> {
+        let file = tokio::fs::File::open(path)
+            .await
+            .map_err(XzatomaError::Io)?;
+        let mut lines = tokio::io::BufReader::new(file).lines();
+
+        let mut selected: VecDeque<String> = VecDeque::new();
+        let mut selected_bytes: u64 = 0;
+        let mut total_lines: usize = 0;
+
+        while let Some(line) = lines.next_line().await.map_err(XzatomaError::Io)? {
+            total_lines += 1;
+            match selection {
+                LineSelection::Tail(count) => {
+                    selected_bytes += line.len() as u64 + 1;
+                    selected.push_back(line);
+                    if selected.len() > count {
+                        if let Some(dropped) = selected.pop_front() {
+                            selected_bytes -= dropped.len() as u64 + 1;
+                        }
+                    }
+                }
+                _ if selection.contains(total_lines) => {
+                    selected_bytes += line.len() as u64 + 1;
+                    selected.push_back(line);
+                    if selected_bytes > self.max_file_size {
+                        return Ok(slice_too_large(selected_bytes, self.max_file_size));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let LineSelection::Range { start, .. } = selection {
+            if start > total_lines {
+                return Ok(ToolResult::error(format!(
+                    "start_line {} exceeds file length of {} lines",
+                    start, total_lines
+                )));
+            }
+        }
+        if selected_bytes > self.max_file_size {
+            return Ok(slice_too_large(selected_bytes, self.max_file_size));
+        }
+
+        let first_line = match selection {
+            LineSelection::Range { start, .. } => start,
+            LineSelection::Head(_) => 1,
+            LineSelection::Tail(_) => total_lines - selected.len() + 1,
+        };
+        let shown = selected.len();
+        let content = Vec::from(selected).join("\n");
+
+        if shown == 0 {
+            return Ok(ToolResult::success(format!(
+                "(showing 0 lines of {})",
+                format_count(total_lines as u64)
+            ))
+            .with_metadata("total_lines".to_string(), total_lines.to_string()));
+        }
+
+        let last_line = first_line + shown - 1;
+        Ok(ToolResult::success(format!(
+            "(showing lines {}-{} of {})\n{}",
+            format_count(first_line as u64),
+            format_count(last_line as u64),
+            format_count(total_lines as u64),
+            content
+        ))
+        .with_metadata(
+            "line_range".to_string(),
+            format!("{}-{}", first_line, last_line),
+        )
+        .with_metadata("total_lines".to_string(), total_lines.to_string()))
+    }
+}
+
+/// Builds the error result for a selection larger than the read limit
+fn slice_too_large(selected_bytes: u64, max_size: u64) -> ToolResult {
+    ToolResult::error(format!(
+        "Selected lines exceed the maximum read size ({} > {} bytes); request fewer lines",
+        selected_bytes, max_size
+    ))
 }
 
 #[async_trait::async_trait]
 impl ToolExecutor for ReadFileTool {
+    fn read_only(&self) -> bool {
+        true
+    }
+
     fn tool_definition(&self) -> serde_json::Value {
         json!({
             "name": "read_file",
-            "description": "Read the contents of a file. For large files, shows outline with first and last 50 lines. For image files, shows file information instead of raw content. Supports optional line range specification.",
+            "description": "Read the contents of a file. For large files, shows outline with first and last 50 lines. Binary files (images, archives, executables) return a summary with size and detected type; pass allow_binary_preview: true to include a hexdump of the first bytes. Text files over the size limit return their size and line count. To read part of a large file or log, pass start_line/end_line, head, or tail (only one of these); size limits then apply to the selected lines only, and the output starts with a note such as \"(showing lines 400-450 of 18,234)\".",
             "parameters": {
                 "type": "object",
                 "properties": {
@@ -153,6 +347,20 @@ impl ToolExecutor for ReadFileTool {
                     "end_line": {
                         "type": "integer",
                         "description": "Optional ending line number (1-based index, inclusive)"
+                    },
+                    "head": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Optional number of lines to read from the start of the file. Cannot be combined with start_line/end_line or tail"
+                    },
+                    "tail": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Optional number of lines to read from the end of the file. Cannot be combined with start_line/end_line or head"
+                    },
+                    "allow_binary_preview": {
+                        "type": "boolean",
+                        "description": "When the file is binary, include a hexdump of its first 256 bytes in the summary (default false)"
                     }
                 },
                 "required": ["path"]
@@ -162,6 +370,10 @@ impl ToolExecutor for ReadFileTool {
 
     async fn execute(&self, args: serde_json::Value) -> Result<ToolResult> {
         let params: ReadFileParams = parse_tool_args(args)?;
+        let selection = match LineSelection::from_params(&params) {
+            Ok(selection) => selection,
+            Err(message) => return Ok(ToolResult::error(message)),
+        };
 
         // Validate path
         let path = self.path_validator.validate(&params.path)?;
@@ -182,68 +394,58 @@ impl ToolExecutor for ReadFileTool {
             )));
         }
 
-        // Check file size
-        file_utils::check_file_size(&path, self.max_file_size).await?;
-
-        // Check if it's an image
-        if file_metadata::is_image_file(&path) {
-            // For images, return file info instead of content
-            match file_metadata::get_file_info(&path).await {
-                Ok(info) => {
-                    return Ok(ToolResult::success(format!(
-                        "Image file: {}\nSize: {} bytes\nModified: {:?}\nRead-only: {}",
-                        params.path, info.size, info.modified, info.readonly
-                    )));
-                }
-                Err(e) => {
-                    return Ok(ToolResult::error(format!(
-                        "Failed to read image metadata: {}",
-                        e
-                    )));
-                }
+        // Summarize binary content instead of decoding it as text
+        let sample = file_summary::read_sample(&path, file_summary::BINARY_SAMPLE_SIZE)
+            .await
+            .map_err(XzatomaError::Io)?;
+        if file_summary::looks_binary(&sample) || file_metadata::is_image_file(&path) {
+            let size = tokio::fs::metadata(&path)
+                .await
+                .map_err(XzatomaError::Io)?
+                .len();
+            let preview = params
+                .allow_binary_preview
+                .then_some(file_summary::DEFAULT_HEXDUMP_BYTES);
+            return Ok(ToolResult::success(file_summary::binary_summary(
+                &params.path,
+                size,
+                &sample,
+                preview,
+            ))
+            .with_metadata("binary".to_string(), "true".to_string()));
+        }
+
+        // Whole-file reads over the size limit get a summary; partial reads
+        // check the selected lines instead
+        if selection.is_none() {
+            let size = tokio::fs::metadata(&path)
+                .await
+                .map_err(XzatomaError::Io)?
+                .len();
+            if size > self.max_file_size {
+                let line_count = file_summary::count_lines(&path)
+                    .await
+                    .map_err(XzatomaError::Io)?;
+                return Ok(ToolResult::success(file_summary::large_text_summary(
+                    &params.path,
+                    size,
+                    line_count,
+                    self.max_file_size,
+                ))
+                .with_metadata("total_lines".to_string(), line_count.to_string())
+                .with_metadata("truncated".to_string(), "true".to_string()));
             }
         }
 
+        if let Some(selection) = selection {
+            return self.read_selection(&path, selection).await;
+        }
+
         // Read file content
         let content = tokio::fs::read_to_string(&path)
             .await
             .map_err(XzatomaError::Io)?;
 
-        // Handle line range if specified
-        if let (Some(start_line), Some(end_line)) = (params.start_line, params.end_line) {
-            if start_line == 0 || end_line == 0 {
-                return Ok(ToolResult::error(
-                    "Line numbers must be greater than 0 (1-based index)".to_string(),
-                ));
-            }
-            if start_line > end_line {
-                return Ok(ToolResult::error(
-                    "start_line must be less than or equal to end_line".to_string(),
-                ));
-            }
-
-            let lines: Vec<&str> = content.lines().collect();
-            let total_lines = lines.len() as u32;
-
-            if start_line > total_lines {
-                return Ok(ToolResult::error(format!(
-                    "start_line {} exceeds file length of {} lines",
-                    start_line, total_lines
-                )));
-            }
-
-            let actual_end = std::cmp::min(end_line, total_lines);
-            let selected_lines = lines
-                .iter()
-                .skip((start_line - 1) as usize)
-                .take((actual_end - start_line + 1) as usize)
-                .map(|l| l.to_string())
-                .collect::<Vec<_>>()
-                .join("\n");
-
-            return Ok(ToolResult::success(selected_lines));
-        }
-
         // Check if file is too large for full content
         if (content.lines().count() as u32) > self.max_outline_lines {
             let outline = self.generate_outline(&path, &content).await?;
@@ -289,7 +491,200 @@ mod tests {
             .unwrap();
 
         assert!(result.success);
-        assert_eq!(result.output, "line2\nline3\nline4");
+        assert_eq!(
+            result.output,
+            "(showing lines 2-4 of 5)\nline2\nline3\nline4"
+        );
+        assert_eq!(result.metadata.get("line_range").unwrap(), "2-4");
+        assert_eq!(result.metadata.get("total_lines").unwrap(), "5");
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_head_returns_first_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("test.txt"), "a\nb\nc\nd").unwrap();
+
+        let tool = ReadFileTool::new(temp_dir.path().to_path_buf(), 1024 * 1024, 100);
+        let result = tool
+            .execute(json!({"path": "test.txt", "head": 2}))
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.output, "(showing lines 1-2 of 4)\na\nb");
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_start_line_only_reads_to_end() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("test.txt"), "a\nb\nc\nd").unwrap();
+
+        let tool = ReadFileTool::new(temp_dir.path().to_path_buf(), 1024 * 1024, 100);
+        let result = tool
+            .execute(json!({"path": "test.txt", "start_line": 3}))
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.output, "(showing lines 3-4 of 4)\nc\nd");
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_tail_on_file_larger_than_read_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let content: String = (1..=20_000).map(|i| format!("log line {}\n", i)).collect();
+        fs::write(temp_dir.path().join("app.log"), &content).unwrap();
+        let max_size = 4 * 1024;
+        assert!(content.len() as u64 > max_size);
+
+        let tool = ReadFileTool::new(temp_dir.path().to_path_buf(), max_size, 100);
+
+        // A whole-file read returns a summary instead of the content
+        let summary = tool.execute(json!({"path": "app.log"})).await.unwrap();
+        assert!(summary.output.contains("too large to read in full"));
+
+        let result = tool
+            .execute(json!({"path": "app.log", "tail": 3}))
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(
+            result.output,
+            "(showing lines 19,998-20,000 of 20,000)\nlog line 19998\nlog line 19999\nlog line 20000"
+        );
+        assert_eq!(result.metadata.get("total_lines").unwrap(), "20000");
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_selection_larger_than_read_limit_returns_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let content: String = (1..=1_000).map(|i| format!("line {}\n", i)).collect();
+        fs::write(temp_dir.path().join("big.txt"), content).unwrap();
+
+        let tool = ReadFileTool::new(temp_dir.path().to_path_buf(), 1024, 100);
+        for args in [
+            json!({"path": "big.txt", "head": 900}),
+            json!({"path": "big.txt", "tail": 900}),
+            json!({"path": "big.txt", "start_line": 10, "end_line": 900}),
+        ] {
+            let result = tool.execute(args).await.unwrap();
+            assert!(!result.success);
+            assert!(result.error.unwrap().contains("maximum read size"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_tail_longer_than_file_returns_all_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("test.txt"), "a\nb").unwrap();
+        fs::write(temp_dir.path().join("empty.txt"), "").unwrap();
+
+        let tool = ReadFileTool::new(temp_dir.path().to_path_buf(), 1024 * 1024, 100);
+        let result = tool
+            .execute(json!({"path": "test.txt", "tail": 10}))
+            .await
+            .unwrap();
+        assert_eq!(result.output, "(showing lines 1-2 of 2)\na\nb");
+
+        let result = tool
+            .execute(json!({"path": "empty.txt", "tail": 10}))
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(result.output, "(showing 0 lines of 0)");
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_invalid_selection_combinations_returns_error() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("test.txt"), "a\nb\nc").unwrap();
+
+        let tool = ReadFileTool::new(temp_dir.path().to_path_buf(), 1024 * 1024, 100);
+        for args in [
+            json!({"path": "test.txt", "head": 1, "tail": 1}),
+            json!({"path": "test.txt", "start_line": 1, "head": 2}),
+            json!({"path": "test.txt", "end_line": 2, "tail": 1}),
+        ] {
+            let result = tool.execute(args).await.unwrap();
+            assert!(!result.success);
+            assert!(result.error.unwrap().contains("mutually exclusive"));
+        }
+
+        for args in [
+            json!({"path": "test.txt", "head": 0}),
+            json!({"path": "test.txt", "tail": 0}),
+            json!({"path": "test.txt", "start_line": 0}),
+            json!({"path": "test.txt", "start_line": 3, "end_line": 2}),
+        ] {
+            let result = tool.execute(args).await.unwrap();
+            assert!(!result.success);
+        }
+    }
+
+    /// A complete 1x1 transparent PNG
+    const PNG_FIXTURE: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f,
+        0x15, 0xc4, 0x89, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00,
+        0x01, 0x00, 0x00, 0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49,
+        0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    #[tokio::test]
+    async fn test_execute_with_png_returns_binary_summary() {
+        let temp_dir = TempDir::new().unwrap();
+        // Misleading extension: detection uses the content, not the name
+        fs::write(temp_dir.path().join("pixel.dat"), PNG_FIXTURE).unwrap();
+
+        let tool = ReadFileTool::new(temp_dir.path().to_path_buf(), 1024 * 1024, 100);
+        let result = tool.execute(json!({"path": "pixel.dat"})).await.unwrap();
+
+        assert!(result.success);
+        assert!(result.output.contains("Binary file: pixel.dat"));
+        assert!(result.output.contains("Size: 67 B"));
+        assert!(result.output.contains("Detected type: PNG image"));
+        assert!(!result.output.contains("00000000"));
+        assert_eq!(result.metadata.get("binary").unwrap(), "true");
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_binary_preview_includes_hexdump() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("pixel.png"), PNG_FIXTURE).unwrap();
+
+        let tool = ReadFileTool::new(temp_dir.path().to_path_buf(), 1024 * 1024, 100);
+        let result = tool
+            .execute(json!({"path": "pixel.png", "allow_binary_preview": true}))
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert!(result.output.contains("First 67 bytes:"));
+        assert!(result.output.contains(
+            "00000000  89 50 4e 47 0d 0a 1a 0a  00 00 00 0d 49 48 44 52  |.PNG........IHDR|"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_large_text_file_returns_summary() {
+        let temp_dir = TempDir::new().unwrap();
+        let content: String = (1..=50_000)
+            .map(|i| format!("2024-01-01T00:00:00Z INFO request {} handled\n", i))
+            .collect();
+        fs::write(temp_dir.path().join("server.log"), &content).unwrap();
+
+        let tool = ReadFileTool::new(temp_dir.path().to_path_buf(), 64 * 1024, 100);
+        let result = tool.execute(json!({"path": "server.log"})).await.unwrap();
+
+        assert!(result.success);
+        assert!(result.output.contains("Lines: 50,000"));
+        assert!(result
+            .output
+            .contains(&crate::format::format_bytes(content.len() as u64)));
+        assert!(result.output.contains("\"tail\": 200"));
+        assert!(!result.output.contains("request 1 handled"));
+        assert_eq!(result.metadata.get("total_lines").unwrap(), "50000");
     }
 
     #[tokio::test]
@@ -6,8 +6,14 @@
 //! In Planning mode, only read-only tools are registered.
 //! In Write mode, all tools are registered.
 //!
+//! Both modes register the `finish` tool, which the agent calls to end a run
+//! with a structured result.
+//!
 //! When provided by the command layer, the builder may also register the
 //! synthetic `activate_skill` tool after standard mode-aware tool setup.
+//!
+//! With a read-only policy, registries are built for Planning mode and
+//! refuse any mutating tool registered later; see [`crate::read_only`].
 
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -17,15 +23,22 @@ use tracing::debug;
 use crate::chat_mode::{ChatMode, SafetyMode};
 use crate::config::{TerminalConfig, ToolsConfig};
 use crate::error::Result;
+use crate::network_policy::NetworkPolicy;
+use crate::read_only::ReadOnlyPolicy;
+
+use crate::tools::audit_log::AuditLog;
+use crate::tools::confirmation::ConfirmationPolicy;
 
 use crate::tools::copy_path::CopyPathTool;
 use crate::tools::create_directory::CreateDirectoryTool;
 use crate::tools::delete_path::DeletePathTool;
 use crate::tools::edit_file::EditFileTool;
 use crate::tools::find_path::FindPathTool;
+use crate::tools::finish::{FinishTool, FINISH_TOOL_NAME};
 use crate::tools::list_directory::ListDirectoryTool;
 use crate::tools::move_path::MovePathTool;
 use crate::tools::read_file::ReadFileTool;
+use crate::tools::script_tool::ScriptToolExecutor;
 use crate::tools::terminal::{CommandValidator, TerminalTool};
 use crate::tools::write_file::WriteFileTool;
 use crate::tools::{ToolExecutor, ToolRegistry};
@@ -49,7 +62,7 @@ use crate::tools::{ToolExecutor, ToolRegistry};
 /// );
 ///
 /// let registry = builder.build_for_planning().expect("Failed to build registry");
-/// assert_eq!(registry.len(), 3); // read_file, list_directory, find_path
+/// assert_eq!(registry.len(), 4); // read_file, list_directory, find_path, finish
 /// ```
 pub struct ToolRegistryBuilder {
     /// The chat mode (Planning or Write)
@@ -64,6 +77,14 @@ pub struct ToolRegistryBuilder {
     terminal_config: TerminalConfig,
     /// Optional activate_skill tool registration
     activate_skill_tool: Option<Arc<dyn ToolExecutor>>,
+    /// Network policy applied to registered tools
+    network_policy: NetworkPolicy,
+    /// Read-only policy applied to registered tools
+    read_only: ReadOnlyPolicy,
+    /// Audit log shared by the file-mutating tools
+    audit_log: Option<Arc<AuditLog>>,
+    /// Confirmation policy shared by the mutating tools
+    confirmation: Option<ConfirmationPolicy>,
 }
 
 impl ToolRegistryBuilder {
@@ -86,6 +107,10 @@ impl ToolRegistryBuilder {
             tools_config: ToolsConfig::default(),
             terminal_config: TerminalConfig::default(),
             activate_skill_tool: None,
+            network_policy: NetworkPolicy::default(),
+            read_only: ReadOnlyPolicy::default(),
+            audit_log: None,
+            confirmation: None,
         }
     }
 
@@ -117,6 +142,73 @@ impl ToolRegistryBuilder {
         self
     }
 
+    /// Set the network policy applied to registered tools
+    ///
+    /// In offline mode, tools that need the network are left out of the
+    /// built registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The network policy
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining
+    pub fn with_network_policy(mut self, policy: NetworkPolicy) -> Self {
+        self.network_policy = policy;
+        self
+    }
+
+    /// Set the read-only policy applied to registered tools
+    ///
+    /// With read-only mode enabled, [`build`](Self::build) builds the
+    /// Planning mode tools whatever the chat mode, and the built registry
+    /// skips the terminal and refuses every call to a mutating tool.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The read-only policy
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining
+    pub fn with_read_only_policy(mut self, policy: ReadOnlyPolicy) -> Self {
+        self.read_only = policy;
+        self
+    }
+
+    /// Set the audit log shared by the file-mutating tools
+    ///
+    /// # Arguments
+    ///
+    /// * `audit_log` - The audit log, or `None` to disable auditing
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining
+    pub fn with_audit_log(mut self, audit_log: Option<Arc<AuditLog>>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// Set the confirmation policy shared by the mutating tools
+    ///
+    /// Without one, a policy for the builder's safety mode with fresh grants
+    /// is used. Pass a policy sharing the session's grants so confirmations
+    /// survive rebuilding the registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `confirmation` - The confirmation policy
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining
+    pub fn with_confirmation(mut self, confirmation: ConfirmationPolicy) -> Self {
+        self.confirmation = Some(confirmation);
+        self
+    }
+
     /// Register an optional `activate_skill` tool.
     ///
     /// The tool is registered only when explicitly provided by the command
@@ -148,7 +240,7 @@ impl ToolRegistryBuilder {
     ///
     /// Returns error if tool initialization fails
     pub fn build(&self) -> Result<ToolRegistry> {
-        match self.mode {
+        match self.read_only.effective_mode(self.mode) {
             ChatMode::Planning => self.build_for_planning(),
             ChatMode::Write => self.build_for_write(),
         }
@@ -192,7 +284,7 @@ impl ToolRegistryBuilder {
     /// );
     ///
     /// let registry = builder.build_for_chat(false).expect("Failed to build registry");
-    /// assert_eq!(registry.len(), 10); // All standard Write mode tools
+    /// assert_eq!(registry.len(), 11); // All standard Write mode tools
     /// ```
     pub fn build_for_chat(&self, subagents_enabled: bool) -> Result<ToolRegistry> {
         // Build the base registry for the current mode
@@ -219,6 +311,7 @@ impl ToolRegistryBuilder {
     /// - `read_file` - Read file contents with optional line range
     /// - `list_directory` - List directory contents with optional recursion and pattern matching
     /// - `find_path` - Find files by glob pattern
+    /// - `finish` - End the run with a structured result
     ///
     /// Excluded:
     /// - Terminal execution
@@ -228,7 +321,9 @@ impl ToolRegistryBuilder {
     ///
     /// Returns a ToolRegistry with only read-only tools
     pub fn build_for_planning(&self) -> Result<ToolRegistry> {
-        let mut registry = ToolRegistry::new();
+        let mut registry = ToolRegistry::new()
+            .with_network_policy(self.network_policy)
+            .with_read_only_policy(self.read_only);
 
         // Register read_file tool
         let read_tool = ReadFileTool::new(
@@ -249,6 +344,8 @@ impl ToolRegistryBuilder {
         let find_tool_executor: Arc<dyn ToolExecutor> = Arc::new(find_tool);
         registry.register("find_path", find_tool_executor);
 
+        registry.register(FINISH_TOOL_NAME, Arc::new(FinishTool));
+
         self.register_activate_skill_tool(&mut registry);
 
         Ok(registry)
@@ -267,10 +364,15 @@ impl ToolRegistryBuilder {
     /// - `find_path` - Find files by glob pattern
     /// - `edit_file` - Edit files with targeted replacements or create new files
     /// - `terminal` - Terminal command execution with safety validation
+    /// - `finish` - End the run with a structured result
+    ///
+    /// Shell-script tools from `tools.custom` are registered too.
     ///
     /// The terminal tool respects the configured safety mode:
     /// - `AlwaysConfirm` - Requires confirmation for dangerous operations
     /// - `NeverConfirm` - Allows all non-blacklisted operations
+    /// - `ConfirmOnce` - Requires confirmation for the first action of each
+    ///   category; the file tools follow the same policy
     ///
     /// # Returns
     ///
@@ -280,7 +382,14 @@ impl ToolRegistryBuilder {
     ///
     /// Returns error if tool initialization fails
     pub fn build_for_write(&self) -> Result<ToolRegistry> {
-        let mut registry = ToolRegistry::new();
+        let mut registry = ToolRegistry::new()
+            .with_network_policy(self.network_policy)
+            .with_read_only_policy(self.read_only);
+        let confirmation = Some(
+            self.confirmation
+                .clone()
+                .unwrap_or_else(|| ConfirmationPolicy::new(self.safety_mode)),
+        );
 
         // Register read_file tool
         let read_tool = ReadFileTool::new(
@@ -295,12 +404,16 @@ impl ToolRegistryBuilder {
         let write_tool = WriteFileTool::new(
             self.working_dir.clone(),
             self.tools_config.max_file_read_size as u64,
-        );
+        )
+        .with_audit_log(self.audit_log.clone())
+        .with_confirmation(confirmation.clone());
         let write_tool_executor: Arc<dyn ToolExecutor> = Arc::new(write_tool);
         registry.register("write_file", write_tool_executor);
 
         // Register delete_path tool
-        let delete_tool = DeletePathTool::new(self.working_dir.clone());
+        let delete_tool = DeletePathTool::new(self.working_dir.clone())
+            .with_audit_log(self.audit_log.clone())
+            .with_confirmation(confirmation.clone());
         let delete_tool_executor: Arc<dyn ToolExecutor> = Arc::new(delete_tool);
         registry.register("delete_path", delete_tool_executor);
 
@@ -310,17 +423,23 @@ impl ToolRegistryBuilder {
         registry.register("list_directory", list_tool_executor);
 
         // Register copy_path tool
-        let copy_tool = CopyPathTool::new(self.working_dir.clone());
+        let copy_tool = CopyPathTool::new(self.working_dir.clone())
+            .with_audit_log(self.audit_log.clone())
+            .with_confirmation(confirmation.clone());
         let copy_tool_executor: Arc<dyn ToolExecutor> = Arc::new(copy_tool);
         registry.register("copy_path", copy_tool_executor);
 
         // Register move_path tool
-        let move_tool = MovePathTool::new(self.working_dir.clone());
+        let move_tool = MovePathTool::new(self.working_dir.clone())
+            .with_audit_log(self.audit_log.clone())
+            .with_confirmation(confirmation.clone());
         let move_tool_executor: Arc<dyn ToolExecutor> = Arc::new(move_tool);
         registry.register("move_path", move_tool_executor);
 
         // Register create_directory tool
-        let create_tool = CreateDirectoryTool::new(self.working_dir.clone());
+        let create_tool = CreateDirectoryTool::new(self.working_dir.clone())
+            .with_audit_log(self.audit_log.clone())
+            .with_confirmation(confirmation.clone());
         let create_tool_executor: Arc<dyn ToolExecutor> = Arc::new(create_tool);
         registry.register("create_directory", create_tool_executor);
 
@@ -333,18 +452,36 @@ impl ToolRegistryBuilder {
         let edit_tool = EditFileTool::new(
             self.working_dir.clone(),
             self.tools_config.max_file_read_size as u64,
-        );
+        )
+        .with_audit_log(self.audit_log.clone())
+        .with_confirmation(confirmation.clone());
         let edit_tool_executor: Arc<dyn ToolExecutor> = Arc::new(edit_tool);
         registry.register("edit_file", edit_tool_executor);
 
         // Register terminal tool with safety mode
         let terminal_validator =
             CommandValidator::new(self.terminal_config.default_mode, self.working_dir.clone());
-        let terminal_tool = TerminalTool::new(terminal_validator, self.terminal_config.clone())
-            .with_safety_mode(self.safety_mode);
+        let terminal_tool =
+            TerminalTool::new(terminal_validator.clone(), self.terminal_config.clone())
+                .with_safety_mode(self.safety_mode)
+                .with_confirmation(confirmation.clone());
         let terminal_tool_executor: Arc<dyn ToolExecutor> = Arc::new(terminal_tool);
         registry.register("terminal", terminal_tool_executor);
 
+        // Register the shell-script tools from `tools.custom`
+        for custom in &self.tools_config.custom {
+            let script_tool = ScriptToolExecutor::new(
+                custom.clone(),
+                terminal_validator.clone(),
+                self.terminal_config.clone(),
+            )
+            .with_safety_mode(self.safety_mode)
+            .with_confirmation(confirmation.clone());
+            registry.register(custom.name.clone(), Arc::new(script_tool));
+        }
+
+        registry.register(FINISH_TOOL_NAME, Arc::new(FinishTool));
+
         self.register_activate_skill_tool(&mut registry);
 
         Ok(registry)
@@ -436,7 +573,8 @@ mod tests {
         let registry = builder
             .build_for_planning()
             .expect("Failed to build registry");
-        assert_eq!(registry.len(), 3);
+        assert_eq!(registry.len(), 4);
+        assert!(registry.get("finish").is_some());
         assert!(registry.get("read_file").is_some());
         assert!(registry.get("list_directory").is_some());
         assert!(registry.get("find_path").is_some());
@@ -444,6 +582,42 @@ mod tests {
         assert!(registry.get("write_file").is_none());
     }
 
+    #[test]
+    fn test_offline_build_contains_no_fetch_tool() {
+        let builder = ToolRegistryBuilder::new(
+            ChatMode::Write,
+            SafetyMode::NeverConfirm,
+            PathBuf::from("."),
+        )
+        .with_network_policy(NetworkPolicy::offline());
+
+        let mut registry = builder.build().expect("Failed to build registry");
+        assert!(registry.network_policy().is_offline());
+        assert!(registry.get("fetch").is_none());
+        assert!(registry.get("read_file").is_some());
+
+        // Tools registered later by the command layer are filtered too
+        let fetch: Arc<dyn ToolExecutor> = registry.get("read_file").unwrap();
+        registry.register("fetch", fetch);
+        assert!(registry.get("fetch").is_none());
+    }
+
+    #[test]
+    fn test_read_only_build_uses_planning_tools_in_write_mode() {
+        let builder = ToolRegistryBuilder::new(
+            ChatMode::Write,
+            SafetyMode::NeverConfirm,
+            PathBuf::from("."),
+        )
+        .with_read_only_policy(ReadOnlyPolicy::enforced());
+
+        let registry = builder.build().expect("Failed to build registry");
+        assert!(registry.read_only_policy().is_enabled());
+        assert_eq!(registry.len(), 4);
+        assert!(registry.get("terminal").is_none());
+        assert!(registry.get("write_file").is_none());
+    }
+
     #[test]
     fn test_build_for_write() {
         let builder = ToolRegistryBuilder::new(
@@ -453,7 +627,8 @@ mod tests {
         );
 
         let registry = builder.build_for_write().expect("Failed to build registry");
-        assert_eq!(registry.len(), 10);
+        assert_eq!(registry.len(), 11);
+        assert!(registry.get("finish").is_some());
         assert!(registry.get("read_file").is_some());
         assert!(registry.get("write_file").is_some());
         assert!(registry.get("delete_path").is_some());
@@ -466,6 +641,36 @@ mod tests {
         assert!(registry.get("terminal").is_some());
     }
 
+    #[test]
+    fn test_build_for_write_registers_custom_tools() {
+        let mut tools_config = ToolsConfig::default();
+        tools_config.custom = vec![serde_yaml::from_str(
+            "name: run_tests\ndescription: Run the tests\ncommand: make test\n",
+        )
+        .unwrap()];
+        let builder = ToolRegistryBuilder::new(
+            ChatMode::Write,
+            SafetyMode::NeverConfirm,
+            PathBuf::from("."),
+        )
+        .with_tools_config(tools_config.clone());
+
+        let registry = builder.build_for_write().expect("Failed to build registry");
+        assert_eq!(registry.len(), 12);
+        let definition = registry.get("run_tests").unwrap().tool_definition();
+        assert_eq!(definition["description"], "Run the tests");
+
+        let planning = ToolRegistryBuilder::new(
+            ChatMode::Planning,
+            SafetyMode::NeverConfirm,
+            PathBuf::from("."),
+        )
+        .with_tools_config(tools_config)
+        .build_for_planning()
+        .unwrap();
+        assert!(planning.get("run_tests").is_none());
+    }
+
     #[test]
     fn test_build_delegates_to_mode() {
         let planning_builder = ToolRegistryBuilder::new(
@@ -475,7 +680,7 @@ mod tests {
         );
 
         let planning_registry = planning_builder.build().expect("Failed to build registry");
-        assert_eq!(planning_registry.len(), 3);
+        assert_eq!(planning_registry.len(), 4);
 
         let write_builder = ToolRegistryBuilder::new(
             ChatMode::Write,
@@ -484,7 +689,7 @@ mod tests {
         );
 
         let write_registry = write_builder.build().expect("Failed to build registry");
-        assert_eq!(write_registry.len(), 10);
+        assert_eq!(write_registry.len(), 11);
     }
 
     #[test]
@@ -511,7 +716,7 @@ mod tests {
         let registry = builder
             .build_for_chat(false)
             .expect("Failed to build registry");
-        assert_eq!(registry.len(), 3); // read_file, list_directory, find_path
+        assert_eq!(registry.len(), 4); // read_file, list_directory, find_path, finish
         assert!(registry.get("read_file").is_some());
         assert!(registry.get("list_directory").is_some());
         assert!(registry.get("find_path").is_some());
@@ -528,7 +733,7 @@ mod tests {
         let registry = builder
             .build_for_chat(false)
             .expect("Failed to build registry");
-        assert_eq!(registry.len(), 10); // All standard Write mode tools
+        assert_eq!(registry.len(), 11); // All standard Write mode tools
     }
 
     #[test]
@@ -543,6 +748,58 @@ mod tests {
             .build_for_chat(true)
             .expect("Failed to build registry");
         // Currently returns the same tools, but flag is passed and logged
-        assert_eq!(registry.len(), 10);
+        assert_eq!(registry.len(), 11);
+    }
+
+    #[tokio::test]
+    async fn test_confirm_once_grants_are_shared_across_tools_and_rebuilds() {
+        use crate::tools::confirmation::ConfirmationGrants;
+        use serde_json::json;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let grants = ConfirmationGrants::new();
+        let build = || {
+            ToolRegistryBuilder::new(
+                ChatMode::Write,
+                SafetyMode::ConfirmOnce,
+                temp_dir.path().to_path_buf(),
+            )
+            .with_confirmation(
+                ConfirmationPolicy::new(SafetyMode::ConfirmOnce).with_grants(grants.clone()),
+            )
+            .build()
+            .unwrap()
+        };
+        let registry = build();
+        let write = registry.get("write_file").unwrap();
+
+        let asked = write
+            .execute(json!({"path": "src/a.txt", "content": "a"}))
+            .await
+            .unwrap();
+        assert!(!asked.success);
+        assert!(asked.error.unwrap().contains("file writes under src/"));
+        assert!(!temp_dir.path().join("src/a.txt").exists());
+
+        let confirmed = write
+            .execute(json!({"path": "src/a.txt", "content": "a", "confirm": true}))
+            .await
+            .unwrap();
+        assert!(confirmed.success);
+
+        // The grant covers other write tools and a rebuilt registry
+        let rebuilt = build();
+        let edit = rebuilt.get("edit_file").unwrap();
+        let result = edit
+            .execute(json!({"path": "src/b.txt", "mode": "create", "content": "b"}))
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+
+        // Deletes are a separate category
+        let delete = rebuilt.get("delete_path").unwrap();
+        let result = delete.execute(json!({"path": "src/b.txt"})).await.unwrap();
+        assert!(!result.success);
+        assert!(temp_dir.path().join("src/b.txt").exists());
     }
 }
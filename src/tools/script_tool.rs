@@ -0,0 +1,669 @@
+//! Tools defined in the config as shell command templates
+//!
+//! Each `agent.tools.custom` entry becomes a [`ScriptToolExecutor`]. A call
+//! is checked against the entry's parameter schema, each `{{param}}`
+//! placeholder in the template is replaced with the argument quoted for the
+//! shell, and the command runs with `sh -c` in the entry's working
+//! directory. Stdout becomes the result; a non-zero exit status fails it.
+//!
+//! The terminal safety rules apply to the template, not to the arguments,
+//! since quoted arguments cannot change what the command does:
+//! - a template matching the terminal denylist needs confirmation unless the
+//!   entry sets `allow_dangerous`
+//! - in `interactive` mode every call needs confirmation, and in
+//!   `restricted_autonomous` mode templates whose program is not on the
+//!   allowlist do
+//!
+//! Confirmation follows the terminal tool: `"confirm": true` in SAFE mode,
+//! the shared grants in ConfirmOnce mode, and none in YOLO mode.
+
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use jsonschema::JSONSchema;
+use regex::Regex;
+use serde_json::Value;
+use tokio::process::Command;
+
+use crate::chat_mode::SafetyMode;
+use crate::config::{CustomToolConfig, ExecutionMode, TerminalConfig};
+use crate::error::{Result, XzatomaError};
+use crate::shutdown::ChildProcessRegistry;
+use crate::tools::argument_validation::validate_tool_arguments;
+use crate::tools::confirmation::{ActionCategory, ConfirmationPolicy};
+use crate::tools::plan_validation::KNOWN_TOOL_NAMES;
+use crate::tools::terminal::CommandValidator;
+use crate::tools::{ToolExecutor, ToolResult};
+
+/// Argument that confirms a call needing confirmation
+const CONFIRM_PARAM: &str = "confirm";
+
+fn placeholder_pattern() -> &'static Regex {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    PLACEHOLDER.get_or_init(|| {
+        Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").expect("placeholder pattern is valid")
+    })
+}
+
+/// Returns the first placeholder that sits inside single or double quotes
+///
+/// A quoted argument placed inside double quotes loses its own quoting: the
+/// single quotes become literal text and `$(...)` or backticks in the value
+/// run. Inside single quotes the value can close the quote. Backslash escapes
+/// are honoured outside single quotes.
+fn quoted_placeholder(template: &str) -> Option<String> {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut quoted = vec![false; template.len()];
+    for (i, c) in template.char_indices() {
+        quoted[i] = quote.is_some();
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (Some('"'), '"') => quote = None,
+            (None, '\'' | '"') => quote = Some(c),
+            _ => {}
+        }
+    }
+    placeholder_pattern()
+        .captures_iter(template)
+        .find(|captures| quoted[captures.get(0).map_or(0, |m| m.start())])
+        .map(|captures| captures[1].to_string())
+}
+
+/// Checks one `agent.tools.custom` entry
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Config` when the name is empty, malformed, or taken
+/// by a built-in tool, the command is empty, the parameters are not an
+/// object schema that compiles, a placeholder names an undeclared parameter
+/// or sits inside quotes, or the timeout is 0.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::CustomToolConfig;
+/// use xzatoma::tools::script_tool::validate_custom_tool;
+///
+/// let mut tool: CustomToolConfig = serde_yaml::from_str(
+///     "name: lint\ndescription: Lint\ncommand: make lint\n",
+/// )
+/// .unwrap();
+/// assert!(validate_custom_tool(&tool).is_ok());
+///
+/// tool.name = "terminal".to_string();
+/// assert!(validate_custom_tool(&tool).is_err());
+/// ```
+pub fn validate_custom_tool(tool: &CustomToolConfig) -> Result<()> {
+    let invalid = |reason: String| {
+        Err(XzatomaError::Config(format!(
+            "tools.custom tool '{}' {}",
+            tool.name, reason
+        )))
+    };
+
+    if tool.name.is_empty()
+        || tool.name.contains("__")
+        || !tool
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return invalid(
+            "must be named with letters, digits, '_', and '-' (no '__', which marks MCP tools)"
+                .to_string(),
+        );
+    }
+    if KNOWN_TOOL_NAMES.contains(&tool.name.as_str()) {
+        return invalid("has the name of a built-in tool".to_string());
+    }
+    if tool.command.trim().is_empty() {
+        return invalid("has an empty command".to_string());
+    }
+    if tool.timeout_seconds == Some(0) {
+        return invalid("must have a timeout_seconds greater than 0".to_string());
+    }
+
+    if tool.parameters.get("type").and_then(Value::as_str) != Some("object") {
+        return invalid("must have parameters with \"type\": \"object\"".to_string());
+    }
+    if let Err(e) = JSONSchema::compile(&tool.parameters) {
+        return invalid(format!("has an invalid parameter schema: {}", e));
+    }
+    let properties = tool.parameters.get("properties").and_then(Value::as_object);
+    if properties.is_some_and(|properties| properties.contains_key(CONFIRM_PARAM)) {
+        return invalid(format!(
+            "cannot declare the parameter '{}', which is reserved",
+            CONFIRM_PARAM
+        ));
+    }
+    if let Some(param) = quoted_placeholder(&tool.command) {
+        return invalid(format!(
+            "puts {{{{{}}}}} inside quotes; placeholders are quoted for the shell and must stand outside quotes",
+            param
+        ));
+    }
+    for captures in placeholder_pattern().captures_iter(&tool.command) {
+        let param = &captures[1];
+        if !properties.is_some_and(|properties| properties.contains_key(param)) {
+            return invalid(format!(
+                "uses {{{{{}}}}} but declares no such parameter",
+                param
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Quotes `value` as a single POSIX shell word
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::tools::script_tool::shell_quote;
+///
+/// assert_eq!(shell_quote("plain"), "'plain'");
+/// assert_eq!(shell_quote("it's"), r#"'it'\''s'"#);
+/// assert_eq!(shell_quote(""), "''");
+/// ```
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Replaces each `{{param}}` in `template` with the quoted argument
+///
+/// Strings are used as they are, other JSON values in their JSON form, and
+/// missing or null arguments as an empty word.
+///
+/// # Examples
+///
+/// ```
+/// use serde_json::json;
+/// use xzatoma::tools::script_tool::render_command;
+///
+/// let command = render_command("grep -c {{ pattern }} {{file}}", &json!({"pattern": "a b"}));
+/// assert_eq!(command, "grep -c 'a b' ''");
+/// ```
+pub fn render_command(template: &str, args: &Value) -> String {
+    placeholder_pattern()
+        .replace_all(template, |captures: &regex::Captures<'_>| {
+            let value = match args.get(&captures[1]) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(value)) => value.clone(),
+                Some(other) => other.to_string(),
+            };
+            shell_quote(&value)
+        })
+        .into_owned()
+}
+
+/// Runs a config-defined shell command template as a tool
+pub struct ScriptToolExecutor {
+    tool: CustomToolConfig,
+    validator: CommandValidator,
+    terminal_config: TerminalConfig,
+    safety_mode: SafetyMode,
+    confirmation: Option<ConfirmationPolicy>,
+    children: ChildProcessRegistry,
+}
+
+impl ScriptToolExecutor {
+    /// Creates an executor for `tool`
+    ///
+    /// # Arguments
+    ///
+    /// * `tool` - The validated config entry
+    /// * `validator` - Terminal validator; supplies the mode, the allowlist,
+    ///   the denylist, and the workspace the working directory is relative to
+    /// * `terminal_config` - Default timeout and output limits
+    pub fn new(
+        tool: CustomToolConfig,
+        validator: CommandValidator,
+        terminal_config: TerminalConfig,
+    ) -> Self {
+        Self {
+            tool,
+            validator,
+            terminal_config,
+            safety_mode: SafetyMode::AlwaysConfirm,
+            confirmation: None,
+            children: ChildProcessRegistry::global(),
+        }
+    }
+
+    /// Set the safety mode for this tool
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining
+    pub fn with_safety_mode(mut self, mode: SafetyMode) -> Self {
+        self.safety_mode = mode;
+        self
+    }
+
+    /// Set the policy that asks for confirmation in ConfirmOnce mode
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining
+    pub fn with_confirmation(mut self, confirmation: Option<ConfirmationPolicy>) -> Self {
+        self.confirmation = confirmation;
+        self
+    }
+
+    /// Returns the tool name
+    pub fn name(&self) -> &str {
+        &self.tool.name
+    }
+
+    /// Returns true when the template matches the terminal denylist
+    pub fn is_dangerous(&self) -> bool {
+        self.validator
+            .denylist
+            .iter()
+            .any(|pattern| pattern.is_match(&self.tool.command))
+    }
+
+    /// Returns true when calls need confirmation under the terminal rules
+    fn needs_confirmation(&self) -> bool {
+        if self.is_dangerous() {
+            return !self.tool.allow_dangerous;
+        }
+        match self.validator.mode {
+            ExecutionMode::Interactive => true,
+            ExecutionMode::RestrictedAutonomous => {
+                let program = self.tool.command.split_whitespace().next().unwrap_or("");
+                !self
+                    .validator
+                    .allowlist
+                    .iter()
+                    .any(|allowed| allowed == program)
+            }
+            ExecutionMode::FullAutonomous => false,
+        }
+    }
+
+    fn working_dir(&self) -> PathBuf {
+        match &self.tool.working_dir {
+            Some(dir) => self.validator.working_dir.join(dir),
+            None => self.validator.working_dir.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for ScriptToolExecutor {
+    fn mutates(&self) -> bool {
+        true
+    }
+
+    fn tool_definition(&self) -> Value {
+        let mut parameters = self.tool.parameters.clone();
+        if let Some(schema) = parameters.as_object_mut() {
+            let properties = schema
+                .entry("properties")
+                .or_insert_with(|| Value::Object(Default::default()));
+            if let Some(properties) = properties.as_object_mut() {
+                properties.insert(
+                    CONFIRM_PARAM.to_string(),
+                    serde_json::json!({
+                        "type": "boolean",
+                        "description": "Set after the user approves a call that needs confirmation"
+                    }),
+                );
+            }
+        }
+        serde_json::json!({
+            "name": self.tool.name,
+            "description": self.tool.description,
+            "parameters": parameters,
+        })
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let mut args = params;
+        let confirm = args
+            .as_object_mut()
+            .and_then(|args| args.remove(CONFIRM_PARAM))
+            .and_then(|confirm| confirm.as_bool())
+            .unwrap_or(false);
+
+        let definition = serde_json::json!({"parameters": self.tool.parameters});
+        let validation = validate_tool_arguments(&definition, args);
+        if !validation.is_valid() {
+            return Ok(ToolResult::error(validation.error_message(&self.tool.name)));
+        }
+        let command = render_command(&self.tool.command, &validation.args);
+
+        let category = if self.needs_confirmation() {
+            if self.safety_mode == SafetyMode::AlwaysConfirm && !confirm {
+                return Ok(ToolResult::error(format!(
+                    "Command requires confirmation in SAFE mode: {}",
+                    command
+                )));
+            }
+            ActionCategory::TerminalDangerous
+        } else {
+            ActionCategory::TerminalSafe
+        };
+        if let Some(refusal) = self
+            .confirmation
+            .as_ref()
+            .and_then(|policy| policy.check(category, "", &command, confirm))
+        {
+            return Ok(refusal);
+        }
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(&command)
+            .current_dir(self.working_dir())
+            .envs(&self.tool.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let child = cmd.spawn().map_err(|e| {
+            XzatomaError::Tool(format!(
+                "Failed to start custom tool '{}': {}",
+                self.tool.name, e
+            ))
+        })?;
+        let _registration = child.id().map(|pid| {
+            self.children
+                .register(pid, format!("{}: {}", self.tool.name, command))
+        });
+
+        let timeout_seconds = self
+            .tool
+            .timeout_seconds
+            .unwrap_or(self.terminal_config.timeout_seconds);
+        let start = std::time::Instant::now();
+        let output = match tokio::time::timeout(
+            Duration::from_secs(timeout_seconds),
+            child.wait_with_output(),
+        )
+        .await
+        {
+            Ok(output) => output.map_err(|e| {
+                XzatomaError::Tool(format!(
+                    "Failed waiting for custom tool '{}': {}",
+                    self.tool.name, e
+                ))
+            })?,
+            Err(_) => {
+                return Ok(ToolResult::error(format!(
+                    "Custom tool '{}' timed out after {} seconds",
+                    self.tool.name, timeout_seconds
+                )))
+            }
+        };
+
+        let stdout = truncate_stream(&output.stdout, self.terminal_config.max_stdout_bytes);
+        let exit_code = output
+            .status
+            .code()
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let result = if output.status.success() {
+            ToolResult::success(stdout)
+        } else {
+            let stderr = truncate_stream(&output.stderr, self.terminal_config.max_stderr_bytes);
+            let detail = if stderr.trim().is_empty() {
+                stdout
+            } else {
+                stderr
+            };
+            ToolResult::error(format!("Exit code {}: {}", exit_code, detail))
+        };
+        Ok(result
+            .with_metadata("exit_code".to_string(), exit_code)
+            .with_metadata(
+                "duration_ms".to_string(),
+                start.elapsed().as_millis().to_string(),
+            ))
+    }
+}
+
+fn truncate_stream(bytes: &[u8], max_bytes: usize) -> String {
+    let mut text = String::from_utf8_lossy(bytes).into_owned();
+    if text.len() > max_bytes {
+        let mut end = max_bytes;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n... (output truncated)");
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn echo_tool(command: &str) -> CustomToolConfig {
+        serde_yaml::from_str(&format!(
+            r#"
+name: echo_value
+description: Echo a value
+parameters:
+  type: object
+  properties:
+    value: {{ type: string }}
+    count: {{ type: integer, minimum: 1 }}
+  required: [value]
+command: {}
+"#,
+            serde_json::to_string(command).unwrap()
+        ))
+        .unwrap()
+    }
+
+    fn executor(tool: CustomToolConfig, dir: &TempDir, mode: ExecutionMode) -> ScriptToolExecutor {
+        ScriptToolExecutor::new(
+            tool,
+            CommandValidator::new(mode, dir.path().to_path_buf()),
+            TerminalConfig::default(),
+        )
+        .with_safety_mode(SafetyMode::AlwaysConfirm)
+    }
+
+    #[test]
+    fn test_validate_custom_tool_rejects_collisions_and_unknown_placeholders() {
+        assert!(validate_custom_tool(&echo_tool("printf %s {{value}}")).is_ok());
+
+        let mut tool = echo_tool("printf %s {{value}}");
+        tool.name = "read_file".to_string();
+        let err = validate_custom_tool(&tool).unwrap_err();
+        assert!(err.to_string().contains("built-in tool"));
+
+        tool.name = "github__search".to_string();
+        assert!(validate_custom_tool(&tool).is_err());
+
+        let err = validate_custom_tool(&echo_tool("printf %s {{missing}}")).unwrap_err();
+        assert!(err.to_string().contains("{{missing}}"));
+
+        let mut tool = echo_tool("printf %s {{value}}");
+        tool.parameters = json!({"type": "object", "properties": {"confirm": {"type": "boolean"}}});
+        assert!(validate_custom_tool(&tool).is_err());
+    }
+
+    #[test]
+    fn test_validate_custom_tool_rejects_quoted_placeholders() {
+        for template in [
+            "printf %s \"{{value}}\"",
+            "curl \"https://example.com/?q={{value}}\"",
+            "echo '{{ value }}'",
+            "echo \"a \\\" {{value}}\"",
+        ] {
+            let err = validate_custom_tool(&echo_tool(template)).unwrap_err();
+            assert!(err.to_string().contains("inside quotes"), "{}", template);
+        }
+        for template in [
+            "printf '%s|' {{value}}",
+            "echo \"done:\" {{value}} 'x'",
+            "echo \\\"{{value}}",
+        ] {
+            assert!(
+                validate_custom_tool(&echo_tool(template)).is_ok(),
+                "{}",
+                template
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_double_quoted_placeholder_is_rejected_before_it_can_run() {
+        let dir = TempDir::new().unwrap();
+        let template = "printf %s \"{{value}}\"";
+
+        // Inside double quotes the value's single quotes are literal, so `sh`
+        // runs the substitution
+        let rendered = render_command(template, &json!({"value": "$(touch pwned)"}));
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&rendered)
+            .current_dir(dir.path())
+            .stdout(Stdio::null())
+            .status()
+            .await
+            .unwrap();
+        assert!(status.success());
+        assert!(dir.path().join("pwned").exists());
+
+        // Such a template never becomes a tool: loading the config fails
+        let mut config = crate::config::Config::default();
+        config.agent.tools.custom.push(echo_tool(template));
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("inside quotes"));
+    }
+
+    #[test]
+    fn test_render_command_quotes_adversarial_values() {
+        let args = json!({
+            "value": "'; rm -rf ~; echo '$(id)`id`\n\"$HOME\"",
+            "count": 3
+        });
+        assert_eq!(
+            render_command("printf %s {{value}} {{count}}", &args),
+            r#"printf %s ''\''; rm -rf ~; echo '\''$(id)`id`
+"$HOME"' '3'"#
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_adversarial_values_reach_the_script_as_one_literal_argument() {
+        let dir = TempDir::new().unwrap();
+        let tool = executor(
+            echo_tool("printf '%s|' {{value}}"),
+            &dir,
+            ExecutionMode::FullAutonomous,
+        );
+        let values = [
+            "'; touch pwned; echo '",
+            "$(touch pwned)",
+            "`touch pwned`",
+            "a\nb; touch pwned",
+            "\"$HOME\" && touch pwned",
+            "* ?",
+        ];
+
+        for value in values {
+            let result = tool.execute(json!({"value": value})).await.unwrap();
+            assert!(result.success, "{:?}", result.error);
+            assert_eq!(result.output, format!("{}|", value));
+        }
+        assert!(!dir.path().join("pwned").exists());
+    }
+
+    #[tokio::test]
+    async fn test_schema_violation_fails_without_running_the_command() {
+        let dir = TempDir::new().unwrap();
+        let tool = executor(
+            echo_tool("touch ran {{value}}"),
+            &dir,
+            ExecutionMode::FullAutonomous,
+        );
+
+        let result = tool.execute(json!({"count": 0})).await.unwrap();
+
+        assert!(!result.success);
+        let error = result.error.unwrap();
+        assert!(error.contains("Invalid arguments for tool 'echo_value'"));
+        assert!(error.contains("value"));
+        assert!(!dir.path().join("ran").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_env_working_dir_and_exit_code_mapping() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("scripts")).unwrap();
+        let mut config = echo_tool("printf '%s %s' \"$GREETING\" {{value}}; basename \"$(pwd)\"");
+        config.working_dir = Some("scripts".to_string());
+        config
+            .env
+            .insert("GREETING".to_string(), "hello".to_string());
+        let tool = executor(config, &dir, ExecutionMode::FullAutonomous);
+
+        let result = tool.execute(json!({"value": "world"})).await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.output, "hello worldscripts\n");
+        assert_eq!(result.metadata.get("exit_code").unwrap(), "0");
+
+        let failing = executor(
+            echo_tool("echo {{value}} >&2; exit 3"),
+            &dir,
+            ExecutionMode::FullAutonomous,
+        );
+        let result = failing.execute(json!({"value": "broken"})).await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.error.unwrap(), "Exit code 3: broken\n");
+    }
+
+    #[tokio::test]
+    async fn test_dangerous_template_needs_confirmation_unless_allowed() {
+        let dir = TempDir::new().unwrap();
+        let tool = executor(
+            echo_tool("sudo true {{value}}"),
+            &dir,
+            ExecutionMode::FullAutonomous,
+        );
+        assert!(tool.is_dangerous());
+
+        let result = tool.execute(json!({"value": "x"})).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("requires confirmation"));
+
+        let mut config = echo_tool("sudo true {{value}}");
+        config.allow_dangerous = true;
+        assert!(!executor(config, &dir, ExecutionMode::FullAutonomous).needs_confirmation());
+
+        let safe = executor(
+            echo_tool("./scripts/test.sh {{value}}"),
+            &dir,
+            ExecutionMode::RestrictedAutonomous,
+        );
+        assert!(safe.needs_confirmation());
+        let allowlisted = executor(
+            echo_tool("cargo test {{value}}"),
+            &dir,
+            ExecutionMode::RestrictedAutonomous,
+        );
+        assert!(!allowlisted.needs_confirmation());
+    }
+}
@@ -0,0 +1,151 @@
+//! Incremental tool output streaming
+//!
+//! Long-running tools such as `terminal` and `subagent` produce output
+//! gradually. A [`ToolOutputSink`] lets a tool push lines as they appear while
+//! the final [`ToolResult`](crate::tools::ToolResult) still carries the
+//! complete output for the conversation.
+//!
+//! # Examples
+//!
+//! ```
+//! use xzatoma::tools::{ToolOutputSink, ToolOutputStream};
+//!
+//! let (sink, mut lines) = ToolOutputSink::channel();
+//! sink.stdout("compiling...");
+//!
+//! let line = lines.try_recv().unwrap();
+//! assert_eq!(line.stream, ToolOutputStream::Stdout);
+//! assert_eq!(line.line, "compiling...");
+//! ```
+
+use tokio::sync::mpsc;
+
+/// Stream a line of tool output was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolOutputStream {
+    /// Standard output or general progress
+    Stdout,
+    /// Standard error
+    Stderr,
+}
+
+/// A single line of incremental tool output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolOutputLine {
+    /// Stream the line was written to
+    pub stream: ToolOutputStream,
+    /// Line content without the trailing newline
+    pub line: String,
+}
+
+/// Cheap, cloneable handle for pushing incremental tool output
+///
+/// A disabled sink discards every line, so tools can push unconditionally.
+/// Pushing never blocks and never fails; lines sent after the receiver is
+/// dropped are discarded.
+#[derive(Debug, Clone, Default)]
+pub struct ToolOutputSink {
+    sender: Option<mpsc::UnboundedSender<ToolOutputLine>>,
+}
+
+impl ToolOutputSink {
+    /// Creates a sink and the receiver that observes its lines
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<ToolOutputLine>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (
+            Self {
+                sender: Some(sender),
+            },
+            receiver,
+        )
+    }
+
+    /// Creates a sink that discards all output
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Returns true when pushed lines reach a receiver
+    pub fn is_enabled(&self) -> bool {
+        self.sender
+            .as_ref()
+            .is_some_and(|sender| !sender.is_closed())
+    }
+
+    /// Pushes a line to the given stream
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - Stream the line belongs to
+    /// * `line` - Line content without the trailing newline
+    pub fn push(&self, stream: ToolOutputStream, line: impl Into<String>) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(ToolOutputLine {
+                stream,
+                line: line.into(),
+            });
+        }
+    }
+
+    /// Pushes a line to standard output
+    pub fn stdout(&self, line: impl Into<String>) {
+        self.push(ToolOutputStream::Stdout, line);
+    }
+
+    /// Pushes a line to standard error
+    pub fn stderr(&self, line: impl Into<String>) {
+        self.push(ToolOutputStream::Stderr, line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_delivers_lines_in_order() {
+        let (sink, mut lines) = ToolOutputSink::channel();
+        let clone = sink.clone();
+
+        sink.stdout("first");
+        clone.stderr("second");
+        sink.stdout("third");
+
+        let received: Vec<ToolOutputLine> = std::iter::from_fn(|| lines.try_recv().ok()).collect();
+        assert_eq!(
+            received,
+            vec![
+                ToolOutputLine {
+                    stream: ToolOutputStream::Stdout,
+                    line: "first".to_string()
+                },
+                ToolOutputLine {
+                    stream: ToolOutputStream::Stderr,
+                    line: "second".to_string()
+                },
+                ToolOutputLine {
+                    stream: ToolOutputStream::Stdout,
+                    line: "third".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disabled_sink_discards_lines() {
+        let sink = ToolOutputSink::disabled();
+        assert!(!sink.is_enabled());
+        sink.stdout("ignored");
+    }
+
+    #[test]
+    fn test_sink_is_disabled_after_receiver_drops() {
+        let (sink, lines) = ToolOutputSink::channel();
+        assert!(sink.is_enabled());
+
+        drop(lines);
+
+        assert!(!sink.is_enabled());
+        sink.stdout("discarded");
+    }
+}
@@ -5,15 +5,20 @@
 //! This feature prevents context pollution and enables parallel exploration
 //! of sub-problems without polluting the main conversation history.
 
-use crate::agent::{quota::QuotaTracker, Agent, ConversationStore, SubagentMetrics};
+use crate::agent::{
+    quota::QuotaTracker, Agent, AgentExecutionEvent, AgentObserver, ConversationStore,
+    ExecutionOutcome, SubagentMetrics,
+};
 use crate::config::{AgentConfig, SubagentConfig};
 use crate::error::{Result, XzatomaError};
 use crate::providers::Provider;
+use crate::tools::finish::FINISH_TOOL_NAME;
 use crate::tools::parse_tool_args;
-use crate::tools::{ToolExecutor, ToolRegistry, ToolResult};
+use crate::tools::{ToolExecutor, ToolOutputSink, ToolRegistry, ToolResult};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 
 /// Telemetry module for subagent execution tracing
 ///
@@ -370,7 +375,7 @@ impl SubagentTool {
     ///
     /// ```no_run
     /// use xzatoma::tools::subagent::SubagentTool;
-    /// use xzatoma::config::{AgentConfig, ProviderConfig, CopilotConfig, OllamaConfig, OpenAIConfig};
+    /// use xzatoma::config::{AgentConfig, ProviderConfig, CopilotConfig, OllamaConfig, OpenAIConfig, ProviderCacheConfig, ProviderQuirksConfig, ProviderRateLimitsConfig};
     /// use xzatoma::tools::ToolRegistry;
     /// use std::sync::Arc;
     ///
@@ -382,6 +387,9 @@ impl SubagentTool {
     ///     copilot: CopilotConfig::default(),
     ///     ollama: OllamaConfig::default(),
     ///     openai: OpenAIConfig::default(),
+    ///     cache: ProviderCacheConfig::default(),
+    ///     quirks: ProviderQuirksConfig::default(),
+    ///     rate_limits: ProviderRateLimitsConfig::default(),
     /// };
     /// let tool = SubagentTool::new_with_config(
     ///     Arc::new(provider),
@@ -404,11 +412,14 @@ impl SubagentTool {
         let provider = if let Some(provider_type) = &agent_config.subagent.provider {
             // Create dedicated provider instance for subagent
             let model_override = agent_config.subagent.model.as_deref();
-            let new_provider = crate::providers::create_provider_with_override(
-                provider_config,
-                Some(provider_type),
-                model_override,
-            )?;
+            let new_provider = crate::providers::wrap_with_quirks(
+                crate::providers::create_provider_with_override(
+                    provider_config,
+                    Some(provider_type),
+                    model_override,
+                )?,
+                &provider_config.quirks,
+            );
             Arc::from(new_provider)
         } else {
             // No override - share parent provider
@@ -446,8 +457,13 @@ impl SubagentTool {
         let subagent_config = config.subagent.clone();
 
         // Initialize conversation store if persistence enabled
-        let conversation_store = if subagent_config.persistence_enabled {
-            match ConversationStore::new(&subagent_config.persistence_path) {
+        let conversation_store = if subagent_config.saves_conversations() {
+            let path = match subagent_config.conversation_database() {
+                Some(path) => Ok(path),
+                None => crate::paths::Paths::from_env()
+                    .map(|paths| crate::storage::SqliteStorage::default_database_path(&paths)),
+            };
+            match path.and_then(ConversationStore::new) {
                 Ok(store) => Some(Arc::new(store)),
                 Err(e) => {
                     tracing::warn!("Failed to initialize conversation store: {}", e);
@@ -499,11 +515,23 @@ impl SubagentTool {
     }
 }
 
+/// Formats a subagent's `finish` result for the parent agent
+fn format_outcome(outcome: &ExecutionOutcome) -> String {
+    let mut output = format!("Status: {}\n\n{}", outcome.status, outcome.summary);
+    if let Some(details) = &outcome.details {
+        output.push_str("\n\nDetails:\n");
+        output.push_str(&serde_json::to_string_pretty(details).unwrap_or_default());
+    }
+    output
+}
+
 /// Creates a filtered tool registry for subagent
 ///
 /// Applies tool filtering to the parent registry based on the allowed_tools
 /// whitelist parameter. The "subagent" tool is always excluded to prevent
-/// infinite recursion through tool definitions.
+/// infinite recursion through tool definitions. The `finish` tool is kept
+/// whenever the parent has it, so the subagent can report a structured
+/// result.
 ///
 /// # Arguments
 ///
@@ -532,7 +560,9 @@ fn create_filtered_registry(
     parent_registry: &ToolRegistry,
     allowed_tools: Option<Vec<String>>,
 ) -> Result<ToolRegistry> {
-    let mut subagent_registry = ToolRegistry::new();
+    let mut subagent_registry = ToolRegistry::new()
+        .with_network_policy(parent_registry.network_policy())
+        .with_read_only_policy(parent_registry.read_only_policy());
 
     match allowed_tools {
         None => {
@@ -564,12 +594,48 @@ fn create_filtered_registry(
 
                 subagent_registry.register(&tool_name, executor);
             }
+            if let Some(finish) = parent_registry.get(FINISH_TOOL_NAME) {
+                subagent_registry.register(FINISH_TOOL_NAME, finish);
+            }
         }
     }
 
     Ok(subagent_registry)
 }
 
+/// Forwards a running subagent's progress to the parent tool's output sink
+///
+/// Tool calls, streamed tool output, and assistant replies are pushed as
+/// lines prefixed with the subagent label.
+struct SubagentProgressObserver {
+    label: String,
+    sink: ToolOutputSink,
+}
+
+impl AgentObserver for SubagentProgressObserver {
+    fn on_event(&mut self, event: AgentExecutionEvent) {
+        match event {
+            AgentExecutionEvent::ToolCallStarted { name, .. } => {
+                self.sink
+                    .stdout(format!("[{}] running {}", self.label, name));
+            }
+            AgentExecutionEvent::ToolOutputChunk { stream, line, .. } => {
+                self.sink.push(stream, format!("[{}] {}", self.label, line));
+            }
+            AgentExecutionEvent::ToolCallFailed { name, error, .. } => {
+                self.sink
+                    .stderr(format!("[{}] {} failed: {}", self.label, name, error));
+            }
+            AgentExecutionEvent::AssistantTextEmitted { text } => {
+                for line in text.lines() {
+                    self.sink.stdout(format!("[{}] {}", self.label, line));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 #[async_trait]
 impl ToolExecutor for SubagentTool {
     fn tool_definition(&self) -> serde_json::Value {
@@ -609,6 +675,19 @@ impl ToolExecutor for SubagentTool {
     }
 
     async fn execute(&self, args: serde_json::Value) -> Result<ToolResult> {
+        self.execute_streaming(args, ToolOutputSink::disabled())
+            .await
+    }
+
+    /// Runs the subagent, streaming its tool activity and replies to `sink`
+    ///
+    /// Each streamed line is prefixed with the subagent label. The returned
+    /// result still carries only the final summary.
+    async fn execute_streaming(
+        &self,
+        args: serde_json::Value,
+        sink: ToolOutputSink,
+    ) -> Result<ToolResult> {
         // Create metrics tracker for this subagent execution
         let metrics = SubagentMetrics::new(
             args.get("label")
@@ -724,7 +803,7 @@ impl ToolExecutor for SubagentTool {
         let mut nested_subagent_tool = SubagentTool::new(
             Arc::clone(&self.provider),
             self.config.clone(),
-            subagent_registry.clone(),
+            subagent_registry.fork(),
             next_depth,
         );
 
@@ -773,8 +852,20 @@ impl ToolExecutor for SubagentTool {
             }
         };
 
-        // Execute task
-        let _task_result = match subagent.execute(input.task_prompt.clone()).await {
+        // Execute task, forwarding progress to the parent's output sink
+        let mut progress = SubagentProgressObserver {
+            label: input.label.clone(),
+            sink,
+        };
+        let cancellation_token = CancellationToken::new();
+        let _task_result = match subagent
+            .execute_with_observer(
+                input.task_prompt.clone(),
+                &cancellation_token,
+                &mut progress,
+            )
+            .await
+        {
             Ok(result) => result,
             Err(e) => {
                 if telemetry_enabled {
@@ -788,16 +879,25 @@ impl ToolExecutor for SubagentTool {
             }
         };
 
-        // STEP 7: Request summary
-        // Decision 3: Always request summary (use default if not provided)
-        let summary_prompt = input
-            .summary_prompt
-            .as_ref()
-            .cloned()
-            .unwrap_or_else(|| "Summarize your findings concisely".to_string());
-
-        // Continue conversation with summary request
-        let final_output = subagent.execute(summary_prompt).await?;
+        // STEP 7: Use the structured result of a `finish` call, or request summary
+        let finished = subagent
+            .last_outcome()
+            .filter(|outcome| outcome.explicit)
+            .cloned();
+        let final_output = match &finished {
+            Some(outcome) => format_outcome(outcome),
+            None => {
+                // Decision 3: Always request summary (use default if not provided)
+                let summary_prompt = input
+                    .summary_prompt
+                    .as_ref()
+                    .cloned()
+                    .unwrap_or_else(|| "Summarize your findings concisely".to_string());
+
+                // Continue conversation with summary request
+                subagent.execute(summary_prompt).await?
+            }
+        };
 
         // Capture values before they're moved
         let label = input.label.clone();
@@ -811,6 +911,10 @@ impl ToolExecutor for SubagentTool {
                 self.current_depth.to_string(),
             );
 
+        if let Some(outcome) = &finished {
+            result = result.with_metadata("finish_status".to_string(), outcome.status.to_string());
+        }
+
         // Check if subagent hit max_turns limit (incomplete execution)
         // Count user messages as turns (each execute() call adds one user message)
         let turn_count = subagent
@@ -1287,6 +1391,31 @@ mod tests {
         assert!(provider.call_count() > 0);
     }
 
+    #[tokio::test]
+    async fn test_subagent_streams_replies_with_label() {
+        let provider = Arc::new(MockProvider::new(vec![
+            "first line\nsecond line".to_string(),
+            "summary".to_string(),
+        ]));
+        let tool = SubagentTool::new(provider, create_test_config(), ToolRegistry::new(), 0);
+
+        let (sink, mut lines) = ToolOutputSink::channel();
+        let result = tool
+            .execute_streaming(
+                serde_json::json!({"label": "scan", "task_prompt": "look around"}),
+                sink,
+            )
+            .await
+            .unwrap();
+
+        let streamed: Vec<String> = std::iter::from_fn(|| lines.try_recv().ok())
+            .map(|line| line.line)
+            .collect();
+        assert_eq!(streamed, vec!["[scan] first line", "[scan] second line"]);
+        assert!(result.success);
+        assert!(result.output.contains("summary"));
+    }
+
     // Test 15: Metadata tracking
     #[tokio::test]
     async fn test_subagent_metadata_tracking() {
@@ -1602,4 +1731,42 @@ mod tests {
         assert_eq!(tracker.remaining_tokens(), Some(5000));
         assert!(tracker.remaining_time().is_none());
     }
+
+    #[tokio::test]
+    async fn test_subagent_propagates_finish_result_without_summary_request() {
+        use crate::testing::MockProvider as ScriptedProvider;
+
+        // The model ends the task with a `finish` call
+        let provider = Arc::new(ScriptedProvider::new().then_tool_call(
+            FINISH_TOOL_NAME,
+            serde_json::json!({
+                "status": "failure",
+                "summary": "The config file does not exist",
+                "details": {"searched": ["config/"]}
+            }),
+        ));
+        let mut registry = ToolRegistry::new();
+        registry.register(FINISH_TOOL_NAME, Arc::new(crate::tools::finish::FinishTool));
+        let tool = SubagentTool::new(provider.clone(), create_test_config(), registry, 0);
+
+        let result = tool
+            .execute(serde_json::json!({
+                "label": "find_config",
+                "task_prompt": "find the config file",
+                "allowed_tools": []
+            }))
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert!(result
+            .output
+            .starts_with("Status: failure\n\nThe config file does not exist"));
+        assert!(result.output.contains("\"searched\""));
+        assert_eq!(
+            result.metadata.get("finish_status"),
+            Some(&"failure".to_string())
+        );
+        provider.assert_request_count(1);
+    }
 }
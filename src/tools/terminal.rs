@@ -15,6 +15,8 @@
 //! - SafetyMode affects confirmation requirements:
 //!   - `AlwaysConfirm`: Requires explicit confirmation for terminal operations
 //!   - `NeverConfirm`: Allows operations without confirmation (YOLO mode)
+//!   - `ConfirmOnce`: Confirms the first safe and the first dangerous command,
+//!     then grants each category for the session
 //!
 //! # Examples
 //!
@@ -37,13 +39,16 @@ use std::time::Duration;
 use async_trait::async_trait;
 use regex::Regex;
 use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
 use tokio::time;
 
 use crate::chat_mode::SafetyMode;
 use crate::config::{ExecutionMode, TerminalConfig};
 use crate::error::{Result, XzatomaError};
-use crate::tools::{ToolExecutor, ToolResult};
+use crate::shutdown::ChildProcessRegistry;
+use crate::tools::confirmation::{ActionCategory, ConfirmationPolicy};
+use crate::tools::{ToolExecutor, ToolOutputSink, ToolOutputStream, ToolResult};
 
 /// Parsed command line with program and arguments
 ///
@@ -444,6 +449,8 @@ pub struct TerminalTool {
     pub validator: CommandValidator,
     pub config: TerminalConfig,
     pub safety_mode: SafetyMode,
+    pub confirmation: Option<ConfirmationPolicy>,
+    pub children: ChildProcessRegistry,
 }
 
 impl TerminalTool {
@@ -453,6 +460,8 @@ impl TerminalTool {
             validator,
             config,
             safety_mode: SafetyMode::AlwaysConfirm,
+            confirmation: None,
+            children: ChildProcessRegistry::global(),
         }
     }
 
@@ -478,14 +487,49 @@ impl TerminalTool {
     pub fn set_safety_mode(&mut self, mode: SafetyMode) {
         self.safety_mode = mode;
     }
+
+    /// Set the policy that asks for confirmation in ConfirmOnce mode
+    ///
+    /// # Arguments
+    ///
+    /// * `confirmation` - The confirmation policy, or `None` to disable it
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining
+    pub fn with_confirmation(mut self, confirmation: Option<ConfirmationPolicy>) -> Self {
+        self.confirmation = confirmation;
+        self
+    }
+
+    /// Set the registry that spawned commands are registered with
+    ///
+    /// Registered commands are stopped when an interrupted run shuts down.
+    /// Defaults to [`ChildProcessRegistry::global`].
+    ///
+    /// # Arguments
+    ///
+    /// * `children` - The child process registry
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining
+    pub fn with_child_registry(mut self, children: ChildProcessRegistry) -> Self {
+        self.children = children;
+        self
+    }
 }
 
 #[async_trait]
 impl ToolExecutor for TerminalTool {
+    fn mutates(&self) -> bool {
+        true
+    }
+
     fn tool_definition(&self) -> Value {
         json!({
             "name": "terminal",
-            "description": "Execute validated commands in the working directory (no shell operators)",
+            "description": "Execute validated commands in the working directory (no shell operators). Long output is paged: follow the note at its end to read the rest with read_tool_output",
             "parameters": {
                 "type": "object",
                 "properties": {
@@ -501,6 +545,13 @@ impl ToolExecutor for TerminalTool {
     }
 
     async fn execute(&self, params: Value) -> Result<ToolResult> {
+        self.execute_streaming(params, ToolOutputSink::disabled())
+            .await
+    }
+
+    /// Runs the command, pushing each stdout and stderr line to `sink` as
+    /// it is produced
+    async fn execute_streaming(&self, params: Value, sink: ToolOutputSink) -> Result<ToolResult> {
         let command = params["command"]
             .as_str()
             .ok_or_else(|| XzatomaError::Tool("Missing 'command' parameter".to_string()))?
@@ -520,8 +571,8 @@ impl ToolExecutor for TerminalTool {
             .unwrap_or(self.config.max_stderr_bytes as u64) as usize;
 
         // Validate permission and paths
-        match self.validator.validate(&command) {
-            Ok(()) => {}
+        let category = match self.validator.validate(&command) {
+            Ok(()) => ActionCategory::TerminalSafe,
             Err(XzatomaError::CommandRequiresConfirmation(_)) => {
                 // Check SafetyMode
                 match self.safety_mode {
@@ -537,12 +588,24 @@ impl ToolExecutor for TerminalTool {
                     SafetyMode::NeverConfirm => {
                         // Proceed without confirmation (YOLO mode)
                     }
+                    SafetyMode::ConfirmOnce => {
+                        // Checked against the session grants below
+                    }
                 }
+                ActionCategory::TerminalDangerous
             }
             Err(err) => {
                 // For dangerous/path errors, return a ToolResult error
                 return Ok(ToolResult::error(err.to_string()));
             }
+        };
+
+        if let Some(refusal) = self
+            .confirmation
+            .as_ref()
+            .and_then(|policy| policy.check(category, "", &command, confirm))
+        {
+            return Ok(refusal);
         }
 
         // Build the program invocation without shell parsing
@@ -553,7 +616,7 @@ impl ToolExecutor for TerminalTool {
         cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
 
         // Spawn and obtain a child
-        let child = cmd
+        let mut child = cmd
             .spawn()
             .map_err(|e| XzatomaError::Tool(format!("Failed to spawn command: {}", e)))?;
 
@@ -562,8 +625,32 @@ impl ToolExecutor for TerminalTool {
         // Preserve the pid for a best-effort kill when we need to kill from outside.
         let pid = child.id();
 
-        // Spawn wait_with_output in a background task so we can poll with select and kill by PID if needed.
-        let wait_handle = tokio::spawn(async move { child.wait_with_output().await });
+        // Read both pipes line by line so output can be streamed while it is
+        // also captured in full for the final result.
+        let stdout_reader =
+            spawn_output_reader(child.stdout.take(), ToolOutputStream::Stdout, sink.clone());
+        let stderr_reader =
+            spawn_output_reader(child.stderr.take(), ToolOutputStream::Stderr, sink);
+
+        // The registration lives in the wait task, which keeps running when an
+        // interrupted agent drops this call, so shutdown can still stop the child.
+        let registration = pid.map(|pid| {
+            self.children
+                .register(pid, format!("terminal: {}", command))
+        });
+
+        // Wait in a background task so we can poll with select and kill by PID if needed.
+        let wait_handle = tokio::spawn(async move {
+            let _registration = registration;
+            let status = child.wait().await?;
+            let stdout = stdout_reader.await.unwrap_or_default();
+            let stderr = stderr_reader.await.unwrap_or_default();
+            Ok::<_, std::io::Error>(std::process::Output {
+                status,
+                stdout,
+                stderr,
+            })
+        });
 
         // Pin join handle so we can await via select without moving it
         let mut join_fut = Box::pin(wait_handle);
@@ -665,6 +752,44 @@ impl ToolExecutor for TerminalTool {
     }
 }
 
+/// Spawn a task that reads a child pipe line by line
+///
+/// Each line is pushed to `sink` without its trailing newline, and the raw
+/// bytes are returned so the complete output can be reported afterwards.
+fn spawn_output_reader<R>(
+    pipe: Option<R>,
+    stream: ToolOutputStream,
+    sink: ToolOutputSink,
+) -> tokio::task::JoinHandle<Vec<u8>>
+where
+    R: tokio::io::AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut captured = Vec::new();
+        let Some(pipe) = pipe else {
+            return captured;
+        };
+
+        let mut reader = BufReader::new(pipe);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    captured.extend_from_slice(&line);
+                    let text = String::from_utf8_lossy(&line);
+                    sink.push(
+                        stream,
+                        text.trim_end_matches(|c: char| c == '\n' || c == '\r'),
+                    );
+                }
+            }
+        }
+        captured
+    })
+}
+
 /// Validate a command for safety (convenience)
 pub fn validate_command(command: &str, mode: ExecutionMode, working_dir: PathBuf) -> Result<()> {
     let validator = CommandValidator::new(mode, working_dir);
@@ -748,6 +873,33 @@ mod tests {
         assert!(!res.success);
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_terminal_tool_streams_output_lines() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "").unwrap();
+        let validator =
+            CommandValidator::new(ExecutionMode::FullAutonomous, dir.path().to_path_buf());
+        let tool = TerminalTool::new(validator, TerminalConfig::default());
+
+        let (sink, mut lines) = ToolOutputSink::channel();
+        let res = tool
+            .execute_streaming(json!({ "command": "ls" }), sink)
+            .await
+            .unwrap();
+
+        let streamed: Vec<String> = std::iter::from_fn(|| lines.try_recv().ok())
+            .map(|line| {
+                assert_eq!(line.stream, ToolOutputStream::Stdout);
+                line.line
+            })
+            .collect();
+        assert!(res.success);
+        assert_eq!(streamed, vec!["a.txt", "b.txt"]);
+        assert_eq!(res.output, "a.txt\nb.txt\n");
+    }
+
     #[test]
     fn test_command_validator_allowlist_and_denylist() {
         let tmp = PathBuf::from("/tmp");
@@ -2,11 +2,15 @@
 //!
 //! Provides a tool to write or overwrite file contents with automatic parent directory creation.
 
-use crate::error::{Result, XzatomaError};
-use crate::tools::{file_utils, parse_tool_args, ToolExecutor, ToolResult};
+use crate::error::Result;
+use crate::tools::audit_log::{self, AuditLog, AuditOperation};
+use crate::tools::change_set::{ChangeKind, FileChange, StagedFiles};
+use crate::tools::confirmation::{path_scope, ActionCategory, ConfirmationPolicy};
+use crate::tools::{file_utils, parse_tool_args, ToolExecutor, ToolResult, TOOL_WRITE_FILE};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 /// Parameters for the write_file tool
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,6 +19,9 @@ struct WriteFileParams {
     path: String,
     /// Content to write to the file
     content: String,
+    /// Set after the user approves a confirmation request
+    #[serde(default)]
+    confirm: bool,
 }
 
 /// Tool for writing content to files
@@ -39,6 +46,8 @@ struct WriteFileParams {
 pub struct WriteFileTool {
     path_validator: file_utils::PathValidator,
     max_file_size: u64,
+    audit_log: Option<Arc<AuditLog>>,
+    confirmation: Option<ConfirmationPolicy>,
 }
 
 impl WriteFileTool {
@@ -68,15 +77,48 @@ impl WriteFileTool {
         Self {
             path_validator: file_utils::PathValidator::new(working_dir),
             max_file_size,
+            audit_log: None,
+            confirmation: None,
         }
     }
+
+    /// Sets the audit log that records every write
+    pub fn with_audit_log(mut self, audit_log: Option<Arc<AuditLog>>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// Sets the policy that asks for confirmation in ConfirmOnce mode
+    pub fn with_confirmation(mut self, confirmation: Option<ConfirmationPolicy>) -> Self {
+        self.confirmation = confirmation;
+        self
+    }
 }
 
 #[async_trait::async_trait]
 impl ToolExecutor for WriteFileTool {
+    fn mutates(&self) -> bool {
+        true
+    }
+
+    fn preview_change(&self, args: &serde_json::Value, staged: &StagedFiles) -> Option<FileChange> {
+        let params: WriteFileParams = serde_json::from_value(args.clone()).ok()?;
+        let path = self.path_validator.validate(&params.path).ok()?;
+        if staged.is_dir(&path) || params.content.len() as u64 > self.max_file_size {
+            return None;
+        }
+        let kind = if staged.exists(&path) {
+            ChangeKind::Modify
+        } else {
+            ChangeKind::Create
+        };
+        let before = staged.read(&path);
+        Some(FileChange::new(params.path, kind, path).with_contents(before, Some(params.content)))
+    }
+
     fn tool_definition(&self) -> serde_json::Value {
         json!({
-            "name": "write_file",
+            "name": TOOL_WRITE_FILE,
             "description": "Write content to a file. Creates parent directories as needed. Overwrites existing files.",
             "parameters": {
                 "type": "object",
@@ -88,6 +130,10 @@ impl ToolExecutor for WriteFileTool {
                     "content": {
                         "type": "string",
                         "description": "Content to write to the file"
+                    },
+                    "confirm": {
+                        "type": "boolean",
+                        "description": "Set to true after the user approves a confirmation request"
                     }
                 },
                 "required": ["path", "content"]
@@ -110,6 +156,17 @@ impl ToolExecutor for WriteFileTool {
         // Validate path
         let path = self.path_validator.validate(&params.path)?;
 
+        if let Some(refusal) = self.confirmation.as_ref().and_then(|policy| {
+            policy.check(
+                ActionCategory::FileWrite,
+                &path_scope(&params.path),
+                &params.path,
+                params.confirm,
+            )
+        }) {
+            return Ok(refusal);
+        }
+
         // Check if path exists and is a directory
         if path.exists() && path.is_dir() {
             return Ok(ToolResult::error(format!(
@@ -118,13 +175,44 @@ impl ToolExecutor for WriteFileTool {
             )));
         }
 
+        // The size and hash before the write are captured now. Strict mode
+        // records them before the write, lenient mode once it succeeds
+        let audit = match &self.audit_log {
+            Some(log) => {
+                let before = audit_log::file_fingerprint(&path).await;
+                let operation = if path.exists() {
+                    AuditOperation::Modify
+                } else {
+                    AuditOperation::Create
+                };
+                let before_size = before.as_ref().map_or(0, |(size, _)| *size);
+                let entry = log
+                    .entry(TOOL_WRITE_FILE, operation, &path)
+                    .with_byte_delta(params.content.len() as i64 - before_size as i64)
+                    .with_hashes(
+                        before.map(|(_, hash)| hash),
+                        Some(audit_log::hash_bytes(params.content.as_bytes())),
+                    );
+                Some((log, entry))
+            }
+            None => None,
+        };
+        if let Some(aborted) = audit
+            .as_ref()
+            .and_then(|(log, entry)| log.record_intent(entry))
+        {
+            return Ok(aborted);
+        }
+
         // Ensure parent directories exist
         file_utils::ensure_parent_dirs(&path).await?;
 
         // Write content to file
-        tokio::fs::write(&path, &params.content)
-            .await
-            .map_err(XzatomaError::Io)?;
+        file_utils::write_atomic(&path, params.content.as_bytes()).await?;
+
+        if let Some((log, entry)) = audit {
+            log.record(&entry);
+        }
 
         Ok(ToolResult::success(format!(
             "File written successfully: {} ({} bytes)",
@@ -188,6 +276,86 @@ mod tests {
         assert_eq!(fs::read_to_string(&file_path).unwrap(), "content");
     }
 
+    #[tokio::test]
+    async fn test_execute_records_create_and_modify_in_audit_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let audit_path = temp_dir.path().join("audit/audit.jsonl");
+        let log = Arc::new(AuditLog::new(&audit_path, "session-1"));
+        let tool = WriteFileTool::new(temp_dir.path().to_path_buf(), 1024 * 1024)
+            .with_audit_log(Some(Arc::clone(&log)));
+
+        tool.execute(json!({"path": "notes.txt", "content": "hello"}))
+            .await
+            .unwrap();
+        tool.execute(json!({"path": "notes.txt", "content": "hi"}))
+            .await
+            .unwrap();
+
+        let entries = AuditLog::read_entries(&audit_path, Some("session-1")).unwrap();
+        assert_eq!(entries.len(), 2);
+        let file_path = temp_dir.path().join("notes.txt");
+
+        assert_eq!(entries[0].tool, "write_file");
+        assert_eq!(entries[0].operation, AuditOperation::Create);
+        assert_eq!(entries[0].path, file_path);
+        assert_eq!(entries[0].byte_delta, 5);
+        assert_eq!(entries[0].hash_before, None);
+
+        assert_eq!(entries[1].operation, AuditOperation::Modify);
+        assert_eq!(entries[1].byte_delta, -3);
+        assert_eq!(entries[1].hash_before, entries[0].hash_after);
+        assert_eq!(
+            entries[1].hash_after.as_deref(),
+            Some(audit_log::hash_bytes(b"hi").as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_aborts_when_strict_audit_write_fails() {
+        let temp_dir = TempDir::new().unwrap();
+        // The audit path is a directory, so appending to it fails
+        let audit_dir = temp_dir.path().join("audit");
+        fs::create_dir(&audit_dir).unwrap();
+
+        let lenient = WriteFileTool::new(temp_dir.path().to_path_buf(), 1024 * 1024)
+            .with_audit_log(Some(Arc::new(AuditLog::new(&audit_dir, "s"))));
+        let result = lenient
+            .execute(json!({"path": "lenient.txt", "content": "x"}))
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert!(temp_dir.path().join("lenient.txt").exists());
+
+        let strict = WriteFileTool::new(temp_dir.path().to_path_buf(), 1024 * 1024).with_audit_log(
+            Some(Arc::new(AuditLog::new(&audit_dir, "s").with_required(true))),
+        );
+        let result = strict
+            .execute(json!({"path": "strict.txt", "content": "x"}))
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("audit_required"));
+        assert!(!temp_dir.path().join("strict.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_execute_does_not_record_failed_write() {
+        let temp_dir = TempDir::new().unwrap();
+        // A regular file cannot be the parent directory of the target
+        fs::write(temp_dir.path().join("blocker"), "x").unwrap();
+        let audit_path = temp_dir.path().join("audit.jsonl");
+        let tool = WriteFileTool::new(temp_dir.path().to_path_buf(), 1024 * 1024)
+            .with_audit_log(Some(Arc::new(AuditLog::new(&audit_path, "s"))));
+
+        let result = tool
+            .execute(json!({"path": "blocker/notes.txt", "content": "x"}))
+            .await;
+        assert!(result.is_err());
+        assert!(AuditLog::read_entries(&audit_path, None)
+            .unwrap()
+            .is_empty());
+    }
+
     #[tokio::test]
     async fn test_execute_with_invalid_path_returns_error() {
         let temp_dir = TempDir::new().unwrap();
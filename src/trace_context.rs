@@ -0,0 +1,418 @@
+//! W3C trace context propagation into agent execution spans
+//!
+//! Events from the platform carry a W3C `traceparent` (and optionally a
+//! `tracestate`) CloudEvents extension. The watcher reads them with
+//! [`RemoteTraceContext::from_extensions`] and opens the plan execution root
+//! span with [`plan_execution_span`]. The agent opens [`agent_span`],
+//! [`provider_span`], and [`tool_span`] below it.
+//!
+//! The event id and plan name travel with the execution through the
+//! task-local [`ExecutionSpanContext`], so every span records them next to
+//! the agent session id without threading them through each call.
+//!
+//! With the `otel` cargo feature, the root span becomes a child of the remote
+//! context through `tracing-opentelemetry`. This takes effect when the
+//! process installs a `tracing_opentelemetry` layer. Without the feature,
+//! the `traceparent` string is recorded as a plain field on the root span.
+//!
+//! Agent, provider, and tool spans declare a `success` field, and provider
+//! spans also declare `prompt_tokens` and `completion_tokens`. They are
+//! filled in with [`record_success`] and [`record_completion`] once the
+//! operation finishes. See [`crate::tracing_setup`] for exporting the spans
+//! over OTLP.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use xzatoma::trace_context::RemoteTraceContext;
+//!
+//! let mut extensions = HashMap::new();
+//! extensions.insert(
+//!     "traceparent".to_string(),
+//!     serde_json::json!("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+//! );
+//!
+//! let remote = RemoteTraceContext::from_extensions(&extensions).unwrap();
+//! assert_eq!(remote.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+
+use serde_json::Value as JsonValue;
+use tracing::Span;
+
+use crate::error::Result;
+use crate::providers::CompletionResponse;
+
+/// CloudEvents extension carrying the W3C traceparent
+pub const TRACEPARENT_EXTENSION: &str = "traceparent";
+
+/// CloudEvents extension carrying the W3C tracestate
+pub const TRACESTATE_EXTENSION: &str = "tracestate";
+
+tokio::task_local! {
+    static EXECUTION_CONTEXT: ExecutionSpanContext;
+}
+
+/// Remote parent context taken from an incoming event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteTraceContext {
+    /// Validated `traceparent` header value
+    pub traceparent: String,
+    /// Optional vendor-specific `tracestate` header value
+    pub tracestate: Option<String>,
+}
+
+impl RemoteTraceContext {
+    /// Builds a context from a `traceparent` and optional `tracestate`
+    ///
+    /// # Returns
+    ///
+    /// `None` when `traceparent` is not a valid W3C traceparent
+    pub fn new(traceparent: &str, tracestate: Option<&str>) -> Option<Self> {
+        let traceparent = traceparent.trim();
+        if !is_valid_traceparent(traceparent) {
+            return None;
+        }
+
+        Some(Self {
+            traceparent: traceparent.to_string(),
+            tracestate: tracestate
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string),
+        })
+    }
+
+    /// Reads `traceparent` and `tracestate` from CloudEvents extensions
+    ///
+    /// # Returns
+    ///
+    /// `None` when the extensions carry no valid traceparent
+    pub fn from_extensions(extensions: &HashMap<String, JsonValue>) -> Option<Self> {
+        let traceparent = extensions.get(TRACEPARENT_EXTENSION)?.as_str()?;
+        let tracestate = extensions
+            .get(TRACESTATE_EXTENSION)
+            .and_then(JsonValue::as_str);
+        Self::new(traceparent, tracestate)
+    }
+
+    /// Returns the 32-character trace id
+    pub fn trace_id(&self) -> &str {
+        &self.traceparent[3..35]
+    }
+
+    /// Returns the 16-character id of the remote parent span
+    pub fn parent_span_id(&self) -> &str {
+        &self.traceparent[36..52]
+    }
+}
+
+/// Checks the `version-traceid-parentid-flags` format of a W3C traceparent
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::trace_context::is_valid_traceparent;
+///
+/// assert!(is_valid_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"));
+/// assert!(!is_valid_traceparent("00-00000000000000000000000000000000-00f067aa0ba902b7-01"));
+/// ```
+pub fn is_valid_traceparent(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    let [version, trace_id, parent_id, flags] = parts[..] else {
+        return false;
+    };
+
+    let is_hex = |s: &str, len: usize| {
+        s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    };
+    let is_zero = |s: &str| s.bytes().all(|b| b == b'0');
+
+    is_hex(version, 2)
+        && version != "ff"
+        && is_hex(trace_id, 32)
+        && !is_zero(trace_id)
+        && is_hex(parent_id, 16)
+        && !is_zero(parent_id)
+        && is_hex(flags, 2)
+}
+
+/// Event and plan attributes recorded on every span of one execution
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionSpanContext {
+    /// Id of the event that triggered the execution
+    pub event_id: Option<String>,
+    /// Name of the plan being executed
+    pub plan_name: Option<String>,
+}
+
+impl ExecutionSpanContext {
+    /// Runs `future` with this context visible to [`ExecutionSpanContext::current`]
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        EXECUTION_CONTEXT.scope(self, future).await
+    }
+
+    /// Returns the context of the running execution, or an empty context
+    pub fn current() -> Self {
+        EXECUTION_CONTEXT.try_with(Clone::clone).unwrap_or_default()
+    }
+}
+
+/// Opens the root span of a plan execution
+///
+/// # Arguments
+///
+/// * `context` - Event id and plan name of the execution
+/// * `remote` - Parent context from the triggering event, if any
+pub fn plan_execution_span(
+    context: &ExecutionSpanContext,
+    remote: Option<&RemoteTraceContext>,
+) -> Span {
+    let span = tracing::info_span!(
+        "plan_execution",
+        event_id = context.event_id.as_deref().unwrap_or_default(),
+        plan_name = context.plan_name.as_deref().unwrap_or_default(),
+        traceparent = tracing::field::Empty,
+        tracestate = tracing::field::Empty,
+    );
+
+    if let Some(remote) = remote {
+        attach_remote_parent(&span, remote);
+    }
+
+    span
+}
+
+/// Opens the span of one agent prompt execution
+///
+/// # Arguments
+///
+/// * `session_id` - Conversation id of the agent
+pub fn agent_span(session_id: &str) -> Span {
+    let context = ExecutionSpanContext::current();
+    tracing::info_span!(
+        "agent_execution",
+        session_id = session_id,
+        event_id = context.event_id.as_deref().unwrap_or_default(),
+        plan_name = context.plan_name.as_deref().unwrap_or_default(),
+        success = tracing::field::Empty,
+    )
+}
+
+/// Opens the span of one provider completion request
+///
+/// # Arguments
+///
+/// * `session_id` - Conversation id of the agent
+/// * `model` - Model serving the request
+pub fn provider_span(session_id: &str, model: &str) -> Span {
+    let context = ExecutionSpanContext::current();
+    tracing::info_span!(
+        "provider_completion",
+        session_id = session_id,
+        model = model,
+        event_id = context.event_id.as_deref().unwrap_or_default(),
+        plan_name = context.plan_name.as_deref().unwrap_or_default(),
+        prompt_tokens = tracing::field::Empty,
+        completion_tokens = tracing::field::Empty,
+        success = tracing::field::Empty,
+    )
+}
+
+/// Opens the span of one tool call
+///
+/// # Arguments
+///
+/// * `session_id` - Conversation id of the agent
+/// * `tool` - Tool name
+pub fn tool_span(session_id: &str, tool: &str) -> Span {
+    let context = ExecutionSpanContext::current();
+    tracing::info_span!(
+        "tool_call",
+        session_id = session_id,
+        tool = tool,
+        event_id = context.event_id.as_deref().unwrap_or_default(),
+        plan_name = context.plan_name.as_deref().unwrap_or_default(),
+        success = tracing::field::Empty,
+    )
+}
+
+/// Records whether the operation behind `span` succeeded
+///
+/// # Arguments
+///
+/// * `span` - Span returned by [`agent_span`], [`provider_span`], or [`tool_span`]
+/// * `success` - Outcome of the operation
+pub fn record_success(span: &Span, success: bool) {
+    span.record("success", success);
+}
+
+/// Records the outcome and token usage of a provider completion
+///
+/// Token counts are only recorded when the provider reported usage.
+///
+/// # Arguments
+///
+/// * `span` - Span returned by [`provider_span`]
+/// * `result` - Result of the completion request
+pub fn record_completion(span: &Span, result: &Result<CompletionResponse>) {
+    record_success(span, result.is_ok());
+    if let Ok(CompletionResponse {
+        usage: Some(usage), ..
+    }) = result
+    {
+        span.record("prompt_tokens", usage.prompt_tokens as u64);
+        span.record("completion_tokens", usage.completion_tokens as u64);
+    }
+}
+
+/// Makes `span` a child of the remote OpenTelemetry context
+#[cfg(feature = "otel")]
+fn attach_remote_parent(span: &Span, remote: &RemoteTraceContext) {
+    use opentelemetry::propagation::TextMapPropagator;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let mut carrier = HashMap::new();
+    carrier.insert(
+        TRACEPARENT_EXTENSION.to_string(),
+        remote.traceparent.clone(),
+    );
+    if let Some(tracestate) = &remote.tracestate {
+        carrier.insert(TRACESTATE_EXTENSION.to_string(), tracestate.clone());
+    }
+
+    let parent = TraceContextPropagator::new().extract(&carrier);
+    span.set_parent(parent);
+}
+
+/// Records the remote context as plain fields on `span`
+#[cfg(not(feature = "otel"))]
+fn attach_remote_parent(span: &Span, remote: &RemoteTraceContext) {
+    span.record(TRACEPARENT_EXTENSION, remote.traceparent.as_str());
+    if let Some(tracestate) = &remote.tracestate {
+        span.record(TRACESTATE_EXTENSION, tracestate.as_str());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_traceparent_validation() {
+        assert!(is_valid_traceparent(TRACEPARENT));
+        assert!(!is_valid_traceparent(""));
+        assert!(!is_valid_traceparent(
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01"
+        ));
+        assert!(!is_valid_traceparent(
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        ));
+        assert!(!is_valid_traceparent(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01"
+        ));
+        assert!(!is_valid_traceparent(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7"
+        ));
+    }
+
+    #[test]
+    fn test_from_extensions_reads_traceparent_and_tracestate() {
+        let mut extensions = HashMap::new();
+        extensions.insert(
+            TRACEPARENT_EXTENSION.to_string(),
+            JsonValue::String(TRACEPARENT.to_string()),
+        );
+        extensions.insert(
+            TRACESTATE_EXTENSION.to_string(),
+            JsonValue::String("vendor=abc".to_string()),
+        );
+
+        let remote = RemoteTraceContext::from_extensions(&extensions).unwrap();
+        assert_eq!(remote.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(remote.parent_span_id(), "00f067aa0ba902b7");
+        assert_eq!(remote.tracestate.as_deref(), Some("vendor=abc"));
+    }
+
+    #[test]
+    fn test_from_extensions_ignores_invalid_or_missing_traceparent() {
+        let mut extensions = HashMap::new();
+        assert!(RemoteTraceContext::from_extensions(&extensions).is_none());
+
+        extensions.insert(
+            TRACEPARENT_EXTENSION.to_string(),
+            JsonValue::String("not-a-traceparent".to_string()),
+        );
+        assert!(RemoteTraceContext::from_extensions(&extensions).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_execution_context_is_task_local() {
+        assert_eq!(ExecutionSpanContext::current(), Default::default());
+
+        let context = ExecutionSpanContext {
+            event_id: Some("evt-1".to_string()),
+            plan_name: Some("deploy".to_string()),
+        };
+        let seen = context
+            .clone()
+            .scope(async { ExecutionSpanContext::current() })
+            .await;
+
+        assert_eq!(seen, context);
+        assert_eq!(ExecutionSpanContext::current(), Default::default());
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn test_spans_are_children_of_remote_context() {
+        use opentelemetry::trace::{SpanId, TraceId, TracerProvider as _};
+        use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+        use opentelemetry_sdk::trace::TracerProvider;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        let remote = RemoteTraceContext::new(TRACEPARENT, None).unwrap();
+        let context = ExecutionSpanContext {
+            event_id: Some("evt-1".to_string()),
+            plan_name: Some("deploy".to_string()),
+        };
+
+        tracing::subscriber::with_default(subscriber, || {
+            let root = plan_execution_span(&context, Some(&remote));
+            let _root = root.enter();
+            let agent = agent_span("session-1");
+            let _agent = agent.enter();
+            let _tool = tool_span("session-1", "read_file").entered();
+        });
+        let _ = provider.force_flush();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let find = |name: &str| spans.iter().find(|s| s.name == name).unwrap();
+        let root = find("plan_execution");
+        let agent = find("agent_execution");
+        let tool = find("tool_call");
+
+        let trace_id = TraceId::from_hex(remote.trace_id()).unwrap();
+        assert_eq!(root.span_context.trace_id(), trace_id);
+        assert_eq!(
+            root.parent_span_id,
+            SpanId::from_hex(remote.parent_span_id()).unwrap()
+        );
+        assert_eq!(agent.span_context.trace_id(), trace_id);
+        assert_eq!(agent.parent_span_id, root.span_context.span_id());
+        assert_eq!(tool.parent_span_id, agent.span_context.span_id());
+    }
+}
@@ -0,0 +1,481 @@
+//! Tracing subscriber setup shared by the CLI and the watcher
+//!
+//! [`init_tracing`] installs the global subscriber first thing in `main`, so
+//! warnings logged while the configuration is located, trust-checked, and
+//! loaded reach stderr. Parts of the subscriber depend on the configuration
+//! and are filled in through the returned [`TracingHandle`] once it is
+//! loaded: the span exporter, and for the watcher its own output layers and
+//! log level from [`crate::watcher::logging`].
+//!
+//! When `telemetry.otlp` is configured and xzatoma is built with the `otel`
+//! feature, the agent, provider, and tool spans from [`crate::trace_context`]
+//! are exported to an OpenTelemetry collector over OTLP/HTTP, unless offline
+//! mode denies [`NetworkCapability::TelemetryExport`]. The returned
+//! [`TracingGuard`] flushes pending spans and shuts the exporter down when it
+//! is dropped, so callers hold it until the command finishes.
+
+use std::any::TypeId;
+use std::sync::{Arc, OnceLock};
+
+use crate::config::TelemetryConfig;
+use crate::error::{Result, XzatomaError};
+use crate::network_policy::{NetworkCapability, NetworkPolicy};
+use tracing::{span, Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layered};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{
+    layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer, Registry,
+};
+
+/// Type-erased layer added to a subscriber
+pub type BoxedLayer<S> = Box<dyn Layer<S> + Send + Sync + 'static>;
+
+/// The registry below the output layers, filtered by the reloadable filter
+pub type FilteredRegistry = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
+
+/// Output layers of the global subscriber, replaceable at runtime
+pub type OutputLayers = Vec<BoxedLayer<FilteredRegistry>>;
+
+/// The subscriber below the span exporter
+type OutputSubscriber = Layered<reload::Layer<OutputLayers, FilteredRegistry>, FilteredRegistry>;
+
+/// Filter used until a command configures its own
+const DEFAULT_FILTER: &str = "xzatoma=info";
+
+/// Owns the span exporter for the lifetime of a command
+///
+/// Dropping the guard flushes buffered spans and shuts down the exporter.
+/// Without an OTLP configuration the guard is inert.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::TelemetryConfig;
+/// use xzatoma::tracing_setup::TracingGuard;
+///
+/// use xzatoma::network_policy::NetworkPolicy;
+///
+/// let guard = TracingGuard::install(&TelemetryConfig::default(), NetworkPolicy::online()).unwrap();
+/// assert!(!guard.is_exporting());
+/// ```
+#[must_use = "dropping the guard shuts down span export"]
+#[derive(Default)]
+pub struct TracingGuard {
+    #[cfg(feature = "otel")]
+    tracer: Option<opentelemetry_sdk::trace::Tracer>,
+}
+
+impl TracingGuard {
+    /// Installs the OTLP exporter described by `telemetry.otlp`, if any
+    ///
+    /// The batch exporter runs on the Tokio runtime, so this must be called
+    /// from within one when OTLP export is configured. Nothing is exported
+    /// when `policy` denies [`NetworkCapability::TelemetryExport`].
+    ///
+    /// # Arguments
+    ///
+    /// * `telemetry` - Telemetry configuration
+    /// * `policy` - Network policy, which offline mode restricts
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Config` if the exporter cannot be built
+    #[cfg(feature = "otel")]
+    pub fn install(telemetry: &TelemetryConfig, policy: NetworkPolicy) -> Result<Self> {
+        use opentelemetry::KeyValue;
+        use opentelemetry_otlp::WithExportConfig;
+
+        let Some(otlp) = &telemetry.otlp else {
+            return Ok(Self::default());
+        };
+        if !export_allowed(policy) {
+            return Ok(Self::default());
+        }
+
+        let exporter = opentelemetry_otlp::new_exporter()
+            .http()
+            .with_endpoint(otlp.endpoint.clone())
+            .with_headers(otlp.headers.clone());
+        let resource = opentelemetry_sdk::Resource::new(vec![KeyValue::new(
+            "service.name",
+            otlp.service_name.clone(),
+        )]);
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(exporter)
+            .with_trace_config(opentelemetry_sdk::trace::config().with_resource(resource))
+            .install_batch(opentelemetry_sdk::runtime::Tokio)
+            .map_err(|error| {
+                XzatomaError::Config(format!(
+                    "Failed to install OTLP exporter for '{}': {}",
+                    otlp.endpoint, error
+                ))
+            })?;
+
+        Ok(Self {
+            tracer: Some(tracer),
+        })
+    }
+
+    /// Installs the OTLP exporter described by `telemetry.otlp`, if any
+    ///
+    /// This build does not include the `otel` feature, so no exporter is
+    /// installed; [`TracingHandle::install_exporter`] logs a warning when
+    /// OTLP is configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `telemetry` - Telemetry configuration
+    /// * `policy` - Network policy, which offline mode restricts
+    ///
+    /// # Errors
+    ///
+    /// Never fails without the `otel` feature
+    #[cfg(not(feature = "otel"))]
+    pub fn install(_telemetry: &TelemetryConfig, _policy: NetworkPolicy) -> Result<Self> {
+        Ok(Self::default())
+    }
+
+    /// Returns true when spans are exported over OTLP
+    #[cfg(feature = "otel")]
+    pub fn is_exporting(&self) -> bool {
+        self.tracer.is_some()
+    }
+
+    /// Returns true when spans are exported over OTLP
+    #[cfg(not(feature = "otel"))]
+    pub fn is_exporting(&self) -> bool {
+        false
+    }
+
+    /// Returns a layer that forwards spans to the exporter
+    ///
+    /// Returns `None` when no exporter is installed, which adds nothing to
+    /// the subscriber.
+    #[cfg(feature = "otel")]
+    pub fn layer<S>(&self) -> Option<BoxedLayer<S>>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span> + Send + Sync + 'static,
+    {
+        self.tracer
+            .clone()
+            .map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer).boxed())
+    }
+
+    /// Returns a layer that forwards spans to the exporter
+    ///
+    /// Always `None` without the `otel` feature.
+    #[cfg(not(feature = "otel"))]
+    pub fn layer<S>(&self) -> Option<BoxedLayer<S>>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span> + Send + Sync + 'static,
+    {
+        None
+    }
+}
+
+impl std::fmt::Debug for TracingGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TracingGuard")
+            .field("exporting", &self.is_exporting())
+            .finish()
+    }
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if self.tracer.take().is_some() {
+            // Flushes the batch processor before shutting the provider down
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// Whether offline mode leaves span export enabled, warning when it does not
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+fn export_allowed(policy: NetworkPolicy) -> bool {
+    let allowed = policy.allows(NetworkCapability::TelemetryExport);
+    if !allowed {
+        tracing::warn!(
+            "telemetry.otlp is configured but offline mode disables {}; spans will not be exported",
+            NetworkCapability::TelemetryExport
+        );
+    }
+    allowed
+}
+
+/// Span exporter layer that is filled in once the configuration is loaded
+///
+/// The exporter is not kept behind a `reload` layer because `reload` never
+/// downcasts through its lock, and `OpenTelemetrySpanExt::set_parent` finds
+/// the exporter by downcasting the subscriber. A `OnceLock` never moves or
+/// drops its value once set, so forwarding the downcast is sound.
+struct ExporterSlot<S> {
+    layer: Arc<OnceLock<BoxedLayer<S>>>,
+}
+
+impl<S> Layer<S> for ExporterSlot<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(layer) = self.layer.get() {
+            layer.on_new_span(attrs, id, ctx);
+        }
+    }
+
+    fn on_record(&self, span: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(layer) = self.layer.get() {
+            layer.on_record(span, values, ctx);
+        }
+    }
+
+    fn on_follows_from(&self, span: &span::Id, follows: &span::Id, ctx: Context<'_, S>) {
+        if let Some(layer) = self.layer.get() {
+            layer.on_follows_from(span, follows, ctx);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if let Some(layer) = self.layer.get() {
+            layer.on_event(event, ctx);
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(layer) = self.layer.get() {
+            layer.on_enter(id, ctx);
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(layer) = self.layer.get() {
+            layer.on_exit(id, ctx);
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        if let Some(layer) = self.layer.get() {
+            layer.on_close(id, ctx);
+        }
+    }
+
+    fn on_id_change(&self, old: &span::Id, new: &span::Id, ctx: Context<'_, S>) {
+        if let Some(layer) = self.layer.get() {
+            layer.on_id_change(old, new, ctx);
+        }
+    }
+
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            return Some(self as *const Self as *const ());
+        }
+        // SAFETY: the layer lives in the `OnceLock` for as long as the
+        // subscriber holds this slot and is never replaced
+        self.layer.get().and_then(|layer| layer.downcast_raw(id))
+    }
+}
+
+/// Configures the global subscriber after it was installed
+///
+/// Returned by [`init_tracing`]. Handles that outlive their subscriber
+/// fail with `XzatomaError::Config`.
+#[derive(Clone)]
+pub struct TracingHandle {
+    filter: reload::Handle<EnvFilter, Registry>,
+    output: reload::Handle<OutputLayers, FilteredRegistry>,
+    exporter: Arc<OnceLock<BoxedLayer<OutputSubscriber>>>,
+}
+
+impl TracingHandle {
+    /// Installs the span exporter described by `telemetry.otlp`
+    ///
+    /// Call this once the configuration is loaded. Spans opened before
+    /// then are not exported.
+    ///
+    /// # Arguments
+    ///
+    /// * `telemetry` - Telemetry configuration
+    /// * `policy` - Network policy; offline mode disables export
+    ///
+    /// # Returns
+    ///
+    /// The guard that owns the span exporter; hold it until the command ends
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Config` if the exporter cannot be built or an
+    /// exporter was already installed
+    pub fn install_exporter(
+        &self,
+        telemetry: &TelemetryConfig,
+        policy: NetworkPolicy,
+    ) -> Result<TracingGuard> {
+        let guard = TracingGuard::install(telemetry, policy)?;
+        if let Some(layer) = guard.layer() {
+            self.exporter.set(layer).map_err(|_| {
+                XzatomaError::Config("A span exporter is already installed".to_string())
+            })?;
+        }
+        warn_if_export_unavailable(telemetry);
+        Ok(guard)
+    }
+
+    /// Replaces the output layers, such as the stderr formatter
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Config` if the subscriber was dropped
+    pub fn set_output(&self, layers: OutputLayers) -> Result<()> {
+        self.output.reload(layers).map_err(|error| {
+            XzatomaError::Config(format!("Failed to replace the log output: {}", error))
+        })
+    }
+
+    /// Returns the handle that replaces the log filter
+    pub fn filter(&self) -> reload::Handle<EnvFilter, Registry> {
+        self.filter.clone()
+    }
+}
+
+impl std::fmt::Debug for TracingHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TracingHandle")
+            .field("exporting", &self.exporter.get().is_some())
+            .finish()
+    }
+}
+
+/// Builds the subscriber installed by [`init_tracing`] without installing it
+///
+/// Log lines go to stderr, filtered by `RUST_LOG` (default `xzatoma=info`).
+/// The handle replaces the filter and the output layers and adds the span
+/// exporter later. Tests use this to configure a subscriber that is only
+/// set as the default for their thread.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::tracing_setup::cli_subscriber;
+///
+/// let (subscriber, handle) = cli_subscriber();
+/// let _default = tracing::subscriber::set_default(subscriber);
+/// handle.set_output(Vec::new()).unwrap();
+/// ```
+pub fn cli_subscriber() -> (impl Subscriber + Send + Sync + 'static, TracingHandle) {
+    let env_filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let (filter_layer, filter) = reload::Layer::new(env_filter);
+    let stderr: BoxedLayer<FilteredRegistry> = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .boxed();
+    let (output_layer, output) = reload::Layer::new(vec![stderr]);
+    let exporter = Arc::new(OnceLock::new());
+
+    let subscriber = tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(output_layer)
+        .with(ExporterSlot {
+            layer: Arc::clone(&exporter),
+        });
+    (
+        subscriber,
+        TracingHandle {
+            filter,
+            output,
+            exporter,
+        },
+    )
+}
+
+/// Installs the global subscriber used by every command
+///
+/// Call this before anything is logged, ahead of loading the
+/// configuration. See [`cli_subscriber`] for what it logs.
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Config` if a global subscriber is already
+/// installed
+pub fn init_tracing() -> Result<TracingHandle> {
+    let (subscriber, handle) = cli_subscriber();
+    subscriber.try_init().map_err(|error| {
+        XzatomaError::Config(format!("Failed to initialize tracing: {}", error))
+    })?;
+    Ok(handle)
+}
+
+/// Logs a warning when OTLP export is configured but not compiled in
+fn warn_if_export_unavailable(telemetry: &TelemetryConfig) {
+    if cfg!(not(feature = "otel")) && telemetry.otlp.is_some() {
+        tracing::warn!(
+            "telemetry.otlp is configured but xzatoma was built without the `otel` feature; spans will not be exported"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OtlpConfig;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn otlp_telemetry() -> TelemetryConfig {
+        TelemetryConfig {
+            otlp: Some(OtlpConfig {
+                endpoint: "http://localhost:4318".to_string(),
+                headers: Default::default(),
+                service_name: "xzatoma".to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_guard_without_otlp_config_is_inert() {
+        let guard =
+            TracingGuard::install(&TelemetryConfig::default(), NetworkPolicy::online()).unwrap();
+        assert!(!guard.is_exporting());
+        assert!(guard.layer::<tracing_subscriber::Registry>().is_none());
+    }
+
+    #[cfg(not(feature = "otel"))]
+    #[test]
+    fn test_otlp_config_without_feature_does_not_export() {
+        let guard = TracingGuard::install(&otlp_telemetry(), NetworkPolicy::online()).unwrap();
+        assert!(!guard.is_exporting());
+    }
+
+    #[test]
+    fn test_offline_mode_does_not_export() {
+        let guard = TracingGuard::install(&otlp_telemetry(), NetworkPolicy::offline()).unwrap();
+        assert!(!guard.is_exporting());
+    }
+
+    #[test]
+    fn test_output_layers_can_be_replaced_after_install() {
+        struct CountEvents(Arc<AtomicUsize>);
+
+        impl<S: Subscriber> Layer<S> for CountEvents {
+            fn on_event(&self, _event: &Event<'_>, _ctx: Context<'_, S>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let (subscriber, handle) = cli_subscriber();
+        let _default = tracing::subscriber::set_default(subscriber);
+        let events = Arc::new(AtomicUsize::new(0));
+        handle
+            .set_output(vec![CountEvents(Arc::clone(&events)).boxed()])
+            .unwrap();
+
+        tracing::warn!(target: "xzatoma", "counted");
+
+        assert_eq!(events.load(Ordering::SeqCst), 1);
+        let guard = handle
+            .install_exporter(&TelemetryConfig::default(), NetworkPolicy::online())
+            .unwrap();
+        assert!(!guard.is_exporting());
+    }
+}
@@ -0,0 +1,483 @@
+//! Live markdown transcript of a chat session
+//!
+//! `xzatoma chat --transcript <path>` (or `agent.chat.transcript_path`)
+//! mirrors the conversation to a markdown file while it happens, so
+//! teammates can follow a pairing session with `tail -f` or the file can be
+//! committed afterwards. User messages, assistant responses, and a one-line
+//! record of each tool call are appended as the turn runs; slash commands
+//! and mode or safety switches are recorded as metadata lines.
+//!
+//! Entries are buffered and written when [`Transcript::flush`] is called,
+//! which chat does at the end of every turn and after every command. If the
+//! file stops accepting writes, the first failed flush returns the error and
+//! the transcript is disabled, so chat can warn once and carry on.
+//!
+//! # Examples
+//!
+//! ```
+//! use xzatoma::transcript::Transcript;
+//!
+//! let dir = tempfile::tempdir()?;
+//! let path = dir.path().join("session.md");
+//!
+//! let mut transcript = Transcript::open(&path)?;
+//! transcript.user_message("What does main.rs do?");
+//! transcript.assistant_message("It parses the CLI and dispatches commands.");
+//! transcript.flush()?;
+//!
+//! let markdown = std::fs::read_to_string(&path)?;
+//! assert!(markdown.contains("## User\n\nWhat does main.rs do?\n"));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{SecondsFormat, Utc};
+
+use crate::agent::events::{AgentExecutionEvent, AgentObserver};
+use crate::agent::ToolCallStatus;
+use crate::error::{Result, XzatomaError};
+
+/// Longest tool-call arguments or error text kept in a tool record
+const MAX_RECORD_CHARS: usize = 120;
+
+/// Markdown transcript that buffers entries until the next flush
+pub struct Transcript {
+    writer: Box<dyn Write + Send>,
+    pending: String,
+    disabled: bool,
+}
+
+impl std::fmt::Debug for Transcript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Transcript")
+            .field("pending_bytes", &self.pending.len())
+            .field("disabled", &self.disabled)
+            .finish()
+    }
+}
+
+impl Transcript {
+    /// Opens `path` for appending and writes a session heading
+    ///
+    /// The parent directory is created if needed. An existing transcript is
+    /// kept, so resumed sessions continue in the same file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or the heading cannot be
+    /// written.
+    pub fn open(path: &Path) -> Result<Self> {
+        let open = || {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)?;
+            }
+            OpenOptions::new().create(true).append(true).open(path)
+        };
+        let file = open().map_err(|e| {
+            XzatomaError::Config(format!("Cannot open transcript {}: {}", path.display(), e))
+        })?;
+
+        let mut transcript = Self::from_writer(Box::new(file));
+        transcript.flush()?;
+        Ok(transcript)
+    }
+
+    /// Creates a transcript that writes to `writer`, starting with a session heading
+    pub fn from_writer(writer: Box<dyn Write + Send>) -> Self {
+        let started = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        Self {
+            writer,
+            pending: format!("# Chat session {}\n\n", started),
+            disabled: false,
+        }
+    }
+
+    /// Records a message typed by the user
+    pub fn user_message(&mut self, text: &str) {
+        self.push(&render_user(text));
+    }
+
+    /// Records text returned by the assistant
+    pub fn assistant_message(&mut self, text: &str) {
+        self.push(&render_assistant(text));
+    }
+
+    /// Records a finished tool call
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Tool name
+    /// * `arguments` - Arguments as sent by the model
+    /// * `outcome` - Status and output or error of the call
+    pub fn tool_call(&mut self, name: &str, arguments: &str, outcome: ToolOutcome<'_>) {
+        self.push(&render_tool_call(name, arguments, outcome));
+    }
+
+    /// Records a metadata line such as a slash command or a mode switch
+    pub fn metadata(&mut self, text: &str) {
+        self.push(&render_metadata(text));
+    }
+
+    /// Writes and flushes the buffered entries
+    ///
+    /// # Errors
+    ///
+    /// Returns the write error the first time the writer fails. The
+    /// transcript is disabled from then on, and later calls discard their
+    /// entries and return `Ok`.
+    pub fn flush(&mut self) -> Result<()> {
+        if self.disabled {
+            self.pending.clear();
+            return Ok(());
+        }
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let written = self
+            .writer
+            .write_all(self.pending.as_bytes())
+            .and_then(|_| self.writer.flush());
+        self.pending.clear();
+        written.map_err(|e| {
+            self.disabled = true;
+            XzatomaError::Io(e)
+        })
+    }
+
+    /// Whether a failed write disabled the transcript
+    pub fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+
+    fn push(&mut self, entry: &str) {
+        if !self.disabled {
+            self.pending.push_str(entry);
+        }
+    }
+}
+
+/// Result of a tool call, as recorded in the transcript
+#[derive(Debug, Clone, Copy)]
+pub enum ToolOutcome<'a> {
+    /// The tool returned output
+    Completed {
+        /// Success, failure, or timeout
+        status: ToolCallStatus,
+        /// Output returned to the model
+        output: &'a str,
+    },
+    /// The tool call failed before producing a result
+    Failed {
+        /// Error description
+        error: &'a str,
+    },
+}
+
+/// Renders a user message as a markdown section
+pub fn render_user(text: &str) -> String {
+    format!("## User\n\n{}\n\n", text.trim_end())
+}
+
+/// Renders an assistant response as a markdown section
+pub fn render_assistant(text: &str) -> String {
+    format!("## Assistant\n\n{}\n\n", text.trim_end())
+}
+
+/// Renders a condensed one-line record of a tool call
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::agent::ToolCallStatus;
+/// use xzatoma::transcript::{render_tool_call, ToolOutcome};
+///
+/// let line = render_tool_call(
+///     "read_file",
+///     r#"{"path":"src/main.rs"}"#,
+///     ToolOutcome::Completed {
+///         status: ToolCallStatus::Success,
+///         output: "fn main() {\n}\n",
+///     },
+/// );
+/// assert_eq!(
+///     line,
+///     "- Tool `read_file` `{\"path\":\"src/main.rs\"}`: success, 2 lines of output\n\n"
+/// );
+/// ```
+pub fn render_tool_call(name: &str, arguments: &str, outcome: ToolOutcome<'_>) -> String {
+    let result = match outcome {
+        ToolOutcome::Completed { status, output } => {
+            let status = match status {
+                ToolCallStatus::Success => "success",
+                ToolCallStatus::Failure => "failure",
+                ToolCallStatus::Timeout => "timeout",
+            };
+            format!("{}, {} lines of output", status, output.lines().count())
+        }
+        ToolOutcome::Failed { error } => format!("error: {}", condense(error)),
+    };
+    format!(
+        "- Tool `{}` `{}`: {}\n\n",
+        name,
+        condense(arguments).replace('`', "'"),
+        result
+    )
+}
+
+/// Renders a metadata line as a markdown quote
+pub fn render_metadata(text: &str) -> String {
+    format!("> {}\n\n", condense(text))
+}
+
+/// Collapses whitespace to single spaces and truncates to `MAX_RECORD_CHARS`
+fn condense(text: &str) -> String {
+    let single_line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if single_line.chars().count() <= MAX_RECORD_CHARS {
+        return single_line;
+    }
+    let mut truncated: String = single_line.chars().take(MAX_RECORD_CHARS - 3).collect();
+    truncated.push_str("...");
+    truncated
+}
+
+/// Observer that records assistant text and tool calls in a transcript
+///
+/// Wraps the chat's own observer and forwards every event to it unchanged.
+pub struct TranscriptObserver<'a> {
+    transcript: &'a mut Transcript,
+    inner: &'a mut dyn AgentObserver,
+    arguments: HashMap<String, String>,
+}
+
+impl<'a> TranscriptObserver<'a> {
+    /// Wraps `inner`, recording events to `transcript`
+    pub fn new(transcript: &'a mut Transcript, inner: &'a mut dyn AgentObserver) -> Self {
+        Self {
+            transcript,
+            inner,
+            arguments: HashMap::new(),
+        }
+    }
+
+    fn record(&mut self, event: &AgentExecutionEvent) {
+        match event {
+            AgentExecutionEvent::AssistantTextEmitted { text } => {
+                self.transcript.assistant_message(text);
+            }
+            AgentExecutionEvent::ToolCallStarted { id, arguments, .. } => {
+                self.arguments.insert(id.clone(), arguments.clone());
+            }
+            AgentExecutionEvent::ToolCallCompleted {
+                id,
+                name,
+                output,
+                status,
+            } => {
+                let arguments = self.arguments.remove(id).unwrap_or_default();
+                self.transcript.tool_call(
+                    name,
+                    &arguments,
+                    ToolOutcome::Completed {
+                        status: *status,
+                        output,
+                    },
+                );
+            }
+            AgentExecutionEvent::ToolCallFailed { id, name, error } => {
+                let arguments = self.arguments.remove(id).unwrap_or_default();
+                self.transcript
+                    .tool_call(name, &arguments, ToolOutcome::Failed { error });
+            }
+            AgentExecutionEvent::ContextCompacted {
+                messages_summarized,
+                tool_outputs_dropped,
+                ..
+            } => self.transcript.metadata(&format!(
+                "Context compacted: summarized {} earlier messages, dropped {} tool outputs",
+                messages_summarized, tool_outputs_dropped
+            )),
+            _ => {}
+        }
+    }
+}
+
+impl AgentObserver for TranscriptObserver<'_> {
+    fn on_event(&mut self, event: AgentExecutionEvent) {
+        self.record(&event);
+        self.inner.on_event(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::events::NoOpObserver;
+    use crate::agent::Agent;
+    use crate::config::AgentConfig;
+    use crate::providers::{CompletionResponse, FunctionCall, Message, ToolCall};
+    use crate::testing::MockProvider;
+    use crate::tools::{ToolExecutor, ToolRegistry, ToolResult};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tokio_util::sync::CancellationToken;
+
+    /// Provider that, for each of two prompts, calls `read_file` once and
+    /// then answers
+    fn scripted_provider() -> MockProvider {
+        let read_main = Message::assistant_with_tools(vec![ToolCall {
+            id: "call_1".to_string(),
+            function: FunctionCall {
+                name: "read_file".to_string(),
+                arguments: r#"{"path":"src/main.rs"}"#.to_string(),
+            },
+        }]);
+        let mut provider = MockProvider::new();
+        for _ in 0..2 {
+            provider = provider
+                .then_response(CompletionResponse::new(read_main.clone()))
+                .then_text("main.rs parses the CLI.");
+        }
+        provider
+    }
+
+    struct ReadFileTool;
+
+    #[async_trait]
+    impl ToolExecutor for ReadFileTool {
+        fn tool_definition(&self) -> serde_json::Value {
+            serde_json::json!({
+                "name": "read_file",
+                "description": "reads a file",
+                "parameters": {
+                    "type": "object",
+                    "properties": {"path": {"type": "string"}}
+                }
+            })
+        }
+
+        async fn execute(&self, _args: serde_json::Value) -> Result<ToolResult> {
+            Ok(ToolResult::success(
+                "fn main() {\n    run();\n}".to_string(),
+            ))
+        }
+    }
+
+    /// Writer into a shared buffer that can be switched to failing
+    #[derive(Clone, Default)]
+    struct SharedWriter {
+        buffer: Arc<Mutex<Vec<u8>>>,
+        failing: Arc<AtomicBool>,
+    }
+
+    impl SharedWriter {
+        fn contents(&self) -> String {
+            String::from_utf8(self.buffer.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, "disk full"));
+            }
+            self.buffer.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transcript_records_chat_turns_and_flushes_per_turn() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes").join("session.md");
+        let mut transcript = Transcript::open(&path).unwrap();
+
+        let mut tools = ToolRegistry::new();
+        tools.register("read_file", Arc::new(ReadFileTool));
+        let mut agent = Agent::new(scripted_provider(), tools, AgentConfig::default()).unwrap();
+
+        for prompt in ["What does main.rs do?", "And again?"] {
+            transcript.user_message(prompt);
+            let before_turn = std::fs::read_to_string(&path).unwrap();
+            assert!(
+                !before_turn.contains(prompt),
+                "flushed before the turn ended"
+            );
+
+            let token = CancellationToken::new();
+            let mut inner = NoOpObserver;
+            let mut observer = TranscriptObserver::new(&mut transcript, &mut inner);
+            agent
+                .execute_with_observer(prompt, &token, &mut observer)
+                .await
+                .unwrap();
+            transcript.flush().unwrap();
+
+            assert!(std::fs::read_to_string(&path).unwrap().contains(prompt));
+        }
+        transcript.metadata("Command: /mode write");
+        transcript.metadata("Switched from PLANNING to WRITE mode");
+        transcript.flush().unwrap();
+
+        let markdown = std::fs::read_to_string(&path).unwrap();
+        assert!(markdown.starts_with("# Chat session "));
+        let turn = "## User\n\nWhat does main.rs do?\n\n\
+                    - Tool `read_file` `{\"path\":\"src/main.rs\"}`: success, 3 lines of output\n\n\
+                    ## Assistant\n\nmain.rs parses the CLI.\n\n";
+        assert!(
+            markdown.contains(turn),
+            "unexpected transcript:\n{}",
+            markdown
+        );
+        assert_eq!(markdown.matches("## User").count(), 2);
+        assert_eq!(markdown.matches("## Assistant").count(), 2);
+        assert!(markdown
+            .ends_with("> Command: /mode write\n\n> Switched from PLANNING to WRITE mode\n\n"));
+    }
+
+    #[test]
+    fn test_transcript_disables_itself_after_first_failed_flush() {
+        let writer = SharedWriter::default();
+        let mut transcript = Transcript::from_writer(Box::new(writer.clone()));
+        transcript.user_message("first");
+        transcript.flush().unwrap();
+
+        writer.failing.store(true, Ordering::SeqCst);
+        transcript.user_message("second");
+        assert!(transcript.flush().is_err());
+        assert!(transcript.is_disabled());
+
+        writer.failing.store(false, Ordering::SeqCst);
+        transcript.user_message("third");
+        assert!(transcript.flush().is_ok());
+
+        let contents = writer.contents();
+        assert!(contents.contains("first"));
+        assert!(!contents.contains("second"));
+        assert!(!contents.contains("third"));
+    }
+
+    #[test]
+    fn test_render_tool_call_condenses_arguments_and_errors() {
+        let arguments = format!("{{\"command\":\n\"{}\"}}", "x".repeat(200));
+        let line = render_tool_call(
+            "terminal",
+            &arguments,
+            ToolOutcome::Failed {
+                error: "command `rm` refused\nby policy",
+            },
+        );
+        assert!(line.starts_with("- Tool `terminal` `{\"command\": \"xxx"));
+        assert!(line.contains("...`: error: command `rm` refused by policy\n"));
+        assert_eq!(line.trim_end().lines().count(), 1);
+    }
+}
@@ -0,0 +1,407 @@
+//! Human-facing terminal output
+//!
+//! Command handlers print through this module instead of calling
+//! `println!` directly, so one place decides how output looks:
+//!
+//! - colors are enabled by `--color always|auto|never`; `auto` colors only
+//!   a terminal, and only when `NO_COLOR` is unset and `TERM` is not `dumb`;
+//! - [`Glyph`]s fall back to ASCII when the locale is not UTF-8;
+//! - commands run with `--json` are quiet: status messages are dropped and
+//!   only the machine-readable [`data`] reaches stdout.
+//!
+//! Status messages are written with [`ui_println!`](crate::ui_println),
+//! [`ui_print!`](crate::ui_print), and [`ui_eprintln!`](crate::ui_eprintln).
+//! Colors come from the `colored` crate, whose global override [`init`]
+//! sets. Tracing output is not affected.
+
+use std::fmt;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{Result, XzatomaError};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static UNICODE: AtomicBool = AtomicBool::new(true);
+
+/// When to color output, as given by `--color`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color a terminal unless `NO_COLOR` is set or `TERM` is `dumb`
+    #[default]
+    Auto,
+    /// Always color, even when piped or with `NO_COLOR` set
+    Always,
+    /// Never color
+    Never,
+}
+
+impl ColorChoice {
+    /// Parses a `--color` value
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Config` for a value other than `auto`,
+    /// `always`, or `never`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::ui::ColorChoice;
+    ///
+    /// assert_eq!(ColorChoice::parse("never").unwrap(), ColorChoice::Never);
+    /// assert!(ColorChoice::parse("sometimes").is_err());
+    /// ```
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            other => Err(XzatomaError::Config(format!(
+                "Invalid --color value '{}'. Expected auto, always, or never",
+                other
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorChoice::Auto => write!(f, "auto"),
+            ColorChoice::Always => write!(f, "always"),
+            ColorChoice::Never => write!(f, "never"),
+        }
+    }
+}
+
+/// Decides whether to color output
+///
+/// # Arguments
+///
+/// * `choice` - The `--color` value
+/// * `no_color` - Whether `NO_COLOR` is set to a non-empty value
+/// * `term` - The `TERM` environment variable
+/// * `is_terminal` - Whether stdout is a terminal
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::ui::{use_color, ColorChoice};
+///
+/// assert!(use_color(ColorChoice::Auto, false, Some("xterm-256color"), true));
+/// assert!(!use_color(ColorChoice::Auto, true, Some("xterm-256color"), true));
+/// assert!(use_color(ColorChoice::Always, true, None, false));
+/// ```
+pub fn use_color(
+    choice: ColorChoice,
+    no_color: bool,
+    term: Option<&str>,
+    is_terminal: bool,
+) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => is_terminal && !no_color && term != Some("dumb"),
+    }
+}
+
+/// Decides whether the terminal can show Unicode glyphs
+///
+/// The locale is the first non-empty of `LC_ALL`, `LC_CTYPE`, and `LANG`,
+/// as the C library resolves it. Windows terminals are assumed to support
+/// Unicode.
+///
+/// # Arguments
+///
+/// * `locale` - The effective locale, if any
+/// * `term` - The `TERM` environment variable
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::ui::use_unicode;
+///
+/// assert!(use_unicode(Some("en_US.UTF-8"), Some("xterm")) || cfg!(windows));
+/// assert!(!use_unicode(Some("C"), Some("xterm")) || cfg!(windows));
+/// ```
+pub fn use_unicode(locale: Option<&str>, term: Option<&str>) -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+    if matches!(term, Some("dumb") | Some("linux")) {
+        return false;
+    }
+    locale.is_some_and(|locale| {
+        let locale = locale.to_lowercase();
+        locale.contains("utf-8") || locale.contains("utf8")
+    })
+}
+
+/// Configures output for this process
+///
+/// Reads `NO_COLOR`, `TERM`, and the locale variables from the
+/// environment. Called once from `main` after parsing the command line.
+///
+/// # Arguments
+///
+/// * `choice` - The `--color` value
+/// * `json_output` - Whether the command prints JSON; status messages are
+///   then dropped
+pub fn init(choice: ColorChoice, json_output: bool) {
+    let term = std::env::var("TERM").ok();
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let color = use_color(
+        choice,
+        no_color,
+        term.as_deref(),
+        std::io::stdout().is_terminal(),
+    );
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+
+    colored::control::set_override(color);
+    UNICODE.store(
+        use_unicode(locale.as_deref(), term.as_deref()),
+        Ordering::Relaxed,
+    );
+    QUIET.store(json_output, Ordering::Relaxed);
+}
+
+/// Returns true when status messages are dropped
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Returns true when glyphs are shown as Unicode
+pub fn unicode() -> bool {
+    UNICODE.load(Ordering::Relaxed)
+}
+
+/// A symbol with an ASCII fallback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyph {
+    /// A change from one value to another
+    Arrow,
+    /// A branch in a tree listing
+    Branch,
+    /// A dash separating two parts of a sentence
+    Dash,
+}
+
+impl Glyph {
+    /// Returns the glyph's Unicode or ASCII form
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::ui::Glyph;
+    ///
+    /// assert_eq!(Glyph::Arrow.symbol(true), "→");
+    /// assert_eq!(Glyph::Arrow.symbol(false), "->");
+    /// ```
+    pub fn symbol(self, unicode: bool) -> &'static str {
+        match (self, unicode) {
+            (Glyph::Arrow, true) => "→",
+            (Glyph::Arrow, false) => "->",
+            (Glyph::Branch, true) => "├─",
+            (Glyph::Branch, false) => "|-",
+            (Glyph::Dash, true) => "—",
+            (Glyph::Dash, false) => "-",
+        }
+    }
+}
+
+impl fmt::Display for Glyph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol(unicode()))
+    }
+}
+
+/// Draws `title` centered in a box `width` characters wide
+///
+/// The box uses line-drawing characters, or `+`, `-`, and `|` when glyphs
+/// fall back to ASCII. The result has three lines and no trailing newline.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::ui::banner_with;
+///
+/// assert_eq!(banner_with("Hi", 8, false), "+------+\n|  Hi  |\n+------+");
+/// ```
+pub fn banner_with(title: &str, width: usize, unicode: bool) -> String {
+    let inner = width.saturating_sub(2).max(title.chars().count());
+    let padding = inner - title.chars().count();
+    let (left, right) = (padding / 2, padding - padding / 2);
+    let (top, bottom, side, line) = if unicode {
+        (("╔", "╗"), ("╚", "╝"), "║", "═")
+    } else {
+        (("+", "+"), ("+", "+"), "|", "-")
+    };
+    format!(
+        "{}{}{}\n{}{}{}{}{}\n{}{}{}",
+        top.0,
+        line.repeat(inner),
+        top.1,
+        side,
+        " ".repeat(left),
+        title,
+        " ".repeat(right),
+        side,
+        bottom.0,
+        line.repeat(inner),
+        bottom.1
+    )
+}
+
+/// Draws `title` in a box using the glyphs configured by [`init`]
+pub fn banner(title: &str, width: usize) -> String {
+    banner_with(title, width, unicode())
+}
+
+/// Writes machine-readable output to stdout
+///
+/// Unlike status messages, data is written in quiet mode too.
+pub fn data(text: impl fmt::Display) {
+    println!("{}", text);
+}
+
+/// Writes pretty-printed JSON to stdout
+///
+/// # Errors
+///
+/// Returns an error if `value` cannot be serialized.
+pub fn json<T: serde::Serialize + ?Sized>(value: &T) -> Result<()> {
+    data(serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+#[doc(hidden)]
+pub fn write_out(args: fmt::Arguments<'_>) {
+    if !is_quiet() {
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_fmt(args);
+        let _ = stdout.flush();
+    }
+}
+
+#[doc(hidden)]
+pub fn write_err(args: fmt::Arguments<'_>) {
+    if !is_quiet() {
+        let _ = std::io::stderr().lock().write_fmt(args);
+    }
+}
+
+/// Prints a status message line to stdout unless output is quiet
+#[macro_export]
+macro_rules! ui_println {
+    () => {
+        $crate::ui::write_out(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::ui::write_out(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+/// Prints a status message to stdout without a newline unless output is quiet
+#[macro_export]
+macro_rules! ui_print {
+    ($($arg:tt)*) => {
+        $crate::ui::write_out(format_args!($($arg)*))
+    };
+}
+
+/// Prints a status message line to stderr unless output is quiet
+#[macro_export]
+macro_rules! ui_eprintln {
+    () => {
+        $crate::ui::write_err(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::ui::write_err(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+/// Prints a status message to stderr without a newline unless output is quiet
+#[macro_export]
+macro_rules! ui_eprint {
+    ($($arg:tt)*) => {
+        $crate::ui::write_err(format_args!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_decision_matrix() {
+        let xterm = Some("xterm-256color");
+        // (choice, NO_COLOR set, TERM, is terminal) -> color
+        let cases = [
+            (ColorChoice::Auto, false, xterm, true, true),
+            (ColorChoice::Auto, false, xterm, false, false),
+            (ColorChoice::Auto, true, xterm, true, false),
+            (ColorChoice::Auto, false, Some("dumb"), true, false),
+            (ColorChoice::Auto, false, None, true, true),
+            (ColorChoice::Always, true, Some("dumb"), false, true),
+            (ColorChoice::Always, false, xterm, false, true),
+            (ColorChoice::Never, false, xterm, true, false),
+            (ColorChoice::Never, true, None, false, false),
+        ];
+        for (choice, no_color, term, is_terminal, expected) in cases {
+            assert_eq!(
+                use_color(choice, no_color, term, is_terminal),
+                expected,
+                "{} no_color={} term={:?} tty={}",
+                choice,
+                no_color,
+                term,
+                is_terminal
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_glyph_decision_matrix() {
+        let cases = [
+            (Some("en_US.UTF-8"), Some("xterm"), true),
+            (Some("de_DE.utf8"), None, true),
+            (Some("C.UTF-8"), Some("screen"), true),
+            (Some("C"), Some("xterm"), false),
+            (Some("POSIX"), None, false),
+            (Some("en_US.ISO-8859-1"), Some("xterm"), false),
+            (None, Some("xterm"), false),
+            (Some("en_US.UTF-8"), Some("dumb"), false),
+            (Some("en_US.UTF-8"), Some("linux"), false),
+        ];
+        for (locale, term, expected) in cases {
+            assert_eq!(
+                use_unicode(locale, term),
+                expected,
+                "locale={:?} term={:?}",
+                locale,
+                term
+            );
+        }
+    }
+
+    #[test]
+    fn test_every_glyph_has_ascii_fallback() {
+        for glyph in [Glyph::Arrow, Glyph::Branch, Glyph::Dash] {
+            assert!(glyph.symbol(false).is_ascii(), "{:?}", glyph);
+            assert_ne!(glyph.symbol(true), glyph.symbol(false));
+        }
+    }
+
+    #[test]
+    fn test_parse_color_choice() {
+        assert_eq!(ColorChoice::parse("AUTO").unwrap(), ColorChoice::Auto);
+        assert_eq!(ColorChoice::parse("always").unwrap(), ColorChoice::Always);
+        let err = ColorChoice::parse("yes").unwrap_err().to_string();
+        assert!(err.contains("--color"));
+    }
+}
@@ -53,6 +53,7 @@ use crate::tools::plan::{Plan, PlanParser};
 use crate::watcher::generic::consumer::RawKafkaMessage;
 use crate::watcher::generic::event::GenericPlanEvent;
 use crate::watcher::generic::matcher::GenericMatcher;
+use crate::watcher::workspace::GitSeed;
 use chrono::{DateTime, Utc};
 use std::path::{Path, PathBuf};
 use tracing::{debug, info};
@@ -103,6 +104,11 @@ pub struct GenericTask {
 
     /// UTC timestamp of when the triggering Kafka message was received and parsed.
     pub received_at: DateTime<Utc>,
+
+    /// Optional git repository used to seed the execution workspace.
+    ///
+    /// Read from the `workspace` object of the raw payload.
+    pub workspace_seed: Option<GitSeed>,
 }
 
 /// Centralized event parsing and matching pipeline for the generic watcher.
@@ -261,6 +267,7 @@ impl GenericEventHandler {
             instruction,
             correlation_key: msg.key,
             received_at: event.received_at,
+            workspace_seed: GitSeed::from_payload(&msg.payload),
         }))
     }
 
@@ -546,6 +553,19 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_handle_reads_workspace_seed_from_payload() {
+        let handler = GenericEventHandler::new(None, None);
+        let payload = "name: deploy\nworkspace:\n  repository: https://example.com/app.git\n  ref: v2\nsteps:\n  - name: s1\n    action: build\n";
+        let task = handler.handle(raw_msg(payload)).await.unwrap().unwrap();
+        let seed = task.workspace_seed.expect("seed should be parsed");
+        assert_eq!(seed.repository, "https://example.com/app.git");
+        assert_eq!(seed.git_ref.as_deref(), Some("v2"));
+
+        let task = handler.handle(raw_msg(VALID_YAML)).await.unwrap().unwrap();
+        assert!(task.workspace_seed.is_none());
+    }
+
     #[tokio::test]
     async fn test_handle_with_plan_directory_parse_error_propagates() {
         // File exists but contains invalid YAML; handler must return Err.
@@ -21,12 +21,16 @@
 //! # Plan execution
 //!
 //! In non-dry-run mode, the instruction derived from the resolved plan is
-//! passed to `crate::commands::run::run_plan_with_options` for execution
-//! through the standard agent plan-execution path. The result captures
-//! actual success/failure status from the execution.
+//! passed to `crate::commands::run::run_plan_in_working_dir` for execution
+//! through the standard agent plan-execution path, inside an isolated
+//! per-execution workspace. The result captures actual success/failure
+//! status from the execution.
+//!
+//! `allow_dangerous` and the concurrency limit are read from
+//! [`LiveSettings`], so a config reload applies them without a restart.
 
 use crate::config::{Config, KafkaSecurityConfig, KafkaWatcherConfig};
-use crate::error::Result;
+use crate::error::{Result, XzatomaError};
 use crate::watcher::generic::consumer::{
     GenericConsumerTrait, RawKafkaMessage, RealGenericConsumer,
 };
@@ -36,12 +40,14 @@ use crate::watcher::generic::result_event::GenericPlanResult;
 use crate::watcher::generic::result_producer::{
     FakeResultProducer, GenericResultProducer, ResultProducerTrait,
 };
+use crate::watcher::reload::LiveSettings;
+use crate::watcher::workspace::ExecutionWorkspace;
 
 use serde_json::json;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use thiserror::Error;
-use tokio::sync::{Mutex, Semaphore};
+use tokio::sync::Mutex;
 use tracing::{debug, error, info, warn};
 use ulid::Ulid;
 
@@ -90,8 +96,8 @@ pub enum MessageDisposition {
 /// in a [`GenericEventHandler`], and constructs a result producer via
 /// [`GenericResultProducer`].
 ///
-/// Concurrency is controlled through an [`Arc<Semaphore>`], mirroring the
-/// pattern used by the XZepr watcher.
+/// Concurrency is controlled through the semaphore in [`LiveSettings`],
+/// shared with the XZepr watcher.
 ///
 /// # Examples
 ///
@@ -117,11 +123,10 @@ pub enum MessageDisposition {
 /// # }
 /// ```
 pub struct GenericWatcher {
-    config: Arc<Config>,
+    settings: Arc<LiveSettings>,
     kafka_config: KafkaWatcherConfig,
     event_handler: GenericEventHandler,
     producer: Arc<dyn ResultProducerTrait>,
-    execution_semaphore: Arc<Semaphore>,
     dry_run: bool,
     published_results: Arc<Mutex<Vec<GenericPlanResult>>>,
     running: Arc<AtomicBool>,
@@ -197,22 +202,28 @@ impl GenericWatcher {
             )
         };
 
-        let execution_semaphore = Arc::new(Semaphore::new(
-            watcher_config.execution.max_concurrent_executions,
-        ));
+        let settings = Arc::new(
+            LiveSettings::new(config).map_err(|e| GenericWatcherError::Config(e.to_string()))?,
+        );
 
         Ok(Self {
-            config: Arc::new(config),
+            settings,
             kafka_config,
             event_handler,
             producer,
-            execution_semaphore,
             dry_run,
             published_results: Arc::new(Mutex::new(Vec::new())),
             running: Arc::new(AtomicBool::new(false)),
         })
     }
 
+    /// Returns the settings a config reload swaps while the watcher runs
+    ///
+    /// Pass them to a [`crate::watcher::reload::ConfigReloader`].
+    pub fn live_settings(&self) -> Arc<LiveSettings> {
+        self.settings.clone()
+    }
+
     /// Replace the result producer with the provided implementation.
     ///
     /// This builder method enables injection of test doubles such as
@@ -305,8 +316,8 @@ impl GenericWatcher {
         self.running.store(true, Ordering::SeqCst);
 
         info!(
-            max_concurrent = self.execution_semaphore.available_permits(),
-            provider = %self.config.provider.provider_type,
+            max_concurrent = self.settings.executions().available_permits(),
+            provider = %self.settings.config().provider.provider_type,
             "Generic watcher consuming from Kafka"
         );
 
@@ -420,7 +431,8 @@ impl GenericWatcher {
                 Ok(MessageDisposition::SkippedNoMatch)
             }
             Ok(Some(task)) => {
-                let _permit = self.execution_semaphore.acquire().await.map_err(|e| {
+                let executions = self.settings.executions();
+                let _permit = executions.acquire().await.map_err(|e| {
                     GenericWatcherError::Execution(format!(
                         "failed to acquire execution semaphore: {}",
                         e
@@ -538,9 +550,14 @@ impl GenericWatcher {
 
     /// Execute a validated plan task via the standard agent execution path.
     ///
-    /// Delegates to `crate::commands::run::run_plan_with_options` with the
-    /// task instruction as the prompt. The execution result (success or
-    /// failure) is captured into a [`GenericPlanResult`].
+    /// Delegates to `crate::commands::run::run_plan_in_working_dir` with the
+    /// task instruction as the prompt, rooted in a fresh
+    /// [`ExecutionWorkspace`] seeded from the task's `workspace` block. The
+    /// execution result (success or failure) is captured into a
+    /// [`GenericPlanResult`]; a retained workspace path is reported in
+    /// `plan_output.workspace` and in the summary. `plan_output.status` is `success`, `failure`
+    /// or `needs_input` as reported by the agent's `finish` call, or `error`
+    /// when the run itself failed.
     ///
     /// # Arguments
     ///
@@ -552,7 +569,8 @@ impl GenericWatcher {
     ///
     /// # Errors
     ///
-    /// Returns an error if the task instruction is empty after trimming.
+    /// Returns an error if the task instruction is empty after trimming or
+    /// the workspace directory cannot be created.
     async fn execute_plan(&self, task: &GenericTask) -> Result<GenericPlanResult> {
         let trimmed = task.instruction.trim();
         if trimmed.is_empty() {
@@ -565,20 +583,32 @@ impl GenericWatcher {
         info!(
             plan_name = %task.plan.name,
             bytes = trimmed.len(),
-            "Executing generic watcher plan via run_plan_with_options"
+            "Executing generic watcher plan via run_plan_in_working_dir"
         );
 
-        let config = self.config.as_ref().clone();
-        let allow_dangerous = self.config.watcher.execution.allow_dangerous;
+        let config = self.settings.config().as_ref().clone();
+        let allow_dangerous = config.watcher.execution.allow_dangerous;
+        let workspace = ExecutionWorkspace::create(&config.watcher.execution)?;
 
-        let execution_result = crate::commands::r#run::run_plan_with_options(
-            config,
-            None,
-            Some(trimmed.to_string()),
-            allow_dangerous,
-            None,
-        )
-        .await;
+        let seeded = match &task.workspace_seed {
+            Some(seed) => workspace.seed(seed).await,
+            None => Ok(()),
+        };
+        let execution_result = match seeded {
+            Ok(()) => {
+                crate::commands::r#run::run_plan_in_working_dir(
+                    config,
+                    None,
+                    Some(trimmed.to_string()),
+                    allow_dangerous,
+                    None,
+                    false,
+                    workspace.path(),
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
 
         let (success, summary) = match &execution_result {
             Ok(()) => (
@@ -591,18 +621,38 @@ impl GenericWatcher {
             ),
         };
 
+        // How the agent finished, from its `finish` call when it made one
+        let status = match &execution_result {
+            Ok(()) => "success",
+            Err(XzatomaError::TaskFailed(_)) => "failure",
+            Err(XzatomaError::TaskNeedsInput(_)) => "needs_input",
+            Err(_) => "error",
+        };
+
         let trigger_id = task
             .correlation_key
             .clone()
             .unwrap_or_else(|| Ulid::new().to_string());
 
-        let mut result = GenericPlanResult::new(trigger_id, success, summary);
-        result.plan_output = Some(json!({
+        let retained_workspace = workspace.finish(success);
+        let summary = match &retained_workspace {
+            Some(path) => format!("{} (workspace retained at {})", summary, path.display()),
+            None => summary,
+        };
+
+        let mut plan_output = json!({
             "mode": "execute",
             "plan_name": task.plan.name,
             "instruction": trimmed,
             "success": success,
-        }));
+            "status": status,
+        });
+        if let Some(path) = retained_workspace {
+            plan_output["workspace"] = json!(path.display().to_string());
+        }
+
+        let mut result = GenericPlanResult::new(trigger_id, success, summary);
+        result.plan_output = Some(plan_output);
         Ok(result)
     }
 
@@ -664,7 +714,8 @@ mod tests {
     use super::*;
     use crate::config::{
         AcpConfig, AgentConfig, CopilotConfig, GenericMatchConfig, OllamaConfig, ProviderConfig,
-        SkillsConfig, WatcherConfig, WatcherExecutionConfig, WatcherLoggingConfig,
+        SkillsConfig, StorageConfig, TelemetryConfig, WatcherConfig, WatcherExecutionConfig,
+        WatcherLoggingConfig,
     };
     use crate::mcp::config::McpConfig;
     use std::collections::HashMap;
@@ -676,6 +727,9 @@ mod tests {
                 copilot: CopilotConfig::default(),
                 ollama: OllamaConfig::default(),
                 openai: crate::config::OpenAIConfig::default(),
+                cache: crate::config::ProviderCacheConfig::default(),
+                quirks: crate::config::ProviderQuirksConfig::default(),
+                rate_limits: crate::config::ProviderRateLimitsConfig::default(),
             },
             agent: AgentConfig::default(),
             watcher: WatcherConfig {
@@ -697,11 +751,20 @@ mod tests {
                     allow_dangerous: false,
                     max_concurrent_executions: 1,
                     execution_timeout_secs: 30,
+                    ..WatcherExecutionConfig::default()
                 },
+                reload: Default::default(),
             },
             mcp: McpConfig::default(),
             acp: AcpConfig::default(),
             skills: SkillsConfig::default(),
+            storage: StorageConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            credentials: Default::default(),
+            semantic: Default::default(),
+            paths: Default::default(),
+            budget: Default::default(),
+            run: Default::default(),
         }
     }
 
@@ -1009,6 +1072,42 @@ mod tests {
         assert_eq!(recorded.len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_watcher_publishes_retained_workspace_of_failed_execution() {
+        let root = tempfile::TempDir::new().unwrap();
+        let mut config = test_config(GenericMatchConfig::default());
+        config.watcher.execution.workspace_root = Some(root.path().join("workspaces"));
+        config.watcher.execution.keep_workspace_on_failure = true;
+
+        let fake_producer = Arc::new(FakeResultProducer::new());
+        let watcher = GenericWatcher::new(config, false)
+            .unwrap()
+            .with_producer(fake_producer.clone());
+
+        // Seeding from a repository that does not exist fails the execution
+        // before any provider is contacted
+        let payload = format!(
+            "{}workspace:\n  repository: {}\n",
+            MATCHING_PLAN_YAML,
+            root.path().join("missing-repo").display()
+        );
+        let msg = TestRawMsg {
+            payload,
+            topic: "generic.input".to_string(),
+            key: None,
+        };
+        let disposition = watcher.process_event(msg).await.unwrap();
+        assert_eq!(disposition, MessageDisposition::Processed);
+
+        let published = fake_producer.published_events().await;
+        assert_eq!(published.len(), 1);
+        assert!(!published[0].success);
+        let output = published[0].plan_output.as_ref().unwrap();
+        let retained = output["workspace"].as_str().unwrap();
+        assert!(std::path::Path::new(retained).exists());
+        assert!(published[0].summary.contains(retained));
+    }
+
     #[tokio::test]
     async fn test_watcher_discards_non_plan_event() {
         // A GenericPlanResult JSON consumed back on the same topic fails plan
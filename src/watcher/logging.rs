@@ -2,30 +2,86 @@
 //!
 //! Provides JSON-formatted and human-readable logging with optional file output.
 //! Integrates with the tracing ecosystem for structured event logging.
+//! Spans are also exported over OTLP when `telemetry.otlp` is configured;
+//! see [`crate::tracing_setup`]. The log level can be changed while the
+//! watcher runs through the returned [`LogLevelHandle`].
 
 use crate::config::WatcherLoggingConfig;
 use crate::error::{Result, XzatomaError};
+use crate::tracing_setup::{OutputLayers, TracingHandle};
 use std::fs::OpenOptions;
 use std::sync::Arc;
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
+
+/// Changes the watcher log level while the watcher runs
+///
+/// `RUST_LOG` takes precedence over `watcher.logging.level`, so when it is
+/// set, level changes from the configuration are ignored.
+#[derive(Clone)]
+pub struct LogLevelHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+    from_env: bool,
+}
+
+impl LogLevelHandle {
+    /// Replaces the active log filter with `level`
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - Level or filter directive, e.g. `debug` or `xzatoma=trace`
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Config` if `level` is not a valid filter
+    pub fn set(&self, level: &str) -> Result<()> {
+        let filter = parse_level(level)?;
+        if self.from_env {
+            tracing::debug!(level, "RUST_LOG is set; ignoring watcher.logging.level");
+            return Ok(());
+        }
+        self.handle.reload(filter).map_err(|error| {
+            XzatomaError::Config(format!("Failed to change the watcher log level: {}", error))
+        })
+    }
+}
+
+/// Parses a `watcher.logging.level` value into a filter
+///
+/// # Errors
+///
+/// Returns `XzatomaError::Config` if `level` is not a valid filter
+pub fn parse_level(level: &str) -> Result<EnvFilter> {
+    EnvFilter::try_new(level).map_err(|error| {
+        XzatomaError::Config(format!(
+            "Invalid watcher logging filter '{}': {}",
+            level, error
+        ))
+    })
+}
 
 /// Initialize watcher logging based on configuration.
 ///
-/// Sets up structured logging with support for both JSON and human-readable formats,
-/// with optional file output in addition to STDOUT.
+/// Replaces the output layers of the global subscriber installed by
+/// [`crate::tracing_setup::init_tracing`] with structured logging in JSON or
+/// human-readable format, with optional file output in addition to STDOUT,
+/// and applies `watcher.logging.level` unless `RUST_LOG` is set. The span
+/// exporter installed by the CLI keeps running.
 ///
 /// # Arguments
 ///
 /// * `config` - Logging configuration
+/// * `tracing_handle` - Handle to the global subscriber
 ///
 /// # Returns
 ///
-/// Returns success or error if logging initialization fails
+/// Returns the handle that changes the log level, or an error if logging
+/// initialization fails.
 ///
 /// # Examples
 ///
 /// ```
 /// use xzatoma::config::WatcherLoggingConfig;
+/// use xzatoma::tracing_setup::cli_subscriber;
 /// use xzatoma::watcher::logging::init_watcher_logging;
 ///
 /// let config = WatcherLoggingConfig {
@@ -35,70 +91,74 @@ use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, Env
 ///     include_payload: false,
 /// };
 ///
-/// let result = init_watcher_logging(&config);
+/// let (subscriber, tracing_handle) = cli_subscriber();
+/// let _default = tracing::subscriber::set_default(subscriber);
+/// let result = init_watcher_logging(&config, &tracing_handle);
 /// assert!(result.is_ok());
 /// ```
-pub fn init_watcher_logging(config: &WatcherLoggingConfig) -> Result<()> {
-    let env_filter = EnvFilter::try_from_default_env()
-        .or_else(|_| EnvFilter::try_new(&config.level))
-        .map_err(|error| {
-            XzatomaError::Config(format!(
-                "Invalid watcher logging filter '{}': {}",
-                config.level, error
-            ))
-        })?;
-
-    let registry = tracing_subscriber::registry().with(env_filter);
-
-    if config.json_format {
-        // JSON formatting
-        let stdout_layer = fmt::layer()
-            .json()
-            .with_current_span(true)
-            .with_span_list(true);
+pub fn init_watcher_logging(
+    config: &WatcherLoggingConfig,
+    tracing_handle: &TracingHandle,
+) -> Result<LogLevelHandle> {
+    let level = LogLevelHandle {
+        handle: tracing_handle.filter(),
+        from_env: EnvFilter::try_from_default_env().is_ok(),
+    };
+    level.set(&config.level)?;
 
-        if let Some(file_path) = &config.file_path {
-            let file = OpenOptions::new()
+    let file = match &config.file_path {
+        Some(file_path) => Some(Arc::new(
+            OpenOptions::new()
                 .create(true)
                 .append(true)
-                .open(file_path)?;
+                .open(file_path)?,
+        )),
+        None => None,
+    };
 
-            let file_layer = fmt::layer()
+    let mut layers: OutputLayers = Vec::new();
+    if config.json_format {
+        // JSON formatting
+        layers.push(
+            fmt::layer()
                 .json()
                 .with_current_span(true)
                 .with_span_list(true)
-                .with_writer(Arc::new(file));
-
-            registry.with(stdout_layer).with(file_layer).init();
-        } else {
-            registry.with(stdout_layer).init();
+                .boxed(),
+        );
+        if let Some(file) = file {
+            layers.push(
+                fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true)
+                    .with_writer(file)
+                    .boxed(),
+            );
         }
     } else {
         // Human-readable formatting
-        let stdout_layer = fmt::layer()
-            .with_target(true)
-            .with_thread_ids(true)
-            .with_level(true);
-
-        if let Some(file_path) = &config.file_path {
-            let file = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(file_path)?;
-
-            let file_layer = fmt::layer()
+        layers.push(
+            fmt::layer()
                 .with_target(true)
                 .with_thread_ids(true)
                 .with_level(true)
-                .with_writer(Arc::new(file));
-
-            registry.with(stdout_layer).with(file_layer).init();
-        } else {
-            registry.with(stdout_layer).init();
+                .boxed(),
+        );
+        if let Some(file) = file {
+            layers.push(
+                fmt::layer()
+                    .with_target(true)
+                    .with_thread_ids(true)
+                    .with_level(true)
+                    .with_writer(file)
+                    .boxed(),
+            );
         }
     }
+    tracing_handle.set_output(layers)?;
 
-    Ok(())
+    Ok(level)
 }
 
 /// Create structured log fields for an event.
@@ -224,4 +284,13 @@ mod tests {
         assert_eq!(config.level, "error");
         assert!(!config.json_format);
     }
+
+    #[test]
+    fn test_parse_level_accepts_levels_and_directives() {
+        assert!(parse_level("debug").is_ok());
+        assert!(parse_level("xzatoma=trace,rdkafka=warn").is_ok());
+
+        let error = parse_level("xzatoma=loud").unwrap_err().to_string();
+        assert!(error.contains("xzatoma=loud"), "{}", error);
+    }
 }
@@ -17,7 +17,9 @@
 //!
 //! - [`generic`]: Generic Kafka watcher backend
 //! - [`logging`]: Structured logging helpers shared across all watcher backends
+//! - [`reload`]: Config hot-reload shared by both backends
 //! - [`topic_admin`]: Shared topic administration helpers for watcher startup
+//! - [`workspace`]: Isolated per-execution workspaces shared by both backends
 //! - [`xzepr`]: XZepr watcher backend (consumer, filter, plan extractor, watcher)
 //!
 //! # XZepr-Specific Types
@@ -32,7 +34,9 @@
 
 pub mod generic;
 pub mod logging;
+pub mod reload;
 pub mod topic_admin;
+pub mod workspace;
 pub mod xzepr;
 
 /// Evaluate whether a plan version satisfies a constraint string.
@@ -0,0 +1,665 @@
+//! Config hot-reload for the watcher
+//!
+//! Changing event filters should not require restarting the watcher and
+//! missing events while it is down. While `xzatoma watch` runs, a change to
+//! the config file or a `SIGHUP` reloads the configuration. Each reload
+//! re-reads and validates the whole file, then swaps in the settings that
+//! are safe to change at runtime:
+//!
+//! - `watcher.filters`
+//! - `watcher.logging.level`
+//! - `watcher.execution.allow_dangerous`
+//! - `watcher.execution.max_concurrent_executions`
+//!
+//! Every other change, including the Kafka connection settings, is logged
+//! as requiring a restart and is not applied. An invalid configuration is
+//! rejected, logged, and counted, and the active configuration stays in
+//! place.
+//!
+//! Both watcher backends read these settings through [`LiveSettings`], so a
+//! swap takes effect from the next event on. Plans already running keep the
+//! settings they started with.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+use crate::config::Config;
+use crate::error::{Result, XzatomaError};
+use crate::watcher::logging::{parse_level, LogLevelHandle};
+use crate::watcher::xzepr::filter::EventFilter;
+
+/// Settings applied by a reload without restarting the watcher
+pub const HOT_RELOADABLE_SETTINGS: [&str; 4] = [
+    "watcher.filters",
+    "watcher.logging.level",
+    "watcher.execution.allow_dangerous",
+    "watcher.execution.max_concurrent_executions",
+];
+
+/// Loads the configuration a reload applies
+type ConfigLoader = Box<dyn Fn() -> Result<Config> + Send + Sync>;
+
+/// Watcher settings that can change while the watcher runs
+///
+/// Holds the active configuration, the compiled event filter, and the
+/// semaphore that limits concurrent plan executions.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::config::Config;
+/// use xzatoma::watcher::reload::LiveSettings;
+///
+/// let settings = LiveSettings::new(Config::default()).unwrap();
+/// assert_eq!(settings.executions().available_permits(), 1);
+/// assert!(!settings.config().watcher.execution.allow_dangerous);
+/// ```
+pub struct LiveSettings {
+    config: RwLock<Arc<Config>>,
+    filter: RwLock<Arc<EventFilter>>,
+    executions: Arc<Semaphore>,
+}
+
+impl LiveSettings {
+    /// Creates the live settings for a watcher
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `watcher.filters.source_pattern` is not a valid
+    /// regular expression
+    pub fn new(config: Config) -> Result<Self> {
+        let filter = EventFilter::new(config.watcher.filters.clone())?;
+        let executions = Semaphore::new(config.watcher.execution.max_concurrent_executions);
+        Ok(Self {
+            config: RwLock::new(Arc::new(config)),
+            filter: RwLock::new(Arc::new(filter)),
+            executions: Arc::new(executions),
+        })
+    }
+
+    /// Returns the active configuration
+    pub fn config(&self) -> Arc<Config> {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Returns the active XZepr event filter
+    pub fn filter(&self) -> Arc<EventFilter> {
+        self.filter
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Returns the semaphore that limits concurrent plan executions
+    pub fn executions(&self) -> Arc<Semaphore> {
+        self.executions.clone()
+    }
+
+    /// Changes the number of execution permits from `from` to `to`
+    ///
+    /// Shrinking waits for running executions to release their permits, so
+    /// no running plan is interrupted.
+    fn resize_executions(&self, from: usize, to: usize) {
+        if to > from {
+            self.executions.add_permits(to - from);
+            return;
+        }
+        let surplus = u32::try_from(from - to).unwrap_or(u32::MAX);
+        if surplus == 0 {
+            return;
+        }
+        match self.executions.clone().try_acquire_many_owned(surplus) {
+            Ok(permits) => permits.forget(),
+            Err(_) => {
+                let executions = self.executions.clone();
+                tokio::spawn(async move {
+                    if let Ok(permits) = executions.acquire_many_owned(surplus).await {
+                        permits.forget();
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// What a successful reload changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReloadOutcome {
+    /// Hot-reloadable settings whose value changed and was applied
+    pub applied: Vec<&'static str>,
+    /// Changed settings that only take effect after a restart
+    pub restart_required: Vec<String>,
+}
+
+/// Validates reloaded configurations and swaps them into [`LiveSettings`]
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use xzatoma::config::Config;
+/// use xzatoma::watcher::reload::{ConfigReloader, LiveSettings};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let settings = Arc::new(LiveSettings::new(Config::default()).unwrap());
+/// let reloader = ConfigReloader::new(settings.clone());
+///
+/// let mut next = Config::default();
+/// next.watcher.filters.event_types = vec!["deployment.success".to_string()];
+/// let outcome = reloader.apply(next).unwrap();
+///
+/// assert_eq!(outcome.applied, ["watcher.filters"]);
+/// assert_eq!(settings.config().watcher.filters.event_types, ["deployment.success"]);
+/// # }
+/// ```
+pub struct ConfigReloader {
+    settings: Arc<LiveSettings>,
+    log_level: Option<LogLevelHandle>,
+    rejected: AtomicUsize,
+    // Serializes reloads so the permit count always matches the config
+    swap: Mutex<()>,
+}
+
+impl ConfigReloader {
+    /// Creates a reloader for `settings`
+    pub fn new(settings: Arc<LiveSettings>) -> Self {
+        Self {
+            settings,
+            log_level: None,
+            rejected: AtomicUsize::new(0),
+            swap: Mutex::new(()),
+        }
+    }
+
+    /// Applies `watcher.logging.level` changes through `handle`
+    pub fn with_log_level(mut self, handle: LogLevelHandle) -> Self {
+        self.log_level = Some(handle);
+        self
+    }
+
+    /// Returns the number of reloads rejected so far
+    pub fn rejected(&self) -> usize {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Loads the configuration from `source` and applies it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration cannot be loaded or is invalid;
+    /// the active configuration is kept.
+    pub fn reload(&self, source: &ReloadSource) -> Result<ReloadOutcome> {
+        match (source.load)() {
+            Ok(candidate) => self.apply(candidate),
+            Err(error) => Err(self.reject(error)),
+        }
+    }
+
+    /// Validates `candidate` and applies its hot-reloadable settings
+    ///
+    /// Must be called from within a Tokio runtime, which completes a
+    /// shrink of `max_concurrent_executions` once running plans finish.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `candidate` is invalid; the active configuration
+    /// is kept.
+    pub fn apply(&self, candidate: Config) -> Result<ReloadOutcome> {
+        let _swap = self.swap.lock().unwrap_or_else(|e| e.into_inner());
+        match self.swap_in(candidate) {
+            Ok(outcome) => {
+                metrics::increment_counter!("watcher_config_reloads_total", "result" => "applied");
+                log_outcome(&outcome);
+                Ok(outcome)
+            }
+            Err(error) => Err(self.reject(error)),
+        }
+    }
+
+    fn swap_in(&self, candidate: Config) -> Result<ReloadOutcome> {
+        candidate.validate()?;
+        let filter = EventFilter::new(candidate.watcher.filters.clone())?;
+        parse_level(&candidate.watcher.logging.level)?;
+
+        let current = self.settings.config();
+        let mut next = current.as_ref().clone();
+        let mut applied = Vec::new();
+
+        if differs(&next.watcher.filters, &candidate.watcher.filters) {
+            next.watcher.filters = candidate.watcher.filters.clone();
+            applied.push(HOT_RELOADABLE_SETTINGS[0]);
+        }
+        let level_changed = next.watcher.logging.level != candidate.watcher.logging.level;
+        if level_changed {
+            next.watcher.logging.level = candidate.watcher.logging.level.clone();
+            applied.push(HOT_RELOADABLE_SETTINGS[1]);
+        }
+        let (from, to) = (&mut next.watcher.execution, &candidate.watcher.execution);
+        if from.allow_dangerous != to.allow_dangerous {
+            from.allow_dangerous = to.allow_dangerous;
+            applied.push(HOT_RELOADABLE_SETTINGS[2]);
+        }
+        let permits = (from.max_concurrent_executions, to.max_concurrent_executions);
+        if permits.0 != permits.1 {
+            from.max_concurrent_executions = permits.1;
+            applied.push(HOT_RELOADABLE_SETTINGS[3]);
+        }
+        let restart_required = changed_sections(&next, &candidate);
+
+        // The level is the only swap that can fail, so it goes first
+        if level_changed {
+            if let Some(handle) = &self.log_level {
+                handle.set(&next.watcher.logging.level)?;
+            }
+        }
+        *self
+            .settings
+            .filter
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Arc::new(filter);
+        *self
+            .settings
+            .config
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Arc::new(next);
+        self.settings.resize_executions(permits.0, permits.1);
+
+        Ok(ReloadOutcome {
+            applied,
+            restart_required,
+        })
+    }
+
+    fn reject(&self, error: XzatomaError) -> XzatomaError {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+        metrics::increment_counter!("watcher_config_reloads_total", "result" => "rejected");
+        error!(
+            error = %error,
+            "Rejected reloaded configuration; keeping the active configuration"
+        );
+        error
+    }
+}
+
+fn log_outcome(outcome: &ReloadOutcome) {
+    if outcome
+        .restart_required
+        .iter()
+        .any(|section| section == "watcher.kafka")
+    {
+        warn!("Kafka connection settings changed; restart the watcher to apply them");
+    }
+    if !outcome.restart_required.is_empty() {
+        warn!(
+            settings = ?outcome.restart_required,
+            "Configuration changes that require a restart were not applied"
+        );
+    }
+    if outcome.applied.is_empty() {
+        info!("Configuration reloaded; no hot-reloadable settings changed");
+    } else {
+        info!(settings = ?outcome.applied, "Configuration reloaded");
+    }
+}
+
+fn differs<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() != serde_json::to_value(b).ok()
+}
+
+/// Names the top-level sections, and the `watcher` subsections, that differ
+fn changed_sections(active: &Config, candidate: &Config) -> Vec<String> {
+    let (Ok(Value::Object(active)), Ok(Value::Object(candidate))) = (
+        serde_json::to_value(active),
+        serde_json::to_value(candidate),
+    ) else {
+        return Vec::new();
+    };
+    let mut changed = Vec::new();
+    for (key, value) in &candidate {
+        let previous = active.get(key);
+        if previous == Some(value) {
+            continue;
+        }
+        match (key.as_str(), previous, value) {
+            ("watcher", Some(Value::Object(before)), Value::Object(after)) => {
+                changed.extend(
+                    after
+                        .iter()
+                        .filter(|(name, value)| before.get(*name) != Some(value))
+                        .map(|(name, _)| format!("watcher.{}", name)),
+                );
+            }
+            _ => changed.push(key.clone()),
+        }
+    }
+    changed.sort();
+    changed
+}
+
+/// Where a reload reads the configuration from
+pub struct ReloadSource {
+    paths: Vec<PathBuf>,
+    load: ConfigLoader,
+}
+
+impl ReloadSource {
+    /// Creates a source that watches `path` and loads with `load`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Config file watched for changes
+    /// * `load` - Reads and merges the configuration, as at startup
+    pub fn new(
+        path: impl Into<PathBuf>,
+        load: impl Fn() -> Result<Config> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            paths: vec![path.into()],
+            load: Box::new(load),
+        }
+    }
+
+    /// Also reloads when `path` changes
+    pub fn also_watch(mut self, path: impl Into<PathBuf>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Applies `overrides` to every loaded configuration
+    ///
+    /// Used to re-apply command-line flags on top of the reloaded file.
+    pub fn with_overrides(
+        self,
+        overrides: impl Fn(&mut Config) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        let load = self.load;
+        Self {
+            paths: self.paths,
+            load: Box::new(move || {
+                let mut config = load()?;
+                overrides(&mut config)?;
+                Ok(config)
+            }),
+        }
+    }
+
+    /// Returns the files watched for changes
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+}
+
+/// Reloads the configuration on `SIGHUP` and, when `watch_files` is set,
+/// whenever one of the source files changes
+///
+/// Changes within `debounce` of each other cause a single reload. The
+/// returned task runs until aborted.
+///
+/// # Arguments
+///
+/// * `reloader` - Applies the reloaded configuration
+/// * `source` - Where the configuration is read from
+/// * `watch_files` - Whether to watch the source files for changes
+/// * `debounce` - Delay between a change and the reload
+pub fn spawn(
+    reloader: ConfigReloader,
+    source: ReloadSource,
+    watch_files: bool,
+    debounce: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let (sender, mut triggers) = mpsc::unbounded_channel::<&'static str>();
+        // Dropping the file watcher stops it, so it lives as long as the task
+        let _file_watcher = if watch_files {
+            watch_paths(source.paths(), sender.clone())
+        } else {
+            None
+        };
+        forward_hangups(sender);
+
+        while let Some(trigger) = triggers.recv().await {
+            // Editors often save in several steps; reload once they are done
+            tokio::time::sleep(debounce).await;
+            while triggers.try_recv().is_ok() {}
+
+            info!(trigger, "Reloading watcher configuration");
+            // Failures are logged and counted by the reloader
+            let _ = reloader.reload(&source);
+        }
+    })
+}
+
+/// Watches the directories holding `paths`, sending a trigger when one of
+/// the files changes
+///
+/// Directories are watched rather than the files, so that editors that
+/// save by renaming a new file over the old one are noticed.
+fn watch_paths(
+    paths: &[PathBuf],
+    sender: mpsc::UnboundedSender<&'static str>,
+) -> Option<RecommendedWatcher> {
+    let names: Vec<_> = paths
+        .iter()
+        .filter_map(|path| path.file_name().map(|name| name.to_os_string()))
+        .collect();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                let relevant = event.paths.iter().any(|path| {
+                    path.file_name()
+                        .is_some_and(|name| names.iter().any(|watched| watched.as_os_str() == name))
+                });
+                if relevant {
+                    let _ = sender.send("config file changed");
+                }
+            }
+            Ok(_) => {}
+            Err(error) => warn!(error = %error, "Config file watch error"),
+        })
+        .map_err(|error| warn!(error = %error, "Failed to watch the config file for changes"))
+        .ok()?;
+
+    let mut directories: Vec<&Path> = paths
+        .iter()
+        .map(|path| match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        })
+        .collect();
+    directories.sort();
+    directories.dedup();
+    for directory in directories {
+        match watcher.watch(directory, RecursiveMode::NonRecursive) {
+            Ok(()) => debug!(directory = %directory.display(), "Watching for config changes"),
+            Err(error) => warn!(
+                directory = %directory.display(),
+                error = %error,
+                "Failed to watch for config changes"
+            ),
+        }
+    }
+    Some(watcher)
+}
+
+/// Sends a trigger for every `SIGHUP`
+#[cfg(unix)]
+fn forward_hangups(sender: mpsc::UnboundedSender<&'static str>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(error) => {
+            warn!(error = %error, "Failed to listen for SIGHUP");
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            if sender.send("SIGHUP").is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// `SIGHUP` does not exist on this platform
+#[cfg(not(unix))]
+fn forward_hangups(_sender: mpsc::UnboundedSender<&'static str>) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KafkaWatcherConfig;
+    use crate::watcher::xzepr::consumer::CloudEventMessage;
+
+    fn kafka(brokers: &str) -> KafkaWatcherConfig {
+        KafkaWatcherConfig {
+            brokers: brokers.to_string(),
+            topic: "events".to_string(),
+            output_topic: None,
+            group_id: "xzatoma-watcher".to_string(),
+            auto_create_topics: false,
+            security: None,
+            num_partitions: 1,
+            replication_factor: 1,
+        }
+    }
+
+    fn snapshot(event_types: &[&str]) -> Config {
+        let mut config = Config::default();
+        config.watcher.kafka = Some(kafka("localhost:9092"));
+        config.watcher.filters.event_types = event_types.iter().map(|t| t.to_string()).collect();
+        config.watcher.filters.success_only = false;
+        config
+    }
+
+    fn event(event_type: &str) -> CloudEventMessage {
+        CloudEventMessage {
+            success: true,
+            id: "01J0000000000000000000TEST".to_string(),
+            specversion: "1.0.1".to_string(),
+            event_type: event_type.to_string(),
+            source: "xzepr".to_string(),
+            api_version: "v1".to_string(),
+            name: "deploy".to_string(),
+            version: "1.0.0".to_string(),
+            release: "1.0.0".to_string(),
+            platform_id: "k8s".to_string(),
+            package: "app".to_string(),
+            data: Default::default(),
+            extensions: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reload_swaps_filter_and_execution_settings() {
+        let settings = Arc::new(LiveSettings::new(snapshot(&["deploy.started"])).unwrap());
+        let reloader = ConfigReloader::new(settings.clone());
+        assert!(settings.filter().should_process(&event("deploy.started")));
+
+        let mut next = snapshot(&["deploy.finished"]);
+        next.watcher.execution.allow_dangerous = true;
+        next.watcher.execution.max_concurrent_executions = 3;
+        let outcome = reloader.apply(next).unwrap();
+
+        assert_eq!(
+            outcome.applied,
+            [
+                "watcher.filters",
+                "watcher.execution.allow_dangerous",
+                "watcher.execution.max_concurrent_executions",
+            ]
+        );
+        assert!(outcome.restart_required.is_empty());
+        assert!(!settings.filter().should_process(&event("deploy.started")));
+        assert!(settings.filter().should_process(&event("deploy.finished")));
+        assert!(settings.config().watcher.execution.allow_dangerous);
+        assert_eq!(settings.executions().available_permits(), 3);
+
+        let mut fewer = snapshot(&["deploy.finished"]);
+        fewer.watcher.execution.allow_dangerous = true;
+        fewer.watcher.execution.max_concurrent_executions = 2;
+        reloader.apply(fewer).unwrap();
+        assert_eq!(settings.executions().available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_config_is_rejected_and_old_one_kept() {
+        let settings = Arc::new(LiveSettings::new(snapshot(&["deploy.started"])).unwrap());
+        let reloader = ConfigReloader::new(settings.clone());
+
+        let mut bad_regex = snapshot(&["deploy.finished"]);
+        bad_regex.watcher.filters.source_pattern = Some("(unclosed".to_string());
+        let mut zero_permits = snapshot(&["deploy.finished"]);
+        zero_permits.watcher.execution.max_concurrent_executions = 0;
+        let mut bad_level = snapshot(&["deploy.finished"]);
+        bad_level.watcher.logging.level = "xzatoma=loud".to_string();
+
+        for candidate in [bad_regex, zero_permits, bad_level] {
+            assert!(reloader.apply(candidate).is_err());
+        }
+        let source = ReloadSource::new("config.yaml", || {
+            Err(XzatomaError::Config("parse error".to_string()))
+        });
+        assert!(reloader.reload(&source).is_err());
+
+        assert_eq!(reloader.rejected(), 4);
+        assert!(settings.filter().should_process(&event("deploy.started")));
+        assert_eq!(
+            settings.config().watcher.filters.event_types,
+            ["deploy.started"]
+        );
+        assert_eq!(settings.executions().available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_kafka_changes_are_not_applied() {
+        let settings = Arc::new(LiveSettings::new(snapshot(&[])).unwrap());
+        let reloader = ConfigReloader::new(settings.clone());
+
+        let mut next = snapshot(&["deploy.finished"]);
+        next.watcher.kafka = Some(kafka("kafka-2:9092"));
+        next.agent.max_turns += 1;
+        let outcome = reloader.apply(next).unwrap();
+
+        assert_eq!(outcome.applied, ["watcher.filters"]);
+        assert_eq!(outcome.restart_required, ["agent", "watcher.kafka"]);
+        let active = settings.config();
+        assert_eq!(
+            active.watcher.kafka.as_ref().unwrap().brokers,
+            "localhost:9092"
+        );
+        assert_eq!(active.agent.max_turns, Config::default().agent.max_turns);
+        assert_eq!(active.watcher.filters.event_types, ["deploy.finished"]);
+    }
+
+    #[tokio::test]
+    async fn test_source_applies_overrides_to_loaded_config() {
+        let source = ReloadSource::new("config/config.yaml", || Ok(snapshot(&[])))
+            .also_watch("filters.yaml")
+            .with_overrides(|config| {
+                config.watcher.filters.event_types = vec!["from.cli".to_string()];
+                Ok(())
+            });
+        let settings = Arc::new(LiveSettings::new(snapshot(&[])).unwrap());
+        let reloader = ConfigReloader::new(settings.clone());
+
+        reloader.reload(&source).unwrap();
+
+        assert_eq!(source.paths().len(), 2);
+        assert_eq!(settings.config().watcher.filters.event_types, ["from.cli"]);
+    }
+}
@@ -0,0 +1,372 @@
+//! Isolated per-execution workspaces for watcher-triggered plans.
+//!
+//! Watcher executions used to run their tools in the process working
+//! directory, so concurrent plans (`max_concurrent_executions > 1`) could
+//! overwrite each other's files and git state. Each execution now gets its
+//! own directory under `watcher.execution.workspace_root`. The directory is
+//! optionally seeded by cloning a git repository named in the event payload,
+//! and is removed afterwards unless the execution failed and
+//! `keep_workspace_on_failure` is set.
+//!
+//! # Event payload
+//!
+//! Seeding is requested with a top-level `workspace` object in the plan
+//! payload (generic watcher) or the event payload (XZepr watcher):
+//!
+//! ```yaml
+//! workspace:
+//!   repository: https://github.com/example/service.git
+//!   ref: release-1.2
+//! ```
+//!
+//! # Examples
+//!
+//! ```
+//! use xzatoma::config::WatcherExecutionConfig;
+//! use xzatoma::watcher::workspace::ExecutionWorkspace;
+//!
+//! let root = tempfile::tempdir().unwrap();
+//! let config = WatcherExecutionConfig {
+//!     workspace_root: Some(root.path().to_path_buf()),
+//!     ..WatcherExecutionConfig::default()
+//! };
+//!
+//! let workspace = ExecutionWorkspace::create(&config).unwrap();
+//! assert!(workspace.path().starts_with(root.path()));
+//! assert!(workspace.finish(true).is_none());
+//! ```
+
+use crate::config::WatcherExecutionConfig;
+use crate::error::{Result, XzatomaError};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+use ulid::Ulid;
+
+/// Directory name used under the system temp directory when no
+/// `workspace_root` is configured.
+const DEFAULT_WORKSPACE_DIR: &str = "xzatoma-watcher";
+
+/// Git repository and ref used to seed an execution workspace.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::watcher::workspace::GitSeed;
+///
+/// let payload = serde_json::json!({
+///     "workspace": { "repository": "https://example.com/repo.git", "ref": "main" }
+/// });
+/// let seed = GitSeed::from_json(&payload).unwrap();
+/// assert_eq!(seed.repository, "https://example.com/repo.git");
+/// assert_eq!(seed.git_ref.as_deref(), Some("main"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitSeed {
+    /// Repository URL or path passed to `git clone`
+    pub repository: String,
+    /// Optional branch, tag, or commit checked out after cloning
+    pub git_ref: Option<String>,
+}
+
+impl GitSeed {
+    /// Reads the `workspace.repository` and `workspace.ref` fields of a payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Parsed event or plan payload
+    ///
+    /// # Returns
+    ///
+    /// The seed, or `None` when the payload names no repository
+    pub fn from_json(value: &serde_json::Value) -> Option<Self> {
+        let workspace = value.get("workspace")?;
+        let repository = workspace
+            .get("repository")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())?;
+        let git_ref = workspace
+            .get("ref")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string);
+
+        Some(Self {
+            repository: repository.to_string(),
+            git_ref,
+        })
+    }
+
+    /// Reads the seed from a raw YAML or JSON plan payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - Raw payload string
+    ///
+    /// # Returns
+    ///
+    /// The seed, or `None` when the payload cannot be parsed or names no
+    /// repository
+    pub fn from_payload(payload: &str) -> Option<Self> {
+        let value: serde_json::Value = serde_yaml::from_str(payload).ok()?;
+        Self::from_json(&value)
+    }
+}
+
+/// A directory owned by one watcher-triggered execution.
+#[derive(Debug)]
+pub struct ExecutionWorkspace {
+    path: PathBuf,
+    keep_on_failure: bool,
+}
+
+impl ExecutionWorkspace {
+    /// Creates a fresh, empty workspace directory.
+    ///
+    /// The directory is named after a new ULID under the configured
+    /// `workspace_root`, or under `xzatoma-watcher` in the system temp
+    /// directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Watcher execution configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created.
+    pub fn create(config: &WatcherExecutionConfig) -> Result<Self> {
+        let root = config
+            .workspace_root
+            .clone()
+            .unwrap_or_else(|| std::env::temp_dir().join(DEFAULT_WORKSPACE_DIR));
+        std::fs::create_dir_all(&root)?;
+
+        let path = root.join(Ulid::new().to_string().to_lowercase());
+        std::fs::create_dir(&path)?;
+        debug!(workspace = %path.display(), "Created execution workspace");
+
+        Ok(Self {
+            path,
+            keep_on_failure: config.keep_workspace_on_failure,
+        })
+    }
+
+    /// Returns the workspace directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Clones the seed repository into the workspace and checks out its ref.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - Repository and optional ref to check out
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Watcher` if the ref is malformed or a git
+    /// command fails.
+    pub async fn seed(&self, seed: &GitSeed) -> Result<()> {
+        if let Some(git_ref) = seed.git_ref.as_deref().filter(|r| r.starts_with('-')) {
+            return Err(XzatomaError::Watcher(format!(
+                "Invalid workspace ref: {}",
+                git_ref
+            )));
+        }
+
+        info!(
+            workspace = %self.path.display(),
+            repository = %seed.repository,
+            git_ref = ?seed.git_ref,
+            "Seeding execution workspace"
+        );
+
+        self.git(&["clone", "--quiet", "--", &seed.repository, "."])
+            .await?;
+
+        if let Some(git_ref) = &seed.git_ref {
+            self.git(&["checkout", "--quiet", git_ref]).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Ends the execution and removes the workspace unless it is retained.
+    ///
+    /// The workspace is retained only when the execution failed and
+    /// `keep_workspace_on_failure` is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `success` - Whether the execution succeeded
+    ///
+    /// # Returns
+    ///
+    /// The workspace path when retained, otherwise `None`
+    pub fn finish(self, success: bool) -> Option<PathBuf> {
+        if !success && self.keep_on_failure {
+            info!(
+                workspace = %self.path.display(),
+                "Keeping workspace of failed execution"
+            );
+            return Some(self.path);
+        }
+
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            warn!(
+                workspace = %self.path.display(),
+                error = %e,
+                "Failed to remove execution workspace"
+            );
+        }
+        None
+    }
+
+    /// Runs a git command inside the workspace.
+    ///
+    /// Credential prompts are disabled, so a private repository fails the
+    /// seed instead of blocking the execution on a terminal no one watches.
+    async fn git(&self, args: &[&str]) -> Result<()> {
+        let output = Command::new("git")
+            .args(["-c", "protocol.ext.allow=never"])
+            .args(args)
+            .current_dir(&self.path)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .await
+            .map_err(|e| XzatomaError::Watcher(format!("Failed to run git: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(XzatomaError::Watcher(format!(
+                "git {} failed: {}",
+                args.first().copied().unwrap_or_default(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::build_agent_environment;
+    use crate::config::Config;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn execution_config(root: &Path, keep_on_failure: bool) -> WatcherExecutionConfig {
+        WatcherExecutionConfig {
+            workspace_root: Some(root.to_path_buf()),
+            keep_workspace_on_failure: keep_on_failure,
+            ..WatcherExecutionConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_git_seed_from_payload_reads_workspace_block() {
+        let payload = "name: deploy\nworkspace:\n  repository: https://example.com/r.git\n  ref: v1\nsteps:\n  - name: s\n    action: a\n";
+        let seed = GitSeed::from_payload(payload).unwrap();
+        assert_eq!(seed.repository, "https://example.com/r.git");
+        assert_eq!(seed.git_ref.as_deref(), Some("v1"));
+    }
+
+    #[test]
+    fn test_git_seed_requires_repository() {
+        assert!(GitSeed::from_json(&json!({"workspace": {"ref": "main"}})).is_none());
+        assert!(GitSeed::from_json(&json!({"workspace": {"repository": "  "}})).is_none());
+        assert!(GitSeed::from_payload("name: plan\nsteps: []\n").is_none());
+    }
+
+    #[test]
+    fn test_workspaces_are_unique_and_removed_on_success() {
+        let root = TempDir::new().unwrap();
+        let config = execution_config(root.path(), true);
+
+        let first = ExecutionWorkspace::create(&config).unwrap();
+        let second = ExecutionWorkspace::create(&config).unwrap();
+        assert_ne!(first.path(), second.path());
+
+        let first_path = first.path().to_path_buf();
+        assert!(first.finish(true).is_none());
+        assert!(!first_path.exists());
+        second.finish(true);
+    }
+
+    #[test]
+    fn test_failed_workspace_kept_only_when_configured() {
+        let root = TempDir::new().unwrap();
+
+        let kept = ExecutionWorkspace::create(&execution_config(root.path(), true)).unwrap();
+        let kept_path = kept.finish(false).unwrap();
+        assert!(kept_path.exists());
+
+        let removed = ExecutionWorkspace::create(&execution_config(root.path(), false)).unwrap();
+        let removed_path = removed.path().to_path_buf();
+        assert!(removed.finish(false).is_none());
+        assert!(!removed_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_seed_rejects_option_like_ref() {
+        let root = TempDir::new().unwrap();
+        let workspace = ExecutionWorkspace::create(&execution_config(root.path(), false)).unwrap();
+        let seed = GitSeed {
+            repository: "https://example.com/repo.git".to_string(),
+            git_ref: Some("--orphan".to_string()),
+        };
+
+        let err = workspace.seed(&seed).await.unwrap_err();
+        assert!(err.to_string().contains("Invalid workspace ref"));
+        assert!(std::fs::read_dir(workspace.path())
+            .unwrap()
+            .next()
+            .is_none());
+        workspace.finish(true);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_executions_write_to_separate_workspaces() {
+        let root = TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.watcher.execution = execution_config(root.path(), false);
+        config.agent.chat.default_mode = "write".to_string();
+        config.agent.tools.audit_log_enabled = false;
+        config.skills.enabled = false;
+
+        async fn fake_execution(config: Config, content: &str) -> ExecutionWorkspace {
+            let workspace = ExecutionWorkspace::create(&config.watcher.execution).unwrap();
+            let env = build_agent_environment(&config, workspace.path(), true)
+                .await
+                .unwrap();
+            let write_file = env.tool_registry.get("write_file").unwrap();
+            let result = write_file
+                .execute(json!({"path": "result.txt", "content": content}))
+                .await
+                .unwrap();
+            assert!(result.success, "{:?}", result.error);
+            workspace
+        }
+
+        let (first, second) = tokio::join!(
+            fake_execution(config.clone(), "first"),
+            fake_execution(config.clone(), "second"),
+        );
+
+        assert_ne!(first.path(), second.path());
+        assert_eq!(
+            std::fs::read_to_string(first.path().join("result.txt")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            std::fs::read_to_string(second.path().join("result.txt")).unwrap(),
+            "second"
+        );
+        assert!(!std::env::current_dir().unwrap().join("result.txt").exists());
+
+        first.finish(true);
+        second.finish(true);
+    }
+}
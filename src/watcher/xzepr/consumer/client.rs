@@ -3,6 +3,12 @@
 //! This module provides an HTTP client for interacting with the XZepr API,
 //! including event receiver management and event creation.
 //!
+//! Work lifecycle posts (`post_work_started`, `post_work_completed`,
+//! `post_work_failed`) are retried with exponential backoff on connection
+//! errors, timeouts, and 5xx responses. Each post carries an
+//! `Idempotency-Key` header derived from the receiver ID, work ID, and
+//! lifecycle phase, so the XZepr API can drop duplicates created by retries.
+//!
 //! # Example
 //!
 //! ```rust,no_run
@@ -13,7 +19,7 @@
 //!     let config = XzeprClientConfig {
 //!         base_url: "http://localhost:8042".to_string(),
 //!         token: "your-jwt-token".to_string(),
-//!         timeout_secs: 30,
+//!         ..XzeprClientConfig::default()
 //!     };
 //!     let client = XzeprClient::new(config)?;
 //!
@@ -33,8 +39,12 @@
 use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
 use thiserror::Error;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
+
+use crate::network_policy::{NetworkCapability, NetworkPolicy};
 
 /// Errors that can occur during client operations.
 #[derive(Error, Debug)]
@@ -63,6 +73,10 @@ pub enum ClientError {
     /// Serialization error.
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
+
+    /// Network access is disabled by offline mode.
+    #[error("Offline mode is enabled: {0} cannot be used")]
+    NetworkDisabled(String),
 }
 
 /// Request to create an event receiver.
@@ -162,6 +176,9 @@ pub struct CreateEventResponse {
     pub data: String,
 }
 
+/// Header carrying the idempotency key of a work lifecycle post.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
 /// XZepr API client configuration.
 #[derive(Debug, Clone)]
 pub struct XzeprClientConfig {
@@ -171,6 +188,28 @@ pub struct XzeprClientConfig {
     pub token: String,
     /// Request timeout in seconds.
     pub timeout_secs: u64,
+    /// Connection timeout in seconds.
+    pub connect_timeout_secs: u64,
+    /// Retries for work lifecycle posts after the first attempt.
+    pub max_retries: u32,
+    /// Delay before the first retry in milliseconds; doubles on each retry.
+    pub retry_backoff_ms: u64,
+    /// Upper bound for the retry delay in milliseconds.
+    pub max_retry_backoff_ms: u64,
+}
+
+impl Default for XzeprClientConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:8042".to_string(),
+            token: String::new(),
+            timeout_secs: 30,
+            connect_timeout_secs: 10,
+            max_retries: 3,
+            retry_backoff_ms: 500,
+            max_retry_backoff_ms: 10_000,
+        }
+    }
 }
 
 impl XzeprClientConfig {
@@ -180,6 +219,8 @@ impl XzeprClientConfig {
     ///
     /// * `XZEPR_API_URL` - Base URL (default: http://localhost:8042)
     /// * `XZEPR_API_TOKEN` - JWT token (required)
+    /// * `XZEPR_API_TIMEOUT_SECS` - Request timeout in seconds (default: 30)
+    /// * `XZEPR_API_MAX_RETRIES` - Retries for lifecycle posts (default: 3)
     ///
     /// # Errors
     ///
@@ -191,10 +232,22 @@ impl XzeprClientConfig {
         let token = std::env::var("XZEPR_API_TOKEN")
             .map_err(|_| ClientError::Authentication("XZEPR_API_TOKEN not set".to_string()))?;
 
+        let defaults = Self::default();
+        let timeout_secs = std::env::var("XZEPR_API_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(defaults.timeout_secs);
+        let max_retries = std::env::var("XZEPR_API_MAX_RETRIES")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(defaults.max_retries);
+
         Ok(Self {
             base_url,
             token,
-            timeout_secs: 30,
+            timeout_secs,
+            max_retries,
+            ..defaults
         })
     }
 }
@@ -208,6 +261,7 @@ impl XzeprClientConfig {
 pub struct XzeprClient {
     client: Client,
     config: XzeprClientConfig,
+    network_policy: NetworkPolicy,
 }
 
 impl XzeprClient {
@@ -222,10 +276,28 @@ impl XzeprClient {
     /// Returns `ClientError::Http` if the HTTP client cannot be created.
     pub fn new(config: XzeprClientConfig) -> Result<Self, ClientError> {
         let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(config.timeout_secs))
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
             .build()?;
 
-        Ok(Self { client, config })
+        Ok(Self {
+            client,
+            config,
+            network_policy: NetworkPolicy::default(),
+        })
+    }
+
+    /// Sets the network policy consulted before every API call.
+    ///
+    /// In offline mode every request fails with
+    /// `ClientError::NetworkDisabled` before any connection is opened.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Network policy to enforce
+    pub fn with_network_policy(mut self, policy: NetworkPolicy) -> Self {
+        self.network_policy = policy;
+        self
     }
 
     /// Creates a client from environment variables.
@@ -239,12 +311,26 @@ impl XzeprClient {
     }
 
     /// Builds a request with authentication headers.
-    fn build_request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+    ///
+    /// # Errors
+    ///
+    /// Returns `ClientError::NetworkDisabled` in offline mode.
+    fn build_request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+    ) -> Result<reqwest::RequestBuilder, ClientError> {
+        if !self.network_policy.allows(NetworkCapability::XzeprClient) {
+            return Err(ClientError::NetworkDisabled(
+                NetworkCapability::XzeprClient.to_string(),
+            ));
+        }
         let url = format!("{}{}", self.config.base_url, path);
-        self.client
+        Ok(self
+            .client
             .request(method, &url)
             .header("Authorization", format!("Bearer {}", self.config.token))
-            .header("Content-Type", "application/json")
+            .header("Content-Type", "application/json"))
     }
 
     /// Creates a new event receiver.
@@ -265,7 +351,7 @@ impl XzeprClient {
         request: CreateEventReceiverRequest,
     ) -> Result<String, ClientError> {
         let response = self
-            .build_request(reqwest::Method::POST, "/api/v1/receivers")
+            .build_request(reqwest::Method::POST, "/api/v1/receivers")?
             .json(&request)
             .send()
             .await?;
@@ -317,7 +403,7 @@ impl XzeprClient {
         }
 
         let response = self
-            .build_request(reqwest::Method::GET, "/api/v1/receivers")
+            .build_request(reqwest::Method::GET, "/api/v1/receivers")?
             .query(&query)
             .send()
             .await?;
@@ -346,7 +432,7 @@ impl XzeprClient {
     /// Returns `ClientError::NotFound` if the receiver doesn't exist.
     pub async fn get_event_receiver(&self, id: &str) -> Result<EventReceiverResponse, ClientError> {
         let response = self
-            .build_request(reqwest::Method::GET, &format!("/api/v1/receivers/{}", id))
+            .build_request(reqwest::Method::GET, &format!("/api/v1/receivers/{}", id))?
             .send()
             .await?;
 
@@ -431,11 +517,64 @@ impl XzeprClient {
     ///
     /// The created event ID.
     pub async fn create_event(&self, request: CreateEventRequest) -> Result<String, ClientError> {
-        let response = self
-            .build_request(reqwest::Method::POST, "/api/v1/events")
-            .json(&request)
-            .send()
-            .await?;
+        self.send_create_event(&request, None).await
+    }
+
+    /// Creates an event, retrying transient failures with the same idempotency key.
+    ///
+    /// Connection errors, timeouts, and 5xx responses are retried up to
+    /// `max_retries` times with exponential backoff. 4xx responses are
+    /// returned immediately.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - Event creation request
+    /// * `idempotency_key` - Value sent in the `Idempotency-Key` header
+    ///
+    /// # Returns
+    ///
+    /// The created event ID.
+    pub async fn create_event_idempotent(
+        &self,
+        request: CreateEventRequest,
+        idempotency_key: &str,
+    ) -> Result<String, ClientError> {
+        let mut attempt = 0;
+        loop {
+            match self
+                .send_create_event(&request, Some(idempotency_key))
+                .await
+            {
+                Err(e) if attempt < self.config.max_retries && is_retryable(&e) => {
+                    let delay = retry_delay(&self.config, attempt);
+                    attempt += 1;
+                    warn!(
+                        event_name = %request.name,
+                        attempt,
+                        max_retries = self.config.max_retries,
+                        delay_ms = delay.as_millis() as u64,
+                        "Retrying event creation: {}", e
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends a single create-event request.
+    async fn send_create_event(
+        &self,
+        request: &CreateEventRequest,
+        idempotency_key: Option<&str>,
+    ) -> Result<String, ClientError> {
+        let mut builder = self
+            .build_request(reqwest::Method::POST, "/api/v1/events")?
+            .json(request);
+        if let Some(key) = idempotency_key {
+            builder = builder.header(IDEMPOTENCY_KEY_HEADER, key);
+        }
+        let response = builder.send().await?;
 
         let status = response.status();
         if status.is_success() {
@@ -531,7 +670,8 @@ impl XzeprClient {
             event_receiver_id: event.receiver_id.to_string(),
         };
 
-        self.create_event(request).await
+        let key = idempotency_key(event.receiver_id, event.work_id, "started");
+        self.create_event_idempotent(request, &key).await
     }
 
     /// Posts a work completed event.
@@ -575,7 +715,8 @@ impl XzeprClient {
             event_receiver_id: event.receiver_id.to_string(),
         };
 
-        self.create_event(request).await
+        let key = idempotency_key(event.receiver_id, event.work_id, status_suffix);
+        self.create_event_idempotent(request, &key).await
     }
 
     /// Posts a work failed event (convenience method).
@@ -618,6 +759,50 @@ impl XzeprClient {
     }
 }
 
+/// Derives the idempotency key of a work lifecycle post.
+///
+/// The key is the hex SHA-256 digest of the receiver ID, work ID, and
+/// lifecycle phase, so every retry of the same post sends the same key.
+///
+/// # Examples
+///
+/// ```
+/// use xzatoma::xzepr::consumer::client::idempotency_key;
+///
+/// let started = idempotency_key("receiver-1", "msg-1", "started");
+/// assert_eq!(started, idempotency_key("receiver-1", "msg-1", "started"));
+/// assert_ne!(started, idempotency_key("receiver-1", "msg-1", "completed"));
+/// ```
+pub fn idempotency_key(receiver_id: &str, work_id: &str, phase: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [receiver_id, work_id, phase] {
+        hasher.update(part.as_bytes());
+        hasher.update([0u8]);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Returns `true` for failures worth retrying: connection errors, timeouts,
+/// and 5xx responses.
+fn is_retryable(error: &ClientError) -> bool {
+    match error {
+        ClientError::Http(e) => e.is_connect() || e.is_timeout(),
+        ClientError::Api { status, .. } => *status >= 500,
+        _ => false,
+    }
+}
+
+/// Returns the delay before retry number `attempt` (zero-based).
+fn retry_delay(config: &XzeprClientConfig, attempt: u32) -> Duration {
+    let factor = 1u64.checked_shl(attempt.min(32)).unwrap_or(u64::MAX);
+    let delay = config.retry_backoff_ms.saturating_mul(factor);
+    Duration::from_millis(delay.min(config.max_retry_backoff_ms))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -747,12 +932,155 @@ mod tests {
     #[test]
     fn test_client_new() {
         let config = XzeprClientConfig {
-            base_url: "http://localhost:8042".to_string(),
             token: "test-token".to_string(),
-            timeout_secs: 30,
+            ..XzeprClientConfig::default()
         };
 
         let result = XzeprClient::new(config);
         assert!(result.is_ok());
     }
+    #[tokio::test]
+    async fn test_offline_client_refuses_requests() {
+        let config = XzeprClientConfig {
+            token: "test-token".to_string(),
+            ..XzeprClientConfig::default()
+        };
+        let client = XzeprClient::new(config)
+            .unwrap()
+            .with_network_policy(NetworkPolicy::offline());
+
+        let result = client.get_event_receiver("receiver-1").await;
+        assert!(matches!(result, Err(ClientError::NetworkDisabled(_))));
+    }
+
+    fn test_client(base_url: String) -> XzeprClient {
+        XzeprClient::new(XzeprClientConfig {
+            base_url,
+            token: "test-token".to_string(),
+            max_retries: 2,
+            retry_backoff_ms: 1,
+            max_retry_backoff_ms: 5,
+            ..XzeprClientConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn work_event() -> WorkEvent<'static> {
+        WorkEvent {
+            receiver_id: "receiver-1",
+            work_id: "msg-1",
+            work_name: "deploy",
+            version: "1.0.0",
+            platform_id: "kubernetes",
+            package: "svc",
+        }
+    }
+
+    #[test]
+    fn test_retry_delay_doubles_and_caps() {
+        let config = XzeprClientConfig {
+            retry_backoff_ms: 100,
+            max_retry_backoff_ms: 350,
+            ..XzeprClientConfig::default()
+        };
+        assert_eq!(retry_delay(&config, 0), Duration::from_millis(100));
+        assert_eq!(retry_delay(&config, 1), Duration::from_millis(200));
+        assert_eq!(retry_delay(&config, 2), Duration::from_millis(350));
+        assert_eq!(retry_delay(&config, 40), Duration::from_millis(350));
+    }
+
+    #[test]
+    fn test_idempotency_key_depends_on_all_parts() {
+        let key = idempotency_key("r", "m", "started");
+        assert_eq!(key.len(), 64);
+        assert_ne!(key, idempotency_key("r", "m2", "started"));
+        assert_ne!(key, idempotency_key("r2", "m", "started"));
+        assert_ne!(
+            idempotency_key("ab", "c", "x"),
+            idempotency_key("a", "bc", "x")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_post_retries_then_succeeds() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/events"))
+            .respond_with(ResponseTemplate::new(502))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/events"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"data": "event-1"})),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = test_client(server.uri());
+        let id = client
+            .post_work_started(work_event(), serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(id, "event-1");
+
+        let requests = server.received_requests().await.unwrap();
+        let keys: Vec<_> = requests
+            .iter()
+            .map(|r| r.headers.get(IDEMPOTENCY_KEY_HEADER).cloned())
+            .collect();
+        assert_eq!(keys.len(), 3);
+        assert!(keys.iter().all(|k| k == &keys[0]));
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_post_does_not_retry_client_errors() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/events"))
+            .respond_with(ResponseTemplate::new(422).set_body_string("bad event"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = test_client(server.uri());
+        let result = client
+            .post_work_failed(work_event(), "boom", Some("E42"))
+            .await;
+        assert!(matches!(result, Err(ClientError::Api { status: 422, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_post_sends_idempotency_header() {
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let expected = idempotency_key("receiver-1", "msg-1", "completed");
+        Mock::given(method("POST"))
+            .and(path("/api/v1/events"))
+            .and(header(IDEMPOTENCY_KEY_HEADER, expected.as_str()))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"data": "event-2"})),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = test_client(server.uri());
+        let id = client
+            .post_work_completed(work_event(), true, serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(id, "event-2");
+    }
 }
@@ -33,6 +33,14 @@ pub enum ConfigError {
     /// Invalid SASL mechanism specified.
     #[error("Invalid SASL mechanism: {0}")]
     InvalidSaslMechanism(String),
+
+    /// Invalid offset commit mode specified.
+    #[error("Invalid commit mode: {0}")]
+    InvalidCommitMode(String),
+
+    /// Invalid in-flight message limit specified.
+    #[error("Invalid max in-flight value: {0}")]
+    InvalidMaxInFlight(String),
 }
 
 /// Security protocol for Kafka connection.
@@ -88,6 +96,58 @@ impl SaslMechanism {
     }
 }
 
+/// Offset commit strategy for the consumer.
+///
+/// Determines when the consumer tells Kafka that a message has been consumed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommitMode {
+    /// Let the Kafka client commit offsets periodically in the background.
+    ///
+    /// Offsets may be committed before the handler finishes, so a crash can
+    /// lose messages. This is the historical behavior.
+    #[default]
+    Auto,
+    /// Commit an offset only after the handler succeeded for that message and
+    /// every earlier message on the same partition.
+    AfterHandle,
+}
+
+impl CommitMode {
+    /// Returns the configuration string for this mode.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::AfterHandle => "after_handle",
+        }
+    }
+
+    /// Parses a commit mode from its configuration string.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Either "auto" or "after_handle" (case-insensitive)
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidCommitMode` for unknown values.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use xzatoma::xzepr::consumer::config::CommitMode;
+    ///
+    /// assert_eq!(CommitMode::parse("after_handle").unwrap(), CommitMode::AfterHandle);
+    /// assert!(CommitMode::parse("sometimes").is_err());
+    /// ```
+    pub fn parse(value: &str) -> Result<Self, ConfigError> {
+        match value.trim().to_lowercase().replace('-', "_").as_str() {
+            "auto" => Ok(Self::Auto),
+            "after_handle" => Ok(Self::AfterHandle),
+            _ => Err(ConfigError::InvalidCommitMode(value.to_string())),
+        }
+    }
+}
+
 /// SASL authentication configuration.
 ///
 /// Contains credentials and mechanism for SASL authentication.
@@ -160,6 +220,15 @@ pub struct KafkaConsumerConfig {
 
     /// Session timeout duration.
     pub session_timeout: Duration,
+
+    /// When offsets are committed (see [`CommitMode`]).
+    pub commit_mode: CommitMode,
+
+    /// Maximum number of messages handled concurrently.
+    ///
+    /// Only used with `CommitMode::AfterHandle`; `1` processes messages
+    /// sequentially.
+    pub max_in_flight: usize,
 }
 
 impl KafkaConsumerConfig {
@@ -194,6 +263,8 @@ impl KafkaConsumerConfig {
             auto_offset_reset: "earliest".to_string(),
             enable_auto_commit: true,
             session_timeout: Duration::from_secs(30),
+            commit_mode: CommitMode::default(),
+            max_in_flight: 1,
         }
     }
 
@@ -295,6 +366,48 @@ impl KafkaConsumerConfig {
         self
     }
 
+    /// Sets the offset commit mode.
+    ///
+    /// `CommitMode::AfterHandle` disables the Kafka client's auto-commit.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - Commit mode to use
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use xzatoma::xzepr::consumer::config::{CommitMode, KafkaConsumerConfig};
+    ///
+    /// let config = KafkaConsumerConfig::new("localhost:9092", "events", "my-service")
+    ///     .with_commit_mode(CommitMode::AfterHandle);
+    /// assert!(!config.auto_commit_enabled());
+    /// ```
+    pub fn with_commit_mode(mut self, mode: CommitMode) -> Self {
+        self.commit_mode = mode;
+        self
+    }
+
+    /// Sets the maximum number of messages handled concurrently.
+    ///
+    /// Values below `1` are treated as `1`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_in_flight` - Size of the in-flight window
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    /// Returns whether the Kafka client should commit offsets automatically.
+    ///
+    /// Auto-commit is used only when it is enabled and the commit mode is
+    /// `CommitMode::Auto`.
+    pub fn auto_commit_enabled(&self) -> bool {
+        self.enable_auto_commit && self.commit_mode == CommitMode::Auto
+    }
+
     /// Loads configuration from environment variables.
     ///
     /// # Environment Variables
@@ -309,6 +422,8 @@ impl KafkaConsumerConfig {
     /// * `XZEPR_KAFKA_SSL_CA_LOCATION` - CA certificate path
     /// * `XZEPR_KAFKA_SSL_CERT_LOCATION` - Client certificate path
     /// * `XZEPR_KAFKA_SSL_KEY_LOCATION` - Client key path
+    /// * `XZEPR_KAFKA_COMMIT_MODE` - Offset commit mode: auto or after_handle (default: auto)
+    /// * `XZEPR_KAFKA_MAX_IN_FLIGHT` - Messages handled concurrently (default: 1)
     ///
     /// # Errors
     ///
@@ -327,6 +442,20 @@ impl KafkaConsumerConfig {
 
         let mut config = Self::new(&brokers, &topic, service_name).with_group_id(&group_id);
 
+        // Load offset commit settings
+        if let Ok(mode) = std::env::var("XZEPR_KAFKA_COMMIT_MODE") {
+            config.commit_mode = CommitMode::parse(&mode)?;
+        }
+        if let Ok(value) = std::env::var("XZEPR_KAFKA_MAX_IN_FLIGHT") {
+            let max_in_flight = value
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or(ConfigError::InvalidMaxInFlight(value))?;
+            config.max_in_flight = max_in_flight;
+        }
+
         // Load security protocol
         let protocol = std::env::var("XZEPR_KAFKA_SECURITY_PROTOCOL")
             .unwrap_or_else(|_| "PLAINTEXT".to_string());
@@ -404,6 +533,9 @@ mod tests {
         assert_eq!(config.auto_offset_reset, "earliest");
         assert!(config.enable_auto_commit);
         assert_eq!(config.session_timeout, Duration::from_secs(30));
+        assert_eq!(config.commit_mode, CommitMode::Auto);
+        assert_eq!(config.max_in_flight, 1);
+        assert!(config.auto_commit_enabled());
     }
 
     #[test]
@@ -488,10 +620,13 @@ mod tests {
             std::env::remove_var("XZEPR_KAFKA_TOPIC");
             std::env::remove_var("XZEPR_KAFKA_GROUP_ID");
             std::env::remove_var("XZEPR_KAFKA_SECURITY_PROTOCOL");
+            std::env::remove_var("XZEPR_KAFKA_COMMIT_MODE");
+            std::env::remove_var("XZEPR_KAFKA_MAX_IN_FLIGHT");
         }
 
         let config = KafkaConsumerConfig::from_env("test-service").unwrap();
 
+        assert_eq!(config.commit_mode, CommitMode::Auto);
         assert_eq!(config.brokers, "localhost:9092");
         assert_eq!(config.topic, "xzepr.dev.events");
         assert_eq!(config.group_id, "xzepr-consumer-test-service");
@@ -577,4 +712,62 @@ mod tests {
         assert!(!config.enable_auto_commit);
         assert!(config.ssl_config.is_some());
     }
+
+    #[test]
+    fn test_commit_mode_parse() {
+        assert_eq!(CommitMode::parse("auto").unwrap(), CommitMode::Auto);
+        assert_eq!(
+            CommitMode::parse("AFTER_HANDLE").unwrap(),
+            CommitMode::AfterHandle
+        );
+        assert_eq!(
+            CommitMode::parse("after-handle").unwrap(),
+            CommitMode::AfterHandle
+        );
+        assert!(matches!(
+            CommitMode::parse("never"),
+            Err(ConfigError::InvalidCommitMode(_))
+        ));
+    }
+
+    #[test]
+    fn test_after_handle_disables_auto_commit() {
+        let config = KafkaConsumerConfig::new("localhost:9092", "topic", "service")
+            .with_commit_mode(CommitMode::AfterHandle)
+            .with_max_in_flight(0);
+
+        assert!(config.enable_auto_commit);
+        assert!(!config.auto_commit_enabled());
+        assert_eq!(config.max_in_flight, 1);
+    }
+
+    #[test]
+    #[serial]
+    fn test_from_env_commit_settings() {
+        // SAFETY: Test environment, no concurrent access
+        unsafe {
+            std::env::remove_var("XZEPR_KAFKA_SECURITY_PROTOCOL");
+            std::env::set_var("XZEPR_KAFKA_COMMIT_MODE", "after_handle");
+            std::env::set_var("XZEPR_KAFKA_MAX_IN_FLIGHT", "8");
+        }
+
+        let config = KafkaConsumerConfig::from_env("test-service").unwrap();
+        assert_eq!(config.commit_mode, CommitMode::AfterHandle);
+        assert_eq!(config.max_in_flight, 8);
+
+        // SAFETY: Test environment, no concurrent access
+        unsafe {
+            std::env::set_var("XZEPR_KAFKA_MAX_IN_FLIGHT", "0");
+        }
+        assert!(matches!(
+            KafkaConsumerConfig::from_env("test-service"),
+            Err(ConfigError::InvalidMaxInFlight(_))
+        ));
+
+        // SAFETY: Test environment, no concurrent access
+        unsafe {
+            std::env::remove_var("XZEPR_KAFKA_COMMIT_MODE");
+            std::env::remove_var("XZEPR_KAFKA_MAX_IN_FLIGHT");
+        }
+    }
 }
@@ -4,6 +4,17 @@
 //! from XZepr topics. It supports SASL/SCRAM authentication and provides
 //! both handler-based and channel-based message processing.
 //!
+//! # Offset Commits
+//!
+//! With [`CommitMode::Auto`] the Kafka client commits offsets in the
+//! background, independent of whether the handler succeeded. With
+//! [`CommitMode::AfterHandle`] auto-commit is disabled and an offset is
+//! committed only after the handler returned `Ok` (or
+//! [`MessageHandler::dead_letter`] accepted the failure) for that message and
+//! every earlier message on its partition. Up to
+//! `KafkaConsumerConfig::max_in_flight` messages are handled concurrently; the
+//! [`OffsetTracker`] keeps commits in offset order.
+//!
 //! # Example
 //!
 //! ```rust,no_run
@@ -34,19 +45,22 @@
 //! }
 //! ```
 
+use std::collections::HashSet;
+use std::panic::AssertUnwindSafe;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::mpsc;
+use tokio::task::JoinSet;
 use tracing::{debug, error, info, warn};
 
-use futures::StreamExt;
-use rdkafka::consumer::{Consumer, StreamConsumer};
-use rdkafka::ClientConfig;
-use rdkafka::Message;
+use futures::{FutureExt, StreamExt};
+use rdkafka::consumer::{CommitMode as KafkaCommitMode, Consumer, StreamConsumer};
+use rdkafka::{ClientConfig, Message, Offset, TopicPartitionList};
 
-use super::config::KafkaConsumerConfig;
+use super::config::{CommitMode, KafkaConsumerConfig};
 use super::message::CloudEventMessage;
+use super::offsets::OffsetTracker;
 
 /// Errors that can occur during consumer operations.
 #[derive(Error, Debug)]
@@ -101,6 +115,30 @@ pub trait MessageHandler: Send + Sync {
         &self,
         message: CloudEventMessage,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Handle a message whose `handle` call failed.
+    ///
+    /// Only called with `CommitMode::AfterHandle`. Return `Ok(())` once the
+    /// failure has been dealt with (for example by publishing the message to a
+    /// dead-letter topic) so that its offset can be committed. The default
+    /// returns the original error, which leaves the offset uncommitted and the
+    /// message is redelivered after a restart or rebalance.
+    async fn dead_letter(
+        &self,
+        message: CloudEventMessage,
+        error: Box<dyn std::error::Error + Send + Sync>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let _ = message;
+        Err(error)
+    }
+}
+
+/// Outcome of handling one message in `CommitMode::AfterHandle`.
+struct HandledMessage {
+    topic: String,
+    partition: i32,
+    offset: i64,
+    committable: bool,
 }
 
 /// XZepr Kafka consumer.
@@ -164,7 +202,7 @@ impl XzeprConsumer {
             ),
             (
                 "enable.auto.commit".to_string(),
-                self.config.enable_auto_commit.to_string(),
+                self.config.auto_commit_enabled().to_string(),
             ),
             (
                 "session.timeout.ms".to_string(),
@@ -281,9 +319,14 @@ impl XzeprConsumer {
     /// streams messages to the provided handler. The consumer runs until
     /// `stop()` is called or a fatal Kafka error occurs.
     ///
-    /// Messages are processed sequentially through the handler. If the handler
-    /// returns an error for a particular message, the error is logged and the
-    /// consumer continues processing subsequent messages.
+    /// With `CommitMode::Auto`, messages are processed sequentially through the
+    /// handler. If the handler returns an error for a particular message, the
+    /// error is logged and the consumer continues processing subsequent
+    /// messages.
+    ///
+    /// With `CommitMode::AfterHandle`, up to `max_in_flight` messages are
+    /// handled concurrently and offsets are committed only after successful
+    /// handling, in per-partition offset order.
     ///
     /// # Arguments
     ///
@@ -306,6 +349,14 @@ impl XzeprConsumer {
         );
 
         let consumer = self.create_subscribed_consumer()?;
+
+        if self.config.commit_mode == CommitMode::AfterHandle {
+            let result = self.run_after_handle(&consumer, handler).await;
+            self.running.store(false, Ordering::SeqCst);
+            info!(service = %self.config.service_name, "Consumer stopped");
+            return result;
+        }
+
         let mut stream = consumer.stream();
 
         while self.running.load(Ordering::SeqCst) {
@@ -369,6 +420,273 @@ impl XzeprConsumer {
         Ok(())
     }
 
+    /// Consume loop for `CommitMode::AfterHandle`.
+    ///
+    /// Messages are handed to spawned handler tasks while the in-flight window
+    /// has room. Completed tasks are recorded in the `OffsetTracker`, which
+    /// yields the offset to commit whenever a partition's low-water mark
+    /// advances. Outstanding tasks are drained before returning.
+    async fn run_after_handle<H: MessageHandler + 'static>(
+        &self,
+        consumer: &StreamConsumer,
+        handler: Arc<H>,
+    ) -> Result<(), ConsumerError> {
+        let mut tracker = OffsetTracker::new(self.config.max_in_flight);
+        let mut tasks: JoinSet<HandledMessage> = JoinSet::new();
+        let mut stream = consumer.stream();
+
+        info!(
+            service = %self.config.service_name,
+            max_in_flight = tracker.capacity(),
+            "Committing offsets after successful handling"
+        );
+
+        let mut result = Ok(());
+        while self.running.load(Ordering::SeqCst) {
+            // Checked on every iteration so a rebalance under steady load is
+            // noticed before the next completion is committed
+            self.sync_assignment(consumer, &mut tracker);
+            tokio::select! {
+                biased;
+                Some(joined) = tasks.join_next(), if !tasks.is_empty() => {
+                    match joined {
+                        Ok(handled) => self.record_handled(consumer, &mut tracker, handled),
+                        Err(e) => error!(
+                            service = %self.config.service_name,
+                            "Message handler task failed: {}", e
+                        ),
+                    }
+                }
+                message = stream.next(), if tracker.has_capacity() => match message {
+                    Some(Ok(borrowed_message)) => {
+                        let topic = borrowed_message.topic().to_string();
+                        let partition = borrowed_message.partition();
+                        let offset = borrowed_message.offset();
+                        tracker.begin(&topic, partition, offset);
+
+                        match Self::decode_message(&borrowed_message) {
+                            Some(event) => {
+                                let handler = handler.clone();
+                                tasks.spawn(async move {
+                                    let committable = Self::handle_with_dead_letter(&*handler, event).await;
+                                    HandledMessage { topic, partition, offset, committable }
+                                });
+                            }
+                            None => {
+                                // Undecodable messages can never succeed, so
+                                // they are committed to avoid blocking the
+                                // partition forever.
+                                self.record_handled(
+                                    consumer,
+                                    &mut tracker,
+                                    HandledMessage { topic, partition, offset, committable: true },
+                                );
+                            }
+                        }
+                    }
+                    Some(Err(e)) => {
+                        error!(
+                            service = %self.config.service_name,
+                            "Kafka consumer error: {}", e
+                        );
+                        result = Err(ConsumerError::Kafka(e.to_string()));
+                        break;
+                    }
+                    None => {
+                        warn!(
+                            service = %self.config.service_name,
+                            "Message stream ended unexpectedly"
+                        );
+                        break;
+                    }
+                },
+                () = tokio::time::sleep(std::time::Duration::from_secs(1)) => {
+                    debug!(
+                        service = %self.config.service_name,
+                        in_flight = tracker.in_flight(),
+                        "No messages received, checking shutdown flag"
+                    );
+                }
+            }
+        }
+
+        while let Some(joined) = tasks.join_next().await {
+            if let Ok(handled) = joined {
+                self.record_handled(consumer, &mut tracker, handled);
+            }
+        }
+
+        result
+    }
+
+    /// Decodes a Kafka message into a CloudEvent, logging why it was skipped.
+    fn decode_message<M: Message>(message: &M) -> Option<CloudEventMessage> {
+        match message.payload_view::<str>() {
+            Some(Ok(payload)) => match serde_json::from_str::<CloudEventMessage>(payload) {
+                Ok(event) => Some(event),
+                Err(e) => {
+                    error!("Error parsing CloudEvent: {}", e);
+                    debug!("Raw payload: {}", payload);
+                    None
+                }
+            },
+            Some(Err(e)) => {
+                error!("Error decoding message payload as UTF-8: {}", e);
+                None
+            }
+            None => {
+                debug!("Received message with empty payload, skipping");
+                None
+            }
+        }
+    }
+
+    /// Runs the handler and, on failure, its dead-letter fallback.
+    ///
+    /// # Returns
+    ///
+    /// `true` when the message's offset may be committed
+    async fn handle_with_dead_letter<H: MessageHandler>(
+        handler: &H,
+        event: CloudEventMessage,
+    ) -> bool {
+        let event_id = event.id.clone();
+        let outcome = AssertUnwindSafe(async {
+            match handler.handle(event.clone()).await {
+                Ok(()) => true,
+                Err(e) => {
+                    error!(event_id = %event_id, "Error handling message: {}", e);
+                    match handler.dead_letter(event, e).await {
+                        Ok(()) => {
+                            info!(event_id = %event_id, "Message dead-lettered");
+                            true
+                        }
+                        Err(e) => {
+                            warn!(
+                                event_id = %event_id,
+                                "Message not dead-lettered, offset will not be committed: {}", e
+                            );
+                            false
+                        }
+                    }
+                }
+            }
+        })
+        .catch_unwind()
+        .await;
+
+        outcome.unwrap_or_else(|_| {
+            error!(event_id = %event_id, "Message handler panicked");
+            false
+        })
+    }
+
+    /// Records a handled message and commits the partition's new low-water mark.
+    fn record_handled(
+        &self,
+        consumer: &StreamConsumer,
+        tracker: &mut OffsetTracker,
+        handled: HandledMessage,
+    ) {
+        let HandledMessage {
+            topic,
+            partition,
+            offset,
+            committable,
+        } = handled;
+
+        if !committable {
+            tracker.fail(&topic, partition, offset);
+            warn!(
+                service = %self.config.service_name,
+                topic = %topic,
+                partition,
+                offset,
+                "Offset commits blocked for partition until restart or rebalance"
+            );
+            return;
+        }
+
+        if let Some(next_offset) = tracker.complete(&topic, partition, offset) {
+            self.commit_offset(consumer, &topic, partition, next_offset);
+        }
+    }
+
+    /// Commits `next_offset` for a topic partition.
+    fn commit_offset(
+        &self,
+        consumer: &StreamConsumer,
+        topic: &str,
+        partition: i32,
+        next_offset: i64,
+    ) {
+        let mut list = TopicPartitionList::new();
+        let result = list
+            .add_partition_offset(topic, partition, Offset::Offset(next_offset))
+            .and_then(|()| consumer.commit(&list, KafkaCommitMode::Async));
+
+        let outcome = match result {
+            Ok(()) => {
+                debug!(
+                    service = %self.config.service_name,
+                    topic = %topic,
+                    partition,
+                    offset = next_offset,
+                    "Committed offset"
+                );
+                "ok"
+            }
+            Err(e) => {
+                warn!(
+                    service = %self.config.service_name,
+                    topic = %topic,
+                    partition,
+                    offset = next_offset,
+                    "Failed to commit offset: {}", e
+                );
+                "error"
+            }
+        };
+
+        metrics::increment_counter!(
+            "xzepr_consumer_offset_commits_total",
+            "topic" => topic.to_string(),
+            "partition" => partition.to_string(),
+            "outcome" => outcome
+        );
+    }
+
+    /// Drops tracked offsets for partitions no longer assigned to this consumer.
+    ///
+    /// After a rebalance the new owner resumes from the last committed offset,
+    /// so late completions for revoked partitions must not be committed.
+    fn sync_assignment(&self, consumer: &StreamConsumer, tracker: &mut OffsetTracker) {
+        let assignment = match consumer.assignment() {
+            Ok(assignment) => assignment,
+            Err(e) => {
+                debug!("Could not read partition assignment: {}", e);
+                return;
+            }
+        };
+        let assigned: HashSet<(String, i32)> = assignment
+            .elements()
+            .iter()
+            .map(|elem| (elem.topic().to_string(), elem.partition()))
+            .collect();
+
+        for (topic, partition) in tracker.partitions() {
+            if !assigned.contains(&(topic.clone(), partition)) {
+                info!(
+                    service = %self.config.service_name,
+                    topic = %topic,
+                    partition,
+                    "Partition revoked, discarding tracked offsets"
+                );
+                tracker.revoke(&topic, partition);
+            }
+        }
+    }
+
     /// Runs the consumer and sends messages to a channel.
     ///
     /// Creates a `StreamConsumer`, subscribes to the configured topic, and
@@ -379,6 +697,9 @@ impl XzeprConsumer {
     /// The consumer stops when `stop()` is called, a fatal Kafka error occurs,
     /// or the channel receiver is dropped.
     ///
+    /// With `CommitMode::AfterHandle`, a message's offset is committed once the
+    /// message has been accepted by the channel.
+    ///
     /// # Arguments
     ///
     /// * `sender` - Channel sender for messages
@@ -431,6 +752,14 @@ impl XzeprConsumer {
                                 );
                                 break;
                             }
+                            if self.config.commit_mode == CommitMode::AfterHandle {
+                                self.commit_offset(
+                                    &consumer,
+                                    borrowed_message.topic(),
+                                    borrowed_message.partition(),
+                                    borrowed_message.offset() + 1,
+                                );
+                            }
                         }
                         Err(e) => {
                             error!(
@@ -689,6 +1018,86 @@ mod tests {
         assert_eq!(map.get("sasl.username").unwrap(), "admin");
     }
 
+    #[test]
+    fn test_after_handle_disables_client_auto_commit() {
+        let config = KafkaConsumerConfig::new("localhost:9092", "test-topic", "test-service")
+            .with_commit_mode(CommitMode::AfterHandle);
+        let consumer = XzeprConsumer::new(config).unwrap();
+
+        let map: std::collections::HashMap<_, _> =
+            consumer.get_kafka_config().into_iter().collect();
+        assert_eq!(map.get("enable.auto.commit").unwrap(), "false");
+    }
+
+    #[tokio::test]
+    async fn test_handle_with_dead_letter_outcomes() {
+        struct FailingHandler {
+            dead_letter_ok: bool,
+        }
+
+        #[async_trait::async_trait]
+        impl MessageHandler for FailingHandler {
+            async fn handle(
+                &self,
+                _message: CloudEventMessage,
+            ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+                Err("boom".into())
+            }
+
+            async fn dead_letter(
+                &self,
+                _message: CloudEventMessage,
+                error: Box<dyn std::error::Error + Send + Sync>,
+            ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+                if self.dead_letter_ok {
+                    Ok(())
+                } else {
+                    Err(error)
+                }
+            }
+        }
+
+        let payload = r#"{
+            "success": true,
+            "id": "dlq-id",
+            "specversion": "1.0.1",
+            "type": "test.event",
+            "source": "test-source",
+            "api_version": "v1",
+            "name": "test.event",
+            "version": "1.0.0",
+            "release": "1.0.0",
+            "platform_id": "test",
+            "package": "testpkg",
+            "data": {
+                "events": [],
+                "event_receivers": [],
+                "event_receiver_groups": []
+            }
+        }"#;
+        let event: CloudEventMessage = serde_json::from_str(payload).unwrap();
+
+        assert!(XzeprConsumer::handle_with_dead_letter(&TestHandler::new(), event.clone()).await);
+        assert!(
+            XzeprConsumer::handle_with_dead_letter(
+                &FailingHandler {
+                    dead_letter_ok: true
+                },
+                event.clone()
+            )
+            .await
+        );
+        assert!(
+            !XzeprConsumer::handle_with_dead_letter(
+                &FailingHandler {
+                    dead_letter_ok: false
+                },
+                event
+            )
+            .await
+        );
+    }
+
     #[test]
     fn test_consumer_not_running_initially() {
         let config = KafkaConsumerConfig::new("localhost:9092", "test-topic", "test-service");
@@ -29,11 +29,30 @@
 //!
 //! let event: CloudEventMessage = serde_json::from_str(json).unwrap();
 //! assert_eq!(event.id, "01JXXXXXXXXXXXXXXXXXXXXXXX");
+//! assert!(event.validate().is_ok());
 //! ```
+//!
+//! Attributes not modelled by [`CloudEventMessage`] (CloudEvents extension
+//! attributes such as `platformid` or `traceparent`) are kept in
+//! [`CloudEventMessage::extensions`].
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Errors reported by [`CloudEventMessage::validate`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CloudEventValidationError {
+    /// One or more required CloudEvents attributes are missing or empty.
+    #[error("Missing required CloudEvents attributes: {}", .0.join(", "))]
+    MissingAttributes(Vec<&'static str>),
+
+    /// The `specversion` attribute names an unsupported version.
+    #[error("Unsupported CloudEvents specversion: {0}")]
+    UnsupportedSpecVersion(String),
+}
 
 /// CloudEvents 1.0.1 message from XZepr.
 ///
@@ -46,16 +65,19 @@ pub struct CloudEventMessage {
     pub success: bool,
 
     /// Unique event identifier (ULID).
+    #[serde(default)]
     pub id: String,
 
     /// CloudEvents specification version (typically "1.0.1").
+    #[serde(default)]
     pub specversion: String,
 
     /// Event type/name (e.g., "deployment.success").
-    #[serde(rename = "type")]
+    #[serde(rename = "type", default)]
     pub event_type: String,
 
     /// Event source URI.
+    #[serde(default)]
     pub source: String,
 
     /// XZepr API version.
@@ -78,6 +100,102 @@ pub struct CloudEventMessage {
 
     /// Event payload data containing entities.
     pub data: CloudEventData,
+
+    /// CloudEvents extension attributes (any top-level attribute not listed
+    /// above, e.g. `platformid` or `traceparent`).
+    #[serde(flatten)]
+    pub extensions: HashMap<String, JsonValue>,
+}
+
+impl CloudEventMessage {
+    /// Checks the attributes required by CloudEvents 1.0.1.
+    ///
+    /// `id`, `source`, `specversion`, and `type` must be present and
+    /// non-empty, and `specversion` must be a 1.0 version.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CloudEventValidationError::MissingAttributes` listing every
+    /// missing attribute, or `CloudEventValidationError::UnsupportedSpecVersion`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::xzepr::consumer::message::{CloudEventMessage, CloudEventValidationError};
+    ///
+    /// let json = r#"{
+    ///   "success": true, "id": "", "specversion": "1.0.1",
+    ///   "api_version": "v1", "name": "n", "version": "1", "release": "1",
+    ///   "platform_id": "p", "package": "pkg", "data": {}
+    /// }"#;
+    /// let event: CloudEventMessage = serde_json::from_str(json).unwrap();
+    ///
+    /// assert_eq!(
+    ///     event.validate(),
+    ///     Err(CloudEventValidationError::MissingAttributes(vec!["id", "source", "type"]))
+    /// );
+    /// ```
+    pub fn validate(&self) -> Result<(), CloudEventValidationError> {
+        let missing: Vec<&'static str> = [
+            ("id", &self.id),
+            ("source", &self.source),
+            ("specversion", &self.specversion),
+            ("type", &self.event_type),
+        ]
+        .into_iter()
+        .filter(|(_, value)| value.trim().is_empty())
+        .map(|(name, _)| name)
+        .collect();
+
+        if !missing.is_empty() {
+            return Err(CloudEventValidationError::MissingAttributes(missing));
+        }
+
+        let specversion = self.specversion.trim();
+        if specversion != "1.0" && !specversion.starts_with("1.0.") {
+            return Err(CloudEventValidationError::UnsupportedSpecVersion(
+                self.specversion.clone(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns an extension attribute.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Extension attribute name (e.g. "traceparent")
+    pub fn extension(&self, name: &str) -> Option<&JsonValue> {
+        self.extensions.get(name)
+    }
+
+    /// Returns an extension attribute when it is a string.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Extension attribute name (e.g. "platformid")
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xzatoma::xzepr::consumer::message::CloudEventMessage;
+    ///
+    /// let json = r#"{
+    ///   "success": true, "id": "1", "specversion": "1.0.1", "type": "t",
+    ///   "source": "s", "api_version": "v1", "name": "n", "version": "1",
+    ///   "release": "1", "platform_id": "p", "package": "pkg", "data": {},
+    ///   "platformid": "k8s-prod", "attempt": 2
+    /// }"#;
+    /// let event: CloudEventMessage = serde_json::from_str(json).unwrap();
+    ///
+    /// assert_eq!(event.extension_str("platformid"), Some("k8s-prod"));
+    /// assert_eq!(event.extension_str("attempt"), None);
+    /// assert_eq!(event.extension_str("missing"), None);
+    /// ```
+    pub fn extension_str(&self, name: &str) -> Option<&str> {
+        self.extensions.get(name).and_then(JsonValue::as_str)
+    }
 }
 
 /// Data payload containing entities from XZepr.
@@ -384,6 +502,7 @@ mod tests {
             platform_id: "test".to_string(),
             package: "testpkg".to_string(),
             data: CloudEventData::default(),
+            extensions: HashMap::new(),
         };
 
         let json = serde_json::to_string(&original).unwrap();
@@ -401,4 +520,90 @@ mod tests {
         assert!(data.event_receivers.is_empty());
         assert!(data.event_receiver_groups.is_empty());
     }
+
+    const EXTENSION_FIXTURE: &str = r#"{
+        "success": true,
+        "id": "01JTEST1234567890123456",
+        "specversion": "1.0.1",
+        "type": "deployment.success",
+        "source": "xzepr.event.receiver.01JTEST1234567890123456",
+        "api_version": "v1",
+        "name": "deployment.success",
+        "version": "1.0.0",
+        "release": "1.0.0-rc.1",
+        "platform_id": "kubernetes",
+        "package": "myapp",
+        "data": {},
+        "platformid": "k8s-prod",
+        "traceparent": "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "retries": 3
+    }"#;
+
+    #[test]
+    fn test_extensions_captured_from_unknown_attributes() {
+        let event: CloudEventMessage = serde_json::from_str(EXTENSION_FIXTURE).unwrap();
+
+        assert_eq!(event.extensions.len(), 3);
+        assert_eq!(event.extension_str("platformid"), Some("k8s-prod"));
+        assert!(event
+            .extension_str("traceparent")
+            .unwrap()
+            .starts_with("00-"));
+        assert_eq!(event.extension("retries"), Some(&serde_json::json!(3)));
+        assert_eq!(event.extension_str("retries"), None);
+        assert!(!event.extensions.contains_key("platform_id"));
+    }
+
+    #[test]
+    fn test_extensions_serialization_roundtrip() {
+        let event: CloudEventMessage = serde_json::from_str(EXTENSION_FIXTURE).unwrap();
+
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["platformid"], "k8s-prod");
+        assert_eq!(value["type"], "deployment.success");
+
+        let roundtrip: CloudEventMessage = serde_json::from_value(value).unwrap();
+        assert_eq!(roundtrip.extensions, event.extensions);
+        assert_eq!(roundtrip.platform_id, "kubernetes");
+    }
+
+    #[test]
+    fn test_validate_accepts_complete_event() {
+        let event: CloudEventMessage = serde_json::from_str(EXTENSION_FIXTURE).unwrap();
+        assert!(event.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_lists_missing_attributes() {
+        let mut value: JsonValue = serde_json::from_str(EXTENSION_FIXTURE).unwrap();
+        let object = value.as_object_mut().unwrap();
+        object.remove("id");
+        object.remove("specversion");
+        object.insert("source".to_string(), JsonValue::String("  ".to_string()));
+
+        let event: CloudEventMessage = serde_json::from_value(value).unwrap();
+        let err = event.validate().unwrap_err();
+
+        assert_eq!(
+            err,
+            CloudEventValidationError::MissingAttributes(vec!["id", "source", "specversion"])
+        );
+        assert_eq!(
+            err.to_string(),
+            "Missing required CloudEvents attributes: id, source, specversion"
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_unsupported_specversion() {
+        let mut event: CloudEventMessage = serde_json::from_str(EXTENSION_FIXTURE).unwrap();
+        event.specversion = "0.3".to_string();
+        assert!(matches!(
+            event.validate(),
+            Err(CloudEventValidationError::UnsupportedSpecVersion(_))
+        ));
+
+        event.specversion = "1.0".to_string();
+        assert!(event.validate().is_ok());
+    }
 }
@@ -115,15 +115,19 @@ pub mod client;
 pub mod config;
 pub mod kafka;
 pub mod message;
+pub mod offsets;
 
 pub use client::{
     ClientError, CreateEventReceiverRequest, CreateEventRequest, EventReceiverResponse,
     PaginatedResponse, PaginationMeta, WorkEvent, XzeprClient, XzeprClientConfig,
 };
 pub use config::{
-    ConfigError, KafkaConsumerConfig, SaslConfig, SaslMechanism, SecurityProtocol, SslConfig,
+    CommitMode, ConfigError, KafkaConsumerConfig, SaslConfig, SaslMechanism, SecurityProtocol,
+    SslConfig,
 };
 pub use kafka::{ConsumerError, MessageHandler, XzeprConsumer};
 pub use message::{
-    CloudEventData, CloudEventMessage, EventEntity, EventReceiverEntity, EventReceiverGroupEntity,
+    CloudEventData, CloudEventMessage, CloudEventValidationError, EventEntity, EventReceiverEntity,
+    EventReceiverGroupEntity,
 };
+pub use offsets::OffsetTracker;
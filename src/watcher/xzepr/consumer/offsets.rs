@@ -0,0 +1,348 @@
+//! Offset tracking for manual Kafka commits.
+//!
+//! When the consumer runs with [`CommitMode::AfterHandle`](super::config::CommitMode),
+//! an offset may only be committed once the message at that offset and every
+//! earlier message on the same partition has been handled. Handlers can finish
+//! out of order when several messages are in flight, so the consumer records
+//! each message in an [`OffsetTracker`] and commits the per-partition
+//! low-water mark the tracker reports.
+//!
+//! The tracker is a plain data structure with no Kafka dependency so that its
+//! ordering rules can be tested directly.
+//!
+//! # Example
+//!
+//! ```rust
+//! use xzatoma::xzepr::consumer::offsets::OffsetTracker;
+//!
+//! let mut tracker = OffsetTracker::new(8);
+//! tracker.begin("events", 0, 10);
+//! tracker.begin("events", 0, 11);
+//!
+//! // Offset 11 finishes first; nothing can be committed yet.
+//! assert_eq!(tracker.complete("events", 0, 11), None);
+//!
+//! // Once offset 10 finishes, both are committed (Kafka commits the next offset).
+//! assert_eq!(tracker.complete("events", 0, 10), Some(12));
+//! ```
+
+use std::collections::{BTreeSet, HashMap};
+
+/// Offsets of one topic partition that have not been committed yet.
+#[derive(Debug, Default)]
+struct PartitionOffsets {
+    /// Messages currently being handled.
+    in_flight: BTreeSet<i64>,
+    /// Messages whose handler failed without a dead-letter fallback.
+    failed: BTreeSet<i64>,
+    /// Messages handled successfully but not yet committed.
+    done: BTreeSet<i64>,
+}
+
+impl PartitionOffsets {
+    /// Returns the lowest offset that blocks committing, if any.
+    fn barrier(&self) -> Option<i64> {
+        let in_flight = self.in_flight.iter().next().copied();
+        let failed = self.failed.iter().next().copied();
+        match (in_flight, failed) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Drops handled offsets that a failure makes uncommittable.
+    ///
+    /// Offsets above the lowest failed offset can only be committed after the
+    /// failure clears, which happens only on revoke or reset, and both discard
+    /// the partition. Keeping them would grow `done` for as long as the
+    /// partition stays blocked.
+    fn prune_blocked(&mut self) {
+        if let Some(&failed) = self.failed.iter().next() {
+            self.done.retain(|&offset| offset < failed);
+        }
+    }
+
+    /// Drains handled offsets below the barrier and returns the offset to commit.
+    fn drain_committable(&mut self) -> Option<i64> {
+        self.prune_blocked();
+        let committable: Vec<i64> = match self.barrier() {
+            Some(barrier) => self.done.range(..barrier).copied().collect(),
+            None => self.done.iter().copied().collect(),
+        };
+        let highest = *committable.last()?;
+        for offset in committable {
+            self.done.remove(&offset);
+        }
+        Some(highest + 1)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.in_flight.is_empty() && self.failed.is_empty() && self.done.is_empty()
+    }
+}
+
+/// Tracks in-flight messages and computes per-partition commit offsets.
+///
+/// Offsets returned by [`complete`](Self::complete) follow the Kafka
+/// convention of committing the offset of the *next* message to read.
+#[derive(Debug)]
+pub struct OffsetTracker {
+    capacity: usize,
+    in_flight: usize,
+    partitions: HashMap<(String, i32), PartitionOffsets>,
+}
+
+impl OffsetTracker {
+    /// Creates a tracker that allows at most `capacity` messages in flight.
+    ///
+    /// A capacity of zero is treated as one.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - Maximum number of messages handled concurrently
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            in_flight: 0,
+            partitions: HashMap::new(),
+        }
+    }
+
+    /// Returns the maximum number of in-flight messages.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of messages currently being handled.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Returns `true` when another message may be started.
+    pub fn has_capacity(&self) -> bool {
+        self.in_flight < self.capacity
+    }
+
+    /// Records that handling of a message has started.
+    ///
+    /// Starting an offset that is already tracked has no effect.
+    ///
+    /// # Arguments
+    ///
+    /// * `topic` - Topic of the message
+    /// * `partition` - Partition of the message
+    /// * `offset` - Offset of the message
+    pub fn begin(&mut self, topic: &str, partition: i32, offset: i64) {
+        let entry = self
+            .partitions
+            .entry((topic.to_string(), partition))
+            .or_default();
+        if entry.in_flight.insert(offset) {
+            self.in_flight += 1;
+        }
+    }
+
+    /// Records that a message was handled (or dead-lettered) successfully.
+    ///
+    /// # Arguments
+    ///
+    /// * `topic` - Topic of the message
+    /// * `partition` - Partition of the message
+    /// * `offset` - Offset of the message
+    ///
+    /// # Returns
+    ///
+    /// The offset to commit for the partition when the low-water mark
+    /// advanced, or `None` when an earlier message is still outstanding or
+    /// the offset is unknown (for example after the partition was revoked)
+    pub fn complete(&mut self, topic: &str, partition: i32, offset: i64) -> Option<i64> {
+        let entry = self.partitions.get_mut(&(topic.to_string(), partition))?;
+        if !entry.in_flight.remove(&offset) {
+            return None;
+        }
+        self.in_flight -= 1;
+        entry.done.insert(offset);
+        entry.drain_committable()
+    }
+
+    /// Records that a message failed and was not dead-lettered.
+    ///
+    /// The offset stops counting against the in-flight window but blocks
+    /// commits for its partition, so the message is redelivered after a
+    /// restart or rebalance.
+    ///
+    /// # Arguments
+    ///
+    /// * `topic` - Topic of the message
+    /// * `partition` - Partition of the message
+    /// * `offset` - Offset of the message
+    pub fn fail(&mut self, topic: &str, partition: i32, offset: i64) {
+        let Some(entry) = self.partitions.get_mut(&(topic.to_string(), partition)) else {
+            return;
+        };
+        if entry.in_flight.remove(&offset) {
+            self.in_flight -= 1;
+            entry.failed.insert(offset);
+            entry.prune_blocked();
+        }
+    }
+
+    /// Returns `true` when commits for the partition are blocked by a failure.
+    pub fn is_blocked(&self, topic: &str, partition: i32) -> bool {
+        self.partitions
+            .get(&(topic.to_string(), partition))
+            .map(|p| !p.failed.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Forgets all state for a partition, e.g. after it was revoked.
+    ///
+    /// Messages from the partition that complete afterwards are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `topic` - Topic of the partition
+    /// * `partition` - Partition number
+    pub fn revoke(&mut self, topic: &str, partition: i32) {
+        if let Some(entry) = self.partitions.remove(&(topic.to_string(), partition)) {
+            self.in_flight -= entry.in_flight.len();
+        }
+    }
+
+    /// Forgets all partitions, e.g. on a full consumer group rebalance.
+    pub fn reset(&mut self) {
+        self.partitions.clear();
+        self.in_flight = 0;
+    }
+
+    /// Returns the topic partitions that currently have tracked offsets.
+    pub fn partitions(&self) -> Vec<(String, i32)> {
+        self.partitions.keys().cloned().collect()
+    }
+
+    /// Returns `true` when no partition has outstanding offsets.
+    pub fn is_idle(&self) -> bool {
+        self.partitions.values().all(PartitionOffsets::is_empty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOPIC: &str = "events";
+
+    #[test]
+    fn test_in_order_completion_commits_each_offset() {
+        let mut tracker = OffsetTracker::new(4);
+        tracker.begin(TOPIC, 0, 5);
+        assert_eq!(tracker.complete(TOPIC, 0, 5), Some(6));
+        tracker.begin(TOPIC, 0, 6);
+        assert_eq!(tracker.complete(TOPIC, 0, 6), Some(7));
+        assert!(tracker.is_idle());
+    }
+
+    #[test]
+    fn test_out_of_order_completion_waits_for_low_water_mark() {
+        let mut tracker = OffsetTracker::new(4);
+        for offset in 0..4 {
+            tracker.begin(TOPIC, 0, offset);
+        }
+
+        assert_eq!(tracker.complete(TOPIC, 0, 2), None);
+        assert_eq!(tracker.complete(TOPIC, 0, 3), None);
+        assert_eq!(tracker.complete(TOPIC, 0, 0), Some(1));
+        assert_eq!(tracker.complete(TOPIC, 0, 1), Some(4));
+        assert_eq!(tracker.in_flight(), 0);
+        assert!(tracker.is_idle());
+    }
+
+    #[test]
+    fn test_partitions_are_tracked_independently() {
+        let mut tracker = OffsetTracker::new(4);
+        tracker.begin(TOPIC, 0, 10);
+        tracker.begin(TOPIC, 1, 20);
+        tracker.begin(TOPIC, 0, 11);
+
+        assert_eq!(tracker.complete(TOPIC, 0, 11), None);
+        assert_eq!(tracker.complete(TOPIC, 1, 20), Some(21));
+        assert_eq!(tracker.complete(TOPIC, 0, 10), Some(12));
+    }
+
+    #[test]
+    fn test_capacity_bounds_in_flight_window() {
+        let mut tracker = OffsetTracker::new(2);
+        tracker.begin(TOPIC, 0, 0);
+        assert!(tracker.has_capacity());
+        tracker.begin(TOPIC, 0, 1);
+        assert!(!tracker.has_capacity());
+
+        tracker.complete(TOPIC, 0, 1);
+        assert!(tracker.has_capacity());
+        assert_eq!(OffsetTracker::new(0).capacity(), 1);
+    }
+
+    #[test]
+    fn test_failed_offset_blocks_later_commits() {
+        let mut tracker = OffsetTracker::new(4);
+        tracker.begin(TOPIC, 0, 0);
+        tracker.begin(TOPIC, 0, 1);
+        tracker.begin(TOPIC, 0, 2);
+
+        assert_eq!(tracker.complete(TOPIC, 0, 0), Some(1));
+        tracker.fail(TOPIC, 0, 1);
+        assert!(tracker.is_blocked(TOPIC, 0));
+        assert_eq!(tracker.in_flight(), 1);
+        assert_eq!(tracker.complete(TOPIC, 0, 2), None);
+    }
+
+    #[test]
+    fn test_failure_prunes_done_offsets_it_blocks() {
+        let mut tracker = OffsetTracker::new(8);
+        for offset in 0..5 {
+            tracker.begin(TOPIC, 0, offset);
+        }
+
+        assert_eq!(tracker.complete(TOPIC, 0, 1), None);
+        assert_eq!(tracker.complete(TOPIC, 0, 3), None);
+        tracker.fail(TOPIC, 0, 2);
+        assert_eq!(tracker.complete(TOPIC, 0, 4), None);
+        assert_eq!(tracker.partitions[&(TOPIC.to_string(), 0)].done.len(), 1);
+
+        // Offset 1 is below the failure and still commits once 0 finishes
+        assert_eq!(tracker.complete(TOPIC, 0, 0), Some(2));
+        assert!(tracker.partitions[&(TOPIC.to_string(), 0)].done.is_empty());
+    }
+
+    #[test]
+    fn test_revoke_resets_partition_and_ignores_late_completions() {
+        let mut tracker = OffsetTracker::new(4);
+        tracker.begin(TOPIC, 0, 0);
+        tracker.begin(TOPIC, 0, 1);
+        tracker.begin(TOPIC, 1, 7);
+        tracker.fail(TOPIC, 0, 0);
+
+        tracker.revoke(TOPIC, 0);
+        assert_eq!(tracker.in_flight(), 1);
+        assert!(!tracker.is_blocked(TOPIC, 0));
+        assert_eq!(tracker.complete(TOPIC, 0, 1), None);
+
+        // The partition is reassigned and consumption resumes from the
+        // last committed offset.
+        tracker.begin(TOPIC, 0, 0);
+        assert_eq!(tracker.complete(TOPIC, 0, 0), Some(1));
+        assert_eq!(tracker.complete(TOPIC, 1, 7), Some(8));
+    }
+
+    #[test]
+    fn test_reset_clears_all_partitions() {
+        let mut tracker = OffsetTracker::new(4);
+        tracker.begin(TOPIC, 0, 0);
+        tracker.begin(TOPIC, 1, 0);
+
+        tracker.reset();
+        assert_eq!(tracker.in_flight(), 0);
+        assert!(tracker.is_idle());
+        assert_eq!(tracker.complete(TOPIC, 1, 0), None);
+    }
+}
@@ -14,7 +14,8 @@ use regex::Regex;
 /// Event filter for determining which CloudEvents to process.
 ///
 /// Filters events based on configured criteria including event types,
-/// source patterns, platform ID, package name, API version, and success status.
+/// source patterns, platform ID, package name, API version, success status,
+/// and CloudEvents extension attributes.
 #[derive(Clone)]
 pub struct EventFilter {
     config: EventFilterConfig,
@@ -49,6 +50,7 @@ impl EventFilter {
     ///     package: None,
     ///     api_version: None,
     ///     success_only: true,
+    ///     extensions: Default::default(),
     /// };
     ///
     /// let filter = EventFilter::new(config);
@@ -97,6 +99,7 @@ impl EventFilter {
     ///     package: None,
     ///     api_version: None,
     ///     success_only: true,
+    ///     extensions: Default::default(),
     /// };
     ///
     /// let filter = EventFilter::new(config).unwrap();
@@ -143,7 +146,11 @@ impl EventFilter {
             }
         }
 
-        true
+        // Filter by extension attributes
+        self.config
+            .extensions
+            .iter()
+            .all(|(name, expected)| extension_matches(event.extension(name), expected))
     }
 
     /// Get filter summary for logging.
@@ -165,6 +172,7 @@ impl EventFilter {
     ///     package: None,
     ///     api_version: None,
     ///     success_only: true,
+    ///     extensions: Default::default(),
     /// };
     ///
     /// let filter = EventFilter::new(config).unwrap();
@@ -195,6 +203,12 @@ impl EventFilter {
             parts.push(format!("api_version={}", version));
         }
 
+        let mut extensions: Vec<_> = self.config.extensions.iter().collect();
+        extensions.sort();
+        for (name, value) in extensions {
+            parts.push(format!("ext.{}={}", name, value));
+        }
+
         if self.config.success_only {
             parts.push("success=true".to_string());
         }
@@ -207,6 +221,20 @@ impl EventFilter {
     }
 }
 
+/// Compares an extension attribute with a configured filter value.
+///
+/// Strings match exactly; numbers and booleans match their JSON text
+/// (e.g. `3` or `true`).
+fn extension_matches(value: Option<&serde_json::Value>, expected: &str) -> bool {
+    match value {
+        Some(serde_json::Value::String(actual)) => actual == expected,
+        Some(value @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => {
+            value.to_string() == expected
+        }
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -234,6 +262,7 @@ mod tests {
             platform_id: platform_id.to_string(),
             package: package.to_string(),
             data: CloudEventData::default(),
+            extensions: Default::default(),
         }
     }
 
@@ -246,6 +275,7 @@ mod tests {
             package: None,
             api_version: None,
             success_only: false,
+            extensions: Default::default(),
         };
 
         let filter = EventFilter::new(config).unwrap();
@@ -269,6 +299,7 @@ mod tests {
             package: None,
             api_version: None,
             success_only: false,
+            extensions: Default::default(),
         };
 
         let filter = EventFilter::new(config).unwrap();
@@ -290,6 +321,7 @@ mod tests {
             package: None,
             api_version: None,
             success_only: true,
+            extensions: Default::default(),
         };
 
         let filter = EventFilter::new(config).unwrap();
@@ -309,6 +341,7 @@ mod tests {
             package: None,
             api_version: None,
             success_only: false,
+            extensions: Default::default(),
         };
 
         let filter = EventFilter::new(config).unwrap();
@@ -335,6 +368,7 @@ mod tests {
             package: None,
             api_version: None,
             success_only: false,
+            extensions: Default::default(),
         };
 
         let filter = EventFilter::new(config).unwrap();
@@ -354,6 +388,7 @@ mod tests {
             package: Some("myapp".to_string()),
             api_version: None,
             success_only: false,
+            extensions: Default::default(),
         };
 
         let filter = EventFilter::new(config).unwrap();
@@ -373,6 +408,7 @@ mod tests {
             package: None,
             api_version: Some("v1".to_string()),
             success_only: false,
+            extensions: Default::default(),
         };
 
         let filter = EventFilter::new(config).unwrap();
@@ -392,6 +428,7 @@ mod tests {
             package: Some("myapp".to_string()),
             api_version: Some("v1".to_string()),
             success_only: true,
+            extensions: Default::default(),
         };
 
         let filter = EventFilter::new(config).unwrap();
@@ -449,6 +486,7 @@ mod tests {
             package: None,
             api_version: None,
             success_only: false,
+            extensions: Default::default(),
         };
 
         let filter = EventFilter::new(config).unwrap();
@@ -476,6 +514,7 @@ mod tests {
             package: None,
             api_version: None,
             success_only: false,
+            extensions: Default::default(),
         };
 
         let filter = EventFilter::new(config).unwrap();
@@ -492,6 +531,7 @@ mod tests {
             package: Some("myapp".to_string()),
             api_version: Some("v1".to_string()),
             success_only: true,
+            extensions: Default::default(),
         };
 
         let filter = EventFilter::new(config).unwrap();
@@ -514,9 +554,45 @@ mod tests {
             package: None,
             api_version: None,
             success_only: false,
+            extensions: Default::default(),
         };
 
         let result = EventFilter::new(config);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_filter_by_extension_attributes() {
+        let config = EventFilterConfig {
+            extensions: [
+                ("platformid".to_string(), "k8s-prod".to_string()),
+                ("tier".to_string(), "1".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            ..EventFilterConfig::default()
+        };
+
+        let filter = EventFilter::new(config).unwrap();
+        let mut matching = create_test_event(true, "test", "source", "k8s", "app", "v1");
+        matching
+            .extensions
+            .insert("platformid".to_string(), serde_json::json!("k8s-prod"));
+        matching
+            .extensions
+            .insert("tier".to_string(), serde_json::json!(1));
+
+        let mut wrong_value = matching.clone();
+        wrong_value
+            .extensions
+            .insert("platformid".to_string(), serde_json::json!("k8s-dev"));
+
+        let mut missing = matching.clone();
+        missing.extensions.remove("tier");
+
+        assert!(filter.should_process(&matching));
+        assert!(!filter.should_process(&wrong_value));
+        assert!(!filter.should_process(&missing));
+        assert!(filter.summary().contains("ext.platformid=k8s-prod"));
+    }
 }
@@ -233,6 +233,7 @@ mod tests {
                 event_receivers: vec![],
                 event_receiver_groups: vec![],
             },
+            extensions: Default::default(),
         }
     }
 
@@ -311,6 +312,7 @@ mod tests {
             platform_id: "test-platform".to_string(),
             package: "test-package".to_string(),
             data: CloudEventData::default(),
+            extensions: Default::default(),
         };
 
         let extractor = PlanExtractor::new();
@@ -341,6 +343,7 @@ mod tests {
                 event_receivers: vec![],
                 event_receiver_groups: vec![],
             },
+            extensions: Default::default(),
         };
 
         let extractor = PlanExtractor::new();
@@ -5,21 +5,30 @@
 //! 2. Consumes XZepr CloudEvents messages
 //! 3. Filters events based on configuration
 //! 4. Extracts plans from event payloads
-//! 5. Executes extracted plans with concurrency control
+//! 5. Executes extracted plans with concurrency control, each in an isolated
+//!    workspace
+//!
+//! Filters, `allow_dangerous`, and the concurrency limit are read from
+//! [`LiveSettings`] for each event, so a config reload applies them without
+//! a restart.
 //!
 //! This module was relocated from `src/watcher/watcher.rs` into
 //! `src/watcher/xzepr/` as part of the generic watcher architecture (Phase 1).
 
-use super::consumer::{CloudEventMessage, KafkaConsumerConfig, MessageHandler, XzeprConsumer};
-use super::filter::EventFilter;
+use super::consumer::{
+    CloudEventMessage, CloudEventValidationError, KafkaConsumerConfig, MessageHandler,
+    XzeprConsumer,
+};
 use super::plan_extractor::PlanExtractor;
-use crate::config::{Config, WatcherConfig};
+use crate::config::Config;
+use crate::trace_context::{plan_execution_span, ExecutionSpanContext, RemoteTraceContext};
+use crate::watcher::reload::LiveSettings;
+use crate::watcher::workspace::{ExecutionWorkspace, GitSeed};
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use std::sync::Arc;
 use thiserror::Error;
-use tokio::sync::Semaphore;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info, warn, Instrument};
 
 /// Errors that can occur in the XZepr watcher service.
 // Variants are defined for completeness and future use; the XZepr watcher
@@ -72,12 +81,9 @@ pub enum WatcherError {
 /// # }
 /// ```
 pub struct Watcher {
-    config: Arc<Config>,
-    watcher_config: WatcherConfig,
+    settings: Arc<LiveSettings>,
     consumer: XzeprConsumer,
-    filter: Arc<EventFilter>,
     extractor: Arc<PlanExtractor>,
-    execution_semaphore: Arc<Semaphore>,
     dry_run: bool,
 }
 
@@ -112,10 +118,8 @@ impl Watcher {
     /// # }
     /// ```
     pub fn new(config: Config, dry_run: bool) -> Result<Self> {
-        let watcher_config = config.watcher.clone();
-
         // Validate Kafka configuration exists
-        let kafka_config = watcher_config.kafka.as_ref().ok_or_else(|| {
+        let kafka_config = config.watcher.kafka.as_ref().ok_or_else(|| {
             WatcherError::Config("Kafka configuration is required for watcher".to_string())
         })?;
 
@@ -143,19 +147,15 @@ impl Watcher {
 
         debug!("Kafka consumer created successfully");
 
-        // Create event filter
-        let filter = Arc::new(
-            EventFilter::new(watcher_config.filters.clone())
-                .map_err(|e| WatcherError::Filter(e.to_string()))?,
-        );
+        let max_concurrent = config.watcher.execution.max_concurrent_executions;
+
+        // Create event filter and execution semaphore for concurrency control
+        let settings =
+            Arc::new(LiveSettings::new(config).map_err(|e| WatcherError::Filter(e.to_string()))?);
 
         // Create plan extractor with default strategies
         let extractor = Arc::new(PlanExtractor::new());
 
-        // Create execution semaphore for concurrency control
-        let max_concurrent = watcher_config.execution.max_concurrent_executions;
-        let execution_semaphore = Arc::new(Semaphore::new(max_concurrent));
-
         debug!(
             max_concurrent = max_concurrent,
             dry_run = dry_run,
@@ -163,16 +163,20 @@ impl Watcher {
         );
 
         Ok(Self {
-            config: Arc::new(config),
-            watcher_config,
+            settings,
             consumer,
-            filter,
             extractor,
-            execution_semaphore,
             dry_run,
         })
     }
 
+    /// Returns the settings a config reload swaps while the watcher runs
+    ///
+    /// Pass them to a [`crate::watcher::reload::ConfigReloader`].
+    pub fn live_settings(&self) -> Arc<LiveSettings> {
+        self.settings.clone()
+    }
+
     /// Start watching for and processing events from the Kafka topic.
     ///
     /// This is the main loop that consumes messages from Kafka. It will run
@@ -205,18 +209,15 @@ impl Watcher {
     /// ```
     pub async fn start(&mut self) -> Result<()> {
         info!(
-            filters = %self.filter.summary(),
+            filters = %self.settings.filter().summary(),
             dry_run = self.dry_run,
             "Starting XZepr watcher service"
         );
 
         // Create message handler with shared state
         let handler = WatcherMessageHandler {
-            config: self.config.clone(),
-            watcher_config: self.watcher_config.clone(),
-            filter: self.filter.clone(),
+            settings: self.settings.clone(),
             extractor: self.extractor.clone(),
-            execution_semaphore: self.execution_semaphore.clone(),
             dry_run: self.dry_run,
         };
 
@@ -310,11 +311,8 @@ impl Watcher {
 /// proper concurrency control and error handling.
 #[derive(Clone)]
 struct WatcherMessageHandler {
-    config: Arc<Config>,
-    watcher_config: WatcherConfig,
-    filter: Arc<EventFilter>,
+    settings: Arc<LiveSettings>,
     extractor: Arc<PlanExtractor>,
-    execution_semaphore: Arc<Semaphore>,
     dry_run: bool,
 }
 
@@ -333,12 +331,13 @@ impl MessageHandler for WatcherMessageHandler {
     ///
     /// # Processing Steps
     ///
-    /// 1. Check if event passes configured filters
-    /// 2. Extract plan from event payload
-    /// 3. Check for dry-run mode
-    /// 4. Acquire execution permit (respects concurrency limit)
-    /// 5. Execute plan in a spawned task
-    /// 6. Log results
+    /// 1. Reject events missing required CloudEvents attributes
+    /// 2. Check if event passes configured filters
+    /// 3. Extract plan from event payload
+    /// 4. Check for dry-run mode
+    /// 5. Acquire execution permit (respects concurrency limit)
+    /// 6. Execute plan in a spawned task
+    /// 7. Log results
     async fn handle(
         &self,
         message: CloudEventMessage,
@@ -354,8 +353,15 @@ impl MessageHandler for WatcherMessageHandler {
 
         debug!("Received CloudEvent message");
 
+        // Invalid events are returned as errors so the consumer routes them
+        // to the dead-letter path
+        if let Err(e) = message.validate() {
+            warn!(error = %e, "Rejecting invalid CloudEvent");
+            return Err(Box::new(e));
+        }
+
         // Apply event filters
-        if !self.filter.should_process(&message) {
+        if !self.settings.filter().should_process(&message) {
             debug!("Event filtered out by configured filters");
             return Ok(());
         }
@@ -386,7 +392,8 @@ impl MessageHandler for WatcherMessageHandler {
         }
 
         // Attempt to acquire execution permit (respects max concurrent executions)
-        let _permit = match self.execution_semaphore.acquire().await {
+        let executions = self.settings.executions();
+        let _permit = match executions.acquire().await {
             Ok(p) => p,
             Err(e) => {
                 error!(
@@ -400,24 +407,59 @@ impl MessageHandler for WatcherMessageHandler {
         debug!("Execution permit acquired, spawning plan execution task");
 
         // Clone values needed for the spawned task
-        let config = self.config.as_ref().clone();
-        let allow_dangerous = self.watcher_config.execution.allow_dangerous;
+        let config = self.settings.config().as_ref().clone();
+        let allow_dangerous = config.watcher.execution.allow_dangerous;
+        let workspace_seed = message
+            .data
+            .events
+            .first()
+            .and_then(|event| GitSeed::from_json(&event.payload));
+
+        // The execution span continues the trace of the triggering event
+        let span_context = ExecutionSpanContext {
+            event_id: Some(message.id.clone()),
+            plan_name: plan_name(&plan_yaml),
+        };
+        let remote_context = RemoteTraceContext::from_extensions(&message.extensions);
+        let execution_span = plan_execution_span(&span_context, remote_context.as_ref());
 
         // Spawn plan execution in background task
-        let execution_task = tokio::spawn(async move {
+        let execution = async move {
             debug!("Plan execution task started");
 
-            let result = crate::commands::r#run::run_plan_with_options(
-                config,
-                None,
-                Some(plan_yaml),
-                allow_dangerous,
-                None,
-            )
-            .await;
+            // Each execution runs in its own workspace so concurrent plans
+            // cannot interfere with each other's files
+            let workspace = ExecutionWorkspace::create(&config.watcher.execution)?;
+            let seeded = match &workspace_seed {
+                Some(seed) => workspace.seed(seed).await,
+                None => Ok(()),
+            };
+            let result = match seeded {
+                Ok(()) => {
+                    crate::commands::r#run::run_plan_in_working_dir(
+                        config,
+                        None,
+                        Some(plan_yaml),
+                        allow_dangerous,
+                        None,
+                        false,
+                        workspace.path(),
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+
+            if let Some(path) = workspace.finish(result.is_ok()) {
+                warn!(
+                    workspace = %path.display(),
+                    "Failed execution workspace retained for debugging"
+                );
+            }
 
             result
-        });
+        };
+        let execution_task = tokio::spawn(span_context.scope(execution.instrument(execution_span)));
 
         // Wait for execution to complete
         match execution_task.await {
@@ -443,6 +485,39 @@ impl MessageHandler for WatcherMessageHandler {
             }
         }
     }
+
+    /// Dead-letters events rejected by CloudEvents validation.
+    ///
+    /// The XZepr watcher has no dead-letter topic, so rejected events are
+    /// logged and counted, and their offsets may be committed. Other
+    /// failures are returned unchanged.
+    async fn dead_letter(
+        &self,
+        message: CloudEventMessage,
+        error: Box<dyn std::error::Error + Send + Sync>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if error.downcast_ref::<CloudEventValidationError>().is_none() {
+            return Err(error);
+        }
+
+        warn!(
+            event_id = %message.id,
+            event_type = %message.event_type,
+            error = %error,
+            "Dead-lettered invalid CloudEvent"
+        );
+        metrics::increment_counter!(
+            "xzepr_watcher_dead_lettered_events_total",
+            "reason" => "invalid_cloudevent"
+        );
+        Ok(())
+    }
+}
+
+/// Reads the top-level `name` of a YAML or JSON plan
+fn plan_name(plan: &str) -> Option<String> {
+    let value: serde_yaml::Value = serde_yaml::from_str(plan).ok()?;
+    value.get("name")?.as_str().map(str::to_string)
 }
 
 #[cfg(test)]
@@ -481,6 +556,9 @@ mod tests {
                 copilot: Default::default(),
                 ollama: Default::default(),
                 openai: Default::default(),
+                cache: Default::default(),
+                quirks: Default::default(),
+                rate_limits: Default::default(),
             },
             agent: crate::config::AgentConfig::default(),
             watcher: crate::config::WatcherConfig {
@@ -490,10 +568,13 @@ mod tests {
                 filters: Default::default(),
                 logging: Default::default(),
                 execution: Default::default(),
+                reload: Default::default(),
             },
             mcp: crate::mcp::config::McpConfig::default(),
             acp: crate::config::AcpConfig::default(),
             skills: crate::config::SkillsConfig::default(),
+            storage: crate::config::StorageConfig::default(),
+            telemetry: crate::config::TelemetryConfig::default(),
         };
 
         let result = Watcher::new(config, false);
@@ -517,10 +598,7 @@ mod tests {
         let result = Watcher::new(config, false);
         assert!(result.is_ok());
         let watcher = result.unwrap();
-        assert_eq!(
-            watcher.watcher_config.execution.max_concurrent_executions,
-            1
-        );
+        assert_eq!(watcher.live_settings().executions().available_permits(), 1);
         assert!(!watcher.dry_run);
     }
 
@@ -551,4 +629,60 @@ mod tests {
         assert_eq!(config.watcher.execution.max_concurrent_executions, 1);
         assert_eq!(config.watcher.execution.execution_timeout_secs, 300);
     }
+
+    fn test_handler() -> WatcherMessageHandler {
+        WatcherMessageHandler {
+            settings: Arc::new(LiveSettings::new(Config::default()).unwrap()),
+            extractor: Arc::new(PlanExtractor::new()),
+            dry_run: true,
+        }
+    }
+
+    fn event_without_source() -> CloudEventMessage {
+        serde_json::from_value(serde_json::json!({
+            "success": true,
+            "id": "evt-1",
+            "specversion": "1.0.1",
+            "type": "deployment.success",
+            "api_version": "v1",
+            "name": "deployment.success",
+            "version": "1.0.0",
+            "release": "1.0.0",
+            "platform_id": "kubernetes",
+            "package": "app",
+            "data": {}
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_invalid_event_is_rejected_and_dead_lettered() {
+        let handler = test_handler();
+
+        let error = handler.handle(event_without_source()).await.unwrap_err();
+        assert!(error.downcast_ref::<CloudEventValidationError>().is_some());
+        assert!(handler
+            .dead_letter(event_without_source(), error)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_returns_other_errors() {
+        let handler = test_handler();
+        let result = handler
+            .dead_letter(event_without_source(), "permit closed".into())
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_plan_name_reads_top_level_name() {
+        assert_eq!(
+            plan_name("name: deploy\nsteps: []\n").as_deref(),
+            Some("deploy")
+        );
+        assert_eq!(plan_name("- task: deploy\n"), None);
+        assert_eq!(plan_name(": not yaml"), None);
+    }
 }
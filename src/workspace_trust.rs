@@ -0,0 +1,729 @@
+//! Workspace trust for project-local configuration
+//!
+//! A freshly cloned repository can ship files that steer the agent: a
+//! `config/config.yaml` that enables full autonomy, project skills under
+//! `.xzatoma/skills/`, or MCP servers in `.mcp.json` that launch arbitrary
+//! commands. Before `chat`, `run`, `watch`, or `plan` start in a
+//! directory, the directory must be trusted.
+//!
+//! Trusted directories are recorded in a [`WorkspaceTrustStore`] together
+//! with a SHA-256 fingerprint of their project-local files. When those files
+//! change, the directory needs to be trusted again. A directory without
+//! project-local files has nothing to trust and passes the check.
+//!
+//! In an untrusted directory:
+//!
+//! - interactive sessions list the project-local files and ask whether to
+//!   trust the directory;
+//! - non-interactive sessions fail with [`XzatomaError::UntrustedWorkspace`];
+//! - when the user declines, project-local config, project skills, and
+//!   project MCP servers are ignored and the terminal execution mode is
+//!   capped at `Interactive`.
+//!
+//! # Examples
+//!
+//! ```
+//! use tempfile::tempdir;
+//! use xzatoma::workspace_trust::{TrustStatus, WorkspaceTrust, WorkspaceTrustStore};
+//!
+//! let workspace = tempdir()?;
+//! let store_dir = tempdir()?;
+//! let mut store = WorkspaceTrustStore::new(store_dir.path().join("workspace_trust.yaml"));
+//! let config_path = workspace.path().join("config/config.yaml");
+//! std::fs::create_dir_all(workspace.path().join("config"))?;
+//! std::fs::write(&config_path, "agent:\n  max_turns: 7\n")?;
+//!
+//! let trust = WorkspaceTrust::evaluate(&store, workspace.path(), &config_path)?;
+//! assert_eq!(trust.status(), TrustStatus::Untrusted);
+//!
+//! store.add(&trust)?;
+//! let trust = WorkspaceTrust::evaluate(&store, workspace.path(), &config_path)?;
+//! assert!(trust.is_trusted());
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::cli::{Cli, Commands};
+use crate::config::{Config, ExecutionMode};
+use crate::error::{Result, XzatomaError};
+use crate::mcp::project_config;
+use crate::paths::Paths;
+
+/// Environment variable that overrides the trust store location
+pub const TRUST_STORE_ENV: &str = "XZATOMA_WORKSPACE_TRUST_STORE";
+
+/// Directory under the workspace holding project skills
+const PROJECT_SKILLS_DIR: &str = ".xzatoma/skills";
+
+/// Whether a workspace is trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustStatus {
+    /// Trusted and its project-local files are unchanged
+    Trusted,
+    /// Never trusted
+    Untrusted,
+    /// Trusted before, but its project-local files changed since
+    Changed,
+}
+
+/// Serializable trust store data
+///
+/// Maps each trusted directory to the fingerprint of its project-local
+/// files at the time it was trusted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceTrustData {
+    /// Trusted directories and their fingerprints
+    #[serde(default)]
+    pub workspaces: BTreeMap<PathBuf, String>,
+}
+
+/// Persistent store of trusted workspace directories
+#[derive(Debug, Clone)]
+pub struct WorkspaceTrustStore {
+    path: PathBuf,
+    data: WorkspaceTrustData,
+}
+
+impl WorkspaceTrustStore {
+    /// Creates an empty store that saves to `path`
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            data: WorkspaceTrustData::default(),
+        }
+    }
+
+    /// Loads the store from `path`, or returns an empty store if it does not
+    /// exist yet
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Config` if the file cannot be read or parsed.
+    pub fn load_or_create(path: PathBuf) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::new(path));
+        }
+
+        let contents = fs::read_to_string(&path).map_err(|error| {
+            XzatomaError::Config(format!(
+                "Failed to read workspace trust store '{}': {}",
+                path.display(),
+                error
+            ))
+        })?;
+        let data: WorkspaceTrustData = serde_yaml::from_str(&contents).map_err(|error| {
+            XzatomaError::Config(format!(
+                "Failed to parse workspace trust store '{}': {}",
+                path.display(),
+                error
+            ))
+        })?;
+
+        Ok(Self { path, data })
+    }
+
+    /// Loads the store from [`default_trust_store_path`]
+    ///
+    /// The store is consulted before any configuration is read, so only the
+    /// `XZATOMA_DATA_DIR` override and the platform default apply.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path cannot be resolved or the store cannot be
+    /// loaded.
+    pub fn load_default() -> Result<Self> {
+        Self::load_or_create(default_trust_store_path(&Paths::from_env()?))
+    }
+
+    /// Writes the store to disk, creating its directory if needed
+    ///
+    /// # Errors
+    ///
+    /// Returns `XzatomaError::Config` if the store cannot be written.
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|error| {
+                XzatomaError::Config(format!(
+                    "Failed to create trust store directory '{}': {}",
+                    parent.display(),
+                    error
+                ))
+            })?;
+        }
+
+        let contents = serde_yaml::to_string(&self.data).map_err(|error| {
+            XzatomaError::Config(format!(
+                "Failed to serialize workspace trust store: {}",
+                error
+            ))
+        })?;
+        fs::write(&self.path, contents).map_err(|error| {
+            XzatomaError::Config(format!(
+                "Failed to write workspace trust store '{}': {}",
+                self.path.display(),
+                error
+            ))
+        })
+    }
+
+    /// Returns the store file path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the trusted directories and their fingerprints
+    pub fn workspaces(&self) -> &BTreeMap<PathBuf, String> {
+        &self.data.workspaces
+    }
+
+    /// Trusts the evaluated workspace with its current fingerprint and saves
+    /// the store
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store cannot be saved.
+    pub fn add(&mut self, trust: &WorkspaceTrust) -> Result<()> {
+        self.data
+            .workspaces
+            .insert(trust.working_dir.clone(), trust.fingerprint.clone());
+        self.save()
+    }
+
+    /// Removes trust for `dir` and saves the store
+    ///
+    /// # Returns
+    ///
+    /// Returns true if the directory was trusted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store cannot be saved.
+    pub fn remove(&mut self, dir: &Path) -> Result<bool> {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        let removed = self.data.workspaces.remove(&dir).is_some();
+        self.save()?;
+        Ok(removed)
+    }
+}
+
+/// Returns the default trust store path
+///
+/// `XZATOMA_WORKSPACE_TRUST_STORE` overrides the location; otherwise the
+/// store is [`Paths::workspace_trust_file`], which stays at
+/// `~/.xzatoma/workspace_trust.yaml` unless the data directory is set.
+pub fn default_trust_store_path(paths: &Paths) -> PathBuf {
+    match std::env::var(TRUST_STORE_ENV) {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => paths.workspace_trust_file(),
+    }
+}
+
+/// Trust evaluation of one workspace directory
+#[derive(Debug, Clone)]
+pub struct WorkspaceTrust {
+    working_dir: PathBuf,
+    config_path: PathBuf,
+    project_files: Vec<PathBuf>,
+    fingerprint: String,
+    status: TrustStatus,
+}
+
+impl WorkspaceTrust {
+    /// Evaluates `working_dir` against the trust store
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The trust store
+    /// * `working_dir` - The directory the command runs in
+    /// * `config_path` - The configuration file path; it counts as
+    ///   project-local when it lies inside `working_dir`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the project-local files cannot be read.
+    pub fn evaluate(
+        store: &WorkspaceTrustStore,
+        working_dir: &Path,
+        config_path: &Path,
+    ) -> Result<Self> {
+        let working_dir = working_dir.canonicalize()?;
+        let config_path = if config_path.is_absolute() {
+            config_path.to_path_buf()
+        } else {
+            working_dir.join(config_path)
+        };
+        let project_files = project_local_files(&working_dir, &config_path);
+        let fingerprint = fingerprint(&working_dir, &project_files)?;
+        let status = match store.workspaces().get(&working_dir) {
+            _ if project_files.is_empty() => TrustStatus::Trusted,
+            Some(trusted) if *trusted == fingerprint => TrustStatus::Trusted,
+            Some(_) => TrustStatus::Changed,
+            None => TrustStatus::Untrusted,
+        };
+
+        Ok(Self {
+            working_dir,
+            config_path,
+            project_files,
+            fingerprint,
+            status,
+        })
+    }
+
+    /// Returns the trust status
+    pub fn status(&self) -> TrustStatus {
+        self.status
+    }
+
+    /// Returns true when the workspace is trusted and unchanged
+    pub fn is_trusted(&self) -> bool {
+        self.status == TrustStatus::Trusted
+    }
+
+    /// Returns the canonical workspace directory
+    pub fn working_dir(&self) -> &Path {
+        &self.working_dir
+    }
+
+    /// Returns the project-local files that would be loaded
+    pub fn project_files(&self) -> &[PathBuf] {
+        &self.project_files
+    }
+
+    /// Returns true when the configuration file lives inside the workspace
+    pub fn config_is_project_local(&self) -> bool {
+        self.config_path.starts_with(&self.working_dir)
+    }
+
+    /// Marks the evaluation trusted after the store was updated
+    fn mark_trusted(&mut self) {
+        self.status = TrustStatus::Trusted;
+    }
+}
+
+/// Lists the project-local files in `working_dir` that xzatoma would load
+fn project_local_files(working_dir: &Path, config_path: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if config_path.starts_with(working_dir) && config_path.is_file() {
+        files.push(config_path.to_path_buf());
+    }
+    let skills_dir = working_dir.join(PROJECT_SKILLS_DIR);
+    if skills_dir.is_dir() {
+        files.push(skills_dir);
+    }
+    files.extend(project_config::project_mcp_files(working_dir));
+    files
+}
+
+/// Hashes the relative path and contents of every project-local file
+fn fingerprint(working_dir: &Path, project_files: &[PathBuf]) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut pending: Vec<PathBuf> = project_files.to_vec();
+    let mut files = Vec::new();
+    while let Some(path) = pending.pop() {
+        if path.is_dir() {
+            for entry in fs::read_dir(&path)? {
+                pending.push(entry?.path());
+            }
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    for file in files {
+        let relative = file.strip_prefix(working_dir).unwrap_or(&file);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(fs::read(&file)?);
+        hasher.update([0]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Returns true for commands that run the agent in the current directory
+///
+/// `run --validate-only` only parses a plan, and `run rollback` only
+/// restores files. ACP agents are started by their client, which applies its
+/// own permission flow, so `agent` and `acp` are not checked.
+pub fn requires_trust(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Chat { .. }
+            | Commands::Run {
+                validate_only: false,
+                command: None,
+                ..
+            }
+            | Commands::Watch { .. }
+            | Commands::Plan { .. }
+    )
+}
+
+/// Checks the workspace before a command runs, asking the user when needed
+///
+/// # Arguments
+///
+/// * `store` - The trust store; updated when the user trusts the workspace
+/// * `working_dir` - The directory the command runs in
+/// * `config_path` - The configuration file path
+/// * `interactive` - Whether the user can be asked on the terminal
+///
+/// # Returns
+///
+/// Returns the evaluation. It is untrusted only when the user declined.
+///
+/// # Errors
+///
+/// Returns `XzatomaError::UntrustedWorkspace` when the workspace is not
+/// trusted and `interactive` is false.
+pub fn ensure_trusted(
+    store: &mut WorkspaceTrustStore,
+    working_dir: &Path,
+    config_path: &Path,
+    interactive: bool,
+) -> Result<WorkspaceTrust> {
+    let mut trust = WorkspaceTrust::evaluate(store, working_dir, config_path)?;
+    if trust.is_trusted() {
+        return Ok(trust);
+    }
+
+    if !interactive {
+        return Err(XzatomaError::UntrustedWorkspace(describe_untrusted(&trust)));
+    }
+
+    eprintln!("{}", describe_untrusted(&trust));
+    if trust.project_files.is_empty() {
+        eprintln!("No project-local files would be loaded.");
+    } else {
+        eprintln!("These project-local files would be loaded:");
+        for file in &trust.project_files {
+            eprintln!("  {}", file.display());
+        }
+    }
+    eprint!("Trust this directory? [y/N] ");
+    let _ = std::io::stderr().flush();
+
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    let answer = line.trim().to_lowercase();
+    if answer == "y" || answer == "yes" {
+        store.add(&trust)?;
+        trust.mark_trusted();
+    } else {
+        eprintln!(
+            "Continuing untrusted: project-local config, skills, and MCP servers are ignored and terminal commands need confirmation."
+        );
+    }
+    Ok(trust)
+}
+
+fn describe_untrusted(trust: &WorkspaceTrust) -> String {
+    match trust.status {
+        TrustStatus::Changed => format!(
+            "{} (its project-local files changed since it was trusted)",
+            trust.working_dir.display()
+        ),
+        _ => trust.working_dir.display().to_string(),
+    }
+}
+
+/// Loads the configuration for an evaluated workspace
+///
+/// A trusted workspace loads `config_path` as usual and merges the servers
+/// of its project MCP files. An untrusted workspace skips the file when it
+/// is project-local, loads no project MCP servers, and then applies
+/// [`restrict_untrusted`].
+///
+/// # Errors
+///
+/// Returns an error if the configuration cannot be loaded.
+pub fn load_config(config_path: &str, cli: &Cli, trust: &WorkspaceTrust) -> Result<Config> {
+    if trust.is_trusted() {
+        let mut config = Config::load(config_path, cli)?;
+        project_config::merge_project_servers(&mut config.mcp, trust.working_dir());
+        return Ok(config);
+    }
+
+    if !project_config::project_mcp_files(trust.working_dir()).is_empty() {
+        tracing::warn!("Ignoring project MCP servers in an untrusted workspace");
+    }
+
+    let mut config = if trust.config_is_project_local() {
+        tracing::warn!(
+            config = config_path,
+            "Ignoring project-local config in an untrusted workspace"
+        );
+        Config::load_without_file(cli)
+    } else {
+        Config::load(config_path, cli)?
+    };
+    restrict_untrusted(&mut config);
+    Ok(config)
+}
+
+/// Loads the configuration again for a command that is already running
+///
+/// The workspace is evaluated again without asking. A project-local config
+/// file that changed since it was trusted is refused rather than skipped,
+/// since skipping it would silently fall back to the defaults.
+///
+/// # Arguments
+///
+/// * `store` - The trust store
+/// * `working_dir` - The directory the command runs in
+/// * `config_path` - The configuration file path
+/// * `cli` - CLI arguments for overrides
+///
+/// # Errors
+///
+/// Returns `XzatomaError::UntrustedWorkspace` when the config file is
+/// project-local and not trusted, or an error if it cannot be loaded.
+pub fn reload_config(
+    store: &WorkspaceTrustStore,
+    working_dir: &Path,
+    config_path: &str,
+    cli: &Cli,
+) -> Result<Config> {
+    let trust = WorkspaceTrust::evaluate(store, working_dir, Path::new(config_path))?;
+    if trust.config_is_project_local() && !trust.is_trusted() {
+        return Err(XzatomaError::UntrustedWorkspace(describe_untrusted(&trust)));
+    }
+    load_config(config_path, cli, &trust)
+}
+
+/// Applies the limits for an untrusted workspace
+///
+/// Project skills are disabled and the terminal execution mode is capped at
+/// `Interactive`, so every command needs confirmation.
+pub fn restrict_untrusted(config: &mut Config) {
+    if config.agent.terminal.default_mode != ExecutionMode::Interactive {
+        tracing::warn!(
+            mode = ?config.agent.terminal.default_mode,
+            "Capping terminal execution mode at Interactive in an untrusted workspace"
+        );
+        config.agent.terminal.default_mode = ExecutionMode::Interactive;
+    }
+    config.skills.project_enabled = false;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    fn store(dir: &TempDir) -> WorkspaceTrustStore {
+        WorkspaceTrustStore::new(dir.path().join("workspace_trust.yaml"))
+    }
+
+    fn workspace_with_config() -> (TempDir, PathBuf) {
+        let workspace = TempDir::new().unwrap();
+        let config_path = workspace.path().join("config/config.yaml");
+        fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        fs::write(
+            &config_path,
+            "agent:\n  max_turns: 7\n  terminal:\n    default_mode: full_autonomous\n",
+        )
+        .unwrap();
+        (workspace, config_path)
+    }
+
+    fn cli() -> Cli {
+        Cli::parse_from(["xzatoma", "chat"])
+    }
+
+    #[test]
+    fn test_untrusted_workspace_lists_project_files() {
+        let (workspace, config_path) = workspace_with_config();
+        fs::create_dir_all(workspace.path().join(PROJECT_SKILLS_DIR)).unwrap();
+        let store_dir = TempDir::new().unwrap();
+
+        let trust =
+            WorkspaceTrust::evaluate(&store(&store_dir), workspace.path(), &config_path).unwrap();
+
+        assert_eq!(trust.status(), TrustStatus::Untrusted);
+        assert_eq!(trust.project_files().len(), 2);
+        assert!(trust.config_is_project_local());
+    }
+
+    #[test]
+    fn test_trust_survives_reload_and_resets_on_change() {
+        let (workspace, config_path) = workspace_with_config();
+        let store_dir = TempDir::new().unwrap();
+        let mut store = store(&store_dir);
+
+        let trust = WorkspaceTrust::evaluate(&store, workspace.path(), &config_path).unwrap();
+        store.add(&trust).unwrap();
+
+        let reloaded = WorkspaceTrustStore::load_or_create(store.path().to_path_buf()).unwrap();
+        let trust = WorkspaceTrust::evaluate(&reloaded, workspace.path(), &config_path).unwrap();
+        assert!(trust.is_trusted());
+
+        fs::write(&config_path, "agent:\n  max_turns: 99\n").unwrap();
+        let trust = WorkspaceTrust::evaluate(&reloaded, workspace.path(), &config_path).unwrap();
+        assert_eq!(trust.status(), TrustStatus::Changed);
+    }
+
+    #[test]
+    fn test_non_interactive_untrusted_workspace_fails() {
+        let (workspace, config_path) = workspace_with_config();
+        let store_dir = TempDir::new().unwrap();
+
+        let error = ensure_trusted(
+            &mut store(&store_dir),
+            workspace.path(),
+            &config_path,
+            false,
+        )
+        .unwrap_err();
+
+        assert!(matches!(error, XzatomaError::UntrustedWorkspace(_)));
+        assert!(error.user_message().contains("xzatoma trust add ."));
+    }
+
+    #[test]
+    fn test_untrusted_workspace_skips_project_config_and_caps_mode() {
+        let (workspace, config_path) = workspace_with_config();
+        let store_dir = TempDir::new().unwrap();
+        let trust =
+            WorkspaceTrust::evaluate(&store(&store_dir), workspace.path(), &config_path).unwrap();
+
+        let config = load_config(config_path.to_str().unwrap(), &cli(), &trust).unwrap();
+
+        assert_ne!(config.agent.max_turns, 7, "project config must be skipped");
+        assert_eq!(
+            config.agent.terminal.default_mode,
+            ExecutionMode::Interactive
+        );
+        assert!(!config.skills.project_enabled);
+    }
+
+    #[test]
+    fn test_trusted_workspace_loads_project_config() {
+        let (workspace, config_path) = workspace_with_config();
+        let store_dir = TempDir::new().unwrap();
+        let mut store = store(&store_dir);
+        let trust = WorkspaceTrust::evaluate(&store, workspace.path(), &config_path).unwrap();
+        store.add(&trust).unwrap();
+        let trust = WorkspaceTrust::evaluate(&store, workspace.path(), &config_path).unwrap();
+
+        let config = load_config(config_path.to_str().unwrap(), &cli(), &trust).unwrap();
+
+        assert_eq!(config.agent.max_turns, 7);
+        assert_eq!(
+            config.agent.terminal.default_mode,
+            ExecutionMode::FullAutonomous
+        );
+    }
+
+    #[test]
+    fn test_project_mcp_servers_load_only_when_trusted() {
+        let workspace = TempDir::new().unwrap();
+        fs::write(
+            workspace.path().join(".mcp.json"),
+            r#"{ "mcpServers": { "git": { "command": "uvx", "args": ["mcp-server-git"] } } }"#,
+        )
+        .unwrap();
+        let store_dir = TempDir::new().unwrap();
+        let mut store = store(&store_dir);
+        let config_path = store_dir.path().join("config.yaml");
+        let path = config_path.to_str().unwrap();
+
+        let trust = WorkspaceTrust::evaluate(&store, workspace.path(), &config_path).unwrap();
+        assert_eq!(trust.status(), TrustStatus::Untrusted);
+        assert!(load_config(path, &cli(), &trust)
+            .unwrap()
+            .mcp
+            .servers
+            .is_empty());
+
+        store.add(&trust).unwrap();
+        let trust = WorkspaceTrust::evaluate(&store, workspace.path(), &config_path).unwrap();
+        let config = load_config(path, &cli(), &trust).unwrap();
+        assert_eq!(config.mcp.servers[0].id, "git");
+        assert_eq!(config.mcp.server_origin("git"), "project (.mcp.json)");
+
+        let no_project_mcp = Cli::parse_from(["xzatoma", "chat", "--no-project-mcp"]);
+        let config = load_config(path, &no_project_mcp, &trust).unwrap();
+        assert!(config.mcp.servers.is_empty());
+    }
+
+    #[test]
+    fn test_reload_refuses_changed_project_config() {
+        let (workspace, config_path) = workspace_with_config();
+        let store_dir = TempDir::new().unwrap();
+        let mut store = store(&store_dir);
+        let trust = WorkspaceTrust::evaluate(&store, workspace.path(), &config_path).unwrap();
+        store.add(&trust).unwrap();
+        let path = config_path.to_str().unwrap();
+
+        let config = reload_config(&store, workspace.path(), path, &cli()).unwrap();
+        assert_eq!(config.agent.max_turns, 7);
+
+        fs::write(&config_path, "agent:\n  max_turns: 99\n").unwrap();
+        let error = reload_config(&store, workspace.path(), path, &cli()).unwrap_err();
+        assert!(matches!(error, XzatomaError::UntrustedWorkspace(_)));
+    }
+
+    #[test]
+    fn test_restrict_untrusted_caps_every_autonomous_mode() {
+        for mode in [
+            ExecutionMode::RestrictedAutonomous,
+            ExecutionMode::FullAutonomous,
+            ExecutionMode::Interactive,
+        ] {
+            let mut config = Config::default();
+            config.agent.terminal.default_mode = mode;
+            restrict_untrusted(&mut config);
+            assert_eq!(
+                config.agent.terminal.default_mode,
+                ExecutionMode::Interactive
+            );
+        }
+    }
+
+    #[test]
+    fn test_workspace_without_project_files_needs_no_trust() {
+        let workspace = TempDir::new().unwrap();
+        let store_dir = TempDir::new().unwrap();
+        let outside_config = store_dir.path().join("config.yaml");
+        fs::write(&outside_config, "agent:\n  max_turns: 7\n").unwrap();
+
+        let trust = ensure_trusted(
+            &mut store(&store_dir),
+            workspace.path(),
+            &outside_config,
+            false,
+        )
+        .unwrap();
+
+        assert!(trust.is_trusted());
+        assert!(!trust.config_is_project_local());
+    }
+
+    #[test]
+    fn test_remove_forgets_workspace() {
+        let workspace = TempDir::new().unwrap();
+        let store_dir = TempDir::new().unwrap();
+        let mut store = store(&store_dir);
+        let config_path = workspace.path().join("config/config.yaml");
+        let trust = WorkspaceTrust::evaluate(&store, workspace.path(), &config_path).unwrap();
+        store.add(&trust).unwrap();
+
+        assert!(store.remove(workspace.path()).unwrap());
+        assert!(!store.remove(workspace.path()).unwrap());
+        assert!(store.workspaces().is_empty());
+    }
+}
@@ -1,71 +1,20 @@
-use async_trait::async_trait;
 use std::time::Duration;
-mod common;
 use tokio::time::sleep;
 use uuid::Uuid;
 
 use xzatoma::agent::Agent;
 use xzatoma::config::AgentConfig;
-use xzatoma::providers::{CompletionResponse, Message, Provider};
+use xzatoma::providers::Message;
+use xzatoma::storage::SqliteStorage;
+use xzatoma::testing::MockProvider;
 use xzatoma::tools::ToolRegistry;
 
-/// Simple mock provider that returns predetermined messages (in order).
-#[derive(Clone)]
-struct MockProvider {
-    responses: Vec<Message>,
-    idx: std::sync::Arc<std::sync::Mutex<usize>>,
-}
-
-impl MockProvider {
-    fn new(responses: Vec<Message>) -> Self {
-        Self {
-            responses,
-            idx: std::sync::Arc::new(std::sync::Mutex::new(0)),
-        }
-    }
-}
-
-#[async_trait]
-impl Provider for MockProvider {
-    fn is_authenticated(&self) -> bool {
-        false
-    }
-
-    fn current_model(&self) -> Option<&str> {
-        None
-    }
-
-    fn set_model(&mut self, _model: &str) {}
-
-    async fn fetch_models(&self) -> xzatoma::error::Result<Vec<xzatoma::providers::ModelInfo>> {
-        Err(xzatoma::error::XzatomaError::Provider(
-            "not supported".to_string(),
-        ))
-    }
-
-    async fn complete(
-        &self,
-        _messages: &[Message],
-        _tools: &[serde_json::Value],
-    ) -> xzatoma::error::Result<CompletionResponse> {
-        let mut lock = self.idx.lock().unwrap();
-        let i = *lock;
-        *lock = i + 1;
-        let msg = if i < self.responses.len() {
-            self.responses[i].clone()
-        } else {
-            Message::assistant("Done")
-        };
-        Ok(CompletionResponse::new(msg))
-    }
-}
-
 #[tokio::test]
 async fn test_conversation_auto_saves_after_message() {
-    let (storage, _tmp) = common::create_temp_storage();
+    let storage = SqliteStorage::new_in_memory().expect("in-memory storage");
 
     // Provider returns a single assistant message
-    let provider = MockProvider::new(vec![Message::assistant("Hello from provider")]);
+    let provider = MockProvider::new().then_text("Hello from provider");
     let tools = ToolRegistry::new();
     let config = AgentConfig::default();
     let mut agent = Agent::new(provider, tools, config).expect("create agent");
@@ -109,7 +58,7 @@ async fn test_conversation_auto_saves_after_message() {
 
 #[tokio::test]
 async fn test_resume_loads_conversation_history() {
-    let (storage, _tmp) = common::create_temp_storage();
+    let storage = SqliteStorage::new_in_memory().expect("in-memory storage");
 
     let id = Uuid::new_v4().to_string();
     let title = "Saved session";
@@ -143,7 +92,7 @@ async fn test_resume_loads_conversation_history() {
 
 #[tokio::test]
 async fn test_resume_invalid_id_starts_new() {
-    let (storage, _tmp) = common::create_temp_storage();
+    let storage = SqliteStorage::new_in_memory().expect("in-memory storage");
 
     // Use a random id which was not saved
     let random_id = Uuid::new_v4().to_string();
@@ -151,7 +100,7 @@ async fn test_resume_invalid_id_starts_new() {
     assert!(loaded.is_none(), "expected no conversation for random id");
 
     // In application flow, this would cause a new Agent to be created. Ensure new agent starts empty.
-    let provider = MockProvider::new(vec![Message::assistant("Hi")]);
+    let provider = MockProvider::new().then_text("Hi");
     let tools = ToolRegistry::new();
     let config = AgentConfig::default();
     let agent = Agent::new(provider, tools, config).expect("create agent");
@@ -160,9 +109,9 @@ async fn test_resume_invalid_id_starts_new() {
 
 #[tokio::test]
 async fn test_title_generated_from_first_user_message() {
-    let (storage, _tmp) = common::create_temp_storage();
+    let storage = SqliteStorage::new_in_memory().expect("in-memory storage");
 
-    let provider = MockProvider::new(vec![Message::assistant("Reply")]);
+    let provider = MockProvider::new().then_text("Reply");
     let tools = ToolRegistry::new();
     let config = AgentConfig::default();
     let mut agent = Agent::new(provider, tools, config).expect("agent");
@@ -193,9 +142,9 @@ async fn test_title_generated_from_first_user_message() {
 
 #[tokio::test]
 async fn test_title_truncates_long_first_message() {
-    let (storage, _tmp) = common::create_temp_storage();
+    let storage = SqliteStorage::new_in_memory().expect("in-memory storage");
 
-    let provider = MockProvider::new(vec![Message::assistant("Reply")]);
+    let provider = MockProvider::new().then_text("Reply");
     let tools = ToolRegistry::new();
     let config = AgentConfig::default();
     let mut agent = Agent::new(provider, tools, config).expect("agent");
@@ -224,7 +173,7 @@ async fn test_title_truncates_long_first_message() {
 
 #[tokio::test]
 async fn test_history_list_displays_sessions() {
-    let (storage, _tmp) = common::create_temp_storage();
+    let storage = SqliteStorage::new_in_memory().expect("in-memory storage");
 
     let id1 = Uuid::new_v4().to_string();
     let id2 = Uuid::new_v4().to_string();
@@ -263,7 +212,7 @@ async fn test_history_list_displays_sessions() {
 
 #[tokio::test]
 async fn test_history_delete_removes_session() {
-    let (storage, _tmp) = common::create_temp_storage();
+    let storage = SqliteStorage::new_in_memory().expect("in-memory storage");
 
     let id = Uuid::new_v4().to_string();
     storage
@@ -5,6 +5,7 @@ use wiremock::matchers::{header, method, path};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
 use xzatoma::config::CopilotConfig;
+use xzatoma::error::XzatomaError;
 use xzatoma::providers::CopilotProvider;
 
 /// Helper to check if keychain tests should run
@@ -165,3 +166,272 @@ async fn test_copilot_models_caching_ttl() {
         .unwrap();
     assert_eq!(models1.len(), models2.len());
 }
+
+/// Models response with a single enabled model, shared by the refresh tests
+fn single_model_body() -> serde_json::Value {
+    json!({
+        "data": [{
+            "id": "gpt-5.3-codex",
+            "name": "gpt-5.3-codex",
+            "capabilities": {
+                "limits": { "max_context_window_tokens": 264000 },
+                "supports": { "tool_calls": true, "vision": false }
+            },
+            "policy": { "state": "enabled" }
+        }]
+    })
+}
+
+/// Seed the keyring with a GitHub token and a Copilot token expiring at `expires_at`
+fn seed_keyring(copilot_token: &str, expires_at: u64) {
+    let cached = json!({
+        "github_token": "gho_cached",
+        "copilot_token": copilot_token,
+        "expires_at": expires_at
+    });
+    let entry = keyring::Entry::new("xzatoma", "github_copilot").unwrap();
+    entry.set_password(&cached.to_string()).unwrap();
+}
+
+fn cached_keyring_token() -> serde_json::Value {
+    let entry = keyring::Entry::new("xzatoma", "github_copilot").unwrap();
+    serde_json::from_str(&entry.get_password().unwrap()).unwrap()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// A token close to expiry is refreshed before the request is sent
+#[tokio::test]
+#[ignore = "requires system keyring; enable with XZATOMA_RUN_KEYCHAIN_TESTS=1"]
+async fn test_copilot_refreshes_token_before_expiry() {
+    if !should_run_keychain_tests() {
+        println!("Skipping keychain test. Enable with: XZATOMA_RUN_KEYCHAIN_TESTS=1 cargo test -- --ignored");
+        return;
+    }
+    let server = MockServer::start().await;
+    let provider = CopilotProvider::new(CopilotConfig {
+        api_base: Some(server.uri()),
+        ..Default::default()
+    })
+    .unwrap();
+
+    // Expires within the default 300 second refresh margin
+    seed_keyring("expiring_token", unix_now() + 60);
+    let new_expiry = unix_now() + 1800;
+
+    Mock::given(method("GET"))
+        .and(path("/copilot_internal/v2/token"))
+        .and(header("authorization", "token gho_cached"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "token": "fresh_token",
+            "expires_at": new_expiry
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/models"))
+        .and(header("authorization", "Bearer expiring_token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(single_model_body()))
+        .expect(0)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/models"))
+        .and(header("authorization", "Bearer fresh_token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(single_model_body()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let models =
+        <xzatoma::providers::CopilotProvider as xzatoma::providers::Provider>::list_models(
+            &provider,
+        )
+        .await
+        .unwrap();
+    assert_eq!(models.len(), 1);
+
+    let cached = cached_keyring_token();
+    assert_eq!(cached["copilot_token"], "fresh_token");
+    assert_eq!(cached["expires_at"], new_expiry);
+}
+
+/// An expired token is refreshed after a 401 and the request is retried once
+#[tokio::test]
+#[ignore = "requires system keyring; enable with XZATOMA_RUN_KEYCHAIN_TESTS=1"]
+async fn test_copilot_401_refreshes_expired_token_and_retries() {
+    if !should_run_keychain_tests() {
+        println!("Skipping keychain test. Enable with: XZATOMA_RUN_KEYCHAIN_TESTS=1 cargo test -- --ignored");
+        return;
+    }
+    let server = MockServer::start().await;
+    let provider = CopilotProvider::new(CopilotConfig {
+        api_base: Some(server.uri()),
+        ..Default::default()
+    })
+    .unwrap();
+
+    // The cached expiry looks valid, but the server has already expired the token
+    seed_keyring("expired_token", unix_now() + 3600);
+
+    Mock::given(method("GET"))
+        .and(path("/models"))
+        .and(header("authorization", "Bearer expired_token"))
+        .respond_with(ResponseTemplate::new(401).set_body_string("token expired"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/copilot_internal/v2/token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "token": "fresh_token",
+            "expires_at": unix_now() + 1800
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/models"))
+        .and(header("authorization", "Bearer fresh_token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(single_model_body()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let models =
+        <xzatoma::providers::CopilotProvider as xzatoma::providers::Provider>::list_models(
+            &provider,
+        )
+        .await
+        .unwrap();
+    assert_eq!(models.len(), 1);
+    assert_eq!(cached_keyring_token()["copilot_token"], "fresh_token");
+}
+
+/// A revoked token fails with an auth error after exactly one refresh attempt
+#[tokio::test]
+#[ignore = "requires system keyring; enable with XZATOMA_RUN_KEYCHAIN_TESTS=1"]
+async fn test_copilot_401_after_refresh_returns_auth_error() {
+    if !should_run_keychain_tests() {
+        println!("Skipping keychain test. Enable with: XZATOMA_RUN_KEYCHAIN_TESTS=1 cargo test -- --ignored");
+        return;
+    }
+    let server = MockServer::start().await;
+    let provider = CopilotProvider::new(CopilotConfig {
+        api_base: Some(server.uri()),
+        ..Default::default()
+    })
+    .unwrap();
+
+    seed_keyring("revoked_token", unix_now() + 3600);
+
+    Mock::given(method("GET"))
+        .and(path("/models"))
+        .respond_with(ResponseTemplate::new(401).set_body_string("access revoked"))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/copilot_internal/v2/token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "token": "still_revoked",
+            "expires_at": unix_now() + 1800
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let err = <xzatoma::providers::CopilotProvider as xzatoma::providers::Provider>::list_models(
+        &provider,
+    )
+    .await
+    .unwrap_err();
+
+    match err {
+        XzatomaError::Auth { provider, reason } => {
+            assert_eq!(provider, "copilot");
+            assert!(
+                reason.contains("xzatoma auth --provider copilot"),
+                "{}",
+                reason
+            );
+        }
+        other => panic!("Expected Auth error, got {:?}", other),
+    }
+}
+
+/// Image parts reach /chat/completions as an OpenAI-style content array
+#[tokio::test]
+#[ignore = "requires system keyring; enable with XZATOMA_RUN_KEYCHAIN_TESTS=1"]
+async fn test_copilot_sends_image_content_array() {
+    use wiremock::matchers::body_partial_json;
+    use xzatoma::providers::{
+        ImagePromptPart, Message, MultimodalPromptInput, PromptInputPart, Provider,
+    };
+
+    if !should_run_keychain_tests() {
+        println!("Skipping keychain test. Enable with: XZATOMA_RUN_KEYCHAIN_TESTS=1 cargo test -- --ignored");
+        return;
+    }
+    let server = MockServer::start().await;
+    let provider = CopilotProvider::new(CopilotConfig {
+        model: "gpt-4o".to_string(),
+        api_base: Some(server.uri()),
+        enable_streaming: false,
+        ..Default::default()
+    })
+    .unwrap();
+    seed_keyring("valid_token", unix_now() + 3600);
+
+    Mock::given(method("GET"))
+        .and(path("/models"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [{
+                "id": "gpt-4o",
+                "name": "gpt-4o",
+                "capabilities": { "supports": { "vision": true } },
+                "policy": { "state": "enabled" }
+            }]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({
+            "model": "gpt-4o",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "What is this?" },
+                    {
+                        "type": "image_url",
+                        "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" }
+                    }
+                ]
+            }]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "message": { "role": "assistant", "content": "A logo" },
+                "finish_reason": "stop"
+            }]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let message = Message::try_user_from_multimodal_input(MultimodalPromptInput::new(vec![
+        PromptInputPart::text("What is this?"),
+        PromptInputPart::image(ImagePromptPart::inline_base64("image/png", "iVBORw0KGgo=")),
+    ]))
+    .unwrap();
+
+    let response = provider.complete(&[message], &[]).await.unwrap();
+    assert_eq!(response.message.content.as_deref(), Some("A logo"));
+}
@@ -104,7 +104,10 @@ fn mention_type_str(mention: &Mention) -> &'static str {
         Mention::File(_) => "file",
         Mention::Search(_) => "search",
         Mention::Grep(_) => "grep",
+        Mention::Semantic(_) => "semantic",
         Mention::Url(_) => "url",
+        Mention::Image(_) => "image",
+        Mention::Diff(_) => "diff",
     }
 }
 
@@ -222,9 +225,9 @@ async fn run_augment(scenario: &Scenario) -> Result<(), String> {
     for (dest, source) in &scenario.input.working_dir_files {
         let dest_path = temp_dir.path().join(dest);
         if source == "__binary__" {
-            // Write programmatic binary content.  Null bytes are valid UTF-8
-            // so read_to_string succeeds, but the binary-detection check in
-            // load_file_content rejects the file with a FileBinary error.
+            // Write programmatic binary content.  The null byte trips the
+            // binary-detection check in load_file_content, which rejects the
+            // file with a FileBinary error.
             fs::write(&dest_path, b"test\x00binary")
                 .map_err(|e| format!("failed to write binary fixture '{}': {}", dest, e))?;
         } else {
@@ -240,13 +243,13 @@ async fn run_augment(scenario: &Scenario) -> Result<(), String> {
         .map_err(|e| format!("parse_mentions failed: {}", e))?;
 
     // Augment the prompt with content resolved from the temporary directory.
-    let mut cache = MentionCache::new();
+    let cache = MentionCache::new();
     let (augmented, errors, _successes) = augment_prompt_with_mentions(
         &mentions,
         &scenario.input.prompt,
         temp_dir.path(),
         1024 * 1024,
-        &mut cache,
+        &cache,
     )
     .await;
 
@@ -313,8 +313,9 @@ mod tests {
             default_max_turns: 10,
             output_max_size: 4096,
             telemetry_enabled: true,
-            persistence_enabled: false,
-            persistence_path: "/tmp/db.sled".to_string(),
+            persistence: None,
+            persistence_enabled: None,
+            persistence_path: None,
             max_executions: Some(5),
             max_total_tokens: Some(50000),
             max_total_time: Some(300),
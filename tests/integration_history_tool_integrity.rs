@@ -5,66 +5,26 @@
 //! - Valid tool call pairs are preserved through persistence
 //! - Pruning maintains integrity during resume with tool pairs
 
-use async_trait::async_trait;
-use std::sync::{Arc, Mutex};
 mod common;
 
 use xzatoma::agent::{Agent, Conversation};
 use xzatoma::config::AgentConfig;
-use xzatoma::providers::{CompletionResponse, Message, Provider, ToolCall};
+use xzatoma::providers::{CompletionResponse, Message, ToolCall};
+use xzatoma::testing::MockProvider;
 use xzatoma::tools::ToolRegistry;
 
-/// Mock provider that tracks all messages received during completion
-#[derive(Clone)]
-struct TrackingMockProvider {
-    response: Message,
-    last_messages: Arc<Mutex<Vec<Message>>>,
+/// Mock provider that answers every request with `response`
+fn tracking_provider(response: Message) -> MockProvider {
+    MockProvider::new().with_fallback(CompletionResponse::new(response))
 }
 
-impl TrackingMockProvider {
-    /// Create a new mock provider with a response message
-    fn new(response: Message) -> Self {
-        Self {
-            response,
-            last_messages: Arc::new(Mutex::new(Vec::new())),
-        }
-    }
-
-    /// Get the last messages that were passed to complete()
-    fn last_messages(&self) -> Vec<Message> {
-        self.last_messages.lock().unwrap().clone()
-    }
-}
-
-#[async_trait]
-impl Provider for TrackingMockProvider {
-    fn is_authenticated(&self) -> bool {
-        false
-    }
-
-    fn current_model(&self) -> Option<&str> {
-        None
-    }
-
-    fn set_model(&mut self, _model: &str) {}
-
-    async fn fetch_models(&self) -> xzatoma::error::Result<Vec<xzatoma::providers::ModelInfo>> {
-        Err(xzatoma::error::XzatomaError::Provider(
-            "not supported".to_string(),
-        ))
-    }
-
-    async fn complete(
-        &self,
-        messages: &[Message],
-        _tools: &[serde_json::Value],
-    ) -> xzatoma::error::Result<CompletionResponse> {
-        // Validate messages like real providers do (removing orphan tool messages)
-        let validated_messages = xzatoma::providers::validate_message_sequence(messages);
-        // Track the sanitized messages we received
-        *self.last_messages.lock().unwrap() = validated_messages;
-        Ok(CompletionResponse::new(self.response.clone()))
-    }
+/// Messages of the last request, validated like real providers do (removing
+/// orphan tool messages)
+fn last_messages(provider: &MockProvider) -> Vec<Message> {
+    provider
+        .last_request()
+        .map(|request| xzatoma::providers::validate_message_sequence(&request.messages))
+        .unwrap_or_default()
 }
 
 #[tokio::test]
@@ -72,7 +32,7 @@ async fn test_save_load_resume_with_orphan_sanitized() {
     // Setup: Create storage and mock provider
     let (storage, _tmp) = common::create_temp_storage();
     let mock_response = Message::assistant("Resumed response");
-    let provider = Arc::new(TrackingMockProvider::new(mock_response));
+    let provider = tracking_provider(mock_response);
 
     // Create agent with conversation containing orphan tool message
     let tools = ToolRegistry::new();
@@ -81,7 +41,7 @@ async fn test_save_load_resume_with_orphan_sanitized() {
     config.conversation.min_retain_turns = 10;
     config.max_turns = 1;
 
-    let mut agent = Agent::new(provider.as_ref().clone(), tools, config).expect("create agent");
+    let mut agent = Agent::new(provider.clone(), tools, config).expect("create agent");
 
     // Add messages: user -> assistant -> orphan tool message (no matching call)
     agent.conversation_mut().add_user_message("Hello");
@@ -129,20 +89,16 @@ async fn test_save_load_resume_with_orphan_sanitized() {
     // Create new agent with loaded conversation
     let tools = ToolRegistry::new();
     let config = AgentConfig::default();
-    let mut resumed_agent = Agent::with_conversation(
-        Box::new(provider.as_ref().clone()),
-        tools,
-        config,
-        conversation,
-    )
-    .expect("create resumed agent");
+    let mut resumed_agent =
+        Agent::with_conversation(Box::new(provider.clone()), tools, config, conversation)
+            .expect("create resumed agent");
 
     // Execute a continuation prompt
     // This triggers provider.complete() which receives the sanitized messages
     let _result = resumed_agent.execute("Continue").await;
 
     // Verify provider received sanitized messages (orphan removed by validate_message_sequence)
-    let received = provider.last_messages();
+    let received = last_messages(&provider);
 
     // The orphan tool message should have been removed by validate_message_sequence
     // because there's no assistant message with a matching tool_call for "call_orphan"
@@ -160,7 +116,7 @@ async fn test_save_load_resume_preserves_valid_tool_pair() {
     // Setup: Create storage and mock provider
     let (storage, _tmp) = common::create_temp_storage();
     let mock_response = Message::assistant("Calculation confirmed");
-    let provider = Arc::new(TrackingMockProvider::new(mock_response));
+    let provider = tracking_provider(mock_response);
 
     // Create agent with valid tool pair
     let tools = ToolRegistry::new();
@@ -169,7 +125,7 @@ async fn test_save_load_resume_preserves_valid_tool_pair() {
     config.conversation.min_retain_turns = 10;
     config.max_turns = 1;
 
-    let mut agent = Agent::new(provider.as_ref().clone(), tools, config).expect("create agent");
+    let mut agent = Agent::new(provider.clone(), tools, config).expect("create agent");
 
     // Add valid tool pair: assistant with tool call -> tool result
     let tool_call = ToolCall {
@@ -216,19 +172,15 @@ async fn test_save_load_resume_preserves_valid_tool_pair() {
         Conversation::with_history(conv_id, loaded_title, loaded_messages, 8000, 10, 0.8);
     let tools = ToolRegistry::new();
     let config = AgentConfig::default();
-    let mut resumed_agent = Agent::with_conversation(
-        Box::new(provider.as_ref().clone()),
-        tools,
-        config,
-        conversation,
-    )
-    .expect("create resumed");
+    let mut resumed_agent =
+        Agent::with_conversation(Box::new(provider.clone()), tools, config, conversation)
+            .expect("create resumed");
 
     // Execute continuation
     let _result = resumed_agent.execute("Continue the calculation").await;
 
     // Verify valid tool pair is preserved
-    let received = provider.last_messages();
+    let received = last_messages(&provider);
 
     // Should have assistant with tool_calls
     let has_assistant = received.iter().any(|m| {
@@ -254,7 +206,7 @@ async fn test_pruning_during_resume_maintains_integrity() {
     // Setup: Create storage and mock provider
     let (storage, _tmp) = common::create_temp_storage();
     let mock_response = Message::assistant("Pruned and continuing");
-    let provider = Arc::new(TrackingMockProvider::new(mock_response));
+    let provider = tracking_provider(mock_response);
 
     // Create agent with small token limits to trigger pruning
     let tools = ToolRegistry::new();
@@ -264,8 +216,7 @@ async fn test_pruning_during_resume_maintains_integrity() {
     config.conversation.prune_threshold = 0.7; // Prune at 70%
     config.max_turns = 1;
 
-    let mut agent =
-        Agent::new(provider.as_ref().clone(), tools, config.clone()).expect("create agent");
+    let mut agent = Agent::new(provider.clone(), tools, config.clone()).expect("create agent");
 
     // Create a tool call that will be early in the conversation
     let early_tool = ToolCall {
@@ -319,13 +270,9 @@ async fn test_pruning_during_resume_maintains_integrity() {
 
     let tools = ToolRegistry::new();
     let config = AgentConfig::default();
-    let mut resumed_agent = Agent::with_conversation(
-        Box::new(provider.as_ref().clone()),
-        tools,
-        config,
-        conversation,
-    )
-    .expect("create resumed");
+    let mut resumed_agent =
+        Agent::with_conversation(Box::new(provider.clone()), tools, config, conversation)
+            .expect("create resumed");
 
     // Execute something (this may trigger pruning)
     let _result = resumed_agent.execute("Continue processing").await;
@@ -7,7 +7,8 @@
 
 use std::sync::Arc;
 use xzatoma::config::{
-    AgentConfig, CopilotConfig, OllamaConfig, OpenAIConfig, ProviderConfig, SubagentConfig,
+    AgentConfig, CopilotConfig, OllamaConfig, OpenAIConfig, ProviderCacheConfig, ProviderConfig,
+    ProviderQuirksConfig, ProviderRateLimitsConfig, SubagentConfig,
 };
 use xzatoma::providers::create_provider_with_override;
 use xzatoma::tools::subagent::SubagentTool;
@@ -24,13 +25,21 @@ fn create_test_provider_config() -> ProviderConfig {
             enable_endpoint_fallback: true,
             reasoning_effort: None,
             include_reasoning: false,
+            request_timeout_seconds: 120,
+            stream_idle_timeout_seconds: 60,
+            token_refresh_margin_seconds: 300,
         },
         ollama: OllamaConfig {
             host: "http://localhost:11434".to_string(),
             model: "llama3.2:3b".to_string(),
             request_timeout_seconds: 600,
+            max_concurrent_requests: 2,
+            prompt_style: None,
         },
         openai: OpenAIConfig::default(),
+        cache: ProviderCacheConfig::default(),
+        quirks: ProviderQuirksConfig::default(),
+        rate_limits: ProviderRateLimitsConfig::default(),
     }
 }
 
@@ -0,0 +1,115 @@
+#![allow(deprecated)]
+//! Integration tests for commands run with `--json`.
+//!
+//! Scripts pipe `--json` output straight into `jq` or a JSON parser, so
+//! stdout must carry nothing but JSON: no banners, status lines, or color
+//! escape codes. Each test runs the compiled `xzatoma` binary against an
+//! empty, per-test data directory and checks that every line of stdout
+//! belongs to a JSON document.
+
+use assert_cmd::Command;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Minimal configuration accepted by `Config::load` and `Config::validate`
+const MINIMAL_CONFIG: &str = r#"provider:
+  type: ollama
+  ollama:
+    host: "http://localhost:11434"
+    model: "llama3.2:latest"
+agent:
+  max_turns: 50
+  timeout_seconds: 300
+skills:
+  enabled: true
+  project_enabled: false
+  user_enabled: false
+  additional_paths: []
+"#;
+
+/// Builds an `xzatoma` command whose config and data directories live in `dir`
+fn xzatoma(dir: &Path) -> Command {
+    let config_path = dir.join("config.yaml");
+    fs::write(&config_path, MINIMAL_CONFIG).expect("failed to write config");
+
+    let mut cmd = Command::cargo_bin("xzatoma").expect("failed to find binary");
+    cmd.env_remove("XZATOMA_HISTORY_DB")
+        .env_remove("NO_COLOR")
+        .env("XZATOMA_DATA_DIR", dir.join("data"))
+        .env("XZATOMA_CACHE_DIR", dir.join("cache"))
+        .env("XZATOMA_STATE_DIR", dir.join("state"))
+        .env("XZATOMA_CONFIG_DIR", dir.join("config"))
+        .env(
+            "XZATOMA_SKILLS_TRUST_STORE_PATH",
+            dir.join("skills_trust.yaml"),
+        )
+        .arg("--config")
+        .arg(&config_path);
+    cmd
+}
+
+/// Asserts that stdout is one or more JSON documents and nothing else
+///
+/// Pretty-printed documents span several lines, so the check parses stdout
+/// as a stream of documents and fails on any line that is not part of one.
+fn assert_stdout_is_json(stdout: &[u8]) {
+    let text = String::from_utf8(stdout.to_vec()).expect("stdout should be UTF-8");
+    assert!(!text.trim().is_empty(), "stdout is empty");
+    assert!(
+        !text.contains('\x1b'),
+        "stdout contains escape codes:\n{}",
+        text
+    );
+
+    for document in serde_json::Deserializer::from_str(&text).into_iter::<serde_json::Value>() {
+        if let Err(e) = document {
+            panic!("stdout line {} is not JSON ({}):\n{}", e.line(), e, text);
+        }
+    }
+}
+
+#[test]
+fn test_history_stats_json_on_empty_data_dir() {
+    let tmp = TempDir::new().expect("failed to create tempdir");
+
+    let output = xzatoma(tmp.path())
+        .args(["history", "stats", "--json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_stdout_is_json(&output);
+}
+
+#[test]
+fn test_history_stats_json_ignores_forced_color() {
+    let tmp = TempDir::new().expect("failed to create tempdir");
+
+    let output = xzatoma(tmp.path())
+        .args(["--color", "always", "history", "stats", "--json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_stdout_is_json(&output);
+}
+
+#[test]
+fn test_usage_json_on_empty_data_dir() {
+    let tmp = TempDir::new().expect("failed to create tempdir");
+
+    let output = xzatoma(tmp.path())
+        .args(["usage", "--by", "session", "--json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_stdout_is_json(&output);
+}
@@ -257,7 +257,7 @@ fn test_service_name_is_unique_per_server_id() {
 #[test]
 #[ignore = "requires system keyring"]
 fn test_save_and_load_token_roundtrip_via_keyring() {
-    let store = TokenStore;
+    let store = TokenStore::new();
     let server_id = "xzatoma_test_integration_server_roundtrip";
 
     let token = OAuthToken {
@@ -309,7 +309,7 @@ fn test_save_and_load_token_roundtrip_via_keyring() {
 #[test]
 #[ignore = "requires system keyring"]
 fn test_load_token_returns_none_when_absent() {
-    let store = TokenStore;
+    let store = TokenStore::new();
     let server_id = "xzatoma_test_definitely_nonexistent_server_load_none";
 
     // Ensure clean state.
@@ -329,7 +329,7 @@ fn test_load_token_returns_none_when_absent() {
 #[test]
 #[ignore = "requires system keyring"]
 fn test_delete_token_is_idempotent() {
-    let store = TokenStore;
+    let store = TokenStore::new();
     let server_id = "xzatoma_test_idempotent_delete_xzatoma";
 
     // First delete: entry may or may not exist -- must not error.
@@ -347,7 +347,7 @@ fn test_delete_token_is_idempotent() {
 #[test]
 #[ignore = "requires system keyring"]
 fn test_save_token_overwrites_existing_entry() {
-    let store = TokenStore;
+    let store = TokenStore::new();
     let server_id = "xzatoma_test_overwrite_server";
 
     let first = OAuthToken {
@@ -31,11 +31,32 @@ fn make_cli() -> xzatoma::cli::Cli {
         config: None,
         verbose: false,
         storage_path: None,
+        offline: false,
+        read_only: false,
+        allow_fetch: false,
+        no_cache: false,
+        ignore_budget: false,
+        no_project_mcp: false,
+        no_setup: false,
+        raw_numbers: false,
+        color: "auto".to_string(),
         command: Commands::Run {
             plan: None,
             prompt: None,
             allow_dangerous: false,
             thinking_effort: None,
+            json: false,
+            validate_only: false,
+            dry_run: false,
+            cwd: None,
+            confirm_dangerous: None,
+            strict_budget: false,
+            record: false,
+            postmortem: false,
+            session_id: None,
+            if_exists: None,
+            snapshot: false,
+            command: None,
         },
     }
 }
@@ -67,6 +88,7 @@ fn make_stdio_server(id: &str) -> McpServerConfig {
         prompts_enabled: false,
         sampling_enabled: false,
         elicitation_enabled: true,
+        read_safe_tools: vec![],
     }
 }
 
@@ -435,6 +457,7 @@ fn test_mcp_config_yaml_with_invalid_http_scheme_fails_validate() {
         prompts_enabled: false,
         sampling_enabled: false,
         elicitation_enabled: true,
+        read_safe_tools: vec![],
     });
 
     assert!(cfg.validate().is_err());
@@ -459,6 +482,7 @@ fn test_mcp_config_yaml_with_empty_executable_fails_validate() {
         prompts_enabled: false,
         sampling_enabled: false,
         elicitation_enabled: true,
+        read_safe_tools: vec![],
     });
 
     assert!(cfg.validate().is_err());
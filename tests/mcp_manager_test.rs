@@ -28,7 +28,10 @@ use xzatoma::mcp::types::{Implementation, InitializeResponse, McpTool, ServerCap
 /// Build an `McpClientManager` backed by a no-op HTTP client and in-memory
 /// token store. No servers are pre-registered.
 fn make_manager() -> McpClientManager {
-    McpClientManager::new(Arc::new(reqwest::Client::new()), Arc::new(TokenStore))
+    McpClientManager::new(
+        Arc::new(reqwest::Client::new()),
+        Arc::new(TokenStore::new()),
+    )
 }
 
 /// Build a default `McpServerConfig` using the stdio transport wired to the
@@ -50,6 +53,7 @@ fn stdio_config(id: &str) -> McpServerConfig {
         prompts_enabled: false,
         sampling_enabled: false,
         elicitation_enabled: false,
+        read_safe_tools: vec![],
     }
 }
 
@@ -561,6 +565,7 @@ async fn test_connect_all_skips_disabled_servers() {
         prompts_enabled: false,
         sampling_enabled: false,
         elicitation_enabled: false,
+        read_safe_tools: vec![],
     };
 
     let cfg = McpConfig {
@@ -569,6 +574,7 @@ async fn test_connect_all_skips_disabled_servers() {
         auto_connect: true,
         expose_resources_tool: true,
         expose_prompts_tool: true,
+        ..McpConfig::default()
     };
 
     let mut manager = make_manager();
@@ -8,69 +8,22 @@
 use std::sync::Arc;
 
 use xzatoma::config::ExecutionMode;
-use xzatoma::error::{Result, XzatomaError};
+use xzatoma::error::XzatomaError;
 use xzatoma::mcp::protocol::SamplingHandler;
 use xzatoma::mcp::sampling::XzatomaSamplingHandler;
 use xzatoma::mcp::types::{CreateMessageRequest, MessageContent, PromptMessage, Role, TextContent};
-use xzatoma::providers::{CompletionResponse, Message, ModelInfo, Provider};
+use xzatoma::providers::{CompletionResponse, Message, Provider};
+use xzatoma::testing::MockProvider;
 
 // ---------------------------------------------------------------------------
-// Mock provider
+// Helpers
 // ---------------------------------------------------------------------------
 
-/// A mock provider that records calls and returns a fixed response.
-#[derive(Debug)]
-struct MockProvider {
-    response_text: String,
-    call_count: std::sync::atomic::AtomicUsize,
-}
-
-impl MockProvider {
-    fn new(text: &str) -> Self {
-        Self {
-            response_text: text.to_string(),
-            call_count: std::sync::atomic::AtomicUsize::new(0),
-        }
-    }
-
-    fn call_count(&self) -> usize {
-        self.call_count.load(std::sync::atomic::Ordering::SeqCst)
-    }
-}
-
-#[async_trait::async_trait]
-impl Provider for MockProvider {
-    fn is_authenticated(&self) -> bool {
-        false
-    }
-
-    fn current_model(&self) -> Option<&str> {
-        Some("mock-model")
-    }
-
-    fn set_model(&mut self, _model: &str) {}
-
-    async fn fetch_models(&self) -> Result<Vec<ModelInfo>> {
-        Ok(vec![])
-    }
-
-    async fn complete(
-        &self,
-        _messages: &[Message],
-        _tools: &[serde_json::Value],
-    ) -> Result<CompletionResponse> {
-        self.call_count
-            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-        Ok(CompletionResponse::new(Message::assistant(
-            &self.response_text,
-        )))
-    }
+/// A mock provider that answers every request with `text`.
+fn mock_provider(text: &str) -> MockProvider {
+    MockProvider::new().with_fallback(CompletionResponse::new(Message::assistant(text)))
 }
 
-// ---------------------------------------------------------------------------
-// Helpers
-// ---------------------------------------------------------------------------
-
 /// Build a minimal `CreateMessageRequest` with a single user text message.
 fn simple_request(system: Option<&str>, user_text: &str) -> CreateMessageRequest {
     CreateMessageRequest {
@@ -109,7 +62,7 @@ fn simple_request(system: Option<&str>, user_text: &str) -> CreateMessageRequest
 ///    once.
 #[tokio::test]
 async fn test_full_autonomous_mode_skips_user_prompt_and_calls_provider() {
-    let mock = Arc::new(MockProvider::new("the answer is 42"));
+    let mock = Arc::new(mock_provider("the answer is 42"));
 
     let handler = XzatomaSamplingHandler {
         provider: Arc::clone(&mock) as Arc<dyn Provider>,
@@ -147,7 +100,7 @@ async fn test_full_autonomous_mode_skips_user_prompt_and_calls_provider() {
     }
 
     assert_eq!(
-        mock.call_count(),
+        mock.request_count(),
         1,
         "provider::complete must be called exactly once"
     );
@@ -156,7 +109,7 @@ async fn test_full_autonomous_mode_skips_user_prompt_and_calls_provider() {
 /// Headless mode must also skip the prompt, regardless of execution mode.
 #[tokio::test]
 async fn test_headless_mode_skips_user_prompt_and_calls_provider() {
-    let mock = Arc::new(MockProvider::new("headless result"));
+    let mock = Arc::new(mock_provider("headless result"));
 
     let handler = XzatomaSamplingHandler {
         provider: Arc::clone(&mock) as Arc<dyn Provider>,
@@ -179,7 +132,7 @@ async fn test_headless_mode_skips_user_prompt_and_calls_provider() {
         result
     );
 
-    assert_eq!(mock.call_count(), 1);
+    assert_eq!(mock.request_count(), 1);
 }
 
 // ---------------------------------------------------------------------------
@@ -214,7 +167,7 @@ async fn test_interactive_mode_with_user_rejection_returns_mcp_elicitation_error
     // have been auto-approved -- the guard triggers a distinct Mcp error.
     // The interactive rejection path (stdin-based) is covered in unit tests.
 
-    let mock = Arc::new(MockProvider::new("unreachable"));
+    let mock = Arc::new(mock_provider("unreachable"));
 
     // headless=false, FullAutonomous=false => approval required, but since
     // we cannot inject "n" into stdin here we test that the empty-messages
@@ -247,7 +200,7 @@ async fn test_interactive_mode_with_user_rejection_returns_mcp_elicitation_error
 
     // Provider must NOT have been called.
     assert_eq!(
-        mock.call_count(),
+        mock.request_count(),
         0,
         "provider must not be called when messages are empty"
     );
@@ -270,7 +223,7 @@ async fn test_interactive_mode_with_user_rejection_returns_mcp_elicitation_error
 /// `stop_reason` is `"endTurn"` for a plain text response with no tool calls.
 #[tokio::test]
 async fn test_stop_reason_is_end_turn_for_plain_text_response() {
-    let mock = Arc::new(MockProvider::new("plain text"));
+    let mock = Arc::new(mock_provider("plain text"));
 
     let handler = XzatomaSamplingHandler {
         provider: Arc::clone(&mock) as Arc<dyn Provider>,
@@ -294,7 +247,7 @@ async fn test_stop_reason_is_end_turn_for_plain_text_response() {
 /// The result's `model` field must not be empty.
 #[tokio::test]
 async fn test_result_model_field_not_empty() {
-    let mock = Arc::new(MockProvider::new("hello"));
+    let mock = Arc::new(mock_provider("hello"));
 
     let handler = XzatomaSamplingHandler {
         provider: Arc::clone(&mock) as Arc<dyn Provider>,
@@ -318,7 +271,7 @@ async fn test_result_model_field_not_empty() {
 /// Multiple user messages are all forwarded to the provider.
 #[tokio::test]
 async fn test_multiple_messages_all_forwarded_to_provider() {
-    let mock = Arc::new(MockProvider::new("multi-turn answer"));
+    let mock = Arc::new(mock_provider("multi-turn answer"));
 
     let handler = XzatomaSamplingHandler {
         provider: Arc::clone(&mock) as Arc<dyn Provider>,
@@ -366,6 +319,6 @@ async fn test_multiple_messages_all_forwarded_to_provider() {
         .await
         .expect("multi-turn create_message must succeed");
 
-    assert_eq!(mock.call_count(), 1);
+    assert_eq!(mock.request_count(), 1);
     assert_eq!(result.role, Role::Assistant);
 }
@@ -30,6 +30,7 @@ use xzatoma::mcp::tool_bridge::{register_mcp_tools, McpToolExecutor};
 use xzatoma::mcp::types::{
     Implementation, InitializeResponse, McpTool, ServerCapabilities, TaskSupport, ToolExecution,
 };
+use xzatoma::read_only::ReadOnlyPolicy;
 use xzatoma::tools::{ToolExecutor, ToolRegistry};
 
 // ---------------------------------------------------------------------------
@@ -38,7 +39,10 @@ use xzatoma::tools::{ToolExecutor, ToolRegistry};
 
 /// Build a bare `McpClientManager` with no servers registered.
 fn make_manager() -> McpClientManager {
-    McpClientManager::new(Arc::new(reqwest::Client::new()), Arc::new(TokenStore))
+    McpClientManager::new(
+        Arc::new(reqwest::Client::new()),
+        Arc::new(TokenStore::new()),
+    )
 }
 
 /// Build a default stdio `McpServerConfig`.
@@ -58,6 +62,7 @@ fn stdio_config(id: &str) -> McpServerConfig {
         prompts_enabled: false,
         sampling_enabled: false,
         elicitation_enabled: false,
+        read_safe_tools: vec![],
     }
 }
 
@@ -105,8 +110,20 @@ fn insert_connected_entry(
     tools: Vec<McpTool>,
     protocol: Arc<InitializedMcpProtocol>,
 ) {
+    insert_connected_entry_with_config(manager, stdio_config(id), tools, protocol);
+}
+
+/// Insert a pre-built protocol into a manager as a Connected entry with the
+/// given config.
+fn insert_connected_entry_with_config(
+    manager: &mut McpClientManager,
+    config: McpServerConfig,
+    tools: Vec<McpTool>,
+    protocol: Arc<InitializedMcpProtocol>,
+) {
+    let id = config.id.clone();
     let entry = McpServerEntry {
-        config: stdio_config(id),
+        config,
         protocol: Some(protocol),
         tools,
         state: McpServerState::Connected,
@@ -115,7 +132,7 @@ fn insert_connected_entry(
         read_loop_handle: None,
         cancellation: None,
     };
-    manager.insert_entry_for_test(id.to_string(), entry);
+    manager.insert_entry_for_test(id, entry);
 }
 
 /// Build an `McpTool` with the given name and optional `TaskSupport`.
@@ -725,6 +742,42 @@ async fn test_register_mcp_tools_registers_one_executor_per_tool() {
     );
 }
 
+/// In read-only mode only the tools listed in `read_safe_tools` are
+/// registered, and the generic resource and prompt executors are left out.
+#[tokio::test]
+async fn test_register_mcp_tools_read_only_keeps_only_read_safe_tools() {
+    let (protocol, _out_rx, _in_tx) = wired_protocol();
+    let mut manager = make_manager();
+    let mut config = stdio_config("github");
+    config.read_safe_tools = vec!["search_issues".to_string()];
+    insert_connected_entry_with_config(
+        &mut manager,
+        config,
+        vec![
+            make_tool("search_issues", None),
+            make_tool("create_issue", None),
+        ],
+        protocol,
+    );
+    let manager = Arc::new(RwLock::new(manager));
+
+    let mut registry = ToolRegistry::new().with_read_only_policy(ReadOnlyPolicy::enforced());
+    let count = register_mcp_tools(
+        &mut registry,
+        Arc::clone(&manager),
+        ExecutionMode::FullAutonomous,
+        false,
+    )
+    .await
+    .expect("register_mcp_tools must not fail");
+
+    assert_eq!(count, 1);
+    assert_eq!(
+        registry.tool_names(),
+        vec!["github__search_issues".to_string()]
+    );
+}
+
 /// The registered executor's `tool_definition()` carries the double-underscore
 /// namespaced name.
 #[tokio::test]
@@ -77,6 +77,7 @@ fn test_server_config() -> McpServerConfig {
         prompts_enabled: false,
         sampling_enabled: false,
         elicitation_enabled: false,
+        read_safe_tools: vec![],
     }
 }
 
@@ -96,7 +97,7 @@ fn test_server_config() -> McpServerConfig {
 #[tokio::test]
 async fn test_end_to_end_tool_call_via_registry() {
     let http_client = Arc::new(reqwest::Client::new());
-    let token_store = Arc::new(TokenStore);
+    let token_store = Arc::new(TokenStore::new());
     let mut manager = McpClientManager::new(http_client, token_store);
 
     // Connect to the test server.  The binary is spawned as a subprocess.
@@ -186,7 +187,7 @@ async fn test_end_to_end_tool_call_via_registry() {
 #[tokio::test]
 async fn test_end_to_end_sequential_echo_calls_via_registry() {
     let http_client = Arc::new(reqwest::Client::new());
-    let token_store = Arc::new(TokenStore);
+    let token_store = Arc::new(TokenStore::new());
     let mut manager = McpClientManager::new(http_client, token_store);
 
     let config = test_server_config();
@@ -250,7 +251,7 @@ async fn test_end_to_end_sequential_echo_calls_via_registry() {
 #[tokio::test]
 async fn test_end_to_end_tool_definition_is_well_formed() {
     let http_client = Arc::new(reqwest::Client::new());
-    let token_store = Arc::new(TokenStore);
+    let token_store = Arc::new(TokenStore::new());
     let mut manager = McpClientManager::new(http_client, token_store);
 
     let config = test_server_config();
@@ -312,7 +313,7 @@ async fn test_end_to_end_tool_definition_is_well_formed() {
 #[tokio::test]
 async fn test_end_to_end_registry_contains_namespaced_tool_name() {
     let http_client = Arc::new(reqwest::Client::new());
-    let token_store = Arc::new(TokenStore);
+    let token_store = Arc::new(TokenStore::new());
     let mut manager = McpClientManager::new(http_client, token_store);
 
     let config = test_server_config();
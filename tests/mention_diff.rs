@@ -0,0 +1,134 @@
+//! Integration tests for `@diff` and `@staged` mentions
+//!
+//! Builds a throwaway git repository with one staged and one unstaged
+//! change and checks what each mention adds to the prompt.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use tempfile::TempDir;
+use xzatoma::mention_parser::{
+    augment_prompt_with_mentions, parse_mentions, LoadErrorKind, MentionCache,
+};
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "xzatoma")
+        .env("GIT_AUTHOR_EMAIL", "xzatoma@localhost")
+        .env("GIT_COMMITTER_NAME", "xzatoma")
+        .env("GIT_COMMITTER_EMAIL", "xzatoma@localhost")
+        .status()
+        .expect("Failed to run git");
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+/// Repository where `staged.txt` has a staged edit and `unstaged.txt` an
+/// unstaged one
+fn repo_with_changes() -> TempDir {
+    let repo = TempDir::new().expect("Failed to create temp dir");
+    let root = repo.path();
+    git(root, &["init", "-q"]);
+    fs::write(root.join("staged.txt"), "one\n").unwrap();
+    fs::write(root.join("unstaged.txt"), "alpha\n").unwrap();
+    git(root, &["add", "-A"]);
+    git(root, &["commit", "-q", "-m", "initial"]);
+
+    fs::write(root.join("staged.txt"), "one\ntwo\n").unwrap();
+    git(root, &["add", "staged.txt"]);
+    fs::write(root.join("unstaged.txt"), "alpha\nbeta\n").unwrap();
+    repo
+}
+
+#[tokio::test]
+async fn test_diff_mention_includes_only_unstaged_changes() {
+    let repo = repo_with_changes();
+    let (mentions, cleaned) = parse_mentions("Review @diff").unwrap();
+
+    let (augmented, errors, successes) = augment_prompt_with_mentions(
+        &mentions,
+        &cleaned,
+        repo.path(),
+        64 * 1024,
+        &MentionCache::new(),
+    )
+    .await;
+
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert!(augmented.contains("unstaged.txt"));
+    assert!(augmented.contains("+beta"));
+    assert!(!augmented.contains("b/staged.txt"));
+    assert!(!augmented.contains("+two"));
+    assert_eq!(
+        successes,
+        vec!["Diff @diff included 1 file(s), 1 hunk(s)".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn test_staged_mention_includes_only_staged_changes() {
+    let repo = repo_with_changes();
+    let (mentions, cleaned) = parse_mentions("Write a commit message for @staged").unwrap();
+
+    let (augmented, errors, successes) = augment_prompt_with_mentions(
+        &mentions,
+        &cleaned,
+        repo.path(),
+        64 * 1024,
+        &MentionCache::new(),
+    )
+    .await;
+
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert!(augmented.contains("Git diff for @staged (staged changes)"));
+    assert!(augmented.contains("+two"));
+    assert!(!augmented.contains("+beta"));
+    assert_eq!(
+        successes,
+        vec!["Diff @staged included 1 file(s), 1 hunk(s)".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn test_diff_range_mention_compares_revisions() {
+    let repo = repo_with_changes();
+    git(repo.path(), &["commit", "-q", "-m", "second"]);
+    let (mentions, cleaned) = parse_mentions("Summarize @diff:HEAD~1..HEAD").unwrap();
+
+    let (augmented, errors, _) = augment_prompt_with_mentions(
+        &mentions,
+        &cleaned,
+        repo.path(),
+        64 * 1024,
+        &MentionCache::new(),
+    )
+    .await;
+
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert!(augmented.contains("+two"));
+    assert!(!augmented.contains("+beta"));
+}
+
+#[tokio::test]
+async fn test_diff_mention_outside_git_repository_reports_load_error() {
+    let dir = TempDir::new().expect("Failed to create temp dir");
+    let (mentions, cleaned) = parse_mentions("Review @diff").unwrap();
+
+    let (augmented, errors, successes) = augment_prompt_with_mentions(
+        &mentions,
+        &cleaned,
+        dir.path(),
+        64 * 1024,
+        &MentionCache::new(),
+    )
+    .await;
+
+    assert!(successes.is_empty());
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, LoadErrorKind::NotAGitRepository);
+    assert_eq!(errors[0].source, "@diff");
+    assert!(errors[0].suggestion.is_some());
+    assert!(augmented.contains("Failed to load @diff"));
+}
@@ -0,0 +1,107 @@
+//! Integration tests for OpenTelemetry span export
+//!
+//! Runs an agent with a tool call under a `tracing_opentelemetry` layer
+//! backed by the in-memory exporter and checks:
+//! - Provider and tool spans are children of the agent execution span
+//! - Spans carry the model, token usage, tool name, and success attributes
+
+#![cfg(feature = "otel")]
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::Value;
+use opentelemetry_sdk::export::trace::SpanData;
+use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+use opentelemetry_sdk::trace::TracerProvider;
+use std::sync::Arc;
+use tracing_subscriber::layer::SubscriberExt;
+
+use xzatoma::agent::Agent;
+use xzatoma::config::AgentConfig;
+use xzatoma::providers::{CompletionResponse, FunctionCall, Message, TokenUsage, ToolCall};
+use xzatoma::testing::{MockProvider, MockToolExecutor};
+use xzatoma::tools::ToolRegistry;
+
+fn attribute(span: &SpanData, key: &str) -> Option<Value> {
+    span.attributes
+        .iter()
+        .find(|kv| kv.key.as_str() == key)
+        .map(|kv| kv.value.clone())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_agent_spans_are_exported_with_hierarchy_and_attributes() {
+    let exporter = InMemorySpanExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    let _default = tracing::subscriber::set_default(subscriber);
+
+    let echo_call = Message::assistant_with_tools(vec![ToolCall {
+        id: "call_1".to_string(),
+        function: FunctionCall {
+            name: "echo".to_string(),
+            arguments: "{}".to_string(),
+        },
+    }]);
+    let mock = MockProvider::new()
+        .then_response(CompletionResponse::with_usage(
+            echo_call,
+            TokenUsage::new(12, 3),
+        ))
+        .then_response(CompletionResponse::with_usage(
+            Message::assistant("Done"),
+            TokenUsage::new(12, 3),
+        ));
+    let mut tools = ToolRegistry::new();
+    tools.register(
+        "echo",
+        Arc::new(MockToolExecutor::builder("echo").output("echoed").build()),
+    );
+    let mut agent = Agent::new(mock.clone(), tools, AgentConfig::default()).expect("create agent");
+
+    let result = agent.execute("Use the echo tool").await;
+    assert!(result.is_ok());
+    mock.assert_script_consumed();
+    let _ = provider.force_flush();
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let agent_span = spans
+        .iter()
+        .find(|s| s.name == "agent_execution")
+        .expect("agent span");
+    let completions: Vec<&SpanData> = spans
+        .iter()
+        .filter(|s| s.name == "provider_completion")
+        .collect();
+    let tool_span = spans
+        .iter()
+        .find(|s| s.name == "tool_call")
+        .expect("tool span");
+
+    assert_eq!(attribute(agent_span, "success"), Some(Value::Bool(true)));
+
+    assert_eq!(completions.len(), 2);
+    for completion in completions {
+        assert_eq!(completion.parent_span_id, agent_span.span_context.span_id());
+        assert_eq!(
+            completion.span_context.trace_id(),
+            agent_span.span_context.trace_id()
+        );
+        assert_eq!(
+            attribute(completion, "model"),
+            Some(Value::from("mock-model"))
+        );
+        assert_eq!(attribute(completion, "prompt_tokens"), Some(Value::I64(12)));
+        assert_eq!(
+            attribute(completion, "completion_tokens"),
+            Some(Value::I64(3))
+        );
+        assert_eq!(attribute(completion, "success"), Some(Value::Bool(true)));
+    }
+
+    assert_eq!(tool_span.parent_span_id, agent_span.span_context.span_id());
+    assert_eq!(attribute(tool_span, "tool"), Some(Value::from("echo")));
+    assert_eq!(attribute(tool_span, "success"), Some(Value::Bool(true)));
+}
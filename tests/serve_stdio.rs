@@ -0,0 +1,318 @@
+//! Integration tests for `xzatoma serve --stdio`
+//!
+//! Each test spawns the binary against a mock Ollama server and talks to it
+//! over stdin and stdout the way an editor plugin would.
+
+#![allow(deprecated)]
+
+use assert_cmd::cargo::cargo_bin;
+use serde_json::{json, Value};
+use serial_test::serial;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+use tempfile::TempDir;
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A running `xzatoma serve --stdio` process
+struct ServeProcess {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    _temp_dir: TempDir,
+}
+
+impl ServeProcess {
+    fn spawn(ollama_host: &str) -> Self {
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        let data_dir = temp_dir.path().join("data");
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            format!(
+                r#"
+provider:
+  type: ollama
+  ollama:
+    host: "{}"
+    model: "llama3.2:latest"
+agent:
+  max_turns: 5
+  timeout_seconds: 60
+paths:
+  data_dir: "{}"
+"#,
+                ollama_host,
+                data_dir.display()
+            ),
+        )
+        .expect("config file should be written");
+
+        let mut child = Command::new(cargo_bin("xzatoma"))
+            .arg("--config")
+            .arg(&config_path)
+            .arg("serve")
+            .arg("--stdio")
+            .arg("--working-dir")
+            .arg(temp_dir.path())
+            .env("XZATOMA_HISTORY_DB", temp_dir.path().join("history.db"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("serve command should spawn");
+
+        let stdin = child.stdin.take().expect("stdin should be piped");
+        let stdout = child.stdout.take().expect("stdout should be piped");
+        let (tx, messages) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                let message = serde_json::from_str(&line)
+                    .unwrap_or_else(|e| panic!("stdout line is not JSON ({}): {}", e, line));
+                if tx.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            child,
+            stdin,
+            messages,
+            _temp_dir: temp_dir,
+        }
+    }
+
+    fn send(&mut self, message: Value) {
+        writeln!(self.stdin, "{}", message).expect("message should be written");
+        self.stdin.flush().expect("stdin should flush");
+    }
+
+    fn request(&mut self, id: u64, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+    }
+
+    fn next_message(&self) -> Value {
+        self.messages
+            .recv_timeout(READ_TIMEOUT)
+            .expect("server should answer in time")
+    }
+
+    /// Reads messages until the response to `id`, returning it and the
+    /// notifications seen on the way
+    fn response_to(&self, id: u64) -> (Value, Vec<Value>) {
+        let mut notifications = Vec::new();
+        loop {
+            let message = self.next_message();
+            if message["id"] == json!(id) {
+                return (message, notifications);
+            }
+            if message.get("id").is_none() {
+                notifications.push(message);
+            }
+        }
+    }
+
+    fn call(&mut self, id: u64, method: &str, params: Value) -> Value {
+        self.request(id, method, params);
+        self.response_to(id).0
+    }
+
+    fn initialize(&mut self) {
+        let response = self.call(
+            1,
+            "initialize",
+            json!({ "protocolVersion": 1, "clientInfo": { "name": "test", "version": "0.0.0" } }),
+        );
+        assert_eq!(response["result"]["protocolVersion"], json!(1));
+    }
+
+    fn new_session(&mut self, id: u64) -> String {
+        let response = self.call(id, "session/new", json!({}));
+        response["result"]["sessionId"]
+            .as_str()
+            .unwrap_or_else(|| panic!("session/new should succeed: {}", response))
+            .to_string()
+    }
+}
+
+impl Drop for ServeProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+async fn mock_ollama() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/version"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"version":"0.5.7"}"#))
+        .mount(&server)
+        .await;
+    server
+}
+
+fn chat_reply(content: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "message": { "role": "assistant", "content": content },
+        "done": true,
+        "prompt_eval_count": 12,
+        "eval_count": 4,
+    }))
+}
+
+#[tokio::test]
+#[serial]
+async fn test_serve_requires_initialize_first() {
+    let mut serve = ServeProcess::spawn("http://127.0.0.1:9");
+
+    let response = serve.call(1, "history/list", json!({}));
+    assert_eq!(response["error"]["code"], json!(-32002));
+
+    let response = serve.call(
+        2,
+        "initialize",
+        json!({ "protocolVersion": 1, "clientInfo": { "name": "test", "version": "0.0.0" } }),
+    );
+    assert_eq!(response["result"]["protocolVersion"], json!(1));
+    assert_eq!(response["result"]["serverInfo"]["name"], json!("xzatoma"));
+
+    let response = serve.call(3, "session/fork", json!({}));
+    assert_eq!(response["error"]["code"], json!(-32601));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_serve_prompt_streams_updates_and_saves_history() {
+    let ollama = mock_ollama().await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(chat_reply("The build is green."))
+        .mount(&ollama)
+        .await;
+
+    let mut serve = ServeProcess::spawn(&ollama.uri());
+    serve.initialize();
+    let session_id = serve.new_session(2);
+
+    let response = serve.call(3, "tools/list", json!({ "sessionId": session_id }));
+    let tools = response["result"]["tools"]
+        .as_array()
+        .expect("tools/list should return tools");
+    assert!(tools.iter().any(|tool| tool["name"] == json!("read_file")));
+
+    serve.request(
+        4,
+        "session/prompt",
+        json!({ "sessionId": session_id, "prompt": "Is the build green?" }),
+    );
+    let (response, notifications) = serve.response_to(4);
+    assert_eq!(response["result"]["stopReason"], json!("end_turn"));
+    assert_eq!(response["result"]["response"], json!("The build is green."));
+    assert!(notifications.iter().any(|notification| {
+        notification["method"] == json!("session/update")
+            && notification["params"]["sessionId"] == json!(session_id)
+            && notification["params"]["requestId"] == json!(4)
+            && notification["params"]["update"]["kind"] == json!("text")
+    }));
+
+    let response = serve.call(5, "history/list", json!({}));
+    assert_eq!(response["result"]["total"], json!(1));
+    assert_eq!(
+        response["result"]["sessions"][0]["id"],
+        json!(session_id),
+        "the session should be saved under its ID: {}",
+        response
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_serve_runs_sessions_concurrently() {
+    let ollama = mock_ollama().await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_string_contains("first task"))
+        .respond_with(chat_reply("first done").set_delay(Duration::from_millis(500)))
+        .mount(&ollama)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_string_contains("second task"))
+        .respond_with(chat_reply("second done").set_delay(Duration::from_millis(500)))
+        .mount(&ollama)
+        .await;
+
+    let mut serve = ServeProcess::spawn(&ollama.uri());
+    serve.initialize();
+    let first = serve.new_session(2);
+    let second = serve.new_session(3);
+    assert_ne!(first, second);
+
+    serve.request(
+        4,
+        "session/prompt",
+        json!({ "sessionId": first, "prompt": "first task" }),
+    );
+    serve.request(
+        5,
+        "session/prompt",
+        json!({ "sessionId": second, "prompt": "second task" }),
+    );
+
+    let mut responses = Vec::new();
+    while responses.len() < 2 {
+        let message = serve.next_message();
+        if message.get("id").is_some() {
+            responses.push(message);
+        }
+    }
+    responses.sort_by_key(|response| response["id"].as_u64());
+    assert_eq!(responses[0]["result"]["response"], json!("first done"));
+    assert_eq!(responses[1]["result"]["response"], json!("second done"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_serve_cancel_stops_a_running_prompt() {
+    let ollama = mock_ollama().await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(chat_reply("too late").set_delay(Duration::from_secs(60)))
+        .mount(&ollama)
+        .await;
+
+    let mut serve = ServeProcess::spawn(&ollama.uri());
+    serve.initialize();
+    let session_id = serve.new_session(2);
+
+    serve.request(
+        3,
+        "session/prompt",
+        json!({ "sessionId": session_id, "prompt": "Take your time" }),
+    );
+    // Give the prompt time to reach the provider
+    thread::sleep(Duration::from_millis(500));
+    serve.request(4, "session/cancel", json!({ "sessionId": session_id }));
+
+    let mut cancel = None;
+    let mut prompt = None;
+    while cancel.is_none() || prompt.is_none() {
+        let message = serve.next_message();
+        match message["id"].as_u64() {
+            Some(3) => prompt = Some(message),
+            Some(4) => cancel = Some(message),
+            _ => {}
+        }
+    }
+    assert_eq!(cancel.unwrap()["result"]["cancelled"], json!(true));
+    assert_eq!(prompt.unwrap()["result"]["stopReason"], json!("cancelled"));
+}
@@ -55,6 +55,15 @@ fn auth_cli() -> Cli {
         config: Some("config/config.yaml".to_string()),
         verbose: false,
         storage_path: None,
+        offline: false,
+        read_only: false,
+        allow_fetch: false,
+        no_cache: false,
+        ignore_budget: false,
+        no_project_mcp: false,
+        no_setup: false,
+        raw_numbers: false,
+        color: "auto".to_string(),
         command: Commands::Auth { provider: None },
     }
 }
@@ -64,6 +73,15 @@ fn skills_cli(command: SkillsCommand) -> Cli {
         config: Some("config/config.yaml".to_string()),
         verbose: false,
         storage_path: None,
+        offline: false,
+        read_only: false,
+        allow_fetch: false,
+        no_cache: false,
+        ignore_budget: false,
+        no_project_mcp: false,
+        no_setup: false,
+        raw_numbers: false,
+        color: "auto".to_string(),
         command: Commands::Skills { command },
     }
 }